@@ -0,0 +1,18 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc();
+}
+
+/// Generates the gRPC service from `proto/`. protox compiles the protos, so
+/// building with the `grpc` feature doesn't need `protoc` installed.
+#[cfg(feature = "grpc")]
+fn grpc() {
+    println!("cargo:rerun-if-changed=proto");
+    let descriptors =
+        protox::compile(["cutman/v1/content.proto"], ["proto"]).expect("failed to compile protos");
+    tonic_build::configure()
+        .build_client(false)
+        .bytes([".cutman.v1.BlobChunk.data"])
+        .compile_fds(descriptors)
+        .expect("failed to generate gRPC code");
+}
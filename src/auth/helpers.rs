@@ -1,23 +1,61 @@
 use std::sync::Arc;
 
+use axum::extract::Request;
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
 use chrono::Utc;
 
-use super::{TokenGenerator, parse_token};
+use super::{TokenFormat, TokenGenerator, parse_token};
 use crate::server::AppState;
-use crate::types::{Principal, Token};
+use crate::server::events::{audit_event, emit};
+use crate::types::{AuditEvent, Principal, Token};
 
 #[derive(Debug)]
 pub enum TokenValidationError {
     InvalidScheme,
     InvalidToken,
+    /// A legacy-format token while `[legacy_tokens]` refuses them.
+    TokenRetired,
     TokenExpired,
     AdminTokenNotAllowed,
+    SudoNotAllowed,
+    SudoPrincipalNotFound,
     InternalError,
 }
 
+/// Header that lets an admin token act as a specific principal.
+pub const SUDO_HEADER: &str = "X-Cutman-Sudo";
+
+/// Audit event kind recorded for each request an admin token makes as
+/// another principal.
+pub const IMPERSONATION_EVENT: &str = "impersonation";
+
+tokio::task_local! {
+    /// Method and path of the request being served, for the audit record of
+    /// an impersonated one; tokens are mostly validated with only the
+    /// headers in hand. Set by [`scope_request`].
+    static REQUEST: (String, String);
+}
+
+/// Middleware that lets token validation see which request it's for.
+pub async fn scope_request(request: Request, next: Next) -> Response {
+    let line = (
+        request.method().to_string(),
+        request.uri().path().to_string(),
+    );
+    REQUEST.scope(line, next.run(request)).await
+}
+
+/// Response header carrying the `[legacy_tokens]` notice to clients that
+/// authenticated with a legacy-format token.
+pub const TOKEN_NOTICE_HEADER: &str = "X-Cutman-Token-Notice";
+
 pub struct ValidatedToken {
     pub token: Token,
     pub principal: Option<Principal>,
+    /// True when an admin token is acting as `principal` via [`SUDO_HEADER`].
+    pub impersonated: bool,
 }
 
 /// Extracts a token string from a Basic auth header.
@@ -42,6 +80,7 @@ pub fn extract_basic_auth_token(header: &str) -> Option<String> {
 /// Validates a raw token string against the store.
 /// Returns the validated token and associated user (if any).
 /// Set `allow_admin` to false to reject admin tokens.
+#[tracing::instrument(name = "auth", skip_all, fields(token.id = tracing::field::Empty))]
 pub fn validate_token(
     state: &Arc<AppState>,
     raw_token: &str,
@@ -49,12 +88,16 @@ pub fn validate_token(
 ) -> Result<ValidatedToken, TokenValidationError> {
     let (lookup, _secret) =
         parse_token(raw_token).map_err(|_| TokenValidationError::InvalidToken)?;
+    if TokenFormat::of_lookup(&lookup) == TokenFormat::Legacy && !state.legacy_tokens.accept {
+        return Err(TokenValidationError::TokenRetired);
+    }
 
     let token = state
         .store
         .get_token_by_lookup(&lookup)
         .map_err(|_| TokenValidationError::InternalError)?
         .ok_or(TokenValidationError::InvalidToken)?;
+    tracing::Span::current().record("token.id", token.id.as_str());
 
     let generator = TokenGenerator::new();
     if !generator
@@ -82,11 +125,112 @@ pub fn validate_token(
         None => None,
     };
 
+    // Tokens of principals deactivated through SCIM stop working until the
+    // principal is reactivated.
+    if let Some(principal) = &principal {
+        if state
+            .store
+            .is_principal_deactivated(&principal.id)
+            .map_err(|_| TokenValidationError::InternalError)?
+        {
+            return Err(TokenValidationError::InvalidToken);
+        }
+    }
+
     if let Err(e) = state.store.update_token_last_used(&token.id) {
         tracing::warn!("Failed to update token last_used_at: {e}");
     }
 
-    Ok(ValidatedToken { token, principal })
+    Ok(ValidatedToken {
+        token,
+        principal,
+        impersonated: false,
+    })
+}
+
+/// Validates a raw token and applies an optional sudo principal ID.
+///
+/// When `sudo_principal_id` is set, the token must be an admin token and the
+/// request runs as that principal. Impersonated requests pass `allow_admin`
+/// checks since they act with the principal's permissions, not admin ones.
+/// Each is recorded as an audit event, and refused if it can't be.
+pub fn validate_token_with_sudo(
+    state: &Arc<AppState>,
+    raw_token: &str,
+    sudo_principal_id: Option<&str>,
+    allow_admin: bool,
+) -> Result<ValidatedToken, TokenValidationError> {
+    let Some(principal_id) = sudo_principal_id else {
+        return validate_token(state, raw_token, allow_admin);
+    };
+
+    let validated = validate_token(state, raw_token, true)?;
+    if !validated.token.is_admin {
+        return Err(TokenValidationError::SudoNotAllowed);
+    }
+
+    let principal = state
+        .store
+        .get_principal(principal_id.trim())
+        .map_err(|_| TokenValidationError::InternalError)?
+        .ok_or(TokenValidationError::SudoPrincipalNotFound)?;
+    if state
+        .store
+        .is_principal_deactivated(&principal.id)
+        .map_err(|_| TokenValidationError::InternalError)?
+    {
+        return Err(TokenValidationError::SudoPrincipalNotFound);
+    }
+
+    let (method, path) = REQUEST.try_with(Clone::clone).unwrap_or_default();
+    tracing::info!(
+        "Admin token {} impersonating principal {} for {method} {path}",
+        validated.token.id,
+        principal.id
+    );
+    let event = AuditEvent {
+        seq: 0,
+        kind: IMPERSONATION_EVENT.to_string(),
+        summary: format!(
+            "Admin token {} acted as {} for {method} {path}",
+            validated.token.id, principal.id
+        ),
+        namespace_id: Some(principal.primary_namespace_id.clone()),
+        repo_id: None,
+        principal_id: Some(principal.id.clone()),
+        token_id: Some(validated.token.id.clone()),
+        address: None,
+        detail: serde_json::json!({ "method": method, "path": path }),
+        created_at: Utc::now(),
+    };
+    let seq = state
+        .store
+        .create_audit_event(&event)
+        .map_err(|_| TokenValidationError::InternalError)?;
+    emit(state, audit_event(&AuditEvent { seq, ..event }));
+
+    Ok(ValidatedToken {
+        token: validated.token,
+        principal: Some(principal),
+        impersonated: true,
+    })
+}
+
+/// Reads the sudo principal ID from request headers, if present.
+#[must_use]
+pub fn sudo_principal_id(headers: &HeaderMap) -> Option<&str> {
+    headers.get(SUDO_HEADER).and_then(|h| h.to_str().ok())
+}
+
+/// Whether the Authorization header carries a token in the legacy format,
+/// valid or not.
+#[must_use]
+pub fn has_legacy_token(auth_header: Option<&str>) -> bool {
+    extract_token_from_header(auth_header)
+        .ok()
+        .flatten()
+        .and_then(|raw_token| parse_token(&raw_token).ok())
+        .is_some_and(|(lookup, _)| TokenFormat::of_lookup(&lookup) == TokenFormat::Legacy)
 }
 
 /// Extracts token from Authorization header (Bearer or Basic).
@@ -107,3 +251,96 @@ pub fn extract_token_from_header(
         None => Ok(None),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{SqliteStore, Store};
+    use crate::types::{AuditEventFilter, Namespace, TrafficClass};
+
+    #[tokio::test]
+    async fn test_sudo_records_audit_event() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "alice".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        store
+            .create_principal(&Principal {
+                id: "principal-1".to_string(),
+                primary_namespace_id: "ns-1".to_string(),
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+        let (raw_token, lookup, hash) = TokenGenerator::new().generate().unwrap();
+        store
+            .create_token(&Token {
+                id: "admin-token".to_string(),
+                token_hash: hash,
+                token_lookup: lookup,
+                is_admin: true,
+                principal_id: None,
+                created_at: now,
+                expires_at: None,
+                last_used_at: None,
+                traffic_class: TrafficClass::default(),
+            })
+            .unwrap();
+        let state = Arc::new(AppState::new(
+            Arc::new(store),
+            temp.path().to_path_buf(),
+            None,
+        ));
+
+        // Requests without sudo leave no record.
+        REQUEST
+            .scope(
+                ("GET".to_string(), "/api/v1/admin/tokens".to_string()),
+                async {
+                    validate_token_with_sudo(&state, &raw_token, None, true).unwrap();
+                },
+            )
+            .await;
+        let filter = AuditEventFilter {
+            kind: Some(IMPERSONATION_EVENT.to_string()),
+            ..AuditEventFilter::default()
+        };
+        assert!(
+            state
+                .store
+                .list_audit_events(&filter, None, 10)
+                .unwrap()
+                .is_empty()
+        );
+
+        REQUEST
+            .scope(
+                ("DELETE".to_string(), "/api/v1/repos/repo-1".to_string()),
+                async {
+                    let validated =
+                        validate_token_with_sudo(&state, &raw_token, Some("principal-1"), false)
+                            .unwrap();
+                    assert_eq!(validated.principal.unwrap().id, "principal-1");
+                },
+            )
+            .await;
+        let events = state.store.list_audit_events(&filter, None, 10).unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.principal_id.as_deref(), Some("principal-1"));
+        assert_eq!(event.token_id.as_deref(), Some("admin-token"));
+        assert_eq!(event.namespace_id.as_deref(), Some("ns-1"));
+        assert_eq!(event.detail["method"], "DELETE");
+        assert_eq!(event.detail["path"], "/api/v1/repos/repo-1");
+    }
+}
@@ -8,7 +8,10 @@ use axum::{
 };
 use serde_json::json;
 
-use super::helpers::{TokenValidationError, extract_token_from_header, validate_token};
+use super::helpers::{
+    TokenValidationError, ValidatedToken, extract_token_from_header, sudo_principal_id,
+    validate_token_with_sudo,
+};
 use crate::server::AppState;
 use crate::types::{Principal, Token};
 
@@ -35,9 +38,12 @@ pub enum AuthError {
     MissingAuth,
     InvalidScheme,
     InvalidToken,
+    TokenRetired,
     TokenExpired,
     NotAdmin,
     NotPrincipal,
+    SudoNotAllowed,
+    SudoPrincipalNotFound,
     InternalError,
 }
 
@@ -47,12 +53,20 @@ impl IntoResponse for AuthError {
             AuthError::MissingAuth => (StatusCode::UNAUTHORIZED, "Authentication required"),
             AuthError::InvalidScheme => (StatusCode::UNAUTHORIZED, "Invalid authorization scheme"),
             AuthError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
+            AuthError::TokenRetired => (
+                StatusCode::UNAUTHORIZED,
+                "Token format retired; reissue it or create a new token",
+            ),
             AuthError::TokenExpired => (StatusCode::UNAUTHORIZED, "Token expired"),
             AuthError::NotAdmin => (StatusCode::FORBIDDEN, "Admin access required"),
             AuthError::NotPrincipal => (
                 StatusCode::FORBIDDEN,
                 "Principal token required for this operation",
             ),
+            AuthError::SudoNotAllowed => (StatusCode::FORBIDDEN, "Sudo requires an admin token"),
+            AuthError::SudoPrincipalNotFound => {
+                (StatusCode::NOT_FOUND, "Sudo principal not found")
+            }
             AuthError::InternalError => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
             }
@@ -80,8 +94,8 @@ impl FromRequestParts<Arc<AppState>> for RequireAuth {
         parts: &mut Parts,
         state: &Arc<AppState>,
     ) -> Result<Self, Self::Rejection> {
-        let token = extract_and_validate_token(parts, state).await?;
-        Ok(RequireAuth(token))
+        let validated = extract_and_validate_token(parts, state).await?;
+        Ok(RequireAuth(validated.token))
     }
 }
 
@@ -92,13 +106,14 @@ impl FromRequestParts<Arc<AppState>> for RequireAdmin {
         parts: &mut Parts,
         state: &Arc<AppState>,
     ) -> Result<Self, Self::Rejection> {
-        let token = extract_and_validate_token(parts, state).await?;
+        let validated = extract_and_validate_token(parts, state).await?;
 
-        if !token.is_admin {
+        // An impersonated request runs with the principal's permissions only.
+        if !validated.token.is_admin || validated.impersonated {
             return Err(AuthError::NotAdmin);
         }
 
-        Ok(RequireAdmin(token))
+        Ok(RequireAdmin(validated.token))
     }
 }
 
@@ -109,28 +124,25 @@ impl FromRequestParts<Arc<AppState>> for RequirePrincipal {
         parts: &mut Parts,
         state: &Arc<AppState>,
     ) -> Result<Self, Self::Rejection> {
-        let token = extract_and_validate_token(parts, state).await?;
+        let validated = extract_and_validate_token(parts, state).await?;
 
-        if token.is_admin {
+        if validated.token.is_admin && !validated.impersonated {
             return Err(AuthError::NotPrincipal);
         }
 
-        let principal_id = token.principal_id.as_ref().ok_or(AuthError::NotPrincipal)?;
+        let principal = validated.principal.ok_or(AuthError::NotPrincipal)?;
 
-        let principal = state
-            .store
-            .get_principal(principal_id)
-            .map_err(|_| AuthError::InternalError)?
-            .ok_or(AuthError::NotPrincipal)?;
-
-        Ok(RequirePrincipal { token, principal })
+        Ok(RequirePrincipal {
+            token: validated.token,
+            principal,
+        })
     }
 }
 
 async fn extract_and_validate_token(
     parts: &mut Parts,
     state: &Arc<AppState>,
-) -> Result<Token, AuthError> {
+) -> Result<ValidatedToken, AuthError> {
     let auth_header = parts
         .headers
         .get(AUTHORIZATION)
@@ -144,13 +156,15 @@ async fn extract_and_validate_token(
         })?
         .ok_or(AuthError::MissingAuth)?;
 
-    let validated = validate_token(state, &raw_token, true).map_err(|e| match e {
+    let sudo = sudo_principal_id(&parts.headers);
+    validate_token_with_sudo(state, &raw_token, sudo, true).map_err(|e| match e {
         TokenValidationError::InvalidScheme => AuthError::InvalidScheme,
         TokenValidationError::InvalidToken => AuthError::InvalidToken,
+        TokenValidationError::TokenRetired => AuthError::TokenRetired,
         TokenValidationError::TokenExpired => AuthError::TokenExpired,
         TokenValidationError::AdminTokenNotAllowed => AuthError::NotAdmin, // unreachable since allow_admin=true
+        TokenValidationError::SudoNotAllowed => AuthError::SudoNotAllowed,
+        TokenValidationError::SudoPrincipalNotFound => AuthError::SudoPrincipalNotFound,
         TokenValidationError::InternalError => AuthError::InternalError,
-    })?;
-
-    Ok(validated.token)
+    })
 }
@@ -1,10 +1,13 @@
 mod helpers;
 mod middleware;
+mod reissue;
 mod token;
 
 pub use helpers::{
-    TokenValidationError, ValidatedToken, extract_basic_auth_token, extract_token_from_header,
-    validate_token,
+    IMPERSONATION_EVENT, SUDO_HEADER, TOKEN_NOTICE_HEADER, TokenValidationError, ValidatedToken,
+    extract_basic_auth_token, extract_token_from_header, has_legacy_token, scope_request,
+    sudo_principal_id, validate_token, validate_token_with_sudo,
 };
 pub use middleware::{AdminToken, AuthToken, RequireAdmin, RequireAuth, RequirePrincipal};
-pub use token::{TokenGenerator, parse_token};
+pub use reissue::reissue_token;
+pub use token::{TokenFormat, TokenGenerator, parse_token};
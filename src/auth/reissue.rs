@@ -0,0 +1,111 @@
+//! Reissuing tokens in the current format.
+//!
+//! A reissued token keeps the old token's principal, admin flag, expiry,
+//! and traffic class. The old token is revoked at once, or left working
+//! for a grace period so clients can be switched over without an outage.
+
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::store::Store;
+use crate::types::Token;
+
+use super::TokenGenerator;
+
+const MAX_RETRIES: u32 = 3;
+
+/// Creates a current-format replacement for `old` and returns it with its
+/// raw token. With a `grace` period `old` expires that long from now (or
+/// sooner, if it already would); without one it is deleted.
+pub fn reissue_token(
+    store: &dyn Store,
+    generator: &TokenGenerator,
+    old: &Token,
+    grace: Option<Duration>,
+) -> Result<(Token, String)> {
+    for _ in 0..MAX_RETRIES {
+        let (raw_token, lookup, hash) = generator.generate()?;
+        let token = Token {
+            id: Uuid::new_v4().to_string(),
+            token_hash: hash,
+            token_lookup: lookup,
+            is_admin: old.is_admin,
+            principal_id: old.principal_id.clone(),
+            created_at: Utc::now(),
+            expires_at: old.expires_at,
+            last_used_at: None,
+            traffic_class: old.traffic_class,
+        };
+
+        match store.create_token(&token) {
+            Ok(()) => {}
+            Err(Error::TokenLookupCollision) => continue,
+            Err(e) => return Err(e),
+        }
+
+        match grace {
+            Some(grace) => {
+                let grace_end = Utc::now() + grace;
+                let expires_at = old.expires_at.map_or(grace_end, |at| at.min(grace_end));
+                store.set_token_expiry(&old.id, Some(expires_at))?;
+            }
+            None => {
+                store.delete_token(&old.id)?;
+            }
+        }
+        return Ok((token, raw_token));
+    }
+
+    Err(Error::TokenLookupCollision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::TokenFormat;
+    use crate::store::SqliteStore;
+    use crate::types::TrafficClass;
+
+    fn legacy_token(store: &SqliteStore, id: &str) -> Token {
+        let token = Token {
+            id: id.to_string(),
+            token_hash: "hash".to_string(),
+            token_lookup: format!("{id:0>8}")[..8].to_string(),
+            is_admin: false,
+            principal_id: None,
+            created_at: Utc::now(),
+            expires_at: None,
+            last_used_at: None,
+            traffic_class: TrafficClass::Batch,
+        };
+        store.create_token(&token).unwrap();
+        token
+    }
+
+    #[test]
+    fn test_reissue_token() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+        let generator = TokenGenerator::new();
+
+        let old = legacy_token(&store, "old-1");
+        let (token, raw) = reissue_token(&store, &generator, &old, None).unwrap();
+        assert_eq!(
+            TokenFormat::of_lookup(&token.token_lookup),
+            TokenFormat::Current
+        );
+        assert_eq!(token.traffic_class, TrafficClass::Batch);
+        assert!(raw.starts_with("cutman_v2_"));
+        assert!(generator.verify(&raw, &token.token_hash).unwrap());
+        assert!(store.get_token_by_id("old-1").unwrap().is_none());
+
+        let old = legacy_token(&store, "old-2");
+        reissue_token(&store, &generator, &old, Some(Duration::days(7))).unwrap();
+        let kept = store.get_token_by_id("old-2").unwrap().unwrap();
+        let expires_at = kept.expires_at.unwrap();
+        assert!(expires_at > Utc::now() + Duration::days(6));
+        assert!(expires_at <= Utc::now() + Duration::days(7));
+    }
+}
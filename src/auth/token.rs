@@ -3,6 +3,7 @@ use argon2::{
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
 };
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::error::{Error, Result};
 
@@ -12,9 +13,49 @@ const ARGON2_PARALLELISM: u32 = 4;
 const ARGON2_OUTPUT_LEN: usize = 32;
 
 const TOKEN_PREFIX: &str = "cutman";
-const LOOKUP_LENGTH: usize = 8;
-const SECRET_LENGTH: usize = 24;
-const SECRET_BYTES: usize = 12;
+const VERSION_TAG: &str = "v2";
+const LOOKUP_LENGTH: usize = 12;
+const SECRET_LENGTH: usize = 40;
+const SECRET_BYTES: usize = 20;
+const LEGACY_LOOKUP_LENGTH: usize = 8;
+const LEGACY_SECRET_LENGTH: usize = 24;
+
+/// Layout of a raw token. New tokens are always [`TokenFormat::Current`];
+/// [`TokenFormat::Legacy`] tokens keep working while `[legacy_tokens]`
+/// accepts them, and are flagged to their users until they are reissued.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenFormat {
+    /// `cutman_<lookup>_<secret>`, with an 8 character lookup and a 96-bit
+    /// secret.
+    Legacy,
+    /// `cutman_v2_<lookup>_<secret>`, with a 12 character lookup and a
+    /// 160-bit secret.
+    #[default]
+    Current,
+}
+
+impl TokenFormat {
+    /// The format of a stored token, told apart by its lookup's length.
+    #[must_use]
+    pub fn of_lookup(lookup: &str) -> Self {
+        if lookup.len() == LEGACY_LOOKUP_LENGTH {
+            Self::Legacy
+        } else {
+            Self::Current
+        }
+    }
+
+    /// The token's visible start, e.g. `cutman_v2_0123456789ab...`, for
+    /// telling tokens apart without revealing their secret.
+    #[must_use]
+    pub fn redacted(lookup: &str) -> String {
+        match Self::of_lookup(lookup) {
+            Self::Legacy => format!("{TOKEN_PREFIX}_{lookup}..."),
+            Self::Current => format!("{TOKEN_PREFIX}_{VERSION_TAG}_{lookup}..."),
+        }
+    }
+}
 
 pub struct TokenGenerator {
     argon2: Argon2<'static>,
@@ -42,7 +83,7 @@ impl TokenGenerator {
         }
     }
 
-    /// Generates a new token with the format: cutman_<lookup>_<secret>
+    /// Generates a new token with the format: cutman_v2_<lookup>_<secret>
     /// Returns (raw_token, lookup, hash)
     pub fn generate(&self) -> Result<(String, String, String)> {
         let lookup = generate_lookup();
@@ -75,11 +116,11 @@ impl TokenGenerator {
     }
 }
 
-/// Generates the lookup portion of the token (first 8 chars of a UUID)
+/// Generates the lookup portion of the token (first 12 hex chars of a UUID)
 #[must_use]
 fn generate_lookup() -> String {
     let uuid = uuid::Uuid::new_v4();
-    uuid.to_string()[..LOOKUP_LENGTH].to_string()
+    uuid.simple().to_string()[..LOOKUP_LENGTH].to_string()
 }
 
 /// Generates a cryptographically secure random hex string for the secret
@@ -93,10 +134,11 @@ fn generate_secret() -> String {
 /// Builds the full token string from lookup and secret
 #[must_use]
 fn build_token(lookup: &str, secret: &str) -> String {
-    format!("{TOKEN_PREFIX}_{lookup}_{secret}")
+    format!("{TOKEN_PREFIX}_{VERSION_TAG}_{lookup}_{secret}")
 }
 
-/// Parses a token string into its components (lookup, secret)
+/// Parses a token string in either format into its components (lookup,
+/// secret). [`TokenFormat::of_lookup`] tells which format it was.
 pub fn parse_token(token: &str) -> Result<(String, String)> {
     let prefix = format!("{TOKEN_PREFIX}_");
     if !token.starts_with(&prefix) {
@@ -104,16 +146,19 @@ pub fn parse_token(token: &str) -> Result<(String, String)> {
     }
 
     let parts: Vec<&str> = token.split('_').collect();
-    if parts.len() != 3 {
-        return Err(Error::InvalidTokenFormat);
-    }
-
-    let lookup = parts[1];
-    let secret = parts[2];
-
-    if lookup.len() != LOOKUP_LENGTH || secret.len() != SECRET_LENGTH {
-        return Err(Error::InvalidTokenFormat);
-    }
+    let (lookup, secret) = match parts[..] {
+        [_, VERSION_TAG, lookup, secret]
+            if lookup.len() == LOOKUP_LENGTH && secret.len() == SECRET_LENGTH =>
+        {
+            (lookup, secret)
+        }
+        [_, lookup, secret]
+            if lookup.len() == LEGACY_LOOKUP_LENGTH && secret.len() == LEGACY_SECRET_LENGTH =>
+        {
+            (lookup, secret)
+        }
+        _ => return Err(Error::InvalidTokenFormat),
+    };
 
     Ok((lookup.to_string(), secret.to_string()))
 }
@@ -141,14 +186,16 @@ mod tests {
         let generator = TokenGenerator::new();
         let (token, lookup, _hash) = generator.generate().unwrap();
 
-        assert!(token.starts_with("cutman_"));
-        assert_eq!(lookup.len(), 8);
+        assert!(token.starts_with("cutman_v2_"));
+        assert_eq!(lookup.len(), 12);
+        assert_eq!(TokenFormat::of_lookup(&lookup), TokenFormat::Current);
 
         let parts: Vec<&str> = token.split('_').collect();
-        assert_eq!(parts.len(), 3);
+        assert_eq!(parts.len(), 4);
         assert_eq!(parts[0], "cutman");
-        assert_eq!(parts[1].len(), 8);
-        assert_eq!(parts[2].len(), 24);
+        assert_eq!(parts[2].len(), 12);
+        assert_eq!(parts[3].len(), 40);
+        assert_eq!(parse_token(&token).unwrap().0, lookup);
     }
 
     #[test]
@@ -175,6 +222,22 @@ mod tests {
         assert_eq!(secret, "123456789012345678901234");
     }
 
+    #[test]
+    fn test_parse_token_formats() {
+        let (lookup, _) =
+            parse_token("cutman_v2_0123456789ab_0123456789012345678901234567890123456789").unwrap();
+        assert_eq!(TokenFormat::of_lookup(&lookup), TokenFormat::Current);
+        assert_eq!(TokenFormat::redacted(&lookup), "cutman_v2_0123456789ab...");
+
+        let (lookup, _) = parse_token("cutman_12345678_123456789012345678901234").unwrap();
+        assert_eq!(TokenFormat::of_lookup(&lookup), TokenFormat::Legacy);
+        assert_eq!(TokenFormat::redacted(&lookup), "cutman_12345678...");
+
+        // Each format's lengths only.
+        assert!(parse_token("cutman_v2_12345678_123456789012345678901234").is_err());
+        assert!(parse_token("cutman_0123456789ab_123456789012345678901234").is_err());
+    }
+
     #[test]
     fn test_parse_token_invalid_prefix() {
         let result = parse_token("invalid_12345678_123456789012345678901234");
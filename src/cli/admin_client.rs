@@ -0,0 +1,492 @@
+//! The admin API, for `cutman admin` commands run with `--server` against a
+//! server whose database isn't reachable from here (a container, another
+//! host). Without `--server` the commands open the database directly, which
+//! keeps working while the server is down.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use chrono::{DateTime, Duration, Utc};
+use inquire::{InquireError, Select};
+use serde::{Deserialize, Serialize};
+
+use super::commands::ServerArgs;
+use super::credentials::Credentials;
+use super::http_client::{ApiClient, PaginatedResponse};
+use super::pickers::format_relative_time;
+use crate::auth::TokenFormat;
+use crate::types::{Namespace, Principal, TrafficClass};
+
+/// Token metadata as the admin API reports it (the lookup prefix is never
+/// exposed).
+#[derive(Debug, Deserialize)]
+pub struct AdminToken {
+    pub id: String,
+    pub is_admin: bool,
+    pub principal_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub format: TokenFormat,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatedToken {
+    pub token: String,
+    pub metadata: AdminToken,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LegacyTokenReport {
+    pub accepted: bool,
+    pub recently_used: usize,
+    pub tokens: Vec<AdminToken>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminNamespaceGrant {
+    pub namespace_id: String,
+    pub allow: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminRepoGrant {
+    pub repo_id: String,
+    pub allow: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PrincipalEmails {
+    emails: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SetPrincipalEmailsRequest<'a> {
+    emails: &'a [String],
+}
+
+#[derive(Serialize)]
+struct CreatePrincipalRequest<'a> {
+    namespace_name: &'a str,
+}
+
+#[derive(Serialize)]
+struct CreateNamespaceRequest<'a> {
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+struct CreateTokenRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_in_seconds: Option<i64>,
+    traffic_class: TrafficClass,
+}
+
+#[derive(Serialize)]
+struct ReissueTokenRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grace_seconds: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct NamespaceGrantRequest<'a> {
+    namespace_id: &'a str,
+    allow: Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct RepoGrantRequest<'a> {
+    repo_id: &'a str,
+    allow: Vec<&'static str>,
+    paths: &'a [String],
+}
+
+pub struct AdminClient {
+    api: ApiClient,
+}
+
+impl AdminClient {
+    /// Connects to the server `--server` names, or returns `None` when it
+    /// wasn't given and the command should use the database.
+    pub fn from_args(args: &ServerArgs) -> anyhow::Result<Option<Self>> {
+        let Some(server_url) = &args.server else {
+            return Ok(None);
+        };
+        let token = args.admin_token.clone().ok_or_else(|| {
+            anyhow::anyhow!("--admin-token (or CUTMAN_ADMIN_TOKEN) is required with --server")
+        })?;
+        let api = ApiClient::new(&Credentials {
+            server_url: server_url.clone(),
+            token,
+        })?;
+        Ok(Some(Self { api }))
+    }
+
+    /// Every page of a paginated admin listing.
+    fn list_all<T: serde::de::DeserializeOwned>(&self, path: &str) -> anyhow::Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut cursor = String::new();
+        loop {
+            let page: PaginatedResponse<T> =
+                self.api.get_raw(&format!("/admin{path}?cursor={cursor}"))?;
+            items.extend(page.data);
+            match page.next_cursor {
+                Some(next) if page.has_more => cursor = next,
+                _ => return Ok(items),
+            }
+        }
+    }
+
+    pub fn principals(&self) -> anyhow::Result<Vec<Principal>> {
+        self.list_all("/principals")
+    }
+
+    pub fn principal(&self, id: &str) -> anyhow::Result<Principal> {
+        self.api.get(&format!("/admin/principals/{id}"))
+    }
+
+    pub fn create_principal(&self, username: &str) -> anyhow::Result<Principal> {
+        self.api.post(
+            "/admin/principals",
+            &CreatePrincipalRequest {
+                namespace_name: username,
+            },
+        )
+    }
+
+    pub fn delete_principal(&self, id: &str) -> anyhow::Result<()> {
+        self.api.delete(&format!("/admin/principals/{id}"))
+    }
+
+    pub fn principal_emails(&self, principal_id: &str) -> anyhow::Result<Vec<String>> {
+        let emails: PrincipalEmails = self
+            .api
+            .get(&format!("/admin/principals/{principal_id}/emails"))?;
+        Ok(emails.emails)
+    }
+
+    pub fn set_principal_emails(
+        &self,
+        principal_id: &str,
+        emails: &[String],
+    ) -> anyhow::Result<Vec<String>> {
+        let emails: PrincipalEmails = self.api.put(
+            &format!("/admin/principals/{principal_id}/emails"),
+            &SetPrincipalEmailsRequest { emails },
+        )?;
+        Ok(emails.emails)
+    }
+
+    pub fn namespaces(&self) -> anyhow::Result<Vec<Namespace>> {
+        self.list_all("/namespaces")
+    }
+
+    /// Looks a namespace up by ID; the API addresses them by name.
+    pub fn namespace(&self, id: &str) -> anyhow::Result<Namespace> {
+        self.namespaces()?
+            .into_iter()
+            .find(|ns| ns.id == id)
+            .ok_or_else(|| anyhow::anyhow!("Namespace not found: {}", id))
+    }
+
+    pub fn create_namespace(&self, name: &str) -> anyhow::Result<Namespace> {
+        self.api
+            .post("/admin/namespaces", &CreateNamespaceRequest { name })
+    }
+
+    pub fn delete_namespace(&self, name: &str) -> anyhow::Result<()> {
+        self.api.delete(&format!("/admin/namespaces/{name}"))
+    }
+
+    pub fn tokens(&self) -> anyhow::Result<Vec<AdminToken>> {
+        self.list_all("/tokens")
+    }
+
+    pub fn token(&self, id: &str) -> anyhow::Result<AdminToken> {
+        self.api.get(&format!("/admin/tokens/{id}"))
+    }
+
+    pub fn create_token(
+        &self,
+        principal_id: &str,
+        expires_in: Option<Duration>,
+        traffic_class: TrafficClass,
+    ) -> anyhow::Result<CreatedToken> {
+        self.api.post(
+            &format!("/admin/principals/{principal_id}/tokens"),
+            &CreateTokenRequest {
+                expires_in_seconds: expires_in.map(|d| d.num_seconds()),
+                traffic_class,
+            },
+        )
+    }
+
+    pub fn legacy_tokens(&self) -> anyhow::Result<LegacyTokenReport> {
+        self.api.get("/admin/tokens/legacy")
+    }
+
+    pub fn reissue_token(&self, id: &str, grace: Option<Duration>) -> anyhow::Result<CreatedToken> {
+        self.api.post(
+            &format!("/admin/tokens/{id}/reissue"),
+            &ReissueTokenRequest {
+                grace_seconds: grace.map(|d| d.num_seconds()),
+            },
+        )
+    }
+
+    pub fn delete_token(&self, id: &str) -> anyhow::Result<()> {
+        self.api.delete(&format!("/admin/tokens/{id}"))
+    }
+
+    pub fn namespace_grants(&self, principal_id: &str) -> anyhow::Result<Vec<AdminNamespaceGrant>> {
+        self.api
+            .get(&format!("/admin/principals/{principal_id}/namespace-grants"))
+    }
+
+    pub fn grant_namespace(
+        &self,
+        principal_id: &str,
+        namespace_id: &str,
+        allow: Vec<&'static str>,
+    ) -> anyhow::Result<()> {
+        let _: Vec<AdminNamespaceGrant> = self.api.post(
+            &format!("/admin/principals/{principal_id}/namespace-grants"),
+            &NamespaceGrantRequest {
+                namespace_id,
+                allow,
+            },
+        )?;
+        Ok(())
+    }
+
+    pub fn revoke_namespace(&self, principal_id: &str, namespace_id: &str) -> anyhow::Result<()> {
+        self.api.delete(&format!(
+            "/admin/principals/{principal_id}/namespace-grants/{namespace_id}"
+        ))
+    }
+
+    pub fn repo_grants(&self, principal_id: &str) -> anyhow::Result<Vec<AdminRepoGrant>> {
+        self.api
+            .get(&format!("/admin/principals/{principal_id}/repo-grants"))
+    }
+
+    pub fn grant_repo(
+        &self,
+        principal_id: &str,
+        repo_id: &str,
+        allow: Vec<&'static str>,
+        paths: &[String],
+    ) -> anyhow::Result<()> {
+        let _: Vec<AdminRepoGrant> = self.api.post(
+            &format!("/admin/principals/{principal_id}/repo-grants"),
+            &RepoGrantRequest {
+                repo_id,
+                allow,
+                paths,
+            },
+        )?;
+        Ok(())
+    }
+
+    pub fn revoke_repo(&self, principal_id: &str, repo_id: &str) -> anyhow::Result<()> {
+        self.api.delete(&format!(
+            "/admin/principals/{principal_id}/repo-grants/{repo_id}"
+        ))
+    }
+
+    /// Namespace names by ID.
+    pub fn namespace_names(&self) -> anyhow::Result<HashMap<String, String>> {
+        Ok(self
+            .namespaces()?
+            .into_iter()
+            .map(|ns| (ns.id, ns.name))
+            .collect())
+    }
+
+    /// A principal's username (the name of its primary namespace).
+    pub fn username(&self, principal: &Principal) -> anyhow::Result<String> {
+        Ok(lookup_name(&self.namespace_names()?, &principal.primary_namespace_id))
+    }
+
+    /// Gets a principal by ID or interactively picks one.
+    pub fn get_or_pick_principal(
+        &self,
+        principal_id: Option<String>,
+        non_interactive: bool,
+    ) -> anyhow::Result<Option<(Principal, String)>> {
+        if let Some(id) = principal_id {
+            let principal = self.principal(&id)?;
+            let username = self.username(&principal)?;
+            return Ok(Some((principal, username)));
+        } else if non_interactive {
+            anyhow::bail!("--principal-id is required in non-interactive mode");
+        }
+
+        let names = self.namespace_names()?;
+        let options: Vec<Labeled<Principal>> = self
+            .principals()?
+            .into_iter()
+            .map(|p| {
+                let label = format!(
+                    "{} ({}...)",
+                    lookup_name(&names, &p.primary_namespace_id),
+                    &p.id[..8]
+                );
+                Labeled(p, label)
+            })
+            .collect();
+        Ok(select("Select principal:", options, "No principals found.")?.map(|principal| {
+            let username = lookup_name(&names, &principal.primary_namespace_id);
+            (principal, username)
+        }))
+    }
+
+    /// Picks a namespace, leaving out principals' primary namespaces when
+    /// `exclude_owned` is set.
+    pub fn pick_namespace(&self, exclude_owned: bool) -> anyhow::Result<Option<Namespace>> {
+        let owned: HashSet<String> = self
+            .principals()?
+            .into_iter()
+            .map(|p| p.primary_namespace_id)
+            .collect();
+        let options: Vec<Labeled<Namespace>> = self
+            .namespaces()?
+            .into_iter()
+            .filter_map(|ns| {
+                let has_owner = owned.contains(&ns.id);
+                if exclude_owned && has_owner {
+                    return None;
+                }
+                let label = format!(
+                    "{} {}",
+                    ns.name,
+                    if has_owner { "[owned]" } else { "[shared]" }
+                );
+                Some(Labeled(ns, label))
+            })
+            .collect();
+        let empty = if exclude_owned {
+            "No shared namespaces found."
+        } else {
+            "No namespaces found."
+        };
+        select("Select namespace:", options, empty)
+    }
+
+    pub fn pick_token(&self) -> anyhow::Result<Option<AdminToken>> {
+        let names = self.namespace_names()?;
+        let usernames: HashMap<String, String> = self
+            .principals()?
+            .into_iter()
+            .map(|p| {
+                let name = lookup_name(&names, &p.primary_namespace_id);
+                (p.id, name)
+            })
+            .collect();
+        let options: Vec<Labeled<AdminToken>> = self
+            .tokens()?
+            .into_iter()
+            .map(|t| {
+                let user = t
+                    .principal_id
+                    .as_ref()
+                    .and_then(|id| usernames.get(id))
+                    .map_or("admin", String::as_str);
+                let last_used = t
+                    .last_used_at
+                    .as_ref()
+                    .map_or_else(|| "never used".to_string(), format_relative_time);
+                let format = match t.format {
+                    TokenFormat::Legacy => "  [legacy]",
+                    TokenFormat::Current => "",
+                };
+                let label = format!(
+                    "{}  {}  created {}  {}{}",
+                    t.id,
+                    user,
+                    format_relative_time(&t.created_at),
+                    last_used,
+                    format
+                );
+                Labeled(t, label)
+            })
+            .collect();
+        select("Select token:", options, "No tokens found.")
+    }
+
+    pub fn pick_namespace_grant(
+        &self,
+        principal_id: &str,
+    ) -> anyhow::Result<Option<AdminNamespaceGrant>> {
+        let names = self.namespace_names()?;
+        let options: Vec<Labeled<AdminNamespaceGrant>> = self
+            .namespace_grants(principal_id)?
+            .into_iter()
+            .map(|g| {
+                let label = format!(
+                    "{} [{}]",
+                    lookup_name(&names, &g.namespace_id),
+                    g.allow.join(", ")
+                );
+                Labeled(g, label)
+            })
+            .collect();
+        select(
+            "Select grant to revoke:",
+            options,
+            "No grants found for this principal.",
+        )
+    }
+
+    pub fn pick_repo_grant(&self, principal_id: &str) -> anyhow::Result<Option<AdminRepoGrant>> {
+        let options: Vec<Labeled<AdminRepoGrant>> = self
+            .repo_grants(principal_id)?
+            .into_iter()
+            .map(|g| {
+                let label = format!("{} [{}]", g.repo_id, g.allow.join(", "));
+                Labeled(g, label)
+            })
+            .collect();
+        select(
+            "Select repo grant to revoke:",
+            options,
+            "No repo grants found for this principal.",
+        )
+    }
+}
+
+fn lookup_name(names: &HashMap<String, String>, namespace_id: &str) -> String {
+    names
+        .get(namespace_id)
+        .cloned()
+        .unwrap_or_else(|| "<unknown>".to_string())
+}
+
+/// A picker option shown by its label.
+struct Labeled<T>(T, String);
+
+impl<T> fmt::Display for Labeled<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.1)
+    }
+}
+
+fn select<T>(prompt: &str, options: Vec<Labeled<T>>, empty: &str) -> anyhow::Result<Option<T>> {
+    if options.is_empty() {
+        println!("{empty}");
+        return Ok(None);
+    }
+
+    let selection = Select::new(prompt, options)
+        .with_page_size(15)
+        .with_help_message("Type to filter, Enter to select")
+        .with_vim_mode(true)
+        .prompt();
+
+    match selection {
+        Ok(Labeled(value, _)) => Ok(Some(value)),
+        Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
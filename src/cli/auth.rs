@@ -2,6 +2,7 @@ use inquire::Text;
 
 use super::credentials::{Credentials, delete_credentials, save_credentials};
 use super::http_client::{ApiClient, NamespaceWithPrimary};
+use crate::server::dto::CapabilitiesResponse;
 
 fn normalize_server_url(url: &str) -> String {
     let url = url.trim().trim_end_matches('/');
@@ -73,12 +74,18 @@ pub fn run_auth_login(
 
     let client = ApiClient::new(&creds)?;
     let _namespaces: Vec<NamespaceWithPrimary> = client.get("/namespaces")?;
+    // Older servers have no capabilities endpoint.
+    let capabilities: Option<CapabilitiesResponse> = client.get("/capabilities").ok();
 
     save_credentials(&creds)?;
 
     println!();
     println!("Logged in to {}", server_url);
     println!();
+    if let Some(banner) = capabilities.and_then(|c| c.banner) {
+        println!("{}", banner.trim_end());
+        println!();
+    }
 
     Ok(())
 }
@@ -1,4 +1,17 @@
-use clap::Subcommand;
+use clap::{Args, Subcommand};
+
+/// Runs an admin command through a server's admin API instead of opening the
+/// database in `--data-dir` directly.
+#[derive(Args, Clone, Default)]
+pub struct ServerArgs {
+    /// Server URL; manage it over the admin API rather than the database
+    #[arg(long)]
+    pub server: Option<String>,
+
+    /// Admin token for --server
+    #[arg(long, env = "CUTMAN_ADMIN_TOKEN", hide_env_values = true)]
+    pub admin_token: Option<String>,
+}
 
 #[derive(Subcommand)]
 pub enum AdminCommands {
@@ -37,18 +50,64 @@ pub enum AdminCommands {
         command: PermissionCommands,
     },
 
+    /// Manage LFS storage
+    Lfs {
+        #[command(subcommand)]
+        command: LfsCommands,
+    },
+
     /// Show server status information
     Info {
         /// Data directory for database and repositories
         #[arg(long, default_value = "./data")]
         data_dir: String,
 
+        #[command(flatten)]
+        server: ServerArgs,
+
         /// Output as JSON
         #[arg(long)]
         json: bool,
     },
 }
 
+#[derive(Subcommand)]
+pub enum LfsCommands {
+    /// Copy LFS objects from the data directory to another storage backend
+    Migrate {
+        /// Data directory for database and repositories
+        #[arg(long, default_value = "./data")]
+        data_dir: String,
+
+        /// Destination, e.g. s3://bucket/prefix (credentials from AWS_ACCESS_KEY_ID
+        /// and AWS_SECRET_ACCESS_KEY)
+        #[arg(long)]
+        to: String,
+
+        /// S3 endpoint, e.g. http://minio:9000 (default: AWS in --region)
+        #[arg(long)]
+        endpoint: Option<String>,
+
+        /// S3 region
+        #[arg(long, default_value = "us-east-1")]
+        region: String,
+
+        /// Objects copied at once
+        #[arg(long, default_value_t = 4)]
+        jobs: usize,
+
+        /// Once every object is copied and verified, point the server config at the
+        /// destination; it takes effect when the server restarts
+        #[arg(long)]
+        cutover: bool,
+
+        /// Server config to update with --cutover (default: ./server.toml or
+        /// /etc/cutman/server.toml)
+        #[arg(long, short)]
+        config: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum PrincipalCommands {
     /// Add a new principal with namespace and optional token
@@ -57,6 +116,9 @@ pub enum PrincipalCommands {
         #[arg(long, default_value = "./data")]
         data_dir: String,
 
+        #[command(flatten)]
+        server: ServerArgs,
+
         /// Username for the new principal
         #[arg(long)]
         username: Option<String>,
@@ -76,6 +138,9 @@ pub enum PrincipalCommands {
         #[arg(long, default_value = "./data")]
         data_dir: String,
 
+        #[command(flatten)]
+        server: ServerArgs,
+
         /// Principal ID to remove
         #[arg(long)]
         principal_id: Option<String>,
@@ -88,6 +153,32 @@ pub enum PrincipalCommands {
         #[arg(long, short = 'y')]
         yes: bool,
     },
+
+    /// List a principal's verified emails, or replace them
+    Emails {
+        /// Data directory for database and repositories
+        #[arg(long, default_value = "./data")]
+        data_dir: String,
+
+        #[command(flatten)]
+        server: ServerArgs,
+
+        /// Principal ID whose emails to manage
+        #[arg(long)]
+        principal_id: Option<String>,
+
+        /// Replace the principal's emails with these (repeatable)
+        #[arg(long = "email")]
+        emails: Vec<String>,
+
+        /// Remove every email
+        #[arg(long, conflicts_with = "emails")]
+        clear: bool,
+
+        /// Skip interactive prompts (requires --principal-id)
+        #[arg(long)]
+        non_interactive: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -98,6 +189,9 @@ pub enum TokenCommands {
         #[arg(long, default_value = "./data")]
         data_dir: String,
 
+        #[command(flatten)]
+        server: ServerArgs,
+
         /// Principal ID for the token
         #[arg(long)]
         principal_id: Option<String>,
@@ -106,6 +200,11 @@ pub enum TokenCommands {
         #[arg(long)]
         expires_days: Option<i64>,
 
+        /// Mark the token as automation; its clones and archives queue
+        /// behind interactive requests
+        #[arg(long)]
+        batch: bool,
+
         /// Skip interactive prompts (requires --principal-id)
         #[arg(long)]
         non_interactive: bool,
@@ -117,6 +216,9 @@ pub enum TokenCommands {
         #[arg(long, default_value = "./data")]
         data_dir: String,
 
+        #[command(flatten)]
+        server: ServerArgs,
+
         /// Token ID to revoke
         #[arg(long)]
         token_id: Option<String>,
@@ -129,6 +231,38 @@ pub enum TokenCommands {
         #[arg(long, short = 'y')]
         yes: bool,
     },
+
+    /// List tokens still in the legacy format
+    Legacy {
+        /// Data directory for database and repositories
+        #[arg(long, default_value = "./data")]
+        data_dir: String,
+
+        #[command(flatten)]
+        server: ServerArgs,
+    },
+
+    /// Replace a token with a new one in the current format
+    Reissue {
+        /// Data directory for database and repositories
+        #[arg(long, default_value = "./data")]
+        data_dir: String,
+
+        #[command(flatten)]
+        server: ServerArgs,
+
+        /// Token ID to reissue
+        #[arg(long)]
+        token_id: Option<String>,
+
+        /// Days the old token keeps working (omit to revoke it at once)
+        #[arg(long)]
+        grace_days: Option<i64>,
+
+        /// Skip interactive prompts (requires --token-id)
+        #[arg(long)]
+        non_interactive: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -139,6 +273,9 @@ pub enum NamespaceCommands {
         #[arg(long, default_value = "./data")]
         data_dir: String,
 
+        #[command(flatten)]
+        server: ServerArgs,
+
         /// Name for the new namespace
         #[arg(long)]
         name: Option<String>,
@@ -154,6 +291,9 @@ pub enum NamespaceCommands {
         #[arg(long, default_value = "./data")]
         data_dir: String,
 
+        #[command(flatten)]
+        server: ServerArgs,
+
         /// Namespace ID to remove
         #[arg(long)]
         namespace_id: Option<String>,
@@ -166,6 +306,32 @@ pub enum NamespaceCommands {
         #[arg(long, short = 'y')]
         yes: bool,
     },
+
+    /// Move a namespace's repositories and LFS objects to another storage
+    /// root. Run it while nothing is pushing to the namespace.
+    Move {
+        /// Data directory for database and repositories
+        #[arg(long, default_value = "./data")]
+        data_dir: String,
+
+        /// Namespace ID to move
+        #[arg(long)]
+        namespace_id: Option<String>,
+
+        /// Storage root from [storage_roots] in the server config, or
+        /// "default" for the data directory
+        #[arg(long)]
+        to: String,
+
+        /// Server config naming the storage roots (default: ./server.toml or
+        /// /etc/cutman/server.toml)
+        #[arg(long, short)]
+        config: Option<String>,
+
+        /// Skip interactive prompts (requires --namespace-id)
+        #[arg(long)]
+        non_interactive: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -176,6 +342,9 @@ pub enum PermissionCommands {
         #[arg(long, default_value = "./data")]
         data_dir: String,
 
+        #[command(flatten)]
+        server: ServerArgs,
+
         /// Principal ID to grant permissions to
         #[arg(long)]
         principal_id: Option<String>,
@@ -184,7 +353,7 @@ pub enum PermissionCommands {
         #[arg(long)]
         namespace_id: Option<String>,
 
-        /// Permissions to grant (comma-separated: repo:read,repo:write,repo:admin,namespace:read,namespace:write,namespace:admin)
+        /// Permissions to grant (comma-separated: repo:read,repo:contents,repo:write,repo:admin,namespace:read,namespace:write,namespace:admin)
         #[arg(long)]
         permissions: Option<String>,
 
@@ -199,6 +368,9 @@ pub enum PermissionCommands {
         #[arg(long, default_value = "./data")]
         data_dir: String,
 
+        #[command(flatten)]
+        server: ServerArgs,
+
         /// Principal ID to revoke permissions from
         #[arg(long)]
         principal_id: Option<String>,
@@ -222,6 +394,9 @@ pub enum PermissionCommands {
         #[arg(long, default_value = "./data")]
         data_dir: String,
 
+        #[command(flatten)]
+        server: ServerArgs,
+
         /// Principal ID to grant permissions to
         #[arg(long)]
         principal_id: Option<String>,
@@ -230,10 +405,14 @@ pub enum PermissionCommands {
         #[arg(long)]
         repo_id: Option<String>,
 
-        /// Permissions to grant (comma-separated: repo:read,repo:write,repo:admin)
+        /// Permissions to grant (comma-separated: repo:read,repo:contents,repo:write,repo:admin)
         #[arg(long)]
         permissions: Option<String>,
 
+        /// Only allow pushes that change files under this path (repeatable)
+        #[arg(long = "path")]
+        paths: Vec<String>,
+
         /// Skip interactive prompts (requires --principal-id, --repo-id, --permissions)
         #[arg(long)]
         non_interactive: bool,
@@ -245,6 +424,9 @@ pub enum PermissionCommands {
         #[arg(long, default_value = "./data")]
         data_dir: String,
 
+        #[command(flatten)]
+        server: ServerArgs,
+
         /// Principal ID to revoke permissions from
         #[arg(long)]
         principal_id: Option<String>,
@@ -284,6 +466,31 @@ pub enum AuthCommands {
     Logout,
 }
 
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Set a preference (keys: protocol, ssh_user, ssh_port)
+    Set {
+        /// Preference key
+        key: String,
+
+        /// New value (e.g. `protocol ssh`)
+        value: String,
+    },
+
+    /// Show one preference, or all of them
+    Get {
+        /// Preference key (omit to list all)
+        key: Option<String>,
+    },
+
+    /// Check a server config file and list every problem in it
+    Validate {
+        /// Config file (defaults to the one `cutman serve` would load)
+        #[arg(long)]
+        file: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum RepoCommands {
     /// Delete a repository
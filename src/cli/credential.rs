@@ -28,12 +28,20 @@ fn extract_host(server_url: &str) -> Option<&str> {
     url.split('/').next()
 }
 
+/// Drops the port from `host[:port]`, keeping bracketed IPv6 literals whole.
+fn strip_port(authority: &str) -> &str {
+    match authority.find(']') {
+        Some(end) if authority.starts_with('[') => &authority[..=end],
+        _ => authority.split(':').next().unwrap_or(authority),
+    }
+}
+
 /// Check if the stored credentials match the requested host
 fn credentials_match_host(stored_url: &str, requested_host: &str) -> bool {
     if let Some(stored_host) = extract_host(stored_url) {
         // Compare hosts, handling port variations
-        let stored_host_base = stored_host.split(':').next().unwrap_or(stored_host);
-        let requested_host_base = requested_host.split(':').next().unwrap_or(requested_host);
+        let stored_host_base = strip_port(stored_host);
+        let requested_host_base = strip_port(requested_host);
 
         stored_host_base == requested_host_base || stored_host == requested_host
     } else {
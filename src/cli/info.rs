@@ -1,7 +1,14 @@
+use std::collections::{HashMap, HashSet};
+
 use serde::Serialize;
 
+use crate::config::StorageMode;
+use crate::netfs::StorageReport;
 use crate::store::Store;
+use crate::types::Permission;
 
+use super::admin_client::AdminClient;
+use super::commands::ServerArgs;
 use super::init_store;
 
 #[derive(Serialize)]
@@ -11,7 +18,7 @@ struct ServerInfo {
     namespaces_primary: i32,
     namespaces_shared: i32,
     tokens: i32,
-    repos: i32,
+    repos: Option<i32>,
 }
 
 #[derive(Serialize)]
@@ -32,7 +39,8 @@ struct NamespaceOutput {
 #[derive(Serialize)]
 struct TokenOutput {
     id: String,
-    lookup: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lookup: Option<String>,
     principal_id: Option<String>,
     username: Option<String>,
     is_admin: bool,
@@ -56,131 +64,245 @@ struct DetailedServerInfo {
     namespaces: Vec<NamespaceOutput>,
     tokens: Vec<TokenOutput>,
     grants: Vec<GrantOutput>,
-    repos: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repos: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    storage: Option<StorageReport>,
 }
 
-pub fn run_info(data_dir: String, json: bool) -> anyhow::Result<()> {
-    let store = init_store(&data_dir)?;
+pub fn run_info(data_dir: String, server: ServerArgs, json: bool) -> anyhow::Result<()> {
+    let info = match AdminClient::from_args(&server)? {
+        Some(client) => collect_remote(&client)?,
+        None => collect_local(&data_dir)?,
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else {
+        let primary_count = info.namespaces.iter().filter(|ns| !ns.is_shared).count() as i32;
+        let summary = ServerInfo {
+            principals: info.principals.len() as i32,
+            namespaces: info.namespaces.len() as i32,
+            namespaces_primary: primary_count,
+            namespaces_shared: info.namespaces.len() as i32 - primary_count,
+            tokens: info.tokens.len() as i32,
+            repos: info.repos,
+        };
+
+        println!();
+        println!("Cutman Server Status");
+        println!("{}", "─".repeat(20));
+        println!("Principals:  {}", summary.principals);
+        println!(
+            "Namespaces:  {} ({} primary, {} shared)",
+            summary.namespaces, summary.namespaces_primary, summary.namespaces_shared
+        );
+        println!("Tokens:      {}", summary.tokens);
+        if let Some(repos) = summary.repos {
+            println!("Repos:       {}", repos);
+        }
+        if let Some(storage) = &info.storage {
+            print_storage(storage);
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+fn collect_local(data_dir: &str) -> anyhow::Result<DetailedServerInfo> {
+    let store = init_store(data_dir)?;
+    let storage = StorageReport::for_path(data_dir.as_ref(), StorageMode::Auto);
 
     let principals = store.list_principals("", 10000)?;
     let namespaces = store.list_namespaces("", 10000)?;
     let tokens = store.list_tokens("", 10000)?;
 
-    let mut primary_count = 0;
     let mut repo_count = 0;
-
     for ns in &namespaces {
-        if store.get_principal_by_primary_namespace_id(&ns.id)?.is_some() {
-            primary_count += 1;
-        }
         repo_count += store.list_repos(&ns.id, "", 10000)?.len() as i32;
     }
 
-    let shared_count = namespaces.len() as i32 - primary_count;
-
-    if json {
-        let mut principal_outputs = Vec::with_capacity(principals.len());
-        for principal in &principals {
-            let username = store
-                .get_namespace(&principal.primary_namespace_id)?
-                .map(|ns| ns.name)
-                .unwrap_or_else(|| "<unknown>".to_string());
-            principal_outputs.push(PrincipalOutput {
-                id: principal.id.clone(),
-                username,
-                created_at: principal.created_at.to_rfc3339(),
-            });
-        }
+    let mut principal_outputs = Vec::with_capacity(principals.len());
+    for principal in &principals {
+        let username = store
+            .get_namespace(&principal.primary_namespace_id)?
+            .map(|ns| ns.name)
+            .unwrap_or_else(|| "<unknown>".to_string());
+        principal_outputs.push(PrincipalOutput {
+            id: principal.id.clone(),
+            username,
+            created_at: principal.created_at.to_rfc3339(),
+        });
+    }
 
-        let mut namespace_outputs = Vec::with_capacity(namespaces.len());
-        for ns in &namespaces {
-            let is_shared = store.get_principal_by_primary_namespace_id(&ns.id)?.is_none();
-            namespace_outputs.push(NamespaceOutput {
-                id: ns.id.clone(),
-                name: ns.name.clone(),
-                is_shared,
-                created_at: ns.created_at.to_rfc3339(),
-            });
-        }
+    let mut namespace_outputs = Vec::with_capacity(namespaces.len());
+    for ns in &namespaces {
+        let is_shared = store.get_principal_by_primary_namespace_id(&ns.id)?.is_none();
+        namespace_outputs.push(NamespaceOutput {
+            id: ns.id.clone(),
+            name: ns.name.clone(),
+            is_shared,
+            created_at: ns.created_at.to_rfc3339(),
+        });
+    }
 
-        let mut token_outputs = Vec::with_capacity(tokens.len());
-        for token in &tokens {
-            let username = if let Some(ref principal_id) = token.principal_id {
-                if let Some(principal) = store.get_principal(principal_id)? {
-                    store
-                        .get_namespace(&principal.primary_namespace_id)?
-                        .map(|ns| ns.name)
-                } else {
-                    None
-                }
+    let mut token_outputs = Vec::with_capacity(tokens.len());
+    for token in &tokens {
+        let username = if let Some(ref principal_id) = token.principal_id {
+            if let Some(principal) = store.get_principal(principal_id)? {
+                store
+                    .get_namespace(&principal.primary_namespace_id)?
+                    .map(|ns| ns.name)
             } else {
                 None
-            };
-            token_outputs.push(TokenOutput {
-                id: token.id.clone(),
-                lookup: token.token_lookup.clone(),
-                principal_id: token.principal_id.clone(),
-                username,
-                is_admin: token.is_admin,
-                created_at: token.created_at.to_rfc3339(),
-                expires_at: token.expires_at.map(|dt| dt.to_rfc3339()),
-                last_used_at: token.last_used_at.map(|dt| dt.to_rfc3339()),
-            });
-        }
+            }
+        } else {
+            None
+        };
+        token_outputs.push(TokenOutput {
+            id: token.id.clone(),
+            lookup: Some(token.token_lookup.clone()),
+            principal_id: token.principal_id.clone(),
+            username,
+            is_admin: token.is_admin,
+            created_at: token.created_at.to_rfc3339(),
+            expires_at: token.expires_at.map(|dt| dt.to_rfc3339()),
+            last_used_at: token.last_used_at.map(|dt| dt.to_rfc3339()),
+        });
+    }
 
-        let mut grant_outputs = Vec::new();
-        for principal in &principals {
-            let username = store
-                .get_namespace(&principal.primary_namespace_id)?
+    let mut grant_outputs = Vec::new();
+    for principal in &principals {
+        let username = store
+            .get_namespace(&principal.primary_namespace_id)?
+            .map(|ns| ns.name)
+            .unwrap_or_else(|| "<unknown>".to_string());
+        let grants = store.list_principal_namespace_grants(&principal.id)?;
+        for grant in grants {
+            let namespace_name = store
+                .get_namespace(&grant.namespace_id)?
                 .map(|ns| ns.name)
                 .unwrap_or_else(|| "<unknown>".to_string());
-            let grants = store.list_principal_namespace_grants(&principal.id)?;
-            for grant in grants {
-                let namespace_name = store
-                    .get_namespace(&grant.namespace_id)?
-                    .map(|ns| ns.name)
-                    .unwrap_or_else(|| "<unknown>".to_string());
-                grant_outputs.push(GrantOutput {
-                    principal_id: principal.id.clone(),
-                    username: username.clone(),
-                    namespace_id: grant.namespace_id.clone(),
-                    namespace_name,
-                    permissions: grant.allow_bits.to_strings(),
-                });
-            }
+            grant_outputs.push(GrantOutput {
+                principal_id: principal.id.clone(),
+                username: username.clone(),
+                namespace_id: grant.namespace_id.clone(),
+                namespace_name,
+                permissions: grant.allow_bits.to_strings(),
+            });
         }
+    }
 
-        let info = DetailedServerInfo {
-            principals: principal_outputs,
-            namespaces: namespace_outputs,
-            tokens: token_outputs,
-            grants: grant_outputs,
-            repos: repo_count,
-        };
+    Ok(DetailedServerInfo {
+        principals: principal_outputs,
+        namespaces: namespace_outputs,
+        tokens: token_outputs,
+        grants: grant_outputs,
+        repos: Some(repo_count),
+        storage: Some(storage),
+    })
+}
 
-        println!("{}", serde_json::to_string_pretty(&info)?);
-    } else {
-        let info = ServerInfo {
-            principals: principals.len() as i32,
-            namespaces: namespaces.len() as i32,
-            namespaces_primary: primary_count,
-            namespaces_shared: shared_count,
-            tokens: tokens.len() as i32,
-            repos: repo_count,
-        };
+/// The same report from the admin API, which doesn't expose repository
+/// counts or the server's storage.
+fn collect_remote(client: &AdminClient) -> anyhow::Result<DetailedServerInfo> {
+    let principals = client.principals()?;
+    let namespaces = client.namespaces()?;
+    let tokens = client.tokens()?;
 
-        println!();
-        println!("Cutman Server Status");
-        println!("{}", "─".repeat(20));
-        println!("Principals:  {}", info.principals);
-        println!(
-            "Namespaces:  {} ({} primary, {} shared)",
-            info.namespaces, info.namespaces_primary, info.namespaces_shared
-        );
-        println!("Tokens:      {}", info.tokens);
-        println!("Repos:       {}", info.repos);
-        println!();
+    let names: HashMap<&str, &str> = namespaces
+        .iter()
+        .map(|ns| (ns.id.as_str(), ns.name.as_str()))
+        .collect();
+    let name_of = |id: &str| names.get(id).map_or("<unknown>", |n| n).to_string();
+    let owned: HashSet<&str> = principals
+        .iter()
+        .map(|p| p.primary_namespace_id.as_str())
+        .collect();
+
+    let principal_outputs = principals
+        .iter()
+        .map(|p| PrincipalOutput {
+            id: p.id.clone(),
+            username: name_of(&p.primary_namespace_id),
+            created_at: p.created_at.to_rfc3339(),
+        })
+        .collect();
+
+    let namespace_outputs = namespaces
+        .iter()
+        .map(|ns| NamespaceOutput {
+            id: ns.id.clone(),
+            name: ns.name.clone(),
+            is_shared: !owned.contains(ns.id.as_str()),
+            created_at: ns.created_at.to_rfc3339(),
+        })
+        .collect();
+
+    let token_outputs = tokens
+        .into_iter()
+        .map(|t| {
+            let username = t
+                .principal_id
+                .as_deref()
+                .and_then(|id| principals.iter().find(|p| p.id == id))
+                .map(|p| name_of(&p.primary_namespace_id));
+            TokenOutput {
+                id: t.id,
+                lookup: None,
+                principal_id: t.principal_id,
+                username,
+                is_admin: t.is_admin,
+                created_at: t.created_at.to_rfc3339(),
+                expires_at: t.expires_at.map(|dt| dt.to_rfc3339()),
+                last_used_at: t.last_used_at.map(|dt| dt.to_rfc3339()),
+            }
+        })
+        .collect();
+
+    let mut grant_outputs = Vec::new();
+    for principal in &principals {
+        for grant in client.namespace_grants(&principal.id)? {
+            let allow: Vec<&str> = grant.allow.iter().map(String::as_str).collect();
+            grant_outputs.push(GrantOutput {
+                principal_id: principal.id.clone(),
+                username: name_of(&principal.primary_namespace_id),
+                namespace_name: name_of(&grant.namespace_id),
+                namespace_id: grant.namespace_id,
+                permissions: Permission::parse_many(&allow)
+                    .unwrap_or_default()
+                    .to_strings(),
+            });
+        }
     }
 
-    Ok(())
+    Ok(DetailedServerInfo {
+        principals: principal_outputs,
+        namespaces: namespace_outputs,
+        tokens: token_outputs,
+        grants: grant_outputs,
+        repos: None,
+        storage: None,
+    })
+}
+
+fn print_storage(storage: &StorageReport) {
+    let Some(mount) = &storage.mount else {
+        println!("Storage:     unknown filesystem");
+        return;
+    };
+    let kind = if storage.network { "network" } else { "local" };
+    println!(
+        "Storage:     {} at {} ({kind})",
+        mount.fs_type,
+        mount.mount_point.display()
+    );
+    if storage.network {
+        println!("             SQLite uses a rollback journal with full syncs;");
+        println!("             LFS uploads take advisory locks and sync directories.");
+    }
+    for warning in &storage.warnings {
+        println!("  warning:   {warning}");
+    }
 }
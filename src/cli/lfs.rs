@@ -0,0 +1,205 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use toml_edit::{DocumentMut, Item, Table, value};
+
+use crate::config::{S3StorageConfig, ServerConfig, validate_config_source};
+use crate::lfs::{FsStorage, LfsStorage, MigrateOptions, migrate_lfs_objects};
+use crate::storage_roots::StorageRoots;
+use crate::store::Store;
+use crate::units::format_size;
+
+use super::{init_store, load_server_config};
+
+/// Splits `s3://bucket/prefix` into the bucket and key prefix.
+fn parse_s3_url(url: &str) -> anyhow::Result<(String, String)> {
+    let Some(rest) = url.strip_prefix("s3://") else {
+        anyhow::bail!("Unsupported destination {url}; use s3://bucket/prefix");
+    };
+    let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    if bucket.is_empty() {
+        anyhow::bail!("{url} names no bucket");
+    }
+    Ok((bucket.to_string(), prefix.trim_matches('/').to_string()))
+}
+
+/// The destination as the server's `[lfs_s3]` config. Without an
+/// endpoint the bucket is on AWS, addressed by virtual host.
+fn s3_config(
+    to: &str,
+    endpoint: Option<String>,
+    region: String,
+) -> anyhow::Result<S3StorageConfig> {
+    let (bucket, prefix) = parse_s3_url(to)?;
+    Ok(S3StorageConfig {
+        path_style: endpoint.is_some(),
+        endpoint: endpoint.unwrap_or_else(|| format!("https://s3.{region}.amazonaws.com")),
+        bucket,
+        region,
+        prefix,
+        access_key_id: None,
+        secret_access_key: None,
+        presigned_urls: true,
+    })
+}
+
+fn destination(s3: &S3StorageConfig, data_path: &Path) -> anyhow::Result<Box<dyn LfsStorage>> {
+    #[cfg(feature = "s3")]
+    {
+        Ok(Box::new(crate::lfs::S3Storage::new(s3, data_path)?))
+    }
+    #[cfg(not(feature = "s3"))]
+    {
+        let _ = data_path;
+        anyhow::bail!(
+            "Migrating to bucket {} needs S3 support, which this build of cutman lacks",
+            s3.bucket
+        )
+    }
+}
+
+/// `source` with `[lfs_s3]` replaced by `s3`, checked the way the server
+/// would load it. Credentials are left to the server's environment.
+fn with_lfs_s3(source: &str, s3: &S3StorageConfig) -> anyhow::Result<String> {
+    let mut doc: DocumentMut = source.parse()?;
+    let mut table = Table::new();
+    table["endpoint"] = value(&s3.endpoint);
+    table["bucket"] = value(&s3.bucket);
+    table["region"] = value(&s3.region);
+    if !s3.prefix.is_empty() {
+        table["prefix"] = value(&s3.prefix);
+    }
+    if !s3.path_style {
+        table["path_style"] = value(false);
+    }
+    doc["lfs_s3"] = Item::Table(table);
+
+    let updated = doc.to_string();
+    validate_config_source(&updated).map_err(|problems| {
+        let problems: Vec<String> = problems.iter().map(ToString::to_string).collect();
+        anyhow::anyhow!("Updated config would be invalid: {}", problems.join("; "))
+    })?;
+    Ok(updated)
+}
+
+/// Copies every LFS object in `data_dir` to `to`, verifying each copy. With
+/// `cutover`, objects already copied are verified too, and once all of
+/// them check out the server config is pointed at the destination.
+pub fn run_lfs_migrate(
+    data_dir: String,
+    to: String,
+    endpoint: Option<String>,
+    region: String,
+    jobs: usize,
+    cutover: bool,
+    config: Option<String>,
+) -> anyhow::Result<()> {
+    let s3 = s3_config(&to, endpoint, region)?;
+    // Found before copying anything, so a missing config fails fast.
+    let config_path = match (cutover, &config) {
+        (false, _) => None,
+        (true, Some(config)) => Some(PathBuf::from(config)),
+        (true, None) => Some(ServerConfig::find_in_search_paths().ok_or_else(|| {
+            anyhow::anyhow!(
+                "No server config in ./server.toml or /etc/cutman/server.toml; pass --config"
+            )
+        })?),
+    };
+
+    let store: Arc<dyn Store> = Arc::new(init_store(&data_dir)?);
+    let data_path = PathBuf::from(&data_dir);
+    let mut source = FsStorage::new(&data_path);
+    // Namespaces kept on other storage roots need the config that names them.
+    if !store.list_namespace_storage_roots()?.is_empty() {
+        let config = load_server_config(config.as_deref())?;
+        let roots = StorageRoots::new(data_path.clone(), config.storage_roots);
+        roots.check_mappings(store.as_ref())?;
+        source = source.with_storage_roots(Arc::new(roots), Arc::clone(&store));
+    }
+    let dest = destination(&s3, &data_path)?;
+    let options = MigrateOptions {
+        jobs,
+        verify_existing: cutover,
+    };
+    let report = tokio::runtime::Runtime::new()?.block_on(migrate_lfs_objects(
+        store.as_ref(),
+        &source,
+        dest.as_ref(),
+        options,
+    ))?;
+
+    println!(
+        "Copied {} objects ({}) to {to}; {} were already there",
+        report.copied,
+        format_size(report.copied_bytes as i64),
+        report.present
+    );
+    if !report.failed.is_empty() {
+        for (oid, error) in &report.failed {
+            eprintln!("  {oid}: {error}");
+        }
+        anyhow::bail!(
+            "{} objects could not be migrated; fix them and run this again",
+            report.failed.len()
+        );
+    }
+
+    let Some(config_path) = config_path else {
+        println!("Run this again with --cutover to switch the server over.");
+        return Ok(());
+    };
+    let source = std::fs::read_to_string(&config_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", config_path.display()))?;
+    std::fs::write(&config_path, with_lfs_s3(&source, &s3)?)?;
+    println!(
+        "Updated {} to store LFS objects in {to}. Restart the server to switch over, \
+         then run this once more without --cutover to copy anything pushed in between.",
+        config_path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_s3_url() {
+        assert_eq!(
+            parse_s3_url("s3://lfs/cutman/objects/").unwrap(),
+            ("lfs".to_string(), "cutman/objects".to_string())
+        );
+        assert_eq!(
+            parse_s3_url("s3://lfs").unwrap(),
+            ("lfs".to_string(), String::new())
+        );
+        assert!(parse_s3_url("s3:///prefix").is_err());
+        assert!(parse_s3_url("/mnt/lfs").is_err());
+    }
+
+    #[test]
+    fn test_cutover_keeps_the_rest_of_the_config() {
+        let source = "# Production\nport = 8080\n\n[lfs_s3]\nendpoint = \"http://old:9000\"\n\
+                      bucket = \"old\"\nsecret_access_key = \"old-secret\"\n";
+
+        let s3 = s3_config("s3://new/lfs", None, "eu-west-1".to_string()).unwrap();
+        let updated = with_lfs_s3(source, &s3).unwrap();
+        assert!(updated.starts_with("# Production\nport = 8080\n"));
+        let config = validate_config_source(&updated).unwrap();
+        let lfs_s3 = config.lfs_s3.unwrap();
+        assert_eq!(lfs_s3.endpoint, "https://s3.eu-west-1.amazonaws.com");
+        assert_eq!(lfs_s3.bucket, "new");
+        assert_eq!(lfs_s3.prefix, "lfs");
+        assert!(!lfs_s3.path_style);
+        assert!(lfs_s3.secret_access_key.is_none());
+
+        let s3 = s3_config(
+            "s3://new",
+            Some("http://minio:9000".to_string()),
+            "us-east-1".to_string(),
+        )
+        .unwrap();
+        let config = validate_config_source(&with_lfs_s3("", &s3).unwrap()).unwrap();
+        assert!(config.lfs_s3.unwrap().path_style);
+    }
+}
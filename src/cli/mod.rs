@@ -1,3 +1,4 @@
+mod admin_client;
 mod auth;
 mod commands;
 mod credential;
@@ -5,36 +6,48 @@ pub mod credentials;
 mod folder;
 pub mod http_client;
 mod info;
+mod lfs;
 mod namespace;
 mod new;
 mod permission;
 pub mod pickers;
 mod principal;
 pub mod repo;
+pub mod settings;
+mod setup;
+mod setup_server;
 mod tag;
 mod token;
+mod validate_config;
 
 pub use auth::{run_auth_login, run_auth_logout};
 pub use commands::{
-    AdminCommands, AuthCommands, CredentialCommands, FolderCommands, NamespaceCommands,
-    PermissionCommands, PrincipalCommands, RepoCommands, TagCommands, TokenCommands,
+    AdminCommands, AuthCommands, ConfigCommands, CredentialCommands, FolderCommands, LfsCommands,
+    NamespaceCommands, PermissionCommands, PrincipalCommands, RepoCommands, ServerArgs,
+    TagCommands, TokenCommands,
 };
 pub use credential::{
     print_credential_help, run_credential_erase, run_credential_get, run_credential_store,
 };
 pub use folder::{run_folder_create, run_folder_delete, run_folder_list, run_folder_move};
 pub use info::run_info;
-pub use namespace::{run_namespace_add, run_namespace_remove};
-pub use new::run_new;
+pub use lfs::run_lfs_migrate;
+pub use namespace::{run_namespace_add, run_namespace_move, run_namespace_remove};
+pub use new::{AutoInit, run_new};
 pub use permission::{
     run_permission_grant, run_permission_repo_grant, run_permission_repo_revoke,
     run_permission_revoke,
 };
-pub use principal::{run_principal_add, run_principal_remove};
+pub use principal::{run_principal_add, run_principal_emails, run_principal_remove};
 pub use repo::{run_repo_clone, run_repo_delete, run_repo_move, run_repo_tag};
+pub use settings::{run_config_get, run_config_set};
+pub use setup::run_setup;
+pub use setup_server::{create_admin_token, run_setup_server};
 pub use tag::{run_tag_create, run_tag_delete};
-pub use token::{run_token_create, run_token_revoke};
+pub use token::{run_token_create, run_token_legacy, run_token_reissue, run_token_revoke};
+pub use validate_config::run_config_validate;
 
+use crate::config::ServerConfig;
 use crate::store::SqliteStore;
 
 /// Initialize store from data directory, checking it exists
@@ -51,3 +64,12 @@ pub fn init_store(data_dir: &str) -> anyhow::Result<SqliteStore> {
 
     SqliteStore::new(&db_path).map_err(Into::into)
 }
+
+/// The server config at `path`, or in the default locations, or the
+/// defaults when there is none.
+fn load_server_config(path: Option<&str>) -> anyhow::Result<ServerConfig> {
+    match path {
+        Some(path) => ServerConfig::load(std::path::Path::new(path)),
+        None => Ok(ServerConfig::load_from_search_paths()?.unwrap_or_default()),
+    }
+}
@@ -1,22 +1,24 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
 use chrono::Utc;
 use inquire::Text;
 use uuid::Uuid;
 
+use crate::lfs::{FsStorage, LfsStorage, LfsStorageError};
 use crate::server::validation::validate_namespace_name;
+use crate::storage_roots::{DEFAULT_ROOT, StorageRoots};
 use crate::store::Store;
-use crate::types::Namespace;
+use crate::types::{Namespace, Repo};
+use crate::units::format_size;
 
-use super::init_store;
+use super::admin_client::AdminClient;
+use super::commands::ServerArgs;
 use super::pickers::{confirm_action, pick_namespace};
+use super::{init_store, load_server_config};
 
-pub fn run_namespace_add(
-    data_dir: String,
-    name: Option<String>,
-    non_interactive: bool,
-) -> anyhow::Result<()> {
-    let store = init_store(&data_dir)?;
-
-    let name = if let Some(n) = name {
+fn prompt_name(name: Option<String>, non_interactive: bool) -> anyhow::Result<String> {
+    Ok(if let Some(n) = name {
         validate_namespace_name(&n).map_err(anyhow::Error::msg)?;
         n
     } else if non_interactive {
@@ -29,7 +31,25 @@ pub fn run_namespace_add(
                     .unwrap_or_else(|e| inquire::validator::Validation::Invalid(e.into())))
             })
             .prompt()?
-    };
+    })
+}
+
+pub fn run_namespace_add(
+    data_dir: String,
+    server: ServerArgs,
+    name: Option<String>,
+    non_interactive: bool,
+) -> anyhow::Result<()> {
+    if let Some(client) = AdminClient::from_args(&server)? {
+        let name = prompt_name(name, non_interactive)?;
+        let namespace = client.create_namespace(&name)?;
+        print_created(&namespace.name);
+        return Ok(());
+    }
+
+    let store = init_store(&data_dir)?;
+
+    let name = prompt_name(name, non_interactive)?;
 
     if store.get_namespace_by_name(&name)?.is_some() {
         anyhow::bail!("Namespace '{}' already exists", name);
@@ -46,19 +66,28 @@ pub fn run_namespace_add(
 
     store.create_namespace(&namespace)?;
 
+    print_created(&name);
+
+    Ok(())
+}
+
+fn print_created(name: &str) {
     println!();
     println!("Created namespace \"{}\"", name);
     println!();
-
-    Ok(())
 }
 
 pub fn run_namespace_remove(
     data_dir: String,
+    server: ServerArgs,
     namespace_id: Option<String>,
     non_interactive: bool,
     yes: bool,
 ) -> anyhow::Result<()> {
+    if let Some(client) = AdminClient::from_args(&server)? {
+        return namespace_remove_remote(&client, namespace_id, non_interactive, yes);
+    }
+
     let store = init_store(&data_dir)?;
 
     let namespace = if let Some(id) = namespace_id {
@@ -103,3 +132,203 @@ pub fn run_namespace_remove(
 
     Ok(())
 }
+
+fn namespace_remove_remote(
+    client: &AdminClient,
+    namespace_id: Option<String>,
+    non_interactive: bool,
+    yes: bool,
+) -> anyhow::Result<()> {
+    let namespace = if let Some(id) = namespace_id {
+        client.namespace(&id)?
+    } else if non_interactive {
+        anyhow::bail!("--namespace-id is required in non-interactive mode");
+    } else {
+        match client.pick_namespace(true)? {
+            Some(ns) => ns,
+            None => return Ok(()),
+        }
+    };
+
+    if client
+        .principals()?
+        .iter()
+        .any(|p| p.primary_namespace_id == namespace.id)
+    {
+        anyhow::bail!(
+            "Cannot delete namespace '{}' - it is a principal's primary namespace. Delete the principal instead.",
+            namespace.name
+        );
+    }
+
+    let confirmed = confirm_action(
+        &format!("Delete namespace '{}'?", namespace.name),
+        yes,
+        non_interactive,
+    )?;
+
+    if !confirmed {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    client.delete_namespace(&namespace.name)?;
+
+    println!();
+    println!("Deleted namespace '{}'", namespace.name);
+    println!();
+
+    Ok(())
+}
+
+/// Copies a namespace's repositories and LFS objects to another storage
+/// root, records the new root, and then removes the old copy of the
+/// repositories. LFS objects stay in the old root's pool too, since other
+/// namespaces there may share them.
+pub fn run_namespace_move(
+    data_dir: String,
+    namespace_id: Option<String>,
+    to: String,
+    config: Option<String>,
+    non_interactive: bool,
+) -> anyhow::Result<()> {
+    let config = load_server_config(config.as_deref())?;
+    let roots = StorageRoots::new(PathBuf::from(&data_dir), config.storage_roots.clone());
+    let Some(dest) = roots.get(&to).map(Path::to_path_buf) else {
+        anyhow::bail!("Storage root '{to}' is not in [storage_roots] of the server config");
+    };
+
+    let store = init_store(&data_dir)?;
+    let namespace = if let Some(id) = namespace_id {
+        store
+            .get_namespace(&id)?
+            .ok_or_else(|| anyhow::anyhow!("Namespace not found: {}", id))?
+    } else if non_interactive {
+        anyhow::bail!("--namespace-id is required in non-interactive mode");
+    } else {
+        match pick_namespace(&store, false)? {
+            Some(ns) => ns,
+            None => return Ok(()),
+        }
+    };
+
+    let source = match store.get_namespace_storage_root(&namespace.id)? {
+        None => roots.data_dir().to_path_buf(),
+        Some(name) => roots.get(&name).map(Path::to_path_buf).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Namespace '{}' is on storage root '{name}', which is not in [storage_roots]",
+                namespace.name
+            )
+        })?,
+    };
+    if source == dest {
+        println!("Namespace '{}' is already on '{to}'", namespace.name);
+        return Ok(());
+    }
+
+    let repos_from = source.join("repos").join(&namespace.id);
+    let repos_to = dest.join("repos").join(&namespace.id);
+    if repos_to.exists() {
+        anyhow::bail!(
+            "{} already exists; remove it before moving the namespace there",
+            repos_to.display()
+        );
+    }
+
+    let repos = store.list_repos(&namespace.id, "", i32::MAX)?;
+    let copied = copy_dir(&repos_from, &repos_to)
+        .map_err(|e| anyhow::anyhow!("Failed to copy {}: {e}", repos_from.display()))
+        .and_then(|()| match config.lfs_s3 {
+            // Objects in S3 don't depend on the namespace's root.
+            Some(_) => Ok(0),
+            None => tokio::runtime::Runtime::new()?.block_on(copy_lfs_objects(
+                &store,
+                &repos,
+                &FsStorage::new(&source),
+                &FsStorage::new(&dest),
+            )),
+        });
+    let lfs_bytes = match copied {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&repos_to);
+            return Err(e);
+        }
+    };
+
+    let root = (to != DEFAULT_ROOT).then_some(to.as_str());
+    store.set_namespace_storage_root(&namespace.id, root)?;
+    if let Err(e) = fs::remove_dir_all(&repos_from)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        eprintln!("Failed to remove {}: {e}", repos_from.display());
+    }
+
+    println!();
+    println!(
+        "Moved namespace '{}' ({} repositories, {} of LFS objects) to '{to}'",
+        namespace.name,
+        repos.len(),
+        format_size(lfs_bytes)
+    );
+    println!();
+
+    Ok(())
+}
+
+/// Copies a directory tree. A missing source copies nothing, since a
+/// namespace without repositories has no directory yet.
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    let entries = match fs::read_dir(from) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    fs::create_dir_all(to)?;
+    for entry in entries {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies the LFS objects of `repos` into the pool of `dest`, verifying
+/// each one, and returns the bytes copied.
+async fn copy_lfs_objects(
+    store: &dyn Store,
+    repos: &[Repo],
+    source: &FsStorage,
+    dest: &FsStorage,
+) -> anyhow::Result<i64> {
+    let mut bytes = 0;
+    for repo in repos {
+        for obj in store.list_lfs_objects(&repo.id)? {
+            if dest.exists(&repo.id, &obj.oid).await? {
+                continue;
+            }
+            let (mut reader, size) = match source.get(&repo.id, &obj.oid).await {
+                Ok(object) => object,
+                Err(LfsStorageError::NotFound) => {
+                    eprintln!("LFS object {} of {} is missing", obj.oid, repo.name);
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let staged = dest.upload_path(&repo.id, &Uuid::new_v4());
+            if let Some(parent) = staged.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let mut file = tokio::fs::File::create(&staged).await?;
+            tokio::io::copy(&mut reader, &mut file).await?;
+            file.sync_all().await?;
+            dest.put_file(&repo.id, &obj.oid, &staged).await?;
+            bytes += size;
+        }
+    }
+    Ok(bytes)
+}
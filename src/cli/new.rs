@@ -7,21 +7,43 @@ use serde::Serialize;
 
 use super::credentials::{Credentials, load_credentials};
 use super::http_client::ApiClient;
-use super::repo::{parse_repo_ref, resolve_namespace_name};
-use crate::types::Repo;
+use super::repo::{
+    connectivity_hint, git_remote_url, parse_repo_ref, resolve_namespace_name, verify_remote,
+};
+use super::settings::load_settings;
+use crate::types::{ObjectFormat, Repo, Visibility};
 
 #[derive(Serialize)]
 struct CreateRepoRequest {
     name: String,
     namespace: Option<String>,
-    public: bool,
+    visibility: Visibility,
+    object_format: ObjectFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auto_init: Option<AutoInit>,
 }
 
-pub fn run_new(name: Option<String>, remote: String) -> anyhow::Result<()> {
+/// Starter files the server commits to the new repo.
+#[derive(Serialize)]
+pub struct AutoInit {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gitignore: Option<String>,
+}
+
+pub fn run_new(
+    name: Option<String>,
+    remote: String,
+    object_format: ObjectFormat,
+    auto_init: Option<AutoInit>,
+) -> anyhow::Result<()> {
     let creds = load_credentials()?;
     let client = ApiClient::new(&creds)?;
+    let settings = load_settings()?;
 
     let original_dir = env::current_dir()?;
+    let auto_init_requested = auto_init.is_some();
 
     let (namespace, repo_name, work_dir) = if let Some(name) = name {
         let (ns, repo_name) = parse_repo_ref(&name)?;
@@ -43,30 +65,51 @@ pub fn run_new(name: Option<String>, remote: String) -> anyhow::Result<()> {
     env::set_current_dir(&work_dir)?;
 
     let result = (|| -> anyhow::Result<()> {
-        if !work_dir.join(".git").exists() {
+        if work_dir.join(".git").exists() {
+            // Fails on git older than 2.29, which only knows SHA-1.
+            let existing = run_git_output(&["rev-parse", "--show-object-format"])
+                .map(|f| f.trim().to_string())
+                .unwrap_or_else(|_| ObjectFormat::Sha1.to_string());
+            if existing != object_format.as_str() {
+                anyhow::bail!(
+                    "Local repository uses {existing} object ids, not {object_format}. Pass --object-format {existing}."
+                );
+            }
+        } else if object_format == ObjectFormat::Sha1 {
             run_git(&["init"])?;
+        } else {
+            run_git(&["init", &format!("--object-format={object_format}")])?;
         }
 
-        let readme_path = work_dir.join("README.md");
-        if !readme_path.exists() {
-            fs::write(&readme_path, format!("# {}\n", repo_name))?;
+        // Only auto-commit if repo has no commits yet
+        let has_commits = run_git_output(&["rev-list", "--count", "HEAD"])
+            .map(|s| s.trim().parse::<u32>().unwrap_or(0) > 0)
+            .unwrap_or(false);
+
+        if auto_init_requested {
+            // The server's initial commit becomes the start of local history.
+            if has_commits {
+                anyhow::bail!("--license and --gitignore only apply to folders without commits");
+            }
+        } else {
+            let readme_path = work_dir.join("README.md");
+            if !readme_path.exists() {
+                fs::write(&readme_path, format!("# {}\n", repo_name))?;
+            }
         }
 
         let request = CreateRepoRequest {
             name: repo_name.clone(),
             namespace: namespace.clone(),
-            public: false,
+            visibility: Visibility::Private,
+            object_format,
+            auto_init,
         };
-        let _repo: Repo = client.post("/repos", &request)?;
+        let repo: Repo = client.post("/repos", &request)?;
 
         let namespace_name = resolve_namespace_name(namespace, &client)?;
 
-        let remote_url = format!(
-            "{}/git/{}/{}.git",
-            client.base_url(),
-            namespace_name,
-            repo_name
-        );
+        let remote_url = git_remote_url(client.base_url(), &settings, &namespace_name, &repo.name)?;
 
         let existing_remotes = run_git_output(&["remote"])?;
         let remote = if existing_remotes.lines().any(|r| r == remote) {
@@ -117,23 +160,23 @@ pub fn run_new(name: Option<String>, remote: String) -> anyhow::Result<()> {
             remote
         };
 
-        // Only auto-commit if repo has no commits yet
-        let has_commits = run_git_output(&["rev-list", "--count", "HEAD"])
-            .map(|s| s.trim().parse::<u32>().unwrap_or(0) > 0)
-            .unwrap_or(false);
-
-        if !has_commits {
-            run_git(&["add", "."])?;
-            let status = run_git_output(&["status", "--porcelain"])?;
-            if !status.is_empty() {
-                run_git(&["commit", "-m", "Initial commit"])?;
-            }
+        if auto_init_requested {
+            run_git_with_auth(&["fetch", &remote], &creds)
+                .map_err(|e| anyhow::anyhow!("{} {}", e, connectivity_hint(settings.protocol)))?;
+            run_git(&["checkout", "-B", "main", &format!("{remote}/main")])?;
+            commit_local_files("Add project files")?;
+        } else if !has_commits {
+            commit_local_files("Initial commit")?;
         }
 
         let branch = run_git_output(&["rev-parse", "--abbrev-ref", "HEAD"])?;
         let branch = branch.trim();
 
-        run_git_with_auth(&["push", "-u", &remote, branch], &creds)?;
+        run_git_with_auth(&["push", "-u", &remote, branch], &creds)
+            .map_err(|e| anyhow::anyhow!("{} {}", e, connectivity_hint(settings.protocol)))?;
+
+        let pushed_ref = format!("refs/heads/{branch}");
+        verify_remote(&remote_url, &creds, settings.protocol, Some(&pushed_ref))?;
 
         println!();
         println!("Created repository '{}'", repo_name);
@@ -148,6 +191,15 @@ pub fn run_new(name: Option<String>, remote: String) -> anyhow::Result<()> {
     result
 }
 
+fn commit_local_files(message: &str) -> anyhow::Result<()> {
+    run_git(&["add", "."])?;
+    let status = run_git_output(&["status", "--porcelain"])?;
+    if !status.is_empty() {
+        run_git(&["commit", "-m", message])?;
+    }
+    Ok(())
+}
+
 fn run_git(args: &[&str]) -> anyhow::Result<()> {
     let status = Command::new("git").args(args).status()?;
     if !status.success() {
@@ -1,21 +1,59 @@
 use chrono::Utc;
 
+use crate::grant_paths;
 use crate::store::Store;
 use crate::types::{NamespaceGrant, Permission, RepoGrant};
 
+use super::admin_client::AdminClient;
+use super::commands::ServerArgs;
 use super::init_store;
 use super::pickers::{
     confirm_action, get_or_pick_principal, pick_grant, pick_namespace, pick_permissions, pick_repo,
     pick_repo_grant, pick_repo_permissions, resolve_namespace_name, resolve_repo_display_name,
 };
 
+/// The permissions `--permissions` lists, or the ones picked interactively;
+/// `None` if none were picked.
+fn resolve_permissions(
+    permissions: Option<String>,
+    non_interactive: bool,
+    pick: fn() -> anyhow::Result<Option<Permission>>,
+) -> anyhow::Result<Option<Permission>> {
+    if let Some(perms_str) = permissions {
+        let perms: Vec<&str> = perms_str.split(',').map(str::trim).collect();
+        Permission::parse_many(&perms)
+            .map(Some)
+            .ok_or_else(|| anyhow::anyhow!("Invalid permission string: {}", perms_str))
+    } else if non_interactive {
+        anyhow::bail!("--permissions is required in non-interactive mode");
+    } else {
+        let perms = pick()?;
+        if perms.is_none() {
+            println!("No permissions selected.");
+        }
+        Ok(perms)
+    }
+}
+
+/// Tells the admin which paths a repo grant confines pushes to, if any.
+fn print_grant_paths(paths: &[String]) {
+    if !paths.is_empty() {
+        println!("Pushes may only change: {}", paths.join(", "));
+    }
+}
+
 pub fn run_permission_grant(
     data_dir: String,
+    server: ServerArgs,
     principal_id: Option<String>,
     namespace_id: Option<String>,
     permissions: Option<String>,
     non_interactive: bool,
 ) -> anyhow::Result<()> {
+    if let Some(client) = AdminClient::from_args(&server)? {
+        return permission_grant_remote(&client, principal_id, namespace_id, permissions, non_interactive);
+    }
+
     let store = init_store(&data_dir)?;
 
     let (principal, username) = match get_or_pick_principal(&store, principal_id, non_interactive)? {
@@ -36,20 +74,9 @@ pub fn run_permission_grant(
         }
     };
 
-    let allow_bits = if let Some(perms_str) = permissions {
-        let perms: Vec<&str> = perms_str.split(',').map(str::trim).collect();
-        Permission::parse_many(&perms)
-            .ok_or_else(|| anyhow::anyhow!("Invalid permission string: {}", perms_str))?
-    } else if non_interactive {
-        anyhow::bail!("--permissions is required in non-interactive mode");
-    } else {
-        match pick_permissions()? {
-            Some(perms) => perms,
-            None => {
-                println!("No permissions selected.");
-                return Ok(());
-            }
-        }
+    let Some(allow_bits) = resolve_permissions(permissions, non_interactive, pick_permissions)?
+    else {
+        return Ok(());
     };
 
     let now = Utc::now();
@@ -78,11 +105,16 @@ pub fn run_permission_grant(
 
 pub fn run_permission_revoke(
     data_dir: String,
+    server: ServerArgs,
     principal_id: Option<String>,
     namespace_id: Option<String>,
     non_interactive: bool,
     yes: bool,
 ) -> anyhow::Result<()> {
+    if let Some(client) = AdminClient::from_args(&server)? {
+        return permission_revoke_remote(&client, principal_id, namespace_id, non_interactive, yes);
+    }
+
     let store = init_store(&data_dir)?;
 
     let (principal, username) = match get_or_pick_principal(&store, principal_id, non_interactive)? {
@@ -133,11 +165,26 @@ pub fn run_permission_revoke(
 
 pub fn run_permission_repo_grant(
     data_dir: String,
+    server: ServerArgs,
     principal_id: Option<String>,
     repo_id: Option<String>,
     permissions: Option<String>,
+    mut paths: Vec<String>,
     non_interactive: bool,
 ) -> anyhow::Result<()> {
+    grant_paths::normalize(&mut paths).map_err(|e| anyhow::anyhow!(e))?;
+
+    if let Some(client) = AdminClient::from_args(&server)? {
+        return permission_repo_grant_remote(
+            &client,
+            principal_id,
+            repo_id,
+            permissions,
+            &paths,
+            non_interactive,
+        );
+    }
+
     let store = init_store(&data_dir)?;
 
     let (principal, username) = match get_or_pick_principal(&store, principal_id, non_interactive)? {
@@ -160,20 +207,9 @@ pub fn run_permission_repo_grant(
 
     let repo_namespace = resolve_namespace_name(&store, &repo.namespace_id);
 
-    let allow_bits = if let Some(perms_str) = permissions {
-        let perms: Vec<&str> = perms_str.split(',').map(str::trim).collect();
-        Permission::parse_many(&perms)
-            .ok_or_else(|| anyhow::anyhow!("Invalid permission string: {}", perms_str))?
-    } else if non_interactive {
-        anyhow::bail!("--permissions is required in non-interactive mode");
-    } else {
-        match pick_repo_permissions()? {
-            Some(perms) => perms,
-            None => {
-                println!("No permissions selected.");
-                return Ok(());
-            }
-        }
+    let Some(allow_bits) = resolve_permissions(permissions, non_interactive, pick_repo_permissions)?
+    else {
+        return Ok(());
     };
 
     let now = Utc::now();
@@ -182,6 +218,7 @@ pub fn run_permission_repo_grant(
         repo_id: repo.id.clone(),
         allow_bits,
         deny_bits: Permission::default(),
+        paths,
         created_at: now,
         updated_at: now,
     };
@@ -196,6 +233,7 @@ pub fn run_permission_repo_grant(
         repo.name,
         allow_bits.to_strings().join(", ")
     );
+    print_grant_paths(&grant.paths);
     println!();
 
     Ok(())
@@ -203,11 +241,16 @@ pub fn run_permission_repo_grant(
 
 pub fn run_permission_repo_revoke(
     data_dir: String,
+    server: ServerArgs,
     principal_id: Option<String>,
     repo_id: Option<String>,
     non_interactive: bool,
     yes: bool,
 ) -> anyhow::Result<()> {
+    if let Some(client) = AdminClient::from_args(&server)? {
+        return permission_repo_revoke_remote(&client, principal_id, repo_id, non_interactive, yes);
+    }
+
     let store = init_store(&data_dir)?;
 
     let (principal, username) = match get_or_pick_principal(&store, principal_id, non_interactive)? {
@@ -249,3 +292,175 @@ pub fn run_permission_repo_revoke(
 
     Ok(())
 }
+
+fn permission_grant_remote(
+    client: &AdminClient,
+    principal_id: Option<String>,
+    namespace_id: Option<String>,
+    permissions: Option<String>,
+    non_interactive: bool,
+) -> anyhow::Result<()> {
+    let (principal, username) = match client.get_or_pick_principal(principal_id, non_interactive)? {
+        Some(result) => result,
+        None => return Ok(()),
+    };
+
+    let namespace = if let Some(id) = namespace_id {
+        client.namespace(&id)?
+    } else if non_interactive {
+        anyhow::bail!("--namespace-id is required in non-interactive mode");
+    } else {
+        match client.pick_namespace(true)? {
+            Some(ns) => ns,
+            None => return Ok(()),
+        }
+    };
+
+    let Some(allow_bits) = resolve_permissions(permissions, non_interactive, pick_permissions)?
+    else {
+        return Ok(());
+    };
+
+    client.grant_namespace(&principal.id, &namespace.id, allow_bits.to_strings())?;
+
+    println!();
+    println!(
+        "Granted {} access to namespace \"{}\" with: {}",
+        username,
+        namespace.name,
+        allow_bits.to_strings().join(", ")
+    );
+    println!();
+
+    Ok(())
+}
+
+fn permission_revoke_remote(
+    client: &AdminClient,
+    principal_id: Option<String>,
+    namespace_id: Option<String>,
+    non_interactive: bool,
+    yes: bool,
+) -> anyhow::Result<()> {
+    let (principal, username) = match client.get_or_pick_principal(principal_id, non_interactive)? {
+        Some(result) => result,
+        None => return Ok(()),
+    };
+
+    let namespace_id = if let Some(ns_id) = namespace_id {
+        ns_id
+    } else if non_interactive {
+        anyhow::bail!("--namespace-id is required in non-interactive mode");
+    } else {
+        match client.pick_namespace_grant(&principal.id)? {
+            Some(grant) => grant.namespace_id,
+            None => return Ok(()),
+        }
+    };
+
+    let namespace_name = client
+        .namespace_names()?
+        .remove(&namespace_id)
+        .unwrap_or_else(|| "<unknown>".to_string());
+
+    let confirmed = confirm_action(
+        &format!("Revoke {}'s access to '{}'?", username, namespace_name),
+        yes,
+        non_interactive,
+    )?;
+
+    if !confirmed {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    client.revoke_namespace(&principal.id, &namespace_id)?;
+
+    println!();
+    println!("Revoked grant.");
+    println!();
+
+    Ok(())
+}
+
+fn permission_repo_grant_remote(
+    client: &AdminClient,
+    principal_id: Option<String>,
+    repo_id: Option<String>,
+    permissions: Option<String>,
+    paths: &[String],
+    non_interactive: bool,
+) -> anyhow::Result<()> {
+    let (principal, username) = match client.get_or_pick_principal(principal_id, non_interactive)? {
+        Some(result) => result,
+        None => return Ok(()),
+    };
+
+    // The admin API has no repository listing to pick from.
+    let Some(repo_id) = repo_id else {
+        anyhow::bail!("--repo-id is required with --server");
+    };
+
+    let Some(allow_bits) =
+        resolve_permissions(permissions, non_interactive, pick_repo_permissions)?
+    else {
+        return Ok(());
+    };
+
+    client.grant_repo(&principal.id, &repo_id, allow_bits.to_strings(), paths)?;
+
+    println!();
+    println!(
+        "Granted {} access to repo {} with: {}",
+        username,
+        repo_id,
+        allow_bits.to_strings().join(", ")
+    );
+    print_grant_paths(paths);
+    println!();
+
+    Ok(())
+}
+
+fn permission_repo_revoke_remote(
+    client: &AdminClient,
+    principal_id: Option<String>,
+    repo_id: Option<String>,
+    non_interactive: bool,
+    yes: bool,
+) -> anyhow::Result<()> {
+    let (principal, username) = match client.get_or_pick_principal(principal_id, non_interactive)? {
+        Some(result) => result,
+        None => return Ok(()),
+    };
+
+    let repo_id = if let Some(r_id) = repo_id {
+        r_id
+    } else if non_interactive {
+        anyhow::bail!("--repo-id is required in non-interactive mode");
+    } else {
+        match client.pick_repo_grant(&principal.id)? {
+            Some(grant) => grant.repo_id,
+            None => return Ok(()),
+        }
+    };
+
+    let confirmed = confirm_action(
+        &format!("Revoke {}'s access to repo {}?", username, repo_id),
+        yes,
+        non_interactive,
+    )?;
+
+    if !confirmed {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    client.revoke_repo(&principal.id, &repo_id)?;
+
+    println!();
+    println!("Revoked repo grant.");
+    println!();
+
+    Ok(())
+}
@@ -4,9 +4,11 @@ use chrono::{DateTime, Duration, Utc};
 use inquire::{InquireError, MultiSelect, Select};
 use uuid::Uuid;
 
-use crate::auth::TokenGenerator;
+use crate::auth::{TokenFormat, TokenGenerator};
 use crate::store::Store;
-use crate::types::{Namespace, NamespaceGrant, Permission, Principal, Repo, RepoGrant, Tag, Token};
+use crate::types::{
+    Namespace, NamespaceGrant, Permission, Principal, Repo, RepoGrant, Tag, Token, TrafficClass,
+};
 
 /// Principal with resolved namespace name for display
 pub struct PrincipalDisplay {
@@ -53,8 +55,11 @@ impl fmt::Display for TokenDisplay {
         };
         write!(
             f,
-            "cutman_{}...  {}  created {}  {}",
-            &self.token.token_lookup, user, created, last_used
+            "{}  {}  created {}  {}",
+            TokenFormat::redacted(&self.token.token_lookup),
+            user,
+            created,
+            last_used
         )
     }
 }
@@ -291,7 +296,7 @@ fn load_namespaces_with_ownership(
 }
 
 /// Load tokens with resolved usernames
-fn load_tokens_with_principals(store: &impl Store) -> anyhow::Result<Vec<TokenDisplay>> {
+pub fn load_tokens_with_principals(store: &impl Store) -> anyhow::Result<Vec<TokenDisplay>> {
     let tokens = store.list_tokens("", 1000)?;
     let mut displays = Vec::with_capacity(tokens.len());
 
@@ -427,6 +432,7 @@ pub fn pick_grant(store: &impl Store, principal_id: &str) -> anyhow::Result<Opti
 pub fn pick_permissions() -> anyhow::Result<Option<Permission>> {
     let options = vec![
         "repo:read",
+        "repo:contents",
         "repo:write",
         "repo:admin",
         "namespace:read",
@@ -435,7 +441,7 @@ pub fn pick_permissions() -> anyhow::Result<Option<Permission>> {
     ];
 
     let selection = MultiSelect::new("Permissions to grant:", options)
-        .with_page_size(6)
+        .with_page_size(7)
         .with_help_message("Space to toggle, Enter to confirm")
         .with_vim_mode(true)
         .prompt();
@@ -589,6 +595,7 @@ pub fn create_token_for_principal(
         created_at: now,
         expires_at: expires_in.map(|d| now + d),
         last_used_at: None,
+        traffic_class: TrafficClass::default(),
     };
     Ok((token, raw_token))
 }
@@ -681,10 +688,10 @@ pub fn pick_repo_grant(store: &impl Store, principal_id: &str) -> anyhow::Result
 
 /// Pick repo permissions using multi-select
 pub fn pick_repo_permissions() -> anyhow::Result<Option<Permission>> {
-    let options = vec!["repo:read", "repo:write", "repo:admin"];
+    let options = vec!["repo:read", "repo:contents", "repo:write", "repo:admin"];
 
     let selection = MultiSelect::new("Permissions to grant:", options)
-        .with_page_size(3)
+        .with_page_size(4)
         .with_help_message("Space to toggle, Enter to confirm")
         .with_vim_mode(true)
         .prompt();
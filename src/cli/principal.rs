@@ -1,24 +1,20 @@
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use inquire::{Confirm, Text};
 use uuid::Uuid;
 
 use crate::auth::TokenGenerator;
+use crate::email_policy::{MAX_PRINCIPAL_EMAILS, validate_email};
 use crate::server::validation::validate_namespace_name;
 use crate::store::Store;
-use crate::types::{Namespace, Principal};
+use crate::types::{Namespace, Principal, TrafficClass};
 
+use super::admin_client::AdminClient;
+use super::commands::ServerArgs;
 use super::init_store;
 use super::pickers::{confirm_action, create_token_for_principal, get_or_pick_principal, pick_expiration};
 
-pub fn run_principal_add(
-    data_dir: String,
-    username: Option<String>,
-    create_token_flag: bool,
-    non_interactive: bool,
-) -> anyhow::Result<()> {
-    let store = init_store(&data_dir)?;
-
-    let username = if let Some(name) = username {
+fn prompt_username(username: Option<String>, non_interactive: bool) -> anyhow::Result<String> {
+    Ok(if let Some(name) = username {
         validate_namespace_name(&name).map_err(anyhow::Error::msg)?;
         name
     } else if non_interactive {
@@ -31,8 +27,53 @@ pub fn run_principal_add(
                     .unwrap_or_else(|e| inquire::validator::Validation::Invalid(e.into())))
             })
             .prompt()?
+    })
+}
+
+/// Asks whether to create a token, and when it should expire; `None` means
+/// no token.
+fn prompt_token_expiration(
+    create_token_flag: bool,
+    non_interactive: bool,
+) -> anyhow::Result<Option<Option<Duration>>> {
+    let should_create_token = if create_token_flag {
+        true
+    } else if non_interactive {
+        false
+    } else {
+        Confirm::new("Create access token?")
+            .with_default(true)
+            .prompt()?
     };
 
+    if !should_create_token {
+        return Ok(None);
+    }
+    if non_interactive {
+        return Ok(Some(None));
+    }
+    let expires_in = pick_expiration()?;
+    if expires_in.is_none() {
+        println!("Token creation cancelled.");
+    }
+    Ok(expires_in)
+}
+
+pub fn run_principal_add(
+    data_dir: String,
+    server: ServerArgs,
+    username: Option<String>,
+    create_token_flag: bool,
+    non_interactive: bool,
+) -> anyhow::Result<()> {
+    if let Some(client) = AdminClient::from_args(&server)? {
+        return principal_add_remote(&client, username, create_token_flag, non_interactive);
+    }
+
+    let store = init_store(&data_dir)?;
+
+    let username = prompt_username(username, non_interactive)?;
+
     if store.get_namespace_by_name(&username)?.is_some() {
         anyhow::bail!("Namespace '{}' already exists", username);
     }
@@ -66,29 +107,7 @@ pub fn run_principal_add(
         username, username
     );
 
-    let should_create_token = if create_token_flag {
-        true
-    } else if non_interactive {
-        false
-    } else {
-        Confirm::new("Create access token?")
-            .with_default(true)
-            .prompt()?
-    };
-
-    if should_create_token {
-        let expires_in = if non_interactive {
-            None
-        } else {
-            match pick_expiration()? {
-                Some(exp) => exp,
-                None => {
-                    println!("Token creation cancelled.");
-                    return Ok(());
-                }
-            }
-        };
-
+    if let Some(expires_in) = prompt_token_expiration(create_token_flag, non_interactive)? {
         let generator = TokenGenerator::new();
         let (token, raw_token) = create_token_for_principal(&generator, Some(principal_id), expires_in)?;
         store.create_token(&token)?;
@@ -103,12 +122,52 @@ pub fn run_principal_add(
     Ok(())
 }
 
+fn principal_add_remote(
+    client: &AdminClient,
+    username: Option<String>,
+    create_token_flag: bool,
+    non_interactive: bool,
+) -> anyhow::Result<()> {
+    let username = prompt_username(username, non_interactive)?;
+
+    // The API would adopt an existing shared namespace; refuse like the
+    // direct path does.
+    if client.namespaces()?.iter().any(|ns| ns.name == username) {
+        anyhow::bail!("Namespace '{}' already exists", username);
+    }
+
+    let principal = client.create_principal(&username)?;
+
+    println!();
+    println!(
+        "Created principal \"{}\" with namespace \"{}\"",
+        username, username
+    );
+
+    if let Some(expires_in) = prompt_token_expiration(create_token_flag, non_interactive)? {
+        let created = client.create_token(&principal.id, expires_in, TrafficClass::default())?;
+
+        println!();
+        println!("Token created: {}", created.token);
+        println!("  Save this now - it cannot be retrieved later.");
+    }
+
+    println!();
+
+    Ok(())
+}
+
 pub fn run_principal_remove(
     data_dir: String,
+    server: ServerArgs,
     principal_id: Option<String>,
     non_interactive: bool,
     yes: bool,
 ) -> anyhow::Result<()> {
+    if let Some(client) = AdminClient::from_args(&server)? {
+        return principal_remove_remote(&client, principal_id, non_interactive, yes);
+    }
+
     let store = init_store(&data_dir)?;
 
     let (principal, username) = match get_or_pick_principal(&store, principal_id, non_interactive)? {
@@ -151,3 +210,97 @@ pub fn run_principal_remove(
 
     Ok(())
 }
+
+fn principal_remove_remote(
+    client: &AdminClient,
+    principal_id: Option<String>,
+    non_interactive: bool,
+    yes: bool,
+) -> anyhow::Result<()> {
+    let (principal, username) = match client.get_or_pick_principal(principal_id, non_interactive)? {
+        Some(result) => result,
+        None => return Ok(()),
+    };
+
+    let confirmed = confirm_action(
+        &format!(
+            "Delete principal '{}'? This will also delete their namespace, tokens, and grants.",
+            username
+        ),
+        yes,
+        non_interactive,
+    )?;
+
+    if !confirmed {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    // Tokens and grants go with the principal; the namespace is separate.
+    client.delete_principal(&principal.id)?;
+
+    println!();
+    println!("Deleted principal '{}'", username);
+    if let Err(e) = client.delete_namespace(&username) {
+        println!("  Kept namespace '{}': {}", username, e);
+    }
+    println!();
+
+    Ok(())
+}
+
+pub fn run_principal_emails(
+    data_dir: String,
+    server: ServerArgs,
+    principal_id: Option<String>,
+    emails: Vec<String>,
+    clear: bool,
+    non_interactive: bool,
+) -> anyhow::Result<()> {
+    let replace = clear || !emails.is_empty();
+    let emails: Vec<String> = emails.iter().map(|e| e.trim().to_string()).collect();
+    if emails.len() > MAX_PRINCIPAL_EMAILS {
+        anyhow::bail!("A principal can have at most {MAX_PRINCIPAL_EMAILS} emails");
+    }
+    for email in &emails {
+        validate_email(email).map_err(anyhow::Error::msg)?;
+    }
+
+    let (username, emails) = if let Some(client) = AdminClient::from_args(&server)? {
+        let Some((principal, username)) =
+            client.get_or_pick_principal(principal_id, non_interactive)?
+        else {
+            return Ok(());
+        };
+        let emails = if replace {
+            client.set_principal_emails(&principal.id, &emails)?
+        } else {
+            client.principal_emails(&principal.id)?
+        };
+        (username, emails)
+    } else {
+        let store = init_store(&data_dir)?;
+        let Some((principal, username)) =
+            get_or_pick_principal(&store, principal_id, non_interactive)?
+        else {
+            return Ok(());
+        };
+        if replace {
+            store.set_principal_emails(&principal.id, &emails)?;
+        }
+        (username, store.list_principal_emails(&principal.id)?)
+    };
+
+    println!();
+    if emails.is_empty() {
+        println!("'{username}' has no verified emails.");
+    } else {
+        println!("Verified emails of '{username}':");
+        for email in &emails {
+            println!("  {email}");
+        }
+    }
+    println!();
+
+    Ok(())
+}
@@ -1,17 +1,22 @@
-use std::process::Command;
+use std::process::{Command, Stdio};
 
 use inquire::{MultiSelect, Select};
 use serde::Serialize;
+use unicode_normalization::UnicodeNormalization;
 
-use super::credentials::load_credentials;
+use super::credentials::{Credentials, load_credentials};
 use super::http_client::{ApiClient, NamespaceMap, PaginatedResponse};
 use super::pickers::{TagDisplay, confirm_action, repos_to_displays};
+use super::settings::{GitProtocol, Settings, load_settings};
 use crate::types::{Folder, Repo, Tag};
 
 /// Parses a repo reference in the format "namespace/name" or "name".
 /// Returns (Some(namespace), name) if namespace was explicit, (None, name) otherwise.
-/// Validates that the reference doesn't contain extra slashes.
+/// Validates that the reference doesn't contain extra slashes. The reference is
+/// NFC-normalized to match how the server stores Unicode repo names.
 pub fn parse_repo_ref(repo_ref: &str) -> anyhow::Result<(Option<String>, String)> {
+    let repo_ref: String = repo_ref.nfc().collect();
+    let repo_ref = repo_ref.as_str();
     let slash_count = repo_ref.chars().filter(|c| *c == '/').count();
     match slash_count {
         0 => Ok((None, repo_ref.to_string())),
@@ -32,6 +37,104 @@ pub fn parse_repo_ref(repo_ref: &str) -> anyhow::Result<(Option<String>, String)
     }
 }
 
+/// Extracts the host (without scheme, port, or path) from a server URL.
+fn server_host(server_url: &str) -> anyhow::Result<&str> {
+    let rest = server_url
+        .split_once("://")
+        .map_or(server_url, |(_, rest)| rest);
+    let authority = rest.split('/').next().unwrap_or(rest);
+
+    let host = if authority.starts_with('[') {
+        authority
+            .find(']')
+            .map_or(authority, |end| &authority[..=end])
+    } else {
+        authority.split(':').next().unwrap_or(authority)
+    };
+
+    if host.is_empty() {
+        anyhow::bail!("Could not determine host from server URL '{}'", server_url);
+    }
+    Ok(host)
+}
+
+/// Builds the git remote URL for a repository using the preferred protocol.
+/// The repo name is percent-encoded so Unicode names survive in URLs.
+pub fn git_remote_url(
+    server_url: &str,
+    settings: &Settings,
+    namespace_name: &str,
+    repo_name: &str,
+) -> anyhow::Result<String> {
+    let repo_name = urlencoding::encode(repo_name);
+    match settings.protocol {
+        GitProtocol::Http => Ok(format!(
+            "{}/git/{}/{}.git",
+            server_url, namespace_name, repo_name
+        )),
+        GitProtocol::Ssh => {
+            let host = server_host(server_url)?;
+            let port = match settings.ssh_port() {
+                22 => String::new(),
+                port => format!(":{port}"),
+            };
+            Ok(format!(
+                "ssh://{}@{}{}/{}/{}.git",
+                settings.ssh_user(),
+                host,
+                port,
+                namespace_name,
+                repo_name
+            ))
+        }
+    }
+}
+
+/// Suggests a fix when a remote is unreachable over the given protocol.
+#[must_use]
+pub fn connectivity_hint(protocol: GitProtocol) -> &'static str {
+    match protocol {
+        GitProtocol::Http => {
+            "Check that the server is reachable and run 'cutman auth login' if your token has expired."
+        }
+        GitProtocol::Ssh => {
+            "Check that your SSH key is authorized on the server, or switch back with 'cutman config set protocol http'."
+        }
+    }
+}
+
+/// Confirms the remote answers `git ls-remote`, optionally requiring a specific ref.
+pub fn verify_remote(
+    url: &str,
+    creds: &Credentials,
+    protocol: GitProtocol,
+    required_ref: Option<&str>,
+) -> anyhow::Result<()> {
+    let auth_header = format!("http.extraHeader=Authorization: Bearer {}", creds.token);
+    let mut cmd = Command::new("git");
+    cmd.arg("-c")
+        .arg(&auth_header)
+        .arg("ls-remote")
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    // --exit-code fails on an empty remote, so only use it when a ref must exist
+    match required_ref {
+        Some(r) => cmd.args(["--exit-code", url, r]),
+        None => cmd.arg(url),
+    };
+
+    if !cmd.status()?.success() {
+        anyhow::bail!(
+            "Could not reach {} over {}. {}",
+            url,
+            protocol.as_str(),
+            connectivity_hint(protocol)
+        );
+    }
+    Ok(())
+}
+
 /// Resolves the namespace name, fetching the primary namespace if not provided.
 pub fn resolve_namespace_name(
     namespace: Option<String>,
@@ -175,7 +278,10 @@ pub fn run_repo_clone(repo_ref: Option<String>, non_interactive: bool) -> anyhow
     };
 
     let ns_name = get_namespace_name(&repo, &namespace_map);
-    let clone_url = format!("{}/git/{}/{}.git", client.base_url(), ns_name, repo.name);
+    let settings = load_settings()?;
+    let clone_url = git_remote_url(client.base_url(), &settings, &ns_name, &repo.name)?;
+
+    verify_remote(&clone_url, &creds, settings.protocol, None)?;
 
     println!("Cloning {}/{}...", ns_name, repo.name);
 
@@ -183,7 +289,7 @@ pub fn run_repo_clone(repo_ref: Option<String>, non_interactive: bool) -> anyhow
     let status = Command::new("git")
         .arg("-c")
         .arg(&auth_header)
-        .args(["clone", &clone_url])
+        .args(["clone", &clone_url, &repo.name])
         .status()?;
 
     if !status.success() {
@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_SSH_USER: &str = "git";
+const DEFAULT_SSH_PORT: u16 = 22;
+
+/// Transport used for git remotes written by `cutman new` and `repo clone`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GitProtocol {
+    #[default]
+    Http,
+    Ssh,
+}
+
+impl GitProtocol {
+    fn parse(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "http" | "https" => Ok(Self::Http),
+            "ssh" => Ok(Self::Ssh),
+            other => anyhow::bail!("Invalid protocol '{}': expected 'http' or 'ssh'", other),
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Http => "http",
+            Self::Ssh => "ssh",
+        }
+    }
+}
+
+/// User preferences stored alongside credentials in ~/.config/cutman/config.toml.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub protocol: GitProtocol,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_port: Option<u16>,
+}
+
+impl Settings {
+    const KEYS: &'static [&'static str] = &["protocol", "ssh_user", "ssh_port"];
+
+    #[must_use]
+    pub fn ssh_user(&self) -> &str {
+        self.ssh_user.as_deref().unwrap_or(DEFAULT_SSH_USER)
+    }
+
+    #[must_use]
+    pub fn ssh_port(&self) -> u16 {
+        self.ssh_port.unwrap_or(DEFAULT_SSH_PORT)
+    }
+
+    fn get(&self, key: &str) -> anyhow::Result<String> {
+        match key {
+            "protocol" => Ok(self.protocol.as_str().to_string()),
+            "ssh_user" => Ok(self.ssh_user().to_string()),
+            "ssh_port" => Ok(self.ssh_port().to_string()),
+            _ => Err(unknown_key(key)),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        match key {
+            "protocol" => self.protocol = GitProtocol::parse(value)?,
+            "ssh_user" => {
+                let value = value.trim();
+                if value.is_empty() {
+                    anyhow::bail!("ssh_user cannot be empty");
+                }
+                self.ssh_user = Some(value.to_string());
+            }
+            "ssh_port" => {
+                let port = value
+                    .trim()
+                    .parse::<u16>()
+                    .ok()
+                    .filter(|p| *p > 0)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid ssh_port '{}'", value))?;
+                self.ssh_port = Some(port);
+            }
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+}
+
+fn unknown_key(key: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Unknown config key '{}'. Valid keys: {}",
+        key,
+        Settings::KEYS.join(", ")
+    )
+}
+
+pub fn settings_path() -> anyhow::Result<PathBuf> {
+    let home = std::env::var("HOME")
+        .map_err(|_| anyhow::anyhow!("Could not determine home directory. Is $HOME set?"))?;
+    Ok(PathBuf::from(home).join(".config/cutman/config.toml"))
+}
+
+/// Loads user settings, falling back to defaults when no config file exists.
+pub fn load_settings() -> anyhow::Result<Settings> {
+    let path = settings_path()?;
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+    let content = fs::read_to_string(&path)?;
+    toml::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))
+}
+
+pub fn save_settings(settings: &Settings) -> anyhow::Result<()> {
+    let path = settings_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, toml::to_string_pretty(settings)?)?;
+    Ok(())
+}
+
+pub fn run_config_set(key: String, value: String) -> anyhow::Result<()> {
+    let mut settings = load_settings()?;
+    settings.set(&key, &value)?;
+    save_settings(&settings)?;
+
+    println!("{} = {}", key, settings.get(&key)?);
+    Ok(())
+}
+
+pub fn run_config_get(key: Option<String>) -> anyhow::Result<()> {
+    let settings = load_settings()?;
+
+    match key {
+        Some(key) => println!("{}", settings.get(&key)?),
+        None => {
+            for key in Settings::KEYS {
+                println!("{} = {}", key, settings.get(key)?);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use std::process::Command;
+
+use super::credentials::load_credentials;
+use super::http_client::ApiClient;
+use super::pickers::confirm_action;
+use crate::server::dto::SetupResponse;
+
+pub fn run_setup(dry_run: bool, non_interactive: bool, yes: bool) -> anyhow::Result<()> {
+    let creds = load_credentials()?;
+    let client = ApiClient::new(&creds)?;
+
+    let setup: SetupResponse = client.get("/user/setup")?;
+
+    println!();
+    println!("Recommended git config for {}:", setup.base_url);
+    println!();
+    for rec in &setup.git_config {
+        println!("  # {}", rec.description);
+        println!("  git config --global {} '{}'", rec.key, rec.value);
+    }
+    println!();
+
+    if dry_run {
+        return Ok(());
+    }
+
+    if !confirm_action("Apply these settings to your global git config?", yes, non_interactive)? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    for rec in &setup.git_config {
+        let status = Command::new("git")
+            .args(["config", "--global", &rec.key, &rec.value])
+            .status()?;
+        if !status.success() {
+            anyhow::bail!("Failed to set git config '{}'", rec.key);
+        }
+    }
+
+    println!("Applied {} settings.", setup.git_config.len());
+    println!();
+
+    Ok(())
+}
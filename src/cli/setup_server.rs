@@ -0,0 +1,368 @@
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use inquire::validator::Validation;
+use inquire::{Confirm, CustomType, Select, Text};
+
+use crate::auth::TokenGenerator;
+use crate::config::{ServerConfig, validate_config_source};
+use crate::store::{SqliteStore, Store};
+
+use super::commands::ServerArgs;
+use super::pickers::create_token_for_principal;
+use super::principal::run_principal_add;
+
+/// How clients reach the server: over HTTPS that cutman serves itself
+/// (with the `tls` feature) or a proxy terminates, or plain HTTP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TlsMode {
+    Native,
+    Proxy,
+    Plain,
+}
+
+impl std::fmt::Display for TlsMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Native => write!(f, "HTTPS, served by cutman from a certificate and key"),
+            Self::Proxy => write!(f, "HTTPS, terminated by a reverse proxy in front of cutman"),
+            Self::Plain => write!(f, "Plain HTTP (local or trusted networks only)"),
+        }
+    }
+}
+
+/// Answers to the wizard's questions about `server.toml`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ServerAnswers {
+    data_dir: PathBuf,
+    hosts: Vec<String>,
+    port: u16,
+    public_base_url: String,
+    /// Certificate chain and key files when cutman serves HTTPS itself.
+    tls: Option<(PathBuf, PathBuf)>,
+}
+
+impl ServerAnswers {
+    fn to_toml(&self) -> String {
+        let hosts = match self.hosts.as_slice() {
+            [host] => toml::Value::from(host.as_str()),
+            hosts => toml::Value::from(hosts.to_vec()),
+        };
+        let mut toml = format!(
+            "# Written by `cutman setup-server`.\n\
+             host = {hosts}\n\
+             port = {}\n\
+             data_dir = {}\n\
+             public_base_url = {}\n",
+            self.port,
+            toml::Value::from(self.data_dir.to_string_lossy().as_ref()),
+            toml::Value::from(self.public_base_url.as_str()),
+        );
+        if let Some((cert, key)) = &self.tls {
+            // Renewed certificates are picked up within the hour.
+            toml.push_str(&format!(
+                "tls_cert = {}\n\
+                 tls_key = {}\n\
+                 tls_reload_interval = \"1h\"\n",
+                toml::Value::from(cert.to_string_lossy().as_ref()),
+                toml::Value::from(key.to_string_lossy().as_ref()),
+            ));
+        }
+        toml
+    }
+
+    /// The config as the server would load it, checked the same way.
+    fn validate(&self) -> anyhow::Result<ServerConfig> {
+        validate_config_source(&self.to_toml()).map_err(|problems| {
+            let problems: Vec<String> = problems.iter().map(ToString::to_string).collect();
+            anyhow::anyhow!("Invalid config: {}", problems.join("; "))
+        })
+    }
+}
+
+fn validate_base_url(input: &str, tls: TlsMode) -> Result<(), String> {
+    let url = reqwest::Url::parse(input).map_err(|e| format!("Not a URL: {e}"))?;
+    match url.scheme() {
+        "https" => {}
+        "http" if tls == TlsMode::Plain => {}
+        "http" => return Err("Clients connect over HTTPS; use https://".to_string()),
+        _ => return Err("Use an http:// or https:// URL".to_string()),
+    }
+    if url.host_str().is_none() {
+        return Err("The URL needs a host".to_string());
+    }
+    Ok(())
+}
+
+fn prompt_hosts() -> anyhow::Result<Vec<String>> {
+    const LOCAL: &str = "127.0.0.1 (this machine only, e.g. behind a proxy)";
+    const IPV4: &str = "0.0.0.0 (every IPv4 interface)";
+    const DUAL: &str = "0.0.0.0 and :: (every IPv4 and IPv6 interface)";
+    const CUSTOM: &str = "Another address";
+
+    Ok(
+        match Select::new("Bind address:", vec![LOCAL, IPV4, DUAL, CUSTOM]).prompt()? {
+            LOCAL => vec!["127.0.0.1".to_string()],
+            IPV4 => vec!["0.0.0.0".to_string()],
+            DUAL => vec!["0.0.0.0".to_string(), "::".to_string()],
+            _ => vec![
+                Text::new("Address:")
+                    .with_validator(|input: &str| {
+                        let host = input.trim().trim_start_matches('[').trim_end_matches(']');
+                        Ok(match host.parse::<IpAddr>() {
+                            Ok(_) => Validation::Valid,
+                            Err(_) => Validation::Invalid("Enter an IPv4 or IPv6 address".into()),
+                        })
+                    })
+                    .prompt()?
+                    .trim()
+                    .to_string(),
+            ],
+        },
+    )
+}
+
+fn prompt_answers() -> anyhow::Result<ServerAnswers> {
+    let data_dir = Text::new("Data directory:")
+        .with_default("./data")
+        .with_help_message("Repositories, LFS objects, and the database live here")
+        .with_validator(|input: &str| {
+            Ok(if input.trim().is_empty() {
+                Validation::Invalid("Enter a directory".into())
+            } else {
+                Validation::Valid
+            })
+        })
+        .prompt()?;
+    let data_dir = std::path::absolute(data_dir.trim())?;
+
+    let hosts = prompt_hosts()?;
+    let port = CustomType::<u16>::new("Port:")
+        .with_default(8080)
+        .with_error_message("Enter a port between 1 and 65535")
+        .prompt()?;
+
+    let mut modes = vec![TlsMode::Proxy, TlsMode::Plain];
+    if cfg!(feature = "tls") {
+        modes.insert(0, TlsMode::Native);
+    }
+    let tls = Select::new("How do clients connect?", modes)
+        .with_help_message(
+            "cutman can serve HTTPS from PEM files, or sit behind nginx, Caddy, or similar",
+        )
+        .prompt()?;
+    let default_url = match tls {
+        TlsMode::Native if port == 443 => "https://git.example.com".to_string(),
+        TlsMode::Native => format!("https://git.example.com:{port}"),
+        TlsMode::Proxy => "https://git.example.com".to_string(),
+        TlsMode::Plain => format!("http://localhost:{port}"),
+    };
+    let tls_files = if tls == TlsMode::Native {
+        let file = |prompt: &str, help: &str| -> anyhow::Result<PathBuf> {
+            let path = Text::new(prompt)
+                .with_help_message(help)
+                .with_validator(|input: &str| {
+                    Ok(if input.trim().is_empty() {
+                        Validation::Invalid("Enter a file".into())
+                    } else {
+                        Validation::Valid
+                    })
+                })
+                .prompt()?;
+            Ok(std::path::absolute(path.trim())?)
+        };
+        Some((
+            file(
+                "Certificate file:",
+                "PEM, full chain, e.g. /etc/letsencrypt/live/<host>/fullchain.pem",
+            )?,
+            file(
+                "Private key file:",
+                "PEM, e.g. /etc/letsencrypt/live/<host>/privkey.pem",
+            )?,
+        ))
+    } else {
+        None
+    };
+    let public_base_url = Text::new("Public base URL:")
+        .with_default(&default_url)
+        .with_help_message("The address clients use; LFS and clone links point here")
+        .with_validator(move |input: &str| {
+            Ok(match validate_base_url(input.trim(), tls) {
+                Ok(()) => Validation::Valid,
+                Err(e) => Validation::Invalid(e.into()),
+            })
+        })
+        .prompt()?
+        .trim()
+        .trim_end_matches('/')
+        .to_string();
+
+    Ok(ServerAnswers {
+        data_dir,
+        hosts,
+        port,
+        public_base_url,
+        tls: tls_files,
+    })
+}
+
+/// Creates the admin token in a new or existing data directory, writing it
+/// to `.admin_token` there. Returns `None` if one already exists.
+pub fn create_admin_token(data_path: &Path) -> anyhow::Result<Option<String>> {
+    fs::create_dir_all(data_path)?;
+    let store = SqliteStore::new(data_path.join("cutman.db"))?;
+    store.initialize()?;
+    if store.has_admin_token()? {
+        return Ok(None);
+    }
+
+    let generator = TokenGenerator::new();
+    let (mut token, raw_token) = create_token_for_principal(&generator, None, None)?;
+    token.is_admin = true;
+    store.create_token(&token)?;
+
+    let token_file = data_path.join(".admin_token");
+    fs::write(&token_file, &raw_token)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Err(e) = fs::set_permissions(&token_file, fs::Permissions::from_mode(0o600)) {
+            tracing::warn!("Failed to set permissions on {}: {e}", token_file.display());
+        }
+    }
+
+    Ok(Some(raw_token))
+}
+
+/// Walks a new operator through `server.toml`, the admin token, and the
+/// first user, then says how to start the server.
+pub fn run_setup_server(config_path: String) -> anyhow::Result<()> {
+    let config_path = PathBuf::from(config_path);
+    if config_path.exists()
+        && !Confirm::new(&format!("{} exists. Overwrite it?", config_path.display()))
+            .with_default(false)
+            .prompt()?
+    {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let answers = prompt_answers()?;
+    answers.validate()?;
+    if let Some(parent) = config_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&config_path, answers.to_toml())?;
+    println!();
+    println!("Wrote {}", config_path.display());
+
+    let token_file = answers.data_dir.join(".admin_token");
+    match create_admin_token(&answers.data_dir)? {
+        Some(raw_token) => {
+            println!();
+            println!("========================================");
+            println!("Admin token (save this, it won't be shown again):");
+            println!();
+            println!("  {raw_token}");
+            println!();
+            println!("Token also written to: {}", token_file.display());
+            println!("========================================");
+        }
+        None => println!(
+            "Keeping the existing admin token in {}",
+            token_file.display()
+        ),
+    }
+    println!();
+
+    if Confirm::new("Create the first user and their namespace now?")
+        .with_default(true)
+        .prompt()?
+    {
+        run_principal_add(
+            answers.data_dir.to_string_lossy().into_owned(),
+            ServerArgs::default(),
+            None,
+            true,
+            false,
+        )?;
+    }
+
+    println!("Start the server with:");
+    println!();
+    println!("  cutman serve --config {}", config_path.display());
+    println!();
+    println!(
+        "Then log in from your machine with `cutman login` at {}",
+        answers.public_base_url
+    );
+    println!();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answers_make_a_loadable_config() {
+        let answers = ServerAnswers {
+            data_dir: PathBuf::from("/var/lib/cutman \"git\""),
+            hosts: vec!["0.0.0.0".to_string(), "::".to_string()],
+            port: 3000,
+            public_base_url: "https://git.example.com".to_string(),
+            tls: None,
+        };
+        let config = answers.validate().unwrap();
+        assert_eq!(config.hosts, answers.hosts);
+        assert_eq!(config.port, 3000);
+        assert_eq!(config.data_dir, answers.data_dir);
+        assert_eq!(
+            config.public_base_url.as_deref(),
+            Some("https://git.example.com")
+        );
+
+        let single = ServerAnswers {
+            hosts: vec!["127.0.0.1".to_string()],
+            ..answers.clone()
+        };
+        assert!(single.to_toml().contains("host = \"127.0.0.1\"\n"));
+
+        let native = ServerAnswers {
+            tls: Some((
+                PathBuf::from("/etc/cutman/cert.pem"),
+                PathBuf::from("/etc/cutman/key.pem"),
+            )),
+            ..answers.clone()
+        };
+        let config = native.validate().unwrap();
+        assert_eq!(
+            config.tls_cert.as_deref(),
+            Some(Path::new("/etc/cutman/cert.pem"))
+        );
+        assert_eq!(
+            config.tls_key.as_deref(),
+            Some(Path::new("/etc/cutman/key.pem"))
+        );
+        assert_eq!(config.tls_reload_secs, Some(3600));
+
+        let invalid = ServerAnswers {
+            hosts: vec!["localhost".to_string()],
+            ..answers
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_base_url() {
+        assert!(validate_base_url("https://git.example.com", TlsMode::Proxy).is_ok());
+        assert!(validate_base_url("http://git.example.com", TlsMode::Proxy).is_err());
+        assert!(validate_base_url("http://git.example.com", TlsMode::Native).is_err());
+        assert!(validate_base_url("http://localhost:8080", TlsMode::Plain).is_ok());
+        assert!(validate_base_url("ftp://git.example.com", TlsMode::Plain).is_err());
+        assert!(validate_base_url("git.example.com", TlsMode::Plain).is_err());
+    }
+}
@@ -1,20 +1,64 @@
-use chrono::Duration;
+use chrono::{Duration, Utc};
 
-use crate::auth::TokenGenerator;
+use crate::auth::{TokenFormat, TokenGenerator, reissue_token};
 use crate::store::Store;
+use crate::types::TrafficClass;
 
+use super::admin_client::AdminClient;
+use super::commands::ServerArgs;
 use super::init_store;
 use super::pickers::{
-    confirm_action, create_token_for_principal, get_or_pick_principal, pick_expiration, pick_token,
-    resolve_token_username,
+    confirm_action, create_token_for_principal, format_relative_time, get_or_pick_principal,
+    load_tokens_with_principals, pick_expiration, pick_token, resolve_token_username,
 };
 
+/// The expiration `--expires-days` asks for, or the one picked
+/// interactively; `None` if the picker was cancelled.
+fn resolve_expiration(
+    expires_days: Option<i64>,
+    non_interactive: bool,
+) -> anyhow::Result<Option<Option<Duration>>> {
+    if let Some(days) = expires_days {
+        Ok(Some((days > 0).then(|| Duration::days(days))))
+    } else if non_interactive {
+        Ok(Some(None))
+    } else {
+        let expires_in = pick_expiration()?;
+        if expires_in.is_none() {
+            println!("Cancelled.");
+        }
+        Ok(expires_in)
+    }
+}
+
 pub fn run_token_create(
     data_dir: String,
+    server: ServerArgs,
     principal_id: Option<String>,
     expires_days: Option<i64>,
+    batch: bool,
     non_interactive: bool,
 ) -> anyhow::Result<()> {
+    let traffic_class = if batch {
+        TrafficClass::Batch
+    } else {
+        TrafficClass::default()
+    };
+
+    if let Some(client) = AdminClient::from_args(&server)? {
+        let Some((principal, username)) =
+            client.get_or_pick_principal(principal_id, non_interactive)?
+        else {
+            return Ok(());
+        };
+        let Some(expires_in) = resolve_expiration(expires_days, non_interactive)? else {
+            return Ok(());
+        };
+        let created = client.create_token(&principal.id, expires_in, traffic_class)?;
+        print_created(&username, &created.token);
+        return Ok(());
+    }
+
     let store = init_store(&data_dir)?;
 
     let (principal_id, username) = match get_or_pick_principal(&store, principal_id, non_interactive)? {
@@ -22,42 +66,38 @@ pub fn run_token_create(
         None => return Ok(()),
     };
 
-    let expires_in = if let Some(days) = expires_days {
-        if days <= 0 {
-            None
-        } else {
-            Some(Duration::days(days))
-        }
-    } else if non_interactive {
-        None
-    } else {
-        match pick_expiration()? {
-            Some(exp) => exp,
-            None => {
-                println!("Cancelled.");
-                return Ok(());
-            }
-        }
+    let Some(expires_in) = resolve_expiration(expires_days, non_interactive)? else {
+        return Ok(());
     };
 
     let generator = TokenGenerator::new();
-    let (token, raw_token) = create_token_for_principal(&generator, principal_id, expires_in)?;
+    let (mut token, raw_token) = create_token_for_principal(&generator, principal_id, expires_in)?;
+    token.traffic_class = traffic_class;
     store.create_token(&token)?;
 
+    print_created(&username, &raw_token);
+
+    Ok(())
+}
+
+fn print_created(username: &str, raw_token: &str) {
     println!();
     println!("Token created for '{}': {}", username, raw_token);
     println!("  Save this now - it cannot be retrieved later.");
     println!();
-
-    Ok(())
 }
 
 pub fn run_token_revoke(
     data_dir: String,
+    server: ServerArgs,
     token_id: Option<String>,
     non_interactive: bool,
     yes: bool,
 ) -> anyhow::Result<()> {
+    if let Some(client) = AdminClient::from_args(&server)? {
+        return token_revoke_remote(&client, token_id, non_interactive, yes);
+    }
+
     let store = init_store(&data_dir)?;
 
     let (token, username) = if let Some(id) = token_id {
@@ -82,8 +122,9 @@ pub fn run_token_revoke(
 
     let confirmed = confirm_action(
         &format!(
-            "Revoke token cutman_{}... for user '{}'?",
-            &token.token_lookup, user_label
+            "Revoke token {} for user '{}'?",
+            TokenFormat::redacted(&token.token_lookup),
+            user_label
         ),
         yes,
         non_interactive,
@@ -102,3 +143,160 @@ pub fn run_token_revoke(
 
     Ok(())
 }
+
+fn token_revoke_remote(
+    client: &AdminClient,
+    token_id: Option<String>,
+    non_interactive: bool,
+    yes: bool,
+) -> anyhow::Result<()> {
+    let token = if let Some(id) = token_id {
+        client.token(&id)?
+    } else if non_interactive {
+        anyhow::bail!("--token-id is required in non-interactive mode");
+    } else {
+        match client.pick_token()? {
+            Some(token) => token,
+            None => return Ok(()),
+        }
+    };
+
+    let username = match &token.principal_id {
+        Some(id) => Some(client.username(&client.principal(id)?)?),
+        None => None,
+    };
+    let user_label = username.as_deref().unwrap_or("admin");
+
+    let confirmed = confirm_action(
+        &format!("Revoke token {} for user '{}'?", token.id, user_label),
+        yes,
+        non_interactive,
+    )?;
+
+    if !confirmed {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    client.delete_token(&token.id)?;
+
+    println!();
+    println!("Token revoked.");
+    println!();
+
+    Ok(())
+}
+
+pub fn run_token_legacy(data_dir: String, server: ServerArgs) -> anyhow::Result<()> {
+    if let Some(client) = AdminClient::from_args(&server)? {
+        let report = client.legacy_tokens()?;
+        println!();
+        for token in &report.tokens {
+            let last_used = token
+                .last_used_at
+                .as_ref()
+                .map_or_else(|| "never used".to_string(), format_relative_time);
+            println!("  {}  {}", token.id, last_used);
+        }
+        print_legacy_summary(report.tokens.len(), report.recently_used, report.accepted);
+        return Ok(());
+    }
+
+    let store = init_store(&data_dir)?;
+    let recent = Utc::now() - Duration::days(30);
+    let legacy: Vec<_> = load_tokens_with_principals(&store)?
+        .into_iter()
+        .filter(|t| TokenFormat::of_lookup(&t.token.token_lookup) == TokenFormat::Legacy)
+        .collect();
+
+    println!();
+    for display in &legacy {
+        println!("  {}  {}", display.token.id, display);
+    }
+    let recently_used = legacy
+        .iter()
+        .filter(|t| t.token.last_used_at.is_some_and(|at| at >= recent))
+        .count();
+    // Without the server's config at hand, assume the default.
+    print_legacy_summary(legacy.len(), recently_used, true);
+
+    Ok(())
+}
+
+fn print_legacy_summary(total: usize, recently_used: usize, accepted: bool) {
+    if total > 0 {
+        println!();
+    }
+    println!("{total} legacy token(s), {recently_used} used in the last 30 days.");
+    if total > 0 {
+        if !accepted {
+            println!("  They no longer authenticate; reissue or revoke them.");
+        }
+        println!("  Reissue with: cutman admin token reissue --token-id <id>");
+    }
+    println!();
+}
+
+pub fn run_token_reissue(
+    data_dir: String,
+    server: ServerArgs,
+    token_id: Option<String>,
+    grace_days: Option<i64>,
+    non_interactive: bool,
+) -> anyhow::Result<()> {
+    if grace_days.is_some_and(|days| days < 0) {
+        anyhow::bail!("--grace-days cannot be negative");
+    }
+    let grace = grace_days.map(Duration::days);
+
+    if let Some(client) = AdminClient::from_args(&server)? {
+        let token = if let Some(id) = token_id {
+            client.token(&id)?
+        } else if non_interactive {
+            anyhow::bail!("--token-id is required in non-interactive mode");
+        } else {
+            match client.pick_token()? {
+                Some(token) => token,
+                None => return Ok(()),
+            }
+        };
+        let created = client.reissue_token(&token.id, grace)?;
+        print_reissued(&created.metadata.id, &created.token, grace);
+        return Ok(());
+    }
+
+    let store = init_store(&data_dir)?;
+
+    let token = if let Some(id) = token_id {
+        store
+            .get_token_by_id(&id)?
+            .ok_or_else(|| anyhow::anyhow!("Token not found: {}", id))?
+    } else if non_interactive {
+        anyhow::bail!("--token-id is required in non-interactive mode");
+    } else {
+        match pick_token(&store)? {
+            Some(token) => token,
+            None => return Ok(()),
+        }
+    };
+
+    let generator = TokenGenerator::new();
+    let (token, raw_token) = reissue_token(&store, &generator, &token, grace)?;
+    print_reissued(&token.id, &raw_token, grace);
+
+    Ok(())
+}
+
+fn print_reissued(id: &str, raw_token: &str, grace: Option<Duration>) {
+    println!();
+    println!("Token reissued as {}: {}", id, raw_token);
+    println!("  Save this now - it cannot be retrieved later.");
+    match grace {
+        Some(grace) => println!(
+            "  The old token stops working in {} day(s).",
+            grace.num_days()
+        ),
+        None => println!("  The old token has been revoked."),
+    }
+    println!();
+}
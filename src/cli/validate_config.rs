@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use crate::config::{ServerConfig, validate_config_source};
+
+/// Checks a server config file, printing every problem in it. Without
+/// `file`, checks the one `cutman serve` would load.
+pub fn run_config_validate(file: Option<String>) -> anyhow::Result<()> {
+    let path = match file {
+        Some(file) => PathBuf::from(file),
+        None => ServerConfig::find_in_search_paths().ok_or_else(|| {
+            anyhow::anyhow!(
+                "No server config in ./server.toml or /etc/cutman/server.toml; pass --file"
+            )
+        })?,
+    };
+    let source = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+
+    match validate_config_source(&source) {
+        Ok(_) => {
+            println!("{} is valid", path.display());
+            Ok(())
+        }
+        Err(problems) => {
+            for problem in &problems {
+                eprintln!("{}", problem.located(&path));
+            }
+            let noun = if problems.len() == 1 {
+                "problem"
+            } else {
+                "problems"
+            };
+            anyhow::bail!("Found {} {noun} in {}", problems.len(), path.display())
+        }
+    }
+}
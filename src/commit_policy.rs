@@ -0,0 +1,280 @@
+//! Commit message rules a repository can set. They're checked for every
+//! new commit a push brings to a branch, in the pre-receive hook, and for
+//! every commit the content API makes. Merge commits are exempt, since git
+//! and the merge API write their messages.
+
+use git2::{Commit, Oid, Repository};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Longest pattern accepted, so a policy can't make every push slow.
+const MAX_PATTERN_LEN: usize = 512;
+
+/// How many rejected commits a push reports before summarising the rest.
+const MAX_REPORTED: usize = 20;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommitPolicy {
+    /// Regex the subject line must match, e.g. `^[A-Z]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject_pattern: Option<String>,
+    /// Subjects must read `type(scope)!: description`, as in Conventional
+    /// Commits; the scope and `!` are optional.
+    #[serde(default)]
+    pub conventional: bool,
+    /// Longest subject line allowed, in characters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_subject_length: Option<u32>,
+    /// Regex for the issue reference every message must contain, e.g.
+    /// `#\d+` or `[A-Z]+-\d+`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue_pattern: Option<String>,
+}
+
+/// A commit whose message breaks the policy, and every rule it breaks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommitViolation {
+    pub sha: String,
+    pub subject: String,
+    pub violations: Vec<String>,
+}
+
+impl CommitPolicy {
+    /// Explains the first setting that can't be used.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, pattern) in [
+            ("subject_pattern", &self.subject_pattern),
+            ("issue_pattern", &self.issue_pattern),
+        ] {
+            if let Some(pattern) = pattern {
+                compile(pattern).map_err(|e| format!("Invalid {name}: {e}"))?;
+            }
+        }
+        if self.max_subject_length == Some(0) {
+            return Err("max_subject_length must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Every rule `message` breaks, empty when it meets the policy.
+    #[must_use]
+    pub fn check(&self, message: &str) -> Vec<String> {
+        let subject = subject(message);
+        let mut violations = Vec::new();
+        if let Some(limit) = self.max_subject_length {
+            let length = subject.chars().count();
+            if length > limit as usize {
+                violations.push(format!(
+                    "subject is {length} characters, over the limit of {limit}"
+                ));
+            }
+        }
+        if self.conventional && !is_conventional(subject) {
+            violations.push(
+                "subject must follow Conventional Commits, e.g. \"fix(parser): handle tabs\""
+                    .to_string(),
+            );
+        }
+        if let Some(pattern) = &self.subject_pattern {
+            if !compile(pattern).is_ok_and(|re| re.is_match(subject)) {
+                violations.push(format!("subject must match {pattern}"));
+            }
+        }
+        if let Some(pattern) = &self.issue_pattern {
+            if !compile(pattern).is_ok_and(|re| re.is_match(message)) {
+                violations.push(format!(
+                    "message must reference an issue matching {pattern}"
+                ));
+            }
+        }
+        violations
+    }
+
+    /// Checks the commits a push adds, see [`new_commits`].
+    pub fn check_new_commits(
+        &self,
+        repo: &Repository,
+        tips: &[Oid],
+    ) -> Result<Vec<CommitViolation>, git2::Error> {
+        let mut rejected = Vec::new();
+        for commit in new_commits(repo, tips)? {
+            let commit = commit?;
+            if commit.parent_count() > 1 {
+                continue;
+            }
+            let message = String::from_utf8_lossy(commit.message_bytes());
+            let violations = self.check(&message);
+            if !violations.is_empty() {
+                rejected.push(CommitViolation::new(&commit, violations));
+            }
+        }
+        Ok(rejected)
+    }
+}
+
+impl CommitViolation {
+    #[must_use]
+    pub fn new(commit: &Commit<'_>, violations: Vec<String>) -> Self {
+        let message = String::from_utf8_lossy(commit.message_bytes());
+        Self {
+            sha: commit.id().to_string(),
+            subject: subject(&message).to_string(),
+            violations,
+        }
+    }
+}
+
+/// The commits a push adds: those reachable from the new branch tips but
+/// from no ref the repository already has.
+pub fn new_commits<'r>(
+    repo: &'r Repository,
+    tips: &[Oid],
+) -> Result<impl Iterator<Item = Result<Commit<'r>, git2::Error>>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    for tip in tips {
+        walk.push(*tip)?;
+    }
+    if !tips.is_empty() {
+        walk.hide_glob("refs/*")?;
+    }
+    Ok(walk.map(|oid| repo.find_commit(oid?)))
+}
+
+/// Lines telling a pusher which commits were rejected and why.
+#[must_use]
+pub fn report(rejected: &[CommitViolation]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for commit in rejected.iter().take(MAX_REPORTED) {
+        let short = &commit.sha[..commit.sha.len().min(7)];
+        lines.push(format!("commit {short} \"{}\":", commit.subject));
+        lines.extend(commit.violations.iter().map(|v| format!("  - {v}")));
+    }
+    if rejected.len() > MAX_REPORTED {
+        lines.push(format!(
+            "...and {} more commits",
+            rejected.len() - MAX_REPORTED
+        ));
+    }
+    lines
+}
+
+fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or_default().trim_end()
+}
+
+fn compile(pattern: &str) -> Result<Regex, String> {
+    if pattern.len() > MAX_PATTERN_LEN {
+        return Err(format!("longer than {MAX_PATTERN_LEN} characters"));
+    }
+    Regex::new(pattern).map_err(|e| e.to_string())
+}
+
+fn is_conventional(subject: &str) -> bool {
+    let Some((prefix, description)) = subject.split_once(": ") else {
+        return false;
+    };
+    let prefix = prefix.strip_suffix('!').unwrap_or(prefix);
+    let kind = match prefix.split_once('(') {
+        Some((kind, scope)) => {
+            let Some(scope) = scope.strip_suffix(')') else {
+                return false;
+            };
+            if scope.is_empty() || scope.contains(['(', ')']) {
+                return false;
+            }
+            kind
+        }
+        None => prefix,
+    };
+    !kind.is_empty()
+        && kind.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && !description.trim().is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_message() {
+        let policy = CommitPolicy {
+            subject_pattern: Some("^[a-z]".to_string()),
+            conventional: true,
+            max_subject_length: Some(30),
+            issue_pattern: Some(r"#\d+".to_string()),
+        };
+        assert!(
+            policy
+                .check("fix(parser)!: handle tabs\n\nCloses #12")
+                .is_empty()
+        );
+        assert!(policy.check("docs: tidy #3").is_empty());
+
+        let violations = policy.check("Handle tabs in the parser and the lexer");
+        assert_eq!(violations.len(), 4, "{violations:?}");
+        assert!(violations[0].starts_with("subject is 39 characters"));
+
+        assert!(!is_conventional("fix(): empty scope"));
+        assert!(!is_conventional("fix:missing space"));
+        assert!(!is_conventional("fix: "));
+        assert!(is_conventional("feat-x(api): add"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(CommitPolicy::default().validate().is_ok());
+        let bad = CommitPolicy {
+            issue_pattern: Some("(".to_string()),
+            ..CommitPolicy::default()
+        };
+        assert!(
+            bad.validate()
+                .unwrap_err()
+                .starts_with("Invalid issue_pattern")
+        );
+        let zero = CommitPolicy {
+            max_subject_length: Some(0),
+            ..CommitPolicy::default()
+        };
+        assert!(zero.validate().is_err());
+    }
+
+    #[test]
+    fn test_checks_only_new_commits() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let sig = git2::Signature::now("Test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let commit = |message: &str, parents: &[Oid]| {
+            let parents: Vec<_> = parents
+                .iter()
+                .map(|p| repo.find_commit(*p).unwrap())
+                .collect();
+            let parents: Vec<_> = parents.iter().collect();
+            repo.commit(None, &sig, &sig, message, &tree, &parents)
+                .unwrap()
+        };
+        let old = commit("bad old commit", &[]);
+        repo.reference("refs/heads/main", old, false, "").unwrap();
+        let good = commit("feat: good", &[old]);
+        let bad = commit("bad new commit", &[good]);
+        let merge = commit("Merge branch 'x'", &[bad, old]);
+
+        let policy = CommitPolicy {
+            conventional: true,
+            ..CommitPolicy::default()
+        };
+        let rejected = policy.check_new_commits(&repo, &[merge]).unwrap();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].sha, bad.to_string());
+        assert_eq!(rejected[0].subject, "bad new commit");
+        assert_eq!(
+            report(&rejected)[0],
+            format!("commit {} \"bad new commit\":", &rejected[0].sha[..7])
+        );
+        assert!(policy.check_new_commits(&repo, &[old]).unwrap().is_empty());
+    }
+}
@@ -1,3 +1,9 @@
 mod server;
+mod validate;
 
-pub use server::{ServerConfig, ServerConfigOverrides};
+pub use server::{
+    AcmeConfig, AuthzConfig, EventKind, EventsConfig, Feature, FeatureFlags, LegacyTokens,
+    MaintenanceTask, ProcessLimits, PushAlertsConfig, RepoNamePolicy, S3StorageConfig,
+    ServerConfig, ServerConfigOverrides, StorageMode, SubprocessLimits, TracingConfig,
+};
+pub use validate::{ConfigProblem, validate_config_source};
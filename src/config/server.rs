@@ -1,11 +1,73 @@
+use std::collections::HashMap;
 use std::fs;
-use std::net::SocketAddr;
+use std::net::{AddrParseError, IpAddr, SocketAddr};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, Serialize};
 
-fn default_host() -> String {
-    "127.0.0.1".to_string()
+use crate::hooks::BUILTIN_HOOKS;
+use crate::server::limits::BACKGROUND_GIT_TIMEOUT;
+use crate::storage_roots::DEFAULT_ROOT;
+use crate::units::{ByteSize, HumanDuration};
+
+use super::validate::validate_config_source;
+
+fn default_hosts() -> Vec<String> {
+    vec!["127.0.0.1".to_string()]
+}
+
+/// Accepts `host = "::"` as well as `host = ["0.0.0.0", "::"]`.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(host) => vec![host],
+        OneOrMany::Many(hosts) => hosts,
+    })
+}
+
+/// Accepts a byte count as a number or with a unit, e.g. `"10GB"`.
+fn size<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    ByteSize::deserialize(deserializer).map(|size| size.0)
+}
+
+fn optional_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    size(deserializer).map(Some)
+}
+
+/// Accepts a number of seconds, or a duration with units, e.g. `"5m"`.
+fn secs<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    HumanDuration::deserialize(deserializer).map(|duration| duration.0.as_secs())
+}
+
+fn optional_secs<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    secs(deserializer).map(Some)
+}
+
+/// A configured time limit in seconds, where 0 means none.
+fn time_limit(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
 }
 
 fn default_port() -> u16 {
@@ -16,36 +78,1074 @@ fn default_data_dir() -> PathBuf {
     PathBuf::from("./data")
 }
 
-/// Configuration for the server, loadable from TOML file.
+/// Storage root names appear in the store and on the command line.
+fn is_root_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_disk_check_interval_secs() -> u64 {
+    30
+}
+
+fn default_temp_max_age_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_temp_sweep_interval_secs() -> u64 {
+    60 * 60
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_authz_timeout_secs() -> u64 {
+    5
+}
+
+fn default_authz_cache_secs() -> u64 {
+    30
+}
+
+fn default_acme_directory() -> String {
+    "https://acme-v02.api.letsencrypt.org/directory".to_string()
+}
+
+fn default_acme_http_port() -> u16 {
+    80
+}
+
+fn default_service_name() -> String {
+    "cutman".to_string()
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
+fn default_nats_subject_prefix() -> String {
+    "cutman".to_string()
+}
+
+fn default_kafka_topic() -> String {
+    "cutman.events".to_string()
+}
+
+fn default_events_queue_size() -> usize {
+    10_000
+}
+
+fn default_rewritten_commits() -> u32 {
+    50
+}
+
+fn default_deleted_branches() -> u32 {
+    5
+}
+
+fn default_pages_max_bytes() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_ssh_idle_timeout_secs() -> u64 {
+    10 * 60
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
+
+fn default_git_timeout_secs() -> u64 {
+    5 * 60
+}
+
+fn default_grep_timeout_secs() -> u64 {
+    60
+}
+
+fn default_background_timeout_secs() -> u64 {
+    BACKGROUND_GIT_TIMEOUT.as_secs()
+}
+
+fn default_signing_timeout_secs() -> u64 {
+    10
+}
+
+fn default_max_upload_bytes() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_max_inline_blob_bytes() -> u64 {
+    1024 * 1024
+}
+
+fn default_pages_max_files() -> u64 {
+    10_000
+}
+
+/// Which characters are accepted in repository names. Either way, names are
+/// stored and looked up lowercased.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum RepoNamePolicy {
+    /// ASCII letters, digits, hyphens, underscores, and periods.
+    #[default]
+    Ascii,
+    /// Any printable Unicode (including emoji), NFC-normalized. On-disk
+    /// directory names are percent-encoded.
+    Unicode,
+}
+
+/// Command run by the background repository maintenance scheduler.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum MaintenanceTask {
+    /// `git gc`: repack, prune unreachable objects, and pack refs.
+    #[default]
+    Gc,
+    /// `git repack -a -d` and `git pack-refs`, without pruning.
+    Repack,
+}
+
+impl MaintenanceTask {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Gc => "gc",
+            Self::Repack => "repack",
+        }
+    }
+}
+
+/// How the data directory's filesystem is treated. See [`crate::netfs`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum StorageMode {
+    /// Use network-safe behavior when the data directory is on NFS or SMB.
+    #[default]
+    Auto,
+    /// Always assume a local disk.
+    Local,
+    /// Always use advisory locking, rollback journaling, and directory syncs.
+    Network,
+}
+
+/// Resource limits for one class of git subprocess. Unset limits fall back
+/// to the class default; `timeout = 0` disables the time limit.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessLimits {
+    /// Wall-clock seconds before the process is killed.
+    #[serde(
+        rename = "timeout",
+        alias = "timeout_secs",
+        default,
+        deserialize_with = "optional_secs"
+    )]
+    pub timeout_secs: Option<u64>,
+    /// CPU seconds (`RLIMIT_CPU`) before the kernel kills the process.
+    #[serde(
+        rename = "cpu_time",
+        alias = "cpu_secs",
+        default,
+        deserialize_with = "optional_secs"
+    )]
+    pub cpu_secs: Option<u64>,
+    /// Address space (`RLIMIT_AS`) in bytes. Git maps pack files into
+    /// memory, so leave room for the largest repository's packs.
+    #[serde(default, deserialize_with = "optional_size")]
+    pub memory_bytes: Option<u64>,
+    /// cgroup v2 directory (e.g. `/sys/fs/cgroup/cutman/archive`) the
+    /// process joins before exec, for limits rlimits cannot express.
+    #[serde(default)]
+    pub cgroup: Option<PathBuf>,
+}
+
+impl ProcessLimits {
+    /// The configured time limit, or `default` when none is set.
+    #[must_use]
+    pub fn timeout(&self, default: Option<Duration>) -> Option<Duration> {
+        match self.timeout_secs {
+            Some(0) => None,
+            Some(secs) => Some(Duration::from_secs(secs)),
+            None => default,
+        }
+    }
+
+    /// These limits with the time limit settled: the configured one, or
+    /// `default` when none is set.
+    #[must_use]
+    pub fn or_timeout(&self, default: Option<Duration>) -> Self {
+        Self {
+            timeout_secs: Some(self.timeout(default).map_or(0, |t| t.as_secs())),
+            ..self.clone()
+        }
+    }
+}
+
+/// Subprocess limits per route class (`[limits.<class>]` in the config).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubprocessLimits {
+    /// `git-upload-pack` for clones and fetches over HTTP and SSH.
+    #[serde(default)]
+    pub fetch: ProcessLimits,
+    /// `git-receive-pack` for pushes over HTTP and SSH.
+    #[serde(default)]
+    pub push: ProcessLimits,
+    /// `git archive` for archive downloads.
+    #[serde(default)]
+    pub archive: ProcessLimits,
+    /// `git grep` for content searches.
+    #[serde(default)]
+    pub search: ProcessLimits,
+    /// Background `gc`/`repack` and post-push optimization.
+    #[serde(default)]
+    pub maintenance: ProcessLimits,
+    /// `git fetch` for pull mirror syncs.
+    #[serde(default)]
+    pub mirror: ProcessLimits,
+    /// `git push` and `git ls-remote` for scheduled exports.
+    #[serde(default)]
+    pub export: ProcessLimits,
+}
+
+/// Capability groups that can be switched off (`[features]` in the config).
+/// Everything is on unless set to `false`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureFlags {
+    /// Content API writes: file commits, uploads, and ref changes.
+    #[serde(default = "default_true")]
+    pub content_mutations: bool,
+    /// Archive downloads.
+    #[serde(default = "default_true")]
+    pub archive: bool,
+    /// The admin API under `/api/v1/admin`.
+    #[serde(default = "default_true")]
+    pub admin_api: bool,
+    /// The Git LFS API and LFS usage reports.
+    #[serde(default = "default_true")]
+    pub lfs: bool,
+}
+
+/// A capability group that [`FeatureFlags`] can switch off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    ContentMutations,
+    Archive,
+    AdminApi,
+    Lfs,
+}
+
+impl Feature {
+    #[must_use]
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::ContentMutations => "Content API writes are",
+            Self::Archive => "Archive downloads are",
+            Self::AdminApi => "The admin API is",
+            Self::Lfs => "Git LFS is",
+        }
+    }
+}
+
+impl FeatureFlags {
+    #[must_use]
+    pub fn enabled(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::ContentMutations => self.content_mutations,
+            Feature::Archive => self.archive,
+            Feature::AdminApi => self.admin_api,
+            Feature::Lfs => self.lfs,
+        }
+    }
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self {
+            content_mutations: true,
+            archive: true,
+            admin_api: true,
+            lfs: true,
+        }
+    }
+}
+
+/// Shown to clients that use a legacy token when `[legacy_tokens]` sets no
+/// notice.
+const DEFAULT_LEGACY_TOKEN_NOTICE: &str =
+    "This token uses a retired format. Reissue it or create a new token.";
+
+/// How tokens in the legacy `cutman_<lookup>_<secret>` format are treated
+/// while their users move to reissued ones (`[legacy_tokens]` in the
+/// config).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LegacyTokens {
+    /// Whether legacy tokens still authenticate. They are flagged either way.
+    #[serde(default = "default_true")]
+    pub accept: bool,
+    /// Announcement shown to clients that use a legacy token, such as the
+    /// date they stop working.
+    #[serde(default)]
+    pub notice: Option<String>,
+}
+
+impl LegacyTokens {
+    #[must_use]
+    pub fn notice(&self) -> &str {
+        self.notice
+            .as_deref()
+            .unwrap_or(DEFAULT_LEGACY_TOKEN_NOTICE)
+    }
+}
+
+impl Default for LegacyTokens {
+    fn default() -> Self {
+        Self {
+            accept: true,
+            notice: None,
+        }
+    }
+}
+
+/// S3-compatible bucket for LFS objects and registry blobs (`[lfs_s3]` in
+/// the config), instead of `lfs/` in the data directory.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct S3StorageConfig {
+    /// Endpoint URL, e.g. `https://s3.us-east-1.amazonaws.com` or
+    /// `http://minio:9000`.
+    pub endpoint: String,
+    pub bucket: String,
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    /// Key prefix for every object, e.g. `cutman/lfs`.
+    #[serde(default)]
+    pub prefix: String,
+    /// Defaults to `AWS_ACCESS_KEY_ID`.
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+    /// Defaults to `AWS_SECRET_ACCESS_KEY`.
+    #[serde(default)]
+    pub secret_access_key: Option<String>,
+    /// Address the bucket as `{endpoint}/{bucket}` rather than
+    /// `{bucket}.{endpoint host}`, as most self-hosted stores expect.
+    #[serde(default = "default_true")]
+    pub path_style: bool,
+    /// Hand LFS clients presigned bucket URLs so objects skip the server.
+    /// Turn off when clients can't reach the endpoint.
+    #[serde(default = "default_true")]
+    pub presigned_urls: bool,
+}
+
+impl std::fmt::Debug for S3StorageConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3StorageConfig")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("prefix", &self.prefix)
+            .field("access_key_id", &self.access_key_id)
+            .field(
+                "secret_access_key",
+                &self.secret_access_key.as_ref().map(|_| "<redacted>"),
+            )
+            .field("path_style", &self.path_style)
+            .field("presigned_urls", &self.presigned_urls)
+            .finish()
+    }
+}
+
+/// External policy service that decides permission checks (`[authz]` in
+/// the config), e.g. an Open Policy Agent rule at
+/// `http://opa:8181/v1/data/cutman/allow`.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthzConfig {
+    /// URL each check is POSTed to.
+    pub url: String,
+    /// Bearer token sent with each check.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// How long to wait for an answer before falling back to grants.
+    #[serde(
+        rename = "timeout",
+        alias = "timeout_secs",
+        default = "default_authz_timeout_secs",
+        deserialize_with = "secs"
+    )]
+    pub timeout_secs: u64,
+    /// How long answers are reused. 0 asks on every check.
+    #[serde(
+        rename = "cache_ttl",
+        alias = "cache_secs",
+        default = "default_authz_cache_secs",
+        deserialize_with = "secs"
+    )]
+    pub cache_secs: u64,
+}
+
+impl std::fmt::Debug for AuthzConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthzConfig")
+            .field("url", &self.url)
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .field("timeout_secs", &self.timeout_secs)
+            .field("cache_secs", &self.cache_secs)
+            .finish()
+    }
+}
+
+/// HTTPS certificates requested from an ACME certificate authority such as
+/// Let's Encrypt (`[acme]` in the config), kept in `acme/` under the data
+/// directory and renewed before they expire. Needs a build with the `acme`
+/// feature.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AcmeConfig {
+    /// Names the certificate covers. Each must resolve to this server, which
+    /// proves control of it by answering an HTTP-01 challenge.
+    pub domains: Vec<String>,
+    /// Address the CA sends expiry and policy notices to.
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Directory URL of the CA. Configuring `[acme]` agrees to its terms of
+    /// service.
+    #[serde(default = "default_acme_directory")]
+    pub directory: String,
+    /// Port HTTP-01 challenges are answered on. The CA connects to port 80,
+    /// so change it only when something forwards that port here.
+    #[serde(default = "default_acme_http_port")]
+    pub http_port: u16,
+}
+
+/// Export of request traces to an OpenTelemetry collector over OTLP/HTTP
+/// (`[tracing]` in the config). Needs a build with the `otel` feature.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TracingConfig {
+    /// Collector URL spans are POSTed to, e.g. `http://collector:4318`.
+    /// `/v1/traces` is added when the URL has no path.
+    pub otlp_endpoint: String,
+    /// Headers sent with each export, such as a vendor API key.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// `service.name` the spans are reported under, to tell servers in a
+    /// fleet apart.
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// Share of traces started here that are exported, from 0 to 1.
+    /// Requests carrying a `traceparent` header follow the caller's choice.
+    #[serde(default = "default_sample_ratio")]
+    pub sample_ratio: f64,
+}
+
+impl TracingConfig {
+    /// The URL spans are sent to.
+    #[must_use]
+    pub fn traces_url(&self) -> String {
+        let endpoint = self.otlp_endpoint.trim_end_matches('/');
+        let authority = endpoint
+            .split_once("://")
+            .map_or(endpoint, |(_, rest)| rest);
+        if authority.contains('/') {
+            endpoint.to_string()
+        } else {
+            format!("{endpoint}/v1/traces")
+        }
+    }
+}
+
+impl std::fmt::Debug for TracingConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let headers: Vec<_> = self.headers.keys().collect();
+        f.debug_struct("TracingConfig")
+            .field("otlp_endpoint", &self.otlp_endpoint)
+            .field("headers", &headers)
+            .field("service_name", &self.service_name)
+            .field("sample_ratio", &self.sample_ratio)
+            .finish()
+    }
+}
+
+/// A kind of server event published to `[events]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum EventKind {
+    /// A push over HTTP or SSH landed.
+    #[serde(rename = "push")]
+    Push,
+    #[serde(rename = "repo.created")]
+    RepoCreated,
+    #[serde(rename = "repo.deleted")]
+    RepoDeleted,
+    /// An LFS object finished uploading.
+    #[serde(rename = "lfs.uploaded")]
+    LfsUploaded,
+    /// An audit event was recorded.
+    #[serde(rename = "audit")]
+    Audit,
+}
+
+impl EventKind {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Push => "push",
+            Self::RepoCreated => "repo.created",
+            Self::RepoDeleted => "repo.deleted",
+            Self::LfsUploaded => "lfs.uploaded",
+            Self::Audit => "audit",
+        }
+    }
+}
+
+/// Publishing of server events to a message bus (`[events]` in the
+/// config), either NATS or Kafka. Needs a build with the `nats` or `kafka`
+/// feature.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventsConfig {
+    /// NATS server, e.g. `nats://nats:4222`; `tls://` connects over TLS.
+    #[serde(default)]
+    pub nats_url: Option<String>,
+    /// Token the NATS server expects, if any.
+    #[serde(default)]
+    pub nats_token: Option<String>,
+    /// Events go to `{prefix}.{kind}`, e.g. `cutman.repo.created`.
+    #[serde(default = "default_nats_subject_prefix")]
+    pub nats_subject_prefix: String,
+    /// Kafka bootstrap brokers as `host:port`. Connections are plaintext.
+    #[serde(default)]
+    pub kafka_brokers: Vec<String>,
+    /// Topic every event goes to, keyed by repository (or namespace) so
+    /// each repository's events stay in order.
+    #[serde(default = "default_kafka_topic")]
+    pub kafka_topic: String,
+    /// Events held while the bus is slow or down. Further events are
+    /// dropped.
+    #[serde(default = "default_events_queue_size")]
+    pub queue_size: usize,
+    /// Kinds to publish; every kind when empty.
+    #[serde(default)]
+    pub kinds: Vec<EventKind>,
+}
+
+impl EventsConfig {
+    /// Where events go, for logs.
+    #[must_use]
+    pub fn destination(&self) -> String {
+        match &self.nats_url {
+            Some(url) => format!("NATS {url}"),
+            None => format!(
+                "Kafka topic {} on {}",
+                self.kafka_topic,
+                self.kafka_brokers.join(",")
+            ),
+        }
+    }
+}
+
+impl std::fmt::Debug for EventsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventsConfig")
+            .field("nats_url", &self.nats_url)
+            .field(
+                "nats_token",
+                &self.nats_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("nats_subject_prefix", &self.nats_subject_prefix)
+            .field("kafka_brokers", &self.kafka_brokers)
+            .field("kafka_topic", &self.kafka_topic)
+            .field("queue_size", &self.queue_size)
+            .field("kinds", &self.kinds)
+            .finish()
+    }
+}
+
+/// Checks that flag unusual pushes as audit events (`[push_alerts]` in the
+/// config), optionally posting each to a webhook.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PushAlertsConfig {
+    /// Flag a force push that drops at least this many commits from a
+    /// branch. 0 turns the check off.
+    #[serde(default = "default_rewritten_commits")]
+    pub rewritten_commits: u32,
+    /// Flag a push that deletes at least this many branches. 0 turns the
+    /// check off.
+    #[serde(default = "default_deleted_branches")]
+    pub deleted_branches: u32,
+    /// Flag a token's first push from an IP address it hasn't pushed from
+    /// before.
+    #[serde(default = "default_true")]
+    pub new_address: bool,
+    /// Header a reverse proxy puts the client's address in, such as
+    /// `X-Forwarded-For`; its last entry is used. The connection's address
+    /// is used when unset.
+    #[serde(default)]
+    pub client_ip_header: Option<String>,
+    /// URL each alert is POSTed to as JSON.
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+    /// Signs each webhook body with HMAC-SHA256, sent as
+    /// `X-Cutman-Signature: sha256=<hex>`.
+    #[serde(default)]
+    pub webhook_secret: Option<String>,
+}
+
+impl std::fmt::Debug for PushAlertsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PushAlertsConfig")
+            .field("rewritten_commits", &self.rewritten_commits)
+            .field("deleted_branches", &self.deleted_branches)
+            .field("new_address", &self.new_address)
+            .field("client_ip_header", &self.client_ip_header)
+            .field("webhook_url", &self.webhook_url)
+            .field(
+                "webhook_secret",
+                &self.webhook_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+/// Configuration for the server, loadable from TOML file. Sizes and
+/// durations may be written with units (`max_pack_bytes = "2GB"`,
+/// `timeout = "5m"`). Durations are named without a unit; their older
+/// `_secs` names are still accepted.
 #[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ServerConfig {
-    #[serde(default = "default_host")]
-    pub host: String,
+    /// Addresses to bind, as one IP or a list (e.g. `["0.0.0.0", "::"]` for
+    /// dual-stack). IPv6 addresses may be written with or without brackets.
+    #[serde(
+        rename = "host",
+        default = "default_hosts",
+        deserialize_with = "one_or_many"
+    )]
+    pub hosts: Vec<String>,
     #[serde(default = "default_port")]
     pub port: u16,
+    /// PEM certificate chain to serve HTTPS with (requires the `tls`
+    /// feature). Plain HTTP is served when unset.
+    #[serde(default)]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key for `tls_cert`.
+    #[serde(default)]
+    pub tls_key: Option<PathBuf>,
+    /// Seconds between checks of `tls_cert` and `tls_key` for changes, so
+    /// a renewed certificate is picked up without a restart. Not checked
+    /// when unset.
+    #[serde(
+        rename = "tls_reload_interval",
+        alias = "tls_reload_secs",
+        default,
+        deserialize_with = "optional_secs"
+    )]
+    pub tls_reload_secs: Option<u64>,
     #[serde(default = "default_data_dir")]
     pub data_dir: PathBuf,
+    /// Further directories namespaces can be kept in, by name, e.g.
+    /// `{ ssd = "/mnt/fast/cutman", hdd = "/mnt/bulk/cutman" }`. Namespaces
+    /// stay in the data directory until `cutman admin namespace move` puts
+    /// them on one.
+    #[serde(default)]
+    pub storage_roots: HashMap<String, PathBuf>,
     /// Public base URL for external access (e.g., "https://git.example.com").
     /// Used for generating LFS action URLs. If not set, URLs are derived from request headers.
     #[serde(default)]
     pub public_base_url: Option<String>,
+    /// Disable all anonymous access. When enabled, repositories marked public
+    /// still require a valid token for content and git reads. This is the
+    /// setting at startup; admins can switch it with `PUT /admin/private-mode`.
+    #[serde(default)]
+    pub private_mode: bool,
+    /// Naming policy for repositories: "ascii" (default) or "unicode".
+    #[serde(default)]
+    pub repo_name_policy: RepoNamePolicy,
+    /// Port for the built-in SSH git server. SSH is disabled when unset.
+    #[serde(default)]
+    pub ssh_port: Option<u16>,
+    /// Seconds an SSH connection may sit idle before it is closed.
+    #[serde(
+        rename = "ssh_idle_timeout",
+        alias = "ssh_idle_timeout_secs",
+        default = "default_ssh_idle_timeout_secs",
+        deserialize_with = "secs"
+    )]
+    pub ssh_idle_timeout_secs: u64,
+    /// SSH host key path. Defaults to `ssh_host_ed25519_key` in the data
+    /// directory, generated on first start.
+    #[serde(default)]
+    pub ssh_host_key: Option<PathBuf>,
+    /// Seconds to wait on SIGTERM or SIGINT for open connections and
+    /// running git operations to finish before exiting anyway.
+    #[serde(
+        rename = "shutdown_timeout",
+        alias = "shutdown_timeout_secs",
+        default = "default_shutdown_timeout_secs",
+        deserialize_with = "secs"
+    )]
+    pub shutdown_timeout_secs: u64,
+    /// Port for the gRPC content service (requires the `grpc` feature).
+    /// gRPC is disabled when unset.
+    #[serde(default)]
+    pub grpc_port: Option<u16>,
+    /// Directory for receive hook shims and the `pre-receive.d/` and
+    /// `post-receive.d/` script directories. Defaults to `hooks` in the data
+    /// directory.
+    #[serde(default)]
+    pub hooks_dir: Option<PathBuf>,
+    /// Built-in Rust hooks to run on every push, e.g. `["protect-default-branch"]`.
+    #[serde(default)]
+    pub builtin_hooks: Vec<String>,
+    /// Filesystem handling for the data directory: "auto" (default), "local",
+    /// or "network" for NFS/SMB mounts.
+    #[serde(default)]
+    pub storage_mode: StorageMode,
+    /// Seconds between background maintenance passes over repositories
+    /// pushed to since their last maintenance. Disabled when unset.
+    #[serde(
+        rename = "maintenance_interval",
+        alias = "maintenance_interval_secs",
+        default,
+        deserialize_with = "optional_secs"
+    )]
+    pub maintenance_interval_secs: Option<u64>,
+    /// Maintenance command: "gc" (default) or "repack".
+    #[serde(default)]
+    pub maintenance_task: MaintenanceTask,
+    /// Refresh commit-graph files and reachability bitmaps after each push.
+    #[serde(default = "default_true")]
+    pub optimize_after_push: bool,
+    /// Write a bundle of each repository's branches and tags during
+    /// maintenance and advertise it to cloning clients through bundle-uri.
+    #[serde(default)]
+    pub bundle_uri: bool,
+    /// Serve a container registry (a subset of the OCI distribution API)
+    /// at `/v2/`, storing images alongside each repository's LFS objects.
+    #[serde(default)]
+    pub registry: bool,
+    /// Keep LFS objects and registry blobs in an S3-compatible bucket.
+    /// Uploads are still staged in the data directory.
+    #[serde(default)]
+    pub lfs_s3: Option<S3StorageConfig>,
+    /// Seconds after which a stored LFS object is re-hashed against its
+    /// OID. Objects that no longer match are reported as corrupted.
+    /// Disabled when unset.
+    #[serde(
+        rename = "lfs_verify_interval",
+        alias = "lfs_verify_interval_secs",
+        default,
+        deserialize_with = "optional_secs"
+    )]
+    pub lfs_verify_interval_secs: Option<u64>,
+    /// Bytes per second a single LFS upload request may send, e.g.
+    /// `"10MB"`. Unlimited when unset.
+    #[serde(default, deserialize_with = "optional_size")]
+    pub lfs_upload_bytes_per_sec: Option<u64>,
+    /// Bytes per second a single LFS download request may receive.
+    /// Unlimited when unset.
+    #[serde(default, deserialize_with = "optional_size")]
+    pub lfs_download_bytes_per_sec: Option<u64>,
+    /// Delegate permission checks to an external policy service, falling
+    /// back to grants when it has no answer.
+    #[serde(default)]
+    pub authz: Option<AuthzConfig>,
+    /// Record unusual pushes as audit events, and post them to a webhook.
+    #[serde(default)]
+    pub push_alerts: Option<PushAlertsConfig>,
+    /// Export request traces to an OpenTelemetry collector.
+    #[serde(default)]
+    pub tracing: Option<TracingConfig>,
+    /// Publish pushes, repository changes, LFS uploads, and audit events
+    /// to NATS or Kafka.
+    #[serde(default)]
+    pub events: Option<EventsConfig>,
+    /// Serve HTTPS with certificates obtained and renewed over ACME, in
+    /// place of `tls_cert` and `tls_key`.
+    #[serde(default)]
+    pub acme: Option<AcmeConfig>,
+    /// Serve each repository's default branch read-only over WebDAV at
+    /// `/dav/{namespace}/{repo}/`.
+    #[serde(default)]
+    pub webdav: bool,
+    /// Publish static sites from a branch of each repository that enables
+    /// them, at `/{namespace}/{repo}/pages/`.
+    #[serde(default)]
+    pub pages: bool,
+    /// Also serve sites at `{namespace}.{pages_domain}/{repo}/`, each
+    /// namespace on its own origin. Needs a wildcard DNS record.
+    #[serde(default)]
+    pub pages_domain: Option<String>,
+    /// Largest site a build may publish, in bytes.
+    #[serde(default = "default_pages_max_bytes", deserialize_with = "size")]
+    pub pages_max_bytes: u64,
+    /// Most files a build may publish.
+    #[serde(default = "default_pages_max_files")]
+    pub pages_max_files: u64,
+    /// Free bytes in the data directory below which pushes and LFS uploads
+    /// are rejected. Disabled when unset.
+    #[serde(default, deserialize_with = "optional_size")]
+    pub min_free_bytes: Option<u64>,
+    /// Free bytes below which low-disk warnings are logged.
+    #[serde(default, deserialize_with = "optional_size")]
+    pub warn_free_bytes: Option<u64>,
+    /// Seconds between free-space checks.
+    #[serde(
+        rename = "disk_check_interval",
+        alias = "disk_check_interval_secs",
+        default = "default_disk_check_interval_secs",
+        deserialize_with = "secs"
+    )]
+    pub disk_check_interval_secs: u64,
+    /// Age in seconds after which partial uploads, interrupted push packs,
+    /// and stale lock files are removed. Set to 0 to disable the sweep.
+    #[serde(
+        rename = "temp_max_age",
+        alias = "temp_max_age_secs",
+        default = "default_temp_max_age_secs",
+        deserialize_with = "secs"
+    )]
+    pub temp_max_age_secs: u64,
+    /// Seconds between temp file sweeps. One also runs at startup.
+    #[serde(
+        rename = "temp_sweep_interval",
+        alias = "temp_sweep_interval_secs",
+        default = "default_temp_sweep_interval_secs",
+        deserialize_with = "secs"
+    )]
+    pub temp_sweep_interval_secs: u64,
+    /// Seconds a `git archive` download may run, unless `[limits.archive]`
+    /// sets a timeout. 0 disables the limit.
+    #[serde(
+        rename = "archive_timeout",
+        default = "default_git_timeout_secs",
+        deserialize_with = "secs"
+    )]
+    pub archive_timeout_secs: u64,
+    /// Seconds git may take to advertise refs to a cloning or pushing
+    /// client, unless `[limits.fetch]` or `[limits.push]` sets a timeout.
+    /// 0 disables the limit.
+    #[serde(
+        rename = "git_command_timeout",
+        default = "default_git_timeout_secs",
+        deserialize_with = "secs"
+    )]
+    pub git_command_timeout_secs: u64,
+    /// Seconds a content search may run, unless `[limits.search]` sets a
+    /// timeout. 0 disables the limit.
+    #[serde(
+        rename = "grep_timeout",
+        default = "default_grep_timeout_secs",
+        deserialize_with = "secs"
+    )]
+    pub grep_timeout_secs: u64,
+    /// Seconds each git command of a maintenance pass may run, unless
+    /// `[limits.maintenance]` sets a timeout. 0 disables the limit.
+    #[serde(
+        rename = "maintenance_timeout",
+        default = "default_background_timeout_secs",
+        deserialize_with = "secs"
+    )]
+    pub maintenance_timeout_secs: u64,
+    /// Seconds each git command of a mirror sync or export may run, unless
+    /// `[limits.mirror]` or `[limits.export]` sets a timeout. 0 disables
+    /// the limit.
+    #[serde(
+        rename = "mirror_timeout",
+        default = "default_background_timeout_secs",
+        deserialize_with = "secs"
+    )]
+    pub mirror_timeout_secs: u64,
+    /// Seconds each git command moving objects into or out of a shared
+    /// object store may run, unless `[limits.maintenance]` sets a timeout.
+    /// 0 disables the limit.
+    #[serde(
+        rename = "shared_objects_timeout",
+        default = "default_background_timeout_secs",
+        deserialize_with = "secs"
+    )]
+    pub shared_objects_timeout_secs: u64,
+    /// Seconds a `gpg`, `gpgv`, or `ssh-keygen` run checking a signature or
+    /// key may take. 0 disables the limit.
+    #[serde(
+        rename = "signing_timeout",
+        default = "default_signing_timeout_secs",
+        deserialize_with = "secs"
+    )]
+    pub signing_timeout_secs: u64,
+    /// CPU, memory, and time limits for git subprocesses.
+    #[serde(default)]
+    pub limits: SubprocessLimits,
+    /// Concurrent clones, fetches, archives, and searches shared fairly
+    /// between namespaces. Unlimited and unqueued when unset.
+    #[serde(default)]
+    pub fair_queue_slots: Option<usize>,
+    /// Relative share of `fair_queue_slots` per namespace name, e.g.
+    /// `{ ci = 1, release = 4 }`. Unlisted namespaces weigh 1.
+    #[serde(default)]
+    pub namespace_weights: HashMap<String, u32>,
+    /// Concurrent clones and fetches of any one repository; more wait
+    /// their turn. Unlimited when unset.
+    #[serde(default)]
+    pub clone_slots_per_repo: Option<usize>,
+    /// Deepest shallow clone or fetch (`--depth`) accepted. Fetches by
+    /// date or by excluded ref are refused while this is set.
+    #[serde(default)]
+    pub max_clone_depth: Option<u32>,
+    /// Largest pack a single push may send, in bytes. Git stops reading
+    /// the push once it passes this. Unlimited when unset.
+    #[serde(default, deserialize_with = "optional_size")]
+    pub max_pack_bytes: Option<u64>,
+    /// Largest single file a push or the content API may add, in bytes.
+    /// Unlimited when unset.
+    #[serde(default, deserialize_with = "optional_size")]
+    pub max_blob_bytes: Option<u64>,
+    /// Largest file the content API accepts in a multipart or raw upload.
+    #[serde(default = "default_max_upload_bytes", deserialize_with = "size")]
+    pub max_upload_bytes: u64,
+    /// Largest file the content API serves raw. Unlimited when unset.
+    #[serde(default, deserialize_with = "optional_size")]
+    pub max_raw_blob_bytes: Option<u64>,
+    /// Most of a file the content API returns inline in JSON; longer files
+    /// are cut short and marked truncated.
+    #[serde(default = "default_max_inline_blob_bytes", deserialize_with = "size")]
+    pub max_inline_blob_bytes: u64,
+    /// Link printed after a push creates a branch, with `{namespace}`,
+    /// `{repo}`, `{repo_id}`, `{base}`, and `{branch}` filled in. Defaults to
+    /// the compare API under the public base URL; set to "" to disable.
+    #[serde(default)]
+    pub pull_request_url: Option<String>,
+    /// Text shown to git clients on pushes and on clones and fetches that
+    /// download objects, and returned by `GET /api/v1/capabilities`. For
+    /// maintenance announcements and acceptable-use notices.
+    #[serde(default)]
+    pub banner: Option<String>,
+    /// Capability groups to switch off without rebuilding.
+    #[serde(default)]
+    pub features: FeatureFlags,
+    /// Acceptance of tokens in the legacy format, and the notice their
+    /// users see.
+    #[serde(default)]
+    pub legacy_tokens: LegacyTokens,
 }
 
 /// CLI overrides that can be applied on top of a config file.
 #[derive(Debug, Default)]
 pub struct ServerConfigOverrides {
-    pub host: Option<String>,
+    pub hosts: Option<Vec<String>>,
     pub port: Option<u16>,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
     pub data_dir: Option<PathBuf>,
     pub public_base_url: Option<String>,
+    pub private_mode: Option<bool>,
+    pub repo_name_policy: Option<RepoNamePolicy>,
+    pub ssh_port: Option<u16>,
+    pub ssh_host_key: Option<PathBuf>,
+    pub grpc_port: Option<u16>,
+    pub hooks_dir: Option<PathBuf>,
+    pub builtin_hooks: Option<Vec<String>>,
+    pub storage_mode: Option<StorageMode>,
+    pub maintenance_interval_secs: Option<u64>,
+    pub maintenance_task: Option<MaintenanceTask>,
+    pub optimize_after_push: Option<bool>,
+    pub bundle_uri: Option<bool>,
+    pub registry: Option<bool>,
+    pub webdav: Option<bool>,
+    pub pages: Option<bool>,
+    pub pages_domain: Option<String>,
+    pub pages_max_bytes: Option<u64>,
+    pub min_free_bytes: Option<u64>,
+    pub warn_free_bytes: Option<u64>,
+    pub temp_max_age_secs: Option<u64>,
+    pub fair_queue_slots: Option<usize>,
+    pub clone_slots_per_repo: Option<usize>,
+    pub max_clone_depth: Option<u32>,
+    pub max_pack_bytes: Option<u64>,
+    pub max_blob_bytes: Option<u64>,
+    /// Sets `[limits.fetch] timeout`, the upload-pack wall time.
+    pub fetch_timeout_secs: Option<u64>,
 }
 
 impl ServerConfig {
     /// Default config file search paths.
     const SEARCH_PATHS: &'static [&'static str] = &["./server.toml", "/etc/cutman/server.toml"];
 
-    pub fn socket_addr(&self) -> Result<SocketAddr, std::net::AddrParseError> {
-        format!("{}:{}", self.host, self.port).parse()
+    /// Addresses for the HTTP server, one per configured host.
+    pub fn socket_addrs(&self) -> Result<Vec<SocketAddr>, AddrParseError> {
+        self.addrs_with_port(self.port)
+    }
+
+    /// Addresses for the SSH git server, if enabled.
+    pub fn ssh_socket_addrs(&self) -> Option<Result<Vec<SocketAddr>, AddrParseError>> {
+        self.ssh_port.map(|port| self.addrs_with_port(port))
+    }
+
+    /// Addresses for the gRPC content service, if enabled.
+    pub fn grpc_socket_addrs(&self) -> Option<Result<Vec<SocketAddr>, AddrParseError>> {
+        self.grpc_port.map(|port| self.addrs_with_port(port))
+    }
+
+    /// Addresses ACME HTTP-01 challenges are answered on, if enabled.
+    pub fn acme_socket_addrs(&self) -> Option<Result<Vec<SocketAddr>, AddrParseError>> {
+        self.acme
+            .as_ref()
+            .map(|acme| self.addrs_with_port(acme.http_port))
+    }
+
+    /// Where ACME certificates and the account key are kept.
+    #[must_use]
+    pub fn acme_dir(&self) -> PathBuf {
+        self.data_dir.join("acme")
+    }
+
+    /// Whether `cutman serve` terminates TLS itself.
+    #[must_use]
+    pub fn serves_tls(&self) -> bool {
+        self.tls_cert.is_some() || self.acme.is_some()
+    }
+
+    fn addrs_with_port(&self, port: u16) -> Result<Vec<SocketAddr>, AddrParseError> {
+        self.hosts
+            .iter()
+            .map(|host| {
+                let host = host.trim();
+                let host = host
+                    .strip_prefix('[')
+                    .and_then(|h| h.strip_suffix(']'))
+                    .unwrap_or(host);
+                Ok(SocketAddr::new(host.parse::<IpAddr>()?, port))
+            })
+            .collect()
+    }
+
+    #[must_use]
+    pub fn ssh_host_key_path(&self) -> PathBuf {
+        self.ssh_host_key
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join("ssh_host_ed25519_key"))
+    }
+
+    #[must_use]
+    pub fn hooks_dir_path(&self) -> PathBuf {
+        self.hooks_dir
+            .clone()
+            .unwrap_or_else(|| self.data_dir.join("hooks"))
     }
 
     #[must_use]
@@ -53,24 +1153,374 @@ impl ServerConfig {
         self.data_dir.join("cutman.db")
     }
 
-    /// Load config from a specific file path.
-    pub fn load(path: &Path) -> anyhow::Result<Self> {
-        let content = fs::read_to_string(path)?;
-        let config: ServerConfig = toml::from_str(&content)?;
-        Ok(config)
+    /// The `archive_timeout` limit, or `None` when it's disabled.
+    #[must_use]
+    pub fn archive_timeout(&self) -> Option<Duration> {
+        time_limit(self.archive_timeout_secs)
     }
 
-    /// Search for config file in default locations and load if found.
-    pub fn load_from_search_paths() -> Option<Self> {
-        for path_str in Self::SEARCH_PATHS {
-            let path = Path::new(path_str);
-            if path.exists() {
-                if let Ok(config) = Self::load(path) {
-                    return Some(config);
+    /// The `git_command_timeout` limit, or `None` when it's disabled.
+    #[must_use]
+    pub fn git_command_timeout(&self) -> Option<Duration> {
+        time_limit(self.git_command_timeout_secs)
+    }
+
+    /// The `grep_timeout` limit, or `None` when it's disabled.
+    #[must_use]
+    pub fn grep_timeout(&self) -> Option<Duration> {
+        time_limit(self.grep_timeout_secs)
+    }
+
+    /// The `maintenance_timeout` limit, or `None` when it's disabled.
+    #[must_use]
+    pub fn maintenance_timeout(&self) -> Option<Duration> {
+        time_limit(self.maintenance_timeout_secs)
+    }
+
+    /// The `mirror_timeout` limit, or `None` when it's disabled.
+    #[must_use]
+    pub fn mirror_timeout(&self) -> Option<Duration> {
+        time_limit(self.mirror_timeout_secs)
+    }
+
+    /// The `shared_objects_timeout` limit, or `None` when it's disabled.
+    #[must_use]
+    pub fn shared_objects_timeout(&self) -> Option<Duration> {
+        time_limit(self.shared_objects_timeout_secs)
+    }
+
+    /// The `signing_timeout` limit, or `None` when it's disabled.
+    #[must_use]
+    pub fn signing_timeout(&self) -> Option<Duration> {
+        time_limit(self.signing_timeout_secs)
+    }
+
+    /// Values that parse but can't work, each with the dotted key it
+    /// belongs to.
+    pub fn problems(&self) -> Vec<(String, String)> {
+        let mut problems = Vec::new();
+        let mut problem = |key: &str, message: String| problems.push((key.to_string(), message));
+
+        if self.hosts.is_empty() {
+            problem("host", "at least one address is required".to_string());
+        }
+        for host in &self.hosts {
+            let bare = host.trim().trim_start_matches('[').trim_end_matches(']');
+            if bare.parse::<IpAddr>().is_err() {
+                problem(
+                    "host",
+                    format!("`{host}` is not an IP address (hostnames aren't accepted)"),
+                );
+            }
+        }
+        if self.port == 0 {
+            problem("port", "must be between 1 and 65535".to_string());
+        }
+        match self.ssh_port {
+            Some(0) => problem("ssh_port", "must be between 1 and 65535".to_string()),
+            Some(port) if port == self.port => {
+                problem("ssh_port", format!("{port} is already the HTTP port"));
+            }
+            _ => {}
+        }
+        match self.grpc_port {
+            Some(0) => problem("grpc_port", "must be between 1 and 65535".to_string()),
+            Some(port) if port == self.port => {
+                problem("grpc_port", format!("{port} is already the HTTP port"));
+            }
+            Some(port) if Some(port) == self.ssh_port => {
+                problem("grpc_port", format!("{port} is already the SSH port"));
+            }
+            _ => {}
+        }
+        if let Some(url) = &self.public_base_url
+            && !is_http_url(url)
+        {
+            problem(
+                "public_base_url",
+                format!("`{url}` is not an http:// or https:// URL"),
+            );
+        }
+        for name in &self.builtin_hooks {
+            if !BUILTIN_HOOKS.contains(&name.as_str()) {
+                problem(
+                    "builtin_hooks",
+                    format!(
+                        "unknown hook `{name}` (available: {})",
+                        BUILTIN_HOOKS.join(", ")
+                    ),
+                );
+            }
+        }
+        if let Some(s3) = &self.lfs_s3 {
+            if !is_http_url(&s3.endpoint) {
+                problem(
+                    "lfs_s3.endpoint",
+                    format!("`{}` is not an http:// or https:// URL", s3.endpoint),
+                );
+            }
+            if s3.bucket.is_empty() {
+                problem("lfs_s3.bucket", "cannot be empty".to_string());
+            }
+        }
+        if self.lfs_upload_bytes_per_sec == Some(0) {
+            problem(
+                "lfs_upload_bytes_per_sec",
+                "must be greater than 0; leave it unset for no limit".to_string(),
+            );
+        }
+        if self.lfs_download_bytes_per_sec == Some(0) {
+            problem(
+                "lfs_download_bytes_per_sec",
+                "must be greater than 0; leave it unset for no limit".to_string(),
+            );
+        }
+        if let Some(authz) = &self.authz {
+            if !is_http_url(&authz.url) {
+                problem(
+                    "authz.url",
+                    format!("`{}` is not an http:// or https:// URL", authz.url),
+                );
+            }
+            if authz.timeout_secs == 0 {
+                problem("authz.timeout", "must be greater than 0".to_string());
+            }
+        }
+        if let Some(alerts) = &self.push_alerts {
+            if let Some(url) = alerts.webhook_url.as_ref().filter(|url| !is_http_url(url)) {
+                problem(
+                    "push_alerts.webhook_url",
+                    format!("`{url}` is not an http:// or https:// URL"),
+                );
+            }
+            if let Some(header) = alerts
+                .client_ip_header
+                .as_ref()
+                .filter(|h| axum::http::HeaderName::from_bytes(h.as_bytes()).is_err())
+            {
+                problem(
+                    "push_alerts.client_ip_header",
+                    format!("`{header}` is not a valid header name"),
+                );
+            }
+        }
+        if let Some(tracing) = &self.tracing {
+            if !is_http_url(&tracing.otlp_endpoint) {
+                problem(
+                    "tracing.otlp_endpoint",
+                    format!(
+                        "`{}` is not an http:// or https:// URL",
+                        tracing.otlp_endpoint
+                    ),
+                );
+            }
+            if !(0.0..=1.0).contains(&tracing.sample_ratio) {
+                problem(
+                    "tracing.sample_ratio",
+                    "must be between 0 and 1".to_string(),
+                );
+            }
+        }
+        if let Some(events) = &self.events {
+            match (&events.nats_url, events.kafka_brokers.is_empty()) {
+                (None, true) => problem("events", "set nats_url or kafka_brokers".to_string()),
+                (Some(_), false) => problem(
+                    "events",
+                    "set nats_url or kafka_brokers, not both".to_string(),
+                ),
+                _ => {}
+            }
+            if let Some(url) = events.nats_url.as_ref().filter(|url| !is_nats_url(url)) {
+                problem(
+                    "events.nats_url",
+                    format!("`{url}` is not a nats:// or tls:// URL"),
+                );
+            }
+            for broker in &events.kafka_brokers {
+                if broker
+                    .rsplit_once(':')
+                    .is_none_or(|(host, port)| host.is_empty() || port.parse::<u16>().is_err())
+                {
+                    problem(
+                        "events.kafka_brokers",
+                        format!("`{broker}` is not a host:port address"),
+                    );
                 }
             }
+            if events.queue_size == 0 {
+                problem("events.queue_size", "must be greater than 0".to_string());
+            }
+        }
+        if self.pages_max_bytes == 0 {
+            problem("pages_max_bytes", "must be greater than 0".to_string());
+        }
+        if let (Some(min), Some(warn)) = (self.min_free_bytes, self.warn_free_bytes)
+            && warn < min
+        {
+            problem(
+                "warn_free_bytes",
+                "must be at least min_free_bytes, or the warning never comes first".to_string(),
+            );
+        }
+        if self.disk_check_interval_secs == 0 {
+            problem("disk_check_interval", "must be greater than 0".to_string());
+        }
+        if self.ssh_idle_timeout_secs == 0 {
+            problem("ssh_idle_timeout", "must be greater than 0".to_string());
+        }
+        if self.max_upload_bytes == 0 {
+            problem("max_upload_bytes", "must be greater than 0".to_string());
+        }
+        if self.max_inline_blob_bytes == 0 {
+            problem(
+                "max_inline_blob_bytes",
+                "must be greater than 0".to_string(),
+            );
+        }
+        if self.temp_sweep_interval_secs == 0 {
+            problem("temp_sweep_interval", "must be greater than 0".to_string());
+        }
+        match (&self.tls_cert, &self.tls_key) {
+            (Some(_), None) => problem("tls_key", "must be set along with tls_cert".to_string()),
+            (None, Some(_)) => problem("tls_cert", "must be set along with tls_key".to_string()),
+            _ => {}
+        }
+        if self.tls_reload_secs == Some(0) {
+            problem(
+                "tls_reload_interval",
+                "must be greater than 0; leave it unset to disable reloading".to_string(),
+            );
+        } else if self.tls_reload_secs.is_some() && self.tls_cert.is_none() {
+            problem(
+                "tls_reload_interval",
+                "needs tls_cert and tls_key".to_string(),
+            );
+        }
+        if let Some(acme) = &self.acme {
+            if acme.domains.is_empty() {
+                problem("acme.domains", "must name at least one domain".to_string());
+            }
+            for domain in acme.domains.iter().filter(|d| !is_acme_domain(d)) {
+                problem(
+                    "acme.domains",
+                    format!(
+                        "`{domain}` is not a domain name HTTP-01 can prove; wildcards need DNS-01"
+                    ),
+                );
+            }
+            if acme
+                .email
+                .as_ref()
+                .is_some_and(|email| !email.contains('@') || email.contains(char::is_whitespace))
+            {
+                problem("acme.email", "is not an email address".to_string());
+            }
+            if !is_http_url(&acme.directory) {
+                problem(
+                    "acme.directory",
+                    format!("`{}` is not an http:// or https:// URL", acme.directory),
+                );
+            }
+            if acme.http_port == self.port {
+                problem(
+                    "acme.http_port",
+                    format!("must differ from port, which serves HTTPS ({})", self.port),
+                );
+            }
+            if self.tls_cert.is_some() {
+                problem(
+                    "acme",
+                    "cannot be combined with tls_cert; certificates are kept in the data directory"
+                        .to_string(),
+                );
+            }
+        }
+        if self.maintenance_interval_secs == Some(0) {
+            problem(
+                "maintenance_interval",
+                "must be greater than 0; leave it unset to disable maintenance".to_string(),
+            );
+        }
+        if self.lfs_verify_interval_secs == Some(0) {
+            problem(
+                "lfs_verify_interval",
+                "must be greater than 0; leave it unset to disable verification".to_string(),
+            );
+        }
+        if self.fair_queue_slots == Some(0) {
+            problem("fair_queue_slots", "must be greater than 0".to_string());
+        }
+        if self.clone_slots_per_repo == Some(0) {
+            problem("clone_slots_per_repo", "must be greater than 0".to_string());
+        }
+        let mut roots: Vec<_> = self.storage_roots.iter().collect();
+        roots.sort();
+        for (name, path) in roots {
+            let key = format!("storage_roots.{name}");
+            if name == DEFAULT_ROOT || !is_root_name(name) {
+                problem(
+                    &key,
+                    format!("use letters, digits, '-', and '_', and not \"{DEFAULT_ROOT}\""),
+                );
+            }
+            if *path == self.data_dir {
+                problem(
+                    &key,
+                    "is the data directory, which needs no root".to_string(),
+                );
+            }
+        }
+        if let Some(notice) = &self.legacy_tokens.notice
+            && (notice.trim().is_empty()
+                || !notice.chars().all(|c| c == ' ' || c.is_ascii_graphic()))
+        {
+            // It is sent as a response header.
+            problem(
+                "legacy_tokens.notice",
+                "must be one line of printable ASCII".to_string(),
+            );
+        }
+        let mut weights: Vec<_> = self.namespace_weights.iter().collect();
+        weights.sort();
+        for (namespace, weight) in weights {
+            if *weight == 0 {
+                problem(
+                    &format!("namespace_weights.{namespace}"),
+                    "must be greater than 0".to_string(),
+                );
+            }
         }
-        None
+
+        problems
+    }
+
+    /// Load config from a specific file path, failing with every problem
+    /// in it.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+        validate_config_source(&content).map_err(|problems| {
+            let lines: Vec<String> = problems
+                .iter()
+                .map(|p| format!("  {}", p.located(path)))
+                .collect();
+            anyhow::anyhow!("Invalid config {}:\n{}", path.display(), lines.join("\n"))
+        })
+    }
+
+    /// The first config file in the default locations that exists.
+    pub fn find_in_search_paths() -> Option<PathBuf> {
+        Self::SEARCH_PATHS
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.exists())
+    }
+
+    /// Search for config file in default locations and load if found.
+    pub fn load_from_search_paths() -> anyhow::Result<Option<Self>> {
+        Self::find_in_search_paths()
+            .map(|path| Self::load(&path))
+            .transpose()
     }
 
     /// Load config with CLI overrides.
@@ -83,11 +1533,11 @@ impl ServerConfig {
         let mut config = if let Some(path) = config_path {
             Self::load(path)?
         } else {
-            Self::load_from_search_paths().unwrap_or_default()
+            Self::load_from_search_paths()?.unwrap_or_default()
         };
 
-        if let Some(host) = overrides.host {
-            config.host = host;
+        if let Some(hosts) = overrides.hosts {
+            config.hosts = hosts;
         }
         if let Some(port) = overrides.port {
             config.port = port;
@@ -98,18 +1548,579 @@ impl ServerConfig {
         if overrides.public_base_url.is_some() {
             config.public_base_url = overrides.public_base_url;
         }
+        if let Some(private_mode) = overrides.private_mode {
+            config.private_mode = private_mode;
+        }
+        if let Some(policy) = overrides.repo_name_policy {
+            config.repo_name_policy = policy;
+        }
+        if overrides.tls_cert.is_some() {
+            config.tls_cert = overrides.tls_cert;
+        }
+        if overrides.tls_key.is_some() {
+            config.tls_key = overrides.tls_key;
+        }
+        if overrides.ssh_port.is_some() {
+            config.ssh_port = overrides.ssh_port;
+        }
+        if overrides.ssh_host_key.is_some() {
+            config.ssh_host_key = overrides.ssh_host_key;
+        }
+        if overrides.grpc_port.is_some() {
+            config.grpc_port = overrides.grpc_port;
+        }
+        if overrides.hooks_dir.is_some() {
+            config.hooks_dir = overrides.hooks_dir;
+        }
+        if let Some(builtin_hooks) = overrides.builtin_hooks {
+            config.builtin_hooks = builtin_hooks;
+        }
+        if let Some(mode) = overrides.storage_mode {
+            config.storage_mode = mode;
+        }
+        if overrides.maintenance_interval_secs.is_some() {
+            config.maintenance_interval_secs = overrides.maintenance_interval_secs;
+        }
+        if let Some(task) = overrides.maintenance_task {
+            config.maintenance_task = task;
+        }
+        if let Some(optimize) = overrides.optimize_after_push {
+            config.optimize_after_push = optimize;
+        }
+        if let Some(bundle_uri) = overrides.bundle_uri {
+            config.bundle_uri = bundle_uri;
+        }
+        if let Some(registry) = overrides.registry {
+            config.registry = registry;
+        }
+        if let Some(webdav) = overrides.webdav {
+            config.webdav = webdav;
+        }
+        if let Some(pages) = overrides.pages {
+            config.pages = pages;
+        }
+        if overrides.pages_domain.is_some() {
+            config.pages_domain = overrides.pages_domain;
+        }
+        if let Some(max) = overrides.pages_max_bytes {
+            config.pages_max_bytes = max;
+        }
+        if overrides.min_free_bytes.is_some() {
+            config.min_free_bytes = overrides.min_free_bytes;
+        }
+        if overrides.warn_free_bytes.is_some() {
+            config.warn_free_bytes = overrides.warn_free_bytes;
+        }
+        if let Some(age) = overrides.temp_max_age_secs {
+            config.temp_max_age_secs = age;
+        }
+        if overrides.fair_queue_slots.is_some() {
+            config.fair_queue_slots = overrides.fair_queue_slots;
+        }
+        if overrides.clone_slots_per_repo.is_some() {
+            config.clone_slots_per_repo = overrides.clone_slots_per_repo;
+        }
+        if overrides.max_clone_depth.is_some() {
+            config.max_clone_depth = overrides.max_clone_depth;
+        }
+        if overrides.max_pack_bytes.is_some() {
+            config.max_pack_bytes = overrides.max_pack_bytes;
+        }
+        if overrides.max_blob_bytes.is_some() {
+            config.max_blob_bytes = overrides.max_blob_bytes;
+        }
+        if overrides.fetch_timeout_secs.is_some() {
+            config.limits.fetch.timeout_secs = overrides.fetch_timeout_secs;
+        }
 
         Ok(config)
     }
 }
 
+fn is_http_url(url: &str) -> bool {
+    url.strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .is_some_and(|rest| !rest.is_empty() && !rest.starts_with('/'))
+}
+
+fn is_acme_domain(domain: &str) -> bool {
+    domain.len() <= 253
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+fn is_nats_url(url: &str) -> bool {
+    url.strip_prefix("nats://")
+        .or_else(|| url.strip_prefix("tls://"))
+        .is_some_and(|rest| !rest.is_empty() && !rest.starts_with('/'))
+}
+
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
-            host: default_host(),
+            hosts: default_hosts(),
             port: default_port(),
+            tls_cert: None,
+            tls_key: None,
+            tls_reload_secs: None,
             data_dir: default_data_dir(),
+            storage_roots: HashMap::new(),
             public_base_url: None,
+            private_mode: false,
+            repo_name_policy: RepoNamePolicy::default(),
+            ssh_port: None,
+            ssh_idle_timeout_secs: default_ssh_idle_timeout_secs(),
+            ssh_host_key: None,
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
+            grpc_port: None,
+            hooks_dir: None,
+            builtin_hooks: Vec::new(),
+            storage_mode: StorageMode::default(),
+            maintenance_interval_secs: None,
+            maintenance_task: MaintenanceTask::default(),
+            optimize_after_push: true,
+            bundle_uri: false,
+            registry: false,
+            lfs_s3: None,
+            lfs_verify_interval_secs: None,
+            lfs_upload_bytes_per_sec: None,
+            lfs_download_bytes_per_sec: None,
+            authz: None,
+            push_alerts: None,
+            tracing: None,
+            events: None,
+            acme: None,
+            webdav: false,
+            pages: false,
+            pages_domain: None,
+            pages_max_bytes: default_pages_max_bytes(),
+            pages_max_files: default_pages_max_files(),
+            min_free_bytes: None,
+            warn_free_bytes: None,
+            disk_check_interval_secs: default_disk_check_interval_secs(),
+            temp_max_age_secs: default_temp_max_age_secs(),
+            temp_sweep_interval_secs: default_temp_sweep_interval_secs(),
+            archive_timeout_secs: default_git_timeout_secs(),
+            git_command_timeout_secs: default_git_timeout_secs(),
+            grep_timeout_secs: default_grep_timeout_secs(),
+            maintenance_timeout_secs: default_background_timeout_secs(),
+            mirror_timeout_secs: default_background_timeout_secs(),
+            shared_objects_timeout_secs: default_background_timeout_secs(),
+            signing_timeout_secs: default_signing_timeout_secs(),
+            limits: SubprocessLimits::default(),
+            fair_queue_slots: None,
+            namespace_weights: HashMap::new(),
+            clone_slots_per_repo: None,
+            max_clone_depth: None,
+            max_pack_bytes: None,
+            max_blob_bytes: None,
+            max_upload_bytes: default_max_upload_bytes(),
+            max_raw_blob_bytes: None,
+            max_inline_blob_bytes: default_max_inline_blob_bytes(),
+            pull_request_url: None,
+            banner: None,
+            features: FeatureFlags::default(),
+            legacy_tokens: LegacyTokens::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_accepts_string_or_list() {
+        let config: ServerConfig = toml::from_str(r#"host = "::""#).unwrap();
+        assert_eq!(config.hosts, vec!["::"]);
+
+        let config: ServerConfig = toml::from_str(r#"host = ["0.0.0.0", "[::]"]"#).unwrap();
+        assert_eq!(config.hosts, vec!["0.0.0.0", "[::]"]);
+
+        let config: ServerConfig = toml::from_str("").unwrap();
+        assert_eq!(config.hosts, vec!["127.0.0.1"]);
+    }
+
+    #[test]
+    fn test_socket_addrs_handle_ipv6() {
+        let config = ServerConfig {
+            hosts: vec!["0.0.0.0".into(), "::".into(), "[::1]".into()],
+            port: 8080,
+            ssh_port: Some(2222),
+            ..ServerConfig::default()
+        };
+
+        let addrs: Vec<String> = config
+            .socket_addrs()
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(addrs, vec!["0.0.0.0:8080", "[::]:8080", "[::1]:8080"]);
+
+        let ssh = config.ssh_socket_addrs().unwrap().unwrap();
+        assert_eq!(ssh[2].to_string(), "[::1]:2222");
+        assert!(config.grpc_socket_addrs().is_none());
+
+        let bad = ServerConfig {
+            hosts: vec!["example.com".into()],
+            ..ServerConfig::default()
+        };
+        assert!(bad.socket_addrs().is_err());
+    }
+
+    #[test]
+    fn test_subprocess_limits_per_class() {
+        let config: ServerConfig = toml::from_str(
+            r#"
+            [limits.archive]
+            timeout = 60
+            memory_bytes = 1073741824
+
+            [limits.fetch]
+            timeout = 0
+            "#,
+        )
+        .unwrap();
+        let default = Some(Duration::from_secs(300));
+
+        let archive = &config.limits.archive;
+        assert_eq!(archive.timeout(default), Some(Duration::from_secs(60)));
+        assert_eq!(archive.memory_bytes, Some(1 << 30));
+        assert_eq!(config.limits.fetch.timeout(default), None);
+        assert_eq!(config.limits.push.timeout(default), default);
+
+        assert!(toml::from_str::<ServerConfig>("[limits.archive]\ncpu = 1").is_err());
+    }
+
+    #[test]
+    fn test_feature_flags() {
+        let config: ServerConfig = toml::from_str(
+            r#"
+            [features]
+            archive = false
+            admin_api = false
+            "#,
+        )
+        .unwrap();
+        assert!(!config.features.enabled(Feature::Archive));
+        assert!(!config.features.enabled(Feature::AdminApi));
+        assert!(config.features.enabled(Feature::ContentMutations));
+        assert!(config.features.enabled(Feature::Lfs));
+
+        let default: ServerConfig = toml::from_str("").unwrap();
+        assert_eq!(default.features, FeatureFlags::default());
+        assert!(toml::from_str::<ServerConfig>("[features]\nssh = false").is_err());
+    }
+
+    #[test]
+    fn test_legacy_tokens() {
+        let default: ServerConfig = toml::from_str("").unwrap();
+        assert!(default.legacy_tokens.accept);
+        assert!(default.legacy_tokens.notice().contains("retired format"));
+
+        let config: ServerConfig = toml::from_str(
+            r#"
+            [legacy_tokens]
+            accept = false
+            notice = "Old tokens stop working on 2027-01-31"
+            "#,
+        )
+        .unwrap();
+        assert!(!config.legacy_tokens.accept);
+        assert_eq!(
+            config.legacy_tokens.notice(),
+            "Old tokens stop working on 2027-01-31"
+        );
+        assert!(config.problems().is_empty());
+
+        let config: ServerConfig =
+            toml::from_str("[legacy_tokens]\nnotice = \"first\\nsecond\"").unwrap();
+        assert_eq!(config.problems()[0].0, "legacy_tokens.notice");
+    }
+
+    #[test]
+    fn test_sizes_and_durations_with_units() {
+        let config: ServerConfig = toml::from_str(
+            r#"
+            temp_max_age = "12h"
+            ssh_idle_timeout = "90s"
+            shutdown_timeout = "2m"
+            archive_timeout = "10m"
+            mirror_timeout = "1h"
+            signing_timeout = 0
+            max_upload_bytes = "250MB"
+            max_inline_blob_bytes = "4MiB"
+            maintenance_interval = 3600
+
+            [limits.archive]
+            timeout = "5m"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.temp_max_age_secs, 12 * 60 * 60);
+        assert_eq!(config.ssh_idle_timeout_secs, 90);
+        assert_eq!(config.shutdown_timeout_secs, 120);
+        assert_eq!(config.archive_timeout_secs, 600);
+        assert_eq!(config.git_command_timeout_secs, 300);
+        assert_eq!(config.grep_timeout(), Some(Duration::from_secs(60)));
+        assert_eq!(config.mirror_timeout(), Some(Duration::from_secs(60 * 60)));
+        assert_eq!(config.signing_timeout(), None);
+        assert_eq!(config.max_upload_bytes, 250_000_000);
+        assert_eq!(config.maintenance_interval_secs, Some(3600));
+        assert_eq!(
+            config.limits.archive.timeout(None),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(config.max_raw_blob_bytes, None);
+        assert_eq!(config.max_inline_blob_bytes, 4 * 1024 * 1024);
+        assert_eq!(ServerConfig::default().max_inline_blob_bytes, 1024 * 1024);
+
+        assert!(toml::from_str::<ServerConfig>(r#"temp_max_age = "soon""#).is_err());
+        assert!(toml::from_str::<ServerConfig>("max_upload_bytes = -1").is_err());
+    }
+
+    #[test]
+    fn test_secs_names_still_accepted() {
+        let config: ServerConfig = toml::from_str(
+            r#"
+            temp_max_age_secs = 3600
+            tls_reload_secs = 60
+
+            [limits.fetch]
+            timeout_secs = 30
+            cpu_secs = 10
+
+            [authz]
+            url = "http://opa:8181"
+            cache_secs = 0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.temp_max_age_secs, 3600);
+        assert_eq!(config.tls_reload_secs, Some(60));
+        assert_eq!(config.limits.fetch.timeout_secs, Some(30));
+        assert_eq!(config.limits.fetch.cpu_secs, Some(10));
+        assert_eq!(config.authz.unwrap().cache_secs, 0);
+
+        let both = "temp_max_age = 60\ntemp_max_age_secs = 60";
+        assert!(toml::from_str::<ServerConfig>(both).is_err());
+    }
+
+    #[test]
+    fn test_authz_defaults_and_problems() {
+        let config: ServerConfig =
+            toml::from_str("[authz]\nurl = \"http://opa:8181/v1/data/cutman/allow\"\n").unwrap();
+        let authz = config.authz.as_ref().unwrap();
+        assert_eq!(authz.timeout_secs, 5);
+        assert_eq!(authz.cache_secs, 30);
+        assert!(config.problems().is_empty());
+
+        let config: ServerConfig =
+            toml::from_str("[authz]\nurl = \"opa:8181\"\ntimeout = 0\n").unwrap();
+        let keys: Vec<String> = config.problems().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec!["authz.url", "authz.timeout"]);
+    }
+
+    #[test]
+    fn test_acme_defaults_and_problems() {
+        let config: ServerConfig =
+            toml::from_str("port = 443\n[acme]\ndomains = [\"git.example.com\"]\n").unwrap();
+        let acme = config.acme.as_ref().unwrap();
+        assert_eq!(
+            acme.directory,
+            "https://acme-v02.api.letsencrypt.org/directory"
+        );
+        assert_eq!(acme.http_port, 80);
+        assert!(config.serves_tls());
+        assert!(config.problems().is_empty());
+
+        let config: ServerConfig = toml::from_str(
+            "port = 80\ntls_cert = \"cert.pem\"\ntls_key = \"key.pem\"\n\
+             [acme]\ndomains = [\"*.example.com\", \"localhost\"]\nemail = \"ops\"\n\
+             directory = \"acme.example.com\"\n",
+        )
+        .unwrap();
+        let keys: Vec<String> = config.problems().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "acme.domains",
+                "acme.domains",
+                "acme.email",
+                "acme.directory",
+                "acme.http_port",
+                "acme"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_push_alerts_defaults_and_problems() {
+        let config: ServerConfig = toml::from_str("[push_alerts]\n").unwrap();
+        let alerts = config.push_alerts.as_ref().unwrap();
+        assert_eq!(alerts.rewritten_commits, 50);
+        assert_eq!(alerts.deleted_branches, 5);
+        assert!(alerts.new_address);
+        assert!(config.problems().is_empty());
+
+        let config: ServerConfig = toml::from_str(
+            "[push_alerts]\nwebhook_url = \"hooks.example.com\"\nclient_ip_header = \"X Forwarded\"\n",
+        )
+        .unwrap();
+        let keys: Vec<String> = config.problems().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(
+            keys,
+            vec!["push_alerts.webhook_url", "push_alerts.client_ip_header"]
+        );
+    }
+
+    #[test]
+    fn test_tracing_defaults_and_problems() {
+        let config: ServerConfig =
+            toml::from_str("[tracing]\notlp_endpoint = \"http://collector:4318\"\n").unwrap();
+        let tracing = config.tracing.as_ref().unwrap();
+        assert_eq!(tracing.service_name, "cutman");
+        assert_eq!(tracing.sample_ratio, 1.0);
+        assert_eq!(tracing.traces_url(), "http://collector:4318/v1/traces");
+        assert!(config.problems().is_empty());
+
+        let config: ServerConfig =
+            toml::from_str("[tracing]\notlp_endpoint = \"collector:4318\"\nsample_ratio = 2.0\n")
+                .unwrap();
+        let keys: Vec<String> = config.problems().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec!["tracing.otlp_endpoint", "tracing.sample_ratio"]);
+
+        let config: ServerConfig =
+            toml::from_str("[tracing]\notlp_endpoint = \"https://otlp.example.com/api/traces\"\n")
+                .unwrap();
+        assert_eq!(
+            config.tracing.unwrap().traces_url(),
+            "https://otlp.example.com/api/traces"
+        );
+    }
+
+    #[test]
+    fn test_events_defaults_and_problems() {
+        let config: ServerConfig =
+            toml::from_str("[events]\nnats_url = \"nats://nats:4222\"\n").unwrap();
+        let events = config.events.as_ref().unwrap();
+        assert_eq!(events.nats_subject_prefix, "cutman");
+        assert_eq!(events.queue_size, 10_000);
+        assert!(events.kinds.is_empty());
+        assert!(config.problems().is_empty());
+
+        let config: ServerConfig = toml::from_str(
+            "[events]\nkafka_brokers = [\"kafka:9092\"]\nkinds = [\"push\", \"repo.deleted\"]\n",
+        )
+        .unwrap();
+        let events = config.events.as_ref().unwrap();
+        assert_eq!(events.kafka_topic, "cutman.events");
+        assert_eq!(events.kinds, [EventKind::Push, EventKind::RepoDeleted]);
+        assert!(config.problems().is_empty());
+
+        for (toml, key) in [
+            ("[events]\n", "events"),
+            (
+                "[events]\nnats_url = \"nats://nats:4222\"\nkafka_brokers = [\"kafka:9092\"]\n",
+                "events",
+            ),
+            (
+                "[events]\nnats_url = \"http://nats:4222\"\n",
+                "events.nats_url",
+            ),
+            (
+                "[events]\nkafka_brokers = [\"kafka\"]\n",
+                "events.kafka_brokers",
+            ),
+            (
+                "[events]\nnats_url = \"nats://nats:4222\"\nqueue_size = 0\n",
+                "events.queue_size",
+            ),
+        ] {
+            let config: ServerConfig = toml::from_str(toml).unwrap();
+            assert_eq!(config.problems()[0].0, key, "{toml}");
         }
+        assert!(toml::from_str::<ServerConfig>("[events]\nkinds = [\"pull\"]\n").is_err());
+    }
+
+    #[test]
+    fn test_grpc_port_problems() {
+        let config: ServerConfig = toml::from_str("grpc_port = 50051").unwrap();
+        assert!(config.problems().is_empty());
+        let grpc = config.grpc_socket_addrs().unwrap().unwrap();
+        assert_eq!(grpc[0].to_string(), "127.0.0.1:50051");
+
+        for toml in [
+            "grpc_port = 0",
+            "port = 9000\ngrpc_port = 9000",
+            "ssh_port = 2222\ngrpc_port = 2222",
+        ] {
+            let config: ServerConfig = toml::from_str(toml).unwrap();
+            assert_eq!(config.problems()[0].0, "grpc_port", "{toml}");
+        }
+    }
+
+    #[test]
+    fn test_tls_problems() {
+        let config: ServerConfig = toml::from_str(
+            "tls_cert = \"cert.pem\"\ntls_key = \"key.pem\"\ntls_reload_interval = \"1h\"",
+        )
+        .unwrap();
+        assert!(config.problems().is_empty());
+        assert_eq!(config.tls_reload_secs, Some(3600));
+
+        for (toml, key) in [
+            ("tls_cert = \"cert.pem\"", "tls_key"),
+            ("tls_key = \"key.pem\"", "tls_cert"),
+            ("tls_reload_interval = 60", "tls_reload_interval"),
+            (
+                "tls_cert = \"cert.pem\"\ntls_key = \"key.pem\"\ntls_reload_secs = 0",
+                "tls_reload_interval",
+            ),
+        ] {
+            let config: ServerConfig = toml::from_str(toml).unwrap();
+            assert_eq!(config.problems()[0].0, key, "{toml}");
+        }
+    }
+
+    #[test]
+    fn test_storage_roots() {
+        let config: ServerConfig = toml::from_str(
+            r#"
+            [storage_roots]
+            ssd = "/mnt/ssd"
+            hdd = "/mnt/hdd"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.storage_roots["ssd"], Path::new("/mnt/ssd"));
+        assert!(config.problems().is_empty());
+
+        let config: ServerConfig = toml::from_str(
+            r#"
+            data_dir = "/data"
+            [storage_roots]
+            default = "/a"
+            "a b" = "/b"
+            same = "/data"
+            "#,
+        )
+        .unwrap();
+        let keys: Vec<String> = config.problems().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "storage_roots.a b",
+                "storage_roots.default",
+                "storage_roots.same"
+            ]
+        );
     }
 }
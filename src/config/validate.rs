@@ -0,0 +1,334 @@
+//! Checks a `server.toml` and reports every problem in it at once, each
+//! with the line it is on, rather than stopping at the first.
+//!
+//! Each top-level key is deserialized on its own, with the rest of the file
+//! blanked out but its line numbers kept, so one bad value doesn't hide the
+//! next. Once every key parses, the whole config goes through
+//! [`ServerConfig::problems`] for values that parse but can't work.
+
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+use toml_edit::{ImDocument, Item, TableLike};
+
+use super::ServerConfig;
+
+/// One problem found in a config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigProblem {
+    /// 1-based line number, if the problem could be traced to one.
+    pub line: Option<usize>,
+    /// Dotted key the problem belongs to; empty for syntax errors.
+    pub key: String,
+    pub message: String,
+}
+
+impl ConfigProblem {
+    /// The problem prefixed with the file it came from, as in
+    /// `server.toml:12: port: ...`.
+    pub fn located(&self, path: &Path) -> String {
+        match self.line {
+            Some(line) => format!("{}:{line}: {}", path.display(), self.describe()),
+            None => format!("{}: {}", path.display(), self.describe()),
+        }
+    }
+
+    fn describe(&self) -> String {
+        if self.key.is_empty() {
+            self.message.clone()
+        } else {
+            format!("{}: {}", self.key, self.message)
+        }
+    }
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {line}: {}", self.describe()),
+            None => f.write_str(&self.describe()),
+        }
+    }
+}
+
+/// Parses `source` as a server config, returning every problem found if
+/// it isn't valid.
+pub fn validate_config_source(source: &str) -> Result<ServerConfig, Vec<ConfigProblem>> {
+    let doc = match ImDocument::parse(source) {
+        Ok(doc) => doc,
+        Err(e) => {
+            return Err(vec![ConfigProblem {
+                line: e.span().map(|span| line_of(source, span.start)),
+                key: String::new(),
+                message: e.message().trim_end().to_string(),
+            }]);
+        }
+    };
+
+    let mut problems = Vec::new();
+    for (key, item) in doc.as_table().iter() {
+        let mut spans = Vec::new();
+        if let Some(span) = doc.as_table().key(key).and_then(|k| k.span()) {
+            spans.push(span);
+        }
+        item_spans(item, &mut spans);
+        problems.extend(check_isolated(source, key, &spans));
+    }
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+
+    let config: ServerConfig = toml::from_str(source).map_err(|e| {
+        vec![ConfigProblem {
+            line: e.span().map(|span| line_of(source, span.start)),
+            key: String::new(),
+            message: e.message().to_string(),
+        }]
+    })?;
+
+    let problems: Vec<ConfigProblem> = config
+        .problems()
+        .into_iter()
+        .map(|(key, message)| ConfigProblem {
+            line: key_line(&doc, source, &key),
+            key,
+            message,
+        })
+        .collect();
+    if problems.is_empty() {
+        Ok(config)
+    } else {
+        Err(problems)
+    }
+}
+
+/// Deserializes the lines `spans` cover on their own. An unknown key's line
+/// is dropped and the rest tried again, so each one is reported.
+fn check_isolated(source: &str, key: &str, spans: &[Range<usize>]) -> Vec<ConfigProblem> {
+    let mut keep = vec![false; source.split_inclusive('\n').count()];
+    for span in spans {
+        let first = line_of(source, span.start) - 1;
+        let last = line_of(source, span.end.saturating_sub(1).max(span.start)) - 1;
+        for kept in keep.iter_mut().take(last + 1).skip(first) {
+            *kept = true;
+        }
+    }
+
+    let mut problems = Vec::new();
+    loop {
+        let isolated: String = source
+            .split_inclusive('\n')
+            .zip(&keep)
+            .map(|(line, kept)| match (kept, line.ends_with('\n')) {
+                (true, _) => line,
+                (false, true) => "\n",
+                (false, false) => "",
+            })
+            .collect();
+        let Err(e) = toml::from_str::<ServerConfig>(&isolated) else {
+            return problems;
+        };
+
+        let line = e.span().map(|span| line_of(&isolated, span.start));
+        let (message, unknown) = match unknown_field(e.message()) {
+            Some(message) => (message, true),
+            None => (e.message().to_string(), false),
+        };
+        problems.push(ConfigProblem {
+            line,
+            key: key.to_string(),
+            message,
+        });
+
+        // Dropping the line only helps if it holds nothing but the key.
+        match line {
+            Some(line) if unknown && keep[line - 1] && line_of(source, spans[0].start) != line => {
+                keep[line - 1] = false;
+            }
+            _ => return problems,
+        }
+    }
+}
+
+/// Every span a top-level item covers: table headers, keys, and values.
+fn item_spans(item: &Item, spans: &mut Vec<Range<usize>>) {
+    spans.extend(item.span());
+    match item {
+        Item::Table(table) => table_spans(table, spans),
+        Item::ArrayOfTables(tables) => {
+            for table in tables.iter() {
+                spans.extend(table.span());
+                table_spans(table, spans);
+            }
+        }
+        Item::None | Item::Value(_) => {}
+    }
+}
+
+fn table_spans(table: &dyn TableLike, spans: &mut Vec<Range<usize>>) {
+    for (key, item) in table.iter() {
+        if let Some(span) = table.key(key).and_then(|k| k.span()) {
+            spans.push(span);
+        }
+        item_spans(item, spans);
+    }
+}
+
+/// The line of a dotted key, or of the nearest parent that exists. Durations
+/// may be written under their older `_secs` names.
+fn key_line(doc: &ImDocument<&str>, source: &str, key: &str) -> Option<usize> {
+    let mut table: &dyn TableLike = doc.as_table();
+    let mut line = None;
+    for segment in key.split('.') {
+        let Some((k, item)) = table
+            .get_key_value(segment)
+            .or_else(|| table.get_key_value(&format!("{segment}_secs")))
+        else {
+            break;
+        };
+        if let Some(span) = k.span() {
+            line = Some(line_of(source, span.start));
+        }
+        match item.as_table_like() {
+            Some(child) => table = child,
+            None => break,
+        }
+    }
+    line
+}
+
+fn line_of(source: &str, offset: usize) -> usize {
+    source[..offset.min(source.len())].matches('\n').count() + 1
+}
+
+/// Rewrites serde's "unknown field `x`, expected one of ..." to name the
+/// closest known key instead of listing them all.
+fn unknown_field(message: &str) -> Option<String> {
+    let rest = message.strip_prefix("unknown field `")?;
+    let (name, expected) = rest.split_once('`')?;
+    let suggestion = expected
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= (name.len() / 3).max(1))
+        .min();
+    Some(match suggestion {
+        Some((_, candidate)) => format!("unknown key `{name}` (did you mean `{candidate}`?)"),
+        None => format!("unknown key `{name}`"),
+    })
+}
+
+/// Edit distance between two keys, counting a swap of adjacent letters
+/// as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(source: &str) -> Vec<String> {
+        validate_config_source(source)
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_reports_every_problem_with_its_line() {
+        let source = r#"
+host = "0.0.0.0"
+prot = 8080
+max_pack_bytes = "2 parsecs"
+
+[lfs_s3]
+endpoint = "http://minio:9000"
+bucket = "lfs"
+bukcet = "lfs"
+regoin = "us-east-1"
+
+[limits.fetch]
+timeout = "soon"
+"#;
+        assert_eq!(
+            problems(source),
+            vec![
+                "line 3: prot: unknown key `prot` (did you mean `port`?)",
+                "line 4: max_pack_bytes: unknown unit `parsecs` in size `2 parsecs` \
+                 (use B, KB, MB, GB, TB, KiB, MiB, GiB, or TiB)",
+                "line 9: lfs_s3: unknown key `bukcet` (did you mean `bucket`?)",
+                "line 10: lfs_s3: unknown key `regoin` (did you mean `region`?)",
+                "line 13: limits: invalid duration `soon`, expected e.g. `300`, `5m`, or `1h30m`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_values_out_of_range() {
+        let source = "port = 2222\nssh_port = 2222\nmin_free_bytes = \"10GB\"\n\
+                      warn_free_bytes = \"1GB\"\nssh_idle_timeout_secs = 0\n\n\
+                      [namespace_weights]\nci = 0\n";
+        assert_eq!(
+            problems(source),
+            vec![
+                "line 2: ssh_port: 2222 is already the HTTP port",
+                "line 4: warn_free_bytes: must be at least min_free_bytes, \
+                 or the warning never comes first",
+                "line 5: ssh_idle_timeout: must be greater than 0",
+                "line 8: namespace_weights.ci: must be greater than 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_syntax_errors() {
+        let problems = problems("port = 8080\nhost = \n");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("line 2: "), "{problems:?}");
+    }
+
+    #[test]
+    fn test_accepts_sizes_with_units() {
+        let config = validate_config_source(
+            "max_blob_bytes = \"100MiB\"\npages_max_bytes = 1000\n\
+             [limits.archive]\nmemory_bytes = \"1GiB\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.max_blob_bytes, Some(100 << 20));
+        assert_eq!(config.pages_max_bytes, 1000);
+        assert_eq!(config.limits.archive.memory_bytes, Some(1 << 30));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("prot", "port"), 1);
+        assert_eq!(edit_distance("bukcet", "bucket"), 1);
+        assert_eq!(edit_distance("dist", "host"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
@@ -0,0 +1,229 @@
+//! Which email addresses the commits pushed to a namespace may carry, for
+//! organisations that need every author and committer in history to be an
+//! attributable corporate identity. The pre-receive hook checks every new
+//! commit a push brings to a branch, merges included, since their author
+//! and committer are whoever made them.
+//!
+//! Cutman can't send mail, so a principal's verified emails are the ones an
+//! admin has recorded for it.
+
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+
+use crate::commit_policy::{CommitViolation, new_commits};
+
+/// Most domains one policy may list.
+pub const MAX_DOMAINS: usize = 100;
+
+/// Most verified emails one principal may have.
+pub const MAX_PRINCIPAL_EMAILS: usize = 20;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailPolicy {
+    /// Domains author and committer emails may use. Matching ignores case
+    /// but is otherwise exact: `example.com` doesn't admit `eu.example.com`.
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    /// Also accept the pusher's verified emails, whatever their domain.
+    #[serde(default)]
+    pub allow_pusher_emails: bool,
+}
+
+impl EmailPolicy {
+    /// Lowercases and deduplicates the domains, or explains the first
+    /// setting that can't be used.
+    pub fn normalize(&mut self) -> Result<(), String> {
+        if self.allowed_domains.is_empty() && !self.allow_pusher_emails {
+            return Err("Set allowed_domains, allow_pusher_emails, or both".to_string());
+        }
+        if self.allowed_domains.len() > MAX_DOMAINS {
+            return Err(format!("At most {MAX_DOMAINS} domains can be allowed"));
+        }
+        for domain in &mut self.allowed_domains {
+            let normalized = domain.trim().to_ascii_lowercase();
+            if !is_domain(&normalized) {
+                return Err(format!("Invalid domain {domain:?}"));
+            }
+            *domain = normalized;
+        }
+        self.allowed_domains.sort();
+        self.allowed_domains.dedup();
+        Ok(())
+    }
+
+    /// Whether a commit may carry `email` when pushed by someone with
+    /// `pusher_emails`.
+    #[must_use]
+    pub fn allows(&self, email: &str, pusher_emails: &[String]) -> bool {
+        if self.allow_pusher_emails && pusher_emails.iter().any(|e| e.eq_ignore_ascii_case(email)) {
+            return true;
+        }
+        email.rsplit_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty()
+                && self
+                    .allowed_domains
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(domain))
+        })
+    }
+
+    /// Checks the author and committer of each commit a push adds, see
+    /// [`new_commits`].
+    pub fn check_new_commits(
+        &self,
+        repo: &Repository,
+        tips: &[Oid],
+        pusher_emails: &[String],
+    ) -> Result<Vec<CommitViolation>, git2::Error> {
+        let mut rejected = Vec::new();
+        for commit in new_commits(repo, tips)? {
+            let commit = commit?;
+            let mut violations = Vec::new();
+            for (role, signature) in [
+                ("author", commit.author()),
+                ("committer", commit.committer()),
+            ] {
+                let email = String::from_utf8_lossy(signature.email_bytes());
+                if !self.allows(&email, pusher_emails) {
+                    violations.push(format!("{role} email <{email}> is not allowed"));
+                }
+            }
+            if !violations.is_empty() {
+                rejected.push(CommitViolation::new(&commit, violations));
+            }
+        }
+        Ok(rejected)
+    }
+
+    /// What the policy accepts, for the end of a rejection report.
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match (self.allowed_domains.as_slice(), self.allow_pusher_emails) {
+            ([], _) => "allowed: your verified emails".to_string(),
+            (domains, false) => format!("allowed domains: {}", domains.join(", ")),
+            (domains, true) => format!(
+                "allowed domains: {}, or your verified emails",
+                domains.join(", ")
+            ),
+        }
+    }
+}
+
+/// Checks an email an admin is recording for a principal.
+pub fn validate_email(email: &str) -> Result<(), String> {
+    let valid = email.len() <= 254
+        && !email.contains(|c: char| c.is_whitespace() || c.is_control() || matches!(c, '<' | '>'))
+        && email.rsplit_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty() && is_domain(&domain.to_ascii_lowercase())
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid email {email:?}"))
+    }
+}
+
+fn is_domain(domain: &str) -> bool {
+    domain.len() <= 253
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let mut policy = EmailPolicy {
+            allowed_domains: vec![" Example.COM".to_string(), "example.com".to_string()],
+            allow_pusher_emails: false,
+        };
+        policy.normalize().unwrap();
+        assert_eq!(policy.allowed_domains, ["example.com"]);
+
+        assert!(EmailPolicy::default().normalize().is_err());
+        for bad in [
+            "localhost",
+            "@example.com",
+            "exa mple.com",
+            "-x.com",
+            "a..com",
+        ] {
+            let mut policy = EmailPolicy {
+                allowed_domains: vec![bad.to_string()],
+                allow_pusher_emails: true,
+            };
+            assert!(policy.normalize().is_err(), "{bad}");
+        }
+
+        assert!(validate_email("Dev@Example.com").is_ok());
+        assert!(validate_email("dev@localhost").is_err());
+        assert!(validate_email("Dev <dev@example.com>").is_err());
+    }
+
+    #[test]
+    fn test_allows() {
+        let policy = EmailPolicy {
+            allowed_domains: vec!["example.com".to_string()],
+            allow_pusher_emails: true,
+        };
+        let pusher = ["me@personal.dev".to_string()];
+        assert!(policy.allows("dev@EXAMPLE.com", &[]));
+        assert!(!policy.allows("dev@eu.example.com", &[]));
+        assert!(!policy.allows("@example.com", &[]));
+        assert!(!policy.allows("me@personal.dev", &[]));
+        assert!(policy.allows("Me@Personal.dev", &pusher));
+
+        let domains_only = EmailPolicy {
+            allow_pusher_emails: false,
+            ..policy
+        };
+        assert!(!domains_only.allows("me@personal.dev", &pusher));
+    }
+
+    #[test]
+    fn test_checks_new_commits() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let commit = |author: &str, committer: &str, parents: &[Oid]| {
+            let author = git2::Signature::now("Author", author).unwrap();
+            let committer = git2::Signature::now("Committer", committer).unwrap();
+            let parents: Vec<_> = parents
+                .iter()
+                .map(|p| repo.find_commit(*p).unwrap())
+                .collect();
+            let parents: Vec<_> = parents.iter().collect();
+            repo.commit(None, &author, &committer, "Change", &tree, &parents)
+                .unwrap()
+        };
+        let old = commit("old@gmail.com", "old@gmail.com", &[]);
+        repo.reference("refs/heads/main", old, false, "").unwrap();
+        let good = commit("dev@example.com", "dev@example.com", &[old]);
+        let bad = commit("dev@example.com", "dev@gmail.com", &[good]);
+
+        let policy = EmailPolicy {
+            allowed_domains: vec!["example.com".to_string()],
+            allow_pusher_emails: false,
+        };
+        let rejected = policy.check_new_commits(&repo, &[bad], &[]).unwrap();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].sha, bad.to_string());
+        assert_eq!(
+            rejected[0].violations,
+            ["committer email <dev@gmail.com> is not allowed"]
+        );
+    }
+}
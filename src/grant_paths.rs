@@ -0,0 +1,313 @@
+//! Paths a repo grant can confine its principal's pushes to, so a team
+//! sharing a monorepo can own `services/payments/` without being able to
+//! change the rest of it. Each path is a file or a directory, and a push is
+//! rejected when any commit it adds changes a file outside all of them. A
+//! merge only counts as changing what differs from every one of its parents.
+//!
+//! Moving a ref to commits the repository already has changes files too,
+//! so a restricted principal can't delete refs or move them other than
+//! forward, and a ref moved forward may only change allowed files between
+//! its old and new tip. Merging in work from elsewhere in the repository
+//! therefore counts everything it brings in.
+//!
+//! The content API can move branches to any commit, so its mutations aren't
+//! open to a principal whose grant lists paths.
+
+use std::collections::BTreeSet;
+
+use git2::{Commit, Oid, Repository, Tree};
+
+use crate::commit_policy::{CommitViolation, new_commits};
+use crate::hooks::RefUpdate;
+use crate::legal_hold::is_fast_forward;
+
+/// Most paths one grant may list.
+pub const MAX_PATHS: usize = 100;
+
+/// Files listed for each rejected commit before summarising the rest.
+const MAX_LISTED: usize = 5;
+
+/// Trims slashes and whitespace from `paths`, sorts and deduplicates them,
+/// or explains the first one that can't be used.
+pub fn normalize(paths: &mut Vec<String>) -> Result<(), String> {
+    if paths.len() > MAX_PATHS {
+        return Err(format!("At most {MAX_PATHS} paths can be allowed"));
+    }
+    for path in paths.iter_mut() {
+        let trimmed = path.trim().trim_matches('/');
+        let valid = !trimmed.is_empty()
+            && !trimmed.contains(char::is_control)
+            && trimmed
+                .split('/')
+                .all(|part| !matches!(part, "" | "." | ".."));
+        if !valid {
+            return Err(format!("Invalid path {path:?}"));
+        }
+        *path = trimmed.to_string();
+    }
+    paths.sort();
+    paths.dedup();
+    Ok(())
+}
+
+/// Whether `path` is one of `allowed` or inside one of them.
+#[must_use]
+pub fn allows(allowed: &[String], path: &str) -> bool {
+    allowed.iter().any(|prefix| {
+        path.strip_prefix(prefix.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+/// Checks the files changed by each commit a push adds, see
+/// [`new_commits`].
+pub fn check_new_commits(
+    repo: &Repository,
+    tips: &[Oid],
+    allowed: &[String],
+) -> Result<Vec<CommitViolation>, git2::Error> {
+    let mut rejected = Vec::new();
+    for commit in new_commits(repo, tips)? {
+        let commit = commit?;
+        let outside: Vec<String> = changed_paths(repo, &commit)?
+            .into_iter()
+            .filter(|path| !allows(allowed, path))
+            .collect();
+        if outside.is_empty() {
+            continue;
+        }
+        let mut violations: Vec<String> = outside
+            .iter()
+            .take(MAX_LISTED)
+            .map(|path| format!("changes {path}"))
+            .collect();
+        if outside.len() > MAX_LISTED {
+            violations.push(format!("...and {} more files", outside.len() - MAX_LISTED));
+        }
+        rejected.push(CommitViolation::new(&commit, violations));
+    }
+    Ok(rejected)
+}
+
+/// Why each of `updates` that isn't allowed is refused: deleting a ref,
+/// moving it other than forward, or moving it forward to a tip whose files
+/// differ from the old one outside `allowed`. Creating a ref is left to
+/// [`check_new_commits`].
+pub fn check_ref_updates(
+    repo: &Repository,
+    updates: &[RefUpdate],
+    allowed: &[String],
+) -> Result<Vec<String>, git2::Error> {
+    let mut refused = Vec::new();
+    for update in updates {
+        let refname = &update.refname;
+        if update.is_create() || update.old == update.new {
+            continue;
+        }
+        if update.is_delete() {
+            refused.push(format!("{refname} can't be deleted"));
+            continue;
+        }
+        if !is_fast_forward(repo, &update.old, &update.new) {
+            refused.push(format!("{refname} can only be fast-forwarded"));
+            continue;
+        }
+        let tree = |oid: &str| repo.revparse_single(oid)?.peel_to_tree();
+        let outside: Vec<String> =
+            diff_paths(repo, Some(&tree(&update.old)?), &tree(&update.new)?)?
+                .into_iter()
+                .filter(|path| !allows(allowed, path))
+                .collect();
+        refused.extend(
+            outside
+                .iter()
+                .take(MAX_LISTED)
+                .map(|path| format!("{refname} changes {path}")),
+        );
+        if outside.len() > MAX_LISTED {
+            refused.push(format!(
+                "{refname} changes {} more files",
+                outside.len() - MAX_LISTED
+            ));
+        }
+    }
+    Ok(refused)
+}
+
+/// What a grant allows, for the end of a rejection report.
+#[must_use]
+pub fn describe(allowed: &[String]) -> String {
+    if allowed.is_empty() {
+        "you may not change any files in this repository".to_string()
+    } else {
+        format!("you may only change: {}", allowed.join(", "))
+    }
+}
+
+/// The files `commit` changes: those that differ from every parent, which
+/// for an ordinary commit is its diff.
+fn changed_paths(repo: &Repository, commit: &Commit<'_>) -> Result<BTreeSet<String>, git2::Error> {
+    let tree = commit.tree()?;
+    let mut changed: Option<BTreeSet<String>> = None;
+    for parent in commit.parents() {
+        let paths = diff_paths(repo, Some(&parent.tree()?), &tree)?;
+        changed = Some(match changed {
+            Some(previous) => previous.intersection(&paths).cloned().collect(),
+            None => paths,
+        });
+    }
+    match changed {
+        Some(changed) => Ok(changed),
+        None => diff_paths(repo, None, &tree),
+    }
+}
+
+/// Both sides of every file `old` and `new` differ in, so a file moved out
+/// of an allowed directory counts as changed there and where it went.
+fn diff_paths(
+    repo: &Repository,
+    old: Option<&Tree<'_>>,
+    new: &Tree<'_>,
+) -> Result<BTreeSet<String>, git2::Error> {
+    let diff = repo.diff_tree_to_tree(old, Some(new), None)?;
+    let mut paths = BTreeSet::new();
+    for delta in diff.deltas() {
+        for file in [delta.old_file(), delta.new_file()] {
+            if let Some(path) = file.path() {
+                paths.insert(path.to_string_lossy().into_owned());
+            }
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_and_allows() {
+        let mut paths = vec![
+            " /services/payments/ ".to_string(),
+            "services/payments".to_string(),
+            "README.md".to_string(),
+        ];
+        normalize(&mut paths).unwrap();
+        assert_eq!(paths, ["README.md", "services/payments"]);
+        for bad in ["", "/", "a//b", "../x", "a/./b", "a\nb"] {
+            assert!(normalize(&mut vec![bad.to_string()]).is_err(), "{bad:?}");
+        }
+
+        assert!(allows(&paths, "services/payments"));
+        assert!(allows(&paths, "services/payments/api/main.go"));
+        assert!(allows(&paths, "README.md"));
+        assert!(!allows(&paths, "services/payments-v2/main.go"));
+        assert!(!allows(&paths, "services/billing/main.go"));
+        assert!(!allows(&[], "README.md"));
+    }
+
+    /// Commits a tree holding exactly `files`.
+    fn commit_files(repo: &Repository, files: &[(&str, &str)], parents: &[Oid]) -> Oid {
+        let sig = git2::Signature::now("Test", "test@example.com").unwrap();
+        let mut index = git2::Index::new().unwrap();
+        for (path, content) in files {
+            let entry = git2::IndexEntry {
+                ctime: git2::IndexTime::new(0, 0),
+                mtime: git2::IndexTime::new(0, 0),
+                dev: 0,
+                ino: 0,
+                mode: 0o100644,
+                uid: 0,
+                gid: 0,
+                file_size: 0,
+                id: repo.blob(content.as_bytes()).unwrap(),
+                flags: 0,
+                flags_extended: 0,
+                path: path.as_bytes().to_vec(),
+            };
+            index.add(&entry).unwrap();
+        }
+        let tree = repo.find_tree(index.write_tree_to(repo).unwrap()).unwrap();
+        let parents: Vec<_> = parents
+            .iter()
+            .map(|p| repo.find_commit(*p).unwrap())
+            .collect();
+        let parents: Vec<_> = parents.iter().collect();
+        repo.commit(None, &sig, &sig, "Change", &tree, &parents)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_checks_new_commits() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let commit = |files: &[(&str, &str)], parents: &[Oid]| commit_files(&repo, files, parents);
+        let root = commit(&[("README.md", "hi"), ("pay/a.go", "1")], &[]);
+        repo.reference("refs/heads/main", root, false, "").unwrap();
+        let theirs = commit(&[("README.md", "hello"), ("pay/a.go", "1")], &[root]);
+        repo.reference("refs/heads/docs", theirs, false, "")
+            .unwrap();
+
+        // Merging in the docs change only counts as changing pay/a.go.
+        let allowed = ["pay".to_string()];
+        let ours = commit(&[("README.md", "hi"), ("pay/a.go", "2")], &[root]);
+        let merge = commit(
+            &[("README.md", "hello"), ("pay/a.go", "2")],
+            &[ours, theirs],
+        );
+        let rejected = check_new_commits(&repo, &[merge], &allowed).unwrap();
+        assert!(rejected.is_empty(), "{rejected:?}");
+
+        let bad = commit(&[("README.md", "bye"), ("pay/a.go", "3")], &[merge]);
+        let rejected = check_new_commits(&repo, &[bad], &allowed).unwrap();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].sha, bad.to_string());
+        assert_eq!(rejected[0].violations, ["changes README.md"]);
+
+        // With nothing allowed, `ours` is caught too, but the merge still
+        // matches one parent or the other everywhere.
+        let rejected = check_new_commits(&repo, &[bad], &[]).unwrap();
+        let shas: Vec<_> = rejected.iter().map(|r| r.sha.clone()).collect();
+        assert_eq!(shas, [bad.to_string(), ours.to_string()]);
+    }
+
+    #[test]
+    fn test_checks_ref_updates() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let commit = |files: &[(&str, &str)], parents: &[Oid]| commit_files(&repo, files, parents);
+        let root = commit(&[("README.md", "hi"), ("pay/a.go", "1")], &[]);
+        let pay = commit(&[("README.md", "hi"), ("pay/a.go", "2")], &[root]);
+        let docs = commit(&[("README.md", "hello"), ("pay/a.go", "2")], &[pay]);
+
+        let zero = Oid::zero().to_string();
+        let update = |old: &Oid, new: &str, refname: &str| RefUpdate {
+            old: old.to_string(),
+            new: new.to_string(),
+            refname: refname.to_string(),
+        };
+        let allowed = ["pay".to_string()];
+        let ok = [
+            update(&Oid::zero(), &docs.to_string(), "refs/heads/copy"),
+            update(&root, &pay.to_string(), "refs/heads/main"),
+        ];
+        assert!(check_ref_updates(&repo, &ok, &allowed).unwrap().is_empty());
+
+        // Deleting a branch, rewinding one to a commit the repository
+        // already has, and moving one forward onto someone else's change
+        // would all change README.md without a new commit that does.
+        let refused = [
+            update(&docs, &zero, "refs/heads/docs"),
+            update(&docs, &root.to_string(), "refs/heads/docs"),
+            update(&pay, &docs.to_string(), "refs/heads/main"),
+        ];
+        assert_eq!(
+            check_ref_updates(&repo, &refused, &allowed).unwrap(),
+            [
+                "refs/heads/docs can't be deleted",
+                "refs/heads/docs can only be fast-forwarded",
+                "refs/heads/main changes README.md",
+            ]
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use git2::Oid;
+
+use super::{HookContext, ReceiveHook, RefUpdate};
+
+/// Names accepted in the `builtin_hooks` server setting.
+pub const BUILTIN_HOOKS: &[&str] = &[PROTECT_DEFAULT_BRANCH];
+
+pub const PROTECT_DEFAULT_BRANCH: &str = "protect-default-branch";
+
+pub(super) fn by_name(name: &str) -> Option<Box<dyn ReceiveHook>> {
+    match name {
+        ProtectDefaultBranch::NAME => Some(Box::new(ProtectDefaultBranch)),
+        _ => None,
+    }
+}
+
+/// Rejects deleting or force-pushing the branch `HEAD` points at.
+struct ProtectDefaultBranch;
+
+impl ProtectDefaultBranch {
+    const NAME: &'static str = PROTECT_DEFAULT_BRANCH;
+}
+
+impl ReceiveHook for ProtectDefaultBranch {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn pre_receive(&self, ctx: &HookContext, updates: &[RefUpdate]) -> Result<(), String> {
+        let Ok(head) = ctx.repo.find_reference("HEAD") else {
+            return Ok(());
+        };
+        let Some(default_branch) = head.symbolic_target() else {
+            return Ok(());
+        };
+
+        for update in updates.iter().filter(|u| u.refname == default_branch) {
+            if update.is_delete() {
+                return Err(format!("cannot delete the default branch {default_branch}"));
+            }
+            if update.is_create() {
+                continue;
+            }
+
+            let old = Oid::from_str(&update.old).map_err(|e| e.to_string())?;
+            let new = Oid::from_str(&update.new).map_err(|e| e.to_string())?;
+            let fast_forward = ctx
+                .repo
+                .graph_descendant_of(new, old)
+                .map_err(|e| e.to_string())?;
+            if !fast_forward {
+                return Err(format!(
+                    "cannot force-push the default branch {default_branch}"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(repo: &git2::Repository, parents: &[&git2::Commit]) -> Oid {
+        let sig = git2::Signature::now("Test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        repo.commit(None, &sig, &sig, "commit", &tree, parents)
+            .unwrap()
+    }
+
+    fn update(old: &str, new: &str, refname: &str) -> RefUpdate {
+        RefUpdate {
+            old: old.to_string(),
+            new: new.to_string(),
+            refname: refname.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_protect_default_branch() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init_bare(dir.path()).unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+
+        let base = commit(&repo, &[]);
+        let child = commit(&repo, &[&repo.find_commit(base).unwrap()]);
+        let unrelated = commit(&repo, &[]);
+        let (base, child, unrelated) = (base.to_string(), child.to_string(), unrelated.to_string());
+        let zero = "0".repeat(40);
+
+        let ctx = HookContext {
+            principal_id: String::new(),
+            namespace: String::new(),
+            repo_id: String::new(),
+            repo_name: String::new(),
+            push_options: Vec::new(),
+            repo,
+        };
+        let hook = ProtectDefaultBranch;
+        let check = |update: RefUpdate| hook.pre_receive(&ctx, &[update]);
+
+        assert!(check(update(&zero, &base, "refs/heads/main")).is_ok());
+        assert!(check(update(&base, &child, "refs/heads/main")).is_ok());
+        assert!(check(update(&child, &base, "refs/heads/main")).is_err());
+        assert!(check(update(&base, &unrelated, "refs/heads/main")).is_err());
+        assert!(check(update(&base, &zero, "refs/heads/main")).is_err());
+        assert!(check(update(&child, &base, "refs/heads/topic")).is_ok());
+        assert!(check(update(&base, &zero, "refs/heads/topic")).is_ok());
+    }
+}
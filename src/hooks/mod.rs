@@ -0,0 +1,740 @@
+//! Server-side hooks around `git-receive-pack`.
+//!
+//! The server points git's `core.hooksPath` at a directory of shims that
+//! re-enter this binary as `cutman hook <name>`. That process runs the
+//! enabled built-in [`ReceiveHook`]s, then every executable in
+//! `<hooks_dir>/<name>.d/` in name order. Scripts get git's standard
+//! `<old> <new> <ref>` lines on stdin and the pusher's identity in the
+//! `CUTMAN_*` environment variables below. Options from `git push -o` arrive
+//! as git's usual `GIT_PUSH_OPTION_COUNT` and `GIT_PUSH_OPTION_<n>`. Anything
+//! a hook prints is shown to the client as `remote:` output, and a failing
+//! pre-receive hook rejects the whole push before any ref is updated.
+//!
+//! Namespace storage quotas, the single-file size limit, the repository's
+//! commit message policy, the namespace's email policy, the paths the
+//! pusher's repo grant allows, and the repository's legal hold are enforced
+//! here as well, before any other hook runs, since only pre-receive sees
+//! the objects a push brings in. The
+//! pack size limit is left to git itself (`receive.maxInputSize`), which
+//! stops reading the push once it passes.
+
+mod builtin;
+
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::commit_policy::{self, CommitPolicy};
+use crate::email_policy::EmailPolicy;
+use crate::error::{Error, Result};
+use crate::grant_paths;
+use crate::legal_hold;
+use crate::push_limits::{PushLimits, PushTooLarge};
+use crate::quota::{QuotaExceeded, StorageUsage};
+use crate::types::{Namespace, Principal, Repo};
+
+pub use builtin::{BUILTIN_HOOKS, PROTECT_DEFAULT_BRANCH};
+
+/// ID of the principal that is pushing.
+pub const ENV_PRINCIPAL_ID: &str = "CUTMAN_PRINCIPAL_ID";
+/// Name of the namespace that owns the repository.
+pub const ENV_NAMESPACE: &str = "CUTMAN_NAMESPACE";
+pub const ENV_NAMESPACE_ID: &str = "CUTMAN_NAMESPACE_ID";
+pub const ENV_REPO_ID: &str = "CUTMAN_REPO_ID";
+pub const ENV_REPO_NAME: &str = "CUTMAN_REPO_NAME";
+
+const ENV_PUSH_OPTION_COUNT: &str = "GIT_PUSH_OPTION_COUNT";
+const ENV_HOOKS_DIR: &str = "CUTMAN_HOOKS_DIR";
+const ENV_BUILTIN_HOOKS: &str = "CUTMAN_BUILTIN_HOOKS";
+const ENV_STORAGE_USED: &str = "CUTMAN_STORAGE_USED";
+const ENV_STORAGE_LIMIT: &str = "CUTMAN_STORAGE_LIMIT";
+const ENV_MAX_BLOB_BYTES: &str = "CUTMAN_MAX_BLOB_BYTES";
+const ENV_COMMIT_POLICY: &str = "CUTMAN_COMMIT_POLICY";
+const ENV_EMAIL_POLICY: &str = "CUTMAN_EMAIL_POLICY";
+const ENV_PUSHER_EMAILS: &str = "CUTMAN_PUSHER_EMAILS";
+const ENV_ALLOWED_PATHS: &str = "CUTMAN_ALLOWED_PATHS";
+const ENV_LEGAL_HOLD: &str = "CUTMAN_LEGAL_HOLD";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookKind {
+    PreReceive,
+    PostReceive,
+}
+
+impl HookKind {
+    pub const ALL: [Self; 2] = [Self::PreReceive, Self::PostReceive];
+
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PreReceive => "pre-receive",
+            Self::PostReceive => "post-receive",
+        }
+    }
+
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+/// One line of the ref list git passes to receive hooks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefUpdate {
+    pub old: String,
+    pub new: String,
+    pub refname: String,
+}
+
+impl RefUpdate {
+    #[must_use]
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let (old, new, refname) = (parts.next()?, parts.next()?, parts.next()?);
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            old: old.to_string(),
+            new: new.to_string(),
+            refname: refname.to_string(),
+        })
+    }
+
+    #[must_use]
+    pub fn is_create(&self) -> bool {
+        is_zero_oid(&self.old)
+    }
+
+    #[must_use]
+    pub fn is_delete(&self) -> bool {
+        is_zero_oid(&self.new)
+    }
+}
+
+fn is_zero_oid(oid: &str) -> bool {
+    oid.bytes().all(|b| b == b'0')
+}
+
+fn parse_ref_updates(input: &str) -> Vec<RefUpdate> {
+    input.lines().filter_map(RefUpdate::parse).collect()
+}
+
+/// What a built-in hook knows about the push it is inspecting.
+pub struct HookContext {
+    pub principal_id: String,
+    pub namespace: String,
+    pub repo_id: String,
+    pub repo_name: String,
+    /// Options passed with `git push -o`, in order, e.g. `skip-ci` or
+    /// `reviewer=alice`.
+    pub push_options: Vec<String>,
+    /// The repository being pushed to. During pre-receive this includes the
+    /// quarantined objects of the incoming pack.
+    pub repo: git2::Repository,
+}
+
+impl HookContext {
+    fn from_env() -> Result<Self> {
+        let var = |name| env::var(name).unwrap_or_default();
+        let repo = git2::Repository::open_from_env()
+            .map_err(|e| Error::BadRequest(format!("Failed to open repository: {e}")))?;
+
+        Ok(Self {
+            principal_id: var(ENV_PRINCIPAL_ID),
+            namespace: var(ENV_NAMESPACE),
+            repo_id: var(ENV_REPO_ID),
+            repo_name: var(ENV_REPO_NAME),
+            push_options: parse_push_options(|name| env::var(name).ok()),
+            repo,
+        })
+    }
+
+    /// Looks up a push option by key. `-o skip-ci` yields `Some("")` for
+    /// `skip-ci`, and `-o reviewer=alice` yields `Some("alice")` for `reviewer`.
+    #[must_use]
+    pub fn push_option(&self, key: &str) -> Option<&str> {
+        self.push_options
+            .iter()
+            .find_map(|option| match option.split_once('=') {
+                Some((k, value)) if k == key => Some(value),
+                None if option == key => Some(""),
+                _ => None,
+            })
+    }
+}
+
+fn parse_push_options(var: impl Fn(&str) -> Option<String>) -> Vec<String> {
+    let count = var(ENV_PUSH_OPTION_COUNT)
+        .and_then(|count| count.parse::<usize>().ok())
+        .unwrap_or(0);
+    (0..count)
+        .filter_map(|i| var(&format!("GIT_PUSH_OPTION_{i}")))
+        .collect()
+}
+
+/// A policy compiled into the server and enabled by name.
+pub trait ReceiveHook {
+    fn name(&self) -> &'static str;
+
+    /// Returning an error rejects the push and shows the message to the client.
+    fn pre_receive(
+        &self,
+        _ctx: &HookContext,
+        _updates: &[RefUpdate],
+    ) -> std::result::Result<(), String> {
+        Ok(())
+    }
+
+    /// Runs after refs are updated; it can only report, not reject.
+    fn post_receive(&self, _ctx: &HookContext, _updates: &[RefUpdate]) {}
+}
+
+/// Rules the server looks up for a push and hands to pre-receive.
+#[derive(Debug, Clone, Default)]
+pub struct PushPolicies {
+    /// The repository's commit message policy.
+    pub commit: Option<CommitPolicy>,
+    /// The namespace's commit email policy.
+    pub email: Option<EmailPolicy>,
+    /// The pusher's verified emails, which the email policy may allow.
+    pub pusher_emails: Vec<String>,
+    /// The paths the pusher's repo grant confines them to, if it does.
+    pub allowed_paths: Option<Vec<String>>,
+    /// The repository is under a legal hold, so refs can't be deleted or
+    /// rewritten.
+    pub legal_hold: bool,
+}
+
+/// Where hook shims and scripts live and which built-in hooks are enabled.
+#[derive(Debug, Clone)]
+pub struct HookSettings {
+    dir: PathBuf,
+    builtin: Vec<String>,
+}
+
+impl HookSettings {
+    /// Fails on unknown built-in hook names so typos surface at startup.
+    pub fn new(dir: &Path, builtin: Vec<String>) -> Result<Self> {
+        if let Some(unknown) = builtin
+            .iter()
+            .find(|name| !BUILTIN_HOOKS.contains(&name.as_str()))
+        {
+            return Err(Error::Config(format!(
+                "Unknown built-in hook '{unknown}' (available: {})",
+                BUILTIN_HOOKS.join(", ")
+            )));
+        }
+
+        // Git runs hooks from inside the repository, so the path must not
+        // depend on the server's working directory.
+        let dir = std::path::absolute(dir).map_err(Error::Io)?;
+        Ok(Self { dir, builtin })
+    }
+
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether the built-in hook `name` runs on every push.
+    #[must_use]
+    pub fn runs_builtin(&self, name: &str) -> bool {
+        self.builtin.iter().any(|b| b == name)
+    }
+
+    /// Runs the enabled built-in pre-receive hooks against ref updates the
+    /// server makes itself, such as merges through the content API, so they
+    /// meet the same branch protection a push would. Scripts are left to
+    /// pushes, since they expect git's hook environment.
+    pub fn check_builtin(
+        &self,
+        ctx: &HookContext,
+        updates: &[RefUpdate],
+    ) -> std::result::Result<(), String> {
+        for hook in self.builtin.iter().filter_map(|name| builtin::by_name(name)) {
+            hook.pre_receive(ctx, updates)
+                .map_err(|message| format!("{}: {message}", hook.name()))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the shims that hand each hook to `exe` and creates the script
+    /// directories. Shims are rewritten on every start so they follow the
+    /// binary if it moves.
+    pub fn install(&self, exe: &Path) -> Result<()> {
+        for kind in HookKind::ALL {
+            fs::create_dir_all(self.dir.join(scripts_dir_name(kind))).map_err(Error::Io)?;
+
+            let shim = self.dir.join(kind.name());
+            fs::write(&shim, shim_script(exe, kind)).map_err(Error::Io)?;
+            fs::set_permissions(&shim, fs::Permissions::from_mode(0o755)).map_err(Error::Io)?;
+        }
+        Ok(())
+    }
+
+    /// Environment for a `git-receive-pack` process, so git runs the shims,
+    /// accepts push options, enforces the push size limits, and the hooks
+    /// can tell who is pushing where. The ref advertisement needs it too,
+    /// since that is where git offers the `push-options` capability.
+    #[must_use]
+    pub fn receive_pack_env(
+        &self,
+        principal: Option<&Principal>,
+        namespace: &Namespace,
+        repo: &Repo,
+        storage: Option<StorageUsage>,
+        limits: PushLimits,
+        policies: &PushPolicies,
+    ) -> Vec<(&'static str, String)> {
+        let dir = self.dir.to_string_lossy().into_owned();
+        let config_count = if limits.max_pack_bytes.is_some() {
+            3
+        } else {
+            2
+        };
+        let mut env = vec![
+            ("GIT_CONFIG_COUNT", config_count.to_string()),
+            ("GIT_CONFIG_KEY_0", "core.hooksPath".to_string()),
+            ("GIT_CONFIG_VALUE_0", dir.clone()),
+            (
+                "GIT_CONFIG_KEY_1",
+                "receive.advertisePushOptions".to_string(),
+            ),
+            ("GIT_CONFIG_VALUE_1", "true".to_string()),
+            (ENV_HOOKS_DIR, dir),
+            (ENV_BUILTIN_HOOKS, self.builtin.join(",")),
+            (
+                ENV_PRINCIPAL_ID,
+                principal.map(|p| p.id.clone()).unwrap_or_default(),
+            ),
+            (ENV_NAMESPACE, namespace.name.clone()),
+            (ENV_NAMESPACE_ID, namespace.id.clone()),
+            (ENV_REPO_ID, repo.id.clone()),
+            (ENV_REPO_NAME, repo.name.clone()),
+        ];
+        if let Some(max_pack_bytes) = limits.max_pack_bytes {
+            env.push(("GIT_CONFIG_KEY_2", "receive.maxInputSize".to_string()));
+            env.push(("GIT_CONFIG_VALUE_2", max_pack_bytes.to_string()));
+        }
+        if let Some(max_blob_bytes) = limits.max_blob_bytes {
+            env.push((ENV_MAX_BLOB_BYTES, max_blob_bytes.to_string()));
+        }
+        if let Some(storage) = storage {
+            env.push((ENV_STORAGE_USED, storage.used.to_string()));
+            env.push((ENV_STORAGE_LIMIT, storage.limit.to_string()));
+        }
+        let commit_policy = policies.commit.as_ref();
+        if let Some(policy) = commit_policy.and_then(|p| serde_json::to_string(p).ok()) {
+            env.push((ENV_COMMIT_POLICY, policy));
+        }
+        let email_policy = policies.email.as_ref();
+        if let Some(policy) = email_policy.and_then(|p| serde_json::to_string(p).ok()) {
+            env.push((ENV_EMAIL_POLICY, policy));
+            env.push((ENV_PUSHER_EMAILS, policies.pusher_emails.join("\n")));
+        }
+        if let Some(paths) = &policies.allowed_paths {
+            env.push((ENV_ALLOWED_PATHS, paths.join("\n")));
+        }
+        if policies.legal_hold {
+            env.push((ENV_LEGAL_HOLD, "1".to_string()));
+        }
+        env
+    }
+}
+
+fn scripts_dir_name(kind: HookKind) -> String {
+    format!("{}.d", kind.name())
+}
+
+fn shim_script(exe: &Path, kind: HookKind) -> String {
+    format!(
+        "#!/bin/sh\n# Generated by cutman on startup. Put custom hooks in {}/ instead.\nexec {} hook {}\n",
+        scripts_dir_name(kind),
+        shell_quote(&exe.to_string_lossy()),
+        kind.name()
+    )
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Entry point for `cutman hook <name>`, which git runs through the shims.
+/// Returns the process exit code.
+pub fn run_hook(kind: HookKind, input: &mut impl Read) -> i32 {
+    let mut stdin = String::new();
+    if let Err(e) = input.read_to_string(&mut stdin) {
+        eprintln!("cutman: failed to read ref updates: {e}");
+        return 1;
+    }
+    let updates = parse_ref_updates(&stdin);
+
+    if kind == HookKind::PreReceive {
+        if let Some(report) = check_legal_hold(&updates) {
+            eprintln!("cutman: push rejected: the repository is under a legal hold");
+            for line in report {
+                eprintln!("cutman: {line}");
+            }
+            return 1;
+        }
+        if let Some(exceeded) = check_storage_quota() {
+            eprintln!("cutman: push rejected: {exceeded}");
+            return 1;
+        }
+        if let Some(too_large) = check_blob_sizes(&updates) {
+            eprintln!("cutman: push rejected: {too_large}");
+            return 1;
+        }
+        if let Some(report) = check_commit_messages(&updates) {
+            eprintln!("cutman: push rejected: commit messages break the repository's policy");
+            for line in report {
+                eprintln!("cutman: {line}");
+            }
+            return 1;
+        }
+        if let Some(report) = check_commit_emails(&updates) {
+            eprintln!("cutman: push rejected: commit emails break the namespace's policy");
+            for line in report {
+                eprintln!("cutman: {line}");
+            }
+            return 1;
+        }
+        if let Some(report) = check_changed_paths(&updates) {
+            eprintln!("cutman: push rejected: commits change files outside your allowed paths");
+            for line in report {
+                eprintln!("cutman: {line}");
+            }
+            return 1;
+        }
+    }
+
+    let enabled = env::var(ENV_BUILTIN_HOOKS).unwrap_or_default();
+    let hooks: Vec<_> = enabled
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .filter_map(builtin::by_name)
+        .collect();
+
+    if !hooks.is_empty() {
+        let ctx = match HookContext::from_env() {
+            Ok(ctx) => ctx,
+            Err(e) => {
+                eprintln!("cutman: {e}");
+                return 1;
+            }
+        };
+
+        for hook in &hooks {
+            match kind {
+                HookKind::PreReceive => {
+                    if let Err(message) = hook.pre_receive(&ctx, &updates) {
+                        eprintln!("cutman: {}: {message}", hook.name());
+                        return 1;
+                    }
+                }
+                HookKind::PostReceive => hook.post_receive(&ctx, &updates),
+            }
+        }
+    }
+
+    match env::var_os(ENV_HOOKS_DIR) {
+        Some(dir) => run_scripts(kind, &Path::new(&dir).join(scripts_dir_name(kind)), &stdin),
+        None => 0,
+    }
+}
+
+/// Checks the namespace storage quota passed in by the server. Git keeps the
+/// incoming objects in a quarantine directory until pre-receive succeeds, so
+/// its size is what the push would add to the repository.
+fn check_storage_quota() -> Option<QuotaExceeded> {
+    let var = |name| env::var(name).ok()?.parse::<i64>().ok();
+    let usage = StorageUsage {
+        used: var(ENV_STORAGE_USED)?,
+        limit: var(ENV_STORAGE_LIMIT)?,
+    };
+    let incoming = env::var_os("GIT_QUARANTINE_PATH").map_or(0, |dir| dir_size(Path::new(&dir)));
+    usage.check(incoming)
+}
+
+/// Checks every incoming blob against the single-file size limit passed in
+/// by the server. Only the quarantined objects are looked at, so files that
+/// were already in the repository do not count again.
+fn check_blob_sizes(updates: &[RefUpdate]) -> Option<PushTooLarge> {
+    let limit: u64 = env::var(ENV_MAX_BLOB_BYTES).ok()?.parse().ok()?;
+    let quarantine = env::var_os("GIT_QUARANTINE_PATH")?;
+    let odb = git2::Odb::new().ok()?;
+    odb.add_disk_alternate(&Path::new(&quarantine).to_string_lossy())
+        .ok()?;
+
+    let mut oversized = None;
+    let _ = odb.foreach(|oid| match odb.read_header(*oid) {
+        Ok((size, git2::ObjectType::Blob)) if size as u64 > limit => {
+            oversized = Some((*oid, size as u64));
+            false
+        }
+        _ => true,
+    });
+    let (oid, size) = oversized?;
+    Some(PushTooLarge::Blob {
+        name: blob_name(oid, updates).unwrap_or_else(|| format!("File {oid}")),
+        size,
+        limit,
+    })
+}
+
+/// Checks the commits a push adds to its branches against the repository's
+/// commit policy passed in by the server, returning the report for the
+/// client when any break it.
+fn check_commit_messages(updates: &[RefUpdate]) -> Option<Vec<String>> {
+    let policy: CommitPolicy = serde_json::from_str(&env::var(ENV_COMMIT_POLICY).ok()?).ok()?;
+    let repo = git2::Repository::open_from_env().ok()?;
+    match policy.check_new_commits(&repo, &branch_tips(updates)) {
+        Ok(rejected) if rejected.is_empty() => None,
+        Ok(rejected) => Some(commit_policy::report(&rejected)),
+        Err(e) => Some(vec![format!("failed to read the pushed commits: {e}")]),
+    }
+}
+
+/// Checks the authors and committers of the commits a push adds to its
+/// branches against the namespace's email policy, returning the report for
+/// the client when any aren't allowed.
+fn check_commit_emails(updates: &[RefUpdate]) -> Option<Vec<String>> {
+    let policy: EmailPolicy = serde_json::from_str(&env::var(ENV_EMAIL_POLICY).ok()?).ok()?;
+    let pusher_emails: Vec<String> = env::var(ENV_PUSHER_EMAILS)
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect();
+    let repo = git2::Repository::open_from_env().ok()?;
+    match policy.check_new_commits(&repo, &branch_tips(updates), &pusher_emails) {
+        Ok(rejected) if rejected.is_empty() => None,
+        Ok(rejected) => {
+            let mut report = commit_policy::report(&rejected);
+            report.push(policy.describe());
+            Some(report)
+        }
+        Err(e) => Some(vec![format!("failed to read the pushed commits: {e}")]),
+    }
+}
+
+/// Checks the files changed by the commits a push adds, to any ref, and by
+/// each ref it moves, against the paths the pusher's repo grant allows,
+/// returning the report for the client when any are outside them. Since
+/// this is access control, a push whose commits can't be read is rejected
+/// too.
+fn check_changed_paths(updates: &[RefUpdate]) -> Option<Vec<String>> {
+    let allowed: Vec<String> = env::var(ENV_ALLOWED_PATHS)
+        .ok()?
+        .lines()
+        .map(str::to_string)
+        .collect();
+    let tips: Vec<git2::Oid> = updates
+        .iter()
+        .filter(|update| !update.is_delete())
+        .filter_map(|update| git2::Oid::from_str(&update.new).ok())
+        .collect();
+    let rejected = git2::Repository::open_from_env().and_then(|repo| {
+        let refused = grant_paths::check_ref_updates(&repo, updates, &allowed)?;
+        let commits = grant_paths::check_new_commits(&repo, &tips, &allowed)?;
+        Ok((refused, commits))
+    });
+    match rejected {
+        Ok((refused, commits)) if refused.is_empty() && commits.is_empty() => None,
+        Ok((mut refused, commits)) => {
+            refused.extend(commit_policy::report(&commits));
+            refused.push(grant_paths::describe(&allowed));
+            Some(refused)
+        }
+        Err(e) => Some(vec![format!("failed to read the pushed commits: {e}")]),
+    }
+}
+
+/// Checks the ref updates of a push against the repository's legal hold,
+/// when the server says it has one, returning the refs it refuses.
+fn check_legal_hold(updates: &[RefUpdate]) -> Option<Vec<String>> {
+    env::var_os(ENV_LEGAL_HOLD)?;
+    let refused = match git2::Repository::open_from_env() {
+        Ok(repo) => legal_hold::check_ref_updates(&repo, updates),
+        Err(e) => vec![format!("failed to open the repository: {e}")],
+    };
+    (!refused.is_empty()).then_some(refused)
+}
+
+/// The new tips of the branches a push creates or updates.
+fn branch_tips(updates: &[RefUpdate]) -> Vec<git2::Oid> {
+    updates
+        .iter()
+        .filter(|update| update.refname.starts_with("refs/heads/") && !update.is_delete())
+        .filter_map(|update| git2::Oid::from_str(&update.new).ok())
+        .collect()
+}
+
+/// The path `blob` has in the tip of an updated ref, for the rejection
+/// message. Blobs that only appear deeper in the pushed history are named
+/// by their id instead.
+fn blob_name(blob: git2::Oid, updates: &[RefUpdate]) -> Option<String> {
+    let repo = git2::Repository::open_from_env().ok()?;
+    updates
+        .iter()
+        .filter(|update| !update.is_delete())
+        .find_map(|update| {
+            let tree = repo
+                .revparse_single(&update.new)
+                .ok()?
+                .peel_to_tree()
+                .ok()?;
+            let mut found = None;
+            let _ = tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+                if entry.id() == blob {
+                    found = Some(format!("{dir}{}", entry.name().unwrap_or_default()));
+                    return git2::TreeWalkResult::Abort;
+                }
+                git2::TreeWalkResult::Ok
+            });
+            found
+        })
+}
+
+fn dir_size(path: &Path) -> i64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len() as i64,
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Runs each executable in `dir` in name order with the ref list on stdin.
+/// A failing pre-receive script stops the rest; post-receive scripts all run.
+fn run_scripts(kind: HookKind, dir: &Path, stdin: &str) -> i32 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+
+    let mut scripts: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .map(|entry| entry.path())
+        .filter(|path| is_executable(path))
+        .collect();
+    scripts.sort();
+
+    let mut code = 0;
+    for script in scripts {
+        let status = run_script(&script, stdin);
+        if status != 0 {
+            if kind == HookKind::PreReceive {
+                return status;
+            }
+            code = status;
+        }
+    }
+    code
+}
+
+fn run_script(script: &Path, stdin: &str) -> i32 {
+    let mut child = match Command::new(script).stdin(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("cutman: failed to run {}: {e}", script.display());
+            return 1;
+        }
+    };
+
+    // A script that ignores its input closes the pipe early; that is fine.
+    if let Some(mut pipe) = child.stdin.take() {
+        let _ = pipe.write_all(stdin.as_bytes());
+    }
+
+    match child.wait() {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(e) => {
+            eprintln!("cutman: failed to wait for {}: {e}", script.display());
+            1
+        }
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ref_updates() {
+        let zero = "0".repeat(40);
+        let sha = "a".repeat(40);
+        let input = format!("{zero} {sha} refs/heads/main\n{sha} {zero} refs/tags/v1\ngarbage\n");
+        let updates = parse_ref_updates(&input);
+
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].refname, "refs/heads/main");
+        assert!(updates[0].is_create());
+        assert!(!updates[0].is_delete());
+        assert!(updates[1].is_delete());
+    }
+
+    #[test]
+    fn test_push_options() {
+        let vars = [
+            ("GIT_PUSH_OPTION_COUNT", "3"),
+            ("GIT_PUSH_OPTION_0", "skip-ci"),
+            ("GIT_PUSH_OPTION_1", "reviewer=alice"),
+            ("GIT_PUSH_OPTION_2", "note=a=b"),
+        ];
+        let options = parse_push_options(|name| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        });
+        assert_eq!(options, vec!["skip-ci", "reviewer=alice", "note=a=b"]);
+        assert!(parse_push_options(|_| None).is_empty());
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let ctx = HookContext {
+            principal_id: String::new(),
+            namespace: String::new(),
+            repo_id: String::new(),
+            repo_name: String::new(),
+            push_options: options,
+            repo: git2::Repository::init_bare(dir.path()).unwrap(),
+        };
+        assert_eq!(ctx.push_option("skip-ci"), Some(""));
+        assert_eq!(ctx.push_option("reviewer"), Some("alice"));
+        assert_eq!(ctx.push_option("note"), Some("a=b"));
+        assert_eq!(ctx.push_option("skip"), None);
+    }
+
+    #[test]
+    fn test_hook_kind_names() {
+        for kind in HookKind::ALL {
+            assert_eq!(HookKind::from_name(kind.name()), Some(kind));
+        }
+        assert_eq!(HookKind::from_name("update"), None);
+    }
+
+    #[test]
+    fn test_shim_quotes_binary_path() {
+        let shim = shim_script(Path::new("/opt/it's here/cutman"), HookKind::PreReceive);
+        assert!(shim.starts_with("#!/bin/sh\n"));
+        assert!(shim.ends_with("exec '/opt/it'\\''s here/cutman' hook pre-receive\n"));
+    }
+
+    #[test]
+    fn test_settings_reject_unknown_builtin() {
+        let result = HookSettings::new(Path::new("/tmp/hooks"), vec!["no-such-hook".into()]);
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+}
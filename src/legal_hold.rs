@@ -0,0 +1,118 @@
+//! Legal holds: a repository under one keeps everything it holds until an
+//! admin lifts the hold. The repository, its namespace, and its LFS objects
+//! can't be deleted, and neither can its history: branches and tags can't
+//! be deleted, tags can't be moved, and branches only move forward. This
+//! applies to every principal regardless of permissions, so the checks sit
+//! next to, not inside, access control.
+
+use git2::{Oid, Repository};
+
+use crate::hooks::RefUpdate;
+
+/// Why the hold refuses each of `updates` it refuses, one line per ref.
+/// Updates that can't be checked, such as a move from a commit that's gone,
+/// are refused too.
+#[must_use]
+pub fn check_ref_updates(repo: &Repository, updates: &[RefUpdate]) -> Vec<String> {
+    updates
+        .iter()
+        .filter_map(|update| refusal(repo, update))
+        .collect()
+}
+
+fn refusal(repo: &Repository, update: &RefUpdate) -> Option<String> {
+    let refname = &update.refname;
+    if update.is_create() || update.old == update.new {
+        return None;
+    }
+    if update.is_delete() {
+        return Some(format!("{refname} can't be deleted"));
+    }
+    if refname.starts_with("refs/tags/") {
+        return Some(format!("{refname} can't be moved"));
+    }
+    if is_fast_forward(repo, &update.old, &update.new) {
+        None
+    } else {
+        Some(format!("{refname} can only be fast-forwarded"))
+    }
+}
+
+/// Whether `new` is `old` or a descendant of it. Ids that aren't commits
+/// in `repo` aren't.
+pub(crate) fn is_fast_forward(repo: &Repository, old: &str, new: &str) -> bool {
+    let commit = |oid: &str| {
+        let oid = Oid::from_str(oid).ok()?;
+        Some(
+            repo.find_object(oid, None)
+                .ok()?
+                .peel_to_commit()
+                .ok()?
+                .id(),
+        )
+    };
+    let (Some(old), Some(new)) = (commit(old), commit(new)) else {
+        return false;
+    };
+    old == new || repo.graph_descendant_of(new, old).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_ref_updates() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let sig = git2::Signature::now("Test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let commit = |message: &str, parents: &[Oid]| {
+            let parents: Vec<_> = parents
+                .iter()
+                .map(|p| repo.find_commit(*p).unwrap())
+                .collect();
+            let parents: Vec<_> = parents.iter().collect();
+            repo.commit(None, &sig, &sig, message, &tree, &parents)
+                .unwrap()
+                .to_string()
+        };
+        let root = commit("root", &[]);
+        let next = commit("next", &[Oid::from_str(&root).unwrap()]);
+        let other = commit("other", &[Oid::from_str(&root).unwrap()]);
+
+        let zero = Oid::zero().to_string();
+        let update = |old: &str, new: &str, refname: &str| RefUpdate {
+            old: old.to_string(),
+            new: new.to_string(),
+            refname: refname.to_string(),
+        };
+        let allowed = [
+            update(&zero, &root, "refs/heads/new"),
+            update(&zero, &root, "refs/tags/v1"),
+            update(&root, &next, "refs/heads/main"),
+            update(&next, &next, "refs/tags/v1"),
+        ];
+        assert!(check_ref_updates(&repo, &allowed).is_empty());
+
+        let refused = [
+            update(&root, &zero, "refs/heads/old"),
+            update(&root, &zero, "refs/tags/v0"),
+            update(&root, &next, "refs/tags/v1"),
+            update(&next, &other, "refs/heads/main"),
+            update(&"1".repeat(40), &next, "refs/heads/gone"),
+        ];
+        assert_eq!(
+            check_ref_updates(&repo, &refused),
+            [
+                "refs/heads/old can't be deleted",
+                "refs/tags/v0 can't be deleted",
+                "refs/tags/v1 can't be moved",
+                "refs/heads/main can only be fast-forwarded",
+                "refs/heads/gone can only be fast-forwarded",
+            ]
+        );
+    }
+}
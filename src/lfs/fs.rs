@@ -0,0 +1,550 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use futures_util::future::BoxFuture;
+use tokio::fs::{self, File};
+use tokio::io::{AsyncWriteExt, BufReader};
+use uuid::Uuid;
+
+use super::storage::{
+    LfsReader, LfsStorage, LfsStorageError, staged_upload_path, validate_oid, verify_data,
+    verify_file,
+};
+use crate::netfs;
+use crate::storage_roots::StorageRoots;
+use crate::store::Store;
+
+/// Objects pooled under `lfs/objects/` in the data directory, or in the
+/// storage root of the repository's namespace. Objects from before the
+/// pool, under `lfs/<repo>/objects/`, are moved there when first read.
+pub struct FsStorage {
+    base_path: PathBuf,
+    network: bool,
+    roots: Option<(Arc<StorageRoots>, Arc<dyn Store>)>,
+}
+
+impl FsStorage {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            base_path: data_dir.join("lfs"),
+            network: false,
+            roots: None,
+        }
+    }
+
+    /// Serializes uploads per repository with an advisory lock and syncs
+    /// object directories after renames, for data directories on NFS/SMB.
+    #[must_use]
+    pub fn with_network_mode(mut self, network: bool) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Keeps each repository's objects under `lfs/` in the storage root of
+    /// its namespace.
+    #[must_use]
+    pub fn with_storage_roots(mut self, roots: Arc<StorageRoots>, store: Arc<dyn Store>) -> Self {
+        self.roots = Some((roots, store));
+        self
+    }
+
+    /// The `lfs/` directory that holds a repository's objects.
+    fn lfs_dir(&self, repo_id: &str) -> PathBuf {
+        match &self.roots {
+            Some((roots, store)) => roots.repo_root(store.as_ref(), repo_id).join("lfs"),
+            None => self.base_path.clone(),
+        }
+    }
+
+    fn object_path(&self, repo_id: &str, oid: &str) -> PathBuf {
+        self.lfs_dir(repo_id).join(object_subpath(oid))
+    }
+
+    fn legacy_object_path(&self, repo_id: &str, oid: &str) -> PathBuf {
+        self.lfs_dir(repo_id)
+            .join(repo_id)
+            .join(object_subpath(oid))
+    }
+
+    /// The pooled object, moving the repository's own copy into the pool
+    /// if it predates it.
+    async fn locate(&self, repo_id: &str, oid: &str) -> Result<Option<PathBuf>, LfsStorageError> {
+        validate_oid(oid)?;
+        let path = self.object_path(repo_id, oid);
+        if fs::try_exists(&path).await? {
+            return Ok(Some(path));
+        }
+
+        let legacy = self.legacy_object_path(repo_id, oid);
+        if !fs::try_exists(&legacy).await? {
+            return Ok(None);
+        }
+        match self.install(&legacy, &path).await {
+            Ok(()) => Ok(Some(path)),
+            // Another request pooled it first.
+            Err(LfsStorageError::Io(e)) if e.kind() == ErrorKind::NotFound => {
+                Ok(fs::try_exists(&path).await?.then_some(path))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Holds the repository's upload lock until the returned file is dropped.
+    async fn lock_repo(&self, repo_id: &str) -> Result<std::fs::File, LfsStorageError> {
+        let dir = self.lfs_dir(repo_id).join(repo_id);
+        fs::create_dir_all(&dir).await?;
+        let path = dir.join("upload.lock");
+        let file = tokio::task::spawn_blocking(move || {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(path)?;
+            netfs::lock_exclusive(&file)?;
+            Ok::<_, std::io::Error>(file)
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+        Ok(file)
+    }
+
+    fn temp_path(&self, repo_id: &str) -> PathBuf {
+        self.lfs_dir(repo_id)
+            .join(repo_id)
+            .join("tmp")
+            .join(Uuid::new_v4().to_string())
+    }
+
+    async fn put_data(
+        &self,
+        repo_id: &str,
+        oid: &str,
+        data: &[u8],
+        expected_size: i64,
+    ) -> Result<(), LfsStorageError> {
+        verify_data(oid, data, expected_size)?;
+
+        let _lock = if self.network {
+            Some(self.lock_repo(repo_id).await?)
+        } else {
+            None
+        };
+        let final_path = self.object_path(repo_id, oid);
+        if fs::try_exists(&final_path).await? {
+            // Already pooled, possibly by another repository.
+            return Ok(());
+        }
+
+        let temp_path = self.temp_path(repo_id);
+        if let Some(parent) = temp_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let mut temp_file = File::create(&temp_path).await?;
+        temp_file.write_all(data).await?;
+        temp_file.sync_all().await?;
+
+        self.install(&temp_path, &final_path).await
+    }
+
+    async fn put_staged(
+        &self,
+        repo_id: &str,
+        oid: &str,
+        staged: &Path,
+    ) -> Result<i64, LfsStorageError> {
+        let size = verify_file(oid, staged).await?;
+
+        let _lock = if self.network {
+            Some(self.lock_repo(repo_id).await?)
+        } else {
+            None
+        };
+        let final_path = self.object_path(repo_id, oid);
+        if fs::try_exists(&final_path).await? {
+            fs::remove_file(staged).await?;
+            return Ok(size);
+        }
+        self.install(staged, &final_path).await?;
+        Ok(size)
+    }
+
+    async fn install(&self, temp_path: &Path, final_path: &Path) -> Result<(), LfsStorageError> {
+        if let Some(parent) = final_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::rename(temp_path, final_path).await?;
+
+        if let Some(parent) = final_path.parent().filter(|_| self.network) {
+            let parent = parent.to_path_buf();
+            tokio::task::spawn_blocking(move || netfs::sync_dir(&parent))
+                .await
+                .map_err(std::io::Error::other)??;
+        }
+
+        Ok(())
+    }
+}
+
+/// `objects/<aa>/<bb>/<oid>`, relative to the pool or a repository's
+/// directory.
+fn object_subpath(oid: &str) -> PathBuf {
+    Path::new("objects").join(&oid[0..2]).join(&oid[2..4]).join(oid)
+}
+
+impl LfsStorage for FsStorage {
+    fn exists<'a>(
+        &'a self,
+        repo_id: &'a str,
+        oid: &'a str,
+    ) -> BoxFuture<'a, Result<bool, LfsStorageError>> {
+        Box::pin(async move { Ok(self.locate(repo_id, oid).await?.is_some()) })
+    }
+
+    fn size<'a>(
+        &'a self,
+        repo_id: &'a str,
+        oid: &'a str,
+    ) -> BoxFuture<'a, Result<i64, LfsStorageError>> {
+        Box::pin(async move {
+            let path = self
+                .locate(repo_id, oid)
+                .await?
+                .ok_or(LfsStorageError::NotFound)?;
+            let metadata = fs::metadata(path).await.map_err(LfsStorageError::from_io)?;
+            Ok(metadata.len() as i64)
+        })
+    }
+
+    fn get<'a>(
+        &'a self,
+        repo_id: &'a str,
+        oid: &'a str,
+    ) -> BoxFuture<'a, Result<(LfsReader, i64), LfsStorageError>> {
+        Box::pin(async move {
+            let path = self
+                .locate(repo_id, oid)
+                .await?
+                .ok_or(LfsStorageError::NotFound)?;
+            let file = File::open(&path).await.map_err(LfsStorageError::from_io)?;
+
+            let metadata = file.metadata().await?;
+            let size = metadata.len() as i64;
+
+            Ok((Box::new(BufReader::new(file)) as LfsReader, size))
+        })
+    }
+
+    fn put<'a>(
+        &'a self,
+        repo_id: &'a str,
+        oid: &'a str,
+        data: &'a [u8],
+        expected_size: i64,
+    ) -> BoxFuture<'a, Result<(), LfsStorageError>> {
+        Box::pin(self.put_data(repo_id, oid, data, expected_size))
+    }
+
+    fn upload_path(&self, repo_id: &str, upload_id: &Uuid) -> PathBuf {
+        staged_upload_path(&self.lfs_dir(repo_id), repo_id, upload_id)
+    }
+
+    fn put_file<'a>(
+        &'a self,
+        repo_id: &'a str,
+        oid: &'a str,
+        staged: &'a Path,
+    ) -> BoxFuture<'a, Result<i64, LfsStorageError>> {
+        Box::pin(async move {
+            let result = self.put_staged(repo_id, oid, staged).await;
+            if result.is_err() {
+                let _ = fs::remove_file(staged).await;
+            }
+            result
+        })
+    }
+
+    fn delete<'a>(&'a self, oid: &'a str) -> BoxFuture<'a, Result<bool, LfsStorageError>> {
+        Box::pin(async move {
+            validate_oid(oid)?;
+            // The object may be pooled in every root that has a repository
+            // referencing it.
+            let lfs_dirs = match &self.roots {
+                Some((roots, _)) => roots.all().iter().map(|root| root.join("lfs")).collect(),
+                None => vec![self.base_path.clone()],
+            };
+            let mut deleted = false;
+            for lfs_dir in lfs_dirs {
+                match fs::remove_file(lfs_dir.join(object_subpath(oid))).await {
+                    Ok(()) => deleted = true,
+                    Err(e) if e.kind() == ErrorKind::NotFound => {}
+                    Err(e) => return Err(LfsStorageError::Io(e)),
+                }
+            }
+            Ok(deleted)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+
+    fn test_oid() -> String {
+        "a665a45920422f9d417e4867efdc4fb8a04a1f3fff1fa07e998e86f7f7a27ae3".to_string()
+    }
+
+    fn test_data() -> Vec<u8> {
+        b"123".to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_put_and_get() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FsStorage::new(temp_dir.path());
+
+        let oid = test_oid();
+        let data = test_data();
+        let repo_id = "test-repo";
+
+        storage
+            .put(repo_id, &oid, &data, data.len() as i64)
+            .await
+            .unwrap();
+
+        assert!(storage.exists(repo_id, &oid).await.unwrap());
+        assert_eq!(
+            storage.size(repo_id, &oid).await.unwrap(),
+            data.len() as i64
+        );
+
+        let (mut reader, size) = storage.get(repo_id, &oid).await.unwrap();
+        assert_eq!(size, data.len() as i64);
+
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).await.unwrap();
+        assert_eq!(content, data);
+    }
+
+    #[tokio::test]
+    async fn test_network_mode_put_is_idempotent() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FsStorage::new(temp_dir.path()).with_network_mode(true);
+
+        let oid = test_oid();
+        let data = test_data();
+        let repo_id = "test-repo";
+
+        for _ in 0..2 {
+            storage
+                .put(repo_id, &oid, &data, data.len() as i64)
+                .await
+                .unwrap();
+        }
+
+        assert!(storage.exists(repo_id, &oid).await.unwrap());
+        assert!(temp_dir.path().join("lfs/test-repo/upload.lock").exists());
+    }
+
+    #[tokio::test]
+    async fn test_put_file_verifies_staged_upload() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FsStorage::new(temp_dir.path());
+
+        let oid = test_oid();
+        let data = test_data();
+        let repo_id = "test-repo";
+
+        let staged = storage.upload_path(repo_id, &Uuid::new_v4());
+        fs::create_dir_all(staged.parent().unwrap()).await.unwrap();
+        fs::write(&staged, &data).await.unwrap();
+        let wrong_oid = "0000000000000000000000000000000000000000000000000000000000000000";
+        assert!(matches!(
+            storage.put_file(repo_id, wrong_oid, &staged).await,
+            Err(LfsStorageError::HashMismatch { .. })
+        ));
+        assert!(!staged.exists());
+
+        fs::write(&staged, &data).await.unwrap();
+        let size = storage.put_file(repo_id, &oid, &staged).await.unwrap();
+        assert_eq!(size, data.len() as i64);
+        assert!(!staged.exists());
+        assert!(storage.exists(repo_id, &oid).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_hash_mismatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FsStorage::new(temp_dir.path());
+
+        let wrong_oid = "0000000000000000000000000000000000000000000000000000000000000000";
+        let data = test_data();
+        let repo_id = "test-repo";
+
+        let result = storage
+            .put(repo_id, wrong_oid, &data, data.len() as i64)
+            .await;
+        assert!(matches!(result, Err(LfsStorageError::HashMismatch { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_oid() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FsStorage::new(temp_dir.path());
+
+        assert!(matches!(
+            storage.exists("repo", "invalid").await,
+            Err(LfsStorageError::InvalidOid)
+        ));
+
+        assert!(matches!(
+            storage
+                .exists(
+                    "repo",
+                    "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"
+                )
+                .await,
+            Err(LfsStorageError::InvalidOid)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_not_found() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FsStorage::new(temp_dir.path());
+
+        let oid = test_oid();
+        assert!(!storage.exists("repo", &oid).await.unwrap());
+        assert!(matches!(
+            storage.get("repo", &oid).await,
+            Err(LfsStorageError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FsStorage::new(temp_dir.path());
+
+        let oid = test_oid();
+        let data = test_data();
+        let repo_id = "test-repo";
+
+        storage
+            .put(repo_id, &oid, &data, data.len() as i64)
+            .await
+            .unwrap();
+        assert!(storage.delete(&oid).await.unwrap());
+        assert!(!storage.exists(repo_id, &oid).await.unwrap());
+        assert!(!storage.delete(&oid).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_objects_are_pooled_across_repos() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FsStorage::new(temp_dir.path());
+
+        let oid = test_oid();
+        let data = test_data();
+        for repo_id in ["repo-a", "repo-b"] {
+            storage
+                .put(repo_id, &oid, &data, data.len() as i64)
+                .await
+                .unwrap();
+        }
+
+        let pooled = temp_dir.path().join("lfs/objects/a6/65").join(&oid);
+        assert!(pooled.exists());
+        assert!(!temp_dir.path().join("lfs/repo-a/objects").exists());
+        assert!(!temp_dir.path().join("lfs/repo-b/objects").exists());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_objects_move_into_pool() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FsStorage::new(temp_dir.path());
+
+        let oid = test_oid();
+        let data = test_data();
+        let legacy = temp_dir.path().join("lfs/repo-a/objects/a6/65").join(&oid);
+        fs::create_dir_all(legacy.parent().unwrap()).await.unwrap();
+        fs::write(&legacy, &data).await.unwrap();
+
+        assert_eq!(
+            storage.size("repo-a", &oid).await.unwrap(),
+            data.len() as i64
+        );
+        assert!(!legacy.exists());
+        assert!(temp_dir.path().join("lfs/objects/a6/65").join(&oid).exists());
+
+        let (mut reader, _) = storage.get("repo-a", &oid).await.unwrap();
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).await.unwrap();
+        assert_eq!(content, data);
+    }
+
+    #[tokio::test]
+    async fn test_objects_follow_namespace_storage_root() {
+        use crate::store::SqliteStore;
+        use crate::types::{Namespace, ObjectFormat, Repo, Visibility};
+
+        let temp_dir = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp_dir.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+        let now = chrono::Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "archive".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-a".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "old".to_string(),
+                description: None,
+                visibility: Visibility::Private,
+                object_format: ObjectFormat::Sha1,
+                size_bytes: 0,
+                folder_id: None,
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+        store
+            .set_namespace_storage_root("ns-1", Some("hdd"))
+            .unwrap();
+
+        let hdd = temp_dir.path().join("hdd");
+        let roots = StorageRoots::new(
+            temp_dir.path().to_path_buf(),
+            std::collections::HashMap::from([("hdd".to_string(), hdd.clone())]),
+        );
+        let storage =
+            FsStorage::new(temp_dir.path()).with_storage_roots(Arc::new(roots), Arc::new(store));
+
+        let oid = test_oid();
+        let data = test_data();
+        storage
+            .put("repo-a", &oid, &data, data.len() as i64)
+            .await
+            .unwrap();
+        assert!(hdd.join("lfs/objects/a6/65").join(&oid).exists());
+        assert!(!temp_dir.path().join("lfs/objects").exists());
+        assert!(storage.exists("repo-a", &oid).await.unwrap());
+        assert!(!storage.exists("repo-b", &oid).await.unwrap());
+
+        assert!(storage.delete(&oid).await.unwrap());
+        assert!(!hdd.join("lfs/objects/a6/65").join(&oid).exists());
+    }
+}
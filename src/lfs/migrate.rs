@@ -0,0 +1,250 @@
+//! Copies pooled LFS objects from one backend to another, so objects can
+//! move (say, from the data directory to S3) while the server keeps
+//! serving them from where they are. Every object is staged locally,
+//! checked against its OID on the way in, then read back from the
+//! destination and hashed again. Objects the destination already holds
+//! at the right size are skipped, so a migration can be run again to pick
+//! up what was pushed since.
+
+use futures_util::{StreamExt, stream};
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::storage::{LfsStorage, LfsStorageError, hash_reader};
+use crate::store::Store;
+use crate::types::LfsObject;
+
+/// Objects read from the store per query.
+const BATCH_SIZE: i32 = 500;
+
+#[derive(Debug, Clone, Copy)]
+pub struct MigrateOptions {
+    /// Objects copied at once.
+    pub jobs: usize,
+    /// Re-hash objects the destination already has, not just copied ones.
+    pub verify_existing: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub copied: u64,
+    pub copied_bytes: u64,
+    /// Objects the destination already had.
+    pub present: u64,
+    /// OIDs that couldn't be copied or verified, with why.
+    pub failed: Vec<(String, String)>,
+}
+
+enum Outcome {
+    Copied(u64),
+    Present,
+}
+
+/// Copies every referenced object from `source` to `dest`.
+pub async fn migrate_lfs_objects(
+    store: &dyn Store,
+    source: &dyn LfsStorage,
+    dest: &dyn LfsStorage,
+    options: MigrateOptions,
+) -> crate::error::Result<MigrationReport> {
+    let mut report = MigrationReport::default();
+    let mut after = String::new();
+    loop {
+        let batch = store.list_pooled_lfs_objects(&after, BATCH_SIZE)?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after = last.oid.clone();
+
+        let mut results = stream::iter(&batch)
+            .map(|obj| async move {
+                let result = migrate_object(source, dest, obj, options.verify_existing).await;
+                (obj, result)
+            })
+            .buffer_unordered(options.jobs.max(1));
+        while let Some((obj, result)) = results.next().await {
+            match result {
+                Ok(Outcome::Copied(size)) => {
+                    report.copied += 1;
+                    report.copied_bytes += size;
+                }
+                Ok(Outcome::Present) => report.present += 1,
+                Err(e) => {
+                    warn!("Failed to migrate LFS object {}: {e}", obj.oid);
+                    report.failed.push((obj.oid.clone(), e.to_string()));
+                }
+            }
+        }
+        info!(
+            "Migrated {} LFS objects, {} already present, {} failed",
+            report.copied,
+            report.present,
+            report.failed.len()
+        );
+    }
+    Ok(report)
+}
+
+async fn migrate_object(
+    source: &dyn LfsStorage,
+    dest: &dyn LfsStorage,
+    obj: &LfsObject,
+    verify_existing: bool,
+) -> Result<Outcome, LfsStorageError> {
+    let size = source.size(&obj.repo_id, &obj.oid).await?;
+    match dest.size(&obj.repo_id, &obj.oid).await {
+        Ok(existing) if existing == size => {
+            if verify_existing {
+                verify(dest, obj, size).await?;
+            }
+            return Ok(Outcome::Present);
+        }
+        Ok(_) | Err(LfsStorageError::NotFound) => {}
+        Err(e) => return Err(e),
+    }
+
+    let staged = dest.upload_path(&obj.repo_id, &Uuid::new_v4());
+    if let Some(parent) = staged.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    if let Err(e) = stage(source, obj, &staged).await {
+        let _ = fs::remove_file(&staged).await;
+        return Err(e);
+    }
+    dest.put_file(&obj.repo_id, &obj.oid, &staged).await?;
+    verify(dest, obj, size).await?;
+    Ok(Outcome::Copied(size as u64))
+}
+
+async fn stage(
+    source: &dyn LfsStorage,
+    obj: &LfsObject,
+    staged: &std::path::Path,
+) -> Result<(), LfsStorageError> {
+    let (mut reader, _) = source.get(&obj.repo_id, &obj.oid).await?;
+    let mut file = File::create(staged).await?;
+    tokio::io::copy(&mut reader, &mut file).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+/// Reads the object back from `dest` and checks it against its OID.
+async fn verify(dest: &dyn LfsStorage, obj: &LfsObject, size: i64) -> Result<(), LfsStorageError> {
+    let (reader, _) = dest.get(&obj.repo_id, &obj.oid).await?;
+    let (actual_hash, actual_size) = hash_reader(reader).await?;
+    if actual_hash != obj.oid || actual_size != size {
+        return Err(LfsStorageError::HashMismatch {
+            expected: obj.oid.clone(),
+            actual: actual_hash,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lfs::FsStorage;
+    use crate::store::SqliteStore;
+    use crate::types::{Namespace, ObjectFormat, Repo, Visibility};
+    use chrono::Utc;
+    use sha2::{Digest, Sha256};
+    use tempfile::TempDir;
+
+    const OPTIONS: MigrateOptions = MigrateOptions {
+        jobs: 2,
+        verify_existing: false,
+    };
+
+    fn test_store(dir: &TempDir) -> SqliteStore {
+        let store = SqliteStore::new(dir.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-1".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "repo".to_string(),
+                description: None,
+                visibility: Visibility::Private,
+                object_format: ObjectFormat::Sha1,
+                size_bytes: 0,
+                folder_id: None,
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+        store
+    }
+
+    async fn add_object(store: &SqliteStore, storage: &FsStorage, data: &[u8]) -> String {
+        let oid = hex::encode(Sha256::digest(data));
+        storage
+            .put("repo-1", &oid, data, data.len() as i64)
+            .await
+            .unwrap();
+        store
+            .create_lfs_object(&LfsObject {
+                repo_id: "repo-1".to_string(),
+                oid: oid.clone(),
+                size: data.len() as i64,
+                created_at: Utc::now(),
+            })
+            .unwrap();
+        oid
+    }
+
+    #[tokio::test]
+    async fn test_migrate_copies_and_verifies_objects() {
+        let source_dir = TempDir::new().unwrap();
+        let dest_dir = TempDir::new().unwrap();
+        let store = test_store(&source_dir);
+        let source = FsStorage::new(source_dir.path());
+        let dest = FsStorage::new(dest_dir.path());
+
+        let first = add_object(&store, &source, b"first").await;
+        let report = migrate_lfs_objects(&store, &source, &dest, OPTIONS)
+            .await
+            .unwrap();
+        assert_eq!((report.copied, report.present), (1, 0));
+        assert_eq!(report.copied_bytes, 5);
+        assert!(report.failed.is_empty());
+        assert!(dest.exists("repo-1", &first).await.unwrap());
+
+        // A second run copies only what arrived since.
+        add_object(&store, &source, b"second").await;
+        let report = migrate_lfs_objects(&store, &source, &dest, OPTIONS)
+            .await
+            .unwrap();
+        assert_eq!((report.copied, report.present), (1, 1));
+
+        // A damaged source object is reported rather than copied.
+        let damaged = add_object(&store, &source, b"third").await;
+        let path = source_dir.path().join(format!(
+            "lfs/objects/{}/{}/{damaged}",
+            &damaged[0..2],
+            &damaged[2..4]
+        ));
+        fs::write(&path, b"THIRD").await.unwrap();
+        let report = migrate_lfs_objects(&store, &source, &dest, OPTIONS)
+            .await
+            .unwrap();
+        assert_eq!((report.copied, report.present), (0, 2));
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, damaged);
+        assert!(!dest.exists("repo-1", &damaged).await.unwrap());
+    }
+}
@@ -1,3 +1,16 @@
+mod fs;
+mod migrate;
+mod pointer;
+#[cfg(feature = "s3")]
+mod s3;
 mod storage;
 
-pub use storage::{LfsStorage, LfsStorageError, is_valid_oid};
+pub use fs::FsStorage;
+pub use migrate::{MigrateOptions, MigrationReport, migrate_lfs_objects};
+pub use pointer::LfsPointer;
+#[cfg(feature = "s3")]
+pub use s3::S3Storage;
+pub use storage::{
+    LfsReader, LfsStorage, LfsStorageError, PresignedRequest, PresignedTransfer, is_valid_oid,
+};
+pub(crate) use storage::hash_reader;
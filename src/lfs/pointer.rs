@@ -0,0 +1,79 @@
+use super::is_valid_oid;
+
+/// Pointer files are never larger than this, so bigger blobs aren't read.
+const MAX_POINTER_SIZE: usize = 1024;
+
+const VERSION_LINE: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// The object a Git LFS pointer file stands in for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsPointer {
+    pub oid: String,
+    pub size: i64,
+}
+
+impl LfsPointer {
+    /// Parses a blob's content as a pointer file, returning `None` if it
+    /// isn't one. Keys other than `oid` and `size` are ignored.
+    pub fn parse(content: &[u8]) -> Option<Self> {
+        if content.len() > MAX_POINTER_SIZE {
+            return None;
+        }
+        let text = std::str::from_utf8(content).ok()?;
+        let mut lines = text.lines();
+        if lines.next()? != VERSION_LINE {
+            return None;
+        }
+
+        let mut oid = None;
+        let mut size = None;
+        for line in lines {
+            let (key, value) = line.split_once(' ')?;
+            match key {
+                "oid" => oid = Some(value.strip_prefix("sha256:")?.to_string()),
+                "size" => size = Some(value.parse::<i64>().ok().filter(|s| *s >= 0)?),
+                _ => {}
+            }
+        }
+
+        let oid = oid.filter(|oid| is_valid_oid(oid))?;
+        Some(Self { oid, size: size? })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OID: &str = "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393";
+
+    #[test]
+    fn test_parse_pointer() {
+        let pointer = format!("{VERSION_LINE}\noid sha256:{OID}\nsize 12345\n");
+        assert_eq!(
+            LfsPointer::parse(pointer.as_bytes()),
+            Some(LfsPointer {
+                oid: OID.to_string(),
+                size: 12345,
+            })
+        );
+
+        let extended =
+            format!("{VERSION_LINE}\next-0-foo sha256:{OID}\noid sha256:{OID}\nsize 0\n");
+        assert!(LfsPointer::parse(extended.as_bytes()).is_some());
+
+        for invalid in [
+            String::from("hello world\n"),
+            format!("{VERSION_LINE}\noid sha256:{OID}\n"),
+            format!("{VERSION_LINE}\noid md5:{OID}\nsize 1\n"),
+            format!("{VERSION_LINE}\noid sha256:abc\nsize 1\n"),
+            format!("{VERSION_LINE}\noid sha256:{OID}\nsize -1\n"),
+            format!(
+                "{VERSION_LINE}\noid sha256:{OID}\nsize 1\n{}",
+                "x".repeat(1024)
+            ),
+        ] {
+            assert_eq!(LfsPointer::parse(invalid.as_bytes()), None, "{invalid}");
+        }
+    }
+}
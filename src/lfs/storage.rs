@@ -1,10 +1,13 @@
+use std::collections::HashMap;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
+use futures_util::future::BoxFuture;
 use sha2::{Digest, Sha256};
 use thiserror::Error;
-use tokio::fs::{self, File};
-use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt};
 use uuid::Uuid;
 
 #[derive(Debug, Error)]
@@ -17,10 +20,12 @@ pub enum LfsStorageError {
     InvalidOid,
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("object storage error: {0}")]
+    Backend(String),
 }
 
 impl LfsStorageError {
-    fn from_io(e: std::io::Error) -> Self {
+    pub(crate) fn from_io(e: std::io::Error) -> Self {
         if e.kind() == ErrorKind::NotFound {
             Self::NotFound
         } else {
@@ -29,124 +34,170 @@ impl LfsStorageError {
     }
 }
 
-pub struct LfsStorage {
-    base_path: PathBuf,
+/// An object's content, as returned by [`LfsStorage::get`].
+pub type LfsReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Which way a presigned URL moves an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresignedTransfer {
+    Download,
+    Upload,
 }
 
-impl LfsStorage {
-    pub fn new(data_dir: &Path) -> Self {
-        Self {
-            base_path: data_dir.join("lfs"),
-        }
-    }
+/// A request a client can make straight to the backend.
+#[derive(Debug, Clone)]
+pub struct PresignedRequest {
+    pub href: String,
+    /// Headers the request must carry; they are part of the signature.
+    pub headers: HashMap<String, String>,
+}
 
-    fn object_path(&self, repo_id: &str, oid: &str) -> PathBuf {
-        let prefix1 = &oid[0..2];
-        let prefix2 = &oid[2..4];
-        self.base_path
-            .join(repo_id)
-            .join("objects")
-            .join(prefix1)
-            .join(prefix2)
-            .join(oid)
+/// Where LFS objects and registry blobs are kept. Objects are verified
+/// against their SHA-256 OID before they are stored, and are pooled by it:
+/// each is stored once however many repositories reference it. Which
+/// repositories may read an object is up to their records in the store,
+/// not the backend.
+///
+/// `repo_id` names the repository an object is read or written for.
+/// Objects stored per repository before the pool existed are found through
+/// it and moved into the pool on first use.
+///
+/// Uploads sent in several requests are staged on local disk at
+/// [`upload_path`](LfsStorage::upload_path) whatever the backend, so the
+/// temp sweeper can clean up after abandoned ones.
+pub trait LfsStorage: Send + Sync {
+    fn exists<'a>(
+        &'a self,
+        repo_id: &'a str,
+        oid: &'a str,
+    ) -> BoxFuture<'a, Result<bool, LfsStorageError>>;
+
+    fn size<'a>(
+        &'a self,
+        repo_id: &'a str,
+        oid: &'a str,
+    ) -> BoxFuture<'a, Result<i64, LfsStorageError>>;
+
+    /// The object's content and size.
+    fn get<'a>(
+        &'a self,
+        repo_id: &'a str,
+        oid: &'a str,
+    ) -> BoxFuture<'a, Result<(LfsReader, i64), LfsStorageError>>;
+
+    fn put<'a>(
+        &'a self,
+        repo_id: &'a str,
+        oid: &'a str,
+        data: &'a [u8],
+        expected_size: i64,
+    ) -> BoxFuture<'a, Result<(), LfsStorageError>>;
+
+    /// Where an upload written in several requests is staged. Uploads left
+    /// there are removed by the temp sweeper.
+    fn upload_path(&self, repo_id: &str, upload_id: &Uuid) -> PathBuf;
+
+    /// Moves a staged upload into place as `oid` once its content is
+    /// verified, returning its size. The staged file is removed either way.
+    fn put_file<'a>(
+        &'a self,
+        repo_id: &'a str,
+        oid: &'a str,
+        staged: &'a Path,
+    ) -> BoxFuture<'a, Result<i64, LfsStorageError>>;
+
+    /// Removes a pooled object, returning whether it existed. Only done
+    /// once no repository references it.
+    fn delete<'a>(&'a self, oid: &'a str) -> BoxFuture<'a, Result<bool, LfsStorageError>>;
+
+    /// Whether [`presign`](LfsStorage::presign) hands out URLs, so objects
+    /// can move between clients and the backend without passing through
+    /// the server.
+    fn can_presign(&self) -> bool {
+        false
+    }
+
+    /// A URL valid for `expires_in` that transfers `oid` to or from the
+    /// pool directly. Uploads through it must still be verified by the
+    /// client before the object counts as stored, and downloads need an
+    /// [`exists`](LfsStorage::exists) check first to pool older objects.
+    fn presign(
+        &self,
+        _oid: &str,
+        _transfer: PresignedTransfer,
+        _expires_in: Duration,
+    ) -> Option<PresignedRequest> {
+        None
     }
+}
 
-    fn temp_path(&self, repo_id: &str) -> PathBuf {
-        self.base_path
-            .join(repo_id)
-            .join("tmp")
-            .join(Uuid::new_v4().to_string())
-    }
+/// Staged uploads live under `lfs/<repo>/tmp/` in the data directory.
+pub(crate) fn staged_upload_path(lfs_dir: &Path, repo_id: &str, upload_id: &Uuid) -> PathBuf {
+    lfs_dir
+        .join(repo_id)
+        .join("tmp")
+        .join(format!("upload-{upload_id}"))
+}
 
-    pub async fn exists(&self, repo_id: &str, oid: &str) -> Result<bool, LfsStorageError> {
-        validate_oid(oid)?;
-        let path = self.object_path(repo_id, oid);
-        Ok(path.exists())
-    }
+/// Checks an upload held in memory against its declared size and OID.
+pub(crate) fn verify_data(
+    oid: &str,
+    data: &[u8],
+    expected_size: i64,
+) -> Result<(), LfsStorageError> {
+    validate_oid(oid)?;
 
-    pub async fn size(&self, repo_id: &str, oid: &str) -> Result<i64, LfsStorageError> {
-        validate_oid(oid)?;
-        let path = self.object_path(repo_id, oid);
-        let metadata = fs::metadata(&path)
-            .await
-            .map_err(LfsStorageError::from_io)?;
-        Ok(metadata.len() as i64)
+    if data.len() as i64 != expected_size {
+        return Err(LfsStorageError::HashMismatch {
+            expected: format!("size {expected_size}"),
+            actual: format!("size {}", data.len()),
+        });
     }
 
-    pub async fn get(
-        &self,
-        repo_id: &str,
-        oid: &str,
-    ) -> Result<(BufReader<File>, i64), LfsStorageError> {
-        validate_oid(oid)?;
-        let path = self.object_path(repo_id, oid);
-        let file = File::open(&path).await.map_err(LfsStorageError::from_io)?;
-
-        let metadata = file.metadata().await?;
-        let size = metadata.len() as i64;
-
-        Ok((BufReader::new(file), size))
+    let actual_hash = hex::encode(Sha256::digest(data));
+    if actual_hash != oid {
+        return Err(LfsStorageError::HashMismatch {
+            expected: oid.to_string(),
+            actual: actual_hash,
+        });
     }
+    Ok(())
+}
 
-    pub async fn put(
-        &self,
-        repo_id: &str,
-        oid: &str,
-        data: &[u8],
-        expected_size: i64,
-    ) -> Result<(), LfsStorageError> {
-        validate_oid(oid)?;
-
-        if data.len() as i64 != expected_size {
-            return Err(LfsStorageError::HashMismatch {
-                expected: format!("size {expected_size}"),
-                actual: format!("size {}", data.len()),
-            });
-        }
-
-        let mut hasher = Sha256::new();
-        hasher.update(data);
-        let actual_hash = hex::encode(hasher.finalize());
-
-        if actual_hash != oid {
-            return Err(LfsStorageError::HashMismatch {
-                expected: oid.to_string(),
-                actual: actual_hash,
-            });
-        }
-
-        let temp_path = self.temp_path(repo_id);
-        if let Some(parent) = temp_path.parent() {
-            fs::create_dir_all(parent).await?;
-        }
-
-        let mut temp_file = File::create(&temp_path).await?;
-        temp_file.write_all(data).await?;
-        temp_file.sync_all().await?;
-
-        let final_path = self.object_path(repo_id, oid);
-        if let Some(parent) = final_path.parent() {
-            fs::create_dir_all(parent).await?;
-        }
-
-        fs::rename(&temp_path, &final_path).await?;
+/// Checks a staged upload against its OID, returning its size.
+pub(crate) async fn verify_file(oid: &str, staged: &Path) -> Result<i64, LfsStorageError> {
+    validate_oid(oid)?;
 
-        Ok(())
+    let file = File::open(staged).await.map_err(LfsStorageError::from_io)?;
+    let (actual_hash, size) = hash_reader(file).await?;
+    if actual_hash != oid {
+        return Err(LfsStorageError::HashMismatch {
+            expected: oid.to_string(),
+            actual: actual_hash,
+        });
     }
+    Ok(size)
+}
 
-    pub async fn delete(&self, repo_id: &str, oid: &str) -> Result<bool, LfsStorageError> {
-        validate_oid(oid)?;
-        let path = self.object_path(repo_id, oid);
-
-        match fs::remove_file(&path).await {
-            Ok(()) => Ok(true),
-            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
-            Err(e) => Err(LfsStorageError::Io(e)),
+/// The hex SHA-256 of everything `reader` yields, and its size.
+pub(crate) async fn hash_reader(
+    mut reader: impl AsyncRead + Unpin,
+) -> Result<(String, i64), LfsStorageError> {
+    let mut hasher = Sha256::new();
+    let mut size = 0i64;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
         }
+        hasher.update(&buf[..n]);
+        size += n as i64;
     }
+    Ok((hex::encode(hasher.finalize()), size))
 }
 
-fn validate_oid(oid: &str) -> Result<(), LfsStorageError> {
+pub(crate) fn validate_oid(oid: &str) -> Result<(), LfsStorageError> {
     if oid.len() != 64 {
         return Err(LfsStorageError::InvalidOid);
     }
@@ -169,111 +220,6 @@ pub fn is_valid_oid(oid: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::TempDir;
-    use tokio::io::AsyncReadExt;
-
-    fn test_oid() -> String {
-        "a665a45920422f9d417e4867efdc4fb8a04a1f3fff1fa07e998e86f7f7a27ae3".to_string()
-    }
-
-    fn test_data() -> Vec<u8> {
-        b"123".to_vec()
-    }
-
-    #[tokio::test]
-    async fn test_put_and_get() {
-        let temp_dir = TempDir::new().unwrap();
-        let storage = LfsStorage::new(temp_dir.path());
-
-        let oid = test_oid();
-        let data = test_data();
-        let repo_id = "test-repo";
-
-        storage
-            .put(repo_id, &oid, &data, data.len() as i64)
-            .await
-            .unwrap();
-
-        assert!(storage.exists(repo_id, &oid).await.unwrap());
-        assert_eq!(
-            storage.size(repo_id, &oid).await.unwrap(),
-            data.len() as i64
-        );
-
-        let (mut reader, size) = storage.get(repo_id, &oid).await.unwrap();
-        assert_eq!(size, data.len() as i64);
-
-        let mut content = Vec::new();
-        reader.read_to_end(&mut content).await.unwrap();
-        assert_eq!(content, data);
-    }
-
-    #[tokio::test]
-    async fn test_hash_mismatch() {
-        let temp_dir = TempDir::new().unwrap();
-        let storage = LfsStorage::new(temp_dir.path());
-
-        let wrong_oid = "0000000000000000000000000000000000000000000000000000000000000000";
-        let data = test_data();
-        let repo_id = "test-repo";
-
-        let result = storage
-            .put(repo_id, wrong_oid, &data, data.len() as i64)
-            .await;
-        assert!(matches!(result, Err(LfsStorageError::HashMismatch { .. })));
-    }
-
-    #[tokio::test]
-    async fn test_invalid_oid() {
-        let temp_dir = TempDir::new().unwrap();
-        let storage = LfsStorage::new(temp_dir.path());
-
-        assert!(matches!(
-            storage.exists("repo", "invalid").await,
-            Err(LfsStorageError::InvalidOid)
-        ));
-
-        assert!(matches!(
-            storage
-                .exists(
-                    "repo",
-                    "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"
-                )
-                .await,
-            Err(LfsStorageError::InvalidOid)
-        ));
-    }
-
-    #[tokio::test]
-    async fn test_not_found() {
-        let temp_dir = TempDir::new().unwrap();
-        let storage = LfsStorage::new(temp_dir.path());
-
-        let oid = test_oid();
-        assert!(!storage.exists("repo", &oid).await.unwrap());
-        assert!(matches!(
-            storage.get("repo", &oid).await,
-            Err(LfsStorageError::NotFound)
-        ));
-    }
-
-    #[tokio::test]
-    async fn test_delete() {
-        let temp_dir = TempDir::new().unwrap();
-        let storage = LfsStorage::new(temp_dir.path());
-
-        let oid = test_oid();
-        let data = test_data();
-        let repo_id = "test-repo";
-
-        storage
-            .put(repo_id, &oid, &data, data.len() as i64)
-            .await
-            .unwrap();
-        assert!(storage.delete(repo_id, &oid).await.unwrap());
-        assert!(!storage.exists(repo_id, &oid).await.unwrap());
-        assert!(!storage.delete(repo_id, &oid).await.unwrap());
-    }
 
     #[test]
     fn test_is_valid_oid() {
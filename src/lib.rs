@@ -27,16 +27,42 @@
 //! // Serve with axum...
 //! ```
 //!
+//! Pushes are refused until the state has receive hooks
+//! (`AppState::with_hooks`), since quotas, path grants, legal holds, and push
+//! policies are enforced by them. Install the hook shims with
+//! `HookSettings::install`, pointing them at a binary that dispatches to
+//! `cutman::hooks::run_hook`, as `cutman serve` does.
+//!
 //! ## Feature Flags
 //!
 //! - `cli` (default): Includes CLI module. Disable with `default-features = false`.
+//! - `tls` (default): Serves HTTPS directly (`tls_cert` and `tls_key` in the
+//!   server config).
+//! - `acme` (default): Obtains and renews the HTTPS certificate from Let's
+//!   Encrypt or another ACME CA (`[acme]` in the server config).
+//! - `otel`: Exports request traces to an OpenTelemetry collector (`[tracing]`
+//!   in the server config).
+//! - `nats`, `kafka`: Publish server events to NATS or Kafka (`[events]` in
+//!   the server config).
 
 pub mod auth;
 #[cfg(feature = "cli")]
 pub mod cli;
+pub mod commit_policy;
 pub mod config;
+pub mod email_policy;
 pub mod error;
+pub mod grant_paths;
+pub mod hooks;
+pub mod legal_hold;
 pub mod lfs;
+pub mod netfs;
+pub mod push_alerts;
+pub mod push_limits;
+pub mod quota;
 pub mod server;
+pub mod storage_roots;
 pub mod store;
+pub mod telemetry;
 pub mod types;
+pub mod units;
@@ -1,32 +1,59 @@
-use std::fs;
+use std::future::IntoFuture;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::bail;
+#[cfg(feature = "tls")]
+use axum::serve::ListenerExt;
 use chrono::Utc;
 use clap::{Parser, Subcommand};
-use tracing::info;
-use tracing_subscriber::EnvFilter;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{info, warn};
 use uuid::Uuid;
 
 use cutman::auth::TokenGenerator;
 use cutman::cli::{
-    AdminCommands, AuthCommands, CredentialCommands, FolderCommands, NamespaceCommands,
-    PermissionCommands, PrincipalCommands, RepoCommands, TagCommands, TokenCommands,
-    print_credential_help, run_auth_login, run_auth_logout, run_credential_erase,
-    run_credential_get, run_credential_store, run_folder_create, run_folder_delete,
-    run_folder_list, run_folder_move, run_info, run_namespace_add, run_namespace_remove, run_new,
-    run_permission_grant, run_permission_repo_grant, run_permission_repo_revoke,
-    run_permission_revoke, run_principal_add, run_principal_remove, run_repo_clone, run_repo_delete,
-    run_repo_move, run_repo_tag, run_tag_create, run_tag_delete, run_token_create, run_token_revoke,
+    AdminCommands, AuthCommands, AutoInit, ConfigCommands, CredentialCommands, FolderCommands,
+    LfsCommands, NamespaceCommands, PermissionCommands, PrincipalCommands, RepoCommands, TagCommands,
+    TokenCommands, create_admin_token, print_credential_help, run_auth_login, run_auth_logout, run_config_get,
+    run_config_set, run_config_validate, run_credential_erase, run_credential_get, run_credential_store,
+    run_folder_create, run_folder_delete, run_folder_list, run_folder_move, run_info,
+    run_lfs_migrate, run_namespace_add, run_namespace_move, run_namespace_remove, run_new, run_permission_grant,
+    run_permission_repo_grant, run_permission_repo_revoke, run_permission_revoke,
+    run_principal_add, run_principal_emails, run_principal_remove, run_repo_clone, run_repo_delete, run_repo_move,
+    run_repo_tag, run_setup, run_setup_server, run_tag_create, run_tag_delete, run_token_create,
+    run_token_legacy, run_token_reissue, run_token_revoke,
+};
+use cutman::config::{
+    MaintenanceTask, RepoNamePolicy, ServerConfig, ServerConfigOverrides, StorageMode,
+};
+use cutman::hooks::{HookKind, HookSettings};
+use cutman::netfs::StorageReport;
+use cutman::push_limits::PushLimits;
+#[cfg(feature = "acme")]
+use cutman::server::acme::{Acme, spawn_acme_renewal};
+use cutman::server::disk::{DiskThresholds, DiskWatchdog, spawn_disk_watchdog};
+use cutman::server::fairness::FairQueue;
+use cutman::server::repo_slots::RepoSlots;
+use cutman::server::sweep::{TempSweeper, spawn_temp_sweeper};
+#[cfg(feature = "tls")]
+use cutman::server::tls::{TlsCertificate, TlsListener, spawn_tls_reload};
+use cutman::server::{
+    AppState, ContentLimits, ExportSync, LfsTransferLimits, Maintenance, MirrorSync, Pages,
+    PagesLimits, SharedObjects, create_router,
+    spawn_change_pruner, spawn_export_sync, spawn_lfs_integrity_check, spawn_lfs_pool_collector,
+    spawn_maintenance, spawn_mirror_sync,
 };
-use cutman::config::{ServerConfig, ServerConfigOverrides};
-use cutman::server::{AppState, create_router};
 use cutman::store::{SqliteStore, Store};
-use cutman::types::{Namespace, Principal, Token};
+use cutman::telemetry;
+use cutman::types::{Namespace, ObjectFormat, Principal, Token, TrafficClass};
+use cutman::units::{parse_duration, parse_size};
 
 fn create_token(
     generator: &TokenGenerator,
-    is_admin: bool,
     principal_id: Option<String>,
 ) -> anyhow::Result<(Token, String)> {
     let (raw_token, lookup, hash) = generator.generate()?;
@@ -34,23 +61,16 @@ fn create_token(
         id: Uuid::new_v4().to_string(),
         token_hash: hash,
         token_lookup: lookup,
-        is_admin,
+        is_admin: false,
         principal_id,
         created_at: Utc::now(),
         expires_at: None,
         last_used_at: None,
+        traffic_class: TrafficClass::default(),
     };
     Ok((token, raw_token))
 }
 
-#[cfg(unix)]
-fn set_restrictive_permissions(path: &std::path::Path) {
-    use std::os::unix::fs::PermissionsExt;
-    if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(0o600)) {
-        tracing::warn!("Failed to set permissions on {}: {e}", path.display());
-    }
-}
-
 #[derive(Parser)]
 #[command(name = "cutman")]
 #[command(about = "A Git hosting server", long_about = None)]
@@ -59,9 +79,11 @@ struct Cli {
     command: Commands,
 }
 
+// Parsed once at startup; `serve` simply has many flags.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
-    /// Administrative commands (requires direct database access)
+    /// Administrative commands (direct database access, or --server for the admin API)
     Admin {
         #[command(subcommand)]
         command: AdminCommands,
@@ -73,9 +95,9 @@ enum Commands {
         #[arg(long, short)]
         config: Option<String>,
 
-        /// Host to bind to (default: 127.0.0.1)
+        /// Address to bind to; repeat for several, e.g. --host 0.0.0.0 --host :: (default: 127.0.0.1)
         #[arg(long)]
-        host: Option<String>,
+        host: Vec<String>,
 
         /// Port to bind to (default: 8080)
         #[arg(long, short)]
@@ -89,6 +111,118 @@ enum Commands {
         /// Used for generating LFS action URLs. If not set, URLs are derived from request headers.
         #[arg(long)]
         public_base_url: Option<String>,
+
+        /// Disable anonymous access, including reads of public repositories
+        #[arg(long)]
+        private_mode: bool,
+
+        /// Repository naming policy (default: ascii)
+        #[arg(long, value_enum)]
+        repo_name_policy: Option<RepoNamePolicy>,
+
+        /// Serve HTTPS with this PEM certificate chain (requires --tls-key)
+        #[arg(long)]
+        tls_cert: Option<String>,
+
+        /// PEM private key for --tls-cert
+        #[arg(long)]
+        tls_key: Option<String>,
+
+        /// Serve git over SSH on this port, authenticated by registered SSH keys
+        #[arg(long)]
+        ssh_port: Option<u16>,
+
+        /// SSH host key path (default: <data_dir>/ssh_host_ed25519_key, generated if missing)
+        #[arg(long)]
+        ssh_host_key: Option<String>,
+
+        /// Serve the gRPC content API on this port (requires the grpc feature)
+        #[arg(long)]
+        grpc_port: Option<u16>,
+
+        /// Receive hook directory (default: <data_dir>/hooks); scripts go in pre-receive.d/ and post-receive.d/
+        #[arg(long)]
+        hooks_dir: Option<String>,
+
+        /// Enable a built-in push hook (repeatable), e.g. protect-default-branch
+        #[arg(long = "builtin-hook")]
+        builtin_hooks: Vec<String>,
+
+        /// Data directory filesystem handling (default: auto-detect NFS/SMB mounts)
+        #[arg(long, value_enum)]
+        storage_mode: Option<StorageMode>,
+
+        /// Run background maintenance on recently pushed repos every N seconds
+        #[arg(long = "maintenance-interval")]
+        maintenance_interval_secs: Option<u64>,
+
+        /// Maintenance command (default: gc)
+        #[arg(long, value_enum)]
+        maintenance_task: Option<MaintenanceTask>,
+
+        /// Skip the commit-graph and bitmap refresh that runs after each push
+        #[arg(long)]
+        no_optimize_after_push: bool,
+
+        /// Bundle repos during maintenance and advertise the bundles to cloning clients
+        #[arg(long)]
+        bundle_uri: bool,
+
+        /// Serve a container registry at /v2/ for images named <namespace>/<repo>
+        #[arg(long)]
+        registry: bool,
+
+        /// Serve repos' default branches read-only over WebDAV at /dav/<namespace>/<repo>/
+        #[arg(long)]
+        webdav: bool,
+
+        /// Publish static sites from repos that enable Pages, at /<namespace>/<repo>/pages/
+        #[arg(long)]
+        pages: bool,
+
+        /// Also serve Pages sites at <namespace>.<domain>/<repo>/
+        #[arg(long)]
+        pages_domain: Option<String>,
+
+        /// Largest Pages site a build may publish (e.g. 10GB, or bytes)
+        #[arg(long, value_parser = parse_size)]
+        pages_max_bytes: Option<u64>,
+
+        /// Reject pushes and LFS uploads when the data directory has less free space (e.g. 10GB, or bytes)
+        #[arg(long, value_parser = parse_size)]
+        min_free_bytes: Option<u64>,
+
+        /// Log low-disk warnings below this much free space (e.g. 10GB, or bytes)
+        #[arg(long, value_parser = parse_size)]
+        warn_free_bytes: Option<u64>,
+
+        /// Remove partial uploads and interrupted push files older than this (e.g. 12h; 0 disables)
+        #[arg(long = "temp-max-age", value_parser = duration_secs)]
+        temp_max_age_secs: Option<u64>,
+
+        /// Share N concurrent clone/archive/search slots fairly between namespaces
+        #[arg(long)]
+        fair_queue_slots: Option<usize>,
+
+        /// Allow at most N concurrent clones/fetches of any one repository
+        #[arg(long)]
+        clone_slots_per_repo: Option<usize>,
+
+        /// Refuse shallow clones and fetches deeper than N commits
+        #[arg(long)]
+        max_clone_depth: Option<u32>,
+
+        /// Refuse pushes that send a pack larger than this (e.g. 10GB, or bytes)
+        #[arg(long, value_parser = parse_size)]
+        max_pack_bytes: Option<u64>,
+
+        /// Refuse pushes and content API commits that add a file larger than this (e.g. 10GB, or bytes)
+        #[arg(long, value_parser = parse_size)]
+        max_blob_bytes: Option<u64>,
+
+        /// Kill git-upload-pack after this long (e.g. 10m; 0 disables)
+        #[arg(long = "fetch-timeout", value_parser = duration_secs)]
+        fetch_timeout_secs: Option<u64>,
     },
 
     /// Authentication commands
@@ -97,6 +231,34 @@ enum Commands {
         command: AuthCommands,
     },
 
+    /// Client preferences (e.g. git remote protocol) and server config checks
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Apply the server's recommended git config (credential helper, LFS, push defaults)
+    Setup {
+        /// Print the recommendations without applying them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Skip interactive prompts (requires --yes to apply)
+        #[arg(long)]
+        non_interactive: bool,
+
+        /// Apply without confirmation
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+
+    /// Set up a new server: write server.toml, create the admin token and first user
+    SetupServer {
+        /// Where to write the config
+        #[arg(long, short, default_value = "./server.toml")]
+        config: String,
+    },
+
     /// Create a new repository
     New {
         /// Repository (format: namespace/repo or just repo for primary namespace)
@@ -105,6 +267,18 @@ enum Commands {
         /// Git remote name (default: origin)
         #[arg(short, long, default_value = "origin")]
         remote: String,
+
+        /// Object hash for the new repository (default: sha1)
+        #[arg(long, value_enum)]
+        object_format: Option<ObjectFormat>,
+
+        /// Start the repository with this license (e.g. MIT, Apache-2.0)
+        #[arg(long)]
+        license: Option<String>,
+
+        /// Start the repository with this .gitignore template (e.g. Rust, Node)
+        #[arg(long)]
+        gitignore: Option<String>,
     },
 
     /// Repository management
@@ -130,33 +304,30 @@ enum Commands {
         #[command(subcommand)]
         command: FolderCommands,
     },
+
+    /// Run a receive hook (invoked by git during pushes, not by hand)
+    #[command(hide = true)]
+    Hook {
+        /// Hook name: pre-receive or post-receive
+        name: String,
+    },
+}
+
+/// Parses a CLI duration such as `300` or `5m` into whole seconds.
+fn duration_secs(input: &str) -> Result<u64, String> {
+    parse_duration(input).map(|duration| duration.as_secs())
 }
 
 fn run_init(data_dir: String, non_interactive: bool) -> anyhow::Result<()> {
     let data_path: std::path::PathBuf = data_dir.into();
-    fs::create_dir_all(&data_path)?;
-
-    let db_path = data_path.join("cutman.db");
-    let store = SqliteStore::new(&db_path)?;
-    store.initialize()?;
-
     let token_file = data_path.join(".admin_token");
 
-    if store.has_admin_token()? {
+    let Some(raw_token) = create_admin_token(&data_path)? else {
         bail!(
             "Server already initialized. Admin token exists at: {}",
             token_file.display()
         );
-    }
-
-    let generator = TokenGenerator::new();
-    let (token, raw_token) = create_token(&generator, true, None)?;
-
-    store.create_token(&token)?;
-    fs::write(&token_file, &raw_token)?;
-
-    #[cfg(unix)]
-    set_restrictive_permissions(&token_file);
+    };
 
     println!();
     println!("========================================");
@@ -169,7 +340,8 @@ fn run_init(data_dir: String, non_interactive: bool) -> anyhow::Result<()> {
     println!();
 
     if !non_interactive {
-        create_default_principal_prompt(&store, &generator)?;
+        let store = SqliteStore::new(data_path.join("cutman.db"))?;
+        create_default_principal_prompt(&store, &TokenGenerator::new())?;
     }
 
     Ok(())
@@ -222,7 +394,7 @@ fn create_default_principal_prompt(
     store.create_namespace(&namespace)?;
     store.create_principal(&principal)?;
 
-    let (principal_token, raw_token) = create_token(generator, false, Some(principal_id))?;
+    let (principal_token, raw_token) = create_token(generator, Some(principal_id))?;
     store.create_token(&principal_token)?;
 
     println!();
@@ -238,11 +410,13 @@ fn create_default_principal_prompt(
 }
 
 fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env().add_directive("cutman=info".parse()?))
-        .init();
-
     let cli = Cli::parse();
+    // The server sets up logging once its config, which may ask for span
+    // export, is loaded.
+    let _tracing = match cli.command {
+        Commands::Serve { .. } => None,
+        _ => Some(telemetry::init(None)?),
+    };
 
     match cli.command {
         Commands::Admin { command } => match command {
@@ -255,59 +429,98 @@ fn main() -> anyhow::Result<()> {
             AdminCommands::Principal { command } => match command {
                 PrincipalCommands::Add {
                     data_dir,
+                    server,
                     username,
                     create_token,
                     non_interactive,
                 } => {
-                    run_principal_add(data_dir, username, create_token, non_interactive)?;
+                    run_principal_add(data_dir, server, username, create_token, non_interactive)?;
                 }
                 PrincipalCommands::Remove {
                     data_dir,
+                    server,
                     principal_id,
                     non_interactive,
                     yes,
                 } => {
-                    run_principal_remove(data_dir, principal_id, non_interactive, yes)?;
+                    run_principal_remove(data_dir, server, principal_id, non_interactive, yes)?;
+                }
+                PrincipalCommands::Emails {
+                    data_dir,
+                    server,
+                    principal_id,
+                    emails,
+                    clear,
+                    non_interactive,
+                } => {
+                    run_principal_emails(data_dir, server, principal_id, emails, clear, non_interactive)?;
                 }
             },
             AdminCommands::Token { command } => match command {
                 TokenCommands::Create {
                     data_dir,
+                    server,
                     principal_id,
                     expires_days,
+                    batch,
                     non_interactive,
                 } => {
-                    run_token_create(data_dir, principal_id, expires_days, non_interactive)?;
+                    run_token_create(data_dir, server, principal_id, expires_days, batch, non_interactive)?;
                 }
                 TokenCommands::Revoke {
                     data_dir,
+                    server,
                     token_id,
                     non_interactive,
                     yes,
                 } => {
-                    run_token_revoke(data_dir, token_id, non_interactive, yes)?;
+                    run_token_revoke(data_dir, server, token_id, non_interactive, yes)?;
+                }
+                TokenCommands::Legacy { data_dir, server } => {
+                    run_token_legacy(data_dir, server)?;
+                }
+                TokenCommands::Reissue {
+                    data_dir,
+                    server,
+                    token_id,
+                    grace_days,
+                    non_interactive,
+                } => {
+                    run_token_reissue(data_dir, server, token_id, grace_days, non_interactive)?;
                 }
             },
             AdminCommands::Namespace { command } => match command {
                 NamespaceCommands::Add {
                     data_dir,
+                    server,
                     name,
                     non_interactive,
                 } => {
-                    run_namespace_add(data_dir, name, non_interactive)?;
+                    run_namespace_add(data_dir, server, name, non_interactive)?;
                 }
                 NamespaceCommands::Remove {
                     data_dir,
+                    server,
                     namespace_id,
                     non_interactive,
                     yes,
                 } => {
-                    run_namespace_remove(data_dir, namespace_id, non_interactive, yes)?;
+                    run_namespace_remove(data_dir, server, namespace_id, non_interactive, yes)?;
+                }
+                NamespaceCommands::Move {
+                    data_dir,
+                    namespace_id,
+                    to,
+                    config,
+                    non_interactive,
+                } => {
+                    run_namespace_move(data_dir, namespace_id, to, config, non_interactive)?;
                 }
             },
             AdminCommands::Permission { command } => match command {
                 PermissionCommands::Grant {
                     data_dir,
+                    server,
                     principal_id,
                     namespace_id,
                     permissions,
@@ -315,6 +528,7 @@ fn main() -> anyhow::Result<()> {
                 } => {
                     run_permission_grant(
                         data_dir,
+                        server,
                         principal_id,
                         namespace_id,
                         permissions,
@@ -323,40 +537,63 @@ fn main() -> anyhow::Result<()> {
                 }
                 PermissionCommands::Revoke {
                     data_dir,
+                    server,
                     principal_id,
                     namespace_id,
                     non_interactive,
                     yes,
                 } => {
-                    run_permission_revoke(data_dir, principal_id, namespace_id, non_interactive, yes)?;
+                    run_permission_revoke(data_dir, server, principal_id, namespace_id, non_interactive, yes)?;
                 }
                 PermissionCommands::RepoGrant {
                     data_dir,
+                    server,
                     principal_id,
                     repo_id,
                     permissions,
+                    paths,
                     non_interactive,
                 } => {
                     run_permission_repo_grant(
                         data_dir,
+                        server,
                         principal_id,
                         repo_id,
                         permissions,
+                        paths,
                         non_interactive,
                     )?;
                 }
                 PermissionCommands::RepoRevoke {
                     data_dir,
+                    server,
                     principal_id,
                     repo_id,
                     non_interactive,
                     yes,
                 } => {
-                    run_permission_repo_revoke(data_dir, principal_id, repo_id, non_interactive, yes)?;
+                    run_permission_repo_revoke(data_dir, server, principal_id, repo_id, non_interactive, yes)?;
+                }
+            },
+            AdminCommands::Lfs { command } => match command {
+                LfsCommands::Migrate {
+                    data_dir,
+                    to,
+                    endpoint,
+                    region,
+                    jobs,
+                    cutover,
+                    config,
+                } => {
+                    run_lfs_migrate(data_dir, to, endpoint, region, jobs, cutover, config)?;
                 }
             },
-            AdminCommands::Info { data_dir, json } => {
-                run_info(data_dir, json)?;
+            AdminCommands::Info {
+                data_dir,
+                server,
+                json,
+            } => {
+                run_info(data_dir, server, json)?;
             }
         },
         Commands::Serve {
@@ -365,15 +602,72 @@ fn main() -> anyhow::Result<()> {
             port,
             data_dir,
             public_base_url,
+            private_mode,
+            repo_name_policy,
+            tls_cert,
+            tls_key,
+            ssh_port,
+            ssh_host_key,
+            grpc_port,
+            hooks_dir,
+            builtin_hooks,
+            storage_mode,
+            maintenance_interval_secs,
+            maintenance_task,
+            no_optimize_after_push,
+            bundle_uri,
+            registry,
+            webdav,
+            pages,
+            pages_domain,
+            pages_max_bytes,
+            min_free_bytes,
+            warn_free_bytes,
+            temp_max_age_secs,
+            fair_queue_slots,
+            clone_slots_per_repo,
+            max_clone_depth,
+            max_pack_bytes,
+            max_blob_bytes,
+            fetch_timeout_secs,
         } => {
             let overrides = ServerConfigOverrides {
-                host,
+                hosts: (!host.is_empty()).then_some(host),
                 port,
+                tls_cert: tls_cert.map(Into::into),
+                tls_key: tls_key.map(Into::into),
                 data_dir: data_dir.map(Into::into),
                 public_base_url,
+                private_mode: private_mode.then_some(true),
+                repo_name_policy,
+                ssh_port,
+                ssh_host_key: ssh_host_key.map(Into::into),
+                grpc_port,
+                hooks_dir: hooks_dir.map(Into::into),
+                builtin_hooks: (!builtin_hooks.is_empty()).then_some(builtin_hooks),
+                storage_mode,
+                maintenance_interval_secs,
+                maintenance_task,
+                optimize_after_push: no_optimize_after_push.then_some(false),
+                bundle_uri: bundle_uri.then_some(true),
+                registry: registry.then_some(true),
+                webdav: webdav.then_some(true),
+                pages: pages.then_some(true),
+                pages_domain,
+                pages_max_bytes,
+                min_free_bytes,
+                warn_free_bytes,
+                temp_max_age_secs,
+                fair_queue_slots,
+                clone_slots_per_repo,
+                max_clone_depth,
+                max_pack_bytes,
+                max_blob_bytes,
+                fetch_timeout_secs,
             };
             let config_path = config.as_ref().map(std::path::Path::new);
             let server_config = ServerConfig::load_with_overrides(config_path, overrides)?;
+            let _tracing = telemetry::init(server_config.tracing.as_ref())?;
             run_server(server_config)?;
         }
         Commands::Auth { command } => match command {
@@ -388,8 +682,37 @@ fn main() -> anyhow::Result<()> {
                 run_auth_logout()?;
             }
         },
-        Commands::New { name, remote } => {
-            run_new(name, remote)?;
+        Commands::Config { command } => match command {
+            ConfigCommands::Set { key, value } => {
+                run_config_set(key, value)?;
+            }
+            ConfigCommands::Get { key } => {
+                run_config_get(key)?;
+            }
+            ConfigCommands::Validate { file } => {
+                run_config_validate(file)?;
+            }
+        },
+        Commands::Setup {
+            dry_run,
+            non_interactive,
+            yes,
+        } => {
+            run_setup(dry_run, non_interactive, yes)?;
+        }
+        Commands::SetupServer { config } => {
+            run_setup_server(config)?;
+        }
+        Commands::New {
+            name,
+            remote,
+            object_format,
+            license,
+            gitignore,
+        } => {
+            let auto_init = (license.is_some() || gitignore.is_some())
+                .then_some(AutoInit { license, gitignore });
+            run_new(name, remote, object_format.unwrap_or_default(), auto_init)?;
         }
         Commands::Repo { command } => match command {
             RepoCommands::Delete {
@@ -484,11 +807,183 @@ fn main() -> anyhow::Result<()> {
                 run_folder_move(old_path, new_path, namespace, non_interactive)?;
             }
         },
+        Commands::Hook { name } => {
+            let Some(kind) = HookKind::from_name(&name) else {
+                bail!("Unknown hook '{name}'");
+            };
+            std::process::exit(cutman::hooks::run_hook(kind, &mut std::io::stdin()));
+        }
     }
 
     Ok(())
 }
 
+/// Binds a listener without IPv4-mapped addresses on IPv6 sockets, so `::`
+/// and `0.0.0.0` can be bound side by side on the same port.
+fn bind_listener(addr: SocketAddr) -> anyhow::Result<tokio::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket
+        .bind(&addr.into())
+        .map_err(|e| anyhow::anyhow!("Failed to bind {addr}: {e}"))?;
+    socket.listen(1024)?;
+
+    Ok(tokio::net::TcpListener::from_std(socket.into())?)
+}
+
+/// Answers HTTP-01 challenges on the ACME port, makes sure a current
+/// certificate is on disk, and keeps it renewed.
+#[cfg(feature = "acme")]
+async fn start_acme(
+    config: &ServerConfig,
+    acme_config: &cutman::config::AcmeConfig,
+    shutdown: &CancellationToken,
+) -> anyhow::Result<Arc<rustls::ServerConfig>> {
+    let acme = Arc::new(Acme::new(acme_config, &config.acme_dir())?);
+    let app = acme.challenge_router();
+    for addr in config.acme_socket_addrs().unwrap_or(Ok(Vec::new()))? {
+        let listener = bind_listener(addr)?;
+        let app = app.clone();
+        let shutdown = shutdown.clone().cancelled_owned();
+
+        info!("Answering ACME challenges on {}", addr);
+
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown)
+                .await
+            {
+                tracing::error!("ACME challenge server on {addr} stopped: {e}");
+            }
+        });
+    }
+
+    if acme.needs_renewal() {
+        if let Err(e) = acme.issue().await {
+            if !acme.cert_path().exists() {
+                return Err(e);
+            }
+            warn!("Certificate renewal failed; serving the current one: {e:#}");
+        }
+    }
+    let certificate = Arc::new(TlsCertificate::load(&acme.cert_path(), &acme.key_path())?);
+    spawn_acme_renewal(acme, certificate.clone());
+    certificate.server_config()
+}
+
+#[cfg(feature = "ssh")]
+async fn start_ssh_server(
+    state: Arc<AppState>,
+    addrs: &[SocketAddr],
+    host_key_path: &std::path::Path,
+    shutdown: &CancellationToken,
+) -> anyhow::Result<()> {
+    let host_key = cutman::server::load_or_create_host_key(host_key_path)?;
+
+    for &addr in addrs {
+        let listener = bind_listener(addr)?;
+        let state = state.clone();
+        let host_key = host_key.clone();
+        let shutdown = shutdown.clone();
+
+        info!("Starting SSH server on {}", addr);
+
+        // On shutdown this stops accepting; open sessions keep running
+        // until their git commands finish.
+        tokio::spawn(async move {
+            tokio::select! {
+                result = cutman::server::run_ssh_server(state, listener, host_key) => {
+                    if let Err(e) = result {
+                        tracing::error!("SSH server on {addr} stopped: {e}");
+                    }
+                }
+                () = shutdown.cancelled() => {}
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "ssh"))]
+async fn start_ssh_server(
+    _state: Arc<AppState>,
+    _addrs: &[SocketAddr],
+    _host_key_path: &std::path::Path,
+    _shutdown: &CancellationToken,
+) -> anyhow::Result<()> {
+    bail!("This build of cutman does not include SSH support (enable the 'ssh' feature)");
+}
+
+#[cfg(feature = "grpc")]
+fn start_grpc_server(
+    state: Arc<AppState>,
+    addrs: &[SocketAddr],
+    shutdown: &CancellationToken,
+    servers: &TaskTracker,
+) -> anyhow::Result<()> {
+    let app = cutman::server::grpc_router(state);
+
+    for &addr in addrs {
+        let listener = bind_listener(addr)?;
+        let app = app.clone();
+        let shutdown = shutdown.clone().cancelled_owned();
+
+        info!("Starting gRPC server on {}", addr);
+
+        servers.spawn(async move {
+            if let Err(e) = axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown)
+                .await
+            {
+                tracing::error!("gRPC server on {addr} stopped: {e}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(not(feature = "grpc"))]
+fn start_grpc_server(
+    _state: Arc<AppState>,
+    _addrs: &[SocketAddr],
+    _shutdown: &CancellationToken,
+    _servers: &TaskTracker,
+) -> anyhow::Result<()> {
+    bail!("This build of cutman does not include gRPC support (enable the 'grpc' feature)");
+}
+
+/// Logs how the data directory will be treated and warns about mount
+/// options that are known to lose writes or break locking.
+fn check_storage(config: &ServerConfig) -> StorageReport {
+    let report = StorageReport::for_path(&config.data_dir, config.storage_mode);
+    if let Some(mount) = &report.mount {
+        if report.network {
+            info!(
+                "Network storage mode enabled ({} at {})",
+                mount.fs_type,
+                mount.mount_point.display()
+            );
+        } else if report.forced_local_on_network() {
+            warn!(
+                "Data directory is on {} but storage_mode is 'local'; SQLite WAL is unsafe on network filesystems",
+                mount.fs_type
+            );
+        }
+        for warning in &report.warnings {
+            warn!("Mount option on {}: {warning}", mount.mount_point.display());
+        }
+    } else if report.network {
+        info!("Network storage mode enabled");
+    }
+    report
+}
+
 #[tokio::main]
 async fn run_server(config: ServerConfig) -> anyhow::Result<()> {
     let token_file = config.data_dir.join(".admin_token");
@@ -498,7 +993,8 @@ async fn run_server(config: ServerConfig) -> anyhow::Result<()> {
         );
     }
 
-    let store = SqliteStore::new(config.db_path())?;
+    let storage = check_storage(&config);
+    let store = SqliteStore::open(config.db_path(), config.storage_mode)?;
     if !store.has_admin_token()? {
         bail!(
             "Server not initialized. Run 'cutman admin init' first to create the database and admin token."
@@ -507,19 +1003,314 @@ async fn run_server(config: ServerConfig) -> anyhow::Result<()> {
 
     info!("Admin token available at {}", token_file.display());
 
-    let state = Arc::new(AppState::new(
+    let hooks = HookSettings::new(&config.hooks_dir_path(), config.builtin_hooks.clone())?;
+    hooks.install(&std::env::current_exe()?)?;
+
+    let mut state = AppState::new(
         Arc::new(store),
         config.data_dir.clone(),
         config.public_base_url.clone(),
+    )
+    .with_storage_roots(config.storage_roots.clone())
+    .with_tls(config.serves_tls())
+    .with_private_mode(config.private_mode)
+    .with_repo_name_policy(config.repo_name_policy)
+    .with_ssh_port(config.ssh_port)
+    .with_ssh_idle_timeout(Duration::from_secs(config.ssh_idle_timeout_secs))
+    .with_hooks(hooks)
+    .with_network_storage(storage.network)
+    .with_subprocess_limits(config.limits.clone())
+    .with_git_command_timeout(config.git_command_timeout())
+    .with_signing_timeout(config.signing_timeout())
+    .with_pull_request_url(config.pull_request_url.clone())
+    .with_banner(config.banner.clone())
+    .with_registry(config.registry)
+    .with_webdav(config.webdav)
+    .with_features(config.features)
+    .with_legacy_tokens(config.legacy_tokens.clone())
+    .with_max_clone_depth(config.max_clone_depth)
+    .with_push_limits(PushLimits {
+        max_pack_bytes: config.max_pack_bytes,
+        max_blob_bytes: config.max_blob_bytes,
+    })
+    .with_lfs_transfer_limits(LfsTransferLimits {
+        upload_bytes_per_sec: config.lfs_upload_bytes_per_sec,
+        download_bytes_per_sec: config.lfs_download_bytes_per_sec,
+    })
+    .with_content_limits(ContentLimits {
+        max_upload_bytes: config.max_upload_bytes,
+        max_raw_blob_bytes: config.max_raw_blob_bytes,
+        max_inline_blob_bytes: config.max_inline_blob_bytes,
+        archive_timeout: config.archive_timeout(),
+        grep_timeout: config.grep_timeout(),
+    })
+    .with_maintenance(
+        Maintenance::new(
+            config.maintenance_task,
+            config.maintenance_interval_secs.map(Duration::from_secs),
+        )
+        .with_post_push_optimization(config.optimize_after_push)
+        .with_bundles(config.bundle_uri)
+        .with_limits(config.limits.maintenance.clone())
+        .with_timeout(config.maintenance_timeout()),
+    )
+    .with_mirror_sync(MirrorSync::new(config.mirror_timeout()))
+    .with_export_sync(ExportSync::new(config.mirror_timeout()))
+    .with_shared_objects(SharedObjects::new(config.shared_objects_timeout()))
+    .with_disk_watchdog(DiskWatchdog::new(
+        config.data_dir.clone(),
+        DiskThresholds {
+            min_free_bytes: config.min_free_bytes,
+            warn_free_bytes: config.warn_free_bytes,
+        },
+        Duration::from_secs(config.disk_check_interval_secs.max(1)),
     ));
+    if config.temp_max_age_secs > 0 {
+        state = state.with_temp_sweeper(
+            TempSweeper::new(
+                config.data_dir.clone(),
+                Duration::from_secs(config.temp_max_age_secs),
+                Duration::from_secs(config.temp_sweep_interval_secs.max(1)),
+            )
+            .with_storage_roots(config.storage_roots.values().cloned().collect()),
+        );
+    }
+    if let Some(slots) = config.fair_queue_slots.filter(|s| *s > 0) {
+        info!("Fair queuing: {slots} slots shared between namespaces");
+        state = state
+            .with_fair_queue(FairQueue::new(slots).with_weights(config.namespace_weights.clone()));
+    }
+    if let Some(slots) = config.clone_slots_per_repo.filter(|s| *s > 0) {
+        info!("Clone limit: {slots} concurrent clones per repository");
+        state = state.with_repo_slots(RepoSlots::new(slots));
+    }
+    if let Some(s3) = &config.lfs_s3 {
+        #[cfg(feature = "s3")]
+        {
+            info!("LFS objects are stored in S3 bucket {}", s3.bucket);
+            state = state.with_lfs_storage(cutman::lfs::S3Storage::new(s3, &config.data_dir)?);
+        }
+        #[cfg(not(feature = "s3"))]
+        bail!(
+            "lfs_s3 names bucket {}, but this build of cutman has no S3 support",
+            s3.bucket
+        );
+    }
+    if let Some(authz) = &config.authz {
+        #[cfg(feature = "authz")]
+        {
+            info!("Permission checks are decided by {}", authz.url);
+            state = state.with_authorizer(cutman::server::authz::ExternalAuthorizer::new(authz)?);
+        }
+        #[cfg(not(feature = "authz"))]
+        bail!(
+            "authz names {}, but this build of cutman has no external authorization support",
+            authz.url
+        );
+    }
+    if let Some(alerts) = &config.push_alerts {
+        info!("Unusual pushes are recorded as audit events");
+        state = state.with_push_alerts(cutman::server::push_alerts::PushAlerts::new(alerts)?);
+    }
+    if let Some(events) = &config.events {
+        info!("Publishing events to {}", events.destination());
+        state = state.with_events(cutman::server::events::Events::new(events)?);
+    }
+    if config.pages {
+        let storage_roots = Arc::clone(&state.storage_roots);
+        state = state.with_pages(
+            Pages::new(
+                config.data_dir.clone(),
+                PagesLimits {
+                    max_bytes: config.pages_max_bytes,
+                    max_files: config.pages_max_files,
+                },
+            )
+            .with_domain(config.pages_domain.clone())
+            .with_storage_roots(storage_roots),
+        );
+    }
+    let state = Arc::new(state);
+    state.storage_roots.check_mappings(state.store.as_ref())?;
+    for (name, root) in &config.storage_roots {
+        info!("Storage root '{name}' is {}", root.display());
+    }
+
+    if config.private_mode {
+        info!("Private mode enabled: anonymous access is disabled");
+    }
+
+    if let Some(secs) = config.maintenance_interval_secs.filter(|s| *s > 0) {
+        info!(
+            "Repository maintenance ({}) runs every {secs}s",
+            config.maintenance_task.name()
+        );
+    }
+    spawn_maintenance(state.clone());
+    spawn_mirror_sync(state.clone());
+    spawn_export_sync(state.clone());
+    spawn_disk_watchdog(state.clone());
+    spawn_temp_sweeper(state.clone());
+    spawn_change_pruner(state.clone());
+    spawn_lfs_pool_collector(state.clone());
+    if let Some(secs) = config.lfs_verify_interval_secs.filter(|s| *s > 0) {
+        info!("LFS objects are re-hashed every {secs}s");
+        spawn_lfs_integrity_check(state.clone(), Duration::from_secs(secs));
+    }
+
+    let addrs = config.socket_addrs()?;
+    if addrs.is_empty() {
+        bail!("No host to bind to. Set 'host' in the config or pass --host.");
+    }
+
+    let shutdown = CancellationToken::new();
+
+    #[cfg(feature = "tls")]
+    let tls = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => {
+            let certificate = Arc::new(TlsCertificate::load(cert, key)?);
+            if let Some(secs) = config.tls_reload_secs {
+                info!("TLS certificate files are checked for changes every {secs}s");
+                spawn_tls_reload(certificate.clone(), Duration::from_secs(secs));
+            }
+            Some(certificate.server_config()?)
+        }
+        _ => None,
+    };
+    #[cfg(not(feature = "tls"))]
+    if let Some(cert) = &config.tls_cert {
+        bail!(
+            "tls_cert names {}, but this build of cutman has no TLS support",
+            cert.display()
+        );
+    }
+    #[cfg(feature = "acme")]
+    let tls = match &config.acme {
+        Some(acme) => Some(start_acme(&config, acme, &shutdown).await?),
+        None => tls,
+    };
+    #[cfg(not(feature = "acme"))]
+    if let Some(acme) = &config.acme {
+        bail!(
+            "acme names {}, but this build of cutman has no ACME support",
+            acme.domains.join(", ")
+        );
+    }
 
+    let grpc_servers = TaskTracker::new();
+    if let Some(ssh_addrs) = config.ssh_socket_addrs() {
+        start_ssh_server(
+            state.clone(),
+            &ssh_addrs?,
+            &config.ssh_host_key_path(),
+            &shutdown,
+        )
+        .await?;
+    }
+    if let Some(grpc_addrs) = config.grpc_socket_addrs() {
+        start_grpc_server(state.clone(), &grpc_addrs?, &shutdown, &grpc_servers)?;
+    }
+
+    let git_tasks = state.git_tasks.clone();
     let app = create_router(state);
-    let addr = config.socket_addr()?;
 
-    info!("Starting server on {}", addr);
+    // Bind everything before serving so a bad address fails startup cleanly.
+    let mut listeners = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        listeners.push((addr, bind_listener(addr)?));
+    }
+
+    let mut servers = tokio::task::JoinSet::new();
+    for (addr, listener) in listeners {
+        let app = app
+            .clone()
+            .into_make_service_with_connect_info::<SocketAddr>();
+        #[cfg(feature = "tls")]
+        if let Some(tls) = &tls {
+            info!("Starting HTTPS server on {}", addr);
+            // Tapping the listener carries each peer address through to
+            // ConnectInfo, which axum implements only for its own listeners.
+            let listener = TlsListener::new(listener, tls.clone()).tap_io(|_| {});
+            servers.spawn(
+                axum::serve(listener, app)
+                    .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+                    .into_future(),
+            );
+            continue;
+        }
+        info!("Starting server on {}", addr);
+        servers.spawn(
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+                .into_future(),
+        );
+    }
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // Stop as soon as any listener fails rather than serving on a subset.
+    let signal = tokio::select! {
+        result = async {
+            while let Some(result) = servers.join_next().await {
+                result??;
+            }
+            anyhow::Ok(())
+        } => return result,
+        signal = shutdown_signal() => signal,
+    };
+
+    // Listeners stop accepting, open requests and SSH sessions finish, and
+    // pushes still running get recorded before the process exits.
+    let timeout = config.shutdown_timeout_secs;
+    info!("Received {signal}; waiting up to {timeout}s for open connections to finish");
+    shutdown.cancel();
+    grpc_servers.close();
+    git_tasks.close();
+    let drained = async {
+        while let Some(result) = servers.join_next().await {
+            if let Ok(Err(e)) = result {
+                warn!("Server stopped with an error while shutting down: {e}");
+            }
+        }
+        grpc_servers.wait().await;
+        git_tasks.wait().await;
+    };
+    tokio::select! {
+        () = drained => info!("Shutdown complete"),
+        () = tokio::time::sleep(Duration::from_secs(timeout)) => warn!(
+            "Shutdown timed out after {timeout}s; stopping {} git operations still running",
+            git_tasks.len()
+        ),
+        signal = shutdown_signal() => warn!("Received {signal} again; exiting without waiting"),
+    }
 
     Ok(())
 }
+
+/// Waits for SIGINT or, on Unix, SIGTERM and returns its name.
+async fn shutdown_signal() -> &'static str {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Cannot listen for SIGINT: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                warn!("Cannot listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = interrupt => "SIGINT",
+        () = terminate => "SIGTERM",
+    }
+}
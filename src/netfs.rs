@@ -0,0 +1,306 @@
+//! Support for data directories on network filesystems (NFS, SMB/CIFS).
+//!
+//! Network mounts break two assumptions the local defaults rely on: SQLite's
+//! WAL mode needs shared memory that only works on a single host, and a
+//! rename is not durable (or even visible to other clients) until the
+//! directory holding it is flushed. In network mode writers coordinate
+//! through advisory locks instead and sync directories after renames.
+
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::config::StorageMode;
+
+/// Filesystem types treated as network mounts.
+const NETWORK_FS_TYPES: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "fuse.sshfs",
+];
+
+/// A mounted filesystem, as listed in `/proc/self/mountinfo`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mount {
+    pub mount_point: PathBuf,
+    pub fs_type: String,
+    pub options: Vec<String>,
+}
+
+impl Mount {
+    #[must_use]
+    pub fn is_network(&self) -> bool {
+        NETWORK_FS_TYPES.contains(&self.fs_type.as_str())
+    }
+
+    fn has_option(&self, name: &str) -> bool {
+        self.options
+            .iter()
+            .any(|o| o == name || o.split_once('=').is_some_and(|(key, _)| key == name))
+    }
+
+    fn option_value(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find_map(|o| o.split_once('=').filter(|(key, _)| *key == name))
+            .map(|(_, value)| value)
+    }
+
+    /// Mount options known to cause lost writes or corruption for a server
+    /// that relies on file locking and durable renames.
+    #[must_use]
+    pub fn warnings(&self) -> Vec<&'static str> {
+        let mut warnings = Vec::new();
+        if self.fs_type.starts_with("nfs") {
+            if self.has_option("nolock") || self.option_value("local_lock") == Some("all") {
+                warnings.push("nolock: locks are not shared with other clients");
+            }
+            if self.has_option("soft") {
+                warnings.push("soft: timed-out writes are silently dropped; use hard");
+            }
+            if self.has_option("async") {
+                warnings.push("async: the server may acknowledge writes before storing them");
+            }
+            if self.has_option("nocto") {
+                warnings.push("nocto: other clients' changes may not be seen on open");
+            }
+        }
+        if matches!(self.fs_type.as_str(), "cifs" | "smb3") {
+            if self.has_option("nobrl") {
+                warnings.push("nobrl: byte-range locks are not sent to the server");
+            }
+            if self.option_value("cache") == Some("loose") {
+                warnings.push("cache=loose: stale reads are possible; use cache=strict");
+            }
+        }
+        warnings
+    }
+}
+
+/// Storage details for a data directory, reported at startup and by
+/// `cutman admin info`.
+#[derive(Debug, Serialize)]
+pub struct StorageReport {
+    pub mode: StorageMode,
+    /// Whether network-safe behavior is in effect.
+    pub network: bool,
+    pub mount: Option<Mount>,
+    pub warnings: Vec<&'static str>,
+}
+
+impl StorageReport {
+    #[must_use]
+    pub fn for_path(path: &Path, mode: StorageMode) -> Self {
+        let mount = find_mount(path);
+        let detected = mount.as_ref().is_some_and(Mount::is_network);
+        let warnings = mount.as_ref().map(Mount::warnings).unwrap_or_default();
+        Self {
+            mode,
+            network: resolve(mode, detected),
+            mount,
+            warnings,
+        }
+    }
+
+    /// Whether auto-detection would disagree with an explicit `local` mode.
+    #[must_use]
+    pub fn forced_local_on_network(&self) -> bool {
+        self.mode == StorageMode::Local && self.mount.as_ref().is_some_and(Mount::is_network)
+    }
+}
+
+fn resolve(mode: StorageMode, detected: bool) -> bool {
+    match mode {
+        StorageMode::Auto => detected,
+        StorageMode::Local => false,
+        StorageMode::Network => true,
+    }
+}
+
+/// Whether `path` should be treated as living on a network filesystem.
+#[must_use]
+pub fn is_network_path(path: &Path, mode: StorageMode) -> bool {
+    match mode {
+        StorageMode::Auto => find_mount(path).is_some_and(|m| m.is_network()),
+        _ => resolve(mode, false),
+    }
+}
+
+/// Finds the mount holding `path`. Returns `None` where mount information
+/// is unavailable (non-Linux systems, restricted `/proc`).
+#[must_use]
+pub fn find_mount(path: &Path) -> Option<Mount> {
+    let path = existing_ancestor(path)?.canonicalize().ok()?;
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    mount_containing(parse_mountinfo(&mountinfo), &path)
+}
+
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors()
+        .find(|p| !p.as_os_str().is_empty() && p.exists())
+}
+
+/// The most specific mount whose mount point is a prefix of `path`.
+fn mount_containing(mounts: Vec<Mount>, path: &Path) -> Option<Mount> {
+    mounts
+        .into_iter()
+        .filter(|m| path.starts_with(&m.mount_point))
+        .max_by_key(|m| m.mount_point.components().count())
+}
+
+/// Parses `/proc/self/mountinfo`. Later entries win ties, matching how
+/// stacked mounts shadow earlier ones.
+fn parse_mountinfo(contents: &str) -> Vec<Mount> {
+    let mut mounts: Vec<Mount> = Vec::new();
+    for line in contents.lines() {
+        let Some((before, after)) = line.split_once(" - ") else {
+            continue;
+        };
+        let fields: Vec<&str> = before.split(' ').collect();
+        let extra: Vec<&str> = after.split(' ').collect();
+        let (Some(mount_point), Some(mount_options), Some(fs_type)) =
+            (fields.get(4), fields.get(5), extra.first())
+        else {
+            continue;
+        };
+        let super_options = extra.get(2).copied().unwrap_or_default();
+
+        let options = mount_options
+            .split(',')
+            .chain(super_options.split(','))
+            .filter(|o| !o.is_empty())
+            .map(str::to_string)
+            .collect();
+        let mount = Mount {
+            mount_point: PathBuf::from(unescape_octal(mount_point)),
+            fs_type: (*fs_type).to_string(),
+            options,
+        };
+        mounts.retain(|m| m.mount_point != mount.mount_point);
+        mounts.push(mount);
+    }
+    mounts
+}
+
+/// Decodes the `\040`-style escapes the kernel uses for spaces and tabs.
+fn unescape_octal(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes
+            .get(i + 1..i + 4)
+            .filter(|digits| bytes[i] == b'\\' && digits.iter().all(|d| (b'0'..=b'7').contains(d)));
+        if let Some(digits) = escape {
+            out.push(
+                digits
+                    .iter()
+                    .fold(0u8, |acc, d| acc.wrapping_mul(8).wrapping_add(d - b'0')),
+            );
+            i += 4;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Blocks until an exclusive advisory lock on `file` is held. The lock is
+/// released when the file is closed. On NFS the kernel forwards these locks
+/// to the server's lock manager, so they coordinate across clients.
+#[cfg(unix)]
+pub fn lock_exclusive(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    loop {
+        // SAFETY: the descriptor is owned by `file` and stays open for the call.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn lock_exclusive(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+/// Flushes a directory so renames and new entries in it are durable.
+#[cfg(unix)]
+pub fn sync_dir(path: &Path) -> io::Result<()> {
+    File::open(path)?.sync_all()
+}
+
+#[cfg(not(unix))]
+pub fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTINFO: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+40 22 0:35 / /srv/data rw,relatime shared:20 - nfs4 nas:/export rw,vers=4.2,soft,proto=tcp
+41 22 0:36 / /mnt/my\\040share rw,relatime - cifs //nas/share rw,cache=loose,nobrl
+";
+
+    #[test]
+    fn test_parse_mountinfo() {
+        let mounts = parse_mountinfo(MOUNTINFO);
+        assert_eq!(mounts.len(), 3);
+        assert_eq!(mounts[1].mount_point, PathBuf::from("/srv/data"));
+        assert_eq!(mounts[1].fs_type, "nfs4");
+        assert!(mounts[1].options.contains(&"soft".to_string()));
+        assert_eq!(mounts[2].mount_point, PathBuf::from("/mnt/my share"));
+    }
+
+    #[test]
+    fn test_mount_containing_prefers_deepest() {
+        let mount = |path: &str| {
+            mount_containing(parse_mountinfo(MOUNTINFO), Path::new(path)).map(|m| m.fs_type)
+        };
+        assert_eq!(mount("/srv/data/cutman.db").as_deref(), Some("nfs4"));
+        assert_eq!(mount("/srv/database").as_deref(), Some("ext4"));
+        assert_eq!(mount("/mnt/my share/repos").as_deref(), Some("cifs"));
+    }
+
+    #[test]
+    fn test_warnings_for_bad_options() {
+        let mounts = parse_mountinfo(MOUNTINFO);
+        assert!(mounts[0].warnings().is_empty());
+        assert!(!mounts[0].is_network());
+
+        assert!(mounts[1].is_network());
+        assert_eq!(mounts[1].warnings().len(), 1);
+        assert!(mounts[1].warnings()[0].starts_with("soft"));
+
+        let cifs = mounts[2].warnings();
+        assert_eq!(cifs.len(), 2);
+        assert!(cifs.iter().any(|w| w.starts_with("nobrl")));
+        assert!(cifs.iter().any(|w| w.starts_with("cache=loose")));
+    }
+
+    #[test]
+    fn test_explicit_mode_overrides_detection() {
+        assert!(resolve(StorageMode::Auto, true));
+        assert!(!resolve(StorageMode::Auto, false));
+        assert!(!resolve(StorageMode::Local, true));
+        assert!(resolve(StorageMode::Network, false));
+    }
+}
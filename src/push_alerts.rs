@@ -0,0 +1,206 @@
+//! Heuristics that flag pushes a small team would want to hear about:
+//! a force push that drops a large part of a branch's history, a push that
+//! deletes many branches at once, and a token pushing from an address it
+//! has never pushed from before. None of them rejects anything; the server
+//! records each as an audit event once the push has landed, and posts it to
+//! the `[push_alerts]` webhook when one is configured.
+
+use git2::{Oid, Repository};
+use serde::Serialize;
+
+use crate::hooks::RefUpdate;
+
+/// Thresholds for the checks that look at a push's ref updates. A
+/// threshold of 0 turns its check off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PushAlertRules {
+    /// Flag a force push that drops at least this many commits from a
+    /// branch.
+    pub rewritten_commits: u32,
+    /// Flag a push that deletes at least this many branches.
+    pub deleted_branches: u32,
+}
+
+/// Something unusual about one push.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PushAlert {
+    /// A branch was moved to a commit that drops `dropped_commits` of the
+    /// commits it had.
+    HistoryRewrite {
+        refname: String,
+        old: String,
+        new: String,
+        dropped_commits: usize,
+    },
+    /// The push deleted these branches.
+    BranchDeletion { branches: Vec<String> },
+    /// A token that had pushed before pushed from an address it never had.
+    NewAddress { token_id: String, address: String },
+}
+
+impl PushAlert {
+    /// The `kind` recorded for the audit event.
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::HistoryRewrite { .. } => "history_rewrite",
+            Self::BranchDeletion { .. } => "branch_deletion",
+            Self::NewAddress { .. } => "new_address",
+        }
+    }
+
+    /// One line for logs and webhook receivers.
+    #[must_use]
+    pub fn summary(&self) -> String {
+        match self {
+            Self::HistoryRewrite {
+                refname,
+                dropped_commits,
+                ..
+            } => format!("force push to {refname} dropped {dropped_commits} commits"),
+            Self::BranchDeletion { branches } => {
+                format!("push deleted {} branches", branches.len())
+            }
+            Self::NewAddress { address, .. } => {
+                format!("first push with this token from {address}")
+            }
+        }
+    }
+}
+
+/// Checks the ref updates of a push that has finished. Updates a hook
+/// refused are skipped, as are rewritten commits that are already gone.
+pub fn check_ref_updates(
+    repo: &Repository,
+    updates: &[RefUpdate],
+    rules: PushAlertRules,
+) -> Result<Vec<PushAlert>, git2::Error> {
+    let landed: Vec<&RefUpdate> = updates
+        .iter()
+        .filter(|update| update.refname.starts_with("refs/heads/"))
+        .filter(|update| match repo.refname_to_id(&update.refname) {
+            Ok(oid) => oid.to_string() == update.new,
+            Err(_) => update.is_delete(),
+        })
+        .collect();
+
+    let mut alerts = Vec::new();
+    if rules.rewritten_commits > 0 {
+        for update in landed.iter().filter(|u| !u.is_create() && !u.is_delete()) {
+            let (Ok(old), Ok(new)) = (Oid::from_str(&update.old), Oid::from_str(&update.new))
+            else {
+                continue;
+            };
+            let Some(dropped) = dropped_commits(repo, old, new)? else {
+                continue;
+            };
+            if dropped >= rules.rewritten_commits as usize {
+                alerts.push(PushAlert::HistoryRewrite {
+                    refname: update.refname.clone(),
+                    old: update.old.clone(),
+                    new: update.new.clone(),
+                    dropped_commits: dropped,
+                });
+            }
+        }
+    }
+
+    let deleted: Vec<String> = landed
+        .iter()
+        .filter(|update| update.is_delete())
+        .map(|update| update.refname.clone())
+        .collect();
+    if rules.deleted_branches > 0 && deleted.len() >= rules.deleted_branches as usize {
+        alerts.push(PushAlert::BranchDeletion { branches: deleted });
+    }
+    Ok(alerts)
+}
+
+/// How many commits reachable from `old` aren't from `new`, or `None` when
+/// `old` is no longer in the repository.
+fn dropped_commits(repo: &Repository, old: Oid, new: Oid) -> Result<Option<usize>, git2::Error> {
+    if repo.find_commit(old).is_err() {
+        return Ok(None);
+    }
+    let mut walk = repo.revwalk()?;
+    walk.push(old)?;
+    walk.hide(new)?;
+    Ok(Some(walk.count()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_ref_updates() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let sig = git2::Signature::now("Test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let commit = |message: &str, parents: &[Oid]| {
+            let parents: Vec<_> = parents
+                .iter()
+                .map(|p| repo.find_commit(*p).unwrap())
+                .collect();
+            let parents: Vec<_> = parents.iter().collect();
+            repo.commit(None, &sig, &sig, message, &tree, &parents)
+                .unwrap()
+        };
+        let root = commit("root", &[]);
+        let mut tip = root;
+        for i in 0..3 {
+            tip = commit(&format!("work {i}"), &[tip]);
+        }
+        let rewritten = commit("squashed", &[root]);
+        repo.reference("refs/heads/main", rewritten, true, "")
+            .unwrap();
+        repo.reference("refs/heads/kept", root, true, "").unwrap();
+
+        let zero = Oid::zero().to_string();
+        let update = |old: &str, new: &str, refname: &str| RefUpdate {
+            old: old.to_string(),
+            new: new.to_string(),
+            refname: refname.to_string(),
+        };
+        let updates = [
+            update(&tip.to_string(), &rewritten.to_string(), "refs/heads/main"),
+            update(&root.to_string(), &zero, "refs/heads/a"),
+            update(&root.to_string(), &zero, "refs/heads/b"),
+            // Refused by a hook, so it's still there.
+            update(&root.to_string(), &zero, "refs/heads/kept"),
+        ];
+        let rules = PushAlertRules {
+            rewritten_commits: 3,
+            deleted_branches: 2,
+        };
+        let alerts = check_ref_updates(&repo, &updates, rules).unwrap();
+        assert_eq!(
+            alerts,
+            [
+                PushAlert::HistoryRewrite {
+                    refname: "refs/heads/main".to_string(),
+                    old: tip.to_string(),
+                    new: rewritten.to_string(),
+                    dropped_commits: 3,
+                },
+                PushAlert::BranchDeletion {
+                    branches: vec!["refs/heads/a".to_string(), "refs/heads/b".to_string()],
+                },
+            ]
+        );
+
+        let strict = PushAlertRules {
+            rewritten_commits: 4,
+            deleted_branches: 0,
+        };
+        assert!(
+            check_ref_updates(&repo, &updates, strict)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Size limits on pushed content. `max_pack_bytes` caps the pack one push
+//! may send and `max_blob_bytes` caps any single file it adds; the content
+//! API holds the files it commits to the same limits. An unset limit means
+//! unlimited.
+
+use std::fmt;
+
+use crate::units::format_size;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PushLimits {
+    pub max_pack_bytes: Option<u64>,
+    pub max_blob_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushTooLarge {
+    Pack { size: u64, limit: u64 },
+    Blob { name: String, size: u64, limit: u64 },
+}
+
+impl fmt::Display for PushTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pack { size, limit } => write!(
+                f,
+                "Push is {}, over the {} limit for a single push",
+                format_size(*size as i64),
+                format_size(*limit as i64)
+            ),
+            Self::Blob { name, size, limit } => write!(
+                f,
+                "{name} is {}, over the {} limit for a single file",
+                format_size(*size as i64),
+                format_size(*limit as i64)
+            ),
+        }
+    }
+}
+
+impl PushLimits {
+    /// Checks the total size of the content a push or commit adds.
+    #[must_use]
+    pub fn check_pack(&self, size: u64) -> Option<PushTooLarge> {
+        let limit = self.max_pack_bytes?;
+        (size > limit).then_some(PushTooLarge::Pack { size, limit })
+    }
+
+    /// Checks one file, named by its path or object id in the message.
+    #[must_use]
+    pub fn check_blob(&self, name: &str, size: u64) -> Option<PushTooLarge> {
+        let limit = self.max_blob_bytes?;
+        (size > limit).then(|| PushTooLarge::Blob {
+            name: name.to_string(),
+            size,
+            limit,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        let limits = PushLimits {
+            max_pack_bytes: Some(2048),
+            max_blob_bytes: Some(1024),
+        };
+        assert_eq!(limits.check_pack(2048), None);
+        assert_eq!(
+            limits.check_pack(3072).unwrap().to_string(),
+            "Push is 3.0 KiB, over the 2.0 KiB limit for a single push"
+        );
+        assert_eq!(limits.check_blob("a.bin", 1024), None);
+        assert_eq!(
+            limits.check_blob("a.bin", 1536).unwrap().to_string(),
+            "a.bin is 1.5 KiB, over the 1.0 KiB limit for a single file"
+        );
+
+        let unlimited = PushLimits::default();
+        assert_eq!(unlimited.check_pack(u64::MAX), None);
+        assert_eq!(unlimited.check_blob("a.bin", u64::MAX), None);
+    }
+}
@@ -0,0 +1,152 @@
+//! Namespace quotas. `repo_limit` caps how many repositories a namespace
+//! holds and `storage_limit_bytes` caps their combined size on disk, plus its
+//! package files and the LFS objects its repos reference; an unset limit
+//! means unlimited.
+
+use std::fmt;
+
+use crate::error::Result;
+use crate::store::Store;
+use crate::types::Namespace;
+use crate::units::format_size;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaExceeded {
+    RepoLimit { limit: i32 },
+    StorageLimit { needed: i64, limit: i64 },
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RepoLimit { limit } => {
+                write!(f, "Namespace has reached its limit of {limit} repositories")
+            }
+            Self::StorageLimit { needed, limit } => write!(
+                f,
+                "Namespace storage limit exceeded: this push needs {} of {}",
+                format_size(*needed),
+                format_size(*limit)
+            ),
+        }
+    }
+}
+
+/// Share of the storage limit, in percent, above which pushes warn that the
+/// namespace is nearly full.
+const STORAGE_WARNING_PERCENT: i64 = 90;
+
+/// A namespace's recorded storage use against its limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageUsage {
+    pub used: i64,
+    pub limit: i64,
+}
+
+impl StorageUsage {
+    /// Whether adding `incoming` bytes would go over the limit. Pushes that
+    /// add nothing (such as ref deletions) are always allowed, so a namespace
+    /// that is already over its limit can still clean up.
+    #[must_use]
+    pub fn check(&self, incoming: i64) -> Option<QuotaExceeded> {
+        let needed = self.used.saturating_add(incoming);
+        (incoming > 0 && needed > self.limit).then_some(QuotaExceeded::StorageLimit {
+            needed,
+            limit: self.limit,
+        })
+    }
+
+    /// A warning once use has crossed [`STORAGE_WARNING_PERCENT`] of the
+    /// limit, so pushers hear about it before pushes start failing.
+    #[must_use]
+    pub fn warning(&self) -> Option<String> {
+        if self.limit <= 0
+            || self.used.saturating_mul(100) < self.limit.saturating_mul(STORAGE_WARNING_PERCENT)
+        {
+            return None;
+        }
+        Some(format!(
+            "Namespace is using {} of its {} storage limit ({}%)",
+            format_size(self.used),
+            format_size(self.limit),
+            self.used.saturating_mul(100) / self.limit
+        ))
+    }
+}
+
+/// Checks that `namespace` has room for one more repository.
+pub fn check_repo_limit(store: &dyn Store, namespace: &Namespace) -> Result<Option<QuotaExceeded>> {
+    let Some(limit) = namespace.repo_limit else {
+        return Ok(None);
+    };
+    let count = store.count_namespace_repos(&namespace.id)?;
+    Ok((count >= limit).then_some(QuotaExceeded::RepoLimit { limit }))
+}
+
+/// Current storage use, if the namespace has a storage limit.
+pub fn storage_usage(store: &dyn Store, namespace: &Namespace) -> Result<Option<StorageUsage>> {
+    let Some(limit) = namespace.storage_limit_bytes else {
+        return Ok(None);
+    };
+    let used = store.get_namespace_repo_size(&namespace.id)?
+        + store.get_namespace_package_size(&namespace.id)?
+        + store.get_namespace_lfs_size(&namespace.id)?;
+    Ok(Some(StorageUsage { used, limit }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_check() {
+        let usage = StorageUsage {
+            used: 900,
+            limit: 1000,
+        };
+        assert_eq!(usage.check(100), None);
+        assert_eq!(
+            usage.check(101),
+            Some(QuotaExceeded::StorageLimit {
+                needed: 1001,
+                limit: 1000
+            })
+        );
+
+        let over = StorageUsage {
+            used: 2000,
+            limit: 1000,
+        };
+        assert_eq!(over.check(0), None);
+        assert!(over.check(1).is_some());
+
+        assert_eq!(
+            usage.warning().as_deref(),
+            Some("Namespace is using 900 B of its 1000 B storage limit (90%)")
+        );
+        assert_eq!(
+            StorageUsage {
+                used: 899,
+                limit: 1000
+            }
+            .warning(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_quota_messages() {
+        assert_eq!(
+            QuotaExceeded::RepoLimit { limit: 3 }.to_string(),
+            "Namespace has reached its limit of 3 repositories"
+        );
+        assert_eq!(
+            QuotaExceeded::StorageLimit {
+                needed: 1_572_864,
+                limit: 1_048_576
+            }
+            .to_string(),
+            "Namespace storage limit exceeded: this push needs 1.5 MiB of 1.0 MiB"
+        );
+    }
+}
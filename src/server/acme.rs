@@ -0,0 +1,1124 @@
+//! HTTPS certificates from an ACME certificate authority such as Let's
+//! Encrypt (RFC 8555). The server proves control of each domain by
+//! answering HTTP-01 challenges on a plain HTTP listener, keeps the issued
+//! certificate and its key in the data directory, and renews them well
+//! before they expire.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, anyhow, bail};
+use axum::Router;
+use axum::extract::{Path as UrlPath, State};
+use axum::http::StatusCode;
+use axum::routing::get;
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use reqwest::header::CONTENT_TYPE;
+use ring::rand::SystemRandom;
+use ring::signature::{
+    ECDSA_P256_SHA256_ASN1_SIGNING, ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair,
+    EcdsaSigningAlgorithm, KeyPair,
+};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use super::tls::TlsCertificate;
+use crate::config::AcmeConfig;
+
+/// Certificates are renewed once they have less than this left.
+const RENEW_BEFORE: chrono::Duration = chrono::Duration::days(30);
+
+/// How often the renewal task looks at the certificate.
+const RENEW_CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// How often a pending authorization or order is checked, and how many
+/// times before giving up.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_ATTEMPTS: u32 = 60;
+
+/// Time limit for each request to the CA.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The error a CA answers with when a nonce has expired; the request is
+/// worth one retry with the fresh nonce that comes with it.
+const BAD_NONCE: &str = "urn:ietf:params:acme:error:badNonce";
+
+// DER encodings of the object identifiers a certificate request needs.
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_PRIME256V1: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_ECDSA_SHA256: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_EXTENSION_REQUEST: &[u8] = &[
+    0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e,
+];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x06, 0x03, 0x55, 0x1d, 0x11];
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const OCTET_STRING: u8 = 0x04;
+const BIT_STRING: u8 = 0x03;
+const DNS_NAME: u8 = 0x82;
+const ATTRIBUTES: u8 = 0xa0;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+
+type Challenges = Arc<Mutex<HashMap<String, String>>>;
+
+/// Obtains and renews the certificate configured in `[acme]`.
+pub struct Acme {
+    config: AcmeConfig,
+    dir: PathBuf,
+    client: reqwest::Client,
+    /// Key authorizations by challenge token, for the CA to fetch.
+    challenges: Challenges,
+}
+
+impl Acme {
+    /// Keeps the certificate, its key, and the account key in `dir`.
+    pub fn new(config: &AcmeConfig, dir: &Path) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!("cutman/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .context("Failed to create ACME client")?;
+        let acme = Self {
+            config: config.clone(),
+            dir: dir.to_path_buf(),
+            client,
+            challenges: Challenges::default(),
+        };
+        acme.finish_install()?;
+        Ok(acme)
+    }
+
+    #[must_use]
+    pub fn cert_path(&self) -> PathBuf {
+        self.dir.join("cert.pem")
+    }
+
+    #[must_use]
+    pub fn key_path(&self) -> PathBuf {
+        self.dir.join("key.pem")
+    }
+
+    fn account_key_path(&self) -> PathBuf {
+        self.dir.join("account.key")
+    }
+
+    /// The domains the current certificate was issued for, one per line.
+    fn domains_path(&self) -> PathBuf {
+        self.dir.join("domains")
+    }
+
+    /// Routes answering the CA's HTTP-01 challenges.
+    pub fn challenge_router(&self) -> Router {
+        Router::new()
+            .route("/.well-known/acme-challenge/{token}", get(answer_challenge))
+            .with_state(self.challenges.clone())
+    }
+
+    /// Whether there's no certificate yet, it was issued for other domains,
+    /// or it expires within [`RENEW_BEFORE`].
+    #[must_use]
+    pub fn needs_renewal(&self) -> bool {
+        let Ok(chain) = fs::read(self.cert_path()) else {
+            return true;
+        };
+        if !self.key_path().exists() {
+            return true;
+        }
+        let domains = fs::read_to_string(self.domains_path()).unwrap_or_default();
+        if domains
+            .lines()
+            .ne(self.config.domains.iter().map(String::as_str))
+        {
+            return true;
+        }
+        not_after(&chain).is_none_or(|expires| expires - Utc::now() < RENEW_BEFORE)
+    }
+
+    /// Requests a certificate for the configured domains and writes it and
+    /// its new key, returning when it expires.
+    pub async fn issue(&self) -> anyhow::Result<DateTime<Utc>> {
+        let domains = self.config.domains.join(", ");
+        info!(
+            "Requesting a certificate for {domains} from {}",
+            self.config.directory
+        );
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+
+        let directory: Directory = self
+            .client
+            .get(&self.config.directory)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .with_context(|| format!("Failed to reach {}", self.config.directory))?
+            .json()
+            .await
+            .context("The ACME directory is not valid")?;
+        let mut session = Session {
+            client: &self.client,
+            key: AccountKey::load_or_create(&self.account_key_path())?,
+            new_nonce: directory.new_nonce,
+            nonce: None,
+            kid: None,
+        };
+
+        let mut account = json!({"termsOfServiceAgreed": true});
+        if let Some(email) = &self.config.email {
+            account["contact"] = json!([format!("mailto:{email}")]);
+        }
+        let response = session.post(&directory.new_account, Some(&account)).await?;
+        session.kid = Some(location(&response)?);
+
+        let identifiers: Vec<Value> = self
+            .config
+            .domains
+            .iter()
+            .map(|domain| json!({"type": "dns", "value": domain}))
+            .collect();
+        let response = session
+            .post(
+                &directory.new_order,
+                Some(&json!({"identifiers": identifiers})),
+            )
+            .await?;
+        let order_url = location(&response)?;
+        let order: Order = response.json().await.context("The order is not valid")?;
+
+        for url in &order.authorizations {
+            self.authorize(&mut session, url).await?;
+        }
+
+        let (key, csr) = certificate_request(&self.config.domains)?;
+        let finalize = json!({"csr": URL_SAFE_NO_PAD.encode(csr)});
+        let mut order: Order = session
+            .post(&order.finalize, Some(&finalize))
+            .await?
+            .json()
+            .await
+            .context("The order is not valid")?;
+        let mut attempts = 0;
+        while matches!(order.status.as_str(), "pending" | "ready" | "processing")
+            && attempts < POLL_ATTEMPTS
+        {
+            attempts += 1;
+            tokio::time::sleep(POLL_INTERVAL).await;
+            order = session.get(&order_url).await?;
+        }
+        let Some(certificate) = order.certificate.filter(|_| order.status == "valid") else {
+            bail!(
+                "The order for {domains} ended {}{}",
+                order.status,
+                problem_suffix(order.error.as_ref())
+            );
+        };
+
+        let chain = session.post(&certificate, None).await?.bytes().await?;
+        let expires = not_after(&chain).context("The CA sent no certificate")?;
+        self.install(
+            &chain,
+            pem("PRIVATE KEY", &key).as_bytes(),
+            self.config.domains.join("\n").as_bytes(),
+        )?;
+        info!("Certificate for {domains} issued, valid until {expires}");
+        Ok(expires)
+    }
+
+    /// Puts a new certificate, its key, and its domains in place of the old
+    /// ones. All three are staged next to the live files before any is
+    /// renamed in, certificate first, so a failed write leaves the old pair
+    /// alone and a crash between the renames is finished on the next start.
+    fn install(&self, chain: &[u8], key: &[u8], domains: &[u8]) -> anyhow::Result<()> {
+        let files = [
+            (self.cert_path(), chain, false),
+            (self.key_path(), key, true),
+            (self.domains_path(), domains, false),
+        ];
+        for (path, contents, private) in &files {
+            if let Err(e) = stage_file(path, contents, *private) {
+                for (path, ..) in &files {
+                    let _ = fs::remove_file(staged_path(path));
+                }
+                return Err(e);
+            }
+        }
+        for (path, ..) in &files {
+            fs::rename(staged_path(path), path)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Completes an [`Acme::install`] that stopped partway. A staged
+    /// certificate means the renames never began, so the staged files are
+    /// dropped and the old pair stays; otherwise the new certificate is in
+    /// place and its staged key and domains follow it.
+    fn finish_install(&self) -> anyhow::Result<()> {
+        let files = [self.cert_path(), self.key_path(), self.domains_path()];
+        let renames_began = !staged_path(&self.cert_path()).exists();
+        for path in &files[1..] {
+            let staged = staged_path(path);
+            if !staged.exists() {
+                continue;
+            }
+            if renames_began {
+                warn!("Finishing the interrupted install of {}", path.display());
+                fs::rename(&staged, path)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+            } else {
+                let _ = fs::remove_file(staged);
+            }
+        }
+        if !renames_began {
+            let _ = fs::remove_file(staged_path(&self.cert_path()));
+        }
+        Ok(())
+    }
+
+    /// Answers the HTTP-01 challenge of one authorization and waits for
+    /// the CA to check it.
+    async fn authorize(&self, session: &mut Session<'_>, url: &str) -> anyhow::Result<()> {
+        let authorization: Authorization = session.get(url).await?;
+        if authorization.status == "valid" {
+            return Ok(());
+        }
+        let domain = authorization.identifier.value;
+        let challenge = authorization
+            .challenges
+            .into_iter()
+            .find(|c| c.kind == "http-01")
+            .with_context(|| format!("The CA offers no HTTP-01 challenge for {domain}"))?;
+
+        let _answer = Answer::new(&self.challenges, &challenge.token, session.key.thumbprint());
+        session.post(&challenge.url, Some(&json!({}))).await?;
+
+        let mut attempts = 0;
+        loop {
+            let authorization: Authorization = session.get(url).await?;
+            match authorization.status.as_str() {
+                "valid" => return Ok(()),
+                "pending" | "processing" if attempts < POLL_ATTEMPTS => {
+                    attempts += 1;
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+                status => {
+                    let problem = authorization
+                        .challenges
+                        .iter()
+                        .find_map(|c| c.error.as_ref());
+                    bail!(
+                        "Validation of {domain} ended {status}{}",
+                        problem_suffix(problem)
+                    );
+                }
+            }
+        }
+    }
+}
+
+async fn answer_challenge(
+    State(challenges): State<Challenges>,
+    UrlPath(token): UrlPath<String>,
+) -> Result<String, StatusCode> {
+    lock(&challenges)
+        .get(&token)
+        .cloned()
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Serves one challenge's key authorization until dropped.
+struct Answer<'a> {
+    challenges: &'a Mutex<HashMap<String, String>>,
+    token: String,
+}
+
+impl<'a> Answer<'a> {
+    fn new(
+        challenges: &'a Mutex<HashMap<String, String>>,
+        token: &str,
+        thumbprint: String,
+    ) -> Self {
+        lock(challenges).insert(token.to_string(), format!("{token}.{thumbprint}"));
+        Self {
+            challenges,
+            token: token.to_string(),
+        }
+    }
+}
+
+impl Drop for Answer<'_> {
+    fn drop(&mut self) {
+        lock(self.challenges).remove(&self.token);
+    }
+}
+
+/// Renews the certificate when it nears expiry and serves the new one.
+pub fn spawn_acme_renewal(acme: Arc<Acme>, certificate: Arc<TlsCertificate>) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(RENEW_CHECK_INTERVAL).await;
+            if !acme.needs_renewal() {
+                continue;
+            }
+            match acme.issue().await {
+                Ok(_) => match certificate.reload_if_changed() {
+                    Ok(_) => info!("Serving the renewed TLS certificate"),
+                    Err(e) => warn!("Keeping the current TLS certificate: {e:#}"),
+                },
+                Err(e) => warn!("Certificate renewal failed; trying again later: {e:#}"),
+            }
+        }
+    });
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Deserialize)]
+struct Order {
+    status: String,
+    #[serde(default)]
+    authorizations: Vec<String>,
+    finalize: String,
+    #[serde(default)]
+    certificate: Option<String>,
+    #[serde(default)]
+    error: Option<Problem>,
+}
+
+#[derive(Deserialize)]
+struct Authorization {
+    status: String,
+    identifier: Identifier,
+    #[serde(default)]
+    challenges: Vec<Challenge>,
+}
+
+#[derive(Deserialize)]
+struct Identifier {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    #[serde(default)]
+    token: String,
+    #[serde(default)]
+    error: Option<Problem>,
+}
+
+/// An error document from the CA (RFC 7807).
+#[derive(Debug, Default, Deserialize)]
+struct Problem {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(default)]
+    detail: String,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.detail.is_empty() {
+            f.write_str(&self.kind)
+        } else {
+            f.write_str(&self.detail)
+        }
+    }
+}
+
+fn problem_suffix(problem: Option<&Problem>) -> String {
+    problem.map_or_else(String::new, |p| format!(": {p}"))
+}
+
+/// Signed requests to the CA, each carrying the nonce the previous answer
+/// handed out.
+struct Session<'a> {
+    client: &'a reqwest::Client,
+    key: AccountKey,
+    new_nonce: String,
+    nonce: Option<String>,
+    /// The account URL, once the account is registered.
+    kid: Option<String>,
+}
+
+impl Session<'_> {
+    async fn nonce(&mut self) -> anyhow::Result<String> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+        let response = self
+            .client
+            .head(&self.new_nonce)
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach {}", self.new_nonce))?;
+        replay_nonce(&response).context("The CA sent no nonce")
+    }
+
+    /// POSTs `payload` signed with the account key, or an empty payload to
+    /// fetch a resource (POST-as-GET).
+    async fn post(
+        &mut self,
+        url: &str,
+        payload: Option<&Value>,
+    ) -> anyhow::Result<reqwest::Response> {
+        let payload = payload.map_or_else(String::new, |p| URL_SAFE_NO_PAD.encode(p.to_string()));
+        let mut retried = false;
+        loop {
+            let mut protected = json!({"alg": "ES256", "nonce": self.nonce().await?, "url": url});
+            match &self.kid {
+                Some(kid) => protected["kid"] = json!(kid),
+                None => protected["jwk"] = self.key.jwk.clone(),
+            }
+            let body = self.key.sign(&protected, &payload)?;
+            let response = self
+                .client
+                .post(url)
+                .header(CONTENT_TYPE, "application/jose+json")
+                .body(body.to_string())
+                .send()
+                .await
+                .with_context(|| format!("Failed to reach {url}"))?;
+            self.nonce = replay_nonce(&response);
+            if response.status().is_success() {
+                return Ok(response);
+            }
+            let status = response.status();
+            let problem: Problem = response.json().await.unwrap_or_default();
+            if problem.kind == BAD_NONCE && !retried {
+                retried = true;
+                continue;
+            }
+            bail!("{url} answered {status}{}", problem_suffix(Some(&problem)));
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&mut self, url: &str) -> anyhow::Result<T> {
+        self.post(url, None)
+            .await?
+            .json()
+            .await
+            .with_context(|| format!("{url} sent an invalid response"))
+    }
+}
+
+fn replay_nonce(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get("replay-nonce")?
+        .to_str()
+        .ok()
+        .map(str::to_string)
+}
+
+fn location(response: &reqwest::Response) -> anyhow::Result<String> {
+    response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .with_context(|| format!("{} sent no Location", response.url()))
+}
+
+/// The P-256 key that identifies the ACME account.
+struct AccountKey {
+    pair: EcdsaKeyPair,
+    jwk: Value,
+}
+
+impl AccountKey {
+    fn load_or_create(path: &Path) -> anyhow::Result<Self> {
+        if path.exists() {
+            let key = PrivatePkcs8KeyDer::from_pem_file(path)
+                .with_context(|| format!("Failed to read ACME account key {}", path.display()))?;
+            return Self::from_pkcs8(key.secret_pkcs8_der());
+        }
+        let key = generate_key(&ECDSA_P256_SHA256_FIXED_SIGNING)?;
+        write_file(path, pem("PRIVATE KEY", &key).as_bytes(), true)?;
+        info!("Generated ACME account key at {}", path.display());
+        Self::from_pkcs8(&key)
+    }
+
+    fn from_pkcs8(key: &[u8]) -> anyhow::Result<Self> {
+        let pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, key, &SystemRandom::new())
+                .map_err(|e| anyhow!("The ACME account key is not a P-256 key: {e}"))?;
+        // An uncompressed point: 0x04, then the x and y coordinates.
+        let point = pair.public_key().as_ref();
+        let jwk = json!({
+            "crv": "P-256",
+            "kty": "EC",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..]),
+        });
+        Ok(Self { pair, jwk })
+    }
+
+    /// The RFC 7638 thumbprint that ends each key authorization. The JWK
+    /// serializes with its members sorted and no whitespace, as required.
+    fn thumbprint(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.jwk.to_string()))
+    }
+
+    /// A flattened JWS of `payload` (already base64url-encoded).
+    fn sign(&self, protected: &Value, payload: &str) -> anyhow::Result<Value> {
+        let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+        let signature = self
+            .pair
+            .sign(
+                &SystemRandom::new(),
+                format!("{protected}.{payload}").as_bytes(),
+            )
+            .map_err(|_| anyhow!("Failed to sign ACME request"))?;
+        Ok(json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": URL_SAFE_NO_PAD.encode(signature),
+        }))
+    }
+}
+
+fn generate_key(algorithm: &'static EcdsaSigningAlgorithm) -> anyhow::Result<Vec<u8>> {
+    let key = EcdsaKeyPair::generate_pkcs8(algorithm, &SystemRandom::new())
+        .map_err(|_| anyhow!("Failed to generate a P-256 key"))?;
+    Ok(key.as_ref().to_vec())
+}
+
+/// A new certificate key (PKCS#8) and a PKCS#10 request naming `domains`
+/// in its subject alternative names, with an empty subject.
+fn certificate_request(domains: &[String]) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+    let key = generate_key(&ECDSA_P256_SHA256_ASN1_SIGNING)?;
+    let rng = SystemRandom::new();
+    let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &key, &rng)
+        .map_err(|e| anyhow!("Failed to load the certificate key: {e}"))?;
+
+    let names: Vec<u8> = domains
+        .iter()
+        .flat_map(|domain| der(DNS_NAME, domain.as_bytes()))
+        .collect();
+    let alt_names = der(
+        SEQUENCE,
+        &[
+            OID_SUBJECT_ALT_NAME,
+            &der(OCTET_STRING, &der(SEQUENCE, &names)),
+        ]
+        .concat(),
+    );
+    let extension_request = der(
+        SEQUENCE,
+        &[OID_EXTENSION_REQUEST, &der(SET, &der(SEQUENCE, &alt_names))].concat(),
+    );
+    let public_key = der(
+        SEQUENCE,
+        &[
+            der(SEQUENCE, &[OID_EC_PUBLIC_KEY, OID_PRIME256V1].concat()),
+            bit_string(pair.public_key().as_ref()),
+        ]
+        .concat(),
+    );
+    let info = der(
+        SEQUENCE,
+        &[
+            &[0x02, 0x01, 0x00][..],
+            &der(SEQUENCE, &[]),
+            &public_key,
+            &der(ATTRIBUTES, &extension_request),
+        ]
+        .concat(),
+    );
+    let signature = pair
+        .sign(&rng, &info)
+        .map_err(|_| anyhow!("Failed to sign the certificate request"))?;
+    let request = der(
+        SEQUENCE,
+        &[
+            info,
+            der(SEQUENCE, OID_ECDSA_SHA256),
+            bit_string(signature.as_ref()),
+        ]
+        .concat(),
+    );
+    Ok((key, request))
+}
+
+/// A DER element with `tag` around `content`.
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    der(BIT_STRING, &[&[0][..], bytes].concat())
+}
+
+/// Splits the DER element at the start of `input` into its tag, its
+/// content, and what follows it.
+fn read_der(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (usize::from(first), rest)
+    } else {
+        let (bytes, rest) = rest.split_at_checked(usize::from(first & 0x7f))?;
+        if bytes.is_empty() || bytes.len() > 4 {
+            return None;
+        }
+        let len = bytes
+            .iter()
+            .fold(0, |len, byte| len << 8 | usize::from(*byte));
+        (len, rest)
+    };
+    let (content, rest) = rest.split_at_checked(len)?;
+    Some((tag, content, rest))
+}
+
+/// When the first certificate in the PEM `chain` expires.
+fn not_after(chain: &[u8]) -> Option<DateTime<Utc>> {
+    let cert = CertificateDer::pem_slice_iter(chain).next()?.ok()?;
+    let (_, cert, _) = read_der(&cert)?;
+    let (_, tbs, _) = read_der(cert)?;
+    // Skip the optional version, then the serial number, signature
+    // algorithm, and issuer.
+    let (tag, _, after_version) = read_der(tbs)?;
+    let mut rest = if tag == 0xa0 { after_version } else { tbs };
+    for _ in 0..3 {
+        rest = read_der(rest)?.2;
+    }
+    let (_, validity, _) = read_der(rest)?;
+    let (_, _, validity) = read_der(validity)?;
+    let (tag, time, _) = read_der(validity)?;
+    let time = std::str::from_utf8(time).ok()?;
+    let time = match tag {
+        // Two-digit years from 50 on are in the 1900s (RFC 5280).
+        UTC_TIME => {
+            let year: u32 = time.get(..2)?.parse().ok()?;
+            format!("{}{time}", if year >= 50 { 19 } else { 20 })
+        }
+        GENERALIZED_TIME => time.to_string(),
+        _ => return None,
+    };
+    NaiveDateTime::parse_from_str(&time, "%Y%m%d%H%M%SZ")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+fn pem(label: &str, der: &[u8]) -> String {
+    let encoded = STANDARD.encode(der);
+    let lines: Vec<&str> = encoded
+        .as_bytes()
+        .chunks(64)
+        .map(|line| std::str::from_utf8(line).expect("base64 is ASCII"))
+        .collect();
+    format!(
+        "-----BEGIN {label}-----\n{}\n-----END {label}-----\n",
+        lines.join("\n")
+    )
+}
+
+/// Replaces `path` in one step, so the TLS reload never reads half a file.
+/// `private` files are readable by the owner only.
+fn write_file(path: &Path, contents: &[u8], private: bool) -> anyhow::Result<()> {
+    stage_file(path, contents, private)?;
+    fs::rename(staged_path(path), path)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Writes `contents` in full to the staging file for `path`, in the same
+/// directory so it can be renamed over `path`.
+fn stage_file(path: &Path, contents: &[u8], private: bool) -> anyhow::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    options
+        .open(staged_path(path))
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn staged_path(path: &Path) -> PathBuf {
+    let mut staged = path.as_os_str().to_owned();
+    staged.push(".new");
+    PathBuf::from(staged)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::process::Command;
+
+    use axum::Json;
+    use axum::http::HeaderMap;
+    use axum::response::IntoResponse;
+    use axum::routing::post;
+    use ring::signature::{ECDSA_P256_SHA256_FIXED, UnparsedPublicKey};
+
+    use super::*;
+
+    /// Runs `openssl`, or returns `None` when it isn't installed.
+    fn openssl(command: &mut Command) -> Option<std::process::Output> {
+        match command.output() {
+            Ok(output) => {
+                assert!(output.status.success(), "openssl failed: {output:?}");
+                Some(output)
+            }
+            Err(_) => {
+                eprintln!("Skipping ACME test: openssl not found in PATH");
+                None
+            }
+        }
+    }
+
+    fn decode(part: &Value) -> Vec<u8> {
+        URL_SAFE_NO_PAD.decode(part.as_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_jws_verifies_with_the_jwk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("account.key");
+        let key = AccountKey::load_or_create(&path).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"a":1}"#);
+        let jws = key
+            .sign(&json!({"alg": "ES256", "jwk": key.jwk}), &payload)
+            .unwrap();
+        let signed = format!(
+            "{}.{}",
+            jws["protected"].as_str().unwrap(),
+            jws["payload"].as_str().unwrap()
+        );
+        let point = [&[0x04][..], &decode(&key.jwk["x"]), &decode(&key.jwk["y"])].concat();
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, point)
+            .verify(signed.as_bytes(), &decode(&jws["signature"]))
+            .unwrap();
+
+        // The key is reused, and with it the account and its thumbprint.
+        let again = AccountKey::load_or_create(&path).unwrap();
+        assert_eq!(again.thumbprint(), key.thumbprint());
+        assert_eq!(key.thumbprint().len(), 43);
+    }
+
+    #[test]
+    fn test_certificate_request_is_valid() {
+        let dir = tempfile::tempdir().unwrap();
+        let domains = ["example.com".to_string(), "www.example.com".to_string()];
+        let (key, csr) = certificate_request(&domains).unwrap();
+        assert!(PrivatePkcs8KeyDer::from_pem_slice(pem("PRIVATE KEY", &key).as_bytes()).is_ok());
+
+        let path = dir.path().join("csr.der");
+        fs::write(&path, csr).unwrap();
+        let path = path.to_str().unwrap();
+        let Some(output) = openssl(
+            Command::new("openssl")
+                .args(["req", "-inform", "DER", "-in", path])
+                .args(["-verify", "-noout", "-text"]),
+        ) else {
+            return;
+        };
+        let text = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            text.contains("DNS:example.com, DNS:www.example.com"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn test_not_after() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, key) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+        let Some(_) = openssl(
+            Command::new("openssl")
+                .args(["req", "-x509", "-newkey", "ec"])
+                .args(["-pkeyopt", "ec_paramgen_curve:prime256v1"])
+                .args(["-nodes", "-subj", "/CN=localhost"])
+                .args(["-days", "1", "-not_after", "20500101000000Z"])
+                .arg("-keyout")
+                .arg(&key)
+                .arg("-out")
+                .arg(&cert),
+        ) else {
+            return;
+        };
+        let expires = not_after(&fs::read(&cert).unwrap()).unwrap();
+        assert_eq!(expires.to_rfc3339(), "2050-01-01T00:00:00+00:00");
+
+        assert_eq!(not_after(b"not a certificate"), None);
+    }
+
+    /// Just enough of an ACME CA to take one order through to a
+    /// certificate signed by a throwaway openssl CA.
+    #[derive(Clone)]
+    struct MockCa {
+        base: String,
+        challenge_addr: SocketAddr,
+        dir: PathBuf,
+        thumbprint: Arc<Mutex<String>>,
+        validated: Arc<Mutex<bool>>,
+    }
+
+    fn nonce() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("replay-nonce", "nonce".parse().unwrap());
+        headers
+    }
+
+    fn payload(jws: &Value) -> Value {
+        let bytes = decode(&jws["payload"]);
+        if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        }
+    }
+
+    async fn mock_directory(State(ca): State<MockCa>) -> Json<Value> {
+        Json(json!({
+            "newNonce": format!("{}/nonce", ca.base),
+            "newAccount": format!("{}/account", ca.base),
+            "newOrder": format!("{}/order", ca.base),
+        }))
+    }
+
+    async fn mock_nonce() -> HeaderMap {
+        nonce()
+    }
+
+    async fn mock_account(State(ca): State<MockCa>, Json(jws): Json<Value>) -> impl IntoResponse {
+        let protected: Value = serde_json::from_slice(&decode(&jws["protected"])).unwrap();
+        let jwk = protected["jwk"].to_string();
+        *lock(&ca.thumbprint) = URL_SAFE_NO_PAD.encode(Sha256::digest(jwk));
+        assert_eq!(payload(&jws)["termsOfServiceAgreed"], true);
+        let mut headers = nonce();
+        headers.insert("location", format!("{}/acct/1", ca.base).parse().unwrap());
+        (
+            StatusCode::CREATED,
+            headers,
+            Json(json!({"status": "valid"})),
+        )
+    }
+
+    fn order(ca: &MockCa, status: &str) -> Value {
+        json!({
+            "status": status,
+            "authorizations": [format!("{}/authz/1", ca.base)],
+            "finalize": format!("{}/finalize", ca.base),
+            "certificate": format!("{}/cert", ca.base),
+        })
+    }
+
+    async fn mock_order(State(ca): State<MockCa>, Json(jws): Json<Value>) -> impl IntoResponse {
+        assert_eq!(
+            payload(&jws)["identifiers"],
+            json!([{"type": "dns", "value": "localhost"}])
+        );
+        let mut headers = nonce();
+        headers.insert("location", format!("{}/order/1", ca.base).parse().unwrap());
+        (StatusCode::CREATED, headers, Json(order(&ca, "pending")))
+    }
+
+    async fn mock_authz(State(ca): State<MockCa>) -> impl IntoResponse {
+        let status = if *lock(&ca.validated) {
+            "valid"
+        } else {
+            "pending"
+        };
+        let body = json!({
+            "status": status,
+            "identifier": {"type": "dns", "value": "localhost"},
+            "challenges": [
+                {"type": "dns-01", "url": format!("{}/nowhere", ca.base), "token": "dns"},
+                {"type": "http-01", "url": format!("{}/chall", ca.base), "token": "tok"},
+            ],
+        });
+        (nonce(), Json(body))
+    }
+
+    async fn mock_challenge(State(ca): State<MockCa>) -> impl IntoResponse {
+        let url = format!(
+            "http://{}/.well-known/acme-challenge/tok",
+            ca.challenge_addr
+        );
+        let answer = reqwest::get(url).await.unwrap().text().await.unwrap();
+        assert_eq!(answer, format!("tok.{}", lock(&ca.thumbprint)));
+        *lock(&ca.validated) = true;
+        (nonce(), Json(json!({"status": "valid"})))
+    }
+
+    async fn mock_finalize(State(ca): State<MockCa>, Json(jws): Json<Value>) -> impl IntoResponse {
+        assert!(*lock(&ca.validated));
+        let csr = URL_SAFE_NO_PAD
+            .decode(payload(&jws)["csr"].as_str().unwrap())
+            .unwrap();
+        let path = |name: &str| ca.dir.join(name).to_str().unwrap().to_string();
+        fs::write(path("csr.der"), csr).unwrap();
+        openssl(
+            Command::new("openssl")
+                .args(["x509", "-req", "-inform", "DER", "-in", &path("csr.der")])
+                .args(["-CA", &path("ca.pem"), "-CAkey", &path("ca.key")])
+                .args(["-copy_extensions", "copy", "-days", "90"])
+                .args(["-out", &path("issued.pem")]),
+        )
+        .unwrap();
+        (nonce(), Json(order(&ca, "valid")))
+    }
+
+    async fn mock_cert(State(ca): State<MockCa>) -> impl IntoResponse {
+        let chain = [
+            fs::read_to_string(ca.dir.join("issued.pem")).unwrap(),
+            fs::read_to_string(ca.dir.join("ca.pem")).unwrap(),
+        ]
+        .concat();
+        (nonce(), chain)
+    }
+
+    async fn serve(router: Router) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_issue_against_a_mock_ca() {
+        let ca_dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| ca_dir.path().join(name).to_str().unwrap().to_string();
+        let Some(_) = openssl(
+            Command::new("openssl")
+                .args(["req", "-x509", "-newkey", "ec"])
+                .args(["-pkeyopt", "ec_paramgen_curve:prime256v1"])
+                .args(["-nodes", "-subj", "/CN=Mock CA", "-days", "1"])
+                .args(["-keyout", &path("ca.key"), "-out", &path("ca.pem")]),
+        ) else {
+            return;
+        };
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let config = AcmeConfig {
+            domains: vec!["localhost".to_string()],
+            email: Some("admin@example.com".to_string()),
+            directory: format!("{base}/directory"),
+            http_port: 0,
+        };
+        let acme = Acme::new(&config, dir.path()).unwrap();
+        assert!(acme.needs_renewal());
+
+        let ca = MockCa {
+            base,
+            challenge_addr: serve(acme.challenge_router()).await,
+            dir: ca_dir.path().to_path_buf(),
+            thumbprint: Arc::default(),
+            validated: Arc::default(),
+        };
+        let router = Router::new()
+            .route("/directory", get(mock_directory))
+            .route("/nonce", get(mock_nonce))
+            .route("/account", post(mock_account))
+            .route("/order", post(mock_order))
+            .route("/authz/1", post(mock_authz))
+            .route("/chall", post(mock_challenge))
+            .route("/finalize", post(mock_finalize))
+            .route("/cert", post(mock_cert))
+            .with_state(ca.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let expires = acme.issue().await.unwrap();
+        assert!(expires - Utc::now() > chrono::Duration::days(89));
+        assert!(*lock(&ca.validated));
+        // The challenge is withdrawn once answered.
+        assert!(lock(&acme.challenges).is_empty());
+        assert!(!acme.needs_renewal());
+        TlsCertificate::load(&acme.cert_path(), &acme.key_path()).unwrap();
+
+        // A change of domains asks for a new certificate.
+        let other = AcmeConfig {
+            domains: vec!["example.com".to_string()],
+            ..config
+        };
+        assert!(Acme::new(&other, dir.path()).unwrap().needs_renewal());
+    }
+
+    #[test]
+    fn test_interrupted_install_keeps_a_matching_pair() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AcmeConfig {
+            domains: vec!["example.com".to_string()],
+            email: None,
+            directory: "http://127.0.0.1:1/directory".to_string(),
+            http_port: 0,
+        };
+        let acme = Acme::new(&config, dir.path()).unwrap();
+        acme.install(b"old cert", b"old key", b"example.com").unwrap();
+        let read = |path: PathBuf| fs::read(path).unwrap();
+
+        // Staged but not yet renamed: the old pair stays.
+        for (path, contents) in [(acme.cert_path(), "new cert"), (acme.key_path(), "new key")] {
+            stage_file(&path, contents.as_bytes(), false).unwrap();
+        }
+        let acme = Acme::new(&config, dir.path()).unwrap();
+        assert_eq!(read(acme.cert_path()), b"old cert");
+        assert_eq!(read(acme.key_path()), b"old key");
+        assert!(!staged_path(&acme.key_path()).exists());
+
+        // Stopped after the certificate was renamed in: its key follows.
+        for (path, contents) in [(acme.cert_path(), "new cert"), (acme.key_path(), "new key")] {
+            stage_file(&path, contents.as_bytes(), false).unwrap();
+        }
+        fs::rename(staged_path(&acme.cert_path()), acme.cert_path()).unwrap();
+        let acme = Acme::new(&config, dir.path()).unwrap();
+        assert_eq!(read(acme.cert_path()), b"new cert");
+        assert_eq!(read(acme.key_path()), b"new key");
+        assert!(!staged_path(&acme.key_path()).exists());
+    }
+}
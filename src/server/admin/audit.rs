@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::IntoResponse,
+};
+
+use crate::auth::RequireAdmin;
+use crate::server::AppState;
+use crate::server::dto::ListAuditEventsParams;
+use crate::server::response::{ApiError, DEFAULT_PAGE_SIZE, PaginatedResponse, paginate};
+use crate::types::AuditEventFilter;
+
+const MAX_PAGE_SIZE: i32 = 200;
+
+/// GET /admin/audit-events - Unusual pushes, legal hold and private mode changes, newest first
+pub async fn list_audit_events(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ListAuditEventsParams>,
+) -> impl IntoResponse {
+    let before_seq = match params.cursor.as_deref().filter(|c| !c.is_empty()) {
+        Some(cursor) => Some(
+            cursor
+                .parse::<i64>()
+                .map_err(|_| ApiError::bad_request("Invalid cursor"))?,
+        ),
+        None => None,
+    };
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let filter = AuditEventFilter {
+        kind: params.kind,
+        repo_id: params.repo_id,
+        principal_id: params.principal_id,
+    };
+
+    let events = state
+        .store
+        .list_audit_events(&filter, before_seq, limit + 1)
+        .map_err(|_| ApiError::internal("Failed to list audit events"))?;
+    let (events, next_cursor, has_more) = paginate(events, limit as usize, |e| e.seq.to_string());
+
+    Ok::<_, ApiError>(Json(PaginatedResponse::new(events, next_cursor, has_more)))
+}
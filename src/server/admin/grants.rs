@@ -9,6 +9,7 @@ use axum::{
 use chrono::Utc;
 
 use crate::auth::RequireAdmin;
+use crate::grant_paths;
 use crate::server::AppState;
 use crate::server::dto::{
     NamespaceGrantRequest, NamespaceGrantResponse, RepoGrantRequest, RepoGrantResponse,
@@ -189,6 +190,8 @@ pub async fn create_repo_grant(
 
     let allow_bits = parse_permissions(&req.allow)?;
     let deny_bits = parse_permissions(&req.deny)?;
+    let mut paths = req.paths;
+    grant_paths::normalize(&mut paths).map_err(ApiError::bad_request)?;
 
     let now = Utc::now();
     let grant = RepoGrant {
@@ -196,6 +199,7 @@ pub async fn create_repo_grant(
         repo_id: repo.id,
         allow_bits,
         deny_bits,
+        paths,
         created_at: now,
         updated_at: now,
     };
@@ -216,6 +220,7 @@ pub async fn create_repo_grant(
             repo_id: g.repo_id,
             allow: g.allow_bits.to_strings(),
             deny: g.deny_bits.to_strings(),
+            paths: g.paths,
         })
         .collect();
 
@@ -244,6 +249,7 @@ pub async fn list_repo_grants(
             repo_id: g.repo_id,
             allow: g.allow_bits.to_strings(),
             deny: g.deny_bits.to_strings(),
+            paths: g.paths,
         })
         .collect();
 
@@ -277,6 +283,7 @@ pub async fn get_repo_grant(
         repo_id: grant.repo_id,
         allow: grant.allow_bits.to_strings(),
         deny: grant.deny_bits.to_strings(),
+        paths: grant.paths,
     };
 
     Ok::<_, ApiError>(Json(ApiResponse::success(response)))
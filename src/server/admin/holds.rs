@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::Utc;
+
+use crate::auth::RequireAdmin;
+use crate::server::AppState;
+use crate::server::dto::{ListRepoHoldsParams, RepoHoldRequest};
+use crate::server::events::{audit_event, emit};
+use crate::server::response::{ApiError, ApiResponse};
+use crate::types::{AuditEvent, Repo, RepoHold};
+
+const MAX_REASON_LEN: usize = 1024;
+
+/// GET /admin/holds - Repos under a legal hold, oldest hold first
+pub async fn list_repo_holds(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ListRepoHoldsParams>,
+) -> impl IntoResponse {
+    let namespace_id = match params.namespace.as_deref().filter(|n| !n.is_empty()) {
+        Some(name) => Some(
+            state
+                .store
+                .get_namespace_by_name(name)
+                .map_err(|_| ApiError::internal("Failed to get namespace"))?
+                .ok_or_else(|| ApiError::not_found("Namespace not found"))?
+                .id,
+        ),
+        None => None,
+    };
+
+    let holds = state
+        .store
+        .list_repo_holds(namespace_id.as_deref())
+        .map_err(|_| ApiError::internal("Failed to list legal holds"))?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(holds)))
+}
+
+/// PUT /admin/repos/{id}/hold - Place a legal hold on a repo
+pub async fn place_repo_hold(
+    admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(req): Json<RepoHoldRequest>,
+) -> impl IntoResponse {
+    let reason = validate_reason(&req.reason)?;
+    let repo = get_repo(&state, &id)?;
+    if get_hold(&state, &repo)?.is_some() {
+        return Err(ApiError::conflict(
+            "Repository is already under a legal hold",
+        ));
+    }
+
+    let hold = RepoHold {
+        repo_id: repo.id.clone(),
+        reason: reason.clone(),
+        token_id: Some(admin.0.id.clone()),
+        created_at: Utc::now(),
+    };
+    record_event(
+        &state,
+        &admin,
+        &repo,
+        "legal_hold_placed",
+        format!("legal hold placed: {reason}"),
+        serde_json::json!({ "reason": reason }),
+    )?;
+    if !state
+        .store
+        .create_repo_hold(&hold)
+        .map_err(|_| ApiError::internal("Failed to place legal hold"))?
+    {
+        return Err(ApiError::conflict(
+            "Repository is already under a legal hold",
+        ));
+    }
+
+    Ok::<_, ApiError>((StatusCode::CREATED, Json(ApiResponse::success(hold))))
+}
+
+/// DELETE /admin/repos/{id}/hold - Lift a repo's legal hold. The reason is
+/// recorded before the hold goes, so no hold is lifted unaudited.
+pub async fn lift_repo_hold(
+    admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(req): Json<RepoHoldRequest>,
+) -> impl IntoResponse {
+    let reason = validate_reason(&req.reason)?;
+    let repo = get_repo(&state, &id)?;
+    let hold = get_hold(&state, &repo)?
+        .ok_or_else(|| ApiError::not_found("Repository is not under a legal hold"))?;
+
+    record_event(
+        &state,
+        &admin,
+        &repo,
+        "legal_hold_lifted",
+        format!("legal hold lifted: {reason}"),
+        serde_json::json!({ "reason": reason, "hold": hold }),
+    )?;
+    state
+        .store
+        .delete_repo_hold(&repo.id)
+        .map_err(|_| ApiError::internal("Failed to lift legal hold"))?;
+
+    Ok::<_, ApiError>(StatusCode::NO_CONTENT)
+}
+
+fn validate_reason(reason: &str) -> Result<String, ApiError> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(ApiError::bad_request("reason is required"));
+    }
+    if reason.len() > MAX_REASON_LEN {
+        return Err(ApiError::bad_request(format!(
+            "reason must be at most {MAX_REASON_LEN} bytes"
+        )));
+    }
+    Ok(reason.to_string())
+}
+
+fn get_repo(state: &AppState, id: &str) -> Result<Repo, ApiError> {
+    state
+        .store
+        .get_repo_by_id(id)
+        .map_err(|_| ApiError::internal("Failed to get repository"))?
+        .ok_or_else(|| ApiError::not_found("Repository not found"))
+}
+
+fn get_hold(state: &AppState, repo: &Repo) -> Result<Option<RepoHold>, ApiError> {
+    state
+        .store
+        .get_repo_hold(&repo.id)
+        .map_err(|_| ApiError::internal("Failed to get legal hold"))
+}
+
+fn record_event(
+    state: &AppState,
+    admin: &RequireAdmin,
+    repo: &Repo,
+    kind: &str,
+    summary: String,
+    detail: serde_json::Value,
+) -> Result<(), ApiError> {
+    let event = AuditEvent {
+        seq: 0,
+        kind: kind.to_string(),
+        summary,
+        namespace_id: Some(repo.namespace_id.clone()),
+        repo_id: Some(repo.id.clone()),
+        principal_id: admin.0.principal_id.clone(),
+        token_id: Some(admin.0.id.clone()),
+        address: None,
+        detail,
+        created_at: Utc::now(),
+    };
+    let seq = state
+        .store
+        .create_audit_event(&event)
+        .map_err(|_| ApiError::internal("Failed to record audit event"))?;
+    emit(state, audit_event(&AuditEvent { seq, ..event }));
+    Ok(())
+}
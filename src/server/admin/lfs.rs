@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State, response::IntoResponse};
+
+use crate::auth::RequireAdmin;
+use crate::server::AppState;
+use crate::server::dto::{LfsTransfersResponse, NamespaceTransfersResponse};
+use crate::server::response::{ApiError, ApiResponse};
+
+/// LFS objects the integrity check found no longer matching their OIDs,
+/// oldest first. An object leaves the list once a client uploads it again.
+pub async fn list_corrupted_lfs_objects(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let objects = state
+        .store
+        .list_corrupted_lfs_objects()
+        .map_err(|_| ApiError::internal("Failed to list corrupted LFS objects"))?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(objects)))
+}
+
+/// Bytes, durations, and failures of LFS transfers through the server,
+/// per namespace, since it started.
+pub async fn get_lfs_transfers(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let limits = state.lfs_transfers.limits();
+    let (since, totals) = state.lfs_transfers.totals();
+
+    Json(ApiResponse::success(LfsTransfersResponse {
+        since,
+        upload_bytes_per_sec: limits.upload_bytes_per_sec,
+        download_bytes_per_sec: limits.download_bytes_per_sec,
+        namespaces: totals
+            .into_iter()
+            .map(|(namespace, totals)| NamespaceTransfersResponse { namespace, totals })
+            .collect(),
+    }))
+}
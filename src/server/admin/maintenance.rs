@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+
+use crate::auth::RequireAdmin;
+use crate::server::AppState;
+use crate::server::dto::{MaintenanceStatusResponse, RepoMaintenanceResponse, TempSweepResponse};
+use crate::server::git::maintenance::{Maintenance, MaintenanceStatus};
+use crate::server::response::{ApiError, ApiResponse};
+use crate::server::sweep::TempSweeper;
+
+fn scheduler(state: &AppState) -> Result<&Arc<Maintenance>, ApiError> {
+    state
+        .maintenance
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Repository maintenance is not enabled"))
+}
+
+pub async fn get_maintenance_status(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let status = scheduler(&state)?.status();
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(status_to_response(
+        status,
+        state.sweeper.as_deref(),
+    ))))
+}
+
+/// Queues a maintenance pass over every repository pushed to since its
+/// last maintenance.
+pub async fn run_maintenance(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let maintenance = scheduler(&state)?;
+    maintenance.trigger();
+
+    Ok::<_, ApiError>((
+        StatusCode::ACCEPTED,
+        Json(ApiResponse::success(status_to_response(
+            maintenance.status(),
+            state.sweeper.as_deref(),
+        ))),
+    ))
+}
+
+fn status_to_response(
+    status: MaintenanceStatus,
+    sweeper: Option<&TempSweeper>,
+) -> MaintenanceStatusResponse {
+    MaintenanceStatusResponse {
+        scheduled: status.interval.is_some(),
+        task: status.task.name(),
+        interval_secs: status.interval.map(|i| i.as_secs()),
+        running: status.running,
+        last_run_started_at: status.last_run_started_at,
+        last_run_finished_at: status.last_run_finished_at,
+        repos: status
+            .repos
+            .into_iter()
+            .map(|r| RepoMaintenanceResponse {
+                repo_id: r.repo_id,
+                namespace_id: r.namespace_id,
+                name: r.name,
+                started_at: r.started_at,
+                duration_ms: r.duration.as_millis() as u64,
+                success: r.error.is_none(),
+                error: r.error,
+                size_before: r.size_before,
+                size_after: r.size_after,
+            })
+            .collect(),
+        temp_sweep: sweeper.map(sweep_to_response),
+    }
+}
+
+fn sweep_to_response(sweeper: &TempSweeper) -> TempSweepResponse {
+    let status = sweeper.status();
+    TempSweepResponse {
+        max_age_secs: sweeper.max_age().as_secs(),
+        interval_secs: sweeper.interval().as_secs(),
+        last_run_at: status.last_run_at,
+        last_files_removed: status.last.files,
+        last_bytes_reclaimed: status.last.bytes,
+        files_removed: status.total.files,
+        bytes_reclaimed: status.total.bytes,
+    }
+}
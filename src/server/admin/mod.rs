@@ -1,13 +1,21 @@
+mod audit;
 mod grants;
+mod holds;
+mod lfs;
+mod maintenance;
 mod namespaces;
 mod principals;
+mod private_mode;
+mod reports;
+mod shared_objects;
+mod storage;
 mod tokens;
 
 use std::sync::Arc;
 
 use axum::{
     Router,
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
 };
 
 use crate::server::AppState;
@@ -19,15 +27,58 @@ pub fn admin_router() -> Router<Arc<AppState>> {
         .route("/namespaces", get(namespaces::list_namespaces))
         .route("/namespaces/{name}", get(namespaces::get_namespace))
         .route("/namespaces/{name}", delete(namespaces::delete_namespace))
+        .route(
+            "/namespaces/{name}/shared-objects",
+            get(shared_objects::get_shared_objects),
+        )
         // Token routes
         .route("/tokens", get(tokens::list_tokens))
+        .route("/tokens/legacy", get(tokens::list_legacy_tokens))
+        .route("/tokens/revoke", post(tokens::revoke_tokens))
         .route("/tokens/{id}", get(tokens::get_token))
         .route("/tokens/{id}", delete(tokens::delete_token))
+        .route("/tokens/{id}/reissue", post(tokens::reissue_token_handler))
+        // Audit routes
+        .route("/audit-events", get(audit::list_audit_events))
+        // Legal hold routes
+        .route("/holds", get(holds::list_repo_holds))
+        .route(
+            "/repos/{id}/hold",
+            put(holds::place_repo_hold).delete(holds::lift_repo_hold),
+        )
+        // Maintenance routes
+        .route("/maintenance", get(maintenance::get_maintenance_status))
+        .route("/maintenance/run", post(maintenance::run_maintenance))
+        // Storage routes
+        .route("/storage/dedup", get(storage::get_dedup_report))
+        .route(
+            "/repos/{id}/shared-objects",
+            put(shared_objects::join_shared_objects).delete(shared_objects::leave_shared_objects),
+        )
+        // Access routes
+        .route(
+            "/private-mode",
+            get(private_mode::get_private_mode).put(private_mode::set_private_mode),
+        )
+        // Report routes
+        .route("/reports/orphans", get(reports::get_orphan_report))
+        .route("/reports/orphans/cleanup", post(reports::cleanup_orphans))
+        // LFS routes
+        .route("/lfs/corrupted", get(lfs::list_corrupted_lfs_objects))
+        .route("/lfs/transfers", get(lfs::get_lfs_transfers))
         // Principal routes
         .route("/principals", post(principals::create_principal))
         .route("/principals", get(principals::list_principals))
         .route("/principals/{id}", get(principals::get_principal))
         .route("/principals/{id}", delete(principals::delete_principal))
+        .route(
+            "/principals/{id}/emails",
+            get(principals::list_principal_emails),
+        )
+        .route(
+            "/principals/{id}/emails",
+            put(principals::set_principal_emails),
+        )
         .route(
             "/principals/{id}/tokens",
             get(principals::list_principal_tokens),
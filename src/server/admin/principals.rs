@@ -10,10 +10,11 @@ use chrono::{Duration, Utc};
 use uuid::Uuid;
 
 use crate::auth::{RequireAdmin, TokenGenerator};
+use crate::email_policy::{MAX_PRINCIPAL_EMAILS, validate_email};
 use crate::server::AppState;
 use crate::server::dto::{
     CreatePrincipalRequest, CreatePrincipalTokenRequest, CreateTokenResponse, PaginationParams,
-    TokenResponse,
+    PrincipalEmailsResponse, SetPrincipalEmailsRequest, TokenResponse,
 };
 use crate::server::response::{
     ApiError, ApiResponse, DEFAULT_PAGE_SIZE, PaginatedResponse, paginate,
@@ -148,6 +149,68 @@ pub async fn delete_principal(
     Ok::<_, ApiError>(StatusCode::NO_CONTENT)
 }
 
+/// GET /admin/principals/{id}/emails - The principal's verified emails
+pub async fn list_principal_emails(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let principal = state
+        .store
+        .get_principal(&id)
+        .map_err(|_| ApiError::internal("Failed to get principal"))?
+        .ok_or_else(|| ApiError::not_found("Principal not found"))?;
+
+    let emails = state
+        .store
+        .list_principal_emails(&principal.id)
+        .map_err(|_| ApiError::internal("Failed to list principal emails"))?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(PrincipalEmailsResponse {
+        principal_id: principal.id,
+        emails,
+    })))
+}
+
+/// PUT /admin/principals/{id}/emails - Record the emails verified as the
+/// principal's, which namespace email policies can let its pushes use
+pub async fn set_principal_emails(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(req): Json<SetPrincipalEmailsRequest>,
+) -> impl IntoResponse {
+    let principal = state
+        .store
+        .get_principal(&id)
+        .map_err(|_| ApiError::internal("Failed to get principal"))?
+        .ok_or_else(|| ApiError::not_found("Principal not found"))?;
+
+    if req.emails.len() > MAX_PRINCIPAL_EMAILS {
+        return Err(ApiError::bad_request(format!(
+            "A principal can have at most {MAX_PRINCIPAL_EMAILS} emails"
+        )));
+    }
+    let emails: Vec<String> = req.emails.iter().map(|e| e.trim().to_string()).collect();
+    for email in &emails {
+        validate_email(email).map_err(ApiError::bad_request)?;
+    }
+
+    state
+        .store
+        .set_principal_emails(&principal.id, &emails)
+        .map_err(|_| ApiError::internal("Failed to save principal emails"))?;
+    let emails = state
+        .store
+        .list_principal_emails(&principal.id)
+        .map_err(|_| ApiError::internal("Failed to list principal emails"))?;
+
+    Ok(Json(ApiResponse::success(PrincipalEmailsResponse {
+        principal_id: principal.id,
+        emails,
+    })))
+}
+
 pub async fn list_principal_tokens(
     _admin: RequireAdmin,
     State(state): State<Arc<AppState>>,
@@ -214,6 +277,7 @@ pub async fn create_principal_token(
             created_at: now,
             expires_at,
             last_used_at: None,
+            traffic_class: req.traffic_class.unwrap_or_default(),
         };
 
         match state.store.create_token(&token) {
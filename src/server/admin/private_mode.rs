@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State, response::IntoResponse};
+use chrono::Utc;
+use tracing::info;
+
+use crate::auth::RequireAdmin;
+use crate::server::AppState;
+use crate::server::dto::PrivateModeRequest;
+use crate::server::events::{audit_event, emit};
+use crate::server::response::{ApiError, ApiResponse};
+use crate::types::AuditEvent;
+
+/// GET /admin/private-mode - Whether anonymous access is disabled
+pub async fn get_private_mode(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    Json(ApiResponse::success(PrivateModeRequest {
+        enabled: !state.allows_anonymous_read(),
+    }))
+}
+
+/// PUT /admin/private-mode - Turn private mode on or off without a restart.
+/// The change lasts until the server restarts, which goes back to
+/// `private_mode` in the config; set that too to keep it.
+pub async fn set_private_mode(
+    admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<PrivateModeRequest>,
+) -> impl IntoResponse {
+    let was_enabled = state.set_private_mode(req.enabled);
+    if was_enabled != req.enabled {
+        let change = if req.enabled { "enabled" } else { "disabled" };
+        info!("Private mode {change} by admin token {}", admin.0.id);
+        let event = AuditEvent {
+            seq: 0,
+            kind: "private_mode_changed".to_string(),
+            summary: format!("private mode {change}"),
+            namespace_id: None,
+            repo_id: None,
+            principal_id: admin.0.principal_id.clone(),
+            token_id: Some(admin.0.id.clone()),
+            address: None,
+            detail: serde_json::json!({ "enabled": req.enabled }),
+            created_at: Utc::now(),
+        };
+        let seq = state
+            .store
+            .create_audit_event(&event)
+            .map_err(|_| ApiError::internal("Failed to record audit event"))?;
+        emit(&state, audit_event(&AuditEvent { seq, ..event }));
+    }
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(req)))
+}
@@ -0,0 +1,276 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::auth::RequireAdmin;
+use crate::server::AppState;
+use crate::server::dto::{
+    OrphanCleanupRequest, OrphanCleanupResponse, OrphanFolderResponse, OrphanNamespaceResponse,
+    OrphanReportParams, OrphanReportResponse, OrphanTokenResponse,
+};
+use crate::server::response::{ApiError, ApiResponse};
+
+const DEFAULT_FOLDER_AGE_DAYS: u32 = 30;
+
+/// GET /admin/reports/orphans - Namespaces with no users and no repos,
+/// tokens of deleted or deactivated users, and folders left empty, for
+/// review before cleaning them up. `format=csv` downloads it as CSV.
+pub async fn get_orphan_report(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<OrphanReportParams>,
+) -> Result<Response, ApiError> {
+    let report = orphan_report(
+        &state,
+        params.folder_age_days.unwrap_or(DEFAULT_FOLDER_AGE_DAYS),
+    )?;
+    match params.format.as_deref() {
+        None | Some("json") => Ok(Json(ApiResponse::success(report)).into_response()),
+        Some("csv") => Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"orphans.csv\"",
+                ),
+            ],
+            orphan_csv(&report),
+        )
+            .into_response()),
+        Some(other) => Err(ApiError::bad_request(format!(
+            "Unknown format {other}; use json or csv"
+        ))),
+    }
+}
+
+/// POST /admin/reports/orphans/cleanup - Removes the listed entries that
+/// the orphan report still shows. Nothing is removed unless `confirm` is
+/// set.
+pub async fn cleanup_orphans(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<OrphanCleanupRequest>,
+) -> impl IntoResponse {
+    let requested = req.namespaces.len() + req.tokens.len() + req.folders.len();
+    if requested == 0 {
+        return Err(ApiError::bad_request(
+            "List the namespaces, tokens, or folders to remove",
+        ));
+    }
+
+    // Check against a fresh report, so anything put back into use since
+    // the admin reviewed it stays.
+    let report = orphan_report(
+        &state,
+        req.folder_age_days.unwrap_or(DEFAULT_FOLDER_AGE_DAYS),
+    )?;
+    let orphan_namespaces: HashSet<_> = report.namespaces.iter().map(|ns| &ns.id).collect();
+    let orphan_tokens: HashSet<_> = report.tokens.iter().map(|t| &t.id).collect();
+    let orphan_folders: HashSet<_> = report.folders.iter().map(|f| f.id).collect();
+    let namespaces: Vec<String> = req
+        .namespaces
+        .into_iter()
+        .filter(|id| orphan_namespaces.contains(id))
+        .collect();
+    let tokens: Vec<String> = req
+        .tokens
+        .into_iter()
+        .filter(|id| orphan_tokens.contains(id))
+        .collect();
+    let folders: Vec<i64> = req
+        .folders
+        .into_iter()
+        .filter(|id| orphan_folders.contains(id))
+        .collect();
+
+    if req.confirm {
+        for id in &namespaces {
+            state
+                .store
+                .delete_namespace(id)
+                .map_err(|_| ApiError::internal("Failed to delete namespace"))?;
+        }
+        for id in &tokens {
+            state
+                .store
+                .delete_token(id)
+                .map_err(|_| ApiError::internal("Failed to delete token"))?;
+        }
+        // Deleting a folder deletes its subfolders, so some may already be
+        // gone.
+        for id in &folders {
+            state
+                .store
+                .delete_folder(*id)
+                .map_err(|_| ApiError::internal("Failed to delete folder"))?;
+        }
+    }
+
+    let skipped = requested - namespaces.len() - tokens.len() - folders.len();
+    Ok::<_, ApiError>(Json(ApiResponse::success(OrphanCleanupResponse {
+        confirmed: req.confirm,
+        namespaces,
+        tokens,
+        folders,
+        skipped,
+    })))
+}
+
+fn orphan_report(state: &AppState, folder_age_days: u32) -> Result<OrphanReportResponse, ApiError> {
+    let namespaces = state
+        .store
+        .list_orphan_namespaces()
+        .map_err(|_| ApiError::internal("Failed to list namespaces"))?;
+    let tokens = state
+        .store
+        .list_orphan_tokens()
+        .map_err(|_| ApiError::internal("Failed to list tokens"))?;
+    let cutoff = Utc::now() - Duration::days(i64::from(folder_age_days));
+    let folders: Vec<_> = state
+        .store
+        .list_empty_folders()
+        .map_err(|_| ApiError::internal("Failed to list folders"))?
+        .into_iter()
+        .filter(|folder| folder.updated_at <= cutoff)
+        .collect();
+
+    let mut namespace_names = HashMap::new();
+    for folder in &folders {
+        if !namespace_names.contains_key(&folder.namespace_id) {
+            let name = state
+                .store
+                .get_namespace(&folder.namespace_id)
+                .map_err(|_| ApiError::internal("Failed to get namespace"))?
+                .map(|ns| ns.name)
+                .unwrap_or_default();
+            namespace_names.insert(folder.namespace_id.clone(), name);
+        }
+    }
+
+    Ok(OrphanReportResponse {
+        folder_age_days,
+        namespaces: namespaces
+            .into_iter()
+            .map(|ns| OrphanNamespaceResponse {
+                id: ns.id,
+                name: ns.name,
+                created_at: ns.created_at,
+            })
+            .collect(),
+        tokens: tokens
+            .into_iter()
+            .map(|(token, reason)| OrphanTokenResponse {
+                id: token.id,
+                principal_id: token.principal_id,
+                reason,
+                created_at: token.created_at,
+                last_used_at: token.last_used_at,
+            })
+            .collect(),
+        folders: folders
+            .into_iter()
+            .map(|folder| OrphanFolderResponse {
+                id: folder.id,
+                namespace: namespace_names[&folder.namespace_id].clone(),
+                path: folder.path,
+                updated_at: folder.updated_at,
+            })
+            .collect(),
+    })
+}
+
+/// One row per orphan: its kind, ID, a name to recognize it by, why it's
+/// listed, and when it was last active.
+fn orphan_csv(report: &OrphanReportResponse) -> String {
+    let mut csv = String::from("kind,id,name,reason,last_active\n");
+    let mut row = |fields: [&str; 4], last_active: &DateTime<Utc>| {
+        for field in fields {
+            csv.push_str(&csv_field(field));
+            csv.push(',');
+        }
+        csv.push_str(&last_active.to_rfc3339());
+        csv.push('\n');
+    };
+    for ns in &report.namespaces {
+        row(
+            ["namespace", &ns.id, &ns.name, "no_users_or_repos"],
+            &ns.created_at,
+        );
+    }
+    for token in &report.tokens {
+        row(
+            [
+                "token",
+                &token.id,
+                token.principal_id.as_deref().unwrap_or_default(),
+                token.reason.as_str(),
+            ],
+            token.last_used_at.as_ref().unwrap_or(&token.created_at),
+        );
+    }
+    for folder in &report.folders {
+        let name = format!("{}{}", folder.namespace, folder.path);
+        row(
+            ["folder", &folder.id.to_string(), &name, "empty"],
+            &folder.updated_at,
+        );
+    }
+    csv
+}
+
+/// Quotes a field that holds a comma, quote, or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::OrphanTokenReason;
+
+    #[test]
+    fn test_orphan_csv() {
+        let at = DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let report = OrphanReportResponse {
+            folder_age_days: 30,
+            namespaces: vec![OrphanNamespaceResponse {
+                id: "ns-1".to_string(),
+                name: "old-team".to_string(),
+                created_at: at,
+            }],
+            tokens: vec![OrphanTokenResponse {
+                id: "tok-1".to_string(),
+                principal_id: None,
+                reason: OrphanTokenReason::MissingPrincipal,
+                created_at: at,
+                last_used_at: None,
+            }],
+            folders: vec![OrphanFolderResponse {
+                id: 7,
+                namespace: "apps".to_string(),
+                path: "/q1, \"draft\"".to_string(),
+                updated_at: at,
+            }],
+        };
+        assert_eq!(
+            orphan_csv(&report),
+            "kind,id,name,reason,last_active\n\
+             namespace,ns-1,old-team,no_users_or_repos,2026-01-02T03:04:05+00:00\n\
+             token,tok-1,,missing_principal,2026-01-02T03:04:05+00:00\n\
+             folder,7,\"apps/q1, \"\"draft\"\"\",empty,2026-01-02T03:04:05+00:00\n"
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::Utc;
+use tracing::warn;
+
+use crate::auth::RequireAdmin;
+use crate::server::AppState;
+use crate::server::dto::{SharedObjectMemberResponse, SharedObjectsResponse};
+use crate::server::git::calculate_repo_size;
+use crate::server::git::shared_objects::{self, borrows_objects, store_path};
+use crate::server::response::{ApiError, ApiResponse};
+use crate::types::{Repo, SharedObjectMember};
+
+/// GET /admin/namespaces/{name}/shared-objects - The namespace's shared
+/// object store and its members
+pub async fn get_shared_objects(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let namespace = state
+        .store
+        .get_namespace_by_name(&name)
+        .map_err(|_| ApiError::internal("Failed to get namespace"))?
+        .ok_or_else(|| ApiError::not_found("Namespace not found"))?;
+
+    let mut members = Vec::new();
+    for member in list_members(&state, &namespace.id)? {
+        let Some(repo) = state
+            .store
+            .get_repo_by_id(&member.repo_id)
+            .map_err(|_| ApiError::internal("Failed to get repository"))?
+        else {
+            continue;
+        };
+        members.push(SharedObjectMemberResponse {
+            repo_id: repo.id,
+            name: repo.name,
+            size_bytes: repo.size_bytes,
+            joined_at: member.joined_at,
+        });
+    }
+    let size_bytes = if members.is_empty() {
+        0
+    } else {
+        calculate_repo_size(&store_path(&state, &namespace.id))
+            .await
+            .unwrap_or(0)
+    };
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(SharedObjectsResponse {
+        namespace: namespace.name,
+        size_bytes,
+        members,
+    })))
+}
+
+/// PUT /admin/repos/{id}/shared-objects - Move a repo's objects into its
+/// namespace's shared object store, which every other member can then
+/// borrow from
+pub async fn join_shared_objects(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let repo = get_repo(&state, &id)?;
+    let _guard = state.shared_objects.lock(&repo.namespace_id).await;
+    if get_member(&state, &repo)?.is_some() {
+        return Err(ApiError::conflict("Repository already shares its objects"));
+    }
+    if let Some(other) = list_members(&state, &repo.namespace_id)?
+        .first()
+        .map(|member| get_repo(&state, &member.repo_id))
+        .transpose()?
+        && other.object_format != repo.object_format
+    {
+        return Err(ApiError::conflict(format!(
+            "The namespace's shared objects are {}, but the repository is {}",
+            other.object_format, repo.object_format
+        )));
+    }
+
+    let path = state.repo_path(&repo.namespace_id, &repo.name);
+    if !path.exists() {
+        return Err(ApiError::conflict("Repository has no objects yet"));
+    }
+    if borrows_objects(&path) {
+        return Err(ApiError::conflict(
+            "Repository already borrows objects from another store",
+        ));
+    }
+    shared_objects::join(
+        &store_path(&state, &repo.namespace_id),
+        &path,
+        &repo.id,
+        repo.object_format,
+        &state.shared_objects.git_limits(&state.limits.maintenance),
+    )
+    .await
+    .map_err(|e| ApiError::internal(format!("Failed to share objects: {e}")))?;
+
+    let member = SharedObjectMember {
+        repo_id: repo.id.clone(),
+        namespace_id: repo.namespace_id.clone(),
+        joined_at: Utc::now(),
+    };
+    state
+        .store
+        .create_shared_object_member(&member)
+        .map_err(|_| ApiError::internal("Failed to record shared objects"))?;
+    update_size(&state, &repo).await;
+
+    Ok::<_, ApiError>((StatusCode::CREATED, Json(ApiResponse::success(member))))
+}
+
+/// DELETE /admin/repos/{id}/shared-objects - Copy back the objects a repo
+/// borrows and stop it sharing. The store goes with its last member.
+pub async fn leave_shared_objects(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let repo = get_repo(&state, &id)?;
+    let _guard = state.shared_objects.lock(&repo.namespace_id).await;
+    if get_member(&state, &repo)?.is_none() {
+        return Err(ApiError::not_found("Repository doesn't share its objects"));
+    }
+    let last = list_members(&state, &repo.namespace_id)?.len() == 1;
+
+    shared_objects::leave(
+        &store_path(&state, &repo.namespace_id),
+        &state.repo_path(&repo.namespace_id, &repo.name),
+        &repo.id,
+        last,
+        &state.shared_objects.git_limits(&state.limits.maintenance),
+    )
+    .await
+    .map_err(|e| ApiError::internal(format!("Failed to stop sharing objects: {e}")))?;
+
+    state
+        .store
+        .delete_shared_object_member(&repo.id)
+        .map_err(|_| ApiError::internal("Failed to record shared objects"))?;
+    update_size(&state, &repo).await;
+
+    Ok::<_, ApiError>(StatusCode::NO_CONTENT)
+}
+
+fn get_repo(state: &AppState, id: &str) -> Result<Repo, ApiError> {
+    state
+        .store
+        .get_repo_by_id(id)
+        .map_err(|_| ApiError::internal("Failed to get repository"))?
+        .ok_or_else(|| ApiError::not_found("Repository not found"))
+}
+
+fn get_member(state: &AppState, repo: &Repo) -> Result<Option<SharedObjectMember>, ApiError> {
+    state
+        .store
+        .get_shared_object_member(&repo.id)
+        .map_err(|_| ApiError::internal("Failed to get shared objects"))
+}
+
+fn list_members(state: &AppState, namespace_id: &str) -> Result<Vec<SharedObjectMember>, ApiError> {
+    state
+        .store
+        .list_shared_object_members(namespace_id)
+        .map_err(|_| ApiError::internal("Failed to list shared objects"))
+}
+
+/// Joining and leaving change how much the repo stores itself.
+async fn update_size(state: &AppState, repo: &Repo) {
+    let path = state.repo_path(&repo.namespace_id, &repo.name);
+    match calculate_repo_size(&path).await {
+        Ok(size) => {
+            if let Err(e) = state.store.update_repo_size(&repo.id, size) {
+                warn!("Failed to update repo size: {e}");
+            }
+        }
+        Err(e) => warn!("Failed to calculate repo size: {e}"),
+    }
+}
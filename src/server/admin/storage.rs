@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use tracing::warn;
+
+use crate::auth::RequireAdmin;
+use crate::server::AppState;
+use crate::server::dto::{DedupPairResponse, DedupParams, DedupRepoResponse, DedupReportResponse};
+use crate::server::git::dedup::{OverlapCounter, repo_objects};
+use crate::server::response::{ApiError, ApiResponse};
+use crate::types::Namespace;
+
+const PAGE_SIZE: i32 = 500;
+const DEFAULT_TOP: usize = 20;
+const MAX_TOP: usize = 100;
+
+/// GET /admin/storage/dedup - How much object storage repos duplicate
+/// between them, and which pairs would gain most from sharing it. Scans
+/// every repo's objects directory, so it takes a while on large servers.
+pub async fn get_dedup_report(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<DedupParams>,
+) -> impl IntoResponse {
+    let top = params.top.unwrap_or(DEFAULT_TOP);
+    if top > MAX_TOP {
+        return Err(ApiError::bad_request(format!(
+            "top must be at most {MAX_TOP}"
+        )));
+    }
+    let namespaces = match params.namespace.as_deref().filter(|n| !n.is_empty()) {
+        Some(name) => vec![
+            state
+                .store
+                .get_namespace_by_name(name)
+                .map_err(|_| ApiError::internal("Failed to get namespace"))?
+                .ok_or_else(|| ApiError::not_found("Namespace not found"))?,
+        ],
+        None => list_all_namespaces(&state)?,
+    };
+
+    let report = tokio::task::spawn_blocking(move || dedup_report(&state, &namespaces, top))
+        .await
+        .map_err(|_| ApiError::internal("Dedup scan failed"))??;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(report)))
+}
+
+fn list_all_namespaces(state: &AppState) -> Result<Vec<Namespace>, ApiError> {
+    let mut all = Vec::new();
+    loop {
+        let cursor = all.last().map_or("", |ns: &Namespace| ns.id.as_str());
+        let page = state
+            .store
+            .list_namespaces(cursor, PAGE_SIZE)
+            .map_err(|_| ApiError::internal("Failed to list namespaces"))?;
+        let full_page = page.len() as i32 == PAGE_SIZE;
+        all.extend(page);
+        if !full_page {
+            return Ok(all);
+        }
+    }
+}
+
+fn dedup_report(
+    state: &AppState,
+    namespaces: &[Namespace],
+    top: usize,
+) -> Result<DedupReportResponse, ApiError> {
+    let mut counter = OverlapCounter::default();
+    let mut repos = Vec::new();
+    let mut skipped = 0;
+    for namespace in namespaces {
+        let mut cursor = String::new();
+        loop {
+            let page = state
+                .store
+                .list_repos(&namespace.id, &cursor, PAGE_SIZE)
+                .map_err(|_| ApiError::internal("Failed to list repositories"))?;
+            let full_page = page.len() as i32 == PAGE_SIZE;
+            for repo in &page {
+                let path = state.repo_path(&repo.namespace_id, &repo.name);
+                let objects = match repo_objects(&path, repo.object_format) {
+                    Ok(objects) => objects,
+                    Err(e) => {
+                        warn!("Skipping {} in dedup scan: {e}", repo.id);
+                        skipped += 1;
+                        continue;
+                    }
+                };
+                repos.push(DedupRepoResponse {
+                    id: repo.id.clone(),
+                    namespace: namespace.name.clone(),
+                    name: repo.name.clone(),
+                    objects: objects.len() as u64,
+                    bytes: objects.values().sum(),
+                });
+                counter.add_repo(objects);
+            }
+            match page.last() {
+                Some(last) if full_page => cursor = last.name.clone(),
+                _ => break,
+            }
+        }
+    }
+
+    let report = counter.report(top);
+    Ok(DedupReportResponse {
+        repos_scanned: repos.len() as u64,
+        repos_skipped: skipped,
+        total_objects: report.total_objects,
+        total_bytes: report.total_bytes,
+        unique_objects: report.unique_objects,
+        unique_bytes: report.unique_bytes,
+        shared_objects: report.shared_objects,
+        savings_bytes: report.savings_bytes(),
+        pairs: report
+            .pairs
+            .into_iter()
+            .map(|pair| DedupPairResponse {
+                a: repos[pair.a].clone(),
+                b: repos[pair.b].clone(),
+                shared_objects: pair.shared_objects,
+                shared_bytes: pair.shared_bytes,
+            })
+            .collect(),
+    })
+}
@@ -7,30 +7,60 @@ use axum::{
     response::IntoResponse,
 };
 
-use crate::auth::RequireAdmin;
+use chrono::{Duration, Utc};
+
+use crate::auth::{RequireAdmin, TokenFormat, TokenGenerator, reissue_token};
 use crate::server::AppState;
 use crate::server::dto::{
-    NamespaceGrantResponse, PaginationParams, RepoGrantResponse, TokenResponse,
+    CreateTokenResponse, LegacyTokenReportResponse, ListTokensParams, NamespaceGrantResponse,
+    ReissueTokenRequest, RepoGrantResponse, RevokeTokensRequest, RevokeTokensResponse, SortOrder,
+    TokenResponse,
 };
 use crate::server::response::{
     ApiError, ApiResponse, DEFAULT_PAGE_SIZE, PaginatedResponse, paginate,
 };
-use crate::types::Token;
+use crate::types::{Token, TokenFilter, TokenSort};
+
+/// Most tokens one page of `GET /admin/tokens` may hold.
+const MAX_PAGE_SIZE: i32 = 200;
 
+/// GET /admin/tokens - Tokens matching the filters, sorted and paginated
 pub async fn list_tokens(
     _admin: RequireAdmin,
     State(state): State<Arc<AppState>>,
-    Query(params): Query<PaginationParams>,
+    Query(params): Query<ListTokensParams>,
 ) -> impl IntoResponse {
     let cursor = params.cursor.as_deref().unwrap_or("");
+    if params.sort != TokenSort::Id && !cursor.is_empty() && !cursor.contains('|') {
+        return Err(ApiError::bad_request("Invalid cursor"));
+    }
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let filter = TokenFilter {
+        principal_id: params.principal_id,
+        is_admin: params.is_admin,
+        expired: params.expired,
+        unused_days: params.unused_days,
+    };
 
     let tokens = state
         .store
-        .list_tokens(cursor, DEFAULT_PAGE_SIZE + 1)
+        .search_tokens(
+            &filter,
+            params.sort,
+            params.order == SortOrder::Desc,
+            cursor,
+            limit + 1,
+        )
         .map_err(|_| ApiError::internal("Failed to list tokens"))?;
 
-    let (tokens, next_cursor, has_more) =
-        paginate(tokens, DEFAULT_PAGE_SIZE as usize, |t| t.id.clone());
+    let sort = params.sort;
+    let (tokens, next_cursor, has_more) = paginate(tokens, limit as usize, |t| match sort {
+        TokenSort::Id => t.id.clone(),
+        _ => format!("{}|{}", sort.key(t), t.id),
+    });
 
     let responses: Vec<TokenResponse> = tokens
         .into_iter()
@@ -44,6 +74,62 @@ pub async fn list_tokens(
     )))
 }
 
+/// POST /admin/tokens/revoke - Revoke every token a filter matches
+pub async fn revoke_tokens(
+    admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<RevokeTokensRequest>,
+) -> impl IntoResponse {
+    if req.filter.is_empty() {
+        return Err(ApiError::bad_request(
+            "Set at least one filter; revoking every token at once isn't supported",
+        ));
+    }
+
+    let mut token_ids = Vec::new();
+    let mut cursor = String::new();
+    loop {
+        let page = state
+            .store
+            .search_tokens(
+                &req.filter,
+                TokenSort::Id,
+                false,
+                &cursor,
+                DEFAULT_PAGE_SIZE,
+            )
+            .map_err(|_| ApiError::internal("Failed to list tokens"))?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        cursor = last.id.clone();
+        let full = page.len() == DEFAULT_PAGE_SIZE as usize;
+        token_ids.extend(
+            page.into_iter()
+                .map(|t| t.id)
+                .filter(|id| *id != admin.0.id),
+        );
+        if !full {
+            break;
+        }
+    }
+
+    if !req.dry_run {
+        for id in &token_ids {
+            state
+                .store
+                .delete_token(id)
+                .map_err(|_| ApiError::internal("Failed to delete token"))?;
+        }
+    }
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(RevokeTokensResponse {
+        dry_run: req.dry_run,
+        revoked: token_ids.len(),
+        token_ids,
+    })))
+}
+
 pub async fn get_token(
     _admin: RequireAdmin,
     State(state): State<Arc<AppState>>,
@@ -83,6 +169,86 @@ pub async fn delete_token(
     Ok::<_, ApiError>(StatusCode::NO_CONTENT)
 }
 
+/// How recently a legacy token must have been used to count as in use.
+const RECENT_USE_DAYS: i64 = 30;
+
+/// GET /admin/tokens/legacy - Tokens still in the legacy format
+pub async fn list_legacy_tokens(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let mut legacy = Vec::new();
+    let mut cursor = String::new();
+    loop {
+        let page = state
+            .store
+            .list_tokens(&cursor, DEFAULT_PAGE_SIZE)
+            .map_err(|_| ApiError::internal("Failed to list tokens"))?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        cursor = last.id.clone();
+        let full = page.len() == DEFAULT_PAGE_SIZE as usize;
+        legacy.extend(
+            page.into_iter()
+                .filter(|t| TokenFormat::of_lookup(&t.token_lookup) == TokenFormat::Legacy),
+        );
+        if !full {
+            break;
+        }
+    }
+
+    let recent = Utc::now() - Duration::days(RECENT_USE_DAYS);
+    let recently_used = legacy
+        .iter()
+        .filter(|t| t.last_used_at.is_some_and(|at| at >= recent))
+        .count();
+    let tokens = legacy
+        .into_iter()
+        .map(|t| token_to_response(&state, t))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(LegacyTokenReportResponse {
+        accepted: state.legacy_tokens.accept,
+        total: tokens.len(),
+        recently_used,
+        tokens,
+    })))
+}
+
+/// POST /admin/tokens/{id}/reissue - Replace a token with a current-format one
+pub async fn reissue_token_handler(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    body: Option<Json<ReissueTokenRequest>>,
+) -> impl IntoResponse {
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+    if req.grace_seconds.is_some_and(|s| s < 0) {
+        return Err(ApiError::bad_request("grace_seconds cannot be negative"));
+    }
+
+    let token = state
+        .store
+        .get_token_by_id(&id)
+        .map_err(|_| ApiError::internal("Failed to get token"))?
+        .ok_or_else(|| ApiError::not_found("Token not found"))?;
+
+    let generator = TokenGenerator::new();
+    let grace = req.grace_seconds.map(Duration::seconds);
+    let (token, raw_token) = reissue_token(state.store.as_ref(), &generator, &token, grace)
+        .map_err(|_| ApiError::internal("Failed to reissue token"))?;
+
+    let metadata = token_to_response(&state, token)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse::success(CreateTokenResponse {
+            token: raw_token,
+            metadata,
+        })),
+    ))
+}
+
 pub fn token_to_response(state: &Arc<AppState>, token: Token) -> Result<TokenResponse, ApiError> {
     let mut response = TokenResponse {
         id: token.id,
@@ -91,6 +257,8 @@ pub fn token_to_response(state: &Arc<AppState>, token: Token) -> Result<TokenRes
         created_at: token.created_at,
         expires_at: token.expires_at,
         last_used_at: token.last_used_at,
+        traffic_class: token.traffic_class,
+        format: TokenFormat::of_lookup(&token.token_lookup),
         namespace_grants: Vec::new(),
         repo_grants: Vec::new(),
     };
@@ -122,6 +290,7 @@ pub fn token_to_response(state: &Arc<AppState>, token: Token) -> Result<TokenRes
                     repo_id: g.repo_id,
                     allow: g.allow_bits.to_strings(),
                     deny: g.deny_bits.to_strings(),
+                    paths: g.paths,
                 })
                 .collect();
         }
@@ -0,0 +1,238 @@
+//! Permission decisions from an external policy service such as Open
+//! Policy Agent. Each check POSTs `{"input": {...}}` describing the
+//! principal, the permission, and the namespace or repo, and reads a
+//! boolean `result` (or `result.allow`) back, the shape of OPA's data API.
+//!
+//! Answers are cached for `cache_ttl`. When the service can't be reached,
+//! errors, or returns no result, the decision falls back to local grants
+//! and isn't cached.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::Url;
+use serde_json::{Value, json};
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tracing::{debug, warn};
+
+use super::{Authorizer, GrantAuthorizer};
+use crate::config::AuthzConfig;
+use crate::server::response::ApiError;
+use crate::store::Store;
+use crate::types::{Permission, Principal, Repo};
+
+/// Cached answers past this many are pruned of expired ones.
+const MAX_CACHE_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    principal_id: String,
+    /// `namespace:<id>` or `repo:<id>`.
+    resource: String,
+    permission: u32,
+}
+
+pub struct ExternalAuthorizer {
+    client: reqwest::Client,
+    url: Url,
+    token: Option<String>,
+    timeout: Duration,
+    cache_ttl: Duration,
+    cache: Mutex<HashMap<CacheKey, (bool, Instant)>>,
+    fallback: GrantAuthorizer,
+}
+
+impl ExternalAuthorizer {
+    pub fn new(config: &AuthzConfig) -> anyhow::Result<Self> {
+        let url = Url::parse(&config.url)
+            .map_err(|e| anyhow::anyhow!("Invalid authz url {}: {e}", config.url))?;
+        Ok(Self {
+            client: reqwest::Client::new(),
+            url,
+            token: config.token.clone().filter(|t| !t.is_empty()),
+            timeout: Duration::from_secs(config.timeout_secs),
+            cache_ttl: Duration::from_secs(config.cache_secs),
+            cache: Mutex::new(HashMap::new()),
+            fallback: GrantAuthorizer,
+        })
+    }
+
+    fn cached(&self, key: &CacheKey) -> Option<bool> {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache
+            .get(key)
+            .filter(|(_, expires)| *expires > Instant::now())
+            .map(|(allowed, _)| *allowed)
+    }
+
+    fn remember(&self, key: CacheKey, allowed: bool) {
+        if self.cache_ttl.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if cache.len() >= MAX_CACHE_ENTRIES {
+            cache.retain(|_, (_, expires)| *expires > now);
+        }
+        cache.insert(key, (allowed, now + self.cache_ttl));
+    }
+
+    /// The service's answer, or `None` if the grants should decide.
+    fn decide(&self, key: CacheKey, input: impl FnOnce() -> Value) -> Option<bool> {
+        if let Some(allowed) = self.cached(&key) {
+            return Some(allowed);
+        }
+        match self.ask(input()) {
+            Ok(Some(allowed)) => {
+                self.remember(key, allowed);
+                Some(allowed)
+            }
+            Ok(None) => {
+                debug!(
+                    "Policy service had no decision for {} on {}; using grants",
+                    key.principal_id, key.resource
+                );
+                None
+            }
+            Err(e) => {
+                warn!("Policy service check failed, using grants: {e}");
+                None
+            }
+        }
+    }
+
+    /// Permission checks run in synchronous code, so the request blocks
+    /// this worker thread while other tasks move to the rest.
+    fn ask(&self, input: Value) -> Result<Option<bool>, String> {
+        let handle = Handle::try_current().map_err(|e| e.to_string())?;
+        if handle.runtime_flavor() != RuntimeFlavor::MultiThread {
+            return Err("policy checks need the multi-threaded runtime".to_string());
+        }
+        let mut request = self
+            .client
+            .post(self.url.clone())
+            .timeout(self.timeout)
+            .json(&json!({ "input": input }));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        tokio::task::block_in_place(|| {
+            handle.block_on(async {
+                let response = request.send().await.map_err(|e| e.to_string())?;
+                let status = response.status();
+                if !status.is_success() {
+                    return Err(format!("{} returned {status}", self.url));
+                }
+                let body: Value = response.json().await.map_err(|e| e.to_string())?;
+                Ok(decision(&body))
+            })
+        })
+    }
+}
+
+/// `result` as a boolean, or its `allow` field; `None` when the policy
+/// left it undefined.
+fn decision(body: &Value) -> Option<bool> {
+    match body.get("result")? {
+        Value::Bool(allowed) => Some(*allowed),
+        result => result.get("allow")?.as_bool(),
+    }
+}
+
+fn principal_input(principal: &Principal) -> Value {
+    json!({
+        "id": principal.id,
+        "primary_namespace_id": principal.primary_namespace_id,
+    })
+}
+
+fn namespace_input(store: &dyn Store, namespace_id: &str) -> Value {
+    let name = store
+        .get_namespace(namespace_id)
+        .ok()
+        .flatten()
+        .map(|ns| ns.name);
+    json!({ "id": namespace_id, "name": name })
+}
+
+fn permission_name(permission: Permission) -> String {
+    permission.to_strings().join(",")
+}
+
+impl Authorizer for ExternalAuthorizer {
+    fn namespace_permission(
+        &self,
+        store: &dyn Store,
+        principal: &Principal,
+        namespace_id: &str,
+        required: Permission,
+    ) -> Result<bool, ApiError> {
+        let key = CacheKey {
+            principal_id: principal.id.clone(),
+            resource: format!("namespace:{namespace_id}"),
+            permission: required.bits(),
+        };
+        let input = || {
+            json!({
+                "principal": principal_input(principal),
+                "permission": permission_name(required),
+                "namespace": namespace_input(store, namespace_id),
+            })
+        };
+        match self.decide(key, input) {
+            Some(allowed) => Ok(allowed),
+            None => self
+                .fallback
+                .namespace_permission(store, principal, namespace_id, required),
+        }
+    }
+
+    fn repo_permission(
+        &self,
+        store: &dyn Store,
+        principal: &Principal,
+        repo: &Repo,
+        required: Permission,
+    ) -> Result<bool, ApiError> {
+        let key = CacheKey {
+            principal_id: principal.id.clone(),
+            resource: format!("repo:{}", repo.id),
+            permission: required.bits(),
+        };
+        let input = || {
+            json!({
+                "principal": principal_input(principal),
+                "permission": permission_name(required),
+                "namespace": namespace_input(store, &repo.namespace_id),
+                "repo": {
+                    "id": repo.id,
+                    "name": repo.name,
+                    "visibility": repo.visibility,
+                },
+            })
+        };
+        match self.decide(key, input) {
+            Some(allowed) => Ok(allowed),
+            None => self
+                .fallback
+                .repo_permission(store, principal, repo, required),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decision() {
+        assert_eq!(decision(&json!({"result": true})), Some(true));
+        assert_eq!(decision(&json!({"result": false})), Some(false));
+        assert_eq!(decision(&json!({"result": {"allow": true}})), Some(true));
+        assert_eq!(decision(&json!({"result": {"deny": true}})), None);
+        assert_eq!(decision(&json!({})), None);
+        assert_eq!(decision(&json!({"result": "yes"})), None);
+    }
+}
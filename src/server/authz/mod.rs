@@ -0,0 +1,104 @@
+//! Permission decisions. By default they come from the grants in the
+//! store; with `[authz]` configured they are delegated to an external
+//! policy service (see [`ExternalAuthorizer`]), which falls back to the
+//! grants when it has no answer.
+//!
+//! Only checks against a principal go through the authorizer. Visibility
+//! still lets anyone read public repositories and any principal read
+//! internal ones, and listings show what local grants reach.
+
+#[cfg(feature = "authz")]
+mod external;
+
+use crate::server::response::{ApiError, StoreResultExt};
+use crate::store::Store;
+use crate::types::{Permission, Principal, Repo};
+
+#[cfg(feature = "authz")]
+pub use external::ExternalAuthorizer;
+
+/// Decides whether a principal holds a permission on a namespace or repo.
+pub trait Authorizer: Send + Sync {
+    fn namespace_permission(
+        &self,
+        store: &dyn Store,
+        principal: &Principal,
+        namespace_id: &str,
+        required: Permission,
+    ) -> Result<bool, ApiError>;
+
+    fn repo_permission(
+        &self,
+        store: &dyn Store,
+        principal: &Principal,
+        repo: &Repo,
+        required: Permission,
+    ) -> Result<bool, ApiError>;
+}
+
+/// Decides from namespace and repo grants. Primary namespace owners have
+/// full access, and repo permissions combine namespace-level and
+/// repo-level grants, with denies winning.
+pub struct GrantAuthorizer;
+
+impl Authorizer for GrantAuthorizer {
+    fn namespace_permission(
+        &self,
+        store: &dyn Store,
+        principal: &Principal,
+        namespace_id: &str,
+        required: Permission,
+    ) -> Result<bool, ApiError> {
+        if principal.primary_namespace_id == namespace_id {
+            return Ok(true);
+        }
+
+        let grant = store
+            .get_namespace_grant(&principal.id, namespace_id)
+            .api_err("Failed to check namespace grant")?;
+
+        Ok(grant
+            .map(|g| {
+                g.allow_bits
+                    .expand_implied()
+                    .difference(g.deny_bits.expand_denied())
+                    .has(required)
+            })
+            .unwrap_or(false))
+    }
+
+    fn repo_permission(
+        &self,
+        store: &dyn Store,
+        principal: &Principal,
+        repo: &Repo,
+        required: Permission,
+    ) -> Result<bool, ApiError> {
+        if principal.primary_namespace_id == repo.namespace_id {
+            return Ok(true);
+        }
+
+        let ns_grant = store
+            .get_namespace_grant(&principal.id, &repo.namespace_id)
+            .api_err("Failed to check namespace grant")?;
+
+        let repo_grant = store
+            .get_repo_grant(&principal.id, &repo.id)
+            .api_err("Failed to check repo grant")?;
+
+        let mut allow = Permission::default();
+        let mut deny = Permission::default();
+
+        if let Some(grant) = ns_grant {
+            allow = allow.union(grant.allow_bits.expand_implied());
+            deny = deny.union(grant.deny_bits.expand_denied());
+        }
+
+        if let Some(grant) = repo_grant {
+            allow = allow.union(grant.allow_bits.expand_implied());
+            deny = deny.union(grant.deny_bits.expand_denied());
+        }
+
+        Ok(allow.difference(deny).has(required))
+    }
+}
@@ -8,24 +8,38 @@ use axum::{
 };
 use serde_json::json;
 
-use crate::auth::{TokenValidationError, extract_token_from_header, validate_token};
+use crate::auth::{
+    TokenValidationError, extract_token_from_header, sudo_principal_id, validate_token_with_sudo,
+};
 use crate::server::AppState;
 use crate::server::response::ApiError;
 use crate::server::user::access::check_repo_permission;
-use crate::types::{Permission, Principal, Repo, Token};
+use crate::types::{Permission, Principal, Repo, Token, TrafficClass};
 
 pub struct OptionalAuth {
     pub principal: Option<Principal>,
-    #[allow(dead_code)]
     pub token: Option<Token>,
 }
 
+impl OptionalAuth {
+    /// Anonymous requests are scheduled as interactive.
+    #[must_use]
+    pub fn traffic_class(&self) -> TrafficClass {
+        self.token
+            .as_ref()
+            .map_or(TrafficClass::Interactive, |t| t.traffic_class)
+    }
+}
+
 #[derive(Debug)]
 pub enum OptionalAuthError {
     InvalidScheme,
     InvalidToken,
+    TokenRetired,
     TokenExpired,
     AdminTokenNotAllowed,
+    SudoNotAllowed,
+    SudoPrincipalNotFound,
     InternalError,
 }
 
@@ -34,11 +48,17 @@ impl IntoResponse for OptionalAuthError {
         let (status, message) = match self {
             Self::InvalidScheme => (StatusCode::UNAUTHORIZED, "Invalid authorization scheme"),
             Self::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
+            Self::TokenRetired => (
+                StatusCode::UNAUTHORIZED,
+                "Token format retired; reissue it or create a new token",
+            ),
             Self::TokenExpired => (StatusCode::UNAUTHORIZED, "Token expired"),
             Self::AdminTokenNotAllowed => (
                 StatusCode::FORBIDDEN,
                 "Admin token cannot be used for this operation",
             ),
+            Self::SudoNotAllowed => (StatusCode::FORBIDDEN, "Sudo requires an admin token"),
+            Self::SudoPrincipalNotFound => (StatusCode::NOT_FOUND, "Sudo principal not found"),
             Self::InternalError => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
         };
 
@@ -86,13 +106,22 @@ impl FromRequestParts<Arc<AppState>> for OptionalAuth {
             }
         };
 
-        let validated = validate_token(state, &raw_token, false).map_err(|e| match e {
-            TokenValidationError::InvalidScheme => OptionalAuthError::InvalidScheme,
-            TokenValidationError::InvalidToken => OptionalAuthError::InvalidToken,
-            TokenValidationError::TokenExpired => OptionalAuthError::TokenExpired,
-            TokenValidationError::AdminTokenNotAllowed => OptionalAuthError::AdminTokenNotAllowed,
-            TokenValidationError::InternalError => OptionalAuthError::InternalError,
-        })?;
+        let sudo = sudo_principal_id(&parts.headers);
+        let validated =
+            validate_token_with_sudo(state, &raw_token, sudo, false).map_err(|e| match e {
+                TokenValidationError::InvalidScheme => OptionalAuthError::InvalidScheme,
+                TokenValidationError::InvalidToken => OptionalAuthError::InvalidToken,
+                TokenValidationError::TokenRetired => OptionalAuthError::TokenRetired,
+                TokenValidationError::TokenExpired => OptionalAuthError::TokenExpired,
+                TokenValidationError::AdminTokenNotAllowed => {
+                    OptionalAuthError::AdminTokenNotAllowed
+                }
+                TokenValidationError::SudoNotAllowed => OptionalAuthError::SudoNotAllowed,
+                TokenValidationError::SudoPrincipalNotFound => {
+                    OptionalAuthError::SudoPrincipalNotFound
+                }
+                TokenValidationError::InternalError => OptionalAuthError::InternalError,
+            })?;
 
         Ok(OptionalAuth {
             principal: validated.principal,
@@ -106,7 +135,7 @@ pub fn check_content_access(
     auth: &OptionalAuth,
     repo: &Repo,
 ) -> Result<(), ApiError> {
-    if repo.public {
+    if repo.visibility.allows_anonymous_read() && state.allows_anonymous_read() {
         return Ok(());
     }
 
@@ -115,7 +144,11 @@ pub fn check_content_access(
         .as_ref()
         .ok_or_else(|| ApiError::unauthorized("Authentication required"))?;
 
-    let has_read = check_repo_permission(state.store.as_ref(), principal, repo, Permission::REPO_READ)?;
+    if repo.visibility.allows_authenticated_read() {
+        return Ok(());
+    }
+
+    let has_read = check_repo_permission(state, principal, repo, Permission::REPO_READ)?;
 
     if !has_read {
         return Err(ApiError::forbidden("Access denied"));
@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::Response,
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+
+use crate::server::AppState;
+use crate::server::response::ApiError;
+use crate::types::Repo;
+use crate::units::format_size;
+
+use super::auth::OptionalAuth;
+use super::etag::{self, with_etag};
+use super::git_ops::resolve_ref;
+use super::handlers::load_repo_and_check_access;
+
+/// Badges are cheap to regenerate but are fetched on every README view.
+const BADGE_MAX_AGE_SECS: u32 = 300;
+
+const COLOR_BRIGHTGREEN: &str = "#4c1";
+const COLOR_GREEN: &str = "#97ca00";
+const COLOR_YELLOW: &str = "#dfb317";
+const COLOR_BLUE: &str = "#007ec6";
+const COLOR_GREY: &str = "#9f9f9f";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BadgeKind {
+    LastCommit,
+    Size,
+    Release,
+}
+
+impl BadgeKind {
+    fn parse(file: &str) -> Option<Self> {
+        match file.strip_suffix(".svg")? {
+            "last-commit" => Some(Self::LastCommit),
+            "size" => Some(Self::Size),
+            "release" => Some(Self::Release),
+            _ => None,
+        }
+    }
+}
+
+struct Badge {
+    label: &'static str,
+    message: String,
+    color: &'static str,
+}
+
+pub async fn get_badge(
+    auth: OptionalAuth,
+    State(state): State<Arc<AppState>>,
+    Path((id, file)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let kind = BadgeKind::parse(&file).ok_or_else(|| ApiError::not_found("Unknown badge kind"))?;
+
+    let (repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+
+    let badge = match kind {
+        BadgeKind::LastCommit => last_commit_badge(&git_repo),
+        BadgeKind::Size => size_badge(&repo),
+        BadgeKind::Release => release_badge(&git_repo),
+    };
+
+    let svg = render_svg(&badge);
+    let etag = format!("\"{}\"", hex::encode(&Sha256::digest(svg.as_bytes())[..16]));
+
+    let visibility = if repo.visibility.allows_anonymous_read() && state.allows_anonymous_read() {
+        "public"
+    } else {
+        "private"
+    };
+    let cache_control = format!("{visibility}, max-age={BADGE_MAX_AGE_SECS}");
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("image/svg+xml; charset=utf-8"),
+    );
+    if let Ok(value) = HeaderValue::from_str(&cache_control) {
+        response_headers.insert(header::CACHE_CONTROL, value);
+    }
+
+    if etag::matches(&headers, &etag) {
+        return Ok(with_etag(
+            (StatusCode::NOT_MODIFIED, response_headers),
+            &etag,
+        ));
+    }
+
+    Ok(with_etag((StatusCode::OK, response_headers, svg), &etag))
+}
+
+fn last_commit_badge(git_repo: &git2::Repository) -> Badge {
+    let commit_time = resolve_ref(git_repo, "HEAD")
+        .ok()
+        .and_then(|oid| git_repo.find_commit(oid).ok())
+        .map(|commit| commit.time().seconds());
+
+    match commit_time {
+        Some(time) => {
+            let age = (Utc::now().timestamp() - time).max(0);
+            let color = match age / 86_400 {
+                0..7 => COLOR_BRIGHTGREEN,
+                7..30 => COLOR_GREEN,
+                30..180 => COLOR_YELLOW,
+                _ => COLOR_GREY,
+            };
+            Badge {
+                label: "last commit",
+                message: format_age(age),
+                color,
+            }
+        }
+        None => Badge {
+            label: "last commit",
+            message: "none".to_string(),
+            color: COLOR_GREY,
+        },
+    }
+}
+
+fn size_badge(repo: &Repo) -> Badge {
+    Badge {
+        label: "repo size",
+        message: format_size(repo.size_bytes),
+        color: COLOR_BLUE,
+    }
+}
+
+fn release_badge(git_repo: &git2::Repository) -> Badge {
+    let latest = git_repo
+        .tag_names(None)
+        .ok()
+        .and_then(|names| {
+            names
+                .iter()
+                .flatten()
+                .filter_map(|name| parse_version(name).map(|v| (v, name.to_string())))
+                .max()
+        })
+        .map(|(_, name)| name);
+
+    match latest {
+        Some(name) => Badge {
+            label: "release",
+            message: name,
+            color: COLOR_BLUE,
+        },
+        None => Badge {
+            label: "release",
+            message: "none".to_string(),
+            color: COLOR_GREY,
+        },
+    }
+}
+
+/// Parses tags like `v1.2.3` or `2.0` into comparable numeric components.
+/// Pre-release suffixes (`-rc1`) sort below the matching release.
+fn parse_version(tag: &str) -> Option<(Vec<u64>, bool)> {
+    let tag = tag.strip_prefix('v').unwrap_or(tag);
+    let (core, pre) = match tag.split_once('-') {
+        Some((core, _)) => (core, true),
+        None => (tag, false),
+    };
+
+    let parts = core
+        .split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    Some((parts, !pre))
+}
+
+pub(super) fn format_age(secs: i64) -> String {
+    let (value, unit) = match secs {
+        0..60 => return "just now".to_string(),
+        60..3_600 => (secs / 60, "minute"),
+        3_600..86_400 => (secs / 3_600, "hour"),
+        86_400..2_592_000 => (secs / 86_400, "day"),
+        2_592_000..31_536_000 => (secs / 2_592_000, "month"),
+        _ => (secs / 31_536_000, "year"),
+    };
+    let plural = if value == 1 { "" } else { "s" };
+    format!("{value} {unit}{plural} ago")
+}
+
+/// Approximate rendered width of `text` in 11px Verdana.
+fn text_width(text: &str) -> u32 {
+    text.chars()
+        .map(|c| match c {
+            'i' | 'l' | 'j' | '.' | ',' | ':' | '\'' | '|' | ' ' => 4,
+            'm' | 'w' | 'M' | 'W' => 10,
+            c if c.is_ascii_uppercase() || c.is_ascii_digit() => 8,
+            _ => 7,
+        })
+        .sum()
+}
+
+pub(super) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn render_svg(badge: &Badge) -> String {
+    let label_width = text_width(badge.label) + 10;
+    let message_width = text_width(&badge.message) + 10;
+    let width = label_width + message_width;
+    let label_x = label_width / 2;
+    let message_x = label_width + message_width / 2;
+    let label = escape_xml(badge.label);
+    let message = escape_xml(&badge.message);
+    let color = badge.color;
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">
+<title>{label}: {message}</title>
+<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+<text x="{label_x}" y="15" fill="#010101" fill-opacity=".3">{label}</text><text x="{label_x}" y="14">{label}</text>
+<text x="{message_x}" y="15" fill="#010101" fill-opacity=".3">{message}</text><text x="{message_x}" y="14">{message}</text>
+</g>
+</svg>
+"##
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kind() {
+        assert_eq!(
+            BadgeKind::parse("last-commit.svg"),
+            Some(BadgeKind::LastCommit)
+        );
+        assert_eq!(BadgeKind::parse("size.svg"), Some(BadgeKind::Size));
+        assert_eq!(BadgeKind::parse("release"), None);
+        assert_eq!(BadgeKind::parse("ci.svg"), None);
+    }
+
+    #[test]
+    fn test_release_ordering() {
+        let mut tags = ["v1.10.0", "v1.2.0", "v2.0.0-rc1", "v1.9.9", "nightly"];
+        tags.sort_by_key(|t| parse_version(t));
+        assert_eq!(tags.last(), Some(&"v2.0.0-rc1"));
+        assert!(parse_version("v2.0.0") > parse_version("v2.0.0-rc1"));
+        assert!(parse_version("v1.10.0") > parse_version("v1.9.9"));
+        assert_eq!(parse_version("nightly"), None);
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(5), "just now");
+        assert_eq!(format_age(3_600), "1 hour ago");
+        assert_eq!(format_age(3 * 86_400), "3 days ago");
+    }
+
+    #[test]
+    fn test_render_escapes_message() {
+        let svg = render_svg(&Badge {
+            label: "release",
+            message: "<v1>".to_string(),
+            color: COLOR_BLUE,
+        });
+        assert!(svg.contains("&lt;v1&gt;"));
+        assert!(!svg.contains("<v1>"));
+    }
+}
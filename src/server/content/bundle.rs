@@ -0,0 +1,175 @@
+use std::process::Stdio;
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderValue, header},
+    response::{IntoResponse, Response},
+};
+use git2::{ObjectType, Oid, Repository};
+use serde::Deserialize;
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tokio_util::io::ReaderStream;
+use tracing::warn;
+
+use crate::server::AppState;
+use crate::server::limits::{apply_limits, deadline};
+use crate::server::response::ApiError;
+
+use super::auth::OptionalAuth;
+use super::git_ops::{GitError, resolve_ref};
+use super::handlers::{load_repo_and_check_access, repo_path};
+
+#[derive(Debug, Deserialize)]
+pub struct BundleParams {
+    /// Branch or tag to bundle. Every branch and tag when omitted.
+    #[serde(rename = "ref")]
+    ref_name: Option<String>,
+    /// Leave out history reachable from this commit, for an incremental
+    /// bundle the receiver applies on top of what it has.
+    since: Option<String>,
+}
+
+/// The full names and tip commits of the refs to bundle.
+fn bundle_refs(
+    git_repo: &Repository,
+    ref_name: Option<&str>,
+) -> Result<Vec<(String, Oid)>, GitError> {
+    let tip = |reference: &git2::Reference| reference.peel(ObjectType::Commit).ok().map(|c| c.id());
+
+    if let Some(name) = ref_name {
+        let reference = git_repo
+            .resolve_reference_from_short_name(name)
+            .and_then(|r| r.resolve())
+            .map_err(|_| GitError::RefNotFound(name.to_string()))?;
+        let full_name = reference
+            .name()
+            .ok_or_else(|| GitError::RefNotFound(name.to_string()))?
+            .to_string();
+        let oid = tip(&reference).ok_or_else(|| GitError::RefNotFound(name.to_string()))?;
+        return Ok(vec![(full_name, oid)]);
+    }
+
+    let mut refs = Vec::new();
+    let references = git_repo
+        .references()
+        .map_err(|e| GitError::Internal(e.to_string()))?;
+    for reference in references.flatten() {
+        let Some(name) = reference.name() else {
+            continue;
+        };
+        if !(name.starts_with("refs/heads/") || name.starts_with("refs/tags/")) {
+            continue;
+        }
+        if let Some(oid) = tip(&reference) {
+            refs.push((name.to_string(), oid));
+        }
+    }
+    if refs.is_empty() {
+        return Err(GitError::EmptyRepo);
+    }
+    Ok(refs)
+}
+
+/// GET /repos/{id}/bundle - Stream a git bundle of one ref or of every
+/// branch and tag, optionally only the history since a commit
+pub async fn get_bundle(
+    auth: OptionalAuth,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<BundleParams>,
+) -> Result<Response, ApiError> {
+    let (repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+
+    let ref_name = params.ref_name.as_deref().filter(|r| !r.is_empty());
+    let refs = bundle_refs(&git_repo, ref_name)?;
+
+    let mut args: Vec<String> = refs.iter().map(|(name, _)| name.clone()).collect();
+    // Lets `git clone` from a full bundle check out the default branch.
+    if ref_name.is_none() && git_repo.head().is_ok() {
+        args.push("HEAD".to_string());
+    }
+    if let Some(since) = params.since.as_deref().filter(|s| !s.is_empty()) {
+        let base = resolve_ref(&git_repo, since)?;
+        // git writes the bundle header before noticing there is nothing to
+        // pack, so reject empty bundles up front.
+        let has_new_history = refs.iter().any(|(_, tip)| {
+            *tip != base && !git_repo.graph_descendant_of(base, *tip).unwrap_or(false)
+        });
+        if !has_new_history {
+            return Err(ApiError::bad_request(format!(
+                "Nothing to bundle: {since} already contains every requested ref"
+            )));
+        }
+        args.push(format!("^{base}"));
+    }
+    drop(git_repo);
+
+    let path = repo_path(&state, &repo.namespace_id, &repo.name);
+    let slot = state
+        .clone_slot(&repo.id, &repo.namespace_id, auth.traffic_class())
+        .await;
+
+    let limits = &state.limits.fetch;
+    let mut cmd = Command::new("git");
+    cmd.arg("--git-dir")
+        .arg(&path)
+        .args(["bundle", "create", "--quiet", "-"])
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    apply_limits(&mut cmd, limits);
+    let timeout = limits.timeout(None);
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| ApiError::internal(format!("Failed to run git bundle: {e}")))?;
+    let (Some(stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err(ApiError::internal("Failed to capture git bundle output"));
+    };
+
+    // Reap git in the background, holding the fair-queue slot until it is
+    // done. The response has already started, so failures are only logged.
+    tokio::spawn(async move {
+        let mut diagnostics = String::new();
+        let run = async {
+            let _ = stderr.read_to_string(&mut diagnostics).await;
+            child.wait().await
+        };
+        let status = match deadline(timeout, run).await {
+            Some(status) => status,
+            None => {
+                warn!("git bundle exceeded its time limit; killing it");
+                let _ = child.start_kill();
+                child.wait().await
+            }
+        };
+        match status {
+            Ok(status) if !status.success() => {
+                warn!("git bundle exited with {status}: {}", diagnostics.trim());
+            }
+            Err(e) => warn!("Failed to wait for git bundle: {e}"),
+            Ok(_) => {}
+        }
+        drop(slot);
+    });
+
+    let filename: String = format!("{}.bundle", repo.name)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        .collect();
+    let mut response = Body::from_stream(ReaderStream::new(stdout)).into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/x-git-bundle"),
+    );
+    if let Ok(value) = HeaderValue::from_str(&format!("attachment; filename=\"{filename}\"")) {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+    Ok(response)
+}
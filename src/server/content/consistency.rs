@@ -0,0 +1,68 @@
+//! Read-your-writes consistency tokens.
+//!
+//! Every content API write is recorded in the change log, and the sequence
+//! number of that entry goes back to the client as a consistency token. A
+//! request that sends the token back in `X-Consistency-Token` is only
+//! answered once this server's store has reached that sequence number, and a
+//! token past anything in the change log is rejected. The only store today is
+//! its own primary, so a token from a write it made has always been reached;
+//! a replica that can lag would hold such requests until it catches up.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::warn;
+
+use crate::server::AppState;
+use crate::server::response::ApiError;
+
+pub const CONSISTENCY_HEADER: &str = "X-Consistency-Token";
+
+/// Records a content write to a repo and returns its consistency token.
+///
+/// The commit is already on disk by the time this runs, so a failure to
+/// record it is logged rather than failing the request; the token then falls
+/// back to the newest sequence number, which is still at or past the write.
+pub(super) fn record_write(state: &AppState, repo_id: &str) -> String {
+    let seq = state.store.record_repo_write(repo_id).or_else(|e| {
+        warn!("Failed to record content write to {repo_id}: {e}");
+        state.store.latest_change_seq()
+    });
+    seq.unwrap_or_default().to_string()
+}
+
+/// Middleware checking that the store has reached the consistency token a
+/// request carries. Requests without a token pass straight through, and
+/// tokens ahead of the change log get 412.
+pub(crate) async fn check_consistency(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(token) = request.headers().get(CONSISTENCY_HEADER) else {
+        return next.run(request).await;
+    };
+    let Some(seq) = token
+        .to_str()
+        .ok()
+        .and_then(|t| t.trim().parse::<i64>().ok())
+    else {
+        return ApiError::bad_request(format!("Invalid {CONSISTENCY_HEADER}")).into_response();
+    };
+
+    match state.store.latest_change_seq() {
+        Ok(latest) if seq > latest => ApiError {
+            status: StatusCode::PRECONDITION_FAILED,
+            message: format!("{CONSISTENCY_HEADER} is ahead of any committed write"),
+            data: None,
+        }
+        .into_response(),
+        Ok(_) => next.run(request).await,
+        Err(e) => ApiError::internal(format!("Failed to read change log: {e}")).into_response(),
+    }
+}
@@ -1,12 +1,18 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, Utc};
 use serde::{Deserialize, Serialize};
 
-pub const MAX_BLOB_SIZE: i64 = 1_048_576;
-pub const MAX_RAW_BLOB_SIZE: i64 = 100_000_000; // 100MB limit for raw blob downloads
+use crate::server::signing::VerificationReason;
+use crate::types::{CommitState, CommitStatus, SigningKeyKind};
+
 pub const MAX_TREE_DEPTH: i32 = 10;
 pub const DEFAULT_TREE_DEPTH: i32 = 1;
+pub const DEFAULT_TREE_PAGE_SIZE: i32 = 100;
+pub const MAX_TREE_PAGE_SIZE: i32 = 1000;
+/// Entries listed for each directory below the top level of a tree.
+pub const MAX_TREE_CHILDREN: usize = 100;
 pub const DEFAULT_PAGE_SIZE: i32 = 20;
 pub const MAX_PAGE_SIZE: i32 = 100;
+pub const MAX_BLAME_LINES: usize = 2000;
 
 #[derive(Debug, Serialize)]
 pub struct RefResponse {
@@ -27,6 +33,29 @@ pub struct CommitResponse {
     pub tree_sha: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stats: Option<CommitStats>,
+    /// Signature check against registered signing keys, on single-commit
+    /// and commit list responses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<VerificationResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerificationResponse {
+    pub verified: bool,
+    pub reason: VerificationReason,
+    /// Owner of the key that made the signature, when it is verified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signer: Option<SignerResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignerResponse {
+    /// Primary namespace of the principal the key is registered to.
+    pub namespace: Option<String>,
+    pub key_id: String,
+    pub key_name: String,
+    pub kind: SigningKeyKind,
+    pub fingerprint: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -55,8 +84,25 @@ pub struct TreeEntryResponse {
     pub size: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub has_children: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submodule: Option<SubmoduleResponse>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub children: Vec<TreeEntryResponse>,
+    /// Set when children were listed, and true when the directory has more
+    /// than `MAX_TREE_CHILDREN` of them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children_truncated: Option<bool>,
+}
+
+/// Where a submodule entry points, from `.gitmodules` at the same ref
+#[derive(Debug, Clone, Serialize)]
+pub struct SubmoduleResponse {
+    pub name: String,
+    /// Clone URL, with relative URLs resolved against this repository's.
+    pub url: String,
+    /// `namespace/repo` when the submodule is hosted on this server.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo_path: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -65,15 +111,47 @@ pub struct DiffResponse {
     pub base_sha: Option<String>,
     pub head_sha: String,
     pub stats: CommitStats,
-    pub patch: String,
+    /// The whole diff as one patch, left out when only files were asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patch: Option<String>,
+    pub files: Vec<DiffFileResponse>,
 }
 
 #[derive(Debug, Serialize)]
-pub struct BlameLineResponse {
-    pub line: usize,
+pub struct DiffFileResponse {
+    /// `added`, `deleted`, `modified`, `renamed`, `copied` or `type_changed`.
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_path: Option<String>,
+    pub additions: usize,
+    pub deletions: usize,
+    pub is_binary: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hunks: Option<Vec<DiffHunkResponse>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffHunkResponse {
+    /// The `@@ -a,b +c,d @@` line, including any function context.
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// Lines prefixed with `+`, `-` or a space, as in a patch.
+    pub lines: Vec<String>,
+}
+
+/// Consecutive lines last changed by the same commit.
+#[derive(Debug, Serialize)]
+pub struct BlameHunkResponse {
     pub sha: String,
     pub author: SignatureResponse,
-    pub text: String,
+    /// Line number of the first line, 1-indexed.
+    pub start_line: usize,
+    pub lines: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -81,7 +159,14 @@ pub struct BlameResponse {
     pub path: String,
     #[serde(rename = "ref")]
     pub ref_name: String,
-    pub lines: Vec<BlameLineResponse>,
+    pub total_lines: usize,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub hunks: Vec<BlameHunkResponse>,
+    /// Set when the range asked for was longer than one response allows.
+    pub is_truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_start_line: Option<usize>,
 }
 
 #[derive(Debug, Serialize)]
@@ -108,6 +193,18 @@ pub struct ReadmeResponse {
     pub sha: String,
     pub is_binary: bool,
     pub is_truncated: bool,
+    /// Sanitized HTML, when asked for and the README is in a markup format.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rendered_html: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenderResponse {
+    pub path: String,
+    pub sha: String,
+    pub format: String,
+    pub html: String,
+    pub is_truncated: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -122,6 +219,9 @@ pub struct ListCommitsParams {
 #[derive(Debug, Deserialize)]
 pub struct TreeParams {
     pub depth: Option<i32>,
+    /// Name of the last top-level entry of the previous page.
+    pub cursor: Option<String>,
+    pub limit: Option<i32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -130,16 +230,32 @@ pub struct ArchiveParams {
     pub path: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct BlameParams {
+    pub start_line: Option<usize>,
+    pub end_line: Option<usize>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ReadmeParams {
     #[serde(rename = "ref")]
     pub ref_name: Option<String>,
+    pub rendered_html: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct CompareParams {
     pub cursor: Option<String>,
     pub limit: Option<i32>,
+    pub paths: Option<String>,
+    pub files_only: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiffParams {
+    /// Comma-separated paths or pathspecs the diff is limited to.
+    pub paths: Option<String>,
+    pub files_only: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -168,6 +284,33 @@ pub struct SetDefaultBranchRequest {
 // Content Mutation DTOs
 // ============================================================================
 
+/// Trailers and dates a commit made through the API can carry.
+#[derive(Debug, Default, Deserialize)]
+pub struct CommitOptions {
+    /// `Name <email>` of each co-author, added as `Co-authored-by` trailers.
+    #[serde(default)]
+    pub co_authors: Vec<String>,
+    /// Adds a `Signed-off-by` trailer for the committer, as `git commit -s`
+    /// does.
+    #[serde(default)]
+    pub signed_off_by: bool,
+    /// Further trailers, added in order before the co-authors.
+    #[serde(default)]
+    pub trailers: Vec<TrailerRequest>,
+    /// Dates to record instead of the current time, e.g. when importing
+    /// history. RFC 3339; the offset is kept.
+    #[serde(default)]
+    pub author_date: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub committer_date: Option<DateTime<FixedOffset>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TrailerRequest {
+    pub key: String,
+    pub value: String,
+}
+
 /// Request to create or update a file
 #[derive(Debug, Deserialize)]
 pub struct PutBlobRequest {
@@ -177,6 +320,19 @@ pub struct PutBlobRequest {
     pub encoding: Option<String>,
     #[serde(default)]
     pub sha: Option<String>,
+    #[serde(flatten)]
+    pub commit: CommitOptions,
+}
+
+/// Query params for creating or updating a file from a raw request body.
+/// The same values can be sent as `X-Commit-Message` and `If-Match` headers.
+#[derive(Debug, Deserialize)]
+pub struct RawPutParams {
+    #[serde(default)]
+    pub message: Option<String>,
+    /// SHA of the file being replaced; required when the file exists.
+    #[serde(default)]
+    pub sha: Option<String>,
 }
 
 /// Request to delete a file
@@ -184,6 +340,8 @@ pub struct PutBlobRequest {
 pub struct DeleteBlobRequest {
     pub message: String,
     pub sha: String,
+    #[serde(flatten)]
+    pub commit: CommitOptions,
 }
 
 /// Request for multi-file atomic commit
@@ -192,7 +350,13 @@ pub struct MultiCommitRequest {
     pub message: String,
     #[serde(default)]
     pub branch: Option<String>,
+    /// Creates `branch` from this ref in the same request; the branch must
+    /// not exist yet.
+    #[serde(default)]
+    pub base_ref: Option<String>,
     pub actions: Vec<CommitAction>,
+    #[serde(flatten)]
+    pub commit: CommitOptions,
 }
 
 /// Tagged enum for commit actions
@@ -223,6 +387,32 @@ pub enum CommitAction {
         #[serde(default)]
         sha: Option<String>,
     },
+    /// Sets a file's mode: `100644` for a regular file, `100755` for an
+    /// executable, or `120000` for a symlink to the path in its content.
+    Chmod {
+        path: String,
+        mode: String,
+        #[serde(default)]
+        sha: Option<String>,
+    },
+    /// Removes a directory and everything under it.
+    #[serde(rename = "delete_dir")]
+    DeleteDir {
+        path: String,
+        /// Tree SHA the directory must still have.
+        #[serde(default)]
+        sha: Option<String>,
+    },
+    /// Moves a directory and everything under it to a path that doesn't
+    /// exist yet.
+    #[serde(rename = "move_dir")]
+    MoveDir {
+        from: String,
+        to: String,
+        /// Tree SHA the directory must still have.
+        #[serde(default)]
+        sha: Option<String>,
+    },
 }
 
 /// Response for mutation operations
@@ -230,6 +420,8 @@ pub enum CommitAction {
 pub struct MutationResponse {
     pub commit_sha: String,
     pub ref_name: String,
+    /// Send back as `X-Consistency-Token` to read this write's results.
+    pub consistency_token: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file: Option<FileInfo>,
 }
@@ -240,6 +432,117 @@ pub struct FileInfo {
     pub path: String,
     pub sha: String,
     pub size: i64,
+    pub mode: String,
+}
+
+/// Query params for applying a patch. The message can also be sent as an
+/// `X-Commit-Message` header. Both only apply to a plain diff; patches from
+/// `git format-patch` carry their own.
+#[derive(Debug, Deserialize)]
+pub struct ApplyPatchParams {
+    #[serde(default)]
+    pub message: Option<String>,
+    /// `Name <email>` to record as the author instead of the committer.
+    #[serde(default)]
+    pub author: Option<String>,
+}
+
+/// Response for an applied patch or series of patches
+#[derive(Debug, Serialize)]
+pub struct PatchResponse {
+    /// Tip of the branch after the last patch.
+    pub commit_sha: String,
+    pub ref_name: String,
+    /// The commit made from each patch, in order.
+    pub commits: Vec<String>,
+    /// Send back as `X-Consistency-Token` to read this write's results.
+    pub consistency_token: String,
+}
+
+/// Request to report a commit's status for one context
+#[derive(Debug, Deserialize)]
+pub struct CreateCommitStatusRequest {
+    pub state: CommitState,
+    /// Tells this status apart from others on the commit, e.g. `ci/build`.
+    /// Defaults to `default`.
+    #[serde(default)]
+    pub context: Option<String>,
+    /// Where to see the details, such as the CI job's log.
+    #[serde(default)]
+    pub target_url: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Every context's latest status for a commit, and their combined state
+#[derive(Debug, Serialize)]
+pub struct CombinedStatusResponse {
+    pub sha: String,
+    pub state: CommitState,
+    pub total_count: usize,
+    pub statuses: Vec<CommitStatus>,
+}
+
+/// Request to merge a branch, tag, or commit into a branch
+#[derive(Debug, Deserialize)]
+pub struct MergeRequest {
+    /// Branch to merge into; it moves to the result.
+    pub base: String,
+    /// Branch, tag, or commit SHA to merge.
+    pub head: String,
+    #[serde(default)]
+    pub strategy: MergeStrategy,
+    /// Defaults to `Merge <head> into <base>`, or `Squash <head> into
+    /// <base>` for squash merges. Unused by fast-forwards.
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(flatten)]
+    pub commit: CommitOptions,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStrategy {
+    /// Moves the base branch to `head`, which must already contain it.
+    FastForward,
+    /// Records a merge commit with both tips as parents, even when a
+    /// fast-forward would do.
+    #[default]
+    Merge,
+    /// Records the combined changes as one commit on the base branch.
+    Squash,
+}
+
+/// Response for a merge, and for one refused because of conflicts
+#[derive(Debug, Serialize)]
+pub struct MergeResponse {
+    /// Tip of the base branch after the merge.
+    pub commit_sha: String,
+    pub ref_name: String,
+    pub strategy: MergeStrategy,
+    /// False when `head` was already part of the branch, or conflicts.
+    pub merged: bool,
+    /// Send back as `X-Consistency-Token` to read this write's results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consistency_token: Option<String>,
+    /// Paths both sides changed in ways that can't be combined.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<String>,
+}
+
+/// Response for a dry-run merge of `head` into the `base` branch
+#[derive(Debug, Serialize)]
+pub struct MergePreviewResponse {
+    pub base_sha: String,
+    pub head_sha: String,
+    /// True unless the merge would conflict.
+    pub mergeable: bool,
+    /// `head` is already part of `base`, so there is nothing to merge.
+    pub up_to_date: bool,
+    /// A `fast_forward` merge would succeed.
+    pub fast_forward: bool,
+    /// Paths both sides changed in ways that can't be combined.
+    pub conflicts: Vec<String>,
 }
 
 /// Query params for enhanced blob retrieval
@@ -257,11 +560,42 @@ pub struct EnhancedBlobParams {
     pub cursor: Option<String>,
     #[serde(default)]
     pub limit: Option<i32>,
+    /// Resolves an LFS pointer file to the object it stands for: its
+    /// content with `raw`, its metadata otherwise.
+    #[serde(default)]
+    pub resolve_lfs: Option<bool>,
+    /// Serves the file a symlink leads to in place of the link.
+    #[serde(default)]
+    pub follow_symlinks: Option<bool>,
+}
+
+/// The LFS object a blob's pointer file refers to.
+#[derive(Debug, Serialize)]
+pub struct LfsPointerResponse {
+    pub oid: String,
+    pub size: i64,
+    /// Whether the object was uploaded to this repository.
+    pub stored: bool,
+}
+
+/// Where a symlink points.
+#[derive(Debug, Serialize)]
+pub struct SymlinkResponse {
+    /// The link's content, as written.
+    pub target: String,
+    /// The path it leads to once every link along the way is followed, or
+    /// null when it leaves the repository or the links loop.
+    pub resolved_path: Option<String>,
+    /// `file`, `dir`, or `submodule`, or null when nothing is there.
+    pub target_type: Option<&'static str>,
 }
 
 /// Enhanced blob response with optional history and frontmatter
 #[derive(Debug, Serialize)]
 pub struct EnhancedBlobResponse {
+    /// `file` or `symlink`, for the path requested.
+    #[serde(rename = "type")]
+    pub entry_type: &'static str,
     pub sha: String,
     pub size: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -279,6 +613,10 @@ pub struct EnhancedBlobResponse {
     pub history_cursor: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub history_has_more: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lfs: Option<LfsPointerResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symlink: Option<SymlinkResponse>,
 }
 
 /// Query params for path search
@@ -296,3 +634,56 @@ pub struct PathSearchParams {
 pub struct PathSearchResponse {
     pub matches: Vec<String>,
 }
+
+/// oEmbed `link` response for a public repository.
+#[derive(Debug, Serialize)]
+pub struct OEmbedResponse {
+    pub version: &'static str,
+    #[serde(rename = "type")]
+    pub embed_type: &'static str,
+    pub title: String,
+    pub description: String,
+    pub author_name: String,
+    pub provider_name: &'static str,
+    pub provider_url: String,
+    pub url: String,
+    pub cache_age: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InsightsResponse {
+    /// 0 to 100; every failed check takes points off by its severity.
+    pub score: u32,
+    pub default_branch: Option<String>,
+    pub checks: Vec<InsightCheck>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InsightCheck {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub passed: bool,
+    pub severity: InsightSeverity,
+    pub message: String,
+    /// Branches or files behind a failed check, largest or oldest first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<InsightItem>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InsightSeverity {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InsightItem {
+    /// Branch name or file path.
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_commit_at: Option<DateTime<Utc>>,
+}
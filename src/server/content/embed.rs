@@ -0,0 +1,292 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::server::AppState;
+use crate::server::response::{ApiError, StoreResultExt};
+use crate::server::validation::normalize_repo_name;
+use crate::types::{Namespace, Repo};
+use crate::units::format_size;
+
+use super::badge::{escape_xml, format_age};
+use super::dto::OEmbedResponse;
+use super::git_ops::{get_default_branch, open_repo};
+use super::handlers::repo_path;
+
+/// Chat tools re-fetch unfurl metadata rarely; keep it in line with badges.
+const EMBED_MAX_AGE_SECS: u32 = 300;
+
+/// Suggested oEmbed cache lifetime, in seconds.
+const OEMBED_CACHE_AGE: u32 = 3600;
+
+const PROVIDER_NAME: &str = "cutman";
+
+#[derive(Deserialize)]
+pub struct OEmbedQuery {
+    url: String,
+    format: Option<String>,
+}
+
+/// What a link preview shows for a repository.
+struct RepoSummary {
+    title: String,
+    description: String,
+    stats: String,
+    url: String,
+    clone_url: String,
+}
+
+/// Looks up a repository for an unauthenticated unfurl request. Anything
+/// other than a public repository on a server that allows anonymous reads
+/// is reported as missing so previews never leak private names.
+fn load_public_repo(
+    state: &AppState,
+    namespace: &str,
+    repo: &str,
+) -> Result<(Namespace, Repo), ApiError> {
+    let not_found = || ApiError::not_found("Repository not found");
+
+    let name = repo.strip_suffix(".git").unwrap_or(repo);
+    let name = normalize_repo_name(name, state.repo_name_policy).map_err(|_| not_found())?;
+
+    let namespace = state
+        .store
+        .get_namespace_by_name(namespace)
+        .api_err("Failed to get namespace")?
+        .ok_or_else(not_found)?;
+
+    let repo = state
+        .store
+        .get_repo(&namespace.id, &name)
+        .api_err("Failed to get repository")?
+        .ok_or_else(not_found)?;
+
+    if !(repo.visibility.allows_anonymous_read() && state.allows_anonymous_read()) {
+        return Err(not_found());
+    }
+
+    Ok((namespace, repo))
+}
+
+fn summarize(state: &AppState, base_url: &str, namespace: &Namespace, repo: &Repo) -> RepoSummary {
+    let title = format!("{}/{}", namespace.name, repo.name);
+    let description = repo
+        .description
+        .clone()
+        .filter(|d| !d.trim().is_empty())
+        .unwrap_or_else(|| format!("{title} on {PROVIDER_NAME}"));
+
+    let mut stats = vec![format_size(repo.size_bytes)];
+
+    if let Ok(git_repo) = open_repo(
+        &repo_path(state, &namespace.id, &repo.name),
+        repo.object_format,
+    ) {
+        let branches = git_repo
+            .branches(Some(git2::BranchType::Local))
+            .map(|b| b.count())
+            .unwrap_or(0);
+        let tags = git_repo.tag_names(None).map(|t| t.len()).unwrap_or(0);
+        stats.push(plural(branches, "branch", "branches"));
+        stats.push(plural(tags, "tag", "tags"));
+        if let Some(branch) = get_default_branch(&git_repo) {
+            stats.push(format!("default branch {branch}"));
+        }
+    }
+
+    if let Some(pushed) = repo.last_push_at {
+        let age = (Utc::now() - pushed).num_seconds().max(0);
+        stats.push(format!("updated {}", format_age(age)));
+    }
+
+    let url = format!(
+        "{base_url}/git/{}/{}",
+        urlencoding::encode(&namespace.name),
+        urlencoding::encode(&repo.name)
+    );
+
+    RepoSummary {
+        title,
+        description,
+        stats: stats.join(" · "),
+        clone_url: format!("{url}.git"),
+        url,
+    }
+}
+
+fn plural(count: usize, one: &str, many: &str) -> String {
+    if count == 1 {
+        format!("{count} {one}")
+    } else {
+        format!("{count} {many}")
+    }
+}
+
+/// Splits a `{base_url}/git/{namespace}/{repo}[.git]` link into its parts.
+fn parse_repo_url<'a>(base_url: &str, url: &'a str) -> Option<(String, &'a str)> {
+    let rest = url.strip_prefix(base_url)?.strip_prefix("/git/")?;
+    let rest = rest.split(['?', '#']).next()?.trim_end_matches('/');
+    let (namespace, repo) = rest.split_once('/')?;
+    if namespace.is_empty() || repo.is_empty() || repo.contains('/') {
+        return None;
+    }
+    let namespace = urlencoding::decode(namespace).ok()?.into_owned();
+    Some((namespace, repo))
+}
+
+fn render_page(summary: &RepoSummary, oembed_url: &str) -> String {
+    let title = escape_xml(&summary.title);
+    let description = escape_xml(&summary.description);
+    let stats = escape_xml(&summary.stats);
+    let url = escape_xml(&summary.url);
+    let clone_url = escape_xml(&summary.clone_url);
+    let oembed_url = escape_xml(oembed_url);
+
+    format!(
+        r#"<!doctype html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<meta name="description" content="{description}">
+<link rel="canonical" href="{url}">
+<link rel="alternate" type="application/json+oembed" href="{oembed_url}" title="{title}">
+<meta property="og:type" content="website">
+<meta property="og:site_name" content="{PROVIDER_NAME}">
+<meta property="og:title" content="{title}">
+<meta property="og:description" content="{description} · {stats}">
+<meta property="og:url" content="{url}">
+<meta name="twitter:card" content="summary">
+<meta name="twitter:title" content="{title}">
+<meta name="twitter:description" content="{description} · {stats}">
+</head>
+<body>
+<h1>{title}</h1>
+<p>{description}</p>
+<p>{stats}</p>
+<pre>git clone {clone_url}</pre>
+</body>
+</html>
+"#
+    )
+}
+
+/// HTML stub with OpenGraph and Twitter card tags for a public repository,
+/// served at the repo's web and clone URLs so shared links unfurl.
+pub async fn get_repo_page(
+    State(state): State<Arc<AppState>>,
+    Path((namespace, repo)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let (namespace, repo) = load_public_repo(&state, &namespace, &repo)?;
+    let base_url = state.base_url(&headers);
+    let summary = summarize(&state, &base_url, &namespace, &repo);
+
+    let oembed_url = format!(
+        "{base_url}/api/v1/oembed?format=json&url={}",
+        urlencoding::encode(&summary.url)
+    );
+    let body = render_page(&summary, &oembed_url);
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    if let Ok(value) = HeaderValue::from_str(&format!("public, max-age={EMBED_MAX_AGE_SECS}")) {
+        response_headers.insert(header::CACHE_CONTROL, value);
+    }
+
+    Ok((StatusCode::OK, response_headers, body).into_response())
+}
+
+/// oEmbed provider endpoint (<https://oembed.com>) for public repository links.
+pub async fn get_oembed(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<OEmbedQuery>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ApiError> {
+    if query.format.as_deref().is_some_and(|f| f != "json") {
+        return Err(ApiError {
+            status: StatusCode::NOT_IMPLEMENTED,
+            message: "Only the json format is supported".to_string(),
+            data: None,
+        });
+    }
+
+    let base_url = state.base_url(&headers);
+    let (namespace, repo) = parse_repo_url(&base_url, &query.url)
+        .ok_or_else(|| ApiError::not_found("URL is not a repository on this server"))?;
+    let (namespace, repo) = load_public_repo(&state, &namespace, repo)?;
+    let summary = summarize(&state, &base_url, &namespace, &repo);
+
+    let response = OEmbedResponse {
+        version: "1.0",
+        embed_type: "link",
+        title: summary.title,
+        description: format!("{} · {}", summary.description, summary.stats),
+        author_name: namespace.name,
+        provider_name: PROVIDER_NAME,
+        provider_url: base_url,
+        url: summary.url,
+        cache_age: OEMBED_CACHE_AGE,
+    };
+
+    Ok(Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_repo_url() {
+        let base = "https://git.example.com";
+        assert_eq!(
+            parse_repo_url(base, "https://git.example.com/git/alice/demo.git"),
+            Some(("alice".to_string(), "demo.git"))
+        );
+        assert_eq!(
+            parse_repo_url(base, "https://git.example.com/git/alice/demo/?tab=1"),
+            Some(("alice".to_string(), "demo"))
+        );
+        assert_eq!(
+            parse_repo_url(base, "https://other.example.com/git/alice/demo"),
+            None
+        );
+        assert_eq!(
+            parse_repo_url(base, "https://git.example.com/git/alice"),
+            None
+        );
+        assert_eq!(
+            parse_repo_url(base, "https://git.example.com/git/alice/demo/info/refs"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_render_page_escapes_metadata() {
+        let summary = RepoSummary {
+            title: "alice/demo".to_string(),
+            description: "<script>\"quoted\"</script>".to_string(),
+            stats: "1.0 KiB · 1 branch".to_string(),
+            url: "https://git.example.com/git/alice/demo".to_string(),
+            clone_url: "https://git.example.com/git/alice/demo.git".to_string(),
+        };
+        let html = render_page(
+            &summary,
+            "https://git.example.com/api/v1/oembed?url=x&format=json",
+        );
+        assert!(html.contains(r#"<meta property="og:title" content="alice/demo">"#));
+        assert!(html.contains("&lt;script&gt;&quot;quoted&quot;"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("oembed?url=x&amp;format=json"));
+    }
+}
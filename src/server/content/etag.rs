@@ -0,0 +1,74 @@
+//! ETags for content responses built wholly from git objects, so clients
+//! polling a tree, file, README, or the refs can send `If-None-Match` and
+//! get a bodiless 304 while nothing has changed. Each tag covers the
+//! objects a response is built from and whatever in the request shaped it,
+//! and is checked before the costly part of building the body.
+
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+/// A strong ETag over `parts`, e.g. object SHAs and query parameters.
+#[must_use]
+pub(super) fn etag(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    format!("\"{}\"", hex::encode(&hasher.finalize()[..16]))
+}
+
+/// Whether the request's `If-None-Match` lists `etag`, or `*`. Weak tags
+/// match too, as RFC 9110 asks for this header.
+#[must_use]
+pub(super) fn matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(',').any(|tag| {
+                let tag = tag.trim();
+                tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag
+            })
+        })
+}
+
+/// A 304 for a request whose `If-None-Match` lists `etag`.
+#[must_use]
+pub(super) fn not_modified(etag: &str) -> Response {
+    with_etag(StatusCode::NOT_MODIFIED, etag)
+}
+
+/// `response` with `etag` in its `ETag` header.
+pub(super) fn with_etag(response: impl IntoResponse, etag: &str) -> Response {
+    let mut response = response.into_response();
+    if let Ok(value) = HeaderValue::from_str(etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let tag = etag(&["abc", "depth=1"]);
+        assert_ne!(tag, etag(&["abcd", "epth=1"]));
+
+        let request = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, HeaderValue::from_str(value).unwrap());
+            headers
+        };
+        assert!(matches(&request(&tag), &tag));
+        assert!(matches(&request(&format!("\"x\", W/{tag}")), &tag));
+        assert!(matches(&request("*"), &tag));
+        assert!(!matches(&request("\"x\""), &tag));
+        assert!(!matches(&HeaderMap::new(), &tag));
+    }
+}
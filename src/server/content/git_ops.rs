@@ -1,11 +1,18 @@
+use std::collections::HashMap;
 use std::path::Path;
 
 use chrono::{TimeZone, Utc};
-use git2::{Commit, DiffOptions, ObjectType, Oid, Repository, Signature, Tree};
+use git2::{
+    Commit, Delta, Diff, DiffLine, DiffOptions, ObjectType, Oid, Patch, Repository, Signature, Tree,
+};
 
 use crate::server::response::ApiError;
+use crate::types::ObjectFormat;
 
-use super::dto::{CommitResponse, CommitStats, SignatureResponse};
+use super::dto::{
+    CommitResponse, CommitStats, DiffFileResponse, DiffHunkResponse, MergeStrategy,
+    SignatureResponse,
+};
 
 #[derive(Debug)]
 pub enum GitError {
@@ -16,6 +23,7 @@ pub enum GitError {
     NotAFile,
     NotADirectory,
     Conflict(String),
+    UnsupportedObjectFormat(ObjectFormat),
     Internal(String),
 }
 
@@ -29,16 +37,30 @@ impl From<GitError> for ApiError {
             GitError::NotAFile => ApiError::bad_request("Path is a directory, not a file"),
             GitError::NotADirectory => ApiError::bad_request("Path is a file, not a directory"),
             GitError::Conflict(msg) => ApiError::conflict(msg),
+            GitError::UnsupportedObjectFormat(format) => ApiError::not_implemented(format!(
+                "The content API does not support {format} repositories yet; use git to read and write them"
+            )),
             GitError::Internal(msg) => ApiError::internal(msg),
         }
     }
 }
 
-pub fn open_repo(path: &Path) -> Result<Repository, GitError> {
-    Repository::open_bare(path).map_err(|_| GitError::RepoNotFound)
+/// libgit2 is built without SHA-256 support, so only SHA-1 repositories
+/// open; the others are served by the git protocols alone.
+pub fn open_repo(path: &Path, format: ObjectFormat) -> Result<Repository, GitError> {
+    Repository::open_bare(path).map_err(|_| match format {
+        ObjectFormat::Sha1 => GitError::RepoNotFound,
+        _ if path.exists() => GitError::UnsupportedObjectFormat(format),
+        _ => GitError::RepoNotFound,
+    })
 }
 
-pub fn open_or_init_repo(path: &Path) -> Result<Repository, GitError> {
+/// Repositories that don't use SHA-1 are initialized with git when they
+/// are created, never here.
+pub fn open_or_init_repo(path: &Path, format: ObjectFormat) -> Result<Repository, GitError> {
+    if format != ObjectFormat::Sha1 {
+        return open_repo(path, format);
+    }
     match Repository::open_bare(path) {
         Ok(repo) => Ok(repo),
         Err(_) => {
@@ -62,7 +84,7 @@ pub fn resolve_ref(repo: &Repository, ref_spec: &str) -> Result<Oid, GitError> {
         ref_spec
     };
 
-    if ref_spec.len() == 40 {
+    if ObjectFormat::from_oid(ref_spec).is_some() {
         if let Ok(oid) = Oid::from_str(ref_spec) {
             if repo.find_commit(oid).is_ok() {
                 return Ok(oid);
@@ -154,6 +176,50 @@ pub fn get_blob_at_path<'a>(
         .map_err(|_| GitError::Internal("Object is not a blob".to_string()))
 }
 
+/// Most symlinks followed resolving one path, as Linux allows.
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// Where `path` in `tree` leads once every symlink along it is followed,
+/// each link's target taken relative to the directory holding it. `None`
+/// when a link leaves the repository or the links loop; the path returned
+/// needn't exist.
+pub fn resolve_symlinks(
+    repo: &Repository,
+    tree: &Tree<'_>,
+    path: &str,
+) -> Result<Option<String>, GitError> {
+    let mut pending: Vec<String> = path.rsplit('/').map(String::from).collect();
+    let mut resolved: Vec<String> = Vec::new();
+    let mut hops = 0;
+    while let Some(part) = pending.pop() {
+        match part.as_str() {
+            "" | "." => continue,
+            ".." => {
+                if resolved.pop().is_none() {
+                    return Ok(None);
+                }
+                continue;
+            }
+            _ => resolved.push(part),
+        }
+        let Ok(entry) = tree.get_path(Path::new(&resolved.join("/"))) else {
+            continue;
+        };
+        if entry.filemode() != 0o120000 {
+            continue;
+        }
+        hops += 1;
+        let target = get_blob_at_path(repo, tree, &resolved.join("/"))?;
+        let target = String::from_utf8_lossy(target.content()).into_owned();
+        if hops > MAX_SYMLINK_HOPS || target.starts_with('/') {
+            return Ok(None);
+        }
+        resolved.pop();
+        pending.extend(target.rsplit('/').map(String::from));
+    }
+    Ok(Some(resolved.join("/")))
+}
+
 #[must_use]
 pub fn is_binary(content: &[u8]) -> bool {
     let sample_size = content.len().min(8192);
@@ -185,9 +251,17 @@ pub fn commit_to_response(commit: &Commit<'_>, stats: Option<CommitStats>) -> Co
         parent_shas,
         tree_sha: commit.tree_id().to_string(),
         stats,
+        verification: None,
     }
 }
 
+/// A commit's signature and the signed payload, if the commit is signed.
+#[must_use]
+pub fn commit_signature(repo: &Repository, oid: Oid) -> Option<(Vec<u8>, Vec<u8>)> {
+    let (signature, payload) = repo.extract_signature(&oid, None).ok()?;
+    Some((signature.to_vec(), payload.to_vec()))
+}
+
 #[must_use]
 pub fn compute_commit_stats(repo: &Repository, commit: &Commit<'_>) -> Option<CommitStats> {
     let current_tree = commit.tree().ok()?;
@@ -211,45 +285,146 @@ pub fn compute_commit_stats(repo: &Repository, commit: &Commit<'_>) -> Option<Co
     })
 }
 
+/// Diffs two trees, limited to `paths` when any are given. Returns the
+/// whole patch, unless `files_only`, along with the stats and each changed
+/// file; files carry their hunks unless `files_only`.
 pub fn build_diff(
     repo: &Repository,
     base_tree: Option<&Tree<'_>>,
     head_tree: &Tree<'_>,
-) -> Result<(String, CommitStats), GitError> {
+    paths: &[String],
+    files_only: bool,
+) -> Result<(Option<String>, CommitStats, Vec<DiffFileResponse>), GitError> {
     let mut opts = DiffOptions::new();
     opts.context_lines(3);
+    for path in paths {
+        opts.pathspec(path);
+    }
 
-    let diff = repo
+    let mut diff = repo
         .diff_tree_to_tree(base_tree, Some(head_tree), Some(&mut opts))
         .map_err(|e| GitError::Internal(format!("Failed to compute diff: {e}")))?;
+    diff.find_similar(None)
+        .map_err(|e| GitError::Internal(format!("Failed to detect renames: {e}")))?;
 
     let stats = diff
         .stats()
         .map_err(|e| GitError::Internal(format!("Failed to get diff stats: {e}")))?;
 
-    let mut patch = Vec::new();
-    diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
-        let origin = line.origin();
-        if origin == '+' || origin == '-' || origin == ' ' {
-            patch.push(origin as u8);
-        }
-        patch.extend_from_slice(line.content());
-        true
-    })
-    .map_err(|e| GitError::Internal(format!("Failed to format diff: {e}")))?;
+    let patch = if files_only {
+        None
+    } else {
+        let mut patch = Vec::new();
+        diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
+            let origin = line.origin();
+            if origin == '+' || origin == '-' || origin == ' ' {
+                patch.push(origin as u8);
+            }
+            patch.extend_from_slice(line.content());
+            true
+        })
+        .map_err(|e| GitError::Internal(format!("Failed to format diff: {e}")))?;
+        Some(String::from_utf8_lossy(&patch).to_string())
+    };
 
-    let patch_str = String::from_utf8_lossy(&patch).to_string();
+    let files = (0..diff.deltas().len())
+        .map(|index| diff_file(&diff, index, files_only))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| GitError::Internal(format!("Failed to format diff: {e}")))?;
 
     Ok((
-        patch_str,
+        patch,
         CommitStats {
             files_changed: stats.files_changed(),
             additions: stats.insertions(),
             deletions: stats.deletions(),
         },
+        files,
     ))
 }
 
+fn diff_file(
+    diff: &Diff<'_>,
+    index: usize,
+    files_only: bool,
+) -> Result<DiffFileResponse, git2::Error> {
+    let patch = Patch::from_diff(diff, index)?;
+    let delta = match &patch {
+        Some(patch) => patch.delta(),
+        None => diff
+            .get_delta(index)
+            .ok_or_else(|| git2::Error::from_str("diff delta out of range"))?,
+    };
+
+    let status = match delta.status() {
+        Delta::Added => "added",
+        Delta::Deleted => "deleted",
+        Delta::Renamed => "renamed",
+        Delta::Copied => "copied",
+        Delta::Typechange => "type_changed",
+        _ => "modified",
+    };
+    let path = |file: git2::DiffFile<'_>| file.path().map(|p| p.to_string_lossy().to_string());
+    let old_path = path(delta.old_file()).filter(|_| status != "added");
+    let new_path = path(delta.new_file()).filter(|_| status != "deleted");
+    let is_binary = delta.flags().is_binary();
+
+    let (additions, deletions) = match &patch {
+        Some(patch) => {
+            let (_, additions, deletions) = patch.line_stats()?;
+            (additions, deletions)
+        }
+        None => (0, 0),
+    };
+
+    let hunks = if files_only {
+        None
+    } else {
+        let mut hunks = Vec::new();
+        if let Some(patch) = &patch {
+            for hunk_index in 0..patch.num_hunks() {
+                let (hunk, line_count) = patch.hunk(hunk_index)?;
+                let lines = (0..line_count)
+                    .map(|i| patch.line_in_hunk(hunk_index, i).map(|l| patch_line(&l)))
+                    .collect::<Result<_, _>>()?;
+                hunks.push(DiffHunkResponse {
+                    header: String::from_utf8_lossy(hunk.header())
+                        .trim_end()
+                        .to_string(),
+                    old_start: hunk.old_start(),
+                    old_lines: hunk.old_lines(),
+                    new_start: hunk.new_start(),
+                    new_lines: hunk.new_lines(),
+                    lines,
+                });
+            }
+        }
+        Some(hunks)
+    };
+
+    Ok(DiffFileResponse {
+        status: status.to_string(),
+        old_path,
+        new_path,
+        additions,
+        deletions,
+        is_binary,
+        hunks,
+    })
+}
+
+/// A diff line as it reads in a patch. The end-of-file markers become the
+/// `\ No newline at end of file` line git prints.
+fn patch_line(line: &DiffLine<'_>) -> String {
+    let content = String::from_utf8_lossy(line.content());
+    match line.origin() {
+        origin @ ('+' | '-' | ' ') => {
+            format!("{origin}{}", content.strip_suffix('\n').unwrap_or(&content))
+        }
+        _ => content.trim().to_string(),
+    }
+}
+
 pub fn find_merge_base(repo: &Repository, base: Oid, head: Oid) -> Result<Oid, GitError> {
     repo.merge_base(base, head)
         .map_err(|e| GitError::Internal(format!("Failed to find merge base: {e}")))
@@ -273,18 +448,146 @@ pub fn get_default_branch(repo: &Repository) -> Option<String> {
 pub fn entry_type_str(kind: Option<ObjectType>, filemode: i32) -> &'static str {
     match kind {
         Some(ObjectType::Tree) => "dir",
-        Some(ObjectType::Blob) => {
-            if filemode == 0o120000 {
-                "symlink"
-            } else {
-                "file"
-            }
-        }
+        Some(ObjectType::Blob) if filemode == 0o120000 => "symlink",
         Some(ObjectType::Commit) => "submodule",
         _ => "file",
     }
 }
 
+/// A submodule declared in `.gitmodules`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Submodule {
+    pub name: String,
+    pub path: String,
+    pub url: String,
+}
+
+/// Submodules declared in the tree's `.gitmodules`, keyed by path. A missing
+/// or unreadable file declares none.
+#[must_use]
+pub fn read_gitmodules(repo: &Repository, tree: &Tree<'_>) -> HashMap<String, Submodule> {
+    let Some(blob) = tree
+        .get_name(".gitmodules")
+        .and_then(|entry| repo.find_blob(entry.id()).ok())
+    else {
+        return HashMap::new();
+    };
+
+    parse_gitmodules(&String::from_utf8_lossy(blob.content()))
+        .into_iter()
+        .map(|sub| (sub.path.clone(), sub))
+        .collect()
+}
+
+/// Parse the `[submodule "name"]` sections of a `.gitmodules` file, keeping
+/// those with both a path and a url. Later values win, as they do for git.
+#[must_use]
+pub fn parse_gitmodules(text: &str) -> Vec<Submodule> {
+    let mut sections: Vec<(String, Option<String>, Option<String>)> = Vec::new();
+    let mut in_submodule = false;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.split(']').next().unwrap_or("");
+            let (section, name) = header
+                .split_once(char::is_whitespace)
+                .unwrap_or((header, ""));
+            in_submodule = section.eq_ignore_ascii_case("submodule");
+            if in_submodule {
+                let name = config_value(name.trim());
+                match sections.iter().position(|(n, _, _)| *n == name) {
+                    // A repeated section continues the earlier one.
+                    Some(i) => {
+                        let section = sections.remove(i);
+                        sections.push(section);
+                    }
+                    None => sections.push((name, None, None)),
+                }
+            }
+            continue;
+        }
+
+        let Some((_, path, url)) = sections.last_mut().filter(|_| in_submodule) else {
+            continue;
+        };
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = config_value(value);
+        match key.trim().to_ascii_lowercase().as_str() {
+            "path" => *path = Some(value.trim_matches('/').to_string()),
+            "url" => *url = Some(value),
+            _ => {}
+        }
+    }
+
+    sections
+        .into_iter()
+        .filter_map(|(name, path, url)| {
+            Some(Submodule {
+                name,
+                path: path.filter(|p| !p.is_empty())?,
+                url: url.filter(|u| !u.is_empty())?,
+            })
+        })
+        .collect()
+}
+
+/// A git config value: quotes removed, escapes applied, and a trailing
+/// comment outside quotes dropped.
+fn config_value(raw: &str) -> String {
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = raw.trim().chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(c) => value.push(c),
+                None => {}
+            },
+            '#' | ';' if !quoted => break,
+            c => value.push(c),
+        }
+    }
+
+    value.trim_end().to_string()
+}
+
+/// Resolve a submodule url written relative to the superproject (`../lib.git`)
+/// against the superproject's own url, the way `git submodule` does.
+#[must_use]
+pub fn resolve_submodule_url(base: &str, url: &str) -> String {
+    if !url.starts_with("./") && !url.starts_with("../") {
+        return url.to_string();
+    }
+
+    let mut base = base.trim_end_matches('/').to_string();
+    let mut rest = url;
+    loop {
+        if let Some(r) = rest.strip_prefix("./") {
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("../") {
+            rest = r;
+            if let Some(i) = base.rfind('/') {
+                base.truncate(i);
+            }
+        } else {
+            break;
+        }
+    }
+
+    format!("{base}/{rest}")
+}
+
 /// Create a new reference (branch or tag)
 pub fn create_ref(
     repo: &Repository,
@@ -442,8 +745,12 @@ pub fn file_exists(tree: &Tree<'_>, path: &str) -> bool {
     tree.get_path(Path::new(path)).is_ok()
 }
 
+/// File modes a blob entry can have: regular, executable, and symlink.
+pub const BLOB_FILEMODES: [i32; 3] = [0o100644, 0o100755, 0o120000];
+
 /// Build a new tree with a blob added or updated at the given path.
 /// Handles nested paths by creating intermediate tree entries as needed.
+/// An updated file keeps its mode; new files are regular files.
 pub fn tree_with_blob(
     repo: &Repository,
     base_tree: Option<&Tree<'_>>,
@@ -454,8 +761,13 @@ pub fn tree_with_blob(
         .blob(content)
         .map_err(|e| GitError::Internal(format!("Failed to create blob: {e}")))?;
 
+    let filemode = base_tree
+        .and_then(|t| t.get_path(Path::new(path)).ok())
+        .map(|entry| entry.filemode())
+        .filter(|mode| BLOB_FILEMODES.contains(mode))
+        .unwrap_or(0o100644);
     let parts: Vec<&str> = path.split('/').collect();
-    build_tree_recursive(repo, base_tree, &parts, blob_oid, 0o100644)
+    build_tree_recursive(repo, base_tree, &parts, blob_oid, filemode)
 }
 
 fn build_tree_recursive(
@@ -564,6 +876,59 @@ fn remove_from_tree_recursive(
         .map_err(|e| GitError::Internal(format!("Failed to write tree: {e}")))
 }
 
+/// Who a commit made through the API is by, and when. Times left out are
+/// the current time.
+#[derive(Debug, Clone)]
+pub struct CommitAuthor {
+    pub name: String,
+    pub email: String,
+    pub author_time: Option<git2::Time>,
+    pub commit_time: Option<git2::Time>,
+}
+
+impl CommitAuthor {
+    fn signature(&self, time: Option<git2::Time>) -> Result<Signature<'static>, GitError> {
+        match time {
+            Some(time) => Signature::new(&self.name, &self.email, &time),
+            None => Signature::now(&self.name, &self.email),
+        }
+        .map_err(|e| GitError::Internal(format!("Failed to create signature: {e}")))
+    }
+}
+
+/// Appends trailers to a commit message the way `git interpret-trailers`
+/// does: they join a trailer block that already ends the message, or start
+/// one after a blank line. Trailers the message already has are skipped.
+pub fn append_trailers(message: &str, trailers: &[(String, String)]) -> String {
+    let mut result = message.trim_end().to_string();
+    let lines: Vec<String> = trailers
+        .iter()
+        .map(|(key, value)| format!("{key}: {value}"))
+        .filter(|line| !result.lines().any(|existing| existing == line))
+        .collect();
+    if lines.is_empty() {
+        return message.to_string();
+    }
+
+    let ends_in_trailers = result.rsplit_once("\n\n").is_some_and(|(_, last)| {
+        last.lines().all(|line| {
+            line.split_once(": ")
+                .is_some_and(|(key, _)| is_trailer_key(key))
+        })
+    });
+    result.push_str(if ends_in_trailers { "\n" } else { "\n\n" });
+    result.push_str(&lines.join("\n"));
+    result.push('\n');
+    result
+}
+
+/// Whether `key` can name a trailer: letters, digits, and dashes.
+pub fn is_trailer_key(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with('-')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
 /// Create a commit on a branch with the given tree and message.
 /// Returns (commit_sha, branch_name).
 pub fn create_commit_on_branch(
@@ -571,15 +936,14 @@ pub fn create_commit_on_branch(
     branch: &str,
     tree_oid: Oid,
     message: &str,
-    author_name: &str,
-    author_email: &str,
+    author: &CommitAuthor,
 ) -> Result<Oid, GitError> {
     let tree = repo
         .find_tree(tree_oid)
         .map_err(|e| GitError::Internal(format!("Failed to find tree: {e}")))?;
 
-    let sig = Signature::now(author_name, author_email)
-        .map_err(|e| GitError::Internal(format!("Failed to create signature: {e}")))?;
+    let author_sig = author.signature(author.author_time)?;
+    let committer_sig = author.signature(author.commit_time)?;
 
     let branch_ref = format!("refs/heads/{branch}");
 
@@ -592,7 +956,14 @@ pub fn create_commit_on_branch(
     let parents: Vec<&Commit<'_>> = parent_commit.iter().collect();
 
     let commit_oid = repo
-        .commit(Some(&branch_ref), &sig, &sig, message, &tree, &parents)
+        .commit(
+            Some(&branch_ref),
+            &author_sig,
+            &committer_sig,
+            message,
+            &tree,
+            &parents,
+        )
         .map_err(|e| GitError::Internal(format!("Failed to create commit: {e}")))?;
 
     Ok(commit_oid)
@@ -619,6 +990,21 @@ pub enum CommitActionOp {
         to: String,
         sha: Option<String>,
     },
+    /// Sets a file's mode to one of [`BLOB_FILEMODES`].
+    Chmod {
+        path: String,
+        mode: i32,
+        sha: Option<String>,
+    },
+    DeleteDir {
+        path: String,
+        sha: Option<String>,
+    },
+    MoveDir {
+        from: String,
+        to: String,
+        sha: Option<String>,
+    },
 }
 
 /// Apply multiple actions to create a new tree, then commit.
@@ -627,8 +1013,7 @@ pub fn apply_actions(
     branch: &str,
     actions: &[CommitActionOp],
     message: &str,
-    author_name: &str,
-    author_email: &str,
+    author: &CommitAuthor,
 ) -> Result<Oid, GitError> {
     let branch_ref = format!("refs/heads/{branch}");
 
@@ -639,7 +1024,269 @@ pub fn apply_actions(
         .and_then(|oid| repo.find_commit(oid).ok())
         .and_then(|c| c.tree().ok());
 
-    let mut current_tree_oid = base_tree.as_ref().map(|t| t.id());
+    let final_tree_oid = tree_after_actions(repo, base_tree.as_ref(), actions)?;
+    create_commit_on_branch(repo, branch, final_tree_oid, message, author)
+}
+
+/// Apply multiple actions on top of `base`, then commit them to a branch
+/// that doesn't exist yet. The branch is only created once the commit is
+/// written, and fails with a conflict if it appeared in the meantime.
+pub fn apply_actions_on_new_branch(
+    repo: &Repository,
+    branch: &str,
+    base: Oid,
+    actions: &[CommitActionOp],
+    message: &str,
+    author: &CommitAuthor,
+) -> Result<Oid, GitError> {
+    let branch_ref = format!("refs/heads/{branch}");
+    let branch_exists = || GitError::Conflict(format!("Branch already exists: {branch}"));
+    if repo.find_reference(&branch_ref).is_ok() {
+        return Err(branch_exists());
+    }
+
+    let parent = repo
+        .find_commit(base)
+        .map_err(|_| GitError::RefNotFound(base.to_string()))?;
+    let base_tree = parent
+        .tree()
+        .map_err(|e| GitError::Internal(format!("Failed to get tree: {e}")))?;
+    let final_tree_oid = tree_after_actions(repo, Some(&base_tree), actions)?;
+    let tree = repo
+        .find_tree(final_tree_oid)
+        .map_err(|e| GitError::Internal(format!("Failed to find tree: {e}")))?;
+
+    let commit_oid = repo
+        .commit(
+            None,
+            &author.signature(author.author_time)?,
+            &author.signature(author.commit_time)?,
+            message,
+            &tree,
+            &[&parent],
+        )
+        .map_err(|e| GitError::Internal(format!("Failed to create commit: {e}")))?;
+
+    repo.reference(
+        &branch_ref,
+        commit_oid,
+        false,
+        &format!("Creating branch {branch}"),
+    )
+    .map_err(|e| match e.code() {
+        git2::ErrorCode::Exists => branch_exists(),
+        _ => GitError::Internal(format!("Failed to create reference: {e}")),
+    })?;
+
+    Ok(commit_oid)
+}
+
+/// What merging one commit into a branch tip produces.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MergeOutcome {
+    /// The head is already part of the branch.
+    UpToDate,
+    /// The commit the branch should move to. It is written, but the branch
+    /// still points at its old tip.
+    Ready(Oid),
+    /// Paths both sides changed in ways that can't be combined, sorted.
+    Conflicts(Vec<String>),
+}
+
+/// Merges `head` into the branch tip `base` with `strategy`, writing the
+/// resulting commit without moving any ref, so the caller can check the
+/// update before [`advance_branch`] applies it.
+pub fn prepare_merge(
+    repo: &Repository,
+    base: Oid,
+    head: Oid,
+    strategy: MergeStrategy,
+    message: &str,
+    author: &CommitAuthor,
+) -> Result<MergeOutcome, GitError> {
+    let descendant_of = |commit, ancestor| {
+        repo.graph_descendant_of(commit, ancestor)
+            .map_err(|e| GitError::Internal(format!("Failed to compare commits: {e}")))
+    };
+    if base == head || descendant_of(base, head)? {
+        return Ok(MergeOutcome::UpToDate);
+    }
+    if strategy == MergeStrategy::FastForward {
+        if !descendant_of(head, base)? {
+            return Err(GitError::Conflict(
+                "Can't fast-forward: the branches have diverged".to_string(),
+            ));
+        }
+        return Ok(MergeOutcome::Ready(head));
+    }
+
+    let base_commit = get_commit(repo, base)?;
+    let head_commit = get_commit(repo, head)?;
+    let mut index = repo
+        .merge_commits(&base_commit, &head_commit, None)
+        .map_err(|e| GitError::Internal(format!("Failed to merge: {e}")))?;
+
+    if index.has_conflicts() {
+        return Ok(MergeOutcome::Conflicts(conflicted_paths(&index)?));
+    }
+
+    let tree_oid = index
+        .write_tree_to(repo)
+        .map_err(|e| GitError::Internal(format!("Failed to write tree: {e}")))?;
+    let tree = repo
+        .find_tree(tree_oid)
+        .map_err(|e| GitError::Internal(format!("Failed to find tree: {e}")))?;
+    let parents: &[&Commit<'_>] = match strategy {
+        MergeStrategy::Squash => &[&base_commit],
+        _ => &[&base_commit, &head_commit],
+    };
+
+    let commit_oid = repo
+        .commit(
+            None,
+            &author.signature(author.author_time)?,
+            &author.signature(author.commit_time)?,
+            message,
+            &tree,
+            parents,
+        )
+        .map_err(|e| GitError::Internal(format!("Failed to create commit: {e}")))?;
+    Ok(MergeOutcome::Ready(commit_oid))
+}
+
+/// Whether merging `head` into the branch tip `base` would go through,
+/// worked out in memory without writing a commit.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MergePreview {
+    /// The head is already part of the branch.
+    pub up_to_date: bool,
+    /// The branch could move straight to the head.
+    pub fast_forward: bool,
+    /// Paths both sides changed in ways that can't be combined, sorted.
+    pub conflicts: Vec<String>,
+}
+
+pub fn preview_merge(repo: &Repository, base: Oid, head: Oid) -> Result<MergePreview, GitError> {
+    let descendant_of = |commit, ancestor| {
+        repo.graph_descendant_of(commit, ancestor)
+            .map_err(|e| GitError::Internal(format!("Failed to compare commits: {e}")))
+    };
+    let mut preview = MergePreview {
+        up_to_date: base == head || descendant_of(base, head)?,
+        fast_forward: false,
+        conflicts: Vec::new(),
+    };
+    if preview.up_to_date {
+        return Ok(preview);
+    }
+    preview.fast_forward = descendant_of(head, base)?;
+    if preview.fast_forward {
+        return Ok(preview);
+    }
+
+    let index = repo
+        .merge_commits(&get_commit(repo, base)?, &get_commit(repo, head)?, None)
+        .map_err(|e| GitError::Internal(format!("Failed to merge: {e}")))?;
+    if index.has_conflicts() {
+        preview.conflicts = conflicted_paths(&index)?;
+    }
+    Ok(preview)
+}
+
+fn conflicted_paths(index: &git2::Index) -> Result<Vec<String>, GitError> {
+    let mut paths: Vec<String> = index
+        .conflicts()
+        .map_err(|e| GitError::Internal(format!("Failed to read conflicts: {e}")))?
+        .filter_map(Result::ok)
+        .filter_map(|conflict| conflict.our.or(conflict.their).or(conflict.ancestor))
+        .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+        .collect();
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+/// Moves a branch from `old` to `new`, failing with a conflict if it was
+/// updated in the meantime. `log_message` goes in the reflog.
+pub fn advance_branch(
+    repo: &Repository,
+    branch: &str,
+    old: Oid,
+    new: Oid,
+    log_message: &str,
+) -> Result<(), GitError> {
+    repo.reference_matching(&format!("refs/heads/{branch}"), new, true, old, log_message)
+        .map_err(|e| match e.code() {
+            git2::ErrorCode::Modified => {
+                GitError::Conflict(format!("Branch {branch} was updated in the meantime"))
+            }
+            _ => GitError::Internal(format!("Failed to update reference: {e}")),
+        })?;
+    Ok(())
+}
+
+/// A commit to write from a patch: `diff` applied to the previous commit's
+/// tree, recorded with `message` and `author`.
+pub struct PatchCommit {
+    pub diff: Diff<'static>,
+    pub message: String,
+    pub author: CommitAuthor,
+}
+
+/// Applies each patch on top of the last, starting from `base`, writing a
+/// commit for each with `committer` as its committer. No ref is moved, so
+/// the caller can check the update before [`advance_branch`] applies it.
+/// Returns the commits in order.
+pub fn apply_patches(
+    repo: &Repository,
+    base: Oid,
+    patches: &[PatchCommit],
+    committer: &CommitAuthor,
+) -> Result<Vec<Oid>, GitError> {
+    let mut parent = repo
+        .find_commit(base)
+        .map_err(|_| GitError::RefNotFound(base.to_string()))?;
+    let mut commits = Vec::with_capacity(patches.len());
+    for (i, patch) in patches.iter().enumerate() {
+        let base_tree = parent
+            .tree()
+            .map_err(|e| GitError::Internal(format!("Failed to get tree: {e}")))?;
+        let mut index = repo
+            .apply_to_tree(&base_tree, &patch.diff, None)
+            .map_err(|e| {
+                GitError::Conflict(format!("Patch {} does not apply: {}", i + 1, e.message()))
+            })?;
+        let tree_oid = index
+            .write_tree_to(repo)
+            .map_err(|e| GitError::Internal(format!("Failed to write tree: {e}")))?;
+        let tree = repo
+            .find_tree(tree_oid)
+            .map_err(|e| GitError::Internal(format!("Failed to find tree: {e}")))?;
+
+        let oid = repo
+            .commit(
+                None,
+                &patch.author.signature(patch.author.author_time)?,
+                &committer.signature(committer.commit_time)?,
+                &patch.message,
+                &tree,
+                &[&parent],
+            )
+            .map_err(|e| GitError::Internal(format!("Failed to create commit: {e}")))?;
+        commits.push(oid);
+        parent = repo
+            .find_commit(oid)
+            .map_err(|e| GitError::Internal(format!("Failed to get commit: {e}")))?;
+    }
+    Ok(commits)
+}
+
+fn tree_after_actions(
+    repo: &Repository,
+    base_tree: Option<&Tree<'_>>,
+    actions: &[CommitActionOp],
+) -> Result<Oid, GitError> {
+    let mut current_tree_oid = base_tree.map(|t| t.id());
 
     for action in actions {
         current_tree_oid = Some(apply_single_action(
@@ -651,17 +1298,7 @@ pub fn apply_actions(
         )?);
     }
 
-    let final_tree_oid = current_tree_oid
-        .ok_or_else(|| GitError::Internal("No tree after applying actions".to_string()))?;
-
-    create_commit_on_branch(
-        repo,
-        branch,
-        final_tree_oid,
-        message,
-        author_name,
-        author_email,
-    )
+    current_tree_oid.ok_or_else(|| GitError::Internal("No tree after applying actions".to_string()))
 }
 
 fn apply_single_action(
@@ -700,19 +1337,96 @@ fn apply_single_action(
                 verify_blob_sha(tree, from, expected_sha)?;
             }
 
-            let blob = get_blob_at_path(repo, tree, from)?;
-            let content = blob.content().to_vec();
+            let entry = blob_entry(tree, from)?;
 
             let intermediate_tree_oid = tree_without_entry(repo, tree, from)?;
             let intermediate_tree = repo.find_tree(intermediate_tree_oid).map_err(|e| {
                 GitError::Internal(format!("Failed to find intermediate tree: {e}"))
             })?;
 
-            tree_with_blob(repo, Some(&intermediate_tree), to, &content)
+            let parts: Vec<&str> = to.split('/').collect();
+            build_tree_recursive(
+                repo,
+                Some(&intermediate_tree),
+                &parts,
+                entry.id(),
+                entry.filemode(),
+            )
+        }
+        CommitActionOp::Chmod { path, mode, sha } => {
+            let tree = base_tree.ok_or_else(|| GitError::PathNotFound(path.clone()))?;
+            if let Some(expected_sha) = sha {
+                verify_blob_sha(tree, path, expected_sha)?;
+            }
+
+            let entry = blob_entry(tree, path)?;
+            let parts: Vec<&str> = path.split('/').collect();
+            build_tree_recursive(repo, Some(tree), &parts, entry.id(), *mode)
+        }
+        CommitActionOp::DeleteDir { path, sha } => {
+            let tree = base_tree.ok_or_else(|| GitError::PathNotFound(path.clone()))?;
+            dir_at_path(tree, path, sha.as_deref())?;
+            tree_without_entry(repo, tree, path)
+        }
+        CommitActionOp::MoveDir { from, to, sha } => {
+            let tree = base_tree.ok_or_else(|| GitError::PathNotFound(from.clone()))?;
+            let subtree_oid = dir_at_path(tree, from, sha.as_deref())?;
+            if file_exists(tree, to) {
+                return Err(GitError::Conflict(format!("Path already exists: {to}")));
+            }
+
+            let intermediate_tree_oid = tree_without_entry(repo, tree, from)?;
+            let intermediate_tree = repo.find_tree(intermediate_tree_oid).map_err(|e| {
+                GitError::Internal(format!("Failed to find intermediate tree: {e}"))
+            })?;
+
+            let parts: Vec<&str> = to.split('/').collect();
+            build_tree_recursive(
+                repo,
+                Some(&intermediate_tree),
+                &parts,
+                subtree_oid,
+                0o040000,
+            )
         }
     }
 }
 
+/// The tree entry of the file at `path`.
+fn blob_entry(tree: &Tree<'_>, path: &str) -> Result<git2::TreeEntry<'static>, GitError> {
+    let entry = tree
+        .get_path(Path::new(path))
+        .map_err(|_| GitError::PathNotFound(path.to_string()))?;
+    if entry.kind() != Some(ObjectType::Blob) {
+        return Err(GitError::NotAFile);
+    }
+    Ok(entry)
+}
+
+/// The id of the directory at `path`, checked against `expected_sha` when
+/// one is given so a stale client can't drop files it hasn't seen.
+fn dir_at_path(tree: &Tree<'_>, path: &str, expected_sha: Option<&str>) -> Result<Oid, GitError> {
+    let entry = tree
+        .get_path(Path::new(path))
+        .map_err(|_| GitError::PathNotFound(path.to_string()))?;
+    if entry.kind() != Some(ObjectType::Tree) {
+        return Err(GitError::NotADirectory);
+    }
+
+    if let Some(expected_sha) = expected_sha {
+        let expected_oid = Oid::from_str(expected_sha)
+            .map_err(|_| GitError::Internal(format!("Invalid SHA: {expected_sha}")))?;
+        if entry.id() != expected_oid {
+            return Err(GitError::Conflict(format!(
+                "Directory has been modified. Expected {expected_sha}, found {}",
+                entry.id()
+            )));
+        }
+    }
+
+    Ok(entry.id())
+}
+
 /// Search for paths matching a glob pattern in the tree.
 pub fn search_paths(
     repo: &Repository,
@@ -832,3 +1546,461 @@ pub fn get_file_history(
 
     Ok((commits, next_cursor, has_more))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trailers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_append_trailers() {
+        let co_author = trailers(&[("Co-authored-by", "Ada <ada@example.com>")]);
+
+        assert_eq!(
+            append_trailers("Fix bug", &co_author),
+            "Fix bug\n\nCo-authored-by: Ada <ada@example.com>\n"
+        );
+        assert_eq!(
+            append_trailers("Fix bug\n\nLonger body.\n", &co_author),
+            "Fix bug\n\nLonger body.\n\nCo-authored-by: Ada <ada@example.com>\n"
+        );
+        assert_eq!(
+            append_trailers("Fix bug\n\nRefs: #12\n", &co_author),
+            "Fix bug\n\nRefs: #12\nCo-authored-by: Ada <ada@example.com>\n"
+        );
+        assert_eq!(
+            append_trailers("Fix bug\n\nCo-authored-by: Ada <ada@example.com>\n", &co_author),
+            "Fix bug\n\nCo-authored-by: Ada <ada@example.com>\n"
+        );
+        assert_eq!(append_trailers("Fix bug", &[]), "Fix bug");
+    }
+
+    #[test]
+    fn test_is_trailer_key() {
+        assert!(is_trailer_key("Signed-off-by"));
+        assert!(is_trailer_key("X-Ticket2"));
+        assert!(!is_trailer_key(""));
+        assert!(!is_trailer_key("-Leading"));
+        assert!(!is_trailer_key("Has space"));
+        assert!(!is_trailer_key("Colon:"));
+    }
+
+    #[test]
+    fn test_parse_gitmodules() {
+        let text = r#"
+[submodule "vendor/lib"]
+	path = vendor/lib
+	url = https://example.com/lib.git ; pinned
+[core]
+	path = ignored
+[submodule "docs theme"]
+	URL = "../theme.git"
+	path = themes/docs/
+[submodule "incomplete"]
+	path = nowhere
+"#;
+
+        assert_eq!(
+            parse_gitmodules(text),
+            vec![
+                Submodule {
+                    name: "vendor/lib".to_string(),
+                    path: "vendor/lib".to_string(),
+                    url: "https://example.com/lib.git".to_string(),
+                },
+                Submodule {
+                    name: "docs theme".to_string(),
+                    path: "themes/docs".to_string(),
+                    url: "../theme.git".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_submodule_url() {
+        let base = "https://git.example.com/git/alice/app.git";
+
+        assert_eq!(
+            resolve_submodule_url(base, "../lib.git"),
+            "https://git.example.com/git/alice/lib.git"
+        );
+        assert_eq!(
+            resolve_submodule_url(base, "../../bob/lib.git"),
+            "https://git.example.com/git/bob/lib.git"
+        );
+        assert_eq!(
+            resolve_submodule_url(base, "./nested"),
+            "https://git.example.com/git/alice/app.git/nested"
+        );
+        assert_eq!(
+            resolve_submodule_url(base, "git@example.com:lib.git"),
+            "git@example.com:lib.git"
+        );
+    }
+
+    #[test]
+    fn test_dir_actions() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let author = CommitAuthor {
+            name: "Test".to_string(),
+            email: "test@example.com".to_string(),
+            author_time: None,
+            commit_time: None,
+        };
+        let create = |path: &str| CommitActionOp::Create {
+            path: path.to_string(),
+            content: b"x".to_vec(),
+        };
+        let paths = |oid: Oid| {
+            let mut paths = Vec::new();
+            let tree = repo.find_commit(oid).unwrap().tree().unwrap();
+            tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+                if entry.kind() == Some(ObjectType::Blob) {
+                    paths.push(format!("{root}{}", entry.name().unwrap()));
+                }
+                git2::TreeWalkResult::Ok
+            })
+            .unwrap();
+            paths
+        };
+
+        let actions = [create("a/x"), create("a/b/y"), create("c")];
+        apply_actions(&repo, "main", &actions, "init", &author).unwrap();
+
+        let move_dir = |from: &str, to: &str| CommitActionOp::MoveDir {
+            from: from.to_string(),
+            to: to.to_string(),
+            sha: None,
+        };
+        let oid = apply_actions(&repo, "main", &[move_dir("a", "d/e")], "mv", &author).unwrap();
+        assert_eq!(paths(oid), ["c", "d/e/b/y", "d/e/x"]);
+
+        let err = apply_actions(&repo, "main", &[move_dir("d", "c")], "mv", &author);
+        assert!(matches!(err, Err(GitError::Conflict(_))));
+        let err = apply_actions(&repo, "main", &[move_dir("c", "f")], "mv", &author);
+        assert!(matches!(err, Err(GitError::NotADirectory)));
+
+        let delete_dir = |sha: Option<&str>| CommitActionOp::DeleteDir {
+            path: "d/e".to_string(),
+            sha: sha.map(str::to_string),
+        };
+        let stale = Oid::zero().to_string();
+        let err = apply_actions(&repo, "main", &[delete_dir(Some(&stale))], "rm", &author);
+        assert!(matches!(err, Err(GitError::Conflict(_))));
+        let oid = apply_actions(&repo, "main", &[delete_dir(None)], "rm", &author).unwrap();
+        assert_eq!(paths(oid), ["c"]);
+    }
+
+    #[test]
+    fn test_chmod_and_kept_modes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let author = CommitAuthor {
+            name: "Test".to_string(),
+            email: "test@example.com".to_string(),
+            author_time: None,
+            commit_time: None,
+        };
+        let mode_of = |oid: Oid, path: &str| {
+            let tree = repo.find_commit(oid).unwrap().tree().unwrap();
+            tree.get_path(Path::new(path)).unwrap().filemode()
+        };
+        let chmod = |path: &str, mode: i32| CommitActionOp::Chmod {
+            path: path.to_string(),
+            mode,
+            sha: None,
+        };
+
+        let actions = [
+            CommitActionOp::Create {
+                path: "run.sh".to_string(),
+                content: b"#!/bin/sh\n".to_vec(),
+            },
+            chmod("run.sh", 0o100755),
+        ];
+        let oid = apply_actions(&repo, "main", &actions, "add", &author).unwrap();
+        assert_eq!(mode_of(oid, "run.sh"), 0o100755);
+
+        let update = CommitActionOp::Update {
+            path: "run.sh".to_string(),
+            content: b"#!/bin/sh\necho hi\n".to_vec(),
+            sha: None,
+        };
+        let oid = apply_actions(&repo, "main", &[update], "edit", &author).unwrap();
+        assert_eq!(mode_of(oid, "run.sh"), 0o100755);
+
+        let rename = CommitActionOp::Move {
+            from: "run.sh".to_string(),
+            to: "bin/run".to_string(),
+            sha: None,
+        };
+        let oid = apply_actions(&repo, "main", &[rename], "mv", &author).unwrap();
+        assert_eq!(mode_of(oid, "bin/run"), 0o100755);
+
+        let link = [
+            CommitActionOp::Create {
+                path: "run".to_string(),
+                content: b"bin/run".to_vec(),
+            },
+            chmod("run", 0o120000),
+        ];
+        let oid = apply_actions(&repo, "main", &link, "link", &author).unwrap();
+        assert_eq!(mode_of(oid, "run"), 0o120000);
+
+        let err = apply_actions(&repo, "main", &[chmod("bin", 0o100755)], "x", &author);
+        assert!(matches!(err, Err(GitError::NotAFile)));
+    }
+
+    #[test]
+    fn test_resolve_symlinks() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let author = CommitAuthor {
+            name: "Test".to_string(),
+            email: "test@example.com".to_string(),
+            author_time: None,
+            commit_time: None,
+        };
+        let mut actions = vec![CommitActionOp::Create {
+            path: "docs/guide/intro.md".to_string(),
+            content: b"# Intro\n".to_vec(),
+        }];
+        for (path, target) in [
+            ("intro", "docs/guide/intro.md"),
+            ("docs/current", "guide"),
+            ("docs/guide/up", "../../intro"),
+            ("latest", "docs/current/intro.md"),
+            ("escape", "../outside"),
+            ("absolute", "/etc/passwd"),
+            ("loop", "loop"),
+            ("dangling", "docs/missing.md"),
+        ] {
+            actions.push(CommitActionOp::Create {
+                path: path.to_string(),
+                content: target.as_bytes().to_vec(),
+            });
+            actions.push(CommitActionOp::Chmod {
+                path: path.to_string(),
+                mode: 0o120000,
+                sha: None,
+            });
+        }
+        let oid = apply_actions(&repo, "main", &actions, "links", &author).unwrap();
+        let tree = repo.find_commit(oid).unwrap().tree().unwrap();
+        let resolve = |path: &str| resolve_symlinks(&repo, &tree, path).unwrap();
+
+        assert_eq!(resolve("intro").as_deref(), Some("docs/guide/intro.md"));
+        assert_eq!(
+            resolve("docs/guide/up").as_deref(),
+            Some("docs/guide/intro.md")
+        );
+        assert_eq!(resolve("latest").as_deref(), Some("docs/guide/intro.md"));
+        assert_eq!(resolve("docs/current").as_deref(), Some("docs/guide"));
+        assert_eq!(resolve("dangling").as_deref(), Some("docs/missing.md"));
+        assert_eq!(resolve("escape"), None);
+        assert_eq!(resolve("absolute"), None);
+        assert_eq!(resolve("loop"), None);
+    }
+
+    #[test]
+    fn test_build_diff() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let author = CommitAuthor {
+            name: "Test".to_string(),
+            email: "test@example.com".to_string(),
+            author_time: None,
+            commit_time: None,
+        };
+        let create = |path: &str, content: &str| CommitActionOp::Create {
+            path: path.to_string(),
+            content: content.as_bytes().to_vec(),
+        };
+        let base = apply_actions(
+            &repo,
+            "main",
+            &[create("a.txt", "one\ntwo\n"), create("old.txt", "keep\n")],
+            "add",
+            &author,
+        )
+        .unwrap();
+        let actions = [
+            CommitActionOp::Update {
+                path: "a.txt".to_string(),
+                content: b"one\n2\n".to_vec(),
+                sha: None,
+            },
+            CommitActionOp::Move {
+                from: "old.txt".to_string(),
+                to: "new.txt".to_string(),
+                sha: None,
+            },
+        ];
+        let head = apply_actions(&repo, "main", &actions, "edit", &author).unwrap();
+        let base_tree = repo.find_commit(base).unwrap().tree().unwrap();
+        let head_tree = repo.find_commit(head).unwrap().tree().unwrap();
+
+        let (patch, stats, files) =
+            build_diff(&repo, Some(&base_tree), &head_tree, &[], false).unwrap();
+        assert!(patch.unwrap().contains("+2\n"));
+        assert_eq!(stats.files_changed, 2);
+        assert_eq!(files[0].status, "modified");
+        assert_eq!((files[0].additions, files[0].deletions), (1, 1));
+        let hunks = files[0].hunks.as_ref().unwrap();
+        assert_eq!(hunks[0].header, "@@ -1,2 +1,2 @@");
+        assert_eq!(hunks[0].lines, [" one", "-two", "+2"]);
+        assert_eq!(files[1].status, "renamed");
+        assert_eq!(files[1].old_path.as_deref(), Some("old.txt"));
+        assert_eq!(files[1].new_path.as_deref(), Some("new.txt"));
+
+        let paths = ["a.txt".to_string()];
+        let (patch, stats, files) =
+            build_diff(&repo, Some(&base_tree), &head_tree, &paths, true).unwrap();
+        assert!(patch.is_none());
+        assert_eq!(stats.files_changed, 1);
+        assert_eq!(files.len(), 1);
+        assert!(files[0].hunks.is_none());
+    }
+
+    #[test]
+    fn test_merge_strategies() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let author = CommitAuthor {
+            name: "Test".to_string(),
+            email: "test@example.com".to_string(),
+            author_time: None,
+            commit_time: None,
+        };
+        let write = |branch: &str, path: &str, content: &str| {
+            let action = CommitActionOp::Update {
+                path: path.to_string(),
+                content: content.as_bytes().to_vec(),
+                sha: None,
+            };
+            apply_actions(&repo, branch, &[action], "edit", &author).unwrap()
+        };
+        let branch = |name: &str, target: Oid| {
+            let commit = repo.find_commit(target).unwrap();
+            repo.branch(name, &commit, true).unwrap();
+        };
+
+        let merge =
+            |base, head, strategy| prepare_merge(&repo, base, head, strategy, "merge", &author);
+
+        let root = write("main", "a", "1");
+        branch("feature", root);
+        let feature = write("feature", "b", "2");
+
+        // The base is already part of the feature branch.
+        let ff = merge(root, feature, MergeStrategy::FastForward);
+        assert_eq!(ff.unwrap(), MergeOutcome::Ready(feature));
+        let done = merge(feature, root, MergeStrategy::Merge);
+        assert_eq!(done.unwrap(), MergeOutcome::UpToDate);
+        assert!(preview_merge(&repo, root, feature).unwrap().fast_forward);
+        assert!(preview_merge(&repo, feature, root).unwrap().up_to_date);
+
+        let main = write("main", "c", "3");
+        let err = merge(main, feature, MergeStrategy::FastForward);
+        assert!(matches!(err, Err(GitError::Conflict(_))));
+
+        for (strategy, parents) in [(MergeStrategy::Merge, 2), (MergeStrategy::Squash, 1)] {
+            let MergeOutcome::Ready(oid) = merge(main, feature, strategy).unwrap() else {
+                panic!("expected a merge commit");
+            };
+            let commit = repo.find_commit(oid).unwrap();
+            assert_eq!(commit.parent_count(), parents);
+            assert_eq!(commit.parent_id(0).unwrap(), main);
+            let tree = commit.tree().unwrap();
+            assert!(["a", "b", "c"].iter().all(|p| tree.get_name(p).is_some()));
+        }
+
+        // The branch has not moved yet, and only moves from the tip it had.
+        let stale = advance_branch(&repo, "main", root, feature, "merge");
+        assert!(matches!(stale, Err(GitError::Conflict(_))));
+
+        write("main", "b", "main");
+        let main = repo.refname_to_id("refs/heads/main").unwrap();
+        let conflicts = MergeOutcome::Conflicts(vec!["b".to_string()]);
+        let outcome = merge(main, feature, MergeStrategy::Merge);
+        assert_eq!(outcome.unwrap(), conflicts);
+
+        let preview = preview_merge(&repo, main, feature).unwrap();
+        assert_eq!(preview.conflicts, ["b"]);
+        assert!(!preview.fast_forward && !preview.up_to_date);
+    }
+
+    #[test]
+    fn test_apply_patches() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let identity = |name: &str| CommitAuthor {
+            name: name.to_string(),
+            email: format!("{}@example.com", name.to_lowercase()),
+            author_time: None,
+            commit_time: None,
+        };
+        let committer = identity("Committer");
+        let action = CommitActionOp::Create {
+            path: "a.txt".to_string(),
+            content: b"one\ntwo\n".to_vec(),
+        };
+        let base = apply_actions(&repo, "main", &[action], "init", &committer).unwrap();
+
+        let patch = |diff: &str, message: &str| PatchCommit {
+            diff: Diff::from_buffer(diff.as_bytes()).unwrap(),
+            message: message.to_string(),
+            author: identity("Author"),
+        };
+        let edit = "diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1,2 @@
+ one
+-two
++three
+";
+        let add = "diff --git a/b.txt b/b.txt
+new file mode 100644
+--- /dev/null
++++ b/b.txt
+@@ -0,0 +1 @@
++new
+";
+        let commits = apply_patches(
+            &repo,
+            base,
+            &[patch(edit, "Edit a"), patch(add, "Add b")],
+            &committer,
+        )
+        .unwrap();
+        assert_eq!(commits.len(), 2);
+
+        let tip = repo.find_commit(commits[1]).unwrap();
+        assert_eq!(tip.parent_id(0).unwrap(), commits[0]);
+        assert_eq!(tip.message(), Some("Add b"));
+        assert_eq!(tip.author().name(), Some("Author"));
+        assert_eq!(tip.committer().name(), Some("Committer"));
+        let tree = tip.tree().unwrap();
+        let blob = |path: &str| {
+            let entry = tree.get_path(std::path::Path::new(path)).unwrap();
+            repo.find_blob(entry.id()).unwrap().content().to_vec()
+        };
+        assert_eq!(blob("a.txt"), b"one\nthree\n");
+        assert_eq!(blob("b.txt"), b"new\n");
+        // Nothing moved the branch.
+        assert_eq!(repo.refname_to_id("refs/heads/main").unwrap(), base);
+
+        // The edit no longer applies once it has been made.
+        let stale = apply_patches(&repo, commits[1], &[patch(edit, "Again")], &committer);
+        assert!(matches!(stale, Err(GitError::Conflict(_))));
+    }
+}
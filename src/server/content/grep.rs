@@ -0,0 +1,415 @@
+use std::process::Stdio;
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderValue, header},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use futures_util::stream;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::server::AppState;
+use crate::server::limits::{apply_limits, deadline};
+use crate::server::response::ApiError;
+
+use super::auth::OptionalAuth;
+use super::git_ops::resolve_ref;
+use super::handlers::{load_repo_and_check_access, repo_path};
+
+const DEFAULT_FILE_LIMIT: u32 = 100;
+const MAX_FILE_LIMIT: u32 = 1000;
+const DEFAULT_MATCHES_PER_FILE: u32 = 50;
+const MAX_MATCHES_PER_FILE: u32 = 1000;
+const MAX_CONTEXT_LINES: u32 = 10;
+
+/// Longer lines (minified code, data files) are cut to this many bytes.
+const MAX_LINE_BYTES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrepCase {
+    #[default]
+    Sensitive,
+    Insensitive,
+    /// Insensitive unless the query has an uppercase letter.
+    Smart,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrepParams {
+    /// Fixed string to look for, or a POSIX extended regex with `regex=true`.
+    q: String,
+    #[serde(rename = "ref")]
+    ref_name: Option<String>,
+    /// Only search files under this path (a git pathspec, so globs work).
+    path: Option<String>,
+    #[serde(default)]
+    regex: bool,
+    #[serde(default)]
+    case: GrepCase,
+    /// Lines shown before and after each match.
+    context: Option<u32>,
+    /// Matching lines reported per file.
+    max_per_file: Option<u32>,
+    /// Files reported before the search stops.
+    limit: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct GrepLine {
+    pub line: u64,
+    pub text: String,
+    pub is_match: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct GrepFile {
+    pub path: String,
+    pub matches: usize,
+    pub lines: Vec<GrepLine>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GrepSummary {
+    pub sha: String,
+    pub files: usize,
+    pub matches: usize,
+    pub is_truncated: bool,
+}
+
+/// One line of the response stream.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum GrepRecord {
+    File(GrepFile),
+    Summary(GrepSummary),
+}
+
+impl GrepRecord {
+    fn to_ndjson(&self) -> Bytes {
+        let mut line = serde_json::to_vec(self).unwrap_or_default();
+        line.push(b'\n');
+        Bytes::from(line)
+    }
+}
+
+/// Splits a line of `git grep --null -n --column <tree>` output into path,
+/// line number, whether it matched, and text. Matches carry a column
+/// between the line number and the text; context lines don't.
+fn parse_line<'a>(line: &'a [u8], prefix: &[u8]) -> Option<(&'a [u8], u64, bool, &'a [u8])> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let mut fields = line.strip_prefix(prefix)?.splitn(3, |&b| b == 0);
+    let path = fields.next()?;
+    let number = std::str::from_utf8(fields.next()?).ok()?.parse().ok()?;
+    let rest = fields.next()?;
+    let column = rest
+        .iter()
+        .position(|&b| b == 0)
+        .filter(|&end| end > 0 && rest[..end].iter().all(u8::is_ascii_digit));
+    Some(match column {
+        Some(end) => (path, number, true, &rest[end + 1..]),
+        None => (path, number, false, rest),
+    })
+}
+
+fn line_text(text: &[u8]) -> String {
+    let mut text = String::from_utf8_lossy(text).into_owned();
+    if text.len() > MAX_LINE_BYTES {
+        let mut end = MAX_LINE_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+    text
+}
+
+/// Collects grep output lines into one [`GrepFile`] per path.
+struct FileGrouper {
+    prefix: Vec<u8>,
+    current: Option<GrepFile>,
+}
+
+impl FileGrouper {
+    fn new(tree: &str) -> Self {
+        Self {
+            prefix: format!("{tree}:").into_bytes(),
+            current: None,
+        }
+    }
+
+    /// Adds a line of output, returning the previous file once a line for
+    /// another one arrives. Group separators and anything unparseable are
+    /// skipped.
+    fn push(&mut self, line: &[u8]) -> Option<GrepFile> {
+        let (path, number, is_match, text) = parse_line(line, &self.prefix)?;
+        let path = String::from_utf8_lossy(path);
+        let finished = match &self.current {
+            Some(file) if file.path == path => None,
+            _ => self.current.replace(GrepFile {
+                path: path.into_owned(),
+                matches: 0,
+                lines: Vec::new(),
+            }),
+        };
+        if let Some(file) = self.current.as_mut() {
+            file.matches += usize::from(is_match);
+            file.lines.push(GrepLine {
+                line: number,
+                text: line_text(text),
+                is_match,
+            });
+        }
+        finished
+    }
+
+    fn finish(&mut self) -> Option<GrepFile> {
+        self.current.take()
+    }
+}
+
+/// GET /repos/{id}/grep - Search file contents at a ref, streaming one JSON
+/// line per matching file followed by a summary line
+pub async fn grep_handler(
+    auth: OptionalAuth,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<GrepParams>,
+) -> Result<Response, ApiError> {
+    let (repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+
+    if params.q.is_empty() {
+        return Err(ApiError::bad_request("Search query (q) is required"));
+    }
+    if params.q.contains('\n') {
+        return Err(ApiError::bad_request("Search query must be a single line"));
+    }
+    let ref_name = params.ref_name.as_deref().unwrap_or("");
+    let tree = resolve_ref(&git_repo, ref_name)?.to_string();
+    drop(git_repo);
+
+    let ignore_case = match params.case {
+        GrepCase::Sensitive => false,
+        GrepCase::Insensitive => true,
+        GrepCase::Smart => !params.q.chars().any(char::is_uppercase),
+    };
+    let context = params.context.unwrap_or(0).min(MAX_CONTEXT_LINES);
+    let max_per_file = params
+        .max_per_file
+        .unwrap_or(DEFAULT_MATCHES_PER_FILE)
+        .clamp(1, MAX_MATCHES_PER_FILE);
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_FILE_LIMIT)
+        .clamp(1, MAX_FILE_LIMIT) as usize;
+
+    let path = repo_path(&state, &repo.namespace_id, &repo.name);
+    let slot = state
+        .fair_slot(&repo.namespace_id, auth.traffic_class())
+        .await;
+
+    let limits = &state.limits.search;
+    let mut cmd = Command::new("git");
+    cmd.arg("--git-dir")
+        .arg(&path)
+        .args(["grep", "-I", "-n", "--column", "--null"])
+        .arg(if params.regex { "-E" } else { "-F" })
+        .arg(format!("--max-count={max_per_file}"))
+        .arg(format!("--context={context}"));
+    if ignore_case {
+        cmd.arg("-i");
+    }
+    cmd.arg("-e").arg(&params.q).arg(&tree).arg("--");
+    if let Some(pathspec) = params.path.as_deref().map(|p| p.trim_start_matches('/')) {
+        if !pathspec.is_empty() {
+            cmd.arg(pathspec);
+        }
+    }
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    apply_limits(&mut cmd, limits);
+    let expires = limits
+        .timeout(state.content_limits.grep_timeout)
+        .map(|timeout| Instant::now() + timeout);
+    let remaining = move || expires.map(|at| at.saturating_duration_since(Instant::now()));
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| ApiError::internal(format!("Failed to run git grep: {e}")))?;
+    let (Some(stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err(ApiError::internal("Failed to capture git grep output"));
+    };
+    let diagnostics = tokio::spawn(async move {
+        let mut diagnostics = String::new();
+        let _ = stderr.read_to_string(&mut diagnostics).await;
+        diagnostics
+    });
+
+    // Wait for the first line before answering, so a pattern git rejects is
+    // still a 400 rather than an error in the middle of a 200 stream.
+    let mut reader = BufReader::new(stdout);
+    let mut line = Vec::new();
+    match deadline(remaining(), reader.read_until(b'\n', &mut line)).await {
+        Some(Ok(_)) => {}
+        Some(Err(e)) => return Err(ApiError::internal(format!("Failed to read git grep: {e}"))),
+        None => return Err(ApiError::internal("git grep timed out")),
+    }
+    if line.is_empty() {
+        let status = deadline(remaining(), child.wait())
+            .await
+            .ok_or_else(|| ApiError::internal("git grep timed out"))?
+            .map_err(|e| ApiError::internal(format!("Failed to wait for git grep: {e}")))?;
+        // git grep exits with 1 when nothing matched.
+        if !status.success() && status.code() != Some(1) {
+            let diagnostics = diagnostics.await.unwrap_or_default();
+            let message = diagnostics.trim().trim_start_matches("fatal: ");
+            return Err(ApiError::bad_request(format!("Search failed: {message}")));
+        }
+    }
+
+    let (tx, rx) = mpsc::channel::<Bytes>(16);
+    tokio::spawn(async move {
+        let mut grouper = FileGrouper::new(&tree);
+        let mut files = 0;
+        let mut matches = 0;
+        let mut is_truncated = false;
+        let mut stopped = false;
+
+        let run = async {
+            let mut emit = async |file: Option<GrepFile>| {
+                let Some(file) = file else {
+                    return true;
+                };
+                if files == limit {
+                    is_truncated = true;
+                    return false;
+                }
+                files += 1;
+                matches += file.matches;
+                tx.send(GrepRecord::File(file).to_ndjson()).await.is_ok()
+            };
+            while !line.is_empty() {
+                if !emit(grouper.push(&line)).await {
+                    return false;
+                }
+                line.clear();
+                if reader.read_until(b'\n', &mut line).await.is_err() {
+                    break;
+                }
+            }
+            emit(grouper.finish()).await
+        };
+        match deadline(remaining(), run).await {
+            Some(true) => {}
+            Some(false) => stopped = true,
+            None => {
+                warn!("git grep exceeded its time limit; killing it");
+                stopped = true;
+                is_truncated = true;
+            }
+        }
+
+        if stopped {
+            let _ = child.start_kill();
+        }
+        match child.wait().await {
+            Ok(status) if !stopped && !status.success() && status.code() != Some(1) => {
+                let diagnostics = diagnostics.await.unwrap_or_default();
+                warn!("git grep exited with {status}: {}", diagnostics.trim());
+                is_truncated = true;
+            }
+            Err(e) => warn!("Failed to wait for git grep: {e}"),
+            Ok(_) => {}
+        }
+        drop(slot);
+
+        let summary = GrepSummary {
+            sha: tree,
+            files,
+            matches,
+            is_truncated,
+        };
+        let _ = tx.send(GrepRecord::Summary(summary).to_ndjson()).await;
+    });
+
+    let body = stream::unfold(rx, |mut rx| async move {
+        let chunk = rx.recv().await?;
+        Some((Ok::<_, std::io::Error>(chunk), rx))
+    });
+    let mut response = Body::from_stream(body).into_response();
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/x-ndjson"),
+    );
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let prefix = b"abc:";
+        assert_eq!(
+            parse_line(b"abc:src/a.rs\x0012\x005\x00let x = 1;\n", prefix),
+            Some((&b"src/a.rs"[..], 12, true, &b"let x = 1;"[..]))
+        );
+        assert_eq!(
+            parse_line(b"abc:src/a.rs\x0011\x00// 12\x00x\r\n", prefix),
+            Some((&b"src/a.rs"[..], 11, false, &b"// 12\x00x"[..]))
+        );
+        assert_eq!(
+            parse_line(b"abc:a:b.txt\x001\x00\n", prefix),
+            Some((&b"a:b.txt"[..], 1, false, &b""[..]))
+        );
+        assert_eq!(parse_line(b"--\n", prefix), None);
+    }
+
+    #[test]
+    fn test_file_grouper() {
+        let mut grouper = FileGrouper::new("abc");
+        assert_eq!(grouper.push(b"abc:a.txt\x001\x00before\n"), None);
+        assert_eq!(grouper.push(b"abc:a.txt\x002\x001\x00hit\n"), None);
+        assert_eq!(grouper.push(b"--\n"), None);
+        let first = grouper.push(b"abc:b.txt\x007\x003\x00hit\n").unwrap();
+        assert_eq!(first.path, "a.txt");
+        assert_eq!(first.matches, 1);
+        assert_eq!(
+            first.lines,
+            vec![
+                GrepLine {
+                    line: 1,
+                    text: "before".into(),
+                    is_match: false
+                },
+                GrepLine {
+                    line: 2,
+                    text: "hit".into(),
+                    is_match: true
+                },
+            ]
+        );
+        assert_eq!(grouper.finish().unwrap().path, "b.txt");
+        assert_eq!(grouper.finish(), None);
+    }
+
+    #[test]
+    fn test_line_text_truncates_on_char_boundary() {
+        let long = "é".repeat(MAX_LINE_BYTES);
+        let text = line_text(long.as_bytes());
+        assert_eq!(text.len(), MAX_LINE_BYTES);
+        assert!(text.chars().all(|c| c == 'é'));
+    }
+}
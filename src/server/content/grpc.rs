@@ -0,0 +1,416 @@
+//! gRPC mirror of the content API's reads, for services that poll cutman
+//! heavily and would rather hold one multiplexed HTTP/2 connection than
+//! page through JSON. Built with the `grpc` feature and served on
+//! `grpc_port`; the service is defined in `proto/cutman/v1/content.proto`.
+//!
+//! Requests go through the same token validation and repository access
+//! checks as the REST handlers, and blobs are read by the same code as the
+//! raw endpoint, so its size limit applies here too.
+
+// tonic's `Status` is large, and it's the error type the service must use.
+#![allow(clippy::result_large_err)]
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use axum::http::{HeaderMap, StatusCode, header::CONTENT_TYPE};
+use futures_util::{Stream, StreamExt, stream};
+use git2::Oid;
+use tonic::{Code, Request, Response, Status, metadata::MetadataMap};
+
+use crate::auth::{
+    TokenValidationError, extract_token_from_header, scope_request, sudo_principal_id,
+    validate_token_with_sudo,
+};
+use crate::server::AppState;
+use crate::server::response::{ApiError, StoreOptionExt, StoreResultExt};
+use crate::types::Repo;
+
+use super::auth::{OptionalAuth, check_content_access};
+use super::dto::SignatureResponse;
+use super::git_ops::{GitError, commit_to_response, get_commit, resolve_ref};
+use super::handlers::{collect_refs, load_repo_and_check_access};
+use super::raw::{RawBlob, serve_raw_blob};
+
+mod proto {
+    tonic::include_proto!("cutman.v1");
+}
+
+use proto::content_server::{Content, ContentServer};
+
+type GrpcStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+/// The gRPC service, as a router to serve on its own listener.
+pub fn grpc_router(state: Arc<AppState>) -> axum::Router {
+    tonic::service::Routes::new(ContentServer::new(ContentService { state }))
+        .into_axum_router()
+        .layer(axum::middleware::from_fn(scope_request))
+}
+
+struct ContentService {
+    state: Arc<AppState>,
+}
+
+impl ContentService {
+    /// The caller, from an `authorization` metadata entry read the way the
+    /// REST API reads the header. Anonymous without one.
+    fn authenticate(&self, metadata: &MetadataMap) -> Result<OptionalAuth, Status> {
+        let headers = metadata.clone().into_headers();
+        let auth_header = headers.get("authorization").and_then(|h| h.to_str().ok());
+        let raw_token = match extract_token_from_header(auth_header) {
+            Ok(Some(token)) => token,
+            Ok(None) => {
+                return Ok(OptionalAuth {
+                    principal: None,
+                    token: None,
+                });
+            }
+            Err(e) => return Err(token_status(e)),
+        };
+
+        let sudo = sudo_principal_id(&headers);
+        let validated =
+            validate_token_with_sudo(&self.state, &raw_token, sudo, false).map_err(token_status)?;
+        Ok(OptionalAuth {
+            principal: validated.principal,
+            token: Some(validated.token),
+        })
+    }
+}
+
+#[tonic::async_trait]
+impl Content for ContentService {
+    type ListCommitsStream = GrpcStream<proto::Commit>;
+    type GetBlobStream = GrpcStream<proto::BlobChunk>;
+
+    async fn get_repo(
+        &self,
+        request: Request<proto::GetRepoRequest>,
+    ) -> Result<Response<proto::Repo>, Status> {
+        let auth = self.authenticate(request.metadata())?;
+        let repo = self
+            .state
+            .store
+            .get_repo_by_id(&request.get_ref().repo_id)
+            .api_err("Failed to get repository")?
+            .or_not_found("Repository not found")?;
+        check_content_access(&self.state, &auth, &repo)?;
+        Ok(Response::new(repo_message(repo)))
+    }
+
+    async fn list_refs(
+        &self,
+        request: Request<proto::ListRefsRequest>,
+    ) -> Result<Response<proto::ListRefsResponse>, Status> {
+        let auth = self.authenticate(request.metadata())?;
+        let req = request.into_inner();
+        let (_repo, git_repo) =
+            load_repo_and_check_access(&self.state, &auth, &req.repo_id).await?;
+
+        let refs = collect_refs(&git_repo)?
+            .into_iter()
+            .map(|r| proto::Ref {
+                name: r.name,
+                r#type: r.ref_type,
+                commit_sha: r.commit_sha,
+                is_default: r.is_default,
+            })
+            .collect();
+        Ok(Response::new(proto::ListRefsResponse { refs }))
+    }
+
+    async fn list_commits(
+        &self,
+        request: Request<proto::ListCommitsRequest>,
+    ) -> Result<Response<Self::ListCommitsStream>, Status> {
+        let auth = self.authenticate(request.metadata())?;
+        let req = request.into_inner();
+        let (_repo, git_repo) =
+            load_repo_and_check_access(&self.state, &auth, &req.repo_id).await?;
+
+        let start = resolve_ref(&git_repo, &req.r#ref)?;
+        let mut revwalk = git_repo
+            .revwalk()
+            .map_err(|e| Status::internal(format!("Failed to create revwalk: {e}")))?;
+        revwalk
+            .push(start)
+            .map_err(|e| Status::internal(format!("Failed to start revwalk: {e}")))?;
+        if !req.since_sha.is_empty() {
+            Oid::from_str(&req.since_sha)
+                .ok()
+                .and_then(|since| revwalk.hide(since).ok())
+                .ok_or_else(|| Status::invalid_argument("since_sha is not a known commit"))?;
+        }
+        let limit = match req.limit {
+            0 => usize::MAX,
+            limit => limit as usize,
+        };
+
+        // git2 objects can't cross an await, so the walk finishes here.
+        let mut commits = Vec::new();
+        for oid in revwalk.take(limit) {
+            let oid = oid.map_err(|e| Status::internal(format!("Revwalk error: {e}")))?;
+            commits.push(Ok(commit_message(&get_commit(&git_repo, oid)?)));
+        }
+        Ok(Response::new(Box::pin(stream::iter(commits))))
+    }
+
+    async fn get_commit(
+        &self,
+        request: Request<proto::GetCommitRequest>,
+    ) -> Result<Response<proto::Commit>, Status> {
+        let auth = self.authenticate(request.metadata())?;
+        let req = request.into_inner();
+        let (_repo, git_repo) =
+            load_repo_and_check_access(&self.state, &auth, &req.repo_id).await?;
+
+        let commit = get_commit(&git_repo, resolve_ref(&git_repo, &req.r#ref)?)?;
+        Ok(Response::new(commit_message(&commit)))
+    }
+
+    async fn get_blob(
+        &self,
+        request: Request<proto::GetBlobRequest>,
+    ) -> Result<Response<Self::GetBlobStream>, Status> {
+        let auth = self.authenticate(request.metadata())?;
+        let req = request.into_inner();
+        let (_repo, git_repo) =
+            load_repo_and_check_access(&self.state, &auth, &req.repo_id).await?;
+
+        let path = req.path.trim_start_matches('/');
+        let blob = RawBlob::find(&git_repo, &req.r#ref, path)?;
+        drop(git_repo);
+        let response = serve_raw_blob(&self.state, &HeaderMap::new(), blob, path).await?;
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+                .unwrap_or_default()
+        };
+        let content_type = header(CONTENT_TYPE);
+        let size = header(axum::http::header::CONTENT_LENGTH)
+            .parse()
+            .unwrap_or_default();
+
+        let first = proto::BlobChunk {
+            data: bytes::Bytes::new(),
+            size,
+            content_type,
+        };
+        if size == 0 {
+            return Ok(Response::new(Box::pin(stream::once(async { Ok(first) }))));
+        }
+        let mut first = Some(first);
+        let chunks = response.into_body().into_data_stream().map(move |data| {
+            let data = data.map_err(|e| Status::internal(format!("Failed to read blob: {e}")))?;
+            let mut chunk = first.take().unwrap_or_default();
+            chunk.data = data;
+            Ok(chunk)
+        });
+        Ok(Response::new(Box::pin(chunks)))
+    }
+}
+
+fn repo_message(repo: Repo) -> proto::Repo {
+    proto::Repo {
+        id: repo.id,
+        namespace_id: repo.namespace_id,
+        name: repo.name,
+        description: repo.description,
+        visibility: repo.visibility.as_str().to_string(),
+        size_bytes: repo.size_bytes,
+        last_push_at: repo.last_push_at.map(|at| at.timestamp()),
+        created_at: repo.created_at.timestamp(),
+        updated_at: repo.updated_at.timestamp(),
+    }
+}
+
+fn commit_message(commit: &git2::Commit<'_>) -> proto::Commit {
+    let signature = |sig: SignatureResponse| proto::Signature {
+        name: sig.name,
+        email: sig.email,
+        timestamp: sig.date.timestamp(),
+    };
+    let commit = commit_to_response(commit, None);
+    proto::Commit {
+        sha: commit.sha,
+        message: commit.message,
+        author: Some(signature(commit.author)),
+        committer: Some(signature(commit.committer)),
+        parent_shas: commit.parent_shas,
+        tree_sha: commit.tree_sha,
+    }
+}
+
+fn token_status(err: TokenValidationError) -> Status {
+    match err {
+        TokenValidationError::InvalidScheme => {
+            Status::unauthenticated("Invalid authorization scheme")
+        }
+        TokenValidationError::InvalidToken => Status::unauthenticated("Invalid token"),
+        TokenValidationError::TokenRetired => {
+            Status::unauthenticated("Token format retired; reissue it or create a new token")
+        }
+        TokenValidationError::TokenExpired => Status::unauthenticated("Token expired"),
+        TokenValidationError::AdminTokenNotAllowed => {
+            Status::permission_denied("Admin token cannot be used for this operation")
+        }
+        TokenValidationError::SudoNotAllowed => {
+            Status::permission_denied("Sudo requires an admin token")
+        }
+        TokenValidationError::SudoPrincipalNotFound => {
+            Status::not_found("Sudo principal not found")
+        }
+        TokenValidationError::InternalError => Status::internal("Internal server error"),
+    }
+}
+
+impl From<ApiError> for Status {
+    fn from(err: ApiError) -> Self {
+        let code = match err.status {
+            StatusCode::BAD_REQUEST => Code::InvalidArgument,
+            StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+            StatusCode::FORBIDDEN => Code::PermissionDenied,
+            StatusCode::NOT_FOUND => Code::NotFound,
+            StatusCode::CONFLICT => Code::FailedPrecondition,
+            StatusCode::PAYLOAD_TOO_LARGE | StatusCode::TOO_MANY_REQUESTS => {
+                Code::ResourceExhausted
+            }
+            StatusCode::NOT_IMPLEMENTED => Code::Unimplemented,
+            StatusCode::SERVICE_UNAVAILABLE => Code::Unavailable,
+            _ => Code::Internal,
+        };
+        Status::new(code, err.message)
+    }
+}
+
+impl From<GitError> for Status {
+    fn from(err: GitError) -> Self {
+        ApiError::from(err).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::content::git_ops::{CommitActionOp, CommitAuthor, apply_actions};
+    use crate::store::{SqliteStore, Store};
+    use crate::types::{Namespace, ObjectFormat, Visibility};
+
+    #[tokio::test]
+    async fn test_reads_mirror_the_content_api() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+        let now = chrono::Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        let repo = Repo {
+            id: "repo-1".to_string(),
+            namespace_id: "ns-1".to_string(),
+            name: "public".to_string(),
+            description: None,
+            visibility: Visibility::Public,
+            object_format: ObjectFormat::Sha1,
+            size_bytes: 0,
+            folder_id: None,
+            last_push_at: None,
+            created_at: now,
+            updated_at: now,
+        };
+        store.create_repo(&repo).unwrap();
+        let state = Arc::new(AppState::new(
+            Arc::new(store),
+            temp.path().to_path_buf(),
+            None,
+        ));
+
+        let git_repo = git2::Repository::init_bare(state.repo_path("ns-1", "public")).unwrap();
+        let author = CommitAuthor {
+            name: "Test".to_string(),
+            email: "test@example.com".to_string(),
+            author_time: None,
+            commit_time: None,
+        };
+        let mut tips = Vec::new();
+        for (message, content) in [("first", "1"), ("second", "2"), ("third", "3")] {
+            let action = CommitActionOp::Create {
+                path: format!("{message}.txt"),
+                content: content.as_bytes().to_vec(),
+            };
+            tips.push(apply_actions(&git_repo, "main", &[action], message, &author).unwrap());
+        }
+        drop(git_repo);
+
+        let service = ContentService { state };
+        let repo = service
+            .get_repo(Request::new(proto::GetRepoRequest {
+                repo_id: "repo-1".to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(repo.visibility, "public");
+
+        let refs = service
+            .list_refs(Request::new(proto::ListRefsRequest {
+                repo_id: "repo-1".to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .refs;
+        assert_eq!(refs[0].name, "main");
+        assert_eq!(refs[0].commit_sha, tips[2].to_string());
+
+        let commits: Vec<_> = service
+            .list_commits(Request::new(proto::ListCommitsRequest {
+                repo_id: "repo-1".to_string(),
+                r#ref: "main".to_string(),
+                since_sha: tips[0].to_string(),
+                limit: 0,
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .map(|commit| commit.unwrap().message)
+            .collect()
+            .await;
+        assert_eq!(commits, ["third", "second"]);
+
+        let chunks: Vec<_> = service
+            .get_blob(Request::new(proto::GetBlobRequest {
+                repo_id: "repo-1".to_string(),
+                r#ref: "main".to_string(),
+                path: "second.txt".to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(chunks[0].size, 1);
+        assert_eq!(chunks[0].data, "2");
+
+        let missing = service
+            .get_commit(Request::new(proto::GetCommitRequest {
+                repo_id: "repo-1".to_string(),
+                r#ref: "no-such-branch".to_string(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(missing.code(), Code::NotFound);
+    }
+}
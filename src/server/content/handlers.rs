@@ -1,60 +1,84 @@
-use std::io::Write;
+use std::collections::HashMap;
 use std::process::Stdio;
 use std::sync::Arc;
-use std::time::Duration;
 
+use tokio::io::AsyncReadExt;
 use tokio::process::Command;
 
+use async_compression::tokio::bufread::{GzipEncoder, ZstdEncoder};
 use axum::{
     Json,
+    body::Body,
     extract::{Path, Query, State},
     http::{HeaderMap, HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
 };
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD;
-use flate2::Compression;
-use flate2::write::GzEncoder;
+use futures_util::StreamExt;
 use git2::{ObjectType, Oid};
+use tokio_util::io::ReaderStream;
+use tracing::warn;
 
 use crate::server::AppState;
+use crate::server::limits::{apply_limits, deadline};
 use crate::server::response::{
     ApiError, ApiResponse, PaginatedResponse, StoreOptionExt, StoreResultExt,
 };
+use crate::server::signing::{Verification, verify_signature};
 
 use crate::auth::RequirePrincipal;
+use crate::config::Feature;
+use crate::hooks::RefUpdate;
+use crate::lfs::{LfsPointer, LfsStorageError};
+use crate::server::legal_hold::check_held_ref_updates;
+use crate::server::lfs::open_lfs_download;
 use crate::server::user::access::require_repo_permission;
 use crate::types::Permission;
 
 use super::auth::{OptionalAuth, check_content_access};
+use super::consistency::{CONSISTENCY_HEADER, record_write};
 use super::dto::{
-    ArchiveParams, BlameLineResponse, BlameResponse, CommitAction, CompareParams, CompareResponse,
-    CreateRefRequest, DEFAULT_PAGE_SIZE, DEFAULT_TREE_DEPTH, DeleteBlobRequest, DiffResponse,
-    EnhancedBlobParams, EnhancedBlobResponse, FileInfo, ListCommitsParams, MAX_BLOB_SIZE,
-    MAX_PAGE_SIZE, MAX_RAW_BLOB_SIZE, MAX_TREE_DEPTH, MultiCommitRequest, MutationResponse,
-    PathSearchParams, PathSearchResponse, PutBlobRequest, ReadmeParams, ReadmeResponse,
-    RefResponse, SetDefaultBranchRequest, TreeEntryResponse, TreeParams, UpdateRefRequest,
+    ArchiveParams, BlameHunkResponse, BlameParams, BlameResponse, CommitAction, CommitOptions,
+    CompareParams, CompareResponse,
+    CreateRefRequest, DEFAULT_PAGE_SIZE, DEFAULT_TREE_DEPTH, DEFAULT_TREE_PAGE_SIZE,
+    DeleteBlobRequest, DiffParams,
+    DiffResponse,
+    EnhancedBlobParams, EnhancedBlobResponse, FileInfo, LfsPointerResponse, ListCommitsParams,
+    MAX_BLAME_LINES,
+    MAX_PAGE_SIZE, MAX_TREE_CHILDREN, MAX_TREE_DEPTH, MAX_TREE_PAGE_SIZE, MultiCommitRequest,
+    MutationResponse,
+    PathSearchParams, PathSearchResponse, PutBlobRequest, RawPutParams, ReadmeParams,
+    ReadmeResponse,
+    RefResponse, SetDefaultBranchRequest, SignerResponse, SubmoduleResponse, SymlinkResponse,
+    TreeEntryResponse, TreeParams, UpdateRefRequest, VerificationResponse,
 };
+use super::etag::{self, etag, not_modified, with_etag};
 
-const ARCHIVE_TIMEOUT: Duration = Duration::from_secs(300); // 5 minutes
+/// Commit signatures checked at once when listing commits.
+const VERIFY_CONCURRENCY: usize = 8;
 use super::git_ops::{
-    CommitActionOp, GitError, apply_actions, build_diff, commit_to_response, compute_commit_stats,
-    count_ahead_behind, create_commit_on_branch, create_ref, delete_ref, entry_type_str,
-    file_exists, find_merge_base, get_blob_at_path, get_commit, get_default_branch,
-    get_file_history, get_tree, get_tree_at_path, is_binary, open_or_init_repo, open_repo,
-    resolve_ref, search_paths, set_default_branch, signature_to_response, tree_with_blob,
-    tree_without_entry, update_ref, verify_blob_sha,
+    BLOB_FILEMODES, CommitActionOp, CommitAuthor, GitError, append_trailers, apply_actions,
+    apply_actions_on_new_branch, build_diff, commit_signature, commit_to_response,
+    compute_commit_stats, count_ahead_behind, create_commit_on_branch, create_ref, delete_ref,
+    entry_type_str, file_exists, find_merge_base, get_blob_at_path, get_commit, get_default_branch,
+    get_file_history, get_tree, get_tree_at_path, is_binary, is_trailer_key, open_or_init_repo,
+    open_repo, read_gitmodules, resolve_ref, resolve_submodule_url, resolve_symlinks, search_paths,
+    set_default_branch, signature_to_response, tree_with_blob, tree_without_entry, update_ref,
+    verify_blob_sha,
 };
-
-fn repo_path(state: &AppState, namespace_id: &str, repo_name: &str) -> std::path::PathBuf {
-    state
-        .data_dir
-        .join("repos")
-        .join(namespace_id)
-        .join(format!("{repo_name}.git"))
+use super::raw::{RawBlob, content_type_for_extension, serve_raw_blob};
+use super::render::{Markup, render};
+
+pub(super) fn repo_path(
+    state: &AppState,
+    namespace_id: &str,
+    repo_name: &str,
+) -> std::path::PathBuf {
+    state.repo_path(namespace_id, repo_name)
 }
 
-async fn load_repo_and_check_access(
+pub(super) async fn load_repo_and_check_access(
     state: &Arc<AppState>,
     auth: &OptionalAuth,
     repo_id: &str,
@@ -68,7 +92,7 @@ async fn load_repo_and_check_access(
     check_content_access(state, auth, &repo)?;
 
     let path = repo_path(state, &repo.namespace_id, &repo.name);
-    let git_repo = open_repo(&path)?;
+    let git_repo = open_repo(&path, repo.object_format)?;
 
     Ok((repo, git_repo))
 }
@@ -77,10 +101,31 @@ pub async fn list_refs(
     auth: OptionalAuth,
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> Result<impl IntoResponse, ApiError> {
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
     let (_repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+    let refs = collect_refs(&git_repo)?;
+
+    let parts: Vec<String> = refs
+        .iter()
+        .map(|r| {
+            format!(
+                "{} {} {} {}",
+                r.ref_type, r.name, r.commit_sha, r.is_default
+            )
+        })
+        .collect();
+    let etag = etag(&parts.iter().map(String::as_str).collect::<Vec<_>>());
+    if etag::matches(&headers, &etag) {
+        return Ok(not_modified(&etag));
+    }
+    Ok(with_etag(Json(ApiResponse::success(refs)), &etag))
+}
 
-    let default_branch = get_default_branch(&git_repo);
+/// Branches and tags, the default branch first, then branches before tags,
+/// each by name.
+pub(super) fn collect_refs(git_repo: &git2::Repository) -> Result<Vec<RefResponse>, GitError> {
+    let default_branch = get_default_branch(git_repo);
     let mut refs = Vec::new();
 
     if let Ok(branches) = git_repo.branches(None) {
@@ -121,7 +166,7 @@ pub async fn list_refs(
     }
 
     if refs.is_empty() {
-        return Err(GitError::EmptyRepo.into());
+        return Err(GitError::EmptyRepo);
     }
 
     refs.sort_by(|a, b| match (a.is_default, b.is_default) {
@@ -134,7 +179,7 @@ pub async fn list_refs(
         },
     });
 
-    Ok(Json(ApiResponse::success(refs)))
+    Ok(refs)
 }
 
 pub async fn list_commits(
@@ -156,42 +201,44 @@ pub async fn list_commits(
         oid
     };
 
-    let mut revwalk = git_repo
-        .revwalk()
-        .map_err(|e| ApiError::internal(format!("Failed to create revwalk: {e}")))?;
-
-    revwalk
-        .push(start_oid)
-        .map_err(|e| ApiError::internal(format!("Failed to start revwalk: {e}")))?;
-
-    if params.cursor.is_some() {
-        revwalk.next();
-    }
-
     let path_filter = params.path.as_deref().map(|p| p.trim_start_matches('/'));
 
     let mut commits = Vec::new();
-    let mut count = 0;
+    let mut signed = Vec::new();
+    {
+        let mut revwalk = git_repo
+            .revwalk()
+            .map_err(|e| ApiError::internal(format!("Failed to create revwalk: {e}")))?;
 
-    for oid_result in revwalk {
-        if count > limit {
-            break;
+        revwalk
+            .push(start_oid)
+            .map_err(|e| ApiError::internal(format!("Failed to start revwalk: {e}")))?;
+
+        if params.cursor.is_some() {
+            revwalk.next();
         }
 
-        let commit_oid =
-            oid_result.map_err(|e| ApiError::internal(format!("Revwalk error: {e}")))?;
-        let commit = get_commit(&git_repo, commit_oid)?;
+        for oid_result in revwalk {
+            if commits.len() > limit {
+                break;
+            }
 
-        if let Some(filter_path) = path_filter {
-            if !commit_touches_path(&commit, filter_path) {
-                continue;
+            let commit_oid =
+                oid_result.map_err(|e| ApiError::internal(format!("Revwalk error: {e}")))?;
+            let commit = get_commit(&git_repo, commit_oid)?;
+
+            if let Some(filter_path) = path_filter {
+                if !commit_touches_path(&commit, filter_path) {
+                    continue;
+                }
             }
-        }
 
-        let stats = compute_commit_stats(&git_repo, &commit);
-        commits.push(commit_to_response(&commit, stats));
-        count += 1;
+            let stats = compute_commit_stats(&git_repo, &commit);
+            commits.push(commit_to_response(&commit, stats));
+            signed.push(commit_signature(&git_repo, commit_oid));
+        }
     }
+    drop(git_repo);
 
     let has_more = commits.len() > limit;
     let next_cursor = if has_more {
@@ -201,9 +248,52 @@ pub async fn list_commits(
         None
     };
 
+    signed.truncate(commits.len());
+    let verifications: Vec<_> = futures_util::stream::iter(signed)
+        .map(|signed| verify_commit(&state, signed))
+        .buffered(VERIFY_CONCURRENCY)
+        .collect()
+        .await;
+    for (commit, verification) in commits.iter_mut().zip(verifications) {
+        commit.verification = Some(verification);
+    }
+
     Ok(Json(PaginatedResponse::new(commits, next_cursor, has_more)))
 }
 
+/// Checks a commit's signature, if it has one, and looks up who signed it.
+async fn verify_commit(
+    state: &AppState,
+    signed: Option<(Vec<u8>, Vec<u8>)>,
+) -> VerificationResponse {
+    let verification = match signed {
+        Some((signature, payload)) => verify_signature(state, &signature, &payload).await,
+        None => Verification::unsigned(),
+    };
+    let verified = verification.verified();
+
+    let signer = verification.key.filter(|_| verified).map(|key| {
+        let principal = state.store.get_principal(&key.principal_id).ok().flatten();
+        let namespace = principal
+            .and_then(|p| state.store.get_namespace(&p.primary_namespace_id).ok())
+            .flatten()
+            .map(|ns| ns.name);
+        SignerResponse {
+            namespace,
+            key_id: key.id,
+            key_name: key.name,
+            kind: key.kind,
+            fingerprint: key.fingerprint,
+        }
+    });
+
+    VerificationResponse {
+        verified,
+        reason: verification.reason,
+        signer,
+    }
+}
+
 fn commit_touches_path(commit: &git2::Commit<'_>, path: &str) -> bool {
     let Ok(tree) = commit.tree() else {
         return false;
@@ -241,18 +331,23 @@ pub async fn get_commit_handler(
     let (_repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
 
     let oid = resolve_ref(&git_repo, &sha)?;
-    let commit = get_commit(&git_repo, oid)?;
-    let stats = compute_commit_stats(&git_repo, &commit);
+    let (mut response, signed) = {
+        let commit = get_commit(&git_repo, oid)?;
+        let stats = compute_commit_stats(&git_repo, &commit);
+        let signed = commit_signature(&git_repo, oid);
+        (commit_to_response(&commit, stats), signed)
+    };
+    drop(git_repo);
 
-    Ok(Json(ApiResponse::success(commit_to_response(
-        &commit, stats,
-    ))))
+    response.verification = Some(verify_commit(&state, signed).await);
+    Ok(Json(ApiResponse::success(response)))
 }
 
 pub async fn get_commit_diff(
     auth: OptionalAuth,
     State(state): State<Arc<AppState>>,
     Path((id, sha)): Path<(String, String)>,
+    Query(params): Query<DiffParams>,
 ) -> Result<impl IntoResponse, ApiError> {
     let (_repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
 
@@ -270,16 +365,34 @@ pub async fn get_commit_diff(
         (None, None)
     };
 
-    let (patch, stats) = build_diff(&git_repo, parent_tree.as_ref(), &head_tree)?;
+    let (patch, stats, files) = build_diff(
+        &git_repo,
+        parent_tree.as_ref(),
+        &head_tree,
+        &diff_paths(params.paths.as_deref()),
+        params.files_only.unwrap_or(false),
+    )?;
 
     Ok(Json(ApiResponse::success(DiffResponse {
         base_sha,
         head_sha: oid.to_string(),
         stats,
         patch,
+        files,
     })))
 }
 
+/// The paths in a comma-separated `?paths=` list.
+fn diff_paths(paths: Option<&str>) -> Vec<String> {
+    paths
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(String::from)
+        .collect()
+}
+
 pub async fn compare_refs(
     auth: OptionalAuth,
     State(state): State<Arc<AppState>>,
@@ -348,7 +461,13 @@ pub async fn compare_refs(
     let base_tree = get_tree(&git_repo, &base_commit)?;
     let head_tree = get_tree(&git_repo, &head_commit)?;
 
-    let (patch, stats) = build_diff(&git_repo, Some(&base_tree), &head_tree)?;
+    let (patch, stats, files) = build_diff(
+        &git_repo,
+        Some(&base_tree),
+        &head_tree,
+        &diff_paths(params.paths.as_deref()),
+        params.files_only.unwrap_or(false),
+    )?;
 
     Ok(Json(ApiResponse::success(CompareResponse {
         base_ref: base_ref.to_string(),
@@ -366,6 +485,7 @@ pub async fn compare_refs(
             head_sha: head_oid.to_string(),
             stats,
             patch,
+            files,
         },
     })))
 }
@@ -375,8 +495,9 @@ pub async fn get_tree_root(
     State(state): State<Arc<AppState>>,
     Path((id, ref_name)): Path<(String, String)>,
     Query(params): Query<TreeParams>,
+    headers: HeaderMap,
 ) -> Result<impl IntoResponse, ApiError> {
-    get_tree_impl(auth, state, id, ref_name, String::new(), params).await
+    get_tree_impl(auth, state, id, ref_name, String::new(), params, headers).await
 }
 
 pub async fn get_tree_handler(
@@ -384,8 +505,9 @@ pub async fn get_tree_handler(
     State(state): State<Arc<AppState>>,
     Path((id, ref_name, path)): Path<(String, String, String)>,
     Query(params): Query<TreeParams>,
+    headers: HeaderMap,
 ) -> Result<impl IntoResponse, ApiError> {
-    get_tree_impl(auth, state, id, ref_name, path, params).await
+    get_tree_impl(auth, state, id, ref_name, path, params, headers).await
 }
 
 async fn get_tree_impl(
@@ -395,8 +517,9 @@ async fn get_tree_impl(
     ref_name: String,
     path: String,
     params: TreeParams,
-) -> Result<impl IntoResponse, ApiError> {
-    let (_repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let (repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
 
     let depth = params
         .depth
@@ -416,21 +539,127 @@ async fn get_tree_impl(
         format!("{path}/")
     };
 
-    let mut entries = build_tree_entries(&git_repo, &tree, &base_path, depth);
-    sort_tree_entries(&mut entries);
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_TREE_PAGE_SIZE)
+        .clamp(1, MAX_TREE_PAGE_SIZE) as usize;
+
+    let sorted = sorted_tree_entries(&tree);
+    let start = match params.cursor.as_deref() {
+        Some(cursor) => {
+            sorted
+                .iter()
+                .position(|entry| entry.name() == Some(cursor))
+                .ok_or_else(|| ApiError::bad_request("Invalid cursor"))?
+                + 1
+        }
+        None => 0,
+    };
+    let end = sorted.len().min(start + limit);
+    let has_more = end < sorted.len();
+    let next_cursor = if has_more {
+        sorted[end - 1].name().map(str::to_string)
+    } else {
+        None
+    };
+
+    let submodules = submodule_links(&state, &headers, &repo, &git_repo, &root_tree)?;
+    let mut links: Vec<String> = submodules
+        .iter()
+        .map(|(path, sub)| format!("{path} {}", sub.url))
+        .collect();
+    links.sort();
+    let tree_sha = tree.id().to_string();
+    let depth_param = depth.to_string();
+    let limit_param = limit.to_string();
+    let mut parts = vec![
+        "tree",
+        tree_sha.as_str(),
+        path,
+        depth_param.as_str(),
+        limit_param.as_str(),
+        params.cursor.as_deref().unwrap_or(""),
+    ];
+    parts.extend(links.iter().map(String::as_str));
+    let etag = etag(&parts);
+    if etag::matches(&headers, &etag) {
+        return Ok(not_modified(&etag));
+    }
 
-    Ok(Json(ApiResponse::success(entries)))
+    let entries = build_tree_entries(
+        &git_repo,
+        &sorted[start..end],
+        &base_path,
+        depth,
+        &submodules,
+    );
+
+    Ok(with_etag(
+        Json(PaginatedResponse::new(entries, next_cursor, has_more)),
+        &etag,
+    ))
+}
+
+/// Submodules declared in `.gitmodules` at the tree's ref, keyed by path,
+/// with relative URLs resolved against this repository's clone URL.
+fn submodule_links(
+    state: &AppState,
+    headers: &HeaderMap,
+    repo: &crate::types::Repo,
+    git_repo: &git2::Repository,
+    root_tree: &git2::Tree<'_>,
+) -> Result<HashMap<String, SubmoduleResponse>, ApiError> {
+    let submodules = read_gitmodules(git_repo, root_tree);
+    if submodules.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let namespace = state
+        .store
+        .get_namespace(&repo.namespace_id)
+        .api_err("Failed to get namespace")?
+        .or_not_found("Namespace not found")?;
+    let git_base = format!("{}/git/", state.base_url(headers));
+    let clone_url = format!(
+        "{git_base}{}/{}.git",
+        urlencoding::encode(&namespace.name),
+        urlencoding::encode(&repo.name)
+    );
+
+    Ok(submodules
+        .into_iter()
+        .map(|(path, sub)| {
+            let url = resolve_submodule_url(&clone_url, &sub.url);
+            let repo_path = url
+                .strip_prefix(&git_base)
+                .map(|rest| rest.trim_end_matches('/').trim_end_matches(".git"))
+                .and_then(|rest| rest.split_once('/'))
+                .filter(|(_, name)| !name.is_empty() && !name.contains('/'))
+                .and_then(|(ns, name)| {
+                    let ns = urlencoding::decode(ns).ok()?;
+                    let name = urlencoding::decode(name).ok()?;
+                    Some(format!("{ns}/{name}"))
+                });
+            let link = SubmoduleResponse {
+                name: sub.name,
+                url,
+                repo_path,
+            };
+            (path, link)
+        })
+        .collect())
 }
 
 fn build_tree_entries(
     repo: &git2::Repository,
-    tree: &git2::Tree<'_>,
+    tree_entries: &[git2::TreeEntry<'_>],
     base_path: &str,
     depth: i32,
+    submodules: &HashMap<String, SubmoduleResponse>,
 ) -> Vec<TreeEntryResponse> {
     let mut entries = Vec::new();
 
-    for entry in tree.iter() {
+    for entry in tree_entries {
         let name = entry.name().unwrap_or("").to_string();
         let entry_path = if base_path.is_empty() {
             name.clone()
@@ -449,7 +678,9 @@ fn build_tree_entries(
             mode,
             size: None,
             has_children: None,
+            submodule: None,
             children: Vec::new(),
+            children_truncated: None,
         };
 
         match entry.kind() {
@@ -460,9 +691,17 @@ fn build_tree_entries(
             }
             Some(ObjectType::Tree) if depth > 1 => {
                 if let Ok(sub_tree) = repo.find_tree(entry.id()) {
-                    resp.has_children = Some(!sub_tree.is_empty());
-                    resp.children =
-                        build_tree_entries(repo, &sub_tree, &format!("{entry_path}/"), depth - 1);
+                    let children = sorted_tree_entries(&sub_tree);
+                    let shown = children.len().min(MAX_TREE_CHILDREN);
+                    resp.has_children = Some(!children.is_empty());
+                    resp.children_truncated = Some(shown < children.len());
+                    resp.children = build_tree_entries(
+                        repo,
+                        &children[..shown],
+                        &format!("{entry_path}/"),
+                        depth - 1,
+                        submodules,
+                    );
                 }
             }
             Some(ObjectType::Tree) => {
@@ -470,6 +709,9 @@ fn build_tree_entries(
                     resp.has_children = Some(!sub_tree.is_empty());
                 }
             }
+            Some(ObjectType::Commit) => {
+                resp.submodule = submodules.get(&entry_path).cloned();
+            }
             _ => {}
         }
 
@@ -479,82 +721,26 @@ fn build_tree_entries(
     entries
 }
 
-fn sort_tree_entries(entries: &mut [TreeEntryResponse]) {
-    entries.sort_by(
-        |a, b| match (a.entry_type.as_str(), b.entry_type.as_str()) {
-            ("dir", t) if t != "dir" => std::cmp::Ordering::Less,
-            (t, "dir") if t != "dir" => std::cmp::Ordering::Greater,
-            _ => a.name.cmp(&b.name),
-        },
-    );
-
-    for entry in entries.iter_mut() {
-        if !entry.children.is_empty() {
-            sort_tree_entries(&mut entry.children);
-        }
-    }
-}
-
-fn serve_raw_blob(blob: &git2::Blob<'_>, filename: &str) -> Result<Response, ApiError> {
-    let size = blob.size() as i64;
-    if size > MAX_RAW_BLOB_SIZE {
-        return Err(ApiError::payload_too_large(format!(
-            "File size ({} bytes) exceeds maximum allowed size ({} bytes)",
-            size, MAX_RAW_BLOB_SIZE
-        )));
-    }
-
-    let content_type = detect_content_type(filename, blob.content());
-    let content = blob.content().to_vec();
-
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        header::CONTENT_TYPE,
-        HeaderValue::from_str(&content_type)
-            .unwrap_or(HeaderValue::from_static("application/octet-stream")),
-    );
-    headers.insert(
-        header::CONTENT_LENGTH,
-        HeaderValue::from_str(&blob.size().to_string()).unwrap(),
-    );
-
-    Ok((StatusCode::OK, headers, content).into_response())
-}
-
-fn detect_content_type(filename: &str, content: &[u8]) -> String {
-    let ext = std::path::Path::new(filename)
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("")
-        .to_lowercase();
-
-    match ext.as_str() {
-        "go" | "rs" | "py" | "rb" | "java" | "c" | "cpp" | "h" | "hpp" | "sh" | "sql" => {
-            "text/plain; charset=utf-8".to_string()
-        }
-        "js" => "text/javascript; charset=utf-8".to_string(),
-        "ts" => "text/typescript; charset=utf-8".to_string(),
-        "md" => "text/markdown; charset=utf-8".to_string(),
-        "json" => "application/json".to_string(),
-        "yaml" | "yml" => "text/yaml; charset=utf-8".to_string(),
-        "xml" => "application/xml".to_string(),
-        "html" | "htm" => "text/html; charset=utf-8".to_string(),
-        "css" => "text/css; charset=utf-8".to_string(),
-        "txt" => "text/plain; charset=utf-8".to_string(),
-        _ => {
-            if is_binary(content) {
-                "application/octet-stream".to_string()
-            } else {
-                "text/plain; charset=utf-8".to_string()
-            }
-        }
-    }
+/// A tree's entries in listing order: directories first, then by name.
+fn sorted_tree_entries(tree: &git2::Tree<'_>) -> Vec<git2::TreeEntry<'static>> {
+    let mut entries: Vec<_> = tree.iter().map(|entry| entry.to_owned()).collect();
+    entries.sort_by(|a, b| {
+        let a_dir = a.kind() == Some(ObjectType::Tree);
+        let b_dir = b.kind() == Some(ObjectType::Tree);
+        b_dir
+            .cmp(&a_dir)
+            .then_with(|| a.name_bytes().cmp(b.name_bytes()))
+    });
+    entries
 }
 
+/// GET /repos/{id}/blame/{ref}/{path} - Blame a range of lines, grouped into
+/// hunks, at most `MAX_BLAME_LINES` at a time
 pub async fn get_blame(
     auth: OptionalAuth,
     State(state): State<Arc<AppState>>,
     Path((id, ref_name, path)): Path<(String, String, String)>,
+    Query(params): Query<BlameParams>,
 ) -> Result<impl IntoResponse, ApiError> {
     let (_repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
 
@@ -564,57 +750,110 @@ pub async fn get_blame(
     }
 
     let oid = resolve_ref(&git_repo, &ref_name)?;
-
-    let blame = git_repo
-        .blame_file(
-            std::path::Path::new(path),
-            Some(git2::BlameOptions::new().newest_commit(oid)),
-        )
-        .map_err(|_| GitError::PathNotFound(path.to_string()))?;
-
     let commit = get_commit(&git_repo, oid)?;
     let tree = get_tree(&git_repo, &commit)?;
     let blob = get_blob_at_path(&git_repo, &tree, path)?;
 
-    let content = blob.content();
-    let text = String::from_utf8_lossy(content);
+    let text = String::from_utf8_lossy(blob.content());
     let text_lines: Vec<&str> = text.lines().collect();
+    let total_lines = text_lines.len();
 
-    let mut lines = Vec::new();
-    for (i, line_text) in text_lines.iter().enumerate() {
-        let line_num = i + 1;
-
-        if let Some(hunk) = blame.get_line(line_num) {
-            lines.push(BlameLineResponse {
-                line: line_num,
-                sha: hunk.final_commit_id().to_string(),
-                author: signature_to_response(&hunk.final_signature()),
-                text: line_text.to_string(),
-            });
+    let start_line = params.start_line.unwrap_or(1);
+    if start_line == 0 {
+        return Err(ApiError::bad_request("start_line must be at least 1"));
+    }
+    if start_line > total_lines.max(1) {
+        return Err(ApiError::bad_request(format!(
+            "start_line is past the end of the file ({total_lines} lines)"
+        )));
+    }
+    let requested_end = params.end_line.unwrap_or(total_lines).min(total_lines);
+    if params.end_line.is_some_and(|end| end < start_line) {
+        return Err(ApiError::bad_request(
+            "end_line must not be before start_line",
+        ));
+    }
+    let end_line = requested_end.min(start_line + MAX_BLAME_LINES - 1);
+    let is_truncated = end_line < requested_end;
+
+    let mut hunks: Vec<BlameHunkResponse> = Vec::new();
+    if total_lines > 0 {
+        let blame = git_repo
+            .blame_file(
+                std::path::Path::new(path),
+                Some(
+                    git2::BlameOptions::new()
+                        .newest_commit(oid)
+                        .min_line(start_line)
+                        .max_line(end_line),
+                ),
+            )
+            .map_err(|_| GitError::PathNotFound(path.to_string()))?;
+
+        for hunk in blame.iter() {
+            let first = hunk.final_start_line().max(start_line);
+            let last = (hunk.final_start_line() + hunk.lines_in_hunk())
+                .saturating_sub(1)
+                .min(end_line);
+            if first > last {
+                continue;
+            }
+            let sha = hunk.final_commit_id().to_string();
+            let lines = text_lines[first - 1..last].iter().map(|l| l.to_string());
+            // libgit2 can split one commit's lines into several hunks, e.g.
+            // where they came from different original paths.
+            match hunks.last_mut() {
+                Some(prev) if prev.sha == sha && prev.start_line + prev.lines.len() == first => {
+                    prev.lines.extend(lines);
+                }
+                _ => hunks.push(BlameHunkResponse {
+                    sha,
+                    author: signature_to_response(&hunk.final_signature()),
+                    start_line: first,
+                    lines: lines.collect(),
+                }),
+            }
         }
     }
 
     Ok(Json(ApiResponse::success(BlameResponse {
         path: path.to_string(),
         ref_name: ref_name.clone(),
-        lines,
+        total_lines,
+        start_line,
+        end_line,
+        hunks,
+        is_truncated,
+        next_start_line: is_truncated.then_some(end_line + 1),
     })))
 }
 
+/// How an archive is compressed on its way to the client.
+#[derive(Clone, Copy)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// GET /repos/{id}/archive/{ref} - Stream a zip, tar.gz, or tar.zst archive
+/// of a ref, compressing git's output as it is produced
 pub async fn get_archive(
     auth: OptionalAuth,
     State(state): State<Arc<AppState>>,
     Path((id, ref_name)): Path<(String, String)>,
     Query(params): Query<ArchiveParams>,
 ) -> Result<Response, ApiError> {
+    state.require_feature(Feature::Archive)?;
     let (repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
 
     let oid = resolve_ref(&git_repo, &ref_name)?;
 
     let format = params.format.as_deref().unwrap_or("zip");
-    let (content_type, extension, git_format, use_gzip) = match format.to_lowercase().as_str() {
-        "zip" => ("application/zip", "zip", "zip", false),
-        "tar.gz" | "tgz" => ("application/gzip", "tar.gz", "tar", true),
+    let (content_type, extension, git_format, compression) = match format.to_lowercase().as_str() {
+        "zip" => ("application/zip", "zip", "zip", Compression::None),
+        "tar.gz" | "tgz" => ("application/gzip", "tar.gz", "tar", Compression::Gzip),
+        "tar.zst" | "tzst" => ("application/zstd", "tar.zst", "tar", Compression::Zstd),
         _ => return Err(ApiError::bad_request("Invalid archive format")),
     };
 
@@ -629,6 +868,7 @@ pub async fn get_archive(
         tree.get_path(std::path::Path::new(path))
             .map_err(|_| GitError::PathNotFound(path.to_string()))?;
     }
+    drop(git_repo);
 
     let repo_path = repo_path(&state, &repo.namespace_id, &repo.name);
     let clean_ref = ref_name.replace('/', "-");
@@ -646,33 +886,76 @@ pub async fn get_archive(
         args.push(path.trim_start_matches('/').to_string());
     }
 
-    let output = tokio::time::timeout(
-        ARCHIVE_TIMEOUT,
-        Command::new("git")
-            .args(&args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output(),
-    )
-    .await
-    .map_err(|_| ApiError::internal("git archive timed out"))?
-    .map_err(|e| ApiError::internal(format!("Failed to run git archive: {e}")))?;
+    let slot = state
+        .fair_slot(&repo.namespace_id, auth.traffic_class())
+        .await;
+    let limits = &state.limits.archive;
+    let mut cmd = Command::new("git");
+    cmd.args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    apply_limits(&mut cmd, limits);
+    let timeout = limits.timeout(state.content_limits.archive_timeout);
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| ApiError::internal(format!("Failed to run git archive: {e}")))?;
+    let (Some(mut stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err(ApiError::internal("Failed to capture git archive output"));
+    };
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(ApiError::internal(format!("git archive failed: {stderr}")));
+    // git fails before writing anything when it can't archive the tree, so
+    // wait for the first bytes to report that as an error rather than send
+    // an empty download.
+    let mut first = vec![0u8; 8192];
+    let read = deadline(timeout, stdout.read(&mut first))
+        .await
+        .ok_or_else(|| ApiError::internal("git archive timed out"))?
+        .map_err(|e| ApiError::internal(format!("Failed to read git archive output: {e}")))?;
+    if read == 0 {
+        let mut diagnostics = String::new();
+        let _ = stderr.read_to_string(&mut diagnostics).await;
+        let _ = child.wait().await;
+        return Err(ApiError::internal(format!(
+            "git archive failed: {}",
+            diagnostics.trim()
+        )));
     }
+    first.truncate(read);
+
+    // Reap git in the background, holding the fair-queue slot until it is
+    // done. The response has already started, so failures are only logged.
+    tokio::spawn(async move {
+        let mut diagnostics = String::new();
+        let run = async {
+            let _ = stderr.read_to_string(&mut diagnostics).await;
+            child.wait().await
+        };
+        let status = match deadline(timeout, run).await {
+            Some(status) => status,
+            None => {
+                warn!("git archive exceeded its time limit; killing it");
+                let _ = child.start_kill();
+                child.wait().await
+            }
+        };
+        match status {
+            Ok(status) if !status.success() => {
+                warn!("git archive exited with {status}: {}", diagnostics.trim());
+            }
+            Err(e) => warn!("Failed to wait for git archive: {e}"),
+            Ok(_) => {}
+        }
+        drop(slot);
+    });
 
-    let body = if use_gzip {
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-        encoder
-            .write_all(&output.stdout)
-            .map_err(|e| ApiError::internal(format!("Failed to gzip: {e}")))?;
-        encoder
-            .finish()
-            .map_err(|e| ApiError::internal(format!("Failed to finish gzip: {e}")))?
-    } else {
-        output.stdout
+    let output = tokio::io::BufReader::new(std::io::Cursor::new(first).chain(stdout));
+    let body = match compression {
+        Compression::None => Body::from_stream(ReaderStream::new(output)),
+        Compression::Gzip => Body::from_stream(ReaderStream::new(GzipEncoder::new(output))),
+        Compression::Zstd => Body::from_stream(ReaderStream::new(ZstdEncoder::new(output))),
     };
 
     let mut headers = HeaderMap::new();
@@ -700,7 +983,7 @@ pub async fn get_archive(
     Ok((StatusCode::OK, headers, body).into_response())
 }
 
-const README_FILENAMES: &[&str] = &[
+pub(super) const README_FILENAMES: &[&str] = &[
     "README.md",
     "readme.md",
     "README.MD",
@@ -718,7 +1001,8 @@ pub async fn get_readme(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
     Query(params): Query<ReadmeParams>,
-) -> Result<impl IntoResponse, ApiError> {
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
     let (_repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
 
     let ref_name = params.ref_name.as_deref().unwrap_or("");
@@ -741,13 +1025,27 @@ pub async fn get_readme(
 
     let entry = readme_file.ok_or_else(|| ApiError::not_found("No README found"))?;
 
+    // Rendered links point into the branch the README was read from.
+    let rendered = params.rendered_html.unwrap_or(false);
+    let branch = if rendered {
+        resolve_branch(&git_repo, ref_name)
+    } else {
+        String::new()
+    };
+    let sha = entry.id().to_string();
+    let etag = etag(&["readme", &sha, &readme_filename, &branch]);
+    if etag::matches(&headers, &etag) {
+        return Ok(not_modified(&etag));
+    }
+
     let blob = git_repo
         .find_blob(entry.id())
         .map_err(|e| ApiError::internal(format!("Failed to get blob: {e}")))?;
 
+    let limit = state.content_limits.max_inline_blob_bytes as i64;
     let size = blob.size() as i64;
-    let is_truncated = size > MAX_BLOB_SIZE;
-    let read_size = size.min(MAX_BLOB_SIZE) as usize;
+    let is_truncated = size > limit;
+    let read_size = size.min(limit) as usize;
 
     let content = &blob.content()[..read_size];
     let is_bin = is_binary(content);
@@ -758,22 +1056,36 @@ pub async fn get_readme(
         String::from_utf8_lossy(content).to_string()
     };
 
-    Ok(Json(ApiResponse::success(ReadmeResponse {
+    let rendered_html = match Markup::from_path(&readme_filename) {
+        Some(markup) if rendered && !is_bin => {
+            Some(render(markup, &content_str, &id, &branch, &readme_filename))
+        }
+        _ => None,
+    };
+
+    let response = Json(ApiResponse::success(ReadmeResponse {
         filename: readme_filename,
         content: content_str,
         size,
-        sha: blob.id().to_string(),
+        sha,
         is_binary: is_bin,
         is_truncated,
-    })))
+        rendered_html,
+    }));
+    Ok(with_etag(response, &etag))
 }
 
-async fn load_repo_with_write_access(
+/// Loads a repository for the content mutation endpoints, which need
+/// `repo:contents` rather than the `repo:write` a push needs, so the two
+/// can be granted or denied apart.
+pub(super) async fn load_repo_with_write_access(
     state: &Arc<AppState>,
     auth: &RequirePrincipal,
     repo_id: &str,
     init_if_missing: bool,
 ) -> Result<(crate::types::Repo, git2::Repository), ApiError> {
+    state.require_feature(Feature::ContentMutations)?;
+
     let repo = state
         .store
         .get_repo_by_id(repo_id)
@@ -781,17 +1093,38 @@ async fn load_repo_with_write_access(
         .or_not_found("Repository not found")?;
 
     require_repo_permission(
-        state.store.as_ref(),
+        state,
         &auth.principal,
         &repo,
-        Permission::REPO_WRITE,
+        Permission::REPO_CONTENTS,
     )?;
 
+    // Grant paths are checked on push; these endpoints can write anywhere.
+    if state
+        .store
+        .get_repo_grant(&auth.principal.id, &repo.id)
+        .api_err("Failed to get repo grant")?
+        .is_some_and(|grant| !grant.paths.is_empty())
+    {
+        return Err(ApiError::forbidden(
+            "Your access to this repository is limited to some paths; push changes with git",
+        ));
+    }
+
+    if state
+        .store
+        .get_repo_mirror(&repo.id)
+        .api_err("Failed to get mirror")?
+        .is_some()
+    {
+        return Err(ApiError::forbidden("Repository is a read-only pull mirror"));
+    }
+
     let path = repo_path(state, &repo.namespace_id, &repo.name);
     let git_repo = if init_if_missing {
-        open_or_init_repo(&path)?
+        open_or_init_repo(&path, repo.object_format)?
     } else {
-        open_repo(&path)?
+        open_repo(&path, repo.object_format)?
     };
 
     Ok((repo, git_repo))
@@ -804,7 +1137,17 @@ pub async fn create_ref_handler(
     Path(id): Path<String>,
     Json(req): Json<CreateRefRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let (_repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
+    let (repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
+    if req.force {
+        check_ref_hold(
+            &state,
+            &repo,
+            &git_repo,
+            &req.ref_type,
+            &req.name,
+            Some(&req.target_sha),
+        )?;
+    }
 
     let oid = create_ref(
         &git_repo,
@@ -818,6 +1161,7 @@ pub async fn create_ref_handler(
 
     Ok((
         StatusCode::CREATED,
+        [(CONSISTENCY_HEADER, record_write(&state, &repo.id))],
         Json(ApiResponse::success(RefResponse {
             name: req.name,
             ref_type: req.ref_type,
@@ -842,7 +1186,15 @@ pub async fn update_ref_handler(
     Path(path): Path<RefPath>,
     Json(req): Json<UpdateRefRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let (_repo, git_repo) = load_repo_with_write_access(&state, &auth, &path.id, false).await?;
+    let (repo, git_repo) = load_repo_with_write_access(&state, &auth, &path.id, false).await?;
+    check_ref_hold(
+        &state,
+        &repo,
+        &git_repo,
+        &path.ref_type,
+        &path.name,
+        Some(&req.target_sha),
+    )?;
 
     let oid = update_ref(
         &git_repo,
@@ -854,12 +1206,15 @@ pub async fn update_ref_handler(
 
     let is_default = get_default_branch(&git_repo).as_deref() == Some(&path.name);
 
-    Ok(Json(ApiResponse::success(RefResponse {
-        name: path.name,
-        ref_type: path.ref_type,
-        commit_sha: oid.to_string(),
-        is_default,
-    })))
+    Ok((
+        [(CONSISTENCY_HEADER, record_write(&state, &repo.id))],
+        Json(ApiResponse::success(RefResponse {
+            name: path.name,
+            ref_type: path.ref_type,
+            commit_sha: oid.to_string(),
+            is_default,
+        })),
+    ))
 }
 
 /// DELETE /repos/{id}/refs/{type}/{name} - Delete a reference
@@ -868,11 +1223,39 @@ pub async fn delete_ref_handler(
     State(state): State<Arc<AppState>>,
     Path(path): Path<RefPath>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let (_repo, git_repo) = load_repo_with_write_access(&state, &auth, &path.id, false).await?;
+    let (repo, git_repo) = load_repo_with_write_access(&state, &auth, &path.id, false).await?;
+    check_ref_hold(&state, &repo, &git_repo, &path.ref_type, &path.name, None)?;
 
     delete_ref(&git_repo, &path.ref_type, &path.name)?;
 
-    Ok(StatusCode::NO_CONTENT)
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(CONSISTENCY_HEADER, record_write(&state, &repo.id))],
+    ))
+}
+
+/// Checks moving an existing branch or tag to `target`, or deleting it when
+/// there's no target, against the repo's legal hold. New refs and bad ref
+/// types are left for the ref operation to handle.
+fn check_ref_hold(
+    state: &AppState,
+    repo: &crate::types::Repo,
+    git_repo: &git2::Repository,
+    ref_type: &str,
+    name: &str,
+    target: Option<&str>,
+) -> Result<(), ApiError> {
+    let refname = match ref_type {
+        "branch" => format!("refs/heads/{name}"),
+        "tag" => format!("refs/tags/{name}"),
+        _ => return Ok(()),
+    };
+    let Ok(old) = git_repo.refname_to_id(&refname) else {
+        return Ok(());
+    };
+    let old = old.to_string();
+    let new = target.map_or_else(|| "0".repeat(old.len()), str::to_string);
+    check_held_ref_updates(state, repo, git_repo, &[RefUpdate { old, new, refname }])
 }
 
 /// PUT /repos/{id}/default-branch - Set the default branch
@@ -882,7 +1265,7 @@ pub async fn set_default_branch_handler(
     Path(id): Path<String>,
     Json(req): Json<SetDefaultBranchRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let (_repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
+    let (repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
 
     set_default_branch(&git_repo, &req.branch)?;
 
@@ -896,12 +1279,15 @@ pub async fn set_default_branch_handler(
         .map(|oid| oid.to_string())
         .unwrap_or_default();
 
-    Ok(Json(ApiResponse::success(RefResponse {
-        name: req.branch,
-        ref_type: "branch".to_string(),
-        commit_sha,
-        is_default: true,
-    })))
+    Ok((
+        [(CONSISTENCY_HEADER, record_write(&state, &repo.id))],
+        Json(ApiResponse::success(RefResponse {
+            name: req.branch,
+            ref_type: "branch".to_string(),
+            commit_sha,
+            is_default: true,
+        })),
+    ))
 }
 
 // ============================================================================
@@ -920,7 +1306,15 @@ fn decode_content(content: &str, encoding: Option<&str>) -> Result<Vec<u8>, ApiE
     }
 }
 
-fn get_commit_author(state: &AppState, principal: &crate::types::Principal) -> (String, String) {
+/// Holds a file added through the API to the push size limits.
+fn check_content_size(state: &AppState, path: &str, content: &[u8]) -> Result<(), ApiError> {
+    match state.push_limits.check_blob(path, content.len() as u64) {
+        Some(too_large) => Err(ApiError::payload_too_large(too_large.to_string())),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn get_commit_author(state: &AppState, principal: &crate::types::Principal) -> (String, String) {
     let name = state
         .store
         .get_namespace(&principal.primary_namespace_id)
@@ -932,7 +1326,96 @@ fn get_commit_author(state: &AppState, principal: &crate::types::Principal) -> (
     (name, email)
 }
 
-fn resolve_branch(git_repo: &git2::Repository, ref_name: &str) -> String {
+/// The message and author for a commit made through the API, with the
+/// request's trailers and dates applied.
+pub(super) fn commit_details(
+    state: &AppState,
+    principal: &crate::types::Principal,
+    message: &str,
+    options: &CommitOptions,
+) -> Result<(String, CommitAuthor), ApiError> {
+    let (name, email) = get_commit_author(state, principal);
+
+    let mut trailers = Vec::new();
+    for trailer in &options.trailers {
+        let key = trailer.key.trim();
+        if !is_trailer_key(key) {
+            return Err(ApiError::bad_request(format!(
+                "Invalid trailer key: {key:?}; use letters, digits, and dashes"
+            )));
+        }
+        if trailer.value.trim().is_empty() || trailer.value.contains('\n') {
+            return Err(ApiError::bad_request(format!(
+                "Trailer {key} needs a value on a single line"
+            )));
+        }
+        trailers.push((key.to_string(), trailer.value.trim().to_string()));
+    }
+    for co_author in &options.co_authors {
+        if !is_identity(co_author.trim()) {
+            return Err(ApiError::bad_request(format!(
+                "Invalid co-author {co_author:?}; use \"Name <email>\""
+            )));
+        }
+        trailers.push(("Co-authored-by".to_string(), co_author.trim().to_string()));
+    }
+    if options.signed_off_by {
+        trailers.push(("Signed-off-by".to_string(), format!("{name} <{email}>")));
+    }
+
+    let git_time = |date: &chrono::DateTime<chrono::FixedOffset>| {
+        git2::Time::new(date.timestamp(), date.offset().local_minus_utc() / 60)
+    };
+    let author = CommitAuthor {
+        author_time: options.author_date.as_ref().map(git_time),
+        commit_time: options.committer_date.as_ref().map(git_time),
+        name,
+        email,
+    };
+    Ok((append_trailers(message, &trailers), author))
+}
+
+/// Holds a commit made through the API to the repository's commit policy,
+/// listing every rule its message breaks.
+pub(super) fn check_commit_policy(
+    state: &AppState,
+    repo: &crate::types::Repo,
+    message: &str,
+) -> Result<(), ApiError> {
+    let Some(policy) = state
+        .store
+        .get_repo_commit_policy(&repo.id)
+        .api_err("Failed to get commit policy")?
+    else {
+        return Ok(());
+    };
+    let violations = policy.check(message);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(ApiError::bad_request(format!(
+        "Commit message breaks the repository's policy: {}",
+        violations.join("; ")
+    ))
+    .with_data(serde_json::json!({ "violations": violations })))
+}
+
+/// Whether `value` reads `Name <email>`, as identities in trailers do.
+pub(super) fn is_identity(value: &str) -> bool {
+    let Some((name, email)) = value
+        .strip_suffix('>')
+        .and_then(|rest| rest.split_once(" <"))
+    else {
+        return false;
+    };
+    !name.trim().is_empty()
+        && email.contains('@')
+        && !value.contains('\n')
+        && !name.contains(['<', '>'])
+        && !email.contains(['<', '>', ' '])
+}
+
+pub(super) fn resolve_branch(git_repo: &git2::Repository, ref_name: &str) -> String {
     if ref_name.is_empty() {
         get_default_branch(git_repo).unwrap_or_else(|| "main".to_string())
     } else {
@@ -955,7 +1438,6 @@ fn check_create_or_update(
     Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
 fn commit_blob_change(
     git_repo: &git2::Repository,
     tree: &git2::Tree<'_>,
@@ -963,37 +1445,33 @@ fn commit_blob_change(
     path: &str,
     content: &[u8],
     message: &str,
-    author_name: &str,
-    author_email: &str,
+    author: &CommitAuthor,
 ) -> Result<(Oid, FileInfo), ApiError> {
     let new_tree_oid = tree_with_blob(git_repo, Some(tree), path, content)?;
 
-    let commit_oid = create_commit_on_branch(
-        git_repo,
-        branch,
-        new_tree_oid,
-        message,
-        author_name,
-        author_email,
-    )?;
+    let commit_oid = create_commit_on_branch(git_repo, branch, new_tree_oid, message, author)?;
 
     let new_tree = git_repo
         .find_tree(new_tree_oid)
         .map_err(|e| ApiError::internal(format!("Failed to find new tree: {e}")))?;
     let new_blob = get_blob_at_path(git_repo, &new_tree, path)?;
+    let mode = new_tree
+        .get_path(std::path::Path::new(path))
+        .map(|entry| entry.filemode())
+        .unwrap_or(0o100644);
 
     let file_info = FileInfo {
         path: path.to_string(),
         sha: new_blob.id().to_string(),
         size: new_blob.size() as i64,
+        mode: format!("{mode:06o}"),
     };
 
     Ok((commit_oid, file_info))
 }
 
-const MAX_UPLOAD_SIZE: usize = 100 * 1024 * 1024;
-
 async fn parse_multipart_upload(
+    state: &AppState,
     multipart: &mut axum::extract::Multipart,
     path: &str,
 ) -> Result<(Vec<u8>, String, Option<String>), ApiError> {
@@ -1008,17 +1486,25 @@ async fn parse_multipart_upload(
     {
         match field.name() {
             Some("file") => {
-                let data = field
-                    .bytes()
+                // Read in chunks so an oversized file is refused as soon as
+                // it passes the limit, not after it has been buffered.
+                let mut field = field;
+                let mut data = Vec::new();
+                while let Some(chunk) = field
+                    .chunk()
                     .await
-                    .map_err(|e| ApiError::bad_request(format!("Failed to read file: {e}")))?;
-                if data.len() > MAX_UPLOAD_SIZE {
-                    return Err(ApiError::payload_too_large(format!(
-                        "File size ({} bytes) exceeds maximum allowed size ({MAX_UPLOAD_SIZE} bytes)",
-                        data.len()
-                    )));
+                    .map_err(|e| ApiError::bad_request(format!("Failed to read file: {e}")))?
+                {
+                    data.extend_from_slice(&chunk);
+                    let limit = state.content_limits.max_upload_bytes;
+                    if data.len() as u64 > limit {
+                        return Err(ApiError::payload_too_large(format!(
+                            "File size exceeds maximum allowed size ({limit} bytes)"
+                        )));
+                    }
+                    check_content_size(state, path, &data)?;
                 }
-                content = Some(data.to_vec());
+                content = Some(data);
             }
             Some("message") => {
                 message =
@@ -1044,6 +1530,46 @@ async fn parse_multipart_upload(
     Ok((content, message, sha))
 }
 
+/// Commit message for a raw upload when it isn't given as a query param.
+pub(super) const COMMIT_MESSAGE_HEADER: &str = "X-Commit-Message";
+
+/// Read a raw upload body, refusing it as soon as it passes the limits
+/// rather than after it has been buffered.
+async fn read_raw_upload(
+    state: &AppState,
+    headers: &HeaderMap,
+    body: Body,
+    path: &str,
+) -> Result<Vec<u8>, ApiError> {
+    let limit = state.content_limits.max_upload_bytes;
+    let too_large = || {
+        ApiError::payload_too_large(format!(
+            "File size exceeds maximum allowed size ({limit} bytes)"
+        ))
+    };
+
+    let declared = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > limit) {
+        return Err(too_large());
+    }
+
+    let mut data = Vec::with_capacity(declared.unwrap_or(0) as usize);
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk =
+            chunk.map_err(|e| ApiError::bad_request(format!("Failed to read body: {e}")))?;
+        data.extend_from_slice(&chunk);
+        if data.len() as u64 > limit {
+            return Err(too_large());
+        }
+        check_content_size(state, path, &data)?;
+    }
+    Ok(data)
+}
+
 /// PUT /repos/{id}/blob/{ref}/{*path} - Create or update a file
 pub async fn put_blob(
     auth: RequirePrincipal,
@@ -1051,7 +1577,7 @@ pub async fn put_blob(
     Path((id, ref_name, path)): Path<(String, String, String)>,
     Json(req): Json<PutBlobRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let (_repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
+    let (repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
 
     let path = path.trim_start_matches('/');
     if path.is_empty() {
@@ -1060,6 +1586,7 @@ pub async fn put_blob(
 
     let branch = resolve_branch(&git_repo, &ref_name);
     let content = decode_content(&req.content, req.encoding.as_deref())?;
+    check_content_size(&state, path, &content)?;
 
     let oid = resolve_ref(&git_repo, &branch)?;
     let commit = get_commit(&git_repo, oid)?;
@@ -1067,23 +1594,20 @@ pub async fn put_blob(
 
     check_create_or_update(&tree, path, req.sha.as_deref())?;
 
-    let (author_name, author_email) = get_commit_author(&state, &auth.principal);
+    let (message, author) = commit_details(&state, &auth.principal, &req.message, &req.commit)?;
+    check_commit_policy(&state, &repo, &message)?;
     let (commit_oid, file_info) = commit_blob_change(
-        &git_repo,
-        &tree,
-        &branch,
-        path,
-        &content,
-        &req.message,
-        &author_name,
-        &author_email,
+        &git_repo, &tree, &branch, path, &content, &message, &author,
     )?;
 
+    let token = record_write(&state, &repo.id);
     Ok((
         StatusCode::CREATED,
+        [(CONSISTENCY_HEADER, token.clone())],
         Json(ApiResponse::success(MutationResponse {
             commit_sha: commit_oid.to_string(),
             ref_name: branch,
+            consistency_token: token,
             file: Some(file_info),
         })),
     ))
@@ -1096,7 +1620,7 @@ pub async fn delete_blob(
     Path((id, ref_name, path)): Path<(String, String, String)>,
     Json(req): Json<DeleteBlobRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let (_repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
+    let (repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
 
     let path = path.trim_start_matches('/');
     if path.is_empty() {
@@ -1112,21 +1636,20 @@ pub async fn delete_blob(
     verify_blob_sha(&tree, path, &req.sha)?;
     let new_tree_oid = tree_without_entry(&git_repo, &tree, path)?;
 
-    let (author_name, author_email) = get_commit_author(&state, &auth.principal);
-    let commit_oid = create_commit_on_branch(
-        &git_repo,
-        &branch,
-        new_tree_oid,
-        &req.message,
-        &author_name,
-        &author_email,
-    )?;
+    let (message, author) = commit_details(&state, &auth.principal, &req.message, &req.commit)?;
+    check_commit_policy(&state, &repo, &message)?;
+    let commit_oid = create_commit_on_branch(&git_repo, &branch, new_tree_oid, &message, &author)?;
 
-    Ok(Json(ApiResponse::success(MutationResponse {
-        commit_sha: commit_oid.to_string(),
-        ref_name: branch,
-        file: None,
-    })))
+    let token = record_write(&state, &repo.id);
+    Ok((
+        [(CONSISTENCY_HEADER, token.clone())],
+        Json(ApiResponse::success(MutationResponse {
+            commit_sha: commit_oid.to_string(),
+            ref_name: branch,
+            consistency_token: token,
+            file: None,
+        })),
+    ))
 }
 
 /// POST /repos/{id}/commits - Multi-file atomic commit
@@ -1136,9 +1659,25 @@ pub async fn create_multi_commit(
     Path(id): Path<String>,
     Json(req): Json<MultiCommitRequest>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let (_repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, true).await?;
+    let (repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, true).await?;
 
     let branch = resolve_branch(&git_repo, req.branch.as_deref().unwrap_or(""));
+    let base = match req.base_ref.as_deref() {
+        Some(base_ref) => {
+            if req.branch.as_deref().unwrap_or("").is_empty() {
+                return Err(ApiError::bad_request(
+                    "base_ref requires a branch to create",
+                ));
+            }
+            if !git2::Reference::is_valid_name(&format!("refs/heads/{branch}")) {
+                return Err(ApiError::bad_request(format!(
+                    "Invalid branch name: {branch}"
+                )));
+            }
+            Some(resolve_ref(&git_repo, base_ref)?)
+        }
+        None => None,
+    };
 
     if req.actions.is_empty() {
         return Err(ApiError::bad_request("At least one action is required"));
@@ -1149,22 +1688,36 @@ pub async fn create_multi_commit(
         .iter()
         .map(action_to_op)
         .collect::<Result<_, _>>()?;
+    let mut total = 0u64;
+    for action in &actions {
+        if let CommitActionOp::Create { path, content }
+        | CommitActionOp::Update { path, content, .. } = action
+        {
+            check_content_size(&state, path, content)?;
+            total += content.len() as u64;
+        }
+    }
+    if let Some(too_large) = state.push_limits.check_pack(total) {
+        return Err(ApiError::payload_too_large(too_large.to_string()));
+    }
 
-    let (author_name, author_email) = get_commit_author(&state, &auth.principal);
-    let commit_oid = apply_actions(
-        &git_repo,
-        &branch,
-        &actions,
-        &req.message,
-        &author_name,
-        &author_email,
-    )?;
+    let (message, author) = commit_details(&state, &auth.principal, &req.message, &req.commit)?;
+    check_commit_policy(&state, &repo, &message)?;
+    let commit_oid = match base {
+        Some(base) => {
+            apply_actions_on_new_branch(&git_repo, &branch, base, &actions, &message, &author)?
+        }
+        None => apply_actions(&git_repo, &branch, &actions, &message, &author)?,
+    };
 
+    let token = record_write(&state, &repo.id);
     Ok((
         StatusCode::CREATED,
+        [(CONSISTENCY_HEADER, token.clone())],
         Json(ApiResponse::success(MutationResponse {
             commit_sha: commit_oid.to_string(),
             ref_name: branch,
+            consistency_token: token,
             file: None,
         })),
     ))
@@ -1205,7 +1758,49 @@ fn action_to_op(action: &CommitAction) -> Result<CommitActionOp, ApiError> {
             to: to.clone(),
             sha: sha.clone(),
         }),
+        CommitAction::Chmod { path, mode, sha } => {
+            let filemode = i32::from_str_radix(mode, 8)
+                .ok()
+                .filter(|m| BLOB_FILEMODES.contains(m))
+                .ok_or_else(|| {
+                    ApiError::bad_request(format!(
+                        "Unsupported mode {mode}: use 100644, 100755, or 120000"
+                    ))
+                })?;
+            Ok(CommitActionOp::Chmod {
+                path: path.clone(),
+                mode: filemode,
+                sha: sha.clone(),
+            })
+        }
+        CommitAction::DeleteDir { path, sha } => Ok(CommitActionOp::DeleteDir {
+            path: dir_path(path)?,
+            sha: sha.clone(),
+        }),
+        CommitAction::MoveDir { from, to, sha } => {
+            let (from, to) = (dir_path(from)?, dir_path(to)?);
+            if to == from || to.starts_with(&format!("{from}/")) {
+                return Err(ApiError::bad_request(format!(
+                    "Can't move {from} into itself"
+                )));
+            }
+            Ok(CommitActionOp::MoveDir {
+                from,
+                to,
+                sha: sha.clone(),
+            })
+        }
+    }
+}
+
+/// A directory path from a commit action, without leading or trailing
+/// slashes. The repository root can't be deleted or moved.
+fn dir_path(path: &str) -> Result<String, ApiError> {
+    let path = path.trim_matches('/');
+    if path.is_empty() {
+        return Err(ApiError::bad_request("Directory path is required"));
     }
+    Ok(path.to_string())
 }
 
 /// POST /repos/{id}/upload/{ref}/{*path} - Binary file upload
@@ -1215,7 +1810,7 @@ pub async fn upload_blob(
     Path((id, ref_name, path)): Path<(String, String, String)>,
     mut multipart: axum::extract::Multipart,
 ) -> Result<impl IntoResponse, ApiError> {
-    let (_repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
+    let (repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
 
     let path = path.trim_start_matches('/');
     if path.is_empty() {
@@ -1224,7 +1819,7 @@ pub async fn upload_blob(
 
     let branch = resolve_branch(&git_repo, &ref_name);
 
-    let (content, message, sha) = parse_multipart_upload(&mut multipart, path).await?;
+    let (content, message, sha) = parse_multipart_upload(&state, &mut multipart, path).await?;
 
     let oid = resolve_ref(&git_repo, &branch)?;
     let commit = get_commit(&git_repo, oid)?;
@@ -1232,23 +1827,81 @@ pub async fn upload_blob(
 
     check_create_or_update(&tree, path, sha.as_deref())?;
 
-    let (author_name, author_email) = get_commit_author(&state, &auth.principal);
+    let (message, author) =
+        commit_details(&state, &auth.principal, &message, &CommitOptions::default())?;
+    check_commit_policy(&state, &repo, &message)?;
     let (commit_oid, file_info) = commit_blob_change(
-        &git_repo,
-        &tree,
-        &branch,
-        path,
-        &content,
-        &message,
-        &author_name,
-        &author_email,
+        &git_repo, &tree, &branch, path, &content, &message, &author,
     )?;
 
+    let token = record_write(&state, &repo.id);
     Ok((
         StatusCode::CREATED,
+        [(CONSISTENCY_HEADER, token.clone())],
         Json(ApiResponse::success(MutationResponse {
             commit_sha: commit_oid.to_string(),
             ref_name: branch,
+            consistency_token: token,
+            file: Some(file_info),
+        })),
+    ))
+}
+
+/// PUT /repos/{id}/raw/{ref}/{*path} - Create or update a file with the
+/// request body as its content, for binaries without base64 or multipart
+pub async fn put_raw_blob(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path((id, ref_name, path)): Path<(String, String, String)>,
+    Query(params): Query<RawPutParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<impl IntoResponse, ApiError> {
+    let (repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
+
+    let path = path.trim_start_matches('/');
+    if path.is_empty() {
+        return Err(ApiError::bad_request("Path is required"));
+    }
+
+    let branch = resolve_branch(&git_repo, &ref_name);
+
+    let content = read_raw_upload(&state, &headers, body, path).await?;
+
+    let oid = resolve_ref(&git_repo, &branch)?;
+    let commit = get_commit(&git_repo, oid)?;
+    let tree = get_tree(&git_repo, &commit)?;
+
+    // Query params win; headers let clients keep the URL to the path alone.
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().trim_matches('"').to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let sha = params.sha.or_else(|| header(header::IF_MATCH.as_str()));
+    check_create_or_update(&tree, path, sha.as_deref())?;
+
+    let message = params
+        .message
+        .or_else(|| header(COMMIT_MESSAGE_HEADER))
+        .filter(|m| !m.is_empty())
+        .unwrap_or_else(|| format!("Upload {path}"));
+    let (message, author) =
+        commit_details(&state, &auth.principal, &message, &CommitOptions::default())?;
+    check_commit_policy(&state, &repo, &message)?;
+    let (commit_oid, file_info) =
+        commit_blob_change(&git_repo, &tree, &branch, path, &content, &message, &author)?;
+
+    let token = record_write(&state, &repo.id);
+    Ok((
+        StatusCode::CREATED,
+        [(CONSISTENCY_HEADER, token.clone())],
+        Json(ApiResponse::success(MutationResponse {
+            commit_sha: commit_oid.to_string(),
+            ref_name: branch,
+            consistency_token: token,
             file: Some(file_info),
         })),
     ))
@@ -1260,8 +1913,9 @@ pub async fn get_blob_enhanced(
     State(state): State<Arc<AppState>>,
     Path((id, ref_name, path)): Path<(String, String, String)>,
     Query(params): Query<EnhancedBlobParams>,
+    headers: HeaderMap,
 ) -> Result<Response, ApiError> {
-    let (_repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+    let (repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
 
     let path = path.trim_start_matches('/');
     if path.is_empty() {
@@ -1269,18 +1923,96 @@ pub async fn get_blob_enhanced(
     }
 
     let ref_to_use = params.at.as_deref().unwrap_or(&ref_name);
+    let (symlink, content_path) = read_symlink(
+        &git_repo,
+        ref_to_use,
+        path,
+        params.follow_symlinks.unwrap_or(false),
+    )?;
+    let entry_type = if symlink.is_some() { "symlink" } else { "file" };
+    let path = content_path.as_str();
+
+    let resolve_lfs = params.resolve_lfs.unwrap_or(false);
+    if resolve_lfs && params.raw.unwrap_or(false) {
+        if let Some(pointer) = read_lfs_pointer(&git_repo, ref_to_use, path)? {
+            return serve_lfs_object(&state, &repo, &pointer, path).await;
+        }
+    }
+
+    if params.raw.unwrap_or(false) {
+        let blob = RawBlob::find(&git_repo, ref_to_use, path)?;
+        return serve_raw_blob(&state, &headers, blob, path).await;
+    }
+
     let oid = resolve_ref(&git_repo, ref_to_use)?;
     let commit = get_commit(&git_repo, oid)?;
     let tree = get_tree(&git_repo, &commit)?;
     let blob = get_blob_at_path(&git_repo, &tree, path)?;
 
-    if params.raw.unwrap_or(false) {
-        return serve_raw_blob(&blob, path);
+    let pointer = if resolve_lfs {
+        LfsPointer::parse(blob.content())
+    } else {
+        None
+    };
+    let lfs = match pointer {
+        Some(pointer) => {
+            let stored = state
+                .store
+                .get_lfs_object(&repo.id, &pointer.oid)
+                .api_err("Failed to get LFS object")?
+                .is_some();
+            Some(LfsPointerResponse {
+                oid: pointer.oid,
+                size: pointer.size,
+                stored,
+            })
+        }
+        None => None,
+    };
+
+    // History depends on the commit, everything else only on the blob.
+    let sha = blob.id().to_string();
+    let history_key = if params.history.unwrap_or(false) {
+        format!(
+            "{oid} {} {}",
+            params.cursor.as_deref().unwrap_or(""),
+            params.limit.unwrap_or(DEFAULT_PAGE_SIZE)
+        )
+    } else {
+        String::new()
+    };
+    let lfs_key = lfs
+        .as_ref()
+        .map_or("", |lfs| if lfs.stored { "stored" } else { "missing" });
+    let parsed_key = if params.parsed.unwrap_or(false) {
+        "parsed"
+    } else {
+        ""
+    };
+    let symlink_key = symlink.as_ref().map_or(String::new(), |link| {
+        format!(
+            "{} {} {}",
+            link.target,
+            link.resolved_path.as_deref().unwrap_or(""),
+            link.target_type.unwrap_or("")
+        )
+    });
+    let etag = etag(&[
+        "blob",
+        &sha,
+        parsed_key,
+        lfs_key,
+        &history_key,
+        &symlink_key,
+    ]);
+    if etag::matches(&headers, &etag) {
+        return Ok(not_modified(&etag));
     }
 
+    let limit = state.content_limits.max_inline_blob_bytes as i64;
     let size = blob.size() as i64;
-    let is_truncated = size > MAX_BLOB_SIZE;
-    let read_size = size.min(MAX_BLOB_SIZE) as usize;
+    let is_truncated = size > limit;
+    let read_size = size.min(limit) as usize;
 
     let content_bytes = &blob.content()[..read_size];
     let binary_content = is_binary(content_bytes);
@@ -1313,8 +2045,9 @@ pub async fn get_blob_enhanced(
         (None, None, None)
     };
 
-    Ok(Json(ApiResponse::success(EnhancedBlobResponse {
-        sha: blob.id().to_string(),
+    let response = Json(ApiResponse::success(EnhancedBlobResponse {
+        entry_type,
+        sha,
         size,
         content: Some(encoded_content),
         encoding,
@@ -1325,8 +2058,134 @@ pub async fn get_blob_enhanced(
         history,
         history_cursor,
         history_has_more,
-    }))
-    .into_response())
+        lfs,
+        symlink,
+    }));
+    Ok(with_etag(response, &etag))
+}
+
+/// Where the symlink at `path` points, or `None` when it isn't one, and
+/// the path to read content from: `path` itself, or with `follow` the file
+/// its links lead to.
+fn read_symlink(
+    git_repo: &git2::Repository,
+    ref_name: &str,
+    path: &str,
+    follow: bool,
+) -> Result<(Option<SymlinkResponse>, String), ApiError> {
+    let oid = resolve_ref(git_repo, ref_name)?;
+    let commit = get_commit(git_repo, oid)?;
+    let tree = get_tree(git_repo, &commit)?;
+    let Ok(entry) = tree.get_path(std::path::Path::new(path)) else {
+        // With `follow`, a path through a linked directory still reaches
+        // the file.
+        let resolved = if follow {
+            resolve_symlinks(git_repo, &tree, path)?
+        } else {
+            None
+        };
+        return match resolved {
+            Some(resolved) if tree.get_path(std::path::Path::new(&resolved)).is_ok() => {
+                Ok((None, resolved))
+            }
+            _ => Err(GitError::PathNotFound(path.to_string()).into()),
+        };
+    };
+    if entry.filemode() != 0o120000 {
+        return Ok((None, path.to_string()));
+    }
+
+    let blob = get_blob_at_path(git_repo, &tree, path)?;
+    let target = String::from_utf8_lossy(blob.content()).into_owned();
+    let resolved_path = resolve_symlinks(git_repo, &tree, path)?;
+    let target_type = resolved_path
+        .as_deref()
+        .and_then(|resolved| tree.get_path(std::path::Path::new(resolved)).ok())
+        .map(|entry| entry_type_str(entry.kind(), entry.filemode()));
+
+    let content_path = if !follow {
+        path.to_string()
+    } else {
+        match (&resolved_path, target_type) {
+            (None, _) => {
+                return Err(ApiError::bad_request(
+                    "Symlink leads outside the repository or loops",
+                ));
+            }
+            (Some(resolved), None) => {
+                return Err(ApiError::not_found(format!(
+                    "Symlink target not found: {resolved}"
+                )));
+            }
+            (Some(resolved), Some("file")) => resolved.clone(),
+            (Some(_), Some("dir")) => {
+                return Err(ApiError::bad_request("Symlink points to a directory"));
+            }
+            (Some(_), Some(_)) => {
+                return Err(ApiError::bad_request("Symlink points to a submodule"));
+            }
+        }
+    };
+
+    let symlink = SymlinkResponse {
+        target,
+        resolved_path,
+        target_type,
+    };
+    Ok((Some(symlink), content_path))
+}
+
+/// The LFS pointer at `path`, if the file there is one. Kept apart from
+/// the download so no git2 handle is held across it.
+fn read_lfs_pointer(
+    git_repo: &git2::Repository,
+    ref_name: &str,
+    path: &str,
+) -> Result<Option<LfsPointer>, ApiError> {
+    let oid = resolve_ref(git_repo, ref_name)?;
+    let commit = get_commit(git_repo, oid)?;
+    let tree = get_tree(git_repo, &commit)?;
+    let blob = get_blob_at_path(git_repo, &tree, path)?;
+    Ok(LfsPointer::parse(blob.content()))
+}
+
+/// Streams the LFS object `pointer` refers to in place of the pointer file.
+async fn serve_lfs_object(
+    state: &AppState,
+    repo: &crate::types::Repo,
+    pointer: &LfsPointer,
+    filename: &str,
+) -> Result<Response, ApiError> {
+    let namespace = state
+        .store
+        .get_namespace(&repo.namespace_id)
+        .api_err("Failed to get namespace")?
+        .or_not_found("Namespace not found")?;
+
+    let (body, size) = match open_lfs_download(state, &namespace.name, repo, &pointer.oid).await
+    {
+        Ok(result) => result,
+        Err(LfsStorageError::NotFound) => {
+            return Err(ApiError::not_found("LFS object not found"));
+        }
+        Err(e) => {
+            tracing::warn!("LFS storage error: {e}");
+            return Err(ApiError::internal("LFS storage error"));
+        }
+    };
+
+    // The content isn't at hand to sniff, and LFS objects are rarely text.
+    let content_type = content_type_for_extension(filename).unwrap_or("application/octet-stream");
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_LENGTH, size.to_string()),
+        ],
+        body,
+    )
+        .into_response())
 }
 
 fn parse_frontmatter(content: &str) -> Option<(serde_json::Value, String)> {
@@ -1394,7 +2253,10 @@ pub async fn search_paths_handler(
     Path(id): Path<String>,
     Query(params): Query<PathSearchParams>,
 ) -> Result<impl IntoResponse, ApiError> {
-    let (_repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+    let (repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+    let _slot = state
+        .fair_slot(&repo.namespace_id, auth.traffic_class())
+        .await;
 
     let ref_name = params.ref_name.as_deref().unwrap_or("");
     let oid = resolve_ref(&git_repo, ref_name)?;
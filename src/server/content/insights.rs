@@ -0,0 +1,397 @@
+//! Repository health checklist.
+//!
+//! Every check looks at signals the server already has (the default branch
+//! tree, branch tips, and the push hook settings) so the endpoint stays
+//! cheap enough to back a health panel shown on every repo page.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use git2::{BranchType, ObjectType, Oid, Repository, Tree, TreeWalkMode, TreeWalkResult};
+
+use crate::hooks::PROTECT_DEFAULT_BRANCH;
+use crate::server::AppState;
+use crate::server::response::{ApiError, ApiResponse};
+use crate::units::format_size;
+
+use super::auth::OptionalAuth;
+use super::dto::{InsightCheck, InsightItem, InsightSeverity, InsightsResponse};
+use super::git_ops::{get_default_branch, is_binary, resolve_ref};
+use super::handlers::{README_FILENAMES, load_repo_and_check_access};
+
+/// Branches with no commits for this long are reported as stale.
+const STALE_BRANCH_DAYS: i64 = 90;
+/// Files above this size are flagged even when no push limit is set.
+const OVERSIZED_BLOB_BYTES: u64 = 50 * 1024 * 1024;
+/// Binary files above this size are suggested for LFS.
+const LFS_CANDIDATE_BYTES: u64 = 1024 * 1024;
+/// Most branches or files listed for a single check.
+const MAX_ITEMS: usize = 20;
+
+const LICENSE_PREFIXES: &[&str] = &["LICENSE", "LICENCE", "COPYING", "UNLICENSE"];
+
+pub async fn get_insights(
+    auth: OptionalAuth,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (_repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+
+    let default_branch = get_default_branch(&git_repo);
+    let tree = resolve_ref(&git_repo, "HEAD")
+        .ok()
+        .and_then(|oid| git_repo.find_commit(oid).ok())
+        .and_then(|commit| commit.tree().ok());
+
+    let protected = state
+        .hooks
+        .as_ref()
+        .is_some_and(|hooks| hooks.runs_builtin(PROTECT_DEFAULT_BRANCH));
+    let oversized_bytes = state
+        .push_limits
+        .max_blob_bytes
+        .map_or(OVERSIZED_BLOB_BYTES, |limit| {
+            limit.min(OVERSIZED_BLOB_BYTES)
+        });
+    let large = tree
+        .as_ref()
+        .map(|tree| large_blobs(&git_repo, tree))
+        .unwrap_or_default();
+    let (oversized, candidates): (Vec<_>, Vec<_>) = large
+        .into_iter()
+        .partition(|blob| blob.size > oversized_bytes);
+
+    let checks = vec![
+        readme_check(tree.as_ref()),
+        license_check(tree.as_ref()),
+        protection_check(protected),
+        stale_branches_check(&git_repo, default_branch.as_deref(), Utc::now()),
+        oversized_check(oversized, oversized_bytes),
+        lfs_check(&git_repo, candidates),
+    ];
+
+    Ok(Json(ApiResponse::success(InsightsResponse {
+        score: score(&checks),
+        default_branch,
+        checks,
+    })))
+}
+
+fn score(checks: &[InsightCheck]) -> u32 {
+    let lost: u32 = checks
+        .iter()
+        .filter(|check| !check.passed)
+        .map(|check| match check.severity {
+            InsightSeverity::Low => 5,
+            InsightSeverity::Medium => 15,
+            InsightSeverity::High => 30,
+        })
+        .sum();
+    100u32.saturating_sub(lost)
+}
+
+fn check(
+    id: &'static str,
+    title: &'static str,
+    severity: InsightSeverity,
+    passed: bool,
+    message: String,
+) -> InsightCheck {
+    InsightCheck {
+        id,
+        title,
+        passed,
+        severity,
+        message,
+        items: Vec::new(),
+    }
+}
+
+/// Name of the first root entry of `tree` that is a file matching `matches`.
+fn root_file(tree: Option<&Tree<'_>>, matches: impl Fn(&str) -> bool) -> Option<String> {
+    tree?
+        .iter()
+        .filter(|entry| entry.kind() == Some(ObjectType::Blob))
+        .filter_map(|entry| entry.name().map(str::to_string))
+        .find(|name| matches(name))
+}
+
+fn readme_check(tree: Option<&Tree<'_>>) -> InsightCheck {
+    let found = root_file(tree, |name| README_FILENAMES.contains(&name));
+    let passed = found.is_some();
+    let message = match found {
+        Some(name) => format!("Found {name}"),
+        None => "No README at the root of the default branch".to_string(),
+    };
+    check("readme", "README", InsightSeverity::Medium, passed, message)
+}
+
+fn is_license_file(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    LICENSE_PREFIXES.iter().any(|prefix| {
+        upper
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '-', '_']))
+    })
+}
+
+fn license_check(tree: Option<&Tree<'_>>) -> InsightCheck {
+    let found = root_file(tree, is_license_file);
+    let passed = found.is_some();
+    let message = match found {
+        Some(name) => format!("Found {name}"),
+        None => "No license file at the root of the default branch".to_string(),
+    };
+    check("license", "License", InsightSeverity::Low, passed, message)
+}
+
+fn protection_check(protected: bool) -> InsightCheck {
+    let message = if protected {
+        "Force-pushes to and deletion of the default branch are rejected".to_string()
+    } else {
+        format!(
+            "The default branch can be force-pushed or deleted; enable the \
+             {PROTECT_DEFAULT_BRANCH} hook to prevent it"
+        )
+    };
+    check(
+        "default_branch_protection",
+        "Protected default branch",
+        InsightSeverity::Medium,
+        protected,
+        message,
+    )
+}
+
+fn commit_time(repo: &Repository, oid: Oid) -> Option<DateTime<Utc>> {
+    let seconds = repo.find_commit(oid).ok()?.time().seconds();
+    Utc.timestamp_opt(seconds, 0).single()
+}
+
+fn stale_branches_check(
+    repo: &Repository,
+    default_branch: Option<&str>,
+    now: DateTime<Utc>,
+) -> InsightCheck {
+    let cutoff = now - chrono::Duration::days(STALE_BRANCH_DAYS);
+    let mut stale: Vec<InsightItem> = repo
+        .branches(Some(BranchType::Local))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|(branch, _)| {
+            let name = branch.name().ok().flatten()?.to_string();
+            let last_commit_at = commit_time(repo, branch.get().target()?)?;
+            (Some(name.as_str()) != default_branch && last_commit_at < cutoff).then_some(
+                InsightItem {
+                    name,
+                    size: None,
+                    last_commit_at: Some(last_commit_at),
+                },
+            )
+        })
+        .collect();
+    stale.sort_by_key(|item| item.last_commit_at);
+
+    let message = match stale.len() {
+        0 => format!("Every branch has commits from the last {STALE_BRANCH_DAYS} days"),
+        n => format!(
+            "{n} branch{} without commits in the last {STALE_BRANCH_DAYS} days",
+            if n == 1 { "" } else { "es" }
+        ),
+    };
+    let mut result = check(
+        "stale_branches",
+        "Stale branches",
+        InsightSeverity::Low,
+        stale.is_empty(),
+        message,
+    );
+    stale.truncate(MAX_ITEMS);
+    result.items = stale;
+    result
+}
+
+struct LargeBlob {
+    path: String,
+    oid: Oid,
+    size: u64,
+}
+
+/// Files in `tree` of at least [`LFS_CANDIDATE_BYTES`], largest first. Sizes
+/// come from object headers, so blobs aren't read in full.
+fn large_blobs(repo: &Repository, tree: &Tree<'_>) -> Vec<LargeBlob> {
+    let Ok(odb) = repo.odb() else {
+        return Vec::new();
+    };
+
+    let mut blobs = Vec::new();
+    let _ = tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() != Some(ObjectType::Blob) {
+            return TreeWalkResult::Ok;
+        }
+        if let Ok((size, _)) = odb.read_header(entry.id()) {
+            let size = size as u64;
+            if size >= LFS_CANDIDATE_BYTES {
+                blobs.push(LargeBlob {
+                    path: format!("{root}{}", entry.name().unwrap_or_default()),
+                    oid: entry.id(),
+                    size,
+                });
+            }
+        }
+        TreeWalkResult::Ok
+    });
+
+    blobs.sort_by_key(|blob| std::cmp::Reverse(blob.size));
+    blobs
+}
+
+fn blob_items(blobs: &[LargeBlob]) -> Vec<InsightItem> {
+    blobs
+        .iter()
+        .take(MAX_ITEMS)
+        .map(|blob| InsightItem {
+            name: blob.path.clone(),
+            size: Some(blob.size),
+            last_commit_at: None,
+        })
+        .collect()
+}
+
+fn files(n: usize) -> String {
+    format!("{n} file{}", if n == 1 { "" } else { "s" })
+}
+
+fn oversized_check(oversized: Vec<LargeBlob>, limit: u64) -> InsightCheck {
+    let limit = format_size(limit as i64);
+    let message = match oversized.len() {
+        0 => format!("No files over {limit}"),
+        n => format!("{} over {limit} on the default branch", files(n)),
+    };
+    let mut result = check(
+        "oversized_files",
+        "Oversized files",
+        InsightSeverity::High,
+        oversized.is_empty(),
+        message,
+    );
+    result.items = blob_items(&oversized);
+    result
+}
+
+/// Binary files big enough to be worth moving to LFS. LFS pointers are a
+/// few hundred bytes, so anything this large is stored in git itself.
+fn lfs_check(repo: &Repository, large: Vec<LargeBlob>) -> InsightCheck {
+    let candidates: Vec<LargeBlob> = large
+        .into_iter()
+        .filter(|blob| {
+            repo.find_blob(blob.oid)
+                .is_ok_and(|content| is_binary(content.content()))
+        })
+        .collect();
+
+    let threshold = format_size(LFS_CANDIDATE_BYTES as i64);
+    let message = match candidates.len() {
+        0 => format!("No binary files over {threshold} stored outside LFS"),
+        n => format!("{} over {threshold} could be tracked with LFS", files(n)),
+    };
+    let mut result = check(
+        "lfs_candidates",
+        "LFS candidates",
+        InsightSeverity::Medium,
+        candidates.is_empty(),
+        message,
+    );
+    result.items = blob_items(&candidates);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit_at(repo: &Repository, branch: &str, files: &[(&str, &[u8])], time: i64) {
+        let mut builder = repo.treebuilder(None).unwrap();
+        for (name, content) in files {
+            let oid = repo.blob(content).unwrap();
+            builder.insert(name, oid, 0o100644).unwrap();
+        }
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let sig =
+            git2::Signature::new("Test", "test@example.com", &git2::Time::new(time, 0)).unwrap();
+        repo.commit(
+            Some(&format!("refs/heads/{branch}")),
+            &sig,
+            &sig,
+            "commit",
+            &tree,
+            &[],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_is_license_file() {
+        assert!(is_license_file("LICENSE"));
+        assert!(is_license_file("license.md"));
+        assert!(is_license_file("LICENSE-MIT"));
+        assert!(is_license_file("COPYING"));
+        assert!(!is_license_file("LICENSES"));
+        assert!(!is_license_file("README.md"));
+    }
+
+    #[test]
+    fn test_score() {
+        let checks = [
+            check("a", "A", InsightSeverity::High, false, String::new()),
+            check("b", "B", InsightSeverity::Low, false, String::new()),
+            check("c", "C", InsightSeverity::Medium, true, String::new()),
+        ];
+        assert_eq!(score(&checks), 65);
+        assert_eq!(score(&checks[2..]), 100);
+    }
+
+    #[test]
+    fn test_tree_checks() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        let now = Utc::now();
+
+        let mut binary = vec![0u8; LFS_CANDIDATE_BYTES as usize];
+        binary[1] = 1;
+        let text = vec![b'a'; LFS_CANDIDATE_BYTES as usize];
+        let files: &[(&str, &[u8])] = &[
+            ("README.md", b"# Hello\n"),
+            ("model.bin", &binary),
+            ("data.csv", &text),
+        ];
+        commit_at(&repo, "main", files, now.timestamp());
+        commit_at(&repo, "old-feature", &[], now.timestamp() - 200 * 86_400);
+        commit_at(&repo, "recent", &[], now.timestamp() - 86_400);
+        repo.set_head("refs/heads/main").unwrap();
+
+        let head = repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(readme_check(Some(&head)).passed);
+        assert!(!license_check(Some(&head)).passed);
+
+        let stale = stale_branches_check(&repo, Some("main"), now);
+        assert!(!stale.passed);
+        let names: Vec<_> = stale.items.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["old-feature"]);
+
+        let large = large_blobs(&repo, &head);
+        assert_eq!(large.len(), 2);
+        let lfs = lfs_check(&repo, large);
+        assert!(!lfs.passed);
+        assert_eq!(lfs.items[0].name, "model.bin");
+        assert_eq!(lfs.items.len(), 1);
+
+        assert!(oversized_check(Vec::new(), OVERSIZED_BLOB_BYTES).passed);
+    }
+}
@@ -0,0 +1,178 @@
+//! Server-side merges.
+//!
+//! The merge commit is written first and the base branch only moves once
+//! the enabled built-in receive hooks accept the update, so branch
+//! protection applies to merges made here just as it does to pushes.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use git2::Oid;
+
+use crate::auth::RequirePrincipal;
+use crate::hooks::{HookContext, RefUpdate};
+use crate::server::AppState;
+use crate::server::response::{ApiError, ApiResponse, StoreOptionExt, StoreResultExt};
+use crate::types::Repo;
+
+use super::auth::OptionalAuth;
+use super::consistency::{CONSISTENCY_HEADER, record_write};
+use super::dto::{MergePreviewResponse, MergeRequest, MergeResponse, MergeStrategy};
+use super::git_ops::{MergeOutcome, advance_branch, prepare_merge, preview_merge, resolve_ref};
+use super::handlers::{
+    check_commit_policy, commit_details, load_repo_and_check_access, load_repo_with_write_access,
+    repo_path,
+};
+
+/// POST /repos/{id}/merge - Merge a branch, tag, or commit into a branch
+pub async fn merge_handler(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(req): Json<MergeRequest>,
+) -> Result<Response, ApiError> {
+    let (repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
+
+    let base = git_repo
+        .find_reference(&format!("refs/heads/{}", req.base))
+        .ok()
+        .and_then(|reference| reference.target())
+        .ok_or_else(|| ApiError::not_found(format!("Branch not found: {}", req.base)))?;
+    let head = resolve_ref(&git_repo, &req.head)?;
+
+    let message = req.message.clone().unwrap_or_else(|| match req.strategy {
+        MergeStrategy::Squash => format!("Squash {} into {}", req.head, req.base),
+        _ => format!("Merge {} into {}", req.head, req.base),
+    });
+    let (message, author) = commit_details(&state, &auth.principal, &message, &req.commit)?;
+    // Merge commits are exempt from the policy, as they are on push.
+    if req.strategy == MergeStrategy::Squash {
+        check_commit_policy(&state, &repo, &message)?;
+    }
+
+    let response = |commit_sha: Oid, merged, consistency_token, conflicts| MergeResponse {
+        commit_sha: commit_sha.to_string(),
+        ref_name: req.base.clone(),
+        strategy: req.strategy,
+        merged,
+        consistency_token,
+        conflicts,
+    };
+    let merged = match prepare_merge(&git_repo, base, head, req.strategy, &message, &author)? {
+        MergeOutcome::UpToDate => {
+            let unchanged = response(base, false, None, Vec::new());
+            return Ok(Json(ApiResponse::success(unchanged)).into_response());
+        }
+        MergeOutcome::Conflicts(paths) => {
+            let error = format!(
+                "Merging {} into {} conflicts in {} file(s)",
+                req.head,
+                req.base,
+                paths.len()
+            );
+            let body = ApiResponse {
+                data: Some(response(base, false, None, paths)),
+                error: Some(error),
+            };
+            return Ok((StatusCode::CONFLICT, Json(body)).into_response());
+        }
+        MergeOutcome::Ready(oid) => oid,
+    };
+
+    check_branch_protection(&state, &auth, &repo, &req.base, base, merged, "Merge")?;
+    advance_branch(
+        &git_repo,
+        &req.base,
+        base,
+        merged,
+        &format!("Merging into {}", req.base),
+    )?;
+
+    let token = record_write(&state, &repo.id);
+    let body = ApiResponse::success(response(merged, true, Some(token.clone()), Vec::new()));
+    Ok((
+        StatusCode::CREATED,
+        [(CONSISTENCY_HEADER, token)],
+        Json(body),
+    )
+        .into_response())
+}
+
+/// GET /repos/{id}/merge-preview/{base}...{head} - Check whether a merge
+/// would go through cleanly, without writing anything
+pub async fn merge_preview_handler(
+    auth: OptionalAuth,
+    State(state): State<Arc<AppState>>,
+    Path((id, spec)): Path<(String, String)>,
+) -> Result<Json<ApiResponse<MergePreviewResponse>>, ApiError> {
+    let (_repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+
+    let (base_ref, head_ref) = spec
+        .split_once("...")
+        .ok_or_else(|| ApiError::bad_request("Invalid merge spec, expected base...head"))?;
+    let base_ref = urlencoding::decode(base_ref)
+        .map_err(|_| ApiError::bad_request("Invalid base ref encoding"))?;
+    let head_ref = urlencoding::decode(head_ref)
+        .map_err(|_| ApiError::bad_request("Invalid head ref encoding"))?;
+
+    let base = git_repo
+        .find_reference(&format!("refs/heads/{base_ref}"))
+        .ok()
+        .and_then(|reference| reference.target())
+        .ok_or_else(|| ApiError::not_found(format!("Branch not found: {base_ref}")))?;
+    let head = resolve_ref(&git_repo, &head_ref)?;
+
+    let preview = preview_merge(&git_repo, base, head)?;
+    Ok(Json(ApiResponse::success(MergePreviewResponse {
+        base_sha: base.to_string(),
+        head_sha: head.to_string(),
+        mergeable: preview.conflicts.is_empty(),
+        up_to_date: preview.up_to_date,
+        fast_forward: preview.fast_forward,
+        conflicts: preview.conflicts,
+    })))
+}
+
+/// Runs the enabled built-in pre-receive hooks against moving `branch`
+/// from `old` to `new`. A refusal is reported as `{action} rejected`.
+pub(super) fn check_branch_protection(
+    state: &AppState,
+    auth: &RequirePrincipal,
+    repo: &Repo,
+    branch: &str,
+    old: Oid,
+    new: Oid,
+    action: &str,
+) -> Result<(), ApiError> {
+    let Some(hooks) = &state.hooks else {
+        return Ok(());
+    };
+    let namespace = state
+        .store
+        .get_namespace(&repo.namespace_id)
+        .api_err("Failed to get namespace")?
+        .or_not_found("Namespace not found")?;
+    let path = repo_path(state, &repo.namespace_id, &repo.name);
+    let ctx = HookContext {
+        principal_id: auth.principal.id.clone(),
+        namespace: namespace.name,
+        repo_id: repo.id.clone(),
+        repo_name: repo.name.clone(),
+        push_options: Vec::new(),
+        repo: git2::Repository::open_bare(&path)
+            .map_err(|e| ApiError::internal(format!("Failed to open repository: {e}")))?,
+    };
+    let update = RefUpdate {
+        old: old.to_string(),
+        new: new.to_string(),
+        refname: format!("refs/heads/{branch}"),
+    };
+    hooks
+        .check_builtin(&ctx, &[update])
+        .map_err(|message| ApiError::forbidden(format!("{action} rejected: {message}")))
+}
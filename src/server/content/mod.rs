@@ -1,9 +1,25 @@
 mod auth;
+mod badge;
+mod bundle;
+mod consistency;
 pub mod dto;
+mod embed;
+mod etag;
 pub mod git_ops;
+mod grep;
+#[cfg(feature = "grpc")]
+mod grpc;
 mod handlers;
+mod insights;
+mod merge;
+mod patches;
+mod raw;
+mod render;
+mod statuses;
+mod webdav;
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
     Router,
@@ -12,6 +28,43 @@ use axum::{
 
 use crate::server::AppState;
 
+pub(crate) use consistency::check_consistency;
+pub(crate) use embed::get_repo_page;
+#[cfg(feature = "grpc")]
+pub use grpc::grpc_router;
+pub(crate) use handlers::get_commit_author;
+pub(crate) use webdav::webdav_router;
+
+/// Size and time limits on files moving through the content API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentLimits {
+    /// Largest file accepted in a multipart or raw upload.
+    pub max_upload_bytes: u64,
+    /// Largest file served raw. Unlimited when unset.
+    pub max_raw_blob_bytes: Option<u64>,
+    /// Most of a file returned inline in JSON (blobs, READMEs, rendered
+    /// markup); longer files are cut and marked truncated.
+    pub max_inline_blob_bytes: u64,
+    /// How long `git archive` may run when `[limits.archive]` sets no
+    /// timeout. Unlimited when unset.
+    pub archive_timeout: Option<Duration>,
+    /// How long a content search may run when `[limits.search]` sets no
+    /// timeout. Unlimited when unset.
+    pub grep_timeout: Option<Duration>,
+}
+
+impl Default for ContentLimits {
+    fn default() -> Self {
+        Self {
+            max_upload_bytes: 100 * 1024 * 1024,
+            max_raw_blob_bytes: None,
+            max_inline_blob_bytes: 1024 * 1024,
+            archive_timeout: Some(Duration::from_secs(5 * 60)),
+            grep_timeout: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
 pub fn content_router() -> Router<Arc<AppState>> {
     Router::new()
         .route("/repos/{id}/refs", get(handlers::list_refs))
@@ -38,7 +91,20 @@ pub fn content_router() -> Router<Arc<AppState>> {
             "/repos/{id}/commits/{sha}/diff",
             get(handlers::get_commit_diff),
         )
+        .route(
+            "/repos/{id}/commits/{sha}/statuses",
+            get(statuses::get_commit_statuses).post(statuses::create_commit_status),
+        )
         .route("/repos/{id}/compare/{spec}", get(handlers::compare_refs))
+        .route("/repos/{id}/merge", post(merge::merge_handler))
+        .route(
+            "/repos/{id}/patches/{*ref}",
+            post(patches::apply_patch_handler),
+        )
+        .route(
+            "/repos/{id}/merge-preview/{spec}",
+            get(merge::merge_preview_handler),
+        )
         .route("/repos/{id}/tree/{ref}", get(handlers::get_tree_root))
         .route(
             "/repos/{id}/tree/{ref}/{*path}",
@@ -54,8 +120,19 @@ pub fn content_router() -> Router<Arc<AppState>> {
             "/repos/{id}/upload/{ref}/{*path}",
             post(handlers::upload_blob),
         )
+        .route("/repos/{id}/raw/{ref}/{*path}", put(handlers::put_raw_blob))
         .route("/repos/{id}/blame/{ref}/{*path}", get(handlers::get_blame))
+        .route("/repos/{id}/render/{ref}/{*path}", get(render::get_render))
+        .route(
+            "/repos/{id}/render-notebook/{ref}/{*path}",
+            get(render::get_render_notebook),
+        )
         .route("/repos/{id}/archive/{ref}", get(handlers::get_archive))
+        .route("/repos/{id}/bundle", get(bundle::get_bundle))
         .route("/repos/{id}/readme", get(handlers::get_readme))
         .route("/repos/{id}/search", get(handlers::search_paths_handler))
+        .route("/repos/{id}/grep", get(grep::grep_handler))
+        .route("/repos/{id}/badge/{kind}", get(badge::get_badge))
+        .route("/repos/{id}/insights", get(insights::get_insights))
+        .route("/oembed", get(embed::get_oembed))
 }
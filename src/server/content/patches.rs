@@ -0,0 +1,668 @@
+//! Applying patches without a working copy.
+//!
+//! The body is either a plain unified diff, committed with the message
+//! from the request, or `git format-patch` output, where each mail becomes
+//! a commit with its own author, date, and message. Every patch is applied
+//! before the branch moves, so a series lands whole or not at all.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::{DateTime, FixedOffset};
+use git2::Diff;
+
+use crate::auth::RequirePrincipal;
+use crate::server::AppState;
+use crate::server::response::{ApiError, ApiResponse};
+
+use super::consistency::{CONSISTENCY_HEADER, record_write};
+use super::dto::{ApplyPatchParams, PatchResponse};
+use super::git_ops::{CommitAuthor, PatchCommit, advance_branch, apply_patches};
+use super::handlers::{
+    COMMIT_MESSAGE_HEADER, check_commit_policy, get_commit_author, is_identity,
+    load_repo_with_write_access, resolve_branch,
+};
+use super::merge::check_branch_protection;
+
+/// Patches one request can apply.
+const MAX_PATCHES: usize = 100;
+
+/// POST /repos/{id}/patches/{*ref} - Apply a diff or a `git format-patch`
+/// series on top of a branch, committing each patch
+pub async fn apply_patch_handler(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path((id, ref_name)): Path<(String, String)>,
+    Query(params): Query<ApplyPatchParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<impl IntoResponse, ApiError> {
+    let (repo, git_repo) = load_repo_with_write_access(&state, &auth, &id, false).await?;
+
+    let branch = resolve_branch(&git_repo, &ref_name);
+    let base = git_repo
+        .find_reference(&format!("refs/heads/{branch}"))
+        .ok()
+        .and_then(|reference| reference.target())
+        .ok_or_else(|| ApiError::not_found(format!("Branch not found: {branch}")))?;
+
+    let limit = state.content_limits.max_upload_bytes;
+    let body = axum::body::to_bytes(body, usize::try_from(limit).unwrap_or(usize::MAX))
+        .await
+        .map_err(|_| {
+            ApiError::payload_too_large(format!(
+                "Patch exceeds maximum allowed size ({limit} bytes)"
+            ))
+        })?;
+    if let Some(too_large) = state.push_limits.check_pack(body.len() as u64) {
+        return Err(ApiError::payload_too_large(too_large.to_string()));
+    }
+
+    let parsed = parse_patches(&body).map_err(ApiError::bad_request)?;
+    if parsed.len() > MAX_PATCHES {
+        return Err(ApiError::bad_request(format!(
+            "At most {MAX_PATCHES} patches can be applied at once"
+        )));
+    }
+
+    let (name, email) = get_commit_author(&state, &auth.principal);
+    let committer = CommitAuthor {
+        name,
+        email,
+        author_time: None,
+        commit_time: None,
+    };
+    let message = params.message.filter(|m| !m.trim().is_empty()).or_else(|| {
+        headers
+            .get(COMMIT_MESSAGE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    });
+    let author = match params.author.as_deref().map(str::trim) {
+        Some(author) => Some(
+            split_identity(author)
+                .filter(|_| is_identity(author))
+                .ok_or_else(|| {
+                    ApiError::bad_request(format!(
+                        "Invalid author {author:?}; use \"Name <email>\""
+                    ))
+                })?,
+        ),
+        None => None,
+    };
+
+    let mut patches = Vec::with_capacity(parsed.len());
+    for (i, patch) in parsed.into_iter().enumerate() {
+        let n = i + 1;
+        let message = patch.message.or_else(|| message.clone()).ok_or_else(|| {
+            ApiError::bad_request(
+                "A commit message is required: pass message, or send git format-patch output",
+            )
+        })?;
+        check_commit_policy(&state, &repo, &message)?;
+
+        let diff = Diff::from_buffer(&with_git_headers(patch.diff)).map_err(|e| {
+            ApiError::bad_request(format!("Patch {n} is not a valid diff: {}", e.message()))
+        })?;
+        if diff.deltas().len() == 0 {
+            return Err(ApiError::bad_request(format!("Patch {n} changes nothing")));
+        }
+
+        let (name, email) = patch
+            .author
+            .or_else(|| author.clone())
+            .unwrap_or_else(|| (committer.name.clone(), committer.email.clone()));
+        patches.push(PatchCommit {
+            diff,
+            message,
+            author: CommitAuthor {
+                name,
+                email,
+                author_time: patch.date.map(|date| {
+                    git2::Time::new(date.timestamp(), date.offset().local_minus_utc() / 60)
+                }),
+                commit_time: None,
+            },
+        });
+    }
+
+    let commits = apply_patches(&git_repo, base, &patches, &committer)?;
+    let tip = *commits.last().expect("at least one patch");
+    check_branch_protection(&state, &auth, &repo, &branch, base, tip, "Patch")?;
+    advance_branch(
+        &git_repo,
+        &branch,
+        base,
+        tip,
+        &format!("Applying patch to {branch}"),
+    )?;
+
+    let token = record_write(&state, &repo.id);
+    Ok((
+        StatusCode::CREATED,
+        [(CONSISTENCY_HEADER, token.clone())],
+        Json(ApiResponse::success(PatchResponse {
+            commit_sha: tip.to_string(),
+            ref_name: branch,
+            commits: commits.iter().map(ToString::to_string).collect(),
+            consistency_token: token,
+        })),
+    ))
+}
+
+/// One patch of a request body. Everything but the diff comes from the
+/// mail headers of `git format-patch` output, and is unset for a plain diff.
+#[derive(Debug, PartialEq)]
+struct ParsedPatch<'a> {
+    /// The subject without its `[PATCH n/m]` prefix, then the body above
+    /// the `---` line.
+    message: Option<String>,
+    author: Option<(String, String)>,
+    date: Option<DateTime<FixedOffset>>,
+    diff: &'a [u8],
+}
+
+/// Splits a body into its patches: the mails of a `git format-patch`
+/// mailbox, or a single plain diff.
+fn parse_patches(body: &[u8]) -> Result<Vec<ParsedPatch<'_>>, String> {
+    let lines: Vec<&[u8]> = body.split_inclusive(|&b| b == b'\n').collect();
+    let first = lines.iter().position(|line| !trim_line(line).is_empty());
+    let Some(first) = first else {
+        return Err("The patch is empty".to_string());
+    };
+    if !is_mbox_separator(lines[first]) && header(lines[first]).is_none() {
+        return Ok(vec![ParsedPatch {
+            message: None,
+            author: None,
+            date: None,
+            diff: body,
+        }]);
+    }
+
+    let mut mails = Vec::new();
+    let mut start = first;
+    for (i, line) in lines.iter().enumerate().skip(first + 1) {
+        if is_mbox_separator(line) {
+            mails.push(&lines[start..i]);
+            start = i;
+        }
+    }
+    mails.push(&lines[start..]);
+
+    mails
+        .into_iter()
+        .enumerate()
+        .map(|(i, mail)| {
+            let mail = match mail.first() {
+                Some(line) if is_mbox_separator(line) => &mail[1..],
+                _ => mail,
+            };
+            parse_mail(body, mail).map_err(|e| format!("Patch {}: {e}", i + 1))
+        })
+        .collect()
+}
+
+/// Parses one mail of `git format-patch` output. `lines` are slices of
+/// `body`, which the diff is cut from so its bytes are kept as sent.
+fn parse_mail<'a>(body: &'a [u8], lines: &[&'a [u8]]) -> Result<ParsedPatch<'a>, String> {
+    let (headers, mut rest) = read_headers(lines);
+    let mut author = None;
+    let mut date = None;
+    let mut subject = None;
+    let mut apply = |headers: Vec<(String, String)>| {
+        for (name, value) in headers {
+            match name.to_ascii_lowercase().as_str() {
+                "from" => author = split_identity(&decode_header(&value)),
+                "date" => date = DateTime::parse_from_rfc2822(value.trim()).ok(),
+                "subject" => subject = Some(strip_subject_prefix(&decode_header(&value))),
+                _ => {}
+            }
+        }
+    };
+    apply(headers);
+    // A `From:` line opening the body names an author other than the sender.
+    let opening = rest.iter().position(|line| !trim_line(line).is_empty());
+    if let Some(opening) = opening
+        && header(rest[opening]).is_some()
+    {
+        let (in_body, after) = read_headers(&rest[opening..]);
+        apply(in_body);
+        rest = after;
+    }
+
+    let diff_start = (0..rest.len())
+        .find(|&i| {
+            rest[i].starts_with(b"diff --git ")
+                || (rest[i].starts_with(b"--- ")
+                    && rest
+                        .get(i + 1)
+                        .is_some_and(|next| next.starts_with(b"+++ ")))
+        })
+        .ok_or("no diff found")?;
+    let description: Vec<String> = rest[..diff_start]
+        .iter()
+        .map(|line| String::from_utf8_lossy(trim_line(line)).into_owned())
+        .take_while(|line| line != "---")
+        .collect();
+    let description = description.join("\n");
+    let message = match (subject.filter(|s| !s.is_empty()), description.trim()) {
+        (Some(subject), "") => subject,
+        (Some(subject), description) => format!("{subject}\n\n{description}"),
+        (None, "") => return Err("no subject".to_string()),
+        (None, description) => description.to_string(),
+    };
+
+    // `git format-patch` ends each mail with a `-- ` line and its version.
+    let mut diff_lines = &rest[diff_start..];
+    if let Some(signature) = diff_lines
+        .iter()
+        .rposition(|line| trim_line(line) == b"-- ")
+        && diff_lines.len() - signature <= 3
+    {
+        diff_lines = &diff_lines[..signature];
+    }
+    let diff = match (diff_lines.first(), diff_lines.last()) {
+        (Some(first), Some(last)) => {
+            let start = offset_in(body, first);
+            &body[start..offset_in(body, last) + last.len()]
+        }
+        _ => return Err("no diff found".to_string()),
+    };
+
+    Ok(ParsedPatch {
+        message: Some(message),
+        author,
+        date,
+        diff,
+    })
+}
+
+/// Reads mail headers up to the blank line ending them, joining folded
+/// lines. Returns the headers and the lines after the blank one.
+fn read_headers<'a, 'b>(lines: &'b [&'a [u8]]) -> (Vec<(String, String)>, &'b [&'a [u8]]) {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let text = String::from_utf8_lossy(trim_line(line));
+        if text.is_empty() {
+            return (headers, &lines[i + 1..]);
+        }
+        if text.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(text.trim());
+            }
+        } else if let Some((name, value)) = text.split_once(':') {
+            headers.push((name.to_string(), value.trim().to_string()));
+        }
+    }
+    (headers, &[])
+}
+
+/// The name of a mail header line, when `line` is one.
+fn header(line: &[u8]) -> Option<&str> {
+    let line = std::str::from_utf8(line).ok()?;
+    let (name, _) = line.split_once(':')?;
+    ["From", "Subject", "Date"]
+        .into_iter()
+        .find(|known| name.eq_ignore_ascii_case(known))
+}
+
+/// Whether `line` starts a mail in an mbox: `From <sha> <date>`, as
+/// `git format-patch` writes it.
+fn is_mbox_separator(line: &[u8]) -> bool {
+    let Some(rest) = line.strip_prefix(b"From ") else {
+        return false;
+    };
+    let sha = rest.split(|&b| b == b' ').next().unwrap_or_default();
+    matches!(sha.len(), 40 | 64) && sha.iter().all(u8::is_ascii_hexdigit)
+}
+
+/// libgit2 only reads diffs in git's format, so give each file of a plain
+/// `diff -u` its `diff --git` header, with paths stripped of their first
+/// directory as `git apply` does. Hunks are skipped by their line counts,
+/// so removed or added lines that look like file headers stay as they are.
+fn with_git_headers(diff: &[u8]) -> Cow<'_, [u8]> {
+    let lines: Vec<&[u8]> = diff.split_inclusive(|&b| b == b'\n').collect();
+    let mut out: Vec<u8> = Vec::with_capacity(diff.len());
+    let mut changed = false;
+    let mut git_header = false;
+    let (mut old_left, mut new_left) = (0u64, 0u64);
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if old_left > 0 || new_left > 0 {
+            match line.first() {
+                Some(b' ') => {
+                    (old_left, new_left) = (old_left.saturating_sub(1), new_left.saturating_sub(1))
+                }
+                Some(b'-') => old_left = old_left.saturating_sub(1),
+                Some(b'+') => new_left = new_left.saturating_sub(1),
+                Some(b'\\') => {}
+                _ => (old_left, new_left) = (0, 0),
+            }
+        } else if line.starts_with(b"diff --git ") {
+            git_header = true;
+        } else if line.starts_with(b"@@ ") {
+            git_header = false;
+            (old_left, new_left) = hunk_lengths(line).unwrap_or((0, 0));
+        } else if !git_header
+            && line.starts_with(b"--- ")
+            && let Some(next) = lines.get(i + 1).filter(|next| next.starts_with(b"+++ "))
+        {
+            let (mut old, mut new) = (diff_path(&line[4..]), diff_path(&next[4..]));
+            // `diff -N` stands in an empty file for a missing one.
+            let hunk = lines.get(i + 2).and_then(|hunk| hunk.strip_prefix(b"@@ "));
+            if hunk.is_some_and(|hunk| hunk.starts_with(b"-0,0 ")) {
+                old = "/dev/null".to_string();
+            } else if hunk.is_some_and(|hunk| hunk.windows(5).any(|w| w == b"+0,0 ")) {
+                new = "/dev/null".to_string();
+            }
+            let name = if new == "/dev/null" { &old } else { &new };
+            let side = |prefix: &str, path: &str| {
+                if path == "/dev/null" {
+                    path.to_string()
+                } else {
+                    format!("{prefix}{path}")
+                }
+            };
+            out.extend_from_slice(format!("diff --git a/{name} b/{name}\n").as_bytes());
+            if old == "/dev/null" {
+                out.extend_from_slice(b"new file mode 100644\n");
+            } else if new == "/dev/null" {
+                out.extend_from_slice(b"deleted file mode 100644\n");
+            }
+            out.extend_from_slice(format!("--- {}\n", side("a/", &old)).as_bytes());
+            out.extend_from_slice(format!("+++ {}\n", side("b/", &new)).as_bytes());
+            changed = true;
+            i += 2;
+            continue;
+        }
+        out.extend_from_slice(line);
+        i += 1;
+    }
+    if changed {
+        Cow::Owned(out)
+    } else {
+        Cow::Borrowed(diff)
+    }
+}
+
+/// The old and new line counts of a `@@ -a,b +c,d @@` hunk header.
+fn hunk_lengths(line: &[u8]) -> Option<(u64, u64)> {
+    let line = std::str::from_utf8(line).ok()?;
+    let mut ranges = line.strip_prefix("@@ ")?.split_whitespace();
+    let count = |range: &str| match range.split_once(',') {
+        Some((_, count)) => count.parse().ok(),
+        None => Some(1),
+    };
+    let old = count(ranges.next()?.strip_prefix('-')?)?;
+    let new = count(ranges.next()?.strip_prefix('+')?)?;
+    Some((old, new))
+}
+
+/// The path from a `---` or `+++` line of a plain diff, without the
+/// timestamp `diff -u` adds or its first directory.
+fn diff_path(header: &[u8]) -> String {
+    let header = String::from_utf8_lossy(trim_line(header));
+    let path = header.split('\t').next().unwrap_or_default().trim();
+    if path == "/dev/null" {
+        return path.to_string();
+    }
+    path.split_once('/')
+        .map_or(path, |(_, rest)| rest)
+        .to_string()
+}
+
+fn trim_line(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Where `part`, a slice of `body`, starts in it.
+fn offset_in(body: &[u8], part: &[u8]) -> usize {
+    part.as_ptr() as usize - body.as_ptr() as usize
+}
+
+/// Drops the `[PATCH v2 1/3]` style prefixes from a subject.
+fn strip_subject_prefix(subject: &str) -> String {
+    let mut subject = subject.trim();
+    while let Some(rest) = subject.strip_prefix('[')
+        && let Some((_, after)) = rest.split_once(']')
+    {
+        subject = after.trim_start();
+    }
+    subject.to_string()
+}
+
+/// Splits `Name <email>` into its parts. A bare address is its own name.
+fn split_identity(value: &str) -> Option<(String, String)> {
+    let value = value.trim();
+    let (name, email) = match value.rsplit_once('<') {
+        Some((name, email)) => (
+            name.trim().trim_matches('"').trim(),
+            email.strip_suffix('>')?,
+        ),
+        None => ("", value),
+    };
+    let email = email.trim();
+    if !email.contains('@') {
+        return None;
+    }
+    let name = if name.is_empty() { email } else { name };
+    Some((name.to_string(), email.to_string()))
+}
+
+/// Decodes the RFC 2047 encoded words `git format-patch` uses for names and
+/// subjects outside ASCII, e.g. `=?UTF-8?q?Ren=C3=A9?=`. Whitespace between
+/// two encoded words is dropped.
+fn decode_header(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let Some((word, len)) = decode_word(&rest[start..]) else {
+            out.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            after_word = false;
+            continue;
+        };
+        let between = &rest[..start];
+        if !(after_word && between.trim().is_empty()) {
+            out.push_str(between);
+        }
+        out.push_str(&word);
+        rest = &rest[start + len..];
+        after_word = true;
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Decodes the encoded word at the start of `text`, returning it and its
+/// length.
+fn decode_word(text: &str) -> Option<(String, usize)> {
+    let mut parts = text.get(2..)?.splitn(3, '?');
+    let _charset = parts.next()?;
+    let encoding = parts.next()?;
+    let encoded_and_rest = parts.next()?;
+    let end = encoded_and_rest.find("?=")?;
+    let encoded = &encoded_and_rest[..end];
+    let bytes = match encoding {
+        "Q" | "q" => {
+            let mut bytes = Vec::with_capacity(encoded.len());
+            let mut chars = encoded.bytes();
+            while let Some(b) = chars.next() {
+                match b {
+                    b'_' => bytes.push(b' '),
+                    b'=' => {
+                        let hex = [chars.next()?, chars.next()?];
+                        bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+                    }
+                    b => bytes.push(b),
+                }
+            }
+            bytes
+        }
+        "B" | "b" => STANDARD.decode(encoded).ok()?,
+        _ => return None,
+    };
+    let len = text.len() - encoded_and_rest.len() + end + 2;
+    Some((String::from_utf8_lossy(&bytes).into_owned(), len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERIES: &str = "From 1111111111111111111111111111111111111111 Mon Sep 17 00:00:00 2001
+From: =?UTF-8?q?Ren=C3=A9?= Doe <rene@example.com>
+Date: Tue, 3 Mar 2026 10:00:00 +0100
+Subject: [PATCH 1/2] Fix the
+ greeting
+
+It said hello twice.
+---
+ a.txt | 2 +-
+ 1 file changed, 1 insertion(+), 1 deletion(-)
+
+diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-hello hello
++hello
+--\x20
+2.45.0
+
+From 2222222222222222222222222222222222222222 Mon Sep 17 00:00:00 2001
+From: Sender <sender@example.com>
+Date: Tue, 3 Mar 2026 11:00:00 +0000
+Subject: [PATCH 2/2] Add b
+
+From: Ann <ann@example.com>
+
+---
+diff --git a/b.txt b/b.txt
+new file mode 100644
+--- /dev/null
++++ b/b.txt
+@@ -0,0 +1 @@
++b
+--\x20
+2.45.0
+";
+
+    #[test]
+    fn test_parse_format_patch_series() {
+        let patches = parse_patches(SERIES.as_bytes()).unwrap();
+        assert_eq!(patches.len(), 2);
+
+        let first = &patches[0];
+        assert_eq!(
+            first.message.as_deref(),
+            Some("Fix the greeting\n\nIt said hello twice.")
+        );
+        assert_eq!(
+            first.author,
+            Some(("René Doe".to_string(), "rene@example.com".to_string()))
+        );
+        assert_eq!(
+            first.date.unwrap().to_rfc3339(),
+            "2026-03-03T10:00:00+01:00"
+        );
+        let diff = std::str::from_utf8(first.diff).unwrap();
+        assert!(diff.starts_with("diff --git a/a.txt b/a.txt\n"));
+        assert!(diff.ends_with("+hello\n"));
+
+        // The in-body From names the author.
+        let second = &patches[1];
+        assert_eq!(second.message.as_deref(), Some("Add b"));
+        assert_eq!(
+            second.author,
+            Some(("Ann".to_string(), "ann@example.com".to_string()))
+        );
+        assert!(std::str::from_utf8(second.diff).unwrap().ends_with("+b\n"));
+    }
+
+    #[test]
+    fn test_parse_plain_diff() {
+        let diff = b"--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-a\n+b\n";
+        let patches = parse_patches(diff).unwrap();
+        assert_eq!(
+            patches,
+            [ParsedPatch {
+                message: None,
+                author: None,
+                date: None,
+                diff,
+            }]
+        );
+        assert!(parse_patches(b"\n\n").is_err());
+        assert!(parse_patches(b"Subject: nothing here\n\nJust words.\n").is_err());
+    }
+
+    #[test]
+    fn test_with_git_headers() {
+        let plain = "--- old/a.txt\t2026-03-03 10:00:00.000000000 +0100
++++ new/a.txt\t2026-03-03 10:05:00.000000000 +0100
+@@ -1,2 +1 @@
+--- kept
+-a
++b
+--- old/c.txt\t1970-01-01 00:00:00.000000000 +0000
++++ new/c.txt\t2026-03-03 10:05:00.000000000 +0100
+@@ -0,0 +1 @@
++c
+";
+        let diff = with_git_headers(plain.as_bytes());
+        assert_eq!(
+            std::str::from_utf8(&diff).unwrap(),
+            "diff --git a/a.txt b/a.txt
+--- a/a.txt
++++ b/a.txt
+@@ -1,2 +1 @@
+--- kept
+-a
++b
+diff --git a/c.txt b/c.txt
+new file mode 100644
+--- /dev/null
++++ b/c.txt
+@@ -0,0 +1 @@
++c
+"
+        );
+        let parsed = Diff::from_buffer(&diff).unwrap();
+        assert_eq!(parsed.deltas().len(), 2);
+
+        // Diffs in git's format are left alone.
+        let git = b"diff --git a/x b/x\n--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a\n+b\n";
+        assert!(matches!(with_git_headers(git), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_decode_header() {
+        assert_eq!(decode_header("plain"), "plain");
+        assert_eq!(
+            decode_header("=?UTF-8?q?Caf=C3=A9_time?= =?UTF-8?B?IQ==?= now"),
+            "Café time! now"
+        );
+        assert_eq!(decode_header("a =?bogus"), "a =?bogus");
+        assert_eq!(strip_subject_prefix("[PATCH v2 1/3] [RFC] Do it"), "Do it");
+        assert_eq!(
+            split_identity("\"Doe, J\" <j@example.com>"),
+            Some(("Doe, J".to_string(), "j@example.com".to_string()))
+        );
+        assert_eq!(split_identity("nobody"), None);
+    }
+}
@@ -0,0 +1,370 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use git2::{ObjectType, Oid, Repository};
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tokio_util::io::ReaderStream;
+use tracing::warn;
+
+use crate::server::AppState;
+use crate::server::limits::{apply_limits, deadline};
+use crate::server::response::ApiError;
+
+use super::etag;
+use super::git_ops::{GitError, get_commit, get_tree, is_binary, resolve_ref};
+
+/// Blobs up to this size are read into memory; larger ones are streamed
+/// from `git cat-file` so they never have to fit.
+const STREAM_THRESHOLD: u64 = 1024 * 1024;
+
+/// How much of a file is looked at to tell text from binary.
+const SNIFF_BYTES: u64 = 8192;
+
+/// A blob to serve raw, read up front when it's small.
+pub(super) struct RawBlob {
+    oid: Oid,
+    size: u64,
+    git_dir: PathBuf,
+    content: Option<Bytes>,
+}
+
+impl RawBlob {
+    pub(super) fn find(repo: &Repository, ref_name: &str, path: &str) -> Result<Self, GitError> {
+        let commit = get_commit(repo, resolve_ref(repo, ref_name)?)?;
+        let tree = get_tree(repo, &commit)?;
+        let entry = tree
+            .get_path(Path::new(path))
+            .map_err(|_| GitError::PathNotFound(path.to_string()))?;
+        if entry.kind() != Some(ObjectType::Blob) {
+            return Err(GitError::NotAFile);
+        }
+
+        // Only the header, so a large blob isn't inflated just to size it.
+        let (size, _) = repo
+            .odb()
+            .and_then(|odb| odb.read_header(entry.id()))
+            .map_err(|e| GitError::Internal(format!("Failed to read blob header: {e}")))?;
+        let size = size as u64;
+        let content = if size <= STREAM_THRESHOLD {
+            let blob = repo
+                .find_blob(entry.id())
+                .map_err(|e| GitError::Internal(format!("Failed to read blob: {e}")))?;
+            Some(Bytes::copy_from_slice(blob.content()))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            oid: entry.id(),
+            size,
+            git_dir: repo.path().to_path_buf(),
+            content,
+        })
+    }
+}
+
+/// Inclusive byte offsets of a single-range request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum RangeRequest {
+    Full,
+    Partial(ByteRange),
+    Unsatisfiable,
+}
+
+/// What a request's `Range` and `If-Range` headers ask for. Multiple
+/// ranges and headers that don't parse are ignored in favour of the whole
+/// blob, as is an `If-Range` that isn't the blob's ETag: there's no
+/// modification date to compare against.
+fn requested_range(headers: &HeaderMap, size: u64, etag: &str) -> RangeRequest {
+    let Some(range) = headers.get(header::RANGE).and_then(|v| v.to_str().ok()) else {
+        return RangeRequest::Full;
+    };
+    if let Some(if_range) = headers.get(header::IF_RANGE) {
+        if if_range.as_bytes() != etag.as_bytes() {
+            return RangeRequest::Full;
+        }
+    }
+    parse_range(range, size)
+}
+
+fn parse_range(value: &str, size: u64) -> RangeRequest {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+    let parse = |n: &str| n.trim().parse::<u64>().ok();
+
+    let range = match (start.trim().is_empty(), end.trim().is_empty()) {
+        // `bytes=-n` is the last n bytes.
+        (true, false) => match parse(end) {
+            Some(0) => return RangeRequest::Unsatisfiable,
+            Some(suffix) => ByteRange {
+                start: size.saturating_sub(suffix),
+                end: size.saturating_sub(1),
+            },
+            None => return RangeRequest::Full,
+        },
+        (false, true) => match parse(start) {
+            Some(start) => ByteRange {
+                start,
+                end: size.saturating_sub(1),
+            },
+            None => return RangeRequest::Full,
+        },
+        (false, false) => match (parse(start), parse(end)) {
+            (Some(start), Some(end)) if start <= end => ByteRange {
+                start,
+                end: end.min(size.saturating_sub(1)),
+            },
+            _ => return RangeRequest::Full,
+        },
+        (true, true) => return RangeRequest::Full,
+    };
+    if size == 0 || range.start >= size {
+        return RangeRequest::Unsatisfiable;
+    }
+    RangeRequest::Partial(range)
+}
+
+/// Serve a blob's bytes, or the one range of them the request asks for.
+pub(super) async fn serve_raw_blob(
+    state: &AppState,
+    request_headers: &HeaderMap,
+    blob: RawBlob,
+    filename: &str,
+) -> Result<Response, ApiError> {
+    if let Some(max) = state.content_limits.max_raw_blob_bytes {
+        if blob.size > max {
+            return Err(ApiError::payload_too_large(format!(
+                "File size ({} bytes) exceeds maximum allowed size ({max} bytes)",
+                blob.size
+            )));
+        }
+    }
+
+    let etag = format!("\"{}\"", blob.oid);
+    if etag::matches(request_headers, &etag) {
+        return Ok(etag::not_modified(&etag));
+    }
+    let range = match requested_range(request_headers, blob.size, &etag) {
+        RangeRequest::Full => None,
+        RangeRequest::Partial(range) => Some(range),
+        RangeRequest::Unsatisfiable => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [
+                    (header::ACCEPT_RANGES, "bytes".to_string()),
+                    (header::CONTENT_RANGE, format!("bytes */{}", blob.size)),
+                ],
+            )
+                .into_response());
+        }
+    };
+    let (start, len) = range.map_or((0, blob.size), |r| (r.start, r.end - r.start + 1));
+
+    let (content_type, body) = match &blob.content {
+        Some(content) => {
+            let content_type = detect_content_type(filename, content);
+            let body = content.slice(start as usize..(start + len) as usize);
+            (content_type, Body::from(body))
+        }
+        None => stream_blob(state, &blob, filename, start, len).await?,
+    };
+
+    let mut response = body.into_response();
+    if let Some(range) = range {
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+        if let Ok(value) = HeaderValue::from_str(&format!(
+            "bytes {}-{}/{}",
+            range.start, range.end, blob.size
+        )) {
+            response.headers_mut().insert(header::CONTENT_RANGE, value);
+        }
+    }
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(&content_type)
+            .unwrap_or(HeaderValue::from_static("application/octet-stream")),
+    );
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, value);
+    }
+    Ok(response)
+}
+
+/// Stream `len` bytes of a blob from `start` out of `git cat-file`, along
+/// with its content type.
+async fn stream_blob(
+    state: &AppState,
+    blob: &RawBlob,
+    filename: &str,
+    start: u64,
+    len: u64,
+) -> Result<(String, Body), ApiError> {
+    let limits = &state.limits.fetch;
+    let mut cmd = Command::new("git");
+    cmd.arg("--git-dir")
+        .arg(&blob.git_dir)
+        .args(["cat-file", "blob"])
+        .arg(blob.oid.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    apply_limits(&mut cmd, limits);
+    let timeout = limits.timeout(None);
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| ApiError::internal(format!("Failed to run git cat-file: {e}")))?;
+    let (Some(mut stdout), Some(mut stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err(ApiError::internal("Failed to capture git cat-file output"));
+    };
+
+    // The type comes from the start of the file even for a range further in.
+    let mut head = vec![0; SNIFF_BYTES.min(blob.size) as usize];
+    stdout
+        .read_exact(&mut head)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to read blob: {e}")))?;
+    let content_type = detect_content_type(filename, &head);
+
+    let mut reader = Cursor::new(head).chain(stdout);
+    let skipped = tokio::io::copy(&mut (&mut reader).take(start), &mut tokio::io::sink())
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to read blob: {e}")))?;
+    if skipped != start {
+        return Err(ApiError::internal("git cat-file ended early"));
+    }
+
+    // Reap git in the background. The response has already started, so
+    // failures are only logged.
+    tokio::spawn(async move {
+        let mut diagnostics = String::new();
+        let run = async {
+            let _ = stderr.read_to_string(&mut diagnostics).await;
+            child.wait().await
+        };
+        let status = match deadline(timeout, run).await {
+            Some(status) => status,
+            None => {
+                warn!("git cat-file exceeded its time limit; killing it");
+                let _ = child.start_kill();
+                child.wait().await
+            }
+        };
+        match status {
+            // A range that stops short of the end closes the pipe on git.
+            Ok(status) if !status.success() && status.code().is_some() => {
+                warn!("git cat-file exited with {status}: {}", diagnostics.trim());
+            }
+            Err(e) => warn!("Failed to wait for git cat-file: {e}"),
+            Ok(_) => {}
+        }
+    });
+
+    let body = Body::from_stream(ReaderStream::new(reader.take(len)));
+    Ok((content_type, body))
+}
+
+fn detect_content_type(filename: &str, content: &[u8]) -> String {
+    let content_type = content_type_for_extension(filename).unwrap_or(if is_binary(content) {
+        "application/octet-stream"
+    } else {
+        "text/plain; charset=utf-8"
+    });
+    content_type.to_string()
+}
+
+pub(super) fn content_type_for_extension(filename: &str) -> Option<&'static str> {
+    let ext = std::path::Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    Some(match ext.as_str() {
+        "go" | "rs" | "py" | "rb" | "java" | "c" | "cpp" | "h" | "hpp" | "sh" | "sql" => {
+            "text/plain; charset=utf-8"
+        }
+        "js" => "text/javascript; charset=utf-8",
+        "ts" => "text/typescript; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "json" => "application/json",
+        "yaml" | "yml" => "text/yaml; charset=utf-8",
+        "xml" => "application/xml",
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, end: u64) -> RangeRequest {
+        RangeRequest::Partial(ByteRange { start, end })
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-9", 100), range(0, 9));
+        assert_eq!(parse_range("bytes=90-", 100), range(90, 99));
+        assert_eq!(parse_range("bytes=-10", 100), range(90, 99));
+        assert_eq!(parse_range("bytes=-500", 100), range(0, 99));
+        assert_eq!(parse_range("bytes=50-500", 100), range(50, 99));
+        assert_eq!(parse_range("bytes=100-", 100), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 100), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-1,5-6", 100), RangeRequest::Full);
+        assert_eq!(parse_range("bytes=9-0", 100), RangeRequest::Full);
+        assert_eq!(parse_range("bytes=a-b", 100), RangeRequest::Full);
+        assert_eq!(parse_range("items=0-9", 100), RangeRequest::Full);
+    }
+
+    #[test]
+    fn test_requested_range_if_range() {
+        let etag = "\"abc\"";
+        let mut headers = HeaderMap::new();
+        assert_eq!(requested_range(&headers, 100, etag), RangeRequest::Full);
+
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=0-9"));
+        assert_eq!(requested_range(&headers, 100, etag), range(0, 9));
+
+        headers.insert(header::IF_RANGE, HeaderValue::from_static("\"abc\""));
+        assert_eq!(requested_range(&headers, 100, etag), range(0, 9));
+
+        headers.insert(header::IF_RANGE, HeaderValue::from_static("\"def\""));
+        assert_eq!(requested_range(&headers, 100, etag), RangeRequest::Full);
+
+        headers.insert(
+            header::IF_RANGE,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(requested_range(&headers, 100, etag), RangeRequest::Full);
+    }
+}
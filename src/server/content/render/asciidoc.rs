@@ -0,0 +1,616 @@
+//! AsciiDoc: section titles, paragraphs, delimited blocks, lists,
+//! description lists, tables, admonitions, and inline formatting, links,
+//! and cross references.
+
+use super::{
+    Links, Slugs, bare_url_len, escape, indent_of, push_code_block, push_heading, push_image,
+    push_link, scheme,
+};
+
+struct Renderer<'a> {
+    links: &'a Links<'a>,
+    slugs: Slugs,
+}
+
+pub(super) fn render(source: &str, links: &Links<'_>) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut renderer = Renderer {
+        links,
+        slugs: Slugs::default(),
+    };
+    let mut out = String::new();
+    renderer.blocks(&lines, &mut out);
+    out
+}
+
+const ADMONITIONS: &[(&str, &str)] = &[
+    ("NOTE: ", "Note"),
+    ("TIP: ", "Tip"),
+    ("IMPORTANT: ", "Important"),
+    ("WARNING: ", "Warning"),
+    ("CAUTION: ", "Caution"),
+];
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let marker = line.chars().next().filter(|c| *c == '=' || *c == '#')?;
+    let level = line.len() - line.trim_start_matches(marker).len();
+    let text = line[level..].strip_prefix(' ')?.trim();
+    ((1..=6).contains(&level) && !text.is_empty()).then_some((level, text))
+}
+
+/// The closing line of a delimited block opened by `line`, if it opens one.
+fn delimiter(line: &str) -> Option<&str> {
+    let line = line.trim_end();
+    let c = line.chars().next()?;
+    if line == "--" || line.starts_with("```") {
+        return Some(if c == '-' { "--" } else { "```" });
+    }
+    (matches!(c, '-' | '.' | '_' | '=' | '*' | '+' | '/')
+        && line.len() >= 4
+        && line.chars().all(|x| x == c))
+    .then_some(line)
+}
+
+struct ListItem<'s> {
+    ordered: bool,
+    level: usize,
+    lines: Vec<&'s str>,
+}
+
+fn list_item(line: &str) -> Option<(bool, usize, &str)> {
+    let line = line.trim_start();
+    let c = line.chars().next()?;
+    let (ordered, level, rest) = match c {
+        '*' | '.' => {
+            let level = line.len() - line.trim_start_matches(c).len();
+            (c == '.', level, &line[level..])
+        }
+        '-' => (false, 1, &line[1..]),
+        c if c.is_ascii_digit() => {
+            let (number, rest) = line.split_once('.')?;
+            if !number.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            (true, 1, rest)
+        }
+        _ => return None,
+    };
+    let text = rest.strip_prefix(' ')?.trim();
+    (level <= 5 && !text.is_empty()).then_some((ordered, level, text))
+}
+
+fn description_term(line: &str) -> Option<(&str, &str)> {
+    let (term, definition) = match line.split_once(":: ") {
+        Some(split) => split,
+        None => (line.trim_end().strip_suffix("::")?, ""),
+    };
+    (!term.trim().is_empty() && !term.contains("::")).then(|| (term.trim(), definition.trim()))
+}
+
+/// Splits `target[text]` at the start of `text`, as in `link:` and
+/// `image:` macros: the length, target, and bracketed text.
+fn macro_target(text: &str) -> Option<(usize, &str, &str)> {
+    let open = text.find('[')?;
+    let target = &text[..open];
+    if target.is_empty() || target.contains(char::is_whitespace) {
+        return None;
+    }
+    let close = open + text[open..].find(']')?;
+    Some((close + 1, target, &text[open + 1..close]))
+}
+
+impl Renderer<'_> {
+    fn blocks(&mut self, lines: &[&str], out: &mut String) {
+        let mut paragraph: Vec<&str> = Vec::new();
+        let mut language: Option<String> = None;
+        let mut title: Option<&str> = None;
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() {
+                self.paragraph(&mut paragraph, out);
+                i += 1;
+                continue;
+            }
+            if !paragraph.is_empty() {
+                if list_item(line).is_some() || delimiter(line).is_some() {
+                    self.paragraph(&mut paragraph, out);
+                } else {
+                    paragraph.push(trimmed);
+                    i += 1;
+                    continue;
+                }
+            }
+
+            if trimmed == "<<<" || (trimmed.starts_with("//") && delimiter(trimmed).is_none()) {
+                i += 1;
+                continue;
+            }
+            if let Some(close) = delimiter(line) {
+                let end = (i + 1..lines.len())
+                    .find(|&n| lines[n].trim_end() == close)
+                    .unwrap_or(lines.len());
+                let body = &lines[i + 1..end];
+                if let Some(title) = title.take() {
+                    out.push_str(&format!(
+                        "<div class=\"title\">{}</div>\n",
+                        self.inline(title)
+                    ));
+                }
+                match close.chars().next() {
+                    Some('`') => {
+                        let fence_language = trimmed.trim_start_matches('`').trim();
+                        let fence_language = Some(fence_language).filter(|l| !l.is_empty());
+                        push_code_block(out, fence_language.or(language.as_deref()), body);
+                    }
+                    Some('-') if close == "--" => self.blocks(body, out),
+                    Some('-') => push_code_block(out, language.as_deref(), body),
+                    Some('.' | '+') => push_code_block(out, None, body),
+                    Some('_') => {
+                        out.push_str("<blockquote>\n");
+                        self.blocks(body, out);
+                        out.push_str("</blockquote>\n");
+                    }
+                    Some('=') => {
+                        out.push_str("<div class=\"example\">\n");
+                        self.blocks(body, out);
+                        out.push_str("</div>\n");
+                    }
+                    Some('*') => {
+                        out.push_str("<div class=\"sidebar\">\n");
+                        self.blocks(body, out);
+                        out.push_str("</div>\n");
+                    }
+                    _ => {}
+                }
+                language = None;
+                i = end + 1;
+                continue;
+            }
+            if trimmed == "|===" {
+                let end = (i + 1..lines.len())
+                    .find(|&n| lines[n].trim() == "|===")
+                    .unwrap_or(lines.len());
+                self.table(&lines[i + 1..end], out);
+                i = end + 1;
+                continue;
+            }
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                let attributes: Vec<&str> = trimmed[1..trimmed.len() - 1]
+                    .split(',')
+                    .map(str::trim)
+                    .collect();
+                if attributes[0] == "source" || attributes[0].starts_with("source%") {
+                    language = attributes.get(1).map(|l| l.to_string());
+                }
+                i += 1;
+                continue;
+            }
+            if trimmed.starts_with(':')
+                && trimmed[1..].contains(':')
+                && !trimmed[1..].starts_with(' ')
+            {
+                i += 1;
+                continue;
+            }
+            if let Some(rest) = trimmed
+                .strip_prefix('.')
+                .filter(|r| r.starts_with(|c: char| c.is_alphanumeric()))
+            {
+                title = Some(rest);
+                i += 1;
+                continue;
+            }
+            if let Some((level, text)) = heading(trimmed) {
+                let html = self.inline(text);
+                push_heading(out, &mut self.slugs, level, text, &html);
+            } else if trimmed == "'''" || trimmed == "---" || trimmed == "***" {
+                out.push_str("<hr>\n");
+            } else if let Some(rest) = trimmed.strip_prefix("image::") {
+                let (_, target, alt) = macro_target(rest).unwrap_or((0, rest, ""));
+                out.push_str("<p>");
+                push_image(out, self.links, target, alt);
+                out.push_str("</p>\n");
+            } else if list_item(line).is_some() {
+                i = self.list(lines, i, out);
+                continue;
+            } else if let Some((term, definition)) = description_term(trimmed) {
+                i = self.description_list(lines, i, term, definition, out);
+                continue;
+            } else if indent_of(line) > 0 {
+                let end = (i..lines.len())
+                    .find(|&n| lines[n].trim().is_empty())
+                    .unwrap_or(lines.len());
+                let indent = lines[i..end]
+                    .iter()
+                    .map(|l| indent_of(l))
+                    .min()
+                    .unwrap_or(0);
+                let code: Vec<&str> = lines[i..end].iter().map(|l| &l[indent..]).collect();
+                push_code_block(out, None, &code);
+                i = end;
+                continue;
+            } else {
+                if let Some(title) = title.take() {
+                    out.push_str(&format!(
+                        "<div class=\"title\">{}</div>\n",
+                        self.inline(title)
+                    ));
+                }
+                paragraph.push(trimmed);
+            }
+            i += 1;
+        }
+        self.paragraph(&mut paragraph, out);
+    }
+
+    fn paragraph(&mut self, lines: &mut Vec<&str>, out: &mut String) {
+        if lines.is_empty() {
+            return;
+        }
+        let text = lines.join("\n");
+        lines.clear();
+        let admonition = ADMONITIONS
+            .iter()
+            .find_map(|(prefix, label)| Some((text.strip_prefix(prefix)?, *label)));
+        match admonition {
+            Some((rest, label)) => out.push_str(&format!(
+                "<div class=\"admonition {}\">\n<p><strong>{label}:</strong> {}</p>\n</div>\n",
+                label.to_lowercase(),
+                self.inline(rest)
+            )),
+            None => out.push_str(&format!("<p>{}</p>\n", self.inline(&text))),
+        }
+    }
+
+    fn list(&mut self, lines: &[&str], start: usize, out: &mut String) -> usize {
+        let mut items: Vec<ListItem<'_>> = Vec::new();
+        let mut i = start;
+        while i < lines.len() {
+            let line = lines[i];
+            if let Some((ordered, level, text)) = list_item(line) {
+                items.push(ListItem {
+                    ordered,
+                    level,
+                    lines: vec![text],
+                });
+            } else if line.trim() == "+" {
+                // A list continuation joins the next paragraph to the item.
+                let item = items.last_mut().expect("list has an item");
+                item.lines.push("");
+                i += 1;
+                while i < lines.len() && !lines[i].trim().is_empty() {
+                    item.lines.push(lines[i].trim());
+                    i += 1;
+                }
+                continue;
+            } else if line.trim().is_empty() {
+                let next = lines[i..].iter().position(|l| !l.trim().is_empty());
+                match next {
+                    Some(n)
+                        if list_item(lines[i + n]).is_some_and(|(ordered, level, _)| {
+                            items
+                                .iter()
+                                .any(|m| m.ordered == ordered && m.level == level)
+                        }) =>
+                    {
+                        i += n;
+                        continue;
+                    }
+                    _ => break,
+                }
+            } else if delimiter(line).is_some() || heading(line.trim()).is_some() {
+                break;
+            } else {
+                let item = items.last_mut().expect("list has an item");
+                item.lines.push(line.trim());
+            }
+            i += 1;
+        }
+
+        // Each distinct marker opens a deeper list, as AsciiDoc nests by
+        // marker rather than indentation.
+        let mut open: Vec<(bool, usize)> = Vec::new();
+        for item in items {
+            let marker = (item.ordered, item.level);
+            match open.iter().position(|m| *m == marker) {
+                Some(depth) => {
+                    while open.len() > depth + 1 {
+                        let (ordered, _) = open.pop().expect("list is open");
+                        out.push_str(if ordered {
+                            "</li>\n</ol>\n"
+                        } else {
+                            "</li>\n</ul>\n"
+                        });
+                    }
+                    out.push_str("</li>\n");
+                }
+                None => {
+                    out.push_str(if item.ordered { "<ol>\n" } else { "<ul>\n" });
+                    open.push(marker);
+                }
+            }
+            out.push_str("<li>");
+            let mut paragraphs = item.lines.split(|l| l.is_empty());
+            if let Some(first) = paragraphs.next() {
+                out.push_str(&self.inline(&first.join("\n")));
+                out.push('\n');
+            }
+            for paragraph in paragraphs {
+                out.push_str(&format!("<p>{}</p>\n", self.inline(&paragraph.join("\n"))));
+            }
+        }
+        while let Some((ordered, _)) = open.pop() {
+            out.push_str(if ordered {
+                "</li>\n</ol>\n"
+            } else {
+                "</li>\n</ul>\n"
+            });
+        }
+        i
+    }
+
+    fn description_list(
+        &mut self,
+        lines: &[&str],
+        start: usize,
+        term: &str,
+        definition: &str,
+        out: &mut String,
+    ) -> usize {
+        out.push_str("<dl>\n");
+        let (mut term, mut definition) = (term, vec![definition]);
+        let mut i = start + 1;
+        loop {
+            while i < lines.len() && !lines[i].trim().is_empty() {
+                if description_term(lines[i].trim()).is_some() {
+                    break;
+                }
+                definition.push(lines[i].trim());
+                i += 1;
+            }
+            let text = definition.join("\n");
+            out.push_str(&format!(
+                "<dt>{}</dt>\n<dd>{}</dd>\n",
+                self.inline(term),
+                self.inline(text.trim())
+            ));
+
+            let next = lines[i..].iter().position(|l| !l.trim().is_empty());
+            match next.and_then(|n| Some((i + n, description_term(lines[i + n].trim())?))) {
+                Some((n, (next_term, next_definition))) => {
+                    term = next_term;
+                    definition = vec![next_definition];
+                    i = n + 1;
+                }
+                None => break,
+            }
+        }
+        out.push_str("</dl>\n");
+        i
+    }
+
+    fn table(&mut self, lines: &[&str], out: &mut String) {
+        let cells = |line: &str| -> Vec<String> {
+            line.split('|')
+                .skip(1)
+                .map(|cell| cell.trim().to_string())
+                .collect()
+        };
+        let Some(first) = lines.iter().position(|l| !l.trim().is_empty()) else {
+            return;
+        };
+        let columns = cells(lines[first]).len().max(1);
+        let has_header = lines.get(first + 1).is_some_and(|l| l.trim().is_empty());
+
+        let all: Vec<String> = lines[first..].iter().flat_map(|l| cells(l)).collect();
+        let mut rows = all.chunks(columns);
+        out.push_str("<table>\n");
+        if has_header {
+            if let Some(header) = rows.next() {
+                out.push_str("<thead>\n<tr>");
+                for cell in header {
+                    out.push_str(&format!("<th>{}</th>", self.inline(cell)));
+                }
+                out.push_str("</tr>\n</thead>\n");
+            }
+        }
+        out.push_str("<tbody>\n");
+        for row in rows {
+            out.push_str("<tr>");
+            for cell in row {
+                out.push_str(&format!("<td>{}</td>", self.inline(cell)));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</tbody>\n</table>\n");
+    }
+
+    fn inline(&self, text: &str) -> String {
+        let mut out = String::new();
+        let mut i = 0;
+        while i < text.len() {
+            let rest = &text[i..];
+            let c = rest.chars().next().expect("not at end");
+            let prev = text[..i].chars().next_back();
+            let boundary = prev.is_none_or(|p| !p.is_alphanumeric());
+
+            if c == '\\' && rest[1..].starts_with(['*', '_', '`', '+', '<', '{', '#']) {
+                out.push_str(&escape(&rest[1..2]));
+                i += 2;
+                continue;
+            }
+            if c == '\n' && out.ends_with(" +") {
+                out.truncate(out.len() - 2);
+                out.push_str("<br>\n");
+                i += 1;
+                continue;
+            }
+            if let Some((len, html)) = self.formatted(rest, boundary) {
+                out.push_str(&html);
+                i += len;
+                continue;
+            }
+            if boundary {
+                if let Some((len, html)) = self.link(rest) {
+                    out.push_str(&html);
+                    i += len;
+                    continue;
+                }
+            }
+            out.push_str(&escape(c.encode_utf8(&mut [0; 4])));
+            i += c.len_utf8();
+        }
+        out
+    }
+
+    /// Parses bold, italic, monospace, or passthrough text at the start of
+    /// `text`: its length and HTML.
+    fn formatted(&self, text: &str, boundary: bool) -> Option<(usize, String)> {
+        let c = text.chars().next()?;
+        let (open, close) = match c {
+            '*' => ("<strong>", "</strong>"),
+            '_' => ("<em>", "</em>"),
+            '`' => ("<code>", "</code>"),
+            '+' => ("", ""),
+            _ => return None,
+        };
+        let double: String = [c, c].iter().collect();
+        let (width, end) = if c != '+' && text.starts_with(double.as_str()) {
+            let body = &text[2..];
+            (2, body.find(double.as_str()).filter(|e| *e > 0)?)
+        } else {
+            if !boundary {
+                return None;
+            }
+            let body = &text[1..];
+            if body.starts_with(char::is_whitespace) {
+                return None;
+            }
+            let mut search = 0;
+            loop {
+                let end = search + body[search..].find(c)?;
+                let before = body[..end].chars().next_back();
+                let after = body[end + 1..].chars().next();
+                if end > 0
+                    && before.is_some_and(|b| !b.is_whitespace())
+                    && after.is_none_or(|a| !a.is_alphanumeric())
+                {
+                    break (1, end);
+                }
+                search = end + 1;
+            }
+        };
+        let inner = &text[width..width + end];
+        let html = match c {
+            '`' | '+' => escape(inner),
+            _ => self.inline(inner),
+        };
+        Some((width * 2 + end, format!("{open}{html}{close}")))
+    }
+
+    /// Parses a URL, `link:`, `mailto:`, `image:`, `xref:`, or `<<id>>`
+    /// reference at the start of `text`: its length and HTML.
+    fn link(&self, text: &str) -> Option<(usize, String)> {
+        let mut html = String::new();
+        if let Some(rest) = text.strip_prefix("<<") {
+            let end = rest.find(">>")?;
+            let (id, label) = match rest[..end].split_once(',') {
+                Some((id, label)) => (id.trim(), label.trim()),
+                None => (rest[..end].trim(), rest[..end].trim()),
+            };
+            let target = match id.split_once('#') {
+                Some((file, fragment)) => format!("{file}#{fragment}"),
+                None if id.contains('.') => id.to_string(),
+                None => format!("#{id}"),
+            };
+            push_link(&mut html, self.links, &target, &escape(label));
+            return Some((2 + end + 2, html));
+        }
+        if let Some(rest) = text.strip_prefix("image:").filter(|r| !r.starts_with(':')) {
+            let (len, target, alt) = macro_target(rest)?;
+            push_image(&mut html, self.links, target, alt);
+            return Some(("image:".len() + len, html));
+        }
+        for prefix in ["link:", "xref:"] {
+            if let Some(rest) = text.strip_prefix(prefix) {
+                let (len, target, label) = macro_target(rest)?;
+                let label = if label.is_empty() { target } else { label };
+                push_link(&mut html, self.links, target, &self.inline(label));
+                return Some((prefix.len() + len, html));
+            }
+        }
+        if text.starts_with("mailto:") || bare_url_len(text).is_some() {
+            if let Some((len, target, label)) = macro_target(text).filter(|(_, target, _)| {
+                scheme(target).is_some() && !target.contains(['<', '>', '"'])
+            }) {
+                let label = if label.is_empty() { target } else { label };
+                push_link(&mut html, self.links, target, &self.inline(label));
+                return Some((len, html));
+            }
+            let len = bare_url_len(text)?;
+            push_link(&mut html, self.links, &text[..len], &escape(&text[..len]));
+            return Some((len, html));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn html(source: &str) -> String {
+        let links = Links {
+            repo_id: "r1",
+            ref_name: "main",
+            dir: "",
+        };
+        render(source, &links)
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(
+            html(
+                "= Title\n:toc:\n\nIntro with *bold* and `code`.\n\n\
+                 [source,rust]\n----\nfn main() {}\n----\n\nNOTE: Read this.\n"
+            ),
+            "<h1 id=\"title\">Title</h1>\n\
+             <p>Intro with <strong>bold</strong> and <code>code</code>.</p>\n\
+             <pre><code class=\"language-rust\">fn main() {}\n</code></pre>\n\
+             <div class=\"admonition note\">\n<p><strong>Note:</strong> Read this.</p>\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn test_lists_and_tables() {
+        assert_eq!(
+            html("* one\n** nested\n* two\n\n. first\n. second\n"),
+            "<ul>\n<li>one\n<ul>\n<li>nested\n</li>\n</ul>\n</li>\n<li>two\n</li>\n</ul>\n\
+             <ol>\n<li>first\n</li>\n<li>second\n</li>\n</ol>\n"
+        );
+        assert_eq!(
+            html("|===\n|Name |Size\n\n|a |1\n|===\n"),
+            "<table>\n<thead>\n<tr><th>Name</th><th>Size</th></tr>\n</thead>\n\
+             <tbody>\n<tr><td>a</td><td>1</td></tr>\n</tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn test_links() {
+        assert_eq!(
+            html(
+                "See link:docs/guide.adoc[the guide], https://example.com[home], and <<setup>>.\n"
+            ),
+            "<p>See <a href=\"/api/v1/repos/r1/render/main/docs/guide.adoc\">the guide</a>, \
+             <a href=\"https://example.com\">home</a>, and <a href=\"#setup\">setup</a>.</p>\n"
+        );
+        assert_eq!(
+            html("link:javascript:alert(1)[click] <script>\n"),
+            "<p>click &lt;script&gt;</p>\n"
+        );
+    }
+}
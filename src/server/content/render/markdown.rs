@@ -0,0 +1,837 @@
+//! Markdown, following CommonMark for blocks and inlines plus GitHub's
+//! tables, task lists, strikethrough, and bare URLs.
+
+use std::collections::HashMap;
+
+use super::{
+    Links, Slugs, bare_url_len, dedent, escape, indent_of, push_code_block, push_heading,
+    push_image, push_link,
+};
+
+struct Renderer<'a> {
+    links: &'a Links<'a>,
+    /// Link reference definitions, keyed by normalized label.
+    refs: HashMap<String, String>,
+    slugs: Slugs,
+}
+
+pub(super) fn render(source: &str, links: &Links<'_>) -> String {
+    let mut refs = HashMap::new();
+    let lines = take_definitions(source, &mut refs);
+    let mut renderer = Renderer {
+        links,
+        refs,
+        slugs: Slugs::default(),
+    };
+    let mut out = String::new();
+    renderer.blocks(&lines, false, &mut out);
+    out
+}
+
+/// Removes `[label]: target` lines outside code blocks, collecting them.
+fn take_definitions<'s>(source: &'s str, refs: &mut HashMap<String, String>) -> Vec<&'s str> {
+    let mut lines = Vec::new();
+    let mut fence: Option<(char, usize)> = None;
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if let Some((c, len)) = fence {
+            if closes_fence(trimmed, c, len) {
+                fence = None;
+            }
+            lines.push(line);
+            continue;
+        }
+        if let Some((c, len, _)) = open_fence(trimmed) {
+            fence = Some((c, len));
+        } else if indent_of(line) < 4 {
+            if let Some((label, target)) = parse_definition(trimmed) {
+                refs.entry(label).or_insert(target);
+                continue;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+fn parse_definition(line: &str) -> Option<(String, String)> {
+    let rest = line.strip_prefix('[')?;
+    let (label, rest) = rest.split_once("]:")?;
+    if label.trim().is_empty() || label.contains('[') {
+        return None;
+    }
+    let target = rest.split_whitespace().next()?;
+    let target = target
+        .strip_prefix('<')
+        .and_then(|t| t.strip_suffix('>'))
+        .unwrap_or(target);
+    Some((normalize_label(label), target.to_string()))
+}
+
+fn normalize_label(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// The fence character, its run length, and the info string.
+fn open_fence(line: &str) -> Option<(char, usize, &str)> {
+    let c = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.len() - line.trim_start_matches(c).len();
+    let info = line[len..].trim();
+    (len >= 3 && !(c == '`' && info.contains('`'))).then_some((c, len, info))
+}
+
+fn closes_fence(line: &str, c: char, len: usize) -> bool {
+    let run = line.len() - line.trim_start_matches(c).len();
+    run >= len && line[run..].trim().is_empty()
+}
+
+fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.len() - line.trim_start_matches('#').len();
+    let rest = &line[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+    let text = rest.trim();
+    let without_closing = text.trim_end_matches('#');
+    let text = if without_closing.is_empty() || without_closing.ends_with(' ') {
+        without_closing.trim_end()
+    } else {
+        text
+    };
+    Some((level, text))
+}
+
+fn setext_level(line: &str) -> Option<usize> {
+    let line = line.trim_end();
+    if !line.is_empty() && line.chars().all(|c| c == '=') {
+        Some(1)
+    } else if !line.is_empty() && line.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn is_thematic_break(line: &str) -> bool {
+    let mut chars = line.chars().filter(|c| *c != ' ');
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    matches!(first, '*' | '-' | '_')
+        && line.chars().filter(|c| *c == first).count() >= 3
+        && chars.all(|c| c == first)
+}
+
+struct ListMarker {
+    ordered: bool,
+    /// The bullet, or the `.` or `)` after an ordered item's number.
+    delimiter: char,
+    start: u64,
+    /// Column the item's content starts at.
+    content_indent: usize,
+}
+
+fn list_marker(line: &str) -> Option<ListMarker> {
+    let indent = indent_of(line);
+    if indent >= 4 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let (ordered, delimiter, start, marker_len) = match rest.chars().next()? {
+        c @ ('-' | '*' | '+') => (false, c, 1, 1),
+        c if c.is_ascii_digit() => {
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let delimiter = rest[digits..].chars().next()?;
+            if digits > 9 || !matches!(delimiter, '.' | ')') {
+                return None;
+            }
+            (true, delimiter, rest[..digits].parse().ok()?, digits + 1)
+        }
+        _ => return None,
+    };
+    let after = &rest[marker_len..];
+    if !after.is_empty() && !after.starts_with(' ') {
+        return None;
+    }
+    let spaces = indent_of(after);
+    let spaces = if after.trim().is_empty() || spaces > 4 {
+        1
+    } else {
+        spaces
+    };
+    Some(ListMarker {
+        ordered,
+        delimiter,
+        start,
+        content_indent: indent + marker_len + spaces,
+    })
+}
+
+fn table_cells(line: &str) -> Vec<&str> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    let mut cells = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '|' if !escaped => {
+                cells.push(line[start..i].trim());
+                start = i + 1;
+            }
+            _ => escaped = false,
+        }
+    }
+    cells.push(line[start..].trim());
+    cells
+}
+
+/// Column alignments from a table's delimiter row.
+fn table_alignments(line: &str) -> Option<Vec<Option<&'static str>>> {
+    if !line.contains('-') {
+        return None;
+    }
+    table_cells(line)
+        .into_iter()
+        .map(|cell| {
+            let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+                (true, true) => Some("center"),
+                (true, false) => Some("left"),
+                (false, true) => Some("right"),
+                (false, false) => None,
+            })
+        })
+        .collect()
+}
+
+/// Whether `line` starts a block that ends a paragraph.
+fn interrupts_paragraph(line: &str) -> bool {
+    if indent_of(line) >= 4 {
+        return false;
+    }
+    let trimmed = line.trim_start();
+    trimmed.starts_with('>')
+        || open_fence(trimmed).is_some()
+        || atx_heading(trimmed).is_some()
+        || is_thematic_break(trimmed)
+        || list_marker(line).is_some_and(|m| {
+            !line[m.content_indent.min(line.len())..].trim().is_empty()
+                && (!m.ordered || m.start == 1)
+        })
+}
+
+impl Renderer<'_> {
+    /// Renders block-level content. In a tight list item, paragraphs are
+    /// written without `<p>` tags.
+    fn blocks(&mut self, lines: &[&str], tight: bool, out: &mut String) {
+        let mut paragraph: Vec<&str> = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            let trimmed = line.trim_start();
+            let indent = indent_of(line);
+
+            if trimmed.is_empty() {
+                self.paragraph(&mut paragraph, tight, out);
+                i += 1;
+                continue;
+            }
+            if !paragraph.is_empty() && indent < 4 {
+                if let Some(level) = setext_level(trimmed) {
+                    let text = paragraph.join("\n");
+                    let html = self.inline(&text);
+                    push_heading(out, &mut self.slugs, level, &text, &html);
+                    paragraph.clear();
+                    i += 1;
+                    continue;
+                }
+            }
+            if indent >= 4 {
+                if paragraph.is_empty() {
+                    let mut end = i;
+                    while end < lines.len()
+                        && (indent_of(lines[end]) >= 4 || lines[end].trim().is_empty())
+                    {
+                        end += 1;
+                    }
+                    while lines[end - 1].trim().is_empty() {
+                        end -= 1;
+                    }
+                    let code: Vec<&str> = lines[i..end].iter().map(|l| dedent(l, 4)).collect();
+                    push_code_block(out, None, &code);
+                    i = end;
+                } else {
+                    paragraph.push(trimmed);
+                    i += 1;
+                }
+                continue;
+            }
+            if !paragraph.is_empty() && !interrupts_paragraph(line) {
+                paragraph.push(trimmed);
+                i += 1;
+                continue;
+            }
+            self.paragraph(&mut paragraph, tight, out);
+
+            if let Some((c, len, info)) = open_fence(trimmed) {
+                let mut end = i + 1;
+                while end < lines.len() && !closes_fence(lines[end].trim_start(), c, len) {
+                    end += 1;
+                }
+                let code: Vec<&str> = lines[i + 1..end]
+                    .iter()
+                    .map(|l| dedent(l, indent))
+                    .collect();
+                push_code_block(out, info.split_whitespace().next(), &code);
+                i = end + 1;
+            } else if let Some((level, text)) = atx_heading(trimmed) {
+                let html = self.inline(text);
+                push_heading(out, &mut self.slugs, level, text, &html);
+                i += 1;
+            } else if is_thematic_break(trimmed) {
+                out.push_str("<hr>\n");
+                i += 1;
+            } else if trimmed.starts_with('>') {
+                i = self.blockquote(lines, i, out);
+            } else if let Some(marker) = list_marker(line) {
+                i = self.list(lines, i, marker, out);
+            } else if let Some(end) = self.table(lines, i, out) {
+                i = end;
+            } else {
+                paragraph.push(trimmed);
+                i += 1;
+            }
+        }
+        self.paragraph(&mut paragraph, tight, out);
+    }
+
+    fn paragraph(&mut self, lines: &mut Vec<&str>, tight: bool, out: &mut String) {
+        if lines.is_empty() {
+            return;
+        }
+        let html = self.inline(lines.join("\n").trim_end());
+        if tight {
+            out.push_str(&html);
+            out.push('\n');
+        } else {
+            out.push_str(&format!("<p>{html}</p>\n"));
+        }
+        lines.clear();
+    }
+
+    fn blockquote(&mut self, lines: &[&str], start: usize, out: &mut String) -> usize {
+        let mut inner = Vec::new();
+        let mut i = start;
+        while i < lines.len() {
+            let trimmed = lines[i].trim_start();
+            if let Some(rest) = trimmed.strip_prefix('>') {
+                inner.push(rest.strip_prefix(' ').unwrap_or(rest));
+            } else if !trimmed.is_empty()
+                && inner.last().is_some_and(|l: &&str| !l.trim().is_empty())
+                && !interrupts_paragraph(lines[i])
+            {
+                inner.push(trimmed);
+            } else {
+                break;
+            }
+            i += 1;
+        }
+        out.push_str("<blockquote>\n");
+        self.blocks(&inner, false, out);
+        out.push_str("</blockquote>\n");
+        i
+    }
+
+    fn list(&mut self, lines: &[&str], start: usize, first: ListMarker, out: &mut String) -> usize {
+        let (ordered, delimiter, number) = (first.ordered, first.delimiter, first.start);
+        let mut items: Vec<Vec<&str>> = Vec::new();
+        let mut loose = false;
+        let mut marker = Some(first);
+        let mut indent = 0;
+        let mut i = start;
+        while i < lines.len() {
+            let line = lines[i];
+            if let Some(m) = marker.take() {
+                indent = m.content_indent;
+                items.push(vec![line.get(indent..).unwrap_or("")]);
+                i += 1;
+                continue;
+            }
+            let item = items.last_mut().expect("list has an item");
+            if line.trim().is_empty() {
+                item.push("");
+            } else if indent_of(line) >= indent {
+                if item.last() == Some(&"") && item.iter().any(|l| !l.is_empty()) {
+                    loose = true;
+                }
+                item.push(&line[indent..]);
+            } else if let Some(m) =
+                list_marker(line).filter(|m| m.ordered == ordered && m.delimiter == delimiter)
+            {
+                if item.last() == Some(&"") {
+                    loose = true;
+                }
+                marker = Some(m);
+                continue;
+            } else if item.last() != Some(&"") && !interrupts_paragraph(line) {
+                item.push(line.trim_start());
+            } else {
+                break;
+            }
+            i += 1;
+        }
+        for item in &mut items {
+            while item.len() > 1 && item.last() == Some(&"") {
+                item.pop();
+            }
+        }
+        // Blank lines a list ends on belong to whatever comes next.
+        while i > start && lines[i - 1].trim().is_empty() {
+            i -= 1;
+        }
+
+        if ordered {
+            if number == 1 {
+                out.push_str("<ol>\n");
+            } else {
+                out.push_str(&format!("<ol start=\"{number}\">\n"));
+            }
+        } else {
+            out.push_str("<ul>\n");
+        }
+        for item in items {
+            out.push_str("<li>");
+            let (checkbox, item) = task_checkbox(item);
+            if let Some(checked) = checkbox {
+                out.push_str(if checked {
+                    "<input type=\"checkbox\" checked disabled> "
+                } else {
+                    "<input type=\"checkbox\" disabled> "
+                });
+            }
+            self.blocks(&item, !loose, out);
+            out.push_str("</li>\n");
+        }
+        out.push_str(if ordered { "</ol>\n" } else { "</ul>\n" });
+        i
+    }
+
+    fn table(&mut self, lines: &[&str], start: usize, out: &mut String) -> Option<usize> {
+        let header = table_cells(lines[start]);
+        let alignments = table_alignments(lines.get(start + 1)?)?;
+        if !lines[start].contains('|') || alignments.len() != header.len() {
+            return None;
+        }
+        let cell = |tag: &str, i: usize, html: String| match alignments.get(i).copied().flatten() {
+            Some(align) => format!("<{tag} align=\"{align}\">{html}</{tag}>"),
+            None => format!("<{tag}>{html}</{tag}>"),
+        };
+
+        out.push_str("<table>\n<thead>\n<tr>");
+        for (i, text) in header.iter().enumerate() {
+            out.push_str(&cell("th", i, self.inline(text)));
+        }
+        out.push_str("</tr>\n</thead>\n");
+        let mut i = start + 2;
+        let mut body = String::new();
+        while i < lines.len() && lines[i].contains('|') && !interrupts_paragraph(lines[i]) {
+            let cells = table_cells(lines[i]);
+            body.push_str("<tr>");
+            for column in 0..header.len() {
+                let html = cells
+                    .get(column)
+                    .map(|c| self.inline(c))
+                    .unwrap_or_default();
+                body.push_str(&cell("td", column, html));
+            }
+            body.push_str("</tr>\n");
+            i += 1;
+        }
+        if !body.is_empty() {
+            out.push_str(&format!("<tbody>\n{body}</tbody>\n"));
+        }
+        out.push_str("</table>\n");
+        Some(i)
+    }
+
+    fn inline(&self, text: &str) -> String {
+        let mut out = String::new();
+        let mut i = 0;
+        while i < text.len() {
+            let rest = &text[i..];
+            let c = rest.chars().next().expect("not at end");
+            let prev = text[..i].chars().next_back();
+            match c {
+                '\\' => {
+                    let next = rest[1..].chars().next();
+                    match next {
+                        Some('\n') => {
+                            out.push_str("<br>\n");
+                            i += 2;
+                        }
+                        Some(n) if n.is_ascii_punctuation() => {
+                            out.push_str(&escape(&n.to_string()));
+                            i += 2;
+                        }
+                        _ => {
+                            out.push('\\');
+                            i += 1;
+                        }
+                    }
+                }
+                '`' => {
+                    let run = rest.len() - rest.trim_start_matches('`').len();
+                    match find_backtick_run(&rest[run..], run) {
+                        Some(end) => {
+                            let code = rest[run..run + end].replace('\n', " ");
+                            let code = if code.len() > 1
+                                && code.starts_with(' ')
+                                && code.ends_with(' ')
+                                && !code.trim().is_empty()
+                            {
+                                &code[1..code.len() - 1]
+                            } else {
+                                &code
+                            };
+                            out.push_str(&format!("<code>{}</code>", escape(code)));
+                            i += run + end + run;
+                        }
+                        None => {
+                            out.push_str(&rest[..run]);
+                            i += run;
+                        }
+                    }
+                }
+                '!' if rest[1..].starts_with('[') => match self.link(&rest[1..]) {
+                    Some((len, label, target)) => {
+                        let alt = plain_text(label);
+                        match target {
+                            Some(target) => push_image(&mut out, self.links, &target, &alt),
+                            None => out.push_str(&escape(&alt)),
+                        }
+                        i += 1 + len;
+                    }
+                    None => {
+                        out.push('!');
+                        i += 1;
+                    }
+                },
+                '[' => match self.link(rest) {
+                    Some((len, label, Some(target))) => {
+                        let html = self.inline(label);
+                        push_link(&mut out, self.links, &target, &html);
+                        i += len;
+                    }
+                    _ => {
+                        out.push('[');
+                        i += 1;
+                    }
+                },
+                '<' => match autolink(rest) {
+                    Some((len, target)) => {
+                        push_link(&mut out, self.links, &target, &escape(&rest[1..len - 1]));
+                        i += len;
+                    }
+                    None => {
+                        out.push_str("&lt;");
+                        i += 1;
+                    }
+                },
+                '*' | '_' | '~' => match self.emphasis(rest, prev) {
+                    Some((len, html)) => {
+                        out.push_str(&html);
+                        i += len;
+                    }
+                    None => {
+                        let run = rest.len() - rest.trim_start_matches(c).len();
+                        out.push_str(&rest[..run]);
+                        i += run;
+                    }
+                },
+                '\n' => {
+                    if text[..i].ends_with("  ") {
+                        out.truncate(out.trim_end_matches(' ').len());
+                        out.push_str("<br>");
+                    }
+                    out.push('\n');
+                    i += 1;
+                }
+                'h' if prev.is_none_or(|p| p.is_whitespace() || p == '(') => {
+                    match bare_url_len(rest) {
+                        Some(len) => {
+                            push_link(&mut out, self.links, &rest[..len], &escape(&rest[..len]));
+                            i += len;
+                        }
+                        None => {
+                            out.push('h');
+                            i += 1;
+                        }
+                    }
+                }
+                c => {
+                    out.push_str(&escape(c.encode_utf8(&mut [0; 4])));
+                    i += c.len_utf8();
+                }
+            }
+        }
+        out
+    }
+
+    /// Parses `[label](target)`, `[label][ref]`, or `[ref]` at the start of
+    /// `text`: its length, the label, and the target if it resolves.
+    fn link<'t>(&self, text: &'t str) -> Option<(usize, &'t str, Option<String>)> {
+        let close = matching_bracket(text)?;
+        let label = &text[1..close];
+        let rest = &text[close + 1..];
+
+        if let Some(inner) = rest.strip_prefix('(') {
+            let end = link_destination_end(inner)?;
+            let body = inner[..end].trim();
+            let target = match body.strip_prefix('<') {
+                Some(angled) => angled.split_once('>')?.0,
+                None => body.split_whitespace().next().unwrap_or(""),
+            };
+            return Some((close + 2 + end + 1, label, Some(target.to_string())));
+        }
+        if let Some(inner) = rest.strip_prefix('[') {
+            let end = inner.find(']')?;
+            let key = if end == 0 { label } else { &inner[..end] };
+            let target = self.refs.get(&normalize_label(key))?;
+            return Some((close + 2 + end + 1, label, Some(target.clone())));
+        }
+        let target = self.refs.get(&normalize_label(label))?;
+        Some((close + 1, label, Some(target.clone())))
+    }
+
+    /// Parses emphasis, strong emphasis, or strikethrough at the start of
+    /// `text`, returning its length and HTML.
+    fn emphasis(&self, text: &str, prev: Option<char>) -> Option<(usize, String)> {
+        let c = text.chars().next()?;
+        let run = text.len() - text.trim_start_matches(c).len();
+        let after = text[run..].chars().next()?;
+        if after.is_whitespace() {
+            return None;
+        }
+        if c == '_' && prev.is_some_and(|p| p.is_alphanumeric()) {
+            return None;
+        }
+
+        let (width, open, close) = match (c, run) {
+            ('~', 2) => (2, "<del>", "</del>"),
+            ('~', _) => return None,
+            (_, 1) => (1, "<em>", "</em>"),
+            (_, 2) => (2, "<strong>", "</strong>"),
+            _ => (3, "<strong><em>", "</em></strong>"),
+        };
+        let delimiter = &text[..width];
+        let body = &text[width..];
+        let mut search = 0;
+        while let Some(found) = body[search..].find(delimiter) {
+            let end = search + found;
+            let closing = &body[end..];
+            let closing_run = closing.len() - closing.trim_start_matches(c).len();
+            let before = body[..end].chars().next_back();
+            let next = closing[width..].chars().next();
+            if end > 0
+                && before.is_some_and(|b| !b.is_whitespace())
+                && closing_run == width
+                && !(c == '_' && next.is_some_and(|n| n.is_alphanumeric()))
+            {
+                let html = self.inline(&body[..end]);
+                return Some((width * 2 + end, format!("{open}{html}{close}")));
+            }
+            search = end + closing_run.max(1);
+        }
+        None
+    }
+}
+
+/// Splits a GitHub task list checkbox off the start of a list item.
+fn task_checkbox(mut item: Vec<&str>) -> (Option<bool>, Vec<&str>) {
+    let Some(first) = item.first().copied() else {
+        return (None, item);
+    };
+    let checked = if let Some(rest) = first.strip_prefix("[ ] ") {
+        item[0] = rest;
+        false
+    } else if let Some(rest) = first
+        .strip_prefix("[x] ")
+        .or_else(|| first.strip_prefix("[X] "))
+    {
+        item[0] = rest;
+        true
+    } else {
+        return (None, item);
+    };
+    (Some(checked), item)
+}
+
+fn find_backtick_run(text: &str, run: usize) -> Option<usize> {
+    let mut i = 0;
+    while let Some(found) = text[i..].find('`') {
+        let start = i + found;
+        let len = text[start..].len() - text[start..].trim_start_matches('`').len();
+        if len == run {
+            return Some(start);
+        }
+        i = start + len;
+    }
+    None
+}
+
+/// Index of the `]` matching the `[` at the start of `text`.
+fn matching_bracket(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Index of the `)` closing an inline link's destination and title.
+fn link_destination_end(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) if text[..i].ends_with(' ') => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {}
+            ('(', None) => depth += 1,
+            (')', None) if depth == 0 => return Some(i),
+            (')', None) => depth -= 1,
+            ('\n', None) if text[..i].trim().is_empty() => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parses `<https://...>` or `<user@example.com>` at the start of `text`.
+fn autolink(text: &str) -> Option<(usize, String)> {
+    let end = text.find('>')?;
+    let inner = &text[1..end];
+    if inner.is_empty() || inner.contains([' ', '<', '\n']) {
+        return None;
+    }
+    if super::scheme(inner).is_some() {
+        Some((end + 1, inner.to_string()))
+    } else if inner.contains('@') && !inner.contains(':') {
+        Some((end + 1, format!("mailto:{inner}")))
+    } else {
+        None
+    }
+}
+
+/// A label's text with emphasis and code markers dropped, for `alt` text.
+fn plain_text(label: &str) -> String {
+    label
+        .chars()
+        .filter(|c| !matches!(c, '*' | '_' | '`' | '[' | ']'))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn html(source: &str) -> String {
+        let links = Links {
+            repo_id: "r1",
+            ref_name: "main",
+            dir: "",
+        };
+        render(source, &links)
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(
+            html("# Title\n\nSome *text* and **bold**.\nNext line.\n\n---\n"),
+            "<h1 id=\"title\">Title</h1>\n\
+             <p>Some <em>text</em> and <strong>bold</strong>.\nNext line.</p>\n<hr>\n"
+        );
+        assert_eq!(
+            html("Title\n=====\n\n```rust\nfn main() {}\n```\n"),
+            "<h1 id=\"title\">Title</h1>\n\
+             <pre><code class=\"language-rust\">fn main() {}\n</code></pre>\n"
+        );
+        assert_eq!(
+            html("> quoted\ncontinued\n"),
+            "<blockquote>\n<p>quoted\ncontinued</p>\n</blockquote>\n"
+        );
+    }
+
+    #[test]
+    fn test_lists() {
+        assert_eq!(
+            html("- one\n- two\n  - nested\n- [x] done\n"),
+            "<ul>\n<li>one\n</li>\n<li>two\n<ul>\n<li>nested\n</li>\n</ul>\n</li>\n\
+             <li><input type=\"checkbox\" checked disabled> done\n</li>\n</ul>\n"
+        );
+        assert_eq!(
+            html("3. three\n4. four\n\n   more\n"),
+            "<ol start=\"3\">\n<li><p>three</p>\n</li>\n\
+             <li><p>four</p>\n<p>more</p>\n</li>\n</ol>\n"
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(
+            html("| Name | Size |\n|:-----|-----:|\n| `a` | 1 |\n"),
+            "<table>\n<thead>\n<tr><th align=\"left\">Name</th><th align=\"right\">Size</th></tr>\n\
+             </thead>\n<tbody>\n<tr><td align=\"left\"><code>a</code></td>\
+             <td align=\"right\">1</td></tr>\n</tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn test_links_and_images() {
+        assert_eq!(
+            html("See [the guide](docs/guide.md) and ![logo](logo.png \"Logo\").\n"),
+            "<p>See <a href=\"/api/v1/repos/r1/render/main/docs/guide.md\">the guide</a> and \
+             <img src=\"/api/v1/repos/r1/blob/main/logo.png?raw=true\" alt=\"logo\">.</p>\n"
+        );
+        assert_eq!(
+            html("[home][site] and https://example.com.\n\n[site]: https://example.com/home\n"),
+            "<p><a href=\"https://example.com/home\">home</a> and \
+             <a href=\"https://example.com\">https://example.com</a>.</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_sanitizes() {
+        assert_eq!(
+            html("<script>alert(1)</script>\n\n[x](javascript:alert(1)) snake_case_name\n"),
+            "<p>&lt;script&gt;alert(1)&lt;/script&gt;</p>\n<p>x snake_case_name</p>\n"
+        );
+        assert_eq!(
+            html("![a](x\" onerror=\"alert(1))\n"),
+            "<p><img src=\"/api/v1/repos/r1/blob/main/x%22?raw=true\" alt=\"a\">\
+             </p>\n"
+        );
+    }
+}
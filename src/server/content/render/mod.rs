@@ -0,0 +1,447 @@
+//! Server-side rendering of Markdown, reStructuredText, AsciiDoc, and
+//! Jupyter notebooks.
+//!
+//! Each renderer covers the parts of its format that READMEs and docs use
+//! day to day. Output is built from escaped text only: raw HTML in the
+//! source is shown as text, links and images keep only `http`, `https`,
+//! and `mailto` targets, and relative paths are rewritten to point back
+//! into the repository at the same ref.
+
+mod asciidoc;
+mod markdown;
+mod notebook;
+mod rst;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+
+use crate::server::AppState;
+use crate::server::response::{ApiError, ApiResponse};
+
+use super::auth::OptionalAuth;
+use super::badge::escape_xml;
+use super::dto::RenderResponse;
+use super::git_ops::{get_blob_at_path, get_commit, get_tree, is_binary, resolve_ref};
+use super::handlers::load_repo_and_check_access;
+
+/// Largest notebook rendered. Notebooks keep their outputs, images
+/// included, and can't be cut short like text.
+const MAX_NOTEBOOK_SIZE: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Markup {
+    Markdown,
+    ReStructuredText,
+    AsciiDoc,
+}
+
+impl Markup {
+    pub(super) fn from_path(path: &str) -> Option<Self> {
+        let (_, ext) = path.rsplit_once('.')?;
+        match ext.to_ascii_lowercase().as_str() {
+            "md" | "markdown" | "mdown" | "mkd" => Some(Self::Markdown),
+            "rst" | "rest" => Some(Self::ReStructuredText),
+            "adoc" | "asciidoc" | "asc" => Some(Self::AsciiDoc),
+            _ => None,
+        }
+    }
+
+    pub(super) fn name(self) -> &'static str {
+        match self {
+            Self::Markdown => "markdown",
+            Self::ReStructuredText => "rst",
+            Self::AsciiDoc => "asciidoc",
+        }
+    }
+}
+
+/// Renders `source` to sanitized HTML, rewriting relative links against
+/// `path` in the repository at `ref_name`.
+pub(super) fn render(
+    markup: Markup,
+    source: &str,
+    repo_id: &str,
+    ref_name: &str,
+    path: &str,
+) -> String {
+    let links = Links {
+        repo_id,
+        ref_name,
+        dir: path.rsplit_once('/').map_or("", |(dir, _)| dir),
+    };
+    let source = source.replace("\r\n", "\n").replace('\t', "    ");
+    match markup {
+        Markup::Markdown => markdown::render(&source, &links),
+        Markup::ReStructuredText => rst::render(&source, &links),
+        Markup::AsciiDoc => asciidoc::render(&source, &links),
+    }
+}
+
+/// GET /repos/{id}/render/{ref}/{*path} - Render a markup file as HTML
+pub async fn get_render(
+    auth: OptionalAuth,
+    State(state): State<Arc<AppState>>,
+    Path((id, ref_name, path)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (_repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+
+    let path = path.trim_start_matches('/');
+    let markup = Markup::from_path(path).ok_or_else(|| {
+        ApiError::bad_request(format!(
+            "Can't render {path}; only Markdown, reStructuredText, and AsciiDoc files can be"
+        ))
+    })?;
+
+    let oid = resolve_ref(&git_repo, &ref_name)?;
+    let commit = get_commit(&git_repo, oid)?;
+    let tree = get_tree(&git_repo, &commit)?;
+    let blob = get_blob_at_path(&git_repo, &tree, path)?;
+
+    let limit = state.content_limits.max_inline_blob_bytes as i64;
+    let size = blob.size() as i64;
+    let content = &blob.content()[..size.min(limit) as usize];
+    if is_binary(content) {
+        return Err(ApiError::bad_request(format!(
+            "Can't render {path}; it is a binary file"
+        )));
+    }
+    let html = render(
+        markup,
+        &String::from_utf8_lossy(content),
+        &id,
+        &ref_name,
+        path,
+    );
+
+    Ok(Json(ApiResponse::success(RenderResponse {
+        path: path.to_string(),
+        sha: blob.id().to_string(),
+        format: markup.name().to_string(),
+        html,
+        is_truncated: size > limit,
+    })))
+}
+
+/// GET /repos/{id}/render-notebook/{ref}/{*path} - Render a Jupyter
+/// notebook as HTML
+pub async fn get_render_notebook(
+    auth: OptionalAuth,
+    State(state): State<Arc<AppState>>,
+    Path((id, ref_name, path)): Path<(String, String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (_repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+
+    let path = path.trim_start_matches('/');
+    if !is_notebook(path) {
+        return Err(ApiError::bad_request(format!(
+            "Can't render {path}; only Jupyter notebooks (.ipynb) can be"
+        )));
+    }
+
+    let oid = resolve_ref(&git_repo, &ref_name)?;
+    let commit = get_commit(&git_repo, oid)?;
+    let tree = get_tree(&git_repo, &commit)?;
+    let blob = get_blob_at_path(&git_repo, &tree, path)?;
+
+    if blob.size() > MAX_NOTEBOOK_SIZE {
+        return Err(ApiError::payload_too_large(format!(
+            "Can't render {path}; notebooks over {MAX_NOTEBOOK_SIZE} bytes aren't rendered"
+        )));
+    }
+    let source = std::str::from_utf8(blob.content())
+        .map_err(|_| ApiError::bad_request(format!("Can't render {path}; it is not UTF-8")))?;
+    let links = Links {
+        repo_id: &id,
+        ref_name: &ref_name,
+        dir: path.rsplit_once('/').map_or("", |(dir, _)| dir),
+    };
+    let html = notebook::render(source, &links)
+        .map_err(|reason| ApiError::bad_request(format!("Can't render {path}; {reason}")))?;
+
+    Ok(Json(ApiResponse::success(RenderResponse {
+        path: path.to_string(),
+        sha: blob.id().to_string(),
+        format: "ipynb".to_string(),
+        html,
+        is_truncated: false,
+    })))
+}
+
+fn is_notebook(path: &str) -> bool {
+    path.rsplit_once('.')
+        .is_some_and(|(_, ext)| ext.eq_ignore_ascii_case("ipynb"))
+}
+
+/// Where links and images in a rendered file point.
+pub(super) struct Links<'a> {
+    repo_id: &'a str,
+    ref_name: &'a str,
+    /// Directory of the file being rendered, which relative links start from.
+    dir: &'a str,
+}
+
+impl Links<'_> {
+    /// The `href` for a link, or `None` if the target isn't safe to link to.
+    fn href(&self, target: &str) -> Option<String> {
+        let target = target.trim();
+        if target.starts_with('#') {
+            return Some(target.to_string());
+        }
+        match scheme(target) {
+            Some(scheme) if ["http", "https", "mailto"].contains(&scheme.as_str()) => {
+                Some(target.to_string())
+            }
+            Some(_) => None,
+            None => self.repo_url(target, false),
+        }
+    }
+
+    /// The `src` for an image, or `None` if the source isn't safe to load.
+    fn src(&self, target: &str) -> Option<String> {
+        let target = target.trim();
+        match scheme(target) {
+            Some(scheme) if scheme == "http" || scheme == "https" => Some(target.to_string()),
+            Some(_) => None,
+            None if target.starts_with('#') => None,
+            None => self.repo_url(target, true),
+        }
+    }
+
+    /// Resolves a relative path against the rendered file: markup files and
+    /// notebooks link to their rendering, directories to their tree, and
+    /// anything else (and every image) to its raw content.
+    fn repo_url(&self, target: &str, raw: bool) -> Option<String> {
+        if target.starts_with("//") {
+            return None;
+        }
+        let (target, fragment) = match target.split_once('#') {
+            Some((target, fragment)) => (target, Some(fragment)),
+            None => (target, None),
+        };
+        let target = target.split('?').next().unwrap_or_default();
+        if target.is_empty() {
+            return fragment.map(|f| format!("#{f}"));
+        }
+
+        let (base, target) = match target.strip_prefix('/') {
+            Some(rest) => ("", rest),
+            None => (self.dir, target),
+        };
+        let mut segments: Vec<&str> = Vec::new();
+        for segment in base.split('/').chain(target.split('/')) {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    segments.pop();
+                }
+                _ => segments.push(segment),
+            }
+        }
+        let path = segments
+            .iter()
+            .map(|s| urlencoding::encode(s))
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let url = |kind: &str| {
+            format!(
+                "/api/v1/repos/{}/{kind}/{}",
+                urlencoding::encode(self.repo_id),
+                urlencoding::encode(self.ref_name)
+            )
+        };
+        let mut url = if path.is_empty() {
+            url("tree")
+        } else if !raw && target.ends_with('/') {
+            format!("{}/{path}", url("tree"))
+        } else if !raw && Markup::from_path(&path).is_some() {
+            format!("{}/{path}", url("render"))
+        } else if !raw && is_notebook(&path) {
+            format!("{}/{path}", url("render-notebook"))
+        } else {
+            format!("{}/{path}?raw=true", url("blob"))
+        };
+        if let Some(fragment) = fragment.filter(|_| !raw) {
+            url.push('#');
+            url.push_str(fragment);
+        }
+        Some(url)
+    }
+}
+
+/// The lowercased scheme of an absolute URL, if `target` has one.
+fn scheme(target: &str) -> Option<String> {
+    let (scheme, _) = target.split_once(':')?;
+    let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| scheme.to_ascii_lowercase())
+}
+
+/// Hands out `id`s for headings, numbering repeats as GitHub does.
+#[derive(Default)]
+struct Slugs(HashMap<String, usize>);
+
+impl Slugs {
+    fn next(&mut self, text: &str) -> String {
+        let slug: String = text
+            .trim()
+            .to_lowercase()
+            .chars()
+            .filter_map(|c| match c {
+                ' ' => Some('-'),
+                c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+                _ => None,
+            })
+            .collect();
+        let count = self.0.entry(slug.clone()).or_insert(0);
+        *count += 1;
+        match *count {
+            1 => slug,
+            n => format!("{slug}-{}", n - 1),
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    escape_xml(text)
+}
+
+fn push_heading(out: &mut String, slugs: &mut Slugs, level: usize, text: &str, html: &str) {
+    let level = level.clamp(1, 6);
+    let id = escape(&slugs.next(text));
+    out.push_str(&format!("<h{level} id=\"{id}\">{html}</h{level}>\n"));
+}
+
+fn push_code_block(out: &mut String, language: Option<&str>, lines: &[&str]) {
+    let mut code = lines.join("\n");
+    code.push('\n');
+    match language.filter(|l| !l.is_empty()) {
+        Some(language) => out.push_str(&format!(
+            "<pre><code class=\"language-{}\">{}</code></pre>\n",
+            escape(language),
+            escape(&code)
+        )),
+        None => out.push_str(&format!("<pre><code>{}</code></pre>\n", escape(&code))),
+    }
+}
+
+fn push_link(out: &mut String, links: &Links<'_>, target: &str, html: &str) {
+    match links.href(target) {
+        Some(href) => out.push_str(&format!("<a href=\"{}\">{html}</a>", escape(&href))),
+        None => out.push_str(html),
+    }
+}
+
+fn push_image(out: &mut String, links: &Links<'_>, target: &str, alt: &str) {
+    match links.src(target) {
+        Some(src) => out.push_str(&format!(
+            "<img src=\"{}\" alt=\"{}\">",
+            escape(&src),
+            escape(alt)
+        )),
+        None => out.push_str(&escape(alt)),
+    }
+}
+
+/// Length of a bare `http://` or `https://` URL at the start of `text`,
+/// leaving off trailing punctuation that usually ends the sentence.
+fn bare_url_len(text: &str) -> Option<usize> {
+    if !(text.starts_with("http://") || text.starts_with("https://")) {
+        return None;
+    }
+    let end = text
+        .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '`'))
+        .unwrap_or(text.len());
+    let url = text[..end].trim_end_matches(['.', ',', ':', ';', '!', '?', ')', '\'']);
+    (url.len() > "https://".len()).then_some(url.len())
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Drops up to `n` leading spaces.
+fn dedent(line: &str, n: usize) -> &str {
+    &line[indent_of(line).min(n)..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links() -> Links<'static> {
+        Links {
+            repo_id: "r1",
+            ref_name: "main",
+            dir: "docs",
+        }
+    }
+
+    #[test]
+    fn test_markup_from_path() {
+        assert_eq!(Markup::from_path("README.md"), Some(Markup::Markdown));
+        assert_eq!(
+            Markup::from_path("docs/index.RST"),
+            Some(Markup::ReStructuredText)
+        );
+        assert_eq!(Markup::from_path("guide.adoc"), Some(Markup::AsciiDoc));
+        assert_eq!(Markup::from_path("README"), None);
+        assert_eq!(Markup::from_path("main.rs"), None);
+    }
+
+    #[test]
+    fn test_rewrites_relative_links() {
+        let links = links();
+        assert_eq!(
+            links.href("install.md#linux").as_deref(),
+            Some("/api/v1/repos/r1/render/main/docs/install.md#linux")
+        );
+        assert_eq!(
+            links.href("../LICENSE").as_deref(),
+            Some("/api/v1/repos/r1/blob/main/LICENSE?raw=true")
+        );
+        assert_eq!(
+            links.href("/src/").as_deref(),
+            Some("/api/v1/repos/r1/tree/main/src")
+        );
+        assert_eq!(
+            links.href("analysis.ipynb").as_deref(),
+            Some("/api/v1/repos/r1/render-notebook/main/docs/analysis.ipynb")
+        );
+        assert_eq!(
+            links.src("img/logo.png").as_deref(),
+            Some("/api/v1/repos/r1/blob/main/docs/img/logo.png?raw=true")
+        );
+        assert_eq!(links.href("#usage").as_deref(), Some("#usage"));
+        assert_eq!(
+            links.href("https://example.com/a").as_deref(),
+            Some("https://example.com/a")
+        );
+    }
+
+    #[test]
+    fn test_drops_unsafe_links() {
+        let links = links();
+        assert_eq!(links.href("javascript:alert(1)"), None);
+        assert_eq!(links.href("JavaScript:alert(1)"), None);
+        assert_eq!(links.href("//evil.example/x"), None);
+        assert_eq!(links.src("data:image/png;base64,AAAA"), None);
+        assert_eq!(links.src("mailto:a@example.com"), None);
+    }
+
+    #[test]
+    fn test_heading_slugs() {
+        let mut slugs = Slugs::default();
+        assert_eq!(slugs.next("Getting Started!"), "getting-started");
+        assert_eq!(slugs.next("Getting Started"), "getting-started-1");
+        assert_eq!(slugs.next("API: v2"), "api-v2");
+    }
+}
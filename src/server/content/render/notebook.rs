@@ -0,0 +1,293 @@
+//! Jupyter notebooks (nbformat 4), rendered as they were last saved: cells
+//! are never executed. Markdown cells go through the Markdown renderer,
+//! code cells become code blocks followed by their stored outputs. Of the
+//! outputs only text, Markdown, and PNG, JPEG, and GIF images are shown;
+//! HTML, SVG, and JavaScript outputs fall back to their plain text.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{Links, escape, markdown, push_code_block};
+
+#[derive(Deserialize)]
+struct Notebook {
+    nbformat: u32,
+    #[serde(default)]
+    metadata: Metadata,
+    #[serde(default)]
+    cells: Vec<Cell>,
+}
+
+#[derive(Default, Deserialize)]
+struct Metadata {
+    language_info: Option<LanguageInfo>,
+    kernelspec: Option<KernelSpec>,
+}
+
+#[derive(Deserialize)]
+struct LanguageInfo {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct KernelSpec {
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Cell {
+    cell_type: String,
+    #[serde(default)]
+    source: Value,
+    execution_count: Option<u64>,
+    #[serde(default)]
+    outputs: Vec<Output>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "output_type", rename_all = "snake_case")]
+enum Output {
+    Stream {
+        name: String,
+        #[serde(default)]
+        text: Value,
+    },
+    DisplayData {
+        #[serde(default)]
+        data: HashMap<String, Value>,
+    },
+    ExecuteResult {
+        #[serde(default)]
+        data: HashMap<String, Value>,
+    },
+    Error {
+        ename: String,
+        evalue: String,
+        #[serde(default)]
+        traceback: Vec<String>,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+/// Image types shown inline, in order of preference.
+const IMAGE_TYPES: [&str; 3] = ["image/png", "image/jpeg", "image/gif"];
+
+/// Renders a notebook, or explains why `source` isn't one.
+pub(super) fn render(source: &str, links: &Links<'_>) -> Result<String, String> {
+    let notebook: Notebook =
+        serde_json::from_str(source).map_err(|e| format!("it is not a valid notebook: {e}"))?;
+    if notebook.nbformat != 4 {
+        return Err(format!(
+            "only nbformat 4 notebooks are supported, not nbformat {}",
+            notebook.nbformat
+        ));
+    }
+    let language = notebook
+        .metadata
+        .language_info
+        .and_then(|l| l.name)
+        .or_else(|| notebook.metadata.kernelspec.and_then(|k| k.language));
+
+    let mut out = String::from("<div class=\"notebook\">\n");
+    for cell in &notebook.cells {
+        let source = text(&cell.source);
+        match cell.cell_type.as_str() {
+            "markdown" => {
+                out.push_str("<div class=\"cell markdown\">\n");
+                out.push_str(&markdown::render(&source, links));
+            }
+            "code" => {
+                out.push_str("<div class=\"cell code\">\n");
+                let prompt = cell
+                    .execution_count
+                    .map_or_else(|| " ".to_string(), |n| n.to_string());
+                out.push_str(&format!("<div class=\"prompt\">In [{prompt}]:</div>\n"));
+                let lines: Vec<&str> = source.lines().collect();
+                push_code_block(&mut out, language.as_deref(), &lines);
+                for output in &cell.outputs {
+                    push_output(&mut out, links, output);
+                }
+            }
+            _ => {
+                out.push_str("<div class=\"cell raw\">\n");
+                out.push_str(&format!("<pre>{}</pre>\n", escape(&source)));
+            }
+        }
+        out.push_str("</div>\n");
+    }
+    out.push_str("</div>\n");
+    Ok(out)
+}
+
+fn push_output(out: &mut String, links: &Links<'_>, output: &Output) {
+    match output {
+        Output::Stream { name, text: stream } => {
+            let class = if name == "stderr" { "stderr" } else { "stdout" };
+            out.push_str(&format!(
+                "<div class=\"output {class}\"><pre>{}</pre></div>\n",
+                escape(&text(stream))
+            ));
+        }
+        Output::DisplayData { data } | Output::ExecuteResult { data } => {
+            if let Some(html) = rich_output(links, data) {
+                out.push_str(&format!("<div class=\"output\">{html}</div>\n"));
+            }
+        }
+        Output::Error {
+            ename,
+            evalue,
+            traceback,
+        } => {
+            let trace = if traceback.is_empty() {
+                format!("{ename}: {evalue}")
+            } else {
+                strip_ansi(&traceback.join("\n"))
+            };
+            out.push_str(&format!(
+                "<div class=\"output error\"><pre>{}</pre></div>\n",
+                escape(&trace)
+            ));
+        }
+        Output::Unknown => {}
+    }
+}
+
+/// The best representation of a display output this renderer can show
+/// safely, if any.
+fn rich_output(links: &Links<'_>, data: &HashMap<String, Value>) -> Option<String> {
+    for mime in IMAGE_TYPES {
+        if let Some(image) = data.get(mime) {
+            let encoded: String = text(image).split_whitespace().collect();
+            if is_base64(&encoded) {
+                return Some(format!(
+                    "<img src=\"data:{mime};base64,{encoded}\" alt=\"\">"
+                ));
+            }
+        }
+    }
+    if let Some(source) = data.get("text/markdown") {
+        return Some(markdown::render(&text(source), links));
+    }
+    data.get("text/plain")
+        .map(|plain| format!("<pre>{}</pre>", escape(&strip_ansi(&text(plain)))))
+}
+
+/// Multiline strings in a notebook are either a string or a list of lines
+/// that already end in newlines.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+fn is_base64(encoded: &str) -> bool {
+    !encoded.is_empty()
+        && encoded
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'='))
+}
+
+/// Removes the terminal color codes IPython puts in tracebacks.
+fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            out.push(c);
+            continue;
+        }
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links() -> Links<'static> {
+        Links {
+            repo_id: "r1",
+            ref_name: "main",
+            dir: "notebooks",
+        }
+    }
+
+    #[test]
+    fn test_renders_cells_and_outputs() {
+        let source = r##"{
+            "nbformat": 4,
+            "nbformat_minor": 5,
+            "metadata": {"language_info": {"name": "python"}},
+            "cells": [
+                {"cell_type": "markdown", "metadata": {}, "source": ["# Results\n", "See [data](data.csv)."]},
+                {
+                    "cell_type": "code",
+                    "execution_count": 3,
+                    "metadata": {},
+                    "source": "print('<b>')\n1 / 0",
+                    "outputs": [
+                        {"output_type": "stream", "name": "stdout", "text": ["<b>\n"]},
+                        {"output_type": "display_data", "metadata": {}, "data": {
+                            "text/html": "<script>alert(1)</script>",
+                            "text/plain": "<Figure>",
+                            "image/png": "iVBORw0KGgo=\n"
+                        }},
+                        {"output_type": "execute_result", "execution_count": 3, "metadata": {}, "data": {
+                            "text/html": "<script>alert(1)</script>",
+                            "text/plain": ["'<i>'"]
+                        }},
+                        {"output_type": "error", "ename": "ZeroDivisionError", "evalue": "division by zero",
+                         "traceback": ["\u001b[0;31mZeroDivisionError\u001b[0m: division by zero"]}
+                    ]
+                }
+            ]
+        }"##;
+        let html = render(source, &links()).unwrap();
+        assert!(html.contains("<h1 id=\"results\">Results</h1>"), "{html}");
+        assert!(
+            html.contains("href=\"/api/v1/repos/r1/blob/main/notebooks/data.csv?raw=true\""),
+            "{html}"
+        );
+        assert!(
+            html.contains("<div class=\"prompt\">In [3]:</div>"),
+            "{html}"
+        );
+        assert!(
+            html.contains("<code class=\"language-python\">print(&apos;&lt;b&gt;&apos;)"),
+            "{html}"
+        );
+        assert!(
+            html.contains("<div class=\"output stdout\"><pre>&lt;b&gt;\n</pre></div>"),
+            "{html}"
+        );
+        assert!(
+            html.contains("<img src=\"data:image/png;base64,iVBORw0KGgo=\" alt=\"\">"),
+            "{html}"
+        );
+        assert!(html.contains("<pre>&apos;&lt;i&gt;&apos;</pre>"), "{html}");
+        assert!(
+            html.contains("<pre>ZeroDivisionError: division by zero</pre>"),
+            "{html}"
+        );
+        assert!(!html.contains("<script"), "{html}");
+    }
+
+    #[test]
+    fn test_rejects_other_formats() {
+        assert!(render("not json", &links()).is_err());
+        let err = render(r#"{"nbformat": 3, "worksheets": []}"#, &links()).unwrap_err();
+        assert!(err.contains("nbformat 3"), "{err}");
+    }
+}
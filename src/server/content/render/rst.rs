@@ -0,0 +1,553 @@
+//! reStructuredText: sections, paragraphs, literal blocks, lists,
+//! definition lists, block quotes, common directives, and inline markup
+//! with hyperlink references.
+
+use std::collections::HashMap;
+
+use super::{
+    Links, Slugs, bare_url_len, dedent, escape, indent_of, push_code_block, push_heading,
+    push_image, push_link,
+};
+
+struct Renderer<'a> {
+    links: &'a Links<'a>,
+    /// Hyperlink targets from `.. _name: url`, keyed by lowercased name.
+    targets: HashMap<String, String>,
+    /// Section adornment styles in the order they first appear; a title's
+    /// level is its style's position here.
+    styles: Vec<(char, bool)>,
+    slugs: Slugs,
+}
+
+pub(super) fn render(source: &str, links: &Links<'_>) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let targets = lines
+        .iter()
+        .filter_map(|line| {
+            let (name, url) = line.trim().strip_prefix(".. _")?.split_once(": ")?;
+            Some((
+                name.trim_matches('`').to_lowercase(),
+                url.trim().to_string(),
+            ))
+        })
+        .collect();
+    let mut renderer = Renderer {
+        links,
+        targets,
+        styles: Vec::new(),
+        slugs: Slugs::default(),
+    };
+    let mut out = String::new();
+    renderer.blocks(&lines, false, &mut out);
+    out
+}
+
+/// Whether `line` is a run of one punctuation character, as section
+/// adornments and transitions are.
+fn adornment(line: &str) -> Option<char> {
+    let line = line.trim_end();
+    let c = line.chars().next()?;
+    (c.is_ascii_punctuation() && line.len() >= 2 && line.chars().all(|x| x == c)).then_some(c)
+}
+
+/// The content column of a bullet or enumerated list item.
+fn list_item(line: &str) -> Option<(bool, usize)> {
+    let rest = line.trim_start();
+    let indent = line.len() - rest.len();
+    if let Some(c) = rest.chars().next().filter(|c| matches!(c, '-' | '*' | '+')) {
+        let after = &rest[c.len_utf8()..];
+        return (after.is_empty() || after.starts_with(' '))
+            .then(|| (false, indent + 1 + indent_of(after).max(1)));
+    }
+    let marker_len = rest.find(['.', ')'])?;
+    let marker = &rest[..marker_len];
+    let after = &rest[marker_len + 1..];
+    let numbered = marker == "#"
+        || (!marker.is_empty() && marker.chars().all(|c| c.is_ascii_digit()))
+        || (marker.len() == 1 && marker.chars().all(|c| c.is_ascii_alphabetic()));
+    (numbered && after.starts_with(' ')).then(|| (true, indent + marker_len + 1 + indent_of(after)))
+}
+
+/// The end of an indented block starting at `start`: every following
+/// line that is blank or indented past `indent`, less trailing blanks.
+fn indented_block_end(lines: &[&str], start: usize, indent: usize) -> usize {
+    let mut end = start;
+    while end < lines.len() && (lines[end].trim().is_empty() || indent_of(lines[end]) > indent) {
+        end += 1;
+    }
+    while end > start && lines[end - 1].trim().is_empty() {
+        end -= 1;
+    }
+    end
+}
+
+/// Lines of an indented block, dedented by their smallest indent.
+fn dedent_block<'s>(lines: &[&'s str]) -> Vec<&'s str> {
+    let indent = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| indent_of(l))
+        .min()
+        .unwrap_or(0);
+    lines.iter().map(|l| dedent(l, indent)).collect()
+}
+
+impl Renderer<'_> {
+    fn blocks(&mut self, lines: &[&str], tight: bool, out: &mut String) {
+        let mut paragraph: Vec<&str> = Vec::new();
+        let mut literal_next = false;
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            let trimmed = line.trim();
+            let indent = indent_of(line);
+
+            if trimmed.is_empty() {
+                literal_next |= self.paragraph(&mut paragraph, tight, out);
+                i += 1;
+                continue;
+            }
+
+            if indent == 0 {
+                if let Some(c) = adornment(line) {
+                    let title = lines.get(i + 1).map(|l| l.trim()).unwrap_or("");
+                    if paragraph.is_empty()
+                        && !title.is_empty()
+                        && lines.get(i + 2).map(|l| l.trim_end()) == Some(line.trim_end())
+                    {
+                        self.section(c, true, title, out);
+                        i += 3;
+                        continue;
+                    }
+                    if paragraph.len() == 1 && line.trim_end().len() >= paragraph[0].chars().count()
+                    {
+                        let title = paragraph.remove(0);
+                        self.section(c, false, title, out);
+                        i += 1;
+                        continue;
+                    }
+                    if paragraph.is_empty() && line.trim_end().len() >= 4 {
+                        out.push_str("<hr>\n");
+                        i += 1;
+                        continue;
+                    }
+                }
+            }
+
+            if !paragraph.is_empty() {
+                if indent > 0 && paragraph.len() == 1 && !literal_next {
+                    let term = paragraph.remove(0);
+                    i = self.definition_list(lines, i, term, out);
+                } else {
+                    paragraph.push(trimmed);
+                    i += 1;
+                }
+                continue;
+            }
+
+            if indent > 0 {
+                let end = indented_block_end(lines, i, 0);
+                let block = dedent_block(&lines[i..end]);
+                if literal_next {
+                    push_code_block(out, None, &block);
+                } else {
+                    out.push_str("<blockquote>\n");
+                    self.blocks(&block, false, out);
+                    out.push_str("</blockquote>\n");
+                }
+                literal_next = false;
+                i = end;
+                continue;
+            }
+            literal_next = false;
+
+            if trimmed == ".." || trimmed.starts_with(".. ") {
+                i = self.directive(lines, i, out);
+            } else if let Some((ordered, _)) = list_item(line) {
+                i = self.list(lines, i, ordered, out);
+            } else {
+                paragraph.push(trimmed);
+                i += 1;
+            }
+        }
+        self.paragraph(&mut paragraph, tight, out);
+    }
+
+    /// Writes a paragraph, returning whether it ended with `::` and so
+    /// introduces a literal block.
+    fn paragraph(&mut self, lines: &mut Vec<&str>, tight: bool, out: &mut String) -> bool {
+        if lines.is_empty() {
+            return false;
+        }
+        let text = lines.join("\n");
+        lines.clear();
+        let (text, literal) = match text.strip_suffix("::") {
+            Some(before) if before.trim().is_empty() => return true,
+            Some(before) if before.ends_with(char::is_whitespace) => (before.trim_end(), true),
+            Some(before) => (&text[..before.len() + 1], true),
+            None => (text.as_str(), false),
+        };
+        let html = self.inline(text);
+        if tight {
+            out.push_str(&html);
+            out.push('\n');
+        } else {
+            out.push_str(&format!("<p>{html}</p>\n"));
+        }
+        literal
+    }
+
+    fn section(&mut self, c: char, overline: bool, title: &str, out: &mut String) {
+        let level = match self.styles.iter().position(|s| *s == (c, overline)) {
+            Some(level) => level + 1,
+            None => {
+                self.styles.push((c, overline));
+                self.styles.len()
+            }
+        };
+        let html = self.inline(title);
+        push_heading(out, &mut self.slugs, level, title, &html);
+    }
+
+    fn directive(&mut self, lines: &[&str], start: usize, out: &mut String) -> usize {
+        let end = indented_block_end(lines, start + 1, 0);
+        let head = lines[start].trim().trim_start_matches("..").trim();
+        let Some((name, argument)) = head.split_once("::") else {
+            return end;
+        };
+        let argument = argument.trim();
+        let body = dedent_block(&lines[start + 1..end]);
+        let options_end = body
+            .iter()
+            .position(|l| !l.trim_start().starts_with(':'))
+            .unwrap_or(body.len());
+        let option = |key: &str| {
+            body[..options_end].iter().find_map(|l| {
+                l.trim()
+                    .strip_prefix(&format!(":{key}:"))
+                    .map(|v| v.trim().to_string())
+            })
+        };
+        let content = &body[options_end..];
+        let content = &content[content.iter().take_while(|l| l.trim().is_empty()).count()..];
+
+        match name.trim() {
+            "code" | "code-block" | "sourcecode" => {
+                push_code_block(out, Some(argument), content);
+            }
+            "image" | "figure" => {
+                let alt = option("alt").unwrap_or_default();
+                out.push_str("<p>");
+                match option("target") {
+                    Some(target) => {
+                        let mut image = String::new();
+                        push_image(&mut image, self.links, argument, &alt);
+                        push_link(out, self.links, &target, &image);
+                    }
+                    None => push_image(out, self.links, argument, &alt),
+                }
+                out.push_str("</p>\n");
+            }
+            kind @ ("note" | "tip" | "hint" | "important" | "attention" | "caution" | "warning"
+            | "danger" | "error") => {
+                out.push_str(&format!(
+                    "<div class=\"admonition {kind}\">\n<p><strong>{}{}</strong></p>\n",
+                    kind[..1].to_uppercase(),
+                    &kind[1..]
+                ));
+                let mut body: Vec<&str> = Vec::new();
+                if !argument.is_empty() {
+                    body.push(argument);
+                }
+                body.extend(content);
+                self.blocks(&body, false, out);
+                out.push_str("</div>\n");
+            }
+            _ => {}
+        }
+        end
+    }
+
+    fn list(&mut self, lines: &[&str], start: usize, ordered: bool, out: &mut String) -> usize {
+        let indent = indent_of(lines[start]);
+        let mut items: Vec<Vec<&str>> = Vec::new();
+        let mut loose = false;
+        let mut i = start;
+        while i < lines.len() {
+            let line = lines[i];
+            match list_item(line) {
+                Some((kind, column)) if kind == ordered && indent_of(line) == indent => {
+                    if i > start && lines[i - 1].trim().is_empty() {
+                        loose = true;
+                    }
+                    let end = indented_block_end(lines, i + 1, indent);
+                    let mut item = vec![line.get(column..).unwrap_or("")];
+                    item.extend(lines[i + 1..end].iter().map(|l| dedent(l, column)));
+                    loose |= item.iter().any(|l| l.trim().is_empty());
+                    items.push(item);
+                    i = end;
+                    while i < lines.len() && lines[i].trim().is_empty() {
+                        i += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        out.push_str(if ordered { "<ol>\n" } else { "<ul>\n" });
+        for item in items {
+            out.push_str("<li>");
+            self.blocks(&item, !loose, out);
+            out.push_str("</li>\n");
+        }
+        out.push_str(if ordered { "</ol>\n" } else { "</ul>\n" });
+        i
+    }
+
+    fn definition_list(
+        &mut self,
+        lines: &[&str],
+        start: usize,
+        term: &str,
+        out: &mut String,
+    ) -> usize {
+        out.push_str("<dl>\n");
+        let mut term = term;
+        let mut i = start;
+        loop {
+            let end = indented_block_end(lines, i, 0);
+            out.push_str(&format!("<dt>{}</dt>\n<dd>\n", self.inline(term)));
+            self.blocks(&dedent_block(&lines[i..end]), false, out);
+            out.push_str("</dd>\n");
+            i = end;
+
+            let next = lines[i..].iter().position(|l| !l.trim().is_empty());
+            match next.map(|n| i + n) {
+                Some(n)
+                    if indent_of(lines[n]) == 0
+                        && lines
+                            .get(n + 1)
+                            .is_some_and(|l| indent_of(l) > 0 && !l.trim().is_empty())
+                        && adornment(lines[n]).is_none()
+                        && list_item(lines[n]).is_none()
+                        && !lines[n].starts_with("..") =>
+                {
+                    term = lines[n].trim();
+                    i = n + 1;
+                }
+                _ => break,
+            }
+        }
+        out.push_str("</dl>\n");
+        i
+    }
+
+    fn inline(&self, text: &str) -> String {
+        let mut out = String::new();
+        let mut i = 0;
+        while i < text.len() {
+            let rest = &text[i..];
+            let c = rest.chars().next().expect("not at end");
+            let prev = text[..i].chars().next_back();
+            let at_start = prev.is_none_or(|p| p.is_whitespace() || "([{<'\"-/:".contains(p));
+
+            if c == '\\' {
+                match rest[1..].chars().next() {
+                    Some(n) => {
+                        out.push_str(&escape(n.encode_utf8(&mut [0; 4])));
+                        i += 1 + n.len_utf8();
+                    }
+                    None => i += 1,
+                }
+                continue;
+            }
+            if at_start {
+                if let Some((len, html)) = self.markup(rest) {
+                    out.push_str(&html);
+                    i += len;
+                    continue;
+                }
+                if let Some(len) = bare_url_len(rest) {
+                    push_link(&mut out, self.links, &rest[..len], &escape(&rest[..len]));
+                    i += len;
+                    continue;
+                }
+            }
+            if c.is_alphanumeric() && prev.is_none_or(|p| !p.is_alphanumeric()) {
+                let word_len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')))
+                    .unwrap_or(rest.len());
+                let word = &rest[..word_len];
+                if let Some(name) = word.strip_suffix('_').filter(|n| !n.ends_with('_')) {
+                    if let Some(url) = self.targets.get(&name.to_lowercase()) {
+                        push_link(&mut out, self.links, url, &escape(name));
+                        i += word_len;
+                        continue;
+                    }
+                }
+                out.push_str(&escape(word));
+                i += word_len;
+                continue;
+            }
+            out.push_str(&escape(c.encode_utf8(&mut [0; 4])));
+            i += c.len_utf8();
+        }
+        out
+    }
+
+    /// Parses inline markup at the start of `text`: its length and HTML.
+    fn markup(&self, text: &str) -> Option<(usize, String)> {
+        if let Some(body) = text.strip_prefix("``") {
+            let end = body.find("``").filter(|e| *e > 0)?;
+            let html = format!("<code>{}</code>", escape(&body[..end].replace('\n', " ")));
+            return Some((end + 4, html));
+        }
+        if let Some(body) = text.strip_prefix("**") {
+            let end = closing(body, "**")?;
+            return Some((
+                end + 4,
+                format!("<strong>{}</strong>", escape(&body[..end])),
+            ));
+        }
+        if let Some(body) = text.strip_prefix('*') {
+            let end = closing(body, "*")?;
+            return Some((end + 2, format!("<em>{}</em>", escape(&body[..end]))));
+        }
+        if let Some(body) = text.strip_prefix('`') {
+            let end = closing(body, "`")?;
+            let inner = &body[..end];
+            let suffix = &body[end + 1..];
+            let underscores = suffix.len() - suffix.trim_start_matches('_').len();
+            let len = end + 2 + underscores.min(2);
+            let (label, target) = split_target(inner);
+            let html = match (underscores, target) {
+                (0, _) => format!("<cite>{}</cite>", escape(inner)),
+                (_, Some(target)) => {
+                    let mut link = String::new();
+                    push_link(&mut link, self.links, target, &escape(label));
+                    link
+                }
+                (_, None) => match self.targets.get(&label.to_lowercase()) {
+                    Some(url) => {
+                        let mut link = String::new();
+                        push_link(&mut link, self.links, url, &escape(label));
+                        link
+                    }
+                    None => escape(label),
+                },
+            };
+            return Some((len, html));
+        }
+        if let Some(body) = text.strip_prefix(':') {
+            let (role, rest) = body.split_once(":`")?;
+            if role.is_empty()
+                || !role
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_:+".contains(c))
+            {
+                return None;
+            }
+            let end = rest.find('`')?;
+            let inner = &rest[..end];
+            let (label, _) = split_target(inner);
+            let html = match role {
+                "code" | "literal" | "samp" | "file" | "command" | "kbd" => {
+                    format!("<code>{}</code>", escape(inner))
+                }
+                "strong" => format!("<strong>{}</strong>", escape(inner)),
+                "emphasis" => format!("<em>{}</em>", escape(inner)),
+                _ => escape(label),
+            };
+            return Some((1 + role.len() + 2 + end + 1, html));
+        }
+        None
+    }
+}
+
+/// Where inline markup started by `delimiter` ends in `body`: the end
+/// string must follow non-whitespace and not be followed by a letter.
+fn closing(body: &str, delimiter: &str) -> Option<usize> {
+    if body.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let mut search = 0;
+    while let Some(found) = body[search..].find(delimiter) {
+        let end = search + found;
+        let before = body[..end].chars().next_back();
+        let after = body[end + delimiter.len()..].chars().next();
+        if end > 0
+            && before.is_some_and(|b| !b.is_whitespace())
+            && after.is_none_or(|a| !a.is_alphanumeric())
+        {
+            return Some(end);
+        }
+        search = end + delimiter.len();
+    }
+    None
+}
+
+/// Splits `text <target>` into its label and target.
+fn split_target(text: &str) -> (&str, Option<&str>) {
+    match text.strip_suffix('>').and_then(|t| t.rsplit_once('<')) {
+        Some((label, target)) if !label.trim().is_empty() => (label.trim(), Some(target)),
+        Some((_, target)) => (target, Some(target)),
+        None => (text, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn html(source: &str) -> String {
+        let links = Links {
+            repo_id: "r1",
+            ref_name: "main",
+            dir: "",
+        };
+        render(source, &links)
+    }
+
+    #[test]
+    fn test_sections_and_literal_blocks() {
+        assert_eq!(
+            html("=====\nTitle\n=====\n\nIntro\n\nUsage\n-----\n\nRun it::\n\n    $ tool --help\n"),
+            "<h1 id=\"title\">Title</h1>\n<p>Intro</p>\n<h2 id=\"usage\">Usage</h2>\n\
+             <p>Run it:</p>\n<pre><code>$ tool --help\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn test_lists_and_directives() {
+        assert_eq!(
+            html(
+                "- one\n- ``two``\n\n.. code-block:: python\n\n    print(1)\n\n.. note:: Careful.\n"
+            ),
+            "<ul>\n<li>one\n</li>\n<li><code>two</code>\n</li>\n</ul>\n\
+             <pre><code class=\"language-python\">print(1)\n</code></pre>\n\
+             <div class=\"admonition note\">\n<p><strong>Note</strong></p>\n\
+             <p>Careful.</p>\n</div>\n"
+        );
+        assert_eq!(
+            html("term\n    Its definition.\n"),
+            "<dl>\n<dt>term</dt>\n<dd>\n<p>Its definition.</p>\n</dd>\n</dl>\n"
+        );
+    }
+
+    #[test]
+    fn test_inline() {
+        assert_eq!(
+            html(
+                "See `the docs <docs/index.rst>`_, Python_, and **bold** *text*.\n\n\
+                 .. _Python: https://www.python.org/\n"
+            ),
+            "<p>See <a href=\"/api/v1/repos/r1/render/main/docs/index.rst\">the docs</a>, \
+             <a href=\"https://www.python.org/\">Python</a>, and <strong>bold</strong> \
+             <em>text</em>.</p>\n"
+        );
+        assert_eq!(
+            html("`x <javascript:alert(1)>`_ <b>\n"),
+            "<p>x &lt;b&gt;</p>\n"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Commit statuses: CI systems report the outcome of each job against a
+//! commit, and readers get every job's latest state along with a combined
+//! one for the commit as a whole.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::Utc;
+
+use crate::auth::RequirePrincipal;
+use crate::server::AppState;
+use crate::server::response::{ApiError, ApiResponse, StoreOptionExt, StoreResultExt};
+use crate::server::user::access::require_repo_permission;
+use crate::types::{CommitState, CommitStatus, Permission};
+
+use super::auth::OptionalAuth;
+use super::dto::{CombinedStatusResponse, CreateCommitStatusRequest};
+use super::git_ops::{open_repo, resolve_ref};
+use super::handlers::{load_repo_and_check_access, repo_path};
+
+const DEFAULT_CONTEXT: &str = "default";
+const MAX_CONTEXT_LEN: usize = 255;
+const MAX_DESCRIPTION_LEN: usize = 1024;
+const MAX_TARGET_URL_LEN: usize = 2048;
+/// Contexts one commit can have statuses for.
+const MAX_CONTEXTS: usize = 100;
+
+/// GET /repos/{id}/commits/{sha}/statuses - Each context's latest status
+/// for a commit, and the combined state
+pub async fn get_commit_statuses(
+    auth: OptionalAuth,
+    State(state): State<Arc<AppState>>,
+    Path((id, sha)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (repo, git_repo) = load_repo_and_check_access(&state, &auth, &id).await?;
+    let sha = resolve_ref(&git_repo, &sha)?.to_string();
+    drop(git_repo);
+
+    let statuses = state
+        .store
+        .list_commit_statuses(&repo.id, &sha)
+        .api_err("Failed to list commit statuses")?;
+
+    Ok(Json(ApiResponse::success(CombinedStatusResponse {
+        sha,
+        state: CommitState::combine(statuses.iter().map(|s| s.state)),
+        total_count: statuses.len(),
+        statuses,
+    })))
+}
+
+/// POST /repos/{id}/commits/{sha}/statuses - Report a commit's status for
+/// one context, replacing what that context reported before
+pub async fn create_commit_status(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path((id, sha)): Path<(String, String)>,
+    Json(req): Json<CreateCommitStatusRequest>,
+) -> Result<impl IntoResponse, ApiError> {
+    let repo = state
+        .store
+        .get_repo_by_id(&id)
+        .api_err("Failed to get repository")?
+        .or_not_found("Repository not found")?;
+    require_repo_permission(&state, &auth.principal, &repo, Permission::REPO_WRITE)?;
+
+    let context = req
+        .context
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| DEFAULT_CONTEXT.to_string());
+    if context.len() > MAX_CONTEXT_LEN {
+        return Err(ApiError::bad_request(format!(
+            "context must be at most {MAX_CONTEXT_LEN} bytes"
+        )));
+    }
+    let description = req.description.filter(|d| !d.is_empty());
+    if description
+        .as_ref()
+        .is_some_and(|d| d.len() > MAX_DESCRIPTION_LEN)
+    {
+        return Err(ApiError::bad_request(format!(
+            "description must be at most {MAX_DESCRIPTION_LEN} bytes"
+        )));
+    }
+    let target_url = req.target_url.filter(|u| !u.is_empty());
+    if let Some(url) = &target_url {
+        if !(url.starts_with("http://") || url.starts_with("https://")) {
+            return Err(ApiError::bad_request("target_url must be an http(s) URL"));
+        }
+        if url.len() > MAX_TARGET_URL_LEN {
+            return Err(ApiError::bad_request(format!(
+                "target_url must be at most {MAX_TARGET_URL_LEN} bytes"
+            )));
+        }
+    }
+
+    let path = repo_path(&state, &repo.namespace_id, &repo.name);
+    let sha = resolve_ref(&open_repo(&path, repo.object_format)?, &sha)?.to_string();
+
+    let existing = state
+        .store
+        .list_commit_statuses(&repo.id, &sha)
+        .api_err("Failed to list commit statuses")?;
+    let previous = existing.iter().find(|s| s.context == context);
+    if previous.is_none() && existing.len() >= MAX_CONTEXTS {
+        return Err(ApiError::bad_request(format!(
+            "A commit can have statuses for at most {MAX_CONTEXTS} contexts"
+        )));
+    }
+
+    let now = Utc::now();
+    let status = CommitStatus {
+        repo_id: repo.id,
+        sha,
+        context,
+        state: req.state,
+        target_url,
+        description,
+        creator_id: Some(auth.principal.id.clone()),
+        created_at: previous.map_or(now, |s| s.created_at),
+        updated_at: now,
+    };
+    state
+        .store
+        .set_commit_status(&status)
+        .api_err("Failed to save commit status")?;
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(status))))
+}
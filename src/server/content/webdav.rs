@@ -0,0 +1,408 @@
+//! Read-only WebDAV view of each repository's default branch at
+//! `/dav/{namespace}/{repo}/`, so a repo can be mounted in a file manager.
+//! Only `OPTIONS`, `GET`, `HEAD` and `PROPFIND` are answered; every method
+//! that would write is refused. Access follows the repository's visibility,
+//! with a token as the basic auth password for private ones.
+
+use std::path::Path as FsPath;
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::{HeaderMap, Method, StatusCode, header},
+    response::{Html, IntoResponse, Response},
+    routing::any,
+};
+use git2::{ObjectType, Oid, Repository};
+
+use crate::server::AppState;
+use crate::server::git::auth::{GitAuthError, check_git_access, extract_git_auth};
+use crate::server::response::ApiError;
+use crate::server::validation::normalize_repo_name;
+use crate::types::Repo;
+
+use super::badge::escape_xml;
+use super::git_ops::{GitError, get_commit, get_tree, open_repo, resolve_ref};
+use super::raw::{RawBlob, content_type_for_extension, serve_raw_blob};
+
+const ALLOW: &str = "OPTIONS, GET, HEAD, PROPFIND";
+
+pub(crate) fn webdav_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/dav/{namespace}/{repo}", any(serve_root))
+        .route("/dav/{namespace}/{repo}/", any(serve_root))
+        .route("/dav/{namespace}/{repo}/{*path}", any(serve_path))
+}
+
+async fn serve_root(
+    State(state): State<Arc<AppState>>,
+    Path((namespace, repo)): Path<(String, String)>,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    serve(&state, &method, &headers, &namespace, &repo, "").await
+}
+
+async fn serve_path(
+    State(state): State<Arc<AppState>>,
+    Path((namespace, repo, path)): Path<(String, String, String)>,
+    method: Method,
+    headers: HeaderMap,
+) -> Response {
+    serve(&state, &method, &headers, &namespace, &repo, &path).await
+}
+
+async fn serve(
+    state: &Arc<AppState>,
+    method: &Method,
+    headers: &HeaderMap,
+    namespace: &str,
+    repo: &str,
+    path: &str,
+) -> Response {
+    match method.as_str() {
+        "OPTIONS" => {
+            return (
+                StatusCode::OK,
+                [
+                    (header::ALLOW, ALLOW),
+                    (header::HeaderName::from_static("dav"), "1"),
+                ],
+            )
+                .into_response();
+        }
+        "GET" | "HEAD" | "PROPFIND" => {}
+        _ => {
+            return (
+                StatusCode::METHOD_NOT_ALLOWED,
+                [(header::ALLOW, ALLOW)],
+                "Repositories are read-only over WebDAV",
+            )
+                .into_response();
+        }
+    }
+
+    let repo = match authorize(state, headers, namespace, repo).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    let path = path.trim_matches('/');
+    let base = format!(
+        "/dav/{}/{}/",
+        urlencoding::encode(namespace),
+        urlencoding::encode(&repo.name)
+    );
+
+    let result = if method == "PROPFIND" {
+        propfind(state, headers, &repo, path, &base)
+    } else {
+        get(state, headers, &repo, path, &base).await
+    };
+    result.unwrap_or_else(IntoResponse::into_response)
+}
+
+/// The same check as git over HTTP, answering with a basic auth challenge
+/// so WebDAV clients prompt for credentials.
+async fn authorize(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    namespace: &str,
+    repo: &str,
+) -> Result<Repo, Response> {
+    let resolve = async {
+        let repo_name = normalize_repo_name(repo, state.repo_name_policy)
+            .map_err(|_| GitAuthError::RepoNotFound)?;
+        let git_auth = extract_git_auth(headers, state).await?;
+        let namespace = state
+            .store
+            .get_namespace_by_name(namespace)
+            .map_err(|_| GitAuthError::InternalError)?
+            .ok_or(GitAuthError::RepoNotFound)?;
+        let repo = state
+            .store
+            .get_repo(&namespace.id, &repo_name)
+            .map_err(|_| GitAuthError::InternalError)?
+            .ok_or(GitAuthError::RepoNotFound)?;
+        check_git_access(state, &git_auth, &namespace, Some(&repo), false)?;
+        Ok(repo)
+    };
+    resolve.await.map_err(|err: GitAuthError| {
+        let mut response = (err.status_code(), err.message()).into_response();
+        if err.requires_auth_header() {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                "Basic realm=\"cutman\"".parse().unwrap(),
+            );
+        }
+        response
+    })
+}
+
+fn propfind(
+    state: &AppState,
+    headers: &HeaderMap,
+    repo: &Repo,
+    path: &str,
+    base: &str,
+) -> Result<Response, ApiError> {
+    // Depth: infinity, which is also what a missing header means, would
+    // walk the whole tree in one response.
+    let depth = match headers.get("depth").and_then(|v| v.to_str().ok()) {
+        Some("0") => 0,
+        Some("1") => 1,
+        _ => {
+            return Ok((
+                StatusCode::FORBIDDEN,
+                [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                 <D:error xmlns:D=\"DAV:\"><D:propfind-finite-depth/></D:error>\n",
+            )
+                .into_response());
+        }
+    };
+
+    let git_repo = open_repo(
+        &state.repo_path(&repo.namespace_id, &repo.name),
+        repo.object_format,
+    )?;
+    let resources = find_resources(&git_repo, path, depth)?;
+    Ok((
+        StatusCode::MULTI_STATUS,
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        multistatus(base, &resources),
+    )
+        .into_response())
+}
+
+async fn get(
+    state: &AppState,
+    headers: &HeaderMap,
+    repo: &Repo,
+    path: &str,
+    base: &str,
+) -> Result<Response, ApiError> {
+    let blob = {
+        let git_repo = open_repo(
+            &state.repo_path(&repo.namespace_id, &repo.name),
+            repo.object_format,
+        )?;
+        let found = if path.is_empty() {
+            Err(GitError::NotAFile)
+        } else {
+            RawBlob::find(&git_repo, "", path)
+        };
+        match found {
+            Ok(blob) => blob,
+            Err(GitError::NotAFile) => {
+                let resources = find_resources(&git_repo, path, 1)?;
+                return Ok(Html(listing(base, &resources)).into_response());
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+    let filename = path.rsplit('/').next().unwrap_or(path);
+    serve_raw_blob(state, headers, blob, filename).await
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Kind {
+    Collection,
+    File { size: u64 },
+}
+
+/// One entry of a PROPFIND response, addressed relative to the repo root.
+#[derive(Debug)]
+struct Resource {
+    path: String,
+    kind: Kind,
+    oid: Oid,
+    /// Seconds since the epoch of the commit being served.
+    modified: i64,
+}
+
+/// The resource at `path` on the default branch and, at depth 1, the
+/// entries of a directory. Submodules are left out.
+fn find_resources(repo: &Repository, path: &str, depth: u8) -> Result<Vec<Resource>, GitError> {
+    let commit = get_commit(repo, resolve_ref(repo, "")?)?;
+    let modified = commit.time().seconds();
+    let root = get_tree(repo, &commit)?;
+
+    let (kind, oid) = if path.is_empty() {
+        (Some(ObjectType::Tree), root.id())
+    } else {
+        let entry = root
+            .get_path(FsPath::new(path))
+            .map_err(|_| GitError::PathNotFound(path.to_string()))?;
+        (entry.kind(), entry.id())
+    };
+    let resource = |path: String, kind: Option<ObjectType>, oid: Oid| {
+        let kind = match kind {
+            Some(ObjectType::Tree) => Kind::Collection,
+            Some(ObjectType::Blob) => Kind::File {
+                size: blob_size(repo, oid)?,
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(Resource {
+            path,
+            kind,
+            oid,
+            modified,
+        }))
+    };
+
+    let Some(target) = resource(path.to_string(), kind, oid)? else {
+        return Err(GitError::PathNotFound(path.to_string()));
+    };
+    let mut resources = vec![target];
+    if depth == 0 || kind != Some(ObjectType::Tree) {
+        return Ok(resources);
+    }
+
+    let tree = repo
+        .find_tree(oid)
+        .map_err(|e| GitError::Internal(format!("Failed to get tree: {e}")))?;
+    for entry in &tree {
+        let Some(name) = entry.name() else {
+            continue;
+        };
+        let child = if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{path}/{name}")
+        };
+        resources.extend(resource(child, entry.kind(), entry.id())?);
+    }
+    Ok(resources)
+}
+
+/// Only the header, so sizing a directory listing inflates nothing.
+fn blob_size(repo: &Repository, oid: Oid) -> Result<u64, GitError> {
+    repo.odb()
+        .and_then(|odb| odb.read_header(oid))
+        .map(|(size, _)| size as u64)
+        .map_err(|e| GitError::Internal(format!("Failed to read blob header: {e}")))
+}
+
+fn href(base: &str, resource: &Resource) -> String {
+    let mut href = base.to_string();
+    let segments: Vec<_> = resource
+        .path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| urlencoding::encode(s).into_owned())
+        .collect();
+    href.push_str(&segments.join("/"));
+    if resource.kind == Kind::Collection && !segments.is_empty() {
+        href.push('/');
+    }
+    href
+}
+
+fn multistatus(base: &str, resources: &[Resource]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    for resource in resources {
+        let name = resource.path.rsplit('/').next().unwrap_or_default();
+        let modified = chrono::DateTime::from_timestamp(resource.modified, 0)
+            .unwrap_or_default()
+            .format("%a, %d %b %Y %H:%M:%S GMT");
+        let kind = match resource.kind {
+            Kind::Collection => "<D:resourcetype><D:collection/></D:resourcetype>".to_string(),
+            Kind::File { size } => format!(
+                "<D:resourcetype/><D:getcontentlength>{size}</D:getcontentlength>\
+                 <D:getcontenttype>{}</D:getcontenttype>",
+                content_type_for_extension(name).unwrap_or("application/octet-stream")
+            ),
+        };
+        xml.push_str(&format!(
+            "<D:response><D:href>{}</D:href><D:propstat><D:prop>\
+             <D:displayname>{}</D:displayname>{kind}\
+             <D:getetag>\"{}\"</D:getetag><D:getlastmodified>{modified}</D:getlastmodified>\
+             </D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n",
+            escape_xml(&href(base, resource)),
+            escape_xml(name),
+            resource.oid,
+        ));
+    }
+    xml.push_str("</D:multistatus>\n");
+    xml
+}
+
+/// A plain index for browsers that open a directory.
+fn listing(base: &str, resources: &[Resource]) -> String {
+    let mut html = String::from("<!DOCTYPE html>\n<ul>\n");
+    for resource in resources.iter().skip(1) {
+        let mut name = resource
+            .path
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        if resource.kind == Kind::Collection {
+            name.push('/');
+        }
+        html.push_str(&format!(
+            "<li><a href=\"{}\">{}</a></li>\n",
+            escape_xml(&href(base, resource)),
+            escape_xml(&name)
+        ));
+    }
+    html.push_str("</ul>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::content::git_ops::{CommitActionOp, CommitAuthor, apply_actions};
+
+    #[test]
+    fn test_propfind_lists_the_default_branch() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let repo = Repository::init_bare(temp.path()).unwrap();
+        let author = CommitAuthor {
+            name: "Test".to_string(),
+            email: "test@example.com".to_string(),
+            author_time: None,
+            commit_time: None,
+        };
+        let actions = [
+            CommitActionOp::Create {
+                path: "README.md".to_string(),
+                content: b"hello".to_vec(),
+            },
+            CommitActionOp::Create {
+                path: "docs/a b.txt".to_string(),
+                content: b"spaced".to_vec(),
+            },
+        ];
+        apply_actions(&repo, "main", &actions, "init", &author).unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+
+        let root = find_resources(&repo, "", 1).unwrap();
+        let paths: Vec<_> = root.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths, ["", "README.md", "docs"]);
+        assert_eq!(root[1].kind, Kind::File { size: 5 });
+        assert_eq!(root[2].kind, Kind::Collection);
+        assert_eq!(find_resources(&repo, "docs", 0).unwrap().len(), 1);
+        assert!(matches!(
+            find_resources(&repo, "missing", 0),
+            Err(GitError::PathNotFound(_))
+        ));
+
+        let xml = multistatus("/dav/ns/r/", &find_resources(&repo, "docs", 1).unwrap());
+        assert!(xml.contains("<D:href>/dav/ns/r/docs/</D:href>"), "{xml}");
+        assert!(
+            xml.contains("<D:href>/dav/ns/r/docs/a%20b.txt</D:href>"),
+            "{xml}"
+        );
+        assert!(
+            xml.contains("<D:getcontentlength>6</D:getcontentlength>"),
+            "{xml}"
+        );
+        assert!(xml.contains("<D:getcontenttype>text/plain"), "{xml}");
+    }
+}
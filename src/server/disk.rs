@@ -0,0 +1,225 @@
+//! Free-space watchdog for the data directory. Below the configured
+//! thresholds it logs alerts and, at the critical level, makes the git and
+//! LFS endpoints reject writes while reads keep working.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::server::AppState;
+use crate::units::format_size;
+
+/// Free-space level of the data directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiskLevel {
+    Ok,
+    /// Below `warn_free_bytes`: still writable, alerts are logged.
+    Low,
+    /// Below `min_free_bytes`: pushes and LFS uploads are rejected.
+    Critical,
+}
+
+impl DiskLevel {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Low,
+            2 => Self::Critical,
+            _ => Self::Ok,
+        }
+    }
+}
+
+/// Free-space thresholds, in bytes. Unset thresholds never trigger.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiskThresholds {
+    pub min_free_bytes: Option<u64>,
+    pub warn_free_bytes: Option<u64>,
+}
+
+impl DiskThresholds {
+    fn level(&self, free: u64) -> DiskLevel {
+        if self.min_free_bytes.is_some_and(|min| free < min) {
+            DiskLevel::Critical
+        } else if self.warn_free_bytes.is_some_and(|warn| free < warn) {
+            DiskLevel::Low
+        } else {
+            DiskLevel::Ok
+        }
+    }
+}
+
+/// Latest free-space reading, as reported by `/readyz`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct DiskStatus {
+    pub level: DiskLevel,
+    pub free_bytes: u64,
+    pub total_bytes: u64,
+    pub writes_allowed: bool,
+}
+
+pub struct DiskWatchdog {
+    path: PathBuf,
+    thresholds: DiskThresholds,
+    interval: Duration,
+    free: AtomicU64,
+    total: AtomicU64,
+    level: AtomicU8,
+}
+
+impl DiskWatchdog {
+    /// Creates a watchdog and takes an initial reading, so writes are
+    /// refused from the first request if the disk is already full.
+    #[must_use]
+    pub fn new(path: PathBuf, thresholds: DiskThresholds, interval: Duration) -> Self {
+        let watchdog = Self {
+            path,
+            thresholds,
+            interval,
+            free: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            level: AtomicU8::new(DiskLevel::Ok as u8),
+        };
+        watchdog.check();
+        watchdog
+    }
+
+    #[must_use]
+    pub fn level(&self) -> DiskLevel {
+        DiskLevel::from_u8(self.level.load(Ordering::Relaxed))
+    }
+
+    #[must_use]
+    pub fn allows_writes(&self) -> bool {
+        self.level() != DiskLevel::Critical
+    }
+
+    #[must_use]
+    pub fn status(&self) -> DiskStatus {
+        DiskStatus {
+            level: self.level(),
+            free_bytes: self.free.load(Ordering::Relaxed),
+            total_bytes: self.total.load(Ordering::Relaxed),
+            writes_allowed: self.allows_writes(),
+        }
+    }
+
+    /// Re-reads free space and logs when the level changes. A failed
+    /// reading keeps the previous level.
+    pub fn check(&self) {
+        let (free, total) = match free_space(&self.path) {
+            Ok(space) => space,
+            Err(e) => {
+                warn!("Failed to read free space of {}: {e}", self.path.display());
+                return;
+            }
+        };
+        self.free.store(free, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+
+        let level = self.thresholds.level(free);
+        let previous = DiskLevel::from_u8(self.level.swap(level as u8, Ordering::Relaxed));
+        if level == previous {
+            return;
+        }
+        let free = format_size(free as i64);
+        match level {
+            DiskLevel::Critical => warn!(
+                "Disk space critical ({free} free in {}): rejecting pushes and LFS uploads",
+                self.path.display()
+            ),
+            DiskLevel::Low => warn!("Disk space low ({free} free in {})", self.path.display()),
+            DiskLevel::Ok => info!("Disk space recovered ({free} free)"),
+        }
+    }
+}
+
+/// Returns `(available, total)` bytes for the filesystem holding `path`.
+#[cfg(unix)]
+fn free_space(path: &std::path::Path) -> std::io::Result<(u64, u64)> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: statvfs only writes to the zeroed struct we hand it.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // Field widths differ between platforms.
+    #[allow(clippy::unnecessary_cast)]
+    let block = stat.f_frsize as u64;
+    #[allow(clippy::unnecessary_cast)]
+    let (available, blocks) = (stat.f_bavail as u64, stat.f_blocks as u64);
+    Ok((available * block, blocks * block))
+}
+
+#[cfg(not(unix))]
+fn free_space(_path: &std::path::Path) -> std::io::Result<(u64, u64)> {
+    Err(std::io::Error::other(
+        "free space is not available on this platform",
+    ))
+}
+
+/// Re-checks free space on the watchdog's interval. Does nothing when the
+/// state has no watchdog.
+pub fn spawn_disk_watchdog(state: Arc<AppState>) {
+    let Some(watchdog) = state.disk.clone() else {
+        return;
+    };
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(watchdog.interval).await;
+            let watchdog = watchdog.clone();
+            let _ = tokio::task::spawn_blocking(move || watchdog.check()).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_levels() {
+        let thresholds = DiskThresholds {
+            min_free_bytes: Some(100),
+            warn_free_bytes: Some(1000),
+        };
+        assert_eq!(thresholds.level(50), DiskLevel::Critical);
+        assert_eq!(thresholds.level(100), DiskLevel::Low);
+        assert_eq!(thresholds.level(999), DiskLevel::Low);
+        assert_eq!(thresholds.level(1000), DiskLevel::Ok);
+
+        assert_eq!(DiskThresholds::default().level(0), DiskLevel::Ok);
+    }
+
+    #[test]
+    fn test_watchdog_reads_free_space() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let ok = DiskWatchdog::new(
+            dir.path().to_path_buf(),
+            DiskThresholds::default(),
+            Duration::from_secs(30),
+        );
+        let status = ok.status();
+        assert!(status.total_bytes > 0);
+        assert!(status.writes_allowed);
+
+        let full = DiskWatchdog::new(
+            dir.path().to_path_buf(),
+            DiskThresholds {
+                min_free_bytes: Some(u64::MAX),
+                warn_free_bytes: None,
+            },
+            Duration::from_secs(30),
+        );
+        assert_eq!(full.level(), DiskLevel::Critical);
+        assert!(!full.allows_writes());
+    }
+}
@@ -1,6 +1,13 @@
 use serde::{Deserialize, Serialize};
 
-use crate::types::Namespace;
+use crate::auth::TokenFormat;
+use crate::commit_policy::CommitPolicy;
+use crate::config::FeatureFlags;
+use crate::email_policy::EmailPolicy;
+use crate::types::{
+    ChangeKind, Folder, Namespace, ObjectFormat, OrphanTokenReason, Repo, RepoSizeSnapshot, Tag,
+    TokenFilter, TokenSort, TrafficClass, Visibility,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct CreateNamespaceRequest {
@@ -16,10 +23,34 @@ pub struct CreatePrincipalRequest {
     pub namespace_name: String,
 }
 
+/// Replaces every verified email of a principal.
+#[derive(Debug, Deserialize)]
+pub struct SetPrincipalEmailsRequest {
+    pub emails: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrincipalEmailsResponse {
+    pub principal_id: String,
+    pub emails: Vec<String>,
+}
+
 #[derive(Debug, Default, Deserialize)]
 pub struct CreatePrincipalTokenRequest {
     #[serde(default)]
     pub expires_in_seconds: Option<i64>,
+    /// `batch` queues the token's clones and archives behind interactive
+    /// traffic. Defaults to `interactive`.
+    #[serde(default)]
+    pub traffic_class: Option<TrafficClass>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReissueTokenRequest {
+    /// How long the old token keeps working. Without it the old token is
+    /// revoked as soon as the new one exists.
+    #[serde(default)]
+    pub grace_seconds: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -36,6 +67,9 @@ pub struct RepoGrantRequest {
     pub allow: Vec<String>,
     #[serde(default)]
     pub deny: Vec<String>,
+    /// Files and directories the principal's pushes may change.
+    #[serde(default)]
+    pub paths: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -52,6 +86,8 @@ pub struct RepoGrantResponse {
     pub allow: Vec<&'static str>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub deny: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -65,6 +101,8 @@ pub struct TokenResponse {
     pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub traffic_class: TrafficClass,
+    pub format: TokenFormat,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub namespace_grants: Vec<NamespaceGrantResponse>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -77,6 +115,308 @@ pub struct CreateTokenResponse {
     pub metadata: TokenResponse,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct ListTokensParams {
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Tokens per page. Defaults to 50, at most 200.
+    #[serde(default)]
+    pub limit: Option<i32>,
+    #[serde(default)]
+    pub principal_id: Option<String>,
+    #[serde(default)]
+    pub is_admin: Option<bool>,
+    #[serde(default)]
+    pub expired: Option<bool>,
+    #[serde(default)]
+    pub unused_days: Option<u32>,
+    #[serde(default)]
+    pub sort: TokenSort,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListAuditEventsParams {
+    /// The `seq` of the last event seen.
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Events per page. Defaults to 50, at most 200.
+    #[serde(default)]
+    pub limit: Option<i32>,
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub repo_id: Option<String>,
+    #[serde(default)]
+    pub principal_id: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListRepoHoldsParams {
+    /// Only holds on repos in this namespace.
+    #[serde(default)]
+    pub namespace: Option<String>,
+}
+
+/// Places or lifts a legal hold; the reason goes in the audit log.
+#[derive(Debug, Deserialize)]
+pub struct RepoHoldRequest {
+    pub reason: String,
+}
+
+/// Switches instance-wide private mode; also the shape of its status.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrivateModeRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Revokes every token the filter matches except the caller's own.
+#[derive(Debug, Deserialize)]
+pub struct RevokeTokensRequest {
+    #[serde(flatten)]
+    pub filter: TokenFilter,
+    /// List the tokens that would be revoked without revoking them.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevokeTokensResponse {
+    pub dry_run: bool,
+    /// How many tokens were revoked, or would be on a dry run.
+    pub revoked: usize,
+    pub token_ids: Vec<String>,
+}
+
+/// Tokens still in the legacy format, for planning their reissue.
+#[derive(Debug, Serialize)]
+pub struct LegacyTokenReportResponse {
+    /// Whether the server still accepts legacy tokens.
+    pub accepted: bool,
+    pub total: usize,
+    /// Legacy tokens used in the last 30 days.
+    pub recently_used: usize,
+    pub tokens: Vec<TokenResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MaintenanceStatusResponse {
+    /// Whether passes run on a schedule (they can always be triggered).
+    pub scheduled: bool,
+    pub task: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval_secs: Option<u64>,
+    pub running: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run_started_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run_finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub repos: Vec<RepoMaintenanceResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temp_sweep: Option<TempSweepResponse>,
+}
+
+/// A namespace's shared object store and the repos borrowing from it.
+#[derive(Debug, Serialize)]
+pub struct SharedObjectsResponse {
+    pub namespace: String,
+    /// Bytes the store takes on disk; 0 when it has no members.
+    pub size_bytes: i64,
+    /// Earliest to join first.
+    pub members: Vec<SharedObjectMemberResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SharedObjectMemberResponse {
+    pub repo_id: String,
+    pub name: String,
+    /// Bytes the repo keeps of its own.
+    pub size_bytes: i64,
+    pub joined_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DedupParams {
+    /// Only compare repos in this namespace.
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// How many repo pairs to report.
+    #[serde(default)]
+    pub top: Option<usize>,
+}
+
+/// Objects stored more than once across repos. Sizes are bytes on disk, so
+/// deltified objects make them estimates.
+#[derive(Debug, Serialize)]
+pub struct DedupReportResponse {
+    pub repos_scanned: u64,
+    /// Repos whose objects couldn't be read.
+    pub repos_skipped: u64,
+    /// Objects and bytes stored, counting each repo's copy.
+    pub total_objects: u64,
+    pub total_bytes: u64,
+    /// Objects and bytes stored, counting each object once.
+    pub unique_objects: u64,
+    pub unique_bytes: u64,
+    /// Objects more than one repo stores.
+    pub shared_objects: u64,
+    /// What a shared object store would save.
+    pub savings_bytes: u64,
+    /// The pairs sharing the most bytes, which either could save by
+    /// borrowing the other's objects through alternates.
+    pub pairs: Vec<DedupPairResponse>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DedupRepoResponse {
+    pub id: String,
+    pub namespace: String,
+    pub name: String,
+    pub objects: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DedupPairResponse {
+    pub a: DedupRepoResponse,
+    pub b: DedupRepoResponse,
+    pub shared_objects: u64,
+    pub shared_bytes: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct OrphanReportParams {
+    /// Days a folder must have gone unchanged to be listed.
+    #[serde(default)]
+    pub folder_age_days: Option<u32>,
+    /// `json` (the default) or `csv`.
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+/// Namespaces, tokens, and folders nothing uses any more.
+#[derive(Debug, Serialize)]
+pub struct OrphanReportResponse {
+    pub folder_age_days: u32,
+    /// Namespaces with no repos and no users.
+    pub namespaces: Vec<OrphanNamespaceResponse>,
+    /// Tokens whose user was deleted or deactivated.
+    pub tokens: Vec<OrphanTokenResponse>,
+    /// Folders with no repos in them or their subfolders, unchanged for
+    /// `folder_age_days`.
+    pub folders: Vec<OrphanFolderResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrphanNamespaceResponse {
+    pub id: String,
+    pub name: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrphanTokenResponse {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub principal_id: Option<String>,
+    pub reason: OrphanTokenReason,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrphanFolderResponse {
+    pub id: i64,
+    pub namespace: String,
+    pub path: String,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Removes entries of the orphan report. Anything that is no longer an
+/// orphan is skipped.
+#[derive(Debug, Deserialize)]
+pub struct OrphanCleanupRequest {
+    /// Namespace IDs.
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    #[serde(default)]
+    pub folders: Vec<i64>,
+    #[serde(default)]
+    pub folder_age_days: Option<u32>,
+    /// Nothing is removed unless set; the response then lists what would
+    /// be.
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrphanCleanupResponse {
+    pub confirmed: bool,
+    /// What was removed, or would be without `confirm`.
+    pub namespaces: Vec<String>,
+    pub tokens: Vec<String>,
+    pub folders: Vec<i64>,
+    /// Requested entries that aren't orphans.
+    pub skipped: usize,
+}
+
+/// LFS transfers through the server since it started, by namespace.
+#[derive(Debug, Serialize)]
+pub struct LfsTransfersResponse {
+    pub since: chrono::DateTime<chrono::Utc>,
+    pub upload_bytes_per_sec: Option<u64>,
+    pub download_bytes_per_sec: Option<u64>,
+    pub namespaces: Vec<NamespaceTransfersResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NamespaceTransfersResponse {
+    pub namespace: String,
+    #[serde(flatten)]
+    pub totals: crate::server::TransferTotals,
+}
+
+/// Stale temp files removed by the crash-recovery sweeper.
+#[derive(Debug, Serialize)]
+pub struct TempSweepResponse {
+    pub max_age_secs: u64,
+    pub interval_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_files_removed: u64,
+    pub last_bytes_reclaimed: u64,
+    /// Totals since the server started.
+    pub files_removed: u64,
+    pub bytes_reclaimed: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepoMaintenanceResponse {
+    pub repo_id: String,
+    pub namespace_id: String,
+    pub name: String,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub duration_ms: u64,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub size_before: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_after: Option<i64>,
+}
+
 #[derive(Debug, Default, Deserialize)]
 pub struct PaginationParams {
     #[serde(default)]
@@ -97,9 +437,192 @@ pub struct CreateRepoRequest {
     #[serde(default)]
     pub description: Option<String>,
     #[serde(default)]
-    pub public: bool,
+    pub visibility: Option<Visibility>,
+    /// Deprecated: `true` means public and `false` private. Ignored when
+    /// `visibility` is set.
+    #[serde(default)]
+    pub public: Option<bool>,
     #[serde(default)]
     pub namespace: Option<String>,
+    /// Hash function for the repo's objects. Cannot be changed later.
+    #[serde(default)]
+    pub object_format: ObjectFormat,
+    /// Create the repo as a pull mirror of an external URL.
+    #[serde(default)]
+    pub mirror: Option<CreateMirrorRequest>,
+    /// Commit a README, and optionally a license and .gitignore, to the
+    /// default branch so the repo isn't empty.
+    #[serde(default)]
+    pub auto_init: Option<AutoInitRequest>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AutoInitRequest {
+    /// License template by SPDX identifier, e.g. "MIT" or "Apache-2.0".
+    #[serde(default)]
+    pub license: Option<String>,
+    /// .gitignore template, e.g. "Rust" or "Node".
+    #[serde(default)]
+    pub gitignore: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateMirrorRequest {
+    /// `https`, `http`, or `git` URL. Credentials may be embedded and are
+    /// never returned.
+    pub url: String,
+    #[serde(default)]
+    pub interval_secs: Option<i64>,
+}
+
+/// Pull mirror settings and the outcome of the most recent sync.
+#[derive(Debug, Serialize)]
+pub struct MirrorResponse {
+    pub repo_id: String,
+    /// Upstream URL with any credentials redacted.
+    pub url: String,
+    pub interval_secs: i64,
+    /// "pending" until the first sync, then "success" or "failed".
+    pub last_sync_status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_sync_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub syncing: bool,
+    /// When a sync requested through `POST /repos/{id}/sync` starts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub triggered_sync_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Fields left out keep their current value, or their default when the
+/// export is created.
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateExportRequest {
+    /// `https`, `http`, or `git` URL to push to. Required when creating the
+    /// export. Credentials may be embedded and are never returned.
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub interval_secs: Option<i64>,
+    /// Ref patterns to export, e.g. `refs/heads/main` or `refs/tags/v*`.
+    /// Defaults to every branch and tag.
+    #[serde(default)]
+    pub include: Option<Vec<String>>,
+    /// Ref patterns left out even when they match `include`.
+    #[serde(default)]
+    pub exclude: Option<Vec<String>>,
+}
+
+/// Scheduled export settings and the outcome of the most recent sync.
+#[derive(Debug, Serialize)]
+pub struct ExportResponse {
+    pub repo_id: String,
+    /// Remote URL with any credentials redacted.
+    pub url: String,
+    pub interval_secs: i64,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// "pending" until the first sync, then "success" or "failed".
+    pub last_sync_status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_sync_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Branches and tags on the remote after the last successful sync.
+    pub exported_refs: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub syncing: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdatePagesRequest {
+    /// Branch to publish. Defaults to the repo's default branch.
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// Directory of the branch to publish, e.g. "docs". Defaults to the root.
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Pages settings and the outcome of the most recent build.
+#[derive(Debug, Serialize)]
+pub struct PagesResponse {
+    pub repo_id: String,
+    pub branch: String,
+    pub path: String,
+    /// Where the site is served.
+    pub url: String,
+    /// "pending" until the first build, then "success" or "failed".
+    pub last_build_status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_build_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Commit the published site was built from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_sha: Option<String>,
+    pub size_bytes: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub building: bool,
+}
+
+/// A repository's commit message rules.
+#[derive(Debug, Serialize)]
+pub struct CommitPolicyResponse {
+    pub repo_id: String,
+    #[serde(flatten)]
+    pub policy: CommitPolicy,
+}
+
+/// The email rules for commits pushed to a namespace.
+#[derive(Debug, Serialize)]
+pub struct EmailPolicyResponse {
+    pub namespace: String,
+    #[serde(flatten)]
+    pub policy: EmailPolicy,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LfsUsageParams {
+    /// How many of the largest objects to list. Defaults to 10, at most 100.
+    #[serde(default)]
+    pub limit: Option<i32>,
+}
+
+/// LFS objects a repository references.
+#[derive(Debug, Serialize)]
+pub struct LfsUsageResponse {
+    pub repo_id: String,
+    pub object_count: i64,
+    pub total_bytes: i64,
+    /// Bytes of objects no other repository references, which deleting
+    /// this one would free.
+    pub unshared_bytes: i64,
+    pub largest: Vec<LfsObjectResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LfsObjectResponse {
+    pub oid: String,
+    pub size: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RepoSizeHistoryParams {
+    /// How many days back to report. Defaults to 90, at most 3650.
+    #[serde(default)]
+    pub days: Option<i32>,
+}
+
+/// How a repository's size changed over recent days.
+#[derive(Debug, Serialize)]
+pub struct RepoSizeHistoryResponse {
+    pub repo_id: String,
+    /// Git and LFS bytes gained from the first snapshot to the last,
+    /// negative when the repository shrank.
+    pub growth_bytes: i64,
+    /// Days on which the size changed, oldest first. The first may predate
+    /// the window: it holds the size going into it.
+    pub snapshots: Vec<RepoSizeSnapshot>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -109,9 +632,31 @@ pub struct UpdateRepoRequest {
     #[serde(default)]
     pub description: Option<String>,
     #[serde(default)]
+    pub visibility: Option<Visibility>,
+    /// Deprecated: `true` means public and `false` private. Ignored when
+    /// `visibility` is set.
+    #[serde(default)]
     pub public: Option<bool>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CreateSshKeyRequest {
+    /// OpenSSH public key line, e.g. the contents of `~/.ssh/id_ed25519.pub`.
+    pub public_key: String,
+    /// Display name; defaults to the key comment.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSigningKeyRequest {
+    /// ASCII-armored OpenPGP public key, or an OpenSSH public key line.
+    pub public_key: String,
+    /// Display name; defaults to the key's user ID or comment.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateTagRequest {
     pub name: String,
@@ -195,9 +740,95 @@ pub struct PrincipalGrantResponse {
     pub deny: Vec<&'static str>,
 }
 
+/// A single `git config` entry recommended for clients of this server.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitConfigRecommendation {
+    pub key: String,
+    pub value: String,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetupResponse {
+    pub base_url: String,
+    /// Prefix for smart HTTP remotes: `{git_http_url}/{namespace}/{repo}.git`.
+    pub git_http_url: String,
+    /// Prefix for SSH remotes, when the server accepts git over SSH.
+    pub git_ssh_url: Option<String>,
+    pub git_config: Vec<GitConfigRecommendation>,
+}
+
+/// What this server offers, for clients deciding how to talk to it. Open
+/// to anonymous callers.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CapabilitiesResponse {
+    pub version: String,
+    /// Announcement set by the server admin, also shown to git clients.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banner: Option<String>,
+    pub ssh_port: Option<u16>,
+    pub object_formats: Vec<ObjectFormat>,
+    /// Whether upload-pack advertises pre-generated bundles.
+    pub bundle_uri: bool,
+    /// Deepest shallow clone accepted, when limited.
+    pub max_clone_depth: Option<u32>,
+    /// Capability groups and whether each is switched on.
+    #[serde(default)]
+    pub features: FeatureFlags,
+}
+
 #[derive(Debug, Serialize)]
 pub struct NamespaceResponse {
     #[serde(flatten)]
     pub namespace: Namespace,
     pub is_primary: bool,
 }
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SyncParams {
+    /// `cursor` from the previous response. Omit for a full snapshot.
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+/// A repository as reported by the sync endpoint, with its tags.
+#[derive(Debug, Serialize)]
+pub struct SyncRepo {
+    #[serde(flatten)]
+    pub repo: Repo,
+    pub tag_ids: Vec<String>,
+}
+
+/// An entity that was deleted since the cursor.
+#[derive(Debug, Serialize)]
+pub struct SyncDeleted {
+    pub kind: ChangeKind,
+    pub id: String,
+}
+
+/// Entities the caller can see that changed since the cursor, or all of
+/// them when `reset` is set. Empty lists are omitted.
+#[derive(Debug, Default, Serialize)]
+pub struct SyncResponse {
+    /// Pass back as `cursor` to get the changes that follow.
+    pub cursor: String,
+    /// This is a full snapshot: anything the client holds that is missing
+    /// from it has been deleted or is no longer visible.
+    pub reset: bool,
+    /// More changes are waiting; request the next page right away.
+    pub has_more: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub repos: Vec<SyncRepo>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub folders: Vec<Folder>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<Tag>,
+    /// The caller's own grants. Only sent with snapshots; any change to
+    /// them forces one.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub namespace_grants: Vec<NamespaceGrantResponse>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub repo_grants: Vec<RepoGrantResponse>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub deleted: Vec<SyncDeleted>,
+}
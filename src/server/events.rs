@@ -0,0 +1,553 @@
+//! Publishes server events to the `[events]` message bus: pushes,
+//! repositories created and deleted, LFS uploads, and audit events.
+//!
+//! Events are queued in memory and sent one at a time by a background
+//! task, so nothing waits on the bus. A send that fails is retried with
+//! backoff until it goes through. While the bus is down the queue fills,
+//! and events that don't fit are logged and dropped.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::config::{EventKind, EventsConfig};
+use crate::types::{AuditEvent, Namespace, Repo};
+
+use super::AppState;
+use super::push_alerts::Push;
+
+/// Wait after the first failed send. It doubles with each retry.
+const FIRST_BACKOFF: Duration = Duration::from_secs(1);
+/// Longest wait between retries.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Something that happened on the server, as published to the bus.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    /// Unique per event, so consumers can drop redeliveries.
+    pub id: String,
+    pub kind: EventKind,
+    pub occurred_at: DateTime<Utc>,
+    pub namespace_id: Option<String>,
+    pub repo_id: Option<String>,
+    pub principal_id: Option<String>,
+    /// Kind-specific details.
+    pub data: serde_json::Value,
+}
+
+impl Event {
+    #[must_use]
+    pub fn new(kind: EventKind, data: serde_json::Value) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind,
+            occurred_at: Utc::now(),
+            namespace_id: None,
+            repo_id: None,
+            principal_id: None,
+            data,
+        }
+    }
+
+    #[must_use]
+    pub fn namespace(mut self, namespace_id: &str) -> Self {
+        self.namespace_id = Some(namespace_id.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn repo(mut self, repo_id: &str) -> Self {
+        self.repo_id = Some(repo_id.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn principal(mut self, principal_id: Option<&str>) -> Self {
+        self.principal_id = principal_id.map(str::to_string);
+        self
+    }
+
+    /// What the event is filed under on the bus. Events with the same key
+    /// stay in order.
+    #[must_use]
+    pub fn key(&self) -> &str {
+        self.repo_id
+            .as_deref()
+            .or(self.namespace_id.as_deref())
+            .unwrap_or(&self.id)
+    }
+}
+
+/// Delivers events to a message bus.
+pub trait EventSink: Send + Sync {
+    /// Sends one event, `payload` being its JSON encoding.
+    fn publish<'a>(
+        &'a self,
+        event: &'a Event,
+        payload: &'a [u8],
+    ) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+pub struct Events {
+    kinds: Vec<EventKind>,
+    queue: mpsc::Sender<Event>,
+    dropped: AtomicU64,
+}
+
+impl Events {
+    /// Connects to the configured bus in the background and starts
+    /// sending. Must be called within the runtime.
+    pub fn new(config: &EventsConfig) -> anyhow::Result<Self> {
+        Ok(Self::with_sink(
+            sink(config)?,
+            config.kinds.clone(),
+            config.queue_size,
+        ))
+    }
+
+    /// Starts sending `kinds` of event (every kind when empty) to `sink`,
+    /// holding up to `queue_size` unsent.
+    #[must_use]
+    pub fn with_sink(sink: Box<dyn EventSink>, kinds: Vec<EventKind>, queue_size: usize) -> Self {
+        let (queue, rx) = mpsc::channel(queue_size);
+        tokio::spawn(send_events(sink, rx));
+        Self {
+            kinds,
+            queue,
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Queues `event` unless its kind is filtered out or the queue is full.
+    pub fn publish(&self, event: Event) {
+        if !self.kinds.is_empty() && !self.kinds.contains(&event.kind) {
+            return;
+        }
+        if let Err(e) = self.queue.try_send(event) {
+            let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            let event = match e {
+                mpsc::error::TrySendError::Full(event)
+                | mpsc::error::TrySendError::Closed(event) => event,
+            };
+            warn!(
+                "Event queue is full, dropped {} event {} ({dropped} dropped so far)",
+                event.kind.name(),
+                event.id
+            );
+        }
+    }
+
+    /// Events dropped because the queue was full.
+    #[must_use]
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+fn sink(config: &EventsConfig) -> anyhow::Result<Box<dyn EventSink>> {
+    match &config.nats_url {
+        #[cfg(feature = "nats")]
+        Some(url) => Ok(Box::new(nats::NatsSink::new(
+            url,
+            config.nats_token.clone(),
+            &config.nats_subject_prefix,
+        ))),
+        #[cfg(not(feature = "nats"))]
+        Some(url) => Err(anyhow::anyhow!(
+            "events names NATS {url}, but this build of cutman has no NATS support"
+        )),
+        #[cfg(feature = "kafka")]
+        None => Ok(Box::new(kafka::KafkaSink::new(
+            config.kafka_brokers.clone(),
+            &config.kafka_topic,
+        ))),
+        #[cfg(not(feature = "kafka"))]
+        None => Err(anyhow::anyhow!(
+            "events names Kafka brokers {}, but this build of cutman has no Kafka support",
+            config.kafka_brokers.join(",")
+        )),
+    }
+}
+
+/// Publishes `event` if `[events]` is configured.
+pub fn emit(state: &AppState, event: Event) {
+    if let Some(events) = &state.events {
+        events.publish(event);
+    }
+}
+
+/// The `push` event for a push that has landed.
+#[must_use]
+pub fn push_event(push: &Push<'_>) -> Event {
+    let updates: Vec<_> = push
+        .updates
+        .iter()
+        .map(|update| {
+            serde_json::json!({
+                "ref": update.refname,
+                "old": update.old,
+                "new": update.new,
+            })
+        })
+        .collect();
+    Event::new(
+        EventKind::Push,
+        serde_json::json!({
+            "namespace": push.namespace.name,
+            "repo": push.repo.name,
+            "address": push.address,
+            "token_id": push.token_id,
+            "updates": updates,
+        }),
+    )
+    .namespace(&push.namespace.id)
+    .repo(&push.repo.id)
+    .principal(push.principal_id.as_deref())
+}
+
+/// A `repo.created` or `repo.deleted` event.
+#[must_use]
+pub fn repo_event(
+    kind: EventKind,
+    namespace: &Namespace,
+    repo: &Repo,
+    principal_id: Option<&str>,
+) -> Event {
+    Event::new(
+        kind,
+        serde_json::json!({
+            "namespace": namespace.name,
+            "repo": repo.name,
+            "visibility": repo.visibility,
+        }),
+    )
+    .namespace(&namespace.id)
+    .repo(&repo.id)
+    .principal(principal_id)
+}
+
+/// The `audit` event for a recorded audit event.
+#[must_use]
+pub fn audit_event(event: &AuditEvent) -> Event {
+    let mut published = Event::new(
+        EventKind::Audit,
+        serde_json::to_value(event).unwrap_or_default(),
+    )
+    .principal(event.principal_id.as_deref());
+    published.namespace_id.clone_from(&event.namespace_id);
+    published.repo_id.clone_from(&event.repo_id);
+    published
+}
+
+async fn send_events(sink: Box<dyn EventSink>, mut rx: mpsc::Receiver<Event>) {
+    while let Some(event) = rx.recv().await {
+        let payload = match serde_json::to_vec(&event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Failed to encode event {}: {e}", event.id);
+                continue;
+            }
+        };
+        let mut backoff = FIRST_BACKOFF;
+        while let Err(e) = sink.publish(&event, &payload).await {
+            warn!(
+                "Failed to publish {} event {}, retrying in {}s: {e:#}",
+                event.kind.name(),
+                event.id,
+                backoff.as_secs()
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+#[cfg(feature = "nats")]
+mod nats {
+    use futures_util::future::BoxFuture;
+
+    use super::{Event, EventSink};
+
+    /// Publishes each event to `{prefix}.{kind}`, with the event ID as
+    /// `Nats-Msg-Id` so JetStream streams drop redeliveries.
+    pub(super) struct NatsSink {
+        client: tokio::sync::OnceCell<async_nats::Client>,
+        url: String,
+        token: Option<String>,
+        prefix: String,
+    }
+
+    impl NatsSink {
+        pub(super) fn new(url: &str, token: Option<String>, prefix: &str) -> Self {
+            Self {
+                client: tokio::sync::OnceCell::new(),
+                url: url.to_string(),
+                token,
+                prefix: prefix.to_string(),
+            }
+        }
+
+        async fn client(&self) -> anyhow::Result<&async_nats::Client> {
+            self.client
+                .get_or_try_init(|| async {
+                    let mut options = async_nats::ConnectOptions::new().name("cutman");
+                    if let Some(token) = &self.token {
+                        options = options.token(token.clone());
+                    }
+                    Ok(options.connect(self.url.as_str()).await?)
+                })
+                .await
+        }
+    }
+
+    impl EventSink for NatsSink {
+        fn publish<'a>(
+            &'a self,
+            event: &'a Event,
+            payload: &'a [u8],
+        ) -> BoxFuture<'a, anyhow::Result<()>> {
+            Box::pin(async move {
+                let client = self.client().await?;
+                let mut headers = async_nats::HeaderMap::new();
+                headers.insert("Nats-Msg-Id", event.id.as_str());
+                let subject = format!("{}.{}", self.prefix, event.kind.name());
+                client
+                    .publish_with_headers(subject, headers, payload.to_vec().into())
+                    .await?;
+                client.flush().await?;
+                Ok(())
+            })
+        }
+    }
+}
+
+#[cfg(feature = "kafka")]
+mod kafka {
+    use std::collections::{BTreeMap, HashMap};
+    use std::sync::Arc;
+
+    use futures_util::future::BoxFuture;
+    use rskafka::client::partition::{Compression, PartitionClient, UnknownTopicHandling};
+    use rskafka::client::{Client, ClientBuilder};
+    use rskafka::record::Record;
+    use tokio::sync::{Mutex, OnceCell};
+
+    use super::{Event, EventSink};
+
+    /// Produces each event to one topic, on a partition picked from its
+    /// key so a repository's events stay in order.
+    pub(super) struct KafkaSink {
+        brokers: Vec<String>,
+        topic: String,
+        connection: OnceCell<Connection>,
+    }
+
+    struct Connection {
+        client: Client,
+        partitions: Vec<i32>,
+        producers: Mutex<HashMap<i32, Arc<PartitionClient>>>,
+    }
+
+    impl KafkaSink {
+        pub(super) fn new(brokers: Vec<String>, topic: &str) -> Self {
+            Self {
+                brokers,
+                topic: topic.to_string(),
+                connection: OnceCell::new(),
+            }
+        }
+
+        async fn connection(&self) -> anyhow::Result<&Connection> {
+            self.connection
+                .get_or_try_init(|| async {
+                    let client = ClientBuilder::new(self.brokers.clone())
+                        .client_id("cutman")
+                        .build()
+                        .await?;
+                    let partitions: Vec<i32> = client
+                        .list_topics()
+                        .await?
+                        .into_iter()
+                        .find(|topic| topic.name == self.topic)
+                        .map(|topic| topic.partitions.into_iter().collect())
+                        .unwrap_or_default();
+                    if partitions.is_empty() {
+                        anyhow::bail!("Kafka topic {} does not exist", self.topic);
+                    }
+                    Ok(Connection {
+                        client,
+                        partitions,
+                        producers: Mutex::new(HashMap::new()),
+                    })
+                })
+                .await
+        }
+
+        async fn producer(&self, key: &str) -> anyhow::Result<Arc<PartitionClient>> {
+            let connection = self.connection().await?;
+            let partition =
+                connection.partitions[partition_index(key, connection.partitions.len())];
+            let mut producers = connection.producers.lock().await;
+            if let Some(producer) = producers.get(&partition) {
+                return Ok(producer.clone());
+            }
+            let producer = Arc::new(
+                connection
+                    .client
+                    .partition_client(self.topic.as_str(), partition, UnknownTopicHandling::Error)
+                    .await?,
+            );
+            producers.insert(partition, producer.clone());
+            Ok(producer)
+        }
+    }
+
+    impl EventSink for KafkaSink {
+        fn publish<'a>(
+            &'a self,
+            event: &'a Event,
+            payload: &'a [u8],
+        ) -> BoxFuture<'a, anyhow::Result<()>> {
+            Box::pin(async move {
+                let producer = self.producer(event.key()).await?;
+                let record = Record {
+                    key: Some(event.key().as_bytes().to_vec()),
+                    value: Some(payload.to_vec()),
+                    headers: BTreeMap::from([(
+                        "kind".to_string(),
+                        event.kind.name().as_bytes().to_vec(),
+                    )]),
+                    timestamp: event.occurred_at,
+                };
+                producer
+                    .produce(vec![record], Compression::NoCompression)
+                    .await?;
+                Ok(())
+            })
+        }
+    }
+
+    /// FNV-1a of `key`, so a key maps to the same partition across
+    /// restarts.
+    pub(super) fn partition_index(key: &str, partitions: usize) -> usize {
+        let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+        (hash % partitions as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Records what it is sent, failing the first `failures` sends.
+    #[derive(Default)]
+    struct MemorySink {
+        sent: Arc<Mutex<Vec<Vec<u8>>>>,
+        failures: AtomicU64,
+    }
+
+    impl EventSink for MemorySink {
+        fn publish<'a>(
+            &'a self,
+            _event: &'a Event,
+            payload: &'a [u8],
+        ) -> BoxFuture<'a, anyhow::Result<()>> {
+            Box::pin(async move {
+                if self
+                    .failures
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                    .is_ok()
+                {
+                    anyhow::bail!("bus is down");
+                }
+                self.sent.lock().unwrap().push(payload.to_vec());
+                Ok(())
+            })
+        }
+    }
+
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[test]
+    fn test_event_key() {
+        let event = Event::new(EventKind::Audit, serde_json::Value::Null);
+        assert_eq!(event.key(), event.id);
+        let event = event.namespace("ns1");
+        assert_eq!(event.key(), "ns1");
+        assert_eq!(event.repo("r1").key(), "r1");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_publish_filters_and_retries() {
+        let sink = MemorySink {
+            failures: AtomicU64::new(2),
+            ..MemorySink::default()
+        };
+        let sent = sink.sent.clone();
+        let events = Events::with_sink(Box::new(sink), vec![EventKind::Push], 10);
+
+        events.publish(Event::new(EventKind::RepoCreated, serde_json::Value::Null));
+        let push = Event::new(
+            EventKind::Push,
+            serde_json::json!({"ref": "refs/heads/main"}),
+        )
+        .namespace("ns1")
+        .repo("r1");
+        let id = push.id.clone();
+        events.publish(push);
+
+        settle().await;
+        assert!(sent.lock().unwrap().is_empty());
+        // Two failures back off for 1s then 2s.
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        settle().await;
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        let payload: serde_json::Value = serde_json::from_slice(&sent[0]).unwrap();
+        assert_eq!(payload["id"], id);
+        assert_eq!(payload["kind"], "push");
+        assert_eq!(payload["repo_id"], "r1");
+        assert_eq!(payload["data"]["ref"], "refs/heads/main");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_full_queue_drops() {
+        let sink = MemorySink {
+            failures: AtomicU64::new(u64::MAX),
+            ..MemorySink::default()
+        };
+        let events = Events::with_sink(Box::new(sink), Vec::new(), 2);
+        for _ in 0..5 {
+            events.publish(Event::new(EventKind::Audit, serde_json::Value::Null));
+            settle().await;
+        }
+        // One is being retried and two wait in the queue.
+        assert_eq!(events.dropped(), 2);
+    }
+
+    #[cfg(feature = "kafka")]
+    #[test]
+    fn test_partition_index() {
+        assert_eq!(
+            kafka::partition_index("", 7),
+            (0xcbf2_9ce4_8422_2325_u64 % 7) as usize
+        );
+        let index = kafka::partition_index("repo-1", 12);
+        assert!(index < 12);
+        assert_eq!(kafka::partition_index("repo-1", 12), index);
+    }
+}
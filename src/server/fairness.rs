@@ -0,0 +1,341 @@
+//! Weighted fair queuing for expensive operations (clones and fetches,
+//! archives, path search). A fixed number of slots is shared by all
+//! namespaces; when operations are waiting, each freed slot goes to the
+//! waiting namespace with the fewest running operations per unit of weight,
+//! so one tenant's burst of CI clones queues behind itself rather than in
+//! front of everyone else. Requests from batch tokens form a second lane
+//! that is only served when no interactive request is waiting, and never
+//! holds the last quarter of the slots.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::oneshot;
+
+use crate::types::TrafficClass;
+
+type Waiter = (u64, oneshot::Sender<FairPermit>);
+
+#[derive(Default)]
+struct NamespaceQueue {
+    running: usize,
+    weight: u32,
+    /// Arrival sequence number and grant channel of each waiter.
+    interactive: VecDeque<Waiter>,
+    batch: VecDeque<Waiter>,
+}
+
+impl NamespaceQueue {
+    fn waiters(&mut self, class: TrafficClass) -> &mut VecDeque<Waiter> {
+        match class {
+            TrafficClass::Interactive => &mut self.interactive,
+            TrafficClass::Batch => &mut self.batch,
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.running == 0 && self.interactive.is_empty() && self.batch.is_empty()
+    }
+}
+
+#[derive(Default)]
+struct Queues {
+    slots: usize,
+    running: usize,
+    batch_running: usize,
+    next_seq: u64,
+    namespaces: HashMap<String, NamespaceQueue>,
+}
+
+impl Queues {
+    /// Slots batch traffic may hold at once. The rest stay free for
+    /// interactive requests, so a wave of CI clones cannot starve them.
+    fn batch_limit(&self) -> usize {
+        if self.slots > 1 {
+            self.slots - (self.slots / 4).max(1)
+        } else {
+            self.slots
+        }
+    }
+
+    /// The namespace whose `class` waiter should get the next free slot:
+    /// lowest running count relative to its weight, then longest waiting.
+    fn next_namespace(&self, class: TrafficClass) -> Option<String> {
+        self.namespaces
+            .iter()
+            .filter_map(|(name, q)| {
+                let waiters = match class {
+                    TrafficClass::Interactive => &q.interactive,
+                    TrafficClass::Batch => &q.batch,
+                };
+                waiters.front().map(|(seq, _)| (name, q, *seq))
+            })
+            .min_by(|(_, a, a_seq), (_, b, b_seq)| {
+                // Compare running/weight without dividing.
+                let a_share = a.running as u64 * u64::from(b.weight.max(1));
+                let b_share = b.running as u64 * u64::from(a.weight.max(1));
+                a_share.cmp(&b_share).then(a_seq.cmp(b_seq))
+            })
+            .map(|(name, _, _)| name.clone())
+    }
+
+    /// The class to serve next: interactive whenever any is waiting, batch
+    /// only while it is under its limit.
+    fn next_waiter(&self) -> Option<(String, TrafficClass)> {
+        if let Some(namespace) = self.next_namespace(TrafficClass::Interactive) {
+            return Some((namespace, TrafficClass::Interactive));
+        }
+        if self.batch_running < self.batch_limit() {
+            return self
+                .next_namespace(TrafficClass::Batch)
+                .map(|namespace| (namespace, TrafficClass::Batch));
+        }
+        None
+    }
+
+    fn release(&mut self, namespace: &str, class: TrafficClass) {
+        self.running -= 1;
+        if class == TrafficClass::Batch {
+            self.batch_running -= 1;
+        }
+        if let Some(queue) = self.namespaces.get_mut(namespace) {
+            queue.running -= 1;
+            if queue.is_idle() {
+                self.namespaces.remove(namespace);
+            }
+        }
+    }
+}
+
+/// Capacity shared fairly between namespaces.
+pub struct FairQueue {
+    queues: Arc<Mutex<Queues>>,
+    /// Weights by namespace name. Unlisted namespaces weigh 1.
+    weights: HashMap<String, u32>,
+}
+
+/// A held slot. The slot is released when the permit is dropped.
+pub struct FairPermit {
+    queues: Arc<Mutex<Queues>>,
+    namespace: String,
+    class: TrafficClass,
+    armed: bool,
+}
+
+impl FairQueue {
+    #[must_use]
+    pub fn new(slots: usize) -> Self {
+        Self {
+            queues: Arc::new(Mutex::new(Queues {
+                slots: slots.max(1),
+                ..Queues::default()
+            })),
+            weights: HashMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_weights(mut self, weights: HashMap<String, u32>) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Whether any namespace has a weight other than the default.
+    #[must_use]
+    pub fn has_weights(&self) -> bool {
+        !self.weights.is_empty()
+    }
+
+    #[must_use]
+    pub fn weight(&self, namespace_name: &str) -> u32 {
+        self.weights.get(namespace_name).copied().unwrap_or(1)
+    }
+
+    /// Waits for a slot on behalf of `namespace`. Namespaces with a higher
+    /// `weight` get proportionally more slots while others are waiting, and
+    /// interactive requests are served before batch ones.
+    pub async fn acquire(&self, namespace: &str, weight: u32, class: TrafficClass) -> FairPermit {
+        let rx = {
+            let mut queues = lock(&self.queues);
+            let (tx, rx) = oneshot::channel();
+            let seq = queues.next_seq;
+            queues.next_seq += 1;
+            let queue = queues.namespaces.entry(namespace.to_string()).or_default();
+            queue.weight = weight;
+            queue.waiters(class).push_back((seq, tx));
+            rx
+        };
+
+        // Grants at once when a slot is free.
+        dispatch(&self.queues);
+        match rx.await {
+            Ok(permit) => permit,
+            // The senders live in the queue until granted, so this only
+            // happens if the queue itself is gone.
+            Err(_) => unreachable!("fair queue dropped a waiter"),
+        }
+    }
+
+    /// Operations running and waiting, for diagnostics.
+    #[must_use]
+    pub fn load(&self) -> (usize, usize) {
+        let queues = lock(&self.queues);
+        let waiting = queues
+            .namespaces
+            .values()
+            .map(|q| q.interactive.len() + q.batch.len())
+            .sum();
+        (queues.running, waiting)
+    }
+}
+
+fn lock(queues: &Mutex<Queues>) -> std::sync::MutexGuard<'_, Queues> {
+    queues.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Hands free slots to waiters, skipping any that gave up waiting.
+fn dispatch(shared: &Arc<Mutex<Queues>>) {
+    let mut queues = lock(shared);
+    while queues.running < queues.slots {
+        let Some((namespace, class)) = queues.next_waiter() else {
+            return;
+        };
+        let Some(queue) = queues.namespaces.get_mut(&namespace) else {
+            return;
+        };
+        let Some((_, tx)) = queue.waiters(class).pop_front() else {
+            continue;
+        };
+        queue.running += 1;
+        queues.running += 1;
+        if class == TrafficClass::Batch {
+            queues.batch_running += 1;
+        }
+
+        let permit = FairPermit {
+            queues: shared.clone(),
+            namespace: namespace.clone(),
+            class,
+            armed: true,
+        };
+        if let Err(mut permit) = tx.send(permit) {
+            // The waiter went away; take the slot back without re-entering
+            // the lock from the permit's destructor.
+            permit.armed = false;
+            queues.release(&namespace, class);
+        }
+    }
+}
+
+impl Drop for FairPermit {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        lock(&self.queues).release(&self.namespace, self.class);
+        dispatch(&self.queues);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_freed_slots_go_to_the_least_served_namespace() {
+        let queue = Arc::new(FairQueue::new(2));
+        let busy_a = queue.acquire("ci", 1, TrafficClass::Interactive).await;
+        let busy_b = queue.acquire("ci", 1, TrafficClass::Interactive).await;
+
+        // "ci" queues three more, then "docs" arrives last.
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for name in ["ci", "ci", "ci", "docs"] {
+            let queue = queue.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let permit = queue.acquire(name, 1, TrafficClass::Interactive).await;
+                tx.send((name, permit)).unwrap();
+            });
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(queue.load(), (2, 4));
+
+        drop(busy_a);
+        let (first, _permit) = rx.recv().await.unwrap();
+        assert_eq!(first, "docs");
+
+        drop(busy_b);
+        let (second, _permit) = rx.recv().await.unwrap();
+        assert_eq!(second, "ci");
+        assert_eq!(queue.load(), (2, 2));
+    }
+
+    #[tokio::test]
+    async fn test_weight_allows_more_concurrent_slots() {
+        let queue = Arc::new(FairQueue::new(4));
+        let _big = [
+            queue.acquire("big", 3, TrafficClass::Interactive).await,
+            queue.acquire("big", 3, TrafficClass::Interactive).await,
+        ];
+        let mut small = vec![
+            queue.acquire("small", 1, TrafficClass::Interactive).await,
+            queue.acquire("small", 1, TrafficClass::Interactive).await,
+        ];
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for name in ["small", "big"] {
+            let queue = queue.clone();
+            let tx = tx.clone();
+            let weight = if name == "big" { 3 } else { 1 };
+            tokio::spawn(async move {
+                let permit = queue.acquire(name, weight, TrafficClass::Interactive).await;
+                tx.send((name, permit)).unwrap();
+            });
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // small would run 1 of weight 1, big 2 of weight 3: big is further
+        // below its share, so it wins even though small asked first.
+        small.pop();
+        let (first, _permit) = rx.recv().await.unwrap();
+        assert_eq!(first, "big");
+    }
+
+    #[tokio::test]
+    async fn test_interactive_requests_jump_batch_and_keep_a_reserved_slot() {
+        let queue = Arc::new(FairQueue::new(4));
+
+        // Batch traffic may fill three of four slots but not the last.
+        let mut batch = Vec::new();
+        for _ in 0..3 {
+            batch.push(queue.acquire("ci", 1, TrafficClass::Batch).await);
+        }
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for (name, class) in [
+            ("ci", TrafficClass::Batch),
+            ("ci", TrafficClass::Interactive),
+            ("ci", TrafficClass::Interactive),
+        ] {
+            let queue = queue.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let permit = queue.acquire(name, 1, class).await;
+                tx.send((class, permit)).unwrap();
+            });
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // The reserved slot went to the first interactive request.
+        let (first, _first) = rx.recv().await.unwrap();
+        assert_eq!(first, TrafficClass::Interactive);
+        assert_eq!(queue.load(), (4, 2));
+
+        // A freed slot goes to the interactive waiter even though the batch
+        // one asked earlier.
+        batch.pop();
+        let (second, _second) = rx.recv().await.unwrap();
+        assert_eq!(second, TrafficClass::Interactive);
+    }
+}
@@ -2,20 +2,32 @@ use std::sync::Arc;
 
 use axum::http::HeaderMap;
 
-use crate::auth::{TokenValidationError, extract_token_from_header, validate_token};
+use crate::auth::{
+    TokenValidationError, extract_token_from_header, sudo_principal_id, validate_token_with_sudo,
+};
 use crate::server::AppState;
 use crate::server::user::access::{check_namespace_permission, check_repo_permission};
-use crate::types::{Namespace, Permission, Principal, Repo, Token};
+use crate::types::{Namespace, Permission, Principal, Repo, Token, TrafficClass};
 
 pub struct GitAuth {
     pub principal: Option<Principal>,
-    #[allow(dead_code)]
     pub token: Option<Token>,
 }
 
+impl GitAuth {
+    /// Anonymous clients are scheduled as interactive.
+    #[must_use]
+    pub fn traffic_class(&self) -> TrafficClass {
+        self.token
+            .as_ref()
+            .map_or(TrafficClass::Interactive, |t| t.traffic_class)
+    }
+}
+
 #[derive(Debug)]
 pub enum GitAuthError {
     InvalidCredentials,
+    TokenRetired,
     TokenExpired,
     AdminTokenNotAllowed,
     AuthRequired,
@@ -24,25 +36,38 @@ pub enum GitAuthError {
     PermissionDenied,
     InternalError,
     InvalidRepoName,
+    RepoLimitReached,
+    InsufficientStorage,
+    ReadOnlyMirror,
+    LfsDisabled,
+    HooksNotConfigured,
 }
 
 impl GitAuthError {
     pub fn status_code(&self) -> axum::http::StatusCode {
         use axum::http::StatusCode;
         match self {
-            Self::InvalidCredentials | Self::TokenExpired | Self::AuthRequired => {
-                StatusCode::UNAUTHORIZED
-            }
-            Self::AdminTokenNotAllowed | Self::PermissionDenied => StatusCode::FORBIDDEN,
+            Self::InvalidCredentials
+            | Self::TokenRetired
+            | Self::TokenExpired
+            | Self::AuthRequired => StatusCode::UNAUTHORIZED,
+            Self::AdminTokenNotAllowed
+            | Self::PermissionDenied
+            | Self::RepoLimitReached
+            | Self::ReadOnlyMirror
+            | Self::LfsDisabled => StatusCode::FORBIDDEN,
             Self::NamespaceNotFound | Self::RepoNotFound => StatusCode::NOT_FOUND,
             Self::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
             Self::InvalidRepoName => StatusCode::BAD_REQUEST,
+            Self::InsufficientStorage => StatusCode::INSUFFICIENT_STORAGE,
+            Self::HooksNotConfigured => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
 
     pub fn message(&self) -> &'static str {
         match self {
             Self::InvalidCredentials => "Invalid credentials",
+            Self::TokenRetired => "Token format retired; reissue it or create a new token",
             Self::TokenExpired => "Token expired",
             Self::AdminTokenNotAllowed => "Admin token cannot be used for git operations",
             Self::AuthRequired => "Authentication required",
@@ -51,13 +76,33 @@ impl GitAuthError {
             Self::PermissionDenied => "Permission denied",
             Self::InternalError => "Internal server error",
             Self::InvalidRepoName => "Invalid repository name",
+            Self::RepoLimitReached => "Namespace has reached its repository limit",
+            Self::InsufficientStorage => {
+                "Server is low on disk space; pushes and uploads are disabled"
+            }
+            Self::ReadOnlyMirror => "Repository is a read-only pull mirror",
+            Self::LfsDisabled => "Git LFS is disabled on this server",
+            Self::HooksNotConfigured => "Pushes are disabled: receive hooks are not configured",
         }
     }
 
+    /// Refusals of the push itself rather than of the credentials, which
+    /// the client can be shown against each ref it tried to update.
+    pub fn rejects_push(&self) -> bool {
+        matches!(
+            self,
+            Self::PermissionDenied
+                | Self::RepoLimitReached
+                | Self::InsufficientStorage
+                | Self::ReadOnlyMirror
+                | Self::HooksNotConfigured
+        )
+    }
+
     pub fn requires_auth_header(&self) -> bool {
         matches!(
             self,
-            Self::InvalidCredentials | Self::TokenExpired | Self::AuthRequired
+            Self::InvalidCredentials | Self::TokenRetired | Self::TokenExpired | Self::AuthRequired
         )
     }
 }
@@ -81,14 +126,18 @@ pub async fn extract_git_auth(
         Err(_) => return Err(GitAuthError::InvalidCredentials),
     };
 
-    let validated = validate_token(state, &raw_token, false).map_err(|e| match e {
-        TokenValidationError::InvalidScheme | TokenValidationError::InvalidToken => {
-            GitAuthError::InvalidCredentials
-        }
-        TokenValidationError::TokenExpired => GitAuthError::TokenExpired,
-        TokenValidationError::AdminTokenNotAllowed => GitAuthError::AdminTokenNotAllowed,
-        TokenValidationError::InternalError => GitAuthError::InternalError,
-    })?;
+    let sudo = sudo_principal_id(headers);
+    let validated =
+        validate_token_with_sudo(state, &raw_token, sudo, false).map_err(|e| match e {
+            TokenValidationError::InvalidScheme
+            | TokenValidationError::InvalidToken
+            | TokenValidationError::SudoPrincipalNotFound => GitAuthError::InvalidCredentials,
+            TokenValidationError::TokenRetired => GitAuthError::TokenRetired,
+            TokenValidationError::TokenExpired => GitAuthError::TokenExpired,
+            TokenValidationError::AdminTokenNotAllowed => GitAuthError::AdminTokenNotAllowed,
+            TokenValidationError::SudoNotAllowed => GitAuthError::PermissionDenied,
+            TokenValidationError::InternalError => GitAuthError::InternalError,
+        })?;
 
     Ok(GitAuth {
         principal: validated.principal,
@@ -103,7 +152,9 @@ pub fn check_git_access(
     repo: Option<&Repo>,
     is_write: bool,
 ) -> Result<(), GitAuthError> {
-    let is_public_read = !is_write && repo.is_some_and(|r| r.public);
+    let is_public_read = !is_write
+        && repo.is_some_and(|r| r.visibility.allows_anonymous_read())
+        && state.allows_anonymous_read();
 
     let principal = match &git_auth.principal {
         Some(p) => p,
@@ -125,9 +176,9 @@ fn check_write_access(
     repo: Option<&Repo>,
 ) -> Result<(), GitAuthError> {
     let has_permission = match repo {
-        Some(r) => check_repo_permission(state.store.as_ref(), principal, r, Permission::REPO_WRITE),
+        Some(r) => check_repo_permission(state, principal, r, Permission::REPO_WRITE),
         None => check_namespace_permission(
-            state.store.as_ref(),
+            state,
             principal,
             &namespace.id,
             Permission::NAMESPACE_WRITE,
@@ -138,6 +189,20 @@ fn check_write_access(
         return Err(GitAuthError::PermissionDenied);
     }
 
+    if let Some(r) = repo {
+        let mirror = state
+            .store
+            .get_repo_mirror(&r.id)
+            .map_err(|_| GitAuthError::InternalError)?;
+        if mirror.is_some() {
+            return Err(GitAuthError::ReadOnlyMirror);
+        }
+    }
+
+    if !state.allows_writes() {
+        return Err(GitAuthError::InsufficientStorage);
+    }
+
     Ok(())
 }
 
@@ -148,11 +213,11 @@ fn check_read_access(
 ) -> Result<(), GitAuthError> {
     let r = repo.ok_or(GitAuthError::RepoNotFound)?;
 
-    if r.public {
+    if r.visibility.allows_authenticated_read() {
         return Ok(());
     }
 
-    let has_read = check_repo_permission(state.store.as_ref(), principal, r, Permission::REPO_READ)
+    let has_read = check_repo_permission(state, principal, r, Permission::REPO_READ)
         .map_err(|_| GitAuthError::InternalError)?;
 
     if !has_read {
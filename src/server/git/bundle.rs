@@ -0,0 +1,105 @@
+//! bundle-uri support. When enabled, maintenance writes a bundle of every
+//! branch and tag into each repository it maintains, and upload-pack
+//! advertises it to protocol v2 clients. A cloning client that opts in
+//! (`transfer.bundleURI`) downloads the bulk of history as one static file
+//! and fetches only what changed since. Advertising needs git 2.40 or newer
+//! on the server; older versions ignore the settings.
+
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use tokio_util::io::ReaderStream;
+use tracing::warn;
+
+use super::dumb::open_repo;
+use super::handlers::GitPathParams;
+use crate::server::AppState;
+
+/// Where the pre-generated bundle lives, relative to the git directory.
+const BUNDLE_FILE: &str = "bundles/full.bundle";
+
+#[must_use]
+pub fn bundle_path(repo_path: &FsPath) -> PathBuf {
+    repo_path.join(BUNDLE_FILE)
+}
+
+/// Config for upload-pack that advertises the repository's bundle, as
+/// `GIT_CONFIG_*` environment variables. Empty when maintenance does not
+/// write bundles or none has been written for this repository yet.
+#[must_use]
+pub fn bundle_uri_env(
+    state: &AppState,
+    base_url: &str,
+    namespace_name: &str,
+    repo_name: &str,
+    repo_path: &FsPath,
+) -> Vec<(&'static str, String)> {
+    let enabled = state
+        .maintenance
+        .as_ref()
+        .is_some_and(|m| m.writes_bundles());
+    if !enabled || !bundle_path(repo_path).exists() {
+        return Vec::new();
+    }
+    let uri = format!(
+        "{base_url}/git/{}/{}.git/{BUNDLE_FILE}",
+        urlencoding::encode(namespace_name),
+        urlencoding::encode(repo_name)
+    );
+    vec![
+        ("GIT_CONFIG_COUNT", "4".to_string()),
+        (
+            "GIT_CONFIG_KEY_0",
+            "uploadpack.advertiseBundleURIs".to_string(),
+        ),
+        ("GIT_CONFIG_VALUE_0", "true".to_string()),
+        ("GIT_CONFIG_KEY_1", "bundle.version".to_string()),
+        ("GIT_CONFIG_VALUE_1", "1".to_string()),
+        ("GIT_CONFIG_KEY_2", "bundle.mode".to_string()),
+        ("GIT_CONFIG_VALUE_2", "all".to_string()),
+        ("GIT_CONFIG_KEY_3", "bundle.full.uri".to_string()),
+        ("GIT_CONFIG_VALUE_3", uri),
+    ]
+}
+
+/// GET bundles/full.bundle - The pre-generated bundle
+pub async fn get_bundle_file(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<GitPathParams>,
+    headers: HeaderMap,
+) -> Response {
+    let repo = match open_repo(&state, &headers, &params).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    let file = match tokio::fs::File::open(bundle_path(&repo.path)).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return (StatusCode::NOT_FOUND, "No bundle available").into_response();
+        }
+        Err(e) => {
+            warn!("Failed to open bundle: {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read bundle").into_response();
+        }
+    };
+    let len = file.metadata().await.map(|m| m.len()).ok();
+
+    let mut response = Body::from_stream(ReaderStream::new(file)).into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/x-git-bundle"),
+    );
+    // Regenerated in place by maintenance.
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    if let Some(len) = len {
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+    }
+    response
+}
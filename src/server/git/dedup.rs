@@ -0,0 +1,338 @@
+//! How many objects repositories have in common, for deciding whether
+//! sharing object storage between them (git alternates, or fork networks
+//! borrowing from one base) would pay off. Objects are listed straight from
+//! pack indexes and the loose object directories without inflating
+//! anything, and each is sized by what it takes on disk: its span in its
+//! pack, or its loose file. A deltified object's span depends on its base,
+//! so the savings are an estimate.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::types::ObjectFormat;
+
+/// Objects shared by more repositories than this still count towards the
+/// totals, but not towards repository pairs, whose count grows with the
+/// square of it.
+const MAX_PAIR_FANOUT: usize = 32;
+
+/// One repository's objects and their sizes on disk, by binary object id.
+pub type RepoObjects = HashMap<Box<[u8]>, u64>;
+
+/// Lists the objects stored in the bare repository at `path`, packed or
+/// loose. Objects borrowed through alternates aren't included. An object
+/// stored more than once is counted at its smallest.
+pub fn repo_objects(path: &Path, format: ObjectFormat) -> io::Result<RepoObjects> {
+    let hash_len = format.hex_len() / 2;
+    let objects_dir = path.join("objects");
+    let mut objects = RepoObjects::new();
+    let mut add = |oid: Box<[u8]>, size: u64| {
+        objects
+            .entry(oid)
+            .and_modify(|s| *s = (*s).min(size))
+            .or_insert(size);
+    };
+
+    let pack_dir = objects_dir.join("pack");
+    if pack_dir.is_dir() {
+        for entry in fs::read_dir(&pack_dir)? {
+            let idx_path = entry?.path();
+            if idx_path.extension().is_none_or(|ext| ext != "idx") {
+                continue;
+            }
+            let Ok(pack) = fs::metadata(idx_path.with_extension("pack")) else {
+                continue;
+            };
+            let idx = fs::read(&idx_path)?;
+            for (oid, size) in read_pack_index(&idx, pack.len(), hash_len)? {
+                add(oid, size);
+            }
+        }
+    }
+
+    for entry in fs::read_dir(&objects_dir)? {
+        let entry = entry?;
+        let prefix = entry.file_name();
+        let Some(prefix) = prefix.to_str().filter(|p| is_hex(p, 2)) else {
+            continue;
+        };
+        for file in fs::read_dir(entry.path())? {
+            let file = file?;
+            let name = file.file_name();
+            let Some(rest) = name.to_str().filter(|r| is_hex(r, hash_len * 2 - 2)) else {
+                continue;
+            };
+            if let Ok(oid) = hex::decode(format!("{prefix}{rest}")) {
+                add(oid.into_boxed_slice(), file.metadata()?.len());
+            }
+        }
+    }
+    Ok(objects)
+}
+
+fn is_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// The object ids in a version 2 pack index, each with the bytes its entry
+/// spans in the pack of `pack_len` bytes.
+fn read_pack_index(
+    idx: &[u8],
+    pack_len: u64,
+    hash_len: usize,
+) -> io::Result<Vec<(Box<[u8]>, u64)>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid pack index");
+    let u32_at = |pos: usize| -> io::Result<u32> {
+        let bytes = idx.get(pos..pos + 4).ok_or_else(invalid)?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    };
+
+    if idx.get(..4) != Some(b"\xfftOc".as_slice()) || u32_at(4)? != 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unsupported pack index version",
+        ));
+    }
+    let count = u32_at(8 + 255 * 4)? as usize;
+    let oids_at = 8 + 256 * 4;
+    let offsets_at = oids_at + count * (hash_len + 4);
+    let large_offsets_at = offsets_at + count * 4;
+
+    let mut entries = Vec::with_capacity(count);
+    for i in 0..count {
+        let oid = idx
+            .get(oids_at + i * hash_len..oids_at + (i + 1) * hash_len)
+            .ok_or_else(invalid)?;
+        let offset = u32_at(offsets_at + i * 4)?;
+        let offset = if offset & 0x8000_0000 == 0 {
+            u64::from(offset)
+        } else {
+            let pos = large_offsets_at + (offset & 0x7fff_ffff) as usize * 8;
+            let bytes = idx.get(pos..pos + 8).ok_or_else(invalid)?;
+            u64::from_be_bytes(bytes.try_into().unwrap())
+        };
+        entries.push((offset, Box::<[u8]>::from(oid)));
+    }
+
+    // An entry runs to the next one, and the last to the pack's trailing
+    // checksum.
+    entries.sort_unstable_by_key(|(offset, _)| *offset);
+    let end = pack_len.saturating_sub(hash_len as u64);
+    let mut sized = Vec::with_capacity(count);
+    for i in 0..entries.len() {
+        let next = entries.get(i + 1).map_or(end, |(offset, _)| *offset);
+        let (offset, oid) = &entries[i];
+        sized.push((oid.clone(), next.saturating_sub(*offset)));
+    }
+    Ok(sized)
+}
+
+struct SharedObject {
+    size: u64,
+    /// Indexes of the repositories that have the object.
+    repos: Vec<u32>,
+}
+
+/// Collects the objects of repositories one at a time, so only the
+/// combined table stays in memory.
+#[derive(Default)]
+pub struct OverlapCounter {
+    objects: HashMap<Box<[u8]>, SharedObject>,
+    repos: u32,
+    total_objects: u64,
+    total_bytes: u64,
+}
+
+impl OverlapCounter {
+    /// Adds the next repository's objects. Repositories are numbered in the
+    /// order they're added, from 0.
+    pub fn add_repo(&mut self, objects: RepoObjects) {
+        let repo = self.repos;
+        self.repos += 1;
+        for (oid, size) in objects {
+            self.total_objects += 1;
+            self.total_bytes += size;
+            self.objects
+                .entry(oid)
+                .and_modify(|shared| {
+                    shared.size = shared.size.min(size);
+                    shared.repos.push(repo);
+                })
+                .or_insert_with(|| SharedObject {
+                    size,
+                    repos: vec![repo],
+                });
+        }
+    }
+
+    /// Totals across every repository added, and the `top` pairs of
+    /// repositories sharing the most bytes.
+    #[must_use]
+    pub fn report(self, top: usize) -> OverlapReport {
+        let mut report = OverlapReport {
+            total_objects: self.total_objects,
+            total_bytes: self.total_bytes,
+            ..OverlapReport::default()
+        };
+        let mut pairs: HashMap<(u32, u32), (u64, u64)> = HashMap::new();
+        for shared in self.objects.values() {
+            report.unique_objects += 1;
+            report.unique_bytes += shared.size;
+            if shared.repos.len() < 2 {
+                continue;
+            }
+            report.shared_objects += 1;
+            if shared.repos.len() > MAX_PAIR_FANOUT {
+                continue;
+            }
+            for (i, &a) in shared.repos.iter().enumerate() {
+                for &b in &shared.repos[i + 1..] {
+                    let pair = pairs.entry((a.min(b), a.max(b))).or_default();
+                    pair.0 += 1;
+                    pair.1 += shared.size;
+                }
+            }
+        }
+
+        let mut pairs: Vec<RepoPair> = pairs
+            .into_iter()
+            .map(|((a, b), (objects, bytes))| RepoPair {
+                a: a as usize,
+                b: b as usize,
+                shared_objects: objects,
+                shared_bytes: bytes,
+            })
+            .collect();
+        pairs.sort_unstable_by(|x, y| {
+            y.shared_bytes
+                .cmp(&x.shared_bytes)
+                .then((x.a, x.b).cmp(&(y.a, y.b)))
+        });
+        pairs.truncate(top);
+        report.pairs = pairs;
+        report
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct OverlapReport {
+    /// Objects stored, counting each repository's copy.
+    pub total_objects: u64,
+    pub total_bytes: u64,
+    /// Objects stored, counting each once.
+    pub unique_objects: u64,
+    pub unique_bytes: u64,
+    /// Objects more than one repository stores.
+    pub shared_objects: u64,
+    pub pairs: Vec<RepoPair>,
+}
+
+impl OverlapReport {
+    /// What storing every object once would save.
+    #[must_use]
+    pub fn savings_bytes(&self) -> u64 {
+        self.total_bytes - self.unique_bytes
+    }
+}
+
+/// Two repositories, by the order they were added, and what they share.
+/// Either borrowing the other's objects would save `shared_bytes`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RepoPair {
+    pub a: usize,
+    pub b: usize,
+    pub shared_objects: u64,
+    pub shared_bytes: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::process::Command;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .env("GIT_AUTHOR_NAME", "Test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "Test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com")
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    #[test]
+    fn test_repo_objects_and_overlap() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let work = temp.path().join("work");
+        std::fs::create_dir(&work).unwrap();
+        git(&work, &["init", "-q", "-b", "main"]);
+        std::fs::write(work.join("a.txt"), "shared\n".repeat(100)).unwrap();
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "-q", "-m", "base"]);
+
+        // The fork is packed, the base stays loose, and both then get a
+        // commit of their own.
+        let base = temp.path().join("base.git");
+        let fork = temp.path().join("fork.git");
+        git(
+            temp.path(),
+            &["clone", "-q", "--bare", "--no-local", "work", "fork.git"],
+        );
+        git(temp.path(), &["init", "-q", "--bare", "base.git"]);
+        git(&work, &["push", "-q", base.to_str().unwrap(), "main"]);
+        std::fs::write(work.join("b.txt"), "fork only\n").unwrap();
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "-q", "-m", "fork"]);
+        git(&work, &["push", "-q", fork.to_str().unwrap(), "main"]);
+        git(&fork, &["repack", "-a", "-d", "-q"]);
+
+        let base_objects = repo_objects(&base, ObjectFormat::Sha1).unwrap();
+        let fork_objects = repo_objects(&fork, ObjectFormat::Sha1).unwrap();
+        // A commit, a tree, and a blob; the fork adds three more.
+        assert_eq!(base_objects.len(), 3);
+        assert_eq!(fork_objects.len(), 6);
+        assert!(fork_objects.values().all(|&size| size > 0));
+
+        let shared_bytes: u64 = base_objects
+            .iter()
+            .map(|(oid, &size)| size.min(fork_objects[oid]))
+            .sum();
+        let mut counter = OverlapCounter::default();
+        counter.add_repo(base_objects);
+        counter.add_repo(fork_objects);
+        counter.add_repo(RepoObjects::new());
+        let report = counter.report(10);
+        assert_eq!(report.total_objects, 9);
+        assert_eq!(report.unique_objects, 6);
+        assert_eq!(report.shared_objects, 3);
+        assert_eq!(
+            report.savings_bytes(),
+            report.total_bytes - report.unique_bytes
+        );
+        assert_eq!(
+            report.pairs,
+            [RepoPair {
+                a: 0,
+                b: 1,
+                shared_objects: 3,
+                shared_bytes,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_read_pack_index_rejects_garbage() {
+        assert!(read_pack_index(b"not an index", 100, 20).is_err());
+        let mut truncated = b"\xfftOc\0\0\0\x02".to_vec();
+        truncated.extend(std::iter::repeat_n(0, 255 * 4));
+        truncated.extend(5u32.to_be_bytes());
+        assert!(read_pack_index(&truncated, 100, 20).is_err());
+    }
+}
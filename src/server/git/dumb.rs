@@ -0,0 +1,328 @@
+//! Dumb HTTP transport for read-only clients and proxies that cannot speak
+//! smart HTTP. Clients fetch `info/refs`, `HEAD`, and `objects/info/packs`,
+//! then download loose objects and packs as static files. The ref and pack
+//! listings are generated on request, so they never go stale the way files
+//! written by `git update-server-info` would.
+
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use git2::{ObjectType, Repository};
+use serde::Deserialize;
+use tokio_util::io::ReaderStream;
+use tracing::warn;
+
+use super::auth::{GitAuthError, check_git_access};
+use super::handlers::{GitPathParams, git_error_response, resolve_git_context};
+use super::shared_objects::borrows_objects;
+use crate::server::AppState;
+
+/// Objects and packs are named by their hash, so they never change.
+const IMMUTABLE_MAX_AGE_SECS: u64 = 365 * 24 * 60 * 60;
+
+#[derive(Deserialize)]
+pub struct LooseObjectParams {
+    namespace: String,
+    repo: String,
+    dir: String,
+    file: String,
+}
+
+#[derive(Deserialize)]
+pub struct PackParams {
+    namespace: String,
+    repo: String,
+    file: String,
+}
+
+/// A readable repository on disk, and whether shared caches may store it.
+pub(super) struct DumbRepo {
+    pub(super) path: PathBuf,
+    public: bool,
+}
+
+/// Applies the same checks as a smart-HTTP fetch.
+pub(super) async fn open_repo(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    params: &GitPathParams,
+) -> Result<DumbRepo, Response> {
+    let ctx = resolve_git_context(state, headers, params)
+        .await
+        .map_err(git_error_response)?;
+    let repo = ctx
+        .repo
+        .ok_or_else(|| git_error_response(GitAuthError::RepoNotFound))?;
+    check_git_access(state, &ctx.git_auth, &ctx.namespace, Some(&repo), false)
+        .map_err(git_error_response)?;
+
+    let path = state.repo_path(&ctx.namespace.id, &ctx.repo_name);
+    if !path.exists() {
+        return Err(git_error_response(GitAuthError::RepoNotFound));
+    }
+    // Its packs lack what it borrows, and the store's hold other
+    // repositories' objects, so neither can be served as files.
+    if borrows_objects(&path) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Repository shares its objects; fetch it over smart HTTP",
+        )
+            .into_response());
+    }
+
+    Ok(DumbRepo {
+        path,
+        public: repo.visibility.allows_anonymous_read() && state.allows_anonymous_read(),
+    })
+}
+
+fn is_hex(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Loose objects live at `objects/xx/<rest>`, where the full name is a
+/// SHA-1 or SHA-256 hex digest.
+fn is_loose_object(dir: &str, file: &str) -> bool {
+    dir.len() == 2 && is_hex(dir) && matches!(file.len(), 38 | 62) && is_hex(file)
+}
+
+fn is_pack_file(file: &str) -> bool {
+    let Some(stem) = file
+        .strip_suffix(".pack")
+        .or_else(|| file.strip_suffix(".idx"))
+    else {
+        return false;
+    };
+    stem.strip_prefix("pack-")
+        .is_some_and(|hash| matches!(hash.len(), 40 | 64) && is_hex(hash))
+}
+
+/// `info/refs` in the format `git update-server-info` writes: one
+/// `<oid>\t<ref>` line per ref, sorted, with annotated tags followed by their
+/// peeled target.
+fn list_refs(path: &FsPath) -> Result<String, git2::Error> {
+    let repo = Repository::open_bare(path)?;
+    let mut refs = Vec::new();
+    for reference in repo.references()? {
+        let reference = reference?;
+        let Some(name) = reference.name().filter(|n| n.starts_with("refs/")) else {
+            continue;
+        };
+        let name = name.to_string();
+        let Some(oid) = reference.resolve().ok().and_then(|r| r.target()) else {
+            continue;
+        };
+        let object = repo.find_object(oid, None)?;
+        let peeled = match object.kind() {
+            Some(ObjectType::Tag) => Some(object.peel(ObjectType::Any)?.id()),
+            _ => None,
+        };
+        refs.push((name, oid, peeled));
+    }
+    refs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut out = String::new();
+    for (name, oid, peeled) in refs {
+        out.push_str(&format!("{oid}\t{name}\n"));
+        if let Some(peeled) = peeled {
+            out.push_str(&format!("{peeled}\t{name}^{{}}\n"));
+        }
+    }
+    Ok(out)
+}
+
+/// `objects/info/packs`: one `P <pack>` line per pack, then a blank line.
+fn list_packs(path: &FsPath) -> String {
+    let mut packs: Vec<String> = std::fs::read_dir(path.join("objects/pack"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".pack") && is_pack_file(name))
+        .collect();
+    packs.sort();
+
+    let mut out = String::new();
+    for pack in packs {
+        out.push_str(&format!("P {pack}\n"));
+    }
+    out.push('\n');
+    out
+}
+
+fn text_response(body: String) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// Streams a file from the repository, or 404s if it does not exist.
+async fn file_response(repo: &DumbRepo, relative: &str, content_type: &'static str) -> Response {
+    let file = match tokio::fs::File::open(repo.path.join(relative)).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return (StatusCode::NOT_FOUND, "Not found").into_response();
+        }
+        Err(e) => {
+            warn!("Failed to open {relative}: {e}");
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read object").into_response();
+        }
+    };
+    let len = file.metadata().await.map(|m| m.len()).ok();
+
+    let visibility = if repo.public { "public" } else { "private" };
+    let mut response = Body::from_stream(ReaderStream::new(file)).into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    if let Ok(value) = HeaderValue::from_str(&format!(
+        "{visibility}, max-age={IMMUTABLE_MAX_AGE_SECS}, immutable"
+    )) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+    if let Some(len) = len {
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+    }
+    response
+}
+
+/// GET info/refs without `?service=`: the dumb ref listing.
+pub(super) async fn info_refs(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    params: &GitPathParams,
+) -> Response {
+    let repo = match open_repo(state, headers, params).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    match tokio::task::spawn_blocking(move || list_refs(&repo.path)).await {
+        Ok(Ok(refs)) => text_response(refs),
+        Ok(Err(e)) => {
+            warn!("Failed to list refs: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list refs").into_response()
+        }
+        Err(e) => {
+            warn!("Ref listing task failed: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list refs").into_response()
+        }
+    }
+}
+
+/// GET HEAD - The default branch, as a symbolic ref
+pub async fn head(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<GitPathParams>,
+    headers: HeaderMap,
+) -> Response {
+    let repo = match open_repo(&state, &headers, &params).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    match tokio::fs::read_to_string(repo.path.join("HEAD")).await {
+        Ok(head) => text_response(head),
+        Err(e) => {
+            warn!("Failed to read HEAD: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read HEAD").into_response()
+        }
+    }
+}
+
+/// GET objects/info/packs - Packs available for download
+pub async fn info_packs(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<GitPathParams>,
+    headers: HeaderMap,
+) -> Response {
+    let repo = match open_repo(&state, &headers, &params).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    match tokio::task::spawn_blocking(move || list_packs(&repo.path)).await {
+        Ok(packs) => text_response(packs),
+        Err(e) => {
+            warn!("Pack listing task failed: {e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to list packs").into_response()
+        }
+    }
+}
+
+/// GET objects/{dir}/{file} - A zlib-compressed loose object
+pub async fn loose_object(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<LooseObjectParams>,
+    headers: HeaderMap,
+) -> Response {
+    // Checked before touching the store so paths like
+    // objects/info/alternates never resolve to a file.
+    if !is_loose_object(&params.dir, &params.file) {
+        return (StatusCode::NOT_FOUND, "Not found").into_response();
+    }
+    let repo_params = GitPathParams {
+        namespace: params.namespace,
+        repo: params.repo,
+    };
+    let repo = match open_repo(&state, &headers, &repo_params).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    let relative = format!("objects/{}/{}", params.dir, params.file);
+    file_response(&repo, &relative, "application/x-git-loose-object").await
+}
+
+/// GET objects/pack/{file} - A pack or its index
+pub async fn pack_file(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<PackParams>,
+    headers: HeaderMap,
+) -> Response {
+    if !is_pack_file(&params.file) {
+        return (StatusCode::NOT_FOUND, "Not found").into_response();
+    }
+    let repo_params = GitPathParams {
+        namespace: params.namespace,
+        repo: params.repo,
+    };
+    let repo = match open_repo(&state, &headers, &repo_params).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    let content_type = if params.file.ends_with(".idx") {
+        "application/x-git-packed-objects-toc"
+    } else {
+        "application/x-git-packed-objects"
+    };
+    let relative = format!("objects/pack/{}", params.file);
+    file_response(&repo, &relative, content_type).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_and_pack_names() {
+        assert!(is_loose_object("ab", &"c".repeat(38)));
+        assert!(is_loose_object("ab", &"c".repeat(62)));
+        assert!(!is_loose_object("info", "alternates"));
+        assert!(!is_loose_object("ab", "../../config"));
+        assert!(!is_loose_object("AB", &"c".repeat(38)));
+
+        let hash = "0".repeat(40);
+        assert!(is_pack_file(&format!("pack-{hash}.pack")));
+        assert!(is_pack_file(&format!("pack-{hash}.idx")));
+        assert!(!is_pack_file(&format!("pack-{hash}.keep")));
+        assert!(!is_pack_file(&format!("tmp_pack-{hash}.pack")));
+        assert!(!is_pack_file("pack-.pack"));
+    }
+}
@@ -0,0 +1,325 @@
+//! Scheduled exports: a selected subset of a repository's branches and
+//! tags force-pushed to an external remote, e.g. a public GitHub copy of
+//! an internal repo. A background task syncs each export when its interval
+//! has elapsed (or a sync is requested). Remote branches and tags outside
+//! the subset are deleted, so excluded refs never linger there.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use super::mirror::{git, list_refs};
+use crate::config::ProcessLimits;
+use crate::server::limits::BACKGROUND_GIT_TIMEOUT;
+use crate::server::AppState;
+use crate::types::{RepoExport, TrafficClass};
+
+/// Sync interval for exports created without one.
+pub const DEFAULT_EXPORT_INTERVAL_SECS: i64 = 60 * 60;
+
+/// Shortest allowed sync interval, to keep remotes from being hammered.
+pub const MIN_EXPORT_INTERVAL_SECS: i64 = 60;
+
+/// Refs exported when no `include` rules are given: every branch and tag.
+pub const DEFAULT_EXPORT_INCLUDE: &[&str] = &["refs/heads/*", "refs/tags/*"];
+
+/// How often the scheduler looks for exports that are due.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Namespaces an export reads from and manages on the remote.
+const EXPORTED_NAMESPACES: &[&str] = &["refs/heads/", "refs/tags/"];
+
+/// Checks an include or exclude rule: a full ref name under `refs/heads/`
+/// or `refs/tags/`, where `*` matches any run of characters.
+pub fn validate_ref_pattern(pattern: &str) -> Result<(), String> {
+    if !EXPORTED_NAMESPACES.iter().any(|ns| pattern.starts_with(ns)) {
+        return Err(format!(
+            "Ref pattern must start with refs/heads/ or refs/tags/: {pattern}"
+        ));
+    }
+    let invalid = pattern.contains("..")
+        || pattern.ends_with('/')
+        || pattern
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || ":?[\\^~".contains(c));
+    if invalid {
+        return Err(format!("Invalid ref pattern: {pattern}"));
+    }
+    Ok(())
+}
+
+/// Whether `refname` matches `pattern`, where `*` matches any run of
+/// characters, slashes included.
+fn ref_matches(pattern: &str, refname: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = refname.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Whether the export's rules select `refname`.
+fn is_exported(export: &RepoExport, refname: &str) -> bool {
+    EXPORTED_NAMESPACES.iter().any(|ns| refname.starts_with(ns))
+        && export.include.iter().any(|p| ref_matches(p, refname))
+        && !export.exclude.iter().any(|p| ref_matches(p, refname))
+}
+
+/// Background sync scheduler for scheduled exports.
+pub struct ExportSync {
+    wake: Notify,
+    /// Repositories with a sync requested ahead of their interval.
+    requested: Mutex<HashSet<String>>,
+    /// The repository being pushed right now, if any.
+    syncing: Mutex<Option<String>>,
+    /// Time limit for each git command of a sync when `[limits.export]`
+    /// sets none.
+    timeout: Option<Duration>,
+}
+
+impl Default for ExportSync {
+    fn default() -> Self {
+        Self::new(Some(BACKGROUND_GIT_TIMEOUT))
+    }
+}
+
+impl ExportSync {
+    /// Creates a scheduler whose syncs stop each git command after
+    /// `timeout`, unless `[limits.export]` sets one. `None` disables it.
+    #[must_use]
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            wake: Notify::new(),
+            requested: Mutex::new(HashSet::new()),
+            syncing: Mutex::new(None),
+            timeout,
+        }
+    }
+
+    /// Syncs `repo_id` as soon as the scheduler is free.
+    pub fn request(&self, repo_id: &str) {
+        lock(&self.requested).insert(repo_id.to_string());
+        self.wake.notify_one();
+    }
+
+    #[must_use]
+    pub fn is_syncing(&self, repo_id: &str) -> bool {
+        lock(&self.syncing).as_deref() == Some(repo_id)
+    }
+
+    fn is_due(&self, export: &RepoExport) -> bool {
+        if lock(&self.requested).contains(&export.repo_id) {
+            return true;
+        }
+        export
+            .last_sync_at
+            .is_none_or(|last| Utc::now() - last >= chrono::Duration::seconds(export.interval_secs))
+    }
+
+    async fn run_pass(&self, state: &AppState) {
+        let exports = match state.store.list_repo_exports() {
+            Ok(exports) => exports,
+            Err(e) => {
+                warn!("Failed to list exports: {e}");
+                return;
+            }
+        };
+        for export in exports.into_iter().filter(|e| self.is_due(e)) {
+            lock(&self.requested).remove(&export.repo_id);
+            *lock(&self.syncing) = Some(export.repo_id.clone());
+            sync_export(state, &export, self.timeout).await;
+            *lock(&self.syncing) = None;
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Pushes one export and records the outcome.
+async fn sync_export(state: &AppState, export: &RepoExport, timeout: Option<Duration>) {
+    let repo = match state.store.get_repo_by_id(&export.repo_id) {
+        Ok(Some(repo)) => repo,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to load exported repo {}: {e}", export.repo_id);
+            return;
+        }
+    };
+    let path = state.repo_path(&repo.namespace_id, &repo.name);
+
+    let slot = state
+        .fair_slot(&repo.namespace_id, TrafficClass::Batch)
+        .await;
+    let result = push(&path, export, &state.limits.export.or_timeout(timeout)).await;
+    drop(slot);
+    let recorded = match result {
+        Ok(count) => {
+            info!("Export of repo {} synced {count} refs", repo.id);
+            state.store.update_repo_export_sync(&repo.id, count)
+        }
+        Err(e) => {
+            warn!("Export failed for repo {}: {e}", repo.id);
+            state.store.update_repo_export_error(&repo.id, &e)
+        }
+    };
+    if let Err(e) = recorded {
+        warn!("Failed to record export sync: {e}");
+    }
+}
+
+/// Makes the remote's branches and tags match the selected local refs,
+/// pushing only refs that differ. Returns the number of refs exported.
+async fn push(path: &Path, export: &RepoExport, limits: &ProcessLimits) -> Result<i64, String> {
+    if !path.exists() {
+        return Err("Repository has no commits yet".to_string());
+    }
+    let mut local = parse_refs(&list_refs(path, limits).await?, ' ');
+    local.retain(|name, _| is_exported(export, name));
+    // An empty selection would delete everything on the remote, which is
+    // far more likely a mistyped rule than intended.
+    if local.is_empty() {
+        return Err("No branches or tags match the export rules".to_string());
+    }
+
+    let remote = parse_refs(
+        &git(
+            path,
+            limits,
+            &["ls-remote", "--heads", "--tags", "--refs", &export.url],
+        )
+        .await?,
+        '\t',
+    );
+
+    let mut refspecs: Vec<String> = local
+        .iter()
+        .filter(|(name, oid)| remote.get(*name) != Some(oid))
+        .map(|(name, _)| format!("+{name}:{name}"))
+        .collect();
+    refspecs.extend(
+        remote
+            .keys()
+            .filter(|name| !local.contains_key(*name))
+            .map(|name| format!(":{name}")),
+    );
+    if !refspecs.is_empty() {
+        let mut args = vec!["push", "--quiet", export.url.as_str()];
+        args.extend(refspecs.iter().map(String::as_str));
+        git(path, limits, &args).await?;
+    }
+
+    Ok(local.len() as i64)
+}
+
+/// Parses `<oid><sep><refname>` lines into branches and tags by name.
+fn parse_refs(output: &str, sep: char) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(sep))
+        .filter(|(_, name)| EXPORTED_NAMESPACES.iter().any(|ns| name.starts_with(ns)))
+        .map(|(oid, name)| (name.to_string(), oid.to_string()))
+        .collect()
+}
+
+/// Syncs due exports every few seconds and whenever a sync is requested.
+/// Does nothing when the state has no export scheduler.
+pub fn spawn_export_sync(state: Arc<AppState>) {
+    let Some(exports) = state.exports.clone() else {
+        return;
+    };
+    tokio::spawn(async move {
+        loop {
+            exports.run_pass(&state).await;
+            tokio::select! {
+                () = tokio::time::sleep(POLL_INTERVAL) => {}
+                () = exports.wake.notified() => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(include: &[&str], exclude: &[&str]) -> RepoExport {
+        RepoExport {
+            repo_id: "repo".to_string(),
+            url: "https://example.com/public.git".to_string(),
+            interval_secs: 3600,
+            include: include.iter().map(|p| p.to_string()).collect(),
+            exclude: exclude.iter().map(|p| p.to_string()).collect(),
+            created_at: Utc::now(),
+            last_sync_at: Some(Utc::now()),
+            exported_refs: 0,
+            last_error: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_ref_pattern() {
+        assert!(validate_ref_pattern("refs/heads/main").is_ok());
+        assert!(validate_ref_pattern("refs/tags/v*").is_ok());
+        assert!(validate_ref_pattern("refs/heads/release/*").is_ok());
+
+        assert!(validate_ref_pattern("main").is_err());
+        assert!(validate_ref_pattern("refs/notes/*").is_err());
+        assert!(validate_ref_pattern("refs/heads/a..b").is_err());
+        assert!(validate_ref_pattern("refs/heads/main:refs/heads/x").is_err());
+        assert!(validate_ref_pattern("refs/heads/ main").is_err());
+    }
+
+    #[test]
+    fn test_ref_matches() {
+        assert!(ref_matches("refs/heads/main", "refs/heads/main"));
+        assert!(!ref_matches("refs/heads/main", "refs/heads/main2"));
+        assert!(ref_matches("refs/heads/*", "refs/heads/feature/login"));
+        assert!(ref_matches("refs/tags/v*", "refs/tags/v1.0"));
+        assert!(!ref_matches("refs/tags/v*", "refs/tags/nightly"));
+        assert!(ref_matches("refs/tags/v*-rc*", "refs/tags/v2-rc1"));
+        assert!(!ref_matches("refs/tags/v*-rc*", "refs/tags/v2"));
+        assert!(ref_matches("refs/heads/*/wip", "refs/heads/alice/wip"));
+        assert!(!ref_matches("refs/heads/*/wip", "refs/heads/alice/wip-2"));
+    }
+
+    #[test]
+    fn test_exclude_rules_win() {
+        let rules = export(
+            &["refs/heads/*", "refs/tags/v*"],
+            &["refs/heads/internal/*"],
+        );
+        assert!(is_exported(&rules, "refs/heads/main"));
+        assert!(is_exported(&rules, "refs/tags/v1"));
+        assert!(!is_exported(&rules, "refs/heads/internal/secret"));
+        assert!(!is_exported(&rules, "refs/tags/nightly"));
+
+        let everything = export(&["refs/*"], &[]);
+        assert!(!is_exported(&everything, "refs/notes/commits"));
+    }
+
+    #[test]
+    fn test_requested_exports_are_due_early() {
+        let sync = ExportSync::default();
+        let export = export(DEFAULT_EXPORT_INCLUDE, &[]);
+        assert!(!sync.is_due(&export));
+        sync.request("repo");
+        assert!(sync.is_due(&export));
+    }
+}
@@ -0,0 +1,235 @@
+//! Limits on what a single clone or fetch may ask for. Requests are read
+//! ahead of git-upload-pack, so a fetch over the limits is refused before
+//! git sees it. Deep shallow fetches are the expensive case: git walks
+//! history commit by commit to find the shallow boundary instead of
+//! serving a full clone from bitmaps.
+
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+#[cfg(feature = "ssh")]
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::sideband::pkt_line;
+
+/// Largest request section buffered for checking over HTTP. Bigger
+/// requests are refused rather than passed through unchecked.
+const MAX_SECTION_BYTES: usize = 32 * 1024 * 1024;
+
+/// `deepen` value git sends for `fetch --unshallow`. It asks for no more
+/// than a full clone does, so it is always allowed.
+const INFINITE_DEPTH: u32 = 0x7fff_ffff;
+
+/// Why a request line is refused, if it is.
+fn check_line(line: &str, max_depth: u32) -> Result<(), String> {
+    if let Some(depth) = line.strip_prefix("deepen ") {
+        return match depth.trim().parse::<u32>() {
+            Ok(depth) if depth <= max_depth || depth == INFINITE_DEPTH => Ok(()),
+            _ => Err(format!(
+                "Shallow fetches are limited to a depth of {max_depth}"
+            )),
+        };
+    }
+    if line.starts_with("deepen-since ") || line.starts_with("deepen-not ") {
+        return Err(format!(
+            "Shallow fetches by date or ref are disabled; use --depth {max_depth} or less"
+        ));
+    }
+    Ok(())
+}
+
+/// The response that refuses a fetch. git prints it as
+/// `fatal: remote error: <reason>`.
+#[must_use]
+pub fn refusal(reason: &str) -> Vec<u8> {
+    pkt_line(format!("ERR {reason}\n").as_bytes())
+}
+
+enum Packet {
+    Flush,
+    /// Delimiter and response-end packets in protocol v2.
+    Marker,
+    Line(String),
+    /// Not pkt-line framing; left for git to reject.
+    Malformed,
+}
+
+/// Reads one packet, appending its raw bytes to `raw`. `None` at the end
+/// of the input.
+async fn read_packet<R>(input: &mut R, raw: &mut Vec<u8>) -> io::Result<Option<Packet>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut len = [0u8; 4];
+    let mut filled = 0;
+    while filled < len.len() {
+        let n = input.read(&mut len[filled..]).await?;
+        if n == 0 {
+            raw.extend_from_slice(&len[..filled]);
+            return Ok(None);
+        }
+        filled += n;
+    }
+    raw.extend_from_slice(&len);
+    let Some(len) = std::str::from_utf8(&len)
+        .ok()
+        .and_then(|l| usize::from_str_radix(l, 16).ok())
+    else {
+        return Ok(Some(Packet::Malformed));
+    };
+    match len {
+        0 => return Ok(Some(Packet::Flush)),
+        1 | 2 => return Ok(Some(Packet::Marker)),
+        3 | 4 => return Ok(Some(Packet::Malformed)),
+        _ => {}
+    }
+
+    let start = raw.len();
+    raw.resize(start + len - 4, 0);
+    if let Err(e) = input.read_exact(&mut raw[start..]).await {
+        raw.truncate(start);
+        return match e.kind() {
+            io::ErrorKind::UnexpectedEof => Ok(None),
+            _ => Err(e),
+        };
+    }
+    let line = String::from_utf8_lossy(&raw[start..]);
+    Ok(Some(Packet::Line(
+        line.strip_suffix('\n').unwrap_or(&line).to_string(),
+    )))
+}
+
+/// Reads an HTTP fetch request up to the flush that ends its first
+/// section, which holds the wants and any `deepen` arguments in both
+/// protocol versions. Returns the bytes consumed, to be replayed to git
+/// ahead of the rest of the request, or why the fetch is refused.
+pub async fn read_request<R>(input: &mut R, max_depth: u32) -> io::Result<Result<Vec<u8>, String>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut consumed = Vec::new();
+    loop {
+        match read_packet(input, &mut consumed).await? {
+            Some(Packet::Line(line)) => {
+                if let Err(reason) = check_line(&line, max_depth) {
+                    return Ok(Err(reason));
+                }
+            }
+            Some(Packet::Marker) => {}
+            Some(Packet::Flush | Packet::Malformed) | None => return Ok(Ok(consumed)),
+        }
+        if consumed.len() > MAX_SECTION_BYTES {
+            return Ok(Err("Fetch request is too large".to_string()));
+        }
+    }
+}
+
+/// Copies a stateful (SSH) fetch conversation into git packet by packet,
+/// checking every line before git sees it. Returns why the fetch was
+/// refused, or `None` once the input ends.
+#[cfg(feature = "ssh")]
+pub async fn copy_request<R, W>(
+    input: &mut R,
+    output: &mut W,
+    max_depth: u32,
+) -> io::Result<Option<String>>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut raw = Vec::new();
+    loop {
+        raw.clear();
+        match read_packet(input, &mut raw).await? {
+            Some(Packet::Line(line)) => {
+                if let Err(reason) = check_line(&line, max_depth) {
+                    return Ok(Some(reason));
+                }
+            }
+            Some(Packet::Flush | Packet::Marker) => {}
+            Some(Packet::Malformed) => {
+                output.write_all(&raw).await?;
+                tokio::io::copy(input, output).await?;
+                return Ok(None);
+            }
+            None => {
+                output.write_all(&raw).await?;
+                return Ok(None);
+            }
+        }
+        output.write_all(&raw).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OID: &str = "1111111111111111111111111111111111111111";
+
+    fn request(lines: &[&str]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for line in lines {
+            body.extend(pkt_line(format!("{line}\n").as_bytes()));
+        }
+        body.extend_from_slice(b"0000");
+        body
+    }
+
+    #[tokio::test]
+    async fn test_read_request_checks_depth() {
+        let mut body = request(&[&format!("want {OID} shallow"), "deepen 10"]);
+        body.extend_from_slice(b"0009done\n");
+        let mut input = body.as_slice();
+        let consumed = read_request(&mut input, 50).await.unwrap().unwrap();
+        assert_eq!(consumed, &body[..body.len() - 9]);
+        assert_eq!(input, b"0009done\n");
+
+        let body = request(&[&format!("want {OID}"), "deepen 51"]);
+        let refused = read_request(&mut body.as_slice(), 50).await.unwrap();
+        assert_eq!(
+            refused.unwrap_err(),
+            "Shallow fetches are limited to a depth of 50"
+        );
+
+        let body = request(&[&format!("want {OID}"), "deepen-since 0"]);
+        assert!(
+            read_request(&mut body.as_slice(), 50)
+                .await
+                .unwrap()
+                .is_err()
+        );
+
+        let body = request(&[&format!("want {OID}"), "deepen 2147483647"]);
+        assert!(
+            read_request(&mut body.as_slice(), 50)
+                .await
+                .unwrap()
+                .is_ok()
+        );
+    }
+
+    #[cfg(feature = "ssh")]
+    #[tokio::test]
+    async fn test_copy_request_checks_every_command() {
+        // Protocol v2 over SSH: ls-refs, then a fetch that goes too deep.
+        let mut body = request(&["command=ls-refs", "agent=git/2.39"]);
+        body.extend(request(&["command=fetch", "deepen 100"]));
+        let mut copied = Vec::new();
+        let refused = copy_request(&mut body.as_slice(), &mut copied, 50)
+            .await
+            .unwrap();
+        assert!(refused.is_some());
+        let mut expected = request(&["command=ls-refs", "agent=git/2.39"]);
+        expected.extend(pkt_line(b"command=fetch\n"));
+        assert_eq!(copied, expected);
+
+        let body = request(&["command=fetch", "deepen 1"]);
+        let mut copied = Vec::new();
+        let refused = copy_request(&mut body.as_slice(), &mut copied, 50)
+            .await
+            .unwrap();
+        assert!(refused.is_none());
+        assert_eq!(copied, body);
+    }
+}
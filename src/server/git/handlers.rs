@@ -1,25 +1,41 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use async_compression::tokio::bufread::GzipDecoder;
 use axum::{
-    body::Bytes,
-    extract::{Path, Query, State},
+    Extension,
+    body::Body,
+    extract::{ConnectInfo, Path, Query, State},
     http::{HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
 use chrono::Utc;
+use futures_util::TryStreamExt;
 use serde::Deserialize;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::io::{ReaderStream, StreamReader};
 use tracing::warn;
 use uuid::Uuid;
 
 use super::auth::{GitAuth, GitAuthError, check_git_access, extract_git_auth};
+use super::bundle::bundle_uri_env;
 use super::process::{
-    GitService, calculate_repo_size, format_pkt_line_header, init_bare_repo, repo_path,
-    run_git_command,
+    GitService, advertise_refs, calculate_repo_size, format_pkt_line_header, init_bare_repo,
+    spawn_git_stream,
 };
+use super::sideband::{
+    ClientMessages, PushCommands, append_messages, read_push_commands, reject_push,
+};
+use super::{dumb, fetch};
+use crate::auth::TokenFormat;
+use crate::config::{EventKind, RepoNamePolicy};
+use crate::hooks::{HookSettings, PushPolicies, RefUpdate};
+use crate::quota;
 use crate::server::AppState;
-use crate::types::{Namespace, Repo};
+use crate::server::events::{emit, push_event, repo_event};
+use crate::server::push_alerts::{Push, check_push};
+use crate::server::validation::normalize_repo_name;
+use crate::types::{Namespace, ObjectFormat, Principal, Repo, Token, Visibility};
 
 #[derive(Deserialize)]
 pub struct InfoRefsQuery {
@@ -28,18 +44,18 @@ pub struct InfoRefsQuery {
 
 #[derive(Deserialize)]
 pub struct GitPathParams {
-    namespace: String,
-    repo: String,
+    pub(super) namespace: String,
+    pub(super) repo: String,
 }
 
-struct GitContext {
-    git_auth: GitAuth,
-    namespace: Namespace,
-    repo: Option<Repo>,
-    repo_name: String,
+pub(super) struct GitContext {
+    pub(super) git_auth: GitAuth,
+    pub(super) namespace: Namespace,
+    pub(super) repo: Option<Repo>,
+    pub(super) repo_name: String,
 }
 
-fn git_error_response(err: GitAuthError) -> Response {
+pub(super) fn git_error_response(err: GitAuthError) -> Response {
     let mut response = (err.status_code(), err.message()).into_response();
 
     if err.requires_auth_header() {
@@ -56,29 +72,16 @@ fn strip_git_suffix(name: &str) -> &str {
     name.strip_suffix(".git").unwrap_or(name)
 }
 
-fn validate_repo_name(name: &str) -> Result<(), GitAuthError> {
-    if name.is_empty() || name.len() > 100 {
-        return Err(GitAuthError::InvalidRepoName);
-    }
-
-    let valid = name
-        .chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
-
-    if !valid {
-        return Err(GitAuthError::InvalidRepoName);
-    }
-
-    Ok(())
+pub(super) fn parse_repo_name(raw: &str, policy: RepoNamePolicy) -> Result<String, GitAuthError> {
+    normalize_repo_name(strip_git_suffix(raw), policy).map_err(|_| GitAuthError::InvalidRepoName)
 }
 
-async fn resolve_git_context(
+pub(super) async fn resolve_git_context(
     state: &Arc<AppState>,
     headers: &HeaderMap,
     params: &GitPathParams,
 ) -> Result<GitContext, GitAuthError> {
-    let repo_name = strip_git_suffix(&params.repo).to_lowercase();
-    validate_repo_name(&repo_name)?;
+    let repo_name = parse_repo_name(&params.repo, state.repo_name_policy)?;
 
     let git_auth = extract_git_auth(headers, state).await?;
 
@@ -101,8 +104,8 @@ async fn resolve_git_context(
     })
 }
 
-fn build_git_response(body: Vec<u8>, content_type: &str) -> Response {
-    let mut response = body.into_response();
+fn build_git_response(body: impl Into<Body>, content_type: &str) -> Response {
+    let mut response = body.into().into_response();
     response
         .headers_mut()
         .insert("Content-Type", content_type.parse().unwrap());
@@ -118,7 +121,11 @@ pub async fn info_refs(
     Query(query): Query<InfoRefsQuery>,
     headers: HeaderMap,
 ) -> Response {
-    let service = match query.service.as_deref().and_then(GitService::from_str) {
+    // Clients that do not ask for a service speak the dumb protocol.
+    let Some(service) = query.service.as_deref() else {
+        return dumb::info_refs(&state, &headers, &params).await;
+    };
+    let service = match GitService::from_str(service) {
         Some(s) => s,
         None => return (StatusCode::BAD_REQUEST, "Invalid service").into_response(),
     };
@@ -139,9 +146,12 @@ pub async fn info_refs(
     ) {
         return git_error_response(e);
     }
+    if is_write && let Err(e) = require_hooks(&state) {
+        return git_error_response(e);
+    }
 
     let repo = if is_write && ctx.repo.is_none() {
-        match create_repo_for_push(&state, &ctx.namespace.id, &ctx.repo_name).await {
+        match create_repo_for_push(&state, &ctx.namespace, &ctx.repo_name).await {
             Ok(r) => Some(r),
             Err(e) => return e,
         }
@@ -151,10 +161,13 @@ pub async fn info_refs(
         ctx.repo
     };
 
-    let path = repo_path(&state.data_dir, &ctx.namespace.id, &ctx.repo_name);
+    let path = state.repo_path(&ctx.namespace.id, &ctx.repo_name);
 
     if is_write && !path.exists() {
-        if let Err(e) = init_bare_repo(&path).await {
+        let format = repo
+            .as_ref()
+            .map_or_else(ObjectFormat::default, |r| r.object_format);
+        if let Err(e) = init_bare_repo(&path, format).await {
             warn!("Failed to init bare repo: {e}");
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -168,7 +181,29 @@ pub async fn info_refs(
         return git_error_response(GitAuthError::RepoNotFound);
     }
 
-    let output = match run_git_command(&path, service, true, None).await {
+    let env = match &repo {
+        Some(repo) if is_write => match receive_pack_env(
+            &state,
+            ctx.git_auth.principal.as_ref(),
+            &ctx.namespace,
+            repo,
+        ) {
+            Ok(env) => env,
+            Err(e) => return git_error_response(e),
+        },
+        _ if is_write => Vec::new(),
+        _ => upload_pack_env(&state, &headers, &ctx.namespace, &ctx.repo_name, &path),
+    };
+
+    let output = match advertise_refs(
+        &path,
+        service,
+        &env,
+        service.limits(&state.limits),
+        state.git_command_timeout,
+    )
+    .await
+    {
         Ok(o) => o,
         Err(e) => {
             warn!("Git command failed: {e}");
@@ -182,7 +217,13 @@ pub async fn info_refs(
         return (StatusCode::INTERNAL_SERVER_ERROR, "Git command failed").into_response();
     }
 
-    let mut body = format_pkt_line_header(service);
+    // Like git-http-backend, the service header is only sent to v0/v1
+    // clients; a v2 advertisement starts with the capability list.
+    let mut body = if !is_write && requested_protocol_v2(&headers) {
+        Vec::new()
+    } else {
+        format_pkt_line_header(service)
+    };
     body.extend_from_slice(&output.stdout);
 
     build_git_response(body, service.advertisement_content_type())
@@ -192,7 +233,7 @@ pub async fn git_upload_pack(
     State(state): State<Arc<AppState>>,
     Path(params): Path<GitPathParams>,
     headers: HeaderMap,
-    body: Bytes,
+    body: Body,
 ) -> Response {
     let ctx = match resolve_git_context(&state, &headers, &params).await {
         Ok(ctx) => ctx,
@@ -208,33 +249,75 @@ pub async fn git_upload_pack(
         return git_error_response(e);
     }
 
-    let path = repo_path(&state.data_dir, &ctx.namespace.id, &ctx.repo_name);
+    let path = state.repo_path(&ctx.namespace.id, &ctx.repo_name);
 
     if !path.exists() {
         return git_error_response(GitAuthError::RepoNotFound);
     }
 
-    let input = match decompress_if_gzip(&headers, body).await {
-        Ok(data) => data,
-        Err(e) => return e,
+    let mut input = request_body_reader(&headers, body);
+    let input: Box<dyn AsyncRead + Send + Unpin> = match state.max_clone_depth {
+        Some(max_depth) => match fetch::read_request(&mut input, max_depth).await {
+            Ok(Ok(consumed)) => Box::new(std::io::Cursor::new(consumed).chain(input)),
+            Ok(Err(reason)) => {
+                return build_git_response(
+                    fetch::refusal(&reason),
+                    GitService::UploadPack.content_type(),
+                );
+            }
+            Err(e) => {
+                warn!("Failed to read fetch request: {e}");
+                return (StatusCode::BAD_REQUEST, "Failed to read request body").into_response();
+            }
+        },
+        None => input,
     };
 
-    let output = match run_git_command(&path, GitService::UploadPack, false, Some(&input)).await {
-        Ok(o) => o,
+    let slot = state
+        .clone_slot(&repo.id, &ctx.namespace.id, ctx.git_auth.traffic_class())
+        .await;
+    let env = upload_pack_env(&state, &headers, &ctx.namespace, &ctx.repo_name, &path);
+    let stream = match spawn_git_stream(
+        &path,
+        GitService::UploadPack,
+        &env,
+        &state.limits.fetch,
+        input,
+    ) {
+        Ok(s) => s,
         Err(e) => {
             warn!("git-upload-pack failed: {e}");
             return (StatusCode::INTERNAL_SERVER_ERROR, "Git command failed").into_response();
         }
     };
 
-    build_git_response(output.stdout, GitService::UploadPack.content_type())
+    // Reap the process in the background, holding the fair-queue slot until
+    // it exits; failures are logged by `exit`.
+    let exit = stream.exit;
+    state.git_tasks.spawn(async move {
+        let _ = exit.await;
+        drop(slot);
+    });
+
+    // The banner goes before the flush that ends the pack; negotiation
+    // rounds and clients without sideband don't get it.
+    let banner = banner_messages(&state, ctx.git_auth.token.as_ref());
+    build_git_response(
+        Body::from_stream(append_messages(
+            ReaderStream::new(stream.stdout),
+            true,
+            async move { banner },
+        )),
+        GitService::UploadPack.content_type(),
+    )
 }
 
 pub async fn git_receive_pack(
     State(state): State<Arc<AppState>>,
     Path(params): Path<GitPathParams>,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
     headers: HeaderMap,
-    body: Bytes,
+    body: Body,
 ) -> Response {
     let ctx = match resolve_git_context(&state, &headers, &params).await {
         Ok(ctx) => ctx,
@@ -248,21 +331,24 @@ pub async fn git_receive_pack(
         ctx.repo.as_ref(),
         true,
     ) {
+        if e.rejects_push() {
+            return reject_push_response(&headers, body, e).await;
+        }
         return git_error_response(e);
     }
 
     let repo = match ctx.repo {
         Some(r) => r,
-        None => match create_repo_for_push(&state, &ctx.namespace.id, &ctx.repo_name).await {
+        None => match create_repo_for_push(&state, &ctx.namespace, &ctx.repo_name).await {
             Ok(r) => r,
             Err(e) => return e,
         },
     };
 
-    let path = repo_path(&state.data_dir, &ctx.namespace.id, &ctx.repo_name);
+    let path = state.repo_path(&ctx.namespace.id, &ctx.repo_name);
 
     if !path.exists() {
-        if let Err(e) = init_bare_repo(&path).await {
+        if let Err(e) = init_bare_repo(&path, repo.object_format).await {
             warn!("Failed to init bare repo: {e}");
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -272,65 +358,397 @@ pub async fn git_receive_pack(
         }
     }
 
-    let input = match decompress_if_gzip(&headers, body).await {
-        Ok(data) => data,
-        Err(e) => return e,
+    let env = match receive_pack_env(
+        &state,
+        ctx.git_auth.principal.as_ref(),
+        &ctx.namespace,
+        &repo,
+    ) {
+        Ok(env) => env,
+        Err(e) => return reject_push_response(&headers, body, e).await,
     };
-
-    let output = match run_git_command(&path, GitService::ReceivePack, false, Some(&input)).await {
-        Ok(o) => o,
+    let mut input = request_body_reader(&headers, body);
+    let (consumed, commands) = match read_push_commands(&mut input).await {
+        Ok(read) => read,
+        Err(e) => {
+            warn!("Failed to read push commands: {e}");
+            return (StatusCode::BAD_REQUEST, "Failed to read request body").into_response();
+        }
+    };
+    let sideband = commands.as_ref().is_some_and(PushCommands::sideband);
+    let input = std::io::Cursor::new(consumed).chain(input);
+    let stream = match spawn_git_stream(
+        &path,
+        GitService::ReceivePack,
+        &env,
+        &state.limits.push,
+        input,
+    ) {
+        Ok(s) => s,
         Err(e) => {
             warn!("git-receive-pack failed: {e}");
             return (StatusCode::INTERNAL_SERVER_ERROR, "Git command failed").into_response();
         }
     };
 
-    if let Err(e) = state.store.update_repo_last_push(&repo.id) {
-        warn!("Failed to update last_push_at: {e}");
+    // Record the push from its own task so it happens even if the client
+    // hangs up early, and hold the response open until it has, so a client
+    // that sees the push complete also sees the updated metadata and any
+    // notices about it.
+    let exit = stream.exit;
+    let namespace = ctx.namespace;
+    let base_url = state.base_url(&headers);
+    let created = commands.map(|c| c.updates).unwrap_or_default();
+    let mut messages = banner_messages(&state, ctx.git_auth.token.as_ref());
+    let principal_id = ctx.git_auth.principal.as_ref().map(|p| p.id.clone());
+    let token_id = ctx.git_auth.token.as_ref().map(|t| t.id.clone());
+    let address = state.push_alerts.as_ref().and_then(|alerts| {
+        alerts.client_address(
+            &headers,
+            connect_info.map(|Extension(ConnectInfo(addr))| addr),
+        )
+    });
+    let tasks = state.git_tasks.clone();
+    let recorded = tasks.spawn(async move {
+        if let Ok(Ok(_)) = exit.await {
+            record_push(&state, &repo, &path).await;
+            let push = Push {
+                namespace: &namespace,
+                repo: &repo,
+                path: path.clone(),
+                principal_id,
+                token_id,
+                address,
+                updates: created.clone(),
+            };
+            emit(&state, push_event(&push));
+            check_push(&state, push).await;
+            return push_messages(&state, Some(&base_url), &namespace, &repo, &path, &created);
+        }
+        ClientMessages::new()
+    });
+    let messages = async move {
+        messages.extend(recorded.await.unwrap_or_default());
+        messages
+    };
+
+    build_git_response(
+        Body::from_stream(append_messages(
+            ReaderStream::new(stream.stdout),
+            sideband,
+            messages,
+        )),
+        GitService::ReceivePack.content_type(),
+    )
+}
+
+/// Refuses a push ref by ref, so git prints the reason next to each ref;
+/// it never shows the body of a failed POST. Clients that do not ask for a
+/// status report get the plain HTTP error.
+async fn reject_push_response(headers: &HeaderMap, body: Body, err: GitAuthError) -> Response {
+    let mut input = request_body_reader(headers, body);
+    match read_push_commands(&mut input).await {
+        Ok((_, Some(commands))) if commands.report_status() => {
+            let mut messages = ClientMessages::new();
+            if let Some(hint) = push_rejection_hint(&err) {
+                messages.info(hint);
+            }
+            build_git_response(
+                reject_push(&commands, err.message(), &messages),
+                GitService::ReceivePack.content_type(),
+            )
+        }
+        _ => git_error_response(err),
     }
+}
 
-    if let Ok(size) = calculate_repo_size(&path).await {
-        if let Err(e) = state.store.update_repo_size(&repo.id, size) {
-            warn!("Failed to update repo size: {e}");
+/// What the pusher can do about a refused push.
+pub(super) fn push_rejection_hint(err: &GitAuthError) -> Option<&'static str> {
+    match err {
+        GitAuthError::ReadOnlyMirror => {
+            Some("Push to the upstream repository, or remove the mirror to accept pushes here")
+        }
+        GitAuthError::InsufficientStorage => {
+            Some("Fetches still work; retry the push once disk space has been freed")
+        }
+        _ => None,
+    }
+}
+
+/// The server banner, shown ahead of any other notices, and the
+/// `[legacy_tokens]` notice for clients that used a legacy-format token.
+pub(super) fn banner_messages(state: &AppState, token: Option<&Token>) -> ClientMessages {
+    let mut messages = ClientMessages::new();
+    if let Some(banner) = &state.banner {
+        messages.info(banner);
+    }
+    if token.is_some_and(|t| TokenFormat::of_lookup(&t.token_lookup) == TokenFormat::Legacy) {
+        messages.warning(state.legacy_tokens.notice());
+    }
+    messages
+}
+
+/// Notices for the pusher once a push has been recorded: links for opening
+/// pull requests from new branches, and a namespace nearing its storage
+/// limit. Links need `base_url`, the server's external URL.
+pub(super) fn push_messages(
+    state: &AppState,
+    base_url: Option<&str>,
+    namespace: &Namespace,
+    repo: &Repo,
+    path: &std::path::Path,
+    created: &[RefUpdate],
+) -> ClientMessages {
+    let mut messages = match (base_url, state.pull_request_url.as_deref()) {
+        (_, Some("")) | (None, None) => ClientMessages::new(),
+        (Some(base_url), None) => {
+            let template =
+                format!("{base_url}/api/v1/repos/{{repo_id}}/compare/{{base}}...{{branch}}");
+            pull_request_hints(&template, namespace, repo, path, created)
+        }
+        (_, Some(template)) => pull_request_hints(template, namespace, repo, path, created),
+    };
+    match quota::storage_usage(state.store.as_ref(), namespace) {
+        Ok(Some(usage)) => {
+            if let Some(warning) = usage.warning() {
+                messages.warning(warning);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Failed to read namespace storage usage: {e}"),
+    }
+    messages
+}
+
+/// The receive hooks pushes run through. Quotas, push size limits, path
+/// grants, legal holds, and push policies are all enforced by the hooks, so
+/// without them pushes are refused rather than let through unchecked.
+pub(super) fn require_hooks(state: &AppState) -> Result<&HookSettings, GitAuthError> {
+    state.hooks.as_ref().ok_or_else(|| {
+        warn!("Refusing push: receive hooks are not configured");
+        GitAuthError::HooksNotConfigured
+    })
+}
+
+/// Environment that makes `git-receive-pack` run the configured hooks,
+/// including the namespace's storage quota and push size checks.
+pub(super) fn receive_pack_env(
+    state: &AppState,
+    principal: Option<&Principal>,
+    namespace: &Namespace,
+    repo: &Repo,
+) -> Result<Vec<(&'static str, String)>, GitAuthError> {
+    let hooks = require_hooks(state)?;
+    let storage = quota::storage_usage(state.store.as_ref(), namespace).unwrap_or_else(|e| {
+        warn!("Failed to read namespace storage usage: {e}");
+        None
+    });
+    let commit = state
+        .store
+        .get_repo_commit_policy(&repo.id)
+        .unwrap_or_else(|e| {
+            warn!("Failed to read the commit policy of {}: {e}", repo.id);
+            None
+        });
+    let email = state
+        .store
+        .get_namespace_email_policy(&namespace.id)
+        .unwrap_or_else(|e| {
+            warn!("Failed to read the email policy of {}: {e}", namespace.name);
+            None
+        });
+    let pusher_emails = match (&email, principal) {
+        (Some(policy), Some(principal)) if policy.allow_pusher_emails => state
+            .store
+            .list_principal_emails(&principal.id)
+            .unwrap_or_else(|e| {
+                warn!("Failed to read the emails of {}: {e}", principal.id);
+                Vec::new()
+            }),
+        _ => Vec::new(),
+    };
+    // Grant paths are access control, so a failed lookup allows nothing.
+    let allowed_paths = match principal.map(|p| state.store.get_repo_grant(&p.id, &repo.id)) {
+        Some(Ok(grant)) => grant
+            .map(|grant| grant.paths)
+            .filter(|paths| !paths.is_empty()),
+        Some(Err(e)) => {
+            warn!("Failed to read the pusher's grant on {}: {e}", repo.id);
+            Some(Vec::new())
         }
+        None => None,
+    };
+    // A hold keeps data from being lost, so a failed lookup counts as one.
+    let legal_hold = match state.store.get_repo_hold(&repo.id) {
+        Ok(hold) => hold.is_some(),
+        Err(e) => {
+            warn!("Failed to read the legal hold of {}: {e}", repo.id);
+            true
+        }
+    };
+    let policies = PushPolicies {
+        commit,
+        email,
+        pusher_emails,
+        allowed_paths,
+        legal_hold,
+    };
+    Ok(hooks.receive_pack_env(
+        principal,
+        namespace,
+        repo,
+        storage,
+        state.push_limits,
+        &policies,
+    ))
+}
+
+/// The client's `Git-Protocol` header, when it is a well-formed list of
+/// `key=value` parameters that is safe to hand to git as `GIT_PROTOCOL`.
+fn git_protocol(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get("git-protocol")?.to_str().ok()?;
+    let valid = !value.is_empty()
+        && value.len() <= 256
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'=' | b':' | b'.' | b'-' | b'_'));
+    valid.then_some(value)
+}
+
+fn requested_protocol_v2(headers: &HeaderMap) -> bool {
+    git_protocol(headers).is_some_and(|p| p.split(':').any(|param| param == "version=2"))
+}
+
+/// Environment for `git-upload-pack`: the client's requested protocol
+/// version, and the repository's bundle-uri advertisement when there is one.
+fn upload_pack_env(
+    state: &AppState,
+    headers: &HeaderMap,
+    namespace: &Namespace,
+    repo_name: &str,
+    path: &std::path::Path,
+) -> Vec<(&'static str, String)> {
+    let base_url = state.base_url(headers);
+    let mut env = bundle_uri_env(state, &base_url, &namespace.name, repo_name, path);
+    if let Some(protocol) = git_protocol(headers) {
+        env.push(("GIT_PROTOCOL", protocol.to_string()));
     }
+    env
+}
+
+/// One link per branch the push created, filled in from `template`. The
+/// default branch is the base, so nothing is suggested until it exists, and
+/// branches a hook refused are skipped.
+fn pull_request_hints(
+    template: &str,
+    namespace: &Namespace,
+    repo: &Repo,
+    path: &std::path::Path,
+    created: &[RefUpdate],
+) -> ClientMessages {
+    let mut messages = ClientMessages::new();
+    let Ok(git_repo) = git2::Repository::open_bare(path) else {
+        return messages;
+    };
+    let Some(base) = git_repo
+        .head()
+        .ok()
+        .and_then(|head| head.shorthand().map(str::to_string))
+    else {
+        return messages;
+    };
 
-    build_git_response(output.stdout, GitService::ReceivePack.content_type())
+    for update in created.iter().filter(|u| u.is_create() && !u.is_delete()) {
+        let Some(branch) = update.refname.strip_prefix("refs/heads/") else {
+            continue;
+        };
+        let landed = git_repo
+            .refname_to_id(&update.refname)
+            .is_ok_and(|oid| oid.to_string() == update.new);
+        if branch == base || !landed {
+            continue;
+        }
+        let url = template
+            .replace("{namespace}", &urlencoding::encode(&namespace.name))
+            .replace("{repo_id}", &repo.id)
+            .replace("{repo}", &urlencoding::encode(&repo.name))
+            .replace("{base}", &urlencoding::encode(&base))
+            .replace("{branch}", &urlencoding::encode(branch));
+        messages.info(format!("Open a pull request for '{branch}':"));
+        messages.info(format!("  {url}"));
+    }
+    messages
 }
 
-async fn decompress_if_gzip(headers: &HeaderMap, body: Bytes) -> Result<Vec<u8>, Response> {
+/// Adapts the request body into a reader, decoding gzip on the fly when the
+/// client compressed it (git does for large fetch negotiations).
+fn request_body_reader(headers: &HeaderMap, body: Body) -> Box<dyn AsyncRead + Send + Unpin> {
     let content_encoding = headers
         .get("Content-Encoding")
         .and_then(|v| v.to_str().ok());
 
+    let reader = StreamReader::new(body.into_data_stream().map_err(std::io::Error::other));
+
     if content_encoding == Some("gzip") {
-        let reader = std::io::Cursor::new(body);
-        let mut decoder = GzipDecoder::new(tokio::io::BufReader::new(reader));
-        let mut decompressed = Vec::new();
+        Box::new(GzipDecoder::new(reader))
+    } else {
+        Box::new(reader)
+    }
+}
+
+/// Updates the push timestamp and on-disk size after a receive-pack run.
+pub(super) async fn record_push(state: &AppState, repo: &Repo, path: &std::path::Path) {
+    if let Err(e) = state.store.update_repo_last_push(&repo.id) {
+        warn!("Failed to update last_push_at: {e}");
+    }
 
-        decoder
-            .read_to_end(&mut decompressed)
-            .await
-            .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid gzip body").into_response())?;
+    if let Ok(size) = calculate_repo_size(path).await {
+        if let Err(e) = state.store.update_repo_size(&repo.id, size) {
+            warn!("Failed to update repo size: {e}");
+        }
+    }
 
-        Ok(decompressed)
-    } else {
-        Ok(body.to_vec())
+    if let Some(maintenance) = &state.maintenance {
+        maintenance.after_push(&repo.id, path.to_path_buf());
+    }
+
+    if let Some(pages) = &state.pages {
+        pages.after_push(&state.store, repo);
     }
 }
 
 async fn create_repo_for_push(
     state: &Arc<AppState>,
-    namespace_id: &str,
+    namespace: &Namespace,
     repo_name: &str,
 ) -> Result<Repo, Response> {
+    insert_pushed_repo(state, namespace, repo_name).map_err(git_error_response)
+}
+
+/// Creates the metadata row for a repository implicitly created by a push,
+/// unless the namespace is at its repository limit.
+pub(super) fn insert_pushed_repo(
+    state: &AppState,
+    namespace: &Namespace,
+    repo_name: &str,
+) -> Result<Repo, GitAuthError> {
+    match quota::check_repo_limit(state.store.as_ref(), namespace) {
+        Ok(None) => {}
+        Ok(Some(_)) => return Err(GitAuthError::RepoLimitReached),
+        Err(e) => {
+            warn!("Failed to check repo limit: {e}");
+            return Err(GitAuthError::InternalError);
+        }
+    }
+
     let now = Utc::now();
     let repo = Repo {
         id: Uuid::new_v4().to_string(),
-        namespace_id: namespace_id.to_string(),
+        namespace_id: namespace.id.clone(),
         name: repo_name.to_string(),
         description: None,
-        public: false,
+        visibility: Visibility::Private,
+        object_format: ObjectFormat::default(),
         folder_id: None,
         size_bytes: 0,
         last_push_at: None,
@@ -340,12 +758,32 @@ async fn create_repo_for_push(
 
     state.store.create_repo(&repo).map_err(|e| {
         warn!("Failed to create repo: {e}");
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to create repository",
-        )
-            .into_response()
+        GitAuthError::InternalError
     })?;
+    emit(
+        state,
+        repo_event(EventKind::RepoCreated, namespace, &repo, None),
+    );
 
     Ok(repo)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::SqliteStore;
+
+    #[test]
+    fn test_pushes_need_hooks() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        let state = AppState::new(Arc::new(store), temp.path().to_path_buf(), None);
+        let err = require_hooks(&state).unwrap_err();
+        assert!(matches!(err, GitAuthError::HooksNotConfigured));
+        assert!(err.rejects_push());
+
+        let hooks = HookSettings::new(&temp.path().join("hooks"), Vec::new()).unwrap();
+        let state = state.with_hooks(hooks);
+        assert!(require_hooks(&state).is_ok());
+    }
+}
@@ -0,0 +1,443 @@
+//! Background `git gc`/`git repack` for repositories pushed to since they
+//! were last maintained, plus a lightweight commit-graph and bitmap refresh
+//! after every push.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use tokio::process::Command;
+use tokio::sync::Notify;
+use tracing::{Instrument, info, warn};
+
+use super::bundle::bundle_path;
+use super::process::calculate_repo_size;
+use crate::config::{MaintenanceTask, ProcessLimits};
+use crate::server::AppState;
+use crate::server::limits::{BACKGROUND_GIT_TIMEOUT, apply_limits, deadline};
+use crate::types::Repo;
+
+/// How many repositories are listed per store query while scanning.
+const SCAN_PAGE_SIZE: i32 = 500;
+
+/// Run after each push: pack the new loose objects, index all packs with a
+/// multi-pack-index carrying a reachability bitmap (so clones skip the
+/// object walk), and append to a split commit-graph (so revwalks skip
+/// parsing commits). Both are incremental and stay cheap on large repos.
+const POST_PUSH_STEPS: &[&[&str]] = &[
+    &["repack", "-d", "-q", "--write-midx", "--write-bitmap-index"],
+    &["commit-graph", "write", "--reachable", "--split"],
+];
+
+/// Outcome of the most recent maintenance of one repository.
+#[derive(Debug, Clone)]
+pub struct RepoMaintenance {
+    pub repo_id: String,
+    pub namespace_id: String,
+    pub name: String,
+    pub started_at: DateTime<Utc>,
+    pub duration: Duration,
+    pub error: Option<String>,
+    pub size_before: i64,
+    pub size_after: Option<i64>,
+}
+
+/// Point-in-time view of the scheduler for the admin API.
+#[derive(Debug, Clone)]
+pub struct MaintenanceStatus {
+    pub task: MaintenanceTask,
+    pub interval: Option<Duration>,
+    pub running: bool,
+    pub last_run_started_at: Option<DateTime<Utc>>,
+    pub last_run_finished_at: Option<DateTime<Utc>>,
+    /// Most recently maintained first.
+    pub repos: Vec<RepoMaintenance>,
+}
+
+#[derive(Default)]
+struct State {
+    running: bool,
+    last_run_started_at: Option<DateTime<Utc>>,
+    last_run_finished_at: Option<DateTime<Utc>>,
+    repos: HashMap<String, RepoMaintenance>,
+}
+
+/// Periodically maintains repositories that have been pushed to since their
+/// last maintenance. Passes run one repository at a time so maintenance
+/// never competes with itself for disk bandwidth.
+pub struct Maintenance {
+    task: MaintenanceTask,
+    interval: Option<Duration>,
+    state: Mutex<State>,
+    wake: Notify,
+    post_push: bool,
+    /// Repositories with a post-push refresh running, mapped to whether
+    /// another push landed meanwhile and a rerun is needed.
+    optimizing: Mutex<HashMap<String, bool>>,
+    limits: ProcessLimits,
+    /// Time limit for each git command when `limits` sets none.
+    timeout: Option<Duration>,
+    bundles: bool,
+}
+
+impl Maintenance {
+    /// Creates a scheduler. Without an interval, passes only run when
+    /// requested through [`Maintenance::trigger`].
+    #[must_use]
+    pub fn new(task: MaintenanceTask, interval: Option<Duration>) -> Self {
+        Self {
+            task,
+            interval: interval.filter(|i| !i.is_zero()),
+            state: Mutex::new(State::default()),
+            wake: Notify::new(),
+            post_push: true,
+            optimizing: Mutex::new(HashMap::new()),
+            limits: ProcessLimits::default(),
+            timeout: Some(BACKGROUND_GIT_TIMEOUT),
+            bundles: false,
+        }
+    }
+
+    /// Whether commit-graphs and bitmaps are refreshed after each push.
+    #[must_use]
+    pub fn with_post_push_optimization(mut self, enabled: bool) -> Self {
+        self.post_push = enabled;
+        self
+    }
+
+    /// Resource limits for the git processes maintenance runs.
+    #[must_use]
+    pub fn with_limits(mut self, limits: ProcessLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Time limit for each git command maintenance runs, unless the
+    /// resource limits set one. `None` disables it.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Limits for the git commands maintenance runs, time limit included.
+    fn git_limits(&self) -> ProcessLimits {
+        self.limits.or_timeout(self.timeout)
+    }
+
+    /// Whether each maintained repository gets a bundle of its branches and
+    /// tags for upload-pack to advertise through bundle-uri.
+    #[must_use]
+    pub fn with_bundles(mut self, enabled: bool) -> Self {
+        self.bundles = enabled;
+        self
+    }
+
+    #[must_use]
+    pub fn writes_bundles(&self) -> bool {
+        self.bundles
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Starts a pass as soon as the current one (if any) finishes.
+    pub fn trigger(&self) {
+        self.wake.notify_one();
+    }
+
+    #[must_use]
+    pub fn status(&self) -> MaintenanceStatus {
+        let state = self.state();
+        let mut repos: Vec<_> = state.repos.values().cloned().collect();
+        repos.sort_by_key(|r| std::cmp::Reverse(r.started_at));
+        MaintenanceStatus {
+            task: self.task,
+            interval: self.interval,
+            running: state.running,
+            last_run_started_at: state.last_run_started_at,
+            last_run_finished_at: state.last_run_finished_at,
+            repos,
+        }
+    }
+
+    /// Refreshes the commit-graph and bitmaps of a just-pushed repository in
+    /// the background. Pushes that land during a refresh are folded into a
+    /// single rerun rather than stacking up concurrent git processes.
+    pub fn after_push(self: &Arc<Self>, repo_id: &str, path: PathBuf) {
+        if !self.post_push {
+            return;
+        }
+        {
+            let mut optimizing = self.optimizing.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(rerun) = optimizing.get_mut(repo_id) {
+                *rerun = true;
+                return;
+            }
+            optimizing.insert(repo_id.to_string(), false);
+        }
+
+        let this = self.clone();
+        let repo_id = repo_id.to_string();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = run_git_steps(&path, POST_PUSH_STEPS, &this.git_limits()).await {
+                    warn!("Post-push optimization failed for repo {repo_id}: {e}");
+                }
+                let mut optimizing = this.optimizing.lock().unwrap_or_else(|e| e.into_inner());
+                match optimizing.get_mut(&repo_id) {
+                    Some(rerun) if *rerun => *rerun = false,
+                    _ => {
+                        optimizing.remove(&repo_id);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Repositories with a push newer than their last maintenance.
+    fn is_due(&self, repo: &Repo) -> bool {
+        let Some(pushed) = repo.last_push_at else {
+            return false;
+        };
+        self.state()
+            .repos
+            .get(&repo.id)
+            .is_none_or(|last| last.started_at < pushed)
+    }
+
+    async fn run_pass(&self, state: &AppState) {
+        {
+            let mut s = self.state();
+            s.running = true;
+            s.last_run_started_at = Some(Utc::now());
+        }
+
+        let due = match self.due_repos(state) {
+            Ok(due) => due,
+            Err(e) => {
+                warn!("Repository maintenance scan failed: {e}");
+                Vec::new()
+            }
+        };
+        if !due.is_empty() {
+            info!("Running {} on {} repositories", self.task.name(), due.len());
+        }
+        for repo in due {
+            let record = self.maintain(state, &repo).await;
+            self.state().repos.insert(repo.id.clone(), record);
+        }
+        state.shared_objects.collect_all(state, &self.limits).await;
+
+        let mut s = self.state();
+        s.running = false;
+        s.last_run_finished_at = Some(Utc::now());
+    }
+
+    fn due_repos(&self, state: &AppState) -> crate::error::Result<Vec<Repo>> {
+        let store = state.store.as_ref();
+        let mut due = Vec::new();
+        let mut ns_cursor = String::new();
+        loop {
+            let namespaces = store.list_namespaces(&ns_cursor, SCAN_PAGE_SIZE)?;
+            for namespace in &namespaces {
+                let mut repo_cursor = String::new();
+                loop {
+                    let repos = store.list_repos(&namespace.id, &repo_cursor, SCAN_PAGE_SIZE)?;
+                    let Some(last) = repos.last() else { break };
+                    repo_cursor = last.name.clone();
+                    let full_page = repos.len() as i32 == SCAN_PAGE_SIZE;
+                    due.extend(repos.into_iter().filter(|r| self.is_due(r)));
+                    if !full_page {
+                        break;
+                    }
+                }
+            }
+            match namespaces.last() {
+                Some(last) if namespaces.len() as i32 == SCAN_PAGE_SIZE => {
+                    ns_cursor = last.id.clone();
+                }
+                _ => break,
+            }
+        }
+        Ok(due)
+    }
+
+    async fn maintain(&self, state: &AppState, repo: &Repo) -> RepoMaintenance {
+        let path = state.repo_path(&repo.namespace_id, &repo.name);
+        let started_at = Utc::now();
+        let clock = Instant::now();
+
+        let limits = self.git_limits();
+        let mut result = run_task(self.task, &path, &limits).await;
+        if result.is_ok() && self.bundles {
+            result = write_bundle(&path, &limits).await;
+        }
+        let size_after = match &result {
+            Ok(()) => calculate_repo_size(&path).await.ok(),
+            Err(e) => {
+                warn!("{} failed for repo {}: {e}", self.task.name(), repo.id);
+                None
+            }
+        };
+        if let Some(size) = size_after {
+            if let Err(e) = state.store.update_repo_size(&repo.id, size) {
+                warn!("Failed to update repo size: {e}");
+            }
+        }
+
+        RepoMaintenance {
+            repo_id: repo.id.clone(),
+            namespace_id: repo.namespace_id.clone(),
+            name: repo.name.clone(),
+            started_at,
+            duration: clock.elapsed(),
+            error: result.err(),
+            size_before: repo.size_bytes,
+            size_after,
+        }
+    }
+}
+
+async fn run_task(
+    task: MaintenanceTask,
+    repo_path: &Path,
+    limits: &ProcessLimits,
+) -> Result<(), String> {
+    let steps: &[&[&str]] = match task {
+        MaintenanceTask::Gc => &[&["gc", "--quiet"]],
+        MaintenanceTask::Repack => &[&["repack", "-a", "-d", "-l", "-q"], &["pack-refs", "--all"]],
+    };
+    run_git_steps(repo_path, steps, limits).await
+}
+
+/// Regenerates the repository's bundle-uri bundle. git writes it through a
+/// lock file, so clients never download a partial bundle. A failed run
+/// (including a repository with no branches or tags) removes the old
+/// bundle rather than leaving a stale one advertised.
+async fn write_bundle(repo_path: &Path, limits: &ProcessLimits) -> Result<(), String> {
+    let bundle = bundle_path(repo_path);
+    if let Some(dir) = bundle.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    let Some(target) = bundle.to_str() else {
+        return Err("Bundle path is not valid UTF-8".to_string());
+    };
+    let args: &[&str] = &[
+        "bundle",
+        "create",
+        "--quiet",
+        target,
+        "--branches",
+        "--tags",
+    ];
+    let result = run_git_steps(repo_path, &[args], limits).await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&bundle).await;
+    }
+    result
+}
+
+async fn run_git_steps(
+    repo_path: &Path,
+    steps: &[&[&str]],
+    limits: &ProcessLimits,
+) -> Result<(), String> {
+    for args in steps {
+        let mut cmd = Command::new("git");
+        cmd.arg("--git-dir")
+            .arg(repo_path)
+            .args(*args)
+            .kill_on_drop(true);
+        apply_limits(&mut cmd, limits);
+        let output = deadline(limits.timeout(None), cmd.output())
+            .instrument(tracing::info_span!("git", process.command = args[0]))
+            .await
+            .ok_or_else(|| format!("git {} timed out", args[0]))?
+            .map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(format!(
+                "git {} exited with {}: {}",
+                args[0],
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Runs maintenance passes on the configured interval and whenever one is
+/// triggered. Does nothing when the state has no scheduler.
+pub fn spawn_maintenance(state: Arc<AppState>) {
+    let Some(maintenance) = state.maintenance.clone() else {
+        return;
+    };
+    tokio::spawn(async move {
+        loop {
+            match maintenance.interval {
+                Some(interval) => {
+                    tokio::select! {
+                        () = tokio::time::sleep(interval) => {}
+                        () = maintenance.wake.notified() => {}
+                    }
+                }
+                None => maintenance.wake.notified().await,
+            }
+            maintenance.run_pass(&state).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(id: &str, last_push_at: Option<DateTime<Utc>>) -> Repo {
+        let now = Utc::now();
+        Repo {
+            id: id.to_string(),
+            namespace_id: "ns".to_string(),
+            name: id.to_string(),
+            description: None,
+            visibility: Default::default(),
+            object_format: Default::default(),
+            size_bytes: 0,
+            folder_id: None,
+            last_push_at,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_only_repos_pushed_since_last_run_are_due() {
+        let maintenance = Maintenance::new(MaintenanceTask::Gc, None);
+        let pushed = Utc::now();
+
+        assert!(!maintenance.is_due(&repo("never-pushed", None)));
+        assert!(maintenance.is_due(&repo("pushed", Some(pushed))));
+
+        maintenance.state().repos.insert(
+            "pushed".to_string(),
+            RepoMaintenance {
+                repo_id: "pushed".to_string(),
+                namespace_id: "ns".to_string(),
+                name: "pushed".to_string(),
+                started_at: pushed + chrono::Duration::seconds(1),
+                duration: Duration::ZERO,
+                error: None,
+                size_before: 0,
+                size_after: None,
+            },
+        );
+        assert!(!maintenance.is_due(&repo("pushed", Some(pushed))));
+        assert!(maintenance.is_due(&repo("pushed", Some(pushed + chrono::Duration::seconds(2)))));
+    }
+}
@@ -1,6 +1,17 @@
 pub mod auth;
+mod bundle;
+pub mod dedup;
+mod dumb;
+pub mod export;
+mod fetch;
 mod handlers;
+pub mod maintenance;
+pub mod mirror;
 mod process;
+pub mod shared_objects;
+mod sideband;
+#[cfg(feature = "ssh")]
+pub mod ssh;
 
 use std::sync::Arc;
 
@@ -9,11 +20,15 @@ use axum::{
     routing::{get, post},
 };
 
+pub(crate) use process::{calculate_repo_size, init_bare_repo, repo_path};
+
 use super::lfs::lfs_router;
 use crate::server::AppState;
+use crate::server::content::get_repo_page;
 
 pub fn git_router() -> Router<Arc<AppState>> {
     Router::new()
+        .route("/{namespace}/{repo}", get(get_repo_page))
         .route("/{namespace}/{repo}/info/refs", get(handlers::info_refs))
         .route(
             "/{namespace}/{repo}/git-upload-pack",
@@ -23,5 +38,23 @@ pub fn git_router() -> Router<Arc<AppState>> {
             "/{namespace}/{repo}/git-receive-pack",
             post(handlers::git_receive_pack),
         )
+        // Dumb HTTP, for clients that cannot use the smart protocol.
+        .route("/{namespace}/{repo}/HEAD", get(dumb::head))
+        .route(
+            "/{namespace}/{repo}/objects/info/packs",
+            get(dumb::info_packs),
+        )
+        .route(
+            "/{namespace}/{repo}/objects/pack/{file}",
+            get(dumb::pack_file),
+        )
+        .route(
+            "/{namespace}/{repo}/objects/{dir}/{file}",
+            get(dumb::loose_object),
+        )
+        .route(
+            "/{namespace}/{repo}/bundles/full.bundle",
+            get(bundle::get_bundle_file),
+        )
         .nest("/{namespace}/{repo}/info/lfs", lfs_router())
 }
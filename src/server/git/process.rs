@@ -1,14 +1,20 @@
 use std::path::Path;
-use std::process::Output;
+use std::process::{ExitStatus, Output, Stdio};
 use std::time::Duration;
 
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
-use tokio::process::Command;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::{ChildStdout, Command};
+use tokio::task::JoinHandle;
+use tracing::warn;
 
+use crate::config::{ProcessLimits, SubprocessLimits};
 use crate::error::{Error, Result};
+use crate::server::limits::{apply_limits, deadline};
+use crate::types::ObjectFormat;
 
-const GIT_COMMAND_TIMEOUT: Duration = Duration::from_secs(300);
+/// How much git stderr is kept for the failure log line.
+const MAX_STDERR_BYTES: u64 = 64 * 1024;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GitService {
@@ -49,53 +55,157 @@ impl GitService {
     pub fn is_write(&self) -> bool {
         matches!(self, Self::ReceivePack)
     }
+
+    /// The configured limits for this service's route class.
+    pub fn limits<'a>(&self, limits: &'a SubprocessLimits) -> &'a ProcessLimits {
+        match self {
+            Self::UploadPack => &limits.fetch,
+            Self::ReceivePack => &limits.push,
+        }
+    }
 }
 
-pub async fn run_git_command(
+/// Runs `--advertise-refs` for the info/refs response, which is small enough
+/// to buffer. `default_timeout` applies when `limits` sets no time limit.
+#[tracing::instrument(name = "git", skip_all, fields(process.command = service.command_name()))]
+pub async fn advertise_refs(
     repo_path: &Path,
     service: GitService,
-    advertise_refs: bool,
-    input: Option<&[u8]>,
+    env: &[(&str, String)],
+    limits: &ProcessLimits,
+    default_timeout: Option<Duration>,
 ) -> Result<Output> {
     let mut cmd = Command::new(service.command_name());
-    cmd.arg("--stateless-rpc");
+    cmd.args(["--stateless-rpc", "--advertise-refs"]);
+    cmd.arg(repo_path);
+    cmd.envs(env.iter().cloned());
+    cmd.stdin(Stdio::null());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    cmd.kill_on_drop(true);
+    apply_limits(&mut cmd, limits);
 
-    if advertise_refs {
-        cmd.arg("--advertise-refs");
-    }
+    let child = cmd.spawn().map_err(Error::Io)?;
+
+    let output = deadline(limits.timeout(default_timeout), child.wait_with_output())
+        .await
+        .ok_or_else(|| Error::BadRequest("Git command timed out".into()))?
+        .map_err(Error::Io)?;
 
+    Ok(output)
+}
+
+/// A stateless-rpc git process whose output is being streamed to a client.
+pub struct GitStream {
+    /// Pack data and protocol output, read as the client consumes it.
+    pub stdout: ChildStdout,
+    /// Resolves once git exits and stdin has been fully fed.
+    pub exit: JoinHandle<Result<ExitStatus>>,
+}
+
+/// Spawns `service` in stateless-rpc mode with `env` added to its
+/// environment and feeds it `input` from a background task. Both directions
+/// are streamed through fixed-size pipe buffers, so a slow peer applies
+/// backpressure instead of growing memory. The process is killed if it
+/// outlives its time limit.
+pub fn spawn_git_stream<R>(
+    repo_path: &Path,
+    service: GitService,
+    env: &[(&str, String)],
+    limits: &ProcessLimits,
+    input: R,
+) -> Result<GitStream>
+where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    let mut cmd = Command::new(service.command_name());
+    cmd.arg("--stateless-rpc");
     cmd.arg(repo_path);
-    cmd.stdin(std::process::Stdio::piped());
-    cmd.stdout(std::process::Stdio::piped());
-    cmd.stderr(std::process::Stdio::piped());
+    cmd.envs(env.iter().cloned());
+    cmd.stdin(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    cmd.kill_on_drop(true);
+    apply_limits(&mut cmd, limits);
+    let timeout = limits.timeout(None);
 
     let mut child = cmd.spawn().map_err(Error::Io)?;
+    let (Some(mut stdin), Some(stdout), Some(mut stderr)) =
+        (child.stdin.take(), child.stdout.take(), child.stderr.take())
+    else {
+        return Err(Error::Io(std::io::Error::other(
+            "failed to capture git stdio",
+        )));
+    };
+
+    let feed = tokio::spawn(async move {
+        let mut input = input;
+        let copied = tokio::io::copy(&mut input, &mut stdin).await;
+        drop(stdin);
+        copied
+    });
+
+    let command = service.command_name();
+    // Moved into the exit task, so the span lasts until git exits however
+    // long the client takes.
+    let span = tracing::info_span!(
+        "git",
+        process.command = command,
+        process.exit.code = tracing::field::Empty,
+    );
+    let exit = tokio::spawn(async move {
+        // Keep the head of stderr for diagnostics and discard the rest so a
+        // chatty git never blocks on a full pipe.
+        let mut diagnostics = Vec::new();
+        let run = async {
+            let _ = (&mut stderr)
+                .take(MAX_STDERR_BYTES)
+                .read_to_end(&mut diagnostics)
+                .await;
+            let _ = tokio::io::copy(&mut stderr, &mut tokio::io::sink()).await;
+            child.wait().await
+        };
+
+        let status = match deadline(timeout, run).await {
+            Some(status) => status.map_err(Error::Io)?,
+            None => {
+                warn!("{command} exceeded its time limit; killing it");
+                let _ = child.start_kill();
+                child.wait().await.map_err(Error::Io)?
+            }
+        };
 
-    if let Some(data) = input {
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(data).await.map_err(Error::Io)?;
+        if let Ok(Err(e)) = feed.await {
+            warn!("{command}: request body ended early: {e}");
+        }
+        if let Some(code) = status.code() {
+            span.record("process.exit.code", code);
+        }
+        if !status.success() {
+            warn!(
+                "{command} exited with {status}: {}",
+                String::from_utf8_lossy(&diagnostics).trim()
+            );
         }
-    }
 
-    let output = tokio::time::timeout(GIT_COMMAND_TIMEOUT, child.wait_with_output())
-        .await
-        .map_err(|_| Error::BadRequest("Git command timed out".into()))?
-        .map_err(Error::Io)?;
+        Ok(status)
+    });
 
-    Ok(output)
+    Ok(GitStream { stdout, exit })
 }
 
-pub async fn init_bare_repo(repo_path: &Path) -> Result<()> {
+pub async fn init_bare_repo(repo_path: &Path, format: ObjectFormat) -> Result<()> {
     if let Some(parent) = repo_path.parent() {
         fs::create_dir_all(parent).await.map_err(Error::Io)?;
     }
 
-    let output = Command::new("git")
-        .args(["init", "--bare"])
-        .arg(repo_path)
-        .output()
-        .await
-        .map_err(Error::Io)?;
+    let mut cmd = Command::new("git");
+    cmd.args(["init", "--bare"]);
+    // Left off for SHA-1 so git older than 2.29 still works.
+    if format != ObjectFormat::Sha1 {
+        cmd.arg(format!("--object-format={format}"));
+    }
+    let output = cmd.arg(repo_path).output().await.map_err(Error::Io)?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -157,12 +267,14 @@ pub fn format_pkt_line_header(service: GitService) -> Vec<u8> {
     result
 }
 
+/// Repo names are percent-encoded on disk so Unicode names survive filesystems
+/// that re-normalize or restrict non-ASCII paths. ASCII-policy names are unchanged.
 #[must_use]
 pub fn repo_path(data_dir: &Path, namespace_id: &str, repo_name: &str) -> std::path::PathBuf {
     data_dir
         .join("repos")
         .join(namespace_id)
-        .join(format!("{repo_name}.git"))
+        .join(format!("{}.git", urlencoding::encode(repo_name)))
 }
 
 #[cfg(test)]
@@ -195,4 +307,13 @@ mod tests {
         let path = repo_path(Path::new("/data"), "ns123", "myrepo");
         assert_eq!(path, Path::new("/data/repos/ns123/myrepo.git"));
     }
+
+    #[test]
+    fn test_repo_path_percent_encodes_unicode() {
+        let path = repo_path(Path::new("/data"), "ns123", "caf\u{e9}-🚀");
+        assert_eq!(
+            path,
+            Path::new("/data/repos/ns123/caf%C3%A9-%F0%9F%9A%80.git")
+        );
+    }
 }
@@ -0,0 +1,522 @@
+//! Namespace-level shared object stores. A member repository lists the
+//! store in `objects/info/alternates` and keeps only the objects the store
+//! lacks, so mirrors and copies of one project hold their common history
+//! once. The store fetches every member's refs under
+//! `refs/members/<repo id>/`, which makes reachability its reference count:
+//! an object stays while any member can reach it, and collection only
+//! prunes what none can, after git's usual grace period.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::OwnedMutexGuard;
+use tracing::{Instrument, info, warn};
+
+use super::process::{calculate_repo_size, init_bare_repo};
+use crate::config::ProcessLimits;
+use crate::server::AppState;
+use crate::server::limits::{BACKGROUND_GIT_TIMEOUT, apply_limits, deadline};
+use crate::types::{ObjectFormat, SharedObjectMember};
+
+/// The store's directory in the namespace's. Repository directories all end
+/// in `.git`, so no repository can take the name.
+pub const SHARED_OBJECTS_DIR: &str = "shared-objects";
+
+/// The store's objects, relative to a member's objects directory. Relative,
+/// so it keeps working when the namespace moves to another storage root.
+const ALTERNATE: &str = "../../shared-objects/objects";
+
+const MEMBER_REFS: &str = "refs/members/";
+
+/// The namespace's shared object store under `root`, its storage root.
+#[must_use]
+pub fn shared_objects_path(root: &Path, namespace_id: &str) -> PathBuf {
+    root.join("repos")
+        .join(namespace_id)
+        .join(SHARED_OBJECTS_DIR)
+}
+
+/// Whether the repository at `repo_path` reads objects from another store.
+#[must_use]
+pub fn borrows_objects(repo_path: &Path) -> bool {
+    alternates_file(repo_path).exists()
+}
+
+fn alternates_file(repo_path: &Path) -> PathBuf {
+    repo_path.join("objects/info/alternates")
+}
+
+/// Serializes changes to each namespace's store: members joining and
+/// leaving, and collection.
+pub struct SharedObjects {
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    /// Time limit for each git command when `[limits.maintenance]` sets
+    /// none.
+    timeout: Option<Duration>,
+}
+
+impl Default for SharedObjects {
+    fn default() -> Self {
+        Self::new(Some(BACKGROUND_GIT_TIMEOUT))
+    }
+}
+
+impl SharedObjects {
+    #[must_use]
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            locks: Mutex::new(HashMap::new()),
+            timeout,
+        }
+    }
+
+    /// `limits` with this store's time limit filled in when they set none.
+    #[must_use]
+    pub fn git_limits(&self, limits: &ProcessLimits) -> ProcessLimits {
+        limits.or_timeout(self.timeout)
+    }
+
+    pub async fn lock(&self, namespace_id: &str) -> OwnedMutexGuard<()> {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(namespace_id.to_string())
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+
+    /// Collects every store with members. Run after each maintenance pass.
+    pub async fn collect_all(&self, state: &AppState, limits: &ProcessLimits) {
+        let limits = &self.git_limits(limits);
+        let namespaces = match state.store.list_shared_object_namespaces() {
+            Ok(namespaces) => namespaces,
+            Err(e) => {
+                warn!("Failed to list shared object stores: {e}");
+                return;
+            }
+        };
+        for namespace_id in namespaces {
+            let _guard = self.lock(&namespace_id).await;
+            let members = match state.store.list_shared_object_members(&namespace_id) {
+                Ok(members) => members,
+                Err(e) => {
+                    warn!("Failed to list shared object members: {e}");
+                    continue;
+                }
+            };
+            let Some(members) = member_paths(state, &members) else {
+                continue;
+            };
+            let path = store_path(state, &namespace_id);
+            let before = calculate_repo_size(&path).await.ok();
+            match collect(&path, &members, limits).await {
+                Ok(()) => {
+                    let after = calculate_repo_size(&path).await.ok();
+                    if let (Some(before), Some(after)) = (before, after) {
+                        info!(
+                            "Collected shared objects of namespace {namespace_id}: {} -> {} bytes",
+                            before, after
+                        );
+                    }
+                }
+                Err(e) => {
+                    warn!("Shared object collection failed for namespace {namespace_id}: {e}")
+                }
+            }
+        }
+    }
+
+    /// Drops a deleted repository's refs from its namespace's store, or the
+    /// whole store once no members remain, in the background.
+    pub fn repo_deleted(self: &Arc<Self>, state: &Arc<AppState>, member: SharedObjectMember) {
+        let this = Arc::clone(self);
+        let state = Arc::clone(state);
+        tokio::spawn(async move {
+            let _guard = this.lock(&member.namespace_id).await;
+            let path = store_path(&state, &member.namespace_id);
+            let remaining = match state.store.list_shared_object_members(&member.namespace_id) {
+                Ok(members) => members.len(),
+                Err(e) => {
+                    warn!("Failed to list shared object members: {e}");
+                    return;
+                }
+            };
+            let result = if remaining == 0 {
+                tokio::fs::remove_dir_all(&path)
+                    .await
+                    .map_err(|e| e.to_string())
+            } else {
+                let limits = this.git_limits(&state.limits.maintenance);
+                drop_member_refs(&path, &[member.repo_id.as_str()], &limits).await
+            };
+            if let Err(e) = result {
+                warn!(
+                    "Failed to release shared objects of deleted repo {}: {e}",
+                    member.repo_id
+                );
+            }
+        });
+    }
+}
+
+/// The namespace's store, in the namespace's storage root.
+#[must_use]
+pub fn store_path(state: &AppState, namespace_id: &str) -> PathBuf {
+    let root = state
+        .storage_roots
+        .namespace_root(state.store.as_ref(), namespace_id);
+    shared_objects_path(&root, namespace_id)
+}
+
+/// Each member's id and path, or None if a member's repository is missing,
+/// since collecting without its refs could prune objects it needs.
+fn member_paths(
+    state: &AppState,
+    members: &[SharedObjectMember],
+) -> Option<Vec<(String, PathBuf)>> {
+    let mut paths = Vec::with_capacity(members.len());
+    for member in members {
+        let repo = match state.store.get_repo_by_id(&member.repo_id) {
+            Ok(Some(repo)) => repo,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Failed to get shared object member {}: {e}", member.repo_id);
+                return None;
+            }
+        };
+        let path = state.repo_path(&repo.namespace_id, &repo.name);
+        if !path.exists() {
+            warn!(
+                "Skipping shared object collection: member {} is missing from disk",
+                repo.id
+            );
+            return None;
+        }
+        paths.push((repo.id, path));
+    }
+    Some(paths)
+}
+
+/// Moves the repository's objects into the store, creating the store if
+/// needed. Objects the store already has are dropped from the repository;
+/// the rest stay where they are.
+pub async fn join(
+    store: &Path,
+    repo_path: &Path,
+    repo_id: &str,
+    format: ObjectFormat,
+    limits: &ProcessLimits,
+) -> Result<(), String> {
+    let alternates = alternates_file(repo_path);
+    if alternates.exists() {
+        return Err("Repository already borrows objects from another store".to_string());
+    }
+    if !store.exists() {
+        init_bare_repo(store, format)
+            .await
+            .map_err(|e| e.to_string())?;
+        // Only collection prunes the store, never a fetch.
+        git(store, &["config", "gc.auto", "0"], None, limits).await?;
+    }
+
+    fetch_member(store, repo_id, repo_path, limits).await?;
+    if let Some(dir) = alternates.parent() {
+        tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    tokio::fs::write(&alternates, format!("{ALTERNATE}\n"))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if let Err(e) = git(repo_path, &["repack", "-a", "-d", "-l", "-q"], None, limits).await {
+        // Until the repack, the repository still has all its objects.
+        let _ = tokio::fs::remove_file(&alternates).await;
+        let _ = drop_member_refs(store, &[repo_id], limits).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Copies the objects the repository borrows back into it, then stops it
+/// borrowing. With `last`, the store is removed as well.
+pub async fn leave(
+    store: &Path,
+    repo_path: &Path,
+    repo_id: &str,
+    last: bool,
+    limits: &ProcessLimits,
+) -> Result<(), String> {
+    // Without -l, repack packs the reachable objects it borrows too.
+    git(repo_path, &["repack", "-a", "-d", "-q"], None, limits).await?;
+    tokio::fs::remove_file(alternates_file(repo_path))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if last {
+        tokio::fs::remove_dir_all(store)
+            .await
+            .map_err(|e| e.to_string())
+    } else {
+        drop_member_refs(store, &[repo_id], limits).await
+    }
+}
+
+/// Refreshes the store's copy of every member's refs, drops the refs of
+/// repositories that are no longer members, and runs `git gc` to prune
+/// what none of them reaches.
+pub async fn collect(
+    store: &Path,
+    members: &[(String, PathBuf)],
+    limits: &ProcessLimits,
+) -> Result<(), String> {
+    for (repo_id, repo_path) in members {
+        fetch_member(store, repo_id, repo_path, limits).await?;
+    }
+
+    let current: HashSet<&str> = members.iter().map(|(id, _)| id.as_str()).collect();
+    let refs = git(
+        store,
+        &["for-each-ref", "--format=%(refname)", MEMBER_REFS],
+        None,
+        limits,
+    )
+    .await?;
+    let stale: HashSet<&str> = refs
+        .lines()
+        .filter_map(|name| name.strip_prefix(MEMBER_REFS)?.split('/').next())
+        .filter(|id| !current.contains(id))
+        .collect();
+    if !stale.is_empty() {
+        let stale: Vec<&str> = stale.into_iter().collect();
+        drop_member_refs(store, &stale, limits).await?;
+    }
+
+    git(store, &["gc", "--quiet"], None, limits).await?;
+    Ok(())
+}
+
+async fn fetch_member(
+    store: &Path,
+    repo_id: &str,
+    repo_path: &Path,
+    limits: &ProcessLimits,
+) -> Result<(), String> {
+    let source = repo_path
+        .to_str()
+        .ok_or("Repository path is not valid UTF-8")?;
+    let refspec = format!("+refs/*:{MEMBER_REFS}{repo_id}/*");
+    let args = [
+        "fetch",
+        "--quiet",
+        "--no-tags",
+        "--prune",
+        "--no-auto-gc",
+        "--no-write-fetch-head",
+        source,
+        &refspec,
+    ];
+    git(store, &args, None, limits).await.map(drop)
+}
+
+async fn drop_member_refs(
+    store: &Path,
+    repo_ids: &[&str],
+    limits: &ProcessLimits,
+) -> Result<(), String> {
+    let patterns: Vec<String> = repo_ids
+        .iter()
+        .map(|id| format!("{MEMBER_REFS}{id}/"))
+        .collect();
+    let mut args = vec!["for-each-ref", "--format=delete %(refname)"];
+    args.extend(patterns.iter().map(String::as_str));
+    let commands = git(store, &args, None, limits).await?;
+    if commands.is_empty() {
+        return Ok(());
+    }
+    git(store, &["update-ref", "--stdin"], Some(&commands), limits)
+        .await
+        .map(drop)
+}
+
+/// Runs git in the repository at `repo_path`, feeding it `input`, and
+/// returns what it printed.
+async fn git(
+    repo_path: &Path,
+    args: &[&str],
+    input: Option<&str>,
+    limits: &ProcessLimits,
+) -> Result<String, String> {
+    let mut cmd = Command::new("git");
+    cmd.arg("--git-dir")
+        .arg(repo_path)
+        .args(args)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    apply_limits(&mut cmd, limits);
+
+    let run = async {
+        let mut child = cmd.spawn()?;
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin.write_all(input.as_bytes()).await?;
+        }
+        child.wait_with_output().await
+    };
+    let output = deadline(limits.timeout(None), run)
+        .instrument(tracing::info_span!("git", process.command = args[0]))
+        .await
+        .ok_or_else(|| format!("git {} timed out", args[0]))?
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} exited with {}: {}",
+            args[0],
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(dir: &Path, args: &[&str]) -> String {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .env("GIT_AUTHOR_NAME", "Test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "Test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    fn local_objects(repo: &Path) -> usize {
+        crate::server::git::dedup::repo_objects(repo, ObjectFormat::Sha1)
+            .unwrap()
+            .len()
+    }
+
+    #[tokio::test]
+    async fn test_join_collect_and_leave() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let ns = temp.path().join("repos/ns");
+        let work = temp.path().join("work");
+        std::fs::create_dir_all(&ns).unwrap();
+        std::fs::create_dir(&work).unwrap();
+        run(&work, &["init", "-q", "-b", "main"]);
+        std::fs::write(work.join("a.txt"), "shared\n").unwrap();
+        run(&work, &["add", "."]);
+        run(&work, &["commit", "-q", "-m", "base"]);
+        for name in ["app.git", "copy.git"] {
+            run(
+                &ns,
+                &["clone", "-q", "--bare", "--no-local", "../../work", name],
+            );
+        }
+        let app = ns.join("app.git");
+        let copy = ns.join("copy.git");
+        let store = shared_objects_path(temp.path(), "ns");
+        let limits = ProcessLimits::default();
+
+        join(&store, &app, "app", ObjectFormat::Sha1, &limits)
+            .await
+            .unwrap();
+        join(&store, &copy, "copy", ObjectFormat::Sha1, &limits)
+            .await
+            .unwrap();
+        assert!(borrows_objects(&app));
+        assert!(
+            join(&store, &app, "app", ObjectFormat::Sha1, &limits)
+                .await
+                .is_err()
+        );
+        // The commit, tree, and blob are stored once, in the store.
+        assert_eq!(local_objects(&app), 0);
+        assert_eq!(local_objects(&copy), 0);
+        assert_eq!(local_objects(&store), 3);
+        run(&app, &["fsck", "--no-progress"]);
+
+        // A push to one member stays in it until it's collected.
+        std::fs::write(work.join("b.txt"), "app only\n").unwrap();
+        run(&work, &["add", "."]);
+        run(&work, &["commit", "-q", "-m", "app"]);
+        run(&work, &["push", "-q", app.to_str().unwrap(), "main"]);
+        let members = [
+            ("app".to_string(), app.clone()),
+            ("copy".to_string(), copy.clone()),
+        ];
+        collect(&store, &members, &limits).await.unwrap();
+        let refs = run(&store, &["for-each-ref", "--format=%(refname)"]);
+        assert_eq!(
+            refs,
+            "refs/members/app/heads/main\nrefs/members/copy/heads/main\n"
+        );
+
+        // Leaving copies the borrowed objects back, and the last to leave
+        // takes the store with it.
+        leave(&store, &copy, "copy", false, &limits).await.unwrap();
+        assert!(!borrows_objects(&copy));
+        assert_eq!(local_objects(&copy), 3);
+        run(&copy, &["fsck", "--no-progress"]);
+        let refs = run(&store, &["for-each-ref", "--format=%(refname)"]);
+        assert_eq!(refs, "refs/members/app/heads/main\n");
+
+        leave(&store, &app, "app", true, &limits).await.unwrap();
+        run(&app, &["fsck", "--no-progress"]);
+        assert!(!store.exists());
+    }
+
+    #[tokio::test]
+    async fn test_collect_drops_former_members() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let ns = temp.path().join("repos/ns");
+        let work = temp.path().join("work");
+        std::fs::create_dir_all(&ns).unwrap();
+        std::fs::create_dir(&work).unwrap();
+        run(&work, &["init", "-q", "-b", "main"]);
+        std::fs::write(work.join("a.txt"), "gone\n").unwrap();
+        run(&work, &["add", "."]);
+        run(&work, &["commit", "-q", "-m", "base"]);
+        run(
+            &ns,
+            &[
+                "clone",
+                "-q",
+                "--bare",
+                "--no-local",
+                "../../work",
+                "app.git",
+            ],
+        );
+        let app = ns.join("app.git");
+        let store = shared_objects_path(temp.path(), "ns");
+        let limits = ProcessLimits::default();
+        join(&store, &app, "app", ObjectFormat::Sha1, &limits)
+            .await
+            .unwrap();
+
+        collect(&store, &[], &limits).await.unwrap();
+        let refs = run(&store, &["for-each-ref", "--format=%(refname)"]);
+        assert!(refs.is_empty());
+    }
+}
@@ -0,0 +1,407 @@
+//! Messages for the person running git. Packets on sideband 2 are printed
+//! by the client as `remote:` lines, which is the only place a push can
+//! explain itself once the ref advertisement has succeeded: git never shows
+//! the body of a failed `git-receive-pack` POST, just "RPC failed".
+//!
+//! Helpers here read the command list that starts a push request, build
+//! `ng` reports for pushes refused before git runs, and slip messages into
+//! git's own response just before its closing flush.
+
+use std::future::Future;
+use std::io;
+
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt, stream};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::hooks::RefUpdate;
+
+/// Largest pkt-line, including its 4-byte length prefix.
+const MAX_PKT_LEN: usize = 65520;
+
+/// Room left for data in a sideband packet after the length and band byte.
+const MAX_BAND_PAYLOAD: usize = MAX_PKT_LEN - 5;
+
+const FLUSH: &[u8] = b"0000";
+
+/// Push requests with a command list larger than this are passed to git
+/// unparsed, so a pathological push cannot make the server buffer it.
+const MAX_COMMAND_BYTES: usize = 8 * 1024 * 1024;
+
+const BAND_DATA: u8 = 1;
+const BAND_PROGRESS: u8 = 2;
+
+pub(super) fn pkt_line(payload: &[u8]) -> Vec<u8> {
+    let mut out = format!("{:04x}", payload.len() + 4).into_bytes();
+    out.extend_from_slice(payload);
+    out
+}
+
+/// `data` as packets on `band`, split to fit the 64k pkt-line limit.
+fn band_packets(band: u8, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for chunk in data.chunks(MAX_BAND_PAYLOAD) {
+        let mut payload = Vec::with_capacity(chunk.len() + 1);
+        payload.push(band);
+        payload.extend_from_slice(chunk);
+        out.extend(pkt_line(&payload));
+    }
+    out
+}
+
+/// Lines to show the client, each prefixed with `cutman:`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientMessages {
+    lines: Vec<String>,
+}
+
+impl ClientMessages {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn info(&mut self, message: impl AsRef<str>) {
+        self.push("", message.as_ref());
+    }
+
+    pub fn warning(&mut self, message: impl AsRef<str>) {
+        self.push("warning: ", message.as_ref());
+    }
+
+    fn push(&mut self, label: &str, message: &str) {
+        for line in message.lines() {
+            self.lines.push(format!("cutman: {label}{line}"));
+        }
+    }
+
+    /// Adds `other`'s lines after these.
+    pub fn extend(&mut self, other: Self) {
+        self.lines.extend(other.lines);
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// The messages as plain text, for stderr on SSH.
+    #[must_use]
+    pub fn text(&self) -> String {
+        self.lines.iter().map(|line| format!("{line}\n")).collect()
+    }
+
+    /// The messages as sideband-2 packets.
+    #[must_use]
+    pub fn sideband(&self) -> Vec<u8> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        band_packets(BAND_PROGRESS, self.text().as_bytes())
+    }
+}
+
+/// The command list that starts a `git-receive-pack` request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PushCommands {
+    pub updates: Vec<RefUpdate>,
+    capabilities: Vec<String>,
+}
+
+impl PushCommands {
+    fn has_capability(&self, name: &str) -> bool {
+        self.capabilities.iter().any(|c| c == name)
+    }
+
+    /// Whether the client demultiplexes the response, so band-2 messages
+    /// can be mixed into it.
+    #[must_use]
+    pub fn sideband(&self) -> bool {
+        self.has_capability("side-band-64k") || self.has_capability("side-band")
+    }
+
+    /// Whether the client reads per-ref results, so a refusal can be
+    /// reported against each ref instead of failing the request.
+    #[must_use]
+    pub fn report_status(&self) -> bool {
+        self.has_capability("report-status") || self.has_capability("report-status-v2")
+    }
+}
+
+/// Reads pkt-lines up to and including the flush that ends the command
+/// list. Returns the bytes consumed, to be replayed to git ahead of the
+/// rest of the request, and the parsed commands. Requests that do not look
+/// like a plain push (push certificates, malformed framing, an early end)
+/// yield no commands; git sees the same bytes and reports on them itself.
+pub async fn read_push_commands<R>(input: &mut R) -> io::Result<(Vec<u8>, Option<PushCommands>)>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut consumed = Vec::new();
+    let mut commands = PushCommands::default();
+    loop {
+        let mut len = [0u8; 4];
+        if let Err(e) = input.read_exact(&mut len).await {
+            return match e.kind() {
+                io::ErrorKind::UnexpectedEof => Ok((consumed, None)),
+                _ => Err(e),
+            };
+        }
+        consumed.extend_from_slice(&len);
+        let Some(len) = std::str::from_utf8(&len)
+            .ok()
+            .and_then(|l| usize::from_str_radix(l, 16).ok())
+        else {
+            return Ok((consumed, None));
+        };
+        if len == 0 {
+            return Ok((consumed, Some(commands)));
+        }
+        if !(5..=MAX_PKT_LEN).contains(&len) || consumed.len() + len > MAX_COMMAND_BYTES {
+            return Ok((consumed, None));
+        }
+
+        let start = consumed.len();
+        consumed.resize(start + len - 4, 0);
+        if let Err(e) = input.read_exact(&mut consumed[start..]).await {
+            return match e.kind() {
+                io::ErrorKind::UnexpectedEof => Ok((consumed, None)),
+                _ => Err(e),
+            };
+        }
+        let Ok(line) = std::str::from_utf8(&consumed[start..]) else {
+            return Ok((consumed, None));
+        };
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let (command, capabilities) = match line.split_once('\0') {
+            Some((command, capabilities)) => (command, Some(capabilities)),
+            None => (line, None),
+        };
+        if let Some(capabilities) = capabilities {
+            commands.capabilities = capabilities.split(' ').map(str::to_string).collect();
+        }
+        if command.starts_with("shallow ") {
+            continue;
+        }
+        match RefUpdate::parse(command) {
+            Some(update) => commands.updates.push(update),
+            None => return Ok((consumed, None)),
+        }
+    }
+}
+
+/// A complete `git-receive-pack` response refusing every update with
+/// `reason`, preceded by `messages`. The client prints each ref as
+/// `! [remote rejected] ... (reason)`.
+#[must_use]
+pub fn reject_push(commands: &PushCommands, reason: &str, messages: &ClientMessages) -> Vec<u8> {
+    let reason = reason.replace(['\n', '\r'], " ");
+    let mut report = pkt_line(b"unpack ok\n");
+    for update in &commands.updates {
+        report.extend(pkt_line(
+            format!("ng {} {reason}\n", update.refname).as_bytes(),
+        ));
+    }
+    report.extend_from_slice(FLUSH);
+
+    if !commands.sideband() {
+        return report;
+    }
+    let mut out = messages.sideband();
+    out.extend(band_packets(BAND_DATA, &report));
+    out.extend_from_slice(FLUSH);
+    out
+}
+
+/// Follows the pkt-line framing of a response as it streams past, to tell
+/// whether it ended in sideband packets. A fetch response only carries them
+/// once the pack is sent; negotiation rounds and clients that did not ask
+/// for sideband get plain protocol lines.
+#[derive(Debug, Default)]
+struct SidebandScan {
+    header: Vec<u8>,
+    /// Payload bytes left in the current packet.
+    remaining: usize,
+    at_payload_start: bool,
+    saw_band: bool,
+    broken: bool,
+}
+
+impl SidebandScan {
+    fn feed(&mut self, mut data: &[u8]) {
+        while !data.is_empty() && !self.broken {
+            if self.remaining > 0 {
+                if self.at_payload_start {
+                    self.saw_band |= matches!(data[0], BAND_DATA | BAND_PROGRESS | 3);
+                    self.at_payload_start = false;
+                }
+                let n = self.remaining.min(data.len());
+                self.remaining -= n;
+                data = &data[n..];
+                continue;
+            }
+
+            let n = (4 - self.header.len()).min(data.len());
+            self.header.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.header.len() < 4 {
+                return;
+            }
+            let len = std::str::from_utf8(&self.header)
+                .ok()
+                .and_then(|l| usize::from_str_radix(l, 16).ok());
+            self.header.clear();
+            match len {
+                // Flush, delimiter, and response-end packets.
+                Some(0..=2) => {}
+                Some(len) if len >= 4 => {
+                    self.remaining = len - 4;
+                    self.at_payload_start = true;
+                }
+                _ => self.broken = true,
+            }
+        }
+    }
+
+    /// Whether messages can go before the final flush.
+    fn accepts_messages(&self) -> bool {
+        self.saw_band && !self.broken && self.remaining == 0 && self.header.is_empty()
+    }
+}
+
+/// Passes through a response from git, sending `messages` on band 2 just
+/// before the flush that ends it if the response is sideband-multiplexed
+/// by then. `messages` is only awaited once git's output is exhausted, so
+/// it can report on what the push did. With `sideband` false, or when the
+/// response turns out not to end in sideband packets, the messages are
+/// still awaited but dropped, since the client would read them as protocol
+/// data.
+pub fn append_messages<S, F>(
+    body: S,
+    sideband: bool,
+    messages: F,
+) -> impl Stream<Item = io::Result<Bytes>> + Send
+where
+    S: Stream<Item = io::Result<Bytes>> + Send + Unpin,
+    F: Future<Output = ClientMessages> + Send,
+{
+    let held: Vec<u8> = Vec::new();
+    stream::unfold(
+        (body, held, SidebandScan::default(), Some(messages)),
+        move |(mut body, mut held, mut scan, messages)| async move {
+            match body.next().await {
+                // Hold back the last four bytes, which may be the flush.
+                Some(Ok(chunk)) => {
+                    scan.feed(&chunk);
+                    held.extend_from_slice(&chunk);
+                    let tail = held.split_off(held.len().saturating_sub(FLUSH.len()));
+                    let out = Bytes::from(std::mem::replace(&mut held, tail));
+                    Some((Ok(out), (body, held, scan, messages)))
+                }
+                Some(Err(e)) => Some((Err(e), (body, held, scan, messages))),
+                None => {
+                    let messages = messages?.await;
+                    let mut out = Vec::new();
+                    if sideband && held == FLUSH && scan.accepts_messages() {
+                        out = messages.sideband();
+                    }
+                    out.append(&mut held);
+                    Some((Ok(Bytes::from(out)), (body, held, scan, None)))
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "0000000000000000000000000000000000000000";
+    const NEW: &str = "1111111111111111111111111111111111111111";
+
+    fn commands_request(capabilities: &str) -> Vec<u8> {
+        let mut body =
+            pkt_line(format!("{OLD} {NEW} refs/heads/main\0{capabilities}\n").as_bytes());
+        body.extend(pkt_line(format!("{NEW} {OLD} refs/heads/old\n").as_bytes()));
+        body.extend_from_slice(FLUSH);
+        body
+    }
+
+    #[tokio::test]
+    async fn test_read_push_commands() {
+        let mut body = commands_request("report-status side-band-64k agent=git/2.39");
+        body.extend_from_slice(b"PACK...");
+        let mut input = body.as_slice();
+
+        let (consumed, commands) = read_push_commands(&mut input).await.unwrap();
+        let commands = commands.expect("parsed commands");
+        assert_eq!(consumed, &body[..body.len() - 7]);
+        assert_eq!(input, b"PACK...");
+        assert_eq!(commands.updates.len(), 2);
+        assert_eq!(commands.updates[0].refname, "refs/heads/main");
+        assert!(commands.updates[1].is_delete());
+        assert!(commands.sideband());
+        assert!(commands.report_status());
+
+        let mut garbage: &[u8] = b"zzzzjunk";
+        let (consumed, commands) = read_push_commands(&mut garbage).await.unwrap();
+        assert_eq!(consumed, b"zzzz");
+        assert!(commands.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reject_push() {
+        let request = commands_request("report-status side-band-64k");
+        let (_, commands) = read_push_commands(&mut request.as_slice()).await.unwrap();
+        let mut messages = ClientMessages::new();
+        messages.warning("read-only");
+
+        let response = reject_push(&commands.unwrap(), "mirror", &messages);
+        let text = String::from_utf8_lossy(&response);
+        assert!(text.starts_with("0020\x02cutman: warning: read-only\n"));
+        assert!(text.contains("ng refs/heads/main mirror\n"));
+        assert!(text.contains("ng refs/heads/old mirror\n"));
+        assert!(text.ends_with("0000"));
+
+        let request = commands_request("report-status");
+        let (_, commands) = read_push_commands(&mut request.as_slice()).await.unwrap();
+        let response = reject_push(&commands.unwrap(), "mirror", &messages);
+        assert!(response.starts_with(b"000eunpack ok\n"));
+    }
+
+    #[tokio::test]
+    async fn test_append_messages_before_flush() {
+        let chunks: Vec<io::Result<Bytes>> = vec![
+            Ok(Bytes::from_static(b"000a\x01hello00")),
+            Ok(Bytes::from_static(b"00")),
+        ];
+        let mut messages = ClientMessages::new();
+        messages.info("done");
+
+        let out: Vec<u8> = append_messages(stream::iter(chunks), true, async { messages })
+            .map(|chunk| chunk.unwrap())
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+        assert_eq!(out, b"000a\x01hello0012\x02cutman: done\n0000");
+    }
+
+    #[tokio::test]
+    async fn test_append_messages_skips_plain_responses() {
+        let mut messages = ClientMessages::new();
+        messages.info("banner");
+
+        // A fetch negotiation round: acknowledgments, no pack yet.
+        for response in [&b"0008NAK\n0000"[..], b"0015acknowledgments\n0008NAK\n0000"] {
+            let chunks: Vec<io::Result<Bytes>> = vec![Ok(Bytes::copy_from_slice(response))];
+            let messages = messages.clone();
+            let out: Vec<u8> = append_messages(stream::iter(chunks), true, async { messages })
+                .map(|chunk| chunk.unwrap())
+                .collect::<Vec<_>>()
+                .await
+                .concat();
+            assert_eq!(out, response);
+        }
+    }
+}
@@ -0,0 +1,687 @@
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use russh::keys::ssh_key::LineEnding;
+use russh::keys::{Algorithm, PrivateKey, PublicKey};
+use russh::server::{Auth, Handler, Msg, Server, Session};
+use russh::{Channel, ChannelId, ChannelReadHalf, ChannelWriteHalf, MethodKind, MethodSet};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use super::auth::{GitAuth, GitAuthError, check_git_access};
+use super::bundle::bundle_uri_env;
+use super::fetch;
+use super::handlers::{
+    banner_messages, insert_pushed_repo, parse_repo_name, push_messages, push_rejection_hint,
+    receive_pack_env, record_push, require_hooks,
+};
+use super::process::{GitService, init_bare_repo};
+use super::sideband::ClientMessages;
+use crate::config::ProcessLimits;
+use crate::error::{Error, Result};
+use crate::hooks::RefUpdate;
+use crate::server::AppState;
+use crate::server::limits::{apply_limits, deadline};
+use crate::server::events::{emit, push_event};
+use crate::server::push_alerts::{Push, check_push};
+use crate::server::validation::ssh_key_fingerprint;
+use crate::types::{Namespace, Principal, Repo, TrafficClass};
+
+/// A `git-upload-pack 'namespace/repo.git'` style request sent by git over SSH.
+#[derive(Debug, PartialEq, Eq)]
+struct GitCommand {
+    service: GitService,
+    namespace: String,
+    repo: String,
+}
+
+/// Parses the exec command git sends for `git@host:namespace/repo.git` and
+/// `ssh://git@host/namespace/repo.git` remotes. Both the dashed
+/// (`git-upload-pack`) and spaced (`git upload-pack`) forms are accepted.
+fn parse_git_command(command: &str) -> Option<GitCommand> {
+    let (program, rest) = command.trim().split_once(char::is_whitespace)?;
+    let (program, rest) = match program {
+        "git" => {
+            let (sub, rest) = rest.trim_start().split_once(char::is_whitespace)?;
+            (format!("git-{sub}"), rest)
+        }
+        _ => (program.to_string(), rest),
+    };
+    let service = GitService::from_str(&program)?;
+
+    let path = rest.trim().trim_matches(|c| c == '\'' || c == '"');
+    let path = path.strip_prefix('/').unwrap_or(path);
+    let (namespace, repo) = path.split_once('/')?;
+    if namespace.is_empty() || repo.is_empty() || repo.contains('/') {
+        return None;
+    }
+
+    Some(GitCommand {
+        service,
+        namespace: namespace.to_string(),
+        repo: repo.to_string(),
+    })
+}
+
+/// Loads the server host key, generating and persisting an Ed25519 key on
+/// first start so clients see a stable fingerprint across restarts.
+pub fn load_or_create_host_key(path: &Path) -> Result<PrivateKey> {
+    if path.exists() {
+        return russh::keys::load_secret_key(path, None).map_err(|e| {
+            Error::Config(format!(
+                "Failed to load SSH host key {}: {e}",
+                path.display()
+            ))
+        });
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let key = PrivateKey::random(&mut rand::rngs::OsRng, Algorithm::Ed25519)
+        .map_err(|e| Error::Config(format!("Failed to generate SSH host key: {e}")))?;
+    key.write_openssh_file(path, LineEnding::LF).map_err(|e| {
+        Error::Config(format!(
+            "Failed to write SSH host key {}: {e}",
+            path.display()
+        ))
+    })?;
+
+    info!("Generated SSH host key at {}", path.display());
+    Ok(key)
+}
+
+/// Serves git over SSH on `listener` until the listener fails.
+pub async fn run_ssh_server(
+    state: Arc<AppState>,
+    listener: TcpListener,
+    host_key: PrivateKey,
+) -> std::io::Result<()> {
+    let config = russh::server::Config {
+        keys: vec![host_key],
+        methods: MethodSet::from(&[MethodKind::PublicKey][..]),
+        auth_rejection_time: Duration::from_secs(1),
+        auth_rejection_time_initial: Some(Duration::ZERO),
+        inactivity_timeout: Some(state.ssh_idle_timeout),
+        ..Default::default()
+    };
+
+    let mut server = SshServer { state };
+    server.run_on_socket(Arc::new(config), &listener).await
+}
+
+struct SshServer {
+    state: Arc<AppState>,
+}
+
+impl Server for SshServer {
+    type Handler = SshSession;
+
+    fn new_client(&mut self, peer_addr: Option<SocketAddr>) -> SshSession {
+        SshSession {
+            state: self.state.clone(),
+            peer_addr,
+            principal: None,
+            channels: HashMap::new(),
+            git_protocol: None,
+        }
+    }
+
+    fn handle_session_error(&mut self, error: russh::Error) {
+        warn!("SSH session error: {error}");
+    }
+}
+
+struct SshSession {
+    state: Arc<AppState>,
+    peer_addr: Option<SocketAddr>,
+    principal: Option<Principal>,
+    channels: HashMap<ChannelId, Channel<Msg>>,
+    git_protocol: Option<String>,
+}
+
+impl SshSession {
+    /// Resolves an offered key to the principal that registered it.
+    fn lookup_key(&self, key: &PublicKey) -> Option<(String, Principal)> {
+        let blob = key.to_bytes().ok()?;
+        let fingerprint = ssh_key_fingerprint(&blob);
+
+        let ssh_key = match self.state.store.get_ssh_key_by_fingerprint(&fingerprint) {
+            Ok(key) => key?,
+            Err(e) => {
+                warn!("Failed to look up SSH key: {e}");
+                return None;
+            }
+        };
+
+        let principal = match self.state.store.get_principal(&ssh_key.principal_id) {
+            Ok(principal) => principal?,
+            Err(e) => {
+                warn!("Failed to look up SSH key owner: {e}");
+                return None;
+            }
+        };
+
+        match self.state.store.is_principal_deactivated(&principal.id) {
+            Ok(false) => Some((ssh_key.id, principal)),
+            Ok(true) => None,
+            Err(e) => {
+                warn!("Failed to look up SSH key owner: {e}");
+                None
+            }
+        }
+    }
+}
+
+impl Handler for SshSession {
+    type Error = russh::Error;
+
+    async fn auth_publickey_offered(
+        &mut self,
+        _user: &str,
+        public_key: &PublicKey,
+    ) -> std::result::Result<Auth, Self::Error> {
+        match self.lookup_key(public_key) {
+            Some(_) => Ok(Auth::Accept),
+            None => Ok(Auth::reject()),
+        }
+    }
+
+    async fn auth_publickey(
+        &mut self,
+        _user: &str,
+        public_key: &PublicKey,
+    ) -> std::result::Result<Auth, Self::Error> {
+        let Some((key_id, principal)) = self.lookup_key(public_key) else {
+            return Ok(Auth::reject());
+        };
+
+        if let Err(e) = self.state.store.update_ssh_key_last_used(&key_id) {
+            warn!("Failed to update SSH key last_used_at: {e}");
+        }
+
+        self.principal = Some(principal);
+        Ok(Auth::Accept)
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        channel: Channel<Msg>,
+        _session: &mut Session,
+    ) -> std::result::Result<bool, Self::Error> {
+        self.channels.insert(channel.id(), channel);
+        Ok(true)
+    }
+
+    async fn env_request(
+        &mut self,
+        channel: ChannelId,
+        variable_name: &str,
+        variable_value: &str,
+        session: &mut Session,
+    ) -> std::result::Result<(), Self::Error> {
+        if variable_name == "GIT_PROTOCOL" {
+            self.git_protocol = Some(variable_value.to_string());
+            session.channel_success(channel)?;
+        } else {
+            session.channel_failure(channel)?;
+        }
+        Ok(())
+    }
+
+    async fn shell_request(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> std::result::Result<(), Self::Error> {
+        let Some(channel) = self.channels.remove(&channel) else {
+            return Ok(());
+        };
+        session.channel_success(channel.id())?;
+
+        let namespace = self
+            .principal
+            .as_ref()
+            .and_then(|p| self.state.store.get_namespace(&p.primary_namespace_id).ok())
+            .flatten()
+            .map(|ns| ns.name)
+            .unwrap_or_default();
+
+        tokio::spawn(async move {
+            let (_, write_half) = channel.split();
+            let message = format!(
+                "Hi {namespace}! You've successfully authenticated, but cutman does not provide shell access.\n"
+            );
+            finish(&write_half, Some(&message), 1).await;
+        });
+
+        Ok(())
+    }
+
+    async fn exec_request(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> std::result::Result<(), Self::Error> {
+        let (Some(channel), Some(principal)) =
+            (self.channels.remove(&channel), self.principal.clone())
+        else {
+            session.channel_failure(channel)?;
+            return Ok(());
+        };
+        session.channel_success(channel.id())?;
+
+        let state = self.state.clone();
+        let command = String::from_utf8_lossy(data).into_owned();
+        let git_protocol = self.git_protocol.clone();
+        let peer_addr = self.peer_addr;
+
+        self.state.git_tasks.spawn(async move {
+            run_exec(state, principal, peer_addr, channel, command, git_protocol).await;
+        });
+
+        Ok(())
+    }
+}
+
+/// Sends an optional stderr message, the exit status, and closes the channel.
+async fn finish(write_half: &ChannelWriteHalf<Msg>, message: Option<&str>, code: u32) {
+    if let Some(message) = message {
+        let _ = write_half.extended_data(1, message.as_bytes()).await;
+    }
+    let _ = write_half.exit_status(code).await;
+    let _ = write_half.eof().await;
+    let _ = write_half.close().await;
+}
+
+async fn run_exec(
+    state: Arc<AppState>,
+    principal: Principal,
+    peer_addr: Option<SocketAddr>,
+    channel: Channel<Msg>,
+    command: String,
+    git_protocol: Option<String>,
+) {
+    let (mut read_half, write_half) = channel.split();
+
+    let Some(command) = parse_git_command(&command) else {
+        let message = "cutman: only git-upload-pack and git-receive-pack are supported\n";
+        finish(&write_half, Some(message), 1).await;
+        return;
+    };
+
+    let principal_id = principal.id.clone();
+    let target = match authorize(&state, principal, &command).await {
+        Ok(target) => target,
+        Err(e) => {
+            let mut messages = ClientMessages::new();
+            messages.info(e.message());
+            if let Some(hint) = push_rejection_hint(&e) {
+                messages.info(hint);
+            }
+            finish(&write_half, Some(&messages.text()), 1).await;
+            return;
+        }
+    };
+
+    // Clones and fetches share fair-queue and per-repo slots; pushes are
+    // not queued. SSH keys carry no traffic class, so these run as
+    // interactive.
+    let (slot, max_depth) = if command.service.is_write() {
+        ((None, None), None)
+    } else {
+        let slot = state
+            .clone_slot(
+                &target.repo.id,
+                &target.repo.namespace_id,
+                TrafficClass::Interactive,
+            )
+            .await;
+        (slot, state.max_clone_depth)
+    };
+    // SSH pushes are piped to git unparsed, so what changed is found by
+    // listing branches before and after.
+    let branches_before = if command.service.is_write() {
+        branch_tips(&target.path)
+    } else {
+        HashMap::new()
+    };
+    let code = match pipe_git(
+        command.service,
+        &target.path,
+        &target.env,
+        command.service.limits(&state.limits),
+        git_protocol.as_deref(),
+        max_depth,
+        &mut read_half,
+        &write_half,
+    )
+    .await
+    {
+        Ok(code) => code,
+        Err(e) => {
+            warn!("{} over SSH failed: {e}", command.service.command_name());
+            finish(&write_half, Some("cutman: Git command failed\n"), 1).await;
+            return;
+        }
+    };
+    drop(slot);
+
+    let mut messages = banner_messages(&state, None);
+    if command.service.is_write() {
+        record_push(&state, &target.repo, &target.path).await;
+        let updates = branch_updates(&branches_before, &branch_tips(&target.path));
+        let push = Push {
+            namespace: &target.namespace,
+            repo: &target.repo,
+            path: target.path.clone(),
+            principal_id: Some(principal_id),
+            token_id: None,
+            address: peer_addr.map(|addr| addr.ip().to_canonical().to_string()),
+            updates: updates.clone(),
+        };
+        emit(&state, push_event(&push));
+        check_push(&state, push).await;
+        let created: Vec<RefUpdate> = updates.into_iter().filter(RefUpdate::is_create).collect();
+        messages.extend(push_messages(
+            &state,
+            state
+                .public_base_url
+                .as_deref()
+                .map(|b| b.trim_end_matches('/')),
+            &target.namespace,
+            &target.repo,
+            &target.path,
+            &created,
+        ));
+    }
+
+    let messages = messages.text();
+    finish(
+        &write_half,
+        Some(messages.as_str()).filter(|m| !m.is_empty()),
+        code,
+    )
+    .await;
+}
+
+fn branch_tips(path: &Path) -> HashMap<String, git2::Oid> {
+    let Ok(repo) = git2::Repository::open_bare(path) else {
+        return HashMap::new();
+    };
+    let Ok(references) = repo.references_glob("refs/heads/*") else {
+        return HashMap::new();
+    };
+    references
+        .flatten()
+        .filter_map(|r| Some((r.name()?.to_string(), r.target()?)))
+        .collect()
+}
+
+/// Branches created, moved, or deleted between `before` and `after`, as
+/// ref updates.
+fn branch_updates(
+    before: &HashMap<String, git2::Oid>,
+    after: &HashMap<String, git2::Oid>,
+) -> Vec<RefUpdate> {
+    let zero = git2::Oid::zero();
+    let refnames: HashSet<&String> = before.keys().chain(after.keys()).collect();
+    let mut updates: Vec<RefUpdate> = refnames
+        .into_iter()
+        .filter_map(|refname| {
+            let old = before.get(refname).copied().unwrap_or(zero);
+            let new = after.get(refname).copied().unwrap_or(zero);
+            (old != new).then(|| RefUpdate {
+                old: old.to_string(),
+                new: new.to_string(),
+                refname: refname.clone(),
+            })
+        })
+        .collect();
+    updates.sort_by(|a, b| a.refname.cmp(&b.refname));
+    updates
+}
+
+/// The repository an exec request resolved to.
+struct ExecTarget {
+    namespace: Namespace,
+    repo: Repo,
+    path: PathBuf,
+    /// Extra environment for the git process (receive hooks on pushes).
+    env: Vec<(&'static str, String)>,
+}
+
+/// Applies the same namespace and repository checks as the HTTP transport,
+/// creating the repository on first push when the principal may do so.
+async fn authorize(
+    state: &Arc<AppState>,
+    principal: Principal,
+    command: &GitCommand,
+) -> std::result::Result<ExecTarget, GitAuthError> {
+    let repo_name = parse_repo_name(&command.repo, state.repo_name_policy)?;
+    let git_auth = GitAuth {
+        principal: Some(principal),
+        token: None,
+    };
+
+    let namespace = state
+        .store
+        .get_namespace_by_name(&command.namespace)
+        .map_err(|_| GitAuthError::InternalError)?
+        .ok_or(GitAuthError::NamespaceNotFound)?;
+
+    let repo = state
+        .store
+        .get_repo(&namespace.id, &repo_name)
+        .map_err(|_| GitAuthError::InternalError)?;
+
+    let is_write = command.service.is_write();
+    check_git_access(state, &git_auth, &namespace, repo.as_ref(), is_write)?;
+    if is_write {
+        require_hooks(state)?;
+    }
+
+    let repo = match repo {
+        Some(r) => r,
+        None if is_write => insert_pushed_repo(state, &namespace, &repo_name)?,
+        None => return Err(GitAuthError::RepoNotFound),
+    };
+
+    let path = state.repo_path(&namespace.id, &repo_name);
+
+    if !path.exists() {
+        if !is_write {
+            return Err(GitAuthError::RepoNotFound);
+        }
+        init_bare_repo(&path, repo.object_format)
+            .await
+            .map_err(|e| {
+                warn!("Failed to init bare repo: {e}");
+                GitAuthError::InternalError
+            })?;
+    }
+
+    // Bundle URIs must be absolute, so SSH clients are only pointed at
+    // bundles when the server knows its public URL.
+    let env = if is_write {
+        receive_pack_env(state, git_auth.principal.as_ref(), &namespace, &repo)?
+    } else if let Some(base_url) = &state.public_base_url {
+        let base_url = base_url.trim_end_matches('/');
+        bundle_uri_env(state, base_url, &namespace.name, &repo.name, &path)
+    } else {
+        Vec::new()
+    };
+
+    Ok(ExecTarget {
+        namespace,
+        repo,
+        path,
+        env,
+    })
+}
+
+/// Runs the git service against `path`, streaming the channel into its stdin
+/// and its stdout/stderr back to the client. With `max_depth`, the input is
+/// checked as a fetch and git is stopped if it asks for too much. Returns
+/// the exit code.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(name = "git", skip_all, fields(process.command = service.command_name()))]
+async fn pipe_git(
+    service: GitService,
+    path: &Path,
+    env: &[(&str, String)],
+    limits: &ProcessLimits,
+    git_protocol: Option<&str>,
+    max_depth: Option<u32>,
+    read_half: &mut ChannelReadHalf,
+    write_half: &ChannelWriteHalf<Msg>,
+) -> std::io::Result<u32> {
+    let mut cmd = Command::new(service.command_name());
+    cmd.arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .envs(env.iter().cloned())
+        .kill_on_drop(true);
+    if let Some(protocol) = git_protocol {
+        cmd.env("GIT_PROTOCOL", protocol);
+    }
+    apply_limits(&mut cmd, limits);
+
+    let mut child = cmd.spawn()?;
+    let (Some(mut stdin), Some(mut stdout), Some(mut stderr)) =
+        (child.stdin.take(), child.stdout.take(), child.stderr.take())
+    else {
+        return Err(std::io::Error::other("failed to capture git stdio"));
+    };
+
+    let mut out_writer = write_half.make_writer();
+    let mut err_writer = write_half.make_writer_ext(Some(1));
+
+    let input = async {
+        let mut reader = read_half.make_reader();
+        let refused = match max_depth {
+            Some(max_depth) => fetch::copy_request(&mut reader, &mut stdin, max_depth)
+                .await
+                .unwrap_or(None),
+            None => {
+                let _ = tokio::io::copy(&mut reader, &mut stdin).await;
+                None
+            }
+        };
+        drop(stdin);
+        match refused {
+            Some(reason) => reason,
+            // Keep polling nothing; the output side decides when we are done.
+            None => std::future::pending().await,
+        }
+    };
+    let output = async {
+        tokio::try_join!(
+            tokio::io::copy(&mut stdout, &mut out_writer),
+            tokio::io::copy(&mut stderr, &mut err_writer),
+        )
+    };
+
+    let transfer = async {
+        tokio::select! {
+            reason = input => Ok(Some(reason)),
+            result = output => result.map(|_| None),
+        }
+    };
+    match deadline(limits.timeout(None), transfer).await {
+        Some(Ok(Some(reason))) => {
+            child.start_kill()?;
+            out_writer.write_all(&fetch::refusal(&reason)).await?;
+            let _ = child.wait().await;
+            return Ok(1);
+        }
+        Some(result) => {
+            result?;
+        }
+        None => {
+            warn!(
+                "{} exceeded its time limit; killing it",
+                service.command_name()
+            );
+            child.start_kill()?;
+        }
+    }
+
+    let status = child.wait().await?;
+    Ok(status.code().map_or(1, |code| code as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_git_command() {
+        assert_eq!(
+            parse_git_command("git-upload-pack 'alice/demo.git'"),
+            Some(GitCommand {
+                service: GitService::UploadPack,
+                namespace: "alice".to_string(),
+                repo: "demo.git".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_git_command("git receive-pack '/alice/demo.git'"),
+            Some(GitCommand {
+                service: GitService::ReceivePack,
+                namespace: "alice".to_string(),
+                repo: "demo.git".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_git_command_rejects_other_commands() {
+        assert_eq!(parse_git_command("ls -la"), None);
+        assert_eq!(
+            parse_git_command("git-upload-archive 'alice/demo.git'"),
+            None
+        );
+        assert_eq!(parse_git_command("git-upload-pack 'demo.git'"), None);
+        assert_eq!(parse_git_command("git-upload-pack 'a/b/c.git'"), None);
+        assert_eq!(parse_git_command("git-upload-pack"), None);
+    }
+
+    #[test]
+    fn test_branch_updates() {
+        let oid = |hex: char| git2::Oid::from_str(&hex.to_string().repeat(40)).unwrap();
+        let tips = |branches: &[(&str, char)]| {
+            branches
+                .iter()
+                .map(|(name, hex)| (format!("refs/heads/{name}"), oid(*hex)))
+                .collect::<HashMap<_, _>>()
+        };
+        let before = tips(&[("main", 'a'), ("old", 'b'), ("same", 'c')]);
+        let after = tips(&[("main", 'd'), ("new", 'e'), ("same", 'c')]);
+
+        let updates = branch_updates(&before, &after);
+        let summary: Vec<_> = updates
+            .iter()
+            .map(|u| (u.refname.as_str(), u.is_create(), u.is_delete()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("refs/heads/main", false, false),
+                ("refs/heads/new", true, false),
+                ("refs/heads/old", false, true),
+            ]
+        );
+        assert_eq!(updates[0].old, oid('a').to_string());
+        assert_eq!(updates[0].new, oid('d').to_string());
+    }
+}
@@ -0,0 +1,70 @@
+//! Enforces [`crate::legal_hold`] in the API. Held repositories refuse
+//! deletions and history rewrites from everyone, admins included; an admin
+//! has to lift the hold first. Pushes are checked in pre-receive instead,
+//! and the database refuses to delete a held repository or its LFS objects
+//! whichever path asks.
+
+use crate::hooks::RefUpdate;
+use crate::legal_hold::check_ref_updates;
+use crate::types::{Repo, RepoHold};
+
+use super::AppState;
+use super::response::{ApiError, StoreResultExt};
+
+/// The repo's hold, if it has one.
+pub(crate) fn repo_hold(state: &AppState, repo: &Repo) -> Result<Option<RepoHold>, ApiError> {
+    state
+        .store
+        .get_repo_hold(&repo.id)
+        .api_err("Failed to get legal hold")
+}
+
+/// Fails when the repo is held, for requests that would delete it.
+pub(crate) fn check_repo_deletable(state: &AppState, repo: &Repo) -> Result<(), ApiError> {
+    match repo_hold(state, repo)? {
+        Some(hold) => Err(held_error(&hold, "it can't be deleted")),
+        None => Ok(()),
+    }
+}
+
+/// Fails when any repo in the namespace is held.
+pub(crate) fn check_namespace_deletable(
+    state: &AppState,
+    namespace_id: &str,
+) -> Result<(), ApiError> {
+    let holds = state
+        .store
+        .list_repo_holds(Some(namespace_id))
+        .api_err("Failed to list legal holds")?;
+    if holds.is_empty() {
+        return Ok(());
+    }
+    Err(ApiError::forbidden(format!(
+        "Namespace has {} repositories under a legal hold and can't be deleted",
+        holds.len()
+    )))
+}
+
+/// Fails when the repo is held and the hold refuses any of `updates`.
+pub(crate) fn check_held_ref_updates(
+    state: &AppState,
+    repo: &Repo,
+    git_repo: &git2::Repository,
+    updates: &[RefUpdate],
+) -> Result<(), ApiError> {
+    let Some(hold) = repo_hold(state, repo)? else {
+        return Ok(());
+    };
+    let refused = check_ref_updates(git_repo, updates);
+    if refused.is_empty() {
+        return Ok(());
+    }
+    Err(held_error(&hold, &refused.join("; ")))
+}
+
+fn held_error(hold: &RepoHold, detail: &str) -> ApiError {
+    ApiError::forbidden(format!(
+        "Repository is under a legal hold ({}): {detail}",
+        hold.reason
+    ))
+}
@@ -5,8 +5,8 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Deserialize)]
 pub struct BatchRequest {
     pub operation: String,
+    /// Transfer adapters the client supports, in order of preference.
     #[serde(default)]
-    #[allow(dead_code)]
     pub transfers: Option<Vec<String>>,
     pub objects: Vec<ObjectSpec>,
 }
@@ -99,3 +99,11 @@ pub struct VerifyRequest {
     pub oid: String,
     pub size: i64,
 }
+
+/// Progress of a chunked upload.
+#[derive(Debug, Serialize)]
+pub struct UploadStatus {
+    pub id: String,
+    /// Bytes received so far; the next chunk starts here.
+    pub offset: u64,
+}
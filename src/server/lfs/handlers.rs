@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::Json;
 use axum::body::Body;
@@ -8,18 +9,33 @@ use axum::http::{HeaderMap, StatusCode, header};
 use axum::response::{IntoResponse, Response};
 use chrono::Utc;
 use tokio_util::io::ReaderStream;
-use tracing::warn;
+use tracing::{info, warn};
 
 use super::dto::{
     Action, BatchRequest, BatchResponse, LfsError, ObjectResponse, ObjectSpec, VerifyRequest,
 };
-use crate::lfs::{LfsStorage, LfsStorageError, is_valid_oid};
+use super::transfer::{Direction, metered, read_body};
+use super::upload::CHUNKED_TRANSFER;
+use crate::config::{EventKind, Feature};
+use crate::lfs::{
+    LfsStorage, LfsStorageError, PresignedRequest, PresignedTransfer, is_valid_oid,
+};
+use crate::quota::{self, StorageUsage};
 use crate::server::AppState;
+use crate::server::events::{Event, emit};
 use crate::server::git::auth::{GitAuth, GitAuthError, check_git_access, extract_git_auth};
+use crate::server::validation::normalize_repo_name;
 use crate::types::{LfsObject, Namespace, Repo};
 
 const LFS_MEDIA_TYPE: &str = "application/vnd.git-lfs+json";
 
+/// How long batch actions, presigned URLs included, stay valid.
+const ACTION_EXPIRES_IN: Duration = Duration::from_secs(60 * 60);
+
+/// Largest object a basic transfer takes in one request, held in memory.
+/// Clients that offer the chunked transfer upload larger ones in parts.
+const MAX_BASIC_UPLOAD_BYTES: usize = 2 * 1024 * 1024;
+
 #[derive(serde::Deserialize)]
 pub struct LfsPathParams {
     pub namespace: String,
@@ -42,14 +58,14 @@ impl From<&LfsObjectPathParams> for LfsPathParams {
     }
 }
 
-struct LfsContext {
-    git_auth: GitAuth,
-    namespace: Namespace,
-    repo: Repo,
+pub(super) struct LfsContext {
+    pub git_auth: GitAuth,
+    pub namespace: Namespace,
+    pub repo: Repo,
 }
 
 #[must_use]
-fn lfs_json_response<T: serde::Serialize>(status: StatusCode, body: &T) -> Response {
+pub(super) fn lfs_json_response<T: serde::Serialize>(status: StatusCode, body: &T) -> Response {
     match serde_json::to_vec(body) {
         Ok(json) => Response::builder()
             .status(status)
@@ -68,7 +84,7 @@ fn lfs_json_response<T: serde::Serialize>(status: StatusCode, body: &T) -> Respo
 }
 
 #[must_use]
-fn lfs_error_response(status: StatusCode, message: &str) -> Response {
+pub(super) fn lfs_error_response(status: StatusCode, message: &str) -> Response {
     lfs_json_response(
         status,
         &LfsError {
@@ -78,7 +94,7 @@ fn lfs_error_response(status: StatusCode, message: &str) -> Response {
 }
 
 #[must_use]
-fn lfs_auth_error_response(err: GitAuthError) -> Response {
+pub(super) fn lfs_auth_error_response(err: GitAuthError) -> Response {
     let status = err.status_code();
     let mut response = lfs_error_response(status, err.message());
 
@@ -97,12 +113,17 @@ fn strip_git_suffix(name: &str) -> &str {
     name.strip_suffix(".git").unwrap_or(name)
 }
 
-async fn resolve_lfs_context(
+pub(super) async fn resolve_lfs_context(
     state: &Arc<AppState>,
     headers: &HeaderMap,
     params: &LfsPathParams,
 ) -> Result<LfsContext, GitAuthError> {
-    let repo_name = strip_git_suffix(&params.repo).to_lowercase();
+    if !state.features.enabled(Feature::Lfs) {
+        return Err(GitAuthError::LfsDisabled);
+    }
+
+    let repo_name = normalize_repo_name(strip_git_suffix(&params.repo), state.repo_name_policy)
+        .map_err(|_| GitAuthError::InvalidRepoName)?;
 
     let git_auth = extract_git_auth(headers, state).await?;
 
@@ -127,41 +148,32 @@ async fn resolve_lfs_context(
 
 #[must_use]
 fn build_object_url(host: &str, namespace: &str, repo: &str, oid: &str) -> String {
+    // `repo` is the path segment, which usually carries `.git` already.
+    let repo = urlencoding::encode(strip_git_suffix(repo));
     format!("{host}/git/{namespace}/{repo}.git/info/lfs/objects/{oid}")
 }
 
+/// Where chunked uploads of `oid` are started.
 #[must_use]
-fn build_verify_url(host: &str, namespace: &str, repo: &str) -> String {
-    format!("{host}/git/{namespace}/{repo}.git/info/lfs/verify")
+pub(super) fn build_upload_url(host: &str, namespace: &str, repo: &str, oid: &str) -> String {
+    format!("{}/uploads", build_object_url(host, namespace, repo, oid))
 }
 
 #[must_use]
-fn get_base_url(state: &AppState, headers: &HeaderMap) -> String {
-    // Use configured public_base_url if available, otherwise derive from headers
-    if let Some(ref base_url) = state.public_base_url {
-        return base_url.trim_end_matches('/').to_string();
-    }
-
-    let host = headers
-        .get(header::HOST)
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("localhost");
-
-    let scheme = headers
-        .get("x-forwarded-proto")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("http");
-
-    format!("{scheme}://{host}")
+fn build_verify_url(host: &str, namespace: &str, repo: &str) -> String {
+    let repo = urlencoding::encode(strip_git_suffix(repo));
+    format!("{host}/git/{namespace}/{repo}.git/info/lfs/verify")
 }
 
 struct BatchContext<'a> {
     state: &'a Arc<AppState>,
-    storage: &'a LfsStorage,
+    storage: &'a dyn LfsStorage,
     ctx: &'a LfsContext,
     host: &'a str,
     namespace: &'a str,
     repo: &'a str,
+    /// Transfer adapter the upload actions are built for.
+    transfer: &'a str,
 }
 
 pub async fn batch(
@@ -191,26 +203,57 @@ pub async fn batch(
         return lfs_auth_error_response(e);
     }
 
-    let storage = LfsStorage::new(&state.data_dir);
-    let host = get_base_url(&state, &headers);
+    // Uploads use the chunked adapter when the client supports it, unless
+    // they go straight to the storage backend. Downloads are plain GETs
+    // either way.
+    let storage = state.lfs_storage();
+    let offers_chunked = request
+        .transfers
+        .as_ref()
+        .is_some_and(|t| t.iter().any(|t| t == CHUNKED_TRANSFER));
+    let transfer = if is_upload && offers_chunked && !storage.can_presign() {
+        CHUNKED_TRANSFER
+    } else {
+        "basic"
+    };
+
+    // New objects count against the namespace's storage limit in the order
+    // they're listed; ones past it get an error instead of an action.
+    let mut usage = if is_upload {
+        match quota::storage_usage(state.store.as_ref(), &ctx.namespace) {
+            Ok(usage) => usage,
+            Err(e) => {
+                warn!("Failed to read storage usage: {e}");
+                return lfs_error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to read storage usage",
+                );
+            }
+        }
+    } else {
+        None
+    };
+
+    let host = state.base_url(&headers);
     let batch_ctx = BatchContext {
         state: &state,
-        storage: &storage,
+        storage: storage.as_ref(),
         ctx: &ctx,
         host: &host,
         namespace: &params.namespace,
         repo: &params.repo,
+        transfer,
     };
 
     let mut objects = Vec::with_capacity(request.objects.len());
 
     for obj in &request.objects {
-        let obj_response = build_object_response(&batch_ctx, obj, is_upload).await;
+        let obj_response = build_object_response(&batch_ctx, obj, is_upload, &mut usage).await;
         objects.push(obj_response);
     }
 
     let response = BatchResponse {
-        transfer: Some("basic".to_string()),
+        transfer: Some(transfer.to_string()),
         objects,
     };
 
@@ -221,71 +264,116 @@ async fn build_object_response(
     batch_ctx: &BatchContext<'_>,
     obj: &ObjectSpec,
     is_upload: bool,
+    usage: &mut Option<StorageUsage>,
 ) -> ObjectResponse {
     if !is_valid_oid(&obj.oid) {
         return ObjectResponse::with_error(obj.oid.clone(), obj.size, 422, "Invalid OID format");
     }
 
-    let exists_in_storage = batch_ctx
-        .storage
-        .exists(&batch_ctx.ctx.repo.id, &obj.oid)
-        .await
-        .unwrap_or(false);
-    let exists_in_db = batch_ctx
-        .state
-        .store
-        .get_lfs_object(&batch_ctx.ctx.repo.id, &obj.oid)
-        .ok()
-        .flatten()
-        .is_some();
-    let exists = exists_in_storage && exists_in_db;
+    // Another repository's copy of a pooled object doesn't count; this one
+    // has to upload it to prove it has the content. A corrupted object is
+    // neither served nor kept, so a client that still has it can replace it.
+    let corrupted = lfs_object_corrupted(batch_ctx.state, &obj.oid);
+    let exists = !corrupted
+        && lfs_object_recorded(batch_ctx.state, &batch_ctx.ctx.repo, &obj.oid)
+        && batch_ctx
+            .storage
+            .exists(&batch_ctx.ctx.repo.id, &obj.oid)
+            .await
+            .unwrap_or(false);
+
+    // Transfers the backend can take directly skip the server.
+    let presign = |transfer| {
+        batch_ctx
+            .storage
+            .presign(&obj.oid, transfer, ACTION_EXPIRES_IN)
+    };
 
     if is_upload {
+        if !exists && !corrupted && let Some(usage) = usage.as_mut() {
+            if let Some(exceeded) = usage.check(obj.size) {
+                return ObjectResponse::with_error(
+                    obj.oid.clone(),
+                    obj.size,
+                    StatusCode::INSUFFICIENT_STORAGE.as_u16().into(),
+                    exceeded.to_string(),
+                );
+            }
+            usage.used += obj.size;
+        }
+        let presigned = if exists {
+            None
+        } else {
+            presign(PresignedTransfer::Upload)
+        };
         build_upload_response(
             obj,
             exists,
             batch_ctx.host,
             batch_ctx.namespace,
             batch_ctx.repo,
+            batch_ctx.transfer,
+            presigned,
         )
     } else {
+        let presigned = if exists {
+            presign(PresignedTransfer::Download)
+        } else {
+            None
+        };
         build_download_response(
             obj,
             exists,
             batch_ctx.host,
             batch_ctx.namespace,
             batch_ctx.repo,
+            presigned,
         )
     }
 }
 
+fn action(href: String) -> Action {
+    Action {
+        href,
+        header: None,
+        expires_in: Some(ACTION_EXPIRES_IN.as_secs() as i32),
+    }
+}
+
+fn presigned_action(presigned: PresignedRequest) -> Action {
+    Action {
+        header: (!presigned.headers.is_empty()).then_some(presigned.headers),
+        ..action(presigned.href)
+    }
+}
+
 fn build_upload_response(
     obj: &ObjectSpec,
     exists: bool,
     host: &str,
     namespace: &str,
     repo: &str,
+    transfer: &str,
+    presigned: Option<PresignedRequest>,
 ) -> ObjectResponse {
     if exists {
         return ObjectResponse::exists(obj.oid.clone(), obj.size);
     }
 
+    let upload = match presigned {
+        Some(presigned) => presigned_action(presigned),
+        None if transfer == CHUNKED_TRANSFER => {
+            action(build_upload_url(host, namespace, repo, &obj.oid))
+        }
+        None => action(build_object_url(host, namespace, repo, &obj.oid)),
+    };
+    // Verifying is what records an object uploaded straight to the
+    // backend, so it always goes to the server.
     let actions = HashMap::from([
-        (
-            "upload".to_string(),
-            Action {
-                href: build_object_url(host, namespace, repo, &obj.oid),
-                header: None,
-                expires_in: Some(3600),
-            },
-        ),
+        ("upload".to_string(), upload),
         (
             "verify".to_string(),
-            Action {
-                href: build_verify_url(host, namespace, repo),
-                header: None,
-                expires_in: Some(3600),
-            },
+            action(build_verify_url(host, namespace, repo)),
         ),
     ]);
 
@@ -298,19 +386,17 @@ fn build_download_response(
     host: &str,
     namespace: &str,
     repo: &str,
+    presigned: Option<PresignedRequest>,
 ) -> ObjectResponse {
     if !exists {
         return ObjectResponse::with_error(obj.oid.clone(), obj.size, 404, "Object not found");
     }
 
-    let actions = HashMap::from([(
-        "download".to_string(),
-        Action {
-            href: build_object_url(host, namespace, repo, &obj.oid),
-            header: None,
-            expires_in: Some(3600),
-        },
-    )]);
+    let download = match presigned {
+        Some(presigned) => presigned_action(presigned),
+        None => action(build_object_url(host, namespace, repo, &obj.oid)),
+    };
+    let actions = HashMap::from([("download".to_string(), download)]);
 
     ObjectResponse::with_actions(obj.oid.clone(), obj.size, actions)
 }
@@ -339,21 +425,17 @@ pub async fn download(
         return lfs_error_response(StatusCode::BAD_REQUEST, "Invalid OID format");
     }
 
-    let storage = LfsStorage::new(&state.data_dir);
-
-    let (reader, size) = match storage.get(&ctx.repo.id, &params.oid).await {
-        Ok(result) => result,
-        Err(LfsStorageError::NotFound) => {
-            return lfs_error_response(StatusCode::NOT_FOUND, "Object not found");
-        }
-        Err(e) => {
-            warn!("LFS storage error: {e}");
-            return lfs_error_response(StatusCode::INTERNAL_SERVER_ERROR, "Storage error");
-        }
-    };
-
-    let stream = ReaderStream::new(reader);
-    let body = Body::from_stream(stream);
+    let (body, size) =
+        match open_lfs_download(&state, &ctx.namespace.name, &ctx.repo, &params.oid).await {
+            Ok(result) => result,
+            Err(LfsStorageError::NotFound) => {
+                return lfs_error_response(StatusCode::NOT_FOUND, "Object not found");
+            }
+            Err(e) => {
+                warn!("LFS storage error: {e}");
+                return lfs_error_response(StatusCode::INTERNAL_SERVER_ERROR, "Storage error");
+            }
+        };
 
     Response::builder()
         .status(StatusCode::OK)
@@ -364,11 +446,33 @@ pub async fn download(
         .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
 }
 
+/// An object's content as a response body held to the download rate, and
+/// its size. Objects `repo` has no record of are `NotFound`.
+pub(crate) async fn open_lfs_download(
+    state: &AppState,
+    namespace: &str,
+    repo: &Repo,
+    oid: &str,
+) -> Result<(Body, i64), LfsStorageError> {
+    // Objects are pooled across repositories, so one this repository never
+    // received may well be in storage.
+    if !lfs_object_recorded(state, repo, oid) {
+        return Err(LfsStorageError::NotFound);
+    }
+
+    let (reader, size) = state.lfs_storage().get(&repo.id, oid).await?;
+    let transfer = state
+        .lfs_transfers
+        .start(Direction::Download, namespace, &repo.name, oid);
+    let body = Body::from_stream(metered(ReaderStream::new(reader), transfer, size as u64));
+    Ok((body, size))
+}
+
 pub async fn upload(
     State(state): State<Arc<AppState>>,
     Path(params): Path<LfsObjectPathParams>,
     headers: HeaderMap,
-    body: axum::body::Bytes,
+    body: Body,
 ) -> Response {
     let ctx = match resolve_lfs_context(&state, &headers, &LfsPathParams::from(&params)).await {
         Ok(ctx) => ctx,
@@ -393,7 +497,32 @@ pub async fn upload(
         None => return lfs_error_response(StatusCode::BAD_REQUEST, "Content-Length required"),
     };
 
-    let storage = LfsStorage::new(&state.data_dir);
+    if let Some(response) = lfs_quota_error(&state, &ctx, &params.oid, expected_size) {
+        return response;
+    }
+
+    let mut transfer = state.lfs_transfers.start(
+        Direction::Upload,
+        &ctx.namespace.name,
+        &ctx.repo.name,
+        &params.oid,
+    );
+    let body = match read_body(body, &mut transfer, MAX_BASIC_UPLOAD_BYTES).await {
+        Ok(body) => body,
+        Err(e) if e.kind() == std::io::ErrorKind::FileTooLarge => {
+            return lfs_error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Object too large for a single request; use the chunked transfer",
+            );
+        }
+        Err(e) => {
+            warn!("Failed to read LFS upload: {e}");
+            return lfs_error_response(StatusCode::BAD_REQUEST, "Failed to read upload");
+        }
+    };
+
+    let storage = state.lfs_storage();
+    let replacing = discard_corrupted(&state, &params.oid).await;
 
     if let Err(e) = storage
         .put(&ctx.repo.id, &params.oid, &body, expected_size)
@@ -410,18 +539,99 @@ pub async fn upload(
         };
     }
 
+    record_lfs_object(&state, &ctx.repo, &params.oid, expected_size);
+    if replacing {
+        mark_lfs_object_repaired(&state, &params.oid);
+    }
+    transfer.finish();
+
+    StatusCode::OK.into_response()
+}
+
+fn lfs_object_recorded(state: &AppState, repo: &Repo, oid: &str) -> bool {
+    state
+        .store
+        .get_lfs_object(&repo.id, oid)
+        .ok()
+        .flatten()
+        .is_some()
+}
+
+fn lfs_object_corrupted(state: &AppState, oid: &str) -> bool {
+    state.store.is_lfs_object_corrupted(oid).unwrap_or(false)
+}
+
+/// Removes a pooled object found corrupted, since storing an object the
+/// pool already holds leaves the existing copy in place. Returns whether
+/// the upload about to be stored replaces one.
+pub(super) async fn discard_corrupted(state: &AppState, oid: &str) -> bool {
+    if !lfs_object_corrupted(state, oid) {
+        return false;
+    }
+    if let Err(e) = state.lfs_storage().delete(oid).await {
+        warn!("Failed to remove corrupted LFS object {oid}: {e}");
+    }
+    true
+}
+
+/// Clears the corrupted mark once a verified copy has been stored.
+pub(super) fn mark_lfs_object_repaired(state: &AppState, oid: &str) {
+    match state.store.record_lfs_check(oid, None) {
+        Ok(()) => info!("Replaced corrupted LFS object {oid}"),
+        Err(e) => warn!("Failed to record repaired LFS object {oid}: {e}"),
+    }
+}
+
+/// The error for an upload of `size` bytes the repository doesn't have
+/// yet, if it would take the namespace over its storage limit.
+pub(super) fn lfs_quota_error(
+    state: &AppState,
+    ctx: &LfsContext,
+    oid: &str,
+    size: i64,
+) -> Option<Response> {
+    if lfs_object_recorded(state, &ctx.repo, oid) {
+        return None;
+    }
+    match quota::storage_usage(state.store.as_ref(), &ctx.namespace) {
+        Ok(usage) => {
+            let exceeded = usage?.check(size)?;
+            Some(lfs_error_response(
+                StatusCode::INSUFFICIENT_STORAGE,
+                &exceeded.to_string(),
+            ))
+        }
+        Err(e) => {
+            warn!("Failed to read storage usage: {e}");
+            Some(lfs_error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read storage usage",
+            ))
+        }
+    }
+}
+
+/// Records an uploaded object in the repository's LFS object list.
+pub(super) fn record_lfs_object(state: &AppState, repo: &Repo, oid: &str, size: i64) {
     let lfs_object = LfsObject {
-        repo_id: ctx.repo.id.clone(),
-        oid: params.oid.clone(),
-        size: expected_size,
+        repo_id: repo.id.clone(),
+        oid: oid.to_string(),
+        size,
         created_at: Utc::now(),
     };
 
-    if let Err(e) = state.store.create_lfs_object(&lfs_object) {
-        warn!("Failed to create LFS object record: {e}");
+    match state.store.create_lfs_object(&lfs_object) {
+        Ok(()) => emit(
+            state,
+            Event::new(
+                EventKind::LfsUploaded,
+                serde_json::json!({ "repo": repo.name, "oid": oid, "size": size }),
+            )
+            .namespace(&repo.namespace_id)
+            .repo(&repo.id),
+        ),
+        Err(e) => warn!("Failed to create LFS object record: {e}"),
     }
-
-    StatusCode::OK.into_response()
 }
 
 pub async fn verify(
@@ -443,7 +653,14 @@ pub async fn verify(
         return lfs_error_response(StatusCode::BAD_REQUEST, "Invalid OID format");
     }
 
-    let storage = LfsStorage::new(&state.data_dir);
+    // Objects uploaded through a presigned URL are only known to storage
+    // until now. Without presigning, an object this repository never
+    // received may only be in the pool on another's behalf.
+    let storage = state.lfs_storage();
+    let recorded = lfs_object_recorded(&state, &ctx.repo, &request.oid);
+    if !recorded && !storage.can_presign() {
+        return lfs_error_response(StatusCode::NOT_FOUND, "Object not found");
+    }
 
     let actual_size = match storage.size(&ctx.repo.id, &request.oid).await {
         Ok(size) => size,
@@ -466,6 +683,10 @@ pub async fn verify(
         );
     }
 
+    if !recorded {
+        record_lfs_object(&state, &ctx.repo, &request.oid, actual_size);
+    }
+
     Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, LFS_MEDIA_TYPE)
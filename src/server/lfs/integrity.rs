@@ -0,0 +1,97 @@
+//! Re-hashes stored LFS objects against their OIDs so that content damaged
+//! at rest is noticed before a client downloads it. An object that no
+//! longer matches, or has gone missing, is marked corrupted in the store
+//! and listed by the admin API until a client uploads it again.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::lfs::{LfsStorageError, hash_reader};
+use crate::server::AppState;
+use crate::types::LfsObject;
+
+/// Upper bound on how long the checker sleeps between passes, so objects
+/// become due close to their interval.
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Objects re-hashed per store query.
+const VERIFY_BATCH_SIZE: i32 = 100;
+
+/// Why an object failed its check, or `None` if it passed. Errors that
+/// say nothing about the object itself are returned as `Err`.
+async fn check(state: &AppState, object: &LfsObject) -> Result<Option<String>, LfsStorageError> {
+    let (reader, _) = match state.lfs_storage().get(&object.repo_id, &object.oid).await {
+        Ok(found) => found,
+        Err(LfsStorageError::NotFound) => return Ok(Some("object is missing".to_string())),
+        Err(e) => return Err(e),
+    };
+    let (hash, size) = hash_reader(reader).await?;
+    if hash != object.oid {
+        return Ok(Some(format!("content hashes to {hash}")));
+    }
+    if size != object.size {
+        return Ok(Some(format!("size is {size}, expected {}", object.size)));
+    }
+    Ok(None)
+}
+
+/// Re-hashes every object not checked within `interval`, returning how
+/// many were newly found corrupted.
+async fn verify(state: &AppState, interval: Duration) -> crate::error::Result<u64> {
+    let mut corrupted = 0;
+    loop {
+        let store = state.store.clone();
+        let objects = tokio::task::spawn_blocking(move || {
+            store.list_lfs_objects_to_verify(interval, VERIFY_BATCH_SIZE)
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+        if objects.is_empty() {
+            return Ok(corrupted);
+        }
+
+        let mut checked = 0;
+        for object in objects {
+            match check(state, &object).await {
+                Ok(None) => state.store.record_lfs_check(&object.oid, None)?,
+                Ok(Some(error)) => {
+                    if !state.store.is_lfs_object_corrupted(&object.oid)? {
+                        warn!("LFS object {} is corrupted: {error}", object.oid);
+                        corrupted += 1;
+                    }
+                    state.store.record_lfs_check(&object.oid, Some(&error))?;
+                }
+                // Left unrecorded, so it is tried again next pass.
+                Err(e) => {
+                    warn!("Failed to verify LFS object {}: {e}", object.oid);
+                    continue;
+                }
+            }
+            checked += 1;
+        }
+        // Every object in the batch failed to read; try again next pass
+        // rather than fetching the same batch forever.
+        if checked == 0 {
+            return Ok(corrupted);
+        }
+    }
+}
+
+/// Re-hashes stored LFS objects once they were last checked more than
+/// `interval` ago.
+pub fn spawn_lfs_integrity_check(state: Arc<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        loop {
+            match verify(&state, interval).await {
+                Ok(corrupted) if corrupted > 0 => {
+                    info!("Found {corrupted} corrupted LFS objects");
+                }
+                Ok(_) => {}
+                Err(e) => warn!("LFS integrity check failed: {e}"),
+            }
+            tokio::time::sleep(interval.min(MAX_POLL_INTERVAL)).await;
+        }
+    });
+}
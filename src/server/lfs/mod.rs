@@ -1,5 +1,9 @@
 mod dto;
 mod handlers;
+mod integrity;
+mod pool;
+mod transfer;
+pub(crate) mod upload;
 
 use std::sync::Arc;
 
@@ -8,10 +12,23 @@ use axum::routing::{get, post, put};
 
 use crate::server::AppState;
 
+pub(crate) use handlers::open_lfs_download;
+pub use integrity::spawn_lfs_integrity_check;
+pub use pool::spawn_lfs_pool_collector;
+pub use transfer::{LfsTransferLimits, LfsTransfers, TransferTotals};
+
 pub fn lfs_router() -> Router<Arc<AppState>> {
     Router::new()
         .route("/objects/batch", post(handlers::batch))
         .route("/objects/{oid}", get(handlers::download))
         .route("/objects/{oid}", put(handlers::upload))
+        .route("/objects/{oid}/uploads", post(upload::start))
+        .route(
+            "/objects/{oid}/uploads/{id}",
+            get(upload::status)
+                .patch(upload::append)
+                .put(upload::finish)
+                .delete(upload::cancel),
+        )
         .route("/verify", post(handlers::verify))
 }
@@ -0,0 +1,68 @@
+//! Removes pooled LFS objects once no repository references them. The
+//! store counts references as repositories record and drop objects; an
+//! object is collected only after going unreferenced for
+//! [`UNREFERENCED_GRACE`], so a repository that is deleted and recreated,
+//! or a presigned upload still waiting to be verified, keeps its content.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::server::AppState;
+
+/// How long an object stays pooled after its last reference goes.
+const UNREFERENCED_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// How often unreferenced objects are collected.
+const COLLECT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Objects removed per store query.
+const COLLECT_BATCH_SIZE: i32 = 500;
+
+/// Removes every object unreferenced for the grace period, returning how
+/// many were removed.
+async fn collect(state: &AppState) -> crate::error::Result<u64> {
+    let storage = state.lfs_storage();
+    let mut removed = 0;
+    loop {
+        let store = state.store.clone();
+        let oids = tokio::task::spawn_blocking(move || {
+            store.list_unreferenced_lfs_objects(UNREFERENCED_GRACE, COLLECT_BATCH_SIZE)
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+        if oids.is_empty() {
+            return Ok(removed);
+        }
+
+        for oid in oids {
+            // Claiming the entry first means an object referenced again in
+            // the meantime is left alone.
+            if !state.store.delete_unreferenced_lfs_object(&oid)? {
+                continue;
+            }
+            match storage.delete(&oid).await {
+                Ok(true) => removed += 1,
+                Ok(false) => {}
+                Err(e) => warn!("Failed to remove unreferenced LFS object {oid}: {e}"),
+            }
+        }
+    }
+}
+
+/// Collects unreferenced LFS objects at startup and then every hour.
+pub fn spawn_lfs_pool_collector(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        loop {
+            match collect(&state).await {
+                Ok(removed) if removed > 0 => {
+                    info!("Removed {removed} unreferenced LFS objects");
+                }
+                Ok(_) => {}
+                Err(e) => warn!("LFS object collection failed: {e}"),
+            }
+            tokio::time::sleep(COLLECT_INTERVAL).await;
+        }
+    });
+}
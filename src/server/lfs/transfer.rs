@@ -0,0 +1,319 @@
+//! Throughput limits and usage counters for LFS objects moving through the
+//! server. Each upload or download request is held to the configured rate
+//! on its own, and its bytes, duration, and outcome are logged and added to
+//! per-namespace totals for `GET /api/v1/admin/lfs/transfers`. Objects
+//! moved with presigned URLs go straight to the backend and aren't counted.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::body::{Body, Bytes};
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, StreamExt, stream};
+use serde::Serialize;
+use tokio::time::Instant;
+use tracing::{Span, info, info_span};
+
+/// Bytes per second a single request may move. Unlimited when unset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LfsTransferLimits {
+    pub upload_bytes_per_sec: Option<u64>,
+    pub download_bytes_per_sec: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Upload,
+    Download,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Upload => f.write_str("upload"),
+            Self::Download => f.write_str("download"),
+        }
+    }
+}
+
+/// Transfer requests for one namespace since the server started. A chunked
+/// upload counts once per chunk.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TransferTotals {
+    pub uploads: u64,
+    pub upload_failures: u64,
+    pub upload_bytes: u64,
+    pub upload_millis: u64,
+    pub downloads: u64,
+    pub download_failures: u64,
+    pub download_bytes: u64,
+    pub download_millis: u64,
+}
+
+impl TransferTotals {
+    fn add(&mut self, direction: Direction, bytes: u64, elapsed: Duration, ok: bool) {
+        let millis = elapsed.as_millis() as u64;
+        let (count, failures, total_bytes, total_millis) = match direction {
+            Direction::Upload => (
+                &mut self.uploads,
+                &mut self.upload_failures,
+                &mut self.upload_bytes,
+                &mut self.upload_millis,
+            ),
+            Direction::Download => (
+                &mut self.downloads,
+                &mut self.download_failures,
+                &mut self.download_bytes,
+                &mut self.download_millis,
+            ),
+        };
+        if ok {
+            *count += 1;
+        } else {
+            *failures += 1;
+        }
+        *total_bytes += bytes;
+        *total_millis += millis;
+    }
+}
+
+pub struct LfsTransfers {
+    limits: LfsTransferLimits,
+    since: DateTime<Utc>,
+    /// Keyed by namespace name.
+    totals: Mutex<HashMap<String, TransferTotals>>,
+}
+
+impl LfsTransfers {
+    #[must_use]
+    pub fn new(limits: LfsTransferLimits) -> Self {
+        Self {
+            limits,
+            since: Utc::now(),
+            totals: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[must_use]
+    pub fn limits(&self) -> LfsTransferLimits {
+        self.limits
+    }
+
+    /// Starts timing and throttling one request's transfer.
+    pub fn start(
+        self: &Arc<Self>,
+        direction: Direction,
+        namespace: &str,
+        repo: &str,
+        oid: &str,
+    ) -> Transfer {
+        let bytes_per_sec = match direction {
+            Direction::Upload => self.limits.upload_bytes_per_sec,
+            Direction::Download => self.limits.download_bytes_per_sec,
+        };
+        let span = info_span!(
+            "lfs_transfer",
+            lfs.direction = %direction,
+            lfs.oid = oid,
+            namespace,
+            repo,
+            lfs.bytes = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+        );
+        Transfer {
+            transfers: Arc::clone(self),
+            span,
+            direction,
+            namespace: namespace.to_string(),
+            repo: repo.to_string(),
+            oid: oid.to_string(),
+            bytes_per_sec: bytes_per_sec.filter(|rate| *rate > 0),
+            started: Instant::now(),
+            bytes: 0,
+            finished: false,
+        }
+    }
+
+    /// When counting started, and the totals for each namespace by name.
+    #[must_use]
+    pub fn totals(&self) -> (DateTime<Utc>, Vec<(String, TransferTotals)>) {
+        let totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        let mut totals: Vec<_> = totals
+            .iter()
+            .map(|(namespace, totals)| (namespace.clone(), totals.clone()))
+            .collect();
+        totals.sort_by(|a, b| a.0.cmp(&b.0));
+        (self.since, totals)
+    }
+}
+
+impl Default for LfsTransfers {
+    fn default() -> Self {
+        Self::new(LfsTransferLimits::default())
+    }
+}
+
+/// One request's transfer. It counts as failed unless
+/// [`finish`](Transfer::finish)ed, so an early return or a dropped
+/// connection is recorded too. Its span closes when it's recorded.
+pub struct Transfer {
+    transfers: Arc<LfsTransfers>,
+    span: Span,
+    direction: Direction,
+    namespace: String,
+    repo: String,
+    oid: String,
+    bytes_per_sec: Option<u64>,
+    started: Instant,
+    bytes: u64,
+    finished: bool,
+}
+
+impl Transfer {
+    /// Counts `len` more bytes, waiting as long as it takes to bring the
+    /// request back down to its rate.
+    pub async fn add(&mut self, len: usize) {
+        self.bytes += len as u64;
+        let Some(rate) = self.bytes_per_sec else {
+            return;
+        };
+        let due = Duration::from_secs_f64(self.bytes as f64 / rate as f64);
+        let elapsed = self.started.elapsed();
+        if due > elapsed {
+            tokio::time::sleep(due - elapsed).await;
+        }
+    }
+
+    pub fn finish(mut self) {
+        self.record(true);
+    }
+
+    fn record(&mut self, ok: bool) {
+        self.finished = true;
+        let elapsed = self.started.elapsed();
+        self.span.record("lfs.bytes", self.bytes);
+        self.span
+            .record("otel.status_code", if ok { "ok" } else { "error" });
+        info!(
+            "LFS {} of {} for {}/{} {} after {} bytes in {}ms",
+            self.direction,
+            self.oid,
+            self.namespace,
+            self.repo,
+            if ok { "finished" } else { "failed" },
+            self.bytes,
+            elapsed.as_millis()
+        );
+        let mut totals = self
+            .transfers
+            .totals
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        totals
+            .entry(self.namespace.clone())
+            .or_default()
+            .add(self.direction, self.bytes, elapsed, ok);
+    }
+}
+
+impl Drop for Transfer {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.record(false);
+        }
+    }
+}
+
+/// `chunks` held to the transfer's rate, finishing it once `size` bytes
+/// have gone out; the connection may be dropped as soon as they have.
+pub(super) fn metered<S>(
+    chunks: S,
+    transfer: Transfer,
+    size: u64,
+) -> impl Stream<Item = io::Result<Bytes>>
+where
+    S: Stream<Item = io::Result<Bytes>> + Unpin,
+{
+    stream::unfold(
+        (chunks, Some(transfer)),
+        move |(mut chunks, mut transfer)| async move {
+            let mut current = transfer.take()?;
+            match chunks.next().await {
+                Some(Ok(chunk)) => {
+                    current.add(chunk.len()).await;
+                    if current.bytes >= size {
+                        current.finish();
+                        return Some((Ok(chunk), (chunks, None)));
+                    }
+                    Some((Ok(chunk), (chunks, Some(current))))
+                }
+                // Dropping the transfer records it as failed.
+                Some(Err(e)) => Some((Err(e), (chunks, None))),
+                None => {
+                    current.finish();
+                    None
+                }
+            }
+        },
+    )
+}
+
+/// Reads a request body of at most `limit` bytes at the transfer's rate.
+/// A larger body fails with [`io::ErrorKind::FileTooLarge`].
+pub(super) async fn read_body(
+    body: Body,
+    transfer: &mut Transfer,
+    limit: usize,
+) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut chunks = body.into_data_stream();
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(io::Error::other)?;
+        if data.len() + chunk.len() > limit {
+            return Err(io::Error::new(
+                io::ErrorKind::FileTooLarge,
+                format!("body is larger than {limit} bytes"),
+            ));
+        }
+        data.extend_from_slice(&chunk);
+        transfer.add(chunk.len()).await;
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_transfers_are_throttled_and_counted() {
+        let transfers = Arc::new(LfsTransfers::new(LfsTransferLimits {
+            upload_bytes_per_sec: Some(1000),
+            download_bytes_per_sec: None,
+        }));
+
+        let started = Instant::now();
+        let mut upload = transfers.start(Direction::Upload, "ns", "repo", "oid");
+        upload.add(1500).await;
+        upload.add(500).await;
+        upload.finish();
+        assert!(started.elapsed() >= Duration::from_secs(2));
+
+        let mut download = transfers.start(Direction::Download, "ns", "repo", "oid");
+        download.add(300).await;
+        drop(download);
+
+        let (_, totals) = transfers.totals();
+        assert_eq!(totals.len(), 1);
+        let (namespace, totals) = &totals[0];
+        assert_eq!(namespace, "ns");
+        assert_eq!(totals.uploads, 1);
+        assert_eq!(totals.upload_bytes, 2000);
+        assert_eq!(totals.downloads, 0);
+        assert_eq!(totals.download_failures, 1);
+        assert_eq!(totals.download_bytes, 300);
+    }
+}
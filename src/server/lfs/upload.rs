@@ -0,0 +1,328 @@
+//! The `chunked` transfer adapter: an object is uploaded in any number of
+//! requests and can be resumed after an interruption, instead of in the one
+//! PUT the `basic` adapter uses. Chunks are appended to a file staged in the
+//! repository's LFS temp directory, so uploads abandoned for longer than
+//! the temp sweeper's max age are cleaned up.
+//!
+//! 1. `POST .../objects/{oid}/uploads` starts an upload and returns its ID.
+//! 2. `PATCH .../objects/{oid}/uploads/{id}` appends a chunk. Its
+//!    `Content-Range` must start where the upload left off.
+//! 3. `GET .../objects/{oid}/uploads/{id}` reports how many bytes arrived,
+//!    so a client can resume from there.
+//! 4. `PUT .../objects/{oid}/uploads/{id}` stores the object once its
+//!    content matches the OID.
+
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use futures_util::StreamExt;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::dto::UploadStatus;
+use super::transfer::{Direction, Transfer};
+use super::handlers::{
+    LfsContext, LfsObjectPathParams, LfsPathParams, build_upload_url, discard_corrupted,
+    lfs_auth_error_response, lfs_error_response, lfs_json_response, lfs_quota_error,
+    mark_lfs_object_repaired, record_lfs_object, resolve_lfs_context,
+};
+use crate::lfs::{LfsStorageError, is_valid_oid};
+use crate::server::AppState;
+use crate::server::git::auth::check_git_access;
+
+/// Name of the transfer adapter in batch requests and responses.
+pub const CHUNKED_TRANSFER: &str = "chunked";
+
+#[derive(serde::Deserialize)]
+pub struct UploadPathParams {
+    pub namespace: String,
+    pub repo: String,
+    pub oid: String,
+    pub id: String,
+}
+
+impl From<&UploadPathParams> for LfsPathParams {
+    fn from(params: &UploadPathParams) -> Self {
+        Self {
+            namespace: params.namespace.clone(),
+            repo: params.repo.clone(),
+        }
+    }
+}
+
+/// Appends a request body to a staged upload, returning the upload's size.
+pub(crate) async fn append_body(
+    path: &FsPath,
+    body: Body,
+    create: bool,
+    mut transfer: Option<&mut Transfer>,
+) -> std::io::Result<u64> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .append(true)
+        .create(create)
+        .open(path)
+        .await?;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(std::io::Error::other)?;
+        file.write_all(&chunk).await?;
+        if let Some(transfer) = transfer.as_deref_mut() {
+            transfer.add(chunk.len()).await;
+        }
+    }
+    file.sync_all().await?;
+    Ok(file.metadata().await?.len())
+}
+
+/// Where the chunk in a `Content-Range: bytes <start>-<end>/<total>` (or
+/// bare `<start>-<end>`) header begins.
+fn range_start(headers: &HeaderMap) -> Option<u64> {
+    let range = headers.get(header::CONTENT_RANGE)?.to_str().ok()?;
+    let range = range.strip_prefix("bytes ").unwrap_or(range);
+    range.split('-').next()?.trim().parse().ok()
+}
+
+/// Resolves the repository and checks the caller may upload to it.
+async fn authorize(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    params: &LfsPathParams,
+    oid: &str,
+) -> Result<LfsContext, Response> {
+    let ctx = resolve_lfs_context(state, headers, params)
+        .await
+        .map_err(lfs_auth_error_response)?;
+    check_git_access(state, &ctx.git_auth, &ctx.namespace, Some(&ctx.repo), true)
+        .map_err(lfs_auth_error_response)?;
+    if !is_valid_oid(oid) {
+        return Err(lfs_error_response(
+            StatusCode::BAD_REQUEST,
+            "Invalid OID format",
+        ));
+    }
+    Ok(ctx)
+}
+
+/// The staged file of the upload `id` names, if it is still in progress.
+fn staged_upload(state: &AppState, ctx: &LfsContext, id: &str) -> Option<PathBuf> {
+    let id = Uuid::parse_str(id).ok()?;
+    let path = state.lfs_storage().upload_path(&ctx.repo.id, &id);
+    path.exists().then_some(path)
+}
+
+fn unknown_upload() -> Response {
+    lfs_error_response(StatusCode::NOT_FOUND, "Upload not found")
+}
+
+fn upload_status(status: StatusCode, id: &str, offset: u64) -> Response {
+    lfs_json_response(
+        status,
+        &UploadStatus {
+            id: id.to_string(),
+            offset,
+        },
+    )
+}
+
+fn storage_error(e: &std::io::Error) -> Response {
+    warn!("LFS storage error during chunked upload: {e}");
+    lfs_error_response(StatusCode::INTERNAL_SERVER_ERROR, "Storage error")
+}
+
+/// POST /objects/{oid}/uploads - Starts a chunked upload
+pub async fn start(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<LfsObjectPathParams>,
+    headers: HeaderMap,
+) -> Response {
+    let ctx = match authorize(&state, &headers, &LfsPathParams::from(&params), &params.oid).await
+    {
+        Ok(ctx) => ctx,
+        Err(response) => return response,
+    };
+
+    let id = Uuid::new_v4();
+    let path = state.lfs_storage().upload_path(&ctx.repo.id, &id);
+    if let Some(parent) = path.parent()
+        && let Err(e) = tokio::fs::create_dir_all(parent).await
+    {
+        return storage_error(&e);
+    }
+    if let Err(e) = tokio::fs::File::create(&path).await {
+        return storage_error(&e);
+    }
+
+    let id = id.to_string();
+    let url = build_upload_url(
+        &state.base_url(&headers),
+        &params.namespace,
+        &params.repo,
+        &params.oid,
+    );
+    let mut response = upload_status(StatusCode::CREATED, &id, 0);
+    if let Ok(location) = format!("{url}/{id}").parse() {
+        response.headers_mut().insert(header::LOCATION, location);
+    }
+    response
+}
+
+/// GET /objects/{oid}/uploads/{id} - Bytes received so far
+pub async fn status(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<UploadPathParams>,
+    headers: HeaderMap,
+) -> Response {
+    let ctx = match authorize(&state, &headers, &LfsPathParams::from(&params), &params.oid).await
+    {
+        Ok(ctx) => ctx,
+        Err(response) => return response,
+    };
+    let Some(path) = staged_upload(&state, &ctx, &params.id) else {
+        return unknown_upload();
+    };
+
+    match tokio::fs::metadata(&path).await {
+        Ok(metadata) => upload_status(StatusCode::OK, &params.id, metadata.len()),
+        Err(e) => storage_error(&e),
+    }
+}
+
+/// PATCH /objects/{oid}/uploads/{id} - Appends a chunk
+pub async fn append(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<UploadPathParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let ctx = match authorize(&state, &headers, &LfsPathParams::from(&params), &params.oid).await
+    {
+        Ok(ctx) => ctx,
+        Err(response) => return response,
+    };
+    let Some(path) = staged_upload(&state, &ctx, &params.id) else {
+        return unknown_upload();
+    };
+
+    // Requiring the offset means a retried chunk can never be appended
+    // twice.
+    let Some(start) = range_start(&headers) else {
+        return lfs_error_response(StatusCode::BAD_REQUEST, "Content-Range required");
+    };
+    let current = match tokio::fs::metadata(&path).await {
+        Ok(metadata) => metadata.len(),
+        Err(e) => return storage_error(&e),
+    };
+    if start != current {
+        return lfs_error_response(
+            StatusCode::CONFLICT,
+            &format!("Chunk starts at {start}, but {current} bytes were uploaded"),
+        );
+    }
+
+    let mut transfer = state.lfs_transfers.start(
+        Direction::Upload,
+        &ctx.namespace.name,
+        &ctx.repo.name,
+        &params.oid,
+    );
+    match append_body(&path, body, false, Some(&mut transfer)).await {
+        Ok(size) => {
+            transfer.finish();
+            upload_status(StatusCode::OK, &params.id, size)
+        }
+        Err(e) => storage_error(&e),
+    }
+}
+
+/// PUT /objects/{oid}/uploads/{id} - Stores the object once every chunk
+/// has arrived
+pub async fn finish(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<UploadPathParams>,
+    headers: HeaderMap,
+) -> Response {
+    let ctx = match authorize(&state, &headers, &LfsPathParams::from(&params), &params.oid).await
+    {
+        Ok(ctx) => ctx,
+        Err(response) => return response,
+    };
+    let Some(path) = staged_upload(&state, &ctx, &params.id) else {
+        return unknown_upload();
+    };
+    let staged_size = match tokio::fs::metadata(&path).await {
+        Ok(metadata) => metadata.len() as i64,
+        Err(e) => return storage_error(&e),
+    };
+    if let Some(response) = lfs_quota_error(&state, &ctx, &params.oid, staged_size) {
+        return response;
+    }
+
+    let replacing = discard_corrupted(&state, &params.oid).await;
+    let size = match state
+        .lfs_storage()
+        .put_file(&ctx.repo.id, &params.oid, &path)
+        .await
+    {
+        Ok(size) => size,
+        Err(LfsStorageError::HashMismatch { .. }) => {
+            return lfs_error_response(StatusCode::BAD_REQUEST, "Hash mismatch");
+        }
+        Err(LfsStorageError::NotFound) => return unknown_upload(),
+        Err(e) => {
+            warn!("LFS storage error during chunked upload: {e}");
+            return lfs_error_response(StatusCode::INTERNAL_SERVER_ERROR, "Storage error");
+        }
+    };
+    record_lfs_object(&state, &ctx.repo, &params.oid, size);
+    if replacing {
+        mark_lfs_object_repaired(&state, &params.oid);
+    }
+
+    StatusCode::OK.into_response()
+}
+
+/// DELETE /objects/{oid}/uploads/{id} - Abandons an upload
+pub async fn cancel(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<UploadPathParams>,
+    headers: HeaderMap,
+) -> Response {
+    let ctx = match authorize(&state, &headers, &LfsPathParams::from(&params), &params.oid).await
+    {
+        Ok(ctx) => ctx,
+        Err(response) => return response,
+    };
+    let Some(path) = staged_upload(&state, &ctx, &params.id) else {
+        return unknown_upload();
+    };
+
+    match tokio::fs::remove_file(&path).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => storage_error(&e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_RANGE, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_range_start() {
+        assert_eq!(range_start(&range("bytes 0-1023/4096")), Some(0));
+        assert_eq!(range_start(&range("bytes 1024-2047/*")), Some(1024));
+        assert_eq!(range_start(&range("2048-4095")), Some(2048));
+        assert_eq!(range_start(&range("bytes */4096")), None);
+        assert_eq!(range_start(&HeaderMap::new()), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! Applies [`ProcessLimits`] to spawned git processes. CPU and memory limits
+//! are set with `setrlimit` and cgroups are joined between fork and exec, so
+//! they hold before git runs a single instruction. Time limits are enforced
+//! by the caller through [`deadline`], killing the process when it elapses.
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::process::Command;
+
+use crate::config::ProcessLimits;
+
+/// Default time limit for each git command run in the background by
+/// maintenance, mirror and export syncs, and shared object collection.
+/// Large repositories can take a while; anything longer is stuck.
+pub const BACKGROUND_GIT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Configures `cmd` to run under the CPU, memory, and cgroup limits in
+/// `limits`. Spawning fails if a limit cannot be applied.
+#[cfg(unix)]
+pub fn apply_limits(cmd: &mut Command, limits: &ProcessLimits) {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let cpu = limits.cpu_secs;
+    let memory = limits.memory_bytes;
+    let cgroup = limits
+        .cgroup
+        .as_ref()
+        .and_then(|dir| CString::new(dir.join("cgroup.procs").as_os_str().as_bytes()).ok());
+    if cpu.is_none() && memory.is_none() && cgroup.is_none() {
+        return;
+    }
+
+    // SAFETY: the hook runs in the forked child and only makes
+    // async-signal-safe calls (open, write, close, setrlimit).
+    unsafe {
+        cmd.pre_exec(move || {
+            if let Some(procs) = &cgroup {
+                join_cgroup(procs)?;
+            }
+            if let Some(secs) = cpu {
+                check(libc::setrlimit(libc::RLIMIT_CPU, &rlimit(secs)))?;
+            }
+            if let Some(bytes) = memory {
+                check(libc::setrlimit(libc::RLIMIT_AS, &rlimit(bytes)))?;
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub fn apply_limits(_cmd: &mut Command, _limits: &ProcessLimits) {}
+
+#[cfg(unix)]
+fn rlimit(value: u64) -> libc::rlimit {
+    let value = libc::rlim_t::try_from(value).unwrap_or(libc::RLIM_INFINITY);
+    libc::rlimit {
+        rlim_cur: value,
+        rlim_max: value,
+    }
+}
+
+#[cfg(unix)]
+fn check(result: libc::c_int) -> std::io::Result<()> {
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Moves the calling process into the cgroup owning `procs`. Writing "0"
+/// to `cgroup.procs` names the writer itself.
+#[cfg(unix)]
+unsafe fn join_cgroup(procs: &std::ffi::CStr) -> std::io::Result<()> {
+    // SAFETY: `procs` is a valid C string and the descriptor is closed
+    // before returning.
+    unsafe {
+        let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let written = libc::write(fd, b"0".as_ptr().cast(), 1);
+        let err = std::io::Error::last_os_error();
+        libc::close(fd);
+        if written == 1 { Ok(()) } else { Err(err) }
+    }
+}
+
+/// Awaits `fut`, giving up after `timeout`. Returns `None` when the time
+/// limit elapsed; callers kill the process in that case.
+pub async fn deadline<F: Future>(timeout: Option<Duration>, fut: F) -> Option<F::Output> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, fut).await.ok(),
+        None => Some(fut.await),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cpu_limit_kills_busy_process() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "while :; do :; done"]);
+        apply_limits(
+            &mut cmd,
+            &ProcessLimits {
+                cpu_secs: Some(1),
+                ..ProcessLimits::default()
+            },
+        );
+        let status = deadline(Some(Duration::from_secs(30)), cmd.status())
+            .await
+            .expect("CPU limit should stop the loop")
+            .unwrap();
+        assert!(!status.success());
+    }
+
+    #[tokio::test]
+    async fn test_deadline_elapses() {
+        let slow = tokio::time::sleep(Duration::from_secs(5));
+        assert!(
+            deadline(Some(Duration::from_millis(10)), slow)
+                .await
+                .is_none()
+        );
+        assert_eq!(deadline(None, async { 1 }).await, Some(1));
+    }
+}
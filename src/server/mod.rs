@@ -1,16 +1,46 @@
+#[cfg(feature = "acme")]
+pub mod acme;
 mod admin;
+pub mod authz;
 pub mod content;
+pub mod disk;
 pub mod dto;
+pub mod events;
+pub mod fairness;
 mod git;
 mod lfs;
+mod legal_hold;
+pub mod limits;
+pub mod push_alerts;
+mod pages;
+mod registry;
+pub mod repo_slots;
 pub mod response;
+pub mod signing;
 mod router;
+mod scim;
+pub mod sweep;
+#[cfg(feature = "tls")]
+pub mod tls;
 pub mod user;
 pub mod validation;
 
 pub use admin::admin_router;
-pub use content::content_router;
+pub use content::{ContentLimits, content_router};
+#[cfg(feature = "grpc")]
+pub use content::grpc_router;
 pub use git::git_router;
-pub use lfs::lfs_router;
+pub use git::maintenance::{Maintenance, spawn_maintenance};
+pub use git::export::{ExportSync, spawn_export_sync};
+pub use git::mirror::{MirrorSync, spawn_mirror_sync};
+pub use git::shared_objects::SharedObjects;
+#[cfg(feature = "ssh")]
+pub use git::ssh::{load_or_create_host_key, run_ssh_server};
+pub use lfs::{
+    LfsTransferLimits, LfsTransfers, TransferTotals, lfs_router, spawn_lfs_integrity_check,
+    spawn_lfs_pool_collector,
+};
+pub use pages::{Pages, PagesLimits};
 pub use router::{AppState, create_router};
+pub use user::sync::spawn_change_pruner;
 pub use user::user_router;
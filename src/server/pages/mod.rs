@@ -0,0 +1,345 @@
+//! Static sites published from a repository branch. Each push to a repo
+//! with Pages enabled extracts the configured directory of its branch into
+//! `pages/sites/<repo_id>/`, which is served at `/{namespace}/{repo}/pages/`
+//! and, with a Pages domain, at `{namespace}.{domain}/{repo}/`.
+//!
+//! Builds skip symlinks and submodules and fail when the site is over the
+//! size or file count limit, leaving the previous site published.
+
+mod serve;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use axum::{Router, routing::get};
+use git2::{ObjectType, Repository, Tree};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::server::AppState;
+use crate::server::content::git_ops::{get_tree_at_path, open_repo};
+use crate::server::git::repo_path;
+use crate::storage_roots::StorageRoots;
+use crate::store::Store;
+use crate::types::{ObjectFormat, Repo, RepoPages};
+use crate::units::format_size;
+
+pub(crate) use serve::pages_host;
+
+/// Git file modes of regular and executable files. Symlinks and
+/// submodules are never published.
+const FILE_MODES: [i32; 2] = [0o100644, 0o100755];
+
+/// Size and file count limits for one site.
+#[derive(Debug, Clone, Copy)]
+pub struct PagesLimits {
+    pub max_bytes: u64,
+    pub max_files: u64,
+}
+
+pub struct Pages {
+    data_dir: PathBuf,
+    storage_roots: Arc<StorageRoots>,
+    limits: PagesLimits,
+    domain: Option<String>,
+    /// Repositories with a build running, and whether another was requested
+    /// while it ran.
+    builds: Mutex<HashMap<String, bool>>,
+}
+
+impl Pages {
+    #[must_use]
+    pub fn new(data_dir: PathBuf, limits: PagesLimits) -> Self {
+        Self {
+            storage_roots: Arc::new(StorageRoots::new(data_dir.clone(), HashMap::new())),
+            data_dir,
+            limits,
+            domain: None,
+            builds: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Serves `{namespace}.{domain}/{repo}/` as well as the path on the
+    /// main host.
+    #[must_use]
+    pub fn with_domain(mut self, domain: Option<String>) -> Self {
+        self.domain = domain
+            .map(|d| d.trim().trim_matches('.').to_lowercase())
+            .filter(|d| !d.is_empty());
+        self
+    }
+
+    /// Reads repositories from their namespace's storage root.
+    #[must_use]
+    pub fn with_storage_roots(mut self, roots: Arc<StorageRoots>) -> Self {
+        self.storage_roots = roots;
+        self
+    }
+
+    #[must_use]
+    pub fn domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+
+    #[must_use]
+    pub fn is_building(&self, repo_id: &str) -> bool {
+        lock(&self.builds).contains_key(repo_id)
+    }
+
+    fn site_dir(&self, repo_id: &str) -> PathBuf {
+        self.data_dir.join("pages/sites").join(repo_id)
+    }
+
+    /// Rebuilds the repo's site after a push, if it has Pages enabled.
+    pub fn after_push(self: &Arc<Self>, store: &Arc<dyn Store>, repo: &Repo) {
+        match store.get_repo_pages(&repo.id) {
+            Ok(Some(_)) => self.request_build(Arc::clone(store), &repo.id),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to load Pages settings for {}: {e}", repo.id),
+        }
+    }
+
+    /// Builds the repo's site in the background. A request made while a
+    /// build of the same repo runs starts one more build once it finishes.
+    pub fn request_build(self: &Arc<Self>, store: Arc<dyn Store>, repo_id: &str) {
+        {
+            let mut builds = lock(&self.builds);
+            if let Some(again) = builds.get_mut(repo_id) {
+                *again = true;
+                return;
+            }
+            builds.insert(repo_id.to_string(), false);
+        }
+
+        let pages = Arc::clone(self);
+        let repo_id = repo_id.to_string();
+        tokio::spawn(async move {
+            loop {
+                pages.build(store.as_ref(), &repo_id).await;
+                let mut builds = lock(&pages.builds);
+                if builds.get(&repo_id) == Some(&true) {
+                    builds.insert(repo_id.clone(), false);
+                } else {
+                    builds.remove(&repo_id);
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Removes the published site, e.g. once Pages are turned off.
+    pub fn remove_site(&self, repo_id: &str) {
+        match fs::remove_dir_all(self.site_dir(repo_id)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to remove Pages site for {repo_id}: {e}"),
+        }
+    }
+
+    async fn build(&self, store: &dyn Store, repo_id: &str) {
+        let (repo, settings) = match (store.get_repo_by_id(repo_id), store.get_repo_pages(repo_id))
+        {
+            (Ok(Some(repo)), Ok(Some(settings))) => (repo, settings),
+            (Err(e), _) | (_, Err(e)) => {
+                warn!("Failed to load Pages settings for {repo_id}: {e}");
+                return;
+            }
+            _ => return,
+        };
+
+        let root = self.storage_roots.namespace_root(store, &repo.namespace_id);
+        let git_dir = repo_path(&root, &repo.namespace_id, &repo.name);
+        let staging = self
+            .data_dir
+            .join("pages/tmp")
+            .join(format!("{repo_id}-{}", Uuid::new_v4()));
+        let site_dir = self.site_dir(repo_id);
+        let limits = self.limits;
+        let format = repo.object_format;
+        let result = tokio::task::spawn_blocking(move || {
+            let site = extract_site(&git_dir, format, &settings, &staging, limits);
+            let site = site.and_then(|site| publish(&staging, &site_dir).map(|()| site));
+            if site.is_err() {
+                let _ = fs::remove_dir_all(&staging);
+            }
+            site
+        })
+        .await
+        .unwrap_or_else(|e| Err(format!("Build failed: {e}")));
+
+        let recorded = match &result {
+            Ok(site) => {
+                info!(
+                    "Published Pages for {repo_id} from {} ({} files, {})",
+                    site.commit,
+                    site.files,
+                    format_size(site.bytes as i64)
+                );
+                store.update_repo_pages_build(repo_id, &site.commit, site.bytes as i64)
+            }
+            Err(error) => {
+                warn!("Pages build failed for {repo_id}: {error}");
+                store.update_repo_pages_error(repo_id, error)
+            }
+        };
+        if let Err(e) = recorded {
+            warn!("Failed to record Pages build for {repo_id}: {e}");
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// What a build published.
+struct Site {
+    commit: String,
+    files: u64,
+    bytes: u64,
+}
+
+/// Writes the files of the settings' branch and directory under `staging`.
+fn extract_site(
+    git_dir: &Path,
+    format: ObjectFormat,
+    settings: &RepoPages,
+    staging: &Path,
+    limits: PagesLimits,
+) -> Result<Site, String> {
+    if format != ObjectFormat::Sha1 {
+        return Err(format!(
+            "Pages are not supported for {format} repositories yet"
+        ));
+    }
+    let repo = open_repo(git_dir, format).map_err(|_| "Repository is empty".to_string())?;
+    let commit = repo
+        .find_reference(&format!("refs/heads/{}", settings.branch))
+        .and_then(|r| r.peel_to_commit())
+        .map_err(|_| format!("Branch {} not found", settings.branch))?;
+    let root = commit
+        .tree()
+        .map_err(|e| format!("Failed to read commit tree: {e}"))?;
+    let tree = get_tree_at_path(&repo, &root, &settings.path).map_err(|_| {
+        format!(
+            "Directory {} not found on {}",
+            settings.path, settings.branch
+        )
+    })?;
+
+    fs::create_dir_all(staging).map_err(|e| format!("Failed to create build directory: {e}"))?;
+    let mut site = Site {
+        commit: commit.id().to_string(),
+        files: 0,
+        bytes: 0,
+    };
+    write_tree(&repo, &tree, staging, limits, &mut site)?;
+    Ok(site)
+}
+
+fn write_tree(
+    repo: &Repository,
+    tree: &Tree<'_>,
+    dir: &Path,
+    limits: PagesLimits,
+    site: &mut Site,
+) -> Result<(), String> {
+    for entry in tree {
+        // Git refuses these names, but a pushed tree is not guaranteed to
+        // have been checked.
+        let Some(name) = entry.name().filter(|n| is_safe_name(n)) else {
+            continue;
+        };
+        let path = dir.join(name);
+        match entry.kind() {
+            Some(ObjectType::Tree) => {
+                let subtree = repo
+                    .find_tree(entry.id())
+                    .map_err(|e| format!("Failed to read tree: {e}"))?;
+                fs::create_dir(&path).map_err(|e| format!("Failed to write site: {e}"))?;
+                write_tree(repo, &subtree, &path, limits, site)?;
+            }
+            Some(ObjectType::Blob) if FILE_MODES.contains(&entry.filemode()) => {
+                let blob = repo
+                    .find_blob(entry.id())
+                    .map_err(|e| format!("Failed to read file: {e}"))?;
+                site.files += 1;
+                site.bytes += blob.size() as u64;
+                if site.files > limits.max_files {
+                    return Err(format!("Site has more than {} files", limits.max_files));
+                }
+                if site.bytes > limits.max_bytes {
+                    return Err(format!(
+                        "Site is over the {} limit",
+                        format_size(limits.max_bytes as i64)
+                    ));
+                }
+                fs::write(&path, blob.content())
+                    .map_err(|e| format!("Failed to write site: {e}"))?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn is_safe_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.eq_ignore_ascii_case(".git")
+        && !name.contains(['/', '\\', '\0'])
+}
+
+/// Swaps the staged build in for the published site.
+fn publish(staging: &Path, site_dir: &Path) -> Result<(), String> {
+    let parent = site_dir
+        .parent()
+        .ok_or_else(|| "Invalid site directory".to_string())?;
+    fs::create_dir_all(parent).map_err(|e| format!("Failed to publish site: {e}"))?;
+
+    let old = staging.with_extension("old");
+    let had_site = match fs::rename(site_dir, &old) {
+        Ok(()) => true,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+        Err(e) => return Err(format!("Failed to publish site: {e}")),
+    };
+    if let Err(e) = fs::rename(staging, site_dir) {
+        if had_site {
+            let _ = fs::rename(&old, site_dir);
+        }
+        return Err(format!("Failed to publish site: {e}"));
+    }
+    if had_site {
+        let _ = fs::remove_dir_all(&old);
+    }
+    Ok(())
+}
+
+/// Routes for sites on the main host. Absolute, like the registry's, so
+/// the `pages/` root matches with its trailing slash.
+pub fn pages_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/{namespace}/{repo}/pages", get(serve::redirect_to_site))
+        .route("/{namespace}/{repo}/pages/", get(serve::serve_site_root))
+        .route(
+            "/{namespace}/{repo}/pages/{*path}",
+            get(serve::serve_site_file),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_names() {
+        assert!(is_safe_name("index.html"));
+        assert!(is_safe_name(".well-known"));
+        assert!(!is_safe_name(".."));
+        assert!(!is_safe_name(".GIT"));
+        assert!(!is_safe_name("a\\b"));
+    }
+}
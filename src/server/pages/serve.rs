@@ -0,0 +1,287 @@
+//! Serves published sites. Sites under `/{namespace}/{repo}/pages/` share
+//! the API's origin, so they are sandboxed into an opaque origin of their
+//! own; sites on the Pages domain get a real origin per namespace instead.
+//! Files are served with a content type chosen by extension, never sniffed.
+
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{Path, Request, State},
+    http::{HeaderMap, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use tokio_util::io::ReaderStream;
+
+use super::is_safe_name;
+use crate::server::AppState;
+use crate::server::git::auth::{GitAuthError, check_git_access, extract_git_auth};
+use crate::server::validation::normalize_repo_name;
+use crate::types::Repo;
+
+/// Keeps scripts working while denying a path-served site the API's
+/// origin, so it can't read other sites or the API with the visitor's
+/// credentials.
+const SANDBOX_CSP: &str =
+    "sandbox allow-scripts allow-forms allow-popups allow-modals allow-downloads";
+
+/// GET /{namespace}/{repo}/pages
+pub async fn redirect_to_site() -> Response {
+    Redirect::permanent("./pages/").into_response()
+}
+
+/// GET /{namespace}/{repo}/pages/
+pub async fn serve_site_root(
+    State(state): State<Arc<AppState>>,
+    Path((namespace, repo)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    serve(&state, &headers, &namespace, &repo, "", true).await
+}
+
+/// GET /{namespace}/{repo}/pages/{*path}
+pub async fn serve_site_file(
+    State(state): State<Arc<AppState>>,
+    Path((namespace, repo, path)): Path<(String, String, String)>,
+    headers: HeaderMap,
+) -> Response {
+    serve(&state, &headers, &namespace, &repo, &path, true).await
+}
+
+/// Answers every request addressed to `{namespace}.{domain}` with the
+/// namespace's sites, before any other route is tried.
+pub(crate) async fn pages_host(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(namespace) = pages_namespace(&state, request.headers()) else {
+        return next.run(request).await;
+    };
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        return StatusCode::METHOD_NOT_ALLOWED.into_response();
+    }
+
+    let path = request.uri().path().trim_start_matches('/');
+    let Some((repo, file)) = path.split_once('/') else {
+        if path.is_empty() {
+            return not_found();
+        }
+        // Relative links on the site need the trailing slash.
+        return Redirect::permanent(&format!("./{path}/")).into_response();
+    };
+    let (Ok(repo), Ok(file)) = (urlencoding::decode(repo), urlencoding::decode(file)) else {
+        return not_found();
+    };
+    serve(&state, request.headers(), &namespace, &repo, &file, false).await
+}
+
+/// The namespace a request's host names on the Pages domain, if any.
+fn pages_namespace(state: &AppState, headers: &HeaderMap) -> Option<String> {
+    let domain = state.pages.as_ref()?.domain()?;
+    let host = headers.get(header::HOST)?.to_str().ok()?;
+    let host = host.rsplit_once(':').map_or(host, |(name, _)| name);
+    let namespace = host
+        .to_lowercase()
+        .strip_suffix(domain)?
+        .strip_suffix('.')?
+        .to_string();
+    (!namespace.is_empty() && !namespace.contains('.')).then_some(namespace)
+}
+
+async fn serve(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    namespace: &str,
+    repo: &str,
+    path: &str,
+    sandbox: bool,
+) -> Response {
+    let repo = match authorize(state, headers, namespace, repo).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    let Some(pages) = &state.pages else {
+        return not_found();
+    };
+    match state.store.get_repo_pages(&repo.id) {
+        Ok(Some(settings)) if settings.commit_sha.is_some() => {}
+        Ok(_) => return not_found(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    let site_dir = pages.site_dir(&repo.id);
+    match resolve(&site_dir, path).await {
+        Some(Resolved::File(file)) => file_response(&file, StatusCode::OK, sandbox).await,
+        Some(Resolved::Directory(name)) => {
+            Redirect::permanent(&format!("./{}/", urlencoding::encode(&name))).into_response()
+        }
+        None => {
+            let custom = site_dir.join("404.html");
+            if is_file(&custom).await {
+                file_response(&custom, StatusCode::NOT_FOUND, sandbox).await
+            } else {
+                not_found()
+            }
+        }
+    }
+}
+
+/// Sites are as visible as their repository; private ones take a token as
+/// the basic auth password.
+async fn authorize(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    namespace: &str,
+    repo: &str,
+) -> Result<Repo, Response> {
+    let resolve = async {
+        let repo_name = normalize_repo_name(repo, state.repo_name_policy)
+            .map_err(|_| GitAuthError::RepoNotFound)?;
+        let git_auth = extract_git_auth(headers, state).await?;
+        let namespace = state
+            .store
+            .get_namespace_by_name(namespace)
+            .map_err(|_| GitAuthError::InternalError)?
+            .ok_or(GitAuthError::RepoNotFound)?;
+        let repo = state
+            .store
+            .get_repo(&namespace.id, &repo_name)
+            .map_err(|_| GitAuthError::InternalError)?
+            .ok_or(GitAuthError::RepoNotFound)?;
+        check_git_access(state, &git_auth, &namespace, Some(&repo), false)?;
+        Ok(repo)
+    };
+    resolve.await.map_err(|err: GitAuthError| {
+        let mut response = (err.status_code(), err.message()).into_response();
+        if err.requires_auth_header() {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                "Basic realm=\"cutman\"".parse().unwrap(),
+            );
+        }
+        response
+    })
+}
+
+enum Resolved {
+    File(PathBuf),
+    /// A directory requested without its trailing slash, by its name.
+    Directory(String),
+}
+
+/// Maps a request path onto the site, serving `index.html` for directories.
+async fn resolve(site_dir: &FsPath, path: &str) -> Option<Resolved> {
+    let mut segments: Vec<&str> = path.split('/').collect();
+    let wants_directory = path.is_empty() || path.ends_with('/');
+    if wants_directory {
+        segments.pop();
+    }
+    if !segments.iter().all(|s| is_safe_name(s)) {
+        return None;
+    }
+
+    let target = segments
+        .iter()
+        .fold(site_dir.to_path_buf(), |p, s| p.join(s));
+    let metadata = tokio::fs::symlink_metadata(&target).await.ok()?;
+    if metadata.is_dir() {
+        if !wants_directory {
+            return Some(Resolved::Directory(segments.last()?.to_string()));
+        }
+        let index = target.join("index.html");
+        return is_file(&index).await.then_some(Resolved::File(index));
+    }
+    (metadata.is_file() && !wants_directory).then_some(Resolved::File(target))
+}
+
+async fn is_file(path: &FsPath) -> bool {
+    tokio::fs::symlink_metadata(path)
+        .await
+        .is_ok_and(|m| m.is_file())
+}
+
+async fn file_response(path: &FsPath, status: StatusCode, sandbox: bool) -> Response {
+    let Ok(file) = tokio::fs::File::open(path).await else {
+        return not_found();
+    };
+    let Ok(metadata) = file.metadata().await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type(path))
+        .header(header::CONTENT_LENGTH, metadata.len())
+        .header("X-Content-Type-Options", "nosniff")
+        .header(header::CACHE_CONTROL, "no-cache");
+    if sandbox {
+        response = response.header(header::CONTENT_SECURITY_POLICY, SANDBOX_CSP);
+    }
+    response
+        .body(Body::from_stream(ReaderStream::new(file)))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, "Page not found").into_response()
+}
+
+/// Known extensions only; anything else downloads rather than renders.
+fn content_type(path: &FsPath) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("json" | "map") => "application/json",
+        Some("webmanifest") => "application/manifest+json",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("md") => "text/markdown; charset=utf-8",
+        Some("xml") => "application/xml",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("ico") => "image/x-icon",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("ttf") => "font/ttf",
+        Some("otf") => "font/otf",
+        Some("wasm") => "application/wasm",
+        Some("pdf") => "application/pdf",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("mp3") => "audio/mpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_types() {
+        assert_eq!(
+            content_type(FsPath::new("index.HTML")),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(content_type(FsPath::new("logo.svg")), "image/svg+xml");
+        assert_eq!(
+            content_type(FsPath::new("payload.exe")),
+            "application/octet-stream"
+        );
+        assert_eq!(
+            content_type(FsPath::new("Makefile")),
+            "application/octet-stream"
+        );
+    }
+}
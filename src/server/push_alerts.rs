@@ -0,0 +1,269 @@
+//! Runs the [`crate::push_alerts`] checks once a push has landed, records
+//! what they flag as audit events, and posts each event to the
+//! `[push_alerts]` webhook. Nothing here holds up the push: alerts are
+//! logged and dropped if they can't be recorded or delivered.
+//!
+//! A token's addresses are only tracked for HTTP pushes. SSH keys aren't
+//! tokens, so SSH pushes get the ref update checks alone.
+
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+use axum::http::{HeaderMap, HeaderName};
+use chrono::Utc;
+use tracing::warn;
+
+use crate::config::PushAlertsConfig;
+use crate::hooks::RefUpdate;
+use crate::push_alerts::{PushAlert, PushAlertRules, check_ref_updates};
+use crate::types::{AuditEvent, Namespace, Repo};
+
+use super::AppState;
+use super::events::{audit_event, emit};
+
+pub struct PushAlerts {
+    rules: PushAlertRules,
+    new_address: bool,
+    client_ip_header: Option<HeaderName>,
+    #[cfg(feature = "webhooks")]
+    webhook: Option<webhook::Webhook>,
+}
+
+/// A push that has finished, and who made it from where.
+pub struct Push<'a> {
+    pub namespace: &'a Namespace,
+    pub repo: &'a Repo,
+    pub path: PathBuf,
+    pub principal_id: Option<String>,
+    pub token_id: Option<String>,
+    pub address: Option<String>,
+    pub updates: Vec<RefUpdate>,
+}
+
+impl PushAlerts {
+    pub fn new(config: &PushAlertsConfig) -> anyhow::Result<Self> {
+        let client_ip_header = config
+            .client_ip_header
+            .as_deref()
+            .map(|name| {
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| anyhow::anyhow!("Invalid client_ip_header {name}: {e}"))
+            })
+            .transpose()?;
+        #[cfg(not(feature = "webhooks"))]
+        if let Some(url) = &config.webhook_url {
+            anyhow::bail!(
+                "push_alerts names webhook {url}, but this build of cutman has no webhook support"
+            );
+        }
+        Ok(Self {
+            rules: PushAlertRules {
+                rewritten_commits: config.rewritten_commits,
+                deleted_branches: config.deleted_branches,
+            },
+            new_address: config.new_address,
+            client_ip_header,
+            #[cfg(feature = "webhooks")]
+            webhook: config
+                .webhook_url
+                .as_deref()
+                .map(|url| webhook::Webhook::new(url, config.webhook_secret.clone()))
+                .transpose()?,
+        })
+    }
+
+    /// The pusher's IP address: the last entry of the configured proxy
+    /// header when there is one, otherwise the connection's.
+    #[must_use]
+    pub fn client_address(&self, headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<String> {
+        let forwarded = self.client_ip_header.as_ref().and_then(|name| {
+            let value = headers.get(name)?.to_str().ok()?;
+            value.rsplit(',').next()?.trim().parse::<IpAddr>().ok()
+        });
+        forwarded
+            .or(peer.map(|peer| peer.ip()))
+            .map(|ip| ip.to_canonical().to_string())
+    }
+}
+
+/// Checks `push` and records whatever it flags. Does nothing unless
+/// `[push_alerts]` is configured.
+pub async fn check_push(state: &AppState, push: Push<'_>) {
+    let Some(alerts) = &state.push_alerts else {
+        return;
+    };
+
+    let rules = alerts.rules;
+    let (path, updates) = (push.path.clone(), push.updates.clone());
+    let checked = tokio::task::spawn_blocking(move || {
+        let repo = git2::Repository::open_bare(&path)?;
+        check_ref_updates(&repo, &updates, rules)
+    })
+    .await;
+    let mut found = match checked {
+        Ok(Ok(found)) => found,
+        Ok(Err(e)) => {
+            warn!("Failed to check push to {} for alerts: {e}", push.repo.id);
+            Vec::new()
+        }
+        Err(e) => {
+            warn!("Push alert check for {} panicked: {e}", push.repo.id);
+            Vec::new()
+        }
+    };
+
+    if alerts.new_address
+        && let (Some(token_id), Some(address)) = (&push.token_id, &push.address)
+    {
+        match state.store.record_token_push_address(token_id, address) {
+            Ok(true) => found.push(PushAlert::NewAddress {
+                token_id: token_id.clone(),
+                address: address.clone(),
+            }),
+            Ok(false) => {}
+            Err(e) => warn!("Failed to record push address: {e}"),
+        }
+    }
+
+    for alert in found {
+        let mut event = AuditEvent {
+            seq: 0,
+            kind: alert.kind().to_string(),
+            summary: alert.summary(),
+            namespace_id: Some(push.namespace.id.clone()),
+            repo_id: Some(push.repo.id.clone()),
+            principal_id: push.principal_id.clone(),
+            token_id: push.token_id.clone(),
+            address: push.address.clone(),
+            detail: serde_json::to_value(&alert).unwrap_or_default(),
+            created_at: Utc::now(),
+        };
+        warn!(
+            "Push alert for {}/{}: {}",
+            push.namespace.name, push.repo.name, event.summary
+        );
+        match state.store.create_audit_event(&event) {
+            Ok(seq) => {
+                event.seq = seq;
+                emit(state, audit_event(&event));
+            }
+            Err(e) => warn!("Failed to record push alert: {e}"),
+        }
+
+        #[cfg(feature = "webhooks")]
+        if let Some(webhook) = &alerts.webhook {
+            let payload = serde_json::json!({
+                "event": event,
+                "namespace": push.namespace.name,
+                "repo": push.repo.name,
+            });
+            let webhook = webhook.clone();
+            tokio::spawn(async move { webhook.send(&payload).await });
+        }
+    }
+}
+
+#[cfg(feature = "webhooks")]
+mod webhook {
+    use std::time::Duration;
+
+    use hmac::{Hmac, Mac};
+    use reqwest::Url;
+    use sha2::Sha256;
+    use tracing::warn;
+
+    /// How long a webhook receiver has to answer.
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[derive(Clone)]
+    pub(super) struct Webhook {
+        client: reqwest::Client,
+        url: Url,
+        secret: Option<String>,
+    }
+
+    impl Webhook {
+        pub(super) fn new(url: &str, secret: Option<String>) -> anyhow::Result<Self> {
+            let url =
+                Url::parse(url).map_err(|e| anyhow::anyhow!("Invalid webhook_url {url}: {e}"))?;
+            Ok(Self {
+                client: reqwest::Client::new(),
+                url,
+                secret: secret.filter(|s| !s.is_empty()),
+            })
+        }
+
+        /// Posts `payload` once; a receiver that is down misses the alert,
+        /// which stays in the audit log.
+        pub(super) async fn send(&self, payload: &serde_json::Value) {
+            let body = payload.to_string();
+            let mut request = self
+                .client
+                .post(self.url.clone())
+                .timeout(TIMEOUT)
+                .header(reqwest::header::CONTENT_TYPE, "application/json");
+            if let Some(secret) = &self.secret {
+                request = request.header("X-Cutman-Signature", signature(secret, &body));
+            }
+            match request.body(body).send().await {
+                Ok(response) if response.status().is_success() => {}
+                Ok(response) => warn!("Push alert webhook returned {}", response.status()),
+                Err(e) => warn!("Push alert webhook failed: {e}"),
+            }
+        }
+    }
+
+    /// `sha256=<hex>` of the body's HMAC-SHA256 under `secret`.
+    pub(super) fn signature(secret: &str, body: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(body.as_bytes());
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_address() {
+        let config: PushAlertsConfig = toml::from_str("").unwrap();
+        let peer: SocketAddr = "[::ffff:10.0.0.9]:5000".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "1.2.3.4, 5.6.7.8".parse().unwrap());
+
+        let direct = PushAlerts::new(&config).unwrap();
+        assert_eq!(
+            direct.client_address(&headers, Some(peer)).as_deref(),
+            Some("10.0.0.9")
+        );
+        assert_eq!(direct.client_address(&headers, None), None);
+
+        let proxied = PushAlerts::new(&PushAlertsConfig {
+            client_ip_header: Some("X-Forwarded-For".to_string()),
+            ..config
+        })
+        .unwrap();
+        assert_eq!(
+            proxied.client_address(&headers, Some(peer)).as_deref(),
+            Some("5.6.7.8")
+        );
+        assert_eq!(
+            proxied
+                .client_address(&HeaderMap::new(), Some(peer))
+                .as_deref(),
+            Some("10.0.0.9")
+        );
+    }
+
+    #[cfg(feature = "webhooks")]
+    #[test]
+    fn test_webhook_signature() {
+        // RFC 4231 test case 2.
+        assert_eq!(
+            webhook::signature("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// Error body defined by the distribution spec.
+#[derive(Debug, Serialize)]
+pub struct RegistryErrors {
+    pub errors: Vec<RegistryError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegistryError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadQuery {
+    pub digest: Option<String>,
+}
+
+/// The fields of an image manifest or index that refer to other content.
+#[derive(Debug, Deserialize)]
+pub struct ManifestRefs {
+    #[serde(rename = "mediaType")]
+    pub media_type: Option<String>,
+    pub config: Option<Descriptor>,
+    #[serde(default)]
+    pub layers: Vec<Descriptor>,
+    #[serde(default)]
+    pub manifests: Vec<Descriptor>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Descriptor {
+    pub digest: String,
+}
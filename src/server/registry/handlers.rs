@@ -0,0 +1,636 @@
+use std::path::Path as FsPath;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use tokio_util::io::ReaderStream;
+use tracing::warn;
+use uuid::Uuid;
+
+use super::dto::{ManifestRefs, RegistryError, RegistryErrors, UploadQuery};
+use crate::lfs::{LfsStorage, LfsStorageError, is_valid_oid};
+use crate::server::AppState;
+use crate::server::git::auth::{GitAuthError, check_git_access, extract_git_auth};
+use crate::server::lfs::upload::append_body;
+use crate::server::validation::normalize_repo_name;
+use crate::types::{LfsObject, RegistryManifest, Repo};
+
+const API_VERSION_HEADER: &str = "Docker-Distribution-API-Version";
+const DIGEST_HEADER: &str = "Docker-Content-Digest";
+const UPLOAD_UUID_HEADER: &str = "Docker-Upload-UUID";
+const DEFAULT_MANIFEST_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+
+/// Registries may refuse larger manifests; clients keep them well below.
+const MAX_MANIFEST_BYTES: usize = 4 * 1024 * 1024;
+
+#[derive(serde::Deserialize)]
+pub struct BlobPathParams {
+    pub namespace: String,
+    pub repo: String,
+    pub digest: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ImagePathParams {
+    pub namespace: String,
+    pub repo: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UploadPathParams {
+    pub namespace: String,
+    pub repo: String,
+    pub id: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ManifestPathParams {
+    pub namespace: String,
+    pub repo: String,
+    pub reference: String,
+}
+
+fn registry_response(status: StatusCode) -> axum::http::response::Builder {
+    Response::builder()
+        .status(status)
+        .header(API_VERSION_HEADER, "registry/2.0")
+}
+
+#[must_use]
+fn registry_error(status: StatusCode, code: &'static str, message: &str) -> Response {
+    let body = RegistryErrors {
+        errors: vec![RegistryError {
+            code,
+            message: message.to_string(),
+        }],
+    };
+    let json = serde_json::to_vec(&body).unwrap_or_default();
+    registry_response(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+#[must_use]
+fn registry_auth_error(err: GitAuthError) -> Response {
+    let status = err.status_code();
+    let code = match status {
+        StatusCode::UNAUTHORIZED => "UNAUTHORIZED",
+        StatusCode::NOT_FOUND => "NAME_UNKNOWN",
+        StatusCode::BAD_REQUEST => "NAME_INVALID",
+        StatusCode::INTERNAL_SERVER_ERROR => "UNKNOWN",
+        _ => "DENIED",
+    };
+    let mut response = registry_error(status, code, err.message());
+    if err.requires_auth_header() {
+        response.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            "Basic realm=\"cutman\"".parse().unwrap(),
+        );
+    }
+    response
+}
+
+#[must_use]
+fn storage_error(e: &LfsStorageError) -> Response {
+    warn!("Registry storage error: {e}");
+    registry_error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "UNKNOWN",
+        "Storage error",
+    )
+}
+
+/// Finds the repository an image is named after and checks the caller's
+/// access to it. Pulls need read access and pushes write access.
+async fn authorize(
+    state: &Arc<AppState>,
+    headers: &HeaderMap,
+    namespace: &str,
+    repo: &str,
+    is_write: bool,
+) -> Result<Repo, Response> {
+    let resolve = async {
+        let repo_name = normalize_repo_name(repo, state.repo_name_policy)
+            .map_err(|_| GitAuthError::InvalidRepoName)?;
+        let git_auth = extract_git_auth(headers, state).await?;
+        let namespace = state
+            .store
+            .get_namespace_by_name(namespace)
+            .map_err(|_| GitAuthError::InternalError)?
+            .ok_or(GitAuthError::NamespaceNotFound)?;
+        let repo = state
+            .store
+            .get_repo(&namespace.id, &repo_name)
+            .map_err(|_| GitAuthError::InternalError)?
+            .ok_or(GitAuthError::RepoNotFound)?;
+        check_git_access(state, &git_auth, &namespace, Some(&repo), is_write)?;
+        Ok(repo)
+    };
+    resolve.await.map_err(registry_auth_error)
+}
+
+/// The hex part of a `sha256:` digest, the only algorithm supported.
+fn parse_digest(digest: &str) -> Option<&str> {
+    digest
+        .strip_prefix("sha256:")
+        .filter(|hex| is_valid_oid(hex))
+}
+
+/// Tags as the distribution spec allows them.
+fn is_valid_tag(tag: &str) -> bool {
+    let mut chars = tag.chars();
+    tag.len() <= 128
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+fn image_path(namespace: &str, repo: &str) -> String {
+    format!(
+        "/v2/{}/{}",
+        urlencoding::encode(namespace),
+        urlencoding::encode(repo)
+    )
+}
+
+/// Records a stored object in the repository's LFS object list, which is
+/// what makes it visible to pulls.
+fn record_object(state: &AppState, repo: &Repo, oid: &str, size: i64) {
+    match state.store.get_lfs_object(&repo.id, oid) {
+        Ok(Some(_)) => return,
+        Ok(None) => {}
+        Err(e) => warn!("Failed to look up LFS object record: {e}"),
+    }
+    let lfs_object = LfsObject {
+        repo_id: repo.id.clone(),
+        oid: oid.to_string(),
+        size,
+        created_at: Utc::now(),
+    };
+    if let Err(e) = state.store.create_lfs_object(&lfs_object) {
+        warn!("Failed to create LFS object record: {e}");
+    }
+}
+
+fn object_recorded(state: &AppState, repo: &Repo, oid: &str) -> bool {
+    state
+        .store
+        .get_lfs_object(&repo.id, oid)
+        .ok()
+        .flatten()
+        .is_some()
+}
+
+/// GET /v2/ - Lets clients check the API version and their credentials.
+pub async fn api_version(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    match extract_git_auth(&headers, &state).await {
+        Ok(auth) if auth.principal.is_some() => registry_response(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{}"))
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+        // Clients only send credentials once challenged.
+        Ok(_) => registry_auth_error(GitAuthError::AuthRequired),
+        Err(e) => registry_auth_error(e),
+    }
+}
+
+/// GET (and HEAD) /v2/{namespace}/{repo}/blobs/{digest}
+pub async fn get_blob(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<BlobPathParams>,
+    headers: HeaderMap,
+) -> Response {
+    let repo = match authorize(&state, &headers, &params.namespace, &params.repo, false).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    let Some(oid) = parse_digest(&params.digest) else {
+        return registry_error(StatusCode::BAD_REQUEST, "DIGEST_INVALID", "Invalid digest");
+    };
+    if !object_recorded(&state, &repo, oid) {
+        return registry_error(StatusCode::NOT_FOUND, "BLOB_UNKNOWN", "Blob not found");
+    }
+
+    let (reader, size) = match state.lfs_storage().get(&repo.id, oid).await {
+        Ok(found) => found,
+        Err(LfsStorageError::NotFound) => {
+            return registry_error(StatusCode::NOT_FOUND, "BLOB_UNKNOWN", "Blob not found");
+        }
+        Err(e) => return storage_error(&e),
+    };
+    registry_response(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, size)
+        .header(DIGEST_HEADER, &params.digest)
+        .body(Body::from_stream(ReaderStream::new(reader)))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+/// POST /v2/{namespace}/{repo}/blobs/uploads/ - Starts an upload, or with
+/// `digest` stores the body as a whole blob. Cross-repository mounts are
+/// not supported; clients fall back to uploading.
+pub async fn start_upload(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<ImagePathParams>,
+    Query(query): Query<UploadQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let repo = match authorize(&state, &headers, &params.namespace, &params.repo, true).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+
+    let storage = state.lfs_storage();
+    let id = Uuid::new_v4();
+    let path = storage.upload_path(&repo.id, &id);
+    if let Some(parent) = path.parent()
+        && let Err(e) = tokio::fs::create_dir_all(parent).await
+    {
+        return storage_error(&e.into());
+    }
+    let size = match append_body(&path, body, true, None).await {
+        Ok(size) => size,
+        Err(e) => return storage_error(&e.into()),
+    };
+
+    match query.digest {
+        Some(digest) => {
+            finish(
+                &state,
+                storage.as_ref(),
+                &repo,
+                &params.namespace,
+                &params.repo,
+                &digest,
+                &path,
+            )
+            .await
+        }
+        None => upload_progress(&params.namespace, &params.repo, &id, size),
+    }
+}
+
+/// 202 response telling the client where to send the rest of its upload.
+fn upload_progress(namespace: &str, repo: &str, id: &Uuid, size: u64) -> Response {
+    registry_response(StatusCode::ACCEPTED)
+        .header(
+            header::LOCATION,
+            format!("{}/blobs/uploads/{id}", image_path(namespace, repo)),
+        )
+        .header(header::RANGE, format!("0-{}", size.saturating_sub(1)))
+        .header(UPLOAD_UUID_HEADER, id.to_string())
+        .header(header::CONTENT_LENGTH, 0)
+        .body(Body::empty())
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+/// The staged file of an upload in progress, if `id` names one.
+fn staged_upload(
+    storage: &dyn LfsStorage,
+    repo: &Repo,
+    id: &str,
+) -> Option<(Uuid, std::path::PathBuf)> {
+    let id = Uuid::parse_str(id).ok()?;
+    let path = storage.upload_path(&repo.id, &id);
+    path.exists().then_some((id, path))
+}
+
+fn unknown_upload() -> Response {
+    registry_error(
+        StatusCode::NOT_FOUND,
+        "BLOB_UPLOAD_UNKNOWN",
+        "Upload not found",
+    )
+}
+
+/// PATCH /v2/{namespace}/{repo}/blobs/uploads/{id} - Adds a chunk.
+pub async fn append_upload(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<UploadPathParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let repo = match authorize(&state, &headers, &params.namespace, &params.repo, true).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    let storage = state.lfs_storage();
+    let Some((id, path)) = staged_upload(storage.as_ref(), &repo, &params.id) else {
+        return unknown_upload();
+    };
+
+    // Chunks must arrive in order.
+    let start = headers
+        .get(header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|range| range.split('-').next())
+        .and_then(|start| start.trim().parse::<u64>().ok());
+    if let Some(start) = start {
+        let current = tokio::fs::metadata(&path).await.map_or(0, |m| m.len());
+        if start != current {
+            return registry_error(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                "BLOB_UPLOAD_INVALID",
+                &format!("Chunk starts at {start}, but {current} bytes were uploaded"),
+            );
+        }
+    }
+
+    match append_body(&path, body, false, None).await {
+        Ok(size) => upload_progress(&params.namespace, &params.repo, &id, size),
+        Err(e) => storage_error(&e.into()),
+    }
+}
+
+/// PUT /v2/{namespace}/{repo}/blobs/uploads/{id}?digest= - Adds the final
+/// chunk, if any, and stores the blob once its digest checks out.
+pub async fn finish_upload(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<UploadPathParams>,
+    Query(query): Query<UploadQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let repo = match authorize(&state, &headers, &params.namespace, &params.repo, true).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    let storage = state.lfs_storage();
+    let Some((_, path)) = staged_upload(storage.as_ref(), &repo, &params.id) else {
+        return unknown_upload();
+    };
+    let Some(digest) = query.digest else {
+        return registry_error(
+            StatusCode::BAD_REQUEST,
+            "DIGEST_INVALID",
+            "digest is required",
+        );
+    };
+    if let Err(e) = append_body(&path, body, false, None).await {
+        return storage_error(&e.into());
+    }
+
+    finish(
+        &state,
+        storage.as_ref(),
+        &repo,
+        &params.namespace,
+        &params.repo,
+        &digest,
+        &path,
+    )
+    .await
+}
+
+async fn finish(
+    state: &AppState,
+    storage: &dyn LfsStorage,
+    repo: &Repo,
+    namespace: &str,
+    repo_name: &str,
+    digest: &str,
+    staged: &FsPath,
+) -> Response {
+    let Some(oid) = parse_digest(digest) else {
+        let _ = tokio::fs::remove_file(staged).await;
+        return registry_error(StatusCode::BAD_REQUEST, "DIGEST_INVALID", "Invalid digest");
+    };
+    let size = match storage.put_file(&repo.id, oid, staged).await {
+        Ok(size) => size,
+        Err(LfsStorageError::HashMismatch { .. }) => {
+            return registry_error(
+                StatusCode::BAD_REQUEST,
+                "DIGEST_INVALID",
+                "Uploaded content does not match the digest",
+            );
+        }
+        Err(LfsStorageError::NotFound) => return unknown_upload(),
+        Err(e) => return storage_error(&e),
+    };
+    record_object(state, repo, oid, size);
+
+    registry_response(StatusCode::CREATED)
+        .header(
+            header::LOCATION,
+            format!("{}/blobs/{digest}", image_path(namespace, repo_name)),
+        )
+        .header(DIGEST_HEADER, digest)
+        .header(header::CONTENT_LENGTH, 0)
+        .body(Body::empty())
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+/// PUT /v2/{namespace}/{repo}/manifests/{reference} - Stores a manifest,
+/// tagging it when `reference` is a tag. Everything it refers to must
+/// have been pushed first.
+pub async fn put_manifest(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<ManifestPathParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let repo = match authorize(&state, &headers, &params.namespace, &params.repo, true).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    let expected_oid = parse_digest(&params.reference);
+    if expected_oid.is_none() && !is_valid_tag(&params.reference) {
+        return registry_error(
+            StatusCode::BAD_REQUEST,
+            "MANIFEST_INVALID",
+            "Invalid tag or digest",
+        );
+    }
+
+    let Ok(content) = axum::body::to_bytes(body, MAX_MANIFEST_BYTES).await else {
+        return registry_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "SIZE_INVALID",
+            "Manifest is too large",
+        );
+    };
+    let refs: ManifestRefs = match serde_json::from_slice(&content) {
+        Ok(refs) => refs,
+        Err(_) => {
+            return registry_error(
+                StatusCode::BAD_REQUEST,
+                "MANIFEST_INVALID",
+                "Manifest is not valid JSON",
+            );
+        }
+    };
+    let oid = hex::encode(Sha256::digest(&content));
+    if expected_oid.is_some_and(|expected| expected != oid) {
+        return registry_error(
+            StatusCode::BAD_REQUEST,
+            "DIGEST_INVALID",
+            "Manifest does not match the digest",
+        );
+    }
+
+    for descriptor in refs
+        .config
+        .iter()
+        .chain(&refs.layers)
+        .chain(&refs.manifests)
+    {
+        let known =
+            parse_digest(&descriptor.digest).is_some_and(|o| object_recorded(&state, &repo, o));
+        if !known {
+            return registry_error(
+                StatusCode::BAD_REQUEST,
+                "MANIFEST_BLOB_UNKNOWN",
+                &format!("{} has not been pushed", descriptor.digest),
+            );
+        }
+    }
+
+    let media_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+        .or(refs.media_type)
+        .unwrap_or_else(|| DEFAULT_MANIFEST_TYPE.to_string());
+    let size = content.len() as i64;
+    if let Err(e) = state
+        .lfs_storage()
+        .put(&repo.id, &oid, &content, size)
+        .await
+    {
+        return storage_error(&e);
+    }
+    record_object(&state, &repo, &oid, size);
+
+    let digest = format!("sha256:{oid}");
+    let manifest = RegistryManifest {
+        repo_id: repo.id.clone(),
+        digest: digest.clone(),
+        media_type,
+        size,
+        created_at: Utc::now(),
+    };
+    let stored = state
+        .store
+        .upsert_registry_manifest(&manifest)
+        .and_then(|()| {
+            if expected_oid.is_none() {
+                state
+                    .store
+                    .set_registry_tag(&repo.id, &params.reference, &digest)
+            } else {
+                Ok(())
+            }
+        });
+    if let Err(e) = stored {
+        warn!("Failed to record manifest: {e}");
+        return registry_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "UNKNOWN",
+            "Failed to record manifest",
+        );
+    }
+
+    registry_response(StatusCode::CREATED)
+        .header(
+            header::LOCATION,
+            format!(
+                "{}/manifests/{digest}",
+                image_path(&params.namespace, &params.repo)
+            ),
+        )
+        .header(DIGEST_HEADER, &digest)
+        .header(header::CONTENT_LENGTH, 0)
+        .body(Body::empty())
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+/// GET (and HEAD) /v2/{namespace}/{repo}/manifests/{reference}
+pub async fn get_manifest(
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<ManifestPathParams>,
+    headers: HeaderMap,
+) -> Response {
+    let repo = match authorize(&state, &headers, &params.namespace, &params.repo, false).await {
+        Ok(repo) => repo,
+        Err(response) => return response,
+    };
+    let unknown = || {
+        registry_error(
+            StatusCode::NOT_FOUND,
+            "MANIFEST_UNKNOWN",
+            "Manifest not found",
+        )
+    };
+
+    let digest = if parse_digest(&params.reference).is_some() {
+        params.reference.clone()
+    } else if is_valid_tag(&params.reference) {
+        match state.store.get_registry_tag(&repo.id, &params.reference) {
+            Ok(Some(digest)) => digest,
+            Ok(None) => return unknown(),
+            Err(e) => {
+                warn!("Failed to look up tag: {e}");
+                return registry_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "UNKNOWN",
+                    "Internal server error",
+                );
+            }
+        }
+    } else {
+        return unknown();
+    };
+    let manifest = match state.store.get_registry_manifest(&repo.id, &digest) {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => return unknown(),
+        Err(e) => {
+            warn!("Failed to look up manifest: {e}");
+            return registry_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "UNKNOWN",
+                "Internal server error",
+            );
+        }
+    };
+    let Some(oid) = parse_digest(&manifest.digest) else {
+        return unknown();
+    };
+
+    let (reader, size) = match state.lfs_storage().get(&repo.id, oid).await {
+        Ok(found) => found,
+        Err(LfsStorageError::NotFound) => return unknown(),
+        Err(e) => return storage_error(&e),
+    };
+    registry_response(StatusCode::OK)
+        .header(header::CONTENT_TYPE, manifest.media_type)
+        .header(header::CONTENT_LENGTH, size)
+        .header(DIGEST_HEADER, &manifest.digest)
+        .body(Body::from_stream(ReaderStream::new(reader)))
+        .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references() {
+        let oid = "a".repeat(64);
+        assert_eq!(parse_digest(&format!("sha256:{oid}")), Some(oid.as_str()));
+        assert_eq!(parse_digest(&format!("sha512:{oid}")), None);
+        assert_eq!(parse_digest("sha256:abc"), None);
+
+        assert!(is_valid_tag("latest"));
+        assert!(is_valid_tag("v1.2.3-rc_1"));
+        assert!(!is_valid_tag(".hidden"));
+        assert!(!is_valid_tag("a:b"));
+        assert!(!is_valid_tag(&"a".repeat(129)));
+    }
+}
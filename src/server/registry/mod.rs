@@ -0,0 +1,38 @@
+//! A container registry: the subset of the OCI distribution API that
+//! `docker push` and `docker pull` need. Images are named
+//! `<namespace>/<repo>` after an existing repository and share its
+//! permissions; layers and manifests are stored as the repository's LFS
+//! objects, so they count towards its LFS usage and are removed with it.
+
+mod dto;
+mod handlers;
+
+use std::sync::Arc;
+
+use axum::Router;
+use axum::routing::{get, patch, post};
+
+use crate::server::AppState;
+
+/// Routes are absolute because clients probe `/v2/` with its trailing
+/// slash, which a nested router cannot match.
+pub fn registry_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/v2/", get(handlers::api_version))
+        .route(
+            "/v2/{namespace}/{repo}/blobs/{digest}",
+            get(handlers::get_blob),
+        )
+        .route(
+            "/v2/{namespace}/{repo}/blobs/uploads/",
+            post(handlers::start_upload),
+        )
+        .route(
+            "/v2/{namespace}/{repo}/blobs/uploads/{id}",
+            patch(handlers::append_upload).put(handlers::finish_upload),
+        )
+        .route(
+            "/v2/{namespace}/{repo}/manifests/{reference}",
+            get(handlers::get_manifest).put(handlers::put_manifest),
+        )
+}
@@ -0,0 +1,111 @@
+//! A cap on concurrent clones and fetches of each repository. Fair queuing
+//! shares slots between namespaces, but one namespace's huge repository can
+//! still fill its share; with a per-repository cap, the extra clones of
+//! that repository wait here without taking a fair-queue slot.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+type RepoMap = HashMap<String, Arc<Semaphore>>;
+type Repos = Arc<Mutex<RepoMap>>;
+
+pub struct RepoSlots {
+    per_repo: usize,
+    repos: Repos,
+}
+
+/// A held slot. The slot is released when the permit is dropped.
+pub struct RepoPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    repos: Repos,
+    repo_id: String,
+}
+
+impl RepoSlots {
+    #[must_use]
+    pub fn new(per_repo: usize) -> Self {
+        Self {
+            per_repo: per_repo.max(1),
+            repos: Arc::default(),
+        }
+    }
+
+    /// Waits until fewer than the configured number of operations are
+    /// running against `repo_id`.
+    pub async fn acquire(&self, repo_id: &str) -> RepoPermit {
+        let semaphore = lock(&self.repos)
+            .entry(repo_id.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.per_repo)))
+            .clone();
+        let permit = match semaphore.acquire_owned().await {
+            Ok(permit) => permit,
+            // The semaphore is never closed.
+            Err(_) => unreachable!("repo slots closed"),
+        };
+        RepoPermit {
+            permit: Some(permit),
+            repos: self.repos.clone(),
+            repo_id: repo_id.to_string(),
+        }
+    }
+
+    /// Repositories with operations running or waiting, for diagnostics.
+    #[must_use]
+    pub fn busy_repos(&self) -> usize {
+        lock(&self.repos).len()
+    }
+}
+
+fn lock(repos: &Mutex<RepoMap>) -> std::sync::MutexGuard<'_, RepoMap> {
+    repos.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Drop for RepoPermit {
+    fn drop(&mut self) {
+        drop(self.permit.take());
+        // Forget idle repositories. Waiters hold a reference to the
+        // semaphore, so it is only removed once nobody is using it.
+        let mut repos = lock(&self.repos);
+        if repos
+            .get(&self.repo_id)
+            .is_some_and(|semaphore| Arc::strong_count(semaphore) == 1)
+        {
+            repos.remove(&self.repo_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_slots_are_per_repo() {
+        let slots = Arc::new(RepoSlots::new(1));
+        let held = slots.acquire("monorepo").await;
+
+        // Another repository is not held up.
+        let other = slots.acquire("small").await;
+        drop(other);
+
+        let waiting = {
+            let slots = slots.clone();
+            tokio::spawn(async move { slots.acquire("monorepo").await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        drop(held);
+        let permit = tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("slot handed over")
+            .unwrap();
+        assert_eq!(slots.busy_repos(), 1);
+        drop(permit);
+        assert_eq!(slots.busy_repos(), 0);
+    }
+}
@@ -57,6 +57,9 @@ impl<T: Serialize> PaginatedResponse<T> {
 pub struct ApiError {
     pub status: StatusCode,
     pub message: String,
+    /// Details returned as `data` alongside the message, for clients that
+    /// act on what exactly was refused.
+    pub data: Option<serde_json::Value>,
 }
 
 impl ApiError {
@@ -65,6 +68,7 @@ impl ApiError {
         Self {
             status: StatusCode::BAD_REQUEST,
             message: message.into(),
+            data: None,
         }
     }
 
@@ -73,6 +77,7 @@ impl ApiError {
         Self {
             status: StatusCode::NOT_FOUND,
             message: message.into(),
+            data: None,
         }
     }
 
@@ -81,6 +86,7 @@ impl ApiError {
         Self {
             status: StatusCode::CONFLICT,
             message: message.into(),
+            data: None,
         }
     }
 
@@ -89,6 +95,7 @@ impl ApiError {
         Self {
             status: StatusCode::FORBIDDEN,
             message: message.into(),
+            data: None,
         }
     }
 
@@ -97,6 +104,7 @@ impl ApiError {
         Self {
             status: StatusCode::INTERNAL_SERVER_ERROR,
             message: message.into(),
+            data: None,
         }
     }
 
@@ -105,6 +113,7 @@ impl ApiError {
         Self {
             status: StatusCode::UNAUTHORIZED,
             message: message.into(),
+            data: None,
         }
     }
 
@@ -113,13 +122,38 @@ impl ApiError {
         Self {
             status: StatusCode::PAYLOAD_TOO_LARGE,
             message: message.into(),
+            data: None,
+        }
+    }
+
+    #[must_use]
+    pub fn insufficient_storage(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::INSUFFICIENT_STORAGE,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    #[must_use]
+    pub fn not_implemented(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::NOT_IMPLEMENTED,
+            message: message.into(),
+            data: None,
         }
     }
+
+    #[must_use]
+    pub fn with_data(mut self, data: impl Serialize) -> Self {
+        self.data = serde_json::to_value(data).ok();
+        self
+    }
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let body = json!({ "data": null, "error": self.message });
+        let body = json!({ "data": self.data, "error": self.message });
         (self.status, Json(body)).into_response()
     }
 }
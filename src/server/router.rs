@@ -1,53 +1,575 @@
+use std::collections::HashMap;
+use std::net::Ipv6Addr;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-use axum::extract::Request;
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
 use axum::middleware::{self, Next};
-use axum::response::Response;
-use axum::{Router, routing::get};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router, routing::get};
+use tokio_util::task::TaskTracker;
+use tracing::Instrument;
 
 use super::admin::admin_router;
-use super::content::content_router;
-use super::git::git_router;
+use super::authz::{Authorizer, GrantAuthorizer};
+use super::content::{ContentLimits, check_consistency, content_router, webdav_router};
+use super::disk::DiskWatchdog;
+use super::events::Events;
+use super::fairness::{FairPermit, FairQueue};
+use super::git::export::ExportSync;
+use super::git::maintenance::Maintenance;
+use super::git::mirror::MirrorSync;
+use super::git::shared_objects::SharedObjects;
+use super::git::{git_router, repo_path};
+use super::lfs::{LfsTransferLimits, LfsTransfers};
+use super::pages::{Pages, pages_host, pages_router};
+use super::push_alerts::PushAlerts;
+use super::registry::registry_router;
+use super::repo_slots::{RepoPermit, RepoSlots};
+use super::response::ApiError;
+use super::scim::scim_router;
+use super::sweep::TempSweeper;
 use super::user::user_router;
+use crate::auth::{TOKEN_NOTICE_HEADER, has_legacy_token, scope_request};
+use crate::config::{Feature, FeatureFlags, LegacyTokens, RepoNamePolicy, SubprocessLimits};
+use crate::hooks::HookSettings;
+use crate::lfs::{FsStorage, LfsStorage};
+use crate::push_limits::PushLimits;
+use crate::storage_roots::StorageRoots;
 use crate::store::Store;
+use crate::telemetry;
+use crate::types::TrafficClass;
 
 pub struct AppState {
     pub store: Arc<dyn Store>,
+    /// Decides what principals may do. Grants in the store decide unless
+    /// an external policy service is configured.
+    pub authorizer: Arc<dyn Authorizer>,
     pub data_dir: PathBuf,
+    /// Where each namespace's repositories and LFS objects live. Everything
+    /// is in the data directory when no roots are configured.
+    pub storage_roots: Arc<StorageRoots>,
     /// Public base URL for external access. Used for LFS action URLs.
     pub public_base_url: Option<String>,
+    /// Whether the server terminates TLS itself, so links derived from
+    /// request headers use https.
+    pub tls: bool,
+    /// When set, anonymous access is rejected even for public repositories.
+    /// Starts from the config and can be flipped by admins at runtime.
+    pub private_mode: AtomicBool,
+    pub repo_name_policy: RepoNamePolicy,
+    /// Port of the built-in SSH git server, when it is running.
+    pub ssh_port: Option<u16>,
+    /// How long an SSH connection may sit idle before it is closed.
+    pub ssh_idle_timeout: Duration,
+    /// Receive hooks to run around pushes. They enforce quotas and push
+    /// policies, so pushes are refused when unset.
+    pub hooks: Option<HookSettings>,
+    /// Whether the data directory needs network filesystem handling.
+    pub network_storage: bool,
+    /// Where LFS objects and registry blobs are stored. They are kept under
+    /// `lfs/` in the data directory when unset.
+    pub lfs_backend: Option<Arc<dyn LfsStorage>>,
+    /// Throughput limits and usage totals for LFS transfers.
+    pub lfs_transfers: Arc<LfsTransfers>,
+    /// Background repository maintenance, when enabled.
+    pub maintenance: Option<Arc<Maintenance>>,
+    /// Pull mirror sync scheduler. Mirrors are never fetched when unset.
+    pub mirrors: Option<Arc<MirrorSync>>,
+    /// Scheduled export sync scheduler. Exports are never pushed when unset.
+    pub exports: Option<Arc<ExportSync>>,
+    /// Free-space watchdog for the data directory. Writes are never
+    /// refused for lack of space when unset.
+    pub disk: Option<Arc<DiskWatchdog>>,
+    /// Cleanup of temp files left by crashes, when enabled.
+    pub sweeper: Option<Arc<TempSweeper>>,
+    /// CPU, memory, and time limits for spawned git processes.
+    pub limits: SubprocessLimits,
+    /// Time limit for ref advertisements when `limits` sets none for the
+    /// route class. Pack transfers are unlimited by default since large
+    /// clones legitimately take a long time.
+    pub git_command_timeout: Option<Duration>,
+    /// Time limit for each `gpg`, `gpgv`, or `ssh-keygen` run when checking
+    /// commit signatures or inspecting uploaded keys.
+    pub signing_timeout: Option<Duration>,
+    /// Shares slots for clones, archives, and search fairly between
+    /// namespaces. Those operations are not queued when unset.
+    pub fair_queue: Option<FairQueue>,
+    /// Caps concurrent clones and fetches of each repository. Unlimited
+    /// when unset.
+    pub repo_slots: Option<RepoSlots>,
+    /// Deepest shallow fetch accepted. Unlimited when unset.
+    pub max_clone_depth: Option<u32>,
+    /// Pack and file size limits for pushes and content API commits.
+    pub push_limits: PushLimits,
+    /// Upload and raw download size limits for the content API.
+    pub content_limits: ContentLimits,
+    /// Template for the link shown after a push creates a branch. The
+    /// compare API is linked when unset; an empty template disables it.
+    pub pull_request_url: Option<String>,
+    /// Shown to git clients and returned by the capabilities endpoint.
+    pub banner: Option<String>,
+    /// Whether the container registry is served at `/v2/`.
+    pub registry: bool,
+    /// Static site publishing. Sites are neither built nor served when
+    /// unset.
+    pub pages: Option<Arc<Pages>>,
+    /// Whether default-branch trees are served read-only over WebDAV at
+    /// `/dav/`.
+    pub webdav: bool,
+    /// Capability groups the operator has switched off.
+    pub features: FeatureFlags,
+    /// Whether legacy-format tokens authenticate, and the notice shown to
+    /// clients that use one.
+    pub legacy_tokens: LegacyTokens,
+    /// Checks that record unusual pushes as audit events. Pushes aren't
+    /// checked when unset.
+    pub push_alerts: Option<Arc<PushAlerts>>,
+    /// Publishes server events to a message bus. Nothing is published
+    /// when unset.
+    pub events: Option<Arc<Events>>,
+    /// Locks on each namespace's shared object store.
+    pub shared_objects: Arc<SharedObjects>,
+    /// Git operations still running for clients, which shutdown waits for.
+    pub git_tasks: TaskTracker,
 }
 
 impl AppState {
     #[must_use]
-    pub fn new(
-        store: Arc<dyn Store>,
-        data_dir: PathBuf,
-        public_base_url: Option<String>,
-    ) -> Self {
+    pub fn new(store: Arc<dyn Store>, data_dir: PathBuf, public_base_url: Option<String>) -> Self {
         Self {
             store,
+            authorizer: Arc::new(GrantAuthorizer),
+            storage_roots: Arc::new(StorageRoots::new(data_dir.clone(), HashMap::new())),
             data_dir,
             public_base_url,
+            tls: false,
+            private_mode: AtomicBool::new(false),
+            repo_name_policy: RepoNamePolicy::default(),
+            ssh_port: None,
+            ssh_idle_timeout: Duration::from_secs(10 * 60),
+            hooks: None,
+            network_storage: false,
+            lfs_backend: None,
+            lfs_transfers: Arc::new(LfsTransfers::default()),
+            maintenance: None,
+            mirrors: None,
+            exports: None,
+            disk: None,
+            sweeper: None,
+            limits: SubprocessLimits::default(),
+            git_command_timeout: Some(Duration::from_secs(5 * 60)),
+            signing_timeout: Some(Duration::from_secs(10)),
+            fair_queue: None,
+            repo_slots: None,
+            max_clone_depth: None,
+            push_limits: PushLimits::default(),
+            content_limits: ContentLimits::default(),
+            pull_request_url: None,
+            banner: None,
+            registry: false,
+            pages: None,
+            webdav: false,
+            features: FeatureFlags::default(),
+            legacy_tokens: LegacyTokens::default(),
+            push_alerts: None,
+            events: None,
+            shared_objects: Arc::new(SharedObjects::default()),
+            git_tasks: TaskTracker::new(),
         }
     }
+
+    #[must_use]
+    pub fn with_authorizer(mut self, authorizer: impl Authorizer + 'static) -> Self {
+        self.authorizer = Arc::new(authorizer);
+        self
+    }
+
+    #[must_use]
+    pub fn with_tls(mut self, tls: bool) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    #[must_use]
+    pub fn with_private_mode(mut self, private_mode: bool) -> Self {
+        self.private_mode = AtomicBool::new(private_mode);
+        self
+    }
+
+    #[must_use]
+    pub fn with_repo_name_policy(mut self, policy: RepoNamePolicy) -> Self {
+        self.repo_name_policy = policy;
+        self
+    }
+
+    #[must_use]
+    pub fn with_ssh_port(mut self, ssh_port: Option<u16>) -> Self {
+        self.ssh_port = ssh_port;
+        self
+    }
+
+    #[must_use]
+    pub fn with_ssh_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.ssh_idle_timeout = timeout;
+        self
+    }
+
+    #[must_use]
+    pub fn with_hooks(mut self, hooks: HookSettings) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    #[must_use]
+    pub fn with_network_storage(mut self, network_storage: bool) -> Self {
+        self.network_storage = network_storage;
+        self
+    }
+
+    #[must_use]
+    pub fn with_lfs_storage(mut self, storage: impl LfsStorage + 'static) -> Self {
+        self.lfs_backend = Some(Arc::new(storage));
+        self
+    }
+
+    #[must_use]
+    pub fn with_lfs_transfer_limits(mut self, limits: LfsTransferLimits) -> Self {
+        self.lfs_transfers = Arc::new(LfsTransfers::new(limits));
+        self
+    }
+
+    #[must_use]
+    pub fn with_maintenance(mut self, maintenance: Maintenance) -> Self {
+        self.maintenance = Some(Arc::new(maintenance));
+        self
+    }
+
+    #[must_use]
+    pub fn with_mirror_sync(mut self, mirrors: MirrorSync) -> Self {
+        self.mirrors = Some(Arc::new(mirrors));
+        self
+    }
+
+    #[must_use]
+    pub fn with_shared_objects(mut self, shared_objects: SharedObjects) -> Self {
+        self.shared_objects = Arc::new(shared_objects);
+        self
+    }
+
+    #[must_use]
+    pub fn with_export_sync(mut self, exports: ExportSync) -> Self {
+        self.exports = Some(Arc::new(exports));
+        self
+    }
+
+    #[must_use]
+    pub fn with_disk_watchdog(mut self, watchdog: DiskWatchdog) -> Self {
+        self.disk = Some(Arc::new(watchdog));
+        self
+    }
+
+    #[must_use]
+    pub fn with_subprocess_limits(mut self, limits: SubprocessLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    #[must_use]
+    pub fn with_git_command_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.git_command_timeout = timeout;
+        self
+    }
+
+    #[must_use]
+    pub fn with_signing_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.signing_timeout = timeout;
+        self
+    }
+
+    #[must_use]
+    pub fn with_fair_queue(mut self, queue: FairQueue) -> Self {
+        self.fair_queue = Some(queue);
+        self
+    }
+
+    #[must_use]
+    pub fn with_repo_slots(mut self, slots: RepoSlots) -> Self {
+        self.repo_slots = Some(slots);
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_clone_depth(mut self, depth: Option<u32>) -> Self {
+        self.max_clone_depth = depth;
+        self
+    }
+
+    #[must_use]
+    pub fn with_pull_request_url(mut self, template: Option<String>) -> Self {
+        self.pull_request_url = template;
+        self
+    }
+
+    #[must_use]
+    pub fn with_push_limits(mut self, limits: PushLimits) -> Self {
+        self.push_limits = limits;
+        self
+    }
+
+    #[must_use]
+    pub fn with_content_limits(mut self, limits: ContentLimits) -> Self {
+        self.content_limits = limits;
+        self
+    }
+
+    #[must_use]
+    pub fn with_banner(mut self, banner: Option<String>) -> Self {
+        self.banner = banner.filter(|b| !b.trim().is_empty());
+        self
+    }
+
+    #[must_use]
+    pub fn with_registry(mut self, registry: bool) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    #[must_use]
+    pub fn with_pages(mut self, pages: Pages) -> Self {
+        self.pages = Some(Arc::new(pages));
+        self
+    }
+
+    #[must_use]
+    pub fn with_webdav(mut self, webdav: bool) -> Self {
+        self.webdav = webdav;
+        self
+    }
+
+    #[must_use]
+    pub fn with_features(mut self, features: FeatureFlags) -> Self {
+        self.features = features;
+        self
+    }
+
+    #[must_use]
+    pub fn with_legacy_tokens(mut self, legacy_tokens: LegacyTokens) -> Self {
+        self.legacy_tokens = legacy_tokens;
+        self
+    }
+
+    #[must_use]
+    pub fn with_push_alerts(mut self, alerts: PushAlerts) -> Self {
+        self.push_alerts = Some(Arc::new(alerts));
+        self
+    }
+
+    #[must_use]
+    pub fn with_events(mut self, events: Events) -> Self {
+        self.events = Some(Arc::new(events));
+        self
+    }
+
+    #[must_use]
+    pub fn with_storage_roots(mut self, roots: HashMap<String, PathBuf>) -> Self {
+        self.storage_roots = Arc::new(StorageRoots::new(self.data_dir.clone(), roots));
+        self
+    }
+
+    #[must_use]
+    pub fn with_temp_sweeper(mut self, sweeper: TempSweeper) -> Self {
+        self.sweeper = Some(Arc::new(sweeper));
+        self
+    }
+
+    /// The configured LFS storage, or the data directory's.
+    #[must_use]
+    pub fn lfs_storage(&self) -> Arc<dyn LfsStorage> {
+        match &self.lfs_backend {
+            Some(storage) => Arc::clone(storage),
+            None => Arc::new(
+                FsStorage::new(&self.data_dir)
+                    .with_network_mode(self.network_storage)
+                    .with_storage_roots(Arc::clone(&self.storage_roots), Arc::clone(&self.store)),
+            ),
+        }
+    }
+
+    /// The on-disk path of a repository, in its namespace's storage root.
+    #[must_use]
+    pub fn repo_path(&self, namespace_id: &str, repo_name: &str) -> PathBuf {
+        let root = self
+            .storage_roots
+            .namespace_root(self.store.as_ref(), namespace_id);
+        repo_path(&root, namespace_id, repo_name)
+    }
+
+    /// External base URL for links handed to clients. Uses the configured
+    /// public_base_url if available, otherwise derives it from request headers.
+    #[must_use]
+    pub fn base_url(&self, headers: &HeaderMap) -> String {
+        if let Some(ref base_url) = self.public_base_url {
+            return base_url.trim_end_matches('/').to_string();
+        }
+
+        let host = headers
+            .get(header::HOST)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("localhost");
+
+        let scheme = headers
+            .get("x-forwarded-proto")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or(if self.tls { "https" } else { "http" });
+
+        // Some proxies forward a bare IPv6 literal, which URLs must bracket.
+        if host.parse::<Ipv6Addr>().is_ok() {
+            return format!("{scheme}://[{host}]");
+        }
+
+        format!("{scheme}://{host}")
+    }
+
+    /// Waits for a fair-queue slot before an expensive operation on a
+    /// repository in `namespace_id`. Returns at once when fair queuing is off.
+    pub async fn fair_slot(&self, namespace_id: &str, class: TrafficClass) -> Option<FairPermit> {
+        let queue = self.fair_queue.as_ref()?;
+        let weight = if queue.has_weights() {
+            match self.store.get_namespace(namespace_id) {
+                Ok(Some(namespace)) => queue.weight(&namespace.name),
+                _ => 1,
+            }
+        } else {
+            1
+        };
+        Some(queue.acquire(namespace_id, weight, class).await)
+    }
+
+    /// Waits for a clone slot on `repo_id`, then for a fair-queue slot.
+    /// Returns at once for whichever limit is off.
+    pub async fn clone_slot(
+        &self,
+        repo_id: &str,
+        namespace_id: &str,
+        class: TrafficClass,
+    ) -> (Option<RepoPermit>, Option<FairPermit>) {
+        let repo = match &self.repo_slots {
+            Some(slots) => Some(slots.acquire(repo_id).await),
+            None => None,
+        };
+        (repo, self.fair_slot(namespace_id, class).await)
+    }
+
+    /// Whether there is enough free space to accept pushes and uploads.
+    #[must_use]
+    pub fn allows_writes(&self) -> bool {
+        self.disk.as_ref().is_none_or(|d| d.allows_writes())
+    }
+
+    /// Whether unauthenticated callers may read public repositories.
+    #[must_use]
+    pub fn allows_anonymous_read(&self) -> bool {
+        !self.private_mode.load(Ordering::Relaxed)
+    }
+
+    /// Switches private mode until the next restart, returning the
+    /// previous setting.
+    pub fn set_private_mode(&self, private_mode: bool) -> bool {
+        self.private_mode.swap(private_mode, Ordering::Relaxed)
+    }
+
+    /// Refuses the request when the operator has switched `feature` off.
+    pub fn require_feature(&self, feature: Feature) -> Result<(), ApiError> {
+        if self.features.enabled(feature) {
+            Ok(())
+        } else {
+            Err(ApiError::forbidden(format!(
+                "{} disabled on this server",
+                feature.description()
+            )))
+        }
+    }
+}
+
+/// Middleware refusing every request to a router whose capability group is
+/// switched off.
+async fn feature_gate(
+    State((state, feature)): State<(Arc<AppState>, Feature)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match state.require_feature(feature) {
+        Ok(()) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Readiness for load balancers: the database answers queries. Disk state
+/// is reported but does not fail readiness, since reads keep working when
+/// writes are refused for lack of space.
+async fn readyz(State(state): State<Arc<AppState>>) -> Response {
+    let database_ok = state.store.has_admin_token().is_ok();
+    let status = if database_ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = serde_json::json!({
+        "status": if database_ok { "ready" } else { "unavailable" },
+        "checks": {
+            "database": if database_ok { "ok" } else { "error" },
+            "disk": state.disk.as_ref().map(|d| d.status()),
+        },
+    });
+    (status, Json(body)).into_response()
 }
 
 async fn health() -> &'static str {
     "OK"
 }
 
+/// Passes the `[legacy_tokens]` notice to clients that authenticated with a
+/// legacy-format token.
+async fn flag_legacy_token(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let auth_header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok());
+    let legacy = has_legacy_token(auth_header);
+
+    let mut response = next.run(request).await;
+    if legacy && let Ok(notice) = HeaderValue::from_str(state.legacy_tokens.notice()) {
+        response.headers_mut().insert(TOKEN_NOTICE_HEADER, notice);
+    }
+    response
+}
+
 async fn log_request(request: Request, next: Next) -> Response {
     let method = request.method().clone();
     let uri = request.uri().clone();
     let start = Instant::now();
 
-    let response = next.run(request).await;
+    let span = tracing::info_span!(
+        "request",
+        otel.kind = "server",
+        http.request.method = %method,
+        url.path = uri.path(),
+        http.response.status_code = tracing::field::Empty,
+    );
+    telemetry::set_parent(&span, request.headers());
+    let response = next.run(request).instrument(span.clone()).await;
 
     let latency = start.elapsed();
     let status = response.status();
+    span.record("http.response.status_code", status.as_u16());
 
     tracing::info!(
         "{} {} {} {}ms",
@@ -61,12 +583,65 @@ async fn log_request(request: Request, next: Next) -> Response {
 }
 
 pub fn create_router(state: Arc<AppState>) -> Router {
-    Router::new()
+    let mut router = Router::new()
         .route("/health", get(health))
-        .nest("/api/v1/admin", admin_router())
+        .route("/readyz", get(readyz))
+        .nest(
+            "/api/v1/admin",
+            admin_router().route_layer(middleware::from_fn_with_state(
+                (state.clone(), Feature::AdminApi),
+                feature_gate,
+            )),
+        )
+        .nest("/scim/v2", scim_router())
         .nest("/api/v1", user_router())
-        .nest("/api/v1", content_router())
-        .nest("/git", git_router())
+        .nest(
+            "/api/v1",
+            content_router().route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                check_consistency,
+            )),
+        )
+        .nest("/git", git_router());
+    if state.registry {
+        router = router.merge(registry_router());
+    }
+    if state.webdav {
+        router = router.merge(webdav_router());
+    }
+    if let Some(pages) = &state.pages {
+        router = router.merge(pages_router());
+        if pages.domain().is_some() {
+            router = router.layer(middleware::from_fn_with_state(state.clone(), pages_host));
+        }
+    }
+    router
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            flag_legacy_token,
+        ))
+        .layer(middleware::from_fn(scope_request))
         .layer(middleware::from_fn(log_request))
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::SqliteStore;
+
+    #[test]
+    fn test_base_url_scheme() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        let state = AppState::new(Arc::new(store), temp.path().to_path_buf(), None);
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("git.example.com"));
+        assert_eq!(state.base_url(&headers), "http://git.example.com");
+
+        let state = state.with_tls(true);
+        assert_eq!(state.base_url(&headers), "https://git.example.com");
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("http"));
+        assert_eq!(state.base_url(&headers), "http://git.example.com");
+    }
+}
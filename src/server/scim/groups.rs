@@ -0,0 +1,466 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::dto::{
+    GROUP_SCHEMA, GroupRequest, GroupResource, ListQuery, Member, Meta, PatchRequest,
+};
+use super::{
+    PatchOp, ScimError, StoreResultExt, list_response, location, page, parse_body, parse_filter,
+    patch_changes, scim_response,
+};
+use crate::auth::RequireAdmin;
+use crate::error::Error;
+use crate::server::AppState;
+use crate::server::validation::validate_namespace_name;
+use crate::types::{Namespace, NamespaceGrant, Permission, ScimGroup};
+
+/// What joining a group grants: seeing the namespace and pushing to its
+/// repositories. Members who need more get it through the admin API, and
+/// keep it for as long as they stay in the group.
+const MEMBER_GRANT: Permission = Permission::NAMESPACE_READ.union(Permission::REPO_WRITE);
+
+/// A change to a group's members, checked before any is applied.
+enum MemberChange {
+    Add(Vec<String>),
+    Remove(Vec<String>),
+    Replace(Vec<String>),
+    RemoveAll,
+}
+
+fn group_namespace(state: &AppState, group: &ScimGroup) -> Result<Namespace, ScimError> {
+    state
+        .store
+        .get_namespace(&group.namespace_id)
+        .scim_err("Failed to get namespace")?
+        .ok_or_else(|| ScimError::not_found(format!("Group {} not found", group.namespace_id)))
+}
+
+fn members(state: &AppState, namespace_id: &str) -> Result<Vec<String>, ScimError> {
+    Ok(state
+        .store
+        .list_namespace_grants_for_namespace(namespace_id)
+        .scim_err("Failed to list members")?
+        .into_iter()
+        .map(|g| g.principal_id)
+        .collect())
+}
+
+fn group_resource(state: &AppState, group: &ScimGroup) -> Result<GroupResource, ScimError> {
+    let namespace = group_namespace(state, group)?;
+    Ok(GroupResource {
+        schemas: [GROUP_SCHEMA],
+        id: namespace.id.clone(),
+        external_id: group.external_id.clone(),
+        display_name: namespace.name,
+        members: members(state, &namespace.id)?
+            .into_iter()
+            .map(|value| Member { value })
+            .collect(),
+        meta: Meta {
+            resource_type: "Group",
+            created: namespace.created_at,
+            last_modified: group.created_at,
+            location: location(state, "Groups", &namespace.id),
+        },
+    })
+}
+
+fn find_group(state: &AppState, id: &str) -> Result<(ScimGroup, Namespace), ScimError> {
+    let group = state
+        .store
+        .get_scim_group(id)
+        .scim_err("Failed to get group")?
+        .ok_or_else(|| ScimError::not_found(format!("Group {id} not found")))?;
+    let namespace = group_namespace(state, &group)?;
+    Ok((group, namespace))
+}
+
+fn group_by_external_id(
+    state: &AppState,
+    external_id: &str,
+) -> Result<Option<ScimGroup>, ScimError> {
+    Ok(state
+        .store
+        .list_scim_groups()
+        .scim_err("Failed to list groups")?
+        .into_iter()
+        .find(|g| g.external_id.as_deref() == Some(external_id)))
+}
+
+fn save_group(state: &AppState, group: &ScimGroup) -> Result<(), ScimError> {
+    match state.store.upsert_scim_group(group) {
+        Ok(()) => Ok(()),
+        Err(Error::AlreadyExists) => Err(ScimError::conflict(
+            "Another group already has this externalId",
+        )),
+        Err(_) => Err(ScimError::internal("Failed to save group")),
+    }
+}
+
+/// Namespace names appear in repository URLs, so groups can't be renamed
+/// through SCIM.
+fn check_display_name(namespace: &Namespace, display_name: &str) -> Result<(), ScimError> {
+    if namespace.name != display_name {
+        return Err(ScimError::bad_request(
+            "mutability",
+            format!("displayName can't be changed from `{}`", namespace.name),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the member IDs from a PATCH value: an array of members, or one.
+fn member_ids(value: Value) -> Result<Vec<String>, ScimError> {
+    let members: Vec<Member> = match value {
+        Value::Array(_) => serde_json::from_value(value),
+        value => serde_json::from_value(value).map(|m| vec![m]),
+    }
+    .map_err(|_| {
+        ScimError::bad_request("invalidValue", "members must be a list of {\"value\": id}")
+    })?;
+    Ok(members.into_iter().map(|m| m.value).collect())
+}
+
+/// Checks that every member is an existing user.
+fn check_members(state: &AppState, ids: &[String]) -> Result<(), ScimError> {
+    for id in ids {
+        if state
+            .store
+            .get_principal(id)
+            .scim_err("Failed to get user")?
+            .is_none()
+        {
+            return Err(ScimError::bad_request(
+                "invalidValue",
+                format!("Member {id} is not a user"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn add_member(state: &AppState, namespace_id: &str, principal_id: &str) -> Result<(), ScimError> {
+    // An existing grant, perhaps a stronger one, is left as it is.
+    if state
+        .store
+        .get_namespace_grant(principal_id, namespace_id)
+        .scim_err("Failed to get grant")?
+        .is_some()
+    {
+        return Ok(());
+    }
+    let now = Utc::now();
+    state
+        .store
+        .upsert_namespace_grant(&NamespaceGrant {
+            principal_id: principal_id.to_string(),
+            namespace_id: namespace_id.to_string(),
+            allow_bits: MEMBER_GRANT,
+            deny_bits: Permission::default(),
+            created_at: now,
+            updated_at: now,
+        })
+        .scim_err("Failed to add member")
+}
+
+fn remove_member(
+    state: &AppState,
+    namespace_id: &str,
+    principal_id: &str,
+) -> Result<(), ScimError> {
+    state
+        .store
+        .delete_namespace_grant(principal_id, namespace_id)
+        .scim_err("Failed to remove member")?;
+    Ok(())
+}
+
+fn apply_member_change(
+    state: &AppState,
+    namespace_id: &str,
+    change: MemberChange,
+) -> Result<(), ScimError> {
+    match change {
+        MemberChange::Add(ids) => {
+            for id in &ids {
+                add_member(state, namespace_id, id)?;
+            }
+        }
+        MemberChange::Remove(ids) => {
+            for id in &ids {
+                remove_member(state, namespace_id, id)?;
+            }
+        }
+        MemberChange::Replace(ids) => {
+            let keep: HashSet<&String> = ids.iter().collect();
+            for id in members(state, namespace_id)? {
+                if !keep.contains(&id) {
+                    remove_member(state, namespace_id, &id)?;
+                }
+            }
+            for id in &ids {
+                add_member(state, namespace_id, id)?;
+            }
+        }
+        MemberChange::RemoveAll => {
+            for id in members(state, namespace_id)? {
+                remove_member(state, namespace_id, &id)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn list_groups(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListQuery>,
+) -> Result<Response, ScimError> {
+    let groups = match query.filter.as_deref().map(parse_filter).transpose()? {
+        Some((attribute, value)) if attribute.eq_ignore_ascii_case("displayName") => {
+            match state
+                .store
+                .get_namespace_by_name(&value)
+                .scim_err("Failed to get namespace")?
+            {
+                Some(ns) => state
+                    .store
+                    .get_scim_group(&ns.id)
+                    .scim_err("Failed to get group")?
+                    .into_iter()
+                    .collect(),
+                None => Vec::new(),
+            }
+        }
+        Some((attribute, value)) if attribute.eq_ignore_ascii_case("externalId") => {
+            group_by_external_id(&state, &value)?.into_iter().collect()
+        }
+        Some((attribute, _)) => {
+            return Err(ScimError::bad_request(
+                "invalidFilter",
+                format!("Groups can't be filtered by `{attribute}`"),
+            ));
+        }
+        None => state
+            .store
+            .list_scim_groups()
+            .scim_err("Failed to list groups")?,
+    };
+
+    let total = groups.len();
+    let (groups, start_index) = page(groups, &query);
+    let groups = groups
+        .iter()
+        .map(|g| group_resource(&state, g))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(list_response(total, start_index, groups))
+}
+
+/// Creates a group, or adopts an existing namespace of the same name that
+/// isn't a user's.
+pub async fn create_group(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    body: Bytes,
+) -> Result<Response, ScimError> {
+    let req: GroupRequest = parse_body(&body)?;
+    validate_namespace_name(&req.display_name)
+        .map_err(|e| ScimError::bad_request("invalidValue", format!("Invalid displayName: {e}")))?;
+    let ids: Vec<String> = req.members.into_iter().map(|m| m.value).collect();
+    check_members(&state, &ids)?;
+    if let Some(external_id) = &req.external_id {
+        if group_by_external_id(&state, external_id)?.is_some() {
+            return Err(ScimError::conflict(
+                "Another group already has this externalId",
+            ));
+        }
+    }
+
+    let namespace = match state
+        .store
+        .get_namespace_by_name(&req.display_name)
+        .scim_err("Failed to get namespace")?
+    {
+        Some(ns) => {
+            let taken = state
+                .store
+                .get_principal_by_primary_namespace_id(&ns.id)
+                .scim_err("Failed to get user")?
+                .is_some()
+                || state
+                    .store
+                    .get_scim_group(&ns.id)
+                    .scim_err("Failed to get group")?
+                    .is_some();
+            if taken {
+                return Err(ScimError::conflict(format!(
+                    "displayName `{}` is already taken",
+                    req.display_name
+                )));
+            }
+            ns
+        }
+        None => {
+            let ns = Namespace {
+                id: Uuid::new_v4().to_string(),
+                name: req.display_name.clone(),
+                created_at: Utc::now(),
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            };
+            state
+                .store
+                .create_namespace(&ns)
+                .scim_err("Failed to create namespace")?;
+            ns
+        }
+    };
+
+    let group = ScimGroup {
+        namespace_id: namespace.id.clone(),
+        external_id: req.external_id,
+        created_at: Utc::now(),
+    };
+    save_group(&state, &group)?;
+    apply_member_change(&state, &namespace.id, MemberChange::Add(ids))?;
+
+    let resource = group_resource(&state, &group)?;
+    let mut response = scim_response(StatusCode::CREATED, &resource);
+    if let Ok(location) = resource.meta.location.parse() {
+        response.headers_mut().insert(header::LOCATION, location);
+    }
+    Ok(response)
+}
+
+pub async fn get_group(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Response, ScimError> {
+    let (group, _) = find_group(&state, &id)?;
+    Ok(scim_response(
+        StatusCode::OK,
+        &group_resource(&state, &group)?,
+    ))
+}
+
+pub async fn replace_group(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> Result<Response, ScimError> {
+    let (mut group, namespace) = find_group(&state, &id)?;
+    let req: GroupRequest = parse_body(&body)?;
+    check_display_name(&namespace, &req.display_name)?;
+    let ids: Vec<String> = req.members.into_iter().map(|m| m.value).collect();
+    check_members(&state, &ids)?;
+
+    group.external_id = req.external_id;
+    save_group(&state, &group)?;
+    apply_member_change(&state, &namespace.id, MemberChange::Replace(ids))?;
+    Ok(scim_response(
+        StatusCode::OK,
+        &group_resource(&state, &group)?,
+    ))
+}
+
+/// Applies PATCH operations to `members` and `externalId`, including
+/// removals by `members[value eq "<id>"]`.
+pub async fn patch_group(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> Result<Response, ScimError> {
+    let (mut group, namespace) = find_group(&state, &id)?;
+    let req: PatchRequest = parse_body(&body)?;
+
+    let mut member_changes = Vec::new();
+    for operation in &req.operations {
+        let (op, changes) = patch_changes(operation)?;
+        for (path, value) in changes {
+            if path.eq_ignore_ascii_case("members") {
+                member_changes.push(match (op, value) {
+                    (PatchOp::Remove, Value::Null) => MemberChange::RemoveAll,
+                    (PatchOp::Remove, value) => MemberChange::Remove(member_ids(value)?),
+                    (PatchOp::Add, value) => MemberChange::Add(member_ids(value)?),
+                    (PatchOp::Replace, value) => MemberChange::Replace(member_ids(value)?),
+                });
+            } else if let Some(filter) = member_filter(&path) {
+                let (attribute, id) = parse_filter(filter)?;
+                if op != PatchOp::Remove || !attribute.eq_ignore_ascii_case("value") {
+                    return Err(ScimError::bad_request(
+                        "invalidPath",
+                        format!("Unsupported path `{path}`"),
+                    ));
+                }
+                member_changes.push(MemberChange::Remove(vec![id]));
+            } else if path.eq_ignore_ascii_case("externalId") {
+                group.external_id = match (op, value) {
+                    (PatchOp::Remove, _) | (_, Value::Null) => None,
+                    (_, Value::String(s)) => Some(s),
+                    _ => {
+                        return Err(ScimError::bad_request(
+                            "invalidValue",
+                            "externalId must be a string",
+                        ));
+                    }
+                };
+            } else if path.eq_ignore_ascii_case("displayName") {
+                let display_name = value.as_str().ok_or_else(|| {
+                    ScimError::bad_request("mutability", "displayName can't be removed")
+                })?;
+                check_display_name(&namespace, display_name)?;
+            }
+        }
+    }
+    for change in &member_changes {
+        if let MemberChange::Add(ids) | MemberChange::Replace(ids) = change {
+            check_members(&state, ids)?;
+        }
+    }
+
+    save_group(&state, &group)?;
+    for change in member_changes {
+        apply_member_change(&state, &namespace.id, change)?;
+    }
+    Ok(scim_response(
+        StatusCode::OK,
+        &group_resource(&state, &group)?,
+    ))
+}
+
+/// The filter in a `members[<filter>]` path.
+fn member_filter(path: &str) -> Option<&str> {
+    let prefix = path.get(..8)?;
+    if !prefix.eq_ignore_ascii_case("members[") {
+        return None;
+    }
+    path[8..].strip_suffix(']')
+}
+
+/// Removes every member's grant and stops managing the namespace as a
+/// group. The namespace and its repositories stay.
+pub async fn delete_group(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ScimError> {
+    let (group, namespace) = find_group(&state, &id)?;
+    apply_member_change(&state, &namespace.id, MemberChange::RemoveAll)?;
+    state
+        .store
+        .delete_scim_group(&group.namespace_id)
+        .scim_err("Failed to delete group")?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,307 @@
+//! SCIM 2.0 provisioning (RFC 7643 and 7644), the subset identity
+//! providers use to manage users and team membership. Users are
+//! principals, named after their primary namespace. Groups are other
+//! namespaces, and a group's members are the principals with a grant on
+//! it. Deactivated users keep their grants but can't authenticate until
+//! they're reactivated. Requests authenticate with the admin token.
+
+mod dto;
+mod groups;
+mod users;
+
+use std::sync::Arc;
+
+use axum::Json;
+use axum::Router;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+use self::dto::{
+    ERROR_SCHEMA, ErrorResponse, LIST_RESPONSE_SCHEMA, ListQuery, ListResponse, PatchOperation,
+};
+use crate::auth::RequireAdmin;
+use crate::error::Result as StoreResult;
+use crate::server::AppState;
+
+const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+
+/// Most resources returned by one list request.
+const MAX_PAGE_SIZE: usize = 100;
+
+pub fn scim_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/ServiceProviderConfig", get(service_provider_config))
+        .route("/Users", get(users::list_users).post(users::create_user))
+        .route(
+            "/Users/{id}",
+            get(users::get_user)
+                .put(users::replace_user)
+                .patch(users::patch_user)
+                .delete(users::delete_user),
+        )
+        .route(
+            "/Groups",
+            get(groups::list_groups).post(groups::create_group),
+        )
+        .route(
+            "/Groups/{id}",
+            get(groups::get_group)
+                .put(groups::replace_group)
+                .patch(groups::patch_group)
+                .delete(groups::delete_group),
+        )
+}
+
+async fn service_provider_config(_admin: RequireAdmin) -> Response {
+    scim_response(
+        StatusCode::OK,
+        &json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig"],
+            "patch": { "supported": true },
+            "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+            "filter": { "supported": true, "maxResults": MAX_PAGE_SIZE },
+            "changePassword": { "supported": false },
+            "sort": { "supported": false },
+            "etag": { "supported": false },
+            "authenticationSchemes": [{
+                "type": "oauthbearertoken",
+                "name": "Admin token",
+                "description": "The cutman admin token as a bearer token",
+            }],
+        }),
+    )
+}
+
+/// An error in the SCIM error format.
+#[derive(Debug)]
+pub struct ScimError {
+    status: StatusCode,
+    scim_type: Option<&'static str>,
+    detail: String,
+}
+
+impl ScimError {
+    fn bad_request(scim_type: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            scim_type: Some(scim_type),
+            detail: detail.into(),
+        }
+    }
+
+    fn not_found(detail: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            scim_type: None,
+            detail: detail.into(),
+        }
+    }
+
+    fn conflict(detail: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::CONFLICT,
+            scim_type: Some("uniqueness"),
+            detail: detail.into(),
+        }
+    }
+
+    fn internal(detail: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            scim_type: None,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl IntoResponse for ScimError {
+    fn into_response(self) -> Response {
+        scim_response(
+            self.status,
+            &ErrorResponse {
+                schemas: [ERROR_SCHEMA],
+                status: self.status.as_u16().to_string(),
+                scim_type: self.scim_type,
+                detail: self.detail,
+            },
+        )
+    }
+}
+
+/// Converts store results to SCIM errors with a custom message.
+trait StoreResultExt<T> {
+    fn scim_err(self, message: &'static str) -> Result<T, ScimError>;
+}
+
+impl<T> StoreResultExt<T> for StoreResult<T> {
+    fn scim_err(self, message: &'static str) -> Result<T, ScimError> {
+        self.map_err(|_| ScimError::internal(message))
+    }
+}
+
+fn scim_response(status: StatusCode, body: &impl Serialize) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, SCIM_CONTENT_TYPE)],
+        Json(body),
+    )
+        .into_response()
+}
+
+/// Parses a request body. Clients send `application/scim+json`, which the
+/// `Json` extractor refuses.
+fn parse_body<T: DeserializeOwned>(body: &[u8]) -> Result<T, ScimError> {
+    serde_json::from_slice(body)
+        .map_err(|e| ScimError::bad_request("invalidSyntax", format!("Invalid request body: {e}")))
+}
+
+fn location(state: &AppState, resource: &str, id: &str) -> String {
+    let base = state.public_base_url.as_deref().unwrap_or("");
+    format!("{}/scim/v2/{resource}/{id}", base.trim_end_matches('/'))
+}
+
+/// Parses the one filter form identity providers send to look resources
+/// up, `<attribute> eq "<value>"`. Returns the attribute and the value.
+fn parse_filter(filter: &str) -> Result<(String, String), ScimError> {
+    let invalid = || {
+        ScimError::bad_request(
+            "invalidFilter",
+            format!(
+                "Unsupported filter `{filter}`; only `<attribute> eq \"<value>\"` is supported"
+            ),
+        )
+    };
+    let (attribute, rest) = filter
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or_else(invalid)?;
+    let (op, value) = rest
+        .trim_start()
+        .split_once(char::is_whitespace)
+        .ok_or_else(invalid)?;
+    if !op.eq_ignore_ascii_case("eq") {
+        return Err(invalid());
+    }
+    let value: String = serde_json::from_str(value.trim()).map_err(|_| invalid())?;
+    Ok((attribute.to_string(), value))
+}
+
+/// Selects the page `query` asks for, counting `startIndex` from 1.
+/// Returns the page with the index of its first item.
+fn page<T>(items: Vec<T>, query: &ListQuery) -> (Vec<T>, usize) {
+    let start_index = query.start_index.unwrap_or(1).max(1);
+    let count = query.count.unwrap_or(MAX_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    let page = items
+        .into_iter()
+        .skip(start_index - 1)
+        .take(count)
+        .collect();
+    (page, start_index)
+}
+
+fn list_response<T: Serialize>(
+    total_results: usize,
+    start_index: usize,
+    resources: Vec<T>,
+) -> Response {
+    scim_response(
+        StatusCode::OK,
+        &ListResponse {
+            schemas: [LIST_RESPONSE_SCHEMA],
+            total_results,
+            start_index,
+            items_per_page: resources.len(),
+            resources,
+        },
+    )
+}
+
+/// What a PATCH operation does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PatchOp {
+    Add,
+    Replace,
+    Remove,
+}
+
+/// Splits a PATCH operation into `(path, value)` pairs. Without a path the
+/// value is an object whose keys are the paths, as some providers send it.
+fn patch_changes(operation: &PatchOperation) -> Result<(PatchOp, Vec<(String, Value)>), ScimError> {
+    let op = match operation.op.to_ascii_lowercase().as_str() {
+        "add" => PatchOp::Add,
+        "replace" => PatchOp::Replace,
+        "remove" => PatchOp::Remove,
+        _ => {
+            return Err(ScimError::bad_request(
+                "invalidSyntax",
+                format!("Unknown PATCH operation `{}`", operation.op),
+            ));
+        }
+    };
+    let value = operation.value.clone().unwrap_or(Value::Null);
+    let changes = match (&operation.path, value) {
+        (Some(path), value) => vec![(path.clone(), value)],
+        (None, Value::Object(values)) if op != PatchOp::Remove => values.into_iter().collect(),
+        (None, _) => {
+            return Err(ScimError::bad_request(
+                "noTarget",
+                "PATCH operation needs a path or an object value",
+            ));
+        }
+    };
+    Ok((op, changes))
+}
+
+/// Reads a boolean some providers send as the string `"True"` or `"False"`.
+fn as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(*b),
+        Value::String(s) if s.eq_ignore_ascii_case("true") => Some(true),
+        Value::String(s) if s.eq_ignore_ascii_case("false") => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            parse_filter("userName eq \"alice\"").unwrap(),
+            ("userName".to_string(), "alice".to_string())
+        );
+        assert_eq!(
+            parse_filter(" externalId  EQ \"a \\\"b\\\"\" ").unwrap(),
+            ("externalId".to_string(), "a \"b\"".to_string())
+        );
+        assert!(parse_filter("userName sw \"a\"").is_err());
+        assert!(parse_filter("userName eq alice").is_err());
+        assert!(parse_filter("userName").is_err());
+    }
+
+    #[test]
+    fn test_page() {
+        let query = |start_index, count| ListQuery {
+            filter: None,
+            start_index,
+            count,
+        };
+        let items: Vec<u32> = (1..=150).collect();
+        assert_eq!(page(items.clone(), &query(None, None)).0.len(), 100);
+        assert_eq!(
+            page(items.clone(), &query(Some(0), Some(2))),
+            (vec![1, 2], 1)
+        );
+        assert_eq!(
+            page(items.clone(), &query(Some(149), Some(5))),
+            (vec![149, 150], 149)
+        );
+        assert!(page(items, &query(Some(1), Some(0))).0.is_empty());
+    }
+}
@@ -0,0 +1,380 @@
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::dto::{ListQuery, Meta, PatchRequest, USER_SCHEMA, UserRequest, UserResource};
+use super::{
+    PatchOp, ScimError, StoreResultExt, as_bool, list_response, location, page, parse_body,
+    parse_filter, patch_changes, scim_response,
+};
+use crate::auth::RequireAdmin;
+use crate::error::Error;
+use crate::server::AppState;
+use crate::server::validation::validate_namespace_name;
+use crate::types::{Namespace, NamespaceGrant, Permission, Principal, ScimUser};
+
+/// Principals read from the store per query when listing them all.
+const LIST_BATCH: i32 = 500;
+
+fn user_resource(state: &AppState, principal: &Principal) -> Result<UserResource, ScimError> {
+    let namespace = state
+        .store
+        .get_namespace(&principal.primary_namespace_id)
+        .scim_err("Failed to get namespace")?
+        .ok_or_else(|| ScimError::internal("Principal has no namespace"))?;
+    let scim = state
+        .store
+        .get_scim_user(&principal.id)
+        .scim_err("Failed to get user")?;
+
+    let last_modified = scim.as_ref().map_or(principal.updated_at, |s| {
+        s.updated_at.max(principal.updated_at)
+    });
+    Ok(UserResource {
+        schemas: [USER_SCHEMA],
+        id: principal.id.clone(),
+        external_id: scim.as_ref().and_then(|s| s.external_id.clone()),
+        user_name: namespace.name,
+        active: scim.is_none_or(|s| s.active),
+        meta: Meta {
+            resource_type: "User",
+            created: principal.created_at,
+            last_modified,
+            location: location(state, "Users", &principal.id),
+        },
+    })
+}
+
+fn find_principal(state: &AppState, id: &str) -> Result<Principal, ScimError> {
+    state
+        .store
+        .get_principal(id)
+        .scim_err("Failed to get user")?
+        .ok_or_else(|| ScimError::not_found(format!("User {id} not found")))
+}
+
+fn principal_by_user_name(
+    state: &AppState,
+    user_name: &str,
+) -> Result<Option<Principal>, ScimError> {
+    let Some(namespace) = state
+        .store
+        .get_namespace_by_name(user_name)
+        .scim_err("Failed to get namespace")?
+    else {
+        return Ok(None);
+    };
+    state
+        .store
+        .get_principal_by_primary_namespace_id(&namespace.id)
+        .scim_err("Failed to get user")
+}
+
+/// Records the user's SCIM attributes, refusing an `externalId` another
+/// user already has.
+fn save_user(state: &AppState, user: &ScimUser) -> Result<(), ScimError> {
+    match state.store.upsert_scim_user(user) {
+        Ok(()) => Ok(()),
+        Err(Error::AlreadyExists) => Err(ScimError::conflict(
+            "Another user already has this externalId",
+        )),
+        Err(_) => Err(ScimError::internal("Failed to save user")),
+    }
+}
+
+fn scim_user(state: &AppState, principal: &Principal) -> Result<ScimUser, ScimError> {
+    Ok(state
+        .store
+        .get_scim_user(&principal.id)
+        .scim_err("Failed to get user")?
+        .unwrap_or_else(|| ScimUser {
+            principal_id: principal.id.clone(),
+            external_id: None,
+            active: true,
+            updated_at: Utc::now(),
+        }))
+}
+
+/// Usernames name the principal's namespace, which repository URLs use,
+/// so they can't be changed through SCIM.
+fn check_user_name(
+    state: &AppState,
+    principal: &Principal,
+    user_name: &str,
+) -> Result<(), ScimError> {
+    let current = user_resource(state, principal)?.user_name;
+    if current != user_name {
+        return Err(ScimError::bad_request(
+            "mutability",
+            format!("userName can't be changed from `{current}`"),
+        ));
+    }
+    Ok(())
+}
+
+pub async fn list_users(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListQuery>,
+) -> Result<Response, ScimError> {
+    let principals = match query.filter.as_deref().map(parse_filter).transpose()? {
+        Some((attribute, value)) if attribute.eq_ignore_ascii_case("userName") => {
+            principal_by_user_name(&state, &value)?
+                .into_iter()
+                .collect()
+        }
+        Some((attribute, value)) if attribute.eq_ignore_ascii_case("externalId") => match state
+            .store
+            .get_scim_user_by_external_id(&value)
+            .scim_err("Failed to get user")?
+        {
+            Some(user) => state
+                .store
+                .get_principal(&user.principal_id)
+                .scim_err("Failed to get user")?
+                .into_iter()
+                .collect(),
+            None => Vec::new(),
+        },
+        Some((attribute, _)) => {
+            return Err(ScimError::bad_request(
+                "invalidFilter",
+                format!("Users can't be filtered by `{attribute}`"),
+            ));
+        }
+        None => {
+            let mut principals = Vec::new();
+            loop {
+                let cursor = principals.last().map_or("", |p: &Principal| p.id.as_str());
+                let batch = state
+                    .store
+                    .list_principals(cursor, LIST_BATCH)
+                    .scim_err("Failed to list users")?;
+                let done = batch.len() < LIST_BATCH as usize;
+                principals.extend(batch);
+                if done {
+                    break principals;
+                }
+            }
+        }
+    };
+
+    let total = principals.len();
+    let (principals, start_index) = page(principals, &query);
+    let users = principals
+        .iter()
+        .map(|p| user_resource(&state, p))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(list_response(total, start_index, users))
+}
+
+pub async fn create_user(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    body: Bytes,
+) -> Result<Response, ScimError> {
+    let req: UserRequest = parse_body(&body)?;
+    validate_namespace_name(&req.user_name)
+        .map_err(|e| ScimError::bad_request("invalidValue", format!("Invalid userName: {e}")))?;
+    if let Some(external_id) = &req.external_id {
+        if state
+            .store
+            .get_scim_user_by_external_id(external_id)
+            .scim_err("Failed to get user")?
+            .is_some()
+        {
+            return Err(ScimError::conflict(
+                "Another user already has this externalId",
+            ));
+        }
+    }
+
+    let namespace = match state
+        .store
+        .get_namespace_by_name(&req.user_name)
+        .scim_err("Failed to get namespace")?
+    {
+        Some(ns) => {
+            let taken = state
+                .store
+                .get_principal_by_primary_namespace_id(&ns.id)
+                .scim_err("Failed to get user")?
+                .is_some()
+                || state
+                    .store
+                    .get_scim_group(&ns.id)
+                    .scim_err("Failed to get group")?
+                    .is_some();
+            if taken {
+                return Err(ScimError::conflict(format!(
+                    "userName `{}` is already taken",
+                    req.user_name
+                )));
+            }
+            ns
+        }
+        None => {
+            let ns = Namespace {
+                id: Uuid::new_v4().to_string(),
+                name: req.user_name.clone(),
+                created_at: Utc::now(),
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            };
+            state
+                .store
+                .create_namespace(&ns)
+                .scim_err("Failed to create namespace")?;
+            ns
+        }
+    };
+
+    let now = Utc::now();
+    let principal = Principal {
+        id: Uuid::new_v4().to_string(),
+        primary_namespace_id: namespace.id.clone(),
+        created_at: now,
+        updated_at: now,
+    };
+    state
+        .store
+        .create_principal(&principal)
+        .scim_err("Failed to create user")?;
+    state
+        .store
+        .upsert_namespace_grant(&NamespaceGrant {
+            principal_id: principal.id.clone(),
+            namespace_id: namespace.id,
+            allow_bits: Permission::default_namespace_grant(),
+            deny_bits: Permission::default(),
+            created_at: now,
+            updated_at: now,
+        })
+        .scim_err("Failed to create grant")?;
+    save_user(
+        &state,
+        &ScimUser {
+            principal_id: principal.id.clone(),
+            external_id: req.external_id,
+            active: req.active,
+            updated_at: now,
+        },
+    )?;
+
+    let user = user_resource(&state, &principal)?;
+    let mut response = scim_response(StatusCode::CREATED, &user);
+    if let Ok(location) = user.meta.location.parse() {
+        response.headers_mut().insert(header::LOCATION, location);
+    }
+    Ok(response)
+}
+
+pub async fn get_user(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Response, ScimError> {
+    let principal = find_principal(&state, &id)?;
+    Ok(scim_response(
+        StatusCode::OK,
+        &user_resource(&state, &principal)?,
+    ))
+}
+
+pub async fn replace_user(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> Result<Response, ScimError> {
+    let principal = find_principal(&state, &id)?;
+    let req: UserRequest = parse_body(&body)?;
+    check_user_name(&state, &principal, &req.user_name)?;
+
+    save_user(
+        &state,
+        &ScimUser {
+            principal_id: principal.id.clone(),
+            external_id: req.external_id,
+            active: req.active,
+            updated_at: Utc::now(),
+        },
+    )?;
+    Ok(scim_response(
+        StatusCode::OK,
+        &user_resource(&state, &principal)?,
+    ))
+}
+
+/// Applies PATCH operations to `active` and `externalId`. Attributes
+/// cutman doesn't keep are ignored, since providers send whatever their
+/// mapping includes.
+pub async fn patch_user(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> Result<Response, ScimError> {
+    let principal = find_principal(&state, &id)?;
+    let req: PatchRequest = parse_body(&body)?;
+    let mut user = scim_user(&state, &principal)?;
+
+    for operation in &req.operations {
+        let (op, changes) = patch_changes(operation)?;
+        for (path, value) in changes {
+            if path.eq_ignore_ascii_case("active") {
+                user.active = match op {
+                    PatchOp::Remove => true,
+                    _ => as_bool(&value).ok_or_else(|| {
+                        ScimError::bad_request("invalidValue", "active must be a boolean")
+                    })?,
+                };
+            } else if path.eq_ignore_ascii_case("externalId") {
+                user.external_id = match (op, value) {
+                    (PatchOp::Remove, _) | (_, Value::Null) => None,
+                    (_, Value::String(s)) => Some(s),
+                    _ => {
+                        return Err(ScimError::bad_request(
+                            "invalidValue",
+                            "externalId must be a string",
+                        ));
+                    }
+                };
+            } else if path.eq_ignore_ascii_case("userName") {
+                let user_name = value.as_str().ok_or_else(|| {
+                    ScimError::bad_request("mutability", "userName can't be removed")
+                })?;
+                check_user_name(&state, &principal, user_name)?;
+            }
+        }
+    }
+
+    user.updated_at = Utc::now();
+    save_user(&state, &user)?;
+    Ok(scim_response(
+        StatusCode::OK,
+        &user_resource(&state, &principal)?,
+    ))
+}
+
+/// Deletes the principal and its tokens. Its namespace and repositories
+/// stay, as when a principal is deleted through the admin API.
+pub async fn delete_user(
+    _admin: RequireAdmin,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ScimError> {
+    let principal = find_principal(&state, &id)?;
+    state
+        .store
+        .delete_principal(&principal.id)
+        .scim_err("Failed to delete user")?;
+    Ok(StatusCode::NO_CONTENT)
+}
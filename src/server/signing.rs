@@ -0,0 +1,621 @@
+//! Commit signature verification against registered signing keys.
+//!
+//! Signatures are checked with the tools git itself uses: `gpgv` for OpenPGP
+//! signatures and `ssh-keygen -Y verify` for SSH ones. A signature names the
+//! key it was made with; only that registered key is trusted for the check,
+//! in a keyring staged under `signing/tmp/` and removed afterwards.
+
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::warn;
+
+use crate::server::AppState;
+use crate::server::limits::deadline;
+use crate::server::validation::ssh_key_fingerprint;
+use crate::types::{SigningKey, SigningKeyKind};
+
+/// SSH signatures git makes are in the `git` namespace.
+const SSH_NAMESPACE: &str = "git";
+
+/// Outcome of checking a commit signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationReason {
+    Valid,
+    Unsigned,
+    /// No registered key matches the one the signature names.
+    UnknownKey,
+    /// The signature doesn't match the commit.
+    Invalid,
+    ExpiredKey,
+    RevokedKey,
+    /// An X.509 or otherwise unsupported signature.
+    UnknownSignatureType,
+    MalformedSignature,
+    /// The check itself failed, e.g. the verifying tool isn't installed.
+    VerifyError,
+}
+
+#[derive(Debug, Clone)]
+pub struct Verification {
+    pub reason: VerificationReason,
+    /// The registered key the signature names, when there is one.
+    pub key: Option<SigningKey>,
+}
+
+impl Verification {
+    #[must_use]
+    pub fn unsigned() -> Self {
+        Self {
+            reason: VerificationReason::Unsigned,
+            key: None,
+        }
+    }
+
+    #[must_use]
+    pub fn verified(&self) -> bool {
+        self.reason == VerificationReason::Valid
+    }
+}
+
+/// What `gpg` reports about an uploaded OpenPGP public key.
+#[derive(Debug)]
+pub struct GpgKeyInfo {
+    pub fingerprint: String,
+    /// Key IDs of the primary key and its subkeys.
+    pub key_ids: Vec<String>,
+    /// The first user ID, e.g. `Alice <alice@example.com>`.
+    pub user_id: Option<String>,
+}
+
+/// Checks a commit signature against the registered signing keys.
+pub async fn verify_signature(state: &AppState, signature: &[u8], payload: &[u8]) -> Verification {
+    let Ok(signature) = std::str::from_utf8(signature) else {
+        return reason_only(VerificationReason::MalformedSignature);
+    };
+    let (kind, key_id) = match signature_key_id(signature) {
+        Ok(found) => found,
+        Err(reason) => return reason_only(reason),
+    };
+
+    let key = match state.store.find_signing_key(kind, &key_id) {
+        Ok(Some(key)) => key,
+        Ok(None) => return reason_only(VerificationReason::UnknownKey),
+        Err(e) => {
+            warn!("Failed to look up signing key {key_id}: {e}");
+            return reason_only(VerificationReason::VerifyError);
+        }
+    };
+
+    let reason = match check_signature(
+        &state.data_dir,
+        state.signing_timeout,
+        &key,
+        signature,
+        payload,
+    )
+    .await
+    {
+        Ok(reason) => reason,
+        Err(e) => {
+            warn!("Failed to verify signature by key {}: {e}", key.id);
+            VerificationReason::VerifyError
+        }
+    };
+    Verification {
+        reason,
+        key: Some(key),
+    }
+}
+
+fn reason_only(reason: VerificationReason) -> Verification {
+    Verification { reason, key: None }
+}
+
+/// The kind of a signature and the ID of the key it names: the 16-digit
+/// OpenPGP key ID, or the SHA256 fingerprint of the SSH key.
+pub fn signature_key_id(signature: &str) -> Result<(SigningKeyKind, String), VerificationReason> {
+    let malformed = VerificationReason::MalformedSignature;
+    if let Some(packet) = dearmor(signature, "PGP SIGNATURE") {
+        let key_id = pgp_issuer(&packet).ok_or(malformed)?;
+        return Ok((SigningKeyKind::Gpg, key_id));
+    }
+    if let Some(blob) = dearmor(signature, "SSH SIGNATURE") {
+        let public_key = sshsig_public_key(&blob).ok_or(malformed)?;
+        return Ok((SigningKeyKind::Ssh, ssh_key_fingerprint(public_key)));
+    }
+    if signature.trim_start().starts_with("-----BEGIN ") {
+        return Err(VerificationReason::UnknownSignatureType);
+    }
+    Err(malformed)
+}
+
+/// The decoded body of the first ASCII-armored block with the given label,
+/// skipping armor headers and the OpenPGP checksum line.
+fn dearmor(text: &str, label: &str) -> Option<Vec<u8>> {
+    let begin = format!("-----BEGIN {label}-----");
+    let end = format!("-----END {label}-----");
+    let mut lines = text.lines().map(str::trim);
+    lines.find(|line| *line == begin)?;
+
+    let mut encoded = String::new();
+    for line in lines {
+        if line == end {
+            return STANDARD.decode(encoded).ok();
+        }
+        if line.contains(": ") || line.is_empty() || line.starts_with('=') {
+            continue;
+        }
+        encoded.push_str(line);
+    }
+    None
+}
+
+/// Key ID of the issuer of an OpenPGP signature packet, from its issuer
+/// fingerprint or issuer subpacket (or the v3 header field).
+fn pgp_issuer(data: &[u8]) -> Option<String> {
+    let (tag, body) = pgp_packet(data)?;
+    if tag != 2 {
+        return None;
+    }
+
+    match *body.first()? {
+        3 => Some(hex::encode_upper(body.get(7..15)?)),
+        version @ 4..=6 => {
+            // v6 counts subpacket bytes in four octets, earlier versions in two.
+            let width = if version == 6 { 4 } else { 2 };
+            let mut offset = 4;
+            let mut areas = Vec::new();
+            for _ in 0..2 {
+                let len = be_uint(body.get(offset..offset + width)?);
+                offset += width;
+                areas.push(body.get(offset..offset + len)?);
+                offset += len;
+            }
+
+            let mut fallback = None;
+            for area in areas {
+                for (kind, value) in pgp_subpackets(area)? {
+                    match (kind, value.first()) {
+                        // Issuer fingerprint: v4 keys take the ID from the
+                        // end of the fingerprint, v5 and v6 from the start.
+                        (33, Some(4)) if value.len() == 21 => {
+                            return Some(hex::encode_upper(&value[13..]));
+                        }
+                        (33, Some(5 | 6)) if value.len() == 33 => {
+                            return Some(hex::encode_upper(&value[1..9]));
+                        }
+                        (16, _) if value.len() == 8 => {
+                            fallback = Some(hex::encode_upper(value));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            fallback
+        }
+        _ => None,
+    }
+}
+
+/// Tag and body of the first OpenPGP packet in `data`.
+fn pgp_packet(data: &[u8]) -> Option<(u8, &[u8])> {
+    let header = *data.first()?;
+    if header & 0x80 == 0 {
+        return None;
+    }
+
+    if header & 0x40 != 0 {
+        let tag = header & 0x3f;
+        let (len, used) = match *data.get(1)? {
+            n @ 0..192 => (n as usize, 1),
+            n @ 192..224 => (
+                (((n as usize) - 192) << 8) + *data.get(2)? as usize + 192,
+                2,
+            ),
+            255 => (be_uint(data.get(2..6)?), 5),
+            // Partial body lengths aren't used for signatures.
+            _ => return None,
+        };
+        return Some((tag, data.get(1 + used..1 + used + len)?));
+    }
+
+    let tag = (header >> 2) & 0x0f;
+    let body = match header & 0x03 {
+        0 => data.get(2..2 + *data.get(1)? as usize)?,
+        1 => data.get(3..3 + be_uint(data.get(1..3)?))?,
+        2 => data.get(5..5 + be_uint(data.get(1..5)?))?,
+        _ => data.get(1..)?,
+    };
+    Some((tag, body))
+}
+
+/// Type (without the critical bit) and value of each subpacket in an area.
+fn pgp_subpackets(mut area: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut subpackets = Vec::new();
+    while !area.is_empty() {
+        let (len, used) = match area[0] {
+            n @ 0..192 => (n as usize, 1),
+            n @ 192..255 => (
+                (((n as usize) - 192) << 8) + *area.get(1)? as usize + 192,
+                2,
+            ),
+            255 => (be_uint(area.get(1..5)?), 5),
+        };
+        let subpacket = area.get(used..used + len)?;
+        let (&kind, value) = subpacket.split_first()?;
+        subpackets.push((kind & 0x7f, value));
+        area = &area[used + len..];
+    }
+    Some(subpackets)
+}
+
+/// The public key blob embedded in an `SSHSIG` signature.
+fn sshsig_public_key(blob: &[u8]) -> Option<&[u8]> {
+    let rest = blob.strip_prefix(b"SSHSIG")?;
+    let len = be_uint(rest.get(4..8)?);
+    rest.get(8..8 + len)
+}
+
+fn be_uint(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |n, &b| (n << 8) | b as usize)
+}
+
+/// A scratch directory under `signing/tmp/` for one tool run.
+fn scratch_dir(data_dir: &Path) -> std::io::Result<tempfile::TempDir> {
+    let tmp = data_dir.join("signing/tmp");
+    std::fs::create_dir_all(&tmp)?;
+    tempfile::TempDir::new_in(tmp)
+}
+
+/// Verifies a signature made by `key` over `payload`.
+async fn check_signature(
+    data_dir: &Path,
+    timeout: Option<Duration>,
+    key: &SigningKey,
+    signature: &str,
+    payload: &[u8],
+) -> std::io::Result<VerificationReason> {
+    let dir = scratch_dir(data_dir)?;
+    let sig_path = dir.path().join("signature");
+    tokio::fs::write(&sig_path, signature).await?;
+
+    match key.kind {
+        SigningKeyKind::Gpg => {
+            let keyring = dir.path().join("keyring.gpg");
+            let key_data = dearmor(&key.public_key, "PGP PUBLIC KEY BLOCK")
+                .ok_or_else(|| std::io::Error::other("stored key is not armored"))?;
+            tokio::fs::write(&keyring, key_data).await?;
+
+            let mut cmd = Command::new("gpgv");
+            cmd.arg("--homedir")
+                .arg(dir.path())
+                .args(["--status-fd", "1", "--keyring"])
+                .arg(&keyring)
+                .arg(&sig_path)
+                .arg("-");
+            let (_, status) = run_tool(cmd, payload, timeout).await?;
+            Ok(gpg_status_reason(&status))
+        }
+        SigningKeyKind::Ssh => {
+            let allowed_signers = dir.path().join("allowed_signers");
+            tokio::fs::write(&allowed_signers, format!("signer {}\n", key.public_key)).await?;
+
+            let mut cmd = Command::new("ssh-keygen");
+            cmd.args(["-Y", "verify", "-I", "signer", "-n", SSH_NAMESPACE, "-f"])
+                .arg(&allowed_signers)
+                .arg("-s")
+                .arg(&sig_path);
+            let (success, _) = run_tool(cmd, payload, timeout).await?;
+            Ok(if success {
+                VerificationReason::Valid
+            } else {
+                VerificationReason::Invalid
+            })
+        }
+    }
+}
+
+/// The outcome `gpgv` reports on its status lines.
+fn gpg_status_reason(status: &str) -> VerificationReason {
+    let mut reason = VerificationReason::Invalid;
+    for line in status.lines() {
+        let Some(keyword) = line
+            .strip_prefix("[GNUPG:] ")
+            .and_then(|rest| rest.split_whitespace().next())
+        else {
+            continue;
+        };
+        reason = match keyword {
+            "GOODSIG" | "VALIDSIG" => VerificationReason::Valid,
+            "EXPKEYSIG" => return VerificationReason::ExpiredKey,
+            "REVKEYSIG" => return VerificationReason::RevokedKey,
+            "BADSIG" | "EXPSIG" => return VerificationReason::Invalid,
+            // The signature names a subkey the registered key doesn't have.
+            "ERRSIG" | "NO_PUBKEY" => return VerificationReason::UnknownKey,
+            _ => continue,
+        };
+    }
+    reason
+}
+
+/// Runs a verifying tool with `input` on stdin, returning whether it
+/// succeeded and its stdout. The tool is killed after `timeout`.
+async fn run_tool(
+    mut cmd: Command,
+    input: &[u8],
+    timeout: Option<Duration>,
+) -> std::io::Result<(bool, String)> {
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    let mut child = cmd.spawn()?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_vec();
+    let writer = tokio::spawn(async move {
+        // The tool may exit before reading everything, e.g. on a bad key.
+        let _ = stdin.write_all(&input).await;
+    });
+
+    let output = deadline(timeout, child.wait_with_output())
+        .await
+        .ok_or_else(|| std::io::Error::other("timed out"))??;
+    let _ = writer.await;
+
+    Ok((
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    ))
+}
+
+/// Reads an uploaded OpenPGP public key with `gpg --show-keys`, which also
+/// rejects anything that isn't one.
+pub async fn inspect_gpg_key(
+    data_dir: &Path,
+    timeout: Option<Duration>,
+    armored: &str,
+) -> Result<GpgKeyInfo, String> {
+    let failed = |e: std::io::Error| {
+        warn!("Failed to run gpg: {e}");
+        "Couldn't read the GPG key".to_string()
+    };
+    let home = scratch_dir(data_dir).map_err(failed)?;
+
+    let mut cmd = Command::new("gpg");
+    cmd.arg("--homedir").arg(home.path()).args([
+        "--batch",
+        "--no-tty",
+        "--with-colons",
+        "--show-keys",
+    ]);
+    let (success, listing) = run_tool(cmd, armored.as_bytes(), timeout)
+        .await
+        .map_err(failed)?;
+    if !success {
+        return Err("Not a valid GPG public key".to_string());
+    }
+
+    parse_gpg_listing(&listing)
+}
+
+/// Key details from `gpg --with-colons` output listing one key.
+fn parse_gpg_listing(listing: &str) -> Result<GpgKeyInfo, String> {
+    let mut fingerprint = None;
+    let mut key_ids = Vec::new();
+    let mut user_id = None;
+    let mut primaries = 0;
+    let mut after_primary = false;
+
+    for line in listing.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        let field = |i: usize| fields.get(i).copied().unwrap_or("");
+        match field(0) {
+            "pub" => {
+                primaries += 1;
+                after_primary = true;
+                key_ids.push(field(4).to_string());
+            }
+            "sub" => {
+                after_primary = false;
+                key_ids.push(field(4).to_string());
+            }
+            "fpr" if after_primary => {
+                fingerprint = Some(field(9).to_string());
+                after_primary = false;
+            }
+            "uid" if user_id.is_none() => {
+                user_id = Some(field(9).to_string()).filter(|u| !u.is_empty());
+            }
+            _ => {}
+        }
+    }
+
+    match (primaries, fingerprint) {
+        (1, Some(fingerprint)) => Ok(GpgKeyInfo {
+            fingerprint,
+            key_ids,
+            user_id,
+        }),
+        (0, _) | (1, None) => Err("Not a valid GPG public key".to_string()),
+        _ => Err("Upload one GPG key at a time".to_string()),
+    }
+}
+
+/// Whether `name` is an executable on the PATH, for skipping checks
+/// that need tools the host doesn't have.
+#[cfg(test)]
+fn have_tool(name: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| Path::new(&dir).join(name).is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    const TIMEOUT: Option<Duration> = Some(Duration::from_secs(10));
+
+    const PAYLOAD: &str = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+        author Alice <alice@example.com> 1700000000 +0000\n\
+        committer Alice <alice@example.com> 1700000000 +0000\n\
+        \n\
+        Signed commit\n";
+
+    const GPG_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEatBd5xYJKwYBBAHaRw8BAQdAPIqVsIDWCR2ZcaLudatIknH0ZT3QXJJtC1lY
+YccQai20GUFsaWNlIDxhbGljZUBleGFtcGxlLmNvbT6IkAQTFggAOBYhBJ/KAKh6
+AdqUrjHTZOtHGhAY0I59BQJq0F3nAhsDBQsJCAcCBhUKCQgLAgQWAgMBAh4BAheA
+AAoJEOtHGhAY0I59n6wA/0F4Qs7a7mGECTEmfulNkVffzZ1qhPXa4v07ezGizg/K
+AP9rsumNT/bey8IgWL+hBVk5AhdLjfyTw3Hv37txaMfyBA==
+=liFV
+-----END PGP PUBLIC KEY BLOCK-----
+";
+
+    const GPG_SIGNATURE: &str = "-----BEGIN PGP SIGNATURE-----
+
+iHUEABYIAB0WIQSfygCoegHalK4x02TrRxoQGNCOfQUCatBeRwAKCRDrRxoQGNCO
+fQt2AP9+mFdwHGnIkeGpXDdbfotPfIYiaour8oU6oRnV12YtCwEApna6jXaG2YIf
+1V0N3kRQSGnOE7fkjr4cVEa3x20ezAA=
+=6CK+
+-----END PGP SIGNATURE-----
+";
+
+    const SSH_KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAINdf7+CLccZCgMlCCAYXAiEAdygpxr4lYa92EtZ1VWM6";
+
+    const SSH_SIGNATURE: &str = "-----BEGIN SSH SIGNATURE-----
+U1NIU0lHAAAAAQAAADMAAAALc3NoLWVkMjU1MTkAAAAg11/v4ItxxkKAyUIIBhcCIQB3KC
+nGviVhr3YS1nVVYzoAAAADZ2l0AAAAAAAAAAZzaGE1MTIAAABTAAAAC3NzaC1lZDI1NTE5
+AAAAQIvR0iYpalAv04uzhIhAnsNfTRRae3FNMtpJy+accP7JStePuNIww0dnyVjZC1wpSt
+6fUFVRG38vjycDLsUw6w4=
+-----END SSH SIGNATURE-----
+";
+
+    fn key(kind: SigningKeyKind, public_key: &str) -> SigningKey {
+        SigningKey {
+            id: "key-1".to_string(),
+            principal_id: "principal-1".to_string(),
+            kind,
+            name: "test".to_string(),
+            public_key: public_key.to_string(),
+            fingerprint: String::new(),
+            key_ids: Vec::new(),
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_signature_key_id() {
+        assert_eq!(
+            signature_key_id(GPG_SIGNATURE),
+            Ok((SigningKeyKind::Gpg, "EB471A1018D08E7D".to_string()))
+        );
+        assert_eq!(
+            signature_key_id(SSH_SIGNATURE),
+            Ok((
+                SigningKeyKind::Ssh,
+                "SHA256:wUd2q+mYyygfEFs7VhFewQ5QRToe+qMoy2ZS6AZfM48".to_string()
+            ))
+        );
+        assert_eq!(
+            signature_key_id(
+                "-----BEGIN SIGNED MESSAGE-----\nMIAG\n-----END SIGNED MESSAGE-----\n"
+            ),
+            Err(VerificationReason::UnknownSignatureType)
+        );
+        assert_eq!(
+            signature_key_id(
+                "-----BEGIN PGP SIGNATURE-----\n\nAAAA\n-----END PGP SIGNATURE-----\n"
+            ),
+            Err(VerificationReason::MalformedSignature)
+        );
+    }
+
+    #[test]
+    fn test_gpg_status_reason() {
+        let good =
+            "[GNUPG:] NEWSIG\n[GNUPG:] GOODSIG EB471A1018D08E7D Alice\n[GNUPG:] VALIDSIG 9FCA\n";
+        assert_eq!(gpg_status_reason(good), VerificationReason::Valid);
+        assert_eq!(
+            gpg_status_reason("[GNUPG:] EXPKEYSIG EB471A1018D08E7D Alice\n"),
+            VerificationReason::ExpiredKey
+        );
+        assert_eq!(
+            gpg_status_reason("[GNUPG:] BADSIG EB471A1018D08E7D Alice\n"),
+            VerificationReason::Invalid
+        );
+        assert_eq!(gpg_status_reason(""), VerificationReason::Invalid);
+    }
+
+    #[test]
+    fn test_parse_gpg_listing() {
+        let listing = "pub:-:255:22:EB471A1018D08E7D:1792040423:::-:::scSC:::::ed25519:::0:\n\
+            fpr:::::::::9FCA00A87A01DA94AE31D364EB471A1018D08E7D:\n\
+            uid:-::::1792040423::AD66::Alice <alice@example.com>::::::::::0:\n\
+            sub:-:255:18:0123456789ABCDEF:1792040423::::::e:::::cv25519::\n\
+            fpr:::::::::11112222333344445555666677778888999900AA:\n";
+        let info = parse_gpg_listing(listing).unwrap();
+        assert_eq!(info.fingerprint, "9FCA00A87A01DA94AE31D364EB471A1018D08E7D");
+        assert_eq!(info.key_ids, ["EB471A1018D08E7D", "0123456789ABCDEF"]);
+        assert_eq!(info.user_id.as_deref(), Some("Alice <alice@example.com>"));
+
+        let two = format!("{listing}{listing}");
+        assert!(parse_gpg_listing(&two).is_err());
+        assert!(parse_gpg_listing("").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_inspect_gpg_key() {
+        if !have_tool("gpg") {
+            return;
+        }
+        let dir = tempfile::TempDir::new().unwrap();
+
+        let info = inspect_gpg_key(dir.path(), TIMEOUT, GPG_KEY).await.unwrap();
+        assert_eq!(info.fingerprint, "9FCA00A87A01DA94AE31D364EB471A1018D08E7D");
+        assert_eq!(info.key_ids, ["EB471A1018D08E7D"]);
+        assert_eq!(info.user_id.as_deref(), Some("Alice <alice@example.com>"));
+
+        let garbage = GPG_KEY.replace("mDMEatBd", "AAAAAAAA");
+        assert!(inspect_gpg_key(dir.path(), TIMEOUT, &garbage).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_signature() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let data_dir = dir.path();
+        if have_tool("gpgv") {
+            let gpg = key(SigningKeyKind::Gpg, GPG_KEY);
+            let checked =
+                check_signature(data_dir, TIMEOUT, &gpg, GPG_SIGNATURE, PAYLOAD.as_bytes()).await;
+            assert_eq!(checked.unwrap(), VerificationReason::Valid);
+            let tampered = PAYLOAD.replace("Signed", "Forged");
+            let checked =
+                check_signature(data_dir, TIMEOUT, &gpg, GPG_SIGNATURE, tampered.as_bytes()).await;
+            assert_eq!(checked.unwrap(), VerificationReason::Invalid);
+        }
+
+        if have_tool("ssh-keygen") {
+            let ssh = key(SigningKeyKind::Ssh, SSH_KEY);
+            let checked =
+                check_signature(data_dir, TIMEOUT, &ssh, SSH_SIGNATURE, PAYLOAD.as_bytes()).await;
+            assert_eq!(checked.unwrap(), VerificationReason::Valid);
+            let tampered = PAYLOAD.replace("Signed", "Forged");
+            let checked =
+                check_signature(data_dir, TIMEOUT, &ssh, SSH_SIGNATURE, tampered.as_bytes()).await;
+            assert_eq!(checked.unwrap(), VerificationReason::Invalid);
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! Removes temporary files that crashes and killed connections leave behind:
+//! partial LFS and package uploads, interrupted Pages builds, git's push quarantine directories and
+//! temporary packs, and stale ref locks that would otherwise block later
+//! pushes.
+//! Archives are streamed from memory and never touch disk.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
+
+use crate::server::AppState;
+use crate::units::format_size;
+
+/// What one sweep removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SweepReport {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+impl SweepReport {
+    fn add(&mut self, other: SweepReport) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Sweep history for the admin maintenance endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct SweepStatus {
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last: SweepReport,
+    /// Totals since the server started.
+    pub total: SweepReport,
+}
+
+pub struct TempSweeper {
+    data_dir: PathBuf,
+    storage_roots: Vec<PathBuf>,
+    max_age: Duration,
+    interval: Duration,
+    status: Mutex<SweepStatus>,
+}
+
+impl TempSweeper {
+    /// Files younger than `max_age` are left alone, since they may belong
+    /// to a push or upload that is still in progress.
+    #[must_use]
+    pub fn new(data_dir: PathBuf, max_age: Duration, interval: Duration) -> Self {
+        Self {
+            data_dir,
+            storage_roots: Vec::new(),
+            max_age,
+            interval,
+            status: Mutex::new(SweepStatus::default()),
+        }
+    }
+
+    /// Also sweeps the repositories and LFS uploads kept in these storage
+    /// roots.
+    #[must_use]
+    pub fn with_storage_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.storage_roots = roots;
+        self
+    }
+
+    #[must_use]
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    #[must_use]
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    #[must_use]
+    pub fn status(&self) -> SweepStatus {
+        self.status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn run(&self) -> SweepReport {
+        let cutoff = SystemTime::now()
+            .checked_sub(self.max_age)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let mut report = sweep(&self.data_dir, cutoff);
+        for root in &self.storage_roots {
+            report.add(sweep_storage_root(root, cutoff));
+        }
+
+        let mut status = self.status.lock().unwrap_or_else(|e| e.into_inner());
+        status.last_run_at = Some(Utc::now());
+        status.last = report;
+        status.total.add(report);
+        report
+    }
+}
+
+/// Sweeps every known temp location under `data_dir`, removing entries last
+/// modified before `cutoff`.
+pub fn sweep(data_dir: &Path, cutoff: SystemTime) -> SweepReport {
+    let mut report = sweep_storage_root(data_dir, cutoff);
+
+    // Package uploads are written to packages/tmp/ and renamed into place.
+    for entry in entries(&data_dir.join("packages/tmp")) {
+        report.add(remove_if_stale(&entry, cutoff));
+    }
+
+    // Pages builds are staged in pages/tmp/ and swapped into place.
+    for entry in entries(&data_dir.join("pages/tmp")) {
+        report.add(remove_if_stale(&entry, cutoff));
+    }
+
+    // Signature checks stage their keyrings in signing/tmp/.
+    for entry in entries(&data_dir.join("signing/tmp")) {
+        report.add(remove_if_stale(&entry, cutoff));
+    }
+
+    report
+}
+
+/// Sweeps the repositories and LFS uploads under the data directory or a
+/// storage root.
+fn sweep_storage_root(root: &Path, cutoff: SystemTime) -> SweepReport {
+    let mut report = SweepReport::default();
+
+    // LFS uploads are written to lfs/<repo>/tmp/ and renamed into place.
+    for repo in subdirs(&root.join("lfs")) {
+        for entry in entries(&repo.join("tmp")) {
+            report.add(remove_if_stale(&entry, cutoff));
+        }
+    }
+
+    for namespace in subdirs(&root.join("repos")) {
+        for repo in subdirs(&namespace) {
+            report.add(sweep_git_dir(&repo, cutoff));
+        }
+    }
+
+    report
+}
+
+fn sweep_git_dir(git_dir: &Path, cutoff: SystemTime) -> SweepReport {
+    let mut report = SweepReport::default();
+    let objects = git_dir.join("objects");
+
+    for entry in entries(&objects) {
+        if file_name_starts_with(&entry, &["tmp_objdir-incoming-", "incoming-", "tmp_obj_"]) {
+            report.add(remove_if_stale(&entry, cutoff));
+        }
+    }
+    for entry in entries(&objects.join("pack")) {
+        if file_name_starts_with(&entry, &["tmp_", ".tmp-"]) {
+            report.add(remove_if_stale(&entry, cutoff));
+        }
+    }
+    for entry in entries(git_dir) {
+        if entry.extension().is_some_and(|e| e == "lock") {
+            report.add(remove_if_stale(&entry, cutoff));
+        }
+    }
+    report.add(sweep_ref_locks(&git_dir.join("refs"), cutoff));
+
+    report
+}
+
+fn sweep_ref_locks(dir: &Path, cutoff: SystemTime) -> SweepReport {
+    let mut report = SweepReport::default();
+    for entry in entries(dir) {
+        if entry.is_dir() {
+            report.add(sweep_ref_locks(&entry, cutoff));
+        } else if entry.extension().is_some_and(|e| e == "lock") {
+            report.add(remove_if_stale(&entry, cutoff));
+        }
+    }
+    report
+}
+
+fn file_name_starts_with(path: &Path, prefixes: &[&str]) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| prefixes.iter().any(|p| n.starts_with(p)))
+}
+
+fn entries(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+}
+
+fn subdirs(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    entries(dir).filter(|p| p.is_dir())
+}
+
+fn remove_if_stale(path: &Path, cutoff: SystemTime) -> SweepReport {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return SweepReport::default();
+    };
+    if metadata.modified().is_ok_and(|m| m >= cutoff) {
+        return SweepReport::default();
+    }
+
+    let (result, report) = if metadata.is_dir() {
+        let report = tree_size(path);
+        (fs::remove_dir_all(path), report)
+    } else {
+        let report = SweepReport {
+            files: 1,
+            bytes: metadata.len(),
+        };
+        (fs::remove_file(path), report)
+    };
+    match result {
+        Ok(()) => report,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => SweepReport::default(),
+        Err(e) => {
+            warn!("Failed to remove {}: {e}", path.display());
+            SweepReport::default()
+        }
+    }
+}
+
+fn tree_size(dir: &Path) -> SweepReport {
+    let mut report = SweepReport::default();
+    for entry in entries(dir) {
+        match fs::symlink_metadata(&entry) {
+            Ok(m) if m.is_dir() => report.add(tree_size(&entry)),
+            Ok(m) => report.add(SweepReport {
+                files: 1,
+                bytes: m.len(),
+            }),
+            Err(_) => {}
+        }
+    }
+    report
+}
+
+/// Sweeps once at startup, to clean up after a crash, then on the
+/// sweeper's interval. Does nothing when the state has no sweeper.
+pub fn spawn_temp_sweeper(state: Arc<AppState>) {
+    let Some(sweeper) = state.sweeper.clone() else {
+        return;
+    };
+    tokio::spawn(async move {
+        loop {
+            let task = sweeper.clone();
+            match tokio::task::spawn_blocking(move || task.run()).await {
+                Ok(report) if report.files > 0 => info!(
+                    "Removed {} stale temp files ({})",
+                    report.files,
+                    format_size(report.bytes as i64)
+                ),
+                Ok(_) => {}
+                Err(e) => warn!("Temp file sweep failed: {e}"),
+            }
+            tokio::time::sleep(sweeper.interval).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, bytes: usize) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![0u8; bytes]).unwrap();
+    }
+
+    #[test]
+    fn test_sweep_removes_only_stale_temp_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let data = dir.path();
+        let git = data.join("repos/ns/demo.git");
+
+        write(&data.join("lfs/repo/tmp/partial"), 10);
+        write(&data.join("packages/tmp/upload-1"), 7);
+        write(&data.join("pages/tmp/repo-1/index.html"), 5);
+        write(
+            &git.join("objects/tmp_objdir-incoming-abc/pack/tmp_pack_1"),
+            20,
+        );
+        write(&git.join("objects/pack/tmp_pack_2"), 30);
+        write(&git.join("refs/heads/main.lock"), 1);
+        write(&git.join("packed-refs.lock"), 1);
+        // Real data that must survive.
+        write(&data.join("lfs/repo/objects/aa/bb/oid"), 5);
+        write(&data.join("packages/ns/tool/1.0/tool.tar.gz"), 5);
+        write(&data.join("pages/sites/repo-1/index.html"), 5);
+        write(&git.join("objects/pack/pack-1.pack"), 5);
+        write(&git.join("refs/heads/main"), 41);
+        write(&git.join("HEAD"), 21);
+
+        // Nothing is old enough yet.
+        let past = SystemTime::now() - Duration::from_secs(3600);
+        assert_eq!(sweep(data, past), SweepReport::default());
+
+        let future = SystemTime::now() + Duration::from_secs(3600);
+        let report = sweep(data, future);
+        assert_eq!(
+            report,
+            SweepReport {
+                files: 7,
+                bytes: 74
+            }
+        );
+
+        assert!(!data.join("lfs/repo/tmp/partial").exists());
+        assert!(!git.join("objects/tmp_objdir-incoming-abc").exists());
+        assert!(!git.join("objects/pack/tmp_pack_2").exists());
+        assert!(!git.join("refs/heads/main.lock").exists());
+        assert!(!data.join("packages/tmp/upload-1").exists());
+        assert!(!data.join("pages/tmp/repo-1").exists());
+        assert!(data.join("lfs/repo/objects/aa/bb/oid").exists());
+        assert!(data.join("packages/ns/tool/1.0/tool.tar.gz").exists());
+        assert!(data.join("pages/sites/repo-1/index.html").exists());
+        assert!(git.join("objects/pack/pack-1.pack").exists());
+        assert!(git.join("refs/heads/main").exists());
+        assert!(git.join("HEAD").exists());
+    }
+}
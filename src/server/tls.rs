@@ -0,0 +1,285 @@
+//! HTTPS for `cutman serve`: a listener that terminates TLS with rustls,
+//! and a certificate that can be reloaded from disk while the server runs.
+
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use axum::serve::Listener;
+use rustls::crypto::{CryptoProvider, ring};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::server::TlsStream;
+use tracing::{debug, error, info, warn};
+
+/// How long a client has to finish the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The certificate chain and key served, read from PEM files.
+#[derive(Debug)]
+pub struct TlsCertificate {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<Loaded>,
+}
+
+#[derive(Debug)]
+struct Loaded {
+    key: Arc<CertifiedKey>,
+    modified: Option<(SystemTime, SystemTime)>,
+}
+
+impl TlsCertificate {
+    /// Reads the certificate chain and key, checking that they belong
+    /// together.
+    pub fn load(cert_path: &Path, key_path: &Path) -> anyhow::Result<Self> {
+        let modified = modified(cert_path, key_path);
+        let key = load_key(cert_path, key_path)?;
+        Ok(Self {
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            current: RwLock::new(Loaded { key, modified }),
+        })
+    }
+
+    /// Reloads the files if either has changed since they were last read,
+    /// returning whether the served certificate changed. On an error the
+    /// old certificate stays in use, and the files are read again next
+    /// time, since a renewal may have replaced only one of them so far.
+    pub fn reload_if_changed(&self) -> anyhow::Result<bool> {
+        let modified = modified(&self.cert_path, &self.key_path);
+        if modified == self.current.read().expect("certificate lock").modified {
+            return Ok(false);
+        }
+        let key = load_key(&self.cert_path, &self.key_path)?;
+        *self.current.write().expect("certificate lock") = Loaded { key, modified };
+        Ok(true)
+    }
+
+    /// A rustls config that serves whichever certificate is current.
+    pub fn server_config(self: &Arc<Self>) -> anyhow::Result<Arc<rustls::ServerConfig>> {
+        let mut config = rustls::ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone());
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+
+    fn key(&self) -> Arc<CertifiedKey> {
+        self.current.read().expect("certificate lock").key.clone()
+    }
+}
+
+impl ResolvesServerCert for TlsCertificate {
+    fn resolve(&self, _hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.key())
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn load_key(cert_path: &Path, key_path: &Path) -> anyhow::Result<Arc<CertifiedKey>> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("Failed to read certificates from {}", cert_path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("{} holds no certificates", cert_path.display());
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .with_context(|| format!("Failed to read private key from {}", key_path.display()))?;
+    let key = CertifiedKey::from_der(certs, key, &provider()).with_context(|| {
+        format!(
+            "{} is not the key for {}",
+            key_path.display(),
+            cert_path.display()
+        )
+    })?;
+    Ok(Arc::new(key))
+}
+
+fn modified(cert_path: &Path, key_path: &Path) -> Option<(SystemTime, SystemTime)> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    Some((modified(cert_path)?, modified(key_path)?))
+}
+
+/// Checks the certificate files every `interval` and serves the new
+/// certificate once they change.
+pub fn spawn_tls_reload(certificate: Arc<TlsCertificate>, interval: Duration) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            let task = certificate.clone();
+            match tokio::task::spawn_blocking(move || task.reload_if_changed()).await {
+                Ok(Ok(true)) => info!(
+                    "Reloaded TLS certificate from {}",
+                    certificate.cert_path.display()
+                ),
+                Ok(Ok(false)) => {}
+                Ok(Err(e)) => warn!("Keeping the current TLS certificate: {e:#}"),
+                Err(e) => warn!("TLS certificate reload failed: {e}"),
+            }
+        }
+    });
+}
+
+/// Accepts TCP connections and hands them to the server once their TLS
+/// handshake completes. Handshakes run concurrently, so a slow client
+/// doesn't hold up the ones behind it.
+pub struct TlsListener {
+    tcp: TcpListener,
+    acceptor: TlsAcceptor,
+    handshakes: JoinSet<Option<(TlsStream<TcpStream>, SocketAddr)>>,
+}
+
+impl TlsListener {
+    #[must_use]
+    pub fn new(tcp: TcpListener, config: Arc<rustls::ServerConfig>) -> Self {
+        Self {
+            tcp,
+            acceptor: TlsAcceptor::from(config),
+            handshakes: JoinSet::new(),
+        }
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            tokio::select! {
+                accepted = self.tcp.accept() => match accepted {
+                    Ok((stream, addr)) => {
+                        let acceptor = self.acceptor.clone();
+                        self.handshakes.spawn(async move {
+                            match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                                Ok(Ok(stream)) => Some((stream, addr)),
+                                Ok(Err(e)) => {
+                                    debug!("TLS handshake with {addr} failed: {e}");
+                                    None
+                                }
+                                Err(_) => {
+                                    debug!("TLS handshake with {addr} timed out");
+                                    None
+                                }
+                            }
+                        });
+                    }
+                    Err(e) => accept_error(e).await,
+                },
+                Some(handshake) = self.handshakes.join_next() => {
+                    if let Ok(Some(connection)) = handshake {
+                        return connection;
+                    }
+                }
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.tcp.local_addr()
+    }
+}
+
+/// Errors for a single connection are skipped; anything else, such as
+/// running out of file descriptors, gets a pause before trying again.
+async fn accept_error(e: io::Error) {
+    if matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    ) {
+        return;
+    }
+    error!("Failed to accept connection: {e}");
+    tokio::time::sleep(Duration::from_secs(1)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+
+    /// Writes a fresh self-signed certificate and key, or returns false
+    /// when there's no `openssl` to make one.
+    fn self_signed(cert: &Path, key: &Path) -> bool {
+        let output = Command::new("openssl")
+            .args(["req", "-x509", "-newkey", "ec"])
+            .args(["-pkeyopt", "ec_paramgen_curve:prime256v1"])
+            .args(["-nodes", "-days", "1", "-subj", "/CN=localhost"])
+            .arg("-keyout")
+            .arg(key)
+            .arg("-out")
+            .arg(cert)
+            .output();
+        match output {
+            Ok(output) => {
+                assert!(output.status.success(), "openssl failed: {output:?}");
+                true
+            }
+            Err(_) => {
+                eprintln!("Skipping TLS test: openssl not found in PATH");
+                false
+            }
+        }
+    }
+
+    #[test]
+    fn test_reload_if_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, key) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+        if !self_signed(&cert, &key) {
+            return;
+        }
+        let certificate = TlsCertificate::load(&cert, &key).unwrap();
+        let first = certificate.key();
+        assert!(!certificate.reload_if_changed().unwrap());
+
+        // A new certificate with the old key is refused, and the old pair
+        // keeps being served until the key arrives too.
+        let (next_cert, next_key) = (dir.path().join("next.pem"), dir.path().join("next.key"));
+        assert!(self_signed(&next_cert, &next_key));
+        let later = SystemTime::now() + Duration::from_secs(5);
+        fs::copy(&next_cert, &cert).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&cert)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert!(certificate.reload_if_changed().is_err());
+        assert_eq!(certificate.key().cert, first.cert);
+
+        fs::copy(&next_key, &key).unwrap();
+        assert!(certificate.reload_if_changed().unwrap());
+        assert_ne!(certificate.key().cert, first.cert);
+        assert!(!certificate.reload_if_changed().unwrap());
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, key) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+        let err = TlsCertificate::load(&cert, &key).unwrap_err();
+        assert!(err.to_string().contains("cert.pem"), "{err}");
+
+        fs::write(&cert, "not a certificate").unwrap();
+        let err = TlsCertificate::load(&cert, &key).unwrap_err();
+        assert!(err.to_string().contains("no certificates"), "{err}");
+    }
+}
@@ -1,67 +1,32 @@
+use crate::server::AppState;
 use crate::server::response::{ApiError, StoreResultExt};
 use crate::store::Store;
 use crate::types::{Permission, Principal, Repo};
 
-/// Returns true if principal has the required permission for a namespace.
-/// Primary namespace owners have full access.
+/// Returns true if principal has the required permission for a namespace,
+/// as decided by the server's [`Authorizer`](crate::server::authz::Authorizer).
 pub fn check_namespace_permission(
-    store: &dyn Store,
+    state: &AppState,
     principal: &Principal,
     namespace_id: &str,
     required: Permission,
 ) -> Result<bool, ApiError> {
-    if principal.primary_namespace_id == namespace_id {
-        return Ok(true);
-    }
-
-    let grant = store
-        .get_namespace_grant(&principal.id, namespace_id)
-        .api_err("Failed to check namespace grant")?;
-
-    Ok(grant
-        .map(|g| {
-            g.allow_bits
-                .expand_implied()
-                .difference(g.deny_bits)
-                .has(required)
-        })
-        .unwrap_or(false))
+    state
+        .authorizer
+        .namespace_permission(state.store.as_ref(), principal, namespace_id, required)
 }
 
-/// Returns true if principal has the required permission for a repo.
-/// Checks both namespace-level and repo-level grants.
+/// Returns true if principal has the required permission for a repo,
+/// as decided by the server's [`Authorizer`](crate::server::authz::Authorizer).
 pub fn check_repo_permission(
-    store: &dyn Store,
+    state: &AppState,
     principal: &Principal,
     repo: &Repo,
     required: Permission,
 ) -> Result<bool, ApiError> {
-    if principal.primary_namespace_id == repo.namespace_id {
-        return Ok(true);
-    }
-
-    let ns_grant = store
-        .get_namespace_grant(&principal.id, &repo.namespace_id)
-        .api_err("Failed to check namespace grant")?;
-
-    let repo_grant = store
-        .get_repo_grant(&principal.id, &repo.id)
-        .api_err("Failed to check repo grant")?;
-
-    let mut allow = Permission::default();
-    let mut deny = Permission::default();
-
-    if let Some(grant) = ns_grant {
-        allow = allow.union(grant.allow_bits.expand_implied());
-        deny = deny.union(grant.deny_bits);
-    }
-
-    if let Some(grant) = repo_grant {
-        allow = allow.union(grant.allow_bits.expand_implied());
-        deny = deny.union(grant.deny_bits);
-    }
-
-    Ok(allow.difference(deny).has(required))
+    state
+        .authorizer
+        .repo_permission(state.store.as_ref(), principal, repo, required)
 }
 
 /// Resolves namespace from optional name or uses principal's primary namespace.
@@ -84,12 +49,12 @@ pub fn resolve_namespace_id(
 
 /// Check if principal has the required namespace permission, returning forbidden error if not.
 pub fn require_namespace_permission(
-    store: &dyn Store,
+    state: &AppState,
     principal: &Principal,
     namespace_id: &str,
     required: Permission,
 ) -> Result<(), ApiError> {
-    if !check_namespace_permission(store, principal, namespace_id, required)? {
+    if !check_namespace_permission(state, principal, namespace_id, required)? {
         return Err(ApiError::forbidden("Insufficient namespace permissions"));
     }
     Ok(())
@@ -97,12 +62,12 @@ pub fn require_namespace_permission(
 
 /// Check if principal has the required repo permission, returning forbidden error if not.
 pub fn require_repo_permission(
-    store: &dyn Store,
+    state: &AppState,
     principal: &Principal,
     repo: &Repo,
     required: Permission,
 ) -> Result<(), ApiError> {
-    if !check_repo_permission(store, principal, repo, required)? {
+    if !check_repo_permission(state, principal, repo, required)? {
         return Err(ApiError::forbidden("Insufficient repository permissions"));
     }
     Ok(())
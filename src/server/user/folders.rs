@@ -27,7 +27,7 @@ pub async fn list_folders(
     let store = state.store.as_ref();
     let ns_id = resolve_namespace_id(store, principal, params.namespace.as_deref())?;
 
-    require_namespace_permission(store, principal, &ns_id, Permission::NAMESPACE_READ)?;
+    require_namespace_permission(&state, principal, &ns_id, Permission::NAMESPACE_READ)?;
 
     let folders = store
         .list_all_folders(&ns_id)
@@ -45,7 +45,7 @@ pub async fn create_folder(
     let store = state.store.as_ref();
     let ns_id = resolve_namespace_id(store, principal, req.namespace.as_deref())?;
 
-    require_namespace_permission(store, principal, &ns_id, Permission::NAMESPACE_WRITE)?;
+    require_namespace_permission(&state, principal, &ns_id, Permission::NAMESPACE_WRITE)?;
 
     let normalized_path =
         normalize_path(&req.path).map_err(|e| ApiError::bad_request(e.to_string()))?;
@@ -84,7 +84,7 @@ pub async fn get_folder(
         .or_not_found("Folder not found")?;
 
     require_namespace_permission(
-        store,
+        &state,
         principal,
         &folder.namespace_id,
         Permission::NAMESPACE_READ,
@@ -108,7 +108,7 @@ pub async fn update_folder(
         .or_not_found("Folder not found")?;
 
     require_namespace_permission(
-        store,
+        &state,
         principal,
         &folder.namespace_id,
         Permission::NAMESPACE_WRITE,
@@ -147,7 +147,7 @@ pub async fn delete_folder(
         .or_not_found("Folder not found")?;
 
     require_namespace_permission(
-        store,
+        &state,
         principal,
         &folder.namespace_id,
         Permission::NAMESPACE_ADMIN,
@@ -173,7 +173,7 @@ pub async fn list_folder_repos(
         .or_not_found("Folder not found")?;
 
     require_namespace_permission(
-        store,
+        &state,
         principal,
         &folder.namespace_id,
         Permission::NAMESPACE_READ,
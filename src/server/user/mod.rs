@@ -1,9 +1,22 @@
 pub mod access;
 mod folders;
+mod namespace_email_policy;
 mod namespaces;
+mod packages;
+mod repo_commit_policy;
 mod repo_folder;
+mod repo_init;
+mod repo_export;
+mod repo_lfs;
+mod repo_mirror;
+mod repo_pages;
+mod repo_size;
 mod repo_tags;
 mod repos;
+mod setup;
+mod signing_keys;
+mod ssh_keys;
+pub mod sync;
 mod tags;
 
 use std::sync::Arc;
@@ -17,6 +30,22 @@ use crate::server::AppState;
 
 pub fn user_router() -> Router<Arc<AppState>> {
     Router::new()
+        // Client setup
+        .route("/capabilities", get(setup::get_capabilities))
+        .route("/user/setup", get(setup::get_setup))
+        // Delta sync
+        .route("/sync", get(sync::sync))
+        // SSH keys
+        .route("/user/ssh-keys", get(ssh_keys::list_ssh_keys))
+        .route("/user/ssh-keys", post(ssh_keys::create_ssh_key))
+        .route("/user/ssh-keys/{id}", delete(ssh_keys::delete_ssh_key))
+        // Commit signing keys
+        .route("/user/signing-keys", get(signing_keys::list_signing_keys))
+        .route("/user/signing-keys", post(signing_keys::create_signing_key))
+        .route(
+            "/user/signing-keys/{id}",
+            delete(signing_keys::delete_signing_key),
+        )
         // Namespaces
         .route("/namespaces", get(namespaces::list_namespaces))
         .route("/namespaces/{name}", patch(namespaces::update_namespace))
@@ -25,6 +54,25 @@ pub fn user_router() -> Router<Arc<AppState>> {
             "/namespaces/{name}/grants",
             get(namespaces::list_namespace_grants),
         )
+        .route(
+            "/namespaces/{name}/email-policy",
+            get(namespace_email_policy::get_email_policy),
+        )
+        .route(
+            "/namespaces/{name}/email-policy",
+            put(namespace_email_policy::update_email_policy),
+        )
+        .route(
+            "/namespaces/{name}/email-policy",
+            delete(namespace_email_policy::delete_email_policy),
+        )
+        // Packages
+        .route(
+            "/namespaces/{name}/packages/{package}/{version}/{filename}",
+            get(packages::download_package_file)
+                .put(packages::upload_package_file)
+                .delete(packages::delete_package_file),
+        )
         // Repos
         .route("/repos", get(repos::list_repos))
         .route("/repos", post(repos::create_repo))
@@ -32,6 +80,55 @@ pub fn user_router() -> Router<Arc<AppState>> {
         .route("/repos/{id}", patch(repos::update_repo))
         .route("/repos/{id}", delete(repos::delete_repo))
         // Repo tags (many-to-many)
+        .route("/repos/{id}/mirror", get(repo_mirror::get_repo_mirror))
+        .route(
+            "/repos/{id}/mirror",
+            delete(repo_mirror::delete_repo_mirror),
+        )
+        .route(
+            "/repos/{id}/mirror/sync",
+            post(repo_mirror::sync_repo_mirror),
+        )
+        // Alias of the above for webhook relays
+        .route("/repos/{id}/sync", post(repo_mirror::sync_repo_mirror))
+        // LFS
+        .route("/repos/{id}/lfs/usage", get(repo_lfs::get_repo_lfs_usage))
+        .route(
+            "/repos/{id}/size-history",
+            get(repo_size::get_repo_size_history),
+        )
+        // Scheduled exports
+        .route("/repos/{id}/export", get(repo_export::get_repo_export))
+        .route("/repos/{id}/export", put(repo_export::update_repo_export))
+        .route(
+            "/repos/{id}/export",
+            delete(repo_export::delete_repo_export),
+        )
+        .route(
+            "/repos/{id}/export/sync",
+            post(repo_export::sync_repo_export),
+        )
+        // Pages
+        .route("/repos/{id}/pages", get(repo_pages::get_repo_pages))
+        .route("/repos/{id}/pages", put(repo_pages::update_repo_pages))
+        .route("/repos/{id}/pages", delete(repo_pages::delete_repo_pages))
+        .route(
+            "/repos/{id}/pages/build",
+            post(repo_pages::build_repo_pages),
+        )
+        // Commit message policy
+        .route(
+            "/repos/{id}/commit-policy",
+            get(repo_commit_policy::get_commit_policy),
+        )
+        .route(
+            "/repos/{id}/commit-policy",
+            put(repo_commit_policy::update_commit_policy),
+        )
+        .route(
+            "/repos/{id}/commit-policy",
+            delete(repo_commit_policy::delete_commit_policy),
+        )
         .route("/repos/{id}/tags", get(repo_tags::list_repo_tags))
         .route("/repos/{id}/tags", post(repo_tags::add_repo_tags))
         .route("/repos/{id}/tags", put(repo_tags::set_repo_tags))
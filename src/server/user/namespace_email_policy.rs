@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+use crate::auth::RequirePrincipal;
+use crate::email_policy::EmailPolicy;
+use crate::server::AppState;
+use crate::server::dto::EmailPolicyResponse;
+use crate::server::response::{ApiError, ApiResponse, StoreOptionExt, StoreResultExt};
+use crate::types::{Namespace, Permission};
+
+use super::access::require_namespace_permission;
+
+fn load_namespace(
+    state: &AppState,
+    auth: &RequirePrincipal,
+    name: &str,
+    permission: Permission,
+) -> Result<Namespace, ApiError> {
+    let ns = state
+        .store
+        .get_namespace_by_name(name)
+        .api_err("Failed to get namespace")?
+        .or_not_found("Namespace not found")?;
+
+    require_namespace_permission(state, &auth.principal, &ns.id, permission)?;
+
+    Ok(ns)
+}
+
+/// GET /namespaces/{name}/email-policy - Which emails pushed commits may carry
+pub async fn get_email_policy(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let ns = load_namespace(&state, &auth, &name, Permission::NAMESPACE_READ)?;
+    let policy = state
+        .store
+        .get_namespace_email_policy(&ns.id)
+        .api_err("Failed to get email policy")?
+        .or_not_found("This namespace has no email policy")?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(EmailPolicyResponse {
+        namespace: ns.name,
+        policy,
+    })))
+}
+
+/// PUT /namespaces/{name}/email-policy - Restrict the author and committer
+/// emails of commits pushed to any repo in the namespace
+pub async fn update_email_policy(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Json(mut policy): Json<EmailPolicy>,
+) -> impl IntoResponse {
+    let ns = load_namespace(&state, &auth, &name, Permission::NAMESPACE_ADMIN)?;
+    policy.normalize().map_err(ApiError::bad_request)?;
+
+    state
+        .store
+        .set_namespace_email_policy(&ns.id, &policy)
+        .api_err("Failed to save email policy")?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(EmailPolicyResponse {
+        namespace: ns.name,
+        policy,
+    })))
+}
+
+/// DELETE /namespaces/{name}/email-policy - Accept any commit email again
+pub async fn delete_email_policy(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let ns = load_namespace(&state, &auth, &name, Permission::NAMESPACE_ADMIN)?;
+
+    let deleted = state
+        .store
+        .delete_namespace_email_policy(&ns.id)
+        .api_err("Failed to delete email policy")?;
+    if !deleted {
+        return Err(ApiError::not_found("This namespace has no email policy"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
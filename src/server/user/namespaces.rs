@@ -10,6 +10,7 @@ use axum::{
 use crate::auth::RequirePrincipal;
 use crate::server::AppState;
 use crate::server::dto::{NamespaceResponse, PrincipalGrantResponse, UpdateNamespaceRequest};
+use crate::server::legal_hold::check_namespace_deletable;
 use crate::server::response::{ApiError, ApiResponse, StoreOptionExt, StoreResultExt};
 use crate::types::Permission;
 
@@ -90,7 +91,7 @@ pub async fn update_namespace(
         .api_err("Failed to get namespace")?
         .or_not_found("Namespace not found")?;
 
-    require_namespace_permission(store, principal, &ns.id, Permission::NAMESPACE_ADMIN)?;
+    require_namespace_permission(&state, principal, &ns.id, Permission::NAMESPACE_ADMIN)?;
 
     if let Some(limit) = req.repo_limit {
         ns.repo_limit = Some(limit);
@@ -123,7 +124,8 @@ pub async fn delete_namespace(
         return Err(ApiError::forbidden("Cannot delete your primary namespace"));
     }
 
-    require_namespace_permission(store, principal, &ns.id, Permission::NAMESPACE_ADMIN)?;
+    require_namespace_permission(&state, principal, &ns.id, Permission::NAMESPACE_ADMIN)?;
+    check_namespace_deletable(&state, &ns.id)?;
 
     store
         .delete_namespace(&ns.id)
@@ -145,7 +147,7 @@ pub async fn list_namespace_grants(
         .api_err("Failed to get namespace")?
         .or_not_found("Namespace not found")?;
 
-    require_namespace_permission(store, principal, &ns.id, Permission::NAMESPACE_ADMIN)?;
+    require_namespace_permission(&state, principal, &ns.id, Permission::NAMESPACE_ADMIN)?;
 
     let grants = store
         .list_namespace_grants_for_namespace(&ns.id)
@@ -0,0 +1,276 @@
+//! Generic package registry: arbitrary files (binaries, tarballs) published
+//! under a namespace as `<package>/<version>/<filename>`. Published files are
+//! immutable, count towards the namespace's storage limit, and need the
+//! namespace's read or write permission to download or publish.
+
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use crate::auth::RequirePrincipal;
+use crate::error::Error;
+use crate::quota;
+use crate::server::AppState;
+use crate::server::response::{ApiError, ApiResponse, StoreOptionExt, StoreResultExt};
+use crate::server::validation::validate_package_name;
+use crate::types::{Namespace, PackageFile, Permission};
+use crate::units::format_size;
+
+use super::access::require_namespace_permission;
+
+/// Optional header carrying the hex SHA-256 the upload must match. Downloads
+/// return it too.
+const CHECKSUM_HEADER: &str = "X-Checksum-Sha256";
+
+fn package_path(data_dir: &FsPath, file: &PackageFile) -> PathBuf {
+    data_dir
+        .join("packages")
+        .join(&file.namespace_id)
+        .join(&file.package)
+        .join(&file.version)
+        .join(&file.filename)
+}
+
+/// Resolves the namespace and validates the file's address.
+fn resolve_package(
+    state: &AppState,
+    auth: &RequirePrincipal,
+    (name, package, version, filename): &(String, String, String, String),
+    required: Permission,
+) -> Result<Namespace, ApiError> {
+    let store = state.store.as_ref();
+    let namespace = store
+        .get_namespace_by_name(name)
+        .api_err("Failed to get namespace")?
+        .or_not_found("Namespace not found")?;
+    require_namespace_permission(state, &auth.principal, &namespace.id, required)?;
+
+    validate_package_name(package, "Package")?;
+    validate_package_name(version, "Version")?;
+    validate_package_name(filename, "File")?;
+    Ok(namespace)
+}
+
+fn storage_limit_error(needed: i64, limit: i64) -> ApiError {
+    ApiError::forbidden(format!(
+        "Namespace storage limit exceeded: this upload needs {} of {}",
+        format_size(needed),
+        format_size(limit)
+    ))
+}
+
+/// Writes the request body to `path`, returning its size and SHA-256. Stops
+/// as soon as the upload passes `max_size`, returning the size read so far.
+async fn write_body(
+    path: &FsPath,
+    body: Body,
+    max_size: Option<u64>,
+) -> std::io::Result<Result<(u64, String), u64>> {
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(std::io::Error::other)?;
+        size += chunk.len() as u64;
+        if max_size.is_some_and(|max| size > max) {
+            return Ok(Err(size));
+        }
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+    }
+    file.sync_all().await?;
+    Ok(Ok((size, hex::encode(hasher.finalize()))))
+}
+
+async fn install(tmp_path: &FsPath, path: &FsPath) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::rename(tmp_path, path).await
+}
+
+/// PUT /namespaces/{name}/packages/{package}/{version}/{filename}
+pub async fn upload_package_file(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<(String, String, String, String)>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<impl IntoResponse, ApiError> {
+    let namespace = resolve_package(&state, &auth, &params, Permission::NAMESPACE_WRITE)?;
+    let (_, package, version, filename) = params;
+    let store = state.store.as_ref();
+
+    if !state.allows_writes() {
+        return Err(ApiError::insufficient_storage(
+            "Server is low on disk space; uploads are temporarily disabled",
+        ));
+    }
+    if store
+        .get_package_file(&namespace.id, &package, &version, &filename)
+        .api_err("Failed to get package file")?
+        .is_some()
+    {
+        return Err(ApiError::conflict(
+            "Package file already exists; published versions cannot be replaced",
+        ));
+    }
+
+    let expected_checksum = headers
+        .get(CHECKSUM_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_ascii_lowercase);
+
+    // Refuse early when the declared size is already over quota, and stop
+    // reading the body once it goes over.
+    let usage = quota::storage_usage(store, &namespace).api_err("Failed to read storage usage")?;
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i64>().ok());
+    if let (Some(usage), Some(length)) = (usage, content_length)
+        && usage.check(length).is_some()
+    {
+        return Err(storage_limit_error(usage.used + length, usage.limit));
+    }
+    let room = usage.map(|u| u.limit.saturating_sub(u.used).max(0) as u64);
+
+    let tmp_dir = state.data_dir.join("packages/tmp");
+    tokio::fs::create_dir_all(&tmp_dir)
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to create upload directory: {e}")))?;
+    let tmp_path = tmp_dir.join(format!("upload-{}", Uuid::new_v4()));
+
+    let (size, sha256) = match write_body(&tmp_path, body, room).await {
+        Ok(Ok(written)) => written,
+        Ok(Err(size)) => {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            let (used, limit) = usage.map_or((0, 0), |u| (u.used, u.limit));
+            return Err(storage_limit_error(used + size as i64, limit));
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(ApiError::bad_request(format!("Failed to read upload: {e}")));
+        }
+    };
+    if expected_checksum.is_some_and(|expected| expected != sha256) {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(ApiError::bad_request(format!(
+            "Checksum mismatch: upload has SHA-256 {sha256}"
+        )));
+    }
+
+    let file = PackageFile {
+        namespace_id: namespace.id.clone(),
+        package,
+        version,
+        filename,
+        size: size as i64,
+        sha256,
+        created_at: Utc::now(),
+    };
+    match store.create_package_file(&file) {
+        Ok(()) => {}
+        Err(Error::AlreadyExists) => {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(ApiError::conflict(
+                "Package file already exists; published versions cannot be replaced",
+            ));
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(ApiError::internal(format!(
+                "Failed to record package file: {e}"
+            )));
+        }
+    }
+
+    if let Err(e) = install(&tmp_path, &package_path(&state.data_dir, &file)).await {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        let _ = store.delete_package_file(
+            &file.namespace_id,
+            &file.package,
+            &file.version,
+            &file.filename,
+        );
+        return Err(ApiError::internal(format!(
+            "Failed to store package file: {e}"
+        )));
+    }
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(file))))
+}
+
+/// GET /namespaces/{name}/packages/{package}/{version}/{filename}
+pub async fn download_package_file(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<(String, String, String, String)>,
+) -> Result<Response, ApiError> {
+    let namespace = resolve_package(&state, &auth, &params, Permission::NAMESPACE_READ)?;
+    let (_, package, version, filename) = &params;
+
+    let file = state
+        .store
+        .get_package_file(&namespace.id, package, version, filename)
+        .api_err("Failed to get package file")?
+        .or_not_found("Package file not found")?;
+    let content = tokio::fs::File::open(package_path(&state.data_dir, &file))
+        .await
+        .map_err(|e| ApiError::internal(format!("Failed to open package file: {e}")))?;
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_LENGTH, file.size)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file.filename),
+        )
+        .header(header::ETAG, format!("\"{}\"", file.sha256))
+        .header(CHECKSUM_HEADER, &file.sha256)
+        .header("X-Content-Type-Options", "nosniff")
+        .body(Body::from_stream(ReaderStream::new(content)))
+        .map_err(|e| ApiError::internal(format!("Failed to build response: {e}")))
+}
+
+/// DELETE /namespaces/{name}/packages/{package}/{version}/{filename}
+pub async fn delete_package_file(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(params): Path<(String, String, String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    let namespace = resolve_package(&state, &auth, &params, Permission::NAMESPACE_WRITE)?;
+    let (_, package, version, filename) = &params;
+    let store = state.store.as_ref();
+
+    let file = store
+        .get_package_file(&namespace.id, package, version, filename)
+        .api_err("Failed to get package file")?
+        .or_not_found("Package file not found")?;
+    store
+        .delete_package_file(&namespace.id, package, version, filename)
+        .api_err("Failed to delete package file")?;
+
+    match tokio::fs::remove_file(package_path(&state.data_dir, &file)).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => tracing::warn!("Failed to remove package file: {e}"),
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+use crate::auth::RequirePrincipal;
+use crate::commit_policy::CommitPolicy;
+use crate::server::AppState;
+use crate::server::dto::CommitPolicyResponse;
+use crate::server::response::{ApiError, ApiResponse, StoreOptionExt, StoreResultExt};
+use crate::types::{Permission, Repo};
+
+use super::access::require_repo_permission;
+
+fn load_repo(
+    state: &AppState,
+    auth: &RequirePrincipal,
+    id: &str,
+    permission: Permission,
+) -> Result<Repo, ApiError> {
+    let repo = state
+        .store
+        .get_repo_by_id(id)
+        .api_err("Failed to get repo")?
+        .or_not_found("Repository not found")?;
+
+    require_repo_permission(state, &auth.principal, &repo, permission)?;
+
+    Ok(repo)
+}
+
+/// GET /repos/{id}/commit-policy - The repo's commit message rules
+pub async fn get_commit_policy(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let repo = load_repo(&state, &auth, &id, Permission::REPO_READ)?;
+    let policy = state
+        .store
+        .get_repo_commit_policy(&repo.id)
+        .api_err("Failed to get commit policy")?
+        .or_not_found("This repository has no commit policy")?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(CommitPolicyResponse {
+        repo_id: repo.id,
+        policy,
+    })))
+}
+
+/// PUT /repos/{id}/commit-policy - Set the rules every new commit message
+/// must meet, on push and through the content API
+pub async fn update_commit_policy(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(policy): Json<CommitPolicy>,
+) -> impl IntoResponse {
+    let repo = load_repo(&state, &auth, &id, Permission::REPO_ADMIN)?;
+    policy.validate().map_err(ApiError::bad_request)?;
+
+    state
+        .store
+        .set_repo_commit_policy(&repo.id, &policy)
+        .api_err("Failed to save commit policy")?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(CommitPolicyResponse {
+        repo_id: repo.id,
+        policy,
+    })))
+}
+
+/// DELETE /repos/{id}/commit-policy - Stop checking commit messages
+pub async fn delete_commit_policy(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let repo = load_repo(&state, &auth, &id, Permission::REPO_ADMIN)?;
+
+    let deleted = state
+        .store
+        .delete_repo_commit_policy(&repo.id)
+        .api_err("Failed to delete commit policy")?;
+    if !deleted {
+        return Err(ApiError::not_found("This repository has no commit policy"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::Utc;
+
+use crate::auth::RequirePrincipal;
+use crate::server::AppState;
+use crate::server::dto::{ExportResponse, UpdateExportRequest};
+use crate::server::git::export::{
+    DEFAULT_EXPORT_INCLUDE, DEFAULT_EXPORT_INTERVAL_SECS, MIN_EXPORT_INTERVAL_SECS,
+    validate_ref_pattern,
+};
+use crate::server::git::mirror::{redact_url, validate_remote_url};
+use crate::server::response::{ApiError, ApiResponse, StoreOptionExt, StoreResultExt};
+use crate::types::{Permission, Repo, RepoExport};
+
+use super::access::require_repo_permission;
+
+fn export_to_response(state: &AppState, export: RepoExport) -> ExportResponse {
+    let last_sync_status = match (&export.last_sync_at, &export.last_error) {
+        (None, _) => "pending",
+        (Some(_), None) => "success",
+        (Some(_), Some(_)) => "failed",
+    };
+    ExportResponse {
+        syncing: state
+            .exports
+            .as_ref()
+            .is_some_and(|e| e.is_syncing(&export.repo_id)),
+        url: redact_url(&export.url),
+        repo_id: export.repo_id,
+        interval_secs: export.interval_secs,
+        include: export.include,
+        exclude: export.exclude,
+        last_sync_status,
+        last_sync_at: export.last_sync_at,
+        exported_refs: export.exported_refs,
+        last_error: export.last_error,
+    }
+}
+
+fn load_repo(
+    state: &AppState,
+    auth: &RequirePrincipal,
+    id: &str,
+    permission: Permission,
+) -> Result<Repo, ApiError> {
+    let store = state.store.as_ref();
+
+    let repo = store
+        .get_repo_by_id(id)
+        .api_err("Failed to get repo")?
+        .or_not_found("Repository not found")?;
+
+    require_repo_permission(state, &auth.principal, &repo, permission)?;
+
+    Ok(repo)
+}
+
+fn load_export(state: &AppState, repo: &Repo) -> Result<RepoExport, ApiError> {
+    state
+        .store
+        .get_repo_export(&repo.id)
+        .api_err("Failed to get export")?
+        .or_not_found("Repository has no export")
+}
+
+fn validate_patterns(patterns: &[String]) -> Result<(), ApiError> {
+    patterns
+        .iter()
+        .try_for_each(|p| validate_ref_pattern(p))
+        .map_err(ApiError::bad_request)
+}
+
+/// GET /repos/{id}/export - Export settings and last sync status
+pub async fn get_repo_export(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let repo = load_repo(&state, &auth, &id, Permission::REPO_READ)?;
+    let export = load_export(&state, &repo)?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(export_to_response(
+        &state, export,
+    ))))
+}
+
+/// PUT /repos/{id}/export - Create or change the export, syncing it now
+pub async fn update_repo_export(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateExportRequest>,
+) -> impl IntoResponse {
+    let repo = load_repo(&state, &auth, &id, Permission::REPO_ADMIN)?;
+    let current = state
+        .store
+        .get_repo_export(&repo.id)
+        .api_err("Failed to get export")?;
+
+    let url = match (req.url, &current) {
+        (Some(url), _) => {
+            validate_remote_url(&url).map_err(|e| ApiError::bad_request(format!("Export {e}")))?;
+            url.trim().to_string()
+        }
+        (None, Some(current)) => current.url.clone(),
+        (None, None) => return Err(ApiError::bad_request("Export URL is required")),
+    };
+    let interval_secs = req
+        .interval_secs
+        .or(current.as_ref().map(|c| c.interval_secs))
+        .unwrap_or(DEFAULT_EXPORT_INTERVAL_SECS);
+    if interval_secs < MIN_EXPORT_INTERVAL_SECS {
+        return Err(ApiError::bad_request(format!(
+            "Export interval must be at least {MIN_EXPORT_INTERVAL_SECS} seconds"
+        )));
+    }
+    let include = req
+        .include
+        .or(current.as_ref().map(|c| c.include.clone()))
+        .unwrap_or_else(|| {
+            DEFAULT_EXPORT_INCLUDE
+                .iter()
+                .map(|p| p.to_string())
+                .collect()
+        });
+    if include.is_empty() {
+        return Err(ApiError::bad_request(
+            "Export include rules must list at least one ref pattern",
+        ));
+    }
+    let exclude = req
+        .exclude
+        .or(current.map(|c| c.exclude))
+        .unwrap_or_default();
+    validate_patterns(&include)?;
+    validate_patterns(&exclude)?;
+
+    state
+        .store
+        .set_repo_export(&RepoExport {
+            repo_id: repo.id.clone(),
+            url,
+            interval_secs,
+            include,
+            exclude,
+            created_at: Utc::now(),
+            last_sync_at: None,
+            exported_refs: 0,
+            last_error: None,
+        })
+        .api_err("Failed to save export")?;
+    if let Some(exports) = &state.exports {
+        exports.request(&repo.id);
+    }
+
+    let export = load_export(&state, &repo)?;
+    Ok(Json(ApiResponse::success(export_to_response(
+        &state, export,
+    ))))
+}
+
+/// POST /repos/{id}/export/sync - Push to the remote without waiting for
+/// the interval
+pub async fn sync_repo_export(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let repo = load_repo(&state, &auth, &id, Permission::REPO_WRITE)?;
+    let export = load_export(&state, &repo)?;
+
+    let scheduler = state
+        .exports
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Export sync is not enabled"))?;
+    scheduler.request(&repo.id);
+
+    Ok::<_, ApiError>((
+        StatusCode::ACCEPTED,
+        Json(ApiResponse::success(export_to_response(&state, export))),
+    ))
+}
+
+/// DELETE /repos/{id}/export - Stop exporting; refs already on the remote
+/// are left there
+pub async fn delete_repo_export(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let repo = load_repo(&state, &auth, &id, Permission::REPO_ADMIN)?;
+
+    let deleted = state
+        .store
+        .delete_repo_export(&repo.id)
+        .api_err("Failed to delete export")?;
+    if !deleted {
+        return Err(ApiError::not_found("Repository has no export"));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -34,7 +34,7 @@ pub async fn list_repo_folders(
         .api_err("Failed to get repo")?
         .or_not_found("Repository not found")?;
 
-    require_repo_permission(store, principal, &repo, Permission::REPO_READ)?;
+    require_repo_permission(&state, principal, &repo, Permission::REPO_READ)?;
 
     let folder = match repo.folder_id {
         Some(folder_id) => store
@@ -61,7 +61,7 @@ pub async fn set_repo_folder(
         .api_err("Failed to get repo")?
         .or_not_found("Repository not found")?;
 
-    require_repo_permission(store, principal, &repo, Permission::REPO_WRITE)?;
+    require_repo_permission(&state, principal, &repo, Permission::REPO_WRITE)?;
 
     let normalized_path = match &req.folder_path {
         Some(path) => Some(normalize_path(path).map_err(|e| ApiError::bad_request(e.to_string()))?),
@@ -100,7 +100,7 @@ pub async fn clear_repo_folder(
         .api_err("Failed to get repo")?
         .or_not_found("Repository not found")?;
 
-    require_repo_permission(store, principal, &repo, Permission::REPO_WRITE)?;
+    require_repo_permission(&state, principal, &repo, Permission::REPO_WRITE)?;
 
     match repo.folder_id {
         Some(current_folder_id) if current_folder_id == path.folder_id => {
@@ -0,0 +1,155 @@
+//! Starter files for `auto_init`: a README, an optional license, and an
+//! optional .gitignore, committed to a new repository's default branch so it
+//! isn't empty.
+
+use std::path::Path;
+
+use chrono::{Datelike, Utc};
+
+use crate::server::content::git_ops::{
+    CommitActionOp, CommitAuthor, apply_actions, open_or_init_repo,
+};
+use crate::server::dto::AutoInitRequest;
+use crate::server::response::ApiError;
+use crate::types::ObjectFormat;
+
+/// License templates by SPDX identifier. `{year}` and `{owner}` are filled
+/// in for the licenses that name a copyright holder.
+const LICENSES: &[(&str, &str)] = &[
+    ("MIT", include_str!("licenses/MIT.txt")),
+    ("Apache-2.0", include_str!("licenses/Apache-2.0.txt")),
+    ("BSD-3-Clause", include_str!("licenses/BSD-3-Clause.txt")),
+    ("MPL-2.0", include_str!("licenses/MPL-2.0.txt")),
+    ("GPL-3.0", include_str!("licenses/GPL-3.0.txt")),
+    ("Unlicense", include_str!("licenses/Unlicense.txt")),
+];
+
+const GITIGNORES: &[(&str, &str)] = &[
+    ("Go", include_str!("gitignore/Go.gitignore")),
+    ("Node", include_str!("gitignore/Node.gitignore")),
+    ("Python", include_str!("gitignore/Python.gitignore")),
+    ("Rust", include_str!("gitignore/Rust.gitignore")),
+];
+
+/// Template names are matched case-insensitively.
+fn find_template(
+    templates: &[(&'static str, &'static str)],
+    kind: &str,
+    name: &str,
+) -> Result<&'static str, ApiError> {
+    templates
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name.trim()))
+        .map(|(_, content)| *content)
+        .ok_or_else(|| {
+            let names: Vec<&str> = templates.iter().map(|(n, _)| *n).collect();
+            ApiError::bad_request(format!(
+                "Unknown {kind} template '{name}'; available: {}",
+                names.join(", ")
+            ))
+        })
+}
+
+/// Builds the starter files. `owner` is named as the copyright holder.
+/// Fails on unknown templates, so call it before creating anything.
+pub fn initial_files(
+    init: &AutoInitRequest,
+    repo_name: &str,
+    description: Option<&str>,
+    owner: &str,
+) -> Result<Vec<CommitActionOp>, ApiError> {
+    let mut readme = format!("# {repo_name}\n");
+    if let Some(description) = description.filter(|d| !d.trim().is_empty()) {
+        readme.push_str(&format!("\n{}\n", description.trim()));
+    }
+    let mut files = vec![CommitActionOp::Create {
+        path: "README.md".to_string(),
+        content: readme.into_bytes(),
+    }];
+
+    if let Some(license) = &init.license {
+        let text = find_template(LICENSES, "license", license)?
+            .trim_start_matches('\n')
+            .replace("{year}", &Utc::now().year().to_string())
+            .replace("{owner}", owner);
+        files.push(CommitActionOp::Create {
+            path: "LICENSE".to_string(),
+            content: text.into_bytes(),
+        });
+    }
+    if let Some(gitignore) = &init.gitignore {
+        files.push(CommitActionOp::Create {
+            path: ".gitignore".to_string(),
+            content: find_template(GITIGNORES, "gitignore", gitignore)?
+                .as_bytes()
+                .to_vec(),
+        });
+    }
+    Ok(files)
+}
+
+/// Commits `files` as the first commit on `main`.
+pub fn commit_initial_files(
+    path: &Path,
+    files: &[CommitActionOp],
+    author_name: &str,
+    author_email: &str,
+) -> Result<(), ApiError> {
+    let git_repo = open_or_init_repo(path, ObjectFormat::Sha1)?;
+    let author = CommitAuthor {
+        name: author_name.to_string(),
+        email: author_email.to_string(),
+        author_time: None,
+        commit_time: None,
+    };
+    apply_actions(&git_repo, "main", files, "Initial commit", &author)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(files: &[CommitActionOp]) -> Vec<&str> {
+        files
+            .iter()
+            .map(|f| match f {
+                CommitActionOp::Create { path, .. } => path.as_str(),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_initial_files() {
+        let readme_only = initial_files(&AutoInitRequest::default(), "app", None, "acme")
+            .ok()
+            .expect("valid templates");
+        assert_eq!(paths(&readme_only), ["README.md"]);
+
+        let init = AutoInitRequest {
+            license: Some("mit".to_string()),
+            gitignore: Some("Rust".to_string()),
+        };
+        let files = initial_files(&init, "app", Some("A tiny app"), "acme")
+            .ok()
+            .expect("valid templates");
+        assert_eq!(paths(&files), ["README.md", "LICENSE", ".gitignore"]);
+        let CommitActionOp::Create { content, .. } = &files[0] else {
+            unreachable!()
+        };
+        assert_eq!(content, b"# app\n\nA tiny app\n");
+        let CommitActionOp::Create { content, .. } = &files[1] else {
+            unreachable!()
+        };
+        let license = String::from_utf8_lossy(content);
+        assert!(license.starts_with("MIT License"));
+        assert!(license.contains(&format!("Copyright (c) {} acme", Utc::now().year())));
+
+        let unknown = AutoInitRequest {
+            license: Some("WTFPL".to_string()),
+            gitignore: None,
+        };
+        assert!(initial_files(&unknown, "app", None, "acme").is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+
+use crate::auth::RequirePrincipal;
+use crate::config::Feature;
+use crate::server::AppState;
+use crate::server::dto::{LfsObjectResponse, LfsUsageParams, LfsUsageResponse};
+use crate::server::response::{ApiError, ApiResponse, StoreOptionExt, StoreResultExt};
+use crate::types::Permission;
+
+use super::access::require_repo_permission;
+
+const DEFAULT_LARGEST_LIMIT: i32 = 10;
+const MAX_LARGEST_LIMIT: i32 = 100;
+
+/// GET /repos/{id}/lfs/usage - Object count, size, and largest objects
+pub async fn get_repo_lfs_usage(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<LfsUsageParams>,
+) -> impl IntoResponse {
+    state.require_feature(Feature::Lfs)?;
+    let store = state.store.as_ref();
+
+    let repo = store
+        .get_repo_by_id(&id)
+        .api_err("Failed to get repo")?
+        .or_not_found("Repository not found")?;
+
+    require_repo_permission(&state, &auth.principal, &repo, Permission::REPO_READ)?;
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_LARGEST_LIMIT)
+        .clamp(1, MAX_LARGEST_LIMIT);
+    let largest = store
+        .list_largest_lfs_objects(&repo.id, limit)
+        .api_err("Failed to list LFS objects")?
+        .into_iter()
+        .map(|obj| LfsObjectResponse {
+            oid: obj.oid,
+            size: obj.size,
+            created_at: obj.created_at,
+        })
+        .collect();
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(LfsUsageResponse {
+        object_count: store
+            .count_lfs_objects(&repo.id)
+            .api_err("Failed to count LFS objects")?,
+        total_bytes: store
+            .get_repo_lfs_size(&repo.id)
+            .api_err("Failed to get LFS size")?,
+        unshared_bytes: store
+            .get_repo_unshared_lfs_size(&repo.id)
+            .api_err("Failed to get LFS size")?,
+        largest,
+        repo_id: repo.id,
+    })))
+}
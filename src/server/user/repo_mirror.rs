@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+
+use crate::auth::RequirePrincipal;
+use crate::server::AppState;
+use crate::server::dto::MirrorResponse;
+use crate::server::git::mirror::redact_url;
+use crate::server::response::{ApiError, ApiResponse, StoreOptionExt, StoreResultExt};
+use crate::types::{Permission, Repo, RepoMirror};
+
+use super::access::require_repo_permission;
+
+pub(super) fn mirror_to_response(state: &AppState, mirror: RepoMirror) -> MirrorResponse {
+    let last_sync_status = match (&mirror.last_sync_at, &mirror.last_error) {
+        (None, _) => "pending",
+        (Some(_), None) => "success",
+        (Some(_), Some(_)) => "failed",
+    };
+    let mirrors = state.mirrors.as_ref();
+    MirrorResponse {
+        syncing: mirrors.is_some_and(|m| m.is_syncing(&mirror.repo_id)),
+        triggered_sync_at: mirrors.and_then(|m| m.triggered_at(&mirror.repo_id)),
+        url: redact_url(&mirror.url),
+        repo_id: mirror.repo_id,
+        interval_secs: mirror.interval_secs,
+        last_sync_status,
+        last_sync_at: mirror.last_sync_at,
+        last_error: mirror.last_error,
+    }
+}
+
+fn load_mirror(
+    state: &AppState,
+    auth: &RequirePrincipal,
+    id: &str,
+    permission: Permission,
+) -> Result<(Repo, RepoMirror), ApiError> {
+    let store = state.store.as_ref();
+
+    let repo = store
+        .get_repo_by_id(id)
+        .api_err("Failed to get repo")?
+        .or_not_found("Repository not found")?;
+
+    require_repo_permission(state, &auth.principal, &repo, permission)?;
+
+    let mirror = store
+        .get_repo_mirror(&repo.id)
+        .api_err("Failed to get mirror")?
+        .or_not_found("Repository is not a mirror")?;
+
+    Ok((repo, mirror))
+}
+
+/// GET /repos/{id}/mirror - Mirror settings and last sync status
+pub async fn get_repo_mirror(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (_repo, mirror) = load_mirror(&state, &auth, &id, Permission::REPO_READ)?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(mirror_to_response(
+        &state, mirror,
+    ))))
+}
+
+/// POST /repos/{id}/mirror/sync - Fetch from upstream without waiting for
+/// the interval. Calls close together share one sync, and a mirror synced
+/// recently is fetched again only after a short wait. Also served at
+/// POST /repos/{id}/sync for external triggers such as webhook relays.
+pub async fn sync_repo_mirror(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (_repo, mirror) = load_mirror(&state, &auth, &id, Permission::REPO_WRITE)?;
+
+    let scheduler = state
+        .mirrors
+        .as_ref()
+        .ok_or_else(|| ApiError::not_found("Mirror sync is not enabled"))?;
+    scheduler.trigger(&mirror);
+
+    Ok::<_, ApiError>((
+        StatusCode::ACCEPTED,
+        Json(ApiResponse::success(mirror_to_response(&state, mirror))),
+    ))
+}
+
+/// DELETE /repos/{id}/mirror - Stop mirroring; the repo keeps its refs and
+/// accepts pushes from then on
+pub async fn delete_repo_mirror(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (repo, _mirror) = load_mirror(&state, &auth, &id, Permission::REPO_ADMIN)?;
+
+    state
+        .store
+        .delete_repo_mirror(&repo.id)
+        .api_err("Failed to delete mirror")?;
+
+    Ok::<_, ApiError>(StatusCode::NO_CONTENT)
+}
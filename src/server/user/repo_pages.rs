@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use chrono::Utc;
+
+use crate::auth::RequirePrincipal;
+use crate::server::AppState;
+use crate::server::content::git_ops::{get_default_branch, open_repo};
+use crate::server::dto::{PagesResponse, UpdatePagesRequest};
+use crate::server::pages::{Pages, is_safe_name};
+use crate::server::response::{ApiError, ApiResponse, StoreOptionExt, StoreResultExt};
+use crate::types::{ObjectFormat, Permission, Repo, RepoPages};
+
+use super::access::require_repo_permission;
+
+fn pages_to_response(
+    state: &AppState,
+    headers: &HeaderMap,
+    pages: &Pages,
+    repo: &Repo,
+    settings: RepoPages,
+) -> PagesResponse {
+    let last_build_status = match (&settings.last_build_at, &settings.last_error) {
+        (None, _) => "pending",
+        (Some(_), None) => "success",
+        (Some(_), Some(_)) => "failed",
+    };
+    let namespace = state
+        .store
+        .get_namespace(&repo.namespace_id)
+        .ok()
+        .flatten()
+        .map(|ns| ns.name)
+        .unwrap_or_default();
+    let base_url = state.base_url(headers);
+    let url = match pages.domain() {
+        Some(domain) => {
+            let scheme = base_url.split_once("://").map_or("http", |(s, _)| s);
+            format!("{scheme}://{namespace}.{domain}/{}/", repo.name)
+        }
+        None => format!("{base_url}/{namespace}/{}/pages/", repo.name),
+    };
+    PagesResponse {
+        building: pages.is_building(&repo.id),
+        repo_id: settings.repo_id,
+        branch: settings.branch,
+        path: settings.path,
+        url,
+        last_build_status,
+        last_build_at: settings.last_build_at,
+        commit_sha: settings.commit_sha,
+        size_bytes: settings.size_bytes,
+        last_error: settings.last_error,
+    }
+}
+
+fn load_repo(
+    state: &AppState,
+    auth: &RequirePrincipal,
+    id: &str,
+    permission: Permission,
+) -> Result<(Repo, Arc<Pages>), ApiError> {
+    let store = state.store.as_ref();
+    let pages = state
+        .pages
+        .clone()
+        .ok_or_else(|| ApiError::not_found("Pages are not enabled on this server"))?;
+
+    let repo = store
+        .get_repo_by_id(id)
+        .api_err("Failed to get repo")?
+        .or_not_found("Repository not found")?;
+
+    require_repo_permission(state, &auth.principal, &repo, permission)?;
+
+    Ok((repo, pages))
+}
+
+fn load_settings(state: &AppState, repo: &Repo) -> Result<RepoPages, ApiError> {
+    state
+        .store
+        .get_repo_pages(&repo.id)
+        .api_err("Failed to get Pages settings")?
+        .or_not_found("Pages are not enabled for this repository")
+}
+
+/// Trims surrounding slashes and refuses `.`, `..`, and `.git` segments.
+fn normalize_site_path(path: &str) -> Result<String, ApiError> {
+    let path = path.trim_matches('/');
+    if !path.is_empty() && !path.split('/').all(is_safe_name) {
+        return Err(ApiError::bad_request(format!("Invalid Pages path: {path}")));
+    }
+    Ok(path.to_string())
+}
+
+/// GET /repos/{id}/pages - Pages settings and last build status
+pub async fn get_repo_pages(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let (repo, pages) = load_repo(&state, &auth, &id, Permission::REPO_READ)?;
+    let settings = load_settings(&state, &repo)?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(pages_to_response(
+        &state, &headers, &pages, &repo, settings,
+    ))))
+}
+
+/// PUT /repos/{id}/pages - Publish a branch directory, building it now and
+/// after every push
+pub async fn update_repo_pages(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(req): Json<UpdatePagesRequest>,
+) -> impl IntoResponse {
+    let (repo, pages) = load_repo(&state, &auth, &id, Permission::REPO_ADMIN)?;
+
+    if repo.object_format != ObjectFormat::Sha1 {
+        return Err(ApiError::not_implemented(format!(
+            "Pages are not supported for {} repositories yet",
+            repo.object_format
+        )));
+    }
+
+    let branch = match req.branch {
+        Some(branch) => branch.trim().to_string(),
+        None => open_repo(
+            &state.repo_path(&repo.namespace_id, &repo.name),
+            repo.object_format,
+        )
+        .ok()
+        .and_then(|git_repo| get_default_branch(&git_repo))
+        .unwrap_or_else(|| "main".to_string()),
+    };
+    if !git2::Reference::is_valid_name(&format!("refs/heads/{branch}")) {
+        return Err(ApiError::bad_request(format!(
+            "Invalid branch name: {branch}"
+        )));
+    }
+    let path = normalize_site_path(req.path.as_deref().unwrap_or(""))?;
+
+    state
+        .store
+        .set_repo_pages(&RepoPages {
+            repo_id: repo.id.clone(),
+            branch,
+            path,
+            created_at: Utc::now(),
+            last_build_at: None,
+            commit_sha: None,
+            size_bytes: 0,
+            last_error: None,
+        })
+        .api_err("Failed to save Pages settings")?;
+    pages.request_build(state.store.clone(), &repo.id);
+
+    let settings = load_settings(&state, &repo)?;
+    Ok(Json(ApiResponse::success(pages_to_response(
+        &state, &headers, &pages, &repo, settings,
+    ))))
+}
+
+/// POST /repos/{id}/pages/build - Rebuild without waiting for a push, e.g.
+/// after commits made through the content API
+pub async fn build_repo_pages(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let (repo, pages) = load_repo(&state, &auth, &id, Permission::REPO_WRITE)?;
+    let settings = load_settings(&state, &repo)?;
+    pages.request_build(state.store.clone(), &repo.id);
+
+    Ok::<_, ApiError>((
+        StatusCode::ACCEPTED,
+        Json(ApiResponse::success(pages_to_response(
+            &state, &headers, &pages, &repo, settings,
+        ))),
+    ))
+}
+
+/// DELETE /repos/{id}/pages - Stop publishing and remove the site
+pub async fn delete_repo_pages(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let (repo, pages) = load_repo(&state, &auth, &id, Permission::REPO_ADMIN)?;
+
+    let deleted = state
+        .store
+        .delete_repo_pages(&repo.id)
+        .api_err("Failed to delete Pages settings")?;
+    if !deleted {
+        return Err(ApiError::not_found(
+            "Pages are not enabled for this repository",
+        ));
+    }
+    pages.remove_site(&repo.id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
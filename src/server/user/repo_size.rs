@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use chrono::{Days, Utc};
+
+use crate::auth::RequirePrincipal;
+use crate::server::AppState;
+use crate::server::dto::{RepoSizeHistoryParams, RepoSizeHistoryResponse};
+use crate::server::response::{ApiError, ApiResponse, StoreOptionExt, StoreResultExt};
+use crate::types::Permission;
+
+use super::access::require_repo_permission;
+
+const DEFAULT_DAYS: i32 = 90;
+const MAX_DAYS: i32 = 3650;
+
+/// GET /repos/{id}/size-history - Git and LFS size on each day it changed
+pub async fn get_repo_size_history(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(params): Query<RepoSizeHistoryParams>,
+) -> impl IntoResponse {
+    let store = state.store.as_ref();
+
+    let repo = store
+        .get_repo_by_id(&id)
+        .api_err("Failed to get repo")?
+        .or_not_found("Repository not found")?;
+
+    require_repo_permission(&state, &auth.principal, &repo, Permission::REPO_READ)?;
+
+    let days = params.days.unwrap_or(DEFAULT_DAYS).clamp(1, MAX_DAYS);
+    let since = Utc::now().date_naive() - Days::new(days as u64 - 1);
+    let snapshots = store
+        .list_repo_size_history(&repo.id, since)
+        .api_err("Failed to get size history")?;
+
+    let growth_bytes = match (snapshots.first(), snapshots.last()) {
+        (Some(first), Some(last)) => {
+            (last.size_bytes + last.lfs_bytes) - (first.size_bytes + first.lfs_bytes)
+        }
+        _ => 0,
+    };
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(RepoSizeHistoryResponse {
+        repo_id: repo.id,
+        growth_bytes,
+        snapshots,
+    })))
+}
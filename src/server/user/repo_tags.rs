@@ -49,7 +49,7 @@ pub async fn list_repo_tags(
         .api_err("Failed to get repo")?
         .or_not_found("Repository not found")?;
 
-    require_repo_permission(store, principal, &repo, Permission::REPO_READ)?;
+    require_repo_permission(&state, principal, &repo, Permission::REPO_READ)?;
 
     let tags = store
         .list_repo_tags(&repo.id)
@@ -72,7 +72,7 @@ pub async fn add_repo_tags(
         .api_err("Failed to get repo")?
         .or_not_found("Repository not found")?;
 
-    require_repo_permission(store, principal, &repo, Permission::REPO_WRITE)?;
+    require_repo_permission(&state, principal, &repo, Permission::REPO_WRITE)?;
     validate_tags_for_repo(store, &repo, &req.tag_ids)?;
 
     for tag_id in &req.tag_ids {
@@ -102,7 +102,7 @@ pub async fn set_repo_tags(
         .api_err("Failed to get repo")?
         .or_not_found("Repository not found")?;
 
-    require_repo_permission(store, principal, &repo, Permission::REPO_WRITE)?;
+    require_repo_permission(&state, principal, &repo, Permission::REPO_WRITE)?;
     validate_tags_for_repo(store, &repo, &req.tag_ids)?;
 
     store
@@ -129,7 +129,7 @@ pub async fn remove_repo_tag(
         .api_err("Failed to get repo")?
         .or_not_found("Repository not found")?;
 
-    require_repo_permission(store, principal, &repo, Permission::REPO_WRITE)?;
+    require_repo_permission(&state, principal, &repo, Permission::REPO_WRITE)?;
 
     store
         .get_tag_by_id(&tag_id)
@@ -10,19 +10,29 @@ use chrono::Utc;
 use uuid::Uuid;
 
 use crate::auth::RequirePrincipal;
+use crate::config::EventKind;
+use crate::quota;
 use crate::server::AppState;
+use crate::server::content::get_commit_author;
 use crate::server::dto::{CreateRepoRequest, ListReposParams, UpdateRepoRequest};
+use crate::server::events::{emit, repo_event};
+use crate::server::git::init_bare_repo;
+use crate::server::git::mirror::{
+    DEFAULT_MIRROR_INTERVAL_SECS, MIN_MIRROR_INTERVAL_SECS, validate_remote_url,
+};
+use crate::server::legal_hold::check_repo_deletable;
 use crate::server::response::{
     ApiError, ApiResponse, DEFAULT_PAGE_SIZE, PaginatedResponse, StoreOptionExt, StoreResultExt,
     paginate,
 };
 use crate::server::validation::validate_repo_name;
-use crate::types::{Permission, Repo};
+use crate::types::{ObjectFormat, Permission, Repo, RepoMirror, Visibility};
 
 use super::access::{
     check_namespace_permission, require_namespace_permission, require_repo_permission,
     resolve_namespace_id,
 };
+use super::repo_init::{commit_initial_files, initial_files};
 
 pub async fn list_repos(
     auth: RequirePrincipal,
@@ -36,7 +46,7 @@ pub async fn list_repos(
     let repos = if let Some(ref ns_name) = params.namespace {
         let ns_id = resolve_namespace_id(store, principal, Some(ns_name))?;
 
-        if check_namespace_permission(store, principal, &ns_id, Permission::NAMESPACE_READ)? {
+        if check_namespace_permission(&state, principal, &ns_id, Permission::NAMESPACE_READ)? {
             store
                 .list_repos(&ns_id, cursor, DEFAULT_PAGE_SIZE + 1)
                 .api_err("Failed to list repos")?
@@ -107,27 +117,73 @@ pub async fn create_repo(
     let principal = &auth.principal;
     let store = state.store.as_ref();
 
-    validate_repo_name(&req.name)?;
+    let name = validate_repo_name(&req.name, state.repo_name_policy)?;
 
     let ns_id = resolve_namespace_id(store, principal, req.namespace.as_deref())?;
 
-    require_namespace_permission(store, principal, &ns_id, Permission::NAMESPACE_WRITE)?;
+    require_namespace_permission(&state, principal, &ns_id, Permission::NAMESPACE_WRITE)?;
+
+    if let Some(ref mirror) = req.mirror {
+        validate_remote_url(&mirror.url)
+            .map_err(|e| ApiError::bad_request(format!("Mirror {e}")))?;
+        if mirror
+            .interval_secs
+            .is_some_and(|i| i < MIN_MIRROR_INTERVAL_SECS)
+        {
+            return Err(ApiError::bad_request(format!(
+                "Mirror interval must be at least {MIN_MIRROR_INTERVAL_SECS} seconds"
+            )));
+        }
+    }
 
     if store
-        .get_repo(&ns_id, &req.name)
+        .get_repo(&ns_id, &name)
         .api_err("Failed to check repo")?
         .is_some()
     {
         return Err(ApiError::conflict("Repository already exists"));
     }
 
+    let namespace = store
+        .get_namespace(&ns_id)
+        .api_err("Failed to get namespace")?
+        .or_not_found("Namespace not found")?;
+    if let Some(exceeded) =
+        quota::check_repo_limit(store, &namespace).api_err("Failed to check repo limit")?
+    {
+        return Err(ApiError::forbidden(exceeded.to_string()));
+    }
+
+    // Templates are checked before anything is created.
+    let init_files = match &req.auto_init {
+        None => None,
+        Some(_) if req.mirror.is_some() => {
+            return Err(ApiError::bad_request(
+                "Mirrors are filled from their upstream and can't be auto-initialized",
+            ));
+        }
+        Some(_) if req.object_format != ObjectFormat::Sha1 => {
+            return Err(ApiError::not_implemented(format!(
+                "Auto-init is not supported for {} repositories yet",
+                req.object_format
+            )));
+        }
+        Some(init) => Some(initial_files(
+            init,
+            &name,
+            req.description.as_deref(),
+            &namespace.name,
+        )?),
+    };
+
     let now = Utc::now();
     let repo = Repo {
         id: Uuid::new_v4().to_string(),
         namespace_id: ns_id,
-        name: req.name,
+        name,
         description: req.description,
-        public: req.public,
+        visibility: requested_visibility(req.visibility, req.public).unwrap_or_default(),
+        object_format: req.object_format,
         folder_id: None,
         size_bytes: 0,
         last_push_at: None,
@@ -137,6 +193,47 @@ pub async fn create_repo(
 
     store.create_repo(&repo).api_err("Failed to create repo")?;
 
+    // The content API creates SHA-1 repositories on first write, but can't
+    // create the others, so initialize those now. Mirrors are initialized
+    // by their first sync.
+    if repo.object_format != ObjectFormat::Sha1 && req.mirror.is_none() {
+        let path = state.repo_path(&repo.namespace_id, &repo.name);
+        if let Err(e) = init_bare_repo(&path, repo.object_format).await {
+            let _ = store.delete_repo(&repo.id);
+            return Err(ApiError::internal(format!(
+                "Failed to initialize repository: {e}"
+            )));
+        }
+    }
+
+    if let Some(files) = init_files {
+        let path = state.repo_path(&repo.namespace_id, &repo.name);
+        let (author_name, author_email) = get_commit_author(&state, principal);
+        if let Err(e) = commit_initial_files(&path, &files, &author_name, &author_email) {
+            let _ = store.delete_repo(&repo.id);
+            let _ = tokio::fs::remove_dir_all(&path).await;
+            return Err(e);
+        }
+    }
+
+    if let Some(mirror) = req.mirror {
+        store
+            .create_repo_mirror(&RepoMirror {
+                repo_id: repo.id.clone(),
+                url: mirror.url.trim().to_string(),
+                interval_secs: mirror.interval_secs.unwrap_or(DEFAULT_MIRROR_INTERVAL_SECS),
+                created_at: now,
+                last_sync_at: None,
+                last_error: None,
+            })
+            .api_err("Failed to create mirror")?;
+        if let Some(mirrors) = &state.mirrors {
+            mirrors.request(&repo.id);
+        }
+    }
+
+    let event = repo_event(EventKind::RepoCreated, &namespace, &repo, Some(&principal.id));
+    emit(&state, event);
     Ok::<_, ApiError>((StatusCode::CREATED, Json(ApiResponse::success(repo))))
 }
 
@@ -153,7 +250,7 @@ pub async fn get_repo(
         .api_err("Failed to get repo")?
         .or_not_found("Repository not found")?;
 
-    require_repo_permission(store, principal, &repo, Permission::REPO_READ)?;
+    require_repo_permission(&state, principal, &repo, Permission::REPO_READ)?;
 
     Ok::<_, ApiError>(Json(ApiResponse::success(repo)))
 }
@@ -172,9 +269,10 @@ pub async fn update_repo(
         .api_err("Failed to get repo")?
         .or_not_found("Repository not found")?;
 
-    require_repo_permission(store, principal, &repo, Permission::REPO_WRITE)?;
+    require_repo_permission(&state, principal, &repo, Permission::REPO_WRITE)?;
 
     if let Some(name) = req.name {
+        let name = validate_repo_name(&name, state.repo_name_policy)?;
         if name != repo.name
             && store
                 .get_repo(&repo.namespace_id, &name)
@@ -188,8 +286,8 @@ pub async fn update_repo(
     if let Some(description) = req.description {
         repo.description = Some(description);
     }
-    if let Some(public) = req.public {
-        repo.public = public;
+    if let Some(visibility) = requested_visibility(req.visibility, req.public) {
+        repo.visibility = visibility;
     }
 
     store.update_repo(&repo).api_err("Failed to update repo")?;
@@ -210,11 +308,39 @@ pub async fn delete_repo(
         .api_err("Failed to get repo")?
         .or_not_found("Repository not found")?;
 
-    require_repo_permission(store, principal, &repo, Permission::REPO_ADMIN)?;
+    require_repo_permission(&state, principal, &repo, Permission::REPO_ADMIN)?;
+    check_repo_deletable(&state, &repo)?;
+    let member = store
+        .get_shared_object_member(&repo.id)
+        .api_err("Failed to get shared object membership")?;
+    let namespace = store
+        .get_namespace(&repo.namespace_id)
+        .api_err("Failed to get namespace")?
+        .or_not_found("Namespace not found")?;
 
     store
         .delete_repo(&repo.id)
         .api_err("Failed to delete repo")?;
+    if let Some(member) = member {
+        state.shared_objects.repo_deleted(&state, member);
+    }
+    let event = repo_event(EventKind::RepoDeleted, &namespace, &repo, Some(&principal.id));
+    emit(&state, event);
 
     Ok::<_, ApiError>(StatusCode::NO_CONTENT)
 }
+
+/// The visibility a create or update asks for, honouring the deprecated
+/// `public` flag older clients still send.
+fn requested_visibility(
+    visibility: Option<Visibility>,
+    public: Option<bool>,
+) -> Option<Visibility> {
+    visibility.or(public.map(|public| {
+        if public {
+            Visibility::Public
+        } else {
+            Visibility::Private
+        }
+    }))
+}
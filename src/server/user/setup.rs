@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State, http::HeaderMap, response::IntoResponse};
+
+use crate::auth::RequirePrincipal;
+use crate::server::AppState;
+use crate::server::dto::{CapabilitiesResponse, GitConfigRecommendation, SetupResponse};
+use crate::server::response::{ApiError, ApiResponse};
+use crate::types::ObjectFormat;
+
+fn recommendation(key: String, value: &str, description: &str) -> GitConfigRecommendation {
+    GitConfigRecommendation {
+        key,
+        value: value.to_string(),
+        description: description.to_string(),
+    }
+}
+
+fn git_config_recommendations(base_url: &str) -> Vec<GitConfigRecommendation> {
+    vec![
+        recommendation(
+            format!("credential.{base_url}.helper"),
+            "!cutman credential",
+            "Use the cutman CLI to supply your token to git for this server",
+        ),
+        recommendation(
+            format!("lfs.{base_url}/git/.locksverify"),
+            "false",
+            "Cutman does not implement the LFS locking API; skip lock verification on push",
+        ),
+        recommendation(
+            "push.default".to_string(),
+            "current",
+            "Push the current branch to a branch of the same name",
+        ),
+        recommendation(
+            "init.defaultBranch".to_string(),
+            "main",
+            "Match the default branch of repositories created on this server",
+        ),
+    ]
+}
+
+/// `ssh://git@host[:port]` for the host clients already use over HTTP.
+fn git_ssh_url(base_url: &str, ssh_port: u16) -> String {
+    let authority = base_url
+        .split_once("://")
+        .map_or(base_url, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let host = match authority.find(']') {
+        Some(end) => &authority[..=end],
+        None => authority.split(':').next().unwrap_or(authority),
+    };
+
+    if ssh_port == 22 {
+        format!("ssh://git@{host}")
+    } else {
+        format!("ssh://git@{host}:{ssh_port}")
+    }
+}
+
+pub async fn get_setup(
+    _auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let base_url = state.base_url(&headers);
+
+    let setup = SetupResponse {
+        git_http_url: format!("{base_url}/git"),
+        git_ssh_url: state.ssh_port.map(|port| git_ssh_url(&base_url, port)),
+        git_config: git_config_recommendations(&base_url),
+        base_url,
+    };
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(setup)))
+}
+
+pub async fn get_capabilities(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let capabilities = CapabilitiesResponse {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        banner: state.banner.clone(),
+        ssh_port: state.ssh_port,
+        object_formats: vec![ObjectFormat::Sha1, ObjectFormat::Sha256],
+        bundle_uri: state
+            .maintenance
+            .as_ref()
+            .is_some_and(|m| m.writes_bundles()),
+        max_clone_depth: state.max_clone_depth,
+        features: state.features,
+    };
+
+    Json(ApiResponse::success(capabilities))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_git_ssh_url() {
+        assert_eq!(
+            git_ssh_url("https://git.example.com", 22),
+            "ssh://git@git.example.com"
+        );
+        assert_eq!(
+            git_ssh_url("http://127.0.0.1:8080", 2222),
+            "ssh://git@127.0.0.1:2222"
+        );
+        assert_eq!(git_ssh_url("http://[::1]:8080/", 22), "ssh://git@[::1]");
+    }
+}
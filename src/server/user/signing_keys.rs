@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::auth::RequirePrincipal;
+use crate::server::AppState;
+use crate::server::dto::CreateSigningKeyRequest;
+use crate::server::response::{ApiError, ApiResponse, StoreResultExt};
+use crate::server::signing::inspect_gpg_key;
+use crate::server::validation::{parse_ssh_public_key, validate_signing_key_name};
+use crate::types::{SigningKey, SigningKeyKind};
+
+const PGP_KEY_HEADER: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----";
+
+pub async fn list_signing_keys(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let keys = state
+        .store
+        .list_principal_signing_keys(&auth.principal.id)
+        .api_err("Failed to list signing keys")?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(keys)))
+}
+
+pub async fn create_signing_key(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateSigningKeyRequest>,
+) -> impl IntoResponse {
+    let public_key = req.public_key.trim();
+
+    // GPG keys are stored armored as uploaded; SSH keys without the comment.
+    let (kind, public_key, fingerprint, key_ids, default_name) =
+        if public_key.starts_with(PGP_KEY_HEADER) {
+            let info = inspect_gpg_key(&state.data_dir, state.signing_timeout, public_key)
+                .await
+                .map_err(ApiError::bad_request)?;
+            let name = info.user_id.unwrap_or_else(|| "GPG key".to_string());
+            let key = format!("{public_key}\n");
+            (
+                SigningKeyKind::Gpg,
+                key,
+                info.fingerprint,
+                info.key_ids,
+                name,
+            )
+        } else {
+            let parsed = parse_ssh_public_key(public_key).map_err(ApiError::bad_request)?;
+            let name = parsed.comment.unwrap_or_else(|| "SSH key".to_string());
+            let key_ids = vec![parsed.fingerprint.clone()];
+            (
+                SigningKeyKind::Ssh,
+                parsed.public_key,
+                parsed.fingerprint,
+                key_ids,
+                name,
+            )
+        };
+
+    let name = req.name.unwrap_or(default_name);
+    validate_signing_key_name(&name)?;
+
+    let key = SigningKey {
+        id: Uuid::new_v4().to_string(),
+        principal_id: auth.principal.id.clone(),
+        kind,
+        name,
+        public_key,
+        fingerprint,
+        key_ids,
+        created_at: Utc::now(),
+    };
+
+    match state.store.create_signing_key(&key) {
+        Ok(()) => {}
+        Err(crate::error::Error::AlreadyExists) => {
+            return Err(ApiError::conflict("Signing key is already registered"));
+        }
+        Err(_) => return Err(ApiError::internal("Failed to create signing key")),
+    }
+
+    Ok::<_, ApiError>((StatusCode::CREATED, Json(ApiResponse::success(key))))
+}
+
+pub async fn delete_signing_key(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let deleted = state
+        .store
+        .delete_principal_signing_key(&auth.principal.id, &id)
+        .api_err("Failed to delete signing key")?;
+
+    if !deleted {
+        return Err(ApiError::not_found("Signing key not found"));
+    }
+
+    Ok::<_, ApiError>(StatusCode::NO_CONTENT)
+}
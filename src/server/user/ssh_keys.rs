@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::auth::RequirePrincipal;
+use crate::server::AppState;
+use crate::server::dto::CreateSshKeyRequest;
+use crate::server::response::{ApiError, ApiResponse, StoreResultExt};
+use crate::server::validation::{parse_ssh_public_key, validate_ssh_key_name};
+use crate::types::SshKey;
+
+pub async fn list_ssh_keys(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let keys = state
+        .store
+        .list_principal_ssh_keys(&auth.principal.id)
+        .api_err("Failed to list SSH keys")?;
+
+    Ok::<_, ApiError>(Json(ApiResponse::success(keys)))
+}
+
+pub async fn create_ssh_key(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<CreateSshKeyRequest>,
+) -> impl IntoResponse {
+    let parsed = parse_ssh_public_key(&req.public_key).map_err(ApiError::bad_request)?;
+
+    let name = req
+        .name
+        .or(parsed.comment)
+        .unwrap_or_else(|| "SSH key".to_string());
+    validate_ssh_key_name(&name)?;
+
+    let key = SshKey {
+        id: Uuid::new_v4().to_string(),
+        principal_id: auth.principal.id.clone(),
+        name,
+        public_key: parsed.public_key,
+        fingerprint: parsed.fingerprint,
+        created_at: Utc::now(),
+        last_used_at: None,
+    };
+
+    match state.store.create_ssh_key(&key) {
+        Ok(()) => {}
+        Err(crate::error::Error::AlreadyExists) => {
+            return Err(ApiError::conflict("SSH key is already registered"));
+        }
+        Err(_) => return Err(ApiError::internal("Failed to create SSH key")),
+    }
+
+    Ok::<_, ApiError>((StatusCode::CREATED, Json(ApiResponse::success(key))))
+}
+
+pub async fn delete_ssh_key(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let deleted = state
+        .store
+        .delete_principal_ssh_key(&auth.principal.id, &id)
+        .api_err("Failed to delete SSH key")?;
+
+    if !deleted {
+        return Err(ApiError::not_found("SSH key not found"));
+    }
+
+    Ok::<_, ApiError>(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,329 @@
+//! Delta sync for clients that keep a local copy of everything the caller
+//! can see. Without a cursor the caller gets a full snapshot; with one, just
+//! the repos, folders, and tags that changed since. Changes come from the
+//! store's trigger-fed change log, which is pruned after
+//! [`CHANGE_RETENTION`]; a cursor older than that gets a fresh snapshot.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use tracing::{info, warn};
+
+use crate::auth::RequirePrincipal;
+use crate::server::AppState;
+use crate::server::dto::{
+    NamespaceGrantResponse, RepoGrantResponse, SyncDeleted, SyncParams, SyncRepo, SyncResponse,
+};
+use crate::server::response::{ApiError, ApiResponse, StoreResultExt};
+use crate::store::Store;
+use crate::types::{Change, ChangeKind, NamespaceGrant, Permission, Principal, Repo, RepoGrant};
+
+use super::access::check_repo_permission;
+
+/// How long change log entries are kept.
+pub const CHANGE_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// How often old change log entries are pruned.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Change log entries read per request.
+const SYNC_PAGE_SIZE: usize = 500;
+
+/// What the caller can see: every repo, folder, and tag in namespaces they
+/// can read, plus repos granted to them directly. Each repo in scope is still
+/// checked against the authorizer, so a repo-level deny hides it.
+struct Scope {
+    namespaces: HashSet<String>,
+    repos: HashSet<String>,
+    namespace_grants: Vec<NamespaceGrant>,
+    repo_grants: Vec<RepoGrant>,
+}
+
+impl Scope {
+    fn load(store: &dyn Store, principal: &Principal) -> Result<Self, ApiError> {
+        let namespace_grants = store
+            .list_principal_namespace_grants(&principal.id)
+            .api_err("Failed to list namespace grants")?;
+        let repo_grants = store
+            .list_principal_repo_grants(&principal.id)
+            .api_err("Failed to list repo grants")?;
+
+        let mut namespaces = HashSet::from([principal.primary_namespace_id.clone()]);
+        namespaces.extend(
+            namespace_grants
+                .iter()
+                .filter(|g| {
+                    g.allow_bits
+                        .expand_implied()
+                        .difference(g.deny_bits)
+                        .has(Permission::NAMESPACE_READ)
+                })
+                .map(|g| g.namespace_id.clone()),
+        );
+        let repos = repo_grants
+            .iter()
+            .filter(|g| {
+                g.allow_bits
+                    .expand_implied()
+                    .difference(g.deny_bits)
+                    .has(Permission::REPO_READ)
+            })
+            .map(|g| g.repo_id.clone())
+            .collect();
+
+        Ok(Self {
+            namespaces,
+            repos,
+            namespace_grants,
+            repo_grants,
+        })
+    }
+
+    fn sees_repo(&self, repo_id: &str, namespace_id: &str) -> bool {
+        self.namespaces.contains(namespace_id) || self.repos.contains(repo_id)
+    }
+
+    fn sees(&self, change: &Change) -> bool {
+        let namespace_id = change.namespace_id.as_deref().unwrap_or_default();
+        match change.kind {
+            ChangeKind::Repo => self.sees_repo(&change.entity_id, namespace_id),
+            _ => self.namespaces.contains(namespace_id),
+        }
+    }
+}
+
+/// Whether the authorizer lets the caller read `repo`.
+fn reads_repo(state: &AppState, principal: &Principal, repo: &Repo) -> Result<bool, ApiError> {
+    check_repo_permission(state, principal, repo, Permission::REPO_READ)
+}
+
+fn sync_repo(store: &dyn Store, repo: Repo) -> Result<SyncRepo, ApiError> {
+    let tag_ids = store
+        .list_repo_tags(&repo.id)
+        .api_err("Failed to list repo tags")?
+        .into_iter()
+        .map(|t| t.id)
+        .collect();
+    Ok(SyncRepo { repo, tag_ids })
+}
+
+/// Everything in scope, as of change `seq` or later.
+fn snapshot(
+    state: &AppState,
+    principal: &Principal,
+    scope: Scope,
+    seq: i64,
+) -> Result<SyncResponse, ApiError> {
+    let store = state.store.as_ref();
+    let mut response = SyncResponse {
+        cursor: seq.to_string(),
+        reset: true,
+        ..SyncResponse::default()
+    };
+
+    let mut namespaces: Vec<&String> = scope.namespaces.iter().collect();
+    namespaces.sort();
+    for ns_id in namespaces {
+        for repo in store
+            .list_repos(ns_id, "", i32::MAX)
+            .api_err("Failed to list repos")?
+        {
+            if reads_repo(state, principal, &repo)? {
+                response.repos.push(sync_repo(store, repo)?);
+            }
+        }
+        response.folders.extend(
+            store
+                .list_all_folders(ns_id)
+                .api_err("Failed to list folders")?,
+        );
+        response.tags.extend(
+            store
+                .list_tags(ns_id, "", i32::MAX)
+                .api_err("Failed to list tags")?,
+        );
+    }
+
+    let mut granted: Vec<&String> = scope.repos.iter().collect();
+    granted.sort();
+    for repo_id in granted {
+        if let Some(repo) = store
+            .get_repo_by_id(repo_id)
+            .api_err("Failed to get repo")?
+        {
+            if !scope.namespaces.contains(&repo.namespace_id)
+                && reads_repo(state, principal, &repo)?
+            {
+                response.repos.push(sync_repo(store, repo)?);
+            }
+        }
+    }
+
+    response.namespace_grants = scope
+        .namespace_grants
+        .into_iter()
+        .map(|g| NamespaceGrantResponse {
+            namespace_id: g.namespace_id,
+            allow: g.allow_bits.to_strings(),
+            deny: g.deny_bits.to_strings(),
+        })
+        .collect();
+    response.repo_grants = scope
+        .repo_grants
+        .into_iter()
+        .map(|g| RepoGrantResponse {
+            repo_id: g.repo_id,
+            allow: g.allow_bits.to_strings(),
+            deny: g.deny_bits.to_strings(),
+            paths: g.paths,
+        })
+        .collect();
+
+    Ok(response)
+}
+
+/// The current state of each entity in `changes` the caller can see,
+/// once per entity in the order of its latest change. A repo the caller can
+/// no longer read is reported as deleted.
+fn delta(
+    state: &AppState,
+    principal: &Principal,
+    scope: &Scope,
+    changes: &[Change],
+    cursor: i64,
+    has_more: bool,
+) -> Result<SyncResponse, ApiError> {
+    let mut response = SyncResponse {
+        cursor: changes.last().map_or(cursor, |c| c.seq).to_string(),
+        has_more,
+        ..SyncResponse::default()
+    };
+
+    let store = state.store.as_ref();
+    let mut seen = HashSet::new();
+    for change in changes.iter().rev() {
+        if !scope.sees(change) || !seen.insert((change.kind, change.entity_id.as_str())) {
+            continue;
+        }
+        let found = match change.kind {
+            ChangeKind::Repo => {
+                let repo = store
+                    .get_repo_by_id(&change.entity_id)
+                    .api_err("Failed to get repo")?;
+                match repo {
+                    Some(repo) if reads_repo(state, principal, &repo)? => {
+                        response.repos.push(sync_repo(store, repo)?);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            ChangeKind::Folder => {
+                let folder = match change.entity_id.parse() {
+                    Ok(id) => store
+                        .get_folder_by_id(id)
+                        .api_err("Failed to get folder")?,
+                    Err(_) => None,
+                };
+                folder.map(|f| response.folders.push(f)).is_some()
+            }
+            ChangeKind::Tag => store
+                .get_tag_by_id(&change.entity_id)
+                .api_err("Failed to get tag")?
+                .map(|t| response.tags.push(t))
+                .is_some(),
+            ChangeKind::NamespaceGrant | ChangeKind::RepoGrant => continue,
+        };
+        if !found {
+            response.deleted.push(SyncDeleted {
+                kind: change.kind,
+                id: change.entity_id.clone(),
+            });
+        }
+    }
+
+    response.repos.reverse();
+    response.folders.reverse();
+    response.tags.reverse();
+    response.deleted.reverse();
+    Ok(response)
+}
+
+/// GET /sync?cursor= - Changes to visible repos, folders, and tags since
+/// `cursor`, or a full snapshot without one
+pub async fn sync(
+    auth: RequirePrincipal,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SyncParams>,
+) -> impl IntoResponse {
+    let principal = &auth.principal;
+    let store = state.store.as_ref();
+
+    let cursor = match params.cursor.as_deref() {
+        None | Some("") => None,
+        Some(c) => Some(
+            c.parse::<i64>()
+                .map_err(|_| ApiError::bad_request("Invalid cursor"))?,
+        ),
+    };
+
+    // Read before loading anything, so a snapshot never skips a change
+    // made while it is assembled.
+    let latest = store
+        .latest_change_seq()
+        .api_err("Failed to read change log")?;
+    let scope = Scope::load(store, principal)?;
+
+    if let Some(cursor) = cursor {
+        let oldest = store
+            .oldest_change_seq()
+            .api_err("Failed to read change log")?;
+        // Cursors from the future come from a different database; ones
+        // behind the oldest entry have missed pruned changes.
+        let valid = cursor <= latest
+            && (cursor == latest || oldest.is_some_and(|oldest| oldest <= cursor + 1));
+        if valid {
+            let mut changes = store
+                .list_changes(cursor, SYNC_PAGE_SIZE as i32 + 1)
+                .api_err("Failed to read change log")?;
+            let has_more = changes.len() > SYNC_PAGE_SIZE;
+            changes.truncate(SYNC_PAGE_SIZE);
+
+            // A grant change can widen or narrow the caller's view of
+            // whole namespaces, which a delta cannot express.
+            let grants_changed = changes.iter().any(|c| {
+                matches!(c.kind, ChangeKind::NamespaceGrant | ChangeKind::RepoGrant)
+                    && c.entity_id == principal.id
+            });
+            if !grants_changed {
+                let response = delta(&state, principal, &scope, &changes, cursor, has_more)?;
+                return Ok::<_, ApiError>(Json(ApiResponse::success(response)));
+            }
+        }
+    }
+
+    Ok(Json(ApiResponse::success(snapshot(&state, principal, scope, latest)?)))
+}
+
+/// Prunes change log entries older than [`CHANGE_RETENTION`] every hour.
+pub fn spawn_change_pruner(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        loop {
+            let store = state.store.clone();
+            match tokio::task::spawn_blocking(move || store.prune_changes(CHANGE_RETENTION)).await
+            {
+                Ok(Ok(pruned)) if pruned > 0 => info!("Pruned {pruned} change log entries"),
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => warn!("Failed to prune change log: {e}"),
+                Err(e) => warn!("Change log pruning failed: {e}"),
+            }
+            tokio::time::sleep(PRUNE_INTERVAL).await;
+        }
+    });
+}
@@ -31,7 +31,7 @@ pub async fn list_tags(
     let ns_id = resolve_namespace_id(store, principal, params.namespace.as_deref())?;
     let cursor = params.cursor.as_deref().unwrap_or("");
 
-    require_namespace_permission(store, principal, &ns_id, Permission::NAMESPACE_READ)?;
+    require_namespace_permission(&state, principal, &ns_id, Permission::NAMESPACE_READ)?;
 
     let tags = store
         .list_tags(&ns_id, cursor, DEFAULT_PAGE_SIZE + 1)
@@ -52,7 +52,7 @@ pub async fn create_tag(
     let store = state.store.as_ref();
     let ns_id = resolve_namespace_id(store, principal, req.namespace.as_deref())?;
 
-    require_namespace_permission(store, principal, &ns_id, Permission::NAMESPACE_WRITE)?;
+    require_namespace_permission(&state, principal, &ns_id, Permission::NAMESPACE_WRITE)?;
 
     validate_tag_name(&req.name)?;
 
@@ -90,7 +90,7 @@ pub async fn get_tag(
         .api_err("Failed to get tag")?
         .or_not_found("Tag not found")?;
 
-    require_namespace_permission(store, principal, &tag.namespace_id, Permission::NAMESPACE_READ)?;
+    require_namespace_permission(&state, principal, &tag.namespace_id, Permission::NAMESPACE_READ)?;
 
     Ok::<_, ApiError>(Json(ApiResponse::success(tag)))
 }
@@ -109,7 +109,7 @@ pub async fn update_tag(
         .api_err("Failed to get tag")?
         .or_not_found("Tag not found")?;
 
-    require_namespace_permission(store, principal, &tag.namespace_id, Permission::NAMESPACE_WRITE)?;
+    require_namespace_permission(&state, principal, &tag.namespace_id, Permission::NAMESPACE_WRITE)?;
 
     if let Some(name) = req.name {
         validate_tag_name(&name)?;
@@ -147,7 +147,7 @@ pub async fn delete_tag(
         .api_err("Failed to get tag")?
         .or_not_found("Tag not found")?;
 
-    require_namespace_permission(store, principal, &tag.namespace_id, Permission::NAMESPACE_ADMIN)?;
+    require_namespace_permission(&state, principal, &tag.namespace_id, Permission::NAMESPACE_ADMIN)?;
 
     let repo_count = store
         .count_tag_repos(&tag.id)
@@ -1,8 +1,36 @@
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use sha2::{Digest, Sha256};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::RepoNamePolicy;
 use crate::server::response::ApiError;
 
 const MAX_NAMESPACE_NAME_LEN: usize = 64;
 const MAX_REPO_NAME_LEN: usize = 100;
+/// Longest file name most filesystems accept. Repos are stored as the
+/// percent-encoded name plus `.git`, which for non-ASCII names can be many
+/// times the character count.
+const MAX_REPO_DIR_NAME_LEN: usize = 255;
 const MAX_TAG_NAME_LEN: usize = 64;
+const MAX_SSH_KEY_NAME_LEN: usize = 100;
+const MAX_SIGNING_KEY_NAME_LEN: usize = 100;
+const MAX_PACKAGE_NAME_LEN: usize = 128;
+
+/// Public key algorithms accepted for SSH authentication.
+const SSH_KEY_ALGORITHMS: &[&str] = &[
+    "ssh-ed25519",
+    "ssh-rsa",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+];
+
+/// Characters rejected in Unicode repo names because they are meaningful in
+/// URLs, git refspecs, or common filesystems.
+const RESERVED_REPO_NAME_CHARS: &[char] = &['/', '\\', '?', '#', '%', ':', '*', '"', '<', '>', '|'];
 
 fn is_valid_name_char(c: char, allow_period: bool) -> bool {
     c.is_ascii_alphanumeric() || c == '-' || c == '_' || (allow_period && c == '.')
@@ -40,10 +68,254 @@ pub fn validate_namespace_name(name: &str) -> Result<(), String> {
     validate_name(name, "Namespace", MAX_NAMESPACE_NAME_LEN, false, true)
 }
 
-pub fn validate_repo_name(name: &str) -> Result<(), ApiError> {
-    validate_name(name, "Repository", MAX_REPO_NAME_LEN, true, false).map_err(ApiError::bad_request)
+fn validate_unicode_repo_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Repository name cannot be empty".to_string());
+    }
+    if name.chars().count() > MAX_REPO_NAME_LEN {
+        return Err(format!(
+            "Repository name cannot exceed {MAX_REPO_NAME_LEN} characters"
+        ));
+    }
+    if urlencoding::encode(name).len() + ".git".len() > MAX_REPO_DIR_NAME_LEN {
+        return Err(format!(
+            "Repository name cannot exceed {MAX_REPO_DIR_NAME_LEN} bytes as a directory name"
+        ));
+    }
+    if name
+        .chars()
+        .any(|c| c.is_control() || c.is_whitespace() || RESERVED_REPO_NAME_CHARS.contains(&c))
+    {
+        return Err(
+            "Repository name cannot contain whitespace, control characters, or any of / \\ ? # % : * \" < > |"
+                .to_string(),
+        );
+    }
+    if name.chars().all(|c| c == '.') {
+        return Err("Repository name cannot consist only of periods".to_string());
+    }
+    Ok(())
+}
+
+/// Validates a repository name under `policy` and returns its canonical form.
+///
+/// Names are case-folded, so the API, CLI, and git URLs all resolve
+/// `MyRepo` and `myrepo` to the same repository. The Unicode policy also
+/// normalizes to NFC so visually identical names typed on different
+/// platforms do too.
+pub fn normalize_repo_name(name: &str, policy: RepoNamePolicy) -> Result<String, String> {
+    match policy {
+        RepoNamePolicy::Ascii => {
+            validate_name(name, "Repository", MAX_REPO_NAME_LEN, true, false)?;
+            Ok(name.to_ascii_lowercase())
+        }
+        RepoNamePolicy::Unicode => {
+            let normalized: String = name.to_lowercase().nfc().collect();
+            validate_unicode_repo_name(&normalized)?;
+            Ok(normalized)
+        }
+    }
+}
+
+pub fn validate_repo_name(name: &str, policy: RepoNamePolicy) -> Result<String, ApiError> {
+    normalize_repo_name(name, policy).map_err(ApiError::bad_request)
 }
 
 pub fn validate_tag_name(name: &str) -> Result<(), ApiError> {
     validate_name(name, "Tag", MAX_TAG_NAME_LEN, false, true).map_err(ApiError::bad_request)
 }
+
+/// Validates a package name, version, or file name. Each is a directory or
+/// file name on disk, so a leading period is refused as well.
+pub fn validate_package_name(name: &str, entity: &str) -> Result<(), ApiError> {
+    validate_name(name, entity, MAX_PACKAGE_NAME_LEN, true, false).map_err(ApiError::bad_request)?;
+    if name.starts_with('.') {
+        return Err(ApiError::bad_request(format!(
+            "{entity} name cannot start with a period"
+        )));
+    }
+    Ok(())
+}
+
+pub fn validate_ssh_key_name(name: &str) -> Result<(), ApiError> {
+    if name.trim().is_empty() {
+        return Err(ApiError::bad_request("SSH key name cannot be empty"));
+    }
+    if name.chars().count() > MAX_SSH_KEY_NAME_LEN {
+        return Err(ApiError::bad_request(format!(
+            "SSH key name cannot exceed {MAX_SSH_KEY_NAME_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+pub fn validate_signing_key_name(name: &str) -> Result<(), ApiError> {
+    if name.trim().is_empty() {
+        return Err(ApiError::bad_request("Signing key name cannot be empty"));
+    }
+    if name.chars().count() > MAX_SIGNING_KEY_NAME_LEN {
+        return Err(ApiError::bad_request(format!(
+            "Signing key name cannot exceed {MAX_SIGNING_KEY_NAME_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+/// An OpenSSH `authorized_keys`-style line split into its stored parts.
+#[derive(Debug)]
+pub struct ParsedSshKey {
+    /// `<algorithm> <base64>` with the comment removed.
+    pub public_key: String,
+    pub fingerprint: String,
+    pub comment: Option<String>,
+}
+
+/// `SHA256:<base64>` fingerprint of a wire-format public key blob, as printed
+/// by `ssh-keygen -l`.
+#[must_use]
+pub fn ssh_key_fingerprint(blob: &[u8]) -> String {
+    format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(blob)))
+}
+
+pub fn parse_ssh_public_key(line: &str) -> Result<ParsedSshKey, String> {
+    let mut parts = line.split_whitespace();
+    let (Some(algorithm), Some(encoded)) = (parts.next(), parts.next()) else {
+        return Err(
+            "SSH public key must be in OpenSSH format: '<algorithm> <base64> [comment]'"
+                .to_string(),
+        );
+    };
+    let comment = parts.collect::<Vec<_>>().join(" ");
+
+    if !SSH_KEY_ALGORITHMS.contains(&algorithm) {
+        return Err(format!("Unsupported SSH key algorithm '{algorithm}'"));
+    }
+
+    let blob = STANDARD
+        .decode(encoded)
+        .map_err(|_| "SSH public key is not valid base64".to_string())?;
+
+    // The blob starts with the algorithm name as a length-prefixed string.
+    let embedded = blob
+        .get(..4)
+        .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+        .and_then(|len| blob.get(4..4 + len));
+    if embedded != Some(algorithm.as_bytes()) {
+        return Err("SSH public key data does not match its algorithm".to_string());
+    }
+
+    Ok(ParsedSshKey {
+        public_key: format!("{algorithm} {encoded}"),
+        fingerprint: ssh_key_fingerprint(&blob),
+        comment: (!comment.is_empty()).then_some(comment),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_policy_rejects_non_ascii() {
+        assert!(normalize_repo_name("my-repo.v2", RepoNamePolicy::Ascii).is_ok());
+        assert!(normalize_repo_name("café", RepoNamePolicy::Ascii).is_err());
+        assert!(normalize_repo_name("🚀", RepoNamePolicy::Ascii).is_err());
+    }
+
+    #[test]
+    fn test_unicode_policy_normalizes_to_nfc() {
+        let decomposed = "cafe\u{301}";
+        let name = normalize_repo_name(decomposed, RepoNamePolicy::Unicode).unwrap();
+        assert_eq!(name, "caf\u{e9}");
+        assert_eq!(
+            normalize_repo_name("🚀-launch", RepoNamePolicy::Unicode).unwrap(),
+            "🚀-launch"
+        );
+    }
+
+    #[test]
+    fn test_repo_names_are_case_folded() {
+        assert_eq!(
+            normalize_repo_name("MyRepo", RepoNamePolicy::Ascii).unwrap(),
+            "myrepo"
+        );
+        assert_eq!(
+            normalize_repo_name("Café", RepoNamePolicy::Unicode).unwrap(),
+            "café"
+        );
+        assert_eq!(
+            normalize_repo_name("CAFE\u{301}", RepoNamePolicy::Unicode).unwrap(),
+            "caf\u{e9}"
+        );
+    }
+
+    #[test]
+    fn test_unicode_policy_rejects_reserved_names() {
+        for name in ["", "a/b", "a b", "tab\there", "50%", "..", "."] {
+            assert!(
+                normalize_repo_name(name, RepoNamePolicy::Unicode).is_err(),
+                "expected {name:?} to be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_unicode_policy_counts_characters_not_bytes() {
+        let name = "a".repeat(MAX_REPO_NAME_LEN);
+        assert!(normalize_repo_name(&name, RepoNamePolicy::Unicode).is_ok());
+        let name = "a".repeat(MAX_REPO_NAME_LEN + 1);
+        assert!(normalize_repo_name(&name, RepoNamePolicy::Unicode).is_err());
+    }
+
+    #[test]
+    fn test_unicode_policy_caps_the_encoded_directory_name() {
+        // Each emoji is 4 bytes, 12 once percent-encoded.
+        let name = "🚀".repeat(20);
+        assert!(normalize_repo_name(&name, RepoNamePolicy::Unicode).is_ok());
+        let name = "🚀".repeat(21);
+        assert!(normalize_repo_name(&name, RepoNamePolicy::Unicode).is_err());
+
+        // The longest accepted name still fits in a directory name.
+        let name = "é".repeat(41);
+        assert!(normalize_repo_name(&name, RepoNamePolicy::Unicode).is_ok());
+        let dir = crate::server::git::repo_path(std::path::Path::new("data"), "ns", &name);
+        assert!(dir.file_name().unwrap().len() <= MAX_REPO_DIR_NAME_LEN);
+        let name = "é".repeat(42);
+        assert!(normalize_repo_name(&name, RepoNamePolicy::Unicode).is_err());
+    }
+
+    #[test]
+    fn test_package_names() {
+        for name in ["my-tool", "1.2.3", "my-tool_1.2.3.tar.gz"] {
+            assert!(validate_package_name(name, "Package").is_ok(), "{name}");
+        }
+        for name in ["", "..", ".hidden", "a/b", "a b", &"a".repeat(129)] {
+            assert!(validate_package_name(name, "Package").is_err(), "{name}");
+        }
+    }
+
+    // ssh-keygen -t ed25519 -C alice@example.com
+    const ED25519_KEY: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIIWxpR1WFDNHpW88p02natHYkDvRMBLYcrLsNPSDiwf1 alice@example.com";
+
+    #[test]
+    fn test_parse_ssh_public_key() {
+        let parsed = parse_ssh_public_key(ED25519_KEY).unwrap();
+        assert_eq!(
+            parsed.public_key,
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIIWxpR1WFDNHpW88p02natHYkDvRMBLYcrLsNPSDiwf1"
+        );
+        assert_eq!(
+            parsed.fingerprint,
+            "SHA256:UFDzScABdj0yEoawGTzFSjOOdjAJTNa3+HsY8MgDvXU"
+        );
+        assert_eq!(parsed.comment.as_deref(), Some("alice@example.com"));
+    }
+
+    #[test]
+    fn test_parse_ssh_public_key_rejects_mismatched_algorithm() {
+        let swapped = ED25519_KEY.replacen("ssh-ed25519", "ssh-rsa", 1);
+        assert!(parse_ssh_public_key(&swapped).is_err());
+        assert!(parse_ssh_public_key("ssh-dss AAAA").is_err());
+        assert!(parse_ssh_public_key("not a key").is_err());
+    }
+}
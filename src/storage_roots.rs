@@ -0,0 +1,156 @@
+//! Storage roots. Namespaces live in the data directory unless the store
+//! maps them to one of the named roots in the `[storage_roots]` config, such
+//! as a fast SSD for active namespaces and a large HDD for archives. A root
+//! is laid out like the data directory: repositories under `repos/` and
+//! pooled LFS objects under `lfs/`. The database and everything else stay
+//! in the data directory.
+//!
+//! The mapping is read from the store on every lookup rather than cached,
+//! so `cutman admin namespace move` takes effect on a running server as
+//! soon as it records the new root.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use tracing::error;
+
+use crate::error::{Error, Result};
+use crate::store::Store;
+
+/// Name that stands for the data directory itself.
+pub const DEFAULT_ROOT: &str = "default";
+
+#[derive(Debug, Clone)]
+pub struct StorageRoots {
+    data_dir: PathBuf,
+    roots: HashMap<String, PathBuf>,
+}
+
+impl StorageRoots {
+    #[must_use]
+    pub fn new(data_dir: PathBuf, roots: HashMap<String, PathBuf>) -> Self {
+        Self { data_dir, roots }
+    }
+
+    #[must_use]
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    /// The directory of a named root; [`DEFAULT_ROOT`] is the data directory.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Path> {
+        match name {
+            DEFAULT_ROOT => Some(&self.data_dir),
+            _ => self.roots.get(name).map(PathBuf::as_path),
+        }
+    }
+
+    /// Every root, the data directory first, for work that covers all of
+    /// them such as the temp sweep and removing pooled LFS objects.
+    #[must_use]
+    pub fn all(&self) -> Vec<&Path> {
+        let mut names: Vec<_> = self.roots.keys().collect();
+        names.sort();
+        std::iter::once(self.data_dir.as_path())
+            .chain(names.into_iter().map(|name| self.roots[name].as_path()))
+            .collect()
+    }
+
+    /// Where a namespace's repositories and LFS objects live.
+    #[must_use]
+    pub fn namespace_root(&self, store: &dyn Store, namespace_id: &str) -> PathBuf {
+        self.resolve(store.get_namespace_storage_root(namespace_id), namespace_id)
+    }
+
+    /// Where the namespace that owns a repository lives.
+    #[must_use]
+    pub fn repo_root(&self, store: &dyn Store, repo_id: &str) -> PathBuf {
+        self.resolve(store.get_repo_storage_root(repo_id), repo_id)
+    }
+
+    /// Roots the config no longer names are refused at startup by
+    /// [`check_mappings`](Self::check_mappings), so falling back here only
+    /// covers a store that can't be read.
+    fn resolve(&self, root: Result<Option<String>>, id: &str) -> PathBuf {
+        match root {
+            Ok(None) => self.data_dir.clone(),
+            Ok(Some(name)) => self.get(&name).map_or_else(
+                || {
+                    error!("Storage root '{name}' of {id} is not configured");
+                    self.data_dir.clone()
+                },
+                Path::to_path_buf,
+            ),
+            Err(e) => {
+                error!("Failed to look up the storage root of {id}: {e}");
+                self.data_dir.clone()
+            }
+        }
+    }
+
+    /// Fails if a namespace is kept on a root the config doesn't name, so a
+    /// root dropped from the config shows up at startup rather than as
+    /// missing repositories.
+    pub fn check_mappings(&self, store: &dyn Store) -> Result<()> {
+        let unknown: Vec<String> = store
+            .list_namespace_storage_roots()?
+            .into_iter()
+            .filter(|(_, root)| self.get(root).is_none())
+            .map(|(namespace_id, root)| format!("namespace {namespace_id} is on '{root}'"))
+            .collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        Err(Error::Config(format!(
+            "Storage roots missing from [storage_roots]: {}",
+            unknown.join(", ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::SqliteStore;
+    use crate::types::Namespace;
+
+    #[test]
+    fn test_namespaces_resolve_to_their_root() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+        for id in ["ns-1", "ns-2"] {
+            store
+                .create_namespace(&Namespace {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    created_at: chrono::Utc::now(),
+                    repo_limit: None,
+                    storage_limit_bytes: None,
+                    external_id: None,
+                })
+                .unwrap();
+        }
+
+        let roots = StorageRoots::new(
+            PathBuf::from("/data"),
+            HashMap::from([
+                ("ssd".to_string(), PathBuf::from("/ssd")),
+                ("hdd".to_string(), PathBuf::from("/hdd")),
+            ]),
+        );
+        assert_eq!(roots.all(), [Path::new("/data"), Path::new("/hdd"), Path::new("/ssd")]);
+        assert_eq!(roots.get(DEFAULT_ROOT), Some(Path::new("/data")));
+
+        store.set_namespace_storage_root("ns-1", Some("ssd")).unwrap();
+        assert_eq!(roots.namespace_root(&store, "ns-1"), Path::new("/ssd"));
+        assert_eq!(roots.namespace_root(&store, "ns-2"), Path::new("/data"));
+        roots.check_mappings(&store).unwrap();
+
+        store.set_namespace_storage_root("ns-2", Some("tape")).unwrap();
+        let err = roots.check_mappings(&store).unwrap_err().to_string();
+        assert!(err.contains("ns-2"), "{err}");
+        assert!(!err.contains("ns-1"), "{err}");
+    }
+}
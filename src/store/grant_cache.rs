@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::types::{NamespaceGrant, RepoGrant};
+
+/// How long a grant lookup is reused before going back to the database.
+pub const DEFAULT_GRANT_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Upper bound on entries per map; expired entries are pruned when reached.
+const MAX_ENTRIES: usize = 10_000;
+
+type Key = (String, String);
+
+struct Entry<V> {
+    value: Option<V>,
+    expires_at: Instant,
+}
+
+struct TtlMap<V> {
+    entries: Mutex<HashMap<Key, Entry<V>>>,
+}
+
+impl<V: Clone> TtlMap<V> {
+    fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Key, Entry<V>>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn get(&self, principal_id: &str, target_id: &str) -> Option<Option<V>> {
+        let key = (principal_id.to_string(), target_id.to_string());
+        let mut entries = self.lock();
+        match entries.get(&key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.value.clone()),
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, principal_id: &str, target_id: &str, value: Option<V>, ttl: Duration) {
+        let now = Instant::now();
+        let mut entries = self.lock();
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, entry| entry.expires_at > now);
+            if entries.len() >= MAX_ENTRIES {
+                entries.clear();
+            }
+        }
+        entries.insert(
+            (principal_id.to_string(), target_id.to_string()),
+            Entry {
+                value,
+                expires_at: now + ttl,
+            },
+        );
+    }
+
+    fn remove(&self, principal_id: &str, target_id: &str) {
+        self.lock()
+            .remove(&(principal_id.to_string(), target_id.to_string()));
+    }
+
+    fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+/// Short-lived cache of grant lookups keyed by (principal, namespace) and
+/// (principal, repo). Writes through the store invalidate affected entries;
+/// changes made outside this process become visible once the TTL lapses.
+pub(crate) struct GrantCache {
+    ttl: Duration,
+    namespace_grants: TtlMap<NamespaceGrant>,
+    repo_grants: TtlMap<RepoGrant>,
+}
+
+impl GrantCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            namespace_grants: TtlMap::new(),
+            repo_grants: TtlMap::new(),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    pub(crate) fn get_namespace_grant(
+        &self,
+        principal_id: &str,
+        namespace_id: &str,
+    ) -> Option<Option<NamespaceGrant>> {
+        if !self.enabled() {
+            return None;
+        }
+        self.namespace_grants.get(principal_id, namespace_id)
+    }
+
+    pub(crate) fn put_namespace_grant(
+        &self,
+        principal_id: &str,
+        namespace_id: &str,
+        grant: Option<NamespaceGrant>,
+    ) {
+        if self.enabled() {
+            self.namespace_grants
+                .insert(principal_id, namespace_id, grant, self.ttl);
+        }
+    }
+
+    pub(crate) fn invalidate_namespace_grant(&self, principal_id: &str, namespace_id: &str) {
+        self.namespace_grants.remove(principal_id, namespace_id);
+    }
+
+    pub(crate) fn get_repo_grant(
+        &self,
+        principal_id: &str,
+        repo_id: &str,
+    ) -> Option<Option<RepoGrant>> {
+        if !self.enabled() {
+            return None;
+        }
+        self.repo_grants.get(principal_id, repo_id)
+    }
+
+    pub(crate) fn put_repo_grant(
+        &self,
+        principal_id: &str,
+        repo_id: &str,
+        grant: Option<RepoGrant>,
+    ) {
+        if self.enabled() {
+            self.repo_grants
+                .insert(principal_id, repo_id, grant, self.ttl);
+        }
+    }
+
+    pub(crate) fn invalidate_repo_grant(&self, principal_id: &str, repo_id: &str) {
+        self.repo_grants.remove(principal_id, repo_id);
+    }
+
+    /// Drops every entry. Used when a delete may cascade to grants.
+    pub(crate) fn clear(&self) {
+        self.namespace_grants.clear();
+        self.repo_grants.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::types::Permission;
+
+    fn repo_grant() -> RepoGrant {
+        let now = Utc::now();
+        RepoGrant {
+            principal_id: "p".to_string(),
+            repo_id: "r".to_string(),
+            allow_bits: Permission::REPO_READ,
+            deny_bits: Permission::default(),
+            paths: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_caches_hits_and_misses() {
+        let cache = GrantCache::new(Duration::from_secs(60));
+        assert!(cache.get_repo_grant("p", "r").is_none());
+
+        cache.put_repo_grant("p", "r", None);
+        assert!(matches!(cache.get_repo_grant("p", "r"), Some(None)));
+
+        cache.put_repo_grant("p", "r", Some(repo_grant()));
+        assert!(matches!(cache.get_repo_grant("p", "r"), Some(Some(_))));
+
+        cache.invalidate_repo_grant("p", "r");
+        assert!(cache.get_repo_grant("p", "r").is_none());
+    }
+
+    #[test]
+    fn test_entries_expire() {
+        let cache = GrantCache::new(Duration::from_millis(1));
+        cache.put_repo_grant("p", "r", Some(repo_grant()));
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(cache.get_repo_grant("p", "r").is_none());
+    }
+
+    #[test]
+    fn test_zero_ttl_disables_cache() {
+        let cache = GrantCache::new(Duration::ZERO);
+        cache.put_namespace_grant("p", "ns", None);
+        assert!(cache.get_namespace_grant("p", "ns").is_none());
+    }
+}
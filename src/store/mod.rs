@@ -1,9 +1,17 @@
+mod grant_cache;
 pub mod path;
 mod schema;
 mod sqlite;
 
+pub use grant_cache::DEFAULT_GRANT_CACHE_TTL;
 pub use sqlite::SqliteStore;
 
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::commit_policy::CommitPolicy;
+use crate::email_policy::EmailPolicy;
 use crate::error::Result;
 use crate::types::*;
 
@@ -28,14 +36,71 @@ pub trait Store: Send + Sync {
     fn update_principal(&self, principal: &Principal) -> Result<()>;
     fn delete_principal(&self, id: &str) -> Result<bool>;
 
+    // Verified email operations
+    /// Replaces every verified email of the principal.
+    fn set_principal_emails(&self, principal_id: &str, emails: &[String]) -> Result<()>;
+    fn list_principal_emails(&self, principal_id: &str) -> Result<Vec<String>>;
+
     // Token operations
     fn create_token(&self, token: &Token) -> Result<()>;
     fn get_token_by_id(&self, id: &str) -> Result<Option<Token>>;
     fn get_token_by_lookup(&self, lookup: &str) -> Result<Option<Token>>;
     fn list_tokens(&self, cursor: &str, limit: i32) -> Result<Vec<Token>>;
+    /// Tokens matching `filter`, ordered by `sort` then ID. A non-empty
+    /// `cursor` is the ID of the last token seen, preceded by its
+    /// [`TokenSort::key`] and a `|` when sorting by anything else.
+    fn search_tokens(
+        &self,
+        filter: &TokenFilter,
+        sort: TokenSort,
+        descending: bool,
+        cursor: &str,
+        limit: i32,
+    ) -> Result<Vec<Token>>;
     fn list_principal_tokens(&self, principal_id: &str) -> Result<Vec<Token>>;
     fn delete_token(&self, id: &str) -> Result<bool>;
     fn update_token_last_used(&self, id: &str) -> Result<()>;
+    /// Returns false if the token doesn't exist.
+    fn set_token_expiry(&self, id: &str, expires_at: Option<DateTime<Utc>>) -> Result<bool>;
+    /// Records that a token pushed from `address`. True when the token had
+    /// pushed before, but never from there.
+    fn record_token_push_address(&self, token_id: &str, address: &str) -> Result<bool>;
+
+    // SSH key operations
+    fn create_ssh_key(&self, key: &SshKey) -> Result<()>;
+    fn get_ssh_key_by_fingerprint(&self, fingerprint: &str) -> Result<Option<SshKey>>;
+    fn list_principal_ssh_keys(&self, principal_id: &str) -> Result<Vec<SshKey>>;
+    fn delete_principal_ssh_key(&self, principal_id: &str, id: &str) -> Result<bool>;
+    fn update_ssh_key_last_used(&self, id: &str) -> Result<()>;
+
+    // Commit signing key operations
+    fn create_signing_key(&self, key: &SigningKey) -> Result<()>;
+    fn list_principal_signing_keys(&self, principal_id: &str) -> Result<Vec<SigningKey>>;
+    fn delete_principal_signing_key(&self, principal_id: &str, id: &str) -> Result<bool>;
+    /// The key of the given kind that a signature naming `key_id` was made with.
+    fn find_signing_key(&self, kind: SigningKeyKind, key_id: &str) -> Result<Option<SigningKey>>;
+
+    // SCIM provisioning operations
+    fn upsert_scim_user(&self, user: &ScimUser) -> Result<()>;
+    fn get_scim_user(&self, principal_id: &str) -> Result<Option<ScimUser>>;
+    fn get_scim_user_by_external_id(&self, external_id: &str) -> Result<Option<ScimUser>>;
+    /// Whether the principal was deactivated through SCIM.
+    fn is_principal_deactivated(&self, principal_id: &str) -> Result<bool>;
+    fn upsert_scim_group(&self, group: &ScimGroup) -> Result<()>;
+    fn get_scim_group(&self, namespace_id: &str) -> Result<Option<ScimGroup>>;
+    fn list_scim_groups(&self) -> Result<Vec<ScimGroup>>;
+    fn delete_scim_group(&self, namespace_id: &str) -> Result<bool>;
+
+    // Storage root operations. Namespaces without a root live in the data
+    // directory.
+    fn get_namespace_storage_root(&self, namespace_id: &str) -> Result<Option<String>>;
+    /// The storage root of the namespace that owns the repo.
+    fn get_repo_storage_root(&self, repo_id: &str) -> Result<Option<String>>;
+    /// Moves the namespace's entry to `root`, or back to the data directory
+    /// with `None`. Only the mapping changes; the files are the caller's.
+    fn set_namespace_storage_root(&self, namespace_id: &str, root: Option<&str>) -> Result<()>;
+    /// Every namespace kept on a storage root, as `(namespace_id, root)`.
+    fn list_namespace_storage_roots(&self) -> Result<Vec<(String, String)>>;
 
     // Repo operations
     fn create_repo(&self, repo: &Repo) -> Result<()>;
@@ -45,7 +110,81 @@ pub trait Store: Send + Sync {
     fn update_repo(&self, repo: &Repo) -> Result<()>;
     fn delete_repo(&self, id: &str) -> Result<bool>;
     fn update_repo_last_push(&self, id: &str) -> Result<()>;
+    /// Marks a repo's contents as changed outside a push, returning the
+    /// change log sequence number that records it.
+    fn record_repo_write(&self, id: &str) -> Result<i64>;
     fn update_repo_size(&self, id: &str, size_bytes: i64) -> Result<()>;
+    fn count_namespace_repos(&self, namespace_id: &str) -> Result<i32>;
+    fn get_namespace_repo_size(&self, namespace_id: &str) -> Result<i64>;
+
+    // Pull mirror operations
+    fn create_repo_mirror(&self, mirror: &RepoMirror) -> Result<()>;
+    fn get_repo_mirror(&self, repo_id: &str) -> Result<Option<RepoMirror>>;
+    fn list_repo_mirrors(&self) -> Result<Vec<RepoMirror>>;
+    fn update_repo_mirror_sync(&self, repo_id: &str, error: Option<&str>) -> Result<()>;
+    fn delete_repo_mirror(&self, repo_id: &str) -> Result<bool>;
+
+    // Scheduled export operations
+    /// Creates the repo's export settings or replaces its URL, interval,
+    /// and ref rules.
+    fn set_repo_export(&self, export: &RepoExport) -> Result<()>;
+    fn get_repo_export(&self, repo_id: &str) -> Result<Option<RepoExport>>;
+    fn list_repo_exports(&self) -> Result<Vec<RepoExport>>;
+    fn update_repo_export_sync(&self, repo_id: &str, exported_refs: i64) -> Result<()>;
+    fn update_repo_export_error(&self, repo_id: &str, error: &str) -> Result<()>;
+    fn delete_repo_export(&self, repo_id: &str) -> Result<bool>;
+
+    // Pages operations
+    /// Creates the repo's Pages settings or replaces its branch and path.
+    fn set_repo_pages(&self, pages: &RepoPages) -> Result<()>;
+    fn get_repo_pages(&self, repo_id: &str) -> Result<Option<RepoPages>>;
+    fn update_repo_pages_build(
+        &self,
+        repo_id: &str,
+        commit_sha: &str,
+        size_bytes: i64,
+    ) -> Result<()>;
+    fn update_repo_pages_error(&self, repo_id: &str, error: &str) -> Result<()>;
+    fn delete_repo_pages(&self, repo_id: &str) -> Result<bool>;
+
+    // Email policy operations
+    /// Creates or replaces the namespace's commit email rules.
+    fn set_namespace_email_policy(&self, namespace_id: &str, policy: &EmailPolicy) -> Result<()>;
+    fn get_namespace_email_policy(&self, namespace_id: &str) -> Result<Option<EmailPolicy>>;
+    fn delete_namespace_email_policy(&self, namespace_id: &str) -> Result<bool>;
+
+    // Commit policy operations
+    /// Creates or replaces the repo's commit message rules.
+    fn set_repo_commit_policy(&self, repo_id: &str, policy: &CommitPolicy) -> Result<()>;
+    fn get_repo_commit_policy(&self, repo_id: &str) -> Result<Option<CommitPolicy>>;
+    fn delete_repo_commit_policy(&self, repo_id: &str) -> Result<bool>;
+
+    // Commit status operations
+    /// Creates or replaces the status of `status.context` for the commit,
+    /// keeping when the context first reported.
+    fn set_commit_status(&self, status: &CommitStatus) -> Result<()>;
+    /// Every context's status for the commit, by context.
+    fn list_commit_statuses(&self, repo_id: &str, sha: &str) -> Result<Vec<CommitStatus>>;
+
+    // Legal hold operations
+    /// Places a hold on the repo. Returns false if it already has one.
+    fn create_repo_hold(&self, hold: &RepoHold) -> Result<bool>;
+    fn get_repo_hold(&self, repo_id: &str) -> Result<Option<RepoHold>>;
+    /// Holds on repos in `namespace_id`, or on every repo when it is unset,
+    /// oldest first.
+    fn list_repo_holds(&self, namespace_id: Option<&str>) -> Result<Vec<RepoHold>>;
+    fn delete_repo_hold(&self, repo_id: &str) -> Result<bool>;
+
+    // Shared object store operations
+    /// Records the repo as borrowing from its namespace's shared object
+    /// store. Returns false if it already is.
+    fn create_shared_object_member(&self, member: &SharedObjectMember) -> Result<bool>;
+    fn get_shared_object_member(&self, repo_id: &str) -> Result<Option<SharedObjectMember>>;
+    /// Members of the namespace's store, earliest first.
+    fn list_shared_object_members(&self, namespace_id: &str) -> Result<Vec<SharedObjectMember>>;
+    /// Namespaces whose store has at least one member.
+    fn list_shared_object_namespaces(&self) -> Result<Vec<String>>;
+    fn delete_shared_object_member(&self, repo_id: &str) -> Result<bool>;
 
     // Tag operations (many-to-many with repos)
     fn create_tag(&self, tag: &Tag) -> Result<()>;
@@ -115,6 +254,98 @@ pub trait Store: Send + Sync {
     fn list_lfs_objects(&self, repo_id: &str) -> Result<Vec<LfsObject>>;
     fn delete_lfs_object(&self, repo_id: &str, oid: &str) -> Result<bool>;
     fn get_repo_lfs_size(&self, repo_id: &str) -> Result<i64>;
+    fn count_lfs_objects(&self, repo_id: &str) -> Result<i64>;
+    /// LFS bytes no other repo references, which deleting the repo frees.
+    fn get_repo_unshared_lfs_size(&self, repo_id: &str) -> Result<i64>;
+    /// The repo's LFS objects, largest first.
+    fn list_largest_lfs_objects(&self, repo_id: &str, limit: i32) -> Result<Vec<LfsObject>>;
+    /// LFS bytes referenced by the namespace's repos. An object shared by
+    /// several repos counts once for each.
+    fn get_namespace_lfs_size(&self, namespace_id: &str) -> Result<i64>;
+    /// Pooled objects no repository has referenced for at least `min_age`.
+    fn list_unreferenced_lfs_objects(&self, min_age: Duration, limit: i32) -> Result<Vec<String>>;
+    /// Drops a pooled object's entry unless it has been referenced again.
+    fn delete_unreferenced_lfs_object(&self, oid: &str) -> Result<bool>;
+    /// Referenced pooled objects with OIDs after `after_oid`, in OID order,
+    /// each with one repository that references it.
+    fn list_pooled_lfs_objects(&self, after_oid: &str, limit: i32) -> Result<Vec<LfsObject>>;
+    /// Referenced pooled objects not re-hashed within `max_age`, least
+    /// recently checked first, each with one repository that references it.
+    fn list_lfs_objects_to_verify(&self, max_age: Duration, limit: i32) -> Result<Vec<LfsObject>>;
+    /// Records a re-hash of a pooled object; an `error` marks it corrupted.
+    fn record_lfs_check(&self, oid: &str, error: Option<&str>) -> Result<()>;
+    fn is_lfs_object_corrupted(&self, oid: &str) -> Result<bool>;
+    fn list_corrupted_lfs_objects(&self) -> Result<Vec<CorruptedLfsObject>>;
+
+    // Repo size history, recorded by the database whenever a repo's git or
+    // LFS size changes
+    /// Snapshots from `since` on, oldest first, after the last one before
+    /// `since`, which gives the size going into that day.
+    fn list_repo_size_history(
+        &self,
+        repo_id: &str,
+        since: NaiveDate,
+    ) -> Result<Vec<RepoSizeSnapshot>>;
+
+    // Container registry operations
+    fn upsert_registry_manifest(&self, manifest: &RegistryManifest) -> Result<()>;
+    fn get_registry_manifest(
+        &self,
+        repo_id: &str,
+        digest: &str,
+    ) -> Result<Option<RegistryManifest>>;
+    fn set_registry_tag(&self, repo_id: &str, tag: &str, digest: &str) -> Result<()>;
+    fn get_registry_tag(&self, repo_id: &str, tag: &str) -> Result<Option<String>>;
+
+    // Package registry operations
+    fn create_package_file(&self, file: &PackageFile) -> Result<()>;
+    fn get_package_file(
+        &self,
+        namespace_id: &str,
+        package: &str,
+        version: &str,
+        filename: &str,
+    ) -> Result<Option<PackageFile>>;
+    fn delete_package_file(
+        &self,
+        namespace_id: &str,
+        package: &str,
+        version: &str,
+        filename: &str,
+    ) -> Result<bool>;
+    fn get_namespace_package_size(&self, namespace_id: &str) -> Result<i64>;
+
+    // Change log operations. Entries are recorded by triggers whenever a
+    // repo, folder, tag, or grant is written.
+    fn list_changes(&self, after_seq: i64, limit: i32) -> Result<Vec<Change>>;
+    /// Sequence number of the most recent change, or 0 before the first.
+    fn latest_change_seq(&self) -> Result<i64>;
+    /// Oldest sequence number still in the log, if any entries remain.
+    fn oldest_change_seq(&self) -> Result<Option<i64>>;
+    /// Deletes entries older than `max_age`, returning how many went.
+    fn prune_changes(&self, max_age: Duration) -> Result<usize>;
+
+    // Audit events for unusual pushes and legal holds
+    /// Records `event`, ignoring its `seq`, and returns the one assigned.
+    fn create_audit_event(&self, event: &AuditEvent) -> Result<i64>;
+    /// Events matching `filter`, newest first, from before `before_seq` when
+    /// it is set.
+    fn list_audit_events(
+        &self,
+        filter: &AuditEventFilter,
+        before_seq: Option<i64>,
+        limit: i32,
+    ) -> Result<Vec<AuditEvent>>;
+
+    // Orphan reports
+    /// Namespaces with no repos, no principal grants, and no principal
+    /// whose primary namespace they are.
+    fn list_orphan_namespaces(&self) -> Result<Vec<Namespace>>;
+    /// Non-admin tokens whose principal is gone or deactivated through
+    /// SCIM.
+    fn list_orphan_tokens(&self) -> Result<Vec<(Token, OrphanTokenReason)>>;
+    /// Folders with no repos in them or any of their subfolders.
+    fn list_empty_folders(&self) -> Result<Vec<Folder>>;
 
     // Admin token check
     fn has_admin_token(&self) -> Result<bool>;
@@ -29,7 +29,10 @@ CREATE TABLE IF NOT EXISTS repos (
     description TEXT,
 
     -- Visibility
-    public INTEGER DEFAULT 0,  -- If 1, anonymous read access allowed
+    public INTEGER DEFAULT 0,  -- 0 = private, 1 = public (anonymous read), 2 = internal (any authenticated principal)
+
+    -- Object hashing, fixed at creation
+    object_format INTEGER NOT NULL DEFAULT 0,  -- 0 = sha1, 1 = sha256
 
     -- Folder assignment (one-to-many, repo belongs to one folder)
     folder_id INTEGER REFERENCES folders(id) ON DELETE SET NULL,
@@ -43,6 +46,88 @@ CREATE TABLE IF NOT EXISTS repos (
     UNIQUE(namespace_id, name)
 );
 
+-- Pull mirrors: repos fetched from an external URL on an interval
+CREATE TABLE IF NOT EXISTS repo_mirrors (
+    repo_id TEXT PRIMARY KEY REFERENCES repos(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,               -- may embed credentials; redacted in API responses
+    interval_secs INTEGER NOT NULL,
+    created_at TEXT DEFAULT (datetime('now')),
+    last_sync_at TEXT,
+    last_error TEXT                  -- NULL after a successful sync
+);
+
+-- Scheduled exports: selected refs force-pushed to an external URL on an interval
+CREATE TABLE IF NOT EXISTS repo_exports (
+    repo_id TEXT PRIMARY KEY REFERENCES repos(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,               -- may embed credentials; redacted in API responses
+    interval_secs INTEGER NOT NULL,
+    include_refs TEXT NOT NULL,      -- newline-separated ref patterns
+    exclude_refs TEXT NOT NULL DEFAULT '',
+    created_at TEXT DEFAULT (datetime('now')),
+    last_sync_at TEXT,
+    exported_refs INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT                  -- NULL after a successful sync
+);
+
+CREATE TABLE IF NOT EXISTS repo_pages (
+    repo_id TEXT PRIMARY KEY REFERENCES repos(id) ON DELETE CASCADE,
+    branch TEXT NOT NULL,
+    path TEXT NOT NULL DEFAULT '',   -- directory of the branch to publish
+    created_at TEXT DEFAULT (datetime('now')),
+    last_build_at TEXT,
+    commit_sha TEXT,                 -- commit of the published site
+    size_bytes INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT                  -- NULL after a successful build
+);
+
+CREATE TABLE IF NOT EXISTS repo_commit_policies (
+    repo_id TEXT PRIMARY KEY REFERENCES repos(id) ON DELETE CASCADE,
+    subject_pattern TEXT,
+    conventional INTEGER NOT NULL DEFAULT 0,
+    max_subject_length INTEGER,
+    issue_pattern TEXT,
+    updated_at TEXT DEFAULT (datetime('now'))
+);
+
+-- Latest state each context (e.g. a CI job) reported for a commit
+CREATE TABLE IF NOT EXISTS commit_statuses (
+    repo_id TEXT NOT NULL REFERENCES repos(id) ON DELETE CASCADE,
+    sha TEXT NOT NULL,
+    context TEXT NOT NULL,
+    state TEXT NOT NULL,               -- pending, success, failure, error
+    target_url TEXT,
+    description TEXT,
+    creator_id TEXT REFERENCES principals(id) ON DELETE SET NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (repo_id, sha, context)
+);
+
+-- Legal holds: a held repository and its LFS objects can't be deleted until
+-- an admin lifts the hold. The triggers below back up the API's checks.
+CREATE TABLE IF NOT EXISTS repo_holds (
+    repo_id TEXT PRIMARY KEY REFERENCES repos(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    token_id TEXT,                     -- the admin token that placed the hold
+    created_at TEXT NOT NULL
+);
+
+-- Repositories borrowing objects from their namespace's shared object store.
+-- The store is kept while any repository is a member.
+CREATE TABLE IF NOT EXISTS shared_object_members (
+    repo_id TEXT PRIMARY KEY REFERENCES repos(id) ON DELETE CASCADE,
+    namespace_id TEXT NOT NULL REFERENCES namespaces(id) ON DELETE CASCADE,
+    joined_at TEXT NOT NULL
+);
+
+-- Domains the author and committer emails of pushed commits must use
+CREATE TABLE IF NOT EXISTS namespace_email_policies (
+    namespace_id TEXT PRIMARY KEY REFERENCES namespaces(id) ON DELETE CASCADE,
+    allowed_domains TEXT NOT NULL DEFAULT '',   -- newline-separated, lowercase
+    allow_pusher_emails INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT DEFAULT (datetime('now'))
+);
+
 -- Namespace grants: permissions a principal has for a namespace
 CREATE TABLE IF NOT EXISTS principal_namespace_grants (
     principal_id TEXT NOT NULL REFERENCES principals(id) ON DELETE CASCADE,
@@ -65,11 +150,22 @@ CREATE TABLE IF NOT EXISTS principal_repo_grants (
     PRIMARY KEY (principal_id, repo_id)
 );
 
+-- Paths a repo grant confines its principal's pushes to; none means the
+-- whole repository
+CREATE TABLE IF NOT EXISTS principal_repo_grant_paths (
+    principal_id TEXT NOT NULL,
+    repo_id TEXT NOT NULL,
+    path TEXT NOT NULL,
+    PRIMARY KEY (principal_id, repo_id, path),
+    FOREIGN KEY (principal_id, repo_id)
+        REFERENCES principal_repo_grants(principal_id, repo_id) ON DELETE CASCADE
+);
+
 -- Tokens are auth credentials; non-admin tokens must belong to a principal
 CREATE TABLE IF NOT EXISTS tokens (
     id TEXT PRIMARY KEY,
     token_hash TEXT NOT NULL,          -- argon2id hash with embedded salt
-    token_lookup TEXT NOT NULL,        -- first 8 chars of ID for fast lookup
+    token_lookup TEXT NOT NULL,        -- lookup part of the raw token (8 chars in legacy tokens)
     is_admin INTEGER NOT NULL DEFAULT 0,  -- admin tokens only access /api/v1/admin/* routes
 
     -- Principal binding (required for non-admin tokens, NULL only for admin tokens)
@@ -78,9 +174,81 @@ CREATE TABLE IF NOT EXISTS tokens (
     -- Lifecycle
     created_at TEXT DEFAULT (datetime('now')),
     expires_at TEXT,            -- NULL = never
+    last_used_at TEXT,
+
+    -- Scheduling: 0 = interactive, 1 = batch (queued behind interactive work)
+    traffic_class INTEGER NOT NULL DEFAULT 0
+);
+
+-- Addresses each token has pushed from, so a push from a new one can be
+-- flagged
+CREATE TABLE IF NOT EXISTS token_push_addresses (
+    token_id TEXT NOT NULL REFERENCES tokens(id) ON DELETE CASCADE,
+    address TEXT NOT NULL,
+    first_seen_at TEXT NOT NULL,
+    last_seen_at TEXT NOT NULL,
+    PRIMARY KEY (token_id, address)
+);
+
+-- SSH public keys authenticate principals on the SSH git transport
+CREATE TABLE IF NOT EXISTS ssh_keys (
+    id TEXT PRIMARY KEY,
+    principal_id TEXT NOT NULL REFERENCES principals(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    public_key TEXT NOT NULL,          -- "<algorithm> <base64>", comment stripped
+    fingerprint TEXT NOT NULL UNIQUE,  -- SHA256:<base64>, used for lookup during auth
+    created_at TEXT DEFAULT (datetime('now')),
     last_used_at TEXT
 );
 
+-- Public keys principals sign commits with; signatures they made are reported
+-- as verified by the commit API
+CREATE TABLE IF NOT EXISTS signing_keys (
+    id TEXT PRIMARY KEY,
+    principal_id TEXT NOT NULL REFERENCES principals(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,                -- gpg, ssh
+    name TEXT NOT NULL,
+    public_key TEXT NOT NULL,          -- armored OpenPGP key, or "<algorithm> <base64>"
+    fingerprint TEXT NOT NULL UNIQUE,
+    key_ids TEXT NOT NULL,             -- space-separated IDs a signature may name the key by
+    created_at TEXT DEFAULT (datetime('now'))
+);
+
+-- Emails an admin has verified as belonging to a principal; namespace email
+-- policies can let a pusher's commits carry them
+CREATE TABLE IF NOT EXISTS principal_emails (
+    principal_id TEXT NOT NULL REFERENCES principals(id) ON DELETE CASCADE,
+    email TEXT NOT NULL COLLATE NOCASE,
+    created_at TEXT DEFAULT (datetime('now')),
+    PRIMARY KEY (principal_id, email)
+);
+
+-- Principals provisioned through SCIM. Inactive principals can't authenticate;
+-- principals without a row are active.
+CREATE TABLE IF NOT EXISTS scim_users (
+    principal_id TEXT PRIMARY KEY REFERENCES principals(id) ON DELETE CASCADE,
+    external_id TEXT UNIQUE,           -- the identity provider's ID, opaque to core
+    active INTEGER NOT NULL DEFAULT 1,
+    updated_at TEXT DEFAULT (datetime('now'))
+);
+
+-- Namespaces managed as SCIM groups; members are the principals granted
+-- access to the namespace
+CREATE TABLE IF NOT EXISTS scim_groups (
+    namespace_id TEXT PRIMARY KEY REFERENCES namespaces(id) ON DELETE CASCADE,
+    external_id TEXT UNIQUE,
+    created_at TEXT DEFAULT (datetime('now'))
+);
+
+-- Namespaces kept on a storage root other than the data directory, named in
+-- the server's [storage_roots] config; namespaces without a row stay in the
+-- data directory
+CREATE TABLE IF NOT EXISTS namespace_storage_roots (
+    namespace_id TEXT PRIMARY KEY REFERENCES namespaces(id) ON DELETE CASCADE,
+    root TEXT NOT NULL,
+    updated_at TEXT DEFAULT (datetime('now'))
+);
+
 -- Tags for labeling repos (many-to-many)
 CREATE TABLE IF NOT EXISTS tags (
     id TEXT PRIMARY KEY,
@@ -119,15 +287,227 @@ CREATE TABLE IF NOT EXISTS lfs_objects (
     PRIMARY KEY (repo_id, oid)
 );
 
+-- LFS objects are stored once per OID however many repos reference them.
+-- ref_count counts the lfs_objects rows naming the OID and is kept by the
+-- triggers below; released_at is when it last dropped to zero, so the
+-- collector can remove objects nothing has referenced for a while.
+CREATE TABLE IF NOT EXISTS lfs_pool (
+    oid TEXT PRIMARY KEY,
+    size INTEGER NOT NULL,
+    ref_count INTEGER NOT NULL DEFAULT 0,
+    released_at TEXT
+);
+
+CREATE TRIGGER IF NOT EXISTS lfs_pool_ref AFTER INSERT ON lfs_objects BEGIN
+    INSERT INTO lfs_pool (oid, size, ref_count) VALUES (NEW.oid, NEW.size, 1)
+        ON CONFLICT(oid) DO UPDATE SET ref_count = ref_count + 1, released_at = NULL;
+END;
+CREATE TRIGGER IF NOT EXISTS lfs_pool_unref AFTER DELETE ON lfs_objects BEGIN
+    UPDATE lfs_pool SET ref_count = ref_count - 1,
+        released_at = CASE WHEN ref_count = 1 THEN datetime('now') ELSE released_at END
+        WHERE oid = OLD.oid;
+END;
+
+-- Held repositories keep their LFS objects, and can't go with their namespace
+CREATE TRIGGER IF NOT EXISTS repo_holds_repo BEFORE DELETE ON repos
+WHEN EXISTS (SELECT 1 FROM repo_holds WHERE repo_id = OLD.id) BEGIN
+    SELECT RAISE(ABORT, 'repository is under a legal hold');
+END;
+CREATE TRIGGER IF NOT EXISTS repo_holds_lfs BEFORE DELETE ON lfs_objects
+WHEN EXISTS (SELECT 1 FROM repo_holds WHERE repo_id = OLD.repo_id) BEGIN
+    SELECT RAISE(ABORT, 'repository is under a legal hold');
+END;
+
+-- Databases from before the pool count their existing references once
+INSERT OR IGNORE INTO lfs_pool (oid, size, ref_count)
+    SELECT oid, MAX(size), COUNT(*) FROM lfs_objects GROUP BY oid;
+
+-- When each pooled LFS object was last re-hashed, and since when its
+-- content has no longer matched its OID
+CREATE TABLE IF NOT EXISTS lfs_integrity (
+    oid TEXT PRIMARY KEY REFERENCES lfs_pool(oid) ON DELETE CASCADE,
+    checked_at TEXT NOT NULL,
+    corrupted_at TEXT,
+    error TEXT
+);
+
+-- Each repository's git and LFS size at the end of every UTC day on which
+-- either changed, kept by the triggers below
+CREATE TABLE IF NOT EXISTS repo_size_history (
+    repo_id TEXT NOT NULL REFERENCES repos(id) ON DELETE CASCADE,
+    day TEXT NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    lfs_bytes INTEGER NOT NULL,
+    PRIMARY KEY (repo_id, day)
+);
+
+CREATE TRIGGER IF NOT EXISTS repo_size_history_git AFTER UPDATE OF size_bytes ON repos BEGIN
+    INSERT INTO repo_size_history (repo_id, day, size_bytes, lfs_bytes)
+        SELECT id, date('now'), COALESCE(size_bytes, 0),
+            (SELECT COALESCE(SUM(size), 0) FROM lfs_objects WHERE repo_id = NEW.id)
+        FROM repos WHERE id = NEW.id
+        ON CONFLICT(repo_id, day) DO UPDATE SET size_bytes = excluded.size_bytes;
+END;
+CREATE TRIGGER IF NOT EXISTS repo_size_history_lfs_add AFTER INSERT ON lfs_objects BEGIN
+    INSERT INTO repo_size_history (repo_id, day, size_bytes, lfs_bytes)
+        SELECT id, date('now'), COALESCE(size_bytes, 0),
+            (SELECT COALESCE(SUM(size), 0) FROM lfs_objects WHERE repo_id = NEW.repo_id)
+        FROM repos WHERE id = NEW.repo_id
+        ON CONFLICT(repo_id, day) DO UPDATE SET lfs_bytes = excluded.lfs_bytes;
+END;
+-- Deleting a repository deletes its LFS objects too, after the repository
+-- row is gone, so there is nothing to record then
+CREATE TRIGGER IF NOT EXISTS repo_size_history_lfs_remove AFTER DELETE ON lfs_objects BEGIN
+    INSERT INTO repo_size_history (repo_id, day, size_bytes, lfs_bytes)
+        SELECT id, date('now'), COALESCE(size_bytes, 0),
+            (SELECT COALESCE(SUM(size), 0) FROM lfs_objects WHERE repo_id = OLD.repo_id)
+        FROM repos WHERE id = OLD.repo_id
+        ON CONFLICT(repo_id, day) DO UPDATE SET lfs_bytes = excluded.lfs_bytes;
+END;
+
+-- Repositories from before the history start it at their current size
+INSERT OR IGNORE INTO repo_size_history (repo_id, day, size_bytes, lfs_bytes)
+    SELECT id, date('now'), COALESCE(size_bytes, 0),
+        (SELECT COALESCE(SUM(size), 0) FROM lfs_objects WHERE repo_id = repos.id)
+    FROM repos
+    WHERE NOT EXISTS (SELECT 1 FROM repo_size_history WHERE repo_id = repos.id);
+
+-- Container registry manifests and the tags that point at them. Manifest
+-- content and image layers are stored as LFS objects.
+CREATE TABLE IF NOT EXISTS registry_manifests (
+    repo_id TEXT NOT NULL REFERENCES repos(id) ON DELETE CASCADE,
+    digest TEXT NOT NULL,
+    media_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    created_at TEXT DEFAULT (datetime('now')),
+    PRIMARY KEY (repo_id, digest)
+);
+
+CREATE TABLE IF NOT EXISTS registry_tags (
+    repo_id TEXT NOT NULL REFERENCES repos(id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    digest TEXT NOT NULL,
+    updated_at TEXT DEFAULT (datetime('now')),
+    PRIMARY KEY (repo_id, tag)
+);
+
+-- Files in each namespace's generic package registry. Content lives under
+-- packages/<namespace_id>/ in the data directory.
+CREATE TABLE IF NOT EXISTS package_files (
+    namespace_id TEXT NOT NULL REFERENCES namespaces(id) ON DELETE CASCADE,
+    package TEXT NOT NULL,
+    version TEXT NOT NULL,
+    filename TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    sha256 TEXT NOT NULL,
+    created_at TEXT DEFAULT (datetime('now')),
+    PRIMARY KEY (namespace_id, package, version, filename)
+);
+
+-- Unusual pushes flagged by the push alert checks, and legal holds placed
+-- and lifted. Nothing references the repo, principal, or token, so events
+-- outlive what they mention.
+CREATE TABLE IF NOT EXISTS audit_events (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,              -- history_rewrite, branch_deletion, new_address,
+                                     -- legal_hold_placed, legal_hold_lifted
+    summary TEXT NOT NULL,
+    namespace_id TEXT,
+    repo_id TEXT,
+    principal_id TEXT,
+    token_id TEXT,
+    address TEXT,                    -- the pusher's IP address, when known
+    detail TEXT NOT NULL,            -- JSON
+    created_at TEXT NOT NULL
+);
+
+-- Change log behind the sync endpoint, filled by the triggers below. seq is
+-- AUTOINCREMENT so pruned sequence numbers are never handed out again.
+CREATE TABLE IF NOT EXISTS changes (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL,              -- repo, folder, tag, namespace_grant, repo_grant
+    entity_id TEXT NOT NULL,         -- principal_id for grants
+    namespace_id TEXT,               -- NULL for grants
+    changed_at TEXT DEFAULT (datetime('now'))
+);
+
+CREATE TRIGGER IF NOT EXISTS changes_repo_insert AFTER INSERT ON repos BEGIN
+    INSERT INTO changes (kind, entity_id, namespace_id) VALUES ('repo', NEW.id, NEW.namespace_id);
+END;
+CREATE TRIGGER IF NOT EXISTS changes_repo_update AFTER UPDATE ON repos BEGIN
+    INSERT INTO changes (kind, entity_id, namespace_id) VALUES ('repo', NEW.id, NEW.namespace_id);
+END;
+CREATE TRIGGER IF NOT EXISTS changes_repo_delete AFTER DELETE ON repos BEGIN
+    INSERT INTO changes (kind, entity_id, namespace_id) VALUES ('repo', OLD.id, OLD.namespace_id);
+END;
+
+-- Tag assignments are reported as part of the repo; nothing is recorded
+-- when they go away with a deleted repo
+CREATE TRIGGER IF NOT EXISTS changes_repo_tag_insert AFTER INSERT ON repo_tags BEGIN
+    INSERT INTO changes (kind, entity_id, namespace_id)
+        SELECT 'repo', id, namespace_id FROM repos WHERE id = NEW.repo_id;
+END;
+CREATE TRIGGER IF NOT EXISTS changes_repo_tag_delete AFTER DELETE ON repo_tags BEGIN
+    INSERT INTO changes (kind, entity_id, namespace_id)
+        SELECT 'repo', id, namespace_id FROM repos WHERE id = OLD.repo_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS changes_folder_insert AFTER INSERT ON folders BEGIN
+    INSERT INTO changes (kind, entity_id, namespace_id) VALUES ('folder', NEW.id, NEW.namespace_id);
+END;
+CREATE TRIGGER IF NOT EXISTS changes_folder_update AFTER UPDATE ON folders BEGIN
+    INSERT INTO changes (kind, entity_id, namespace_id) VALUES ('folder', NEW.id, NEW.namespace_id);
+END;
+CREATE TRIGGER IF NOT EXISTS changes_folder_delete AFTER DELETE ON folders BEGIN
+    INSERT INTO changes (kind, entity_id, namespace_id) VALUES ('folder', OLD.id, OLD.namespace_id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS changes_tag_insert AFTER INSERT ON tags BEGIN
+    INSERT INTO changes (kind, entity_id, namespace_id) VALUES ('tag', NEW.id, NEW.namespace_id);
+END;
+CREATE TRIGGER IF NOT EXISTS changes_tag_update AFTER UPDATE ON tags BEGIN
+    INSERT INTO changes (kind, entity_id, namespace_id) VALUES ('tag', NEW.id, NEW.namespace_id);
+END;
+CREATE TRIGGER IF NOT EXISTS changes_tag_delete AFTER DELETE ON tags BEGIN
+    INSERT INTO changes (kind, entity_id, namespace_id) VALUES ('tag', OLD.id, OLD.namespace_id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS changes_namespace_grant_insert AFTER INSERT ON principal_namespace_grants BEGIN
+    INSERT INTO changes (kind, entity_id) VALUES ('namespace_grant', NEW.principal_id);
+END;
+CREATE TRIGGER IF NOT EXISTS changes_namespace_grant_update AFTER UPDATE ON principal_namespace_grants BEGIN
+    INSERT INTO changes (kind, entity_id) VALUES ('namespace_grant', NEW.principal_id);
+END;
+CREATE TRIGGER IF NOT EXISTS changes_namespace_grant_delete AFTER DELETE ON principal_namespace_grants BEGIN
+    INSERT INTO changes (kind, entity_id) VALUES ('namespace_grant', OLD.principal_id);
+END;
+
+CREATE TRIGGER IF NOT EXISTS changes_repo_grant_insert AFTER INSERT ON principal_repo_grants BEGIN
+    INSERT INTO changes (kind, entity_id) VALUES ('repo_grant', NEW.principal_id);
+END;
+CREATE TRIGGER IF NOT EXISTS changes_repo_grant_update AFTER UPDATE ON principal_repo_grants BEGIN
+    INSERT INTO changes (kind, entity_id) VALUES ('repo_grant', NEW.principal_id);
+END;
+CREATE TRIGGER IF NOT EXISTS changes_repo_grant_delete AFTER DELETE ON principal_repo_grants BEGIN
+    INSERT INTO changes (kind, entity_id) VALUES ('repo_grant', OLD.principal_id);
+END;
+
 -- Create indexes
 CREATE INDEX IF NOT EXISTS idx_repos_namespace ON repos(namespace_id);
 CREATE INDEX IF NOT EXISTS idx_repos_folder ON repos(folder_id);
+CREATE INDEX IF NOT EXISTS idx_shared_object_members_namespace ON shared_object_members(namespace_id);
 CREATE UNIQUE INDEX IF NOT EXISTS idx_tokens_lookup ON tokens(token_lookup);
 CREATE INDEX IF NOT EXISTS idx_tokens_principal ON tokens(principal_id);
+CREATE INDEX IF NOT EXISTS idx_ssh_keys_principal ON ssh_keys(principal_id);
+CREATE INDEX IF NOT EXISTS idx_signing_keys_principal ON signing_keys(principal_id);
 CREATE INDEX IF NOT EXISTS idx_tags_namespace ON tags(namespace_id);
 CREATE INDEX IF NOT EXISTS idx_folders_namespace_path ON folders(namespace_id, path);
 CREATE INDEX IF NOT EXISTS idx_lfs_objects_repo ON lfs_objects(repo_id);
 CREATE INDEX IF NOT EXISTS idx_namespace_grants_principal ON principal_namespace_grants(principal_id);
 CREATE INDEX IF NOT EXISTS idx_repo_grants_principal ON principal_repo_grants(principal_id);
 CREATE INDEX IF NOT EXISTS idx_principals_primary_namespace ON principals(primary_namespace_id);
+CREATE INDEX IF NOT EXISTS idx_changes_changed_at ON changes(changed_at);
+CREATE INDEX IF NOT EXISTS idx_audit_events_repo ON audit_events(repo_id);
+CREATE INDEX IF NOT EXISTS idx_lfs_pool_released ON lfs_pool(released_at) WHERE ref_count = 0;
+CREATE INDEX IF NOT EXISTS idx_lfs_integrity_checked ON lfs_integrity(checked_at);
 "#;
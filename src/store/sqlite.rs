@@ -1,38 +1,119 @@
+use std::ops::{Deref, DerefMut};
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use rusqlite::{Connection, OptionalExtension, params};
+use tracing::span::EnteredSpan;
 
 use super::Store;
+use super::grant_cache::{DEFAULT_GRANT_CACHE_TTL, GrantCache};
 use super::schema::SCHEMA;
+use crate::commit_policy::CommitPolicy;
+use crate::config::StorageMode;
+use crate::email_policy::EmailPolicy;
 use crate::error::{Error, Result};
+use crate::netfs;
 use crate::types::*;
 
 pub struct SqliteStore {
     conn: Mutex<Connection>,
+    grants: GrantCache,
 }
 
 impl SqliteStore {
+    /// Opens the database, detecting whether it lives on a network
+    /// filesystem.
     pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        Self::open(db_path, StorageMode::Auto)
+    }
+
+    /// Opens the database with an explicit storage mode. WAL needs shared
+    /// memory that network filesystems cannot provide, so network mode uses
+    /// a rollback journal with full syncs and relies on file locks instead.
+    pub fn open<P: AsRef<Path>>(db_path: P, mode: StorageMode) -> Result<Self> {
+        let db_path = db_path.as_ref();
+        let network = netfs::is_network_path(db_path, mode);
+        let existing = db_path.metadata().is_ok_and(|m| m.len() > 0);
         let conn = Connection::open(db_path)?;
 
         conn.pragma_update(None, "foreign_keys", "ON")?;
-        conn.pragma_update(None, "journal_mode", "WAL")?;
+        if network {
+            conn.pragma_update(None, "journal_mode", "DELETE")?;
+            conn.pragma_update(None, "synchronous", "FULL")?;
+        } else if mode == StorageMode::Local || !existing || Self::uses_wal(&conn)? {
+            conn.pragma_update(None, "journal_mode", "WAL")?;
+        } else {
+            // Only network mode leaves a database out of WAL. Keep it that way
+            // so admin commands that cannot see the server's storage_mode do
+            // not switch a database the server is using back to WAL.
+            conn.pragma_update(None, "synchronous", "FULL")?;
+        }
 
         Ok(Self {
             conn: Mutex::new(conn),
+            grants: GrantCache::new(DEFAULT_GRANT_CACHE_TTL),
         })
     }
 
-    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
-        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    fn uses_wal(conn: &Connection) -> Result<bool> {
+        let mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+        Ok(mode.eq_ignore_ascii_case("wal"))
+    }
+
+    /// Sets how long grant lookups are cached. A zero TTL disables caching.
+    #[must_use]
+    pub fn with_grant_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.grants = GrantCache::new(ttl);
+        self
+    }
+
+    /// Locks the connection inside a span that lasts until it's released,
+    /// so traces show each store call, time spent waiting included, by the
+    /// line that made it.
+    #[track_caller]
+    fn conn(&self) -> TracedConn<'_> {
+        let caller = std::panic::Location::caller();
+        let span = tracing::info_span!(
+            "store",
+            db.system = "sqlite",
+            code.filepath = caller.file(),
+            code.lineno = caller.line(),
+        )
+        .entered();
+        TracedConn {
+            conn: self.conn.lock().unwrap_or_else(|e| e.into_inner()),
+            _span: span,
+        }
     }
 
     /// Returns a guard to the underlying database connection.
     /// This allows consuming applications to execute custom SQL.
+    /// Grant changes made this way bypass the grant cache and take effect
+    /// once cached entries expire.
     pub fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
-        self.conn()
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The locked connection. The lock is released before the span closes.
+struct TracedConn<'a> {
+    conn: MutexGuard<'a, Connection>,
+    _span: EnteredSpan,
+}
+
+impl Deref for TracedConn<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+impl DerefMut for TracedConn<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        &mut self.conn
     }
 }
 
@@ -53,6 +134,155 @@ fn format_datetime(dt: &DateTime<Utc>) -> String {
     dt.to_rfc3339()
 }
 
+fn row_to_repo_mirror(row: &rusqlite::Row<'_>) -> rusqlite::Result<RepoMirror> {
+    Ok(RepoMirror {
+        repo_id: row.get(0)?,
+        url: row.get(1)?,
+        interval_secs: row.get(2)?,
+        created_at: parse_datetime(&row.get::<_, String>(3)?),
+        last_sync_at: row.get::<_, Option<String>>(4)?.map(|s| parse_datetime(&s)),
+        last_error: row.get(5)?,
+    })
+}
+
+fn row_to_repo_export(row: &rusqlite::Row<'_>) -> rusqlite::Result<RepoExport> {
+    Ok(RepoExport {
+        repo_id: row.get(0)?,
+        url: row.get(1)?,
+        interval_secs: row.get(2)?,
+        include: split_lines(&row.get::<_, String>(3)?),
+        exclude: split_lines(&row.get::<_, String>(4)?),
+        created_at: parse_datetime(&row.get::<_, String>(5)?),
+        last_sync_at: row.get::<_, Option<String>>(6)?.map(|s| parse_datetime(&s)),
+        exported_refs: row.get(7)?,
+        last_error: row.get(8)?,
+    })
+}
+
+fn split_lines(s: &str) -> Vec<String> {
+    s.lines().map(str::to_string).collect()
+}
+
+fn row_to_repo_pages(row: &rusqlite::Row<'_>) -> rusqlite::Result<RepoPages> {
+    Ok(RepoPages {
+        repo_id: row.get(0)?,
+        branch: row.get(1)?,
+        path: row.get(2)?,
+        created_at: parse_datetime(&row.get::<_, String>(3)?),
+        last_build_at: row.get::<_, Option<String>>(4)?.map(|s| parse_datetime(&s)),
+        commit_sha: row.get(5)?,
+        size_bytes: row.get(6)?,
+        last_error: row.get(7)?,
+    })
+}
+
+fn row_to_scim_user(row: &rusqlite::Row<'_>) -> rusqlite::Result<ScimUser> {
+    Ok(ScimUser {
+        principal_id: row.get(0)?,
+        external_id: row.get(1)?,
+        active: row.get(2)?,
+        updated_at: parse_datetime(&row.get::<_, String>(3)?),
+    })
+}
+
+fn row_to_scim_group(row: &rusqlite::Row<'_>) -> rusqlite::Result<ScimGroup> {
+    Ok(ScimGroup {
+        namespace_id: row.get(0)?,
+        external_id: row.get(1)?,
+        created_at: parse_datetime(&row.get::<_, String>(2)?),
+    })
+}
+
+const SIGNING_KEY_COLUMNS: &str =
+    "id, principal_id, kind, name, public_key, fingerprint, key_ids, created_at";
+
+/// A repo grant without its paths, which [`repo_grant_paths`] reads.
+fn row_to_repo_grant(row: &rusqlite::Row<'_>) -> rusqlite::Result<RepoGrant> {
+    Ok(RepoGrant {
+        principal_id: row.get(0)?,
+        repo_id: row.get(1)?,
+        allow_bits: Permission::from(row.get::<_, i64>(2)?),
+        deny_bits: Permission::from(row.get::<_, i64>(3)?),
+        paths: Vec::new(),
+        created_at: parse_datetime(&row.get::<_, String>(4)?),
+        updated_at: parse_datetime(&row.get::<_, String>(5)?),
+    })
+}
+
+fn repo_grant_paths(conn: &Connection, principal_id: &str, repo_id: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT path FROM principal_repo_grant_paths
+         WHERE principal_id = ?1 AND repo_id = ?2 ORDER BY path",
+    )?;
+    let rows = stmt.query_map(params![principal_id, repo_id], |row| row.get(0))?;
+    rows.collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(Error::from)
+}
+
+fn row_to_signing_key(row: &rusqlite::Row<'_>) -> rusqlite::Result<SigningKey> {
+    let kind: String = row.get(2)?;
+    let kind = SigningKeyKind::parse(&kind).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            2,
+            rusqlite::types::Type::Text,
+            format!("unknown signing key kind '{kind}'").into(),
+        )
+    })?;
+    Ok(SigningKey {
+        id: row.get(0)?,
+        principal_id: row.get(1)?,
+        kind,
+        name: row.get(3)?,
+        public_key: row.get(4)?,
+        fingerprint: row.get(5)?,
+        key_ids: row
+            .get::<_, String>(6)?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        created_at: parse_datetime(&row.get::<_, String>(7)?),
+    })
+}
+
+fn row_to_repo_hold(row: &rusqlite::Row<'_>) -> rusqlite::Result<RepoHold> {
+    Ok(RepoHold {
+        repo_id: row.get(0)?,
+        reason: row.get(1)?,
+        token_id: row.get(2)?,
+        created_at: parse_datetime(&row.get::<_, String>(3)?),
+    })
+}
+
+fn row_to_shared_object_member(row: &rusqlite::Row<'_>) -> rusqlite::Result<SharedObjectMember> {
+    Ok(SharedObjectMember {
+        repo_id: row.get(0)?,
+        namespace_id: row.get(1)?,
+        joined_at: parse_datetime(&row.get::<_, String>(2)?),
+    })
+}
+
+fn row_to_commit_status(row: &rusqlite::Row<'_>) -> rusqlite::Result<CommitStatus> {
+    let state: String = row.get(3)?;
+    let state = CommitState::parse(&state).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            3,
+            rusqlite::types::Type::Text,
+            format!("unknown commit state '{state}'").into(),
+        )
+    })?;
+    Ok(CommitStatus {
+        repo_id: row.get(0)?,
+        sha: row.get(1)?,
+        context: row.get(2)?,
+        state,
+        target_url: row.get(4)?,
+        description: row.get(5)?,
+        creator_id: row.get(6)?,
+        created_at: parse_datetime(&row.get::<_, String>(7)?),
+        updated_at: parse_datetime(&row.get::<_, String>(8)?),
+    })
+}
+
 impl Store for SqliteStore {
     fn initialize(&self) -> Result<()> {
         self.initialize_with_extensions(&[])
@@ -165,6 +395,7 @@ impl Store for SqliteStore {
         let rows = self
             .conn()
             .execute("DELETE FROM namespaces WHERE id = ?1", params![id])?;
+        self.grants.clear();
         Ok(rows > 0)
     }
 
@@ -261,15 +492,47 @@ impl Store for SqliteStore {
         let rows = self
             .conn()
             .execute("DELETE FROM principals WHERE id = ?1", params![id])?;
+        self.grants.clear();
         Ok(rows > 0)
     }
 
+    // Verified email operations
+
+    fn set_principal_emails(&self, principal_id: &str, emails: &[String]) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "DELETE FROM principal_emails WHERE principal_id = ?1",
+            params![principal_id],
+        )?;
+
+        for email in emails {
+            tx.execute(
+                "INSERT OR IGNORE INTO principal_emails (principal_id, email) VALUES (?1, ?2)",
+                params![principal_id, email],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn list_principal_emails(&self, principal_id: &str) -> Result<Vec<String>> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare("SELECT email FROM principal_emails WHERE principal_id = ?1 ORDER BY email")?;
+        let rows = stmt.query_map(params![principal_id], |row| row.get(0))?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
     // Token operations
 
     fn create_token(&self, token: &Token) -> Result<()> {
         let result = self.conn().execute(
-            "INSERT INTO tokens (id, token_hash, token_lookup, is_admin, principal_id, created_at, expires_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            "INSERT INTO tokens (id, token_hash, token_lookup, is_admin, principal_id, created_at, expires_at, traffic_class)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             params![
                 token.id,
                 token.token_hash,
@@ -278,6 +541,7 @@ impl Store for SqliteStore {
                 token.principal_id,
                 format_datetime(&token.created_at),
                 token.expires_at.as_ref().map(format_datetime),
+                i64::from(token.traffic_class),
             ],
         );
 
@@ -295,7 +559,7 @@ impl Store for SqliteStore {
     fn get_token_by_id(&self, id: &str) -> Result<Option<Token>> {
         let conn = self.conn();
         conn.query_row(
-            "SELECT id, token_hash, token_lookup, is_admin, principal_id, created_at, expires_at, last_used_at
+            "SELECT id, token_hash, token_lookup, is_admin, principal_id, created_at, expires_at, last_used_at, traffic_class
              FROM tokens WHERE id = ?1",
             params![id],
             |row| {
@@ -308,6 +572,7 @@ impl Store for SqliteStore {
                     created_at: parse_datetime(&row.get::<_, String>(5)?),
                     expires_at: row.get::<_, Option<String>>(6)?.map(|s| parse_datetime(&s)),
                     last_used_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                    traffic_class: TrafficClass::from(row.get::<_, i64>(8)?),
                 })
             },
         )
@@ -318,7 +583,7 @@ impl Store for SqliteStore {
     fn get_token_by_lookup(&self, lookup: &str) -> Result<Option<Token>> {
         let conn = self.conn();
         conn.query_row(
-            "SELECT id, token_hash, token_lookup, is_admin, principal_id, created_at, expires_at, last_used_at
+            "SELECT id, token_hash, token_lookup, is_admin, principal_id, created_at, expires_at, last_used_at, traffic_class
              FROM tokens WHERE token_lookup = ?1",
             params![lookup],
             |row| {
@@ -331,6 +596,7 @@ impl Store for SqliteStore {
                     created_at: parse_datetime(&row.get::<_, String>(5)?),
                     expires_at: row.get::<_, Option<String>>(6)?.map(|s| parse_datetime(&s)),
                     last_used_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                    traffic_class: TrafficClass::from(row.get::<_, i64>(8)?),
                 })
             },
         )
@@ -341,7 +607,7 @@ impl Store for SqliteStore {
     fn list_tokens(&self, cursor: &str, limit: i32) -> Result<Vec<Token>> {
         let conn = self.conn();
         let mut stmt = conn.prepare(
-            "SELECT id, token_hash, token_lookup, is_admin, principal_id, created_at, expires_at, last_used_at
+            "SELECT id, token_hash, token_lookup, is_admin, principal_id, created_at, expires_at, last_used_at, traffic_class
              FROM tokens WHERE id > ?1 ORDER BY id LIMIT ?2",
         )?;
 
@@ -355,6 +621,7 @@ impl Store for SqliteStore {
                 created_at: parse_datetime(&row.get::<_, String>(5)?),
                 expires_at: row.get::<_, Option<String>>(6)?.map(|s| parse_datetime(&s)),
                 last_used_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                traffic_class: TrafficClass::from(row.get::<_, i64>(8)?),
             })
         })?;
 
@@ -362,10 +629,85 @@ impl Store for SqliteStore {
             .map_err(Error::from)
     }
 
+    fn search_tokens(
+        &self,
+        filter: &TokenFilter,
+        sort: TokenSort,
+        descending: bool,
+        cursor: &str,
+        limit: i32,
+    ) -> Result<Vec<Token>> {
+        // Matches TokenSort::key, so a cursor is a key and an ID.
+        let key = match sort {
+            TokenSort::Id => "id",
+            TokenSort::CreatedAt => "COALESCE(datetime(created_at), '')",
+            TokenSort::LastUsedAt => "COALESCE(datetime(last_used_at), '')",
+            TokenSort::ExpiresAt => "COALESCE(datetime(expires_at), '')",
+        };
+        let (after, direction) = if descending {
+            ("<", "DESC")
+        } else {
+            (">", "ASC")
+        };
+        let (cursor_key, cursor_id) = match sort {
+            _ if cursor.is_empty() => (None, None),
+            TokenSort::Id => (Some(cursor), Some(cursor)),
+            _ => {
+                let (key, id) = cursor.rsplit_once('|').unwrap_or((cursor, ""));
+                (Some(key), Some(id))
+            }
+        };
+        let now = Utc::now();
+        let unused_since = filter
+            .unused_days
+            .map(|days| format_datetime(&(now - chrono::Duration::days(i64::from(days)))));
+
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, token_hash, token_lookup, is_admin, principal_id, created_at, expires_at, last_used_at, traffic_class
+             FROM tokens
+             WHERE (?1 IS NULL OR principal_id = ?1)
+               AND (?2 IS NULL OR is_admin = ?2)
+               AND (?3 IS NULL OR (expires_at IS NOT NULL AND datetime(expires_at) <= datetime(?4)) = ?3)
+               AND (?5 IS NULL OR datetime(COALESCE(last_used_at, created_at)) < datetime(?5))
+               AND (?6 IS NULL OR ({key}, id) {after} (?6, ?7))
+             ORDER BY {key} {direction}, id {direction} LIMIT ?8"
+        ))?;
+
+        let rows = stmt.query_map(
+            params![
+                filter.principal_id,
+                filter.is_admin,
+                filter.expired,
+                format_datetime(&now),
+                unused_since,
+                cursor_key,
+                cursor_id,
+                limit
+            ],
+            |row| {
+                Ok(Token {
+                    id: row.get(0)?,
+                    token_hash: row.get(1)?,
+                    token_lookup: row.get(2)?,
+                    is_admin: row.get(3)?,
+                    principal_id: row.get(4)?,
+                    created_at: parse_datetime(&row.get::<_, String>(5)?),
+                    expires_at: row.get::<_, Option<String>>(6)?.map(|s| parse_datetime(&s)),
+                    last_used_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                    traffic_class: TrafficClass::from(row.get::<_, i64>(8)?),
+                })
+            },
+        )?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
     fn list_principal_tokens(&self, principal_id: &str) -> Result<Vec<Token>> {
         let conn = self.conn();
         let mut stmt = conn.prepare(
-            "SELECT id, token_hash, token_lookup, is_admin, principal_id, created_at, expires_at, last_used_at
+            "SELECT id, token_hash, token_lookup, is_admin, principal_id, created_at, expires_at, last_used_at, traffic_class
              FROM tokens WHERE principal_id = ?1 ORDER BY created_at DESC",
         )?;
 
@@ -379,6 +721,7 @@ impl Store for SqliteStore {
                 created_at: parse_datetime(&row.get::<_, String>(5)?),
                 expires_at: row.get::<_, Option<String>>(6)?.map(|s| parse_datetime(&s)),
                 last_used_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                traffic_class: TrafficClass::from(row.get::<_, i64>(8)?),
             })
         })?;
 
@@ -401,71 +744,83 @@ impl Store for SqliteStore {
         Ok(())
     }
 
-    // Repo operations
+    fn record_token_push_address(&self, token_id: &str, address: &str) -> Result<bool> {
+        let conn = self.conn();
+        let now = format_datetime(&Utc::now());
+        let seen = conn.execute(
+            "UPDATE token_push_addresses SET last_seen_at = ?3
+             WHERE token_id = ?1 AND address = ?2",
+            params![token_id, address, now],
+        )?;
+        if seen > 0 {
+            return Ok(false);
+        }
+        let pushed_before: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM token_push_addresses WHERE token_id = ?1)",
+            params![token_id],
+            |row| row.get(0),
+        )?;
+        // Of two pushes from a new address at once, only the one that
+        // records it reports it.
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO token_push_addresses
+                 (token_id, address, first_seen_at, last_seen_at)
+             VALUES (?1, ?2, ?3, ?3)",
+            params![token_id, address, now],
+        )?;
+        Ok(pushed_before && inserted > 0)
+    }
 
-    fn create_repo(&self, repo: &Repo) -> Result<()> {
-        self.conn().execute(
-            "INSERT INTO repos (id, namespace_id, name, description, public, folder_id, size_bytes, last_push_at, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-            params![
-                repo.id,
-                repo.namespace_id,
-                repo.name,
-                repo.description,
-                repo.public,
-                repo.folder_id,
-                repo.size_bytes,
-                repo.last_push_at.as_ref().map(format_datetime),
-                format_datetime(&repo.created_at),
-                format_datetime(&repo.updated_at),
-            ],
+    fn set_token_expiry(&self, id: &str, expires_at: Option<DateTime<Utc>>) -> Result<bool> {
+        let updated = self.conn().execute(
+            "UPDATE tokens SET expires_at = ?1 WHERE id = ?2",
+            params![expires_at.as_ref().map(format_datetime), id],
         )?;
-        Ok(())
+        Ok(updated > 0)
     }
 
-    fn get_repo(&self, namespace_id: &str, name: &str) -> Result<Option<Repo>> {
-        let conn = self.conn();
-        conn.query_row(
-            "SELECT id, namespace_id, name, description, public, folder_id, size_bytes, last_push_at, created_at, updated_at
-             FROM repos WHERE namespace_id = ?1 AND name = ?2",
-            params![namespace_id, name],
-            |row| {
-                Ok(Repo {
-                    id: row.get(0)?,
-                    namespace_id: row.get(1)?,
-                    name: row.get(2)?,
-                    description: row.get(3)?,
-                    public: row.get(4)?,
-                    folder_id: row.get(5)?,
-                    size_bytes: row.get(6)?,
-                    last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
-                    created_at: parse_datetime(&row.get::<_, String>(8)?),
-                    updated_at: parse_datetime(&row.get::<_, String>(9)?),
-                })
-            },
-        )
-        .optional()
-        .map_err(Error::from)
+    // SSH key operations
+
+    fn create_ssh_key(&self, key: &SshKey) -> Result<()> {
+        let result = self.conn().execute(
+            "INSERT INTO ssh_keys (id, principal_id, name, public_key, fingerprint, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                key.id,
+                key.principal_id,
+                key.name,
+                key.public_key,
+                key.fingerprint,
+                format_datetime(&key.created_at),
+            ],
+        );
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(rusqlite::Error::SqliteFailure(err, _))
+                if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                Err(Error::AlreadyExists)
+            }
+            Err(e) => Err(Error::from(e)),
+        }
     }
 
-    fn get_repo_by_id(&self, id: &str) -> Result<Option<Repo>> {
+    fn get_ssh_key_by_fingerprint(&self, fingerprint: &str) -> Result<Option<SshKey>> {
         let conn = self.conn();
         conn.query_row(
-            "SELECT id, namespace_id, name, description, public, folder_id, size_bytes, last_push_at, created_at, updated_at
-             FROM repos WHERE id = ?1",
-            params![id],
+            "SELECT id, principal_id, name, public_key, fingerprint, created_at, last_used_at
+             FROM ssh_keys WHERE fingerprint = ?1",
+            params![fingerprint],
             |row| {
-                Ok(Repo {
+                Ok(SshKey {
                     id: row.get(0)?,
-                    namespace_id: row.get(1)?,
+                    principal_id: row.get(1)?,
                     name: row.get(2)?,
-                    description: row.get(3)?,
-                    public: row.get(4)?,
-                    folder_id: row.get(5)?,
-                    size_bytes: row.get(6)?,
-                    last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
-                    created_at: parse_datetime(&row.get::<_, String>(8)?),
-                    updated_at: parse_datetime(&row.get::<_, String>(9)?),
+                    public_key: row.get(3)?,
+                    fingerprint: row.get(4)?,
+                    created_at: parse_datetime(&row.get::<_, String>(5)?),
+                    last_used_at: row.get::<_, Option<String>>(6)?.map(|s| parse_datetime(&s)),
                 })
             },
         )
@@ -473,25 +828,22 @@ impl Store for SqliteStore {
         .map_err(Error::from)
     }
 
-    fn list_repos(&self, namespace_id: &str, cursor: &str, limit: i32) -> Result<Vec<Repo>> {
+    fn list_principal_ssh_keys(&self, principal_id: &str) -> Result<Vec<SshKey>> {
         let conn = self.conn();
         let mut stmt = conn.prepare(
-            "SELECT id, namespace_id, name, description, public, folder_id, size_bytes, last_push_at, created_at, updated_at
-             FROM repos WHERE namespace_id = ?1 AND name > ?2 ORDER BY name LIMIT ?3",
+            "SELECT id, principal_id, name, public_key, fingerprint, created_at, last_used_at
+             FROM ssh_keys WHERE principal_id = ?1 ORDER BY created_at DESC",
         )?;
 
-        let rows = stmt.query_map(params![namespace_id, cursor, limit], |row| {
-            Ok(Repo {
+        let rows = stmt.query_map(params![principal_id], |row| {
+            Ok(SshKey {
                 id: row.get(0)?,
-                namespace_id: row.get(1)?,
+                principal_id: row.get(1)?,
                 name: row.get(2)?,
-                description: row.get(3)?,
-                public: row.get(4)?,
-                folder_id: row.get(5)?,
-                size_bytes: row.get(6)?,
-                last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
-                created_at: parse_datetime(&row.get::<_, String>(8)?),
-                updated_at: parse_datetime(&row.get::<_, String>(9)?),
+                public_key: row.get(3)?,
+                fingerprint: row.get(4)?,
+                created_at: parse_datetime(&row.get::<_, String>(5)?),
+                last_used_at: row.get::<_, Option<String>>(6)?.map(|s| parse_datetime(&s)),
             })
         })?;
 
@@ -499,82 +851,300 @@ impl Store for SqliteStore {
             .map_err(Error::from)
     }
 
-    fn update_repo(&self, repo: &Repo) -> Result<()> {
+    fn delete_principal_ssh_key(&self, principal_id: &str, id: &str) -> Result<bool> {
         let rows = self.conn().execute(
-            "UPDATE repos SET name = ?1, description = ?2, public = ?3, updated_at = ?4 WHERE id = ?5",
-            params![
-                repo.name,
-                repo.description,
-                repo.public,
-                format_datetime(&Utc::now()),
-                repo.id
-            ],
+            "DELETE FROM ssh_keys WHERE id = ?1 AND principal_id = ?2",
+            params![id, principal_id],
         )?;
-
-        if rows == 0 {
-            return Err(Error::NotFound);
-        }
-        Ok(())
-    }
-
-    fn delete_repo(&self, id: &str) -> Result<bool> {
-        let rows = self
-            .conn()
-            .execute("DELETE FROM repos WHERE id = ?1", params![id])?;
         Ok(rows > 0)
     }
 
-    fn update_repo_last_push(&self, id: &str) -> Result<()> {
-        let rows = self.conn().execute(
-            "UPDATE repos SET last_push_at = ?1, updated_at = ?1 WHERE id = ?2",
+    fn update_ssh_key_last_used(&self, id: &str) -> Result<()> {
+        self.conn().execute(
+            "UPDATE ssh_keys SET last_used_at = ?1 WHERE id = ?2",
             params![format_datetime(&Utc::now()), id],
         )?;
-
-        if rows == 0 {
-            return Err(Error::NotFound);
-        }
         Ok(())
     }
 
-    fn update_repo_size(&self, id: &str, size_bytes: i64) -> Result<()> {
-        let rows = self.conn().execute(
-            "UPDATE repos SET size_bytes = ?1, updated_at = ?2 WHERE id = ?3",
-            params![size_bytes, format_datetime(&Utc::now()), id],
-        )?;
+    // Commit signing key operations
 
-        if rows == 0 {
-            return Err(Error::NotFound);
+    fn create_signing_key(&self, key: &SigningKey) -> Result<()> {
+        let result = self.conn().execute(
+            "INSERT INTO signing_keys (id, principal_id, kind, name, public_key, fingerprint, key_ids, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                key.id,
+                key.principal_id,
+                key.kind.as_str(),
+                key.name,
+                key.public_key,
+                key.fingerprint,
+                key.key_ids.join(" "),
+                format_datetime(&key.created_at),
+            ],
+        );
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(rusqlite::Error::SqliteFailure(err, _))
+                if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                Err(Error::AlreadyExists)
+            }
+            Err(e) => Err(Error::from(e)),
         }
-        Ok(())
     }
 
-    fn create_tag(&self, tag: &Tag) -> Result<()> {
-        self.conn().execute(
-            "INSERT INTO tags (id, namespace_id, name, color, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![
-                tag.id,
-                tag.namespace_id,
-                tag.name,
-                tag.color,
-                format_datetime(&tag.created_at),
-            ],
+    fn list_principal_signing_keys(&self, principal_id: &str) -> Result<Vec<SigningKey>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {SIGNING_KEY_COLUMNS} FROM signing_keys
+             WHERE principal_id = ?1 ORDER BY created_at DESC"
+        ))?;
+
+        let rows = stmt.query_map(params![principal_id], row_to_signing_key)?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn delete_principal_signing_key(&self, principal_id: &str, id: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM signing_keys WHERE id = ?1 AND principal_id = ?2",
+            params![id, principal_id],
         )?;
-        Ok(())
+        Ok(rows > 0)
     }
 
-    fn get_tag_by_id(&self, id: &str) -> Result<Option<Tag>> {
+    fn find_signing_key(&self, kind: SigningKeyKind, key_id: &str) -> Result<Option<SigningKey>> {
         let conn = self.conn();
         conn.query_row(
-            "SELECT id, namespace_id, name, color, created_at FROM tags WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(Tag {
-                    id: row.get(0)?,
-                    namespace_id: row.get(1)?,
+            &format!(
+                "SELECT {SIGNING_KEY_COLUMNS} FROM signing_keys
+                 WHERE kind = ?1 AND instr(' ' || key_ids || ' ', ?2) > 0"
+            ),
+            params![kind.as_str(), format!(" {key_id} ")],
+            row_to_signing_key,
+        )
+        .optional()
+        .map_err(Error::from)
+    }
+
+    // SCIM provisioning operations
+
+    fn upsert_scim_user(&self, user: &ScimUser) -> Result<()> {
+        let result = self.conn().execute(
+            "INSERT INTO scim_users (principal_id, external_id, active, updated_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(principal_id) DO UPDATE SET
+                 external_id = excluded.external_id,
+                 active = excluded.active,
+                 updated_at = excluded.updated_at",
+            params![
+                user.principal_id,
+                user.external_id,
+                user.active,
+                format_datetime(&user.updated_at),
+            ],
+        );
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(rusqlite::Error::SqliteFailure(err, _))
+                if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                Err(Error::AlreadyExists)
+            }
+            Err(e) => Err(Error::from(e)),
+        }
+    }
+
+    fn get_scim_user(&self, principal_id: &str) -> Result<Option<ScimUser>> {
+        self.conn()
+            .query_row(
+                "SELECT principal_id, external_id, active, updated_at
+                 FROM scim_users WHERE principal_id = ?1",
+                params![principal_id],
+                row_to_scim_user,
+            )
+            .optional()
+            .map_err(Error::from)
+    }
+
+    fn get_scim_user_by_external_id(&self, external_id: &str) -> Result<Option<ScimUser>> {
+        self.conn()
+            .query_row(
+                "SELECT principal_id, external_id, active, updated_at
+                 FROM scim_users WHERE external_id = ?1",
+                params![external_id],
+                row_to_scim_user,
+            )
+            .optional()
+            .map_err(Error::from)
+    }
+
+    fn is_principal_deactivated(&self, principal_id: &str) -> Result<bool> {
+        let deactivated = self
+            .conn()
+            .query_row(
+                "SELECT 1 FROM scim_users WHERE principal_id = ?1 AND active = 0",
+                params![principal_id],
+                |_| Ok(()),
+            )
+            .optional()?;
+        Ok(deactivated.is_some())
+    }
+
+    fn upsert_scim_group(&self, group: &ScimGroup) -> Result<()> {
+        let result = self.conn().execute(
+            "INSERT INTO scim_groups (namespace_id, external_id, created_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(namespace_id) DO UPDATE SET external_id = excluded.external_id",
+            params![
+                group.namespace_id,
+                group.external_id,
+                format_datetime(&group.created_at),
+            ],
+        );
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(rusqlite::Error::SqliteFailure(err, _))
+                if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                Err(Error::AlreadyExists)
+            }
+            Err(e) => Err(Error::from(e)),
+        }
+    }
+
+    fn get_scim_group(&self, namespace_id: &str) -> Result<Option<ScimGroup>> {
+        self.conn()
+            .query_row(
+                "SELECT namespace_id, external_id, created_at
+                 FROM scim_groups WHERE namespace_id = ?1",
+                params![namespace_id],
+                row_to_scim_group,
+            )
+            .optional()
+            .map_err(Error::from)
+    }
+
+    fn list_scim_groups(&self) -> Result<Vec<ScimGroup>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT namespace_id, external_id, created_at
+             FROM scim_groups ORDER BY namespace_id",
+        )?;
+        let rows = stmt.query_map([], row_to_scim_group)?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn delete_scim_group(&self, namespace_id: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM scim_groups WHERE namespace_id = ?1",
+            params![namespace_id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    // Storage root operations
+
+    fn get_namespace_storage_root(&self, namespace_id: &str) -> Result<Option<String>> {
+        self.conn()
+            .query_row(
+                "SELECT root FROM namespace_storage_roots WHERE namespace_id = ?1",
+                params![namespace_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(Error::from)
+    }
+
+    fn get_repo_storage_root(&self, repo_id: &str) -> Result<Option<String>> {
+        self.conn()
+            .query_row(
+                "SELECT nsr.root FROM repos r
+                 JOIN namespace_storage_roots nsr ON nsr.namespace_id = r.namespace_id
+                 WHERE r.id = ?1",
+                params![repo_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(Error::from)
+    }
+
+    fn set_namespace_storage_root(&self, namespace_id: &str, root: Option<&str>) -> Result<()> {
+        let conn = self.conn();
+        match root {
+            Some(root) => conn.execute(
+                "INSERT INTO namespace_storage_roots (namespace_id, root, updated_at)
+                 VALUES (?1, ?2, ?3)
+                 ON CONFLICT(namespace_id) DO UPDATE
+                 SET root = excluded.root, updated_at = excluded.updated_at",
+                params![namespace_id, root, format_datetime(&Utc::now())],
+            )?,
+            None => conn.execute(
+                "DELETE FROM namespace_storage_roots WHERE namespace_id = ?1",
+                params![namespace_id],
+            )?,
+        };
+        Ok(())
+    }
+
+    fn list_namespace_storage_roots(&self) -> Result<Vec<(String, String)>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT namespace_id, root FROM namespace_storage_roots ORDER BY namespace_id",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    // Repo operations
+
+    fn create_repo(&self, repo: &Repo) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO repos (id, namespace_id, name, description, public, folder_id, size_bytes, last_push_at, created_at, updated_at, object_format)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                repo.id,
+                repo.namespace_id,
+                repo.name,
+                repo.description,
+                i64::from(repo.visibility),
+                repo.folder_id,
+                repo.size_bytes,
+                repo.last_push_at.as_ref().map(format_datetime),
+                format_datetime(&repo.created_at),
+                format_datetime(&repo.updated_at),
+                i64::from(repo.object_format),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_repo(&self, namespace_id: &str, name: &str) -> Result<Option<Repo>> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT id, namespace_id, name, description, public, folder_id, size_bytes, last_push_at, created_at, updated_at, object_format
+             FROM repos WHERE namespace_id = ?1 AND name = ?2",
+            params![namespace_id, name],
+            |row| {
+                Ok(Repo {
+                    id: row.get(0)?,
+                    namespace_id: row.get(1)?,
                     name: row.get(2)?,
-                    color: row.get(3)?,
-                    created_at: parse_datetime(&row.get::<_, String>(4)?),
+                    description: row.get(3)?,
+                    visibility: Visibility::from(row.get::<_, i64>(4)?),
+                    object_format: ObjectFormat::from(row.get::<_, i64>(10)?),
+                    folder_id: row.get(5)?,
+                    size_bytes: row.get(6)?,
+                    last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                    created_at: parse_datetime(&row.get::<_, String>(8)?),
+                    updated_at: parse_datetime(&row.get::<_, String>(9)?),
                 })
             },
         )
@@ -582,19 +1152,25 @@ impl Store for SqliteStore {
         .map_err(Error::from)
     }
 
-    fn get_tag_by_name(&self, namespace_id: &str, name: &str) -> Result<Option<Tag>> {
+    fn get_repo_by_id(&self, id: &str) -> Result<Option<Repo>> {
         let conn = self.conn();
         conn.query_row(
-            "SELECT id, namespace_id, name, color, created_at
-             FROM tags WHERE namespace_id = ?1 AND name = ?2",
-            params![namespace_id, name],
+            "SELECT id, namespace_id, name, description, public, folder_id, size_bytes, last_push_at, created_at, updated_at, object_format
+             FROM repos WHERE id = ?1",
+            params![id],
             |row| {
-                Ok(Tag {
+                Ok(Repo {
                     id: row.get(0)?,
                     namespace_id: row.get(1)?,
                     name: row.get(2)?,
-                    color: row.get(3)?,
-                    created_at: parse_datetime(&row.get::<_, String>(4)?),
+                    description: row.get(3)?,
+                    visibility: Visibility::from(row.get::<_, i64>(4)?),
+                    object_format: ObjectFormat::from(row.get::<_, i64>(10)?),
+                    folder_id: row.get(5)?,
+                    size_bytes: row.get(6)?,
+                    last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                    created_at: parse_datetime(&row.get::<_, String>(8)?),
+                    updated_at: parse_datetime(&row.get::<_, String>(9)?),
                 })
             },
         )
@@ -602,20 +1178,26 @@ impl Store for SqliteStore {
         .map_err(Error::from)
     }
 
-    fn list_tags(&self, namespace_id: &str, cursor: &str, limit: i32) -> Result<Vec<Tag>> {
+    fn list_repos(&self, namespace_id: &str, cursor: &str, limit: i32) -> Result<Vec<Repo>> {
         let conn = self.conn();
         let mut stmt = conn.prepare(
-            "SELECT id, namespace_id, name, color, created_at
-             FROM tags WHERE namespace_id = ?1 AND name > ?2 ORDER BY name LIMIT ?3",
+            "SELECT id, namespace_id, name, description, public, folder_id, size_bytes, last_push_at, created_at, updated_at, object_format
+             FROM repos WHERE namespace_id = ?1 AND name > ?2 ORDER BY name LIMIT ?3",
         )?;
 
         let rows = stmt.query_map(params![namespace_id, cursor, limit], |row| {
-            Ok(Tag {
+            Ok(Repo {
                 id: row.get(0)?,
                 namespace_id: row.get(1)?,
                 name: row.get(2)?,
-                color: row.get(3)?,
-                created_at: parse_datetime(&row.get::<_, String>(4)?),
+                description: row.get(3)?,
+                visibility: Visibility::from(row.get::<_, i64>(4)?),
+                object_format: ObjectFormat::from(row.get::<_, i64>(10)?),
+                folder_id: row.get(5)?,
+                size_bytes: row.get(6)?,
+                last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                created_at: parse_datetime(&row.get::<_, String>(8)?),
+                updated_at: parse_datetime(&row.get::<_, String>(9)?),
             })
         })?;
 
@@ -623,10 +1205,16 @@ impl Store for SqliteStore {
             .map_err(Error::from)
     }
 
-    fn update_tag(&self, tag: &Tag) -> Result<()> {
+    fn update_repo(&self, repo: &Repo) -> Result<()> {
         let rows = self.conn().execute(
-            "UPDATE tags SET name = ?1, color = ?2 WHERE id = ?3",
-            params![tag.name, tag.color, tag.id],
+            "UPDATE repos SET name = ?1, description = ?2, public = ?3, updated_at = ?4 WHERE id = ?5",
+            params![
+                repo.name,
+                repo.description,
+                i64::from(repo.visibility),
+                format_datetime(&Utc::now()),
+                repo.id
+            ],
         )?;
 
         if rows == 0 {
@@ -635,121 +1223,293 @@ impl Store for SqliteStore {
         Ok(())
     }
 
-    fn delete_tag(&self, id: &str) -> Result<bool> {
+    fn delete_repo(&self, id: &str) -> Result<bool> {
         let rows = self
             .conn()
-            .execute("DELETE FROM tags WHERE id = ?1", params![id])?;
+            .execute("DELETE FROM repos WHERE id = ?1", params![id])?;
+        self.grants.clear();
         Ok(rows > 0)
     }
 
-    fn count_tag_repos(&self, id: &str) -> Result<i32> {
+    fn update_repo_last_push(&self, id: &str) -> Result<()> {
+        let rows = self.conn().execute(
+            "UPDATE repos SET last_push_at = ?1, updated_at = ?1 WHERE id = ?2",
+            params![format_datetime(&Utc::now()), id],
+        )?;
+
+        if rows == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    fn record_repo_write(&self, id: &str) -> Result<i64> {
+        // One lock for both statements, so the sequence read back is the
+        // entry the update's trigger wrote.
+        let conn = self.conn();
+        let rows = conn.execute(
+            "UPDATE repos SET updated_at = ?1 WHERE id = ?2",
+            params![format_datetime(&Utc::now()), id],
+        )?;
+
+        if rows == 0 {
+            return Err(Error::NotFound);
+        }
+        let seq = conn.query_row(
+            "SELECT seq FROM sqlite_sequence WHERE name = 'changes'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(seq)
+    }
+
+    fn update_repo_size(&self, id: &str, size_bytes: i64) -> Result<()> {
+        let rows = self.conn().execute(
+            "UPDATE repos SET size_bytes = ?1, updated_at = ?2 WHERE id = ?3",
+            params![size_bytes, format_datetime(&Utc::now()), id],
+        )?;
+
+        if rows == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    fn count_namespace_repos(&self, namespace_id: &str) -> Result<i32> {
         let conn = self.conn();
         let count: i32 = conn.query_row(
-            "SELECT COUNT(*) FROM repo_tags WHERE tag_id = ?1",
-            params![id],
+            "SELECT COUNT(*) FROM repos WHERE namespace_id = ?1",
+            params![namespace_id],
             |row| row.get(0),
         )?;
         Ok(count)
     }
 
-    fn add_repo_tag(&self, repo_id: &str, tag_id: &str) -> Result<()> {
+    fn get_namespace_repo_size(&self, namespace_id: &str) -> Result<i64> {
+        let conn = self.conn();
+        let size: Option<i64> = conn.query_row(
+            "SELECT SUM(size_bytes) FROM repos WHERE namespace_id = ?1",
+            params![namespace_id],
+            |row| row.get(0),
+        )?;
+        Ok(size.unwrap_or(0))
+    }
+
+    fn create_repo_mirror(&self, mirror: &RepoMirror) -> Result<()> {
         self.conn().execute(
-            "INSERT OR IGNORE INTO repo_tags (repo_id, tag_id) VALUES (?1, ?2)",
-            params![repo_id, tag_id],
+            "INSERT INTO repo_mirrors (repo_id, url, interval_secs, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                mirror.repo_id,
+                mirror.url,
+                mirror.interval_secs,
+                format_datetime(&mirror.created_at),
+            ],
         )?;
         Ok(())
     }
 
-    fn remove_repo_tag(&self, repo_id: &str, tag_id: &str) -> Result<bool> {
-        let rows = self.conn().execute(
-            "DELETE FROM repo_tags WHERE repo_id = ?1 AND tag_id = ?2",
-            params![repo_id, tag_id],
-        )?;
-        Ok(rows > 0)
+    fn get_repo_mirror(&self, repo_id: &str) -> Result<Option<RepoMirror>> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT repo_id, url, interval_secs, created_at, last_sync_at, last_error
+             FROM repo_mirrors WHERE repo_id = ?1",
+            params![repo_id],
+            row_to_repo_mirror,
+        )
+        .optional()
+        .map_err(Error::from)
     }
 
-    fn list_repo_tags(&self, repo_id: &str) -> Result<Vec<Tag>> {
+    fn list_repo_mirrors(&self) -> Result<Vec<RepoMirror>> {
         let conn = self.conn();
         let mut stmt = conn.prepare(
-            "SELECT t.id, t.namespace_id, t.name, t.color, t.created_at
-             FROM tags t
-             JOIN repo_tags rt ON t.id = rt.tag_id
-             WHERE rt.repo_id = ?1
-             ORDER BY t.name",
+            "SELECT repo_id, url, interval_secs, created_at, last_sync_at, last_error
+             FROM repo_mirrors ORDER BY repo_id",
         )?;
 
-        let rows = stmt.query_map(params![repo_id], |row| {
-            Ok(Tag {
-                id: row.get(0)?,
-                namespace_id: row.get(1)?,
-                name: row.get(2)?,
-                color: row.get(3)?,
-                created_at: parse_datetime(&row.get::<_, String>(4)?),
-            })
-        })?;
+        let rows = stmt.query_map([], row_to_repo_mirror)?;
 
         rows.collect::<std::result::Result<Vec<_>, _>>()
             .map_err(Error::from)
     }
 
-    fn list_tag_repos(&self, tag_id: &str) -> Result<Vec<Repo>> {
+    fn update_repo_mirror_sync(&self, repo_id: &str, error: Option<&str>) -> Result<()> {
+        self.conn().execute(
+            "UPDATE repo_mirrors SET last_sync_at = ?1, last_error = ?2 WHERE repo_id = ?3",
+            params![format_datetime(&Utc::now()), error, repo_id],
+        )?;
+        Ok(())
+    }
+
+    fn delete_repo_mirror(&self, repo_id: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM repo_mirrors WHERE repo_id = ?1",
+            params![repo_id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn set_repo_export(&self, export: &RepoExport) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO repo_exports (repo_id, url, interval_secs, include_refs, exclude_refs,
+                                       created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(repo_id) DO UPDATE SET url = excluded.url,
+                 interval_secs = excluded.interval_secs, include_refs = excluded.include_refs,
+                 exclude_refs = excluded.exclude_refs",
+            params![
+                export.repo_id,
+                export.url,
+                export.interval_secs,
+                export.include.join("\n"),
+                export.exclude.join("\n"),
+                format_datetime(&export.created_at),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_repo_export(&self, repo_id: &str) -> Result<Option<RepoExport>> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT repo_id, url, interval_secs, include_refs, exclude_refs, created_at,
+                    last_sync_at, exported_refs, last_error
+             FROM repo_exports WHERE repo_id = ?1",
+            params![repo_id],
+            row_to_repo_export,
+        )
+        .optional()
+        .map_err(Error::from)
+    }
+
+    fn list_repo_exports(&self) -> Result<Vec<RepoExport>> {
         let conn = self.conn();
         let mut stmt = conn.prepare(
-            "SELECT r.id, r.namespace_id, r.name, r.description, r.public, r.folder_id, r.size_bytes, r.last_push_at, r.created_at, r.updated_at
-             FROM repos r
-             JOIN repo_tags rt ON r.id = rt.repo_id
-             WHERE rt.tag_id = ?1
-             ORDER BY r.name",
+            "SELECT repo_id, url, interval_secs, include_refs, exclude_refs, created_at,
+                    last_sync_at, exported_refs, last_error
+             FROM repo_exports ORDER BY repo_id",
         )?;
 
-        let rows = stmt.query_map(params![tag_id], |row| {
-            Ok(Repo {
-                id: row.get(0)?,
-                namespace_id: row.get(1)?,
-                name: row.get(2)?,
-                description: row.get(3)?,
-                public: row.get(4)?,
-                folder_id: row.get(5)?,
-                size_bytes: row.get(6)?,
-                last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
-                created_at: parse_datetime(&row.get::<_, String>(8)?),
-                updated_at: parse_datetime(&row.get::<_, String>(9)?),
-            })
-        })?;
+        let rows = stmt.query_map([], row_to_repo_export)?;
 
         rows.collect::<std::result::Result<Vec<_>, _>>()
             .map_err(Error::from)
     }
 
-    fn set_repo_tags(&self, repo_id: &str, tag_ids: &[String]) -> Result<()> {
-        let mut conn = self.conn();
-        let tx = conn.transaction()?;
+    fn update_repo_export_sync(&self, repo_id: &str, exported_refs: i64) -> Result<()> {
+        self.conn().execute(
+            "UPDATE repo_exports SET last_sync_at = ?1, exported_refs = ?2, last_error = NULL
+             WHERE repo_id = ?3",
+            params![format_datetime(&Utc::now()), exported_refs, repo_id],
+        )?;
+        Ok(())
+    }
 
-        tx.execute("DELETE FROM repo_tags WHERE repo_id = ?1", params![repo_id])?;
+    fn update_repo_export_error(&self, repo_id: &str, error: &str) -> Result<()> {
+        self.conn().execute(
+            "UPDATE repo_exports SET last_sync_at = ?1, last_error = ?2 WHERE repo_id = ?3",
+            params![format_datetime(&Utc::now()), error, repo_id],
+        )?;
+        Ok(())
+    }
 
-        for tag_id in tag_ids {
-            tx.execute(
-                "INSERT INTO repo_tags (repo_id, tag_id) VALUES (?1, ?2)",
-                params![repo_id, tag_id],
-            )?;
-        }
+    fn delete_repo_export(&self, repo_id: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM repo_exports WHERE repo_id = ?1",
+            params![repo_id],
+        )?;
+        Ok(rows > 0)
+    }
 
-        tx.commit()?;
+    fn set_repo_pages(&self, pages: &RepoPages) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO repo_pages (repo_id, branch, path, created_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(repo_id) DO UPDATE SET branch = excluded.branch, path = excluded.path",
+            params![
+                pages.repo_id,
+                pages.branch,
+                pages.path,
+                format_datetime(&pages.created_at),
+            ],
+        )?;
         Ok(())
     }
 
-    fn get_folder_by_id(&self, id: i64) -> Result<Option<Folder>> {
+    fn get_repo_pages(&self, repo_id: &str) -> Result<Option<RepoPages>> {
         let conn = self.conn();
         conn.query_row(
-            "SELECT id, namespace_id, path, created_at, updated_at FROM folders WHERE id = ?1",
-            params![id],
+            "SELECT repo_id, branch, path, created_at, last_build_at, commit_sha, size_bytes,
+                    last_error
+             FROM repo_pages WHERE repo_id = ?1",
+            params![repo_id],
+            row_to_repo_pages,
+        )
+        .optional()
+        .map_err(Error::from)
+    }
+
+    fn update_repo_pages_build(
+        &self,
+        repo_id: &str,
+        commit_sha: &str,
+        size_bytes: i64,
+    ) -> Result<()> {
+        self.conn().execute(
+            "UPDATE repo_pages
+             SET last_build_at = ?1, commit_sha = ?2, size_bytes = ?3, last_error = NULL
+             WHERE repo_id = ?4",
+            params![format_datetime(&Utc::now()), commit_sha, size_bytes, repo_id],
+        )?;
+        Ok(())
+    }
+
+    fn update_repo_pages_error(&self, repo_id: &str, error: &str) -> Result<()> {
+        self.conn().execute(
+            "UPDATE repo_pages SET last_build_at = ?1, last_error = ?2 WHERE repo_id = ?3",
+            params![format_datetime(&Utc::now()), error, repo_id],
+        )?;
+        Ok(())
+    }
+
+    fn delete_repo_pages(&self, repo_id: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM repo_pages WHERE repo_id = ?1",
+            params![repo_id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn set_namespace_email_policy(&self, namespace_id: &str, policy: &EmailPolicy) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO namespace_email_policies (namespace_id, allowed_domains,
+                                                   allow_pusher_emails, updated_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(namespace_id) DO UPDATE SET
+                allowed_domains = excluded.allowed_domains,
+                allow_pusher_emails = excluded.allow_pusher_emails,
+                updated_at = excluded.updated_at",
+            params![
+                namespace_id,
+                policy.allowed_domains.join("\n"),
+                policy.allow_pusher_emails,
+                format_datetime(&Utc::now()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_namespace_email_policy(&self, namespace_id: &str) -> Result<Option<EmailPolicy>> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT allowed_domains, allow_pusher_emails
+             FROM namespace_email_policies WHERE namespace_id = ?1",
+            params![namespace_id],
             |row| {
-                Ok(Folder {
-                    id: row.get(0)?,
-                    namespace_id: row.get(1)?,
-                    path: row.get(2)?,
-                    created_at: parse_datetime(&row.get::<_, String>(3)?),
-                    updated_at: parse_datetime(&row.get::<_, String>(4)?),
+                Ok(EmailPolicy {
+                    allowed_domains: split_lines(&row.get::<_, String>(0)?),
+                    allow_pusher_emails: row.get(1)?,
                 })
             },
         )
@@ -757,19 +1517,49 @@ impl Store for SqliteStore {
         .map_err(Error::from)
     }
 
-    fn get_folder_by_path(&self, namespace_id: &str, path: &str) -> Result<Option<Folder>> {
+    fn delete_namespace_email_policy(&self, namespace_id: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM namespace_email_policies WHERE namespace_id = ?1",
+            params![namespace_id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn set_repo_commit_policy(&self, repo_id: &str, policy: &CommitPolicy) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO repo_commit_policies (repo_id, subject_pattern, conventional,
+                                               max_subject_length, issue_pattern, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(repo_id) DO UPDATE SET
+                subject_pattern = excluded.subject_pattern,
+                conventional = excluded.conventional,
+                max_subject_length = excluded.max_subject_length,
+                issue_pattern = excluded.issue_pattern,
+                updated_at = excluded.updated_at",
+            params![
+                repo_id,
+                policy.subject_pattern,
+                policy.conventional,
+                policy.max_subject_length,
+                policy.issue_pattern,
+                format_datetime(&Utc::now()),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_repo_commit_policy(&self, repo_id: &str) -> Result<Option<CommitPolicy>> {
         let conn = self.conn();
         conn.query_row(
-            "SELECT id, namespace_id, path, created_at, updated_at
-             FROM folders WHERE namespace_id = ?1 AND path = ?2",
-            params![namespace_id, path],
+            "SELECT subject_pattern, conventional, max_subject_length, issue_pattern
+             FROM repo_commit_policies WHERE repo_id = ?1",
+            params![repo_id],
             |row| {
-                Ok(Folder {
-                    id: row.get(0)?,
-                    namespace_id: row.get(1)?,
-                    path: row.get(2)?,
-                    created_at: parse_datetime(&row.get::<_, String>(3)?),
-                    updated_at: parse_datetime(&row.get::<_, String>(4)?),
+                Ok(CommitPolicy {
+                    subject_pattern: row.get(0)?,
+                    conventional: row.get(1)?,
+                    max_subject_length: row.get(2)?,
+                    issue_pattern: row.get(3)?,
                 })
             },
         )
@@ -777,321 +1567,202 @@ impl Store for SqliteStore {
         .map_err(Error::from)
     }
 
-    fn ensure_folder_path(&self, namespace_id: &str, path: &str) -> Result<i64> {
-        use super::path::normalize_path;
-
-        let normalized = normalize_path(path)?;
+    fn delete_repo_commit_policy(&self, repo_id: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM repo_commit_policies WHERE repo_id = ?1",
+            params![repo_id],
+        )?;
+        Ok(rows > 0)
+    }
 
-        if let Some(folder) = self.get_folder_by_path(namespace_id, &normalized)? {
-            return Ok(folder.id);
-        }
+    fn set_commit_status(&self, status: &CommitStatus) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO commit_statuses (repo_id, sha, context, state, target_url, description,
+                                          creator_id, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT(repo_id, sha, context) DO UPDATE SET
+                state = excluded.state,
+                target_url = excluded.target_url,
+                description = excluded.description,
+                creator_id = excluded.creator_id,
+                updated_at = excluded.updated_at",
+            params![
+                status.repo_id,
+                status.sha,
+                status.context,
+                status.state.as_str(),
+                status.target_url,
+                status.description,
+                status.creator_id,
+                format_datetime(&status.created_at),
+                format_datetime(&status.updated_at),
+            ],
+        )?;
+        Ok(())
+    }
 
-        let segments: Vec<&str> = normalized.trim_start_matches('/').split('/').collect();
+    fn list_commit_statuses(&self, repo_id: &str, sha: &str) -> Result<Vec<CommitStatus>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT repo_id, sha, context, state, target_url, description, creator_id,
+                    created_at, updated_at
+             FROM commit_statuses WHERE repo_id = ?1 AND sha = ?2 ORDER BY context",
+        )?;
+        let rows = stmt.query_map(params![repo_id, sha], row_to_commit_status)?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
 
-        let mut conn = self.conn();
-        let tx = conn.transaction()?;
+    fn create_repo_hold(&self, hold: &RepoHold) -> Result<bool> {
+        let rows = self.conn().execute(
+            "INSERT INTO repo_holds (repo_id, reason, token_id, created_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(repo_id) DO NOTHING",
+            params![
+                hold.repo_id,
+                hold.reason,
+                hold.token_id,
+                format_datetime(&hold.created_at),
+            ],
+        )?;
+        Ok(rows > 0)
+    }
 
-        let mut current_path = String::new();
-        let mut last_id: i64 = 0;
+    fn get_repo_hold(&self, repo_id: &str) -> Result<Option<RepoHold>> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT repo_id, reason, token_id, created_at FROM repo_holds WHERE repo_id = ?1",
+            params![repo_id],
+            row_to_repo_hold,
+        )
+        .optional()
+        .map_err(Error::from)
+    }
 
-        for segment in segments {
-            current_path = format!("{}/{}", current_path, segment);
+    fn list_repo_holds(&self, namespace_id: Option<&str>) -> Result<Vec<RepoHold>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT h.repo_id, h.reason, h.token_id, h.created_at
+             FROM repo_holds h JOIN repos r ON r.id = h.repo_id
+             WHERE ?1 IS NULL OR r.namespace_id = ?1
+             ORDER BY h.created_at, h.repo_id",
+        )?;
+        let rows = stmt.query_map(params![namespace_id], row_to_repo_hold)?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
 
-            let existing: Option<i64> = tx
-                .query_row(
-                    "SELECT id FROM folders WHERE namespace_id = ?1 AND path = ?2",
-                    params![namespace_id, current_path],
-                    |row| row.get(0),
-                )
-                .optional()?;
+    fn delete_repo_hold(&self, repo_id: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM repo_holds WHERE repo_id = ?1",
+            params![repo_id],
+        )?;
+        Ok(rows > 0)
+    }
 
-            match existing {
-                Some(id) => {
-                    last_id = id;
-                }
-                None => {
-                    tx.execute(
-                        "INSERT INTO folders (namespace_id, path, created_at, updated_at)
-                         VALUES (?1, ?2, ?3, ?3)",
-                        params![namespace_id, current_path, format_datetime(&Utc::now())],
-                    )?;
-                    last_id = tx.last_insert_rowid();
-                }
-            }
-        }
+    fn create_shared_object_member(&self, member: &SharedObjectMember) -> Result<bool> {
+        let rows = self.conn().execute(
+            "INSERT INTO shared_object_members (repo_id, namespace_id, joined_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(repo_id) DO NOTHING",
+            params![
+                member.repo_id,
+                member.namespace_id,
+                format_datetime(&member.joined_at),
+            ],
+        )?;
+        Ok(rows > 0)
+    }
 
-        tx.commit()?;
-        Ok(last_id)
+    fn get_shared_object_member(&self, repo_id: &str) -> Result<Option<SharedObjectMember>> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT repo_id, namespace_id, joined_at FROM shared_object_members
+             WHERE repo_id = ?1",
+            params![repo_id],
+            row_to_shared_object_member,
+        )
+        .optional()
+        .map_err(Error::from)
     }
 
-    fn list_all_folders(&self, namespace_id: &str) -> Result<Vec<Folder>> {
+    fn list_shared_object_members(&self, namespace_id: &str) -> Result<Vec<SharedObjectMember>> {
         let conn = self.conn();
         let mut stmt = conn.prepare(
-            "SELECT id, namespace_id, path, created_at, updated_at
-             FROM folders WHERE namespace_id = ?1 ORDER BY path",
+            "SELECT repo_id, namespace_id, joined_at FROM shared_object_members
+             WHERE namespace_id = ?1
+             ORDER BY joined_at, repo_id",
         )?;
-
-        let rows = stmt.query_map(params![namespace_id], |row| {
-            Ok(Folder {
-                id: row.get(0)?,
-                namespace_id: row.get(1)?,
-                path: row.get(2)?,
-                created_at: parse_datetime(&row.get::<_, String>(3)?),
-                updated_at: parse_datetime(&row.get::<_, String>(4)?),
-            })
-        })?;
-
+        let rows = stmt.query_map(params![namespace_id], row_to_shared_object_member)?;
         rows.collect::<std::result::Result<Vec<_>, _>>()
             .map_err(Error::from)
     }
 
-    fn move_folder(&self, id: i64, new_path: &str) -> Result<()> {
-        use super::path::normalize_path;
-
-        let normalized_new = normalize_path(new_path)?;
+    fn list_shared_object_namespaces(&self) -> Result<Vec<String>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT namespace_id FROM shared_object_members ORDER BY namespace_id",
+        )?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
 
-        let folder = self.get_folder_by_id(id)?.ok_or(Error::NotFound)?;
-
-        if normalized_new.starts_with(&format!("{}/", folder.path)) {
-            return Err(Error::BadRequest(
-                "Cannot move folder into itself or its descendants".to_string(),
-            ));
-        }
-
-        if let Some(existing) = self.get_folder_by_path(&folder.namespace_id, &normalized_new)? {
-            if existing.id != id {
-                return Err(Error::Conflict(format!(
-                    "Folder already exists at path '{}'",
-                    normalized_new
-                )));
-            }
-        }
-
-        let old_path = &folder.path;
-        let old_prefix = format!("{}/", old_path);
-        let new_prefix = format!("{}/", normalized_new);
-
-        let mut conn = self.conn();
-        let tx = conn.transaction()?;
-        let now = format_datetime(&Utc::now());
-
-        tx.execute(
-            "UPDATE folders SET path = ?1, updated_at = ?2 WHERE id = ?3",
-            params![normalized_new, now, id],
-        )?;
-
-        tx.execute(
-            "UPDATE folders SET path = ?1 || substr(path, ?2), updated_at = ?3
-             WHERE namespace_id = ?4 AND path LIKE ?5",
-            params![
-                new_prefix.trim_end_matches('/'),
-                old_prefix.len() as i32,
-                now,
-                folder.namespace_id,
-                format!("{}%", old_prefix)
-            ],
+    fn delete_shared_object_member(&self, repo_id: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM shared_object_members WHERE repo_id = ?1",
+            params![repo_id],
         )?;
-
-        tx.commit()?;
-        Ok(())
+        Ok(rows > 0)
     }
 
-    fn delete_folder(&self, id: i64) -> Result<bool> {
-        let folder = match self.get_folder_by_id(id)? {
-            Some(f) => f,
-            None => return Ok(false),
-        };
-
-        let path_prefix = format!("{}/", folder.path);
-
-        let mut conn = self.conn();
-        let tx = conn.transaction()?;
-
-        tx.execute(
-            "UPDATE repos SET folder_id = NULL, updated_at = ?1
-             WHERE folder_id IN (
-                 SELECT id FROM folders
-                 WHERE namespace_id = ?2 AND (path = ?3 OR path LIKE ?4)
-             )",
+    fn create_tag(&self, tag: &Tag) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO tags (id, namespace_id, name, color, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
             params![
-                format_datetime(&Utc::now()),
-                folder.namespace_id,
-                folder.path,
-                format!("{}%", path_prefix)
+                tag.id,
+                tag.namespace_id,
+                tag.name,
+                tag.color,
+                format_datetime(&tag.created_at),
             ],
         )?;
-
-        tx.execute(
-            "DELETE FROM folders WHERE namespace_id = ?1 AND path LIKE ?2",
-            params![folder.namespace_id, format!("{}%", path_prefix)],
-        )?;
-
-        let rows = tx.execute("DELETE FROM folders WHERE id = ?1", params![id])?;
-
-        tx.commit()?;
-        Ok(rows > 0)
-    }
-
-    fn set_repo_folder(&self, repo_id: &str, folder_id: Option<i64>) -> Result<()> {
-        let rows = self.conn().execute(
-            "UPDATE repos SET folder_id = ?1, updated_at = ?2 WHERE id = ?3",
-            params![folder_id, format_datetime(&Utc::now()), repo_id],
-        )?;
-
-        if rows == 0 {
-            return Err(Error::NotFound);
-        }
         Ok(())
     }
 
-    fn set_repo_folder_by_path(
-        &self,
-        repo_id: &str,
-        namespace_id: &str,
-        path: Option<&str>,
-    ) -> Result<Option<i64>> {
-        match path {
-            Some(p) => {
-                let folder_id = self.ensure_folder_path(namespace_id, p)?;
-                self.set_repo_folder(repo_id, Some(folder_id))?;
-                Ok(Some(folder_id))
-            }
-            None => {
-                self.set_repo_folder(repo_id, None)?;
-                Ok(None)
-            }
-        }
-    }
-
-    fn list_folder_repos(
-        &self,
-        namespace_id: &str,
-        path: &str,
-        recursive: bool,
-    ) -> Result<Vec<Repo>> {
-        use super::path::normalize_path;
-
-        let normalized = normalize_path(path)?;
+    fn get_tag_by_id(&self, id: &str) -> Result<Option<Tag>> {
         let conn = self.conn();
-
-        if recursive {
-            // Match exact path or any path that starts with path/
-            let path_prefix = format!("{}/", normalized);
-            let mut stmt = conn.prepare(
-                "SELECT r.id, r.namespace_id, r.name, r.description, r.public, r.folder_id,
-                        r.size_bytes, r.last_push_at, r.created_at, r.updated_at
-                 FROM repos r
-                 JOIN folders f ON r.folder_id = f.id
-                 WHERE f.namespace_id = ?1 AND (f.path = ?2 OR f.path LIKE ?3)
-                 ORDER BY r.name",
-            )?;
-
-            let rows = stmt.query_map(
-                params![namespace_id, normalized, format!("{}%", path_prefix)],
-                |row| {
-                    Ok(Repo {
-                        id: row.get(0)?,
-                        namespace_id: row.get(1)?,
-                        name: row.get(2)?,
-                        description: row.get(3)?,
-                        public: row.get(4)?,
-                        folder_id: row.get(5)?,
-                        size_bytes: row.get(6)?,
-                        last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
-                        created_at: parse_datetime(&row.get::<_, String>(8)?),
-                        updated_at: parse_datetime(&row.get::<_, String>(9)?),
-                    })
-                },
-            )?;
-
-            rows.collect::<std::result::Result<Vec<_>, _>>()
-                .map_err(Error::from)
-        } else {
-            // Match exact path only
-            let mut stmt = conn.prepare(
-                "SELECT r.id, r.namespace_id, r.name, r.description, r.public, r.folder_id,
-                        r.size_bytes, r.last_push_at, r.created_at, r.updated_at
-                 FROM repos r
-                 JOIN folders f ON r.folder_id = f.id
-                 WHERE f.namespace_id = ?1 AND f.path = ?2
-                 ORDER BY r.name",
-            )?;
-
-            let rows = stmt.query_map(params![namespace_id, normalized], |row| {
-                Ok(Repo {
+        conn.query_row(
+            "SELECT id, namespace_id, name, color, created_at FROM tags WHERE id = ?1",
+            params![id],
+            |row| {
+                Ok(Tag {
                     id: row.get(0)?,
                     namespace_id: row.get(1)?,
                     name: row.get(2)?,
-                    description: row.get(3)?,
-                    public: row.get(4)?,
-                    folder_id: row.get(5)?,
-                    size_bytes: row.get(6)?,
-                    last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
-                    created_at: parse_datetime(&row.get::<_, String>(8)?),
-                    updated_at: parse_datetime(&row.get::<_, String>(9)?),
+                    color: row.get(3)?,
+                    created_at: parse_datetime(&row.get::<_, String>(4)?),
                 })
-            })?;
-
-            rows.collect::<std::result::Result<Vec<_>, _>>()
-                .map_err(Error::from)
-        }
-    }
-
-    // Namespace grant operations
-
-    fn upsert_namespace_grant(&self, grant: &NamespaceGrant) -> Result<()> {
-        // Check if the namespace belongs to another principal as their primary
-        if let Some(owner) = self.get_principal_by_primary_namespace_id(&grant.namespace_id)? {
-            if owner.id != grant.principal_id {
-                return Err(Error::PrimaryNamespaceGrant);
-            }
-        }
-
-        self.conn().execute(
-            "INSERT INTO principal_namespace_grants (principal_id, namespace_id, allow_bits, deny_bits, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-             ON CONFLICT (principal_id, namespace_id) DO UPDATE SET
-                allow_bits = excluded.allow_bits,
-                deny_bits = excluded.deny_bits,
-                updated_at = excluded.updated_at",
-            params![
-                grant.principal_id,
-                grant.namespace_id,
-                i64::from(grant.allow_bits),
-                i64::from(grant.deny_bits),
-                format_datetime(&grant.created_at),
-                format_datetime(&grant.updated_at),
-            ],
-        )?;
-        Ok(())
-    }
-
-    fn delete_namespace_grant(&self, principal_id: &str, namespace_id: &str) -> Result<bool> {
-        let rows = self.conn().execute(
-            "DELETE FROM principal_namespace_grants WHERE principal_id = ?1 AND namespace_id = ?2",
-            params![principal_id, namespace_id],
-        )?;
-        Ok(rows > 0)
+            },
+        )
+        .optional()
+        .map_err(Error::from)
     }
 
-    fn get_namespace_grant(
-        &self,
-        principal_id: &str,
-        namespace_id: &str,
-    ) -> Result<Option<NamespaceGrant>> {
+    fn get_tag_by_name(&self, namespace_id: &str, name: &str) -> Result<Option<Tag>> {
         let conn = self.conn();
         conn.query_row(
-            "SELECT principal_id, namespace_id, allow_bits, deny_bits, created_at, updated_at
-             FROM principal_namespace_grants WHERE principal_id = ?1 AND namespace_id = ?2",
-            params![principal_id, namespace_id],
+            "SELECT id, namespace_id, name, color, created_at
+             FROM tags WHERE namespace_id = ?1 AND name = ?2",
+            params![namespace_id, name],
             |row| {
-                Ok(NamespaceGrant {
-                    principal_id: row.get(0)?,
+                Ok(Tag {
+                    id: row.get(0)?,
                     namespace_id: row.get(1)?,
-                    allow_bits: Permission::from(row.get::<_, i64>(2)?),
-                    deny_bits: Permission::from(row.get::<_, i64>(3)?),
+                    name: row.get(2)?,
+                    color: row.get(3)?,
                     created_at: parse_datetime(&row.get::<_, String>(4)?),
-                    updated_at: parse_datetime(&row.get::<_, String>(5)?),
                 })
             },
         )
@@ -1099,21 +1770,20 @@ impl Store for SqliteStore {
         .map_err(Error::from)
     }
 
-    fn list_principal_namespace_grants(&self, principal_id: &str) -> Result<Vec<NamespaceGrant>> {
+    fn list_tags(&self, namespace_id: &str, cursor: &str, limit: i32) -> Result<Vec<Tag>> {
         let conn = self.conn();
         let mut stmt = conn.prepare(
-            "SELECT principal_id, namespace_id, allow_bits, deny_bits, created_at, updated_at
-             FROM principal_namespace_grants WHERE principal_id = ?1 ORDER BY namespace_id",
+            "SELECT id, namespace_id, name, color, created_at
+             FROM tags WHERE namespace_id = ?1 AND name > ?2 ORDER BY name LIMIT ?3",
         )?;
 
-        let rows = stmt.query_map(params![principal_id], |row| {
-            Ok(NamespaceGrant {
-                principal_id: row.get(0)?,
+        let rows = stmt.query_map(params![namespace_id, cursor, limit], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
                 namespace_id: row.get(1)?,
-                allow_bits: Permission::from(row.get::<_, i64>(2)?),
-                deny_bits: Permission::from(row.get::<_, i64>(3)?),
+                name: row.get(2)?,
+                color: row.get(3)?,
                 created_at: parse_datetime(&row.get::<_, String>(4)?),
-                updated_at: parse_datetime(&row.get::<_, String>(5)?),
             })
         })?;
 
@@ -1121,308 +1791,2491 @@ impl Store for SqliteStore {
             .map_err(Error::from)
     }
 
-    fn list_namespace_grants_for_namespace(
-        &self,
-        namespace_id: &str,
-    ) -> Result<Vec<NamespaceGrant>> {
-        let conn = self.conn();
-        let mut stmt = conn.prepare(
-            "SELECT principal_id, namespace_id, allow_bits, deny_bits, created_at, updated_at
-             FROM principal_namespace_grants WHERE namespace_id = ?1 ORDER BY principal_id",
-        )?;
+    fn update_tag(&self, tag: &Tag) -> Result<()> {
+        let rows = self.conn().execute(
+            "UPDATE tags SET name = ?1, color = ?2 WHERE id = ?3",
+            params![tag.name, tag.color, tag.id],
+        )?;
+
+        if rows == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    fn delete_tag(&self, id: &str) -> Result<bool> {
+        let rows = self
+            .conn()
+            .execute("DELETE FROM tags WHERE id = ?1", params![id])?;
+        Ok(rows > 0)
+    }
+
+    fn count_tag_repos(&self, id: &str) -> Result<i32> {
+        let conn = self.conn();
+        let count: i32 = conn.query_row(
+            "SELECT COUNT(*) FROM repo_tags WHERE tag_id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+
+    fn add_repo_tag(&self, repo_id: &str, tag_id: &str) -> Result<()> {
+        self.conn().execute(
+            "INSERT OR IGNORE INTO repo_tags (repo_id, tag_id) VALUES (?1, ?2)",
+            params![repo_id, tag_id],
+        )?;
+        Ok(())
+    }
+
+    fn remove_repo_tag(&self, repo_id: &str, tag_id: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM repo_tags WHERE repo_id = ?1 AND tag_id = ?2",
+            params![repo_id, tag_id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn list_repo_tags(&self, repo_id: &str) -> Result<Vec<Tag>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT t.id, t.namespace_id, t.name, t.color, t.created_at
+             FROM tags t
+             JOIN repo_tags rt ON t.id = rt.tag_id
+             WHERE rt.repo_id = ?1
+             ORDER BY t.name",
+        )?;
+
+        let rows = stmt.query_map(params![repo_id], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                namespace_id: row.get(1)?,
+                name: row.get(2)?,
+                color: row.get(3)?,
+                created_at: parse_datetime(&row.get::<_, String>(4)?),
+            })
+        })?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn list_tag_repos(&self, tag_id: &str) -> Result<Vec<Repo>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT r.id, r.namespace_id, r.name, r.description, r.public, r.folder_id, r.size_bytes, r.last_push_at, r.created_at, r.updated_at, r.object_format
+             FROM repos r
+             JOIN repo_tags rt ON r.id = rt.repo_id
+             WHERE rt.tag_id = ?1
+             ORDER BY r.name",
+        )?;
+
+        let rows = stmt.query_map(params![tag_id], |row| {
+            Ok(Repo {
+                id: row.get(0)?,
+                namespace_id: row.get(1)?,
+                name: row.get(2)?,
+                description: row.get(3)?,
+                visibility: Visibility::from(row.get::<_, i64>(4)?),
+                object_format: ObjectFormat::from(row.get::<_, i64>(10)?),
+                folder_id: row.get(5)?,
+                size_bytes: row.get(6)?,
+                last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                created_at: parse_datetime(&row.get::<_, String>(8)?),
+                updated_at: parse_datetime(&row.get::<_, String>(9)?),
+            })
+        })?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn set_repo_tags(&self, repo_id: &str, tag_ids: &[String]) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM repo_tags WHERE repo_id = ?1", params![repo_id])?;
+
+        for tag_id in tag_ids {
+            tx.execute(
+                "INSERT INTO repo_tags (repo_id, tag_id) VALUES (?1, ?2)",
+                params![repo_id, tag_id],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_folder_by_id(&self, id: i64) -> Result<Option<Folder>> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT id, namespace_id, path, created_at, updated_at FROM folders WHERE id = ?1",
+            params![id],
+            |row| {
+                Ok(Folder {
+                    id: row.get(0)?,
+                    namespace_id: row.get(1)?,
+                    path: row.get(2)?,
+                    created_at: parse_datetime(&row.get::<_, String>(3)?),
+                    updated_at: parse_datetime(&row.get::<_, String>(4)?),
+                })
+            },
+        )
+        .optional()
+        .map_err(Error::from)
+    }
+
+    fn get_folder_by_path(&self, namespace_id: &str, path: &str) -> Result<Option<Folder>> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT id, namespace_id, path, created_at, updated_at
+             FROM folders WHERE namespace_id = ?1 AND path = ?2",
+            params![namespace_id, path],
+            |row| {
+                Ok(Folder {
+                    id: row.get(0)?,
+                    namespace_id: row.get(1)?,
+                    path: row.get(2)?,
+                    created_at: parse_datetime(&row.get::<_, String>(3)?),
+                    updated_at: parse_datetime(&row.get::<_, String>(4)?),
+                })
+            },
+        )
+        .optional()
+        .map_err(Error::from)
+    }
+
+    fn ensure_folder_path(&self, namespace_id: &str, path: &str) -> Result<i64> {
+        use super::path::normalize_path;
+
+        let normalized = normalize_path(path)?;
+
+        if let Some(folder) = self.get_folder_by_path(namespace_id, &normalized)? {
+            return Ok(folder.id);
+        }
+
+        let segments: Vec<&str> = normalized.trim_start_matches('/').split('/').collect();
+
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let mut current_path = String::new();
+        let mut last_id: i64 = 0;
+
+        for segment in segments {
+            current_path = format!("{}/{}", current_path, segment);
+
+            let existing: Option<i64> = tx
+                .query_row(
+                    "SELECT id FROM folders WHERE namespace_id = ?1 AND path = ?2",
+                    params![namespace_id, current_path],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            match existing {
+                Some(id) => {
+                    last_id = id;
+                }
+                None => {
+                    tx.execute(
+                        "INSERT INTO folders (namespace_id, path, created_at, updated_at)
+                         VALUES (?1, ?2, ?3, ?3)",
+                        params![namespace_id, current_path, format_datetime(&Utc::now())],
+                    )?;
+                    last_id = tx.last_insert_rowid();
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok(last_id)
+    }
+
+    fn list_all_folders(&self, namespace_id: &str) -> Result<Vec<Folder>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, namespace_id, path, created_at, updated_at
+             FROM folders WHERE namespace_id = ?1 ORDER BY path",
+        )?;
+
+        let rows = stmt.query_map(params![namespace_id], |row| {
+            Ok(Folder {
+                id: row.get(0)?,
+                namespace_id: row.get(1)?,
+                path: row.get(2)?,
+                created_at: parse_datetime(&row.get::<_, String>(3)?),
+                updated_at: parse_datetime(&row.get::<_, String>(4)?),
+            })
+        })?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn move_folder(&self, id: i64, new_path: &str) -> Result<()> {
+        use super::path::normalize_path;
+
+        let normalized_new = normalize_path(new_path)?;
+
+        let folder = self.get_folder_by_id(id)?.ok_or(Error::NotFound)?;
+
+        if normalized_new.starts_with(&format!("{}/", folder.path)) {
+            return Err(Error::BadRequest(
+                "Cannot move folder into itself or its descendants".to_string(),
+            ));
+        }
+
+        if let Some(existing) = self.get_folder_by_path(&folder.namespace_id, &normalized_new)? {
+            if existing.id != id {
+                return Err(Error::Conflict(format!(
+                    "Folder already exists at path '{}'",
+                    normalized_new
+                )));
+            }
+        }
+
+        let old_path = &folder.path;
+        let old_prefix = format!("{}/", old_path);
+        let new_prefix = format!("{}/", normalized_new);
+
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let now = format_datetime(&Utc::now());
+
+        tx.execute(
+            "UPDATE folders SET path = ?1, updated_at = ?2 WHERE id = ?3",
+            params![normalized_new, now, id],
+        )?;
+
+        tx.execute(
+            "UPDATE folders SET path = ?1 || substr(path, ?2), updated_at = ?3
+             WHERE namespace_id = ?4 AND path LIKE ?5",
+            params![
+                new_prefix.trim_end_matches('/'),
+                old_prefix.len() as i32,
+                now,
+                folder.namespace_id,
+                format!("{}%", old_prefix)
+            ],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete_folder(&self, id: i64) -> Result<bool> {
+        let folder = match self.get_folder_by_id(id)? {
+            Some(f) => f,
+            None => return Ok(false),
+        };
+
+        let path_prefix = format!("{}/", folder.path);
+
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "UPDATE repos SET folder_id = NULL, updated_at = ?1
+             WHERE folder_id IN (
+                 SELECT id FROM folders
+                 WHERE namespace_id = ?2 AND (path = ?3 OR path LIKE ?4)
+             )",
+            params![
+                format_datetime(&Utc::now()),
+                folder.namespace_id,
+                folder.path,
+                format!("{}%", path_prefix)
+            ],
+        )?;
+
+        tx.execute(
+            "DELETE FROM folders WHERE namespace_id = ?1 AND path LIKE ?2",
+            params![folder.namespace_id, format!("{}%", path_prefix)],
+        )?;
+
+        let rows = tx.execute("DELETE FROM folders WHERE id = ?1", params![id])?;
+
+        tx.commit()?;
+        Ok(rows > 0)
+    }
+
+    fn set_repo_folder(&self, repo_id: &str, folder_id: Option<i64>) -> Result<()> {
+        let rows = self.conn().execute(
+            "UPDATE repos SET folder_id = ?1, updated_at = ?2 WHERE id = ?3",
+            params![folder_id, format_datetime(&Utc::now()), repo_id],
+        )?;
+
+        if rows == 0 {
+            return Err(Error::NotFound);
+        }
+        Ok(())
+    }
+
+    fn set_repo_folder_by_path(
+        &self,
+        repo_id: &str,
+        namespace_id: &str,
+        path: Option<&str>,
+    ) -> Result<Option<i64>> {
+        match path {
+            Some(p) => {
+                let folder_id = self.ensure_folder_path(namespace_id, p)?;
+                self.set_repo_folder(repo_id, Some(folder_id))?;
+                Ok(Some(folder_id))
+            }
+            None => {
+                self.set_repo_folder(repo_id, None)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn list_folder_repos(
+        &self,
+        namespace_id: &str,
+        path: &str,
+        recursive: bool,
+    ) -> Result<Vec<Repo>> {
+        use super::path::normalize_path;
+
+        let normalized = normalize_path(path)?;
+        let conn = self.conn();
+
+        if recursive {
+            // Match exact path or any path that starts with path/
+            let path_prefix = format!("{}/", normalized);
+            let mut stmt = conn.prepare(
+                "SELECT r.id, r.namespace_id, r.name, r.description, r.public, r.folder_id,
+                        r.size_bytes, r.last_push_at, r.created_at, r.updated_at, r.object_format
+                 FROM repos r
+                 JOIN folders f ON r.folder_id = f.id
+                 WHERE f.namespace_id = ?1 AND (f.path = ?2 OR f.path LIKE ?3)
+                 ORDER BY r.name",
+            )?;
+
+            let rows = stmt.query_map(
+                params![namespace_id, normalized, format!("{}%", path_prefix)],
+                |row| {
+                    Ok(Repo {
+                        id: row.get(0)?,
+                        namespace_id: row.get(1)?,
+                        name: row.get(2)?,
+                        description: row.get(3)?,
+                        visibility: Visibility::from(row.get::<_, i64>(4)?),
+                        object_format: ObjectFormat::from(row.get::<_, i64>(10)?),
+                        folder_id: row.get(5)?,
+                        size_bytes: row.get(6)?,
+                        last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                        created_at: parse_datetime(&row.get::<_, String>(8)?),
+                        updated_at: parse_datetime(&row.get::<_, String>(9)?),
+                    })
+                },
+            )?;
+
+            rows.collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(Error::from)
+        } else {
+            // Match exact path only
+            let mut stmt = conn.prepare(
+                "SELECT r.id, r.namespace_id, r.name, r.description, r.public, r.folder_id,
+                        r.size_bytes, r.last_push_at, r.created_at, r.updated_at, r.object_format
+                 FROM repos r
+                 JOIN folders f ON r.folder_id = f.id
+                 WHERE f.namespace_id = ?1 AND f.path = ?2
+                 ORDER BY r.name",
+            )?;
+
+            let rows = stmt.query_map(params![namespace_id, normalized], |row| {
+                Ok(Repo {
+                    id: row.get(0)?,
+                    namespace_id: row.get(1)?,
+                    name: row.get(2)?,
+                    description: row.get(3)?,
+                    visibility: Visibility::from(row.get::<_, i64>(4)?),
+                    object_format: ObjectFormat::from(row.get::<_, i64>(10)?),
+                    folder_id: row.get(5)?,
+                    size_bytes: row.get(6)?,
+                    last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                    created_at: parse_datetime(&row.get::<_, String>(8)?),
+                    updated_at: parse_datetime(&row.get::<_, String>(9)?),
+                })
+            })?;
+
+            rows.collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(Error::from)
+        }
+    }
+
+    // Namespace grant operations
+
+    fn upsert_namespace_grant(&self, grant: &NamespaceGrant) -> Result<()> {
+        // Check if the namespace belongs to another principal as their primary
+        if let Some(owner) = self.get_principal_by_primary_namespace_id(&grant.namespace_id)? {
+            if owner.id != grant.principal_id {
+                return Err(Error::PrimaryNamespaceGrant);
+            }
+        }
+
+        self.conn().execute(
+            "INSERT INTO principal_namespace_grants (principal_id, namespace_id, allow_bits, deny_bits, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT (principal_id, namespace_id) DO UPDATE SET
+                allow_bits = excluded.allow_bits,
+                deny_bits = excluded.deny_bits,
+                updated_at = excluded.updated_at",
+            params![
+                grant.principal_id,
+                grant.namespace_id,
+                i64::from(grant.allow_bits),
+                i64::from(grant.deny_bits),
+                format_datetime(&grant.created_at),
+                format_datetime(&grant.updated_at),
+            ],
+        )?;
+        self.grants
+            .invalidate_namespace_grant(&grant.principal_id, &grant.namespace_id);
+        Ok(())
+    }
+
+    fn delete_namespace_grant(&self, principal_id: &str, namespace_id: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM principal_namespace_grants WHERE principal_id = ?1 AND namespace_id = ?2",
+            params![principal_id, namespace_id],
+        )?;
+        self.grants
+            .invalidate_namespace_grant(principal_id, namespace_id);
+        Ok(rows > 0)
+    }
+
+    fn get_namespace_grant(
+        &self,
+        principal_id: &str,
+        namespace_id: &str,
+    ) -> Result<Option<NamespaceGrant>> {
+        if let Some(grant) = self.grants.get_namespace_grant(principal_id, namespace_id) {
+            return Ok(grant);
+        }
+
+        let grant = self
+            .conn()
+            .query_row(
+                "SELECT principal_id, namespace_id, allow_bits, deny_bits, created_at, updated_at
+                 FROM principal_namespace_grants WHERE principal_id = ?1 AND namespace_id = ?2",
+                params![principal_id, namespace_id],
+                |row| {
+                    Ok(NamespaceGrant {
+                        principal_id: row.get(0)?,
+                        namespace_id: row.get(1)?,
+                        allow_bits: Permission::from(row.get::<_, i64>(2)?),
+                        deny_bits: Permission::from(row.get::<_, i64>(3)?),
+                        created_at: parse_datetime(&row.get::<_, String>(4)?),
+                        updated_at: parse_datetime(&row.get::<_, String>(5)?),
+                    })
+                },
+            )
+            .optional()?;
+
+        self.grants
+            .put_namespace_grant(principal_id, namespace_id, grant.clone());
+        Ok(grant)
+    }
+
+    fn list_principal_namespace_grants(&self, principal_id: &str) -> Result<Vec<NamespaceGrant>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT principal_id, namespace_id, allow_bits, deny_bits, created_at, updated_at
+             FROM principal_namespace_grants WHERE principal_id = ?1 ORDER BY namespace_id",
+        )?;
+
+        let rows = stmt.query_map(params![principal_id], |row| {
+            Ok(NamespaceGrant {
+                principal_id: row.get(0)?,
+                namespace_id: row.get(1)?,
+                allow_bits: Permission::from(row.get::<_, i64>(2)?),
+                deny_bits: Permission::from(row.get::<_, i64>(3)?),
+                created_at: parse_datetime(&row.get::<_, String>(4)?),
+                updated_at: parse_datetime(&row.get::<_, String>(5)?),
+            })
+        })?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn list_namespace_grants_for_namespace(
+        &self,
+        namespace_id: &str,
+    ) -> Result<Vec<NamespaceGrant>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT principal_id, namespace_id, allow_bits, deny_bits, created_at, updated_at
+             FROM principal_namespace_grants WHERE namespace_id = ?1 ORDER BY principal_id",
+        )?;
+
+        let rows = stmt.query_map(params![namespace_id], |row| {
+            Ok(NamespaceGrant {
+                principal_id: row.get(0)?,
+                namespace_id: row.get(1)?,
+                allow_bits: Permission::from(row.get::<_, i64>(2)?),
+                deny_bits: Permission::from(row.get::<_, i64>(3)?),
+                created_at: parse_datetime(&row.get::<_, String>(4)?),
+                updated_at: parse_datetime(&row.get::<_, String>(5)?),
+            })
+        })?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn count_namespace_principals(&self, namespace_id: &str) -> Result<i32> {
+        let conn = self.conn();
+        let count: i32 = conn.query_row(
+            "SELECT COUNT(*) FROM principal_namespace_grants WHERE namespace_id = ?1",
+            params![namespace_id],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+
+    // Repo grant operations
+
+    fn upsert_repo_grant(&self, grant: &RepoGrant) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "INSERT INTO principal_repo_grants (principal_id, repo_id, allow_bits, deny_bits, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT (principal_id, repo_id) DO UPDATE SET
+                allow_bits = excluded.allow_bits,
+                deny_bits = excluded.deny_bits,
+                updated_at = excluded.updated_at",
+            params![
+                grant.principal_id,
+                grant.repo_id,
+                i64::from(grant.allow_bits),
+                i64::from(grant.deny_bits),
+                format_datetime(&grant.created_at),
+                format_datetime(&grant.updated_at),
+            ],
+        )?;
+
+        tx.execute(
+            "DELETE FROM principal_repo_grant_paths WHERE principal_id = ?1 AND repo_id = ?2",
+            params![grant.principal_id, grant.repo_id],
+        )?;
+        for path in &grant.paths {
+            tx.execute(
+                "INSERT OR IGNORE INTO principal_repo_grant_paths (principal_id, repo_id, path)
+                 VALUES (?1, ?2, ?3)",
+                params![grant.principal_id, grant.repo_id, path],
+            )?;
+        }
+
+        tx.commit()?;
+        self.grants
+            .invalidate_repo_grant(&grant.principal_id, &grant.repo_id);
+        Ok(())
+    }
+
+    fn delete_repo_grant(&self, principal_id: &str, repo_id: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM principal_repo_grants WHERE principal_id = ?1 AND repo_id = ?2",
+            params![principal_id, repo_id],
+        )?;
+        self.grants.invalidate_repo_grant(principal_id, repo_id);
+        Ok(rows > 0)
+    }
+
+    fn get_repo_grant(&self, principal_id: &str, repo_id: &str) -> Result<Option<RepoGrant>> {
+        if let Some(grant) = self.grants.get_repo_grant(principal_id, repo_id) {
+            return Ok(grant);
+        }
+
+        let conn = self.conn();
+        let mut grant = conn
+            .query_row(
+                "SELECT principal_id, repo_id, allow_bits, deny_bits, created_at, updated_at
+                 FROM principal_repo_grants WHERE principal_id = ?1 AND repo_id = ?2",
+                params![principal_id, repo_id],
+                row_to_repo_grant,
+            )
+            .optional()?;
+        if let Some(grant) = &mut grant {
+            grant.paths = repo_grant_paths(&conn, principal_id, repo_id)?;
+        }
+        drop(conn);
+
+        self.grants
+            .put_repo_grant(principal_id, repo_id, grant.clone());
+        Ok(grant)
+    }
+
+    fn list_principal_repo_grants(&self, principal_id: &str) -> Result<Vec<RepoGrant>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT principal_id, repo_id, allow_bits, deny_bits, created_at, updated_at
+             FROM principal_repo_grants WHERE principal_id = ?1 ORDER BY repo_id",
+        )?;
+
+        let mut grants = stmt
+            .query_map(params![principal_id], row_to_repo_grant)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        for grant in &mut grants {
+            grant.paths = repo_grant_paths(&conn, &grant.principal_id, &grant.repo_id)?;
+        }
+        Ok(grants)
+    }
+
+    fn list_principal_repos_with_grants(&self, principal_id: &str, namespace_id: &str) -> Result<Vec<Repo>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT r.id, r.namespace_id, r.name, r.description, r.public, r.folder_id, r.size_bytes, r.last_push_at, r.created_at, r.updated_at, r.object_format
+             FROM repos r
+             JOIN principal_repo_grants g ON r.id = g.repo_id
+             WHERE g.principal_id = ?1 AND r.namespace_id = ?2
+             ORDER BY r.name",
+        )?;
+
+        let rows = stmt.query_map(params![principal_id, namespace_id], |row| {
+            Ok(Repo {
+                id: row.get(0)?,
+                namespace_id: row.get(1)?,
+                name: row.get(2)?,
+                description: row.get(3)?,
+                visibility: Visibility::from(row.get::<_, i64>(4)?),
+                object_format: ObjectFormat::from(row.get::<_, i64>(10)?),
+                folder_id: row.get(5)?,
+                size_bytes: row.get(6)?,
+                last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                created_at: parse_datetime(&row.get::<_, String>(8)?),
+                updated_at: parse_datetime(&row.get::<_, String>(9)?),
+            })
+        })?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn has_repo_grants_in_namespace(&self, principal_id: &str, namespace_id: &str) -> Result<bool> {
+        let conn = self.conn();
+        let count: i32 = conn.query_row(
+            "SELECT COUNT(*) FROM principal_repo_grants g
+             JOIN repos r ON r.id = g.repo_id
+             WHERE g.principal_id = ?1 AND r.namespace_id = ?2",
+            params![principal_id, namespace_id],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    // LFS object operations
+
+    fn create_lfs_object(&self, obj: &LfsObject) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO lfs_objects (repo_id, oid, size, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                obj.repo_id,
+                obj.oid,
+                obj.size,
+                format_datetime(&obj.created_at),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_lfs_object(&self, repo_id: &str, oid: &str) -> Result<Option<LfsObject>> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT repo_id, oid, size, created_at FROM lfs_objects WHERE repo_id = ?1 AND oid = ?2",
+            params![repo_id, oid],
+            |row| {
+                Ok(LfsObject {
+                    repo_id: row.get(0)?,
+                    oid: row.get(1)?,
+                    size: row.get(2)?,
+                    created_at: parse_datetime(&row.get::<_, String>(3)?),
+                })
+            },
+        )
+        .optional()
+        .map_err(Error::from)
+    }
+
+    fn list_lfs_objects(&self, repo_id: &str) -> Result<Vec<LfsObject>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT repo_id, oid, size, created_at FROM lfs_objects WHERE repo_id = ?1 ORDER BY created_at",
+        )?;
+
+        let rows = stmt.query_map(params![repo_id], |row| {
+            Ok(LfsObject {
+                repo_id: row.get(0)?,
+                oid: row.get(1)?,
+                size: row.get(2)?,
+                created_at: parse_datetime(&row.get::<_, String>(3)?),
+            })
+        })?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn delete_lfs_object(&self, repo_id: &str, oid: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM lfs_objects WHERE repo_id = ?1 AND oid = ?2",
+            params![repo_id, oid],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn get_repo_lfs_size(&self, repo_id: &str) -> Result<i64> {
+        let conn = self.conn();
+        let size: Option<i64> = conn
+            .query_row(
+                "SELECT SUM(size) FROM lfs_objects WHERE repo_id = ?1",
+                params![repo_id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        Ok(size.unwrap_or(0))
+    }
+
+    fn count_lfs_objects(&self, repo_id: &str) -> Result<i64> {
+        let conn = self.conn();
+        let count = conn.query_row(
+            "SELECT COUNT(*) FROM lfs_objects WHERE repo_id = ?1",
+            params![repo_id],
+            |row| row.get(0),
+        )?;
+        Ok(count)
+    }
+
+    fn get_repo_unshared_lfs_size(&self, repo_id: &str) -> Result<i64> {
+        let conn = self.conn();
+        let size: Option<i64> = conn.query_row(
+            "SELECT SUM(o.size) FROM lfs_objects o
+             JOIN lfs_pool p ON p.oid = o.oid
+             WHERE o.repo_id = ?1 AND p.ref_count = 1",
+            params![repo_id],
+            |row| row.get(0),
+        )?;
+        Ok(size.unwrap_or(0))
+    }
+
+    fn list_largest_lfs_objects(&self, repo_id: &str, limit: i32) -> Result<Vec<LfsObject>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT repo_id, oid, size, created_at FROM lfs_objects WHERE repo_id = ?1
+             ORDER BY size DESC, oid LIMIT ?2",
+        )?;
+
+        let rows = stmt.query_map(params![repo_id, limit], |row| {
+            Ok(LfsObject {
+                repo_id: row.get(0)?,
+                oid: row.get(1)?,
+                size: row.get(2)?,
+                created_at: parse_datetime(&row.get::<_, String>(3)?),
+            })
+        })?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn get_namespace_lfs_size(&self, namespace_id: &str) -> Result<i64> {
+        let conn = self.conn();
+        let size: Option<i64> = conn.query_row(
+            "SELECT SUM(o.size) FROM lfs_objects o
+             JOIN repos r ON r.id = o.repo_id
+             WHERE r.namespace_id = ?1",
+            params![namespace_id],
+            |row| row.get(0),
+        )?;
+        Ok(size.unwrap_or(0))
+    }
+
+    fn list_unreferenced_lfs_objects(&self, min_age: Duration, limit: i32) -> Result<Vec<String>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT oid FROM lfs_pool
+             WHERE ref_count = 0 AND released_at < datetime('now', ?1)
+             ORDER BY released_at LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(
+            params![format!("-{} seconds", min_age.as_secs()), limit],
+            |row| row.get(0),
+        )?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn delete_unreferenced_lfs_object(&self, oid: &str) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM lfs_pool WHERE oid = ?1 AND ref_count = 0",
+            params![oid],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn list_pooled_lfs_objects(&self, after_oid: &str, limit: i32) -> Result<Vec<LfsObject>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT o.repo_id, o.oid, o.size, o.created_at
+             FROM lfs_pool p
+             JOIN lfs_objects o ON o.oid = p.oid
+                 AND o.repo_id = (SELECT MIN(repo_id) FROM lfs_objects WHERE oid = p.oid)
+             WHERE p.ref_count > 0 AND p.oid > ?1
+             ORDER BY p.oid
+             LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![after_oid, limit], |row| {
+            Ok(LfsObject {
+                repo_id: row.get(0)?,
+                oid: row.get(1)?,
+                size: row.get(2)?,
+                created_at: parse_datetime(&row.get::<_, String>(3)?),
+            })
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn list_lfs_objects_to_verify(&self, max_age: Duration, limit: i32) -> Result<Vec<LfsObject>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT o.repo_id, o.oid, o.size, o.created_at
+             FROM lfs_pool p
+             JOIN lfs_objects o ON o.oid = p.oid
+                 AND o.repo_id = (SELECT MIN(repo_id) FROM lfs_objects WHERE oid = p.oid)
+             LEFT JOIN lfs_integrity i ON i.oid = p.oid
+             WHERE p.ref_count > 0
+                 AND (i.checked_at IS NULL OR i.checked_at < datetime('now', ?1))
+             ORDER BY i.checked_at IS NOT NULL, i.checked_at
+             LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(
+            params![format!("-{} seconds", max_age.as_secs()), limit],
+            |row| {
+                Ok(LfsObject {
+                    repo_id: row.get(0)?,
+                    oid: row.get(1)?,
+                    size: row.get(2)?,
+                    created_at: parse_datetime(&row.get::<_, String>(3)?),
+                })
+            },
+        )?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn record_lfs_check(&self, oid: &str, error: Option<&str>) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO lfs_integrity (oid, checked_at, corrupted_at, error)
+             VALUES (?1, datetime('now'), CASE WHEN ?2 IS NULL THEN NULL ELSE datetime('now') END, ?2)
+             ON CONFLICT(oid) DO UPDATE SET
+                 checked_at = excluded.checked_at,
+                 corrupted_at = CASE WHEN excluded.error IS NULL THEN NULL
+                     ELSE COALESCE(lfs_integrity.corrupted_at, excluded.checked_at) END,
+                 error = excluded.error",
+            params![oid, error],
+        )?;
+        Ok(())
+    }
+
+    fn is_lfs_object_corrupted(&self, oid: &str) -> Result<bool> {
+        let corrupted = self
+            .conn()
+            .query_row(
+                "SELECT 1 FROM lfs_integrity WHERE oid = ?1 AND corrupted_at IS NOT NULL",
+                params![oid],
+                |_| Ok(()),
+            )
+            .optional()?;
+        Ok(corrupted.is_some())
+    }
+
+    fn list_corrupted_lfs_objects(&self) -> Result<Vec<CorruptedLfsObject>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT i.oid, p.size, i.corrupted_at, i.checked_at, i.error,
+                 (SELECT GROUP_CONCAT(repo_id) FROM lfs_objects WHERE oid = i.oid)
+             FROM lfs_integrity i
+             JOIN lfs_pool p ON p.oid = i.oid
+             WHERE i.corrupted_at IS NOT NULL
+             ORDER BY i.corrupted_at, i.oid",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let repo_ids: Option<String> = row.get(5)?;
+            Ok(CorruptedLfsObject {
+                oid: row.get(0)?,
+                size: row.get(1)?,
+                repo_ids: repo_ids
+                    .map(|ids| ids.split(',').map(str::to_string).collect())
+                    .unwrap_or_default(),
+                detected_at: parse_datetime(&row.get::<_, String>(2)?),
+                checked_at: parse_datetime(&row.get::<_, String>(3)?),
+                error: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+            })
+        })?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    // Repo size history
+
+    fn list_repo_size_history(
+        &self,
+        repo_id: &str,
+        since: NaiveDate,
+    ) -> Result<Vec<RepoSizeSnapshot>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT day, size_bytes, lfs_bytes FROM repo_size_history
+             WHERE repo_id = ?1 AND day >= COALESCE(
+                 (SELECT MAX(day) FROM repo_size_history WHERE repo_id = ?1 AND day < ?2), ?2)
+             ORDER BY day",
+        )?;
+        let rows = stmt.query_map(params![repo_id, since.to_string()], |row| {
+            let day: String = row.get(0)?;
+            Ok(RepoSizeSnapshot {
+                day: NaiveDate::parse_from_str(&day, "%Y-%m-%d").unwrap_or_default(),
+                size_bytes: row.get(1)?,
+                lfs_bytes: row.get(2)?,
+            })
+        })?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    // Container registry operations
+
+    fn upsert_registry_manifest(&self, manifest: &RegistryManifest) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO registry_manifests (repo_id, digest, media_type, size, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(repo_id, digest) DO UPDATE SET media_type = excluded.media_type",
+            params![
+                manifest.repo_id,
+                manifest.digest,
+                manifest.media_type,
+                manifest.size,
+                format_datetime(&manifest.created_at),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_registry_manifest(
+        &self,
+        repo_id: &str,
+        digest: &str,
+    ) -> Result<Option<RegistryManifest>> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT repo_id, digest, media_type, size, created_at FROM registry_manifests
+             WHERE repo_id = ?1 AND digest = ?2",
+            params![repo_id, digest],
+            |row| {
+                Ok(RegistryManifest {
+                    repo_id: row.get(0)?,
+                    digest: row.get(1)?,
+                    media_type: row.get(2)?,
+                    size: row.get(3)?,
+                    created_at: parse_datetime(&row.get::<_, String>(4)?),
+                })
+            },
+        )
+        .optional()
+        .map_err(Error::from)
+    }
+
+    fn set_registry_tag(&self, repo_id: &str, tag: &str, digest: &str) -> Result<()> {
+        self.conn().execute(
+            "INSERT INTO registry_tags (repo_id, tag, digest, updated_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(repo_id, tag) DO UPDATE SET
+                digest = excluded.digest, updated_at = excluded.updated_at",
+            params![repo_id, tag, digest, format_datetime(&Utc::now())],
+        )?;
+        Ok(())
+    }
+
+    fn get_registry_tag(&self, repo_id: &str, tag: &str) -> Result<Option<String>> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT digest FROM registry_tags WHERE repo_id = ?1 AND tag = ?2",
+            params![repo_id, tag],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(Error::from)
+    }
+
+    // Package registry operations
+
+    fn create_package_file(&self, file: &PackageFile) -> Result<()> {
+        let result = self.conn().execute(
+            "INSERT INTO package_files
+                (namespace_id, package, version, filename, size, sha256, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                file.namespace_id,
+                file.package,
+                file.version,
+                file.filename,
+                file.size,
+                file.sha256,
+                format_datetime(&file.created_at),
+            ],
+        );
+        match result {
+            Ok(_) => Ok(()),
+            Err(rusqlite::Error::SqliteFailure(e, _))
+                if e.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                Err(Error::AlreadyExists)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn get_package_file(
+        &self,
+        namespace_id: &str,
+        package: &str,
+        version: &str,
+        filename: &str,
+    ) -> Result<Option<PackageFile>> {
+        let conn = self.conn();
+        conn.query_row(
+            "SELECT namespace_id, package, version, filename, size, sha256, created_at
+             FROM package_files
+             WHERE namespace_id = ?1 AND package = ?2 AND version = ?3 AND filename = ?4",
+            params![namespace_id, package, version, filename],
+            |row| {
+                Ok(PackageFile {
+                    namespace_id: row.get(0)?,
+                    package: row.get(1)?,
+                    version: row.get(2)?,
+                    filename: row.get(3)?,
+                    size: row.get(4)?,
+                    sha256: row.get(5)?,
+                    created_at: parse_datetime(&row.get::<_, String>(6)?),
+                })
+            },
+        )
+        .optional()
+        .map_err(Error::from)
+    }
+
+    fn delete_package_file(
+        &self,
+        namespace_id: &str,
+        package: &str,
+        version: &str,
+        filename: &str,
+    ) -> Result<bool> {
+        let rows = self.conn().execute(
+            "DELETE FROM package_files
+             WHERE namespace_id = ?1 AND package = ?2 AND version = ?3 AND filename = ?4",
+            params![namespace_id, package, version, filename],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn get_namespace_package_size(&self, namespace_id: &str) -> Result<i64> {
+        let conn = self.conn();
+        let size: Option<i64> = conn.query_row(
+            "SELECT SUM(size) FROM package_files WHERE namespace_id = ?1",
+            params![namespace_id],
+            |row| row.get(0),
+        )?;
+        Ok(size.unwrap_or(0))
+    }
+
+    fn list_changes(&self, after_seq: i64, limit: i32) -> Result<Vec<Change>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT seq, kind, entity_id, namespace_id, changed_at
+             FROM changes WHERE seq > ?1 ORDER BY seq LIMIT ?2",
+        )?;
+
+        let rows = stmt.query_map(params![after_seq, limit], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+
+        let mut changes = Vec::new();
+        for row in rows {
+            let (seq, kind, entity_id, namespace_id, changed_at) = row?;
+            let Some(kind) = ChangeKind::parse(&kind) else {
+                tracing::error!("Unknown change kind in database: '{kind}'");
+                continue;
+            };
+            changes.push(Change {
+                seq,
+                kind,
+                entity_id,
+                namespace_id,
+                changed_at: parse_datetime(&changed_at),
+            });
+        }
+        Ok(changes)
+    }
+
+    fn latest_change_seq(&self) -> Result<i64> {
+        let conn = self.conn();
+        let seq: Option<i64> = conn
+            .query_row(
+                "SELECT seq FROM sqlite_sequence WHERE name = 'changes'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(seq.unwrap_or(0))
+    }
+
+    fn oldest_change_seq(&self) -> Result<Option<i64>> {
+        let conn = self.conn();
+        let seq: Option<i64> = conn.query_row("SELECT MIN(seq) FROM changes", [], |row| row.get(0))?;
+        Ok(seq)
+    }
+
+    fn prune_changes(&self, max_age: Duration) -> Result<usize> {
+        // Deleting everything up to the newest stale entry keeps the log a
+        // contiguous run of sequence numbers, which cursors rely on.
+        let rows = self.conn().execute(
+            "DELETE FROM changes WHERE seq <= (
+                 SELECT MAX(seq) FROM changes WHERE changed_at < datetime('now', ?1)
+             )",
+            params![format!("-{} seconds", max_age.as_secs())],
+        )?;
+        Ok(rows)
+    }
+
+    fn create_audit_event(&self, event: &AuditEvent) -> Result<i64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO audit_events (kind, summary, namespace_id, repo_id, principal_id,
+                 token_id, address, detail, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                event.kind,
+                event.summary,
+                event.namespace_id,
+                event.repo_id,
+                event.principal_id,
+                event.token_id,
+                event.address,
+                event.detail.to_string(),
+                format_datetime(&event.created_at),
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    fn list_audit_events(
+        &self,
+        filter: &AuditEventFilter,
+        before_seq: Option<i64>,
+        limit: i32,
+    ) -> Result<Vec<AuditEvent>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT seq, kind, summary, namespace_id, repo_id, principal_id, token_id,
+                 address, detail, created_at
+             FROM audit_events
+             WHERE (?1 IS NULL OR kind = ?1)
+               AND (?2 IS NULL OR repo_id = ?2)
+               AND (?3 IS NULL OR principal_id = ?3)
+               AND (?4 IS NULL OR seq < ?4)
+             ORDER BY seq DESC LIMIT ?5",
+        )?;
+
+        let rows = stmt.query_map(
+            params![
+                filter.kind,
+                filter.repo_id,
+                filter.principal_id,
+                before_seq,
+                limit
+            ],
+            |row| {
+                let detail: String = row.get(8)?;
+                let created_at: String = row.get(9)?;
+                Ok(AuditEvent {
+                    seq: row.get(0)?,
+                    kind: row.get(1)?,
+                    summary: row.get(2)?,
+                    namespace_id: row.get(3)?,
+                    repo_id: row.get(4)?,
+                    principal_id: row.get(5)?,
+                    token_id: row.get(6)?,
+                    address: row.get(7)?,
+                    detail: serde_json::from_str(&detail).unwrap_or_default(),
+                    created_at: parse_datetime(&created_at),
+                })
+            },
+        )?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn list_orphan_namespaces(&self) -> Result<Vec<Namespace>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, created_at, repo_limit, storage_limit_bytes, external_id
+             FROM namespaces n
+             WHERE NOT EXISTS (SELECT 1 FROM repos WHERE namespace_id = n.id)
+               AND NOT EXISTS (SELECT 1 FROM principal_namespace_grants WHERE namespace_id = n.id)
+               AND NOT EXISTS (SELECT 1 FROM principals WHERE primary_namespace_id = n.id)
+             ORDER BY name",
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(Namespace {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                created_at: parse_datetime(&row.get::<_, String>(2)?),
+                repo_limit: row.get(3)?,
+                storage_limit_bytes: row.get(4)?,
+                external_id: row.get(5)?,
+            })
+        })?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn list_orphan_tokens(&self) -> Result<Vec<(Token, OrphanTokenReason)>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT t.id, t.token_hash, t.token_lookup, t.is_admin, t.principal_id, t.created_at,
+                    t.expires_at, t.last_used_at, t.traffic_class, p.id IS NULL
+             FROM tokens t
+             LEFT JOIN principals p ON p.id = t.principal_id
+             LEFT JOIN scim_users s ON s.principal_id = t.principal_id
+             WHERE t.is_admin = 0 AND (p.id IS NULL OR s.active = 0)
+             ORDER BY t.id",
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            let token = Token {
+                id: row.get(0)?,
+                token_hash: row.get(1)?,
+                token_lookup: row.get(2)?,
+                is_admin: row.get(3)?,
+                principal_id: row.get(4)?,
+                created_at: parse_datetime(&row.get::<_, String>(5)?),
+                expires_at: row.get::<_, Option<String>>(6)?.map(|s| parse_datetime(&s)),
+                last_used_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
+                traffic_class: TrafficClass::from(row.get::<_, i64>(8)?),
+            };
+            let reason = if row.get(9)? {
+                OrphanTokenReason::MissingPrincipal
+            } else {
+                OrphanTokenReason::Deactivated
+            };
+            Ok((token, reason))
+        })?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn list_empty_folders(&self) -> Result<Vec<Folder>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT f.id, f.namespace_id, f.path, f.created_at, f.updated_at
+             FROM folders f
+             WHERE NOT EXISTS (
+                 SELECT 1 FROM repos r JOIN folders d ON d.id = r.folder_id
+                 WHERE d.namespace_id = f.namespace_id
+                   AND (d.id = f.id OR d.path LIKE f.path || '/%')
+             )
+             ORDER BY f.namespace_id, f.path",
+        )?;
+
+        let rows = stmt.query_map([], |row| {
+            Ok(Folder {
+                id: row.get(0)?,
+                namespace_id: row.get(1)?,
+                path: row.get(2)?,
+                created_at: parse_datetime(&row.get::<_, String>(3)?),
+                updated_at: parse_datetime(&row.get::<_, String>(4)?),
+            })
+        })?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::from)
+    }
+
+    fn has_admin_token(&self) -> Result<bool> {
+        let conn = self.conn();
+        let count: i32 = conn.query_row(
+            "SELECT COUNT(*) FROM tokens WHERE is_admin = 1",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    fn close(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_initialize_creates_tables() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        let conn = store.conn();
+        let tables: Vec<String> = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type='table' ORDER BY name")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert!(tables.contains(&"namespaces".to_string()));
+        assert!(tables.contains(&"principals".to_string()));
+        assert!(tables.contains(&"tokens".to_string()));
+        assert!(tables.contains(&"ssh_keys".to_string()));
+        assert!(tables.contains(&"signing_keys".to_string()));
+        assert!(tables.contains(&"repos".to_string()));
+        assert!(tables.contains(&"tags".to_string()));
+        assert!(tables.contains(&"repo_tags".to_string()));
+        assert!(tables.contains(&"folders".to_string()));
+        assert!(tables.contains(&"principal_namespace_grants".to_string()));
+        assert!(tables.contains(&"principal_repo_grants".to_string()));
+        assert!(tables.contains(&"lfs_objects".to_string()));
+        assert!(tables.contains(&"repo_mirrors".to_string()));
+        assert!(tables.contains(&"repo_exports".to_string()));
+        assert!(tables.contains(&"repo_pages".to_string()));
+        assert!(tables.contains(&"registry_manifests".to_string()));
+        assert!(tables.contains(&"registry_tags".to_string()));
+        assert!(tables.contains(&"package_files".to_string()));
+        assert!(tables.contains(&"changes".to_string()));
+        assert!(tables.contains(&"lfs_pool".to_string()));
+        assert!(tables.contains(&"namespace_storage_roots".to_string()));
+    }
+
+    #[test]
+    fn test_network_mode_avoids_wal() {
+        let temp = TempDir::new().unwrap();
+        let journal_mode = |mode| {
+            let store = SqliteStore::open(temp.path().join("test.db"), mode).unwrap();
+            let conn = store.conn();
+            conn.query_row("PRAGMA journal_mode", [], |row| row.get::<_, String>(0))
+                .unwrap()
+        };
+
+        assert_eq!(journal_mode(StorageMode::Auto), "wal");
+        assert_eq!(journal_mode(StorageMode::Network), "delete");
+        // Auto-detection keeps a database that network mode configured.
+        assert_eq!(journal_mode(StorageMode::Auto), "delete");
+        assert_eq!(journal_mode(StorageMode::Local), "wal");
+    }
+
+    #[test]
+    fn test_namespace_crud() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        let ns = Namespace {
+            id: "ns-1".to_string(),
+            name: "test-namespace".to_string(),
+            created_at: Utc::now(),
+            repo_limit: Some(10),
+            storage_limit_bytes: Some(1024 * 1024),
+            external_id: None,
+        };
+
+        store.create_namespace(&ns).unwrap();
+
+        let fetched = store.get_namespace("ns-1").unwrap().unwrap();
+        assert_eq!(fetched.name, "test-namespace");
+        assert_eq!(fetched.repo_limit, Some(10));
+
+        let by_name = store
+            .get_namespace_by_name("test-namespace")
+            .unwrap()
+            .unwrap();
+        assert_eq!(by_name.id, "ns-1");
+
+        let deleted = store.delete_namespace("ns-1").unwrap();
+        assert!(deleted);
+
+        let gone = store.get_namespace("ns-1").unwrap();
+        assert!(gone.is_none());
+    }
+
+    #[test]
+    fn test_namespace_storage_roots() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "archive".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-1".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "old".to_string(),
+                description: None,
+                visibility: Visibility::Private,
+                object_format: ObjectFormat::Sha1,
+                size_bytes: 0,
+                folder_id: None,
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+
+        assert!(store.get_namespace_storage_root("ns-1").unwrap().is_none());
+        store
+            .set_namespace_storage_root("ns-1", Some("hdd"))
+            .unwrap();
+        store
+            .set_namespace_storage_root("ns-1", Some("cold"))
+            .unwrap();
+        assert_eq!(
+            store.get_namespace_storage_root("ns-1").unwrap().as_deref(),
+            Some("cold")
+        );
+        assert_eq!(
+            store.get_repo_storage_root("repo-1").unwrap().as_deref(),
+            Some("cold")
+        );
+        assert_eq!(
+            store.list_namespace_storage_roots().unwrap(),
+            [("ns-1".to_string(), "cold".to_string())]
+        );
+
+        store.set_namespace_storage_root("ns-1", None).unwrap();
+        assert!(store.get_repo_storage_root("repo-1").unwrap().is_none());
+        assert!(store.list_namespace_storage_roots().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_repo_mirror_sync_status() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-1".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "upstream".to_string(),
+                description: None,
+                visibility: Visibility::Private,
+                object_format: ObjectFormat::Sha256,
+                size_bytes: 0,
+                folder_id: None,
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+        let repo = store.get_repo_by_id("repo-1").unwrap().unwrap();
+        assert_eq!(repo.object_format, ObjectFormat::Sha256);
+        store
+            .create_repo_mirror(&RepoMirror {
+                repo_id: "repo-1".to_string(),
+                url: "https://example.com/upstream.git".to_string(),
+                interval_secs: 3600,
+                created_at: now,
+                last_sync_at: None,
+                last_error: None,
+            })
+            .unwrap();
+
+        store
+            .update_repo_mirror_sync("repo-1", Some("connection refused"))
+            .unwrap();
+        let failed = store.get_repo_mirror("repo-1").unwrap().unwrap();
+        assert!(failed.last_sync_at.is_some());
+        assert_eq!(failed.last_error.as_deref(), Some("connection refused"));
+
+        store.update_repo_mirror_sync("repo-1", None).unwrap();
+        let synced = store.get_repo_mirror("repo-1").unwrap().unwrap();
+        assert!(synced.last_error.is_none());
+        assert_eq!(store.list_repo_mirrors().unwrap().len(), 1);
+
+        // Deleting the repo removes its mirror settings.
+        store.delete_repo("repo-1").unwrap();
+        assert!(store.get_repo_mirror("repo-1").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_repo_pages_builds() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
 
-        let rows = stmt.query_map(params![namespace_id], |row| {
-            Ok(NamespaceGrant {
-                principal_id: row.get(0)?,
-                namespace_id: row.get(1)?,
-                allow_bits: Permission::from(row.get::<_, i64>(2)?),
-                deny_bits: Permission::from(row.get::<_, i64>(3)?),
-                created_at: parse_datetime(&row.get::<_, String>(4)?),
-                updated_at: parse_datetime(&row.get::<_, String>(5)?),
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
             })
-        })?;
+            .unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-1".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "site".to_string(),
+                description: None,
+                visibility: Visibility::Public,
+                object_format: ObjectFormat::Sha1,
+                size_bytes: 0,
+                folder_id: None,
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+        let mut pages = RepoPages {
+            repo_id: "repo-1".to_string(),
+            branch: "main".to_string(),
+            path: String::new(),
+            created_at: now,
+            last_build_at: None,
+            commit_sha: None,
+            size_bytes: 0,
+            last_error: None,
+        };
+        store.set_repo_pages(&pages).unwrap();
+        store.update_repo_pages_build("repo-1", "abc123", 42).unwrap();
 
-        rows.collect::<std::result::Result<Vec<_>, _>>()
-            .map_err(Error::from)
+        // A failed build keeps the published commit.
+        store
+            .update_repo_pages_error("repo-1", "Branch gh-pages not found")
+            .unwrap();
+        let failed = store.get_repo_pages("repo-1").unwrap().unwrap();
+        assert_eq!(failed.commit_sha.as_deref(), Some("abc123"));
+        assert_eq!(failed.size_bytes, 42);
+        assert_eq!(failed.last_error.as_deref(), Some("Branch gh-pages not found"));
+
+        // Changing the settings keeps the build history.
+        pages.branch = "gh-pages".to_string();
+        pages.path = "docs".to_string();
+        store.set_repo_pages(&pages).unwrap();
+        let updated = store.get_repo_pages("repo-1").unwrap().unwrap();
+        assert_eq!((updated.branch.as_str(), updated.path.as_str()), ("gh-pages", "docs"));
+        assert_eq!(updated.commit_sha.as_deref(), Some("abc123"));
+
+        store.update_repo_pages_build("repo-1", "def456", 7).unwrap();
+        assert!(store.get_repo_pages("repo-1").unwrap().unwrap().last_error.is_none());
+
+        store.delete_repo("repo-1").unwrap();
+        assert!(store.get_repo_pages("repo-1").unwrap().is_none());
     }
 
-    fn count_namespace_principals(&self, namespace_id: &str) -> Result<i32> {
-        let conn = self.conn();
-        let count: i32 = conn.query_row(
-            "SELECT COUNT(*) FROM principal_namespace_grants WHERE namespace_id = ?1",
-            params![namespace_id],
-            |row| row.get(0),
-        )?;
-        Ok(count)
-    }
+    #[test]
+    fn test_repo_commit_policy() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
 
-    // Repo grant operations
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-1".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "app".to_string(),
+                description: None,
+                visibility: Visibility::Private,
+                object_format: ObjectFormat::Sha1,
+                size_bytes: 0,
+                folder_id: None,
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+        assert!(store.get_repo_commit_policy("repo-1").unwrap().is_none());
 
-    fn upsert_repo_grant(&self, grant: &RepoGrant) -> Result<()> {
-        self.conn().execute(
-            "INSERT INTO principal_repo_grants (principal_id, repo_id, allow_bits, deny_bits, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-             ON CONFLICT (principal_id, repo_id) DO UPDATE SET
-                allow_bits = excluded.allow_bits,
-                deny_bits = excluded.deny_bits,
-                updated_at = excluded.updated_at",
-            params![
-                grant.principal_id,
-                grant.repo_id,
-                i64::from(grant.allow_bits),
-                i64::from(grant.deny_bits),
-                format_datetime(&grant.created_at),
-                format_datetime(&grant.updated_at),
-            ],
-        )?;
-        Ok(())
-    }
+        let mut policy = CommitPolicy {
+            subject_pattern: Some("^[A-Z]".to_string()),
+            conventional: false,
+            max_subject_length: Some(72),
+            issue_pattern: None,
+        };
+        store.set_repo_commit_policy("repo-1", &policy).unwrap();
+        assert_eq!(
+            store.get_repo_commit_policy("repo-1").unwrap(),
+            Some(policy.clone())
+        );
 
-    fn delete_repo_grant(&self, principal_id: &str, repo_id: &str) -> Result<bool> {
-        let rows = self.conn().execute(
-            "DELETE FROM principal_repo_grants WHERE principal_id = ?1 AND repo_id = ?2",
-            params![principal_id, repo_id],
-        )?;
-        Ok(rows > 0)
-    }
+        // Setting it again replaces every rule.
+        policy.subject_pattern = None;
+        policy.conventional = true;
+        store.set_repo_commit_policy("repo-1", &policy).unwrap();
+        assert_eq!(
+            store.get_repo_commit_policy("repo-1").unwrap(),
+            Some(policy)
+        );
 
-    fn get_repo_grant(&self, principal_id: &str, repo_id: &str) -> Result<Option<RepoGrant>> {
-        let conn = self.conn();
-        conn.query_row(
-            "SELECT principal_id, repo_id, allow_bits, deny_bits, created_at, updated_at
-             FROM principal_repo_grants WHERE principal_id = ?1 AND repo_id = ?2",
-            params![principal_id, repo_id],
-            |row| {
-                Ok(RepoGrant {
-                    principal_id: row.get(0)?,
-                    repo_id: row.get(1)?,
-                    allow_bits: Permission::from(row.get::<_, i64>(2)?),
-                    deny_bits: Permission::from(row.get::<_, i64>(3)?),
-                    created_at: parse_datetime(&row.get::<_, String>(4)?),
-                    updated_at: parse_datetime(&row.get::<_, String>(5)?),
-                })
-            },
-        )
-        .optional()
-        .map_err(Error::from)
+        assert!(store.delete_repo_commit_policy("repo-1").unwrap());
+        assert!(!store.delete_repo_commit_policy("repo-1").unwrap());
+        assert!(store.get_repo_commit_policy("repo-1").unwrap().is_none());
     }
 
-    fn list_principal_repo_grants(&self, principal_id: &str) -> Result<Vec<RepoGrant>> {
-        let conn = self.conn();
-        let mut stmt = conn.prepare(
-            "SELECT principal_id, repo_id, allow_bits, deny_bits, created_at, updated_at
-             FROM principal_repo_grants WHERE principal_id = ?1 ORDER BY repo_id",
-        )?;
+    #[test]
+    fn test_commit_statuses() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
 
-        let rows = stmt.query_map(params![principal_id], |row| {
-            Ok(RepoGrant {
-                principal_id: row.get(0)?,
-                repo_id: row.get(1)?,
-                allow_bits: Permission::from(row.get::<_, i64>(2)?),
-                deny_bits: Permission::from(row.get::<_, i64>(3)?),
-                created_at: parse_datetime(&row.get::<_, String>(4)?),
-                updated_at: parse_datetime(&row.get::<_, String>(5)?),
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
             })
-        })?;
+            .unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-1".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "app".to_string(),
+                description: None,
+                visibility: Visibility::Private,
+                object_format: ObjectFormat::Sha1,
+                size_bytes: 0,
+                folder_id: None,
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
 
-        rows.collect::<std::result::Result<Vec<_>, _>>()
-            .map_err(Error::from)
+        let status = |context: &str, state, at| CommitStatus {
+            repo_id: "repo-1".to_string(),
+            sha: "abc".to_string(),
+            context: context.to_string(),
+            state,
+            target_url: None,
+            description: None,
+            creator_id: None,
+            created_at: at,
+            updated_at: at,
+        };
+        let later = now + chrono::Duration::minutes(5);
+        store
+            .set_commit_status(&status("test", CommitState::Pending, now))
+            .unwrap();
+        store
+            .set_commit_status(&status("lint", CommitState::Success, now))
+            .unwrap();
+        store
+            .set_commit_status(&status("test", CommitState::Failure, later))
+            .unwrap();
+
+        let statuses = store.list_commit_statuses("repo-1", "abc").unwrap();
+        let contexts: Vec<_> = statuses
+            .iter()
+            .map(|s| (s.context.as_str(), s.state))
+            .collect();
+        assert_eq!(
+            contexts,
+            [
+                ("lint", CommitState::Success),
+                ("test", CommitState::Failure)
+            ]
+        );
+        // The context keeps when it first reported.
+        assert_eq!(statuses[1].created_at, now);
+        assert_eq!(statuses[1].updated_at, later);
+        assert!(
+            store
+                .list_commit_statuses("repo-1", "def")
+                .unwrap()
+                .is_empty()
+        );
+
+        store.delete_repo("repo-1").unwrap();
+        assert!(
+            store
+                .list_commit_statuses("repo-1", "abc")
+                .unwrap()
+                .is_empty()
+        );
     }
 
-    fn list_principal_repos_with_grants(&self, principal_id: &str, namespace_id: &str) -> Result<Vec<Repo>> {
-        let conn = self.conn();
-        let mut stmt = conn.prepare(
-            "SELECT r.id, r.namespace_id, r.name, r.description, r.public, r.folder_id, r.size_bytes, r.last_push_at, r.created_at, r.updated_at
-             FROM repos r
-             JOIN principal_repo_grants g ON r.id = g.repo_id
-             WHERE g.principal_id = ?1 AND r.namespace_id = ?2
-             ORDER BY r.name",
-        )?;
+    #[test]
+    fn test_repo_holds() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
 
-        let rows = stmt.query_map(params![principal_id, namespace_id], |row| {
-            Ok(Repo {
-                id: row.get(0)?,
-                namespace_id: row.get(1)?,
-                name: row.get(2)?,
-                description: row.get(3)?,
-                public: row.get(4)?,
-                folder_id: row.get(5)?,
-                size_bytes: row.get(6)?,
-                last_push_at: row.get::<_, Option<String>>(7)?.map(|s| parse_datetime(&s)),
-                created_at: parse_datetime(&row.get::<_, String>(8)?),
-                updated_at: parse_datetime(&row.get::<_, String>(9)?),
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
             })
-        })?;
-
-        rows.collect::<std::result::Result<Vec<_>, _>>()
-            .map_err(Error::from)
-    }
+            .unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-1".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "app".to_string(),
+                description: None,
+                visibility: Visibility::Private,
+                object_format: ObjectFormat::Sha1,
+                size_bytes: 0,
+                folder_id: None,
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+        store
+            .create_lfs_object(&LfsObject {
+                repo_id: "repo-1".to_string(),
+                oid: "oid-1".to_string(),
+                size: 10,
+                created_at: now,
+            })
+            .unwrap();
 
-    fn has_repo_grants_in_namespace(&self, principal_id: &str, namespace_id: &str) -> Result<bool> {
-        let conn = self.conn();
-        let count: i32 = conn.query_row(
-            "SELECT COUNT(*) FROM principal_repo_grants g
-             JOIN repos r ON r.id = g.repo_id
-             WHERE g.principal_id = ?1 AND r.namespace_id = ?2",
-            params![principal_id, namespace_id],
-            |row| row.get(0),
-        )?;
-        Ok(count > 0)
+        let hold = RepoHold {
+            repo_id: "repo-1".to_string(),
+            reason: "Case 42".to_string(),
+            token_id: Some("tok-1".to_string()),
+            created_at: now,
+        };
+        assert!(store.create_repo_hold(&hold).unwrap());
+        assert!(
+            !store
+                .create_repo_hold(&RepoHold {
+                    reason: "again".to_string(),
+                    ..hold.clone()
+                })
+                .unwrap()
+        );
+        assert_eq!(store.get_repo_hold("repo-1").unwrap(), Some(hold.clone()));
+        assert_eq!(store.list_repo_holds(None).unwrap(), std::slice::from_ref(&hold));
+        assert_eq!(store.list_repo_holds(Some("ns-1")).unwrap(), [hold]);
+        assert!(store.list_repo_holds(Some("ns-2")).unwrap().is_empty());
+
+        // Nothing held can be deleted, even by cascade.
+        assert!(store.delete_repo("repo-1").is_err());
+        assert!(store.delete_namespace("ns-1").is_err());
+        assert!(store.delete_lfs_object("repo-1", "oid-1").is_err());
+        assert!(store.get_repo_by_id("repo-1").unwrap().is_some());
+
+        assert!(store.delete_repo_hold("repo-1").unwrap());
+        assert!(!store.delete_repo_hold("repo-1").unwrap());
+        assert!(store.delete_repo("repo-1").unwrap());
     }
 
-    // LFS object operations
+    #[test]
+    fn test_shared_object_members() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
 
-    fn create_lfs_object(&self, obj: &LfsObject) -> Result<()> {
-        self.conn().execute(
-            "INSERT INTO lfs_objects (repo_id, oid, size, created_at)
-             VALUES (?1, ?2, ?3, ?4)",
-            params![
-                obj.repo_id,
-                obj.oid,
-                obj.size,
-                format_datetime(&obj.created_at),
-            ],
-        )?;
-        Ok(())
-    }
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        for (id, name) in [("repo-1", "app"), ("repo-2", "app-mirror")] {
+            store
+                .create_repo(&Repo {
+                    id: id.to_string(),
+                    namespace_id: "ns-1".to_string(),
+                    name: name.to_string(),
+                    description: None,
+                    visibility: Visibility::Private,
+                    object_format: ObjectFormat::Sha1,
+                    size_bytes: 0,
+                    folder_id: None,
+                    last_push_at: None,
+                    created_at: now,
+                    updated_at: now,
+                })
+                .unwrap();
+        }
+        assert!(store.list_shared_object_namespaces().unwrap().is_empty());
 
-    fn get_lfs_object(&self, repo_id: &str, oid: &str) -> Result<Option<LfsObject>> {
-        let conn = self.conn();
-        conn.query_row(
-            "SELECT repo_id, oid, size, created_at FROM lfs_objects WHERE repo_id = ?1 AND oid = ?2",
-            params![repo_id, oid],
-            |row| {
-                Ok(LfsObject {
-                    repo_id: row.get(0)?,
-                    oid: row.get(1)?,
-                    size: row.get(2)?,
-                    created_at: parse_datetime(&row.get::<_, String>(3)?),
+        let member = SharedObjectMember {
+            repo_id: "repo-1".to_string(),
+            namespace_id: "ns-1".to_string(),
+            joined_at: now,
+        };
+        assert!(store.create_shared_object_member(&member).unwrap());
+        assert!(!store.create_shared_object_member(&member).unwrap());
+        assert!(
+            store
+                .create_shared_object_member(&SharedObjectMember {
+                    repo_id: "repo-2".to_string(),
+                    joined_at: now + chrono::Duration::seconds(1),
+                    ..member.clone()
                 })
-            },
-        )
-        .optional()
-        .map_err(Error::from)
+                .unwrap()
+        );
+        assert_eq!(
+            store
+                .get_shared_object_member("repo-1")
+                .unwrap()
+                .unwrap()
+                .repo_id,
+            "repo-1"
+        );
+        let members = store.list_shared_object_members("ns-1").unwrap();
+        assert_eq!(
+            members
+                .iter()
+                .map(|m| m.repo_id.as_str())
+                .collect::<Vec<_>>(),
+            ["repo-1", "repo-2"]
+        );
+        assert_eq!(store.list_shared_object_namespaces().unwrap(), ["ns-1"]);
+
+        assert!(store.delete_shared_object_member("repo-1").unwrap());
+        assert!(!store.delete_shared_object_member("repo-1").unwrap());
+        assert!(store.get_shared_object_member("repo-1").unwrap().is_none());
+
+        // Deleting the repo drops its membership.
+        assert!(store.delete_repo("repo-2").unwrap());
+        assert!(store.list_shared_object_members("ns-1").unwrap().is_empty());
+        assert!(store.list_shared_object_namespaces().unwrap().is_empty());
     }
 
-    fn list_lfs_objects(&self, repo_id: &str) -> Result<Vec<LfsObject>> {
-        let conn = self.conn();
-        let mut stmt = conn.prepare(
-            "SELECT repo_id, oid, size, created_at FROM lfs_objects WHERE repo_id = ?1 ORDER BY created_at",
-        )?;
+    #[test]
+    fn test_namespace_email_policy_and_principal_emails() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
 
-        let rows = stmt.query_map(params![repo_id], |row| {
-            Ok(LfsObject {
-                repo_id: row.get(0)?,
-                oid: row.get(1)?,
-                size: row.get(2)?,
-                created_at: parse_datetime(&row.get::<_, String>(3)?),
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
             })
-        })?;
+            .unwrap();
+        store
+            .create_principal(&Principal {
+                id: "principal-1".to_string(),
+                primary_namespace_id: "ns-1".to_string(),
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+        assert!(store.get_namespace_email_policy("ns-1").unwrap().is_none());
 
-        rows.collect::<std::result::Result<Vec<_>, _>>()
-            .map_err(Error::from)
+        let mut policy = EmailPolicy {
+            allowed_domains: vec!["example.com".to_string(), "example.org".to_string()],
+            allow_pusher_emails: false,
+        };
+        store.set_namespace_email_policy("ns-1", &policy).unwrap();
+        assert_eq!(
+            store.get_namespace_email_policy("ns-1").unwrap(),
+            Some(policy.clone())
+        );
+        policy.allowed_domains.clear();
+        policy.allow_pusher_emails = true;
+        store.set_namespace_email_policy("ns-1", &policy).unwrap();
+        assert_eq!(
+            store.get_namespace_email_policy("ns-1").unwrap(),
+            Some(policy)
+        );
+        assert!(store.delete_namespace_email_policy("ns-1").unwrap());
+        assert!(!store.delete_namespace_email_policy("ns-1").unwrap());
+
+        let emails = ["b@example.com", "a@example.com", "A@example.com"].map(String::from);
+        store.set_principal_emails("principal-1", &emails).unwrap();
+        assert_eq!(
+            store.list_principal_emails("principal-1").unwrap(),
+            ["a@example.com", "b@example.com"]
+        );
+        store.set_principal_emails("principal-1", &[]).unwrap();
+        assert!(
+            store
+                .list_principal_emails("principal-1")
+                .unwrap()
+                .is_empty()
+        );
     }
 
-    fn delete_lfs_object(&self, repo_id: &str, oid: &str) -> Result<bool> {
-        let rows = self.conn().execute(
-            "DELETE FROM lfs_objects WHERE repo_id = ?1 AND oid = ?2",
-            params![repo_id, oid],
-        )?;
-        Ok(rows > 0)
-    }
+    #[test]
+    fn test_repo_grant_paths() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        store
+            .create_principal(&Principal {
+                id: "principal-1".to_string(),
+                primary_namespace_id: "ns-1".to_string(),
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-1".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "mono".to_string(),
+                description: None,
+                visibility: Visibility::Private,
+                object_format: ObjectFormat::Sha1,
+                size_bytes: 0,
+                folder_id: None,
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+
+        let mut grant = RepoGrant {
+            principal_id: "principal-1".to_string(),
+            repo_id: "repo-1".to_string(),
+            allow_bits: Permission::REPO_WRITE,
+            deny_bits: Permission::default(),
+            paths: vec!["services/payments".to_string(), "docs".to_string()],
+            created_at: now,
+            updated_at: now,
+        };
+        store.upsert_repo_grant(&grant).unwrap();
+        let stored = store
+            .get_repo_grant("principal-1", "repo-1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.paths, ["docs", "services/payments"]);
+        let listed = store.list_principal_repo_grants("principal-1").unwrap();
+        assert_eq!(listed[0].paths, ["docs", "services/payments"]);
+
+        grant.paths.clear();
+        store.upsert_repo_grant(&grant).unwrap();
+        let stored = store
+            .get_repo_grant("principal-1", "repo-1")
+            .unwrap()
+            .unwrap();
+        assert!(stored.paths.is_empty());
 
-    fn get_repo_lfs_size(&self, repo_id: &str) -> Result<i64> {
-        let conn = self.conn();
-        let size: Option<i64> = conn
+        grant.paths = vec!["docs".to_string()];
+        store.upsert_repo_grant(&grant).unwrap();
+        assert!(store.delete_repo_grant("principal-1", "repo-1").unwrap());
+        let count: i64 = store
+            .conn()
             .query_row(
-                "SELECT SUM(size) FROM lfs_objects WHERE repo_id = ?1",
-                params![repo_id],
+                "SELECT COUNT(*) FROM principal_repo_grant_paths",
+                [],
                 |row| row.get(0),
             )
-            .optional()?
-            .flatten();
-        Ok(size.unwrap_or(0))
+            .unwrap();
+        assert_eq!(count, 0);
     }
 
-    fn has_admin_token(&self) -> Result<bool> {
-        let conn = self.conn();
-        let count: i32 = conn.query_row(
-            "SELECT COUNT(*) FROM tokens WHERE is_admin = 1",
-            [],
-            |row| row.get(0),
-        )?;
-        Ok(count > 0)
-    }
+    #[test]
+    fn test_repo_export_rules_and_status() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
 
-    fn close(&self) -> Result<()> {
-        Ok(())
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-1".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "internal".to_string(),
+                description: None,
+                visibility: Visibility::Private,
+                object_format: ObjectFormat::Sha1,
+                size_bytes: 0,
+                folder_id: None,
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+        let mut export = RepoExport {
+            repo_id: "repo-1".to_string(),
+            url: "https://example.com/public.git".to_string(),
+            interval_secs: 3600,
+            include: vec!["refs/heads/main".to_string(), "refs/tags/v*".to_string()],
+            exclude: Vec::new(),
+            created_at: now,
+            last_sync_at: None,
+            exported_refs: 0,
+            last_error: None,
+        };
+        store.set_repo_export(&export).unwrap();
+        let saved = store.get_repo_export("repo-1").unwrap().unwrap();
+        assert_eq!(saved.include, export.include);
+        assert!(saved.exclude.is_empty());
+
+        store.update_repo_export_sync("repo-1", 3).unwrap();
+        store
+            .update_repo_export_error("repo-1", "authentication failed")
+            .unwrap();
+        let failed = store.get_repo_export("repo-1").unwrap().unwrap();
+        assert_eq!(failed.exported_refs, 3);
+        assert_eq!(failed.last_error.as_deref(), Some("authentication failed"));
+
+        // Changing the rules keeps the sync history.
+        export.exclude = vec!["refs/tags/v*-rc*".to_string()];
+        store.set_repo_export(&export).unwrap();
+        let updated = store.get_repo_export("repo-1").unwrap().unwrap();
+        assert_eq!(updated.exclude, export.exclude);
+        assert_eq!(updated.exported_refs, 3);
+        assert_eq!(store.list_repo_exports().unwrap().len(), 1);
+
+        store.delete_repo("repo-1").unwrap();
+        assert!(store.get_repo_export("repo-1").unwrap().is_none());
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
 
     #[test]
-    fn test_initialize_creates_tables() {
+    fn test_change_log_records_writes() {
         let temp = TempDir::new().unwrap();
         let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
         store.initialize().unwrap();
-
-        let conn = store.conn();
-        let tables: Vec<String> = conn
-            .prepare("SELECT name FROM sqlite_master WHERE type='table' ORDER BY name")
-            .unwrap()
-            .query_map([], |row| row.get(0))
-            .unwrap()
-            .collect::<std::result::Result<Vec<_>, _>>()
+        assert_eq!(store.latest_change_seq().unwrap(), 0);
+
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-1".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "demo".to_string(),
+                description: None,
+                visibility: Visibility::Private,
+                object_format: ObjectFormat::Sha1,
+                size_bytes: 0,
+                folder_id: None,
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
             .unwrap();
+        store
+            .create_tag(&Tag {
+                id: "tag-1".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "rust".to_string(),
+                color: None,
+                created_at: now,
+            })
+            .unwrap();
+        store.add_repo_tag("repo-1", "tag-1").unwrap();
+        let after_setup = store.latest_change_seq().unwrap();
+
+        let seq = store.record_repo_write("repo-1").unwrap();
+        assert_eq!(seq, after_setup + 1);
+        assert!(matches!(
+            store.record_repo_write("missing"),
+            Err(Error::NotFound)
+        ));
+        let after_setup = seq;
+
+        // The repo's tag assignment goes with it and records nothing more.
+        store.delete_repo("repo-1").unwrap();
+
+        let changes = store.list_changes(0, 100).unwrap();
+        let kinds: Vec<_> = changes
+            .iter()
+            .map(|c| (c.kind, c.entity_id.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ChangeKind::Repo, "repo-1"),
+                (ChangeKind::Tag, "tag-1"),
+                (ChangeKind::Repo, "repo-1"),
+                (ChangeKind::Repo, "repo-1"),
+                (ChangeKind::Repo, "repo-1"),
+            ]
+        );
+        assert!(changes.iter().all(|c| c.namespace_id.as_deref() == Some("ns-1")));
+        assert_eq!(store.list_changes(after_setup, 100).unwrap().len(), 1);
+        assert_eq!(store.oldest_change_seq().unwrap(), Some(1));
 
-        assert!(tables.contains(&"namespaces".to_string()));
-        assert!(tables.contains(&"principals".to_string()));
-        assert!(tables.contains(&"tokens".to_string()));
-        assert!(tables.contains(&"repos".to_string()));
-        assert!(tables.contains(&"tags".to_string()));
-        assert!(tables.contains(&"repo_tags".to_string()));
-        assert!(tables.contains(&"folders".to_string()));
-        assert!(tables.contains(&"principal_namespace_grants".to_string()));
-        assert!(tables.contains(&"principal_repo_grants".to_string()));
-        assert!(tables.contains(&"lfs_objects".to_string()));
+        store
+            .conn()
+            .execute(
+                "UPDATE changes SET changed_at = datetime('now', '-2 days') WHERE seq <= 2",
+                [],
+            )
+            .unwrap();
+        assert_eq!(store.prune_changes(Duration::from_secs(86400)).unwrap(), 2);
+        assert_eq!(store.oldest_change_seq().unwrap(), Some(3));
+        assert_eq!(store.latest_change_seq().unwrap(), 5);
     }
 
     #[test]
-    fn test_namespace_crud() {
+    fn test_lfs_pool_counts_references() {
         let temp = TempDir::new().unwrap();
         let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
         store.initialize().unwrap();
 
-        let ns = Namespace {
-            id: "ns-1".to_string(),
-            name: "test-namespace".to_string(),
-            created_at: Utc::now(),
-            repo_limit: Some(10),
-            storage_limit_bytes: Some(1024 * 1024),
-            external_id: None,
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        for id in ["repo-1", "repo-2"] {
+            store
+                .create_repo(&Repo {
+                    id: id.to_string(),
+                    namespace_id: "ns-1".to_string(),
+                    name: id.to_string(),
+                    description: None,
+                    visibility: Visibility::Private,
+                    object_format: ObjectFormat::Sha1,
+                    size_bytes: 0,
+                    folder_id: None,
+                    last_push_at: None,
+                    created_at: now,
+                    updated_at: now,
+                })
+                .unwrap();
+            store
+                .create_lfs_object(&LfsObject {
+                    repo_id: id.to_string(),
+                    oid: "oid-1".to_string(),
+                    size: 3,
+                    created_at: now,
+                })
+                .unwrap();
+        }
+        let ref_count = |store: &SqliteStore| -> i64 {
+            store
+                .conn()
+                .query_row("SELECT ref_count FROM lfs_pool WHERE oid = 'oid-1'", [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
         };
+        assert_eq!(ref_count(&store), 2);
+        assert_eq!(store.get_namespace_lfs_size("ns-1").unwrap(), 6);
+
+        store
+            .create_lfs_object(&LfsObject {
+                repo_id: "repo-2".to_string(),
+                oid: "oid-2".to_string(),
+                size: 5,
+                created_at: now,
+            })
+            .unwrap();
+        assert_eq!(store.count_lfs_objects("repo-2").unwrap(), 2);
+        assert_eq!(store.get_repo_unshared_lfs_size("repo-2").unwrap(), 5);
+        let largest = store.list_largest_lfs_objects("repo-2", 1).unwrap();
+        assert_eq!(largest.len(), 1);
+        assert_eq!(largest[0].oid, "oid-2");
+        store.delete_lfs_object("repo-2", "oid-2").unwrap();
+        assert!(store.delete_unreferenced_lfs_object("oid-2").unwrap());
+
+        // Deleting a repo drops its references with it.
+        store.delete_repo("repo-1").unwrap();
+        assert_eq!(ref_count(&store), 1);
+        assert_eq!(store.get_namespace_lfs_size("ns-1").unwrap(), 3);
+        assert_eq!(store.get_repo_unshared_lfs_size("repo-2").unwrap(), 3);
+        assert!(!store.delete_unreferenced_lfs_object("oid-1").unwrap());
+
+        store.delete_lfs_object("repo-2", "oid-1").unwrap();
+        assert_eq!(ref_count(&store), 0);
+        let hour = Duration::from_secs(3600);
+        assert!(store.list_unreferenced_lfs_objects(hour, 10).unwrap().is_empty());
+
+        store
+            .conn()
+            .execute(
+                "UPDATE lfs_pool SET released_at = datetime('now', '-2 hours')",
+                [],
+            )
+            .unwrap();
+        assert_eq!(
+            store.list_unreferenced_lfs_objects(hour, 10).unwrap(),
+            vec!["oid-1".to_string()]
+        );
+        assert!(store.delete_unreferenced_lfs_object("oid-1").unwrap());
+        assert!(store.list_unreferenced_lfs_objects(hour, 10).unwrap().is_empty());
+    }
 
-        store.create_namespace(&ns).unwrap();
+    #[test]
+    fn test_repo_size_history() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
 
-        let fetched = store.get_namespace("ns-1").unwrap().unwrap();
-        assert_eq!(fetched.name, "test-namespace");
-        assert_eq!(fetched.repo_limit, Some(10));
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-1".to_string(),
+                namespace_id: "ns-1".to_string(),
+                name: "repo-1".to_string(),
+                description: None,
+                visibility: Visibility::Private,
+                object_format: ObjectFormat::Sha1,
+                size_bytes: 0,
+                folder_id: None,
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
 
-        let by_name = store
-            .get_namespace_by_name("test-namespace")
-            .unwrap()
+        store.update_repo_size("repo-1", 100).unwrap();
+        store
+            .create_lfs_object(&LfsObject {
+                repo_id: "repo-1".to_string(),
+                oid: "oid-1".to_string(),
+                size: 7,
+                created_at: now,
+            })
             .unwrap();
-        assert_eq!(by_name.id, "ns-1");
+        // Later changes the same day replace that day's snapshot.
+        store
+            .conn()
+            .execute(
+                "UPDATE repo_size_history SET day = date('now', '-10 days')",
+                [],
+            )
+            .unwrap();
+        store.update_repo_size("repo-1", 200).unwrap();
+        store.update_repo_size("repo-1", 250).unwrap();
 
-        let deleted = store.delete_namespace("ns-1").unwrap();
-        assert!(deleted);
+        let today = now.date_naive();
+        let history = store
+            .list_repo_size_history("repo-1", today - chrono::Days::new(3))
+            .unwrap();
+        let sizes: Vec<_> = history
+            .iter()
+            .map(|s| (s.size_bytes, s.lfs_bytes))
+            .collect();
+        assert_eq!(sizes, [(100, 7), (250, 7)]);
+        assert_eq!(history[0].day, today - chrono::Days::new(10));
+        assert_eq!(history[1].day, today);
+
+        store.delete_lfs_object("repo-1", "oid-1").unwrap();
+        let history = store.list_repo_size_history("repo-1", today).unwrap();
+        let sizes: Vec<_> = history
+            .iter()
+            .map(|s| (s.size_bytes, s.lfs_bytes))
+            .collect();
+        assert_eq!(sizes, [(100, 7), (250, 0)]);
+
+        store.delete_repo("repo-1").unwrap();
+        assert!(
+            store
+                .list_repo_size_history("repo-1", today)
+                .unwrap()
+                .is_empty()
+        );
+    }
 
-        let gone = store.get_namespace("ns-1").unwrap();
-        assert!(gone.is_none());
+    #[test]
+    fn test_lfs_integrity_checks() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "test-ns".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        for id in ["repo-1", "repo-2"] {
+            store
+                .create_repo(&Repo {
+                    id: id.to_string(),
+                    namespace_id: "ns-1".to_string(),
+                    name: id.to_string(),
+                    description: None,
+                    visibility: Visibility::Private,
+                    object_format: ObjectFormat::Sha1,
+                    size_bytes: 0,
+                    folder_id: None,
+                    last_push_at: None,
+                    created_at: now,
+                    updated_at: now,
+                })
+                .unwrap();
+            store
+                .create_lfs_object(&LfsObject {
+                    repo_id: id.to_string(),
+                    oid: "oid-1".to_string(),
+                    size: 3,
+                    created_at: now,
+                })
+                .unwrap();
+        }
+
+        // Never-checked objects are due, once each.
+        let hour = Duration::from_secs(3600);
+        let due = store.list_lfs_objects_to_verify(hour, 10).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].oid, "oid-1");
+        assert_eq!(due[0].repo_id, "repo-1");
+
+        store.record_lfs_check("oid-1", None).unwrap();
+        assert!(store.list_lfs_objects_to_verify(hour, 10).unwrap().is_empty());
+        assert!(!store.is_lfs_object_corrupted("oid-1").unwrap());
+
+        store.record_lfs_check("oid-1", Some("object is missing")).unwrap();
+        store
+            .conn()
+            .execute(
+                "UPDATE lfs_integrity SET corrupted_at = '2020-01-01T00:00:00+00:00'",
+                [],
+            )
+            .unwrap();
+        // A failure found again keeps when it was first detected.
+        store.record_lfs_check("oid-1", Some("size is 2, expected 3")).unwrap();
+        assert!(store.is_lfs_object_corrupted("oid-1").unwrap());
+        let corrupted = store.list_corrupted_lfs_objects().unwrap();
+        assert_eq!(corrupted.len(), 1);
+        assert_eq!(corrupted[0].repo_ids, vec!["repo-1", "repo-2"]);
+        assert_eq!(corrupted[0].error, "size is 2, expected 3");
+        assert_eq!(corrupted[0].detected_at.timestamp(), 1_577_836_800);
+
+        store.record_lfs_check("oid-1", None).unwrap();
+        assert!(store.list_corrupted_lfs_objects().unwrap().is_empty());
+
+        // Unreferenced objects aren't checked.
+        store
+            .conn()
+            .execute("UPDATE lfs_integrity SET checked_at = datetime('now', '-2 hours')", [])
+            .unwrap();
+        assert_eq!(store.list_lfs_objects_to_verify(hour, 10).unwrap().len(), 1);
+        store.delete_lfs_object("repo-1", "oid-1").unwrap();
+        store.delete_lfs_object("repo-2", "oid-1").unwrap();
+        assert!(store.list_lfs_objects_to_verify(hour, 10).unwrap().is_empty());
     }
 
     #[test]
@@ -1474,6 +4327,7 @@ mod tests {
             created_at: Utc::now(),
             expires_at: None,
             last_used_at: None,
+            traffic_class: TrafficClass::Interactive,
         };
         store.create_token(&token1).unwrap();
 
@@ -1486,9 +4340,516 @@ mod tests {
             created_at: Utc::now(),
             expires_at: None,
             last_used_at: None,
+            traffic_class: TrafficClass::Interactive,
         };
 
         let result = store.create_token(&token2);
         assert!(matches!(result, Err(Error::TokenLookupCollision)));
     }
+
+    #[test]
+    fn test_set_token_expiry() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        store
+            .create_token(&Token {
+                id: "token-1".to_string(),
+                token_hash: "hash1".to_string(),
+                token_lookup: "lookup12".to_string(),
+                is_admin: true,
+                principal_id: None,
+                created_at: Utc::now(),
+                expires_at: None,
+                last_used_at: None,
+                traffic_class: TrafficClass::Interactive,
+            })
+            .unwrap();
+
+        let expires_at = Utc::now() + chrono::Duration::days(7);
+        assert!(store.set_token_expiry("token-1", Some(expires_at)).unwrap());
+        let token = store.get_token_by_id("token-1").unwrap().unwrap();
+        assert_eq!(
+            token.expires_at.map(|t| t.timestamp()),
+            Some(expires_at.timestamp())
+        );
+
+        assert!(store.set_token_expiry("token-1", None).unwrap());
+        let token = store.get_token_by_id("token-1").unwrap().unwrap();
+        assert!(token.expires_at.is_none());
+        assert!(!store.set_token_expiry("missing", None).unwrap());
+    }
+
+    #[test]
+    fn test_search_tokens() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        let now = Utc::now();
+        let old = now - chrono::Duration::days(400);
+        for (id, is_admin, created_at, expires_at) in [
+            ("a", true, old, None),
+            ("b", true, old, Some(now - chrono::Duration::days(1))),
+            ("c", false, now, None),
+        ] {
+            store
+                .create_token(&Token {
+                    id: id.to_string(),
+                    token_hash: "hash".to_string(),
+                    token_lookup: format!("lookup-{id}"),
+                    is_admin,
+                    principal_id: None,
+                    created_at,
+                    expires_at,
+                    last_used_at: None,
+                    traffic_class: TrafficClass::Interactive,
+                })
+                .unwrap();
+        }
+        store.update_token_last_used("b").unwrap();
+
+        let ids = |filter: TokenFilter, sort: TokenSort, descending: bool, cursor: &str| {
+            store
+                .search_tokens(&filter, sort, descending, cursor, 2)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.id)
+                .collect::<Vec<_>>()
+        };
+        let filter = |f: fn(&mut TokenFilter)| {
+            let mut filter = TokenFilter::default();
+            f(&mut filter);
+            filter
+        };
+
+        let unused = filter(|f| f.unused_days = Some(365));
+        assert_eq!(ids(unused, TokenSort::Id, false, ""), ["a"]);
+        let expired = filter(|f| f.expired = Some(true));
+        assert_eq!(ids(expired, TokenSort::Id, false, ""), ["b"]);
+        let current = filter(|f| f.expired = Some(false));
+        assert_eq!(ids(current, TokenSort::Id, false, ""), ["a", "c"]);
+        let users = filter(|f| f.is_admin = Some(false));
+        assert_eq!(ids(users, TokenSort::Id, false, ""), ["c"]);
+
+        // Tokens never used sort first, and the cursor carries the key.
+        let all = TokenFilter::default;
+        assert_eq!(ids(all(), TokenSort::LastUsedAt, false, ""), ["a", "c"]);
+        let c = store.get_token_by_id("c").unwrap().unwrap();
+        let cursor = format!("{}|c", TokenSort::LastUsedAt.key(&c));
+        assert_eq!(ids(all(), TokenSort::LastUsedAt, false, &cursor), ["b"]);
+        assert_eq!(ids(all(), TokenSort::CreatedAt, true, ""), ["c", "b"]);
+        assert_eq!(ids(all(), TokenSort::Id, true, "b"), ["a"]);
+    }
+
+    #[test]
+    fn test_audit_events_and_push_addresses() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        store
+            .create_token(&Token {
+                id: "t".to_string(),
+                token_hash: "hash".to_string(),
+                token_lookup: "lookup-t".to_string(),
+                is_admin: true,
+                principal_id: None,
+                created_at: Utc::now(),
+                expires_at: None,
+                last_used_at: None,
+                traffic_class: TrafficClass::Interactive,
+            })
+            .unwrap();
+        // The first address isn't new to anything; the next one is, once.
+        assert!(!store.record_token_push_address("t", "10.0.0.1").unwrap());
+        assert!(!store.record_token_push_address("t", "10.0.0.1").unwrap());
+        assert!(store.record_token_push_address("t", "10.0.0.2").unwrap());
+        assert!(!store.record_token_push_address("t", "10.0.0.2").unwrap());
+
+        for (kind, repo_id) in [
+            ("history_rewrite", "r1"),
+            ("branch_deletion", "r1"),
+            ("history_rewrite", "r2"),
+        ] {
+            store
+                .create_audit_event(&AuditEvent {
+                    seq: 0,
+                    kind: kind.to_string(),
+                    summary: "summary".to_string(),
+                    namespace_id: None,
+                    repo_id: Some(repo_id.to_string()),
+                    principal_id: None,
+                    token_id: Some("t".to_string()),
+                    address: None,
+                    detail: serde_json::json!({ "kind": kind }),
+                    created_at: Utc::now(),
+                })
+                .unwrap();
+        }
+        let seqs = |filter: AuditEventFilter, before_seq: Option<i64>| {
+            store
+                .list_audit_events(&filter, before_seq, 10)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.seq)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(seqs(AuditEventFilter::default(), None), [3, 2, 1]);
+        assert_eq!(seqs(AuditEventFilter::default(), Some(3)), [2, 1]);
+        let rewrites = AuditEventFilter {
+            kind: Some("history_rewrite".to_string()),
+            ..AuditEventFilter::default()
+        };
+        assert_eq!(seqs(rewrites, None), [3, 1]);
+        let r1 = AuditEventFilter {
+            repo_id: Some("r1".to_string()),
+            ..AuditEventFilter::default()
+        };
+        assert_eq!(seqs(r1, None), [2, 1]);
+
+        let event = &store
+            .list_audit_events(&AuditEventFilter::default(), None, 1)
+            .unwrap()[0];
+        assert_eq!(event.detail["kind"], "history_rewrite");
+    }
+
+    #[test]
+    fn test_namespace_grant_cache_invalidation() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        for (id, name) in [("ns-1", "owner-ns"), ("ns-2", "shared-ns")] {
+            store
+                .create_namespace(&Namespace {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    created_at: Utc::now(),
+                    repo_limit: None,
+                    storage_limit_bytes: None,
+                    external_id: None,
+                })
+                .unwrap();
+        }
+        store
+            .create_principal(&Principal {
+                id: "principal-1".to_string(),
+                primary_namespace_id: "ns-1".to_string(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .unwrap();
+
+        // Cache the miss, then make sure the grant is visible immediately.
+        assert!(
+            store
+                .get_namespace_grant("principal-1", "ns-2")
+                .unwrap()
+                .is_none()
+        );
+
+        let grant = NamespaceGrant {
+            principal_id: "principal-1".to_string(),
+            namespace_id: "ns-2".to_string(),
+            allow_bits: Permission::REPO_READ,
+            deny_bits: Permission::default(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        store.upsert_namespace_grant(&grant).unwrap();
+        let fetched = store
+            .get_namespace_grant("principal-1", "ns-2")
+            .unwrap()
+            .unwrap();
+        assert_eq!(fetched.allow_bits, Permission::REPO_READ);
+
+        store.delete_namespace_grant("principal-1", "ns-2").unwrap();
+        assert!(
+            store
+                .get_namespace_grant("principal-1", "ns-2")
+                .unwrap()
+                .is_none()
+        );
+
+        store.upsert_namespace_grant(&grant).unwrap();
+        store.get_namespace_grant("principal-1", "ns-2").unwrap();
+        store.delete_principal("principal-1").unwrap();
+        assert!(
+            store
+                .get_namespace_grant("principal-1", "ns-2")
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_scim_users_and_groups() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        let now = Utc::now();
+        for (id, name) in [("ns-1", "alice"), ("ns-2", "team")] {
+            store
+                .create_namespace(&Namespace {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    created_at: now,
+                    repo_limit: None,
+                    storage_limit_bytes: None,
+                    external_id: None,
+                })
+                .unwrap();
+        }
+        store
+            .create_principal(&Principal {
+                id: "principal-1".to_string(),
+                primary_namespace_id: "ns-1".to_string(),
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+
+        // Principals without a SCIM record are active.
+        assert!(!store.is_principal_deactivated("principal-1").unwrap());
+
+        let mut user = ScimUser {
+            principal_id: "principal-1".to_string(),
+            external_id: Some("idp-1".to_string()),
+            active: false,
+            updated_at: now,
+        };
+        store.upsert_scim_user(&user).unwrap();
+        assert!(store.is_principal_deactivated("principal-1").unwrap());
+        let fetched = store.get_scim_user_by_external_id("idp-1").unwrap().unwrap();
+        assert_eq!(fetched.principal_id, "principal-1");
+
+        user.active = true;
+        store.upsert_scim_user(&user).unwrap();
+        assert!(!store.is_principal_deactivated("principal-1").unwrap());
+
+        let group = ScimGroup {
+            namespace_id: "ns-2".to_string(),
+            external_id: Some("idp-1".to_string()),
+            created_at: now,
+        };
+        store.upsert_scim_group(&group).unwrap();
+        let groups = store.list_scim_groups().unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].external_id.as_deref(), Some("idp-1"));
+
+        // External IDs are unique among groups.
+        let other = ScimGroup {
+            namespace_id: "ns-1".to_string(),
+            ..group
+        };
+        assert!(matches!(
+            store.upsert_scim_group(&other),
+            Err(Error::AlreadyExists)
+        ));
+
+        store.delete_principal("principal-1").unwrap();
+        assert!(store.get_scim_user("principal-1").unwrap().is_none());
+        assert!(store.delete_scim_group("ns-2").unwrap());
+        assert!(store.get_scim_group("ns-2").unwrap().is_none());
+        assert!(store.get_namespace("ns-2").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_signing_keys() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        let now = Utc::now();
+        store
+            .create_namespace(&Namespace {
+                id: "ns-1".to_string(),
+                name: "alice".to_string(),
+                created_at: now,
+                repo_limit: None,
+                storage_limit_bytes: None,
+                external_id: None,
+            })
+            .unwrap();
+        store
+            .create_principal(&Principal {
+                id: "principal-1".to_string(),
+                primary_namespace_id: "ns-1".to_string(),
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+
+        let gpg = SigningKey {
+            id: "key-1".to_string(),
+            principal_id: "principal-1".to_string(),
+            kind: SigningKeyKind::Gpg,
+            name: "Alice".to_string(),
+            public_key: "-----BEGIN PGP PUBLIC KEY BLOCK-----".to_string(),
+            fingerprint: "9FCA00A87A01DA94AE31D364EB471A1018D08E7D".to_string(),
+            key_ids: vec!["EB471A1018D08E7D".to_string(), "0123456789ABCDEF".to_string()],
+            created_at: now,
+        };
+        store.create_signing_key(&gpg).unwrap();
+        assert!(matches!(
+            store.create_signing_key(&SigningKey {
+                id: "key-2".to_string(),
+                ..gpg.clone()
+            }),
+            Err(Error::AlreadyExists)
+        ));
+
+        // Subkeys find the key too, but only for signatures of its kind.
+        let found = store
+            .find_signing_key(SigningKeyKind::Gpg, "0123456789ABCDEF")
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, "key-1");
+        assert_eq!(found.key_ids, gpg.key_ids);
+        assert!(
+            store
+                .find_signing_key(SigningKeyKind::Ssh, "EB471A1018D08E7D")
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            store
+                .find_signing_key(SigningKeyKind::Gpg, "1018D08E7D")
+                .unwrap()
+                .is_none()
+        );
+
+        assert_eq!(store.list_principal_signing_keys("principal-1").unwrap().len(), 1);
+        assert!(!store.delete_principal_signing_key("principal-2", "key-1").unwrap());
+        assert!(store.delete_principal_signing_key("principal-1", "key-1").unwrap());
+        assert!(store.list_principal_signing_keys("principal-1").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_orphan_reports() {
+        let temp = TempDir::new().unwrap();
+        let store = SqliteStore::new(temp.path().join("test.db")).unwrap();
+        store.initialize().unwrap();
+
+        let now = Utc::now();
+        for (id, name) in [
+            ("ns-1", "alice"),
+            ("ns-2", "bob"),
+            ("ns-3", "team"),
+            ("ns-4", "stale"),
+            ("ns-5", "apps"),
+        ] {
+            store
+                .create_namespace(&Namespace {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    created_at: now,
+                    repo_limit: None,
+                    storage_limit_bytes: None,
+                    external_id: None,
+                })
+                .unwrap();
+        }
+        for (id, namespace_id) in [("principal-1", "ns-1"), ("principal-2", "ns-2")] {
+            store
+                .create_principal(&Principal {
+                    id: id.to_string(),
+                    primary_namespace_id: namespace_id.to_string(),
+                    created_at: now,
+                    updated_at: now,
+                })
+                .unwrap();
+        }
+        store
+            .upsert_namespace_grant(&NamespaceGrant {
+                principal_id: "principal-1".to_string(),
+                namespace_id: "ns-3".to_string(),
+                allow_bits: Permission::default_namespace_grant(),
+                deny_bits: Permission::default(),
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+        let nested = store.ensure_folder_path("ns-5", "a/b").unwrap();
+        store.ensure_folder_path("ns-5", "c/d").unwrap();
+        store
+            .create_repo(&Repo {
+                id: "repo-1".to_string(),
+                namespace_id: "ns-5".to_string(),
+                name: "app".to_string(),
+                description: None,
+                visibility: Visibility::Private,
+                object_format: ObjectFormat::Sha1,
+                size_bytes: 0,
+                folder_id: Some(nested),
+                last_push_at: None,
+                created_at: now,
+                updated_at: now,
+            })
+            .unwrap();
+
+        let namespaces = store.list_orphan_namespaces().unwrap();
+        assert_eq!(
+            namespaces
+                .iter()
+                .map(|ns| ns.name.as_str())
+                .collect::<Vec<_>>(),
+            ["stale"]
+        );
+        let folders = store.list_empty_folders().unwrap();
+        assert_eq!(
+            folders.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(),
+            ["/c", "/c/d"]
+        );
+
+        store
+            .upsert_scim_user(&ScimUser {
+                principal_id: "principal-2".to_string(),
+                external_id: None,
+                active: false,
+                updated_at: now,
+            })
+            .unwrap();
+        // Only a database that once ran without foreign keys can hold a
+        // token for a principal that's gone.
+        store
+            .conn()
+            .pragma_update(None, "foreign_keys", "OFF")
+            .unwrap();
+        for (id, is_admin, principal_id) in [
+            ("tok-admin", true, None),
+            ("tok-1", false, Some("principal-1")),
+            ("tok-2", false, Some("principal-2")),
+            ("tok-3", false, Some("principal-gone")),
+        ] {
+            store
+                .create_token(&Token {
+                    id: id.to_string(),
+                    token_hash: "hash".to_string(),
+                    token_lookup: format!("lookup-{id}"),
+                    is_admin,
+                    principal_id: principal_id.map(str::to_string),
+                    created_at: now,
+                    expires_at: None,
+                    last_used_at: None,
+                    traffic_class: TrafficClass::Interactive,
+                })
+                .unwrap();
+        }
+
+        let tokens = store.list_orphan_tokens().unwrap();
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|(token, reason)| (token.id.as_str(), *reason))
+                .collect::<Vec<_>>(),
+            [
+                ("tok-2", OrphanTokenReason::Deactivated),
+                ("tok-3", OrphanTokenReason::MissingPrincipal),
+            ]
+        );
+    }
 }
@@ -0,0 +1,130 @@
+//! Logging and request tracing. Each HTTP request runs in a span, with
+//! child spans for token checks, store calls, git subprocesses, and LFS
+//! transfers. Built with the `otel` feature and given a `[tracing]`
+//! section, the server exports those spans to an OpenTelemetry collector
+//! over OTLP/HTTP, and requests carrying a W3C `traceparent` header join
+//! the caller's trace, so one request can be followed across a fleet.
+
+use axum::http::HeaderMap;
+use tracing::Span;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::config::TracingConfig;
+
+/// Keeps span export running. Dropping it sends the spans still queued.
+#[must_use]
+pub struct TracingGuard {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Failed to flush traces: {e}");
+        }
+    }
+}
+
+/// Installs the global subscriber: log lines filtered by `RUST_LOG`
+/// (cutman at info by default), and span export when `config` is set.
+pub fn init(config: Option<&TracingConfig>) -> anyhow::Result<TracingGuard> {
+    let filter = EnvFilter::from_default_env().add_directive("cutman=info".parse()?);
+    let subscriber = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer());
+
+    #[cfg(feature = "otel")]
+    {
+        use opentelemetry::trace::TracerProvider;
+
+        let provider = config.map(otlp::provider).transpose()?;
+        let layer = provider
+            .as_ref()
+            .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer("cutman")));
+        subscriber.with(layer).try_init()?;
+        if let Some(config) = config {
+            tracing::info!("Exporting traces to {}", config.traces_url());
+        }
+        Ok(TracingGuard { provider })
+    }
+
+    #[cfg(not(feature = "otel"))]
+    {
+        if let Some(config) = config {
+            anyhow::bail!(
+                "tracing names collector {}, but this build of cutman has no OpenTelemetry support",
+                config.otlp_endpoint
+            );
+        }
+        subscriber.try_init()?;
+        Ok(TracingGuard {})
+    }
+}
+
+/// Makes `span` part of the trace named by the request's `traceparent`
+/// header, if it has one and spans are exported.
+pub fn set_parent(span: &Span, headers: &HeaderMap) {
+    #[cfg(feature = "otel")]
+    {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let context = opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.extract(&otlp::HeaderExtractor(headers))
+        });
+        span.set_parent(context);
+    }
+
+    #[cfg(not(feature = "otel"))]
+    let _ = (span, headers);
+}
+
+#[cfg(feature = "otel")]
+mod otlp {
+    use axum::http::HeaderMap;
+    use opentelemetry::propagation::Extractor;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig, WithHttpConfig};
+    use opentelemetry_sdk::Resource;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+
+    use crate::config::TracingConfig;
+
+    pub(super) fn provider(config: &TracingConfig) -> anyhow::Result<SdkTracerProvider> {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(config.traces_url())
+            .with_headers(config.headers.clone())
+            .build()?;
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+        Ok(SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                config.sample_ratio,
+            ))))
+            .with_resource(
+                Resource::builder()
+                    .with_service_name(config.service_name.clone())
+                    .build(),
+            )
+            .build())
+    }
+
+    pub(super) struct HeaderExtractor<'a>(pub &'a HeaderMap);
+
+    impl Extractor for HeaderExtractor<'_> {
+        fn get(&self, key: &str) -> Option<&str> {
+            self.0.get(key).and_then(|value| value.to_str().ok())
+        }
+
+        fn keys(&self) -> Vec<&str> {
+            self.0.keys().map(|name| name.as_str()).collect()
+        }
+    }
+}
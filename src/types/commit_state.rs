@@ -0,0 +1,90 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// What a CI system reports about a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitState {
+    Pending,
+    Success,
+    Failure,
+    Error,
+}
+
+impl CommitState {
+    /// Parses the value stored in the `commit_statuses.state` column.
+    #[must_use]
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "pending" => Some(Self::Pending),
+            "success" => Some(Self::Success),
+            "failure" => Some(Self::Failure),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Success => "success",
+            Self::Failure => "failure",
+            Self::Error => "error",
+        }
+    }
+
+    /// The state of a commit as a whole: `failure` if any context failed or
+    /// errored, `success` if every context succeeded, and `pending`
+    /// otherwise, including when nothing has reported yet.
+    #[must_use]
+    pub fn combine(states: impl IntoIterator<Item = Self>) -> Self {
+        let mut combined = None;
+        for state in states {
+            match state {
+                Self::Failure | Self::Error => return Self::Failure,
+                Self::Pending => combined = Some(Self::Pending),
+                Self::Success => {
+                    combined.get_or_insert(Self::Success);
+                }
+            }
+        }
+        combined.unwrap_or(Self::Pending)
+    }
+}
+
+impl fmt::Display for CommitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_db_roundtrip() {
+        for state in [
+            CommitState::Pending,
+            CommitState::Success,
+            CommitState::Failure,
+            CommitState::Error,
+        ] {
+            assert_eq!(CommitState::parse(state.as_str()), Some(state));
+        }
+        assert_eq!(CommitState::parse("passed"), None);
+    }
+
+    #[test]
+    fn test_combine() {
+        use CommitState::*;
+
+        assert_eq!(CommitState::combine([]), Pending);
+        assert_eq!(CommitState::combine([Success, Success]), Success);
+        assert_eq!(CommitState::combine([Success, Pending]), Pending);
+        assert_eq!(CommitState::combine([Pending, Error, Success]), Failure);
+        assert_eq!(CommitState::combine([Failure]), Failure);
+    }
+}
@@ -1,5 +1,13 @@
+mod commit_state;
 mod models;
+mod object_format;
 mod permission;
+mod traffic;
+mod visibility;
 
+pub use commit_state::CommitState;
 pub use models::*;
+pub use object_format::ObjectFormat;
 pub use permission::*;
+pub use traffic::TrafficClass;
+pub use visibility::Visibility;
@@ -1,7 +1,7 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 
-use super::Permission;
+use super::{CommitState, ObjectFormat, Permission, TrafficClass, Visibility};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Namespace {
@@ -39,6 +39,258 @@ pub struct Token {
     pub expires_at: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub traffic_class: TrafficClass,
+}
+
+/// Which tokens an admin listing or bulk revoke covers. Unset fields match
+/// every token.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct TokenFilter {
+    #[serde(default)]
+    pub principal_id: Option<String>,
+    #[serde(default)]
+    pub is_admin: Option<bool>,
+    #[serde(default)]
+    pub expired: Option<bool>,
+    /// Tokens not used for this many days, counting a token never used from
+    /// when it was created.
+    #[serde(default)]
+    pub unused_days: Option<u32>,
+}
+
+impl TokenFilter {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// What admin token listings are ordered by. Ties, and tokens missing the
+/// timestamp, are ordered by ID.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenSort {
+    #[default]
+    Id,
+    CreatedAt,
+    LastUsedAt,
+    ExpiresAt,
+}
+
+impl TokenSort {
+    /// The value `token` is ordered by, to second precision; tokens missing
+    /// it sort first.
+    #[must_use]
+    pub fn key(self, token: &Token) -> String {
+        let at = match self {
+            Self::Id => return token.id.clone(),
+            Self::CreatedAt => Some(token.created_at),
+            Self::LastUsedAt => token.last_used_at,
+            Self::ExpiresAt => token.expires_at,
+        };
+        at.map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// How an identity provider sees a principal it provisioned over SCIM.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScimUser {
+    pub principal_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    /// Inactive principals keep their grants but can't authenticate.
+    pub active: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A namespace an identity provider manages as a SCIM group. Its members
+/// are the principals with a grant on the namespace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScimGroup {
+    pub namespace_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An SSH public key that authenticates a principal on the SSH git transport.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshKey {
+    pub id: String,
+    pub principal_id: String,
+    pub name: String,
+    /// OpenSSH `<algorithm> <base64>` form, without the comment.
+    pub public_key: String,
+    /// `SHA256:<base64>`, matching `ssh-keygen -l`.
+    pub fingerprint: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// Format of a commit signing key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SigningKeyKind {
+    Gpg,
+    Ssh,
+}
+
+impl SigningKeyKind {
+    /// Parses the value stored in the `signing_keys.kind` column.
+    #[must_use]
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "gpg" => Some(Self::Gpg),
+            "ssh" => Some(Self::Ssh),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Gpg => "gpg",
+            Self::Ssh => "ssh",
+        }
+    }
+}
+
+/// A public key a principal signs commits with. Commit signatures made by a
+/// registered key are reported as verified and attributed to its principal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningKey {
+    pub id: String,
+    pub principal_id: String,
+    pub kind: SigningKeyKind,
+    pub name: String,
+    /// ASCII-armored OpenPGP key, or OpenSSH `<algorithm> <base64>`.
+    pub public_key: String,
+    /// Hex fingerprint of an OpenPGP primary key, or `SHA256:<base64>`.
+    pub fingerprint: String,
+    /// IDs a signature may name its key by: the 16-digit key IDs of an
+    /// OpenPGP key and its subkeys, or the SSH fingerprint.
+    pub key_ids: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A repository kept in sync with an external URL by scheduled fetches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoMirror {
+    pub repo_id: String,
+    /// Upstream URL. May embed credentials, so never return it unredacted.
+    pub url: String,
+    pub interval_secs: i64,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_sync_at: Option<DateTime<Utc>>,
+    /// Failure output of the most recent sync, when it failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// A repository whose selected branches and tags are force-pushed to an
+/// external remote on a schedule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoExport {
+    pub repo_id: String,
+    /// Remote URL. May embed credentials, so never return it unredacted.
+    pub url: String,
+    pub interval_secs: i64,
+    /// Ref patterns to export, e.g. `refs/heads/main` or `refs/tags/v*`.
+    pub include: Vec<String>,
+    /// Ref patterns left out even when they match `include`.
+    pub exclude: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_sync_at: Option<DateTime<Utc>>,
+    /// Number of refs the remote was left with by the last successful sync.
+    pub exported_refs: i64,
+    /// Failure output of the most recent sync, when it failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// A static site published from a directory of one of a repository's
+/// branches, and the outcome of its most recent build.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoPages {
+    pub repo_id: String,
+    pub branch: String,
+    /// Directory of the branch to publish; empty for its root.
+    pub path: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_build_at: Option<DateTime<Utc>>,
+    /// Commit the published site was built from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_sha: Option<String>,
+    pub size_bytes: i64,
+    /// Why the most recent build failed, when it did. The previous site
+    /// stays published.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// The latest status one context, such as a CI job, reported for a commit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitStatus {
+    pub repo_id: String,
+    pub sha: String,
+    pub context: String,
+    pub state: CommitState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Principal that reported the latest state.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creator_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A legal hold on a repository, which stops anything in it from being
+/// deleted or rewritten until an admin lifts it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoHold {
+    pub repo_id: String,
+    pub reason: String,
+    /// The admin token that placed the hold.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A repository borrowing objects from its namespace's shared object store
+/// through git alternates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedObjectMember {
+    pub repo_id: String,
+    pub namespace_id: String,
+    pub joined_at: DateTime<Utc>,
+}
+
+/// Why a token is reported as orphaned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanTokenReason {
+    /// The principal the token belongs to no longer exists.
+    MissingPrincipal,
+    /// The identity provider deactivated the principal through SCIM.
+    Deactivated,
+}
+
+impl OrphanTokenReason {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::MissingPrincipal => "missing_principal",
+            Self::Deactivated => "deactivated",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,7 +300,9 @@ pub struct Repo {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
-    pub public: bool,
+    pub visibility: Visibility,
+    #[serde(default)]
+    pub object_format: ObjectFormat,
     pub size_bytes: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub folder_id: Option<i64>,
@@ -108,10 +362,54 @@ pub struct RepoGrant {
     pub repo_id: String,
     pub allow_bits: Permission,
     pub deny_bits: Permission,
+    /// Files and directories the principal's pushes may change; empty
+    /// leaves the whole repository open. See [`crate::grant_paths`].
+    #[serde(default)]
+    pub paths: Vec<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Kind of entity a change log entry refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Repo,
+    Folder,
+    Tag,
+    NamespaceGrant,
+    RepoGrant,
+}
+
+impl ChangeKind {
+    /// Parses the value stored in the `changes.kind` column.
+    #[must_use]
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "repo" => Some(Self::Repo),
+            "folder" => Some(Self::Folder),
+            "tag" => Some(Self::Tag),
+            "namespace_grant" => Some(Self::NamespaceGrant),
+            "repo_grant" => Some(Self::RepoGrant),
+            _ => None,
+        }
+    }
+}
+
+/// A change log entry: the entity was created, updated, or deleted. Only
+/// the entity's identity is recorded; readers load its current state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub seq: i64,
+    pub kind: ChangeKind,
+    /// Entity ID, or the grantee's principal ID for grants.
+    pub entity_id: String,
+    /// Namespace the entity belongs to; `None` for grants.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace_id: Option<String>,
+    pub changed_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LfsObject {
     pub repo_id: String,
@@ -120,6 +418,89 @@ pub struct LfsObject {
     pub created_at: DateTime<Utc>,
 }
 
+/// A repository's size at the end of a UTC day on which it changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoSizeSnapshot {
+    pub day: NaiveDate,
+    pub size_bytes: i64,
+    pub lfs_bytes: i64,
+}
+
+/// An unusual push flagged by the push alert checks, a legal hold placed
+/// or lifted by an admin, or a request an admin token made as another
+/// principal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub seq: i64,
+    /// `history_rewrite`, `branch_deletion`, `new_address`,
+    /// `legal_hold_placed`, `legal_hold_lifted`, `impersonation`, or
+    /// `private_mode_changed`.
+    pub kind: String,
+    pub summary: String,
+    pub namespace_id: Option<String>,
+    pub repo_id: Option<String>,
+    pub principal_id: Option<String>,
+    pub token_id: Option<String>,
+    /// The pusher's IP address, when known.
+    pub address: Option<String>,
+    /// What the check found, e.g. the branch and how many commits a
+    /// history rewrite dropped, the reason a hold was placed or lifted, or
+    /// the method and path of an impersonated request.
+    pub detail: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Which audit events a listing covers. Unset fields match every event.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct AuditEventFilter {
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub repo_id: Option<String>,
+    #[serde(default)]
+    pub principal_id: Option<String>,
+}
+
+/// A pooled LFS object whose content no longer matched its OID when it was
+/// last re-hashed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorruptedLfsObject {
+    pub oid: String,
+    pub size: i64,
+    /// Repositories that reference the object.
+    pub repo_ids: Vec<String>,
+    /// When the corruption was first found.
+    pub detected_at: DateTime<Utc>,
+    pub checked_at: DateTime<Utc>,
+    pub error: String,
+}
+
+/// An image manifest pushed to a repository's container registry. Its
+/// content is stored as an LFS object named by the digest's hex part.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryManifest {
+    pub repo_id: String,
+    /// `sha256:<hex>`.
+    pub digest: String,
+    pub media_type: String,
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A file published to a namespace's generic package registry, addressed
+/// by package name, version, and file name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageFile {
+    pub namespace_id: String,
+    pub package: String,
+    pub version: String,
+    pub filename: String,
+    pub size: i64,
+    /// Hex SHA-256 of the content.
+    pub sha256: String,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RepoWithTags {
     #[serde(flatten)]
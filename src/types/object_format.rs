@@ -0,0 +1,96 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Hash function a repository names its objects with. Fixed when the
+/// repository is created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectFormat {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl ObjectFormat {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+        }
+    }
+
+    /// Length of a full object id in hex.
+    #[must_use]
+    pub fn hex_len(self) -> usize {
+        match self {
+            Self::Sha1 => 40,
+            Self::Sha256 => 64,
+        }
+    }
+
+    /// The format `hex` is a full object id of, judging by its length.
+    #[must_use]
+    pub fn from_oid(hex: &str) -> Option<Self> {
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        [Self::Sha1, Self::Sha256]
+            .into_iter()
+            .find(|format| format.hex_len() == hex.len())
+    }
+}
+
+impl fmt::Display for ObjectFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Stored in the `repos.object_format` column: 0 = sha1, 1 = sha256.
+impl From<i64> for ObjectFormat {
+    fn from(value: i64) -> Self {
+        match value {
+            1 => Self::Sha256,
+            _ => Self::Sha1,
+        }
+    }
+}
+
+impl From<ObjectFormat> for i64 {
+    fn from(format: ObjectFormat) -> Self {
+        match format {
+            ObjectFormat::Sha1 => 0,
+            ObjectFormat::Sha256 => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_db_roundtrip() {
+        for format in [ObjectFormat::Sha1, ObjectFormat::Sha256] {
+            assert_eq!(ObjectFormat::from(i64::from(format)), format);
+        }
+    }
+
+    #[test]
+    fn test_from_oid() {
+        assert_eq!(
+            ObjectFormat::from_oid(&"a".repeat(40)),
+            Some(ObjectFormat::Sha1)
+        );
+        assert_eq!(
+            ObjectFormat::from_oid(&"0".repeat(64)),
+            Some(ObjectFormat::Sha256)
+        );
+        assert_eq!(ObjectFormat::from_oid(&"a".repeat(41)), None);
+        assert_eq!(ObjectFormat::from_oid(&"g".repeat(40)), None);
+        assert_eq!(ObjectFormat::from_oid("main"), None);
+    }
+}
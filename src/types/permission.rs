@@ -14,6 +14,8 @@ impl Permission {
     pub const NAMESPACE_READ: Permission = Permission(1 << 3); // 8
     pub const NAMESPACE_WRITE: Permission = Permission(1 << 4); // 16
     pub const NAMESPACE_ADMIN: Permission = Permission(1 << 5); // 32
+    /// Changing files and refs through the content API, apart from pushing.
+    pub const REPO_CONTENTS: Permission = Permission(1 << 6); // 64
 
     pub const fn new(bits: u32) -> Self {
         Self(bits)
@@ -42,7 +44,8 @@ impl Permission {
     }
 
     /// Expands a permission bitmask to include implied permissions.
-    /// admin implies write implies read, for both repo and namespace permissions.
+    /// admin implies write implies read, for both repo and namespace permissions,
+    /// and repo write implies contents, which implies read.
     /// This should only be used for ALLOW permissions, never for DENY.
     #[must_use]
     pub fn expand_implied(self) -> Permission {
@@ -52,6 +55,9 @@ impl Permission {
             result |= Self::REPO_WRITE.0;
         }
         if Permission(result).has(Self::REPO_WRITE) {
+            result |= Self::REPO_CONTENTS.0;
+        }
+        if Permission(result).has(Self::REPO_CONTENTS) {
             result |= Self::REPO_READ.0;
         }
 
@@ -65,6 +71,19 @@ impl Permission {
         Permission(result)
     }
 
+    /// Expands a DENY bitmask so that denying repo write also denies
+    /// contents, which write implies on the allow side. Without this a
+    /// grant allowing repo:admin but denying repo:write would keep
+    /// content API mutations.
+    #[must_use]
+    pub fn expand_denied(self) -> Permission {
+        if self.has(Self::REPO_WRITE) {
+            self.union(Self::REPO_CONTENTS)
+        } else {
+            self
+        }
+    }
+
     /// Returns the default permissions for simple token creation:
     /// namespace:write + repo:admin (which implies namespace:read, repo:read, repo:write).
     #[must_use]
@@ -78,6 +97,7 @@ impl Permission {
             "repo:read" => Some(Self::REPO_READ),
             "repo:write" => Some(Self::REPO_WRITE),
             "repo:admin" => Some(Self::REPO_ADMIN),
+            "repo:contents" => Some(Self::REPO_CONTENTS),
             "namespace:read" => Some(Self::NAMESPACE_READ),
             "namespace:write" => Some(Self::NAMESPACE_WRITE),
             "namespace:admin" => Some(Self::NAMESPACE_ADMIN),
@@ -107,6 +127,9 @@ impl Permission {
         if self.has(Self::REPO_ADMIN) {
             perms.push("repo:admin");
         }
+        if self.has(Self::REPO_CONTENTS) {
+            perms.push("repo:contents");
+        }
         if self.has(Self::NAMESPACE_READ) {
             perms.push("namespace:read");
         }
@@ -171,9 +194,46 @@ mod tests {
         assert!(expanded.has(Permission::REPO_READ));
     }
 
+    #[test]
+    fn test_write_implies_contents_but_contents_does_not_imply_write() {
+        let write = Permission::REPO_WRITE.expand_implied();
+        assert!(write.has(Permission::REPO_CONTENTS));
+        assert!(write.has(Permission::REPO_READ));
+
+        // A contents grant edits files through the API but can't push.
+        let contents = Permission::REPO_CONTENTS.expand_implied();
+        assert!(contents.has(Permission::REPO_READ));
+        assert!(!contents.has(Permission::REPO_WRITE));
+        assert!(!contents.has(Permission::REPO_ADMIN));
+
+        let read = Permission::REPO_READ.expand_implied();
+        assert!(!read.has(Permission::REPO_CONTENTS));
+    }
+
+    #[test]
+    fn test_deny_write_also_denies_contents() {
+        let allow = Permission::REPO_ADMIN.expand_implied();
+        let deny = Permission::REPO_WRITE.expand_denied();
+        let effective = allow.difference(deny);
+        assert!(effective.has(Permission::REPO_ADMIN));
+        assert!(effective.has(Permission::REPO_READ));
+        assert!(!effective.has(Permission::REPO_WRITE));
+        assert!(!effective.has(Permission::REPO_CONTENTS));
+
+        // Denying contents alone still leaves push.
+        let deny = Permission::REPO_CONTENTS.expand_denied();
+        let effective = allow.difference(deny);
+        assert!(effective.has(Permission::REPO_WRITE));
+        assert!(!effective.has(Permission::REPO_CONTENTS));
+    }
+
     #[test]
     fn test_parse_permission() {
         assert_eq!(Permission::parse("repo:read"), Some(Permission::REPO_READ));
+        assert_eq!(
+            Permission::parse("repo:contents"),
+            Some(Permission::REPO_CONTENTS)
+        );
         assert_eq!(Permission::parse("invalid"), None);
     }
 }
@@ -0,0 +1,62 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Scheduling class of a token's requests. When expensive operations queue,
+/// interactive requests are served before batch ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrafficClass {
+    /// People at a terminal or in the TUI.
+    #[default]
+    Interactive,
+    /// CI, mirrors, and other automation that can tolerate waiting.
+    Batch,
+}
+
+impl TrafficClass {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Interactive => "interactive",
+            Self::Batch => "batch",
+        }
+    }
+}
+
+impl fmt::Display for TrafficClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Stored in the `tokens.traffic_class` column: 0 = interactive, 1 = batch.
+impl From<i64> for TrafficClass {
+    fn from(value: i64) -> Self {
+        match value {
+            1 => Self::Batch,
+            _ => Self::Interactive,
+        }
+    }
+}
+
+impl From<TrafficClass> for i64 {
+    fn from(class: TrafficClass) -> Self {
+        match class {
+            TrafficClass::Interactive => 0,
+            TrafficClass::Batch => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_db_roundtrip() {
+        for class in [TrafficClass::Interactive, TrafficClass::Batch] {
+            assert_eq!(TrafficClass::from(i64::from(class)), class);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Who may read a repository without an explicit grant.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Only principals with repo read permission.
+    #[default]
+    Private,
+    /// Any authenticated principal; anonymous clients are rejected.
+    Internal,
+    /// Anyone, including anonymous clients.
+    Public,
+}
+
+impl Visibility {
+    #[must_use]
+    pub fn allows_anonymous_read(self) -> bool {
+        matches!(self, Self::Public)
+    }
+
+    #[must_use]
+    pub fn allows_authenticated_read(self) -> bool {
+        matches!(self, Self::Internal | Self::Public)
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Private => "private",
+            Self::Internal => "internal",
+            Self::Public => "public",
+        }
+    }
+}
+
+impl fmt::Display for Visibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Stored in the `repos.public` column, which predates internal visibility:
+// 0 = private, 1 = public, 2 = internal.
+impl From<i64> for Visibility {
+    fn from(value: i64) -> Self {
+        match value {
+            1 => Self::Public,
+            2 => Self::Internal,
+            _ => Self::Private,
+        }
+    }
+}
+
+impl From<Visibility> for i64 {
+    fn from(v: Visibility) -> Self {
+        match v {
+            Visibility::Private => 0,
+            Visibility::Public => 1,
+            Visibility::Internal => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_db_roundtrip() {
+        for v in [
+            Visibility::Private,
+            Visibility::Internal,
+            Visibility::Public,
+        ] {
+            assert_eq!(Visibility::from(i64::from(v)), v);
+        }
+    }
+
+    #[test]
+    fn test_legacy_public_flag() {
+        assert_eq!(Visibility::from(0), Visibility::Private);
+        assert_eq!(Visibility::from(1), Visibility::Public);
+    }
+
+    #[test]
+    fn test_read_rules() {
+        assert!(!Visibility::Private.allows_authenticated_read());
+        assert!(Visibility::Internal.allows_authenticated_read());
+        assert!(!Visibility::Internal.allows_anonymous_read());
+        assert!(Visibility::Public.allows_anonymous_read());
+    }
+}
@@ -0,0 +1,233 @@
+//! Formatting of sizes for messages shown to people, and parsing of the
+//! sizes and durations people write in config files.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer};
+
+/// Formats a byte count with binary units, e.g. `512 B` or `1.5 MiB`.
+#[must_use]
+pub fn format_size(bytes: i64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes.max(0) as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes.max(0), UNITS[0])
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Parses a byte count written with an optional unit, e.g. `512`,
+/// `10GB`, or `1.5 GiB`. `KB`, `MB`, `GB`, and `TB` are powers of 1000;
+/// `KiB`, `MiB`, `GiB`, and `TiB` are powers of 1024. Units are not case
+/// sensitive.
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1000,
+        "mb" => 1000_u64.pow(2),
+        "gb" => 1000_u64.pow(3),
+        "tb" => 1000_u64.pow(4),
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => {
+            return Err(format!(
+                "unknown unit `{}` in size `{input}` (use B, KB, MB, GB, TB, KiB, MiB, GiB, or TiB)",
+                unit.trim()
+            ));
+        }
+    };
+    let invalid = || format!("invalid size `{input}`, expected e.g. `512`, `10GB`, or `1.5 GiB`");
+
+    if let Ok(whole) = number.parse::<u64>() {
+        return whole
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("size `{input}` is too large"));
+    }
+    let value = number.parse::<f64>().map_err(|_| invalid())?;
+    let bytes = value * multiplier as f64;
+    if !bytes.is_finite() || bytes >= u64::MAX as f64 {
+        return Err(format!("size `{input}` is too large"));
+    }
+    Ok(bytes.round() as u64)
+}
+
+/// Parses a duration written as whole seconds or with units, e.g. `300`,
+/// `5m`, or `1h30m`. Units are `s`, `m`, `h`, `d`, and `w`.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    if let Ok(secs) = input.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    let invalid = || format!("invalid duration `{input}`, expected e.g. `300`, `5m`, or `1h30m`");
+    if input.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut secs: u64 = 0;
+    let mut rest = input;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(digits);
+        let amount: u64 = number.parse().map_err(|_| invalid())?;
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let multiplier = match unit.trim() {
+            "s" | "sec" | "secs" => 1,
+            "m" | "min" | "mins" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            "w" => 7 * 24 * 60 * 60,
+            "" => return Err(invalid()),
+            other => {
+                return Err(format!(
+                    "unknown unit `{other}` in duration `{input}` (use s, m, h, d, or w)"
+                ));
+            }
+        };
+        secs = amount
+            .checked_mul(multiplier)
+            .and_then(|s| secs.checked_add(s))
+            .ok_or_else(|| format!("duration `{input}` is too long"))?;
+        rest = tail.trim_start();
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+/// A byte count in config, written as a number or as a string with a unit
+/// (see [`parse_size`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = ByteSize;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a size in bytes, or a string like \"10GB\"")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<ByteSize, E> {
+                Ok(ByteSize(value))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<ByteSize, E> {
+                u64::try_from(value)
+                    .map(ByteSize)
+                    .map_err(|_| E::custom("size cannot be negative"))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<ByteSize, E> {
+                parse_size(value).map(ByteSize).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// A duration in config, written as whole seconds or as a string with
+/// units (see [`parse_duration`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HumanDuration(pub Duration);
+
+impl<'de> Deserialize<'de> for HumanDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = HumanDuration;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a number of seconds, or a string like \"5m\"")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<HumanDuration, E> {
+                Ok(HumanDuration(Duration::from_secs(value)))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<HumanDuration, E> {
+                u64::try_from(value)
+                    .map(|secs| HumanDuration(Duration::from_secs(secs)))
+                    .map_err(|_| E::custom("duration cannot be negative"))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<HumanDuration, E> {
+                parse_duration(value).map(HumanDuration).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1_572_864), "1.5 MiB");
+        assert_eq!(format_size(-1), "0 B");
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10GB"), Ok(10_000_000_000));
+        assert_eq!(parse_size("10 gb"), Ok(10_000_000_000));
+        assert_eq!(parse_size("1.5 GiB"), Ok(1_610_612_736));
+        assert_eq!(parse_size("100MiB"), Ok(100 * 1024 * 1024));
+        assert_eq!(parse_size("64 B"), Ok(64));
+        assert!(parse_size("10 GX").unwrap_err().contains("unknown unit"));
+        assert!(parse_size("GB").is_err());
+        assert!(parse_size("-1").is_err());
+        assert!(parse_size("1.2.3MB").is_err());
+        assert!(
+            parse_size("99999999999TB")
+                .unwrap_err()
+                .contains("too large")
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("300"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1h 30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(172_800)));
+        assert_eq!(parse_duration("45 s"), Ok(Duration::from_secs(45)));
+        assert!(
+            parse_duration("5 fortnights")
+                .unwrap_err()
+                .contains("unknown unit")
+        );
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("1.5h").is_err());
+        assert!(parse_duration("-5m").is_err());
+    }
+}
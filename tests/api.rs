@@ -233,10 +233,22 @@ async fn api_hurl_tests() {
         "admin/namespaces.hurl",
         "admin/tokens.hurl",
         "admin/principals.hurl",
+        "admin/sudo.hurl",
+        "admin/maintenance.hurl",
+        "admin/audit.hurl",
+        "admin/holds.hurl",
+        "admin/storage.hurl",
+        "admin/shared_objects.hurl",
+        "admin/reports.hurl",
         "user/namespaces.hurl",
+        "user/setup.hurl",
+        "user/ssh_keys.hurl",
+        "user/signing_keys.hurl",
         "user/repos.hurl",
         "user/repo_tags.hurl",
         "user/repo_folder.hurl",
+        "user/repo_mirror.hurl",
+        "user/sync.hurl",
         "user/tags.hurl",
         "user/folders.hurl",
         "content/refs.hurl",
@@ -246,11 +258,20 @@ async fn api_hurl_tests() {
         "content/mutations.hurl",
         "content/compare.hurl",
         "content/blame.hurl",
+        "content/render.hurl",
+        "content/grep.hurl",
         "content/archive.hurl",
+        "content/bundle.hurl",
         "content/readme.hurl",
+        "content/badge.hurl",
+        "content/insights.hurl",
+        "content/embed.hurl",
+        "content/merge.hurl",
+        "content/patches.hurl",
         "git/protocol.hurl",
         "lfs/batch.hurl",
         "lfs/objects.hurl",
+        "lfs/chunked.hurl",
     ];
 
     let test_paths: Vec<_> = test_files.iter().map(|f| test_dir.join(f)).collect();
@@ -0,0 +1,144 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use axum::extract::State;
+use axum::{Json, Router};
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+
+use common::TestServer;
+use common::fixtures::create_principal;
+
+type Checks = Arc<Mutex<Vec<Value>>>;
+
+/// Lets anyone read any repo and nobody administer one; everything else is
+/// left to the server's grants.
+async fn mock_policy(State(checks): State<Checks>, Json(body): Json<Value>) -> Json<Value> {
+    let input = body["input"].clone();
+    checks.lock().unwrap().push(input.clone());
+    Json(match input["permission"].as_str() {
+        Some("repo:read") => json!({"result": {"allow": true}}),
+        Some("repo:admin") => json!({"result": false}),
+        _ => json!({}),
+    })
+}
+
+async fn start_mock_policy() -> (String, Checks) {
+    let checks = Checks::default();
+    let app = Router::new()
+        .fallback(mock_policy)
+        .with_state(checks.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind mock policy");
+    let addr = listener.local_addr().expect("local addr");
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (format!("http://{addr}/v1/data/cutman/allow"), checks)
+}
+
+#[tokio::test]
+async fn permission_checks_are_delegated_to_the_policy_service() {
+    let (url, checks) = start_mock_policy().await;
+    let config_dir = tempfile::TempDir::new().expect("temp dir");
+    let config_path = config_dir.path().join("server.toml");
+    std::fs::write(&config_path, format!("[authz]\nurl = \"{url}\"\n")).expect("write config");
+    let server = TestServer::start_with_args(&["--config", config_path.to_str().unwrap()]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "authz-owner")
+        .await
+        .token;
+    let outsider = create_principal(&client, &server, "authz-outsider")
+        .await
+        .token;
+
+    // Creating falls back to grants, under which the owner may write.
+    let resp: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner)
+        .json(&json!({"name": "secret", "visibility": "private"}))
+        .send()
+        .await
+        .expect("create repo")
+        .json()
+        .await
+        .expect("parse repo response");
+    let repo_id = resp["data"]["id"].as_str().expect("repo id").to_string();
+    let repo_url = format!("{}/api/v1/repos/{repo_id}", server.base_url);
+
+    // The policy lets a principal without grants read the private repo.
+    let resp = client
+        .get(&repo_url)
+        .bearer_auth(&outsider)
+        .send()
+        .await
+        .expect("get repo");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // And overrides the owner's own access.
+    let resp = client
+        .delete(&repo_url)
+        .bearer_auth(&owner)
+        .send()
+        .await
+        .expect("delete repo");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let checks = checks.lock().unwrap();
+    let read = checks
+        .iter()
+        .find(|c| c["permission"] == "repo:read")
+        .expect("read check");
+    assert_eq!(read["repo"]["id"], repo_id);
+    assert_eq!(read["repo"]["visibility"], "private");
+    assert_eq!(read["namespace"]["name"], "authz-owner");
+    assert!(read["principal"]["id"].is_string());
+}
+
+#[tokio::test]
+async fn unreachable_policy_service_falls_back_to_grants() {
+    // Nothing listens on port 9 of localhost.
+    let config_dir = tempfile::TempDir::new().expect("temp dir");
+    let config_path = config_dir.path().join("server.toml");
+    std::fs::write(
+        &config_path,
+        "[authz]\nurl = \"http://127.0.0.1:9/allow\"\ntimeout_secs = 1\n",
+    )
+    .expect("write config");
+    let server = TestServer::start_with_args(&["--config", config_path.to_str().unwrap()]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "fallback-owner")
+        .await
+        .token;
+    let outsider = create_principal(&client, &server, "fallback-outsider")
+        .await
+        .token;
+
+    let resp: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner)
+        .json(&json!({"name": "secret", "visibility": "private"}))
+        .send()
+        .await
+        .expect("create repo")
+        .json()
+        .await
+        .expect("parse repo response");
+    let repo_id = resp["data"]["id"].as_str().expect("repo id").to_string();
+    let repo_url = format!("{}/api/v1/repos/{repo_id}", server.base_url);
+
+    let resp = client
+        .get(&repo_url)
+        .bearer_auth(&owner)
+        .send()
+        .await
+        .expect("get repo as owner");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = client
+        .get(&repo_url)
+        .bearer_auth(&outsider)
+        .send()
+        .await
+        .expect("get repo as outsider");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
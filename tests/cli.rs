@@ -11,7 +11,7 @@ use assert_cmd::Command;
 use assert_fs::TempDir;
 use chrono::Utc;
 use cutman::store::{SqliteStore, Store};
-use cutman::types::Repo;
+use cutman::types::{ObjectFormat, Repo, Visibility};
 use predicates::prelude::*;
 use serde_json::Value;
 use uuid::Uuid;
@@ -258,7 +258,8 @@ fn create_repo(ctx: &TestContext, namespace_id: &str, name: &str) -> String {
         namespace_id: namespace_id.to_string(),
         name: name.to_string(),
         description: None,
-        public: false,
+        visibility: Visibility::Private,
+        object_format: ObjectFormat::default(),
         size_bytes: 0,
         folder_id: None,
         last_push_at: None,
@@ -1233,3 +1234,72 @@ fn serve_requires_initialization() {
         .failure()
         .stderr(predicate::str::contains("Server not initialized"));
 }
+
+#[test]
+fn serve_rejects_invalid_config() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config = temp_dir.path().join("server.toml");
+    std::fs::write(&config, "prot = 8080\n").expect("write config");
+
+    Command::cargo_bin("cutman")
+        .expect("failed to find binary")
+        .args(["serve", "--config"])
+        .arg(&config)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "server.toml:1: prot: unknown key `prot` (did you mean `port`?)",
+        ));
+}
+
+// ============================================================================
+// Config Validate Tests
+// ============================================================================
+
+#[test]
+fn config_validate_lists_every_problem() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config = temp_dir.path().join("server.toml");
+    std::fs::write(
+        &config,
+        "port = 0\nmax_pack_bytes = \"1 ZB\"\n\n[lfs_s3]\nendpoint = \"minio:9000\"\nbucket = \"lfs\"\n",
+    )
+    .expect("write config");
+
+    Command::cargo_bin("cutman")
+        .expect("failed to find binary")
+        .args(["config", "validate", "--file"])
+        .arg(&config)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("server.toml:2: max_pack_bytes: unknown unit `ZB`"))
+        .stderr(predicate::str::contains("Found 1 problem in"));
+
+    std::fs::write(
+        &config,
+        "port = 0\nmax_pack_bytes = \"1 GB\"\n\n[lfs_s3]\nendpoint = \"minio:9000\"\nbucket = \"lfs\"\n",
+    )
+    .expect("write config");
+
+    Command::cargo_bin("cutman")
+        .expect("failed to find binary")
+        .args(["config", "validate", "--file"])
+        .arg(&config)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("server.toml:1: port: must be between 1 and 65535"))
+        .stderr(predicate::str::contains(
+            "server.toml:5: lfs_s3.endpoint: `minio:9000` is not an http:// or https:// URL",
+        ))
+        .stderr(predicate::str::contains("Found 2 problems in"));
+
+    std::fs::write(&config, "port = 8080\nmax_pack_bytes = \"1 GB\"\n").expect("write config");
+
+    Command::cargo_bin("cutman")
+        .expect("failed to find binary")
+        .args(["config", "validate", "--file"])
+        .arg(&config)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("is valid"));
+}
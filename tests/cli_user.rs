@@ -207,6 +207,46 @@ fn auth_login_rejects_invalid_token_format() {
         .stderr(predicate::str::contains("Invalid token format"));
 }
 
+#[test]
+fn config_set_protocol_persists_preference() {
+    let config_dir = TempDir::new().expect("failed to create temp dir");
+
+    cli_cmd(&config_dir)
+        .args(["config", "set", "protocol", "ssh"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("protocol = ssh"));
+
+    cli_cmd(&config_dir)
+        .args(["config", "get", "protocol"])
+        .assert()
+        .success()
+        .stdout("ssh\n");
+
+    cli_cmd(&config_dir)
+        .args(["config", "get"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ssh_port = 22"));
+}
+
+#[test]
+fn config_set_rejects_unknown_key_and_invalid_protocol() {
+    let config_dir = TempDir::new().expect("failed to create temp dir");
+
+    cli_cmd(&config_dir)
+        .args(["config", "set", "colour", "blue"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown config key"));
+
+    cli_cmd(&config_dir)
+        .args(["config", "set", "protocol", "ftp"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid protocol"));
+}
+
 #[tokio::test]
 async fn auth_login_and_credential_helper_roundtrip() {
     let server = TestServer::start().await;
@@ -290,6 +330,60 @@ async fn auth_login_and_credential_helper_roundtrip() {
         .stdout(predicate::str::contains("Logged out successfully"));
 }
 
+#[tokio::test]
+async fn setup_applies_recommended_git_config() {
+    if !git_available() {
+        eprintln!("Skipping setup test: git not available");
+        return;
+    }
+
+    let server = TestServer::start().await;
+    let client = Client::new();
+
+    let principal =
+        create_principal_and_token(&client, &server.base_url, &server.admin_token, "cli-setup")
+            .await;
+
+    let config_dir = TempDir::new().expect("failed to create temp dir");
+
+    cli_cmd(&config_dir)
+        .args([
+            "auth",
+            "login",
+            "--server",
+            &server.base_url,
+            "--token",
+            &principal.principal_token,
+            "--non-interactive",
+        ])
+        .assert()
+        .success();
+
+    cli_cmd(&config_dir)
+        .args(["setup", "--non-interactive"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--yes is required"));
+
+    cli_cmd(&config_dir)
+        .args(["setup", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("push.default"));
+    assert!(!config_dir.path().join(".gitconfig").exists());
+
+    cli_cmd(&config_dir)
+        .args(["setup", "--yes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Applied"));
+
+    let gitconfig =
+        std::fs::read_to_string(config_dir.path().join(".gitconfig")).expect("read gitconfig");
+    assert!(gitconfig.contains("!cutman credential"));
+    assert!(gitconfig.contains("default = current"));
+}
+
 #[tokio::test]
 async fn repo_tag_and_delete_flow() {
     let server = TestServer::start().await;
@@ -565,3 +659,175 @@ fn new_requires_login() {
         .failure()
         .stderr(predicate::str::contains("Not logged in"));
 }
+
+#[tokio::test]
+async fn new_with_license_builds_on_the_server_commit() {
+    if !git_available() {
+        eprintln!("Skipping new auto-init test: git not available");
+        return;
+    }
+
+    let server = TestServer::start().await;
+    let client = Client::new();
+
+    let principal = create_principal_and_token(
+        &client,
+        &server.base_url,
+        &server.admin_token,
+        "cli-init-user",
+    )
+    .await;
+
+    let config_dir = TempDir::new().expect("failed to create temp dir");
+
+    cli_cmd(&config_dir)
+        .args([
+            "auth",
+            "login",
+            "--server",
+            &server.base_url,
+            "--token",
+            &principal.principal_token,
+            "--non-interactive",
+        ])
+        .assert()
+        .success();
+
+    let work_dir = TempDir::new().expect("failed to create temp dir");
+    let project = work_dir.path().join("init-app");
+    std::fs::create_dir(&project).expect("create project dir");
+    std::fs::write(project.join("main.rs"), "fn main() {}\n").expect("write project file");
+
+    cli_cmd(&config_dir)
+        .current_dir(&project)
+        .env("GIT_AUTHOR_NAME", "Test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "Test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .args(["new", "--license", "MIT", "--gitignore", "Rust"])
+        .assert()
+        .success();
+
+    assert!(project.join("LICENSE").exists());
+    assert!(project.join(".gitignore").exists());
+
+    let log = ProcessCommand::new("git")
+        .current_dir(&project)
+        .args(["log", "--format=%s", "origin/main"])
+        .output()
+        .expect("git log");
+    assert_eq!(
+        String::from_utf8_lossy(&log.stdout),
+        "Add project files\nInitial commit\n"
+    );
+}
+
+#[tokio::test]
+async fn admin_commands_run_over_server() {
+    let server = TestServer::start().await;
+    let config_dir = TempDir::new().expect("failed to create temp dir");
+
+    let admin = |args: &[&str]| {
+        let mut cmd = cli_cmd(&config_dir);
+        cmd.arg("admin")
+            .args(args)
+            .args(["--server", &server.base_url])
+            .env("CUTMAN_ADMIN_TOKEN", &server.admin_token);
+        cmd
+    };
+
+    admin(&[
+        "principal",
+        "add",
+        "--username",
+        "remote-user",
+        "--create-token",
+        "--non-interactive",
+    ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Created principal \"remote-user\""))
+        .stdout(predicate::str::contains("Token created: cutman_"));
+
+    admin(&["namespace", "add", "--name", "remote-shared", "--non-interactive"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Created namespace \"remote-shared\""));
+
+    let output = admin(&["info", "--json"]).output().expect("admin info");
+    assert!(output.status.success());
+    let info: Value = serde_json::from_slice(&output.stdout).expect("parse info");
+    let principal_id = info["principals"][0]["id"].as_str().expect("principal id");
+    let namespace_id = info["namespaces"]
+        .as_array()
+        .expect("namespaces")
+        .iter()
+        .find(|ns| ns["name"] == "remote-shared")
+        .expect("shared namespace")["id"]
+        .as_str()
+        .expect("namespace id")
+        .to_string();
+    assert_eq!(info["principals"][0]["username"], "remote-user");
+    assert!(info.get("storage").is_none());
+
+    admin(&[
+        "permission",
+        "grant",
+        "--principal-id",
+        principal_id,
+        "--namespace-id",
+        &namespace_id,
+        "--permissions",
+        "repo:read,namespace:read",
+        "--non-interactive",
+    ])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(
+        "Granted remote-user access to namespace \"remote-shared\"",
+    ));
+
+    admin(&[
+        "token",
+        "create",
+        "--principal-id",
+        principal_id,
+        "--expires-days",
+        "30",
+        "--batch",
+        "--non-interactive",
+    ])
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("Token created for 'remote-user'"));
+
+    admin(&[
+        "principal",
+        "remove",
+        "--principal-id",
+        principal_id,
+        "--yes",
+        "--non-interactive",
+    ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deleted principal 'remote-user'"));
+
+    let output = admin(&["info", "--json"]).output().expect("admin info");
+    let info: Value = serde_json::from_slice(&output.stdout).expect("parse info");
+    assert_eq!(info["principals"].as_array().expect("principals").len(), 0);
+    let names: Vec<&str> = info["namespaces"]
+        .as_array()
+        .expect("namespaces")
+        .iter()
+        .filter_map(|ns| ns["name"].as_str())
+        .collect();
+    assert_eq!(names, ["remote-shared"]);
+
+    cli_cmd(&config_dir)
+        .args(["admin", "info", "--server", &server.base_url])
+        .env_remove("CUTMAN_ADMIN_TOKEN")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--admin-token"));
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use std::path::Path;
+
+use reqwest::Client;
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::{TestPrincipal, assert_git, assert_git_fails, create_principal, git};
+
+/// Pushes a repository with `commits` commits and returns its clone URL.
+fn push_history(work: &Path, owner: &TestPrincipal, server: &TestServer, commits: usize) -> String {
+    let source = work.join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    assert_git(&source, &["init", "-q", "-b", "main"]);
+    for i in 0..commits {
+        std::fs::write(source.join("history.txt"), format!("{i}\n")).expect("write file");
+        assert_git(&source, &["add", "history.txt"]);
+        assert_git(&source, &["commit", "-q", "-m", &format!("Commit {i}")]);
+    }
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/history.git",
+        owner.token, owner.namespace
+    );
+    assert_git(&source, &["push", "-q", &url, "main"]);
+    url
+}
+
+#[tokio::test]
+async fn shallow_clones_are_limited_to_max_depth() {
+    let server = TestServer::start_with_args(&["--max-clone-depth", "2"]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "depth-owner").await;
+    let work = TempDir::new().expect("temp dir");
+    let url = push_history(work.path(), &owner, &server, 4);
+
+    assert_git(
+        work.path(),
+        &["clone", "-q", "--depth", "2", &url, "shallow"],
+    );
+    let count = assert_git(
+        &work.path().join("shallow"),
+        &["rev-list", "--count", "HEAD"],
+    );
+    assert_eq!(count, "2");
+
+    for protocol in ["protocol.version=2", "protocol.version=0"] {
+        let stderr = assert_git_fails(
+            work.path(),
+            &["-c", protocol, "clone", "-q", "--depth", "3", &url, "deep"],
+        );
+        assert!(
+            stderr.contains("remote error: Shallow fetches are limited to a depth of 2"),
+            "{protocol}: {stderr}"
+        );
+    }
+
+    let stderr = assert_git_fails(
+        work.path(),
+        &[
+            "clone",
+            "-q",
+            "--shallow-since",
+            "2000-01-01",
+            &url,
+            "since",
+        ],
+    );
+    assert!(stderr.contains("use --depth 2 or less"), "{stderr}");
+
+    // Full clones, and unshallowing, cost no more than each other.
+    assert_git(work.path(), &["clone", "-q", &url, "full"]);
+    assert_git(
+        &work.path().join("shallow"),
+        &["fetch", "-q", "--unshallow"],
+    );
+    let count = assert_git(
+        &work.path().join("shallow"),
+        &["rev-list", "--count", "HEAD"],
+    );
+    assert_eq!(count, "4");
+}
+
+#[tokio::test]
+async fn clones_queue_for_a_per_repo_slot() {
+    let server = TestServer::start_with_args(&["--clone-slots-per-repo", "1"]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "slots-owner").await;
+    let work = TempDir::new().expect("temp dir");
+    let url = push_history(work.path(), &owner, &server, 2);
+
+    // Concurrent clones of one repository wait for each other and all
+    // complete.
+    let clones: Vec<_> = (0..3)
+        .map(|i| {
+            let dir = work.path().to_path_buf();
+            let url = url.clone();
+            std::thread::spawn(move || git(&dir, &["clone", "-q", &url, &format!("clone-{i}")]))
+        })
+        .collect();
+    for clone in clones {
+        let output = clone.join().expect("clone thread");
+        assert!(
+            output.status.success(),
+            "clone failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
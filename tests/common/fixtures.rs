@@ -0,0 +1,113 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+use reqwest::Client;
+use serde_json::{Value, json};
+
+use super::TestServer;
+
+pub struct TestPrincipal {
+    pub id: String,
+    pub namespace: String,
+    pub namespace_id: String,
+    pub token: String,
+}
+
+/// Creates a principal with its own namespace, and a token for it.
+pub async fn create_principal(
+    client: &Client,
+    server: &TestServer,
+    namespace: &str,
+) -> TestPrincipal {
+    let resp: Value = client
+        .post(format!("{}/api/v1/admin/principals", server.base_url))
+        .bearer_auth(&server.admin_token)
+        .json(&json!({"namespace_name": namespace}))
+        .send()
+        .await
+        .expect("create principal")
+        .json()
+        .await
+        .expect("parse principal response");
+    let id = resp["data"]["id"]
+        .as_str()
+        .expect("principal id")
+        .to_string();
+    let namespace_id = resp["data"]["primary_namespace_id"]
+        .as_str()
+        .expect("namespace id")
+        .to_string();
+
+    let resp: Value = client
+        .post(format!(
+            "{}/api/v1/admin/principals/{}/tokens",
+            server.base_url, id
+        ))
+        .bearer_auth(&server.admin_token)
+        .json(&json!({}))
+        .send()
+        .await
+        .expect("create token")
+        .json()
+        .await
+        .expect("parse token response");
+    let token = resp["data"]["token"].as_str().expect("token").to_string();
+
+    TestPrincipal {
+        id,
+        namespace: namespace.to_string(),
+        namespace_id,
+        token,
+    }
+}
+
+/// Creates a repo with an initial commit on `main` in the token's own
+/// namespace, and returns its ID.
+pub async fn create_repo(client: &Client, server: &TestServer, token: &str, name: &str) -> String {
+    let resp = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(token)
+        .json(&json!({"name": name, "auto_init": {}}))
+        .send()
+        .await
+        .expect("create repo");
+    let status = resp.status();
+    let body: Value = resp.json().await.expect("parse repo response");
+    assert!(status.is_success(), "create repo: {status} {body}");
+    body["data"]["id"].as_str().expect("repo id").to_string()
+}
+
+/// Runs git in `dir` with a fixed author and committer.
+pub fn git(dir: &Path, args: &[&str]) -> Output {
+    Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "Test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "Test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .output()
+        .expect("run git")
+}
+
+/// Runs git, failing the test unless it succeeds, and returns its trimmed
+/// stdout.
+pub fn assert_git(dir: &Path, args: &[&str]) -> String {
+    let output = git(dir, args);
+    assert!(
+        output.status.success(),
+        "git {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Runs git, failing the test if it succeeds, and returns its stderr.
+pub fn assert_git_fails(dir: &Path, args: &[&str]) -> String {
+    let output = git(dir, args);
+    assert!(
+        !output.status.success(),
+        "git {args:?} unexpectedly succeeded"
+    );
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
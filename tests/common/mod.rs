@@ -1,3 +1,7 @@
+// Each integration test binary uses a different subset of these helpers.
+#![allow(dead_code)]
+
+pub mod fixtures;
 pub mod test_server;
 
 pub use test_server::TestServer;
@@ -1,6 +1,7 @@
 use std::path::Path;
-use std::process::{Child, Command, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::sync::LazyLock;
+use std::time::{Duration, Instant};
 
 use tempfile::TempDir;
 
@@ -23,6 +24,10 @@ static BUILD_RELEASE: LazyLock<()> = LazyLock::new(|| {
 
 impl TestServer {
     pub async fn start() -> Self {
+        Self::start_with_args(&[]).await
+    }
+
+    pub async fn start_with_args(extra_args: &[&str]) -> Self {
         LazyLock::force(&BUILD_RELEASE);
 
         let temp_dir = TempDir::new().expect("create temp dir");
@@ -57,6 +62,7 @@ impl TestServer {
             .arg(data_dir)
             .args(["--host", "127.0.0.1", "--port"])
             .arg(port.to_string())
+            .args(extra_args)
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .spawn()
@@ -91,6 +97,31 @@ impl TestServer {
     pub fn data_dir(&self) -> &Path {
         self.temp_dir.path()
     }
+
+    /// Sends SIGTERM, as a supervisor stopping the server would.
+    pub fn terminate(&self) {
+        let process = self.server_process.as_ref().expect("server running");
+        let pid = libc::pid_t::try_from(process.id()).expect("pid");
+        // SAFETY: kill takes no pointers.
+        let sent = unsafe { libc::kill(pid, libc::SIGTERM) };
+        assert_eq!(sent, 0, "send SIGTERM");
+    }
+
+    /// Waits up to `timeout` for the server to exit, returning its status,
+    /// or `None` if it's still running.
+    pub fn wait_for_exit(&mut self, timeout: Duration) -> Option<ExitStatus> {
+        let process = self.server_process.as_mut().expect("server running");
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = process.try_wait().expect("check server") {
+                return Some(status);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
 }
 
 impl Drop for TestServer {
@@ -0,0 +1,581 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
+use reqwest::{Client, StatusCode};
+use tokio::io::AsyncReadExt;
+use serde_json::{Value, json};
+
+use common::TestServer;
+use common::fixtures::{create_principal, create_repo};
+
+/// Creates or updates `path` on main through the content API, failing the
+/// test unless it succeeds, and returns the response's data.
+async fn put_file(
+    client: &Client,
+    repo_url: &str,
+    token: &str,
+    path: &str,
+    body: Value,
+) -> Value {
+    let resp = client
+        .put(format!("{repo_url}/blob/main/{path}"))
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+        .expect("put blob");
+    let status = resp.status();
+    let body: Value = resp.json().await.expect("parse put response");
+    assert_eq!(status, StatusCode::CREATED, "put {path}: {body}");
+    body["data"].clone()
+}
+
+#[tokio::test]
+async fn consistency_tokens_ahead_of_the_change_log_are_rejected_at_once() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "consistency").await.token;
+    let repo_id = create_repo(&client, &server, &token, "tokens").await;
+    let repo_url = format!("{}/api/v1/repos/{repo_id}", server.base_url);
+
+    let resp = client
+        .put(format!("{repo_url}/blob/main/notes.txt"))
+        .bearer_auth(&token)
+        .json(&json!({"message": "Add notes", "content": "hello"}))
+        .send()
+        .await
+        .expect("put blob");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = resp.json().await.expect("parse put response");
+    let written: i64 = body["data"]["consistency_token"]
+        .as_str()
+        .and_then(|t| t.parse().ok())
+        .expect("consistency token");
+
+    let resp = client
+        .get(format!("{repo_url}/blob/main/notes.txt"))
+        .bearer_auth(&token)
+        .header("X-Consistency-Token", written.to_string())
+        .send()
+        .await
+        .expect("read with token");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Nothing will ever commit this sequence, so holding the request for the
+    // catch-up timeout would only tie up the server.
+    let started = Instant::now();
+    let resp = client
+        .get(format!("{repo_url}/blob/main/notes.txt"))
+        .header("X-Consistency-Token", (written + 1000).to_string())
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("read with future token");
+    assert_eq!(resp.status(), StatusCode::PRECONDITION_FAILED);
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn every_content_write_returns_a_newer_consistency_token() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "consistency-writes").await.token;
+    let repo_id = create_repo(&client, &server, &token, "writes").await;
+    let repo_url = format!("{}/api/v1/repos/{repo_id}", server.base_url);
+
+    let writes = [
+        client
+            .put(format!("{repo_url}/blob/main/notes.txt"))
+            .json(&json!({"message": "Add notes", "content": "hello"})),
+        client.post(format!("{repo_url}/commits")).json(&json!({
+            "message": "Add more",
+            "branch": "main",
+            "actions": [{"action": "create", "path": "more.txt", "content": "more"}],
+        })),
+        client
+            .put(format!("{repo_url}/raw/main/data.bin?message=Add%20data"))
+            .body(vec![0u8, 1, 2]),
+    ];
+    let mut last = 0;
+    for write in writes {
+        let resp = write.bearer_auth(&token).send().await.expect("write");
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let header = resp.headers()["x-consistency-token"]
+            .to_str()
+            .expect("token header")
+            .to_string();
+        let body: Value = resp.json().await.expect("parse write response");
+        assert_eq!(body["data"]["consistency_token"], header.as_str());
+        let sequence: i64 = header.parse().expect("numeric token");
+        assert!(sequence > last, "{sequence} follows {last}");
+        last = sequence;
+    }
+
+    let resp = client
+        .get(format!("{repo_url}/blob/main/data.bin"))
+        .bearer_auth(&token)
+        .header("X-Consistency-Token", last.to_string())
+        .send()
+        .await
+        .expect("read with latest token");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = client
+        .get(format!("{repo_url}/tree/main"))
+        .bearer_auth(&token)
+        .header("X-Consistency-Token", "not-a-token")
+        .send()
+        .await
+        .expect("read with malformed token");
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn multi_file_commit_creates_its_branch_from_a_base_ref() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "branch-commit").await.token;
+    let repo_id = create_repo(&client, &server, &token, "proposals").await;
+    let repo_url = format!("{}/api/v1/repos/{repo_id}", server.base_url);
+
+    let propose = |branch: &str, action: &str| {
+        json!({
+            "message": "Propose a change",
+            "branch": branch,
+            "base_ref": "main",
+            "actions": [{"action": action, "path": "PROPOSAL.md", "content": "proposed\n"}],
+        })
+    };
+
+    let resp = client
+        .post(format!("{repo_url}/commits"))
+        .bearer_auth(&token)
+        .json(&propose("proposal", "create"))
+        .send()
+        .await
+        .expect("commit on new branch");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = resp.json().await.expect("parse commit response");
+    assert_eq!(body["data"]["ref_name"], "proposal");
+
+    // The branch starts from main's tree, and main itself is untouched.
+    for (path, status) in [
+        ("proposal/PROPOSAL.md", StatusCode::OK),
+        ("proposal/README.md", StatusCode::OK),
+        ("main/PROPOSAL.md", StatusCode::NOT_FOUND),
+    ] {
+        let resp = client
+            .get(format!("{repo_url}/blob/{path}"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("get blob");
+        assert_eq!(resp.status(), status, "{path}");
+    }
+
+    let resp = client
+        .post(format!("{repo_url}/commits"))
+        .bearer_auth(&token)
+        .json(&propose("proposal", "create"))
+        .send()
+        .await
+        .expect("commit on existing branch");
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    // A failing action leaves no branch behind.
+    let resp = client
+        .post(format!("{repo_url}/commits"))
+        .bearer_auth(&token)
+        .json(&json!({
+            "message": "Propose a broken change",
+            "branch": "broken",
+            "base_ref": "main",
+            "actions": [{"action": "delete", "path": "MISSING.md"}],
+        }))
+        .send()
+        .await
+        .expect("commit failing action");
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let resp = client
+        .get(format!("{repo_url}/blob/broken/README.md"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get blob on broken branch");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn raw_put_stores_the_request_body_byte_for_byte() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "raw-put").await.token;
+    let repo_id = create_repo(&client, &server, &token, "binaries").await;
+    let raw_url = format!(
+        "{}/api/v1/repos/{repo_id}/raw/main/assets/data.bin",
+        server.base_url
+    );
+    let blob_url = format!(
+        "{}/api/v1/repos/{repo_id}/blob/main/assets/data.bin?raw=true",
+        server.base_url
+    );
+
+    // Every byte value, so nothing survives only by being valid UTF-8.
+    let content: Vec<u8> = (0..=255).collect();
+    let resp = client
+        .put(format!("{raw_url}?message=Add%20data"))
+        .bearer_auth(&token)
+        .header("Content-Type", "application/octet-stream")
+        .body(content.clone())
+        .send()
+        .await
+        .expect("put raw");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = resp.json().await.expect("parse put response");
+    assert_eq!(body["data"]["file"]["size"], content.len());
+    let sha = body["data"]["file"]["sha"].as_str().expect("file sha").to_string();
+
+    let resp = client
+        .get(&blob_url)
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get raw blob");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.bytes().await.expect("read blob"), content);
+
+    // Replacing needs the current SHA, here sent as If-Match.
+    let resp = client
+        .put(&raw_url)
+        .bearer_auth(&token)
+        .body(vec![0u8, 1])
+        .send()
+        .await
+        .expect("put raw without sha");
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let resp = client
+        .put(&raw_url)
+        .bearer_auth(&token)
+        .header("If-Match", format!("\"{sha}\""))
+        .header("X-Commit-Message", "Shrink data")
+        .body(vec![0u8, 1])
+        .send()
+        .await
+        .expect("put raw with sha");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let resp = client
+        .get(&blob_url)
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get replaced blob");
+    assert_eq!(resp.bytes().await.expect("read blob"), [0u8, 1].as_slice());
+}
+
+#[tokio::test]
+async fn blame_groups_lines_into_hunks_within_the_requested_range() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "blame-range").await.token;
+    let repo_id = create_repo(&client, &server, &token, "history").await;
+    let repo_url = format!("{}/api/v1/repos/{repo_id}", server.base_url);
+
+    let first = put_file(
+        &client,
+        &repo_url,
+        &token,
+        "poem.txt",
+        json!({"message": "Add poem", "content": "one\ntwo\nthree\nfour\nfive\n"}),
+    )
+    .await;
+    let second = put_file(
+        &client,
+        &repo_url,
+        &token,
+        "poem.txt",
+        json!({
+            "message": "Revise poem",
+            "content": "one\ntwo\nTHREE\nFOUR\nfive\n",
+            "sha": first["file"]["sha"],
+        }),
+    )
+    .await;
+
+    let resp: Value = client
+        .get(format!(
+            "{repo_url}/blame/main/poem.txt?start_line=2&end_line=4"
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get blame")
+        .json()
+        .await
+        .expect("parse blame");
+    let blame = &resp["data"];
+    assert_eq!(blame["total_lines"], 5);
+    assert_eq!(blame["start_line"], 2);
+    assert_eq!(blame["end_line"], 4);
+    assert_eq!(blame["is_truncated"], false);
+    assert_eq!(
+        blame["hunks"],
+        json!([
+            {
+                "sha": first["commit_sha"],
+                "author": blame["hunks"][0]["author"],
+                "start_line": 2,
+                "lines": ["two"],
+            },
+            {
+                "sha": second["commit_sha"],
+                "author": blame["hunks"][1]["author"],
+                "start_line": 3,
+                "lines": ["THREE", "FOUR"],
+            },
+        ])
+    );
+
+    // Longer files come back a page at a time.
+    let long: String = (1..=2500).map(|n| format!("line {n}\n")).collect();
+    put_file(
+        &client,
+        &repo_url,
+        &token,
+        "long.txt",
+        json!({"message": "Add long file", "content": long}),
+    )
+    .await;
+    let resp: Value = client
+        .get(format!("{repo_url}/blame/main/long.txt"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get blame")
+        .json()
+        .await
+        .expect("parse blame");
+    let blame = &resp["data"];
+    assert_eq!(blame["total_lines"], 2500);
+    assert_eq!(blame["end_line"], 2000);
+    assert_eq!(blame["is_truncated"], true);
+    assert_eq!(blame["next_start_line"], 2001);
+    assert_eq!(blame["hunks"][0]["lines"].as_array().map(Vec::len), Some(2000));
+
+    let resp = client
+        .get(format!("{repo_url}/blame/main/long.txt?start_line=2501"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get blame past the end");
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn tree_listings_page_through_entries_and_cap_children() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "tree-pages").await.token;
+    let repo_id = create_repo(&client, &server, &token, "crowded").await;
+    let repo_url = format!("{}/api/v1/repos/{repo_id}", server.base_url);
+
+    let mut actions: Vec<Value> = (0..101)
+        .map(|n| json!({"action": "create", "path": format!("big/{n:03}.txt"), "content": "x"}))
+        .collect();
+    for name in ["a.txt", "b.txt"] {
+        actions.push(json!({"action": "create", "path": name, "content": "x"}));
+    }
+    let resp = client
+        .post(format!("{repo_url}/commits"))
+        .bearer_auth(&token)
+        .json(&json!({"message": "Add files", "branch": "main", "actions": actions}))
+        .send()
+        .await
+        .expect("commit files");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let mut names = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut url = format!("{repo_url}/tree/main?limit=2");
+        if let Some(cursor) = &cursor {
+            url.push_str(&format!("&cursor={cursor}"));
+        }
+        let page: Value = client
+            .get(url)
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("get tree page")
+            .json()
+            .await
+            .expect("parse tree page");
+        let entries = page["data"].as_array().expect("tree entries");
+        assert!(entries.len() <= 2);
+        names.extend(entries.iter().map(|e| e["name"].as_str().expect("name").to_string()));
+        if page["has_more"] != true {
+            assert!(page["next_cursor"].is_null());
+            break;
+        }
+        cursor = page["next_cursor"].as_str().map(str::to_string);
+    }
+    assert_eq!(names, ["big", "README.md", "a.txt", "b.txt"]);
+
+    let tree: Value = client
+        .get(format!("{repo_url}/tree/main?depth=2"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get nested tree")
+        .json()
+        .await
+        .expect("parse nested tree");
+    let big = &tree["data"][0];
+    assert_eq!(big["name"], "big");
+    assert_eq!(big["children"].as_array().map(Vec::len), Some(100));
+    assert_eq!(big["children_truncated"], true);
+}
+
+#[tokio::test]
+async fn compressed_archives_stream_the_same_tarball() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "archives").await.token;
+    let repo_id = create_repo(&client, &server, &token, "bundle").await;
+    let repo_url = format!("{}/api/v1/repos/{repo_id}", server.base_url);
+
+    // Large enough to span many chunks of the streamed response.
+    let data: String = (0..50_000).map(|n| format!("{n:x} {}\n", n * 7919)).collect();
+    put_file(
+        &client,
+        &repo_url,
+        &token,
+        "data.txt",
+        json!({"message": "Add data", "content": data}),
+    )
+    .await;
+
+    let mut tarballs = Vec::new();
+    for (format, content_type) in [
+        ("tar.gz", "application/gzip"),
+        ("tar.zst", "application/zstd"),
+    ] {
+        let resp = client
+            .get(format!("{repo_url}/archive/main?format={format}"))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .expect("get archive");
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["content-type"], content_type);
+        let disposition = resp.headers()["content-disposition"].to_str().expect("header");
+        assert!(disposition.contains(&format!(".{format}")), "{disposition}");
+        let compressed = resp.bytes().await.expect("read archive");
+
+        let mut tarball = Vec::new();
+        if format == "tar.gz" {
+            GzipDecoder::new(compressed.as_ref()).read_to_end(&mut tarball).await
+        } else {
+            ZstdDecoder::new(compressed.as_ref()).read_to_end(&mut tarball).await
+        }
+        .expect("decompress archive");
+        tarballs.push(tarball);
+    }
+
+    assert_eq!(tarballs[0], tarballs[1]);
+    let tarball = &tarballs[0];
+    assert!(tarball.len() > data.len());
+    assert_eq!(&tarball[257..262], b"ustar");
+    let contains = |needle: &[u8]| tarball.windows(needle.len()).any(|w| w == needle);
+    assert!(contains(b"data.txt"));
+    assert!(contains(data.as_bytes()));
+}
+
+#[tokio::test]
+async fn merge_preview_reports_conflicts_without_writing() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "merge-preview").await.token;
+    let repo_id = create_repo(&client, &server, &token, "branches").await;
+    let repo_url = format!("{}/api/v1/repos/{repo_id}", server.base_url);
+
+    for (branch, action, path, content) in [
+        ("topic", "create", "TOPIC.md", "topic\n"),
+        ("clash", "update", "README.md", "from clash\n"),
+    ] {
+        let resp = client
+            .post(format!("{repo_url}/commits"))
+            .bearer_auth(&token)
+            .json(&json!({
+                "message": format!("Work on {branch}"),
+                "branch": branch,
+                "base_ref": "main",
+                "actions": [{"action": action, "path": path, "content": content}],
+            }))
+            .send()
+            .await
+            .expect("commit on branch");
+        assert_eq!(resp.status(), StatusCode::CREATED, "{branch}");
+    }
+
+    let preview = |spec: &str| {
+        let url = format!("{repo_url}/merge-preview/{spec}");
+        let client = &client;
+        let token = &token;
+        async move {
+            let resp = client
+                .get(url)
+                .bearer_auth(token)
+                .send()
+                .await
+                .expect("merge preview");
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body: Value = resp.json().await.expect("parse merge preview");
+            body["data"].clone()
+        }
+    };
+
+    let topic = preview("main...topic").await;
+    assert_eq!(topic["mergeable"], true);
+    assert_eq!(topic["fast_forward"], true);
+    assert_eq!(topic["up_to_date"], false);
+    assert_eq!(topic["conflicts"], json!([]));
+
+    let readme: Value = client
+        .get(format!("{repo_url}/blob/main/README.md"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get README")
+        .json()
+        .await
+        .expect("parse README");
+    let main = put_file(
+        &client,
+        &repo_url,
+        &token,
+        "README.md",
+        json!({"message": "Edit README", "content": "from main\n", "sha": readme["data"]["sha"]}),
+    )
+    .await;
+
+    let clash = preview("main...clash").await;
+    assert_eq!(clash["base_sha"], main["commit_sha"]);
+    assert_eq!(clash["mergeable"], false);
+    assert_eq!(clash["fast_forward"], false);
+    assert_eq!(clash["conflicts"], json!(["README.md"]));
+
+    // Neither preview moved main.
+    let head: Value = client
+        .get(format!("{repo_url}/commits/main"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get main")
+        .json()
+        .await
+        .expect("parse commit");
+    assert_eq!(head["data"]["sha"], main["commit_sha"]);
+}
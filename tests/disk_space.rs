@@ -0,0 +1,84 @@
+mod common;
+
+
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::{TestPrincipal, assert_git, assert_git_fails, create_principal};
+
+fn remote_url(server: &TestServer, owner: &TestPrincipal, repo: &str) -> String {
+    let host = server.base_url.trim_start_matches("http://");
+    format!(
+        "http://x-token:{}@{host}/git/{}/{repo}.git",
+        owner.token, owner.namespace
+    )
+}
+
+#[tokio::test]
+async fn low_disk_space_rejects_writes_but_allows_reads() {
+    let server = TestServer::start_with_args(&["--min-free-bytes", &u64::MAX.to_string()]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "disk-owner").await;
+
+    let ready: Value = client
+        .get(format!("{}/readyz", server.base_url))
+        .send()
+        .await
+        .expect("get readyz")
+        .json()
+        .await
+        .expect("parse readyz");
+    assert_eq!(ready["status"], "ready");
+    assert_eq!(ready["checks"]["disk"]["level"], "critical");
+    assert_eq!(ready["checks"]["disk"]["writes_allowed"], false);
+
+    let resp = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"name": "existing"}))
+        .send()
+        .await
+        .expect("create repo");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path();
+    std::fs::write(source.join("README.md"), "# disk\n").expect("write file");
+    assert_git(source, &["init", "-q", "-b", "main"]);
+    assert_git(source, &["add", "README.md"]);
+    assert_git(source, &["commit", "-q", "-m", "Initial commit"]);
+
+    let url = remote_url(&server, &owner, "existing");
+    let stderr = assert_git_fails(source, &["push", &url, "main"]);
+    assert!(
+        stderr.contains("Server is low on disk space"),
+        "unexpected stderr: {stderr}"
+    );
+
+    // Reads are unaffected.
+    let resp = client
+        .get(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("list repos");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = client
+        .post(format!(
+            "{}/git/{}/existing.git/info/lfs/objects/batch",
+            server.base_url, owner.namespace
+        ))
+        .bearer_auth(&owner.token)
+        .header("Accept", "application/vnd.git-lfs+json")
+        .json(&serde_json::json!({
+            "operation": "upload",
+            "objects": [{"oid": "a".repeat(64), "size": 1}],
+        }))
+        .send()
+        .await
+        .expect("lfs batch");
+    assert_eq!(resp.status(), StatusCode::INSUFFICIENT_STORAGE);
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+
+use common::TestServer;
+use common::fixtures::create_principal;
+
+async fn start_with_features(features: &str) -> (TestServer, tempfile::TempDir) {
+    let config_dir = tempfile::TempDir::new().expect("temp dir");
+    let config_path = config_dir.path().join("server.toml");
+    std::fs::write(&config_path, format!("[features]\n{features}")).expect("write config");
+    let server = TestServer::start_with_args(&["--config", config_path.to_str().unwrap()]).await;
+    (server, config_dir)
+}
+
+#[tokio::test]
+async fn disabled_capability_groups_are_refused() {
+    let (server, _config_dir) =
+        start_with_features("content_mutations = false\narchive = false\nlfs = false\n").await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "features").await.token;
+
+    let resp: Value = client
+        .get(format!("{}/api/v1/capabilities", server.base_url))
+        .send()
+        .await
+        .expect("get capabilities")
+        .json()
+        .await
+        .expect("parse capabilities");
+    assert_eq!(
+        resp["data"]["features"],
+        json!({"content_mutations": false, "archive": false, "admin_api": true, "lfs": false})
+    );
+
+    // Creating repos is not a content write, so it still works.
+    let resp: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&token)
+        .json(&json!({"name": "frozen", "auto_init": {}}))
+        .send()
+        .await
+        .expect("create repo")
+        .json()
+        .await
+        .expect("parse repo response");
+    let repo_id = resp["data"]["id"].as_str().expect("repo id");
+    let repo_url = format!("{}/api/v1/repos/{repo_id}", server.base_url);
+
+    let resp = client
+        .get(format!("{repo_url}/tree/main"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get tree");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = client
+        .put(format!("{repo_url}/blob/main/notes.txt"))
+        .bearer_auth(&token)
+        .json(&json!({"message": "Add notes", "content": "hello"}))
+        .send()
+        .await
+        .expect("put blob");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = client
+        .get(format!("{repo_url}/archive/main"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get archive");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = client
+        .post(format!(
+            "{}/git/features/frozen.git/info/lfs/objects/batch",
+            server.base_url
+        ))
+        .bearer_auth(&token)
+        .header("Accept", "application/vnd.git-lfs+json")
+        .json(&json!({
+            "operation": "upload",
+            "objects": [{"oid": "a".repeat(64), "size": 1}],
+        }))
+        .send()
+        .await
+        .expect("lfs batch");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn disabled_admin_api_is_refused() {
+    let (server, _config_dir) = start_with_features("admin_api = false\n").await;
+    let client = Client::new();
+
+    let resp = client
+        .get(format!("{}/api/v1/admin/principals", server.base_url))
+        .bearer_auth(&server.admin_token)
+        .send()
+        .await
+        .expect("list principals");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
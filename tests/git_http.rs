@@ -0,0 +1,628 @@
+mod common;
+
+use std::process::Command;
+
+use rand::RngCore;
+use reqwest::Client;
+use serde_json::{Value, json};
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::{assert_git, create_principal, git};
+
+/// Larger than axum's default 2 MiB body limit, so the pack must be streamed.
+const LARGE_FILE_BYTES: usize = 8 * 1024 * 1024;
+
+#[tokio::test]
+async fn large_push_and_clone_stream_over_http() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "stream-owner").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+
+    // Random bytes do not compress, so the pack is at least this large.
+    let mut contents = vec![0u8; LARGE_FILE_BYTES];
+    rand::thread_rng().fill_bytes(&mut contents);
+    std::fs::write(source.join("large.bin"), &contents).expect("write large file");
+
+    assert_git(&source, &["init", "-q", "-b", "main"]);
+    assert_git(&source, &["add", "large.bin"]);
+    assert_git(&source, &["commit", "-q", "-m", "Add large file"]);
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/large.git",
+        owner.token, owner.namespace
+    );
+    assert_git(&source, &["push", "-q", &url, "main"]);
+
+    // The push is recorded before the receive-pack response completes.
+    let repos: Value = client
+        .get(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("list repos")
+        .json()
+        .await
+        .expect("parse repos");
+    let repo = &repos["data"][0];
+    assert_eq!(repo["name"], "large");
+    assert!(repo["last_push_at"].is_string());
+    assert!(repo["size_bytes"].as_i64().expect("size") >= LARGE_FILE_BYTES as i64);
+
+    assert_git(work.path(), &["clone", "-q", &url, "clone"]);
+    let cloned = std::fs::read(work.path().join("clone/large.bin")).expect("read clone");
+    assert!(cloned == contents, "cloned file differs from pushed file");
+}
+
+#[tokio::test]
+async fn dumb_http_clone_reads_packs_and_loose_objects() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "dumb-owner").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    assert_git(&source, &["init", "-q", "-b", "main"]);
+
+    // Over git's unpack limit, so the first push is stored as a pack.
+    for i in 0..120 {
+        std::fs::write(source.join(format!("file-{i}.txt")), format!("{i}\n")).expect("write file");
+    }
+    assert_git(&source, &["add", "."]);
+    assert_git(&source, &["commit", "-q", "-m", "Many files"]);
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/dumb.git",
+        owner.token, owner.namespace
+    );
+    assert_git(&source, &["push", "-q", &url, "main"]);
+
+    // A small follow-up push is stored as loose objects.
+    std::fs::write(source.join("file-0.txt"), "changed\n").expect("write file");
+    assert_git(&source, &["commit", "-q", "-am", "Change one file"]);
+    assert_git(&source, &["tag", "-a", "v1", "-m", "Release"]);
+    assert_git(&source, &["push", "-q", &url, "main", "v1"]);
+
+    let anonymous = client
+        .get(format!(
+            "{}/git/{}/dumb.git/info/refs",
+            server.base_url, owner.namespace
+        ))
+        .send()
+        .await
+        .expect("fetch info/refs");
+    assert_eq!(anonymous.status(), 401);
+
+    let output = Command::new("git")
+        .args(["clone", "-q", &url, "clone"])
+        .current_dir(work.path())
+        .env("GIT_SMART_HTTP", "0")
+        .output()
+        .expect("run git");
+    assert!(
+        output.status.success(),
+        "dumb clone failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let clone = work.path().join("clone");
+    assert_eq!(
+        std::fs::read_to_string(clone.join("file-0.txt")).expect("read clone"),
+        "changed\n"
+    );
+    assert!(clone.join("file-119.txt").exists());
+    let tag = git(&clone, &["rev-parse", "v1^{commit}"]);
+    let head = git(&clone, &["rev-parse", "HEAD"]);
+    assert_eq!(tag.stdout, head.stdout);
+}
+
+#[tokio::test]
+async fn bundle_endpoint_streams_full_and_incremental_bundles() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "bundle-owner").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    assert_git(&source, &["init", "-q", "-b", "main"]);
+    std::fs::write(source.join("README.md"), "# bundled\n").expect("write file");
+    assert_git(&source, &["add", "README.md"]);
+    assert_git(&source, &["commit", "-q", "-m", "Initial commit"]);
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/bundled.git",
+        owner.token, owner.namespace
+    );
+    assert_git(&source, &["push", "-q", &url, "main"]);
+    let base = String::from_utf8_lossy(&git(&source, &["rev-parse", "HEAD"]).stdout)
+        .trim()
+        .to_string();
+
+    std::fs::write(source.join("README.md"), "# bundled\n\nMore.\n").expect("write file");
+    assert_git(&source, &["commit", "-q", "-am", "Second commit"]);
+    assert_git(&source, &["push", "-q", &url, "main"]);
+
+    let repos: Value = client
+        .get(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("list repos")
+        .json()
+        .await
+        .expect("parse repos");
+    let repo_id = repos["data"][0]["id"].as_str().expect("repo id");
+    let bundle_url = format!("{}/api/v1/repos/{repo_id}/bundle", server.base_url);
+
+    let resp = client
+        .get(&bundle_url)
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("download bundle");
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "application/x-git-bundle");
+    let full = work.path().join("full.bundle");
+    std::fs::write(&full, resp.bytes().await.expect("read bundle")).expect("write bundle");
+
+    let full_path = full.to_str().expect("bundle path");
+    assert_git(work.path(), &["clone", "-q", full_path, "clone"]);
+    let clone = work.path().join("clone");
+    assert_eq!(
+        std::fs::read_to_string(clone.join("README.md")).expect("read clone"),
+        "# bundled\n\nMore.\n"
+    );
+
+    // An incremental bundle applies on top of a repository that has `since`.
+    let resp = client
+        .get(&bundle_url)
+        .query(&[("ref", "main"), ("since", base.as_str())])
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("download incremental bundle");
+    assert_eq!(resp.status(), 200);
+    let incremental = work.path().join("incremental.bundle");
+    std::fs::write(&incremental, resp.bytes().await.expect("read bundle")).expect("write bundle");
+
+    let partial = work.path().join("partial");
+    assert_git(work.path(), &["init", "-q", "--bare", "partial"]);
+    assert_git(
+        &partial,
+        &["fetch", "-q", full_path, &format!("{base}:refs/heads/main")],
+    );
+    let verify = git(
+        &partial,
+        &["bundle", "verify", "-q", incremental.to_str().unwrap()],
+    );
+    assert!(
+        verify.status.success(),
+        "incremental bundle does not apply: {}",
+        String::from_utf8_lossy(&verify.stderr)
+    );
+
+    let resp = client
+        .get(&bundle_url)
+        .query(&[("since", "main")])
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("request empty bundle");
+    assert_eq!(resp.status(), 400);
+
+    let resp = client
+        .get(&bundle_url)
+        .send()
+        .await
+        .expect("anonymous bundle");
+    assert_eq!(resp.status(), 401);
+}
+
+#[tokio::test]
+async fn tree_api_links_submodules_from_gitmodules() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "submodule-owner").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    assert_git(&source, &["init", "-q", "-b", "main"]);
+    std::fs::write(
+        source.join(".gitmodules"),
+        "[submodule \"lib\"]\n\tpath = vendor/lib\n\turl = ../lib.git\n\
+         [submodule \"theme\"]\n\tpath = theme\n\turl = https://example.com/theme.git\n",
+    )
+    .expect("write .gitmodules");
+    assert_git(&source, &["add", ".gitmodules"]);
+    // Gitlinks only need a commit id; the submodule itself isn't fetched.
+    let target = "0123456789abcdef0123456789abcdef01234567";
+    for path in ["vendor/lib", "theme", "unlisted"] {
+        assert_git(
+            &source,
+            &[
+                "update-index",
+                "--add",
+                "--cacheinfo",
+                &format!("160000,{target},{path}"),
+            ],
+        );
+    }
+    assert_git(&source, &["commit", "-q", "-m", "Add submodules"]);
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/app.git",
+        owner.token, owner.namespace
+    );
+    assert_git(&source, &["push", "-q", &url, "main"]);
+
+    let repos: Value = client
+        .get(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("list repos")
+        .json()
+        .await
+        .expect("parse repos");
+    let repo_id = repos["data"][0]["id"].as_str().expect("repo id");
+
+    let tree: Value = client
+        .get(format!(
+            "{}/api/v1/repos/{repo_id}/tree/main",
+            server.base_url
+        ))
+        .query(&[("depth", "2")])
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("get tree")
+        .json()
+        .await
+        .expect("parse tree");
+    let entries = tree["data"].as_array().expect("entries");
+    let entry = |name: &str| {
+        entries
+            .iter()
+            .find(|e| e["name"] == name)
+            .unwrap_or_else(|| panic!("no entry {name}"))
+    };
+
+    let lib = &entry("vendor")["children"][0];
+    assert_eq!(lib["type"], "submodule");
+    assert_eq!(lib["sha"], target);
+    assert_eq!(lib["submodule"]["name"], "lib");
+    assert_eq!(
+        lib["submodule"]["url"],
+        format!("{}/git/submodule-owner/lib.git", server.base_url)
+    );
+    assert_eq!(lib["submodule"]["repo_path"], "submodule-owner/lib");
+
+    let theme = &entry("theme")["submodule"];
+    assert_eq!(theme["url"], "https://example.com/theme.git");
+    assert!(theme.get("repo_path").is_none());
+
+    assert!(entry("unlisted").get("submodule").is_none());
+}
+
+#[tokio::test]
+async fn commits_signed_with_registered_ssh_keys_are_verified() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "signer").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let key = work.path().join("signing_key");
+    let keygen = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-C", "laptop", "-f"])
+        .arg(&key)
+        .output()
+        .expect("run ssh-keygen");
+    assert!(keygen.status.success(), "ssh-keygen failed");
+
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    assert_git(&source, &["init", "-q", "-b", "main"]);
+    assert_git(
+        &source,
+        &["commit", "-q", "--allow-empty", "-m", "Unsigned"],
+    );
+    let signing_key = format!("user.signingkey={}", key.display());
+    assert_git(
+        &source,
+        &[
+            "-c",
+            "gpg.format=ssh",
+            "-c",
+            &signing_key,
+            "commit",
+            "-q",
+            "-S",
+            "--allow-empty",
+            "-m",
+            "Signed",
+        ],
+    );
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/app.git",
+        owner.token, owner.namespace
+    );
+    assert_git(&source, &["push", "-q", &url, "main"]);
+
+    let repos: Value = client
+        .get(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("list repos")
+        .json()
+        .await
+        .expect("parse repos");
+    let repo_id = repos["data"][0]["id"].as_str().expect("repo id");
+    let commits_url = format!("{}/api/v1/repos/{repo_id}/commits", server.base_url);
+
+    let commits: Value = client
+        .get(&commits_url)
+        .query(&[("ref", "main")])
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("list commits")
+        .json()
+        .await
+        .expect("parse commits");
+    let signed = &commits["data"][0]["verification"];
+    assert_eq!(signed["verified"], false);
+    assert_eq!(signed["reason"], "unknown_key");
+    let unsigned = &commits["data"][1]["verification"];
+    assert_eq!(unsigned["reason"], "unsigned");
+
+    let public_key = std::fs::read_to_string(key.with_extension("pub")).expect("read public key");
+    let resp = client
+        .post(format!("{}/api/v1/user/signing-keys", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&json!({"public_key": public_key}))
+        .send()
+        .await
+        .expect("register signing key");
+    assert_eq!(resp.status(), 201);
+    let registered: Value = resp.json().await.expect("parse signing key");
+    assert_eq!(registered["data"]["kind"], "ssh");
+    assert_eq!(registered["data"]["name"], "laptop");
+
+    let commit: Value = client
+        .get(format!("{commits_url}/main"))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("get commit")
+        .json()
+        .await
+        .expect("parse commit");
+    let verification = &commit["data"]["verification"];
+    assert_eq!(verification["verified"], true);
+    assert_eq!(verification["reason"], "valid");
+    assert_eq!(verification["signer"]["namespace"], "signer");
+    assert_eq!(verification["signer"]["key_id"], registered["data"]["id"]);
+    assert_eq!(verification["signer"]["key_name"], "laptop");
+}
+
+#[tokio::test]
+async fn pushing_a_new_branch_prints_a_pull_request_link() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "hint-owner").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    assert_git(&source, &["init", "-q", "-b", "main"]);
+    std::fs::write(source.join("README.md"), "# hints\n").expect("write file");
+    assert_git(&source, &["add", "README.md"]);
+    assert_git(&source, &["commit", "-q", "-m", "Initial commit"]);
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/hints.git",
+        owner.token, owner.namespace
+    );
+    let output = git(&source, &["push", &url, "main"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "push failed: {stderr}");
+    assert!(
+        !stderr.contains("Open a pull request"),
+        "default branch got a hint: {stderr}"
+    );
+
+    assert_git(&source, &["checkout", "-q", "-b", "feature/login"]);
+    std::fs::write(source.join("login.txt"), "login\n").expect("write file");
+    assert_git(&source, &["add", "login.txt"]);
+    assert_git(&source, &["commit", "-q", "-m", "Add login"]);
+    let output = git(&source, &["push", &url, "feature/login"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "push failed: {stderr}");
+    assert!(
+        stderr.contains("remote: cutman: Open a pull request for 'feature/login':"),
+        "missing hint: {stderr}"
+    );
+    let link = stderr
+        .lines()
+        .find_map(|line| {
+            line.trim_start_matches("remote: cutman:")
+                .trim()
+                .strip_prefix(&server.base_url)
+        })
+        .expect("hint link")
+        .to_string();
+    assert!(link.ends_with("/compare/main...feature%2Flogin"), "{link}");
+
+    let compare: Value = client
+        .get(format!("{}{link}", server.base_url))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("open compare link")
+        .json()
+        .await
+        .expect("parse compare");
+    assert_eq!(compare["data"]["ahead_by"], 1, "{compare}");
+
+    // Updating the branch again is not a new branch.
+    std::fs::write(source.join("login.txt"), "login v2\n").expect("write file");
+    assert_git(&source, &["commit", "-q", "-am", "Update login"]);
+    let output = git(&source, &["push", &url, "feature/login"]);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Open a pull request"));
+}
+
+#[tokio::test]
+async fn sha256_repo_accepts_push_and_clone() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "sha256-owner").await;
+
+    let created: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&json!({ "name": "modern", "object_format": "sha256" }))
+        .send()
+        .await
+        .expect("create repo")
+        .json()
+        .await
+        .expect("parse repo");
+    assert_eq!(created["data"]["object_format"], "sha256", "{created}");
+    let repo_id = created["data"]["id"].as_str().expect("repo id");
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    assert_git(
+        &source,
+        &["init", "-q", "-b", "main", "--object-format=sha256"],
+    );
+    std::fs::write(source.join("README.md"), "# modern\n").expect("write file");
+    assert_git(&source, &["add", "README.md"]);
+    assert_git(&source, &["commit", "-q", "-m", "Initial commit"]);
+    let head = String::from_utf8_lossy(&git(&source, &["rev-parse", "HEAD"]).stdout)
+        .trim()
+        .to_string();
+    assert_eq!(head.len(), 64);
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/modern.git",
+        owner.token, owner.namespace
+    );
+    assert_git(&source, &["push", "-q", &url, "main"]);
+
+    let clone = work.path().join("clone");
+    let clone_path = clone.to_str().expect("clone path");
+    assert_git(work.path(), &["clone", "-q", &url, clone_path]);
+    let format = git(&clone, &["rev-parse", "--show-object-format"]);
+    assert_eq!(String::from_utf8_lossy(&format.stdout).trim(), "sha256");
+    let cloned = git(&clone, &["rev-parse", "HEAD"]);
+    assert_eq!(String::from_utf8_lossy(&cloned.stdout).trim(), head);
+
+    // libgit2 can't read SHA-256 objects, so the content API says so
+    // instead of claiming the repository is missing.
+    let resp = client
+        .get(format!(
+            "{}/api/v1/repos/{repo_id}/commits/{head}",
+            server.base_url
+        ))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("get commit");
+    assert_eq!(resp.status(), 501);
+}
+
+#[tokio::test]
+async fn server_banner_is_shown_on_clone_push_and_capabilities() {
+    let config_dir = TempDir::new().expect("temp dir");
+    let config_path = config_dir.path().join("server.toml");
+    std::fs::write(
+        &config_path,
+        "banner = \"\"\"\nMaintenance tonight at 22:00 UTC\nSee https://status.example.com\n\"\"\"\n",
+    )
+    .expect("write config");
+    let server =
+        TestServer::start_with_args(&["--config", config_path.to_str().expect("utf-8 path")]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "banner-owner").await;
+
+    let capabilities: Value = client
+        .get(format!("{}/api/v1/capabilities", server.base_url))
+        .send()
+        .await
+        .expect("get capabilities")
+        .json()
+        .await
+        .expect("parse capabilities");
+    assert_eq!(
+        capabilities["data"]["banner"],
+        "Maintenance tonight at 22:00 UTC\nSee https://status.example.com\n"
+    );
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    assert_git(&source, &["init", "-q", "-b", "main"]);
+    std::fs::write(source.join("README.md"), "# banner\n").expect("write file");
+    assert_git(&source, &["add", "README.md"]);
+    assert_git(&source, &["commit", "-q", "-m", "Initial commit"]);
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/banner.git",
+        owner.token, owner.namespace
+    );
+    let output = git(&source, &["push", &url, "main"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "push failed: {stderr}");
+    assert!(
+        stderr.contains("remote: cutman: Maintenance tonight at 22:00 UTC"),
+        "missing banner: {stderr}"
+    );
+
+    for protocol in ["version=2", "version=0"] {
+        let clone = work.path().join(format!("clone-{protocol}"));
+        let output = git(
+            work.path(),
+            &[
+                "-c",
+                &format!("protocol.{protocol}"),
+                "clone",
+                &url,
+                clone.to_str().expect("utf-8 path"),
+            ],
+        );
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "clone failed: {stderr}");
+        assert!(
+            stderr.contains("remote: cutman: See https://status.example.com"),
+            "missing banner with {protocol}: {stderr}"
+        );
+        assert_eq!(
+            std::fs::read_to_string(clone.join("README.md")).expect("read clone"),
+            "# banner\n"
+        );
+
+        // A fetch with nothing new sends no pack, and no banner.
+        let output = git(&clone, &["fetch", "origin"]);
+        assert!(output.status.success());
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("Maintenance"));
+    }
+}
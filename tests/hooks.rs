@@ -0,0 +1,154 @@
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use reqwest::Client;
+use serde_json::Value;
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::{TestPrincipal, assert_git, assert_git_fails, create_principal};
+
+fn install_script(server: &TestServer, hook: &str, name: &str, body: &str) {
+    let dir = server.data_dir().join("hooks").join(format!("{hook}.d"));
+    let path = dir.join(name);
+    std::fs::write(&path, format!("#!/bin/sh\n{body}")).expect("write hook script");
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+        .expect("make hook executable");
+}
+
+fn init_work_tree(dir: &Path) {
+    std::fs::create_dir(dir).expect("create work tree");
+    std::fs::write(dir.join("README.md"), "# hooked\n").expect("write file");
+    assert_git(dir, &["init", "-q", "-b", "main"]);
+    assert_git(dir, &["add", "README.md"]);
+    assert_git(dir, &["commit", "-q", "-m", "Initial commit"]);
+}
+
+fn remote_url(server: &TestServer, owner: &TestPrincipal, repo: &str) -> String {
+    let host = server.base_url.trim_start_matches("http://");
+    format!(
+        "http://x-token:{}@{host}/git/{}/{repo}.git",
+        owner.token, owner.namespace
+    )
+}
+
+#[tokio::test]
+async fn hook_scripts_see_ref_updates_and_can_reject_pushes() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "hook-owner").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let log = work.path().join("log");
+    std::fs::create_dir(&log).expect("create log dir");
+    let log = log.display();
+
+    install_script(
+        &server,
+        "pre-receive",
+        "10-policy",
+        &format!(
+            "env | grep -E '^(CUTMAN_|GIT_PUSH_OPTION)' > '{log}/env'\n\
+             cat > '{log}/pre'\n\
+             if grep -q ' refs/heads/blocked$' '{log}/pre'; then\n\
+             echo 'blocked branches are not allowed'\n\
+             exit 1\n\
+             fi\n"
+        ),
+    );
+    install_script(
+        &server,
+        "post-receive",
+        "10-record",
+        &format!("cat >> '{log}/post'\n"),
+    );
+
+    let source = work.path().join("source");
+    init_work_tree(&source);
+    let head = assert_git(&source, &["rev-parse", "HEAD"]);
+    let url = remote_url(&server, &owner, "hooked");
+
+    assert_git(
+        &source,
+        &[
+            "push",
+            "-q",
+            "-o",
+            "skip-ci",
+            "-o",
+            "reviewer=alice",
+            &url,
+            "main",
+        ],
+    );
+
+    let repos: Value = client
+        .get(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("list repos")
+        .json()
+        .await
+        .expect("parse repos");
+    let repo_id = repos["data"][0]["id"].as_str().expect("repo id");
+
+    let env = std::fs::read_to_string(format!("{log}/env")).expect("read hook env");
+    assert!(env.contains(&format!("CUTMAN_PRINCIPAL_ID={}\n", owner.id)));
+    assert!(env.contains(&format!("CUTMAN_REPO_ID={repo_id}\n")));
+    assert!(env.contains("CUTMAN_NAMESPACE=hook-owner\n"));
+    assert!(env.contains("CUTMAN_REPO_NAME=hooked\n"));
+    assert!(env.contains("GIT_PUSH_OPTION_COUNT=2\n"));
+    assert!(env.contains("GIT_PUSH_OPTION_0=skip-ci\n"));
+    assert!(env.contains("GIT_PUSH_OPTION_1=reviewer=alice\n"));
+
+    let expected = format!("{} {head} refs/heads/main\n", "0".repeat(40));
+    let pre = std::fs::read_to_string(format!("{log}/pre")).expect("read pre-receive input");
+    assert_eq!(pre, expected);
+    let post = std::fs::read_to_string(format!("{log}/post")).expect("read post-receive input");
+    assert_eq!(post, expected);
+
+    let stderr = assert_git_fails(&source, &["push", &url, "main:blocked"]);
+    assert!(
+        stderr.contains("remote: blocked branches are not allowed"),
+        "unexpected stderr: {stderr}"
+    );
+    let refs = assert_git(&source, &["ls-remote", &url]);
+    assert!(!refs.contains("refs/heads/blocked"), "rejected ref exists");
+
+    let post = std::fs::read_to_string(format!("{log}/post")).expect("read post-receive input");
+    assert_eq!(post, expected, "post-receive ran for a rejected push");
+}
+
+#[tokio::test]
+async fn builtin_hook_protects_default_branch() {
+    let server = TestServer::start_with_args(&["--builtin-hook", "protect-default-branch"]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "protected-owner").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path().join("source");
+    init_work_tree(&source);
+    let url = remote_url(&server, &owner, "protected");
+
+    assert_git(&source, &["push", "-q", &url, "main", "main:topic"]);
+
+    assert_git(&source, &["commit", "-q", "--amend", "-m", "Rewritten"]);
+    let stderr = assert_git_fails(&source, &["push", "--force", &url, "main"]);
+    assert!(
+        stderr.contains("cannot force-push the default branch refs/heads/main"),
+        "unexpected stderr: {stderr}"
+    );
+
+    let stderr = assert_git_fails(&source, &["push", &url, ":main"]);
+    assert!(
+        stderr.contains("cannot delete the default branch refs/heads/main"),
+        "unexpected stderr: {stderr}"
+    );
+
+    // Other branches are unaffected.
+    assert_git(&source, &["push", "-q", "--force", &url, "main:topic"]);
+    assert_git(&source, &["push", "-q", &url, ":topic"]);
+}
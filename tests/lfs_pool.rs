@@ -0,0 +1,387 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::create_principal;
+
+async fn create_repo(client: &Client, server: &TestServer, token: &str, name: &str) -> String {
+    let resp: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(token)
+        .json(&serde_json::json!({"name": name}))
+        .send()
+        .await
+        .expect("create repo")
+        .json()
+        .await
+        .expect("parse repo response");
+    resp["data"]["id"].as_str().expect("repo id").to_string()
+}
+
+async fn batch(client: &Client, lfs_url: &str, token: &str, operation: &str, oid: &str) -> Value {
+    client
+        .post(format!("{lfs_url}/objects/batch"))
+        .bearer_auth(token)
+        .header("Accept", "application/vnd.git-lfs+json")
+        .json(&serde_json::json!({
+            "operation": operation,
+            "objects": [{"oid": oid, "size": 20}],
+        }))
+        .send()
+        .await
+        .expect("lfs batch")
+        .json()
+        .await
+        .expect("parse batch response")
+}
+
+#[tokio::test]
+async fn lfs_objects_are_shared_between_repos() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "lfs-pool").await.token;
+    let original_id = create_repo(&client, &server, &token, "assets").await;
+    create_repo(&client, &server, &token, "assets-fork").await;
+    let original = format!("{}/git/lfs-pool/assets.git/info/lfs", server.base_url);
+    let fork = format!("{}/git/lfs-pool/assets-fork.git/info/lfs", server.base_url);
+
+    let content = b"a large binary asset".to_vec();
+    let oid = hex::encode(Sha256::digest(&content));
+    let resp = client
+        .put(format!("{original}/objects/{oid}"))
+        .bearer_auth(&token)
+        .body(content.clone())
+        .send()
+        .await
+        .expect("upload object");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // The fork can't reach the object without uploading it itself.
+    let resp = batch(&client, &fork, &token, "download", &oid).await;
+    assert_eq!(resp["objects"][0]["error"]["code"], 404);
+    let resp = client
+        .get(format!("{fork}/objects/{oid}"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("download object");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = client
+        .post(format!("{fork}/verify"))
+        .bearer_auth(&token)
+        .header("Accept", "application/vnd.git-lfs+json")
+        .json(&serde_json::json!({"oid": oid, "size": content.len()}))
+        .send()
+        .await
+        .expect("verify object");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = batch(&client, &fork, &token, "upload", &oid).await;
+    assert!(
+        resp["objects"][0]["actions"]["upload"].is_object(),
+        "unexpected batch response: {resp}"
+    );
+
+    let resp = client
+        .put(format!("{fork}/objects/{oid}"))
+        .bearer_auth(&token)
+        .body(content.clone())
+        .send()
+        .await
+        .expect("upload object to fork");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Both repos' uploads are the one pooled file.
+    let pooled = server
+        .data_dir()
+        .join("lfs/objects")
+        .join(&oid[0..2])
+        .join(&oid[2..4])
+        .join(&oid);
+    assert!(pooled.exists());
+    assert!(
+        !server
+            .data_dir()
+            .join("lfs")
+            .join(&original_id)
+            .join("objects")
+            .exists()
+    );
+
+    // The object counts towards both repos, but deleting either frees
+    // nothing.
+    let usage: Value = client
+        .get(format!(
+            "{}/api/v1/repos/{original_id}/lfs/usage",
+            server.base_url
+        ))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get LFS usage")
+        .json()
+        .await
+        .expect("parse usage");
+    assert_eq!(usage["data"]["object_count"], 1);
+    assert_eq!(usage["data"]["total_bytes"], content.len());
+    assert_eq!(usage["data"]["unshared_bytes"], 0);
+    assert_eq!(usage["data"]["largest"][0]["oid"], oid.as_str());
+
+    // Deleting the original leaves the fork's reference in place.
+    let resp = client
+        .delete(format!("{}/api/v1/repos/{original_id}", server.base_url))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("delete repo");
+    assert!(resp.status().is_success());
+
+    let resp = client
+        .get(format!("{fork}/objects/{oid}"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("download object from fork");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.bytes().await.expect("read object").to_vec(), content);
+}
+
+#[tokio::test]
+async fn lfs_usage_reports_a_repos_objects_largest_first() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "lfs-usage").await;
+    let outsider = create_principal(&client, &server, "lfs-usage-outsider").await;
+    let repo_id = create_repo(&client, &server, &owner.token, "assets").await;
+    let lfs = format!("{}/git/lfs-usage/assets.git/info/lfs", server.base_url);
+    let usage_url = format!("{}/api/v1/repos/{repo_id}/lfs/usage", server.base_url);
+
+    let small = b"a small asset".to_vec();
+    let large = b"a considerably larger binary asset".to_vec();
+    for content in [&small, &large] {
+        let oid = hex::encode(Sha256::digest(content));
+        let resp = client
+            .put(format!("{lfs}/objects/{oid}"))
+            .bearer_auth(&owner.token)
+            .body(content.clone())
+            .send()
+            .await
+            .expect("upload object");
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let usage: Value = client
+        .get(format!("{usage_url}?limit=1"))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("get LFS usage")
+        .json()
+        .await
+        .expect("parse usage");
+    assert_eq!(usage["data"]["repo_id"], repo_id.as_str());
+    assert_eq!(usage["data"]["object_count"], 2);
+    assert_eq!(usage["data"]["total_bytes"], small.len() + large.len());
+    assert_eq!(usage["data"]["unshared_bytes"], small.len() + large.len());
+    let largest = usage["data"]["largest"].as_array().expect("largest objects");
+    assert_eq!(largest.len(), 1);
+    assert_eq!(largest[0]["oid"], hex::encode(Sha256::digest(&large)));
+    assert_eq!(largest[0]["size"], large.len());
+
+    let resp = client
+        .get(&usage_url)
+        .bearer_auth(&outsider.token)
+        .send()
+        .await
+        .expect("get LFS usage as outsider");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn corrupted_lfs_objects_are_reported_and_replaced() {
+    let config_dir = TempDir::new().expect("temp dir");
+    let config_path = config_dir.path().join("server.toml");
+    std::fs::write(&config_path, "lfs_verify_interval = 1\n").expect("write config");
+    let server =
+        TestServer::start_with_args(&["--config", config_path.to_str().expect("utf-8 path")]).await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "lfs-integrity")
+        .await
+        .token;
+    let repo_id = create_repo(&client, &server, &token, "assets").await;
+    let lfs = format!("{}/git/lfs-integrity/assets.git/info/lfs", server.base_url);
+
+    let content = b"a large binary asset".to_vec();
+    let oid = hex::encode(Sha256::digest(&content));
+    let resp = client
+        .put(format!("{lfs}/objects/{oid}"))
+        .bearer_auth(&token)
+        .body(content.clone())
+        .send()
+        .await
+        .expect("upload object");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let pooled = server
+        .data_dir()
+        .join("lfs/objects")
+        .join(&oid[0..2])
+        .join(&oid[2..4])
+        .join(&oid);
+    std::fs::write(&pooled, b"a large binary assey").expect("corrupt object");
+
+    let corrupted_url = format!("{}/api/v1/admin/lfs/corrupted", server.base_url);
+    let mut corrupted = Value::Null;
+    for _ in 0..50 {
+        let resp: Value = client
+            .get(&corrupted_url)
+            .bearer_auth(&server.admin_token)
+            .send()
+            .await
+            .expect("list corrupted objects")
+            .json()
+            .await
+            .expect("parse corrupted objects");
+        if resp["data"].as_array().is_some_and(|a| !a.is_empty()) {
+            corrupted = resp["data"].clone();
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+    assert_eq!(corrupted[0]["oid"], oid, "not reported: {corrupted}");
+    assert_eq!(corrupted[0]["repo_ids"][0], repo_id);
+
+    // The damaged copy isn't served, and the client is asked for it again.
+    let resp = batch(&client, &lfs, &token, "download", &oid).await;
+    assert_eq!(resp["objects"][0]["error"]["code"], 404);
+    let resp = batch(&client, &lfs, &token, "upload", &oid).await;
+    assert!(
+        resp["objects"][0]["actions"]["upload"].is_object(),
+        "unexpected batch response: {resp}"
+    );
+    let resp = client
+        .put(format!("{lfs}/objects/{oid}"))
+        .bearer_auth(&token)
+        .body(content.clone())
+        .send()
+        .await
+        .expect("upload object again");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp: Value = client
+        .get(&corrupted_url)
+        .bearer_auth(&server.admin_token)
+        .send()
+        .await
+        .expect("list corrupted objects")
+        .json()
+        .await
+        .expect("parse corrupted objects");
+    assert_eq!(resp["data"], serde_json::json!([]));
+    assert_eq!(std::fs::read(&pooled).expect("read object"), content);
+}
+
+#[tokio::test]
+async fn blob_api_resolves_lfs_pointers() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "lfs-blob").await.token;
+    let resp: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"name": "assets", "auto_init": {}}))
+        .send()
+        .await
+        .expect("create repo")
+        .json()
+        .await
+        .expect("parse repo response");
+    let repo_id = resp["data"]["id"].as_str().expect("repo id");
+    let lfs = format!("{}/git/lfs-blob/assets.git/info/lfs", server.base_url);
+    let blob_url = format!("{}/api/v1/repos/{repo_id}/blob/main", server.base_url);
+
+    let content = b"a large binary asset".to_vec();
+    let oid = hex::encode(Sha256::digest(&content));
+    let resp = client
+        .put(format!("{lfs}/objects/{oid}"))
+        .bearer_auth(&token)
+        .body(content.clone())
+        .send()
+        .await
+        .expect("upload object");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let missing = "0".repeat(64);
+    for (path, oid) in [("asset.bin", oid.as_str()), ("missing.bin", missing.as_str())] {
+        let pointer = format!(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:{oid}\nsize {}\n",
+            content.len()
+        );
+        let resp = client
+            .put(format!("{blob_url}/{path}"))
+            .bearer_auth(&token)
+            .json(&serde_json::json!({"message": format!("Add {path}"), "content": pointer}))
+            .send()
+            .await
+            .expect("commit pointer");
+        assert!(resp.status().is_success(), "commit pointer: {}", resp.status());
+    }
+
+    // Without the flag the pointer file is served as it is.
+    let resp = client
+        .get(format!("{blob_url}/asset.bin?raw=true"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get raw pointer");
+    assert!(resp.text().await.expect("read pointer").starts_with("version "));
+
+    let resp = client
+        .get(format!("{blob_url}/asset.bin?raw=true&resolve_lfs=true"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get resolved object");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers()["content-type"],
+        "application/octet-stream",
+        "objects aren't sniffed"
+    );
+    assert_eq!(resp.bytes().await.expect("read object").to_vec(), content);
+
+    let resp: Value = client
+        .get(format!("{blob_url}/asset.bin?resolve_lfs=true"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get pointer metadata")
+        .json()
+        .await
+        .expect("parse blob response");
+    assert_eq!(resp["data"]["lfs"]["oid"], oid.as_str());
+    assert_eq!(resp["data"]["lfs"]["size"], content.len());
+    assert_eq!(resp["data"]["lfs"]["stored"], true);
+
+    // A pointer to an object the repository never received.
+    let resp: Value = client
+        .get(format!("{blob_url}/missing.bin?resolve_lfs=true"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get missing pointer metadata")
+        .json()
+        .await
+        .expect("parse blob response");
+    assert_eq!(resp["data"]["lfs"]["stored"], false);
+    let resp = client
+        .get(format!("{blob_url}/missing.bin?raw=true&resolve_lfs=true"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("get missing object");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,296 @@
+mod common;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, Method, StatusCode as MockStatus, Uri};
+use axum::response::{IntoResponse, Response};
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use common::TestServer;
+use common::fixtures::create_principal;
+
+type Bucket = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+/// Just enough of S3 for object GET, HEAD, PUT, and DELETE, refusing
+/// unsigned requests and uploads that don't match their signed hash.
+/// Requests are signed in headers or, for presigned URLs, the query string.
+async fn mock_s3(
+    State(bucket): State<Bucket>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let signed_in_headers = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("AWS4-HMAC-SHA256 Credential=test-key/"))
+        && headers.contains_key("x-amz-date");
+    let query = uri.query().unwrap_or_default();
+    let presigned = query.contains("X-Amz-Credential=test-key%2F")
+        && query.contains("X-Amz-Signature=");
+    if !signed_in_headers && !presigned {
+        return MockStatus::FORBIDDEN.into_response();
+    }
+    let key = uri.path().to_string();
+    let mut bucket = bucket.lock().unwrap();
+    match method {
+        Method::PUT => {
+            let hash = hex::encode(Sha256::digest(&body));
+            if headers["x-amz-content-sha256"] != hash.as_str() {
+                return MockStatus::BAD_REQUEST.into_response();
+            }
+            bucket.insert(key, body.to_vec());
+            MockStatus::OK.into_response()
+        }
+        Method::GET | Method::HEAD => match bucket.get(&key) {
+            Some(data) if method == Method::GET => data.clone().into_response(),
+            Some(data) => ([("content-length", data.len().to_string())], ()).into_response(),
+            None => MockStatus::NOT_FOUND.into_response(),
+        },
+        Method::DELETE => {
+            bucket.remove(&key);
+            MockStatus::NO_CONTENT.into_response()
+        }
+        _ => MockStatus::METHOD_NOT_ALLOWED.into_response(),
+    }
+}
+
+async fn start_mock_s3() -> (String, Bucket) {
+    let bucket = Bucket::default();
+    let app = Router::new().fallback(mock_s3).with_state(bucket.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind mock s3");
+    let addr = listener.local_addr().expect("local addr");
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (format!("http://{addr}"), bucket)
+}
+
+#[tokio::test]
+async fn lfs_objects_are_stored_in_s3() {
+    let (endpoint, bucket) = start_mock_s3().await;
+    let config_dir = tempfile::TempDir::new().expect("temp dir");
+    let config_path = config_dir.path().join("server.toml");
+    std::fs::write(
+        &config_path,
+        format!(
+            "[lfs_s3]\nendpoint = \"{endpoint}\"\nbucket = \"objects\"\nprefix = \"cutman/lfs\"\n\
+             access_key_id = \"test-key\"\nsecret_access_key = \"test-secret\"\n"
+        ),
+    )
+    .expect("write config");
+
+    let server = TestServer::start_with_args(&["--config", config_path.to_str().unwrap()]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "lfs-s3").await;
+    let resp: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"name": "assets"}))
+        .send()
+        .await
+        .expect("create repo")
+        .json()
+        .await
+        .expect("parse repo response");
+    let repo_id = resp["data"]["id"].as_str().expect("repo id").to_string();
+
+    let content = b"a large binary asset".to_vec();
+    let oid = hex::encode(Sha256::digest(&content));
+    let lfs_url = format!(
+        "{}/git/{}/assets.git/info/lfs",
+        server.base_url, owner.namespace
+    );
+
+    let resp = client
+        .put(format!("{lfs_url}/objects/{oid}"))
+        .bearer_auth(&owner.token)
+        .body(content.clone())
+        .send()
+        .await
+        .expect("upload object");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let key = format!(
+        "/objects/cutman/lfs/objects/{}/{}/{oid}",
+        &oid[0..2],
+        &oid[2..4]
+    );
+    assert_eq!(bucket.lock().unwrap().get(&key), Some(&content));
+    assert!(
+        !server
+            .data_dir()
+            .join("lfs")
+            .join(&repo_id)
+            .join("objects")
+            .exists()
+    );
+
+    let resp: Value = client
+        .post(format!("{lfs_url}/objects/batch"))
+        .bearer_auth(&owner.token)
+        .header("Accept", "application/vnd.git-lfs+json")
+        .json(&serde_json::json!({
+            "operation": "download",
+            "objects": [{"oid": oid, "size": content.len()}],
+        }))
+        .send()
+        .await
+        .expect("lfs batch")
+        .json()
+        .await
+        .expect("parse batch response");
+    assert!(
+        resp["objects"][0]["actions"]["download"].is_object(),
+        "unexpected batch response: {resp}"
+    );
+
+    let resp = client
+        .post(format!("{lfs_url}/verify"))
+        .bearer_auth(&owner.token)
+        .header("Accept", "application/vnd.git-lfs+json")
+        .json(&serde_json::json!({"oid": oid, "size": content.len()}))
+        .send()
+        .await
+        .expect("verify object");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = client
+        .get(format!("{lfs_url}/objects/{oid}"))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("download object");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.bytes().await.expect("read object").to_vec(), content);
+
+    let missing = "0".repeat(64);
+    let resp = client
+        .get(format!("{lfs_url}/objects/{missing}"))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("download missing object");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn lfs_batch_hands_out_presigned_urls() {
+    let (endpoint, bucket) = start_mock_s3().await;
+    let config_dir = tempfile::TempDir::new().expect("temp dir");
+    let config_path = config_dir.path().join("server.toml");
+    std::fs::write(
+        &config_path,
+        format!(
+            "[lfs_s3]\nendpoint = \"{endpoint}\"\nbucket = \"objects\"\n\
+             access_key_id = \"test-key\"\nsecret_access_key = \"test-secret\"\n"
+        ),
+    )
+    .expect("write config");
+
+    let server = TestServer::start_with_args(&["--config", config_path.to_str().unwrap()]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "lfs-presign").await;
+    let resp = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"name": "assets"}))
+        .send()
+        .await
+        .expect("create repo");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let content = b"an asset that never passes through the server".to_vec();
+    let oid = hex::encode(Sha256::digest(&content));
+    let lfs_url = format!(
+        "{}/git/{}/assets.git/info/lfs",
+        server.base_url, owner.namespace
+    );
+    let batch = |operation: &'static str| {
+        client
+            .post(format!("{lfs_url}/objects/batch"))
+            .bearer_auth(&owner.token)
+            .header("Accept", "application/vnd.git-lfs+json")
+            .json(&serde_json::json!({
+                "operation": operation,
+                "transfers": ["chunked", "basic"],
+                "objects": [{"oid": oid, "size": content.len()}],
+            }))
+            .send()
+    };
+
+    let resp: Value = batch("upload")
+        .await
+        .expect("upload batch")
+        .json()
+        .await
+        .expect("parse batch response");
+    assert_eq!(resp["transfer"], "basic");
+    let upload = &resp["objects"][0]["actions"]["upload"];
+    let href = upload["href"].as_str().expect("upload href");
+    assert!(href.starts_with(&endpoint), "unexpected upload href: {href}");
+    assert_eq!(upload["header"]["x-amz-content-sha256"], oid.as_str());
+    let verify = resp["objects"][0]["actions"]["verify"]["href"]
+        .as_str()
+        .expect("verify href");
+    assert!(verify.starts_with(&server.base_url));
+
+    let resp = client
+        .put(href)
+        .header("x-amz-content-sha256", &oid)
+        .body(content.clone())
+        .send()
+        .await
+        .expect("upload to bucket");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(bucket.lock().unwrap().len(), 1);
+
+    // Until it's verified the server doesn't count the object as stored.
+    let resp: Value = batch("download")
+        .await
+        .expect("download batch")
+        .json()
+        .await
+        .expect("parse batch response");
+    assert_eq!(resp["objects"][0]["error"]["code"], 404);
+
+    let resp = client
+        .post(verify)
+        .bearer_auth(&owner.token)
+        .header("Accept", "application/vnd.git-lfs+json")
+        .json(&serde_json::json!({"oid": oid, "size": content.len()}))
+        .send()
+        .await
+        .expect("verify object");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp: Value = batch("download")
+        .await
+        .expect("download batch")
+        .json()
+        .await
+        .expect("parse batch response");
+    let href = resp["objects"][0]["actions"]["download"]["href"]
+        .as_str()
+        .expect("download href");
+    assert!(href.starts_with(&endpoint), "unexpected download href: {href}");
+
+    let resp = client.get(href).send().await.expect("download from bucket");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.bytes().await.expect("read object").to_vec(), content);
+
+    let resp: Value = batch("upload")
+        .await
+        .expect("upload batch")
+        .json()
+        .await
+        .expect("parse batch response");
+    assert!(resp["objects"][0]["actions"].is_null());
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::create_principal;
+
+#[tokio::test]
+async fn lfs_transfers_are_throttled_and_counted() {
+    let config_dir = TempDir::new().expect("temp dir");
+    let config_path = config_dir.path().join("server.toml");
+    std::fs::write(
+        &config_path,
+        "lfs_upload_bytes_per_sec = \"10KB\"\nlfs_download_bytes_per_sec = \"10KB\"\n",
+    )
+    .expect("write config");
+    let server =
+        TestServer::start_with_args(&["--config", config_path.to_str().expect("utf-8 path")]).await;
+    let client = Client::new();
+    let token = create_principal(&client, &server, "lfs-transfers")
+        .await
+        .token;
+    let resp = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"name": "assets"}))
+        .send()
+        .await
+        .expect("create repo");
+    assert!(resp.status().is_success());
+    let lfs = format!("{}/git/lfs-transfers/assets.git/info/lfs", server.base_url);
+
+    let content = vec![7u8; 20_000];
+    let oid = hex::encode(Sha256::digest(&content));
+    let started = Instant::now();
+    let resp = client
+        .put(format!("{lfs}/objects/{oid}"))
+        .bearer_auth(&token)
+        .body(content.clone())
+        .send()
+        .await
+        .expect("upload object");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+
+    let started = Instant::now();
+    let resp = client
+        .get(format!("{lfs}/objects/{oid}"))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("download object");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.bytes().await.expect("read object").to_vec(), content);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+
+    // A corrupt upload is counted as a failure.
+    let resp = client
+        .put(format!("{lfs}/objects/{oid}"))
+        .bearer_auth(&token)
+        .body(vec![8u8; 10])
+        .send()
+        .await
+        .expect("upload mismatched object");
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp: Value = client
+        .get(format!("{}/api/v1/admin/lfs/transfers", server.base_url))
+        .bearer_auth(&server.admin_token)
+        .send()
+        .await
+        .expect("get transfers")
+        .json()
+        .await
+        .expect("parse transfers");
+    let data = &resp["data"];
+    assert_eq!(data["upload_bytes_per_sec"], 10_000);
+    assert_eq!(data["namespaces"][0]["namespace"], "lfs-transfers");
+    let totals = &data["namespaces"][0];
+    assert_eq!(totals["uploads"], 1);
+    assert_eq!(totals["upload_failures"], 1);
+    assert_eq!(totals["upload_bytes"], 20_010);
+    assert_eq!(totals["downloads"], 1);
+    assert_eq!(totals["download_bytes"], 20_000);
+    assert!(totals["download_millis"].as_u64().expect("millis") >= 1000);
+}
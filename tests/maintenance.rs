@@ -0,0 +1,227 @@
+mod common;
+
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::{assert_git, create_principal};
+
+async fn maintenance_status(client: &Client, server: &TestServer) -> Value {
+    client
+        .get(format!("{}/api/v1/admin/maintenance", server.base_url))
+        .bearer_auth(&server.admin_token)
+        .send()
+        .await
+        .expect("get maintenance status")
+        .json()
+        .await
+        .expect("parse maintenance status")
+}
+
+#[tokio::test]
+async fn triggered_maintenance_packs_pushed_repos() {
+    let server = TestServer::start_with_args(&["--maintenance-task", "repack"]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "maint-owner").await;
+
+    let status = maintenance_status(&client, &server).await;
+    assert_eq!(status["data"]["scheduled"], false);
+    assert_eq!(status["data"]["task"], "repack");
+    assert_eq!(status["data"]["repos"], serde_json::json!([]));
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    std::fs::write(source.join("README.md"), "# maintained\n").expect("write file");
+    assert_git(&source, &["init", "-q", "-b", "main"]);
+    assert_git(&source, &["add", "README.md"]);
+    assert_git(&source, &["commit", "-q", "-m", "Initial commit"]);
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/maintained.git",
+        owner.token, owner.namespace
+    );
+    assert_git(&source, &["push", "-q", &url, "main"]);
+
+    let resp = client
+        .post(format!("{}/api/v1/admin/maintenance/run", server.base_url))
+        .bearer_auth(&server.admin_token)
+        .send()
+        .await
+        .expect("trigger maintenance");
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+
+    let mut repos = Value::Null;
+    for _ in 0..100 {
+        let status = maintenance_status(&client, &server).await;
+        if status["data"]["last_run_finished_at"].is_string() {
+            repos = status["data"]["repos"].clone();
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let repos = repos.as_array().expect("maintenance finished");
+    assert_eq!(repos.len(), 1);
+    assert_eq!(repos[0]["name"], "maintained");
+    assert_eq!(repos[0]["success"], true, "maintenance failed: {repos:?}");
+
+    let git_dir = std::fs::read_dir(server.data_dir().join("repos"))
+        .expect("list namespaces")
+        .next()
+        .expect("namespace dir")
+        .expect("read namespace dir")
+        .path()
+        .join("maintained.git");
+    let packs = assert_git(&git_dir, &["count-objects", "-v"]);
+    assert!(
+        packs.contains("count: 0\n"),
+        "loose objects remain: {packs}"
+    );
+    assert!(
+        packs.contains("packs: 1\n"),
+        "objects were not packed: {packs}"
+    );
+}
+
+#[tokio::test]
+async fn pushes_refresh_commit_graph_and_bitmaps() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "graph-owner").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    assert_git(&source, &["init", "-q", "-b", "main"]);
+    for i in 0..3 {
+        std::fs::write(source.join("file.txt"), format!("{i}\n")).expect("write file");
+        assert_git(&source, &["add", "file.txt"]);
+        assert_git(&source, &["commit", "-q", "-m", &format!("Commit {i}")]);
+    }
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/graphed.git",
+        owner.token, owner.namespace
+    );
+    assert_git(&source, &["push", "-q", &url, "main"]);
+
+    let objects = std::fs::read_dir(server.data_dir().join("repos"))
+        .expect("list namespaces")
+        .next()
+        .expect("namespace dir")
+        .expect("read namespace dir")
+        .path()
+        .join("graphed.git/objects");
+    let chain = objects.join("info/commit-graphs/commit-graph-chain");
+    let has_bitmap = || {
+        std::fs::read_dir(objects.join("pack"))
+            .map(|entries| {
+                entries.flatten().any(|e| {
+                    let name = e.file_name();
+                    let name = name.to_string_lossy();
+                    name.starts_with("multi-pack-index-") && name.ends_with(".bitmap")
+                })
+            })
+            .unwrap_or(false)
+    };
+
+    for _ in 0..100 {
+        if chain.exists() && has_bitmap() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(chain.exists(), "commit-graph chain was not written");
+    assert!(has_bitmap(), "multi-pack-index bitmap was not written");
+
+    // The optimized repository still serves clones.
+    assert_git(work.path(), &["clone", "-q", &url, "clone"]);
+}
+
+#[tokio::test]
+async fn maintenance_writes_bundle_for_bundle_uri() {
+    let server = TestServer::start_with_args(&["--bundle-uri"]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "bundle-owner").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    std::fs::write(source.join("README.md"), "# bundled\n").expect("write file");
+    assert_git(&source, &["init", "-q", "-b", "main"]);
+    assert_git(&source, &["add", "README.md"]);
+    assert_git(&source, &["commit", "-q", "-m", "Initial commit"]);
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/bundled.git",
+        owner.token, owner.namespace
+    );
+    assert_git(&source, &["push", "-q", &url, "main"]);
+
+    let bundle_url = format!(
+        "{}/git/{}/bundled.git/bundles/full.bundle",
+        server.base_url, owner.namespace
+    );
+    let resp = client
+        .get(&bundle_url)
+        .basic_auth("x-token", Some(&owner.token))
+        .send()
+        .await
+        .expect("fetch missing bundle");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = client
+        .post(format!("{}/api/v1/admin/maintenance/run", server.base_url))
+        .bearer_auth(&server.admin_token)
+        .send()
+        .await
+        .expect("trigger maintenance");
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    for _ in 0..100 {
+        let status = maintenance_status(&client, &server).await;
+        if status["data"]["last_run_finished_at"].is_string() {
+            assert_eq!(status["data"]["repos"][0]["success"], true, "{status}");
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let resp = client
+        .get(&bundle_url)
+        .basic_auth("x-token", Some(&owner.token))
+        .send()
+        .await
+        .expect("fetch bundle");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let bundle = work.path().join("full.bundle");
+    std::fs::write(&bundle, resp.bytes().await.expect("read bundle")).expect("write bundle");
+
+    assert_git(
+        work.path(),
+        &[
+            "clone",
+            "-q",
+            "-b",
+            "main",
+            bundle.to_str().expect("bundle path"),
+            "clone",
+        ],
+    );
+    assert_eq!(
+        std::fs::read_to_string(work.path().join("clone/README.md")).expect("read clone"),
+        "# bundled\n"
+    );
+
+    // Clones still work with the bundle advertised (git 2.40+) or ignored.
+    assert_git(
+        work.path(),
+        &["-c", "protocol.version=2", "clone", "-q", &url, "v2-clone"],
+    );
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::{assert_git, create_principal, git};
+
+async fn wait_for_sync(client: &Client, server: &TestServer, token: &str, repo_id: &str) -> Value {
+    for _ in 0..100 {
+        let resp: Value = client
+            .get(format!("{}/api/v1/repos/{repo_id}/mirror", server.base_url))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("get mirror")
+            .json()
+            .await
+            .expect("parse mirror");
+        if resp["data"]["last_sync_status"] != "pending" && resp["data"]["syncing"] == false {
+            return resp["data"].clone();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("mirror never finished syncing");
+}
+
+#[tokio::test]
+async fn pull_mirror_fetches_upstream_and_rejects_pushes() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let upstream_token = create_principal(&client, &server, "upstream").await.token;
+    let mirror_token = create_principal(&client, &server, "downstream").await.token;
+    let host = server.base_url.trim_start_matches("http://");
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    std::fs::write(source.join("README.md"), "# Upstream\n").expect("write file");
+    assert_git(&source, &["init", "-q", "-b", "trunk"]);
+    assert_git(&source, &["add", "README.md"]);
+    assert_git(&source, &["commit", "-q", "-m", "Initial commit"]);
+    assert_git(&source, &["tag", "v1"]);
+
+    let upstream_url = format!("http://x-token:{upstream_token}@{host}/git/upstream/project.git");
+    assert_git(&source, &["push", "-q", &upstream_url, "trunk", "v1"]);
+
+    let repos: Value = client
+        .get(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&upstream_token)
+        .send()
+        .await
+        .expect("list repos")
+        .json()
+        .await
+        .expect("parse repos");
+    let upstream_id = repos["data"][0]["id"].as_str().expect("repo id");
+    let resp = client
+        .put(format!(
+            "{}/api/v1/repos/{upstream_id}/default-branch",
+            server.base_url
+        ))
+        .bearer_auth(&upstream_token)
+        .json(&serde_json::json!({"branch": "trunk"}))
+        .send()
+        .await
+        .expect("set default branch");
+    assert!(resp.status().is_success());
+
+    let resp: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&mirror_token)
+        .json(&serde_json::json!({
+            "name": "project",
+            "mirror": {"url": upstream_url},
+        }))
+        .send()
+        .await
+        .expect("create mirror")
+        .json()
+        .await
+        .expect("parse repo");
+    let repo_id = resp["data"]["id"].as_str().expect("repo id").to_string();
+
+    let mirror = wait_for_sync(&client, &server, &mirror_token, &repo_id).await;
+    assert_eq!(mirror["last_sync_status"], "success", "{mirror}");
+    assert_eq!(
+        mirror["url"],
+        format!("http://***@{host}/git/upstream/project.git")
+    );
+
+    // Triggers close together share one sync, held back since the mirror
+    // just synced.
+    let mut scheduled = Vec::new();
+    for _ in 0..2 {
+        let resp = client
+            .post(format!("{}/api/v1/repos/{repo_id}/sync", server.base_url))
+            .bearer_auth(&mirror_token)
+            .send()
+            .await
+            .expect("trigger sync");
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let resp: Value = resp.json().await.expect("parse mirror");
+        scheduled.push(resp["data"]["triggered_sync_at"].clone());
+    }
+    assert!(scheduled[0].is_string(), "{scheduled:?}");
+    assert_eq!(scheduled[0], scheduled[1]);
+    let resp = client
+        .post(format!("{}/api/v1/repos/{repo_id}/sync", server.base_url))
+        .bearer_auth(&upstream_token)
+        .send()
+        .await
+        .expect("trigger sync without access");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Branches, tags, and the default branch all come from upstream.
+    let mirror_url = format!("http://x-token:{mirror_token}@{host}/git/downstream/project.git");
+    assert_git(work.path(), &["clone", "-q", &mirror_url, "clone"]);
+    let clone = work.path().join("clone");
+    assert_eq!(
+        std::fs::read_to_string(clone.join("README.md")).expect("read clone"),
+        "# Upstream\n"
+    );
+    let tags = git(&clone, &["tag", "--list"]);
+    assert_eq!(String::from_utf8_lossy(&tags.stdout).trim(), "v1");
+    let head = git(&clone, &["symbolic-ref", "--short", "HEAD"]);
+    assert_eq!(String::from_utf8_lossy(&head.stdout).trim(), "trunk");
+
+    std::fs::write(clone.join("local.txt"), "local\n").expect("write file");
+    assert_git(&clone, &["add", "local.txt"]);
+    assert_git(&clone, &["commit", "-q", "-m", "Local change"]);
+    let push = git(&clone, &["push", "-q", "origin", "trunk"]);
+    assert!(!push.status.success(), "push into a mirror should fail");
+    assert!(
+        String::from_utf8_lossy(&push.stderr).contains("403"),
+        "{}",
+        String::from_utf8_lossy(&push.stderr)
+    );
+
+    // Stopping the mirror makes the repository writable.
+    let resp = client
+        .delete(format!("{}/api/v1/repos/{repo_id}/mirror", server.base_url))
+        .bearer_auth(&mirror_token)
+        .send()
+        .await
+        .expect("delete mirror");
+    assert_eq!(resp.status(), 204);
+    assert_git(&clone, &["push", "-q", "origin", "trunk"]);
+}
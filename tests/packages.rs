@@ -0,0 +1,149 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use common::TestServer;
+use common::fixtures::{TestPrincipal, create_principal};
+
+fn package_url(server: &TestServer, owner: &TestPrincipal, file: &str) -> String {
+    format!(
+        "{}/api/v1/namespaces/{}/packages/{file}",
+        server.base_url, owner.namespace
+    )
+}
+
+#[tokio::test]
+async fn package_files_are_published_and_downloaded() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "pkg-owner").await;
+    let other = create_principal(&client, &server, "pkg-other").await;
+    let url = package_url(&server, &owner, "tool/1.0.0/tool-linux-amd64.tar.gz");
+    let content = b"pretend this is a tarball".to_vec();
+    let sha256 = hex::encode(Sha256::digest(&content));
+
+    let resp = client
+        .put(&url)
+        .bearer_auth(&owner.token)
+        .header("X-Checksum-Sha256", "0".repeat(64))
+        .body(content.clone())
+        .send()
+        .await
+        .expect("upload with wrong checksum");
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = client
+        .put(&url)
+        .bearer_auth(&owner.token)
+        .header("X-Checksum-Sha256", &sha256)
+        .body(content.clone())
+        .send()
+        .await
+        .expect("upload");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: Value = resp.json().await.expect("parse upload response");
+    assert_eq!(body["data"]["sha256"], sha256);
+    assert_eq!(body["data"]["size"], content.len());
+
+    // Published versions are immutable.
+    let resp = client
+        .put(&url)
+        .bearer_auth(&owner.token)
+        .body("different")
+        .send()
+        .await
+        .expect("re-upload");
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let resp = client
+        .get(&url)
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("download");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["x-checksum-sha256"], sha256.as_str());
+    assert_eq!(resp.bytes().await.expect("read download"), content);
+
+    // Other principals need a grant on the namespace.
+    for resp in [
+        client.get(&url).send().await.expect("anonymous download"),
+        client
+            .get(&url)
+            .bearer_auth(&other.token)
+            .send()
+            .await
+            .expect("download without access"),
+    ] {
+        assert!(resp.status().is_client_error(), "{}", resp.status());
+        assert_ne!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    let resp = client
+        .put(package_url(&server, &owner, "tool/1.0.0/..hidden"))
+        .bearer_auth(&owner.token)
+        .body("x")
+        .send()
+        .await
+        .expect("upload with invalid name");
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = client
+        .delete(&url)
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("delete");
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = client
+        .get(&url)
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("download deleted");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn package_files_count_towards_the_storage_limit() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "pkg-quota").await;
+
+    let resp = client
+        .patch(format!(
+            "{}/api/v1/namespaces/{}",
+            server.base_url, owner.namespace
+        ))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"storage_limit_bytes": 4096}))
+        .send()
+        .await
+        .expect("set limit");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = client
+        .put(package_url(&server, &owner, "data/1/first.bin"))
+        .bearer_auth(&owner.token)
+        .body(vec![0u8; 3072])
+        .send()
+        .await
+        .expect("upload first file");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let resp = client
+        .put(package_url(&server, &owner, "data/1/second.bin"))
+        .bearer_auth(&owner.token)
+        .body(vec![0u8; 2048])
+        .send()
+        .await
+        .expect("upload second file");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: Value = resp.json().await.expect("parse error");
+    assert_eq!(
+        body["error"],
+        "Namespace storage limit exceeded: this upload needs 5.0 KiB of 4.0 KiB"
+    );
+}
@@ -0,0 +1,301 @@
+mod common;
+
+use std::path::Path;
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode, redirect};
+use serde_json::Value;
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::{TestPrincipal, assert_git, create_principal};
+
+fn client() -> Client {
+    Client::builder()
+        .redirect(redirect::Policy::none())
+        .build()
+        .expect("build client")
+}
+
+fn write(dir: &Path, path: &str, content: &str) {
+    let path = dir.join(path);
+    std::fs::create_dir_all(path.parent().expect("parent")).expect("create dir");
+    std::fs::write(path, content).expect("write file");
+}
+
+/// Creates a repo and pushes a site under `docs/` to its main branch,
+/// returning the repo id and the working copy.
+async fn push_site(
+    client: &Client,
+    server: &TestServer,
+    owner: &TestPrincipal,
+    name: &str,
+    visibility: &str,
+) -> (String, TempDir) {
+    let resp: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"name": name, "visibility": visibility}))
+        .send()
+        .await
+        .expect("create repo")
+        .json()
+        .await
+        .expect("parse repo response");
+    let repo_id = resp["data"]["id"].as_str().expect("repo id").to_string();
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path();
+    write(source, "README.md", "# site\n");
+    write(source, "docs/index.html", "<h1>Home</h1>");
+    write(source, "docs/style.css", "h1 { color: red }");
+    write(source, "docs/guide/index.html", "<h1>Guide</h1>");
+    write(source, "docs/404.html", "<h1>Lost</h1>");
+    write(source, "docs/payload.exe", "MZ");
+    assert_git(source, &["init", "-q", "-b", "main"]);
+    assert_git(source, &["add", "."]);
+    assert_git(source, &["commit", "-q", "-m", "Add site"]);
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/{name}.git",
+        owner.token, owner.namespace
+    );
+    assert_git(source, &["push", "-q", &url, "main"]);
+    assert_git(source, &["remote", "add", "origin", &url]);
+    (repo_id, work)
+}
+
+async fn enable_pages(
+    client: &Client,
+    server: &TestServer,
+    owner: &TestPrincipal,
+    repo_id: &str,
+) -> Value {
+    let resp = client
+        .put(format!("{}/api/v1/repos/{repo_id}/pages", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"path": "/docs/"}))
+        .send()
+        .await
+        .expect("enable pages");
+    assert_eq!(resp.status(), StatusCode::OK);
+    resp.json().await.expect("parse pages response")
+}
+
+/// Polls the Pages settings until the build that's running has finished.
+async fn wait_for_build(
+    client: &Client,
+    server: &TestServer,
+    owner: &TestPrincipal,
+    repo_id: &str,
+    previous_commit: Option<&str>,
+) -> Value {
+    for _ in 0..200 {
+        let resp: Value = client
+            .get(format!("{}/api/v1/repos/{repo_id}/pages", server.base_url))
+            .bearer_auth(&owner.token)
+            .send()
+            .await
+            .expect("get pages")
+            .json()
+            .await
+            .expect("parse pages response");
+        let data = &resp["data"];
+        if data["building"] == false
+            && !data["last_build_at"].is_null()
+            && data["commit_sha"].as_str() != previous_commit
+        {
+            return data.clone();
+        }
+        if data["building"] == false && data["last_error"].is_string() {
+            return data.clone();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("Pages build did not finish");
+}
+
+#[tokio::test]
+async fn pages_publish_a_branch_directory_and_rebuild_on_push() {
+    let server = TestServer::start_with_args(&["--pages"]).await;
+    let client = client();
+    let owner = create_principal(&client, &server, "pages-owner").await;
+    let (repo_id, work) = push_site(&client, &server, &owner, "site", "public").await;
+
+    let enabled = enable_pages(&client, &server, &owner, &repo_id).await;
+    assert_eq!(enabled["data"]["branch"], "main");
+    assert_eq!(enabled["data"]["path"], "docs");
+    assert_eq!(
+        enabled["data"]["url"],
+        format!("{}/pages-owner/site/pages/", server.base_url)
+    );
+
+    let build = wait_for_build(&client, &server, &owner, &repo_id, None).await;
+    assert_eq!(build["last_build_status"], "success");
+    let first_commit = build["commit_sha"]
+        .as_str()
+        .expect("commit sha")
+        .to_string();
+
+    let site = format!("{}/pages-owner/site/pages", server.base_url);
+    let resp = client.get(&site).send().await.expect("get site root");
+    assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(resp.headers()["location"], "./pages/");
+
+    let resp = client
+        .get(format!("{site}/"))
+        .send()
+        .await
+        .expect("get index");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["content-type"], "text/html; charset=utf-8");
+    assert_eq!(resp.headers()["x-content-type-options"], "nosniff");
+    let csp = resp.headers()["content-security-policy"]
+        .to_str()
+        .expect("csp");
+    assert!(csp.starts_with("sandbox"), "unexpected CSP: {csp}");
+    assert_eq!(resp.text().await.expect("read index"), "<h1>Home</h1>");
+
+    let resp = client
+        .get(format!("{site}/style.css"))
+        .send()
+        .await
+        .expect("get stylesheet");
+    assert_eq!(resp.headers()["content-type"], "text/css; charset=utf-8");
+
+    let resp = client
+        .get(format!("{site}/payload.exe"))
+        .send()
+        .await
+        .expect("get unknown type");
+    assert_eq!(resp.headers()["content-type"], "application/octet-stream");
+
+    let resp = client
+        .get(format!("{site}/guide"))
+        .send()
+        .await
+        .expect("get directory");
+    assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(resp.headers()["location"], "./guide/");
+    let resp = client
+        .get(format!("{site}/guide/"))
+        .send()
+        .await
+        .expect("get directory index");
+    assert_eq!(resp.text().await.expect("read guide"), "<h1>Guide</h1>");
+
+    for missing in [
+        "missing.html",
+        "..%2F..%2FREADME.md",
+        "guide%2F..%2F..%2FREADME.md",
+    ] {
+        let resp = client
+            .get(format!("{site}/{missing}"))
+            .send()
+            .await
+            .expect("get missing page");
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{missing}");
+        assert_eq!(resp.text().await.expect("read 404"), "<h1>Lost</h1>");
+    }
+
+    // A push rebuilds the site.
+    let source = work.path();
+    write(source, "docs/index.html", "<h1>Home v2</h1>");
+    assert_git(source, &["commit", "-q", "-am", "Update site"]);
+    assert_git(source, &["push", "-q", "origin", "main"]);
+    let build = wait_for_build(&client, &server, &owner, &repo_id, Some(&first_commit)).await;
+    assert_eq!(build["last_build_status"], "success");
+    let resp = client
+        .get(format!("{site}/"))
+        .send()
+        .await
+        .expect("get rebuilt index");
+    assert_eq!(resp.text().await.expect("read index"), "<h1>Home v2</h1>");
+
+    let resp = client
+        .delete(format!("{}/api/v1/repos/{repo_id}/pages", server.base_url))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("disable pages");
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    let resp = client
+        .get(format!("{site}/"))
+        .send()
+        .await
+        .expect("get disabled site");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn private_sites_need_a_token_and_oversized_sites_fail() {
+    let server = TestServer::start_with_args(&["--pages", "--pages-max-bytes", "20"]).await;
+    let client = client();
+    let owner = create_principal(&client, &server, "pages-private").await;
+    let (repo_id, _work) = push_site(&client, &server, &owner, "site", "private").await;
+
+    enable_pages(&client, &server, &owner, &repo_id).await;
+    let build = wait_for_build(&client, &server, &owner, &repo_id, None).await;
+    assert_eq!(build["last_build_status"], "failed");
+    assert!(build["commit_sha"].is_null());
+    let error = build["last_error"].as_str().expect("build error");
+    assert!(error.contains("limit"), "unexpected error: {error}");
+
+    let site = format!("{}/pages-private/site/pages/", server.base_url);
+    let resp = client.get(&site).send().await.expect("get private site");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert!(resp.headers().contains_key("www-authenticate"));
+
+    let resp = client
+        .get(&site)
+        .basic_auth("x-token", Some(&owner.token))
+        .send()
+        .await
+        .expect("get unbuilt site");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn pages_domain_serves_namespace_subdomains() {
+    let server = TestServer::start_with_args(&["--pages", "--pages-domain", "pages.test"]).await;
+    let client = client();
+    let owner = create_principal(&client, &server, "pages-domain").await;
+    let (repo_id, _work) = push_site(&client, &server, &owner, "site", "public").await;
+
+    let enabled = enable_pages(&client, &server, &owner, &repo_id).await;
+    assert_eq!(
+        enabled["data"]["url"],
+        "http://pages-domain.pages.test/site/"
+    );
+    wait_for_build(&client, &server, &owner, &repo_id, None).await;
+
+    let resp = client
+        .get(format!("{}/site/", server.base_url))
+        .header("Host", "pages-domain.pages.test")
+        .send()
+        .await
+        .expect("get site on domain");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(!resp.headers().contains_key("content-security-policy"));
+    assert_eq!(resp.text().await.expect("read index"), "<h1>Home</h1>");
+
+    let resp = client
+        .get(format!("{}/site", server.base_url))
+        .header("Host", "pages-domain.pages.test")
+        .send()
+        .await
+        .expect("get site without slash");
+    assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(resp.headers()["location"], "./site/");
+
+    let resp = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .header("Host", "pages-domain.pages.test")
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"name": "nope"}))
+        .send()
+        .await
+        .expect("post to pages domain");
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use std::path::Path;
+
+use rand::RngCore;
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::{TestPrincipal, assert_git, assert_git_fails, create_principal};
+
+fn remote_url(server: &TestServer, owner: &TestPrincipal, repo: &str) -> String {
+    let host = server.base_url.trim_start_matches("http://");
+    format!(
+        "http://x-token:{}@{host}/git/{}/{repo}.git",
+        owner.token, owner.namespace
+    )
+}
+
+fn commit_random_file(source: &Path, name: &str, size: usize) {
+    let mut contents = vec![0u8; size];
+    rand::thread_rng().fill_bytes(&mut contents);
+    std::fs::write(source.join(name), &contents).expect("write file");
+    assert_git(source, &["add", name]);
+    assert_git(source, &["commit", "-q", "-m", &format!("Add {name}")]);
+}
+
+#[tokio::test]
+async fn pushes_with_oversized_files_are_rejected() {
+    let server = TestServer::start_with_args(&["--max-blob-bytes", "65536"]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "blob-owner").await;
+    let url = remote_url(&server, &owner, "blobs");
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path();
+    assert_git(source, &["init", "-q", "-b", "main"]);
+    commit_random_file(source, "small.bin", 1024);
+    assert_git(source, &["push", "-q", &url, "main"]);
+    let head = assert_git(source, &["rev-parse", "HEAD"]);
+
+    std::fs::create_dir(source.join("assets")).expect("create dir");
+    commit_random_file(source, "assets/large.bin", 100 * 1024);
+    let stderr = assert_git_fails(source, &["push", &url, "main"]);
+    assert!(
+        stderr.contains(
+            "remote: cutman: push rejected: assets/large.bin is 100.0 KiB, over the 64.0 KiB limit for a single file"
+        ),
+        "unexpected stderr: {stderr}"
+    );
+    let refs = assert_git(source, &["ls-remote", &url, "refs/heads/main"]);
+    assert!(
+        refs.starts_with(&head),
+        "rejected push updated main: {refs}"
+    );
+}
+
+#[tokio::test]
+async fn oversized_packs_are_rejected_while_streaming() {
+    let server = TestServer::start_with_args(&["--max-pack-bytes", "262144"]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "pack-owner").await;
+    let url = remote_url(&server, &owner, "packs");
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path();
+    assert_git(source, &["init", "-q", "-b", "main"]);
+    commit_random_file(source, "small.bin", 1024);
+    assert_git(source, &["push", "-q", &url, "main"]);
+
+    // Random bytes do not compress, so the pack is well over the limit.
+    commit_random_file(source, "large.bin", 1024 * 1024);
+    let stderr = assert_git_fails(source, &["push", &url, "main"]);
+    assert!(
+        stderr.contains("pack exceeds maximum allowed size"),
+        "unexpected stderr: {stderr}"
+    );
+}
+
+#[tokio::test]
+async fn content_api_refuses_oversized_files() {
+    let server =
+        TestServer::start_with_args(&["--max-blob-bytes", "1024", "--max-pack-bytes", "1536"])
+            .await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "api-owner").await;
+
+    let resp: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"name": "api"}))
+        .send()
+        .await
+        .expect("create repo")
+        .json()
+        .await
+        .expect("parse repo response");
+    let repo_id = resp["data"]["id"].as_str().expect("repo id");
+    let commits_url = format!("{}/api/v1/repos/{repo_id}/commits", server.base_url);
+
+    let resp = client
+        .post(&commits_url)
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({
+            "message": "Add large file",
+            "actions": [{"action": "create", "path": "large.txt", "content": "x".repeat(2048)}],
+        }))
+        .send()
+        .await
+        .expect("commit large file");
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body: Value = resp.json().await.expect("parse error");
+    assert_eq!(
+        body["error"],
+        "large.txt is 2.0 KiB, over the 1.0 KiB limit for a single file"
+    );
+
+    let resp = client
+        .post(&commits_url)
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({
+            "message": "Add two files",
+            "actions": [
+                {"action": "create", "path": "a.txt", "content": "a".repeat(1024)},
+                {"action": "create", "path": "b.txt", "content": "b".repeat(1024)},
+            ],
+        }))
+        .send()
+        .await
+        .expect("commit two files");
+    assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let resp = client
+        .post(&commits_url)
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({
+            "message": "Add small file",
+            "actions": [{"action": "create", "path": "small.txt", "content": "small"}],
+        }))
+        .send()
+        .await
+        .expect("commit small file");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+}
@@ -0,0 +1,271 @@
+mod common;
+
+use std::path::Path;
+
+use rand::RngCore;
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::{TestPrincipal, assert_git, assert_git_fails, create_principal, git};
+
+fn remote_url(server: &TestServer, owner: &TestPrincipal, repo: &str) -> String {
+    let host = server.base_url.trim_start_matches("http://");
+    format!(
+        "http://x-token:{}@{host}/git/{}/{repo}.git",
+        owner.token, owner.namespace
+    )
+}
+
+#[tokio::test]
+async fn namespace_quotas_are_enforced_on_push_and_create() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "quota-owner").await;
+
+    let resp = client
+        .patch(format!(
+            "{}/api/v1/namespaces/{}",
+            server.base_url, owner.namespace
+        ))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"repo_limit": 1, "storage_limit_bytes": 1_048_576}))
+        .send()
+        .await
+        .expect("set limits");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path();
+    std::fs::write(source.join("README.md"), "# quota\n").expect("write file");
+    assert_git(source, &["init", "-q", "-b", "main"]);
+    assert_git(source, &["add", "README.md"]);
+    assert_git(source, &["commit", "-q", "-m", "Initial commit"]);
+    let head = assert_git(source, &["rev-parse", "HEAD"]);
+
+    let first = remote_url(&server, &owner, "first");
+    assert_git(source, &["push", "-q", &first, "main"]);
+
+    // The namespace now holds its one allowed repository.
+    let resp = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"name": "second"}))
+        .send()
+        .await
+        .expect("create repo");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: Value = resp.json().await.expect("parse error");
+    assert_eq!(
+        body["error"],
+        "Namespace has reached its limit of 1 repositories"
+    );
+
+    let stderr = assert_git_fails(
+        source,
+        &["push", &remote_url(&server, &owner, "third"), "main"],
+    );
+    assert!(
+        stderr.contains("Namespace has reached its repository limit"),
+        "unexpected stderr: {stderr}"
+    );
+
+    // Random bytes do not compress, so this push is well over 1 MiB.
+    let mut contents = vec![0u8; 2 * 1024 * 1024];
+    rand::thread_rng().fill_bytes(&mut contents);
+    std::fs::write(source.join("large.bin"), &contents).expect("write large file");
+    assert_git(source, &["add", "large.bin"]);
+    assert_git(source, &["commit", "-q", "-m", "Add large file"]);
+
+    let stderr = assert_git_fails(source, &["push", &first, "main"]);
+    assert!(
+        stderr.contains("remote: cutman: push rejected: Namespace storage limit exceeded"),
+        "unexpected stderr: {stderr}"
+    );
+    let refs = assert_git(source, &["ls-remote", &first, "refs/heads/main"]);
+    assert!(
+        refs.starts_with(&head),
+        "rejected push updated main: {refs}"
+    );
+
+    // Pushes that add no data are still accepted.
+    assert_git(
+        source,
+        &["push", "-q", &first, &format!("{head}:refs/heads/copy")],
+    );
+}
+
+fn dir_size(path: &Path) -> u64 {
+    std::fs::read_dir(path)
+        .expect("read dir")
+        .flatten()
+        .filter_map(|entry| {
+            // A repack can remove its temporary files while they're counted.
+            let metadata = entry.metadata().ok()?;
+            Some(if metadata.is_dir() {
+                dir_size(&entry.path())
+            } else {
+                metadata.len()
+            })
+        })
+        .sum()
+}
+
+/// Size of `path` once the repack the server runs after a push has finished
+/// with it, since the repack briefly holds both the old and the new packs.
+async fn settled_size(path: &Path) -> u64 {
+    let mut size = dir_size(path);
+    loop {
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+        let next = dir_size(path);
+        if next == size {
+            return size;
+        }
+        size = next;
+    }
+}
+
+#[tokio::test]
+async fn pushes_warn_when_namespace_storage_is_nearly_full() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "nearly-full").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path();
+    let mut contents = vec![0u8; 256 * 1024];
+    rand::thread_rng().fill_bytes(&mut contents);
+    std::fs::write(source.join("data.bin"), &contents).expect("write file");
+    assert_git(source, &["init", "-q", "-b", "main"]);
+    assert_git(source, &["add", "data.bin"]);
+    assert_git(source, &["commit", "-q", "-m", "Initial commit"]);
+
+    let url = remote_url(&server, &owner, "full");
+    let output = git(source, &["push", &url, "main"]);
+    assert!(output.status.success());
+    assert!(
+        !String::from_utf8_lossy(&output.stderr).contains("cutman: warning"),
+        "unexpected warning without a limit"
+    );
+
+    let repos = server.data_dir().join("repos");
+    let size = settled_size(&repos).await;
+    let resp = client
+        .patch(format!(
+            "{}/api/v1/namespaces/{}",
+            server.base_url, owner.namespace
+        ))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"storage_limit_bytes": size + 16 * 1024}))
+        .send()
+        .await
+        .expect("set limit");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    std::fs::write(source.join("README.md"), "# nearly full\n").expect("write file");
+    assert_git(source, &["add", "README.md"]);
+    assert_git(source, &["commit", "-q", "-m", "Add readme"]);
+    let output = git(source, &["push", &url, "main"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "push failed: {stderr}");
+    assert!(
+        stderr.contains("remote: cutman: warning: Namespace is using"),
+        "missing storage warning: {stderr}"
+    );
+}
+
+#[tokio::test]
+async fn lfs_uploads_count_toward_namespace_storage() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "quota-lfs").await;
+
+    let resp = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"name": "assets"}))
+        .send()
+        .await
+        .expect("create repo");
+    assert!(resp.status().is_success());
+    let resp = client
+        .patch(format!(
+            "{}/api/v1/namespaces/{}",
+            server.base_url, owner.namespace
+        ))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"storage_limit_bytes": 100}))
+        .send()
+        .await
+        .expect("set limits");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let lfs = format!(
+        "{}/git/{}/assets.git/info/lfs",
+        server.base_url, owner.namespace
+    );
+    let first = vec![b'a'; 60];
+    let second = vec![b'b'; 60];
+    let first_oid = hex::encode(Sha256::digest(&first));
+    let second_oid = hex::encode(Sha256::digest(&second));
+
+    // Together the objects don't fit, so only the first gets an action.
+    let resp: Value = client
+        .post(format!("{lfs}/objects/batch"))
+        .bearer_auth(&owner.token)
+        .header("Accept", "application/vnd.git-lfs+json")
+        .json(&serde_json::json!({
+            "operation": "upload",
+            "objects": [
+                {"oid": first_oid, "size": 60},
+                {"oid": second_oid, "size": 60},
+            ],
+        }))
+        .send()
+        .await
+        .expect("lfs batch")
+        .json()
+        .await
+        .expect("parse batch response");
+    assert!(
+        resp["objects"][0]["actions"]["upload"].is_object(),
+        "unexpected batch response: {resp}"
+    );
+    assert_eq!(resp["objects"][1]["error"]["code"], 507);
+    assert!(
+        resp["objects"][1]["error"]["message"]
+            .as_str()
+            .expect("error message")
+            .contains("storage limit exceeded")
+    );
+
+    let resp = client
+        .put(format!("{lfs}/objects/{first_oid}"))
+        .bearer_auth(&owner.token)
+        .body(first)
+        .send()
+        .await
+        .expect("upload first object");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // The first object's bytes now count, so the second is refused even
+    // when uploaded without asking the batch API.
+    let resp = client
+        .put(format!("{lfs}/objects/{second_oid}"))
+        .bearer_auth(&owner.token)
+        .body(second)
+        .send()
+        .await
+        .expect("upload second object");
+    assert_eq!(resp.status(), StatusCode::INSUFFICIENT_STORAGE);
+    let body: Value = resp.json().await.expect("parse LFS error");
+    assert!(
+        body["message"]
+            .as_str()
+            .expect("error message")
+            .contains("storage limit exceeded"),
+        "unexpected error: {body}"
+    );
+}
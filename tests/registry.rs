@@ -0,0 +1,217 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use common::TestServer;
+use common::fixtures::{TestPrincipal, create_principal};
+
+async fn create_repo(client: &Client, server: &TestServer, owner: &TestPrincipal, name: &str) {
+    let resp = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"name": name, "namespace": owner.namespace}))
+        .send()
+        .await
+        .expect("create repo");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+}
+
+fn digest(content: &[u8]) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(content)))
+}
+
+#[tokio::test]
+async fn images_are_pushed_and_pulled_by_tag() {
+    let server = TestServer::start_with_args(&["--registry"]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "registry-owner").await;
+    create_repo(&client, &server, &owner, "app").await;
+    let image = format!("{}/v2/{}/app", server.base_url, owner.namespace);
+
+    // Clients probe /v2/ anonymously and send credentials once challenged.
+    let resp = client
+        .get(format!("{}/v2/", server.base_url))
+        .send()
+        .await
+        .expect("probe");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert!(resp.headers().contains_key("www-authenticate"));
+    let resp = client
+        .get(format!("{}/v2/", server.base_url))
+        .basic_auth("x-token", Some(&owner.token))
+        .send()
+        .await
+        .expect("probe with credentials");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // A config blob uploaded in one request.
+    let config = br#"{"architecture":"amd64","os":"linux"}"#;
+    let config_digest = digest(config);
+    let resp = client
+        .post(format!("{image}/blobs/uploads/?digest={config_digest}"))
+        .basic_auth("x-token", Some(&owner.token))
+        .body(config.to_vec())
+        .send()
+        .await
+        .expect("monolithic upload");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    // A layer uploaded in chunks.
+    let layer = b"layer-part-one layer-part-two".to_vec();
+    let layer_digest = digest(&layer);
+    let resp = client
+        .post(format!("{image}/blobs/uploads/"))
+        .basic_auth("x-token", Some(&owner.token))
+        .send()
+        .await
+        .expect("start upload");
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let location = resp.headers()["location"].to_str().unwrap().to_string();
+    let upload = format!("{}{location}", server.base_url);
+    let resp = client
+        .patch(&upload)
+        .basic_auth("x-token", Some(&owner.token))
+        .header("Content-Range", "0-14")
+        .body(layer[..15].to_vec())
+        .send()
+        .await
+        .expect("upload chunk");
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    assert_eq!(resp.headers()["range"], "0-14");
+
+    let resp = client
+        .put(format!("{upload}?digest={}", digest(b"wrong")))
+        .basic_auth("x-token", Some(&owner.token))
+        .body(layer[15..].to_vec())
+        .send()
+        .await
+        .expect("finish upload with wrong digest");
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = client
+        .post(format!("{image}/blobs/uploads/?digest={layer_digest}"))
+        .basic_auth("x-token", Some(&owner.token))
+        .body(layer.clone())
+        .send()
+        .await
+        .expect("upload layer");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let manifest = serde_json::to_vec(&serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {"mediaType": "application/vnd.oci.image.config.v1+json", "digest": config_digest, "size": config.len()},
+        "layers": [{"mediaType": "application/vnd.oci.image.layer.v1.tar", "digest": layer_digest, "size": layer.len()}],
+    }))
+    .unwrap();
+
+    // Manifests may only refer to blobs that were pushed.
+    let dangling = serde_json::to_vec(&serde_json::json!({
+        "schemaVersion": 2,
+        "layers": [{"digest": digest(b"missing"), "size": 7}],
+    }))
+    .unwrap();
+    let resp = client
+        .put(format!("{image}/manifests/broken"))
+        .basic_auth("x-token", Some(&owner.token))
+        .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+        .body(dangling)
+        .send()
+        .await
+        .expect("put dangling manifest");
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["errors"][0]["code"], "MANIFEST_BLOB_UNKNOWN");
+
+    let resp = client
+        .put(format!("{image}/manifests/latest"))
+        .basic_auth("x-token", Some(&owner.token))
+        .header("Content-Type", "application/vnd.oci.image.manifest.v1+json")
+        .body(manifest.clone())
+        .send()
+        .await
+        .expect("put manifest");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers()["docker-content-digest"], digest(&manifest));
+
+    let resp = client
+        .get(format!("{image}/manifests/latest"))
+        .basic_auth("x-token", Some(&owner.token))
+        .send()
+        .await
+        .expect("get manifest");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers()["content-type"],
+        "application/vnd.oci.image.manifest.v1+json"
+    );
+    assert_eq!(resp.bytes().await.unwrap(), manifest);
+
+    let resp = client
+        .get(format!("{image}/blobs/{layer_digest}"))
+        .basic_auth("x-token", Some(&owner.token))
+        .send()
+        .await
+        .expect("get layer");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.bytes().await.unwrap(), layer);
+
+    let resp = client
+        .get(format!("{image}/manifests/missing"))
+        .basic_auth("x-token", Some(&owner.token))
+        .send()
+        .await
+        .expect("get unknown tag");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn images_share_repository_permissions() {
+    let server = TestServer::start_with_args(&["--registry"]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "perm-owner").await;
+    let other = create_principal(&client, &server, "perm-other").await;
+    create_repo(&client, &server, &owner, "private").await;
+    let image = format!("{}/v2/{}/private", server.base_url, owner.namespace);
+
+    let resp = client
+        .post(format!("{image}/blobs/uploads/"))
+        .send()
+        .await
+        .expect("anonymous upload");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = client
+        .post(format!("{image}/blobs/uploads/"))
+        .basic_auth("x-token", Some(&other.token))
+        .send()
+        .await
+        .expect("upload without access");
+    assert!(resp.status().is_client_error());
+    assert_ne!(resp.status(), StatusCode::ACCEPTED);
+
+    // Images need a repository to be named after.
+    let resp = client
+        .post(format!(
+            "{}/v2/{}/no-such-repo/blobs/uploads/",
+            server.base_url, owner.namespace
+        ))
+        .basic_auth("x-token", Some(&owner.token))
+        .send()
+        .await
+        .expect("upload to unknown repo");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn registry_is_off_by_default() {
+    let server = TestServer::start().await;
+    let resp = Client::new()
+        .get(format!("{}/v2/", server.base_url))
+        .send()
+        .await
+        .expect("probe");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,366 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+
+use common::TestServer;
+use common::fixtures::{TestPrincipal, create_principal};
+
+struct TestRepo {
+    id: String,
+    namespace: String,
+    name: String,
+}
+
+impl TestRepo {
+    fn info_refs_url(&self, server: &TestServer) -> String {
+        format!(
+            "{}/git/{}/{}.git/info/refs?service=git-upload-pack",
+            server.base_url, self.namespace, self.name
+        )
+    }
+
+    fn refs_url(&self, server: &TestServer) -> String {
+        format!("{}/api/v1/repos/{}/refs", server.base_url, self.id)
+    }
+}
+
+async fn create_repo(
+    client: &Client,
+    server: &TestServer,
+    owner: &TestPrincipal,
+    name: &str,
+    visibility: &str,
+) -> TestRepo {
+    let resp: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({
+            "name": name,
+            "namespace": owner.namespace,
+            "visibility": visibility,
+        }))
+        .send()
+        .await
+        .expect("create repo")
+        .json()
+        .await
+        .expect("parse repo response");
+    assert_eq!(resp["data"]["visibility"], visibility);
+    let id = resp["data"]["id"].as_str().expect("repo id").to_string();
+
+    let repo_path = server
+        .data_dir()
+        .join("repos")
+        .join(&owner.namespace_id)
+        .join(format!("{name}.git"));
+    git2::Repository::init_bare(&repo_path).expect("init bare repo");
+
+    TestRepo {
+        id,
+        namespace: owner.namespace.clone(),
+        name: name.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn public_repo_is_readable_anonymously_by_default() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "private-mode-user").await;
+    let repo = create_repo(&client, &server, &owner, "open", "public").await;
+
+    let resp = client
+        .get(repo.info_refs_url(&server))
+        .send()
+        .await
+        .expect("info/refs");
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn private_mode_rejects_anonymous_reads_of_public_repos() {
+    let server = TestServer::start_with_args(&["--private-mode"]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "private-mode-user").await;
+    let repo = create_repo(&client, &server, &owner, "open", "public").await;
+
+    let resp = client
+        .get(repo.info_refs_url(&server))
+        .send()
+        .await
+        .expect("anonymous info/refs");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = client
+        .get(repo.refs_url(&server))
+        .send()
+        .await
+        .expect("anonymous refs");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = client
+        .get(repo.refs_url(&server))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("authenticated refs");
+    // The repo has no commits yet, so passing auth surfaces as "Repository is empty".
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = client
+        .get(repo.info_refs_url(&server))
+        .basic_auth("x-token", Some(&owner.token))
+        .send()
+        .await
+        .expect("authenticated info/refs");
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn admin_can_toggle_private_mode_at_runtime() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "private-mode-user").await;
+    let repo = create_repo(&client, &server, &owner, "open", "public").await;
+    let toggle_url = format!("{}/api/v1/admin/private-mode", server.base_url);
+
+    let resp = client
+        .put(&toggle_url)
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"enabled": true}))
+        .send()
+        .await
+        .expect("non-admin toggle");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp: Value = client
+        .put(&toggle_url)
+        .bearer_auth(&server.admin_token)
+        .json(&serde_json::json!({"enabled": true}))
+        .send()
+        .await
+        .expect("enable private mode")
+        .json()
+        .await
+        .expect("parse toggle response");
+    assert_eq!(resp["data"]["enabled"], true);
+
+    let resp = client
+        .get(repo.info_refs_url(&server))
+        .send()
+        .await
+        .expect("anonymous info/refs");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp: Value = client
+        .get(format!(
+            "{}/api/v1/admin/audit-events?kind=private_mode_changed",
+            server.base_url
+        ))
+        .bearer_auth(&server.admin_token)
+        .send()
+        .await
+        .expect("audit events")
+        .json()
+        .await
+        .expect("parse audit events");
+    assert_eq!(resp["data"].as_array().map(Vec::len), Some(1));
+
+    let resp: Value = client
+        .put(&toggle_url)
+        .bearer_auth(&server.admin_token)
+        .json(&serde_json::json!({"enabled": false}))
+        .send()
+        .await
+        .expect("disable private mode")
+        .json()
+        .await
+        .expect("parse toggle response");
+    assert_eq!(resp["data"]["enabled"], false);
+
+    let resp = client
+        .get(repo.info_refs_url(&server))
+        .send()
+        .await
+        .expect("anonymous info/refs");
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn internal_repo_is_readable_by_any_authenticated_principal() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "internal-owner").await;
+    let other = create_principal(&client, &server, "internal-reader").await;
+    let repo = create_repo(&client, &server, &owner, "shared", "internal").await;
+
+    let resp = client
+        .get(repo.info_refs_url(&server))
+        .send()
+        .await
+        .expect("anonymous info/refs");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = client
+        .get(repo.refs_url(&server))
+        .send()
+        .await
+        .expect("anonymous refs");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = client
+        .get(repo.info_refs_url(&server))
+        .basic_auth("x-token", Some(&other.token))
+        .send()
+        .await
+        .expect("authenticated info/refs");
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = client
+        .get(repo.refs_url(&server))
+        .bearer_auth(&other.token)
+        .send()
+        .await
+        .expect("authenticated refs");
+    // Empty repo: a 404 here means access was granted.
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = client
+        .get(format!(
+            "{}/git/{}/{}.git/info/refs?service=git-receive-pack",
+            server.base_url, repo.namespace, repo.name
+        ))
+        .basic_auth("x-token", Some(&other.token))
+        .send()
+        .await
+        .expect("authenticated receive-pack");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn deprecated_public_flag_still_sets_visibility() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "legacy-owner").await;
+
+    let resp: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"name": "legacy", "public": true}))
+        .send()
+        .await
+        .expect("create repo")
+        .json()
+        .await
+        .expect("parse repo response");
+    assert_eq!(resp["data"]["visibility"], "public");
+    let id = resp["data"]["id"].as_str().expect("repo id");
+
+    let resp: Value = client
+        .patch(format!("{}/api/v1/repos/{id}", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"public": false}))
+        .send()
+        .await
+        .expect("update repo")
+        .json()
+        .await
+        .expect("parse repo response");
+    assert_eq!(resp["data"]["visibility"], "private");
+}
+
+#[tokio::test]
+async fn private_repo_is_hidden_from_other_principals() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "private-owner").await;
+    let other = create_principal(&client, &server, "private-outsider").await;
+    let repo = create_repo(&client, &server, &owner, "secret", "private").await;
+
+    let resp = client
+        .get(repo.info_refs_url(&server))
+        .basic_auth("x-token", Some(&other.token))
+        .send()
+        .await
+        .expect("authenticated info/refs");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = client
+        .get(repo.refs_url(&server))
+        .bearer_auth(&other.token)
+        .send()
+        .await
+        .expect("authenticated refs");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn content_api_and_push_are_granted_apart() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "contents-owner").await;
+    let pusher = create_principal(&client, &server, "contents-pusher").await;
+    let editor = create_principal(&client, &server, "contents-editor").await;
+    let repo = create_repo(&client, &server, &owner, "docs", "private").await;
+
+    for (principal, allow, deny) in [
+        (&pusher, "repo:write", vec!["repo:contents"]),
+        (&editor, "repo:contents", vec![]),
+    ] {
+        let resp = client
+            .post(format!(
+                "{}/api/v1/admin/principals/{}/repo-grants",
+                server.base_url, principal.id
+            ))
+            .bearer_auth(&server.admin_token)
+            .json(&serde_json::json!({"repo_id": repo.id, "allow": [allow], "deny": deny}))
+            .send()
+            .await
+            .expect("grant repo access");
+        assert!(resp.status().is_success(), "grant: {}", resp.status());
+    }
+
+    let receive_pack = format!(
+        "{}/git/{}/{}.git/info/refs?service=git-receive-pack",
+        server.base_url, repo.namespace, repo.name
+    );
+    let commit = serde_json::json!({
+        "message": "Add notes",
+        "branch": "main",
+        "actions": [{"action": "create", "path": "NOTES.md", "content": "notes\n"}],
+    });
+    let commits_url = format!("{}/api/v1/repos/{}/commits", server.base_url, repo.id);
+
+    let resp = client
+        .get(&receive_pack)
+        .basic_auth("x-token", Some(&pusher.token))
+        .send()
+        .await
+        .expect("pusher receive-pack");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = client
+        .post(&commits_url)
+        .bearer_auth(&pusher.token)
+        .json(&commit)
+        .send()
+        .await
+        .expect("pusher commit");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = client
+        .get(&receive_pack)
+        .basic_auth("x-token", Some(&editor.token))
+        .send()
+        .await
+        .expect("editor receive-pack");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let resp = client
+        .post(&commits_url)
+        .bearer_auth(&editor.token)
+        .json(&commit)
+        .send()
+        .await
+        .expect("editor commit");
+    assert!(resp.status().is_success(), "editor commit: {}", resp.status());
+}
@@ -0,0 +1,322 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+
+use common::TestServer;
+
+async fn scim(
+    client: &Client,
+    server: &TestServer,
+    method: reqwest::Method,
+    path: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let mut req = client
+        .request(method, format!("{}/scim/v2{path}", server.base_url))
+        .bearer_auth(&server.admin_token);
+    if let Some(body) = body {
+        req = req
+            .header("Content-Type", "application/scim+json")
+            .body(body.to_string());
+    }
+    let resp = req.send().await.expect("scim request");
+    let status = resp.status();
+    let text = resp.text().await.expect("scim body");
+    (status, serde_json::from_str(&text).unwrap_or(Value::Null))
+}
+
+async fn create_token(client: &Client, server: &TestServer, principal_id: &str) -> String {
+    let resp: Value = client
+        .post(format!(
+            "{}/api/v1/admin/principals/{}/tokens",
+            server.base_url, principal_id
+        ))
+        .bearer_auth(&server.admin_token)
+        .json(&json!({}))
+        .send()
+        .await
+        .expect("create token")
+        .json()
+        .await
+        .expect("parse token response");
+    resp["data"]["token"].as_str().expect("token").to_string()
+}
+
+async fn visible_namespaces(client: &Client, server: &TestServer, token: &str) -> Option<String> {
+    let resp = client
+        .get(format!("{}/api/v1/namespaces", server.base_url))
+        .bearer_auth(token)
+        .send()
+        .await
+        .expect("list namespaces");
+    if resp.status() != StatusCode::OK {
+        return None;
+    }
+    Some(resp.text().await.expect("namespaces body"))
+}
+
+#[tokio::test]
+async fn users_are_provisioned_and_deactivated() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+
+    let (status, user) = scim(
+        &client,
+        &server,
+        reqwest::Method::POST,
+        "/Users",
+        Some(json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+            "userName": "scim-alice",
+            "externalId": "idp-alice",
+            "name": {"givenName": "Alice"},
+            "active": true,
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{user}");
+    assert_eq!(user["userName"], "scim-alice");
+    assert_eq!(user["active"], true);
+    let id = user["id"].as_str().expect("user id").to_string();
+
+    // Providers look users up by userName or externalId before creating them.
+    for filter in [
+        "userName%20eq%20%22scim-alice%22",
+        "externalId eq \"idp-alice\"",
+    ] {
+        let (status, list) = scim(
+            &client,
+            &server,
+            reqwest::Method::GET,
+            &format!("/Users?filter={filter}"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(list["totalResults"], 1);
+        assert_eq!(list["Resources"][0]["id"], id.as_str());
+    }
+
+    let (status, error) = scim(
+        &client,
+        &server,
+        reqwest::Method::POST,
+        "/Users",
+        Some(json!({"userName": "scim-alice"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(error["scimType"], "uniqueness");
+
+    let token = create_token(&client, &server, &id).await;
+    assert!(visible_namespaces(&client, &server, &token).await.is_some());
+
+    let deactivate = json!({
+        "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+        "Operations": [{"op": "replace", "value": {"active": false}}],
+    });
+    let (status, user) = scim(
+        &client,
+        &server,
+        reqwest::Method::PATCH,
+        &format!("/Users/{id}"),
+        Some(deactivate),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(user["active"], false);
+    assert!(visible_namespaces(&client, &server, &token).await.is_none());
+
+    let (status, _) = scim(
+        &client,
+        &server,
+        reqwest::Method::PATCH,
+        &format!("/Users/{id}"),
+        Some(json!({"Operations": [{"op": "Replace", "path": "active", "value": "True"}]})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(visible_namespaces(&client, &server, &token).await.is_some());
+
+    let (status, error) = scim(
+        &client,
+        &server,
+        reqwest::Method::PUT,
+        &format!("/Users/{id}"),
+        Some(json!({"userName": "scim-renamed", "active": true})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["scimType"], "mutability");
+
+    let (status, _) = scim(
+        &client,
+        &server,
+        reqwest::Method::DELETE,
+        &format!("/Users/{id}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, error) = scim(
+        &client,
+        &server,
+        reqwest::Method::GET,
+        &format!("/Users/{id}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(
+        error["schemas"][0],
+        "urn:ietf:params:scim:api:messages:2.0:Error"
+    );
+}
+
+#[tokio::test]
+async fn group_members_are_granted_the_namespace() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+
+    let mut ids = Vec::new();
+    for name in ["scim-bob", "scim-carol"] {
+        let (status, user) = scim(
+            &client,
+            &server,
+            reqwest::Method::POST,
+            "/Users",
+            Some(json!({"userName": name})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        ids.push(user["id"].as_str().expect("user id").to_string());
+    }
+    let bob = create_token(&client, &server, &ids[0]).await;
+    let carol = create_token(&client, &server, &ids[1]).await;
+
+    let (status, group) = scim(
+        &client,
+        &server,
+        reqwest::Method::POST,
+        "/Groups",
+        Some(json!({
+            "displayName": "scim-team",
+            "externalId": "idp-team",
+            "members": [{"value": ids[0], "display": "scim-bob"}],
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{group}");
+    let group_id = group["id"].as_str().expect("group id").to_string();
+    assert!(
+        visible_namespaces(&client, &server, &bob)
+            .await
+            .unwrap()
+            .contains("scim-team")
+    );
+    assert!(
+        !visible_namespaces(&client, &server, &carol)
+            .await
+            .unwrap()
+            .contains("scim-team")
+    );
+
+    // A user's namespace is not a group.
+    let (status, _) = scim(
+        &client,
+        &server,
+        reqwest::Method::POST,
+        "/Groups",
+        Some(json!({"displayName": "scim-bob"})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, group) = scim(
+        &client,
+        &server,
+        reqwest::Method::PATCH,
+        &format!("/Groups/{group_id}"),
+        Some(json!({"Operations": [
+            {"op": "add", "path": "members", "value": [{"value": ids[1]}]},
+            {"op": "remove", "path": format!("members[value eq \"{}\"]", ids[0])},
+        ]})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{group}");
+    assert_eq!(group["members"], json!([{"value": ids[1]}]));
+    assert!(
+        !visible_namespaces(&client, &server, &bob)
+            .await
+            .unwrap()
+            .contains("scim-team")
+    );
+    assert!(
+        visible_namespaces(&client, &server, &carol)
+            .await
+            .unwrap()
+            .contains("scim-team")
+    );
+
+    let (status, error) = scim(
+        &client,
+        &server,
+        reqwest::Method::PATCH,
+        &format!("/Groups/{group_id}"),
+        Some(json!({"Operations": [
+            {"op": "add", "path": "members", "value": [{"value": "no-such-user"}]},
+        ]})),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["scimType"], "invalidValue");
+
+    let (status, list) = scim(
+        &client,
+        &server,
+        reqwest::Method::GET,
+        "/Groups?filter=displayName%20eq%20%22scim-team%22",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(list["totalResults"], 1);
+    assert_eq!(list["Resources"][0]["externalId"], "idp-team");
+
+    // Deleting the group revokes access but keeps the namespace.
+    let (status, _) = scim(
+        &client,
+        &server,
+        reqwest::Method::DELETE,
+        &format!("/Groups/{group_id}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(
+        !visible_namespaces(&client, &server, &carol)
+            .await
+            .unwrap()
+            .contains("scim-team")
+    );
+    let (status, _) = scim(
+        &client,
+        &server,
+        reqwest::Method::GET,
+        &format!("/Groups/{group_id}"),
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let resp = client
+        .get(format!(
+            "{}/api/v1/admin/namespaces/scim-team",
+            server.base_url
+        ))
+        .bearer_auth(&server.admin_token)
+        .send()
+        .await
+        .expect("get namespace");
+    assert_eq!(resp.status(), StatusCode::OK);
+}
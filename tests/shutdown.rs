@@ -0,0 +1,94 @@
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::time::{Duration, Instant};
+
+use reqwest::Client;
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::{assert_git, create_principal, git};
+
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[tokio::test]
+async fn sigterm_lets_a_running_push_finish() {
+    let config_dir = TempDir::new().expect("temp dir");
+    let config_path = config_dir.path().join("server.toml");
+    std::fs::write(
+        &config_path,
+        format!("shutdown_timeout = {}\n", SHUTDOWN_TIMEOUT.as_secs()),
+    )
+    .expect("write config");
+    let mut server =
+        TestServer::start_with_args(&["--config", config_path.to_str().expect("utf-8 path")]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "shutdown-owner").await;
+
+    // A pre-receive script that holds the push open long enough to stop
+    // the server in the middle of it.
+    let work = TempDir::new().expect("temp dir");
+    let started = work.path().join("started");
+    let hooks = server.data_dir().join("hooks").join("pre-receive.d");
+    let script = hooks.join("10-slow");
+    std::fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\ncat > /dev/null\ntouch '{}'\nsleep 3\n",
+            started.display()
+        ),
+    )
+    .expect("write hook script");
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))
+        .expect("make hook executable");
+
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create work tree");
+    std::fs::write(source.join("README.md"), "# slow\n").expect("write file");
+    assert_git(&source, &["init", "-q", "-b", "main"]);
+    assert_git(&source, &["add", "README.md"]);
+    assert_git(&source, &["commit", "-q", "-m", "Initial commit"]);
+    let head = assert_git(&source, &["rev-parse", "HEAD"]);
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/slow.git",
+        owner.token, owner.namespace
+    );
+
+    let push = {
+        let source = source.clone();
+        std::thread::spawn(move || git(&source, &["push", &url, "main"]))
+    };
+    for _ in 0..300 {
+        if started.exists() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(started.exists(), "push never reached the pre-receive hook");
+
+    let stopping = Instant::now();
+    server.terminate();
+    let status = server
+        .wait_for_exit(SHUTDOWN_TIMEOUT)
+        .expect("server still running after the shutdown timeout");
+    assert!(status.success(), "server exited with {status}");
+    assert!(stopping.elapsed() < SHUTDOWN_TIMEOUT);
+
+    let output = push.join().expect("push thread");
+    assert!(
+        output.status.success(),
+        "push failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let repo = git2::Repository::open_bare(
+        server
+            .data_dir()
+            .join("repos")
+            .join(&owner.namespace_id)
+            .join("slow.git"),
+    )
+    .expect("open pushed repo");
+    let main = repo.refname_to_id("refs/heads/main").expect("main branch");
+    assert_eq!(main.to_string(), head);
+}
@@ -0,0 +1,301 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::{TestPrincipal, create_principal};
+
+fn ssh_available() -> bool {
+    let found = ["ssh", "ssh-keygen"]
+        .iter()
+        .all(|bin| Command::new(bin).arg("-V").output().is_ok());
+    if !found {
+        eprintln!("Skipping SSH tests: ssh or ssh-keygen not found in PATH");
+    }
+    found
+}
+
+fn free_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+    listener.local_addr().expect("local addr").port()
+}
+
+/// Generates an unencrypted Ed25519 key pair and returns the private key path.
+fn generate_key(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    let status = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-C", name, "-f"])
+        .arg(&path)
+        .status()
+        .expect("run ssh-keygen");
+    assert!(status.success(), "ssh-keygen failed");
+    path
+}
+
+async fn register_key(client: &Client, server: &TestServer, owner: &TestPrincipal, key: &Path) {
+    let public_key = std::fs::read_to_string(key.with_extension("pub")).expect("read public key");
+    let resp = client
+        .post(format!("{}/api/v1/user/ssh-keys", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"public_key": public_key}))
+        .send()
+        .await
+        .expect("register ssh key");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+}
+
+fn git(dir: &Path, key: &Path, ssh_port: u16, args: &[&str]) -> Output {
+    let ssh_command = format!(
+        "ssh -i {} -p {} -o IdentitiesOnly=yes -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null -o LogLevel=ERROR",
+        key.display(),
+        ssh_port
+    );
+    Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_SSH_COMMAND", ssh_command)
+        .env("GIT_AUTHOR_NAME", "Test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "Test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .output()
+        .expect("run git")
+}
+
+fn remote_url(ssh_port: u16, namespace: &str, repo: &str) -> String {
+    format!("ssh://git@127.0.0.1:{ssh_port}/{namespace}/{repo}.git")
+}
+
+fn init_work_tree(dir: &Path, key: &Path, ssh_port: u16) {
+    std::fs::write(dir.join("README.md"), "# over ssh\n").expect("write file");
+    for args in [
+        &["init", "-q", "-b", "main"][..],
+        &["add", "README.md"],
+        &["commit", "-q", "-m", "Initial commit"],
+    ] {
+        let output = git(dir, key, ssh_port, args);
+        assert!(output.status.success(), "git {args:?} failed");
+    }
+}
+
+async fn setup(client: &Client, server: &TestServer, owner: &TestPrincipal) -> Value {
+    client
+        .get(format!("{}/api/v1/user/setup", server.base_url))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("get setup")
+        .json()
+        .await
+        .expect("parse setup")
+}
+
+#[tokio::test]
+async fn setup_omits_ssh_url_when_ssh_is_disabled() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "ssh-disabled").await;
+
+    let setup = setup(&client, &server, &owner).await;
+    assert!(setup["data"]["git_ssh_url"].is_null());
+}
+
+#[tokio::test]
+async fn push_and_clone_over_ssh_with_registered_key() {
+    if !ssh_available() {
+        return;
+    }
+    let ssh_port = free_port();
+    let server = TestServer::start_with_args(&["--ssh-port", &ssh_port.to_string()]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "ssh-owner").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let key = generate_key(work.path(), "owner_key");
+    register_key(&client, &server, &owner, &key).await;
+
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    init_work_tree(&source, &key, ssh_port);
+
+    let url = remote_url(ssh_port, &owner.namespace, "over-ssh");
+    let output = git(&source, &key, ssh_port, &["push", "-q", &url, "main"]);
+    assert!(
+        output.status.success(),
+        "push failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = git(work.path(), &key, ssh_port, &["clone", "-q", &url, "clone"]);
+    assert!(
+        output.status.success(),
+        "clone failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let readme = std::fs::read_to_string(work.path().join("clone/README.md")).expect("read clone");
+    assert_eq!(readme, "# over ssh\n");
+
+    let keys: Value = client
+        .get(format!("{}/api/v1/user/ssh-keys", server.base_url))
+        .bearer_auth(&owner.token)
+        .send()
+        .await
+        .expect("list ssh keys")
+        .json()
+        .await
+        .expect("parse ssh keys");
+    assert!(keys["data"][0]["last_used_at"].is_string());
+
+    assert!(server.data_dir().join("ssh_host_ed25519_key").exists());
+    let setup = setup(&client, &server, &owner).await;
+    assert_eq!(
+        setup["data"]["git_ssh_url"],
+        format!("ssh://git@127.0.0.1:{ssh_port}")
+    );
+}
+
+#[tokio::test]
+async fn ssh_rejects_unregistered_keys_and_unauthorized_pushes() {
+    if !ssh_available() {
+        return;
+    }
+    let ssh_port = free_port();
+    let server = TestServer::start_with_args(&["--ssh-port", &ssh_port.to_string()]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "ssh-repo-owner").await;
+    let other = create_principal(&client, &server, "ssh-outsider").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let unregistered = generate_key(work.path(), "unregistered_key");
+    let other_key = generate_key(work.path(), "other_key");
+    register_key(&client, &server, &other, &other_key).await;
+
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    init_work_tree(&source, &other_key, ssh_port);
+
+    let url = remote_url(ssh_port, &owner.namespace, "guarded");
+
+    let output = git(
+        &source,
+        &unregistered,
+        ssh_port,
+        &["push", "-q", &url, "main"],
+    );
+    assert!(
+        !output.status.success(),
+        "push with unregistered key succeeded"
+    );
+
+    let output = git(&source, &other_key, ssh_port, &["push", "-q", &url, "main"]);
+    assert!(
+        !output.status.success(),
+        "push to foreign namespace succeeded"
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Permission denied"),
+        "unexpected stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[tokio::test]
+async fn ssh_push_of_new_branch_prints_pull_request_link() {
+    if !ssh_available() {
+        return;
+    }
+    let ssh_port = free_port();
+    let server = TestServer::start_with_args(&[
+        "--ssh-port",
+        &ssh_port.to_string(),
+        "--public-base-url",
+        "https://git.example.com",
+    ])
+    .await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "ssh-hints").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let key = generate_key(work.path(), "owner_key");
+    register_key(&client, &server, &owner, &key).await;
+
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    init_work_tree(&source, &key, ssh_port);
+
+    let url = remote_url(ssh_port, &owner.namespace, "hints");
+    let output = git(&source, &key, ssh_port, &["push", "-q", &url, "main"]);
+    assert!(output.status.success(), "push failed");
+
+    let output = git(&source, &key, ssh_port, &["push", "-q", &url, "main:topic"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "push failed: {stderr}");
+    assert!(
+        stderr.contains("cutman: Open a pull request for 'topic':"),
+        "missing hint: {stderr}"
+    );
+    assert!(
+        stderr.contains("https://git.example.com/api/v1/repos/"),
+        "hint does not use the public URL: {stderr}"
+    );
+}
+
+#[tokio::test]
+async fn ssh_refuses_fetches_deeper_than_max_clone_depth() {
+    if !ssh_available() {
+        return;
+    }
+    let ssh_port = free_port();
+    let server = TestServer::start_with_args(&[
+        "--ssh-port",
+        &ssh_port.to_string(),
+        "--max-clone-depth",
+        "1",
+    ])
+    .await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "ssh-depth").await;
+
+    let work = TempDir::new().expect("temp dir");
+    let key = generate_key(work.path(), "owner_key");
+    register_key(&client, &server, &owner, &key).await;
+
+    let source = work.path().join("source");
+    std::fs::create_dir(&source).expect("create source dir");
+    init_work_tree(&source, &key, ssh_port);
+    let url = remote_url(ssh_port, &owner.namespace, "depth");
+    let output = git(&source, &key, ssh_port, &["push", "-q", &url, "main"]);
+    assert!(output.status.success(), "push failed");
+
+    for protocol in ["protocol.version=2", "protocol.version=0"] {
+        let output = git(
+            work.path(),
+            &key,
+            ssh_port,
+            &["-c", protocol, "clone", "-q", "--depth", "5", &url, "deep"],
+        );
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success(), "{protocol}: deep clone succeeded");
+        assert!(
+            stderr.contains("Shallow fetches are limited to a depth of 1"),
+            "{protocol}: {stderr}"
+        );
+    }
+
+    let output = git(
+        work.path(),
+        &key,
+        ssh_port,
+        &["clone", "-q", "--depth", "1", &url, "shallow"],
+    );
+    assert!(
+        output.status.success(),
+        "shallow clone failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+
+use common::TestServer;
+use common::fixtures::{TestPrincipal, create_principal};
+
+async fn create_repo(
+    client: &Client,
+    server: &TestServer,
+    owner: &TestPrincipal,
+    namespace: &str,
+    name: &str,
+) -> String {
+    let resp: Value = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&json!({"name": name, "visibility": "private", "namespace": namespace}))
+        .send()
+        .await
+        .expect("create repo")
+        .json()
+        .await
+        .expect("parse repo response");
+    resp["data"]["id"].as_str().expect("repo id").to_string()
+}
+
+async fn grant(
+    client: &Client,
+    server: &TestServer,
+    principal: &TestPrincipal,
+    kind: &str,
+    body: Value,
+) {
+    let resp = client
+        .post(format!(
+            "{}/api/v1/admin/principals/{}/{kind}-grants",
+            server.base_url, principal.id
+        ))
+        .bearer_auth(&server.admin_token)
+        .json(&body)
+        .send()
+        .await
+        .expect("create grant");
+    let status = resp.status();
+    let text = resp.text().await.expect("read grant response");
+    assert!(status.is_success(), "{kind} grant: {status} {text}");
+}
+
+async fn sync(
+    client: &Client,
+    server: &TestServer,
+    principal: &TestPrincipal,
+    cursor: Option<&str>,
+) -> Value {
+    let mut url = format!("{}/api/v1/sync", server.base_url);
+    if let Some(cursor) = cursor {
+        url.push_str(&format!("?cursor={cursor}"));
+    }
+    let resp = client
+        .get(url)
+        .bearer_auth(&principal.token)
+        .send()
+        .await
+        .expect("sync");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = resp.json().await.expect("parse sync response");
+    body["data"].clone()
+}
+
+fn ids(list: &Value) -> Vec<&str> {
+    list.as_array()
+        .map(|items| items.iter().filter_map(|i| i["id"].as_str()).collect())
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn sync_leaves_out_repos_the_caller_cannot_read() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "sync-owner").await;
+    let reader = create_principal(&client, &server, "sync-reader").await;
+
+    // Primary namespaces can't be shared, so the repos live in a team one.
+    let resp: Value = client
+        .post(format!("{}/api/v1/admin/namespaces", server.base_url))
+        .bearer_auth(&server.admin_token)
+        .json(&json!({"name": "sync-team"}))
+        .send()
+        .await
+        .expect("create namespace")
+        .json()
+        .await
+        .expect("parse namespace response");
+    let team_id = resp["data"]["id"]
+        .as_str()
+        .expect("namespace id")
+        .to_string();
+    grant(
+        &client,
+        &server,
+        &owner,
+        "namespace",
+        json!({"namespace_id": team_id, "allow": ["namespace:write", "repo:admin"]}),
+    )
+    .await;
+    let open = create_repo(&client, &server, &owner, "sync-team", "open").await;
+    let hidden = create_repo(&client, &server, &owner, "sync-team", "hidden").await;
+    let denied = create_repo(&client, &server, &owner, &owner.namespace, "denied").await;
+
+    // Namespace read covers `open` and `hidden`; the repo grant on `hidden`
+    // denies read, which wins. The grant on `denied` only denies, so it
+    // must not bring that repo into view either.
+    grant(
+        &client,
+        &server,
+        &reader,
+        "namespace",
+        json!({"namespace_id": team_id, "allow": ["namespace:read", "repo:read"]}),
+    )
+    .await;
+    grant(
+        &client,
+        &server,
+        &reader,
+        "repo",
+        json!({"repo_id": hidden, "allow": [], "deny": ["repo:read"]}),
+    )
+    .await;
+
+    let outsider = create_principal(&client, &server, "sync-outsider").await;
+    grant(
+        &client,
+        &server,
+        &outsider,
+        "repo",
+        json!({"repo_id": denied, "allow": [], "deny": ["repo:read"]}),
+    )
+    .await;
+    let snapshot = sync(&client, &server, &outsider, None).await;
+    assert!(
+        !ids(&snapshot["repos"]).contains(&denied.as_str()),
+        "{snapshot}"
+    );
+
+    let snapshot = sync(&client, &server, &reader, None).await;
+    let repos = ids(&snapshot["repos"]);
+    assert!(repos.contains(&open.as_str()), "{snapshot}");
+    assert!(!repos.contains(&hidden.as_str()), "{snapshot}");
+    let cursor = snapshot["cursor"].as_str().expect("cursor").to_string();
+
+    for id in [&open, &hidden] {
+        let resp = client
+            .patch(format!("{}/api/v1/repos/{id}", server.base_url))
+            .bearer_auth(&owner.token)
+            .json(&json!({"description": "changed"}))
+            .send()
+            .await
+            .expect("update repo");
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    // The denied repo's change is reported as a deletion, never its state.
+    let delta = sync(&client, &server, &reader, Some(&cursor)).await;
+    assert_eq!(delta["reset"], false, "{delta}");
+    assert_eq!(ids(&delta["repos"]), [open.as_str()], "{delta}");
+    assert_eq!(ids(&delta["deleted"]), [hidden.as_str()], "{delta}");
+}
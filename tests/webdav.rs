@@ -0,0 +1,272 @@
+mod common;
+
+use std::path::Path;
+
+use reqwest::header::{ALLOW, WWW_AUTHENTICATE};
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use tempfile::TempDir;
+
+use common::TestServer;
+use common::fixtures::{TestPrincipal, assert_git, create_principal};
+
+fn propfind() -> Method {
+    Method::from_bytes(b"PROPFIND").expect("PROPFIND method")
+}
+
+fn write(dir: &Path, path: &str, content: &str) {
+    let path = dir.join(path);
+    std::fs::create_dir_all(path.parent().expect("parent")).expect("create dir");
+    std::fs::write(path, content).expect("write file");
+}
+
+/// Creates a repo and pushes a README and a `docs/` directory to its main
+/// branch, returning the git URL and the working copy.
+async fn push_repo(
+    client: &Client,
+    server: &TestServer,
+    owner: &TestPrincipal,
+    name: &str,
+    visibility: &str,
+) -> (String, TempDir) {
+    let resp = client
+        .post(format!("{}/api/v1/repos", server.base_url))
+        .bearer_auth(&owner.token)
+        .json(&serde_json::json!({"name": name, "visibility": visibility}))
+        .send()
+        .await
+        .expect("create repo");
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let work = TempDir::new().expect("temp dir");
+    let source = work.path();
+    write(source, "README.md", "# mounted\n");
+    write(source, "docs/guide.md", "Read me in a file manager.\n");
+    assert_git(source, &["init", "-q", "-b", "main"]);
+    assert_git(source, &["add", "."]);
+    assert_git(source, &["commit", "-q", "-m", "Add docs"]);
+
+    let host = server.base_url.trim_start_matches("http://");
+    let url = format!(
+        "http://x-token:{}@{host}/git/{}/{name}.git",
+        owner.token, owner.namespace
+    );
+    assert_git(source, &["push", "-q", &url, "main"]);
+    (url, work)
+}
+
+async fn multistatus(request: RequestBuilder) -> String {
+    let resp = request.send().await.expect("PROPFIND");
+    assert_eq!(resp.status(), StatusCode::MULTI_STATUS);
+    resp.text().await.expect("read multistatus")
+}
+
+#[tokio::test]
+async fn dav_routes_need_the_webdav_setting() {
+    let server = TestServer::start().await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "dav-off").await;
+    push_repo(&client, &server, &owner, "docs", "public").await;
+
+    let resp = client
+        .request(propfind(), format!("{}/dav/dav-off/docs/", server.base_url))
+        .header("Depth", "1")
+        .send()
+        .await
+        .expect("PROPFIND");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn dav_serves_the_default_branch_read_only() {
+    let server = TestServer::start_with_args(&["--webdav"]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "dav-owner").await;
+    let (url, work) = push_repo(&client, &server, &owner, "docs", "public").await;
+    let root = format!("{}/dav/dav-owner/docs/", server.base_url);
+
+    let resp = client
+        .request(Method::OPTIONS, &root)
+        .send()
+        .await
+        .expect("OPTIONS");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers()["dav"], "1");
+    assert_eq!(resp.headers()[ALLOW], "OPTIONS, GET, HEAD, PROPFIND");
+
+    // Depth 0 describes only the resource itself.
+    let xml = multistatus(client.request(propfind(), &root).header("Depth", "0")).await;
+    assert_eq!(xml.matches("<D:response>").count(), 1, "{xml}");
+    assert!(
+        xml.contains("<D:href>/dav/dav-owner/docs/</D:href>"),
+        "{xml}"
+    );
+    assert!(xml.contains("<D:collection/>"), "{xml}");
+
+    // Depth 1 adds the entries of a directory.
+    let xml = multistatus(client.request(propfind(), &root).header("Depth", "1")).await;
+    assert_eq!(xml.matches("<D:response>").count(), 3, "{xml}");
+    assert!(
+        xml.contains("<D:href>/dav/dav-owner/docs/README.md</D:href>"),
+        "{xml}"
+    );
+    assert!(
+        xml.contains("<D:getcontentlength>10</D:getcontentlength>"),
+        "{xml}"
+    );
+    assert!(
+        xml.contains("<D:href>/dav/dav-owner/docs/docs/</D:href>"),
+        "{xml}"
+    );
+
+    let xml = multistatus(
+        client
+            .request(propfind(), format!("{root}docs"))
+            .header("Depth", "1"),
+    )
+    .await;
+    assert!(
+        xml.contains("<D:href>/dav/dav-owner/docs/docs/guide.md</D:href>"),
+        "{xml}"
+    );
+
+    // A file has no entries, whatever the depth.
+    let xml = multistatus(
+        client
+            .request(propfind(), format!("{root}README.md"))
+            .header("Depth", "1"),
+    )
+    .await;
+    assert_eq!(xml.matches("<D:response>").count(), 1, "{xml}");
+
+    // Infinite depth, which a missing header also means, is refused.
+    for depth in [Some("infinity"), None] {
+        let mut request = client.request(propfind(), &root);
+        if let Some(depth) = depth {
+            request = request.header("Depth", depth);
+        }
+        let resp = request.send().await.expect("PROPFIND");
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN, "Depth {depth:?}");
+        let body = resp.text().await.expect("read error");
+        assert!(body.contains("<D:propfind-finite-depth/>"), "{body}");
+    }
+
+    let resp = client
+        .request(propfind(), format!("{root}missing.md"))
+        .header("Depth", "0")
+        .send()
+        .await
+        .expect("PROPFIND");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let resp = client
+        .get(format!("{root}docs/guide.md"))
+        .send()
+        .await
+        .expect("GET file");
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.text().await.expect("read file"),
+        "Read me in a file manager.\n"
+    );
+
+    // Without the trailing slash the repo root is still served, as a
+    // listing for browsers.
+    let resp = client
+        .get(format!("{}/dav/dav-owner/docs", server.base_url))
+        .send()
+        .await
+        .expect("GET root");
+    assert_eq!(resp.status(), StatusCode::OK);
+    let html = resp.text().await.expect("read listing");
+    assert!(
+        html.contains("<a href=\"/dav/dav-owner/docs/docs/\">docs/</a>"),
+        "{html}"
+    );
+
+    for method in ["PUT", "DELETE", "MKCOL", "MOVE", "PROPPATCH", "LOCK"] {
+        let resp = client
+            .request(
+                Method::from_bytes(method.as_bytes()).expect("method"),
+                format!("{root}README.md"),
+            )
+            .basic_auth("x-token", Some(&owner.token))
+            .body("changed\n")
+            .send()
+            .await
+            .expect("write request");
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED, "{method}");
+        assert_eq!(resp.headers()[ALLOW], "OPTIONS, GET, HEAD, PROPFIND");
+    }
+    let head = assert_git(work.path(), &["rev-parse", "HEAD"]);
+    let remote = assert_git(work.path(), &["ls-remote", &url, "refs/heads/main"]);
+    assert!(remote.starts_with(&head), "main moved: {remote}");
+}
+
+#[tokio::test]
+async fn dav_follows_repo_visibility() {
+    let server = TestServer::start_with_args(&["--webdav"]).await;
+    let client = Client::new();
+    let owner = create_principal(&client, &server, "dav-private").await;
+    let outsider = create_principal(&client, &server, "dav-outsider").await;
+    push_repo(&client, &server, &owner, "secret", "private").await;
+    let root = format!("{}/dav/dav-private/secret/", server.base_url);
+
+    // Clients are asked to log in, so file managers prompt for a token.
+    let resp = client
+        .request(propfind(), &root)
+        .header("Depth", "1")
+        .send()
+        .await
+        .expect("anonymous PROPFIND");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.headers()[WWW_AUTHENTICATE], "Basic realm=\"cutman\"");
+
+    let resp = client
+        .get(format!("{root}README.md"))
+        .send()
+        .await
+        .expect("anonymous GET");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = client
+        .request(propfind(), &root)
+        .header("Depth", "1")
+        .basic_auth("x-token", Some(&outsider.token))
+        .send()
+        .await
+        .expect("outsider PROPFIND");
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = client
+        .request(propfind(), &root)
+        .header("Depth", "1")
+        .basic_auth("x-token", Some("not-a-token"))
+        .send()
+        .await
+        .expect("bad token PROPFIND");
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let xml = multistatus(
+        client
+            .request(propfind(), &root)
+            .header("Depth", "1")
+            .basic_auth("x-token", Some(&owner.token)),
+    )
+    .await;
+    assert!(
+        xml.contains("<D:href>/dav/dav-private/secret/README.md</D:href>"),
+        "{xml}"
+    );
+
+    let resp = client
+        .request(
+            propfind(),
+            format!("{}/dav/dav-private/nothing/", server.base_url),
+        )
+        .header("Depth", "1")
+        .basic_auth("x-token", Some(&owner.token))
+        .send()
+        .await
+        .expect("unknown repo PROPFIND");
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}